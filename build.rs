@@ -0,0 +1,48 @@
+//! Embeds the built Studio (`studio/dist`, or `BACKWORKS_STUDIO_DIST`) into
+//! the binary so the dashboard works from any directory. Without a built
+//! Studio the binary embeds nothing and the dashboard asks for one.
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=BACKWORKS_STUDIO_DIST");
+
+    let dist = std::env::var_os("BACKWORKS_STUDIO_DIST")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("studio/dist"));
+    let mut files = Vec::new();
+    if dist.is_dir() {
+        println!("cargo:rerun-if-changed={}", dist.display());
+        collect(&dist, &dist, &mut files);
+        files.sort();
+    }
+
+    let mut generated = String::from("/// Files of the built Studio, by path below its `dist` directory\npub static EMBEDDED: &[(&str, &[u8])] = &[\n");
+    for (relative, absolute) in &files {
+        writeln!(generated, "    ({:?}, include_bytes!({:?})),", relative, absolute).unwrap();
+    }
+    generated.push_str("];\n");
+
+    let out = Path::new(&std::env::var_os("OUT_DIR").expect("cargo sets OUT_DIR")).join("studio_assets.rs");
+    std::fs::write(out, generated).expect("cannot write the embedded Studio assets");
+}
+
+fn collect(root: &Path, dir: &Path, files: &mut Vec<(String, String)>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect(root, &path, files);
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            let relative = relative.components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.push((relative, path.display().to_string()));
+        }
+    }
+}
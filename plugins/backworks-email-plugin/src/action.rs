@@ -0,0 +1,169 @@
+//! The `email` middleware
+//!
+//! Sends an email for each request to an endpoint, without handler code: the
+//! recipients, subject and template data are Handlebars templates over the
+//! request and its response. Emails go out in the background once the
+//! response is ready.
+
+use crate::config::EmailRequest;
+use crate::mailer::Mailer;
+use async_trait::async_trait;
+use axum::body::{Body, Bytes};
+use axum::extract::{FromRequestParts, Query, RawPathParams, Request};
+use axum::response::{IntoResponse, Response};
+use backworks::contract::body_value;
+use backworks::error::{BackworksError, BackworksResult};
+use backworks::pipeline::{EndpointMiddleware, Next};
+use backworks::server::MatchedEndpoint;
+use handlebars::Handlebars;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Settings of the `email` middleware of one endpoint
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmailAction {
+    /// Recipients, each a template such as `{{request.body.email}}`
+    pub to: Vec<String>,
+    #[serde(default)]
+    pub cc: Vec<String>,
+    #[serde(default)]
+    pub bcc: Vec<String>,
+
+    /// Name of a configured template
+    pub template: Option<String>,
+
+    /// Subject template; the template's subject when unset
+    pub subject: Option<String>,
+
+    /// Values added to the template data next to `request` and `response`
+    #[serde(default)]
+    pub data: Map<String, Value>,
+
+    /// Which responses send the email
+    #[serde(default)]
+    pub on: SendOn,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SendOn {
+    /// Responses with a 2xx status
+    #[default]
+    Success,
+    /// Every response
+    Always,
+}
+
+pub struct SendEmail {
+    mailer: Arc<Mailer>,
+    action: EmailAction,
+    fields: Handlebars<'static>,
+}
+
+impl SendEmail {
+    pub fn new(mailer: Arc<Mailer>, settings: &Value) -> BackworksResult<Self> {
+        let action: EmailAction = serde_json::from_value(settings.clone())
+            .map_err(|e| BackworksError::config(format!("invalid email settings: {}", e)))?;
+        if action.to.is_empty() {
+            return Err(BackworksError::config("invalid email settings: `to` needs at least one recipient"));
+        }
+        match action.template {
+            Some(ref template) if !mailer.config.templates.contains_key(template) => {
+                return Err(BackworksError::config(format!("invalid email settings: unknown template '{}'", template)));
+            }
+            None if action.subject.is_none() => {
+                return Err(BackworksError::config("invalid email settings: a subject or a template is required"));
+            }
+            _ => {}
+        }
+        let mut fields = Handlebars::new();
+        fields.register_escape_fn(handlebars::no_escape);
+        Ok(Self { mailer, action, fields })
+    }
+
+    /// The email a request to `endpoint` sends, given what it saw, or `None`
+    /// when the response does not qualify
+    pub fn email(&self, endpoint: &str, request: Value, response: Value) -> BackworksResult<Option<EmailRequest>> {
+        let status = response["status"].as_u64().unwrap_or_default();
+        if self.action.on == SendOn::Success && !(200..300).contains(&status) {
+            return Ok(None);
+        }
+        let mut data = self.action.data.clone();
+        data.insert("endpoint".to_string(), Value::String(endpoint.to_string()));
+        data.insert("request".to_string(), request);
+        data.insert("response".to_string(), response);
+        let data = Value::Object(data);
+        let render = |template: &String| {
+            self.fields.render_template(template, &data)
+                .map_err(|e| BackworksError::config(format!("email: {}", e)))
+        };
+        let addresses = |templates: &[String]| -> BackworksResult<Vec<String>> {
+            Ok(templates.iter().map(render).collect::<BackworksResult<Vec<_>>>()?
+                .into_iter()
+                .filter(|address| !address.trim().is_empty())
+                .collect())
+        };
+        Ok(Some(EmailRequest {
+            to: addresses(&self.action.to)?,
+            cc: addresses(&self.action.cc)?,
+            bcc: addresses(&self.action.bcc)?,
+            subject: self.action.subject.as_ref().map(render).transpose()?,
+            template: self.action.template.clone(),
+            data,
+            ..Default::default()
+        }))
+    }
+}
+
+async fn buffer(body: Body) -> Result<Bytes, Response> {
+    axum::body::to_bytes(body, usize::MAX).await
+        .map_err(|e| BackworksError::server(format!("cannot buffer body for email: {}", e)).into_response())
+}
+
+#[async_trait]
+impl EndpointMiddleware for SendEmail {
+    async fn handle(&self, request: Request, next: Next<'_>) -> Response {
+        let endpoint = request.extensions().get::<MatchedEndpoint>().map(|MatchedEndpoint(name)| name.clone()).unwrap_or_default();
+        let (mut parts, body) = request.into_parts();
+        let params: HashMap<String, String> = match RawPathParams::from_request_parts(&mut parts, &()).await {
+            Ok(params) => params.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+            Err(_) => HashMap::new(),
+        };
+        let body = match buffer(body).await {
+            Ok(body) => body,
+            Err(response) => return response,
+        };
+        let query: HashMap<String, String> = Query::try_from_uri(&parts.uri).map(|Query(query)| query).unwrap_or_default();
+        let seen = json!({
+            "method": parts.method.as_str(),
+            "path": parts.uri.path(),
+            "params": params,
+            "query": query,
+            "body": body_value(&body),
+        });
+        let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+        let (parts, body) = response.into_parts();
+        let body = match buffer(body).await {
+            Ok(body) => body,
+            Err(response) => return response,
+        };
+        let outcome = json!({ "status": parts.status.as_u16(), "body": body_value(&body) });
+        match self.email(&endpoint, seen, outcome) {
+            Ok(Some(email)) => {
+                let mailer = self.mailer.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = mailer.send(&email).await {
+                        tracing::warn!("Failed to send email for endpoint '{}': {}", endpoint, e);
+                    }
+                });
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Failed to prepare email for endpoint '{}': {}", endpoint, e),
+        }
+        Response::from_parts(parts, Body::from(body))
+    }
+}
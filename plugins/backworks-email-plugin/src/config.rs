@@ -0,0 +1,99 @@
+//! Configuration for the email plugin
+
+use backworks::config::AlertChannelConfig;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Email plugin configuration, read from the plugin's `config:` block
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmailConfig {
+    /// SMTP server, with the settings of an email alert channel
+    /// (`smtp_host`, `smtp_port`, `username_env`, `password_env`, `from`)
+    pub smtp: Option<AlertChannelConfig>,
+
+    /// Keep emails in the mailbox shown on the dashboard instead of sending
+    /// them; always the case without an `smtp_host`
+    #[serde(default)]
+    pub mailbox: bool,
+
+    /// Emails the mailbox keeps, the oldest dropped first
+    #[serde(default = "default_mailbox_size")]
+    pub mailbox_size: usize,
+
+    /// Handlebars templates emails name under `template`, by name
+    #[serde(default)]
+    pub templates: HashMap<String, EmailTemplate>,
+}
+
+/// Subject and bodies of an email, rendered with its `data`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmailTemplate {
+    pub subject: String,
+
+    /// Plain text body
+    pub text: Option<String>,
+
+    /// HTML body; values are HTML-escaped unless written as `{{{value}}}`
+    pub html: Option<String>,
+
+    /// File holding the plain text body, instead of `text`
+    pub text_file: Option<PathBuf>,
+
+    /// File holding the HTML body, instead of `html`
+    pub html_file: Option<PathBuf>,
+}
+
+fn default_mailbox_size() -> usize { 100 }
+
+impl EmailConfig {
+    /// The SMTP server emails are sent through, `None` when they go to the
+    /// mailbox
+    pub fn smtp(&self) -> Option<&AlertChannelConfig> {
+        self.smtp.as_ref().filter(|smtp| !self.mailbox && smtp.smtp_host.is_some())
+    }
+}
+
+/// An email a handler or an endpoint action asks for
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EmailRequest {
+    /// One address or a list
+    #[serde(deserialize_with = "addresses")]
+    pub to: Vec<String>,
+    #[serde(default, deserialize_with = "addresses")]
+    pub cc: Vec<String>,
+    #[serde(default, deserialize_with = "addresses")]
+    pub bcc: Vec<String>,
+    pub from: Option<String>,
+    pub reply_to: Option<String>,
+
+    /// Replaces the template's subject; required without a template
+    pub subject: Option<String>,
+
+    /// Name of a configured template
+    pub template: Option<String>,
+
+    /// Values the subject and bodies are rendered with
+    #[serde(default)]
+    pub data: Value,
+
+    /// Plain text body template, without `template`
+    pub text: Option<String>,
+
+    /// HTML body template, without `template`
+    pub html: Option<String>,
+}
+
+fn addresses<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Addresses {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match Addresses::deserialize(deserializer)? {
+        Addresses::One(address) => vec![address],
+        Addresses::Many(addresses) => addresses,
+    })
+}
@@ -0,0 +1,66 @@
+//! The mailbox on the dashboard, below `/plugins/email`
+//!
+//! - `GET /` lists the emails in the mailbox
+//! - `GET /messages` returns them as JSON, newest first
+//! - `GET /messages/{id}` shows one email's body, HTML when it has one
+//! - `DELETE /messages` empties the mailbox
+
+use crate::mailer::{Mailer, StoredEmail};
+use axum::extract::Path;
+use axum::http::{header, Method, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use axum::Json;
+use backworks::plugin::PluginRoutes;
+use serde_json::json;
+use std::sync::Arc;
+
+pub fn routes(mailer: Arc<Mailer>) -> PluginRoutes {
+    let (list, messages, message, clear) = (mailer.clone(), mailer.clone(), mailer.clone(), mailer);
+    PluginRoutes::new()
+        .route(Method::GET, "/", move || async move { Html(page(&list.messages(), list.config.smtp().is_some())) })
+        .route(Method::GET, "/messages", move || async move { Json(messages.messages()) })
+        .route(Method::GET, "/messages/{id}", move |Path(id): Path<u64>| async move {
+            match message.message(id) {
+                Some(email) => show(email),
+                None => (StatusCode::NOT_FOUND, Json(json!({ "error": format!("No email {} in the mailbox", id), "status": 404 }))).into_response(),
+            }
+        })
+        .route(Method::DELETE, "/messages", move || async move {
+            (StatusCode::OK, Json(json!({ "cleared": clear.clear() })))
+        })
+}
+
+fn show(email: StoredEmail) -> Response {
+    match email.mail.html {
+        Some(html) => Html(html).into_response(),
+        None => ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], email.mail.text.unwrap_or_default()).into_response(),
+    }
+}
+
+fn page(emails: &[StoredEmail], sending: bool) -> String {
+    let notice = if sending {
+        "Emails are sent through SMTP; the mailbox stays empty."
+    } else {
+        "Emails are kept here instead of being sent."
+    };
+    let rows: String = emails.iter()
+        .map(|email| format!(
+            "<tr><td>{}</td><td>{}</td><td><a href=\"messages/{}\">{}</a></td></tr>",
+            email.received_at.format("%Y-%m-%d %H:%M:%S"),
+            escape(&email.mail.to.join(", ")),
+            email.id,
+            escape(&email.mail.subject),
+        ))
+        .collect();
+    format!(
+        "<!DOCTYPE html><html><head><title>Mailbox</title></head><body>\
+         <h1>Mailbox</h1><p>{}</p>\
+         <table><thead><tr><th>Received</th><th>To</th><th>Subject</th></tr></thead><tbody>{}</tbody></table>\
+         </body></html>",
+        notice, rows
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
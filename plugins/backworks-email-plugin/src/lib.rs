@@ -0,0 +1,194 @@
+//! # Backworks Email Plugin
+//!
+//! Sends email from a Backworks API, rendered from Handlebars templates:
+//!
+//! - from handlers: JavaScript handlers call `ctx.email.send({ to, template,
+//!   data })`, and the email goes out once the response is ready
+//! - from the blueprint: endpoints listing the `email` middleware send one
+//!   email per request, addressed and filled from the request and response
+//! - in development: without an `smtp_host` (or with `mailbox: true`) emails
+//!   are kept in a mailbox on the dashboard, at `/plugins/email`
+//!
+//! The SMTP settings are those of email alert channels.
+//!
+//! ```yaml
+//! plugins:
+//!   email:
+//!     enabled: true
+//!     config:
+//!       smtp:
+//!         smtp_host: "smtp.example.com"
+//!         smtp_port: 587
+//!         username_env: "SMTP_USER"
+//!         password_env: "SMTP_PASSWORD"
+//!         from: "Shop <shop@example.com>"
+//!       mailbox: false
+//!       templates:
+//!         welcome:
+//!           subject: "Welcome, {{name}}"
+//!           text: "Hi {{name}}, thanks for signing up."
+//!           html_file: "./emails/welcome.html"
+//!
+//! endpoints:
+//!   signup:
+//!     path: "/signup"
+//!     methods: [POST]
+//!     middleware:
+//!       - email:
+//!           to: ["{{request.body.email}}"]
+//!           template: welcome
+//!           data: { name: "new customer" }
+//! ```
+
+pub mod action;
+pub mod config;
+pub mod dashboard;
+pub mod mailer;
+pub mod plugin;
+
+pub use action::{EmailAction, SendEmail, SendOn};
+pub use config::{EmailConfig, EmailRequest, EmailTemplate};
+pub use mailer::{Mailer, StoredEmail};
+pub use plugin::EmailPlugin;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use backworks::alerting::Mail;
+    use backworks::config::MiddlewareSpec;
+    use backworks::pipeline::{run_pipeline, MiddlewareRegistry};
+    use backworks::server::HandlerEmails;
+    use backworks::BackworksPlugin;
+    use serde_json::{json, Value};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn config() -> Value {
+        json!({
+            "templates": {
+                "welcome": {
+                    "subject": "Welcome, {{name}}",
+                    "text": "Hi {{name}} & co",
+                    "html": "<p>Hi {{name}}</p>",
+                },
+            },
+        })
+    }
+
+    async fn mailbox(plugin: &EmailPlugin, count: usize) -> Vec<StoredEmail> {
+        let mailer = plugin.mailer().unwrap();
+        for _ in 0..100 {
+            if mailer.messages().len() >= count {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        mailer.messages()
+    }
+
+    #[tokio::test]
+    async fn test_emails_render_from_templates_into_the_mailbox() {
+        let mailer = Mailer::new(serde_json::from_value(config()).unwrap()).unwrap();
+        assert!(mailer.config.smtp().is_none());
+
+        let request: EmailRequest = serde_json::from_value(json!({
+            "to": "ada@example.com", "template": "welcome", "data": { "name": "Ada <3" },
+        })).unwrap();
+        let mail = mailer.render(&request).unwrap();
+        assert_eq!(mail, Mail {
+            to: vec!["ada@example.com".to_string()],
+            subject: "Welcome, Ada <3".to_string(),
+            text: Some("Hi Ada <3 & co".to_string()),
+            html: Some("<p>Hi Ada &lt;3</p>".to_string()),
+            ..Default::default()
+        });
+
+        // Inline bodies without a template, and a replaced subject with one
+        let inline: EmailRequest = serde_json::from_value(json!({
+            "to": ["a@example.com", "b@example.com"], "subject": "Order {{id}}", "text": "Total {{total}}", "data": { "id": 7, "total": 9.5 },
+        })).unwrap();
+        let mail = mailer.render(&inline).unwrap();
+        assert_eq!((mail.to.len(), mail.subject.as_str(), mail.text.as_deref(), mail.html), (2, "Order 7", Some("Total 9.5"), None));
+        let replaced = EmailRequest { subject: Some("Hello {{name}}".to_string()), ..request.clone() };
+        assert_eq!(mailer.render(&replaced).unwrap().subject, "Hello Ada <3");
+
+        for (email, problem) in [
+            (json!({ "to": "a@example.com", "template": "goodbye" }), "unknown template"),
+            (json!({ "to": "a@example.com", "text": "hi" }), "subject or a template"),
+            (json!({ "to": [], "subject": "hi" }), "'to' address"),
+        ] {
+            let error = mailer.render(&serde_json::from_value(email).unwrap()).unwrap_err();
+            assert!(error.to_string().contains(problem), "{}", error);
+        }
+
+        let small = Mailer::new(serde_json::from_value(json!({ "mailbox_size": 2 })).unwrap()).unwrap();
+        for n in 1..=3 {
+            small.send(&serde_json::from_value(json!({ "to": "a@example.com", "subject": format!("#{}", n) })).unwrap()).await.unwrap();
+        }
+        let subjects: Vec<String> = small.messages().into_iter().map(|email| email.mail.subject).collect();
+        assert_eq!(subjects, vec!["#3", "#2"]);
+        assert_eq!(small.message(3).unwrap().mail.subject, "#3");
+        assert_eq!(small.clear(), 2);
+
+        let error = Mailer::new(serde_json::from_value(json!({ "templates": { "bad": { "subject": "{{#if}}" } } })).unwrap()).err().unwrap();
+        assert!(error.to_string().contains("template bad"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_handler_emails_and_endpoint_actions_are_sent() {
+        let plugin = EmailPlugin::new();
+        plugin.initialize(&config()).await.unwrap();
+
+        // Emails a handler queued leave with its response
+        let mut response = axum::response::Response::new(Body::empty());
+        response.extensions_mut().insert(HandlerEmails(vec![
+            json!({ "to": "ada@example.com", "template": "welcome", "data": { "name": "Ada" } }),
+            json!({ "subject": "no recipient" }),
+        ]));
+        plugin.after_response(&mut response).await.unwrap();
+        assert!(response.extensions().get::<HandlerEmails>().is_none());
+        let emails = mailbox(&plugin, 1).await;
+        assert_eq!((emails.len(), emails[0].mail.subject.as_str()), (1, "Welcome, Ada"));
+
+        // The `email` middleware addresses and fills emails from the request
+        let mut registry = MiddlewareRegistry::default();
+        plugin.register_middleware(&mut registry);
+        let settings = json!({ "to": ["{{request.body.email}}"], "template": "welcome", "data": { "name": "{{ignored}}" } });
+        let pipeline = registry.build("signup", &[MiddlewareSpec { name: "email".to_string(), config: settings }]).unwrap().unwrap();
+        let app = Router::new()
+            .route("/signup", post(|body: String| async move { (StatusCode::CREATED, body) }))
+            .route_layer(axum::middleware::from_fn_with_state(pipeline, run_pipeline));
+        let request = Request::post("/signup").body(Body::from(r#"{"email":"grace@example.com"}"#)).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], br#"{"email":"grace@example.com"}"#);
+        let emails = mailbox(&plugin, 2).await;
+        assert_eq!(emails[0].mail.to, vec!["grace@example.com".to_string()]);
+        assert_eq!(emails[0].mail.subject, "Welcome, {{ignored}}");
+
+        for (settings, problem) in [
+            (json!({ "to": [], "template": "welcome" }), "at least one recipient"),
+            (json!({ "to": ["a@example.com"], "template": "goodbye" }), "unknown template"),
+            (json!({ "to": ["a@example.com"] }), "subject or a template"),
+        ] {
+            let error = SendEmail::new(plugin.mailer().unwrap(), &settings).err().unwrap();
+            assert!(error.to_string().contains(problem), "{}", error);
+        }
+        let failed = SendEmail::new(plugin.mailer().unwrap(), &json!({ "to": ["a@example.com"], "subject": "x" })).unwrap();
+        assert!(failed.email("signup", json!({}), json!({ "status": 500 })).unwrap().is_none());
+
+        let pages: Vec<(String, Vec<String>)> = plugin.dashboard_routes().declared()
+            .map(|(path, methods)| (path.to_string(), methods.to_vec()))
+            .collect();
+        assert_eq!(pages, vec![
+            ("/".to_string(), vec!["GET".to_string()]),
+            ("/messages".to_string(), vec!["GET".to_string(), "DELETE".to_string()]),
+            ("/messages/{id}".to_string(), vec!["GET".to_string()]),
+        ]);
+    }
+}
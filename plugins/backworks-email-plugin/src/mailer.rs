@@ -0,0 +1,145 @@
+//! Rendering emails from templates, and sending or keeping them
+//!
+//! Subjects and plain text bodies are rendered without escaping, HTML bodies
+//! with it. Without an SMTP server emails land in the mailbox, a bounded
+//! in-memory list the dashboard shows.
+
+use crate::config::{EmailConfig, EmailRequest};
+use backworks::alerting::{send_mail, Mail};
+use backworks::error::{BackworksError, BackworksResult};
+use chrono::{DateTime, Utc};
+use handlebars::Handlebars;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// An email kept in the mailbox
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StoredEmail {
+    pub id: u64,
+    pub received_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub mail: Mail,
+}
+
+pub struct Mailer {
+    pub config: EmailConfig,
+    plain: Handlebars<'static>,
+    html: Handlebars<'static>,
+    mailbox: Mutex<VecDeque<StoredEmail>>,
+    next_id: AtomicU64,
+}
+
+impl Mailer {
+    /// Compile every configured template up front
+    pub fn new(config: EmailConfig) -> BackworksResult<Self> {
+        let mut plain = Handlebars::new();
+        plain.register_escape_fn(handlebars::no_escape);
+        let mut html = Handlebars::new();
+        let invalid = |name: &str, e: &dyn std::fmt::Display| BackworksError::PluginConfigInvalid(format!("email: template {}: {}", name, e));
+        for (name, template) in &config.templates {
+            plain.register_template_string(&format!("{}.subject", name), &template.subject).map_err(|e| invalid(name, &e))?;
+            if let Some(text) = body(&template.text, &template.text_file).map_err(|e| invalid(name, &e))? {
+                plain.register_template_string(&format!("{}.text", name), text).map_err(|e| invalid(name, &e))?;
+            }
+            if let Some(source) = body(&template.html, &template.html_file).map_err(|e| invalid(name, &e))? {
+                html.register_template_string(&format!("{}.html", name), source).map_err(|e| invalid(name, &e))?;
+            }
+        }
+        Ok(Self { config, plain, html, mailbox: Mutex::new(VecDeque::new()), next_id: AtomicU64::new(1) })
+    }
+
+    /// The email `request` describes, rendered
+    pub fn render(&self, request: &EmailRequest) -> BackworksResult<Mail> {
+        let failed = |e: handlebars::RenderError| BackworksError::config(format!("email: {}", e));
+        let (subject, text, html) = match request.template {
+            Some(ref name) => {
+                if !self.config.templates.contains_key(name) {
+                    return Err(BackworksError::config(format!("email: unknown template '{}'", name)));
+                }
+                let render = |registry: &Handlebars, part: &str| -> BackworksResult<Option<String>> {
+                    let template = format!("{}.{}", name, part);
+                    if !registry.has_template(&template) {
+                        return Ok(None);
+                    }
+                    registry.render(&template, &request.data).map(Some).map_err(failed)
+                };
+                let subject = match request.subject {
+                    Some(ref subject) => self.plain.render_template(subject, &request.data).map_err(failed)?,
+                    None => render(&self.plain, "subject")?.unwrap_or_default(),
+                };
+                (subject, render(&self.plain, "text")?, render(&self.html, "html")?)
+            }
+            None => {
+                let subject = request.subject.as_ref()
+                    .ok_or_else(|| BackworksError::config("email: a subject or a template is required"))?;
+                let render = |registry: &Handlebars, source: &Option<String>| -> BackworksResult<Option<String>> {
+                    source.as_ref().map(|source| registry.render_template(source, &request.data).map_err(failed)).transpose()
+                };
+                (
+                    self.plain.render_template(subject, &request.data).map_err(failed)?,
+                    render(&self.plain, &request.text)?,
+                    render(&self.html, &request.html)?,
+                )
+            }
+        };
+        if request.to.is_empty() {
+            return Err(BackworksError::config("email: at least one 'to' address is required"));
+        }
+        Ok(Mail {
+            from: request.from.clone(),
+            to: request.to.clone(),
+            cc: request.cc.clone(),
+            bcc: request.bcc.clone(),
+            reply_to: request.reply_to.clone(),
+            subject,
+            text,
+            html,
+        })
+    }
+
+    /// Send `mail`, or keep it in the mailbox
+    pub async fn deliver(&self, mail: Mail) -> BackworksResult<()> {
+        match self.config.smtp() {
+            Some(smtp) => send_mail(smtp, &mail).await,
+            None => {
+                let stored = StoredEmail { id: self.next_id.fetch_add(1, Ordering::Relaxed), received_at: Utc::now(), mail };
+                let mut mailbox = self.mailbox.lock().unwrap_or_else(|e| e.into_inner());
+                mailbox.push_front(stored);
+                mailbox.truncate(self.config.mailbox_size);
+                Ok(())
+            }
+        }
+    }
+
+    pub async fn send(&self, request: &EmailRequest) -> BackworksResult<()> {
+        self.deliver(self.render(request)?).await
+    }
+
+    /// Emails in the mailbox, newest first
+    pub fn messages(&self) -> Vec<StoredEmail> {
+        self.mailbox.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+    }
+
+    pub fn message(&self, id: u64) -> Option<StoredEmail> {
+        self.mailbox.lock().unwrap_or_else(|e| e.into_inner()).iter().find(|email| email.id == id).cloned()
+    }
+
+    /// Empty the mailbox; returns how many emails it held
+    pub fn clear(&self) -> usize {
+        self.mailbox.lock().unwrap_or_else(|e| e.into_inner()).drain(..).count()
+    }
+}
+
+/// A template body, inline or read from its file
+fn body(inline: &Option<String>, file: &Option<std::path::PathBuf>) -> Result<Option<String>, String> {
+    match (inline, file) {
+        (Some(_), Some(_)) => Err("set a body or its file, not both".to_string()),
+        (Some(source), None) => Ok(Some(source.clone())),
+        (None, Some(file)) => std::fs::read_to_string(file)
+            .map(Some)
+            .map_err(|e| format!("cannot read {}: {}", file.display(), e)),
+        (None, None) => Ok(None),
+    }
+}
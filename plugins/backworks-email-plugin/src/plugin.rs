@@ -0,0 +1,120 @@
+//! Backworks plugin wiring for email
+
+use crate::action::SendEmail;
+use crate::config::{EmailConfig, EmailRequest};
+use crate::mailer::Mailer;
+use async_trait::async_trait;
+use axum::body::Body;
+use axum::response::Response;
+use backworks::error::{BackworksError, BackworksResult};
+use backworks::pipeline::{EndpointMiddleware, MiddlewareRegistry};
+use backworks::plugin::{BackworksPlugin, HealthStatus, PluginHealth, PluginRoutes};
+use backworks::server::HandlerEmails;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Sends the emails handlers queue with `ctx.email.send()` and endpoints ask
+/// for with the `email` middleware
+pub struct EmailPlugin {
+    mailer: RwLock<Option<Arc<Mailer>>>,
+}
+
+impl EmailPlugin {
+    pub fn new() -> Self {
+        Self { mailer: RwLock::new(None) }
+    }
+
+    pub fn mailer(&self) -> BackworksResult<Arc<Mailer>> {
+        self.mailer.read().unwrap_or_else(|e| e.into_inner()).clone()
+            .ok_or_else(|| BackworksError::plugin("Email plugin is not initialized"))
+    }
+}
+
+impl Default for EmailPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl BackworksPlugin for EmailPlugin {
+    fn name(&self) -> &str {
+        "email"
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &str {
+        "Templated email over SMTP, with a development mailbox"
+    }
+
+    async fn initialize(&self, config: &Value) -> BackworksResult<()> {
+        let config: EmailConfig = serde_json::from_value(config.clone())
+            .map_err(|e| BackworksError::PluginConfigInvalid(format!("email: {}", e)))?;
+        if config.smtp().is_none() {
+            tracing::info!("📬 Email plugin keeps emails in the dashboard mailbox instead of sending them");
+        }
+        *self.mailer.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(Mailer::new(config)?));
+        Ok(())
+    }
+
+    async fn shutdown(&self) -> BackworksResult<()> {
+        *self.mailer.write().unwrap_or_else(|e| e.into_inner()) = None;
+        Ok(())
+    }
+
+    async fn health_check(&self) -> BackworksResult<PluginHealth> {
+        let mailer = self.mailer()?;
+        let (message, details) = match mailer.config.smtp() {
+            Some(smtp) => (
+                "Sending through SMTP".to_string(),
+                HashMap::from([("smtp_host".to_string(), Value::from(smtp.smtp_host.clone()))]),
+            ),
+            None => (
+                "Keeping emails in the mailbox".to_string(),
+                HashMap::from([("mailbox".to_string(), Value::from(mailer.messages().len()))]),
+            ),
+        };
+        Ok(PluginHealth { status: HealthStatus::Healthy, message, details })
+    }
+
+    /// Send the emails the handler queued
+    async fn after_response(&self, response: &mut Response<Body>) -> BackworksResult<()> {
+        let Some(HandlerEmails(emails)) = response.extensions_mut().remove::<HandlerEmails>() else {
+            return Ok(());
+        };
+        let mailer = self.mailer()?;
+        tokio::spawn(async move {
+            for email in emails {
+                let sent = match serde_json::from_value::<EmailRequest>(email) {
+                    Ok(email) => mailer.send(&email).await,
+                    Err(e) => Err(BackworksError::config(format!("email: {}", e))),
+                };
+                if let Err(e) = sent {
+                    tracing::warn!("Failed to send email queued by a handler: {}", e);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn dashboard_routes(&self) -> PluginRoutes {
+        match self.mailer() {
+            Ok(mailer) => crate::dashboard::routes(mailer),
+            Err(_) => PluginRoutes::new(),
+        }
+    }
+
+    /// The `email` middleware, once the plugin is configured
+    fn register_middleware(&self, registry: &mut MiddlewareRegistry) {
+        let Ok(mailer) = self.mailer() else {
+            return;
+        };
+        registry.register("email", move |settings| {
+            Ok(Arc::new(SendEmail::new(mailer.clone(), settings)?) as Arc<dyn EndpointMiddleware>)
+        });
+    }
+}
@@ -0,0 +1,48 @@
+//! Configuration for the GeoIP plugin
+
+use serde::{Deserialize, Serialize};
+
+/// GeoIP plugin configuration, read from the plugin's `config:` block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeoIpConfig {
+    /// GeoLite2 / GeoIP2 Country or City database (`.mmdb`)
+    pub country_db: Option<String>,
+
+    /// GeoLite2 / GeoIP2 ASN database (`.mmdb`)
+    pub asn_db: Option<String>,
+
+    /// Proxies and load balancers whose forwarded-for header is believed.
+    /// Addresses or CIDR ranges; without any, the peer address is the client.
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+
+    /// Header carrying the client chain when the peer is a trusted proxy
+    #[serde(default = "default_forwarded_header")]
+    pub forwarded_header: String,
+
+    /// Also expose the origin as `X-Geo-*` request headers, for proxied
+    /// upstreams and header-keyed rules. `X-Geo-*` headers sent by clients
+    /// are always dropped.
+    #[serde(default)]
+    pub add_headers: bool,
+
+    /// Language of `country_name`
+    #[serde(default = "default_locale")]
+    pub locale: String,
+}
+
+fn default_forwarded_header() -> String { "X-Forwarded-For".to_string() }
+fn default_locale() -> String { "en".to_string() }
+
+impl Default for GeoIpConfig {
+    fn default() -> Self {
+        Self {
+            country_db: None,
+            asn_db: None,
+            trusted_proxies: Vec::new(),
+            forwarded_header: default_forwarded_header(),
+            add_headers: false,
+            locale: default_locale(),
+        }
+    }
+}
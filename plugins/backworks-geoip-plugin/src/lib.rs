@@ -0,0 +1,120 @@
+//! # Backworks GeoIP Plugin
+//!
+//! Enriches every request with its origin: the client address and the
+//! country, continent and autonomous system it resolves to in local MaxMind
+//! databases (GeoLite2 or GeoIP2, Country/City and ASN).
+//!
+//! The origin is attached as a `RequestOrigin` request extension. Handlers
+//! see it as the `origin` field of the request data, the access log records
+//! its country and ASN, and `RequestOrigin::key` turns it into routing
+//! conditions and rate limiting keys. With `add_headers`, it is also exposed
+//! as `X-Geo-Country`, `X-Geo-Continent` and `X-Geo-ASN` request headers.
+//!
+//! ```yaml
+//! plugins:
+//!   geoip:
+//!     enabled: true
+//!     config:
+//!       country_db: "/var/lib/GeoIP/GeoLite2-Country.mmdb"
+//!       asn_db: "/var/lib/GeoIP/GeoLite2-ASN.mmdb"
+//!       trusted_proxies: ["10.0.0.0/8"]
+//!       add_headers: true
+//! ```
+
+pub mod config;
+pub mod lookup;
+pub mod plugin;
+
+pub use config::GeoIpConfig;
+pub use lookup::{GeoLookup, MmdbLookup};
+pub use plugin::GeoIpPlugin;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::ConnectInfo;
+    use backworks::origin::RequestOrigin;
+    use backworks::BackworksPlugin;
+    use serde_json::json;
+    use std::net::{IpAddr, SocketAddr};
+    use std::sync::Arc;
+
+    /// Everything in 203.0.113.0/24 is in Sweden, the rest is unknown
+    struct FixedLookup;
+
+    impl GeoLookup for FixedLookup {
+        fn lookup(&self, ip: IpAddr) -> RequestOrigin {
+            let mut origin = RequestOrigin { ip: Some(ip), ..Default::default() };
+            if ip.to_string().starts_with("203.0.113.") {
+                origin.country = Some("SE".to_string());
+                origin.continent = Some("EU".to_string());
+                origin.asn = Some(64500);
+            }
+            origin
+        }
+    }
+
+    async fn plugin(config: serde_json::Value) -> GeoIpPlugin {
+        let plugin = GeoIpPlugin::with_lookup(Arc::new(FixedLookup));
+        plugin.initialize(&config).await.unwrap();
+        plugin
+    }
+
+    fn request(peer: &str, forwarded: Option<&str>) -> axum::extract::Request {
+        let mut request = axum::extract::Request::new(axum::body::Body::empty());
+        let peer: SocketAddr = format!("{}:40000", peer).parse().unwrap();
+        request.extensions_mut().insert(ConnectInfo(peer));
+        request.headers_mut().insert("x-geo-country", "US".parse().unwrap());
+        if let Some(forwarded) = forwarded {
+            request.headers_mut().insert("x-forwarded-for", forwarded.parse().unwrap());
+        }
+        request
+    }
+
+    #[tokio::test]
+    async fn test_origin_is_attached() {
+        let plugin = plugin(json!({ "add_headers": true })).await;
+        let mut request = request("203.0.113.7", None);
+        plugin.before_request(&mut request).await.unwrap();
+
+        let origin = request.extensions().get::<RequestOrigin>().unwrap();
+        assert_eq!(origin.country.as_deref(), Some("SE"));
+        assert_eq!(origin.key("asn").as_deref(), Some("64500"));
+        assert_eq!(request.headers()["x-geo-country"], "SE");
+        assert_eq!(request.headers()["x-geo-asn"], "64500");
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_for_only_from_trusted_proxies() {
+        let plugin = plugin(json!({ "trusted_proxies": ["10.0.0.0/8"] })).await;
+
+        // Rightmost untrusted hop; the leftmost entry is client-controlled
+        let mut proxied = request("10.0.0.2", Some("198.51.100.1, 203.0.113.7, 10.0.0.9"));
+        plugin.before_request(&mut proxied).await.unwrap();
+        let origin = proxied.extensions().get::<RequestOrigin>().unwrap();
+        assert_eq!(origin.ip, Some("203.0.113.7".parse().unwrap()));
+
+        // A dual-stack listener reports the proxy as IPv4-mapped; hops may carry ports
+        let mut proxied = request("[::ffff:10.0.0.2]", Some("[2001:db8::7]:4711, 10.0.0.9:80"));
+        plugin.before_request(&mut proxied).await.unwrap();
+        let origin = proxied.extensions().get::<RequestOrigin>().unwrap();
+        assert_eq!(origin.ip, Some("2001:db8::7".parse().unwrap()));
+
+        let mut direct = request("198.51.100.1", Some("203.0.113.7"));
+        plugin.before_request(&mut direct).await.unwrap();
+        let origin = direct.extensions().get::<RequestOrigin>().unwrap();
+        assert_eq!(origin.ip, Some("198.51.100.1".parse().unwrap()));
+        assert_eq!(origin.country, None);
+        // Spoofed enrichment headers never reach the handler
+        assert!(direct.headers().get("x-geo-country").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_databases_are_required() {
+        let error = GeoIpPlugin::new().initialize(&json!({})).await.unwrap_err();
+        assert!(error.to_string().contains("country_db or asn_db"));
+
+        let error = GeoIpPlugin::new().initialize(&json!({ "asn_db": "/nonexistent.mmdb" })).await.unwrap_err();
+        assert!(error.to_string().contains("/nonexistent.mmdb"));
+    }
+}
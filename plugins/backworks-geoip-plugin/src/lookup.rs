@@ -0,0 +1,96 @@
+//! Address lookups against MaxMind databases
+
+use backworks::error::{BackworksError, BackworksResult};
+use backworks::origin::RequestOrigin;
+use maxminddb::{geoip2, MaxMindDBError, Reader};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use tracing::debug;
+
+/// Source of origin data for an address
+pub trait GeoLookup: Send + Sync {
+    /// Origin of `ip`; fields the source knows nothing about stay `None`
+    fn lookup(&self, ip: IpAddr) -> RequestOrigin;
+
+    /// Loaded databases, for the plugin health report
+    fn describe(&self) -> HashMap<String, Value> {
+        HashMap::new()
+    }
+}
+
+/// Lookups in local `.mmdb` files, loaded into memory once
+pub struct MmdbLookup {
+    country: Option<Reader<Vec<u8>>>,
+    asn: Option<Reader<Vec<u8>>>,
+    locale: String,
+}
+
+impl MmdbLookup {
+    pub fn open(country_db: Option<&str>, asn_db: Option<&str>, locale: &str) -> BackworksResult<Self> {
+        if country_db.is_none() && asn_db.is_none() {
+            return Err(BackworksError::PluginConfigInvalid(
+                "geoip: at least one of country_db or asn_db is required".to_string(),
+            ));
+        }
+        Ok(Self {
+            country: country_db.map(open).transpose()?,
+            asn: asn_db.map(open).transpose()?,
+            locale: locale.to_string(),
+        })
+    }
+}
+
+fn open(path: &str) -> BackworksResult<Reader<Vec<u8>>> {
+    Reader::open_readfile(path)
+        .map_err(|e| BackworksError::PluginConfigInvalid(format!("geoip: cannot open '{}': {}", path, e)))
+}
+
+/// A missing address is an ordinary outcome; anything else is worth a note
+fn found<T>(result: Result<T, MaxMindDBError>, ip: IpAddr) -> Option<T> {
+    match result {
+        Ok(record) => Some(record),
+        Err(MaxMindDBError::AddressNotFoundError(_)) => None,
+        Err(e) => {
+            debug!("GeoIP lookup for {} failed: {}", ip, e);
+            None
+        }
+    }
+}
+
+impl GeoLookup for MmdbLookup {
+    fn lookup(&self, ip: IpAddr) -> RequestOrigin {
+        let mut origin = RequestOrigin { ip: Some(ip), ..Default::default() };
+
+        // City databases are a superset of Country ones
+        if let Some(record) = self.country.as_ref().and_then(|db| found(db.lookup::<geoip2::Country>(ip), ip)) {
+            if let Some(country) = record.country {
+                origin.country = country.iso_code.map(String::from);
+                origin.country_name = country.names
+                    .and_then(|names| names.get(self.locale.as_str()).or_else(|| names.get("en")).copied())
+                    .map(String::from);
+            }
+            origin.continent = record.continent.and_then(|continent| continent.code).map(String::from);
+        }
+
+        if let Some(record) = self.asn.as_ref().and_then(|db| found(db.lookup::<geoip2::Asn>(ip), ip)) {
+            origin.asn = record.autonomous_system_number;
+            origin.as_org = record.autonomous_system_organization.map(String::from);
+        }
+
+        origin
+    }
+
+    fn describe(&self) -> HashMap<String, Value> {
+        [("country_db", &self.country), ("asn_db", &self.asn)]
+            .into_iter()
+            .filter_map(|(name, reader)| {
+                let metadata = &reader.as_ref()?.metadata;
+                Some((name.to_string(), json!({
+                    "database_type": metadata.database_type,
+                    "build_epoch": metadata.build_epoch,
+                })))
+            })
+            .collect()
+    }
+}
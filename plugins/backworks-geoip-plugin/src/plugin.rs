@@ -0,0 +1,163 @@
+//! Backworks plugin wiring for GeoIP enrichment
+
+use crate::config::GeoIpConfig;
+use crate::lookup::{GeoLookup, MmdbLookup};
+use async_trait::async_trait;
+use axum::extract::ConnectInfo;
+use axum::http::{HeaderMap, HeaderValue};
+use backworks::error::{BackworksError, BackworksResult};
+use backworks::origin::{parse_client_ip, RequestOrigin};
+use backworks::plugin::{BackworksPlugin, HealthStatus, PluginHealth};
+use ipnet::IpNet;
+use serde_json::Value;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub const COUNTRY_HEADER: &str = "x-geo-country";
+pub const CONTINENT_HEADER: &str = "x-geo-continent";
+pub const ASN_HEADER: &str = "x-geo-asn";
+
+struct Enricher {
+    config: GeoIpConfig,
+    proxies: Vec<IpNet>,
+    lookup: Arc<dyn GeoLookup>,
+}
+
+impl Enricher {
+    /// The client address: the peer, or when the peer is a trusted proxy, the
+    /// rightmost untrusted hop of the forwarded-for chain
+    fn client_ip(&self, headers: &HeaderMap, peer: IpAddr) -> IpAddr {
+        let peer = peer.to_canonical();
+        if !self.is_trusted(peer) {
+            return peer;
+        }
+        let hops = headers.get_all(self.config.forwarded_header.as_str())
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .collect::<Vec<_>>();
+
+        let mut client = peer;
+        for hop in hops.into_iter().rev() {
+            // Anything unparseable was written by someone we cannot vouch for
+            let Some(ip) = parse_client_ip(hop) else {
+                break;
+            };
+            client = ip;
+            if !self.is_trusted(ip) {
+                break;
+            }
+        }
+        client
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.proxies.iter().any(|net| net.contains(&ip))
+    }
+}
+
+/// Attaches the client's `RequestOrigin` (address, country, ASN) to each
+/// request
+pub struct GeoIpPlugin {
+    enricher: RwLock<Option<Arc<Enricher>>>,
+    lookup: Option<Arc<dyn GeoLookup>>,
+}
+
+impl GeoIpPlugin {
+    pub fn new() -> Self {
+        Self { enricher: RwLock::new(None), lookup: None }
+    }
+
+    /// Use `lookup` instead of the databases named in the configuration
+    pub fn with_lookup(lookup: Arc<dyn GeoLookup>) -> Self {
+        Self { enricher: RwLock::new(None), lookup: Some(lookup) }
+    }
+
+    async fn enricher(&self) -> BackworksResult<Arc<Enricher>> {
+        self.enricher.read().await.clone()
+            .ok_or_else(|| BackworksError::plugin("GeoIP plugin is not initialized"))
+    }
+}
+
+impl Default for GeoIpPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl BackworksPlugin for GeoIpPlugin {
+    fn name(&self) -> &str {
+        "geoip"
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &str {
+        "IP geolocation and request origin enrichment"
+    }
+
+    async fn initialize(&self, config: &Value) -> BackworksResult<()> {
+        let config: GeoIpConfig = serde_json::from_value(config.clone())
+            .map_err(|e| BackworksError::PluginConfigInvalid(format!("geoip: {}", e)))?;
+        let proxies = config.trusted_proxies.iter()
+            .map(|entry| {
+                entry.parse::<IpNet>()
+                    .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| BackworksError::PluginConfigInvalid(format!("geoip: invalid trusted proxy '{}'", entry)))
+            })
+            .collect::<BackworksResult<Vec<_>>>()?;
+        let lookup = match self.lookup {
+            Some(ref lookup) => lookup.clone(),
+            None => Arc::new(MmdbLookup::open(config.country_db.as_deref(), config.asn_db.as_deref(), &config.locale)?),
+        };
+
+        *self.enricher.write().await = Some(Arc::new(Enricher { config, proxies, lookup }));
+        Ok(())
+    }
+
+    async fn shutdown(&self) -> BackworksResult<()> {
+        *self.enricher.write().await = None;
+        Ok(())
+    }
+
+    async fn health_check(&self) -> BackworksResult<PluginHealth> {
+        let enricher = self.enricher().await?;
+        Ok(PluginHealth {
+            status: HealthStatus::Healthy,
+            message: "GeoIP databases loaded".to_string(),
+            details: enricher.lookup.describe(),
+        })
+    }
+
+    async fn before_request(&self, request: &mut axum::extract::Request) -> BackworksResult<()> {
+        let enricher = self.enricher().await?;
+
+        let headers = request.headers_mut();
+        for name in [COUNTRY_HEADER, CONTINENT_HEADER, ASN_HEADER] {
+            headers.remove(name);
+        }
+
+        let Some(peer) = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|info| info.0.ip()) else {
+            return Ok(());
+        };
+        let origin = enricher.lookup.lookup(enricher.client_ip(request.headers(), peer));
+
+        if enricher.config.add_headers {
+            set_origin_headers(request.headers_mut(), &origin);
+        }
+        request.extensions_mut().insert(origin);
+        Ok(())
+    }
+}
+
+fn set_origin_headers(headers: &mut HeaderMap, origin: &RequestOrigin) {
+    for (name, field) in [(COUNTRY_HEADER, "country"), (CONTINENT_HEADER, "continent"), (ASN_HEADER, "asn")] {
+        if let Some(value) = origin.key(field).and_then(|value| HeaderValue::from_str(&value).ok()) {
+            headers.insert(name, value);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Configuration for the jobs plugin
+
+use backworks::config::{parse_duration, RuntimeConfig};
+use backworks::error::{BackworksError, BackworksResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Jobs plugin configuration, read from the plugin's `config:` block
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobsConfig {
+    /// Where queued and dead jobs are kept
+    #[serde(default)]
+    pub store: StoreConfig,
+
+    /// Jobs run at the same time
+    #[serde(default = "default_workers")]
+    pub workers: usize,
+
+    /// How often idle workers look for due jobs, such as `1s`; jobs queued
+    /// by this server wake them at once
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: String,
+
+    /// Attempts and backoff of failing jobs
+    #[serde(default)]
+    pub retry: RetryPolicy,
+
+    /// Longest a job may run, such as `5m`, unless its definition says
+    #[serde(default = "default_timeout")]
+    pub timeout: String,
+
+    /// Handlers jobs name when they are queued, by name
+    #[serde(default)]
+    pub jobs: HashMap<String, JobDefinition>,
+}
+
+/// Which store to use, and where it keeps jobs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum StoreConfig {
+    /// Lost on restart; for development and tests
+    Memory,
+    Sqlite {
+        #[serde(default = "default_sqlite_path")]
+        path: PathBuf,
+    },
+    Redis {
+        /// `redis://host:6379/0`
+        #[serde(default = "default_redis_url")]
+        url: String,
+        /// Prepended to the plugin's keys
+        #[serde(default = "default_redis_prefix")]
+        prefix: String,
+    },
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        Self::Sqlite { path: default_sqlite_path() }
+    }
+}
+
+/// How failing jobs are retried before landing in the dead-letter list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Runs of a job, the first one included
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+
+    #[serde(default)]
+    pub backoff: Backoff,
+
+    /// Wait before the first retry, such as `10s`
+    #[serde(default = "default_delay")]
+    pub delay: String,
+
+    /// Longest wait between two attempts
+    #[serde(default = "default_max_delay")]
+    pub max_delay: String,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_max_attempts(),
+            backoff: Backoff::default(),
+            delay: default_delay(),
+            max_delay: default_max_delay(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backoff {
+    /// The delay doubles after every failed attempt
+    #[default]
+    Exponential,
+    /// The same delay between all attempts
+    Fixed,
+}
+
+/// The handler running one kind of job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobDefinition {
+    pub runtime: RuntimeConfig,
+
+    /// Replaces `retry.max_attempts` for this job
+    pub max_attempts: Option<u32>,
+
+    /// Replaces the plugin's `timeout` for this job
+    pub timeout: Option<String>,
+}
+
+fn default_workers() -> usize { 2 }
+fn default_poll_interval() -> String { "1s".to_string() }
+fn default_timeout() -> String { "5m".to_string() }
+fn default_sqlite_path() -> PathBuf { PathBuf::from("jobs.db") }
+fn default_redis_url() -> String { "redis://127.0.0.1:6379".to_string() }
+fn default_redis_prefix() -> String { "backworks:jobs:".to_string() }
+fn default_max_attempts() -> u32 { 5 }
+fn default_delay() -> String { "10s".to_string() }
+fn default_max_delay() -> String { "10m".to_string() }
+
+/// `value` of the setting `name`, parsed
+pub fn duration(name: &str, value: &str) -> BackworksResult<Duration> {
+    parse_duration(value).map_err(|e| BackworksError::PluginConfigInvalid(format!("jobs: {}: {}", name, e)))
+}
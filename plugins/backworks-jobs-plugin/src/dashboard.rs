@@ -0,0 +1,79 @@
+//! The queue on the dashboard, below `/plugins/jobs`
+//!
+//! - `GET /` shows the queue and its dead-letter list
+//! - `GET /dead` returns the dead-letter list as JSON, newest first
+//! - `POST /dead/{id}/retry` queues a dead job again with fresh attempts
+//! - `DELETE /dead/{id}` drops a dead job, `DELETE /dead` all of them
+
+use crate::job::{Job, QueueCounts};
+use crate::queue::Queue;
+use axum::extract::Path;
+use axum::http::{Method, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use axum::Json;
+use backworks::error::BackworksResult;
+use backworks::plugin::PluginRoutes;
+use serde::Serialize;
+use serde_json::json;
+use std::sync::Arc;
+
+pub fn routes(queue: Arc<Queue>) -> PluginRoutes {
+    let (page_queue, dead, retry, discard, clear) = (queue.clone(), queue.clone(), queue.clone(), queue.clone(), queue);
+    PluginRoutes::new()
+        .route(Method::GET, "/", move || async move {
+            match (page_queue.counts().await, page_queue.dead().await) {
+                (Ok(counts), Ok(dead)) => Html(page(counts, &dead)).into_response(),
+                (Err(e), _) | (_, Err(e)) => e.into_response(),
+            }
+        })
+        .route(Method::GET, "/dead", move || async move { reply(dead.dead().await, StatusCode::OK) })
+        .route(Method::DELETE, "/dead", move || async move {
+            reply(clear.clear_dead().await.map(|cleared| json!({ "cleared": cleared })), StatusCode::OK)
+        })
+        .route(Method::POST, "/dead/{id}/retry", move |Path(id): Path<String>| async move {
+            found(retry.retry_dead(&id).await, &id, StatusCode::ACCEPTED)
+        })
+        .route(Method::DELETE, "/dead/{id}", move |Path(id): Path<String>| async move {
+            found(discard.discard_dead(&id).await, &id, StatusCode::OK)
+        })
+}
+
+fn reply<T: Serialize>(result: BackworksResult<T>, status: StatusCode) -> Response {
+    match result {
+        Ok(value) => (status, Json(value)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+fn found(result: BackworksResult<Option<Job>>, id: &str, status: StatusCode) -> Response {
+    match result {
+        Ok(Some(job)) => (status, Json(job)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, Json(json!({ "error": format!("No job {} in the dead-letter list", id), "status": 404 }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+fn page(counts: QueueCounts, dead: &[Job]) -> String {
+    let rows: String = dead.iter()
+        .map(|job| format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            job.failed_at.map(|at| at.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_default(),
+            escape(&job.name),
+            escape(&job.id),
+            job.attempts,
+            escape(job.last_error.as_deref().unwrap_or_default()),
+        ))
+        .collect();
+    format!(
+        "<!DOCTYPE html><html><head><title>Jobs</title></head><body>\
+         <h1>Jobs</h1><p>{} queued, {} dead</p>\
+         <h2>Dead letters</h2>\
+         <table><thead><tr><th>Failed</th><th>Job</th><th>Id</th><th>Attempts</th><th>Last error</th></tr></thead><tbody>{}</tbody></table>\
+         </body></html>",
+        counts.queued, counts.dead, rows
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,47 @@
+//! Queued jobs and the requests queuing them
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A job in the queue or the dead-letter list
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    /// The definition that runs the job
+    pub name: String,
+    pub payload: Value,
+    /// Runs started so far
+    pub attempts: u32,
+    pub max_attempts: u32,
+    pub enqueued_at: DateTime<Utc>,
+    /// When the job is due; while it runs, when another worker may take it
+    /// over if this one never finishes
+    pub run_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// When the job landed in the dead-letter list
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failed_at: Option<DateTime<Utc>>,
+}
+
+/// A job a handler queues with `ctx.jobs.enqueue(name, payload, options)`,
+/// or a schedule with the plugin's `enqueue` action
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EnqueueRequest {
+    pub name: String,
+    #[serde(default)]
+    pub payload: Value,
+    /// Run the job after this long, such as `30s`, instead of at once
+    pub delay: Option<String>,
+    /// Replaces the job's own `max_attempts`
+    pub max_attempts: Option<u32>,
+}
+
+/// Jobs in each part of the store
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct QueueCounts {
+    /// Waiting or running
+    pub queued: usize,
+    pub dead: usize,
+}
@@ -0,0 +1,210 @@
+//! # Backworks Jobs Plugin
+//!
+//! A background job queue for Backworks APIs:
+//!
+//! - from handlers: JavaScript handlers call `ctx.jobs.enqueue("send_welcome",
+//!   payload)`, and the job is queued once the response is ready
+//! - from schedules: the plugin's `enqueue` action queues the job its
+//!   `params` describe
+//! - workers run each job's runtime handler, retry failures with a backoff
+//!   and move jobs out of attempts to a dead-letter list, shown on the
+//!   dashboard at `/plugins/jobs`
+//!
+//! Jobs are kept in SQLite by default, or in Redis with the `redis` feature.
+//!
+//! ```yaml
+//! plugins:
+//!   jobs:
+//!     enabled: true
+//!     config:
+//!       store: { type: sqlite, path: "./jobs.db" }
+//!       workers: 4
+//!       retry: { max_attempts: 5, backoff: exponential, delay: 10s, max_delay: 10m }
+//!       jobs:
+//!         send_welcome:
+//!           runtime: { language: javascript, handler: "./jobs/send_welcome.js" }
+//!           timeout: 30s
+//! ```
+
+pub mod config;
+pub mod dashboard;
+pub mod job;
+pub mod plugin;
+pub mod queue;
+pub mod store;
+
+pub use config::{Backoff, JobDefinition, JobsConfig, RetryPolicy, StoreConfig};
+pub use job::{EnqueueRequest, Job, QueueCounts};
+pub use plugin::JobsPlugin;
+pub use queue::{Outcome, Queue};
+pub use store::{JobStore, MemoryStore};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use backworks::server::HandlerJobs;
+    use backworks::BackworksPlugin;
+    use chrono::{Duration, Utc};
+    use serde_json::{json, Value};
+    use std::time::Duration as StdDuration;
+
+    fn job(id: &str, run_in: i64) -> Job {
+        Job {
+            id: id.to_string(),
+            name: "send_welcome".to_string(),
+            payload: json!({ "id": id }),
+            attempts: 0,
+            max_attempts: 3,
+            enqueued_at: Utc::now(),
+            run_at: Utc::now() + Duration::seconds(run_in),
+            last_error: None,
+            failed_at: None,
+        }
+    }
+
+    async fn exercise(store: &dyn JobStore) {
+        store.push(&job("later", 3600)).await.unwrap();
+        store.push(&job("second", -5)).await.unwrap();
+        store.push(&job("first", -10)).await.unwrap();
+        assert_eq!(store.counts().await.unwrap(), QueueCounts { queued: 3, dead: 0 });
+
+        // Due jobs are claimed in order, once, and counted as attempted
+        let now = Utc::now();
+        let first = store.claim(now, now + Duration::minutes(5)).await.unwrap().unwrap();
+        assert_eq!((first.id.as_str(), first.attempts), ("first", 1));
+        let second = store.claim(now, now + Duration::minutes(10)).await.unwrap().unwrap();
+        assert_eq!(second.id, "second");
+        assert!(store.claim(now, now + Duration::minutes(5)).await.unwrap().is_none());
+        // Until their lease runs out
+        let abandoned = store.claim(now + Duration::minutes(6), now + Duration::minutes(12)).await.unwrap().unwrap();
+        assert_eq!((abandoned.id.as_str(), abandoned.attempts), ("first", 2));
+
+        store.complete("first").await.unwrap();
+        let mut failed = second.clone();
+        failed.last_error = Some("boom".to_string());
+        failed.failed_at = Some(Utc::now());
+        store.bury(&failed).await.unwrap();
+        assert_eq!(store.counts().await.unwrap(), QueueCounts { queued: 1, dead: 1 });
+        assert_eq!(store.dead().await.unwrap(), vec![failed.clone()]);
+        assert_eq!(store.take_dead("second").await.unwrap(), Some(failed.clone()));
+        assert_eq!(store.take_dead("second").await.unwrap(), None);
+        store.bury(&failed).await.unwrap();
+        assert_eq!(store.clear_dead().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stores_claim_due_jobs_and_keep_dead_letters() {
+        exercise(&MemoryStore::default()).await;
+        #[cfg(feature = "sqlite")]
+        {
+            let path = std::env::temp_dir().join(format!("backworks_jobs_{}.db", uuid::Uuid::new_v4()));
+            let sqlite = store::open(&StoreConfig::Sqlite { path: path.clone() }).unwrap();
+            exercise(sqlite.as_ref()).await;
+            drop(sqlite);
+            // Queued jobs survive a restart
+            let reopened = store::open(&StoreConfig::Sqlite { path: path.clone() }).unwrap();
+            assert_eq!(reopened.counts().await.unwrap(), QueueCounts { queued: 1, dead: 0 });
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    fn config(handler: &str) -> Value {
+        json!({
+            "store": { "type": "memory" },
+            "retry": { "max_attempts": 2, "backoff": "fixed", "delay": "0s" },
+            "jobs": {
+                "send_welcome": { "runtime": { "language": "javascript", "handler": handler } },
+                "flaky": { "runtime": { "language": "cobol", "handler": "" }, "max_attempts": 3 },
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn test_failing_jobs_are_retried_then_dead_lettered() {
+        let backoff = |settings: Value| {
+            let config: JobsConfig = serde_json::from_value(json!({ "store": { "type": "memory" }, "retry": settings })).unwrap();
+            let queue = Queue::new(config).unwrap();
+            (1..=4).map(|attempts| queue.backoff(attempts).as_secs()).collect::<Vec<_>>()
+        };
+        assert_eq!(backoff(json!({ "delay": "10s", "max_delay": "30s" })), vec![10, 20, 30, 30]);
+        assert_eq!(backoff(json!({ "backoff": "fixed", "delay": "5s" })), vec![5, 5, 5, 5]);
+
+        let queue = Queue::new(serde_json::from_value(config("function handler() {}")).unwrap()).unwrap();
+        let refused = queue.enqueue(EnqueueRequest { name: "unknown".to_string(), ..Default::default() }).await.unwrap_err();
+        assert!(refused.to_string().contains("unknown job 'unknown'"), "{}", refused);
+
+        let flaky = queue.enqueue(EnqueueRequest { name: "flaky".to_string(), max_attempts: Some(2), ..Default::default() }).await.unwrap();
+        let (attempt, outcome) = queue.work().await.unwrap().unwrap();
+        assert_eq!(attempt.attempts, 1);
+        assert!(matches!(outcome, Outcome::Retrying(ref error) if error.contains("Unsupported runtime language")), "{:?}", outcome);
+        let (_, outcome) = queue.work().await.unwrap().unwrap();
+        assert!(matches!(outcome, Outcome::Dead(_)), "{:?}", outcome);
+        assert!(queue.work().await.unwrap().is_none());
+
+        let dead = queue.dead().await.unwrap();
+        assert_eq!((dead.len(), dead[0].id.as_str(), dead[0].attempts), (1, flaky.id.as_str(), 2));
+        assert!(dead[0].failed_at.is_some() && dead[0].last_error.is_some());
+        let retried = queue.retry_dead(&flaky.id).await.unwrap().unwrap();
+        assert_eq!((retried.attempts, retried.failed_at), (0, None));
+        assert_eq!(queue.counts().await.unwrap(), QueueCounts { queued: 1, dead: 0 });
+        assert!(queue.discard_dead(&flaky.id).await.unwrap().is_none());
+
+        // Delayed jobs wait
+        queue.work().await.unwrap();
+        queue.work().await.unwrap();
+        queue.enqueue(EnqueueRequest { name: "flaky".to_string(), delay: Some("1h".to_string()), ..Default::default() }).await.unwrap();
+        assert!(queue.work().await.unwrap().is_none());
+        assert_eq!(queue.counts().await.unwrap(), QueueCounts { queued: 1, dead: 1 });
+    }
+
+    #[tokio::test]
+    async fn test_plugin_queues_handler_jobs_and_runs_them() {
+        let plugin = JobsPlugin::new();
+        plugin.initialize(&config("function handler(req) { return { welcomed: req.payload.id, attempt: req.job.attempt }; }")).await.unwrap();
+        let queue = plugin.queue().unwrap();
+
+        let mut response = axum::response::Response::new(Body::empty());
+        response.extensions_mut().insert(HandlerJobs(vec![
+            json!({ "name": "flaky", "payload": { "id": 7 }, "delay": "1h" }),
+            json!({ "payload": "no name" }),
+        ]));
+        plugin.after_response(&mut response).await.unwrap();
+        assert!(response.extensions().get::<HandlerJobs>().is_none());
+        for _ in 0..100 {
+            if queue.counts().await.unwrap().queued == 1 {
+                break;
+            }
+            tokio::time::sleep(StdDuration::from_millis(10)).await;
+        }
+        assert_eq!(queue.counts().await.unwrap(), QueueCounts { queued: 1, dead: 0 });
+
+        let queued = plugin.run_action("enqueue", &json!({ "name": "flaky", "delay": "1h" })).await.unwrap();
+        assert_eq!(queued["name"], "flaky");
+        assert!(plugin.run_action("purge", &json!({})).await.unwrap_err().to_string().contains("no action 'purge'"));
+
+        let routes: Vec<(String, Vec<String>)> = plugin.dashboard_routes().declared()
+            .map(|(path, methods)| (path.to_string(), methods.to_vec()))
+            .collect();
+        assert_eq!(routes, vec![
+            ("/".to_string(), vec!["GET".to_string()]),
+            ("/dead".to_string(), vec!["GET".to_string(), "DELETE".to_string()]),
+            ("/dead/{id}".to_string(), vec!["DELETE".to_string()]),
+            ("/dead/{id}/retry".to_string(), vec!["POST".to_string()]),
+        ]);
+
+        // Workers run due jobs through their handler
+        if tokio::process::Command::new("node").arg("--version").output().await.is_ok() {
+            let job = queue.enqueue(EnqueueRequest { name: "send_welcome".to_string(), payload: json!({ "id": 7 }), ..Default::default() }).await.unwrap();
+            for _ in 0..500 {
+                if queue.counts().await.unwrap().queued == 2 {
+                    break;
+                }
+                tokio::time::sleep(StdDuration::from_millis(10)).await;
+            }
+            assert_eq!(queue.counts().await.unwrap(), QueueCounts { queued: 2, dead: 0 });
+            assert!(queue.dead().await.unwrap().iter().all(|dead| dead.id != job.id));
+        }
+        plugin.shutdown().await.unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+//! Backworks plugin wiring for the job queue
+
+use crate::config::JobsConfig;
+use crate::job::EnqueueRequest;
+use crate::queue::Queue;
+use async_trait::async_trait;
+use axum::body::Body;
+use axum::response::Response;
+use backworks::error::{BackworksError, BackworksResult};
+use backworks::plugin::{BackworksPlugin, HealthStatus, PluginHealth, PluginRoutes};
+use backworks::server::HandlerJobs;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::task::JoinHandle;
+
+/// Queues the jobs handlers ask for with `ctx.jobs.enqueue()` and runs them
+/// on worker tasks
+pub struct JobsPlugin {
+    queue: RwLock<Option<Arc<Queue>>>,
+    workers: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl JobsPlugin {
+    pub fn new() -> Self {
+        Self { queue: RwLock::new(None), workers: Mutex::new(Vec::new()) }
+    }
+
+    pub fn queue(&self) -> BackworksResult<Arc<Queue>> {
+        self.queue.read().unwrap_or_else(|e| e.into_inner()).clone()
+            .ok_or_else(|| BackworksError::plugin("Jobs plugin is not initialized"))
+    }
+
+    fn stop_workers(&self) {
+        for worker in self.workers.lock().unwrap_or_else(|e| e.into_inner()).drain(..) {
+            worker.abort();
+        }
+    }
+}
+
+impl Default for JobsPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl BackworksPlugin for JobsPlugin {
+    fn name(&self) -> &str {
+        "jobs"
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &str {
+        "Background job queue with retries and a dead-letter list"
+    }
+
+    async fn initialize(&self, config: &Value) -> BackworksResult<()> {
+        let config: JobsConfig = serde_json::from_value(config.clone())
+            .map_err(|e| BackworksError::PluginConfigInvalid(format!("jobs: {}", e)))?;
+        let queue = Arc::new(Queue::new(config)?);
+        tracing::info!("🧵 Jobs plugin running {} worker(s) on the {} store", queue.config.workers.max(1), queue.config.store.kind());
+        self.stop_workers();
+        *self.workers.lock().unwrap_or_else(|e| e.into_inner()) = queue.start();
+        *self.queue.write().unwrap_or_else(|e| e.into_inner()) = Some(queue);
+        Ok(())
+    }
+
+    async fn shutdown(&self) -> BackworksResult<()> {
+        self.stop_workers();
+        *self.queue.write().unwrap_or_else(|e| e.into_inner()) = None;
+        Ok(())
+    }
+
+    async fn health_check(&self) -> BackworksResult<PluginHealth> {
+        let queue = self.queue()?;
+        Ok(match queue.counts().await {
+            Ok(counts) => PluginHealth {
+                status: if counts.dead > 0 { HealthStatus::Degraded } else { HealthStatus::Healthy },
+                message: format!("{} queued, {} dead", counts.queued, counts.dead),
+                details: HashMap::from([
+                    ("queued".to_string(), Value::from(counts.queued)),
+                    ("dead".to_string(), Value::from(counts.dead)),
+                ]),
+            },
+            Err(e) => PluginHealth {
+                status: HealthStatus::Unhealthy,
+                message: e.to_string(),
+                details: HashMap::new(),
+            },
+        })
+    }
+
+    /// Queue the jobs the handler asked for
+    async fn after_response(&self, response: &mut Response<Body>) -> BackworksResult<()> {
+        let Some(HandlerJobs(jobs)) = response.extensions_mut().remove::<HandlerJobs>() else {
+            return Ok(());
+        };
+        let queue = self.queue()?;
+        tokio::spawn(async move {
+            for job in jobs {
+                let queued = match serde_json::from_value::<EnqueueRequest>(job) {
+                    Ok(job) => queue.enqueue(job).await.map(drop),
+                    Err(e) => Err(BackworksError::config(format!("jobs: {}", e))),
+                };
+                if let Err(e) = queued {
+                    tracing::warn!("Failed to queue job asked for by a handler: {}", e);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// `enqueue` queues the job its params describe, such as on a schedule
+    async fn run_action(&self, action: &str, params: &Value) -> BackworksResult<Value> {
+        if action != "enqueue" {
+            return Err(BackworksError::plugin(format!("Plugin jobs has no action '{}'", action)));
+        }
+        let request: EnqueueRequest = serde_json::from_value(params.clone())
+            .map_err(|e| BackworksError::config(format!("jobs: {}", e)))?;
+        let job = self.queue()?.enqueue(request).await?;
+        Ok(serde_json::to_value(job).unwrap_or_default())
+    }
+
+    fn dashboard_routes(&self) -> PluginRoutes {
+        match self.queue() {
+            Ok(queue) => crate::dashboard::routes(queue),
+            Err(_) => PluginRoutes::new(),
+        }
+    }
+}
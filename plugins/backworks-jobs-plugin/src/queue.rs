@@ -0,0 +1,210 @@
+//! Queuing jobs and the workers running them
+//!
+//! A job runs its definition's runtime handler with
+//! `{ "job": { "id", "name", "attempt" }, "payload" }`. A handler that
+//! throws, returns `ctx.error()` or runs past its timeout fails the
+//! attempt; the job is retried after a backoff until it has used its
+//! attempts, then moved to the dead-letter list.
+
+use crate::config::{duration, Backoff, JobsConfig};
+use crate::job::{EnqueueRequest, Job, QueueCounts};
+use crate::store::{self, JobStore};
+use backworks::error::{BackworksError, BackworksResult};
+use backworks::runtime::{RuntimeManager, RuntimeManagerConfig};
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+/// Beyond its timeout, how long a claimed job stays hidden from other
+/// workers
+const LEASE_MARGIN: Duration = Duration::from_secs(30);
+
+pub struct Queue {
+    pub config: JobsConfig,
+    store: Box<dyn JobStore>,
+    runtime: RuntimeManager,
+    timeouts: HashMap<String, Duration>,
+    poll_interval: Duration,
+    delay: Duration,
+    max_delay: Duration,
+    wake: Notify,
+}
+
+/// What became of a job after an attempt
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Succeeded(Value),
+    /// Queued again for another attempt
+    Retrying(String),
+    /// Out of attempts, or without a definition
+    Dead(String),
+}
+
+impl Queue {
+    /// Open the store and check the durations of `config`
+    pub fn new(config: JobsConfig) -> BackworksResult<Self> {
+        let timeout = duration("timeout", &config.timeout)?;
+        let timeouts = config.jobs.iter()
+            .map(|(name, job)| {
+                let own = job.timeout.as_deref().map(|value| duration(&format!("jobs.{}.timeout", name), value)).transpose()?;
+                Ok((name.clone(), own.unwrap_or(timeout)))
+            })
+            .collect::<BackworksResult<_>>()?;
+        Ok(Self {
+            store: store::open(&config.store)?,
+            runtime: RuntimeManager::new(RuntimeManagerConfig::default()),
+            timeouts,
+            poll_interval: duration("poll_interval", &config.poll_interval)?,
+            delay: duration("retry.delay", &config.retry.delay)?,
+            max_delay: duration("retry.max_delay", &config.retry.max_delay)?,
+            wake: Notify::new(),
+            config,
+        })
+    }
+
+    /// Queue the job `request` asks for; unknown job names are refused
+    pub async fn enqueue(&self, request: EnqueueRequest) -> BackworksResult<Job> {
+        let Some(definition) = self.config.jobs.get(&request.name) else {
+            return Err(BackworksError::config(format!("jobs: unknown job '{}'", request.name)));
+        };
+        let delay = request.delay.as_deref()
+            .map(|delay| backworks::config::parse_duration(delay).map_err(|e| BackworksError::config(format!("jobs: delay: {}", e))))
+            .transpose()?
+            .unwrap_or_default();
+        let now = Utc::now();
+        let job = Job {
+            id: uuid::Uuid::new_v4().to_string(),
+            max_attempts: request.max_attempts.or(definition.max_attempts).unwrap_or(self.config.retry.max_attempts).max(1),
+            name: request.name,
+            payload: request.payload,
+            attempts: 0,
+            enqueued_at: now,
+            run_at: now + chrono::Duration::from_std(delay).unwrap_or_default(),
+            last_error: None,
+            failed_at: None,
+        };
+        self.store.push(&job).await?;
+        if delay.is_zero() {
+            self.wake.notify_one();
+        }
+        Ok(job)
+    }
+
+    /// The wait before attempt `attempts + 1`
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let delay = match self.config.retry.backoff {
+            Backoff::Fixed => self.delay,
+            Backoff::Exponential => self.delay.saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1))),
+        };
+        delay.min(self.max_delay)
+    }
+
+    fn lease(&self) -> Duration {
+        self.timeouts.values().copied().max().unwrap_or_default() + LEASE_MARGIN
+    }
+
+    /// Run one due job, if there is one
+    pub async fn work(&self) -> BackworksResult<Option<(Job, Outcome)>> {
+        let now = Utc::now();
+        let until = now + chrono::Duration::from_std(self.lease()).unwrap_or_default();
+        let Some(mut job) = self.store.claim(now, until).await? else {
+            return Ok(None);
+        };
+        let error = match self.run(&job).await {
+            Ok(output) => {
+                self.store.complete(&job.id).await?;
+                return Ok(Some((job, Outcome::Succeeded(output))));
+            }
+            Err(e) => e,
+        };
+        job.last_error = Some(error.clone());
+        if job.attempts < job.max_attempts && self.config.jobs.contains_key(&job.name) {
+            job.run_at = Utc::now() + chrono::Duration::from_std(self.backoff(job.attempts)).unwrap_or_default();
+            self.store.push(&job).await?;
+            tracing::warn!("Job {} ({}) failed attempt {} of {}, retrying at {}: {}", job.name, job.id, job.attempts, job.max_attempts, job.run_at, error);
+            return Ok(Some((job, Outcome::Retrying(error))));
+        }
+        job.failed_at = Some(Utc::now());
+        self.store.bury(&job).await?;
+        tracing::warn!("Job {} ({}) moved to the dead-letter list: {}", job.name, job.id, error);
+        Ok(Some((job, Outcome::Dead(error))))
+    }
+
+    /// Run the job's handler, returning its output
+    async fn run(&self, job: &Job) -> Result<Value, String> {
+        let (Some(definition), Some(timeout)) = (self.config.jobs.get(&job.name), self.timeouts.get(&job.name)) else {
+            return Err(format!("No job is named '{}'", job.name));
+        };
+        let input = json!({
+            "job": { "id": job.id, "name": job.name, "attempt": job.attempts },
+            "payload": job.payload,
+        });
+        let output = match tokio::time::timeout(*timeout, self.runtime.handle_request(&definition.runtime, &input.to_string())).await {
+            Ok(Ok(output)) => output,
+            Ok(Err(e)) => return Err(e.to_string()),
+            Err(_) => return Err(format!("The job took longer than {:?}", timeout)),
+        };
+        let output = serde_json::from_str(output.trim()).unwrap_or_else(|_| Value::String(output.trim().to_string()));
+        match output.get("$error") {
+            Some(error) => Err(format!("Handler returned error {}", error["code"].as_str().unwrap_or_default())),
+            None => Ok(output),
+        }
+    }
+
+    /// Start the workers; they stop when the handles are aborted
+    pub fn start(self: &Arc<Self>) -> Vec<JoinHandle<()>> {
+        (0..self.config.workers.max(1))
+            .map(|_| {
+                let queue = self.clone();
+                tokio::spawn(async move {
+                    loop {
+                        match queue.work().await {
+                            Ok(Some(_)) => continue,
+                            Ok(None) => {}
+                            Err(e) => tracing::warn!("Jobs worker failed: {}", e),
+                        }
+                        tokio::select! {
+                            _ = queue.wake.notified() => {}
+                            _ = tokio::time::sleep(queue.poll_interval) => {}
+                        }
+                    }
+                })
+            })
+            .collect()
+    }
+
+    pub async fn counts(&self) -> BackworksResult<QueueCounts> {
+        self.store.counts().await
+    }
+
+    pub async fn dead(&self) -> BackworksResult<Vec<Job>> {
+        self.store.dead().await
+    }
+
+    /// Queue a dead job again with fresh attempts, `None` when it is not in
+    /// the dead-letter list
+    pub async fn retry_dead(&self, id: &str) -> BackworksResult<Option<Job>> {
+        let Some(mut job) = self.store.take_dead(id).await? else {
+            return Ok(None);
+        };
+        job.attempts = 0;
+        job.run_at = Utc::now();
+        job.failed_at = None;
+        self.store.push(&job).await?;
+        self.wake.notify_one();
+        Ok(Some(job))
+    }
+
+    /// Drop a dead job for good
+    pub async fn discard_dead(&self, id: &str) -> BackworksResult<Option<Job>> {
+        self.store.take_dead(id).await
+    }
+
+    pub async fn clear_dead(&self) -> BackworksResult<usize> {
+        self.store.clear_dead().await
+    }
+}
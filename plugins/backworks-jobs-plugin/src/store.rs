@@ -0,0 +1,374 @@
+//! Stores keeping queued and dead jobs
+//!
+//! SQLite is built in by default and Redis needs the `redis` feature; the
+//! memory store, lost on restart, is always available. A worker claims a
+//! job by moving its `run_at` past the job's timeout, so a job whose worker
+//! died is picked up again once that time has passed.
+
+use crate::config::StoreConfig;
+use crate::job::{Job, QueueCounts};
+use async_trait::async_trait;
+use backworks::error::{BackworksError, BackworksResult};
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+#[async_trait]
+pub trait JobStore: Send + Sync {
+    /// Queue `job`, or update the queued job with its id
+    async fn push(&self, job: &Job) -> BackworksResult<()>;
+
+    /// The job due first at `now`, counted as attempted and hidden from
+    /// other claims until `until`
+    async fn claim(&self, now: DateTime<Utc>, until: DateTime<Utc>) -> BackworksResult<Option<Job>>;
+
+    /// Remove a finished job from the queue
+    async fn complete(&self, id: &str) -> BackworksResult<()>;
+
+    /// Move a job from the queue to the dead-letter list
+    async fn bury(&self, job: &Job) -> BackworksResult<()>;
+
+    /// The dead-letter list, newest first
+    async fn dead(&self) -> BackworksResult<Vec<Job>>;
+
+    /// Remove a job from the dead-letter list, returning it
+    async fn take_dead(&self, id: &str) -> BackworksResult<Option<Job>>;
+
+    /// Empty the dead-letter list, returning how many jobs it held
+    async fn clear_dead(&self) -> BackworksResult<usize>;
+
+    async fn counts(&self) -> BackworksResult<QueueCounts>;
+}
+
+/// The store `config` names
+pub fn open(config: &StoreConfig) -> BackworksResult<Box<dyn JobStore>> {
+    match config {
+        StoreConfig::Memory => Ok(Box::new(MemoryStore::default())),
+        #[cfg(feature = "sqlite")]
+        StoreConfig::Sqlite { path } => Ok(Box::new(sqlite::SqliteStore::open(path)?)),
+        #[cfg(feature = "redis")]
+        StoreConfig::Redis { url, prefix } => Ok(Box::new(redis::RedisStore::open(url, prefix)?)),
+        #[allow(unreachable_patterns)]
+        store => Err(BackworksError::PluginConfigInvalid(format!(
+            "jobs: the {} store needs the plugin's `{}` feature",
+            store.kind(), store.kind()
+        ))),
+    }
+}
+
+impl StoreConfig {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Memory => "memory",
+            Self::Sqlite { .. } => "sqlite",
+            Self::Redis { .. } => "redis",
+        }
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "redis"))]
+fn encode(job: &Job) -> BackworksResult<String> {
+    serde_json::to_string(job).map_err(|e| BackworksError::plugin(format!("jobs: cannot store job {}: {}", job.id, e)))
+}
+
+#[cfg(any(feature = "sqlite", feature = "redis"))]
+fn decode(data: &str) -> BackworksResult<Job> {
+    serde_json::from_str(data).map_err(|e| BackworksError::plugin(format!("jobs: cannot read stored job: {}", e)))
+}
+
+#[derive(Default)]
+pub struct MemoryStore {
+    queued: Mutex<HashMap<String, Job>>,
+    /// Newest first
+    dead: Mutex<VecDeque<Job>>,
+}
+
+#[async_trait]
+impl JobStore for MemoryStore {
+    async fn push(&self, job: &Job) -> BackworksResult<()> {
+        self.queued.lock().unwrap_or_else(|e| e.into_inner()).insert(job.id.clone(), job.clone());
+        Ok(())
+    }
+
+    async fn claim(&self, now: DateTime<Utc>, until: DateTime<Utc>) -> BackworksResult<Option<Job>> {
+        let mut queued = self.queued.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(job) = queued.values_mut().filter(|job| job.run_at <= now).min_by_key(|job| job.run_at) else {
+            return Ok(None);
+        };
+        job.attempts += 1;
+        job.run_at = until;
+        Ok(Some(job.clone()))
+    }
+
+    async fn complete(&self, id: &str) -> BackworksResult<()> {
+        self.queued.lock().unwrap_or_else(|e| e.into_inner()).remove(id);
+        Ok(())
+    }
+
+    async fn bury(&self, job: &Job) -> BackworksResult<()> {
+        self.queued.lock().unwrap_or_else(|e| e.into_inner()).remove(&job.id);
+        self.dead.lock().unwrap_or_else(|e| e.into_inner()).push_front(job.clone());
+        Ok(())
+    }
+
+    async fn dead(&self) -> BackworksResult<Vec<Job>> {
+        Ok(self.dead.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect())
+    }
+
+    async fn take_dead(&self, id: &str) -> BackworksResult<Option<Job>> {
+        let mut dead = self.dead.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(dead.iter().position(|job| job.id == id).and_then(|index| dead.remove(index)))
+    }
+
+    async fn clear_dead(&self) -> BackworksResult<usize> {
+        let mut dead = self.dead.lock().unwrap_or_else(|e| e.into_inner());
+        let cleared = dead.len();
+        dead.clear();
+        Ok(cleared)
+    }
+
+    async fn counts(&self) -> BackworksResult<QueueCounts> {
+        Ok(QueueCounts {
+            queued: self.queued.lock().unwrap_or_else(|e| e.into_inner()).len(),
+            dead: self.dead.lock().unwrap_or_else(|e| e.into_inner()).len(),
+        })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use super::{decode, encode, JobStore};
+    use crate::job::{Job, QueueCounts};
+    use async_trait::async_trait;
+    use backworks::error::{BackworksError, BackworksResult};
+    use chrono::{DateTime, Utc};
+    use rusqlite::{params, Connection, OptionalExtension};
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+
+    const SCHEMA: &str = "
+        CREATE TABLE IF NOT EXISTS jobs (id TEXT PRIMARY KEY, run_at INTEGER NOT NULL, data TEXT NOT NULL);
+        CREATE INDEX IF NOT EXISTS jobs_run_at ON jobs (run_at);
+        CREATE TABLE IF NOT EXISTS dead_jobs (id TEXT PRIMARY KEY, failed_at INTEGER NOT NULL, data TEXT NOT NULL);
+    ";
+
+    /// Jobs in two tables of one database file, written through one
+    /// connection off the async threads
+    pub struct SqliteStore {
+        connection: Arc<Mutex<Connection>>,
+    }
+
+    impl SqliteStore {
+        pub fn open(path: &Path) -> BackworksResult<Self> {
+            let connection = Connection::open(path)
+                .and_then(|connection| connection.execute_batch(SCHEMA).map(|_| connection))
+                .map_err(|e| BackworksError::PluginConfigInvalid(format!("jobs: cannot open {}: {}", path.display(), e)))?;
+            Ok(Self { connection: Arc::new(Mutex::new(connection)) })
+        }
+
+        async fn with<T: Send + 'static>(&self, operation: impl FnOnce(&mut Connection) -> BackworksResult<T> + Send + 'static) -> BackworksResult<T> {
+            let connection = self.connection.clone();
+            tokio::task::spawn_blocking(move || operation(&mut connection.lock().unwrap_or_else(|e| e.into_inner())))
+                .await
+                .map_err(|e| BackworksError::plugin(format!("jobs: sqlite task failed: {}", e)))?
+        }
+    }
+
+    fn failed(e: rusqlite::Error) -> BackworksError {
+        BackworksError::plugin(format!("jobs: sqlite: {}", e))
+    }
+
+    #[async_trait]
+    impl JobStore for SqliteStore {
+        async fn push(&self, job: &Job) -> BackworksResult<()> {
+            let (id, run_at, data) = (job.id.clone(), job.run_at.timestamp_millis(), encode(job)?);
+            self.with(move |connection| {
+                connection.execute("INSERT OR REPLACE INTO jobs (id, run_at, data) VALUES (?1, ?2, ?3)", params![id, run_at, data])
+                    .map(drop)
+                    .map_err(failed)
+            }).await
+        }
+
+        async fn claim(&self, now: DateTime<Utc>, until: DateTime<Utc>) -> BackworksResult<Option<Job>> {
+            self.with(move |connection| {
+                let transaction = connection.transaction().map_err(failed)?;
+                let data: Option<String> = transaction
+                    .query_row("SELECT data FROM jobs WHERE run_at <= ?1 ORDER BY run_at LIMIT 1", params![now.timestamp_millis()], |row| row.get(0))
+                    .optional()
+                    .map_err(failed)?;
+                let Some(data) = data else {
+                    return Ok(None);
+                };
+                let mut job = decode(&data)?;
+                job.attempts += 1;
+                job.run_at = until;
+                transaction.execute("UPDATE jobs SET run_at = ?2, data = ?3 WHERE id = ?1", params![job.id, until.timestamp_millis(), encode(&job)?])
+                    .map_err(failed)?;
+                transaction.commit().map_err(failed)?;
+                Ok(Some(job))
+            }).await
+        }
+
+        async fn complete(&self, id: &str) -> BackworksResult<()> {
+            let id = id.to_string();
+            self.with(move |connection| connection.execute("DELETE FROM jobs WHERE id = ?1", params![id]).map(drop).map_err(failed)).await
+        }
+
+        async fn bury(&self, job: &Job) -> BackworksResult<()> {
+            let (id, data) = (job.id.clone(), encode(job)?);
+            let failed_at = job.failed_at.unwrap_or_else(Utc::now).timestamp_millis();
+            self.with(move |connection| {
+                let transaction = connection.transaction().map_err(failed)?;
+                transaction.execute("DELETE FROM jobs WHERE id = ?1", params![id]).map_err(failed)?;
+                transaction.execute("INSERT OR REPLACE INTO dead_jobs (id, failed_at, data) VALUES (?1, ?2, ?3)", params![id, failed_at, data])
+                    .map_err(failed)?;
+                transaction.commit().map_err(failed)
+            }).await
+        }
+
+        async fn dead(&self) -> BackworksResult<Vec<Job>> {
+            self.with(|connection| {
+                let mut statement = connection.prepare("SELECT data FROM dead_jobs ORDER BY failed_at DESC").map_err(failed)?;
+                let rows = statement.query_map([], |row| row.get::<_, String>(0)).map_err(failed)?;
+                rows.map(|data| decode(&data.map_err(failed)?)).collect()
+            }).await
+        }
+
+        async fn take_dead(&self, id: &str) -> BackworksResult<Option<Job>> {
+            let id = id.to_string();
+            self.with(move |connection| {
+                let data: Option<String> = connection
+                    .query_row("DELETE FROM dead_jobs WHERE id = ?1 RETURNING data", params![id], |row| row.get(0))
+                    .optional()
+                    .map_err(failed)?;
+                data.as_deref().map(decode).transpose()
+            }).await
+        }
+
+        async fn clear_dead(&self) -> BackworksResult<usize> {
+            self.with(|connection| connection.execute("DELETE FROM dead_jobs", []).map_err(failed)).await
+        }
+
+        async fn counts(&self) -> BackworksResult<QueueCounts> {
+            self.with(|connection| {
+                let count = |table: &str| connection
+                    .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get::<_, i64>(0))
+                    .map(|count| count as usize)
+                    .map_err(failed);
+                Ok(QueueCounts { queued: count("jobs")?, dead: count("dead_jobs")? })
+            }).await
+        }
+    }
+}
+
+#[cfg(feature = "redis")]
+mod redis {
+    use super::{decode, encode, JobStore};
+    use crate::job::{Job, QueueCounts};
+    use async_trait::async_trait;
+    use backworks::error::{BackworksError, BackworksResult};
+    use chrono::{DateTime, Utc};
+    use redis::aio::ConnectionManager;
+    use redis::{Client, Cmd, FromRedisValue};
+    use tokio::sync::OnceCell;
+
+    /// Takes the id due first off the schedule by moving its score, and
+    /// returns the job
+    const CLAIM: &str = "
+        local ids = redis.call('ZRANGEBYSCORE', KEYS[1], '-inf', ARGV[1], 'LIMIT', 0, 1)
+        if #ids == 0 then return false end
+        redis.call('ZADD', KEYS[1], ARGV[2], ids[1])
+        return redis.call('HGET', KEYS[2], ids[1])
+    ";
+
+    /// Queued jobs in a hash by id plus a sorted set of ids by `run_at`;
+    /// dead jobs in a hash by id plus a sorted set by `failed_at`
+    pub struct RedisStore {
+        client: Client,
+        prefix: String,
+        connection: OnceCell<ConnectionManager>,
+    }
+
+    impl RedisStore {
+        pub fn open(url: &str, prefix: &str) -> BackworksResult<Self> {
+            let client = Client::open(url)
+                .map_err(|e| BackworksError::PluginConfigInvalid(format!("jobs: invalid redis url: {}", e)))?;
+            Ok(Self { client, prefix: prefix.to_string(), connection: OnceCell::new() })
+        }
+
+        fn key(&self, name: &str) -> String {
+            format!("{}{}", self.prefix, name)
+        }
+
+        async fn run<T: FromRedisValue>(&self, command: &Cmd) -> BackworksResult<T> {
+            let mut connection = self.connection.get_or_try_init(|| self.client.get_connection_manager()).await
+                .map_err(|e| BackworksError::plugin(format!("jobs: redis connecting: {}", e)))?
+                .clone();
+            command.query_async(&mut connection).await
+                .map_err(|e| BackworksError::plugin(format!("jobs: redis: {}", e)))
+        }
+    }
+
+    #[async_trait]
+    impl JobStore for RedisStore {
+        async fn push(&self, job: &Job) -> BackworksResult<()> {
+            self.run::<()>(redis::cmd("HSET").arg(self.key("jobs")).arg(&job.id).arg(encode(job)?)).await?;
+            self.run(redis::cmd("ZADD").arg(self.key("schedule")).arg(job.run_at.timestamp_millis()).arg(&job.id)).await
+        }
+
+        async fn claim(&self, now: DateTime<Utc>, until: DateTime<Utc>) -> BackworksResult<Option<Job>> {
+            let claimed: Option<String> = self.run(redis::cmd("EVAL")
+                .arg(CLAIM).arg(2).arg(self.key("schedule")).arg(self.key("jobs"))
+                .arg(now.timestamp_millis()).arg(until.timestamp_millis())).await?;
+            let Some(data) = claimed else {
+                return Ok(None);
+            };
+            let mut job = decode(&data)?;
+            job.attempts += 1;
+            job.run_at = until;
+            self.run::<()>(redis::cmd("HSET").arg(self.key("jobs")).arg(&job.id).arg(encode(&job)?)).await?;
+            Ok(Some(job))
+        }
+
+        async fn complete(&self, id: &str) -> BackworksResult<()> {
+            self.run::<()>(redis::cmd("ZREM").arg(self.key("schedule")).arg(id)).await?;
+            self.run(redis::cmd("HDEL").arg(self.key("jobs")).arg(id)).await
+        }
+
+        async fn bury(&self, job: &Job) -> BackworksResult<()> {
+            self.complete(&job.id).await?;
+            let failed_at = job.failed_at.unwrap_or_else(Utc::now).timestamp_millis();
+            self.run::<()>(redis::cmd("HSET").arg(self.key("dead")).arg(&job.id).arg(encode(job)?)).await?;
+            self.run(redis::cmd("ZADD").arg(self.key("dead_order")).arg(failed_at).arg(&job.id)).await
+        }
+
+        async fn dead(&self) -> BackworksResult<Vec<Job>> {
+            let ids: Vec<String> = self.run(redis::cmd("ZREVRANGE").arg(self.key("dead_order")).arg(0).arg(-1)).await?;
+            if ids.is_empty() {
+                return Ok(Vec::new());
+            }
+            let jobs: Vec<Option<String>> = self.run(redis::cmd("HMGET").arg(self.key("dead")).arg(&ids)).await?;
+            jobs.iter().flatten().map(|data| decode(data)).collect()
+        }
+
+        async fn take_dead(&self, id: &str) -> BackworksResult<Option<Job>> {
+            let data: Option<String> = self.run(redis::cmd("HGET").arg(self.key("dead")).arg(id)).await?;
+            self.run::<()>(redis::cmd("ZREM").arg(self.key("dead_order")).arg(id)).await?;
+            self.run::<()>(redis::cmd("HDEL").arg(self.key("dead")).arg(id)).await?;
+            data.as_deref().map(decode).transpose()
+        }
+
+        async fn clear_dead(&self) -> BackworksResult<usize> {
+            let cleared: usize = self.run(redis::cmd("HLEN").arg(self.key("dead"))).await?;
+            self.run::<()>(redis::cmd("DEL").arg(self.key("dead")).arg(self.key("dead_order"))).await?;
+            Ok(cleared)
+        }
+
+        async fn counts(&self) -> BackworksResult<QueueCounts> {
+            Ok(QueueCounts {
+                queued: self.run(redis::cmd("ZCARD").arg(self.key("schedule"))).await?,
+                dead: self.run(redis::cmd("ZCARD").arg(self.key("dead_order"))).await?,
+            })
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Short-lived cache of successful logins
+//!
+//! Avoids a directory round-trip on every request from the same caller.
+//! Passwords are never stored; entries keep a salted SHA-256 digest and are
+//! only reused when the presented password hashes to the same value.
+
+use crate::directory::DirectoryUser;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+struct CachedLogin {
+    digest: [u8; 32],
+    user: DirectoryUser,
+    expires_at: Instant,
+}
+
+pub struct CredentialCache {
+    ttl: Duration,
+    salt: [u8; 16],
+    entries: RwLock<HashMap<String, CachedLogin>>,
+}
+
+impl CredentialCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            salt: rand::random(),
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub async fn get(&self, username: &str, password: &str) -> Option<DirectoryUser> {
+        if self.ttl.is_zero() {
+            return None;
+        }
+        let entries = self.entries.read().await;
+        let cached = entries.get(username)?;
+        (cached.expires_at > Instant::now() && cached.digest == self.digest(username, password))
+            .then(|| cached.user.clone())
+    }
+
+    pub async fn insert(&self, password: &str, user: DirectoryUser) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let now = Instant::now();
+        let mut entries = self.entries.write().await;
+        entries.retain(|_, cached| cached.expires_at > now);
+        entries.insert(user.username.clone(), CachedLogin {
+            digest: self.digest(&user.username, password),
+            user,
+            expires_at: now + self.ttl,
+        });
+    }
+
+    /// Forget a user, e.g. after the directory rejected their password
+    pub async fn evict(&self, username: &str) {
+        self.entries.write().await.remove(username);
+    }
+
+    fn digest(&self, username: &str, password: &str) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.salt);
+        hasher.update(username.as_bytes());
+        hasher.update([0]);
+        hasher.update(password.as_bytes());
+        hasher.finalize().into()
+    }
+}
@@ -0,0 +1,111 @@
+//! Configuration for the LDAP authentication plugin
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// LDAP plugin configuration, read from the plugin's `config:` block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LdapConfig {
+    /// Directory URL, `ldap://host:389` or `ldaps://host:636`
+    pub url: String,
+
+    /// Upgrade plain `ldap://` connections with StartTLS
+    #[serde(default)]
+    pub start_tls: bool,
+
+    /// Service account used to look users up (anonymous if unset)
+    pub bind_dn: Option<String>,
+    pub bind_password: Option<String>,
+
+    /// Subtree searched for user entries
+    pub base_dn: String,
+
+    /// User search filter; `{username}` is replaced with the escaped login
+    #[serde(default = "default_user_filter")]
+    pub user_filter: String,
+
+    /// Attribute on the user entry listing group DNs
+    #[serde(default = "default_group_attribute")]
+    pub group_attribute: String,
+
+    /// Search for groups instead of reading `group_attribute`, e.g. for
+    /// servers without `memberOf` or to resolve nested AD groups
+    pub group_search: Option<GroupSearchConfig>,
+
+    #[serde(default = "default_email_attribute")]
+    pub email_attribute: String,
+
+    /// Group name -> role. Groups without a mapping become roles as-is.
+    #[serde(default)]
+    pub role_mapping: HashMap<String, String>,
+
+    /// Users must belong to at least one of these groups
+    #[serde(default)]
+    pub required_groups: Vec<String>,
+
+    /// Reject requests without Basic credentials instead of passing them on
+    /// unauthenticated
+    #[serde(default)]
+    pub required: bool,
+
+    /// Maximum pooled connections to the directory
+    #[serde(default = "default_pool_size")]
+    pub pool_size: usize,
+
+    /// Seconds a successful login is cached
+    #[serde(default = "default_cache_ttl")]
+    pub cache_ttl: u64,
+
+    /// Seconds allowed for each directory operation
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+/// Group lookup by search
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupSearchConfig {
+    pub base_dn: String,
+
+    /// `{dn}` and `{username}` are replaced with the escaped user DN and login
+    #[serde(default = "default_group_filter")]
+    pub filter: String,
+
+    #[serde(default = "default_group_name_attribute")]
+    pub name_attribute: String,
+}
+
+fn default_user_filter() -> String { "(uid={username})".to_string() }
+fn default_group_attribute() -> String { "memberOf".to_string() }
+fn default_email_attribute() -> String { "mail".to_string() }
+fn default_group_filter() -> String { "(member={dn})".to_string() }
+fn default_group_name_attribute() -> String { "cn".to_string() }
+fn default_pool_size() -> usize { 4 }
+fn default_cache_ttl() -> u64 { 300 }
+fn default_timeout() -> u64 { 5 }
+
+impl LdapConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout)
+    }
+
+    pub fn cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.cache_ttl)
+    }
+
+    /// Roles for a set of group names
+    pub fn roles_for(&self, groups: &[String]) -> Vec<String> {
+        let mut roles: Vec<String> = groups.iter()
+            .map(|group| self.role_mapping.get(group).cloned().unwrap_or_else(|| group.clone()))
+            .collect();
+        roles.sort();
+        roles.dedup();
+        roles
+    }
+
+    /// Whether the groups satisfy `required_groups` (case-insensitive)
+    pub fn allows(&self, groups: &[String]) -> bool {
+        self.required_groups.is_empty()
+            || self.required_groups.iter().any(|required| groups.iter().any(|g| g.eq_ignore_ascii_case(required)))
+    }
+}
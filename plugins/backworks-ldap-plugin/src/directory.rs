@@ -0,0 +1,181 @@
+//! Pooled access to the LDAP directory
+
+use crate::config::LdapConfig;
+use backworks::error::{BackworksError, BackworksResult};
+use ldap3::{ldap_escape, Ldap, LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+use std::sync::Arc;
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+
+/// LDAP result code for a wrong password or unknown bind DN
+const INVALID_CREDENTIALS: u32 = 49;
+
+/// A user whose credentials the directory accepted
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirectoryUser {
+    pub username: String,
+    pub dn: String,
+    pub email: Option<String>,
+    pub groups: Vec<String>,
+}
+
+/// Outcome of checking a username and password
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verification {
+    Accepted(DirectoryUser),
+    Rejected,
+}
+
+/// Connections bound as the service account, reused across requests
+pub struct ConnectionPool {
+    config: Arc<LdapConfig>,
+    idle: Mutex<Vec<Ldap>>,
+    permits: Arc<Semaphore>,
+}
+
+impl ConnectionPool {
+    pub fn new(config: Arc<LdapConfig>) -> Self {
+        let permits = Arc::new(Semaphore::new(config.pool_size.max(1)));
+        Self { config, idle: Mutex::new(Vec::new()), permits }
+    }
+
+    /// Connections checked out and the pool's capacity
+    pub fn usage(&self) -> (usize, usize) {
+        let size = self.config.pool_size.max(1);
+        (size - self.permits.available_permits(), size)
+    }
+
+    /// Check out a connection, opening one if none is idle
+    async fn acquire(&self) -> BackworksResult<(Ldap, OwnedSemaphorePermit)> {
+        let permit = self.permits.clone().acquire_owned().await
+            .map_err(|_| BackworksError::plugin("LDAP connection pool closed"))?;
+
+        while let Some(mut ldap) = self.idle.lock().await.pop() {
+            if !ldap.is_closed() {
+                return Ok((ldap, permit));
+            }
+        }
+        Ok((self.connect().await?, permit))
+    }
+
+    /// Return a connection that is still bound as the service account
+    async fn release(&self, ldap: Ldap, _permit: OwnedSemaphorePermit) {
+        self.idle.lock().await.push(ldap);
+    }
+
+    async fn connect(&self) -> BackworksResult<Ldap> {
+        let settings = LdapConnSettings::new()
+            .set_conn_timeout(self.config.timeout())
+            .set_starttls(self.config.start_tls);
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &self.config.url).await
+            .map_err(|e| BackworksError::plugin(format!("Cannot connect to {}: {}", self.config.url, e)))?;
+        ldap3::drive!(conn);
+
+        self.bind_service(&mut ldap).await?;
+        Ok(ldap)
+    }
+
+    async fn bind_service(&self, ldap: &mut Ldap) -> BackworksResult<()> {
+        let (dn, password) = match (&self.config.bind_dn, &self.config.bind_password) {
+            (Some(dn), Some(password)) => (dn.as_str(), password.as_str()),
+            _ => ("", ""),
+        };
+        ldap.with_timeout(self.config.timeout())
+            .simple_bind(dn, password).await
+            .and_then(|result| result.success())
+            .map_err(|e| BackworksError::plugin(format!("LDAP service bind failed: {}", e)))?;
+        Ok(())
+    }
+
+    /// Open and bind a connection, as a health probe
+    pub async fn ping(&self) -> BackworksResult<()> {
+        let (ldap, permit) = self.acquire().await?;
+        self.release(ldap, permit).await;
+        Ok(())
+    }
+
+    /// Look `username` up and bind as them with `password`
+    pub async fn verify(&self, username: &str, password: &str) -> BackworksResult<Verification> {
+        // An empty password is an unauthenticated bind, which most servers
+        // accept for any DN
+        if username.is_empty() || password.is_empty() {
+            return Ok(Verification::Rejected);
+        }
+
+        let (mut ldap, permit) = self.acquire().await?;
+        // Any error leaves the connection in an unknown bind state, so it is
+        // dropped instead of returned to the pool
+        let outcome = self.verify_on(&mut ldap, username, password).await?;
+        self.release(ldap, permit).await;
+        Ok(outcome)
+    }
+
+    async fn verify_on(&self, ldap: &mut Ldap, username: &str, password: &str) -> BackworksResult<Verification> {
+        let config = &self.config;
+        let timeout = config.timeout();
+
+        let filter = config.user_filter.replace("{username}", &ldap_escape(username));
+        let attrs = vec![config.email_attribute.as_str(), config.group_attribute.as_str()];
+        let (entries, _) = ldap.with_timeout(timeout)
+            .search(&config.base_dn, Scope::Subtree, &filter, attrs).await
+            .and_then(|result| result.success())
+            .map_err(|e| BackworksError::plugin(format!("LDAP user search failed: {}", e)))?;
+
+        // Unknown and ambiguous logins are both refused
+        let mut entries = entries.into_iter();
+        let (Some(entry), None) = (entries.next(), entries.next()) else {
+            return Ok(Verification::Rejected);
+        };
+        let entry = SearchEntry::construct(entry);
+
+        let bind = ldap.with_timeout(timeout).simple_bind(&entry.dn, password).await
+            .map_err(|e| BackworksError::plugin(format!("LDAP bind failed: {}", e)))?;
+        if bind.rc == INVALID_CREDENTIALS {
+            self.bind_service(ldap).await?;
+            return Ok(Verification::Rejected);
+        }
+        bind.success().map_err(|e| BackworksError::plugin(format!("LDAP bind failed: {}", e)))?;
+
+        // Group searches run with the service account's permissions
+        self.bind_service(ldap).await?;
+        let groups = match &config.group_search {
+            Some(search) => {
+                let filter = search.filter
+                    .replace("{dn}", &ldap_escape(entry.dn.as_str()))
+                    .replace("{username}", &ldap_escape(username));
+                let (groups, _) = ldap.with_timeout(timeout)
+                    .search(&search.base_dn, Scope::Subtree, &filter, vec![search.name_attribute.as_str()]).await
+                    .and_then(|result| result.success())
+                    .map_err(|e| BackworksError::plugin(format!("LDAP group search failed: {}", e)))?;
+                groups.into_iter()
+                    .map(SearchEntry::construct)
+                    .filter_map(|group| {
+                        group.attrs.get(&search.name_attribute)
+                            .and_then(|names| names.first().cloned())
+                            .or_else(|| group_name(&group.dn))
+                    })
+                    .collect()
+            }
+            None => entry.attrs.get(&config.group_attribute)
+                .into_iter()
+                .flatten()
+                .filter_map(|dn| group_name(dn))
+                .collect(),
+        };
+
+        Ok(Verification::Accepted(DirectoryUser {
+            username: username.to_string(),
+            email: entry.attrs.get(&config.email_attribute).and_then(|values| values.first().cloned()),
+            dn: entry.dn,
+            groups,
+        }))
+    }
+}
+
+/// Group name from a group DN: the value of its first RDN
+/// (`CN=Admins,OU=Groups,DC=corp` -> `Admins`)
+pub fn group_name(dn: &str) -> Option<String> {
+    let rdn = dn.split(',').next()?.trim();
+    let (_, value) = rdn.split_once('=')?;
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
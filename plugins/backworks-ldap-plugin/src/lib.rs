@@ -0,0 +1,119 @@
+//! # Backworks LDAP Plugin
+//!
+//! Authenticates requests against LDAP or Active Directory.
+//!
+//! The plugin reads HTTP Basic credentials, looks the user up with a pooled
+//! service-account connection, verifies the password by binding as the user
+//! and attaches an `AuthContext` whose roles are the user's groups (optionally
+//! renamed through `role_mapping`). Successful logins are cached for
+//! `cache_ttl` seconds.
+//!
+//! ```yaml
+//! plugins:
+//!   ldap:
+//!     enabled: true
+//!     config:
+//!       url: "ldaps://dc1.corp.example.com"
+//!       bind_dn: "CN=svc-backworks,OU=Service,DC=corp,DC=example,DC=com"
+//!       bind_password: "${LDAP_BIND_PASSWORD}"
+//!       base_dn: "DC=corp,DC=example,DC=com"
+//!       user_filter: "(sAMAccountName={username})"
+//!       role_mapping:
+//!         "API Admins": admin
+//!       required_groups: ["API Users", "API Admins"]
+//!       required: true
+//! ```
+
+pub mod cache;
+pub mod config;
+pub mod directory;
+pub mod plugin;
+
+pub use config::{GroupSearchConfig, LdapConfig};
+pub use directory::{DirectoryUser, Verification};
+pub use plugin::LdapAuthPlugin;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CredentialCache;
+    use crate::directory::group_name;
+    use crate::plugin::basic_credentials;
+    use axum::http::HeaderMap;
+    use backworks::BackworksPlugin;
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn config() -> LdapConfig {
+        serde_json::from_value(json!({
+            "url": "ldap://localhost:389",
+            "base_dn": "dc=example,dc=com",
+            "role_mapping": { "API Admins": "admin" },
+            "required_groups": ["api users", "API Admins"],
+        })).unwrap()
+    }
+
+    fn user(groups: &[&str]) -> DirectoryUser {
+        DirectoryUser {
+            username: "ada".to_string(),
+            dn: "uid=ada,dc=example,dc=com".to_string(),
+            email: None,
+            groups: groups.iter().map(|g| g.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_groups_become_roles() {
+        let config = config();
+        assert_eq!(config.user_filter, "(uid={username})");
+        assert_eq!(config.pool_size, 4);
+
+        let groups = vec!["API Admins".to_string(), "API Users".to_string()];
+        assert_eq!(config.roles_for(&groups), vec!["API Users", "admin"]);
+        assert!(config.allows(&groups));
+        assert!(!config.allows(&["Staff".to_string()]));
+    }
+
+    #[test]
+    fn test_group_name_from_dn() {
+        assert_eq!(group_name("CN=API Admins,OU=Groups,DC=corp"), Some("API Admins".to_string()));
+        assert_eq!(group_name("cn=devs, ou=groups"), Some("devs".to_string()));
+        assert_eq!(group_name("not a dn"), None);
+    }
+
+    #[test]
+    fn test_basic_credentials() {
+        let mut headers = HeaderMap::new();
+        // ada:s3cr:et
+        headers.insert("authorization", "Basic YWRhOnMzY3I6ZXQ=".parse().unwrap());
+        assert_eq!(basic_credentials(&headers), Some(("ada".to_string(), "s3cr:et".to_string())));
+
+        headers.insert("authorization", "Bearer token".parse().unwrap());
+        assert_eq!(basic_credentials(&headers), None);
+    }
+
+    #[tokio::test]
+    async fn test_cache_requires_matching_password() {
+        let cache = CredentialCache::new(Duration::from_secs(60));
+        cache.insert("right", user(&["API Users"])).await;
+
+        assert_eq!(cache.get("ada", "right").await, Some(user(&["API Users"])));
+        assert_eq!(cache.get("ada", "wrong").await, None);
+
+        cache.evict("ada").await;
+        assert_eq!(cache.get("ada", "right").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_required_plugin_rejects_missing_credentials() {
+        let plugin = LdapAuthPlugin::new();
+        let mut config = serde_json::to_value(config()).unwrap();
+        config["required"] = json!(true);
+        plugin.initialize(&config).await.unwrap();
+        assert!(plugin.is_critical());
+
+        let mut request = axum::extract::Request::new(axum::body::Body::empty());
+        let error = plugin.before_request(&mut request).await.unwrap_err();
+        assert!(error.is_rejection());
+    }
+}
@@ -0,0 +1,176 @@
+//! Backworks plugin wiring for LDAP authentication
+
+use crate::cache::CredentialCache;
+use crate::config::LdapConfig;
+use crate::directory::{ConnectionPool, DirectoryUser, Verification};
+use async_trait::async_trait;
+use axum::http::{header, HeaderMap};
+use backworks::auth::{AuthContext, AuthMethod};
+use backworks::error::{BackworksError, BackworksResult};
+use backworks::plugin::{BackworksPlugin, HealthStatus, PluginHealth};
+use base64::Engine;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+struct Authenticator {
+    config: Arc<LdapConfig>,
+    pool: ConnectionPool,
+    cache: CredentialCache,
+}
+
+/// Authenticates HTTP Basic credentials against LDAP / Active Directory and
+/// attaches the user, with their groups as roles, as the request's
+/// `AuthContext`
+pub struct LdapAuthPlugin {
+    authenticator: RwLock<Option<Arc<Authenticator>>>,
+    required: AtomicBool,
+}
+
+impl LdapAuthPlugin {
+    pub fn new() -> Self {
+        Self {
+            authenticator: RwLock::new(None),
+            required: AtomicBool::new(false),
+        }
+    }
+
+    async fn authenticator(&self) -> BackworksResult<Arc<Authenticator>> {
+        self.authenticator.read().await.clone()
+            .ok_or_else(|| BackworksError::plugin("LDAP plugin is not initialized"))
+    }
+}
+
+impl Default for LdapAuthPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl BackworksPlugin for LdapAuthPlugin {
+    fn name(&self) -> &str {
+        "ldap"
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &str {
+        "LDAP / Active Directory authentication"
+    }
+
+    async fn initialize(&self, config: &Value) -> BackworksResult<()> {
+        let config: LdapConfig = serde_json::from_value(config.clone())
+            .map_err(|e| BackworksError::PluginConfigInvalid(format!("ldap: {}", e)))?;
+        let config = Arc::new(config);
+
+        self.required.store(config.required, Ordering::Relaxed);
+        *self.authenticator.write().await = Some(Arc::new(Authenticator {
+            pool: ConnectionPool::new(config.clone()),
+            cache: CredentialCache::new(config.cache_ttl()),
+            config,
+        }));
+        Ok(())
+    }
+
+    async fn shutdown(&self) -> BackworksResult<()> {
+        *self.authenticator.write().await = None;
+        Ok(())
+    }
+
+    async fn health_check(&self) -> BackworksResult<PluginHealth> {
+        let authenticator = self.authenticator().await?;
+        let (status, message) = match authenticator.pool.ping().await {
+            Ok(()) => (HealthStatus::Healthy, "Directory reachable".to_string()),
+            Err(e) => (HealthStatus::Unhealthy, e.to_string()),
+        };
+        // Pool usage feeds the readiness saturation check
+        let (in_use, size) = authenticator.pool.usage();
+        let details = HashMap::from([
+            ("pool_in_use".to_string(), Value::from(in_use)),
+            ("pool_size".to_string(), Value::from(size)),
+        ]);
+        Ok(PluginHealth { status, message, details })
+    }
+
+    /// Directory lookups are network round-trips; the per-operation timeout
+    /// is enforced by the plugin itself
+    fn max_execution_time(&self) -> Duration {
+        Duration::from_secs(30)
+    }
+
+    /// When credentials are required, an unreachable directory must not let
+    /// requests through
+    fn is_critical(&self) -> bool {
+        self.required.load(Ordering::Relaxed)
+    }
+
+    async fn before_request(&self, request: &mut axum::extract::Request) -> BackworksResult<()> {
+        let authenticator = self.authenticator().await?;
+        let config = &authenticator.config;
+
+        let Some((username, password)) = basic_credentials(request.headers()) else {
+            if config.required {
+                return Err(BackworksError::unauthorized("Basic credentials required"));
+            }
+            return Ok(());
+        };
+
+        let user = match authenticator.cache.get(&username, &password).await {
+            Some(user) => user,
+            None => match authenticator.pool.verify(&username, &password).await? {
+                Verification::Accepted(user) => {
+                    authenticator.cache.insert(&password, user.clone()).await;
+                    user
+                }
+                Verification::Rejected => {
+                    authenticator.cache.evict(&username).await;
+                    return Err(BackworksError::unauthorized("Invalid username or password"));
+                }
+            },
+        };
+
+        if !config.allows(&user.groups) {
+            return Err(BackworksError::forbidden(format!(
+                "User '{}' is not a member of a required group",
+                user.username
+            )));
+        }
+
+        request.extensions_mut().insert(auth_context(config, user));
+        Ok(())
+    }
+}
+
+fn auth_context(config: &LdapConfig, user: DirectoryUser) -> AuthContext {
+    let mut claims = HashMap::new();
+    claims.insert("dn".to_string(), Value::String(user.dn));
+    claims.insert("groups".to_string(), serde_json::json!(user.groups));
+    claims.insert("provider".to_string(), Value::String("ldap".to_string()));
+
+    AuthContext {
+        roles: config.roles_for(&user.groups),
+        subject: user.username,
+        method: AuthMethod::Basic,
+        email: user.email,
+        claims,
+    }
+}
+
+/// Username and password from an `Authorization: Basic` header
+pub fn basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, encoded) = value.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = base64::engine::general_purpose::STANDARD.decode(encoded.trim()).ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
@@ -0,0 +1,66 @@
+//! Routes bridging topics to server-sent events
+//!
+//! Each `bridges` entry serves its path on the API server as a stream of
+//! server-sent events, one per message published to the topic from the time
+//! the client connects, named after the topic it came from.
+
+use crate::broker::{Broker, Message};
+use crate::config::{MessagingConfig, TopicBridge};
+use axum::extract::Path;
+use axum::http::Method;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use backworks::error::BackworksError;
+use backworks::plugin::PluginRoutes;
+use backworks::routes::fill;
+use futures::StreamExt;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+type PathParams = Option<Path<HashMap<String, String>>>;
+
+/// A route for every bridge in the configuration
+pub fn routes(config: Arc<MessagingConfig>, broker: Arc<dyn Broker>) -> PluginRoutes {
+    let mut routes = PluginRoutes::new();
+    for bridge in &config.bridges {
+        let bridge = Arc::new(bridge.clone());
+        let config = config.clone();
+        let broker = broker.clone();
+        routes = routes.route(Method::GET, &bridge.path.clone(), move |params: PathParams| async move {
+            match topic(&config, &bridge, params) {
+                Ok(topic) => stream(broker, topic, bridge.group.as_deref()).await,
+                Err(e) => e.into_response(),
+            }
+        });
+    }
+    routes
+}
+
+/// The topic `bridge` names for a request with `params`
+fn topic(config: &MessagingConfig, bridge: &TopicBridge, params: PathParams) -> Result<String, BackworksError> {
+    let params: HashMap<String, Value> = params.map(|Path(params)| params).unwrap_or_default()
+        .into_iter()
+        .map(|(name, value)| (name, Value::String(value)))
+        .collect();
+    fill(&bridge.topic, &params)
+        .map(|topic| config.topic(&topic))
+        .map_err(|e| BackworksError::config(format!("messaging bridge: {}", e)))
+}
+
+async fn stream(broker: Arc<dyn Broker>, topic: String, group: Option<&str>) -> Response {
+    match broker.subscribe(&topic, group).await {
+        Ok(messages) => Sse::new(messages.map(|message| Ok::<_, Infallible>(event(&message))))
+            .keep_alive(KeepAlive::default())
+            .into_response(),
+        Err(e) => BackworksError::unavailable(e.to_string()).into_response(),
+    }
+}
+
+/// A message as a server-sent event named after its topic
+pub fn event(message: &Message) -> Event {
+    Event::default()
+        .event(message.topic.replace(['\r', '\n'], ""))
+        .data(String::from_utf8_lossy(&message.payload).replace('\r', ""))
+}
@@ -0,0 +1,211 @@
+//! Brokers the plugin publishes to and subscribes to
+//!
+//! NATS is built in by default; Kafka needs the `kafka` feature, which
+//! builds librdkafka. Both connect on first use, so a server starts while
+//! its broker is down and publishes once it is back.
+
+use crate::config::{BrokerConfig, MessagingConfig};
+use async_trait::async_trait;
+use backworks::error::{BackworksError, BackworksResult};
+use futures::stream::BoxStream;
+
+/// A message read from a topic
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub topic: String,
+    pub payload: Vec<u8>,
+}
+
+#[async_trait]
+pub trait Broker: Send + Sync {
+    /// Send `payload` to `topic`, with `key` for partitioning (Kafka) or
+    /// deduplication (the `Nats-Msg-Id` header of NATS)
+    async fn publish(&self, topic: &str, key: Option<&str>, payload: Vec<u8>) -> BackworksResult<()>;
+
+    /// Messages published to `topic` from now on, shared among the
+    /// subscribers of `group` when given
+    async fn subscribe(&self, topic: &str, group: Option<&str>) -> BackworksResult<BoxStream<'static, Message>>;
+
+    /// Whether the broker answers
+    async fn ping(&self) -> BackworksResult<()>;
+}
+
+/// The broker `config` names
+pub fn open(config: &MessagingConfig) -> BackworksResult<Box<dyn Broker>> {
+    match config.broker {
+        #[cfg(feature = "nats")]
+        BrokerConfig::Nats { ref url } => Ok(Box::new(nats::Nats::new(url, config.timeout()))),
+        #[cfg(feature = "kafka")]
+        BrokerConfig::Kafka { ref brokers, ref properties } => Ok(Box::new(kafka::Kafka::new(brokers, properties, config.timeout())?)),
+        #[allow(unreachable_patterns)]
+        ref broker => Err(BackworksError::PluginConfigInvalid(format!(
+            "messaging: the {} broker needs the plugin's `{}` feature",
+            broker.kind(), broker.kind()
+        ))),
+    }
+}
+
+impl BrokerConfig {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Nats { .. } => "nats",
+            Self::Kafka { .. } => "kafka",
+        }
+    }
+}
+
+#[cfg(any(feature = "nats", feature = "kafka"))]
+async fn within<T, E: std::fmt::Display>(timeout: std::time::Duration, doing: &str, operation: impl std::future::Future<Output = Result<T, E>>) -> BackworksResult<T> {
+    match tokio::time::timeout(timeout, operation).await {
+        Ok(result) => result.map_err(|e| BackworksError::plugin(format!("messaging {}: {}", doing, e))),
+        Err(_) => Err(BackworksError::timeout(format!("messaging {} took longer than {:?}", doing, timeout))),
+    }
+}
+
+#[cfg(feature = "nats")]
+mod nats {
+    use super::{within, Broker, Message};
+    use async_trait::async_trait;
+    use backworks::error::BackworksResult;
+    use futures::stream::BoxStream;
+    use futures::StreamExt;
+    use std::time::Duration;
+    use tokio::sync::OnceCell;
+
+    pub struct Nats {
+        url: String,
+        timeout: Duration,
+        client: OnceCell<async_nats::Client>,
+    }
+
+    impl Nats {
+        pub fn new(url: &str, timeout: Duration) -> Self {
+            Self { url: url.to_string(), timeout, client: OnceCell::new() }
+        }
+
+        /// The client reconnects by itself once connected
+        async fn client(&self) -> BackworksResult<&async_nats::Client> {
+            self.client.get_or_try_init(|| {
+                let options = async_nats::ConnectOptions::new().connection_timeout(self.timeout);
+                within(self.timeout, "connecting", options.connect(self.url.as_str()))
+            }).await
+        }
+    }
+
+    #[async_trait]
+    impl Broker for Nats {
+        async fn publish(&self, topic: &str, key: Option<&str>, payload: Vec<u8>) -> BackworksResult<()> {
+            let client = self.client().await?;
+            let subject = topic.to_string();
+            match key {
+                Some(key) => {
+                    let mut headers = async_nats::HeaderMap::new();
+                    headers.insert("Nats-Msg-Id", key);
+                    within(self.timeout, "publishing", client.publish_with_headers(subject, headers, payload.into())).await
+                }
+                None => within(self.timeout, "publishing", client.publish(subject, payload.into())).await,
+            }
+        }
+
+        async fn subscribe(&self, topic: &str, group: Option<&str>) -> BackworksResult<BoxStream<'static, Message>> {
+            let client = self.client().await?;
+            let subscriber = match group {
+                Some(group) => within(self.timeout, "subscribing", client.queue_subscribe(topic.to_string(), group.to_string())).await?,
+                None => within(self.timeout, "subscribing", client.subscribe(topic.to_string())).await?,
+            };
+            Ok(subscriber
+                .map(|message| Message { topic: message.subject.to_string(), payload: message.payload.to_vec() })
+                .boxed())
+        }
+
+        async fn ping(&self) -> BackworksResult<()> {
+            within(self.timeout, "pinging", self.client().await?.flush()).await
+        }
+    }
+}
+
+#[cfg(feature = "kafka")]
+mod kafka {
+    use super::{within, Broker, Message};
+    use async_trait::async_trait;
+    use backworks::error::{BackworksError, BackworksResult};
+    use futures::stream::BoxStream;
+    use futures::StreamExt;
+    use rdkafka::consumer::{Consumer, StreamConsumer};
+    use rdkafka::producer::{FutureProducer, FutureRecord, Producer};
+    use rdkafka::{ClientConfig, Message as _};
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    pub struct Kafka {
+        client: ClientConfig,
+        producer: FutureProducer,
+        timeout: Duration,
+    }
+
+    impl Kafka {
+        pub fn new(brokers: &str, properties: &HashMap<String, String>, timeout: Duration) -> BackworksResult<Self> {
+            let mut client = ClientConfig::new();
+            client.set("bootstrap.servers", brokers);
+            for (name, value) in properties {
+                client.set(name, value);
+            }
+            let producer = client.create()
+                .map_err(|e| BackworksError::PluginConfigInvalid(format!("messaging: kafka producer: {}", e)))?;
+            Ok(Self { client, producer, timeout })
+        }
+    }
+
+    #[async_trait]
+    impl Broker for Kafka {
+        async fn publish(&self, topic: &str, key: Option<&str>, payload: Vec<u8>) -> BackworksResult<()> {
+            let mut record = FutureRecord::<str, [u8]>::to(topic).payload(&payload);
+            if let Some(key) = key {
+                record = record.key(key);
+            }
+            within(self.timeout, "publishing", async {
+                self.producer.send(record, self.timeout).await.map(drop).map_err(|(e, _)| e)
+            }).await
+        }
+
+        /// Without a group, each subscriber joins a group of its own so it
+        /// sees every message
+        async fn subscribe(&self, topic: &str, group: Option<&str>) -> BackworksResult<BoxStream<'static, Message>> {
+            let group = group.map(str::to_string).unwrap_or_else(|| format!("backworks-{}", uuid::Uuid::new_v4()));
+            let consumer: StreamConsumer = self.client.clone()
+                .set("group.id", &group)
+                .set("auto.offset.reset", "latest")
+                .create()
+                .map_err(|e| BackworksError::plugin(format!("messaging subscribing: {}", e)))?;
+            consumer.subscribe(&[topic])
+                .map_err(|e| BackworksError::plugin(format!("messaging subscribing: {}", e)))?;
+            Ok(futures::stream::unfold(consumer, |consumer| async move {
+                loop {
+                    let message = match consumer.recv().await {
+                        Ok(message) => Message {
+                            topic: message.topic().to_string(),
+                            payload: message.payload().unwrap_or_default().to_vec(),
+                        },
+                        Err(e) => {
+                            tracing::warn!("Kafka consumer failed to read: {}", e);
+                            tokio::time::sleep(Duration::from_secs(1)).await;
+                            continue;
+                        }
+                    };
+                    return Some((message, consumer));
+                }
+            }).boxed())
+        }
+
+        async fn ping(&self) -> BackworksResult<()> {
+            let producer = self.producer.clone();
+            let timeout = self.timeout;
+            within(timeout, "pinging", async move {
+                tokio::task::spawn_blocking(move || producer.client().fetch_metadata(None, timeout).map(drop))
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .map_err(|e| e.to_string())
+            }).await
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! Configuration for the messaging plugin
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Messaging plugin configuration, read from the plugin's `config:` block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessagingConfig {
+    /// The broker events go to and bridges read from
+    #[serde(default)]
+    pub broker: BrokerConfig,
+
+    /// Prepended to every topic the plugin publishes to or subscribes to
+    #[serde(default)]
+    pub topic_prefix: String,
+
+    /// Seconds allowed for connecting and for each publish
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+
+    /// Routes streaming the messages of a topic as server-sent events
+    #[serde(default)]
+    pub bridges: Vec<TopicBridge>,
+}
+
+/// Which broker to use, and how to reach it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum BrokerConfig {
+    Nats {
+        /// Server URL, `nats://host:4222`, or several separated by commas
+        #[serde(default = "default_nats_url")]
+        url: String,
+    },
+    Kafka {
+        /// Bootstrap servers, `host:9092,host:9093`
+        brokers: String,
+
+        /// librdkafka client properties, such as `security.protocol`
+        #[serde(default)]
+        properties: HashMap<String, String>,
+    },
+}
+
+impl Default for BrokerConfig {
+    fn default() -> Self {
+        Self::Nats { url: default_nats_url() }
+    }
+}
+
+/// A route bridging a topic to server-sent events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopicBridge {
+    /// Route path on the API server, with parameters like endpoint paths
+    pub path: String,
+
+    /// Topic with `{param}` placeholders for path parameters; NATS wildcards
+    /// (`*`, `>`) subscribe to every matching subject
+    pub topic: String,
+
+    /// Consumer group (Kafka) or queue group (NATS) clients share, each
+    /// message going to one of them; every client gets every message when
+    /// unset
+    pub group: Option<String>,
+}
+
+fn default_nats_url() -> String { "nats://127.0.0.1:4222".to_string() }
+fn default_timeout() -> u64 { 5 }
+
+impl MessagingConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout)
+    }
+
+    /// The full name of `topic`
+    pub fn topic(&self, topic: &str) -> String {
+        format!("{}{}", self.topic_prefix, topic)
+    }
+}
@@ -0,0 +1,198 @@
+//! # Backworks Messaging Plugin
+//!
+//! Feeds an event pipeline from a Backworks API, over NATS (built in) or
+//! Kafka (the `kafka` feature):
+//!
+//! - request events: endpoints listing the `publish` middleware with a
+//!   `topic` publish one event per request, carrying the request and the
+//!   response
+//! - handler events: the `events` a handler returns next to its `status` and
+//!   `body` are published by the same middleware, with or without a `topic`
+//! - topic streams: routes under `bridges` relay the messages of a topic to
+//!   HTTP clients as server-sent events
+//!
+//! ```yaml
+//! plugins:
+//!   messaging:
+//!     enabled: true
+//!     config:
+//!       broker: { type: nats, url: "nats://localhost:4222" }
+//!       # broker: { type: kafka, brokers: "localhost:9092" }
+//!       topic_prefix: "shop."
+//!       bridges:
+//!         - { path: "/events/orders/{id}", topic: "orders.{id}" }
+//!
+//! endpoints:
+//!   order:
+//!     path: "/orders/{id}"
+//!     methods: [GET, PUT]
+//!     middleware:
+//!       - publish: { topic: "orders.{id}", key: "{id}", on: success }
+//! ```
+//!
+//! A request event looks like:
+//!
+//! ```json
+//! {
+//!   "id": "6f1c…", "endpoint": "order", "method": "PUT", "path": "/orders/42",
+//!   "status": 200, "timestamp": "2024-05-01T12:00:00+00:00",
+//!   "request": { "params": { "id": "42" }, "query": {}, "body": { "total": 9.5 } },
+//!   "response": { "body": { "id": 42, "total": 9.5 } }
+//! }
+//! ```
+
+pub mod bridge;
+pub mod broker;
+pub mod config;
+pub mod plugin;
+pub mod publish;
+
+pub use broker::{Broker, Message};
+pub use config::{BrokerConfig, MessagingConfig, TopicBridge};
+pub use plugin::MessagingPlugin;
+pub use publish::{Publish, PublishOn, PublishSettings};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::publish::{RequestSummary, ResponseSummary};
+    use async_trait::async_trait;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::routing::put;
+    use axum::Router;
+    use backworks::config::MiddlewareSpec;
+    use backworks::error::BackworksResult;
+    use backworks::pipeline::{run_pipeline, EndpointMiddleware, MiddlewareRegistry};
+    use backworks::server::{HandlerEvent, HandlerEvents};
+    use backworks::BackworksPlugin;
+    use futures::stream::BoxStream;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tower::ServiceExt;
+
+    /// Keeps what is published
+    #[derive(Default)]
+    struct Recorded(Mutex<Vec<(String, Option<String>, Value)>>);
+
+    #[async_trait]
+    impl Broker for Recorded {
+        async fn publish(&self, topic: &str, key: Option<&str>, payload: Vec<u8>) -> BackworksResult<()> {
+            let payload = serde_json::from_slice(&payload).unwrap();
+            self.0.lock().unwrap().push((topic.to_string(), key.map(str::to_string), payload));
+            Ok(())
+        }
+
+        async fn subscribe(&self, topic: &str, _group: Option<&str>) -> BackworksResult<BoxStream<'static, Message>> {
+            let message = Message { topic: topic.to_string(), payload: b"{}".to_vec() };
+            Ok(Box::pin(futures::stream::iter([message])))
+        }
+
+        async fn ping(&self) -> BackworksResult<()> {
+            Ok(())
+        }
+    }
+
+    fn config(value: Value) -> Arc<MessagingConfig> {
+        Arc::new(serde_json::from_value(value).unwrap())
+    }
+
+    #[test]
+    fn test_request_events_follow_the_endpoint_settings() {
+        let messaging = config(json!({ "topic_prefix": "shop." }));
+        assert!(matches!(messaging.broker, BrokerConfig::Nats { ref url } if url == "nats://127.0.0.1:4222"));
+        let broker: Arc<dyn Broker> = Arc::new(Recorded::default());
+        let publish = Publish::new(messaging.clone(), broker.clone(), &json!({ "topic": "{endpoint}.{id}.{method}", "key": "{id}" })).unwrap();
+
+        let params = HashMap::from([("id".to_string(), json!("42"))]);
+        let request = RequestSummary { method: "PUT".to_string(), path: "/orders/42".to_string(), body: json!({"total": 9.5}), ..Default::default() };
+        let event = publish.request_event("order", &params, &request, &ResponseSummary { status: 200, body: json!({"id": 42}) }).unwrap().unwrap();
+        assert_eq!((event.topic.as_str(), event.key.as_deref()), ("shop.order.42.PUT", Some("42")));
+        let payload: Value = serde_json::from_slice(&event.payload).unwrap();
+        assert_eq!(payload["request"], json!({ "params": {"id": "42"}, "query": {}, "body": {"total": 9.5} }));
+        assert_eq!((&payload["status"], &payload["response"]["body"]), (&json!(200), &json!({"id": 42})));
+
+        // Failed requests only with `on: always`
+        let failed = ResponseSummary { status: 500, body: Value::Null };
+        assert!(publish.request_event("order", &params, &request, &failed).unwrap().is_none());
+        let always = Publish::new(messaging.clone(), broker.clone(), &json!({ "topic": "errors", "on": "always" })).unwrap();
+        assert!(always.request_event("order", &params, &request, &failed).unwrap().is_some());
+
+        let unknown = Publish::new(messaging.clone(), broker.clone(), &json!({ "topic": "orders.{user}" })).unwrap();
+        assert!(unknown.request_event("order", &params, &request, &ResponseSummary { status: 200, body: Value::Null }).unwrap_err().contains("not a path parameter"));
+        assert!(Publish::new(messaging, broker, &json!({ "topics": "orders" })).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_publish_middleware_sends_request_and_handler_events() {
+        let recorded = Arc::new(Recorded::default());
+        let broker: Arc<dyn Broker> = recorded.clone();
+        let messaging = config(json!({}));
+        let mut registry = MiddlewareRegistry::default();
+        registry.register("publish", move |settings| Ok(Arc::new(Publish::new(messaging.clone(), broker.clone(), settings)?) as Arc<dyn EndpointMiddleware>));
+        let spec = MiddlewareSpec { name: "publish".to_string(), config: json!({ "topic": "orders.{id}" }) };
+        let pipeline = registry.build("order", &[spec]).unwrap().unwrap();
+        let app = Router::new()
+            .route("/orders/:id", put(|body: String| async move {
+                let mut response = axum::response::Response::new(Body::from(body));
+                response.extensions_mut().insert(HandlerEvents(vec![
+                    HandlerEvent { topic: "orders.changed".to_string(), key: Some("42".to_string()), data: json!({"id": 42}) },
+                ]));
+                response
+            }))
+            .route_layer(axum::middleware::from_fn_with_state(pipeline, run_pipeline));
+
+        let request = Request::put("/orders/42?notify=yes").body(Body::from(r#"{"total":9.5}"#)).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], br#"{"total":9.5}"#);
+
+        // Publishing happens in the background
+        for _ in 0..100 {
+            if recorded.0.lock().unwrap().len() == 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let published = recorded.0.lock().unwrap().clone();
+        assert_eq!(published[1], ("orders.changed".to_string(), Some("42".to_string()), json!({"id": 42})));
+        let (ref topic, ref key, ref event) = published[0];
+        assert_eq!((topic.as_str(), key), ("orders.42", &None));
+        assert_eq!(event["endpoint"], "order");
+        assert_eq!(event["request"]["query"], json!({"notify": "yes"}));
+        assert_eq!(event["response"]["body"], json!({"total": 9.5}));
+    }
+
+    #[tokio::test]
+    async fn test_plugin_serves_bridges_and_registers_publish() {
+        let message = bridge::event(&Message { topic: "orders.42".to_string(), payload: b"a\r\nb".to_vec() });
+        assert!(format!("{:?}", message).contains("orders.42"));
+
+        let plugin = MessagingPlugin::new();
+        let mut registry = MiddlewareRegistry::default();
+        plugin.register_middleware(&mut registry);
+        assert!(!registry.names().contains(&"publish"));
+
+        plugin.initialize(&json!({
+            "broker": { "type": "nats", "url": "nats://localhost:4222" },
+            "bridges": [{ "path": "/events/orders/{id}", "topic": "orders.{id}", "group": "dashboard" }],
+        })).await.unwrap();
+        plugin.register_middleware(&mut registry);
+        assert!(registry.names().contains(&"publish"));
+        let routes: Vec<(String, Vec<String>)> = plugin.routes().declared()
+            .map(|(path, methods)| (path.to_string(), methods.to_vec()))
+            .collect();
+        assert_eq!(routes, vec![("/events/orders/{id}".to_string(), vec!["GET".to_string()])]);
+
+        let error = MessagingPlugin::new().initialize(&json!({ "bridges": [{ "path": "/events", "topic": "orders.{id}" }] })).await.unwrap_err();
+        assert!(error.to_string().contains("not a path parameter"), "{}", error);
+        assert!(MessagingPlugin::new().initialize(&json!({ "broker": { "type": "amqp" } })).await.is_err());
+        #[cfg(not(feature = "kafka"))]
+        {
+            let error = MessagingPlugin::new().initialize(&json!({ "broker": { "type": "kafka", "brokers": "localhost:9092" } })).await.unwrap_err();
+            assert!(error.to_string().contains("`kafka` feature"), "{}", error);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Backworks plugin wiring for the messaging brokers
+
+use crate::broker::Broker;
+use crate::config::MessagingConfig;
+use crate::publish::Publish;
+use async_trait::async_trait;
+use backworks::error::{BackworksError, BackworksResult};
+use backworks::pipeline::{EndpointMiddleware, MiddlewareRegistry};
+use backworks::plugin::{BackworksPlugin, HealthStatus, PluginHealth, PluginRoutes};
+use backworks::routes::RoutePattern;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// The configuration and the broker it opened
+#[derive(Clone)]
+struct Messaging {
+    config: Arc<MessagingConfig>,
+    broker: Arc<dyn Broker>,
+}
+
+/// Publishes request and handler events to NATS or Kafka, and streams
+/// topics as server-sent events
+pub struct MessagingPlugin {
+    messaging: RwLock<Option<Messaging>>,
+}
+
+impl MessagingPlugin {
+    pub fn new() -> Self {
+        Self { messaging: RwLock::new(None) }
+    }
+
+    fn messaging(&self) -> BackworksResult<Messaging> {
+        self.messaging.read().unwrap_or_else(|e| e.into_inner()).clone()
+            .ok_or_else(|| BackworksError::plugin("Messaging plugin is not initialized"))
+    }
+}
+
+impl Default for MessagingPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl BackworksPlugin for MessagingPlugin {
+    fn name(&self) -> &str {
+        "messaging"
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &str {
+        "Request and handler events published to NATS or Kafka, and topics streamed as server-sent events"
+    }
+
+    async fn initialize(&self, config: &Value) -> BackworksResult<()> {
+        let config: MessagingConfig = serde_json::from_value(config.clone())
+            .map_err(|e| BackworksError::PluginConfigInvalid(format!("messaging: {}", e)))?;
+        check_bridges(&config)?;
+        let broker = crate::broker::open(&config)?;
+        *self.messaging.write().unwrap_or_else(|e| e.into_inner()) = Some(Messaging {
+            config: Arc::new(config),
+            broker: Arc::from(broker),
+        });
+        Ok(())
+    }
+
+    async fn shutdown(&self) -> BackworksResult<()> {
+        *self.messaging.write().unwrap_or_else(|e| e.into_inner()) = None;
+        Ok(())
+    }
+
+    async fn health_check(&self) -> BackworksResult<PluginHealth> {
+        let messaging = self.messaging()?;
+        let kind = messaging.config.broker.kind();
+        let (status, message) = match messaging.broker.ping().await {
+            Ok(()) => (HealthStatus::Healthy, format!("{} reachable", kind)),
+            Err(e) => (HealthStatus::Unhealthy, e.to_string()),
+        };
+        let details = HashMap::from([("broker".to_string(), Value::String(kind.to_string()))]);
+        Ok(PluginHealth { status, message, details })
+    }
+
+    fn routes(&self) -> PluginRoutes {
+        match self.messaging() {
+            Ok(messaging) => crate::bridge::routes(messaging.config, messaging.broker),
+            Err(_) => PluginRoutes::new(),
+        }
+    }
+
+    /// The `publish` middleware, once the broker is configured
+    fn register_middleware(&self, registry: &mut MiddlewareRegistry) {
+        let Ok(messaging) = self.messaging() else {
+            return;
+        };
+        registry.register("publish", move |settings| {
+            let publish = Publish::new(messaging.config.clone(), messaging.broker.clone(), settings)?;
+            Ok(Arc::new(publish) as Arc<dyn EndpointMiddleware>)
+        });
+    }
+}
+
+/// Bridge topics may only use parameters of their path
+fn check_bridges(config: &MessagingConfig) -> BackworksResult<()> {
+    for bridge in &config.bridges {
+        let params: HashMap<String, Value> = RoutePattern::parse(&bridge.path).params()
+            .map(|name| (name.to_string(), Value::String(String::new())))
+            .collect();
+        backworks::routes::fill(&bridge.topic, &params)
+            .map_err(|e| BackworksError::PluginConfigInvalid(format!("messaging: bridge {}: {}", bridge.path, e)))?;
+    }
+    Ok(())
+}
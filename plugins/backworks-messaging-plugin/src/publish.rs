@@ -0,0 +1,196 @@
+//! The `publish` middleware
+//!
+//! Endpoints listing `publish` send the events their handler returns under
+//! `events`, and with a `topic` also one event per request describing the
+//! request and its response. Events are published once the response is
+//! ready, in the background, so a slow or unreachable broker never delays
+//! the response.
+
+use crate::broker::Broker;
+use crate::config::MessagingConfig;
+use async_trait::async_trait;
+use axum::body::{Body, Bytes};
+use axum::extract::{FromRequestParts, Query, RawPathParams, Request};
+use axum::response::{IntoResponse, Response};
+use backworks::contract::body_value;
+use backworks::error::{BackworksError, BackworksResult};
+use backworks::pipeline::{EndpointMiddleware, Next};
+use backworks::routes::fill;
+use backworks::server::{HandlerEvents, MatchedEndpoint};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Settings of the `publish` middleware of one endpoint
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PublishSettings {
+    /// Topic of the request event, with `{param}` placeholders for path
+    /// parameters, `{endpoint}` and `{method}`; only handler events are
+    /// published when unset
+    pub topic: Option<String>,
+
+    /// Key of the request event, a template like `topic`
+    pub key: Option<String>,
+
+    /// Which responses produce a request event
+    #[serde(default)]
+    pub on: PublishOn,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PublishOn {
+    /// Responses with a 2xx status
+    #[default]
+    Success,
+    /// Every response
+    Always,
+}
+
+pub struct Publish {
+    config: Arc<MessagingConfig>,
+    broker: Arc<dyn Broker>,
+    settings: PublishSettings,
+}
+
+/// An event ready to send
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outgoing {
+    pub topic: String,
+    pub key: Option<String>,
+    pub payload: Vec<u8>,
+}
+
+impl Publish {
+    pub fn new(config: Arc<MessagingConfig>, broker: Arc<dyn Broker>, settings: &Value) -> BackworksResult<Self> {
+        let settings = if settings.is_null() {
+            PublishSettings::default()
+        } else {
+            serde_json::from_value(settings.clone())
+                .map_err(|e| BackworksError::config(format!("invalid publish settings: {}", e)))?
+        };
+        Ok(Self { config, broker, settings })
+    }
+
+    /// The request event for a request to `endpoint` and its response
+    pub fn request_event(&self, endpoint: &str, params: &HashMap<String, Value>, request: &RequestSummary, response: &ResponseSummary) -> Result<Option<Outgoing>, String> {
+        let Some(ref topic) = self.settings.topic else {
+            return Ok(None);
+        };
+        if self.settings.on == PublishOn::Success && !(200..300).contains(&response.status) {
+            return Ok(None);
+        }
+        let mut placeholders = params.clone();
+        placeholders.insert("endpoint".to_string(), Value::String(endpoint.to_string()));
+        placeholders.insert("method".to_string(), Value::String(request.method.clone()));
+        let key = self.settings.key.as_deref().map(|key| fill(key, &placeholders)).transpose()?;
+        let event = json!({
+            "id": uuid::Uuid::new_v4().to_string(),
+            "endpoint": endpoint,
+            "method": request.method,
+            "path": request.path,
+            "status": response.status,
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "request": { "params": params, "query": request.query, "body": request.body },
+            "response": { "body": response.body },
+        });
+        Ok(Some(Outgoing { topic: self.config.topic(&fill(topic, &placeholders)?), key, payload: event.to_string().into_bytes() }))
+    }
+
+    /// The events a handler returned
+    pub fn handler_events(&self, events: &HandlerEvents) -> Vec<Outgoing> {
+        events.0.iter()
+            .map(|event| Outgoing {
+                topic: self.config.topic(&event.topic),
+                key: event.key.clone(),
+                payload: event.data.to_string().into_bytes(),
+            })
+            .collect()
+    }
+
+    fn send(&self, events: Vec<Outgoing>) {
+        if events.is_empty() {
+            return;
+        }
+        let broker = self.broker.clone();
+        tokio::spawn(async move {
+            for event in events {
+                if let Err(e) = broker.publish(&event.topic, event.key.as_deref(), event.payload).await {
+                    tracing::warn!("Failed to publish event to {}: {}", event.topic, e);
+                }
+            }
+        });
+    }
+}
+
+/// What a request event tells about the request
+#[derive(Debug, Clone, Default)]
+pub struct RequestSummary {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub body: Value,
+}
+
+/// What a request event tells about the response
+#[derive(Debug, Clone, Default)]
+pub struct ResponseSummary {
+    pub status: u16,
+    pub body: Value,
+}
+
+async fn buffer(body: Body) -> Result<Bytes, Response> {
+    axum::body::to_bytes(body, usize::MAX).await
+        .map_err(|e| BackworksError::server(format!("cannot buffer body for publishing: {}", e)).into_response())
+}
+
+#[async_trait]
+impl EndpointMiddleware for Publish {
+    async fn handle(&self, request: Request, next: Next<'_>) -> Response {
+        let endpoint = request.extensions().get::<MatchedEndpoint>().map(|MatchedEndpoint(name)| name.clone()).unwrap_or_default();
+        if self.settings.topic.is_none() {
+            let response = next.run(request).await;
+            if let Some(events) = response.extensions().get::<HandlerEvents>() {
+                self.send(self.handler_events(events));
+            }
+            return response;
+        }
+
+        // The request event carries both bodies, so both are buffered
+        let (mut parts, body) = request.into_parts();
+        let params: HashMap<String, Value> = match RawPathParams::from_request_parts(&mut parts, &()).await {
+            Ok(params) => params.iter().map(|(name, value)| (name.to_string(), Value::String(value.to_string()))).collect(),
+            Err(_) => HashMap::new(),
+        };
+        let body = match buffer(body).await {
+            Ok(body) => body,
+            Err(response) => return response,
+        };
+        let summary = RequestSummary {
+            method: parts.method.to_string(),
+            path: parts.uri.path().to_string(),
+            query: Query::try_from_uri(&parts.uri).map(|Query(query)| query).unwrap_or_default(),
+            body: body_value(&body),
+        };
+        let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+        let (parts, body) = response.into_parts();
+        let body = match buffer(body).await {
+            Ok(body) => body,
+            Err(response) => return response,
+        };
+        let mut events = Vec::new();
+        let outcome = ResponseSummary { status: parts.status.as_u16(), body: body_value(&body) };
+        match self.request_event(&endpoint, &params, &summary, &outcome) {
+            Ok(event) => events.extend(event),
+            Err(e) => tracing::warn!("Endpoint '{}' publish topic: {}", endpoint, e),
+        }
+        if let Some(handler_events) = parts.extensions.get::<HandlerEvents>() {
+            events.extend(self.handler_events(handler_events));
+        }
+        self.send(events);
+        Response::from_parts(parts, Body::from(body))
+    }
+}
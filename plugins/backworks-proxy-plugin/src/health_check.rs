@@ -40,6 +40,42 @@ impl Default for HealthCheckConfig {
     }
 }
 
+/// Protocol used to probe a target
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthCheckProtocol {
+    /// HTTP GET, healthy on any 2xx status
+    #[default]
+    Http,
+    
+    /// `grpc.health.v1.Health/Check`, healthy when the service reports SERVING
+    Grpc,
+}
+
+/// Per-target health check settings
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetHealthCheck {
+    /// Probe protocol
+    #[serde(default)]
+    pub protocol: HealthCheckProtocol,
+    
+    /// Path for HTTP checks (defaults to `/health`)
+    pub path: Option<String>,
+    
+    /// Service name for gRPC checks (empty checks the server as a whole)
+    pub service: Option<String>,
+}
+
+impl TargetHealthCheck {
+    pub fn grpc(service: impl Into<String>) -> Self {
+        Self {
+            protocol: HealthCheckProtocol::Grpc,
+            path: None,
+            service: Some(service.into()),
+        }
+    }
+}
+
 /// Health check result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HealthCheckResult {
@@ -129,6 +165,9 @@ impl TargetHealthStats {
 pub struct HealthChecker {
     config: HealthCheckConfig,
     client: reqwest::Client,
+    grpc_client: reqwest::Client,
+    /// HTTP and gRPC clients of targets with TLS settings of their own
+    target_clients: Arc<RwLock<HashMap<String, (reqwest::Client, reqwest::Client)>>>,
     target_stats: Arc<RwLock<HashMap<String, TargetHealthStats>>>,
     health_change_callback: Option<Arc<dyn Fn(&str, bool) + Send + Sync>>,
 }
@@ -139,10 +178,18 @@ impl HealthChecker {
             .timeout(config.timeout)
             .build()
             .unwrap();
+        // gRPC requires HTTP/2 even over plaintext connections
+        let grpc_client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .http2_prior_knowledge()
+            .build()
+            .unwrap();
 
         Self {
             config,
             client,
+            grpc_client,
+            target_clients: Arc::new(RwLock::new(HashMap::new())),
             target_stats: Arc::new(RwLock::new(HashMap::new())),
             health_change_callback: None,
         }
@@ -158,6 +205,11 @@ impl HealthChecker {
 
     /// Add a target for health checking
     pub async fn add_target(&self, target: &ProxyTarget) -> ProxyResult<()> {
+        if target.tls.is_some() {
+            let client = target.client(reqwest::Client::builder().timeout(self.config.timeout))?;
+            let grpc_client = target.client(reqwest::Client::builder().timeout(self.config.timeout).http2_prior_knowledge())?;
+            self.target_clients.write().await.insert(target.name.clone(), (client, grpc_client));
+        }
         let mut stats = self.target_stats.write().await;
         stats.insert(target.name.clone(), TargetHealthStats::new());
         Ok(())
@@ -165,6 +217,7 @@ impl HealthChecker {
 
     /// Remove a target from health checking
     pub async fn remove_target(&self, target_name: &str) -> ProxyResult<()> {
+        self.target_clients.write().await.remove(target_name);
         let mut stats = self.target_stats.write().await;
         stats.remove(target_name);
         Ok(())
@@ -196,17 +249,22 @@ impl HealthChecker {
 
     /// Perform health check for a single target
     pub async fn check_target_health(&self, target: &ProxyTarget) -> HealthCheckResult {
+        let settings = target.health_check.clone().unwrap_or_default();
+        match settings.protocol {
+            HealthCheckProtocol::Http => self.check_http(target, &settings).await,
+            HealthCheckProtocol::Grpc => self.check_grpc(target, &settings).await,
+        }
+    }
+
+    async fn check_http(&self, target: &ProxyTarget, settings: &TargetHealthCheck) -> HealthCheckResult {
         let start_time = Instant::now();
         let timestamp = chrono::Utc::now();
 
-        // Build health check URL (assume /health endpoint if not specified)
-        let health_url = if target.url.ends_with('/') {
-            format!("{}health", target.url)
-        } else {
-            format!("{}/health", target.url)
-        };
+        let path = settings.path.as_deref().unwrap_or("/health");
+        let health_url = format!("{}/{}", target.url.trim_end_matches('/'), path.trim_start_matches('/'));
 
-        match self.client.get(&health_url).send().await {
+        let client = self.clients(target).await.0;
+        match client.get(&health_url).send().await {
             Ok(response) => {
                 let response_time = start_time.elapsed().as_millis() as u64;
                 let status_code = response.status().as_u16();
@@ -235,6 +293,67 @@ impl HealthChecker {
         }
     }
 
+    /// The HTTP and gRPC clients checking `target`
+    async fn clients(&self, target: &ProxyTarget) -> (reqwest::Client, reqwest::Client) {
+        match self.target_clients.read().await.get(&target.name) {
+            Some(clients) => clients.clone(),
+            None => (self.client.clone(), self.grpc_client.clone()),
+        }
+    }
+
+    async fn check_grpc(&self, target: &ProxyTarget, settings: &TargetHealthCheck) -> HealthCheckResult {
+        let start_time = Instant::now();
+        let timestamp = chrono::Utc::now();
+
+        let url = format!("{}/grpc.health.v1.Health/Check", target.url.trim_end_matches('/'));
+        let service = settings.service.as_deref().unwrap_or("");
+
+        let outcome = async {
+            let response = self.clients(target).await.1
+                .post(&url)
+                .header("content-type", "application/grpc")
+                .header("te", "trailers")
+                .body(grpc::encode_check_request(service))
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            let status_code = response.status().as_u16();
+            if !response.status().is_success() {
+                return Err(format!("HTTP {}", status_code));
+            }
+
+            // Trailers-only responses carry the gRPC status in the headers
+            if let Some(grpc_status) = response.headers().get("grpc-status").and_then(|v| v.to_str().ok()) {
+                if grpc_status != "0" {
+                    let message = response.headers().get("grpc-message")
+                        .and_then(|v| v.to_str().ok())
+                        .unwrap_or("");
+                    return Err(format!("gRPC status {} {}", grpc_status, message).trim_end().to_string());
+                }
+            }
+
+            let body = response.bytes().await.map_err(|e| e.to_string())?;
+            let serving = grpc::decode_check_response(&body)?;
+            Ok((status_code, serving))
+        }.await;
+
+        let (healthy, status_code, error) = match outcome {
+            Ok((status_code, grpc::ServingStatus::Serving)) => (true, Some(status_code), None),
+            Ok((status_code, status)) => (false, Some(status_code), Some(format!("gRPC service {:?}", status))),
+            Err(error) => (false, None, Some(error)),
+        };
+
+        HealthCheckResult {
+            target_name: target.name.clone(),
+            healthy,
+            response_time_ms: status_code.map(|_| start_time.elapsed().as_millis() as u64),
+            status_code,
+            error,
+            timestamp,
+        }
+    }
+
     /// Stop health checking
     pub async fn stop(&mut self) -> ProxyResult<()> {
         // Clear all target stats
@@ -348,6 +467,8 @@ impl Clone for HealthChecker {
         Self {
             config: self.config.clone(),
             client: self.client.clone(),
+            grpc_client: self.grpc_client.clone(),
+            target_clients: Arc::clone(&self.target_clients),
             target_stats: Arc::clone(&self.target_stats),
             health_change_callback: self.health_change_callback.clone(),
         }
@@ -365,6 +486,91 @@ impl std::fmt::Debug for HealthChecker {
     }
 }
 
+/// Minimal `grpc.health.v1` wire encoding, enough for the Check call
+mod grpc {
+    /// `grpc.health.v1.HealthCheckResponse.ServingStatus`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ServingStatus {
+        Unknown,
+        Serving,
+        NotServing,
+        ServiceUnknown,
+    }
+
+    /// Length-prefixed `HealthCheckRequest { string service = 1; }`
+    pub fn encode_check_request(service: &str) -> Vec<u8> {
+        let mut message = Vec::new();
+        if !service.is_empty() {
+            message.push(0x0a); // field 1, length-delimited
+            write_varint(&mut message, service.len() as u64);
+            message.extend_from_slice(service.as_bytes());
+        }
+
+        let mut frame = Vec::with_capacity(5 + message.len());
+        frame.push(0); // uncompressed
+        frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&message);
+        frame
+    }
+
+    /// Decode the first frame of a `HealthCheckResponse { ServingStatus status = 1; }`
+    pub fn decode_check_response(body: &[u8]) -> Result<ServingStatus, String> {
+        if body.len() < 5 {
+            return Err("empty gRPC health response".to_string());
+        }
+        if body[0] != 0 {
+            return Err("compressed gRPC health responses are not supported".to_string());
+        }
+        let length = u32::from_be_bytes([body[1], body[2], body[3], body[4]]) as usize;
+        let mut message = body.get(5..5 + length).ok_or("truncated gRPC health response")?;
+
+        // proto3 omits the field when it holds the default (UNKNOWN)
+        let mut status = 0;
+        while !message.is_empty() {
+            let key = read_varint(&mut message)?;
+            match (key >> 3, key & 0x7) {
+                (1, 0) => status = read_varint(&mut message)?,
+                (_, 0) => { read_varint(&mut message)?; }
+                (_, 1) => message = message.get(8..).ok_or("truncated field")?,
+                (_, 2) => {
+                    let len = read_varint(&mut message)? as usize;
+                    message = message.get(len..).ok_or("truncated field")?;
+                }
+                (_, 5) => message = message.get(4..).ok_or("truncated field")?,
+                (_, wire_type) => return Err(format!("unsupported wire type {}", wire_type)),
+            }
+        }
+
+        Ok(match status {
+            1 => ServingStatus::Serving,
+            2 => ServingStatus::NotServing,
+            3 => ServingStatus::ServiceUnknown,
+            _ => ServingStatus::Unknown,
+        })
+    }
+
+    fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            buf.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        buf.push(value as u8);
+    }
+
+    fn read_varint(buf: &mut &[u8]) -> Result<u64, String> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = buf.split_first().ok_or("truncated varint")?;
+            *buf = rest;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("varint too long".to_string())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -442,4 +648,37 @@ mod tests {
         assert!(stats.should_mark_healthy(3));
         assert!(!stats.should_mark_healthy(4));
     }
+
+    #[test]
+    fn test_grpc_check_request_encoding() {
+        assert_eq!(grpc::encode_check_request(""), vec![0, 0, 0, 0, 0]);
+        assert_eq!(
+            grpc::encode_check_request("orders"),
+            vec![0, 0, 0, 0, 8, 0x0a, 6, b'o', b'r', b'd', b'e', b'r', b's']
+        );
+    }
+
+    #[test]
+    fn test_grpc_check_response_decoding() {
+        assert_eq!(grpc::decode_check_response(&[0, 0, 0, 0, 2, 0x08, 1]), Ok(grpc::ServingStatus::Serving));
+        assert_eq!(grpc::decode_check_response(&[0, 0, 0, 0, 2, 0x08, 2]), Ok(grpc::ServingStatus::NotServing));
+        // Default status is omitted on the wire
+        assert_eq!(grpc::decode_check_response(&[0, 0, 0, 0, 0]), Ok(grpc::ServingStatus::Unknown));
+        assert!(grpc::decode_check_response(&[0, 0, 0, 0, 4, 0x08]).is_err());
+        assert!(grpc::decode_check_response(&[]).is_err());
+    }
+
+    #[test]
+    fn test_target_health_check_protocol_from_config() {
+        let target: ProxyTarget = serde_json::from_value(serde_json::json!({
+            "name": "orders",
+            "url": "http://orders:50051",
+            "weight": 1.0,
+            "healthy": true,
+            "active_connections": 0,
+            "timeout": null,
+            "health_check": { "protocol": "grpc", "service": "orders.v1.Orders" }
+        })).unwrap();
+        assert_eq!(target.health_check, Some(TargetHealthCheck::grpc("orders.v1.Orders")));
+    }
 }
@@ -59,6 +59,7 @@ mod tests {
                     "interval": 10
                 }
             }),
+            ..Default::default()
         };
 
         let result = plugin.initialize(&config).await;
@@ -75,6 +76,7 @@ mod tests {
                 "health_checks": true,
                 "timeout": 30
             }),
+            ..Default::default()
         };
         
         // Initialize
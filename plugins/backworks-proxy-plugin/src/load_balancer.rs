@@ -1,7 +1,12 @@
 //! Load balancing algorithms for the proxy plugin
 
 use crate::error::{ProxyError, ProxyResult};
+use crate::health_check::TargetHealthCheck;
+use backworks::config::UpstreamTlsConfig;
+use backworks::origin::parse_client_ip;
+use backworks::proxy::configure_tls;
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use sha2::{Digest, Sha256};
@@ -51,6 +56,14 @@ pub struct ProxyTarget {
     
     /// Request timeout
     pub timeout: Option<std::time::Duration>,
+    
+    /// Health check protocol and probe settings (HTTP GET `/health` if unset)
+    #[serde(default)]
+    pub health_check: Option<TargetHealthCheck>,
+    
+    /// CA bundle, client certificate and verification of an HTTPS target
+    #[serde(default)]
+    pub tls: Option<UpstreamTlsConfig>,
 }
 
 impl ProxyTarget {
@@ -62,8 +75,30 @@ impl ProxyTarget {
             healthy: true,
             active_connections: 0,
             timeout: None,
+            health_check: None,
+            tls: None,
         }
     }
+
+    pub fn with_health_check(mut self, health_check: TargetHealthCheck) -> Self {
+        self.health_check = Some(health_check);
+        self
+    }
+
+    pub fn with_tls(mut self, tls: UpstreamTlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// `builder` with the target's TLS settings applied, if it has any
+    pub fn client(&self, builder: reqwest::ClientBuilder) -> ProxyResult<reqwest::Client> {
+        let builder = match self.tls {
+            Some(ref tls) => configure_tls(builder, tls)
+                .map_err(|e| ProxyError::Configuration(format!("Target {}: {}", self.name, e)))?,
+            None => builder,
+        };
+        builder.build().map_err(|e| ProxyError::Configuration(format!("Failed to create HTTP client for target {}: {}", self.name, e)))
+    }
 }
 
 /// Load balancer implementation
@@ -166,9 +201,15 @@ impl LoadBalancer {
 
     /// IP hash based selection
     async fn ip_hash_select<'a>(&self, targets: &'a [&'a ProxyTarget], client_ip: Option<&str>) -> ProxyResult<&'a ProxyTarget> {
-        let ip = client_ip.unwrap_or("127.0.0.1");
+        // IPv6 clients are hashed by their /64, which stays put while
+        // privacy extensions rotate the interface identifier
+        let key = match client_ip.and_then(parse_client_ip) {
+            Some(IpAddr::V4(ip)) => ip.octets().to_vec(),
+            Some(IpAddr::V6(ip)) => ip.octets()[..8].to_vec(),
+            None => client_ip.unwrap_or("127.0.0.1").as_bytes().to_vec(),
+        };
         let mut hasher = Sha256::new();
-        hasher.update(ip.as_bytes());
+        hasher.update(&key);
         let hash = hasher.finalize();
         
         // Convert first 8 bytes to u64
@@ -293,6 +334,13 @@ mod tests {
         let target2 = lb.get_next_target(Some("192.168.1.100")).await.unwrap();
         
         assert_eq!(target1.name, target2.name);
+
+        // IPv4-mapped and bracketed forms are the same client; an IPv6 /64 sticks together
+        let mapped = lb.get_next_target(Some("::ffff:192.168.1.100")).await.unwrap();
+        assert_eq!(mapped.name, target1.name);
+        let v6 = lb.get_next_target(Some("2001:db8:0:1::10")).await.unwrap();
+        let rotated = lb.get_next_target(Some("[2001:db8:0:1:abcd::7]:443")).await.unwrap();
+        assert_eq!(v6.name, rotated.name);
     }
 
     #[tokio::test]
@@ -7,11 +7,14 @@ use crate::health_check::{HealthChecker, HealthCheckConfig};
 use crate::transformations::{RequestTransformer, ResponseTransformer, RequestTransformConfig, ResponseTransformConfig};
 use crate::metrics::{ProxyMetrics, ProxyMetricsManager};
 
-use axum::{body::Body, http::{Request, Response, HeaderName, HeaderValue, StatusCode}};
+use axum::{body::Body, extract::ConnectInfo, http::{Request, Response, HeaderName, HeaderValue, StatusCode}};
+use backworks::origin::{parse_client_ip, RequestOrigin};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
 use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
 use url::Url;
 
 /// Proxy configuration for a single endpoint
@@ -48,6 +51,9 @@ pub struct ProxyManager {
     /// HTTP client for making proxy requests
     client: Client,
     
+    /// Clients of targets with TLS settings of their own, by target name
+    target_clients: RwLock<HashMap<String, Client>>,
+    
     /// Load balancer instance
     load_balancer: LoadBalancer,
     
@@ -70,7 +76,6 @@ pub struct ProxyManager {
     additional_headers: HashMap<String, String>,
     
     /// Default timeout
-    #[allow(dead_code)]
     default_timeout: Duration,
 }
 
@@ -78,10 +83,15 @@ impl ProxyManager {
     /// Create a new proxy manager with the given configuration
     pub async fn new(config: ProxyConfig) -> ProxyResult<Self> {
         // Create HTTP client
+        let timeout = config.timeout.unwrap_or(Duration::from_secs(30));
         let client = Client::builder()
-            .timeout(config.timeout.unwrap_or(Duration::from_secs(30)))
+            .timeout(timeout)
             .build()
             .map_err(|e| ProxyError::Configuration(format!("Failed to create HTTP client: {}", e)))?;
+        let mut target_clients = HashMap::new();
+        for target in config.targets.iter().filter(|target| target.tls.is_some()) {
+            target_clients.insert(target.name.clone(), target.client(Client::builder().timeout(timeout))?);
+        }
 
         // Create load balancer
         let load_balancer = LoadBalancer::new(config.load_balancing.clone());
@@ -136,6 +146,7 @@ impl ProxyManager {
 
         Ok(Self {
             client,
+            target_clients: RwLock::new(target_clients),
             load_balancer,
             circuit_breaker,
             health_checker,
@@ -184,13 +195,10 @@ impl ProxyManager {
         }
 
         // Get client IP for load balancing
-        let client_ip = request.headers()
-            .get("x-forwarded-for")
-            .or_else(|| request.headers().get("x-real-ip"))
-            .and_then(|v| v.to_str().ok());
+        let client_ip = client_address(&request).map(|ip| ip.to_string());
 
         // Select target using load balancer
-        let target = self.load_balancer.get_next_target(client_ip).await?;
+        let target = self.load_balancer.get_next_target(client_ip.as_deref()).await?;
         
         // Record request start for metrics
         self.metrics_manager.record_request_start(&target.name).await;
@@ -340,7 +348,8 @@ impl ProxyManager {
             .map_err(|e| ProxyError::Http(format!("Failed to read request body: {}", e)))?;
         
         // Build reqwest request
-        let mut reqwest_request = self.client
+        let client = self.target_clients.read().await.get(&target.name).cloned().unwrap_or_else(|| self.client.clone());
+        let mut reqwest_request = client
             .request(method, target_url.clone())
             .body(body_bytes.to_vec());
         
@@ -367,7 +376,7 @@ impl ProxyManager {
             let cloned_request = final_request.try_clone()
                 .ok_or_else(|| ProxyError::Http("Failed to clone request for retry".to_string()))?;
             
-            match self.client.execute(cloned_request).await {
+            match client.execute(cloned_request).await {
                 Ok(response) => {
                     return self.convert_reqwest_response(response).await;
                 }
@@ -446,6 +455,11 @@ impl ProxyManager {
 
     /// Add a new target to the proxy
     pub async fn add_target(&self, target: ProxyTarget) -> ProxyResult<()> {
+        if target.tls.is_some() {
+            let client = target.client(Client::builder().timeout(self.default_timeout))?;
+            self.target_clients.write().await.insert(target.name.clone(), client);
+        }
+        
         // Add to load balancer
         self.load_balancer.add_target(target.clone()).await?;
         
@@ -463,6 +477,8 @@ impl ProxyManager {
 
     /// Remove a target from the proxy
     pub async fn remove_target(&self, target_name: &str) -> ProxyResult<()> {
+        self.target_clients.write().await.remove(target_name);
+        
         // Remove from load balancer
         self.load_balancer.remove_target(target_name).await?;
         
@@ -535,6 +551,20 @@ impl ProxyManager {
     }
 }
 
+/// The client's address: the origin resolved by the server, else the
+/// original client of the forwarded-for chain, else the connection's peer
+fn client_address(request: &Request<Body>) -> Option<IpAddr> {
+    if let Some(ip) = request.extensions().get::<RequestOrigin>().and_then(|origin| origin.ip) {
+        return Some(ip.to_canonical());
+    }
+    request.headers().get("x-forwarded-for")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').next())
+        .or_else(|| request.headers().get("x-real-ip").and_then(|v| v.to_str().ok()))
+        .and_then(parse_client_ip)
+        .or_else(|| request.extensions().get::<ConnectInfo<SocketAddr>>().map(|info| info.0.ip().to_canonical()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -604,4 +634,19 @@ mod tests {
         let aggregated = manager.get_aggregated_metrics().await;
         assert_eq!(aggregated.target_name, "aggregated");
     }
+
+    #[tokio::test]
+    async fn test_targets_with_tls_settings() {
+        let mut config = create_test_config();
+        let tls: backworks::config::UpstreamTlsConfig = serde_json::from_value(serde_json::json!({ "danger_accept_invalid_certs": true })).unwrap();
+        config.targets.push(ProxyTarget::new("internal".to_string(), "https://localhost:8443".to_string()).with_tls(tls));
+        let manager = ProxyManager::new(config).await.unwrap();
+        assert!(manager.target_clients.read().await.contains_key("internal"));
+
+        let half: backworks::config::UpstreamTlsConfig = serde_json::from_value(serde_json::json!({ "client_cert": "client.pem" })).unwrap();
+        let target = ProxyTarget::new("broken".to_string(), "https://localhost:8444".to_string()).with_tls(half);
+        assert!(manager.add_target(target).await.is_err());
+        assert!(manager.remove_target("internal").await.is_ok());
+        assert!(manager.target_clients.read().await.is_empty());
+    }
 }
@@ -0,0 +1,109 @@
+//! Configuration for the Redis plugin
+
+use serde::{Deserialize, Serialize};
+use backworks::routes::fill;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Redis plugin configuration, read from the plugin's `config:` block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedisConfig {
+    /// Server URL, `redis://host:6379/0` or `rediss://` for TLS
+    #[serde(default = "default_url")]
+    pub url: String,
+
+    /// Prepended to every key the plugin reads or writes
+    #[serde(default = "default_key_prefix")]
+    pub key_prefix: String,
+
+    /// Seconds allowed for each Redis command
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+
+    /// Endpoints served as keys, by endpoint name
+    #[serde(default)]
+    pub endpoints: HashMap<String, KeyValueEndpoint>,
+
+    /// Routes streaming the messages of a channel
+    #[serde(default)]
+    pub channels: Vec<ChannelBridge>,
+}
+
+/// An endpoint reading and writing one key per request
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeyValueEndpoint {
+    /// Key with `{param}` placeholders for path parameters; the endpoint
+    /// name followed by the parameter values when unset
+    pub key: Option<String>,
+
+    /// Seconds written values live; they do not expire when unset
+    pub ttl: Option<u64>,
+}
+
+/// A route bridging a Redis channel to HTTP clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelBridge {
+    /// Route path on the API server, with parameters like endpoint paths
+    pub path: String,
+
+    /// Channel with `{param}` placeholders for path parameters; a `*` makes
+    /// it a pattern
+    pub channel: String,
+
+    /// Serve a WebSocket instead of server-sent events
+    #[serde(default)]
+    pub websocket: bool,
+
+    /// Publish the text messages WebSocket clients send to the channel
+    #[serde(default)]
+    pub publish: bool,
+}
+
+fn default_url() -> String { "redis://127.0.0.1:6379".to_string() }
+fn default_key_prefix() -> String { "backworks:".to_string() }
+fn default_timeout() -> u64 { 5 }
+
+impl RedisConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout)
+    }
+
+    /// The full key of `key`
+    pub fn key(&self, key: &str) -> String {
+        format!("{}{}", self.key_prefix, key)
+    }
+
+    /// Keys of cached responses, below `key_prefix`
+    pub fn cache_key(&self, key: &str) -> String {
+        format!("{}cache:{}", self.key_prefix, key)
+    }
+}
+
+impl KeyValueEndpoint {
+    /// The key a request to `endpoint` with `params` reads or writes
+    pub fn key_for(&self, endpoint: &str, params: &HashMap<String, Value>) -> Result<String, String> {
+        match self.key {
+            Some(ref template) => fill(template, params),
+            None => {
+                let mut names: Vec<&String> = params.keys().collect();
+                names.sort();
+                Ok(std::iter::once(endpoint.to_string())
+                    .chain(names.into_iter().map(|name| text(&params[name])))
+                    .collect::<Vec<_>>()
+                    .join(":"))
+            }
+        }
+    }
+
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl.map(Duration::from_secs)
+    }
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
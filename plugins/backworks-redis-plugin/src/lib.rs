@@ -0,0 +1,123 @@
+//! # Backworks Redis Plugin
+//!
+//! Brings Redis to Backworks in three ways:
+//!
+//! - a cache store: with `cache: { type: redis }` in the blueprint, the
+//!   `cache` middleware keeps responses in Redis, shared by every server
+//!   instance and kept across restarts
+//! - key/value endpoints: endpoints listed under `endpoints` read (`GET`),
+//!   write (`PUT`, `POST`) and delete (`DELETE`) one key per request, named
+//!   by a template over the path parameters
+//! - pub/sub streams: routes under `channels` relay the messages of a channel
+//!   as server-sent events or over a WebSocket
+//!
+//! ```yaml
+//! cache:
+//!   type: redis
+//!
+//! plugins:
+//!   redis:
+//!     enabled: true
+//!     config:
+//!       url: "redis://localhost:6379/0"
+//!       key_prefix: "shop:"
+//!       endpoints:
+//!         session: { key: "session:{id}", ttl: 3600 }
+//!       channels:
+//!         - { path: "/events/orders", channel: "orders" }
+//!         - { path: "/chat/{room}", channel: "chat:{room}", websocket: true, publish: true }
+//!
+//! endpoints:
+//!   session:
+//!     path: "/sessions/{id}"
+//!     methods: [GET, PUT, DELETE]
+//!     mode: plugin
+//!     plugin: redis
+//! ```
+
+pub mod config;
+pub mod plugin;
+pub mod pubsub;
+pub mod store;
+
+pub use config::{ChannelBridge, KeyValueEndpoint, RedisConfig};
+pub use plugin::RedisPlugin;
+pub use store::Redis;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::decode;
+    use crate::store::escape_pattern;
+    use backworks::plugin::EndpointHandlerPlugin;
+    use backworks::BackworksPlugin;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    fn params(pairs: &[(&str, Value)]) -> HashMap<String, Value> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.clone())).collect()
+    }
+
+    #[test]
+    fn test_keys_from_path_parameters() {
+        let config: RedisConfig = serde_json::from_value(json!({
+            "endpoints": {
+                "session": { "key": "session:{id}", "ttl": 60 },
+                "cart": {},
+            },
+        })).unwrap();
+        assert_eq!((config.url.as_str(), config.key_prefix.as_str(), config.timeout), ("redis://127.0.0.1:6379", "backworks:", 5));
+
+        let session = &config.endpoints["session"];
+        assert_eq!(session.key_for("session", &params(&[("id", json!("abc"))])).unwrap(), "session:abc");
+        assert!(session.key_for("session", &HashMap::new()).unwrap_err().contains("not a path parameter"));
+        let cart = &config.endpoints["cart"];
+        assert_eq!(cart.key_for("cart", &params(&[("user", json!(7)), ("store", json!("nl"))])).unwrap(), "cart:nl:7");
+        assert_eq!(cart.key_for("cart", &HashMap::new()).unwrap(), "cart");
+        assert_eq!(config.key("cart:nl:7"), "backworks:cart:nl:7");
+        assert_eq!(config.cache_key("response:users:"), "backworks:cache:response:users:");
+
+        assert_eq!(escape_pattern("response:users:/users?page=[2]*"), r"response:users:/users\?page=\[2\]\*");
+    }
+
+    #[test]
+    fn test_stored_values_decode_as_json_or_text() {
+        assert_eq!(decode(br#"{"cart":[1,2]}"#), json!({"cart": [1, 2]}));
+        assert_eq!(decode(b"plain text"), json!("plain text"));
+    }
+
+    #[tokio::test]
+    async fn test_plugin_claims_endpoints_and_serves_channel_routes() {
+        let plugin = RedisPlugin::new();
+        assert!(plugin.routes().is_empty());
+        plugin.initialize(&json!({
+            "url": "redis://localhost:6379/1",
+            "endpoints": { "session": { "key": "session:{id}" } },
+            "channels": [
+                { "path": "/events/orders", "channel": "orders" },
+                { "path": "/chat/{room}", "channel": "chat:{room}", "websocket": true, "publish": true },
+            ],
+        })).await.unwrap();
+
+        assert!(plugin.claims("session"));
+        assert!(!plugin.claims("users"));
+        assert!(plugin.cache().is_some());
+        let routes: Vec<(String, Vec<String>)> = plugin.routes().declared()
+            .map(|(path, methods)| (path.to_string(), methods.to_vec()))
+            .collect();
+        assert_eq!(routes, vec![
+            ("/chat/{room}".to_string(), vec!["GET".to_string()]),
+            ("/events/orders".to_string(), vec!["GET".to_string()]),
+        ]);
+
+        for (channels, problem) in [
+            (json!([{ "path": "/chat", "channel": "chat:{room}" }]), "not a path parameter"),
+            (json!([{ "path": "/events", "channel": "orders", "publish": true }]), "publish needs websocket"),
+            (json!([{ "path": "/events", "channel": "orders.*", "websocket": true, "publish": true }]), "cannot publish to a pattern"),
+        ] {
+            let error = RedisPlugin::new().initialize(&json!({ "channels": channels })).await.unwrap_err();
+            assert!(error.to_string().contains(problem), "{}", error);
+        }
+        assert!(RedisPlugin::new().initialize(&json!({ "url": "not a url" })).await.is_err());
+    }
+}
@@ -0,0 +1,183 @@
+//! Backworks plugin wiring for Redis
+
+use crate::config::{KeyValueEndpoint, RedisConfig};
+use crate::store::Redis;
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use backworks::error::{BackworksError, BackworksResult};
+use backworks::plugin::{BackworksPlugin, CacheBackend, EndpointHandlerPlugin, EndpointResponse, HealthStatus, PluginHealth, PluginRoutes};
+use backworks::routes::RoutePattern;
+use backworks::server::RequestData;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Keeps cached responses, serves key/value endpoints and streams pub/sub
+/// channels, all in Redis
+pub struct RedisPlugin {
+    redis: RwLock<Option<Arc<Redis>>>,
+}
+
+impl RedisPlugin {
+    pub fn new() -> Self {
+        Self { redis: RwLock::new(None) }
+    }
+
+    fn redis(&self) -> BackworksResult<Arc<Redis>> {
+        self.redis.read().unwrap_or_else(|e| e.into_inner()).clone()
+            .ok_or_else(|| BackworksError::plugin("Redis plugin is not initialized"))
+    }
+}
+
+impl Default for RedisPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl BackworksPlugin for RedisPlugin {
+    fn name(&self) -> &str {
+        "redis"
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &str {
+        "Redis cache store, key/value endpoints and pub/sub streams"
+    }
+
+    async fn initialize(&self, config: &Value) -> BackworksResult<()> {
+        let config: RedisConfig = serde_json::from_value(config.clone())
+            .map_err(|e| BackworksError::PluginConfigInvalid(format!("redis: {}", e)))?;
+        check_channels(&config)?;
+        *self.redis.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(Redis::open(config)?));
+        Ok(())
+    }
+
+    async fn shutdown(&self) -> BackworksResult<()> {
+        *self.redis.write().unwrap_or_else(|e| e.into_inner()) = None;
+        Ok(())
+    }
+
+    async fn health_check(&self) -> BackworksResult<PluginHealth> {
+        let redis = self.redis()?;
+        let (status, message) = match redis.ping().await {
+            Ok(()) => (HealthStatus::Healthy, "Redis reachable".to_string()),
+            Err(e) => (HealthStatus::Unhealthy, e.to_string()),
+        };
+        Ok(PluginHealth { status, message, details: HashMap::new() })
+    }
+
+    fn routes(&self) -> PluginRoutes {
+        match self.redis() {
+            Ok(redis) => crate::pubsub::routes(redis),
+            Err(_) => PluginRoutes::new(),
+        }
+    }
+
+    fn endpoint_handler(&self) -> Option<&dyn EndpointHandlerPlugin> {
+        Some(self)
+    }
+
+    fn cache(&self) -> Option<&dyn CacheBackend> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl CacheBackend for RedisPlugin {
+    async fn get(&self, key: &str) -> BackworksResult<Option<Vec<u8>>> {
+        let redis = self.redis()?;
+        redis.get(&redis.config.cache_key(key)).await
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Duration) -> BackworksResult<()> {
+        let redis = self.redis()?;
+        redis.set(&redis.config.cache_key(key), &value, Some(ttl)).await
+    }
+
+    async fn clear(&self, prefix: &str) -> BackworksResult<usize> {
+        let redis = self.redis()?;
+        redis.clear(&redis.config.cache_key(prefix)).await
+    }
+}
+
+#[async_trait]
+impl EndpointHandlerPlugin for RedisPlugin {
+    /// Endpoints listed under `endpoints`
+    fn claims(&self, endpoint: &str) -> bool {
+        self.redis().is_ok_and(|redis| redis.config.endpoints.contains_key(endpoint))
+    }
+
+    async fn handle_endpoint(&self, endpoint: &str, request: &RequestData) -> BackworksResult<EndpointResponse> {
+        let redis = self.redis()?;
+        let settings = redis.config.endpoints.get(endpoint)
+            .ok_or_else(|| BackworksError::config(format!("Redis does not serve endpoint '{}'", endpoint)))?;
+        key_value(&redis, endpoint, settings, request).await
+    }
+}
+
+/// Read, write or delete the key a request names
+async fn key_value(redis: &Redis, endpoint: &str, settings: &KeyValueEndpoint, request: &RequestData) -> BackworksResult<EndpointResponse> {
+    let key = settings.key_for(endpoint, &request.path_params)
+        .map_err(|e| BackworksError::config(format!("Endpoint '{}' key: {}", endpoint, e)))?;
+    let key = redis.config.key(&key);
+    match request.method.to_ascii_uppercase().as_str() {
+        "GET" => Ok(match redis.get(&key).await? {
+            Some(stored) => EndpointResponse::new(StatusCode::OK, decode(&stored)),
+            None => failure(StatusCode::NOT_FOUND, "Key not found"),
+        }),
+        method @ ("PUT" | "POST") => {
+            let Some(ref value) = request.body else {
+                return Ok(failure(StatusCode::BAD_REQUEST, "A value to store is required"));
+            };
+            let ttl = match request.query_params.get("ttl") {
+                Some(ttl) => match ttl.parse::<u64>() {
+                    Ok(seconds) if seconds > 0 => Some(Duration::from_secs(seconds)),
+                    _ => return Ok(failure(StatusCode::BAD_REQUEST, "ttl must be a positive number of seconds")),
+                },
+                None => settings.ttl(),
+            };
+            redis.set(&key, value.to_string().as_bytes(), ttl).await?;
+            let status = if method == "POST" { StatusCode::CREATED } else { StatusCode::OK };
+            Ok(EndpointResponse::new(status, value.clone()))
+        }
+        "DELETE" => Ok(match redis.delete(&key).await? {
+            true => EndpointResponse::new(StatusCode::NO_CONTENT, Value::Null),
+            false => failure(StatusCode::NOT_FOUND, "Key not found"),
+        }),
+        _ => Ok(failure(StatusCode::METHOD_NOT_ALLOWED, "Keys are read with GET, written with PUT or POST and deleted with DELETE")),
+    }
+}
+
+/// A stored value: JSON as written, anything else as text
+pub fn decode(stored: &[u8]) -> Value {
+    serde_json::from_slice(stored).unwrap_or_else(|_| Value::String(String::from_utf8_lossy(stored).into_owned()))
+}
+
+fn failure(status: StatusCode, message: &str) -> EndpointResponse {
+    EndpointResponse::new(status, json!({ "error": message, "status": status.as_u16() }))
+}
+
+/// Channels may only use parameters of their path, and only WebSockets on a
+/// single channel can publish
+fn check_channels(config: &RedisConfig) -> BackworksResult<()> {
+    for bridge in &config.channels {
+        let invalid = |problem: String| BackworksError::PluginConfigInvalid(format!("redis: channel {}: {}", bridge.path, problem));
+        let params: HashMap<String, Value> = RoutePattern::parse(&bridge.path).params()
+            .map(|name| (name.to_string(), Value::String(String::new())))
+            .collect();
+        backworks::routes::fill(&bridge.channel, &params).map_err(invalid)?;
+        if bridge.publish && !bridge.websocket {
+            return Err(invalid("publish needs websocket".to_string()));
+        }
+        if bridge.publish && bridge.channel.contains('*') {
+            return Err(invalid("cannot publish to a pattern".to_string()));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,116 @@
+//! Routes bridging Redis channels to HTTP clients
+//!
+//! Each `channels` entry serves its path on the API server: a stream of
+//! server-sent events, one per message published to the channel, or a
+//! WebSocket receiving the messages as text frames. Channels take path
+//! parameters (`chat:{room}`), and WebSocket clients of a `publish` bridge
+//! publish the text frames they send.
+
+use crate::config::ChannelBridge;
+use crate::store::Redis;
+use axum::extract::ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade};
+use axum::extract::Path;
+use axum::http::Method;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use backworks::error::BackworksError;
+use backworks::plugin::PluginRoutes;
+use backworks::routes::fill;
+use futures::StreamExt;
+use redis::Msg;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+/// WebSocket close code of a subscription that failed
+const INTERNAL_ERROR: u16 = 1011;
+
+type PathParams = Option<Path<HashMap<String, String>>>;
+
+/// A route for every bridge in the configuration
+pub fn routes(redis: Arc<Redis>) -> PluginRoutes {
+    let mut routes = PluginRoutes::new();
+    for bridge in &redis.config.channels {
+        let bridge = Arc::new(bridge.clone());
+        let redis = redis.clone();
+        routes = if bridge.websocket {
+            routes.route(Method::GET, &bridge.path.clone(), move |params: PathParams, upgrade: WebSocketUpgrade| async move {
+                match channel(&bridge, params) {
+                    Ok(channel) => upgrade.on_upgrade(move |socket| relay(redis, channel, bridge.publish, socket)),
+                    Err(e) => e.into_response(),
+                }
+            })
+        } else {
+            routes.route(Method::GET, &bridge.path.clone(), move |params: PathParams| async move {
+                match channel(&bridge, params) {
+                    Ok(channel) => stream(redis, channel).await,
+                    Err(e) => e.into_response(),
+                }
+            })
+        };
+    }
+    routes
+}
+
+/// The channel `bridge` names for a request with `params`
+fn channel(bridge: &ChannelBridge, params: PathParams) -> Result<String, BackworksError> {
+    let params: HashMap<String, Value> = params.map(|Path(params)| params).unwrap_or_default()
+        .into_iter()
+        .map(|(name, value)| (name, Value::String(value)))
+        .collect();
+    fill(&bridge.channel, &params).map_err(|e| BackworksError::config(format!("redis channel: {}", e)))
+}
+
+async fn stream(redis: Arc<Redis>, channel: String) -> Response {
+    match redis.subscribe(&channel).await {
+        Ok(messages) => Sse::new(messages.map(|message| Ok::<_, Infallible>(event(&message))))
+            .keep_alive(KeepAlive::default())
+            .into_response(),
+        Err(e) => BackworksError::unavailable(e.to_string()).into_response(),
+    }
+}
+
+/// A message as a server-sent event named after its channel
+fn event(message: &Msg) -> Event {
+    Event::default()
+        .event(message.get_channel_name().replace(['\r', '\n'], ""))
+        .data(payload(message).replace('\r', ""))
+}
+
+fn payload(message: &Msg) -> String {
+    String::from_utf8_lossy(message.get_payload_bytes()).into_owned()
+}
+
+async fn relay(redis: Arc<Redis>, channel: String, publish: bool, mut socket: WebSocket) {
+    let messages = match redis.subscribe(&channel).await {
+        Ok(messages) => messages,
+        Err(e) => {
+            let reason = e.to_string().into();
+            let _ = socket.send(Message::Close(Some(CloseFrame { code: INTERNAL_ERROR, reason }))).await;
+            return;
+        }
+    };
+    tokio::pin!(messages);
+    loop {
+        tokio::select! {
+            message = messages.next() => {
+                let Some(message) = message else {
+                    return;
+                };
+                if socket.send(Message::Text(payload(&message))).await.is_err() {
+                    return;
+                }
+            }
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Text(text))) if publish => {
+                    if let Err(e) = redis.publish(&channel, &text).await {
+                        tracing::warn!("Failed to publish to redis channel {}: {}", channel, e);
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
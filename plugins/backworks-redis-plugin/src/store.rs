@@ -0,0 +1,119 @@
+//! Redis commands the plugin runs
+//!
+//! Commands share one multiplexed connection, opened on first use and
+//! re-established by the connection manager after a failure. Subscriptions
+//! take a connection each, closed when the subscriber goes away.
+
+use crate::config::RedisConfig;
+use backworks::error::{BackworksError, BackworksResult};
+use futures::Stream;
+use redis::aio::ConnectionManager;
+use redis::{Client, Cmd, FromRedisValue, Msg};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+
+/// Keys deleted per `DEL` when clearing a prefix
+const CLEAR_BATCH: usize = 500;
+
+pub struct Redis {
+    pub config: RedisConfig,
+    client: Client,
+    connection: OnceCell<ConnectionManager>,
+}
+
+impl Redis {
+    /// Check the URL; the connection is opened by the first command
+    pub fn open(config: RedisConfig) -> BackworksResult<Self> {
+        let client = Client::open(config.url.as_str())
+            .map_err(|e| BackworksError::PluginConfigInvalid(format!("redis: invalid url: {}", e)))?;
+        Ok(Self { config, client, connection: OnceCell::new() })
+    }
+
+    async fn connection(&self) -> BackworksResult<ConnectionManager> {
+        self.connection.get_or_try_init(|| async {
+            self.within("connecting", self.client.get_connection_manager()).await
+        }).await.cloned()
+    }
+
+    async fn within<T>(&self, doing: &str, operation: impl std::future::Future<Output = redis::RedisResult<T>>) -> BackworksResult<T> {
+        match tokio::time::timeout(self.config.timeout(), operation).await {
+            Ok(result) => result.map_err(|e| BackworksError::plugin(format!("redis {}: {}", doing, e))),
+            Err(_) => Err(BackworksError::timeout(format!("redis {} took longer than {:?}", doing, self.config.timeout()))),
+        }
+    }
+
+    async fn run<T: FromRedisValue>(&self, command: &Cmd) -> BackworksResult<T> {
+        let mut connection = self.connection().await?;
+        self.within("command", command.query_async(&mut connection)).await
+    }
+
+    pub async fn ping(&self) -> BackworksResult<()> {
+        self.run::<String>(&redis::cmd("PING")).await.map(drop)
+    }
+
+    pub async fn get(&self, key: &str) -> BackworksResult<Option<Vec<u8>>> {
+        self.run(redis::cmd("GET").arg(key)).await
+    }
+
+    /// Store `value` under `key`, for `ttl` when given
+    pub async fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> BackworksResult<()> {
+        let mut command = redis::cmd("SET");
+        command.arg(key).arg(value);
+        if let Some(ttl) = ttl {
+            command.arg("PX").arg(ttl.as_millis().max(1) as u64);
+        }
+        self.run::<()>(&command).await
+    }
+
+    /// Whether there was a key to delete
+    pub async fn delete(&self, key: &str) -> BackworksResult<bool> {
+        self.run::<u64>(redis::cmd("DEL").arg(key)).await.map(|deleted| deleted > 0)
+    }
+
+    /// Delete every key starting with `prefix`; how many there were
+    pub async fn clear(&self, prefix: &str) -> BackworksResult<usize> {
+        let pattern = format!("{}*", escape_pattern(prefix));
+        let mut cursor: u64 = 0;
+        let mut cleared = 0;
+        loop {
+            let (next, keys): (u64, Vec<String>) = self.run(
+                redis::cmd("SCAN").arg(cursor).arg("MATCH").arg(&pattern).arg("COUNT").arg(CLEAR_BATCH)
+            ).await?;
+            for batch in keys.chunks(CLEAR_BATCH) {
+                cleared += self.run::<usize>(redis::cmd("DEL").arg(batch)).await?;
+            }
+            if next == 0 {
+                return Ok(cleared);
+            }
+            cursor = next;
+        }
+    }
+
+    /// How many subscribers received `message`
+    pub async fn publish(&self, channel: &str, message: &str) -> BackworksResult<u64> {
+        self.run(redis::cmd("PUBLISH").arg(channel).arg(message)).await
+    }
+
+    /// Messages of `channel`, or of the channels it matches when it has a `*`
+    pub async fn subscribe(&self, channel: &str) -> BackworksResult<impl Stream<Item = Msg>> {
+        let mut pubsub = self.within("subscribing", self.client.get_async_pubsub()).await?;
+        if channel.contains('*') {
+            self.within("subscribing", pubsub.psubscribe(channel)).await?;
+        } else {
+            self.within("subscribing", pubsub.subscribe(channel)).await?;
+        }
+        Ok(pubsub.into_on_message())
+    }
+}
+
+/// `text` matching only itself in a `SCAN` pattern
+pub fn escape_pattern(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
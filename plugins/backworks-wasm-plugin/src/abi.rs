@@ -0,0 +1,183 @@
+//! Host side of the filter ABI
+//!
+//! Modeled on proxy-wasm, reduced to what a body/header transform needs.
+//! A filter module exports its linear `memory` and
+//!
+//! - `bw_alloc(len: i32) -> i32`: buffer the host copies data into; only
+//!   needed by filters that read headers, bodies, properties or config
+//! - `bw_on_request() -> i32` and/or `bw_on_response() -> i32`: called once
+//!   per request or response; `0` continues, anything else ends the request
+//!   with a `403`
+//!
+//! It may import from the `backworks` module:
+//!
+//! - `bw_get_header(name_ptr, name_len, ret_ptr, ret_len) -> i32`
+//! - `bw_set_header(name_ptr, name_len, value_ptr, value_len) -> i32`
+//! - `bw_remove_header(name_ptr, name_len)`
+//! - `bw_get_body(ret_ptr, ret_len) -> i32`
+//! - `bw_set_body(ptr, len)`
+//! - `bw_get_property(name_ptr, name_len, ret_ptr, ret_len) -> i32`:
+//!   `method`, `path`, `query`, `endpoint`, `status` or `filter`
+//! - `bw_get_config(ret_ptr, ret_len) -> i32`: the filter's `config` as JSON
+//! - `bw_reject(status, msg_ptr, msg_len)`: end the request with `401` when
+//!   `status` is 401, `403` otherwise
+//! - `bw_log(level, ptr, len)`: `0` debug, `1` info, `2` warn, `3` error
+//!
+//! Getters allocate a buffer with `bw_alloc`, copy the value into it and
+//! store its address and length as little-endian `i32`s at `ret_ptr` and
+//! `ret_len`. They return `0` on success and `1` when there is no such value.
+//! Setters return `1` when the value is not a valid header.
+
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use std::collections::HashMap;
+use wasmi::{Caller, Error, Extern, Linker, Memory};
+
+pub const OK: i32 = 0;
+pub const NOT_FOUND: i32 = 1;
+pub const INVALID: i32 = 1;
+
+/// Everything a filter can read and change for one request or response
+pub struct HostState {
+    pub filter: String,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+    pub body_changed: bool,
+    pub properties: HashMap<&'static str, String>,
+    pub config: Vec<u8>,
+    pub rejection: Option<(u16, String)>,
+}
+
+impl HostState {
+    pub fn new(headers: HeaderMap, body: Vec<u8>, properties: HashMap<&'static str, String>) -> Self {
+        Self {
+            filter: String::new(),
+            headers,
+            body,
+            body_changed: false,
+            properties,
+            config: Vec::new(),
+            rejection: None,
+        }
+    }
+}
+
+fn memory(caller: &Caller<'_, HostState>) -> Result<Memory, Error> {
+    caller.get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| Error::new("filter does not export `memory`"))
+}
+
+fn read(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> Result<Vec<u8>, Error> {
+    let mut buffer = vec![0; len.max(0) as usize];
+    memory(caller)?.read(caller, ptr as u32 as usize, &mut buffer)?;
+    Ok(buffer)
+}
+
+fn read_string(caller: &Caller<'_, HostState>, ptr: i32, len: i32) -> Result<String, Error> {
+    String::from_utf8(read(caller, ptr, len)?).map_err(|_| Error::new("filter passed a string that is not UTF-8"))
+}
+
+/// Hand `value` to the guest: allocate with `bw_alloc`, copy, and report
+/// address and length through `ret_ptr` / `ret_len`
+fn give(caller: &mut Caller<'_, HostState>, value: &[u8], ret_ptr: i32, ret_len: i32) -> Result<i32, Error> {
+    let alloc = caller.get_export("bw_alloc")
+        .and_then(Extern::into_func)
+        .ok_or_else(|| Error::new("filter reads host data but does not export `bw_alloc`"))?
+        .typed::<i32, i32>(&*caller)?;
+    let len = i32::try_from(value.len()).map_err(|_| Error::new("value too large for the filter"))?;
+    let ptr = alloc.call(&mut *caller, len)?;
+
+    let memory = memory(caller)?;
+    memory.write(&mut *caller, ptr as u32 as usize, value)?;
+    memory.write(&mut *caller, ret_ptr as u32 as usize, &ptr.to_le_bytes())?;
+    memory.write(&mut *caller, ret_len as u32 as usize, &len.to_le_bytes())?;
+    Ok(OK)
+}
+
+/// Register the `backworks` host module
+pub fn link(linker: &mut Linker<HostState>) -> Result<(), Error> {
+    linker.func_wrap("backworks", "bw_get_header",
+        |mut caller: Caller<'_, HostState>, name_ptr: i32, name_len: i32, ret_ptr: i32, ret_len: i32| -> Result<i32, Error> {
+            let name = read_string(&caller, name_ptr, name_len)?;
+            match caller.data().headers.get(name.as_str()).map(|value| value.as_bytes().to_vec()) {
+                Some(value) => give(&mut caller, &value, ret_ptr, ret_len),
+                None => Ok(NOT_FOUND),
+            }
+        })?;
+
+    linker.func_wrap("backworks", "bw_set_header",
+        |mut caller: Caller<'_, HostState>, name_ptr: i32, name_len: i32, value_ptr: i32, value_len: i32| -> Result<i32, Error> {
+            let name = read(&caller, name_ptr, name_len)?;
+            let value = read(&caller, value_ptr, value_len)?;
+            match (HeaderName::from_bytes(&name), HeaderValue::from_bytes(&value)) {
+                (Ok(name), Ok(value)) => {
+                    caller.data_mut().headers.insert(name, value);
+                    Ok(OK)
+                }
+                _ => Ok(INVALID),
+            }
+        })?;
+
+    linker.func_wrap("backworks", "bw_remove_header",
+        |mut caller: Caller<'_, HostState>, name_ptr: i32, name_len: i32| -> Result<(), Error> {
+            let name = read_string(&caller, name_ptr, name_len)?;
+            caller.data_mut().headers.remove(name.as_str());
+            Ok(())
+        })?;
+
+    linker.func_wrap("backworks", "bw_get_body",
+        |mut caller: Caller<'_, HostState>, ret_ptr: i32, ret_len: i32| -> Result<i32, Error> {
+            let body = caller.data().body.clone();
+            give(&mut caller, &body, ret_ptr, ret_len)
+        })?;
+
+    linker.func_wrap("backworks", "bw_set_body",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> Result<(), Error> {
+            let body = read(&caller, ptr, len)?;
+            let state = caller.data_mut();
+            state.body = body;
+            state.body_changed = true;
+            Ok(())
+        })?;
+
+    linker.func_wrap("backworks", "bw_get_property",
+        |mut caller: Caller<'_, HostState>, name_ptr: i32, name_len: i32, ret_ptr: i32, ret_len: i32| -> Result<i32, Error> {
+            let name = read_string(&caller, name_ptr, name_len)?;
+            let value = match name.as_str() {
+                "filter" => Some(caller.data().filter.clone()),
+                name => caller.data().properties.get(name).cloned(),
+            };
+            match value {
+                Some(value) => give(&mut caller, value.as_bytes(), ret_ptr, ret_len),
+                None => Ok(NOT_FOUND),
+            }
+        })?;
+
+    linker.func_wrap("backworks", "bw_get_config",
+        |mut caller: Caller<'_, HostState>, ret_ptr: i32, ret_len: i32| -> Result<i32, Error> {
+            let config = caller.data().config.clone();
+            give(&mut caller, &config, ret_ptr, ret_len)
+        })?;
+
+    linker.func_wrap("backworks", "bw_reject",
+        |mut caller: Caller<'_, HostState>, status: i32, msg_ptr: i32, msg_len: i32| -> Result<(), Error> {
+            let message = read_string(&caller, msg_ptr, msg_len)?;
+            caller.data_mut().rejection = Some((u16::try_from(status).unwrap_or(403), message));
+            Ok(())
+        })?;
+
+    linker.func_wrap("backworks", "bw_log",
+        |caller: Caller<'_, HostState>, level: i32, ptr: i32, len: i32| -> Result<(), Error> {
+            let message = read_string(&caller, ptr, len)?;
+            let filter = caller.data().filter.as_str();
+            match level {
+                0 => tracing::debug!("wasm filter {}: {}", filter, message),
+                1 => tracing::info!("wasm filter {}: {}", filter, message),
+                2 => tracing::warn!("wasm filter {}: {}", filter, message),
+                _ => tracing::error!("wasm filter {}: {}", filter, message),
+            }
+            Ok(())
+        })?;
+
+    Ok(())
+}
@@ -0,0 +1,69 @@
+//! Configuration for the WASM filter plugin
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// WASM filter plugin configuration, read from the plugin's `config:` block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WasmPluginConfig {
+    /// Filters in the order they see requests; responses pass through them
+    /// in reverse
+    #[serde(default)]
+    pub filters: Vec<FilterConfig>,
+
+    /// Instructions a filter may execute per request or response before it
+    /// is stopped
+    #[serde(default = "default_fuel")]
+    pub fuel: u64,
+
+    /// Largest request or response body handed to filters; bigger bodies fail
+    /// the request
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+}
+
+/// One filter module and the endpoints it applies to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilterConfig {
+    /// Name used in logs and errors; defaults to the module's file name
+    pub name: Option<String>,
+
+    /// Path to the compiled `.wasm` module
+    pub module: String,
+
+    /// Endpoint names the filter runs for; empty runs it for every endpoint
+    #[serde(default)]
+    pub endpoints: Vec<String>,
+
+    /// Filter-specific configuration, handed to the module as JSON
+    #[serde(default)]
+    pub config: Value,
+}
+
+impl FilterConfig {
+    pub fn display_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            std::path::Path::new(&self.module)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| self.module.clone())
+        })
+    }
+
+    pub fn applies_to(&self, endpoint: &str) -> bool {
+        self.endpoints.is_empty() || self.endpoints.iter().any(|name| name == endpoint)
+    }
+}
+
+fn default_fuel() -> u64 { 10_000_000 }
+fn default_max_body_bytes() -> usize { 1024 * 1024 }
+
+impl Default for WasmPluginConfig {
+    fn default() -> Self {
+        Self {
+            filters: Vec::new(),
+            fuel: default_fuel(),
+            max_body_bytes: default_max_body_bytes(),
+        }
+    }
+}
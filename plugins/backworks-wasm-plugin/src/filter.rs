@@ -0,0 +1,109 @@
+//! Compiled filter modules and their execution
+
+use crate::abi::{self, HostState};
+use crate::config::FilterConfig;
+use backworks::error::{BackworksError, BackworksResult};
+use wasmi::core::TrapCode;
+use wasmi::{Config, Engine, Linker, Module, Store};
+
+/// Which side of the exchange a filter is called for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Request,
+    Response,
+}
+
+impl Phase {
+    fn all() -> impl Iterator<Item = Phase> {
+        [Phase::Request, Phase::Response].into_iter()
+    }
+
+    fn export(self) -> &'static str {
+        match self {
+            Phase::Request => "bw_on_request",
+            Phase::Response => "bw_on_response",
+        }
+    }
+}
+
+/// Interpreter and host functions shared by all filters
+pub struct FilterRuntime {
+    engine: Engine,
+    linker: Linker<HostState>,
+}
+
+impl FilterRuntime {
+    pub fn new() -> BackworksResult<Self> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let mut linker = Linker::new(&engine);
+        abi::link(&mut linker).map_err(|e| BackworksError::plugin(format!("wasm: {}", e)))?;
+        Ok(Self { engine, linker })
+    }
+}
+
+/// A compiled filter module and where it applies
+pub struct WasmFilter {
+    pub name: String,
+    pub config: FilterConfig,
+    module: Module,
+    config_json: Vec<u8>,
+}
+
+impl WasmFilter {
+    /// Compile the module named in `config`
+    pub fn load(runtime: &FilterRuntime, config: FilterConfig) -> BackworksResult<Self> {
+        let wasm = std::fs::read(&config.module).map_err(|e| {
+            BackworksError::PluginConfigInvalid(format!("wasm: cannot read filter module '{}': {}", config.module, e))
+        })?;
+        Self::from_bytes(runtime, config, &wasm)
+    }
+
+    pub fn from_bytes(runtime: &FilterRuntime, config: FilterConfig, wasm: &[u8]) -> BackworksResult<Self> {
+        let name = config.display_name();
+        let module = Module::new(&runtime.engine, wasm)
+            .map_err(|e| BackworksError::PluginConfigInvalid(format!("wasm: invalid filter module '{}': {}", name, e)))?;
+        if !Phase::all().any(|phase| module.get_export(phase.export()).is_some()) {
+            return Err(BackworksError::PluginConfigInvalid(format!(
+                "wasm: filter '{}' exports neither bw_on_request nor bw_on_response", name
+            )));
+        }
+        let config_json = serde_json::to_vec(&config.config)?;
+        Ok(Self { name, config, module, config_json })
+    }
+
+    pub fn handles(&self, phase: Phase) -> bool {
+        self.module.get_export(phase.export()).is_some()
+    }
+
+    /// Run the filter on a fresh instance, so no state leaks between requests
+    pub fn run(&self, runtime: &FilterRuntime, phase: Phase, mut state: HostState, fuel: u64) -> BackworksResult<HostState> {
+        state.filter = self.name.clone();
+        state.config = self.config_json.clone();
+
+        let mut store = Store::new(&runtime.engine, state);
+        store.set_fuel(fuel).map_err(|e| self.failure(e))?;
+        let instance = runtime.linker.instantiate(&mut store, &self.module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|e| self.failure(e))?;
+        let action = instance.get_typed_func::<(), i32>(&store, phase.export())
+            .and_then(|hook| hook.call(&mut store, ()))
+            .map_err(|e| match e.as_trap_code() {
+                Some(TrapCode::OutOfFuel) => BackworksError::plugin(format!(
+                    "wasm filter '{}' exceeded its fuel budget of {}", self.name, fuel
+                )),
+                _ => self.failure(e),
+            })?;
+
+        let mut state = store.into_data();
+        if action != 0 && state.rejection.is_none() {
+            state.rejection = Some((403, format!("rejected by filter '{}'", self.name)));
+        }
+        Ok(state)
+    }
+
+    fn failure(&self, error: impl std::fmt::Display) -> BackworksError {
+        BackworksError::plugin(format!("wasm filter '{}' failed: {}", self.name, error))
+    }
+}
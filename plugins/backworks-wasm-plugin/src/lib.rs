@@ -0,0 +1,174 @@
+//! # Backworks WASM Filter Plugin
+//!
+//! Runs request and response transforms written as WebAssembly filters, for
+//! changes the declarative `transform` options cannot express but that do
+//! not warrant a full runtime handler: redacting fields, re-signing bodies,
+//! normalizing headers, rejecting requests on custom rules.
+//!
+//! Filters are compiled once at startup and run on a fresh, fuel-limited
+//! instance for every request and response of the endpoints they are listed
+//! for. Requests pass through the filters in configuration order, responses
+//! in reverse. The host interface is described in [`abi`].
+//!
+//! ```yaml
+//! plugins:
+//!   wasm:
+//!     enabled: true
+//!     config:
+//!       fuel: 10000000          # instructions per filter call
+//!       max_body_bytes: 1048576
+//!       filters:
+//!         - module: "./filters/redact.wasm"
+//!           endpoints: ["users", "orders"]
+//!           config: { fields: ["ssn", "card_number"] }
+//! ```
+
+pub mod abi;
+pub mod config;
+pub mod filter;
+pub mod plugin;
+
+pub use config::{FilterConfig, WasmPluginConfig};
+pub use filter::{Phase, WasmFilter};
+pub use plugin::WasmFilterPlugin;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backworks::server::MatchedEndpoint;
+    use backworks::{BackworksError, BackworksPlugin};
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    /// Bump allocator shared by the test filters
+    const ALLOC: &str = r#"
+        (memory (export "memory") 1)
+        (global $heap (mut i32) (i32.const 4096))
+        (func (export "bw_alloc") (param $len i32) (result i32)
+            (global.get $heap)
+            (global.set $heap (i32.add (global.get $heap) (local.get $len))))
+    "#;
+
+    /// Request: tags the request and replaces its body.
+    /// Response: echoes the filter config as body and the status as header.
+    fn rewrite_filter() -> Vec<u8> {
+        wat::parse_str(format!(r#"(module
+            (import "backworks" "bw_set_header" (func $set_header (param i32 i32 i32 i32) (result i32)))
+            (import "backworks" "bw_set_body" (func $set_body (param i32 i32)))
+            (import "backworks" "bw_get_config" (func $get_config (param i32 i32) (result i32)))
+            (import "backworks" "bw_get_property" (func $get_property (param i32 i32 i32 i32) (result i32)))
+            {}
+            (data (i32.const 0) "x-filtered")
+            (data (i32.const 16) "yes")
+            (data (i32.const 32) "{{\"rewritten\":true}}")
+            (data (i32.const 64) "status")
+            (data (i32.const 80) "x-upstream-status")
+            (func (export "bw_on_request") (result i32)
+                (drop (call $set_header (i32.const 0) (i32.const 10) (i32.const 16) (i32.const 3)))
+                (call $set_body (i32.const 32) (i32.const 18))
+                (i32.const 0))
+            (func (export "bw_on_response") (result i32)
+                (drop (call $get_config (i32.const 1024) (i32.const 1028)))
+                (call $set_body (i32.load (i32.const 1024)) (i32.load (i32.const 1028)))
+                (drop (call $get_property (i32.const 64) (i32.const 6) (i32.const 1032) (i32.const 1036)))
+                (drop (call $set_header (i32.const 80) (i32.const 17) (i32.load (i32.const 1032)) (i32.load (i32.const 1036))))
+                (i32.const 0)))"#, ALLOC)).unwrap()
+    }
+
+    fn reject_filter() -> Vec<u8> {
+        wat::parse_str(r#"(module
+            (import "backworks" "bw_reject" (func $reject (param i32 i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "missing token")
+            (func (export "bw_on_request") (result i32)
+                (call $reject (i32.const 401) (i32.const 0) (i32.const 13))
+                (i32.const 1)))"#).unwrap()
+    }
+
+    fn spin_filter() -> Vec<u8> {
+        wat::parse_str(r#"(module
+            (func (export "bw_on_request") (result i32)
+                (loop $forever (br $forever))
+                (i32.const 0)))"#).unwrap()
+    }
+
+    async fn plugin(config: serde_json::Value, modules: &[(&str, Vec<u8>)]) -> WasmFilterPlugin {
+        let modules: HashMap<String, Vec<u8>> = modules.iter().map(|(name, wasm)| (name.to_string(), wasm.clone())).collect();
+        let plugin = WasmFilterPlugin::new();
+        plugin.initialize_with_modules(&config, &modules).await.unwrap();
+        plugin
+    }
+
+    fn request(endpoint: &str) -> axum::extract::Request {
+        let mut request = axum::http::Request::post("/users?page=2")
+            .header("content-length", "2")
+            .body(axum::body::Body::from("{}"))
+            .unwrap();
+        request.extensions_mut().insert(MatchedEndpoint(endpoint.to_string()));
+        request
+    }
+
+    async fn body_of(body: axum::body::Body) -> String {
+        String::from_utf8(axum::body::to_bytes(body, usize::MAX).await.unwrap().to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_filters_rewrite_requests_and_responses() {
+        let plugin = plugin(json!({
+            "filters": [{ "name": "rewrite", "module": "rewrite.wasm", "endpoints": ["users"], "config": { "mode": "test" } }]
+        }), &[("rewrite", rewrite_filter())]).await;
+
+        let mut filtered = request("users");
+        plugin.before_request(&mut filtered).await.unwrap();
+        assert_eq!(filtered.headers()["x-filtered"], "yes");
+        assert_eq!(filtered.headers()["content-length"], "18");
+        assert_eq!(body_of(std::mem::take(filtered.body_mut())).await, r#"{"rewritten":true}"#);
+
+        let mut response = axum::response::Response::new(axum::body::Body::from("original"));
+        *response.status_mut() = axum::http::StatusCode::CREATED;
+        response.extensions_mut().insert(MatchedEndpoint("users".to_string()));
+        plugin.after_response(&mut response).await.unwrap();
+        assert_eq!(response.headers()["x-upstream-status"], "201");
+        assert_eq!(body_of(response.into_body()).await, r#"{"mode":"test"}"#);
+
+        // Other endpoints pass through untouched
+        let mut other = request("orders");
+        plugin.before_request(&mut other).await.unwrap();
+        assert!(other.headers().get("x-filtered").is_none());
+        assert_eq!(body_of(std::mem::take(other.body_mut())).await, "{}");
+    }
+
+    #[tokio::test]
+    async fn test_filter_rejection_and_fuel_limit() {
+        let plugin = plugin(json!({
+            "fuel": 100000,
+            "filters": [
+                { "name": "reject", "module": "reject.wasm", "endpoints": ["users"] },
+                { "name": "spin", "module": "spin.wasm", "endpoints": ["orders"] }
+            ]
+        }), &[("reject", reject_filter()), ("spin", spin_filter())]).await;
+
+        let error = plugin.before_request(&mut request("users")).await.unwrap_err();
+        assert!(matches!(error, BackworksError::Unauthorized(ref message) if message == "missing token"));
+
+        let error = plugin.before_request(&mut request("orders")).await.unwrap_err();
+        assert!(error.to_string().contains("exceeded its fuel budget of 100000"));
+    }
+
+    #[tokio::test]
+    async fn test_modules_are_validated_at_startup() {
+        let error = WasmFilterPlugin::new()
+            .initialize(&json!({ "filters": [{ "module": "/nonexistent/filter.wasm" }] }))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("/nonexistent/filter.wasm"));
+
+        let no_hooks = wat::parse_str(r#"(module (memory (export "memory") 1))"#).unwrap();
+        let modules = HashMap::from([("empty".to_string(), no_hooks)]);
+        let error = WasmFilterPlugin::new()
+            .initialize_with_modules(&json!({ "filters": [{ "module": "empty.wasm" }] }), &modules)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("exports neither bw_on_request nor bw_on_response"));
+    }
+}
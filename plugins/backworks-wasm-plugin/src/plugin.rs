@@ -0,0 +1,225 @@
+//! Backworks plugin wiring for WASM filters
+
+use crate::abi::HostState;
+use crate::config::WasmPluginConfig;
+use crate::filter::{FilterRuntime, Phase, WasmFilter};
+use async_trait::async_trait;
+use axum::body::Body;
+use axum::http::{header, HeaderMap, HeaderValue};
+use axum::response::IntoResponse;
+use backworks::error::{BackworksError, BackworksResult};
+use backworks::plugin::{BackworksPlugin, HealthStatus, PluginHealth};
+use backworks::server::MatchedEndpoint;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+struct Filters {
+    config: WasmPluginConfig,
+    runtime: FilterRuntime,
+    filters: Vec<WasmFilter>,
+}
+
+impl Filters {
+    /// Filters of `endpoint` for `phase`, in the order they run
+    fn chain(&self, endpoint: &str, phase: Phase) -> Vec<usize> {
+        let mut chain: Vec<usize> = self.filters.iter()
+            .enumerate()
+            .filter(|(_, filter)| filter.config.applies_to(endpoint) && filter.handles(phase))
+            .map(|(index, _)| index)
+            .collect();
+        if phase == Phase::Response {
+            chain.reverse();
+        }
+        chain
+    }
+
+    /// Pass `state` through the filters in `chain`, stopping at the first
+    /// rejection
+    fn run(&self, chain: &[usize], phase: Phase, mut state: HostState) -> BackworksResult<HostState> {
+        for &index in chain {
+            let filter = &self.filters[index];
+            state = filter.run(&self.runtime, phase, state, self.config.fuel)?;
+            if let Some((status, ref message)) = state.rejection {
+                tracing::debug!("WASM filter '{}' rejected the {:?} with {}", filter.name, phase, status);
+                return Err(match status {
+                    401 => BackworksError::unauthorized(message),
+                    _ => BackworksError::forbidden(message),
+                });
+            }
+        }
+        Ok(state)
+    }
+}
+
+/// Runs WebAssembly filters over the requests and responses of selected
+/// endpoints
+pub struct WasmFilterPlugin {
+    filters: RwLock<Option<Arc<Filters>>>,
+}
+
+impl WasmFilterPlugin {
+    pub fn new() -> Self {
+        Self { filters: RwLock::new(None) }
+    }
+
+    async fn filters(&self) -> BackworksResult<Arc<Filters>> {
+        self.filters.read().await.clone()
+            .ok_or_else(|| BackworksError::plugin("WASM filter plugin is not initialized"))
+    }
+
+    /// Use already compiled modules instead of the files named in the
+    /// configuration, keyed by filter name
+    pub async fn initialize_with_modules(&self, config: &Value, modules: &HashMap<String, Vec<u8>>) -> BackworksResult<()> {
+        let config: WasmPluginConfig = serde_json::from_value(config.clone())
+            .map_err(|e| BackworksError::PluginConfigInvalid(format!("wasm: {}", e)))?;
+        let runtime = FilterRuntime::new()?;
+        let filters = config.filters.iter()
+            .map(|filter| match modules.get(&filter.display_name()) {
+                Some(wasm) => WasmFilter::from_bytes(&runtime, filter.clone(), wasm),
+                None => WasmFilter::load(&runtime, filter.clone()),
+            })
+            .collect::<BackworksResult<Vec<_>>>()?;
+
+        tracing::info!("Loaded {} WASM filter(s)", filters.len());
+        *self.filters.write().await = Some(Arc::new(Filters { config, runtime, filters }));
+        Ok(())
+    }
+
+    /// Run `chain` off the async runtime; filters are bounded by fuel, not
+    /// by yielding
+    async fn run(&self, filters: Arc<Filters>, chain: Vec<usize>, phase: Phase, state: HostState) -> BackworksResult<HostState> {
+        tokio::task::spawn_blocking(move || filters.run(&chain, phase, state))
+            .await
+            .map_err(|e| BackworksError::plugin(format!("WASM filter task failed: {}", e)))?
+    }
+}
+
+impl Default for WasmFilterPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl BackworksPlugin for WasmFilterPlugin {
+    fn name(&self) -> &str {
+        "wasm"
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn description(&self) -> &str {
+        "WebAssembly request and response transform filters"
+    }
+
+    // Failing filters must not let untransformed data through
+    fn is_critical(&self) -> bool {
+        true
+    }
+
+    // Execution is bounded by `fuel`; this only covers body buffering and
+    // scheduling
+    fn max_execution_time(&self) -> Duration {
+        Duration::from_secs(5)
+    }
+
+    async fn initialize(&self, config: &Value) -> BackworksResult<()> {
+        self.initialize_with_modules(config, &HashMap::new()).await
+    }
+
+    async fn shutdown(&self) -> BackworksResult<()> {
+        *self.filters.write().await = None;
+        Ok(())
+    }
+
+    async fn health_check(&self) -> BackworksResult<PluginHealth> {
+        let filters = self.filters().await?;
+        let details = filters.filters.iter()
+            .map(|filter| (filter.name.clone(), Value::String(filter.config.module.clone())))
+            .collect();
+        Ok(PluginHealth {
+            status: HealthStatus::Healthy,
+            message: format!("{} WASM filter(s) loaded", filters.filters.len()),
+            details,
+        })
+    }
+
+    async fn before_request(&self, request: &mut axum::extract::Request) -> BackworksResult<()> {
+        let Some(MatchedEndpoint(endpoint)) = request.extensions().get::<MatchedEndpoint>().cloned() else {
+            return Ok(());
+        };
+        let filters = self.filters().await?;
+        let chain = filters.chain(&endpoint, Phase::Request);
+        if chain.is_empty() {
+            return Ok(());
+        }
+
+        let body = std::mem::take(request.body_mut());
+        let body = read_body(body, filters.config.max_body_bytes, "request").await?;
+        let properties = HashMap::from([
+            ("method", request.method().to_string()),
+            ("path", request.uri().path().to_string()),
+            ("query", request.uri().query().unwrap_or_default().to_string()),
+            ("endpoint", endpoint),
+        ]);
+        let headers = std::mem::take(request.headers_mut());
+
+        let state = self.run(filters, chain, Phase::Request, HostState::new(headers, body, properties)).await?;
+        *request.headers_mut() = state.headers;
+        *request.body_mut() = rebuild_body(request.headers_mut(), state.body, state.body_changed);
+        Ok(())
+    }
+
+    async fn after_response(&self, response: &mut axum::response::Response) -> BackworksResult<()> {
+        let Some(MatchedEndpoint(endpoint)) = response.extensions().get::<MatchedEndpoint>().cloned() else {
+            return Ok(());
+        };
+        let filters = self.filters().await?;
+        let chain = filters.chain(&endpoint, Phase::Response);
+        if chain.is_empty() {
+            return Ok(());
+        }
+
+        let body = std::mem::take(response.body_mut());
+        let body = read_body(body, filters.config.max_body_bytes, "response").await?;
+        let properties = HashMap::from([
+            ("status", response.status().as_u16().to_string()),
+            ("endpoint", endpoint),
+        ]);
+        let headers = std::mem::take(response.headers_mut());
+
+        // The original response is gone; a failed filter must not pass on a
+        // half-transformed one
+        let state = match self.run(filters, chain, Phase::Response, HostState::new(headers, body, properties)).await {
+            Ok(state) => state,
+            Err(e) => {
+                let message = e.to_string();
+                *response = e.into_response();
+                return Err(BackworksError::plugin(message));
+            }
+        };
+        *response.headers_mut() = state.headers;
+        *response.body_mut() = rebuild_body(response.headers_mut(), state.body, state.body_changed);
+        Ok(())
+    }
+}
+
+async fn read_body(body: Body, limit: usize, side: &str) -> BackworksResult<Vec<u8>> {
+    axum::body::to_bytes(body, limit)
+        .await
+        .map(|bytes| bytes.to_vec())
+        .map_err(|e| BackworksError::plugin(format!("cannot buffer {} body for WASM filters: {}", side, e)))
+}
+
+/// The body to pass on; a changed body gets a matching `Content-Length`
+fn rebuild_body(headers: &mut HeaderMap, body: Vec<u8>, changed: bool) -> Body {
+    if changed {
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+    }
+    Body::from(body)
+}
@@ -0,0 +1,389 @@
+//! Runtime control of a running server
+//!
+//! The admin API lives under `/_backworks` on the API port, next to the
+//! state, journal, scenario, chaos, proxy target and purge routes. It is
+//! only served with an `admin.token`, and every one of those routes requires
+//! `Authorization: Bearer <token>`. The routes here disable and re-enable endpoints, start and stop
+//! capture sessions, page through the request log, flush middleware caches,
+//! report plugin health, reload external plugin libraries, follow and
+//! trigger [scheduled jobs](crate::schedule) and reload the blueprint
+//! without dropping connections.
+
+use crate::capture::{CaptureSession, RecordingStatus, RecordingSwitch};
+use crate::config::{load_yaml_config, validate_config, BackworksConfig, ExecutionMode};
+use crate::crud::CrudTables;
+use crate::error::{BackworksError, Result};
+use crate::balancer::LoadBalancerRegistry;
+use crate::pipeline::MiddlewareRegistry;
+use crate::plugin::{PluginHealth, PluginRoutes};
+use crate::request_log::RequestLogQuery;
+use crate::schedule::{RunTrigger, ScheduleStatus};
+use crate::server::{AppState, BackworksServer};
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use axum::Router;
+use ring::hmac;
+use ring::rand::SystemRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path as FilePath, PathBuf};
+use std::sync::{Arc, RwLock};
+use tower::ServiceExt;
+use tracing::info;
+
+/// Environment variable the CLI reads the admin token from
+pub const ADMIN_TOKEN_VAR: &str = "BACKWORKS_ADMIN_TOKEN";
+
+/// Endpoints switched off at runtime; requests to them get `503`
+#[derive(Clone, Default)]
+pub struct EndpointSwitches(Arc<RwLock<HashSet<String>>>);
+
+impl EndpointSwitches {
+    pub fn is_enabled(&self, endpoint: &str) -> bool {
+        !self.0.read().unwrap_or_else(|e| e.into_inner()).contains(endpoint)
+    }
+
+    pub fn set_enabled(&self, endpoint: &str, enabled: bool) {
+        let mut disabled = self.0.write().unwrap_or_else(|e| e.into_inner());
+        if enabled {
+            disabled.remove(endpoint);
+        } else {
+            disabled.insert(endpoint.to_string());
+        }
+    }
+}
+
+/// Route layer rejecting admin requests without the configured bearer token
+pub async fn require_token(State(token): State<Arc<str>>, request: Request, next: Next) -> Response {
+    let presented = request.headers().get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !presented.is_some_and(|presented| token_matches(presented, &token)) {
+        return BackworksError::unauthorized("The admin API requires a valid bearer token").into_response();
+    }
+    next.run(request).await
+}
+
+/// Compare a presented token with the expected one in constant time, by
+/// their HMACs under a throwaway key
+pub(crate) fn token_matches(presented: &str, expected: &str) -> bool {
+    let Ok(key) = hmac::Key::generate(hmac::HMAC_SHA256, &SystemRandom::new()) else {
+        return false;
+    };
+    hmac::verify(&key, presented.as_bytes(), hmac::sign(&key, expected.as_bytes()).as_ref()).is_ok()
+}
+
+fn error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(serde_json::json!({"error": message.into()}))).into_response()
+}
+
+/// An endpoint as the admin API lists it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EndpointStatus {
+    pub name: String,
+    pub path: String,
+    pub methods: Vec<String>,
+    pub mode: ExecutionMode,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EndpointSwitch {
+    pub enabled: bool,
+}
+
+fn endpoint_status(state: &AppState, name: &str) -> Option<EndpointStatus> {
+    let endpoint = state.config.endpoints.get(name)?;
+    Some(EndpointStatus {
+        name: name.to_string(),
+        path: endpoint.path.clone(),
+        methods: endpoint.methods.clone(),
+        mode: endpoint.primary_mode(&state.config.mode).clone(),
+        enabled: state.switches.is_enabled(name),
+    })
+}
+
+// Every endpoint of the blueprint, sorted by name
+pub(crate) async fn endpoints_handler(State(state): State<AppState>) -> Json<Vec<EndpointStatus>> {
+    let names: BTreeSet<&String> = state.config.endpoints.keys().collect();
+    Json(names.into_iter().filter_map(|name| endpoint_status(&state, name)).collect())
+}
+
+// Disable an endpoint, or enable it again
+pub(crate) async fn switch_endpoint_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(switch): Json<EndpointSwitch>,
+) -> Response {
+    if !state.config.endpoints.contains_key(&name) {
+        return error(StatusCode::NOT_FOUND, format!("Unknown endpoint '{}'", name));
+    }
+    state.switches.set_enabled(&name, switch.enabled);
+    info!("Endpoint '{}' {}", name, if switch.enabled { "enabled" } else { "disabled" });
+    Json(endpoint_status(&state, &name)).into_response()
+}
+
+// Whether requests are being captured, and into which session
+pub(crate) async fn capture_handler(State(state): State<AppState>) -> Response {
+    match state.capture {
+        Some(ref capture) => Json(capture.recording_status().await).into_response(),
+        None => error(StatusCode::NOT_FOUND, "Capture is disabled"),
+    }
+}
+
+// Start, resume or stop recording
+pub(crate) async fn switch_capture_handler(
+    State(state): State<AppState>,
+    Json(switch): Json<RecordingSwitch>,
+) -> Response {
+    let Some(ref capture) = state.capture else {
+        return error(StatusCode::NOT_FOUND, "Capture is disabled");
+    };
+    match capture.switch_recording(switch, "admin").await {
+        Ok(status) => {
+            info!("Capture {}", if status.recording { "recording" } else { "stopped" });
+            Json::<RecordingStatus>(status).into_response()
+        }
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+// Capture sessions, oldest first
+pub(crate) async fn capture_sessions_handler(State(state): State<AppState>) -> Response {
+    let Some(ref capture) = state.capture else {
+        return error(StatusCode::NOT_FOUND, "Capture is disabled");
+    };
+    let mut sessions: Vec<CaptureSession> = capture.get_sessions().await;
+    sessions.sort_by_key(|session| session.started_at);
+    Json(sessions).into_response()
+}
+
+// Recent requests, newest first, as the dashboard's request log pages them
+pub(crate) async fn requests_handler(State(state): State<AppState>, Query(query): Query<RequestLogQuery>) -> Response {
+    let Some(ref dashboard) = state.dashboard else {
+        return error(StatusCode::NOT_FOUND, "The request log is kept by the dashboard, which is disabled");
+    };
+    match query.filter() {
+        Ok(filter) => Json(dashboard.requests().page(&query, &filter)).into_response(),
+        Err(e) => error(StatusCode::BAD_REQUEST, e.to_string()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct FlushQuery {
+    endpoint: Option<String>,
+}
+
+// Drop cached responses of one endpoint's middleware, or of every endpoint's
+pub(crate) async fn flush_caches_handler(
+    State(state): State<AppState>,
+    Query(query): Query<FlushQuery>,
+) -> Response {
+    if let Some(ref endpoint) = query.endpoint {
+        if !state.config.endpoints.contains_key(endpoint) {
+            return error(StatusCode::NOT_FOUND, format!("Unknown endpoint '{}'", endpoint));
+        }
+    }
+    let flushed = state.pipelines.flush(query.endpoint.as_deref()).await;
+    info!("Flushed {} cached entries", flushed);
+    Json(serde_json::json!({"flushed": flushed})).into_response()
+}
+
+// Health of every registered plugin
+pub(crate) async fn plugins_handler(State(state): State<AppState>) -> Json<HashMap<String, PluginHealth>> {
+    Json(state.plugin_manager.get_all_plugin_health().await)
+}
+
+// Load an external plugin's library again, without restarting
+pub(crate) async fn reload_plugin_handler(State(state): State<AppState>, Path(name): Path<String>) -> Response {
+    if !state.plugin_manager.list_plugins().await.contains(&name) {
+        return error(StatusCode::NOT_FOUND, format!("Unknown plugin '{}'", name));
+    }
+    match state.plugin_manager.reload_plugin(&name).await {
+        Ok(()) => match state.plugin_manager.get_plugin_health(&name).await {
+            Some(health) => Json(serde_json::json!({"plugin": name, "health": health})).into_response(),
+            None => Json(serde_json::json!({"plugin": name})).into_response(),
+        },
+        Err(e) => error(StatusCode::UNPROCESSABLE_ENTITY, e.to_string()),
+    }
+}
+
+// Every schedule with its next and last run, sorted by name
+pub(crate) async fn schedules_handler(State(state): State<AppState>) -> Json<Vec<ScheduleStatus>> {
+    Json(state.schedules.statuses())
+}
+
+// The kept runs of a schedule, newest first
+pub(crate) async fn schedule_runs_handler(State(state): State<AppState>, Path(name): Path<String>) -> Response {
+    match state.schedules.runs(&name) {
+        Some(runs) => Json(runs).into_response(),
+        None => error(StatusCode::NOT_FOUND, format!("Unknown schedule '{}'", name)),
+    }
+}
+
+// Run a schedule now, enabled or not, and return the finished run
+pub(crate) async fn run_schedule_handler(State(state): State<AppState>, Path(name): Path<String>) -> Response {
+    match state.schedules.run(&state, &name, RunTrigger::Manual).await {
+        Some(run) => {
+            info!("Ran schedule '{}' on demand: {:?}", name, run.status);
+            Json(run).into_response()
+        }
+        None => error(StatusCode::NOT_FOUND, format!("Unknown schedule '{}'", name)),
+    }
+}
+
+// Load the blueprint again and serve its endpoints
+pub(crate) async fn reload_handler(State(state): State<AppState>) -> Response {
+    let Some(ref reloader) = state.reloader else {
+        return error(StatusCode::NOT_FOUND, "The server was not started from a blueprint file");
+    };
+    match reloader.reload().await {
+        Ok(summary) => {
+            info!("🔄 Reloaded {} endpoint(s): {} added, {} removed", summary.endpoints, summary.added.len(), summary.removed.len());
+            Json(summary).into_response()
+        }
+        Err(e) => error(StatusCode::UNPROCESSABLE_ENTITY, e.to_string()),
+    }
+}
+
+/// The router requests are currently served by; reloads swap it while
+/// connections stay open
+#[derive(Clone, Default)]
+pub struct LiveRouter(Arc<RwLock<Router>>);
+
+impl LiveRouter {
+    pub fn replace(&self, router: Router) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = router;
+    }
+
+    /// A router passing every request to the current router
+    pub fn service(&self) -> Router {
+        let live = self.clone();
+        Router::new().fallback_service(tower::service_fn(move |request: Request| {
+            let router = live.0.read().unwrap_or_else(|e| e.into_inner()).clone();
+            router.oneshot(request)
+        }))
+    }
+}
+
+/// Counts reported after a reload
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReloadSummary {
+    pub endpoints: usize,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Rebuilds the server's routes from its blueprint file
+pub struct Reloader {
+    source: PathBuf,
+    middleware: MiddlewareRegistry,
+    load_balancers: LoadBalancerRegistry,
+    plugin_routes: Vec<(String, PluginRoutes)>,
+    router: LiveRouter,
+    /// State of the server requests are routed to, carried over by reloads
+    current: RwLock<Option<AppState>>,
+    reloading: tokio::sync::Mutex<()>,
+}
+
+impl Reloader {
+    pub fn new(source: PathBuf, middleware: MiddlewareRegistry, load_balancers: LoadBalancerRegistry) -> Self {
+        Self {
+            source,
+            middleware,
+            load_balancers,
+            plugin_routes: Vec::new(),
+            router: LiveRouter::default(),
+            current: RwLock::new(None),
+            reloading: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Serve the routes plugins add on every reloaded server too
+    pub fn with_plugin_routes(mut self, routes: Vec<(String, PluginRoutes)>) -> Self {
+        self.plugin_routes = routes;
+        self
+    }
+
+    /// The blueprint file reloads read
+    pub fn source(&self) -> &FilePath {
+        &self.source
+    }
+
+    /// Blueprint of the server requests are routed to, once it serves
+    pub fn config(&self) -> Option<Arc<BackworksConfig>> {
+        self.current.read().unwrap_or_else(|e| e.into_inner()).as_ref().map(|state| state.config.clone())
+    }
+
+    /// Route new requests to `router`, which serves `state`
+    pub(crate) fn serve(&self, state: AppState, router: Router) {
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Some(state);
+        self.router.replace(router);
+    }
+
+    /// A router passing every request to the router served last
+    pub(crate) fn service(&self) -> Router {
+        self.router.service()
+    }
+
+    /// Load and validate the blueprint, then route new requests to a server
+    /// built from it. Runtime state, such as the state store, statistics,
+    /// capture sessions, the active scenario and disabled endpoints, carries
+    /// over; the listener keeps its address.
+    pub async fn reload(&self) -> Result<ReloadSummary> {
+        let _reloading = self.reloading.lock().await;
+        let state = self.current.read().unwrap_or_else(|e| e.into_inner()).clone()
+            .ok_or_else(|| BackworksError::unavailable("The API server is not running"))?;
+        let mut config = load_yaml_config(&self.source).await?;
+        validate_config(&config)?;
+        config.server.host = state.config.server.host.clone();
+        config.server.port = state.config.server.port;
+
+        let plugin_configs = config.plugins.iter()
+            .filter(|(_, plugin)| plugin.enabled)
+            .map(|(name, plugin)| (name.clone(), plugin.config.clone()))
+            .collect();
+        state.plugin_manager.reload_configs(plugin_configs).await?;
+        let policies = config.plugins.iter()
+            .filter(|(_, plugin)| plugin.enabled)
+            .map(|(name, plugin)| (name.clone(), plugin.resilience.clone()))
+            .collect();
+        state.plugin_manager.apply_resilience_policies(policies).await;
+
+        let before: BTreeSet<&String> = state.config.endpoints.keys().collect();
+        let after: BTreeSet<&String> = config.endpoints.keys().collect();
+        let summary = ReloadSummary {
+            endpoints: after.len(),
+            added: after.difference(&before).map(|name| name.to_string()).collect(),
+            removed: before.difference(&after).map(|name| name.to_string()).collect(),
+        };
+
+        let crud = CrudTables::load(&config, &state.plugin_manager).await?;
+        BackworksServer::succeeding(config, &state)?
+            .with_crud_tables(crud)
+            .with_middleware(self.middleware.clone())
+            .with_load_balancers(self.load_balancers.clone())
+            .with_plugin_routes(self.plugin_routes.clone())
+            .serve_through(self)?;
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_switches_are_shared_between_clones() {
+        let switches = EndpointSwitches::default();
+        let shared = switches.clone();
+        assert!(switches.is_enabled("users"));
+        shared.set_enabled("users", false);
+        assert!(!switches.is_enabled("users"));
+        assert!(switches.is_enabled("orders"));
+        switches.set_enabled("users", true);
+        assert!(shared.is_enabled("users"));
+    }
+}
@@ -0,0 +1,539 @@
+//! Alerting engine
+//!
+//! Evaluates the `monitoring.alerts` rules against rolling request statistics
+//! and plugin health, and delivers firing/resolved notifications to webhook,
+//! Slack and SMTP channels.
+
+use crate::config::{parse_duration, AlertChannelConfig, AlertRuleConfig, AlertsConfig};
+use crate::error::{BackworksError, BackworksResult};
+use crate::plugin::{HealthStatus, PluginManager};
+use crate::stats::{RequestStats, StatsSnapshot};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(30);
+const DEFAULT_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// Metric an alert condition is evaluated against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertMetric {
+    /// Fraction of 5xx responses in the window
+    ErrorRate,
+    AvgLatency,
+    P50Latency,
+    P95Latency,
+    P99Latency,
+    /// Number of requests in the window
+    RequestCount,
+    /// Number of plugins currently reporting an unhealthy status
+    HealthCheckFailures,
+}
+
+impl AlertMetric {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "error_rate" => Some(Self::ErrorRate),
+            "avg_latency" | "latency" => Some(Self::AvgLatency),
+            "p50_latency" => Some(Self::P50Latency),
+            "p95_latency" => Some(Self::P95Latency),
+            "p99_latency" => Some(Self::P99Latency),
+            "request_count" | "requests" => Some(Self::RequestCount),
+            "health_check_failures" => Some(Self::HealthCheckFailures),
+            _ => None,
+        }
+    }
+
+    fn is_latency(self) -> bool {
+        matches!(self, Self::AvgLatency | Self::P50Latency | Self::P95Latency | Self::P99Latency)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+}
+
+impl Comparison {
+    fn parse(op: &str) -> Option<Self> {
+        match op {
+            ">" => Some(Self::Greater),
+            ">=" => Some(Self::GreaterOrEqual),
+            "<" => Some(Self::Less),
+            "<=" => Some(Self::LessOrEqual),
+            "==" | "=" => Some(Self::Equal),
+            _ => None,
+        }
+    }
+
+    fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Self::Greater => value > threshold,
+            Self::GreaterOrEqual => value >= threshold,
+            Self::Less => value < threshold,
+            Self::LessOrEqual => value <= threshold,
+            Self::Equal => (value - threshold).abs() < f64::EPSILON,
+        }
+    }
+}
+
+/// Parsed form of a rule condition such as `error_rate > 5%` or
+/// `p95_latency > 500ms`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertCondition {
+    pub metric: AlertMetric,
+    pub comparison: Comparison,
+    /// Threshold in the metric's unit: a fraction for error rate,
+    /// milliseconds for latencies, a count otherwise
+    pub threshold: f64,
+}
+
+impl AlertCondition {
+    pub fn parse(condition: &str) -> BackworksResult<Self> {
+        let parts: Vec<&str> = condition.split_whitespace().collect();
+        let [metric, op, threshold] = parts.as_slice() else {
+            return Err(BackworksError::config(format!(
+                "Invalid alert condition '{}': expected '<metric> <operator> <threshold>'",
+                condition
+            )));
+        };
+
+        let metric = AlertMetric::parse(metric).ok_or_else(|| {
+            BackworksError::config(format!("Unknown alert metric '{}' in condition '{}'", metric, condition))
+        })?;
+        let comparison = Comparison::parse(op).ok_or_else(|| {
+            BackworksError::config(format!("Unknown operator '{}' in condition '{}'", op, condition))
+        })?;
+        let threshold = Self::parse_threshold(metric, threshold).ok_or_else(|| {
+            BackworksError::config(format!("Invalid threshold '{}' in condition '{}'", threshold, condition))
+        })?;
+
+        Ok(Self { metric, comparison, threshold })
+    }
+
+    fn parse_threshold(metric: AlertMetric, raw: &str) -> Option<f64> {
+        if let Some(percent) = raw.strip_suffix('%') {
+            return percent.parse::<f64>().ok().map(|p| p / 100.0);
+        }
+        if metric.is_latency() && raw.ends_with(|c: char| c.is_ascii_alphabetic()) {
+            return parse_duration(raw).ok().map(|d| d.as_secs_f64() * 1000.0);
+        }
+        raw.parse().ok()
+    }
+
+    pub fn holds(&self, value: f64) -> bool {
+        self.comparison.holds(value, self.threshold)
+    }
+}
+
+/// Observed values for every metric at one evaluation tick
+#[derive(Debug, Clone, Default)]
+pub struct MetricValues {
+    pub stats: StatsSnapshot,
+    pub health_check_failures: u64,
+}
+
+impl MetricValues {
+    pub fn get(&self, metric: AlertMetric) -> f64 {
+        match metric {
+            AlertMetric::ErrorRate => self.stats.error_rate,
+            AlertMetric::AvgLatency => self.stats.avg_latency_ms,
+            AlertMetric::P50Latency => self.stats.p50_latency_ms,
+            AlertMetric::P95Latency => self.stats.p95_latency_ms,
+            AlertMetric::P99Latency => self.stats.p99_latency_ms,
+            AlertMetric::RequestCount => self.stats.requests as f64,
+            AlertMetric::HealthCheckFailures => self.health_check_failures as f64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Firing,
+    Resolved,
+}
+
+/// Notification payload delivered to channels
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertEvent {
+    pub rule: String,
+    pub state: AlertState,
+    pub condition: String,
+    pub value: f64,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl AlertEvent {
+    pub fn summary(&self) -> String {
+        match self.state {
+            AlertState::Firing => format!("🚨 Alert '{}' firing: {} (current value {:.4})", self.rule, self.condition, self.value),
+            AlertState::Resolved => format!("✅ Alert '{}' resolved: {} (current value {:.4})", self.rule, self.condition, self.value),
+        }
+    }
+}
+
+/// Tracks one rule's pending/firing state across evaluations
+#[derive(Debug)]
+pub struct RuleEvaluator {
+    rule: AlertRuleConfig,
+    condition: AlertCondition,
+    hold_for: Duration,
+    pending_since: Option<Instant>,
+    firing: bool,
+}
+
+impl RuleEvaluator {
+    pub fn new(rule: AlertRuleConfig) -> BackworksResult<Self> {
+        let condition = AlertCondition::parse(&rule.condition)?;
+        let hold_for = match rule.duration {
+            Some(ref duration) => parse_duration(duration)?,
+            None => Duration::ZERO,
+        };
+
+        Ok(Self { rule, condition, hold_for, pending_since: None, firing: false })
+    }
+
+    /// Feed the current values; returns an event when the rule changes state
+    pub fn observe(&mut self, values: &MetricValues, now: Instant) -> Option<AlertEvent> {
+        let value = values.get(self.condition.metric);
+
+        let state = if self.condition.holds(value) {
+            let since = *self.pending_since.get_or_insert(now);
+            if self.firing || now.saturating_duration_since(since) < self.hold_for {
+                return None;
+            }
+            self.firing = true;
+            AlertState::Firing
+        } else {
+            self.pending_since = None;
+            if !self.firing {
+                return None;
+            }
+            self.firing = false;
+            AlertState::Resolved
+        };
+
+        Some(AlertEvent {
+            rule: self.rule.name.clone(),
+            state,
+            condition: self.rule.condition.clone(),
+            value,
+            timestamp: Utc::now(),
+        })
+    }
+
+    pub fn channels(&self) -> &[String] {
+        &self.rule.channels
+    }
+}
+
+/// Validate alert rules and channel references at config load time
+pub fn validate_alerts(config: &AlertsConfig) -> BackworksResult<()> {
+    if let Some(ref interval) = config.interval {
+        parse_duration(interval)?;
+    }
+    if let Some(ref window) = config.window {
+        parse_duration(window)?;
+    }
+
+    let channels = config.channels.clone().unwrap_or_default();
+    for rule in config.rules.iter().flatten() {
+        RuleEvaluator::new(rule.clone())?;
+        for channel in &rule.channels {
+            if !channels.contains_key(channel) {
+                return Err(BackworksError::config(format!(
+                    "Alert rule '{}' references unknown channel '{}'",
+                    rule.name, channel
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub struct AlertEngine {
+    interval: Duration,
+    window: Duration,
+    evaluators: Vec<RuleEvaluator>,
+    channels: HashMap<String, AlertChannelConfig>,
+    stats: RequestStats,
+    plugin_manager: PluginManager,
+    client: reqwest::Client,
+}
+
+impl AlertEngine {
+    pub fn new(config: &AlertsConfig, stats: RequestStats, plugin_manager: PluginManager) -> BackworksResult<Self> {
+        validate_alerts(config)?;
+
+        let interval = config.interval.as_deref().map(parse_duration).transpose()?.unwrap_or(DEFAULT_INTERVAL);
+        let window = config.window.as_deref().map(parse_duration).transpose()?.unwrap_or(DEFAULT_WINDOW);
+        let evaluators = config.rules.iter().flatten()
+            .cloned()
+            .map(RuleEvaluator::new)
+            .collect::<BackworksResult<Vec<_>>>()?;
+
+        Ok(Self {
+            interval,
+            window,
+            evaluators,
+            channels: config.channels.clone().unwrap_or_default(),
+            stats,
+            plugin_manager,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    /// Run the evaluation loop in the background
+    pub fn spawn(mut self) -> JoinHandle<()> {
+        info!("🚨 Alerting engine started with {} rule(s), evaluating every {:?}", self.evaluators.len(), self.interval);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            loop {
+                ticker.tick().await;
+                self.evaluate().await;
+            }
+        })
+    }
+
+    async fn evaluate(&mut self) {
+        let values = self.collect().await;
+        let now = Instant::now();
+
+        let mut events = Vec::new();
+        for evaluator in &mut self.evaluators {
+            if let Some(event) = evaluator.observe(&values, now) {
+                events.push((event, evaluator.channels().to_vec()));
+            }
+        }
+
+        for (event, channels) in events {
+            warn!("{}", event.summary());
+            for channel in channels {
+                if let Err(e) = self.deliver(&channel, &event).await {
+                    error!("Failed to deliver alert '{}' to channel '{}': {}", event.rule, channel, e);
+                }
+            }
+        }
+    }
+
+    async fn collect(&self) -> MetricValues {
+        let health_check_failures = self.plugin_manager.get_all_plugin_health().await
+            .values()
+            .filter(|health| health.status == HealthStatus::Unhealthy)
+            .count() as u64;
+
+        MetricValues {
+            stats: self.stats.snapshot(self.window).await,
+            health_check_failures,
+        }
+    }
+
+    async fn deliver(&self, channel_name: &str, event: &AlertEvent) -> BackworksResult<()> {
+        let channel = self.channels.get(channel_name)
+            .ok_or_else(|| BackworksError::config(format!("Unknown alert channel '{}'", channel_name)))?;
+
+        if channel.smtp_host.is_some() {
+            send_email(channel, event).await
+        } else {
+            self.send_webhook(channel, event).await
+        }
+    }
+
+    async fn send_webhook(&self, channel: &AlertChannelConfig, event: &AlertEvent) -> BackworksResult<()> {
+        let url = match (&channel.webhook_url, &channel.webhook_url_env) {
+            (Some(url), _) => url.clone(),
+            (None, Some(var)) => std::env::var(var)
+                .map_err(|_| BackworksError::config(format!("Alert webhook environment variable '{}' is not set", var)))?,
+            (None, None) => return Err(BackworksError::config("Alert channel has no webhook_url, webhook_url_env or smtp_host")),
+        };
+
+        // Slack incoming webhooks expect a `text` payload; other receivers get the raw event
+        let payload = if channel.channel.is_some() || url.contains("hooks.slack.com") {
+            serde_json::json!({ "text": event.summary(), "channel": channel.channel })
+        } else {
+            serde_json::to_value(event)?
+        };
+
+        let response = self.client.post(&url).json(&payload).send().await?;
+        if !response.status().is_success() {
+            return Err(BackworksError::http(format!("Webhook responded with status {}", response.status())));
+        }
+        Ok(())
+    }
+}
+
+async fn send_email(channel: &AlertChannelConfig, event: &AlertEvent) -> BackworksResult<()> {
+    let recipients = channel.to.clone().unwrap_or_default();
+    if recipients.is_empty() {
+        return Err(BackworksError::config("Email alert channel requires at least one 'to' address"));
+    }
+    let mail = Mail {
+        to: recipients,
+        subject: event.summary(),
+        text: Some(serde_json::to_string_pretty(event)?),
+        ..Default::default()
+    };
+    send_mail(channel, &mail).await
+}
+
+/// An email to send through the SMTP settings of an alert channel
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Mail {
+    /// Sender; the channel's `from`, or `backworks@<smtp_host>`, when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    pub to: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cc: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bcc: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+    pub subject: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html: Option<String>,
+}
+
+/// Send `mail` through the SMTP server of `channel`: implicit TLS on port
+/// 465, STARTTLS otherwise, with the credentials its `username_env` and
+/// `password_env` name
+pub async fn send_mail(channel: &AlertChannelConfig, mail: &Mail) -> BackworksResult<()> {
+    use lettre::message::{Mailbox, MultiPart, SinglePart};
+    use lettre::transport::smtp::authentication::Credentials;
+    use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+    let host = channel.smtp_host.as_deref()
+        .ok_or_else(|| BackworksError::config("Sending email requires an smtp_host"))?;
+    if mail.to.is_empty() {
+        return Err(BackworksError::config("Email requires at least one 'to' address"));
+    }
+
+    let parse_mailbox = |address: &str| -> BackworksResult<Mailbox> {
+        address.parse().map_err(|e| BackworksError::config(format!("Invalid email address '{}': {}", address, e)))
+    };
+
+    let from = mail.from.clone().or_else(|| channel.from.clone()).unwrap_or_else(|| format!("backworks@{}", host));
+    let mut builder = Message::builder()
+        .from(parse_mailbox(&from)?)
+        .subject(mail.subject.as_str());
+    for recipient in &mail.to {
+        builder = builder.to(parse_mailbox(recipient)?);
+    }
+    for recipient in &mail.cc {
+        builder = builder.cc(parse_mailbox(recipient)?);
+    }
+    for recipient in &mail.bcc {
+        builder = builder.bcc(parse_mailbox(recipient)?);
+    }
+    if let Some(ref reply_to) = mail.reply_to {
+        builder = builder.reply_to(parse_mailbox(reply_to)?);
+    }
+    let message = match (&mail.text, &mail.html) {
+        (Some(text), Some(html)) => builder.multipart(MultiPart::alternative_plain_html(text.clone(), html.clone())),
+        (None, Some(html)) => builder.singlepart(SinglePart::html(html.clone())),
+        (text, None) => builder.body(text.clone().unwrap_or_default()),
+    }
+    .map_err(|e| BackworksError::config(format!("Failed to build email: {}", e)))?;
+
+    let port = channel.smtp_port.unwrap_or(587);
+    let transport = if port == 465 {
+        AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+    } else {
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+    }
+    .map_err(|e| BackworksError::http(format!("SMTP setup failed: {}", e)))?
+    .port(port);
+
+    let transport = match (&channel.username_env, &channel.password_env) {
+        (Some(user_var), Some(pass_var)) => {
+            let username = std::env::var(user_var).unwrap_or_default();
+            let password = std::env::var(pass_var).unwrap_or_default();
+            transport.credentials(Credentials::new(username, password))
+        }
+        _ => transport,
+    };
+
+    transport.build().send(message).await
+        .map_err(|e| BackworksError::http(format!("SMTP delivery failed: {}", e)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(condition: &str, duration: Option<&str>) -> AlertRuleConfig {
+        AlertRuleConfig {
+            name: "test".to_string(),
+            condition: condition.to_string(),
+            duration: duration.map(String::from),
+            channels: vec![],
+        }
+    }
+
+    fn values(error_rate: f64) -> MetricValues {
+        MetricValues {
+            stats: StatsSnapshot { error_rate, ..Default::default() },
+            health_check_failures: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_conditions() {
+        let condition = AlertCondition::parse("error_rate > 5%").unwrap();
+        assert_eq!(condition.metric, AlertMetric::ErrorRate);
+        assert!((condition.threshold - 0.05).abs() < 1e-9);
+
+        let condition = AlertCondition::parse("p95_latency >= 1s").unwrap();
+        assert_eq!(condition.threshold, 1000.0);
+        assert!(condition.holds(1000.0));
+
+        let condition = AlertCondition::parse("health_check_failures > 0").unwrap();
+        assert!(condition.holds(1.0));
+
+        assert!(AlertCondition::parse("error_rate above 5%").is_err());
+        assert!(AlertCondition::parse("cpu > 5").is_err());
+        assert!(AlertCondition::parse("error_rate > lots").is_err());
+    }
+
+    #[test]
+    fn test_rule_fires_after_duration_and_resolves() {
+        let mut evaluator = RuleEvaluator::new(rule("error_rate > 0.1", Some("1m"))).unwrap();
+        let start = Instant::now();
+
+        assert!(evaluator.observe(&values(0.5), start).is_none());
+        assert!(evaluator.observe(&values(0.5), start + Duration::from_secs(30)).is_none());
+
+        let fired = evaluator.observe(&values(0.5), start + Duration::from_secs(61)).unwrap();
+        assert_eq!(fired.state, AlertState::Firing);
+        assert!(evaluator.observe(&values(0.5), start + Duration::from_secs(90)).is_none());
+
+        let resolved = evaluator.observe(&values(0.0), start + Duration::from_secs(120)).unwrap();
+        assert_eq!(resolved.state, AlertState::Resolved);
+        assert!(evaluator.observe(&values(0.0), start + Duration::from_secs(150)).is_none());
+    }
+
+    #[test]
+    fn test_validate_rejects_unknown_channel() {
+        let config = AlertsConfig {
+            enabled: Some(true),
+            interval: None,
+            window: None,
+            channels: None,
+            rules: Some(vec![AlertRuleConfig { channels: vec!["slack".to_string()], ..rule("error_rate > 1%", None) }]),
+        };
+        assert!(validate_alerts(&config).is_err());
+    }
+}
@@ -1,10 +1,33 @@
-use crate::config::BackworksConfig;
-use crate::error::BackworksResult;
+//! Blueprint linting for `backworks analyze`
+//!
+//! Runs every check over a loaded blueprint and collects the findings in one
+//! report instead of stopping at the first problem: parse warnings, routes
+//! that collide or shadow each other, endpoints nothing can serve, plugins
+//! nothing uses, unauthenticated write endpoints and inline handlers that
+//! have outgrown the blueprint. Findings point at the file, line and column
+//! of the endpoint they concern when the blueprint was read from disk.
+
+use crate::blueprint::ResolvedBlueprint;
+use crate::config::{BackworksConfig, EndpointConfig, ExecutionMode};
+use crate::diagnostics::{Diagnostic, Location, Locator, Segment};
+use crate::error::{BackworksError, BackworksResult};
+use crate::routes::{find_conflicts, Overlap};
+use crate::usage::UsageReport;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fmt;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write};
+use std::path::Path;
 use tracing::info;
 
+/// Inline handlers longer than this belong in their own file
+const MAX_INLINE_HANDLER_LINES: usize = 40;
+
+/// Methods that change state and should not be reachable anonymously
+const MUTATING_METHODS: &[&str] = &["POST", "PUT", "PATCH", "DELETE"];
+
+/// Plugin names that indicate request authentication
+const AUTH_PLUGIN_HINTS: &[&str] = &["auth", "jwt", "ldap", "oauth", "oidc", "api_key", "apikey"];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnalysisReport {
     pub blueprint_path: String,
@@ -23,10 +46,13 @@ pub enum AnalysisStatus {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Default)]
 pub struct AnalysisSummary {
     pub endpoints: usize,
     pub runtime_endpoints: usize,
     pub database_endpoints: usize,
+    #[serde(default)]
+    pub plugin_endpoints: usize,
     pub transformations: usize,
     pub potential_conflicts: usize,
 }
@@ -40,7 +66,7 @@ pub struct AnalysisIssue {
     pub help: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum IssueSeverity {
     Error,
     Warning,
@@ -61,11 +87,35 @@ pub enum IssueCategory {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IssueLocation {
     pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
     pub line: Option<usize>,
     pub column: Option<usize>,
     pub context: Option<String>,
 }
 
+impl IssueLocation {
+    fn at(path: impl Into<String>) -> Self {
+        Self { path: path.into(), file: None, line: None, column: None, context: None }
+    }
+
+    fn with_context(mut self, context: impl Into<String>) -> Self {
+        self.context = Some(context.into());
+        self
+    }
+}
+
+impl fmt::Display for IssueLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path)?;
+        match (&self.file, self.line, self.column) {
+            (Some(file), Some(line), Some(column)) => write!(f, " ({}:{}:{})", file, line, column),
+            (Some(file), _, _) => write!(f, " ({})", file),
+            _ => Ok(()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnalysisSuggestion {
     pub title: String,
@@ -91,8 +141,95 @@ pub struct GitDiff {
     pub line_end: usize,
 }
 
+/// Report output formats accepted by `backworks analyze --format`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Yaml,
+}
+
+impl std::str::FromStr for ReportFormat {
+    type Err = BackworksError;
+
+    fn from_str(format: &str) -> BackworksResult<Self> {
+        match format.to_ascii_lowercase().as_str() {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            "yaml" | "yml" => Ok(ReportFormat::Yaml),
+            other => Err(BackworksError::config(format!(
+                "Unknown report format '{}', expected text, json or yaml", other
+            ))),
+        }
+    }
+}
+
+/// Maps endpoints to where they were written in the blueprint sources
+struct SourceMap {
+    locator: Locator,
+    endpoints: HashMap<String, Vec<Segment>>,
+}
+
+impl SourceMap {
+    fn new(resolved: &ResolvedBlueprint, config: &BackworksConfig) -> Self {
+        let mut endpoints = HashMap::new();
+        match resolved.value.get("endpoints") {
+            Some(serde_yaml::Value::Mapping(_)) => {
+                for name in config.endpoints.keys() {
+                    endpoints.insert(name.clone(), vec![Segment::Key("endpoints".to_string()), Segment::Key(name.clone())]);
+                }
+            }
+            // List-format endpoints are named after their path, so match them
+            // back by path
+            Some(serde_yaml::Value::Sequence(items)) => {
+                let mut claimed = HashSet::new();
+                let mut names: Vec<_> = config.endpoints.iter().collect();
+                names.sort_by_key(|(name, _)| name.as_str());
+                for (name, endpoint) in names {
+                    let index = items.iter().enumerate().position(|(i, item)| {
+                        !claimed.contains(&i) && item.get("path").and_then(|p| p.as_str()) == Some(endpoint.path.as_str())
+                    });
+                    if let Some(index) = index {
+                        claimed.insert(index);
+                        endpoints.insert(name.clone(), vec![Segment::Key("endpoints".to_string()), Segment::Index(index)]);
+                    }
+                }
+            }
+            _ => {}
+        }
+        Self { locator: Locator::new(&resolved.sources), endpoints }
+    }
+
+    /// Position of `keys` inside endpoint `name`, or of the endpoint itself
+    fn locate(&self, name: &str, keys: &[&str]) -> Option<Location> {
+        let mut path = self.endpoints.get(name)?.clone();
+        path.extend(keys.iter().map(|key| Segment::Key(key.to_string())));
+        self.locator.locate(&path)
+    }
+
+    /// Fill in where an endpoint finding points, e.g.
+    /// `endpoints.orders.runtime.handler`
+    fn place(&self, name: &str, location: &mut IssueLocation) {
+        let keys: Vec<&str> = location.path
+            .strip_prefix(&format!("endpoints.{}", name))
+            .map(|rest| rest.split('.').filter(|key| !key.is_empty()).collect())
+            .unwrap_or_default();
+        if let Some(found) = self.locate(name, &keys) {
+            location.file = Some(found.file.display().to_string());
+            location.line = Some(found.line);
+            location.column = Some(found.column);
+        }
+    }
+}
+
 pub struct BlueprintAnalyzer;
 
+impl Default for BlueprintAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl BlueprintAnalyzer {
     pub fn new() -> Self {
         Self
@@ -101,82 +238,152 @@ impl BlueprintAnalyzer {
     /// Analyze a blueprint configuration file
     pub async fn analyze_file(&self, blueprint_path: &str) -> BackworksResult<AnalysisReport> {
         info!("🔍 Analyzing blueprint: {}", blueprint_path);
-        
-        // Load and parse the configuration
-        let config_path_buf = std::path::PathBuf::from(blueprint_path);
-        let config = match crate::config::load_config(&config_path_buf).await {
-            Ok(config) => config,
-            Err(e) => {
-                return Ok(AnalysisReport {
-                    blueprint_path: blueprint_path.to_string(),
-                    status: AnalysisStatus::Error,
-                    summary: AnalysisSummary::default(),
-                    issues: vec![AnalysisIssue {
-                        severity: IssueSeverity::Error,
-                        category: IssueCategory::Configuration,
-                        message: format!("Failed to parse blueprint: {}", e),
-                        location: IssueLocation {
-                            path: blueprint_path.to_string(),
-                            line: None,
-                            column: None,
-                            context: None,
-                        },
-                        help: Some("Check YAML syntax and required fields".to_string()),
-                    }],
-                    suggestions: vec![],
-                    recommendations: vec![],
-                });
-            }
+
+        // Load and parse the configuration without validating it, so that
+        // validation failures are reported next to everything else
+        let resolved = match crate::blueprint::resolve(Path::new(blueprint_path)) {
+            Ok(resolved) => resolved,
+            Err(e) => return Ok(self.failed_report(blueprint_path, e)),
+        };
+        let parsed = match crate::diagnostics::parse_resolved(&resolved) {
+            Ok(parsed) => parsed,
+            Err(e) => return Ok(self.failed_report(blueprint_path, e)),
         };
 
-        self.analyze_config(&config, blueprint_path).await
+        let mut issues: Vec<AnalysisIssue> = parsed.warnings.iter().map(diagnostic_issue).collect();
+        if !resolved.missing_env.is_empty() {
+            issues.push(AnalysisIssue {
+                severity: IssueSeverity::Warning,
+                category: IssueCategory::Configuration,
+                message: format!("Environment variables not set: {}", resolved.missing_env.join(", ")),
+                location: IssueLocation::at("(environment)"),
+                help: Some("Unset variables without a default are substituted with empty values".to_string()),
+            });
+        }
+        if let Err(e) = crate::config::validate_config(&parsed.config) {
+            issues.push(AnalysisIssue {
+                severity: IssueSeverity::Error,
+                category: IssueCategory::Configuration,
+                message: e.to_string(),
+                location: IssueLocation::at("(blueprint)"),
+                help: None,
+            });
+        }
+
+        let sources = SourceMap::new(&resolved, &parsed.config);
+        Ok(self.analyze(&parsed.config, blueprint_path, issues, Some(&sources)))
     }
 
     /// Analyze a loaded configuration
     pub async fn analyze_config(&self, config: &BackworksConfig, blueprint_path: &str) -> BackworksResult<AnalysisReport> {
-        let mut issues = Vec::new();
+        Ok(self.analyze(config, blueprint_path, Vec::new(), None))
+    }
+
+    fn failed_report(&self, blueprint_path: &str, error: BackworksError) -> AnalysisReport {
+        AnalysisReport {
+            blueprint_path: blueprint_path.to_string(),
+            status: AnalysisStatus::Error,
+            summary: AnalysisSummary::default(),
+            issues: vec![AnalysisIssue {
+                severity: IssueSeverity::Error,
+                category: IssueCategory::Configuration,
+                message: format!("Failed to parse blueprint: {}", error),
+                location: IssueLocation::at(blueprint_path),
+                help: Some("Check YAML syntax and required fields".to_string()),
+            }],
+            suggestions: vec![],
+            recommendations: vec![],
+        }
+    }
+
+    fn analyze(&self, config: &BackworksConfig, blueprint_path: &str, mut issues: Vec<AnalysisIssue>, sources: Option<&SourceMap>) -> AnalysisReport {
         let mut suggestions = Vec::new();
         let mut recommendations = Vec::new();
 
         // Generate summary
-        let summary = self.generate_summary(config);
+        let mut summary = self.generate_summary(config);
 
-        // Run all analysis checks
-        self.check_routing_conflicts(config, &mut issues, &mut suggestions);
-        self.check_performance_considerations(config, &mut issues, &mut recommendations);
-        self.check_security_considerations(config, &mut issues, &mut recommendations);
+        // Endpoint-level findings; endpoints are visited by name so the
+        // report is stable between runs
+        let mut endpoints: Vec<(&String, &EndpointConfig)> = config.endpoints.iter().collect();
+        endpoints.sort_by_key(|(name, _)| name.as_str());
+
+        let mut findings = Vec::new();
+        summary.potential_conflicts = self.check_routing_conflicts(&endpoints, &mut findings);
+        self.check_handlers(config, &endpoints, &mut findings);
+        self.check_unused_plugins(config, &endpoints, &mut findings);
+        self.check_deprecations(&endpoints, chrono::Utc::now().date_naive(), &mut findings);
+        self.check_performance_considerations(config, &endpoints, blueprint_path, sources, &mut findings, &mut suggestions, &mut recommendations);
+        self.check_security_considerations(config, &endpoints, &mut findings, &mut recommendations);
         self.suggest_improvements(config, &mut suggestions, &mut recommendations);
 
-        // Determine overall status
-        let status = if issues.iter().any(|i| matches!(i.severity, IssueSeverity::Error)) {
-            AnalysisStatus::Error
-        } else if issues.iter().any(|i| matches!(i.severity, IssueSeverity::Warning)) {
-            AnalysisStatus::Warning
-        } else {
-            AnalysisStatus::Valid
-        };
+        for (endpoint, mut issue) in findings {
+            if let (Some(sources), Some(endpoint)) = (sources, endpoint) {
+                sources.place(&endpoint, &mut issue.location);
+            }
+            issues.push(issue);
+        }
+        issues.sort_by_key(|issue| issue.severity);
 
-        Ok(AnalysisReport {
+        AnalysisReport {
             blueprint_path: blueprint_path.to_string(),
-            status,
+            status: overall_status(&issues),
             summary,
             issues,
             suggestions,
             recommendations,
-        })
+        }
+    }
+
+    /// Add findings from the traffic a running server observed: endpoints
+    /// nobody calls, 404 hotspots that look like missing endpoints, and
+    /// declared paths called with undeclared methods
+    pub fn add_usage(&self, report: &mut AnalysisReport, usage: &UsageReport) {
+        let window = format_window(usage.window_seconds);
+        for endpoint in &usage.unused {
+            report.issues.push(AnalysisIssue {
+                severity: IssueSeverity::Info,
+                category: IssueCategory::Routing,
+                message: format!("Endpoint '{}' received no requests in the last {}", endpoint, window),
+                location: IssueLocation::at(format!("endpoints.{}", endpoint)),
+                help: Some("Unused endpoints can be deprecated and removed once no client depends on them".to_string()),
+            });
+        }
+        for hotspot in &usage.undeclared {
+            report.issues.push(AnalysisIssue {
+                severity: IssueSeverity::Warning,
+                category: IssueCategory::Routing,
+                message: format!("{} {} answered 404 to {} request(s), such as {}", hotspot.method, hotspot.path, hotspot.requests, hotspot.example),
+                location: IssueLocation::at("endpoints"),
+                help: Some("Clients expect an endpoint here; declare it or fix the clients".to_string()),
+            });
+        }
+        for mismatch in &usage.method_mismatches {
+            report.issues.push(AnalysisIssue {
+                severity: IssueSeverity::Warning,
+                category: IssueCategory::Routing,
+                message: format!("{} {} was requested {} time(s) but endpoint '{}' does not declare it", mismatch.method, mismatch.path, mismatch.requests, mismatch.endpoint),
+                location: IssueLocation::at(format!("endpoints.{}.methods", mismatch.endpoint)),
+                help: Some(format!("Add {} to the endpoint's methods or fix the clients", mismatch.method)),
+            });
+        }
+        report.issues.sort_by_key(|issue| issue.severity);
+        report.status = overall_status(&report.issues);
     }
 
     fn generate_summary(&self, config: &BackworksConfig) -> AnalysisSummary {
         let endpoints = config.endpoints.len();
         let mut runtime_endpoints = 0;
         let mut database_endpoints = 0;
+        let mut plugin_endpoints = 0;
         let transformations = 0;
 
         for endpoint in config.endpoints.values() {
-            match endpoint.mode.as_ref().unwrap_or(&config.mode) {
-                crate::config::ExecutionMode::Runtime => runtime_endpoints += 1,
-                crate::config::ExecutionMode::Database => database_endpoints += 1,
-                _ => {}
+            match endpoint.primary_mode(&config.mode) {
+                ExecutionMode::Runtime => runtime_endpoints += 1,
+                ExecutionMode::Database => database_endpoints += 1,
+                ExecutionMode::Plugin => plugin_endpoints += 1,
+                ExecutionMode::Static | ExecutionMode::Mock | ExecutionMode::Proxy => {}
             }
         }
 
@@ -184,122 +391,245 @@ impl BlueprintAnalyzer {
             endpoints,
             runtime_endpoints,
             database_endpoints,
+            plugin_endpoints,
             transformations,
-            potential_conflicts: 0, // Will be calculated during analysis
-        }
-    }
-
-    fn check_routing_conflicts(&self, config: &BackworksConfig, issues: &mut Vec<AnalysisIssue>, suggestions: &mut Vec<AnalysisSuggestion>) {
-        let mut path_conflicts = HashMap::new();
-        
-        // Check for path conflicts
-        for (name, endpoint) in &config.endpoints {
-            let key = format!("{}:{}", endpoint.methods.join(","), endpoint.path);
-            if let Some(existing) = path_conflicts.get(&key) {
-                issues.push(AnalysisIssue {
-                    severity: IssueSeverity::Error,
-                    category: IssueCategory::Routing,
-                    message: format!("Path conflict: '{}' and '{}' both handle {} {}", 
-                        existing, name, endpoint.methods.join(","), endpoint.path),
-                    location: IssueLocation {
-                        path: "endpoints".to_string(),
-                        line: None,
-                        column: None,
-                        context: Some(format!("endpoint: {}", name)),
-                    },
-                    help: Some("Each endpoint must have a unique combination of HTTP methods and path".to_string()),
-                });
-            } else {
-                path_conflicts.insert(key, name.clone());
-            }
+            potential_conflicts: 0, // Calculated by the routing checks
         }
+    }
 
-        // Check for ambiguous path patterns
-        self.check_path_ambiguity(config, issues, suggestions);
-    }
-
-    fn check_path_ambiguity(&self, config: &BackworksConfig, issues: &mut Vec<AnalysisIssue>, suggestions: &mut Vec<AnalysisSuggestion>) {
-        let paths: Vec<_> = config.endpoints.iter().collect();
-        
-        for (i, (name1, endpoint1)) in paths.iter().enumerate() {
-            for (name2, endpoint2) in paths.iter().skip(i + 1) {
-                if self.paths_may_conflict(&endpoint1.path, &endpoint2.path) {
-                    let issue = AnalysisIssue {
-                        severity: IssueSeverity::Warning,
-                        category: IssueCategory::Routing,
-                        message: format!("Potentially ambiguous paths: '{}' and '{}'", endpoint1.path, endpoint2.path),
-                        location: IssueLocation {
-                            path: "endpoints".to_string(),
-                            line: None,
-                            column: None,
-                            context: Some(format!("endpoints: {}, {}", name1, name2)),
+    /// Routes the server cannot register together, or that take requests
+    /// away from each other. Returns the number of findings.
+    fn check_routing_conflicts(&self, endpoints: &[(&String, &EndpointConfig)], findings: &mut Vec<(Option<String>, AnalysisIssue)>) -> usize {
+        let routes: Vec<(&str, &str, &[String])> = endpoints.iter()
+            .map(|(name, endpoint)| (name.as_str(), endpoint.path.as_str(), endpoint.methods.as_slice()))
+            .collect();
+        let conflicts = find_conflicts(&routes);
+
+        for conflict in &conflicts {
+            let (severity, help) = match conflict.overlap {
+                Overlap::Identical => (IssueSeverity::Error, "Each method and path can be served by one endpoint only; the server refuses to start"),
+                Overlap::ParamNames => (IssueSeverity::Error, "The router needs the same parameter name at the same position; rename one of them"),
+                _ => (IssueSeverity::Warning, "Requests matching both paths go to the more specific one; make sure that is intended"),
+            };
+            let subject = conflict.subject().to_string();
+            findings.push((Some(subject.clone()), AnalysisIssue {
+                severity,
+                category: IssueCategory::Routing,
+                message: conflict.to_string(),
+                location: IssueLocation::at(format!("endpoints.{}", subject))
+                    .with_context(format!("endpoints: {}, {}", conflict.first, conflict.second)),
+                help: Some(help.to_string()),
+            }));
+        }
+
+        conflicts.len()
+    }
+
+    /// Endpoints with an execution mode that has nothing to execute
+    fn check_handlers(&self, config: &BackworksConfig, endpoints: &[(&String, &EndpointConfig)], findings: &mut Vec<(Option<String>, AnalysisIssue)>) {
+        let any_plugin_enabled = config.plugins.values().any(|plugin| plugin.enabled);
+
+        for (name, endpoint) in endpoints {
+            for mode in endpoint.mode_chain(&config.mode) {
+                let location = IssueLocation::at(format!("endpoints.{}", name));
+                let (severity, message, help) = match mode {
+                    ExecutionMode::Runtime => match endpoint.runtime {
+                        Some(ref runtime) if !runtime.handler.trim().is_empty() => continue,
+                        _ => (
+                            IssueSeverity::Error,
+                            format!("Endpoint '{}' runs in runtime mode but has no handler", name),
+                            "Add a `runtime:` block with a language and handler, or change the endpoint's mode",
+                        ),
+                    },
+                    ExecutionMode::Plugin => match endpoint.plugin {
+                        None if endpoint.runtime.is_some() => (
+                            IssueSeverity::Error,
+                            format!("Endpoint '{}' has a runtime handler but runs in plugin mode", name),
+                            "Set `mode: runtime` on the endpoint or the blueprint",
+                        ),
+                        None => (
+                            IssueSeverity::Error,
+                            format!("Endpoint '{}' runs in plugin mode but names no plugin", name),
+                            "Set `plugin:` to the plugin that serves it, or change the endpoint's mode",
+                        ),
+                        Some(ref plugin) => match config.plugins.get(plugin) {
+                            Some(declared) if declared.enabled => continue,
+                            Some(_) => (
+                                IssueSeverity::Error,
+                                format!("Endpoint '{}' is served by plugin '{}', which is disabled", name, plugin),
+                                "Enable the plugin under `plugins:`",
+                            ),
+                            None => (
+                                IssueSeverity::Warning,
+                                format!("Endpoint '{}' is served by plugin '{}', which is not declared under plugins", name, plugin),
+                                "Declare the plugin unless it is registered from code",
+                            ),
                         },
-                        help: Some("Consider using more specific paths or different HTTP methods".to_string()),
-                    };
-                    issues.push(issue);
-
-                    // Generate suggestion
-                    suggestions.push(AnalysisSuggestion {
-                        title: "Resolve path ambiguity".to_string(),
-                        description: format!("Make paths '{}' and '{}' more distinct", endpoint1.path, endpoint2.path),
-                        diff: self.generate_path_disambiguation_diff(name1, &endpoint1.path, name2, &endpoint2.path),
-                        priority: SuggestionPriority::Medium,
-                    });
-                }
+                    },
+                    ExecutionMode::Database if !any_plugin_enabled => (
+                        IssueSeverity::Error,
+                        format!("Endpoint '{}' runs in database mode but no plugin is enabled to serve it", name),
+                        "Enable a database plugin such as sqlite",
+                    ),
+                    ExecutionMode::Database => continue,
+                    ExecutionMode::Mock if endpoint.mock.is_some() => continue,
+                    ExecutionMode::Mock => (
+                        IssueSeverity::Error,
+                        format!("Endpoint '{}' runs in mock mode but has no mock data", name),
+                        "Add a `mock:` block with a `schema` or `item_of`",
+                    ),
+                    ExecutionMode::Proxy if endpoint.proxy.is_some() => continue,
+                    ExecutionMode::Proxy => (
+                        IssueSeverity::Error,
+                        format!("Endpoint '{}' runs in proxy mode but has no upstream", name),
+                        "Add a `proxy:` block with the `upstream` URL or `targets`",
+                    ),
+                    ExecutionMode::Static => match endpoint.static_files {
+                        Some(ref files) if files.dir.is_dir() => continue,
+                        Some(ref files) => (
+                            IssueSeverity::Warning,
+                            format!("Endpoint '{}' serves {}, which does not exist yet", name, files.dir.display()),
+                            "Build the frontend into that directory before starting the server",
+                        ),
+                        None => (
+                            IssueSeverity::Error,
+                            format!("Endpoint '{}' runs in static mode but has no directory", name),
+                            "Add a `static:` block with the `dir` to serve",
+                        ),
+                    },
+                };
+                findings.push((Some(name.to_string()), AnalysisIssue {
+                    severity,
+                    category: IssueCategory::Configuration,
+                    message,
+                    location,
+                    help: Some(help.to_string()),
+                }));
             }
         }
     }
 
-    fn paths_may_conflict(&self, path1: &str, path2: &str) -> bool {
-        // Simple heuristic for path conflicts
-        if path1 == path2 {
-            return true;
-        }
-        
-        // Check for wildcard conflicts (simplified)
-        let segments1: Vec<&str> = path1.trim_start_matches('/').split('/').collect();
-        let segments2: Vec<&str> = path2.trim_start_matches('/').split('/').collect();
-        
-        if segments1.len() != segments2.len() {
-            return false;
-        }
-        
-        for (seg1, seg2) in segments1.iter().zip(segments2.iter()) {
-            if seg1.starts_with(':') || seg2.starts_with(':') {
-                // Potential parameter conflict
+    fn check_unused_plugins(&self, config: &BackworksConfig, endpoints: &[(&String, &EndpointConfig)], findings: &mut Vec<(Option<String>, AnalysisIssue)>) {
+        let referenced: HashSet<&str> = endpoints.iter().filter_map(|(_, e)| e.plugin.as_deref()).collect();
+        // Database endpoints are offered to every enabled plugin
+        let database_endpoints = endpoints.iter()
+            .any(|(_, e)| e.mode_chain(&config.mode).contains(&&ExecutionMode::Database));
+
+        let mut plugins: Vec<_> = config.plugins.iter().collect();
+        plugins.sort_by_key(|(name, _)| name.as_str());
+        for (name, plugin) in plugins {
+            if referenced.contains(name.as_str()) || (plugin.enabled && database_endpoints) {
                 continue;
             }
-            if seg1 != seg2 {
-                return false;
-            }
+            let issue = if plugin.enabled {
+                AnalysisIssue {
+                    severity: IssueSeverity::Info,
+                    category: IssueCategory::Configuration,
+                    message: format!("Plugin '{}' is not used by any endpoint", name),
+                    location: IssueLocation::at(format!("plugins.{}", name)),
+                    help: Some("Expected for plugins that act on every request, such as authentication; otherwise remove it".to_string()),
+                }
+            } else {
+                AnalysisIssue {
+                    severity: IssueSeverity::Warning,
+                    category: IssueCategory::Configuration,
+                    message: format!("Plugin '{}' is disabled and not used by any endpoint", name),
+                    location: IssueLocation::at(format!("plugins.{}", name)),
+                    help: Some("Remove the plugin or enable it".to_string()),
+                }
+            };
+            findings.push((None, issue));
+        }
+    }
+
+    fn check_deprecations(&self, endpoints: &[(&String, &EndpointConfig)], today: chrono::NaiveDate, findings: &mut Vec<(Option<String>, AnalysisIssue)>) {
+        for (name, endpoint) in endpoints {
+            let Some(ref deprecation) = endpoint.deprecated else {
+                continue;
+            };
+            let Some(sunset) = deprecation.sunset.filter(|_| crate::deprecation::sunset_passed(deprecation, today)) else {
+                continue;
+            };
+            findings.push((Some(name.to_string()), AnalysisIssue {
+                severity: IssueSeverity::Warning,
+                category: IssueCategory::Compatibility,
+                message: format!("Endpoint '{}' is past its sunset date ({})", name, sunset),
+                location: IssueLocation::at(format!("endpoints.{}.deprecated.sunset", name)),
+                help: Some("Remove the endpoint once its callers have migrated, or move the sunset date".to_string()),
+            }));
         }
-        
-        true
     }
 
-    fn check_performance_considerations(&self, config: &BackworksConfig, issues: &mut Vec<AnalysisIssue>, recommendations: &mut Vec<String>) {
+    fn check_performance_considerations(
+        &self,
+        config: &BackworksConfig,
+        endpoints: &[(&String, &EndpointConfig)],
+        blueprint_path: &str,
+        sources: Option<&SourceMap>,
+        findings: &mut Vec<(Option<String>, AnalysisIssue)>,
+        suggestions: &mut Vec<AnalysisSuggestion>,
+        recommendations: &mut Vec<String>,
+    ) {
         let endpoint_count = config.endpoints.len();
-        
+
         if endpoint_count > 50 {
-            issues.push(AnalysisIssue {
+            findings.push((None, AnalysisIssue {
                 severity: IssueSeverity::Info,
                 category: IssueCategory::Performance,
                 message: format!("High number of endpoints ({})", endpoint_count),
-                location: IssueLocation {
-                    path: "endpoints".to_string(),
-                    line: None,
-                    column: None,
-                    context: None,
-                },
+                location: IssueLocation::at("endpoints"),
                 help: Some("Consider consolidating similar endpoints or using path parameters".to_string()),
-            });
-            
+            }));
+
             recommendations.push("Consider implementing caching for frequently accessed endpoints".to_string());
             recommendations.push("Monitor endpoint performance and optimize as needed".to_string());
         }
+
+        for (name, endpoint) in endpoints {
+            let Some(ref runtime) = endpoint.runtime else { continue };
+            let lines = runtime.handler.trim().lines().count();
+            if is_handler_file(&runtime.handler) || lines <= MAX_INLINE_HANDLER_LINES {
+                continue;
+            }
+
+            findings.push((Some(name.to_string()), AnalysisIssue {
+                severity: IssueSeverity::Warning,
+                category: IssueCategory::Performance,
+                message: format!("Inline handler of '{}' is {} lines long", name, lines),
+                location: IssueLocation::at(format!("endpoints.{}.runtime.handler", name)),
+                help: Some(format!(
+                    "Inline handlers over {} lines are hard to review and test; move it to a file",
+                    MAX_INLINE_HANDLER_LINES
+                )),
+            }));
+            let file = format!("handlers/{}.{}", name, handler_extension(&runtime.language));
+            let handler_at = sources.and_then(|sources| sources.locate(name, &["runtime", "handler"]));
+            let (file_path, line_start, indent) = match handler_at {
+                Some(found) => (found.file.display().to_string(), found.line, found.column - 1),
+                None => (blueprint_path.to_string(), 1, 6),
+            };
+            suggestions.push(AnalysisSuggestion {
+                title: format!("Move the '{}' handler to a file", name),
+                description: format!("Save the handler as {} and reference it from the blueprint", file),
+                diff: Some(GitDiff {
+                    file_path,
+                    original: format!("{:indent$}handler: |", ""),
+                    suggested: format!("{:indent$}handler: \"./{}\"", "", file),
+                    line_start,
+                    line_end: line_start + lines,
+                }),
+                priority: SuggestionPriority::Low,
+            });
+        }
     }
 
-    fn check_security_considerations(&self, config: &BackworksConfig, _issues: &mut Vec<AnalysisIssue>, recommendations: &mut Vec<String>) {
+    fn check_security_considerations(
+        &self,
+        config: &BackworksConfig,
+        endpoints: &[(&String, &EndpointConfig)],
+        findings: &mut Vec<(Option<String>, AnalysisIssue)>,
+        recommendations: &mut Vec<String>,
+    ) {
         // Check CORS configuration
         if let Some(ref security) = &config.security {
             if let Some(ref cors) = &security.cors {
@@ -312,10 +642,28 @@ impl BlueprintAnalyzer {
                 }
             }
         }
-        
+
+        if !has_authentication(config) {
+            for (name, endpoint) in endpoints {
+                let mutating: Vec<&str> = endpoint.methods.iter()
+                    .map(String::as_str)
+                    .filter(|method| MUTATING_METHODS.iter().any(|m| m.eq_ignore_ascii_case(method)))
+                    .collect();
+                if mutating.is_empty() {
+                    continue;
+                }
+                findings.push((Some(name.to_string()), AnalysisIssue {
+                    severity: IssueSeverity::Warning,
+                    category: IssueCategory::Security,
+                    message: format!("Endpoint '{}' accepts {} {} without authentication", name, mutating.join(","), endpoint.path),
+                    location: IssueLocation::at(format!("endpoints.{}", name)),
+                    help: Some("Configure security.authentication or enable an authentication plugin".to_string()),
+                }));
+            }
+        }
+
         // General security recommendations
         recommendations.push("Consider implementing rate limiting for API endpoints".to_string());
-        recommendations.push("Enable authentication and authorization for sensitive endpoints".to_string());
     }
 
     fn suggest_improvements(&self, config: &BackworksConfig, suggestions: &mut Vec<AnalysisSuggestion>, recommendations: &mut Vec<String>) {
@@ -336,63 +684,74 @@ impl BlueprintAnalyzer {
         }
     }
 
-    fn generate_path_disambiguation_diff(&self, name1: &str, path1: &str, _name2: &str, _path2: &str) -> Option<GitDiff> {
-        // Generate a simple suggestion to make paths more specific
-        Some(GitDiff {
-            file_path: "blueprint.yaml".to_string(),
-            original: format!("  {}:\n    path: \"{}\"", name1, path1),
-            suggested: format!("  {}:\n    path: \"{}/v1\"", name1, path1.trim_end_matches('/')),
-            line_start: 1,
-            line_end: 2,
-        })
+    /// Render a report in the requested format
+    pub fn render(&self, report: &AnalysisReport, format: ReportFormat) -> BackworksResult<String> {
+        match format {
+            ReportFormat::Text => Ok(self.render_text(report, false)),
+            ReportFormat::Json => serde_json::to_string_pretty(report).map_err(BackworksError::Json),
+            ReportFormat::Yaml => serde_yaml::to_string(report)
+                .map_err(|e| BackworksError::config(format!("Cannot serialize report: {}", e))),
+        }
     }
 
     /// Print analysis report in a user-friendly format
     pub fn print_report(&self, report: &AnalysisReport) {
-        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-        println!("🔍 Blueprint Analysis Report");
-        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-        println!("📋 File: {}", report.blueprint_path);
-        println!("📊 Status: {}", self.format_status(&report.status));
-        println!();
+        print!("{}", self.render_text(report, true));
+    }
+
+    /// Human-readable report; `color` adds ANSI colors to diffs
+    pub fn render_text(&self, report: &AnalysisReport, color: bool) -> String {
+        let mut out = String::new();
+        self.write_text(&mut out, report, color).expect("writing to a String cannot fail");
+        out
+    }
+
+    fn write_text(&self, out: &mut String, report: &AnalysisReport, color: bool) -> fmt::Result {
+        writeln!(out, "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━")?;
+        writeln!(out, "🔍 Blueprint Analysis Report")?;
+        writeln!(out, "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━")?;
+        writeln!(out, "📋 File: {}", report.blueprint_path)?;
+        writeln!(out, "📊 Status: {}", self.format_status(&report.status))?;
+        writeln!(out)?;
 
         // Summary
-        println!("📈 Summary:");
-        println!("   Endpoints: {}", report.summary.endpoints);
-        println!("   ├─ Runtime: {}", report.summary.runtime_endpoints);
-        println!("   ├─ Runtime: {}", report.summary.runtime_endpoints);
-        println!("   └─ Database: {}", report.summary.database_endpoints);
-        println!("   Transformations: {}", report.summary.transformations);
-        println!();
+        writeln!(out, "📈 Summary:")?;
+        writeln!(out, "   Endpoints: {}", report.summary.endpoints)?;
+        writeln!(out, "   ├─ Runtime: {}", report.summary.runtime_endpoints)?;
+        writeln!(out, "   ├─ Plugin: {}", report.summary.plugin_endpoints)?;
+        writeln!(out, "   └─ Database: {}", report.summary.database_endpoints)?;
+        writeln!(out, "   Transformations: {}", report.summary.transformations)?;
+        writeln!(out, "   Route conflicts: {}", report.summary.potential_conflicts)?;
+        writeln!(out)?;
 
         // Issues
         if !report.issues.is_empty() {
-            println!("⚠️  Issues ({}):", report.issues.len());
+            writeln!(out, "⚠️  Issues ({}):", report.issues.len())?;
             for issue in &report.issues {
-                self.print_issue(issue);
+                self.write_issue(out, issue)?;
             }
-            println!();
+            writeln!(out)?;
         }
 
         // Suggestions
         if !report.suggestions.is_empty() {
-            println!("💡 Suggestions ({}):", report.suggestions.len());
+            writeln!(out, "💡 Suggestions ({}):", report.suggestions.len())?;
             for suggestion in &report.suggestions {
-                self.print_suggestion(suggestion);
+                self.write_suggestion(out, suggestion, color)?;
             }
-            println!();
+            writeln!(out)?;
         }
 
         // Recommendations
         if !report.recommendations.is_empty() {
-            println!("🎯 Recommendations:");
+            writeln!(out, "🎯 Recommendations:")?;
             for rec in &report.recommendations {
-                println!("   • {}", rec);
+                writeln!(out, "   • {}", rec)?;
             }
-            println!();
+            writeln!(out)?;
         }
 
-        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+        writeln!(out, "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━")
     }
 
     fn format_status(&self, status: &AnalysisStatus) -> String {
@@ -403,7 +762,7 @@ impl BlueprintAnalyzer {
         }
     }
 
-    fn print_issue(&self, issue: &AnalysisIssue) {
+    fn write_issue(&self, out: &mut String, issue: &AnalysisIssue) -> fmt::Result {
         let severity_icon = match issue.severity {
             IssueSeverity::Error => "❌",
             IssueSeverity::Warning => "⚠️ ",
@@ -411,18 +770,18 @@ impl BlueprintAnalyzer {
             IssueSeverity::Hint => "💡",
         };
 
-        println!("   {} {}", severity_icon, issue.message);
-        println!("      └─ {}", issue.location.path);
+        writeln!(out, "   {} [{}] {}", severity_icon, issue.category, issue.message)?;
+        writeln!(out, "      └─ {}", issue.location)?;
         if let Some(ref context) = issue.location.context {
-            println!("         Context: {}", context);
+            writeln!(out, "         Context: {}", context)?;
         }
         if let Some(ref help) = issue.help {
-            println!("         Help: {}", help);
+            writeln!(out, "         Help: {}", help)?;
         }
-        println!();
+        writeln!(out)
     }
 
-    fn print_suggestion(&self, suggestion: &AnalysisSuggestion) {
+    fn write_suggestion(&self, out: &mut String, suggestion: &AnalysisSuggestion, color: bool) -> fmt::Result {
         let priority_icon = match suggestion.priority {
             SuggestionPriority::Critical => "🔥",
             SuggestionPriority::High => "⭐",
@@ -430,38 +789,90 @@ impl BlueprintAnalyzer {
             SuggestionPriority::Low => "✨",
         };
 
-        println!("   {} {}", priority_icon, suggestion.title);
-        println!("      {}", suggestion.description);
-        
+        writeln!(out, "   {} {}", priority_icon, suggestion.title)?;
+        writeln!(out, "      {}", suggestion.description)?;
+
         if let Some(ref diff) = suggestion.diff {
-            self.print_diff(diff);
+            self.write_diff(out, diff, color)?;
         }
-        println!();
+        writeln!(out)
     }
 
-    fn print_diff(&self, diff: &GitDiff) {
-        println!("      Change in {}:", diff.file_path);
+    fn write_diff(&self, out: &mut String, diff: &GitDiff, color: bool) -> fmt::Result {
+        let (red, green, reset) = if color { ("\x1b[31m", "\x1b[32m", "\x1b[0m") } else { ("", "", "") };
+        writeln!(out, "      Change in {}:", diff.file_path)?;
         for line in diff.original.lines() {
-            println!("\x1b[31m        - {}\x1b[0m", line);
+            writeln!(out, "{}        - {}{}", red, line, reset)?;
         }
         for line in diff.suggested.lines() {
-            println!("\x1b[32m        + {}\x1b[0m", line);
+            writeln!(out, "{}        + {}{}", green, line, reset)?;
         }
+        Ok(())
     }
 }
 
-impl Default for AnalysisSummary {
-    fn default() -> Self {
-        Self {
-            endpoints: 0,
-            runtime_endpoints: 0,
-            database_endpoints: 0,
-            transformations: 0,
-            potential_conflicts: 0,
-        }
+/// A parse warning, such as an unknown field, as a report issue
+/// A window length in its largest whole unit, such as `1h` or `90m`
+fn format_window(seconds: u64) -> String {
+    match seconds {
+        s if s >= 86_400 && s % 86_400 == 0 => format!("{}d", s / 86_400),
+        s if s >= 3_600 && s % 3_600 == 0 => format!("{}h", s / 3_600),
+        s if s >= 60 && s % 60 == 0 => format!("{}m", s / 60),
+        s => format!("{}s", s),
+    }
+}
+
+fn overall_status(issues: &[AnalysisIssue]) -> AnalysisStatus {
+    if issues.iter().any(|i| i.severity == IssueSeverity::Error) {
+        AnalysisStatus::Error
+    } else if issues.iter().any(|i| i.severity == IssueSeverity::Warning) {
+        AnalysisStatus::Warning
+    } else {
+        AnalysisStatus::Valid
+    }
+}
+
+fn diagnostic_issue(diagnostic: &Diagnostic) -> AnalysisIssue {
+    let mut location = IssueLocation::at(diagnostic.path_string());
+    if let Some(ref found) = diagnostic.location {
+        location.file = Some(found.file.display().to_string());
+        location.line = Some(found.line);
+        location.column = Some(found.column);
+    }
+    AnalysisIssue {
+        severity: IssueSeverity::Warning,
+        category: IssueCategory::Configuration,
+        message: diagnostic.message.clone(),
+        location,
+        help: diagnostic.suggestion.as_ref().map(|s| format!("Did you mean `{}`?", s)),
+    }
+}
+
+/// Handlers are read from disk when they look like a path, the same rule the
+/// runtime applies
+pub(crate) fn is_handler_file(handler: &str) -> bool {
+    let handler = handler.trim();
+    !handler.contains('\n')
+        && (handler.starts_with("./") || handler.starts_with("../") || handler.ends_with(".js") || handler.ends_with(".py"))
+}
+
+fn handler_extension(language: &str) -> &'static str {
+    match language {
+        "python" | "py" => "py",
+        _ => "js",
     }
 }
 
+/// Whether requests can be authenticated at all: globally configured
+/// authentication, or an enabled plugin that looks like an auth provider
+fn has_authentication(config: &BackworksConfig) -> bool {
+    let configured = config.security.as_ref().is_some_and(|security| security.authentication.is_some());
+    configured || config.plugins.iter().any(|(name, plugin)| {
+        let name = name.to_ascii_lowercase();
+        plugin.enabled && AUTH_PLUGIN_HINTS.iter().any(|hint| name.contains(hint))
+    })
+}
+
 impl fmt::Display for IssueSeverity {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -485,3 +896,154 @@ impl fmt::Display for IssueCategory {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(yaml: &str) -> AnalysisReport {
+        let config = crate::config::parse_blueprint(serde_yaml::from_str(yaml).unwrap()).unwrap();
+        BlueprintAnalyzer::new().analyze(&config, "test.yaml", Vec::new(), None)
+    }
+
+    fn messages(report: &AnalysisReport, severity: IssueSeverity) -> Vec<&str> {
+        report.issues.iter().filter(|i| i.severity == severity).map(|i| i.message.as_str()).collect()
+    }
+
+    #[test]
+    fn test_routing_conflicts() {
+        let report = report(r#"
+name: routes
+endpoints:
+  user:
+    path: "/users/{id}"
+    methods: ["GET", "DELETE"]
+    plugin: users
+  me:
+    path: "/users/me"
+    plugin: users
+  user_again:
+    path: "/users/:id"
+    methods: ["get"]
+    plugin: users
+  user_renamed:
+    path: "/users/{user_id}"
+    methods: ["PUT"]
+    plugin: users
+plugins:
+  users: { enabled: true }
+  auth: { enabled: true }
+"#);
+
+        assert!(matches!(report.status, AnalysisStatus::Error));
+        assert_eq!(report.summary.potential_conflicts, 5);
+        let errors = messages(&report, IssueSeverity::Error);
+        assert!(errors.contains(&"Duplicate route GET /users/{id}: declared by 'user' and 'user_again'"));
+        assert!(errors.contains(&"Routes '/users/{id}' and '/users/{user_id}' differ only in parameter names"));
+        let warnings = messages(&report, IssueSeverity::Warning);
+        assert!(warnings.contains(&"'user' (/users/{id}) is shadowed by 'me' (/users/me) for requests like GET /users/me"));
+    }
+
+    #[test]
+    fn test_endpoints_without_handlers_and_unused_plugins() {
+        let report = report(r#"
+name: handlers
+endpoints:
+  script:
+    path: "/script"
+    mode: runtime
+  orphan:
+    path: "/orphan"
+  legacy:
+    path: "/legacy"
+    plugin: legacy
+    deprecated:
+      sunset: 2020-01-31
+  current:
+    path: "/current"
+    plugin: legacy
+    deprecated:
+      sunset: 2999-01-31
+plugins:
+  legacy: { enabled: false }
+  metrics: { enabled: true }
+  old_cache: { enabled: false }
+"#);
+
+        let errors = messages(&report, IssueSeverity::Error);
+        assert!(errors.contains(&"Endpoint 'script' runs in runtime mode but has no handler"));
+        assert!(errors.contains(&"Endpoint 'orphan' runs in plugin mode but names no plugin"));
+        assert!(errors.contains(&"Endpoint 'legacy' is served by plugin 'legacy', which is disabled"));
+        let warnings = messages(&report, IssueSeverity::Warning);
+        assert!(warnings.contains(&"Plugin 'old_cache' is disabled and not used by any endpoint"));
+        assert!(warnings.contains(&"Endpoint 'legacy' is past its sunset date (2020-01-31)"));
+        assert!(!warnings.iter().any(|w| w.contains("'current'")));
+        assert!(messages(&report, IssueSeverity::Info).contains(&"Plugin 'metrics' is not used by any endpoint"));
+    }
+
+    #[test]
+    fn test_security_and_inline_handler_checks() {
+        let handler: String = (0..=MAX_INLINE_HANDLER_LINES).map(|i| format!("        // line {}\n", i)).collect();
+        let report = report(&format!(r#"
+name: security
+endpoints:
+  orders:
+    path: "/orders"
+    methods: ["GET", "POST"]
+    mode: runtime
+    runtime:
+      language: javascript
+      handler: |
+{}
+  files:
+    path: "/files"
+    mode: runtime
+    runtime:
+      language: javascript
+      handler: "./handlers/files.js"
+"#, handler));
+
+        let warnings = messages(&report, IssueSeverity::Warning);
+        assert!(warnings.contains(&"Endpoint 'orders' accepts POST /orders without authentication"));
+        assert!(warnings.contains(&format!("Inline handler of 'orders' is {} lines long", MAX_INLINE_HANDLER_LINES + 1).as_str()));
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(report.suggestions.iter().filter(|s| s.diff.is_some()).count(), 1);
+
+        let json = BlueprintAnalyzer::new().render(&report, ReportFormat::Json).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["status"], "Warning");
+        assert!("yml".parse::<ReportFormat>().is_ok());
+        assert!("xml".parse::<ReportFormat>().is_err());
+    }
+
+    #[test]
+    fn test_usage_findings() {
+        let mut report = report(r#"
+name: usage
+endpoints:
+  users:
+    path: "/users/{id}"
+    plugin: users
+plugins:
+  users: { enabled: true }
+"#);
+        assert!(matches!(report.status, AnalysisStatus::Valid));
+        let usage: UsageReport = serde_json::from_value(serde_json::json!({
+            "window_seconds": 86400,
+            "generated_at": "2026-01-01T00:00:00Z",
+            "requests": 5,
+            "endpoints": [],
+            "unused": ["users"],
+            "undeclared": [{ "method": "GET", "path": "/orders/{id}", "requests": 4, "example": "/orders/7" }],
+            "method_mismatches": [{ "endpoint": "users", "path": "/users/{id}", "method": "DELETE", "requests": 1 }]
+        })).unwrap();
+        BlueprintAnalyzer::new().add_usage(&mut report, &usage);
+
+        assert!(matches!(report.status, AnalysisStatus::Warning));
+        assert_eq!(messages(&report, IssueSeverity::Info), vec!["Endpoint 'users' received no requests in the last 1d"]);
+        assert_eq!(messages(&report, IssueSeverity::Warning), vec![
+            "GET /orders/{id} answered 404 to 4 request(s), such as /orders/7",
+            "DELETE /users/{id} was requested 1 time(s) but endpoint 'users' does not declare it",
+        ]);
+    }
+}
@@ -0,0 +1,285 @@
+//! Architecture graph of a blueprint, for the dashboard
+//!
+//! Nodes are the server, its endpoints, the runtime handlers and plugins
+//! that answer them, proxy targets, databases and external APIs; edges lead
+//! from the server to its endpoints and from each endpoint to what serves
+//! it. Nodes the server measures are colored by their live health: endpoints
+//! and proxy targets by their error rate, plugins by their health checks and
+//! circuit breakers.
+
+use crate::config::{BackworksConfig, ExecutionMode};
+use crate::metrics_recorder::{MetricSource, MetricsSnapshot};
+use crate::plugin::{HealthStatus, PluginHealth};
+use crate::resilience::PluginMetrics;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Error rate from which a node is degraded
+const DEGRADED_ERROR_RATE: f64 = 0.05;
+
+/// Error rate from which a node is unhealthy
+const UNHEALTHY_ERROR_RATE: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    Server,
+    Endpoint,
+    Handler,
+    Plugin,
+    Upstream,
+    Database,
+    ExternalApi,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeHealth {
+    Healthy,
+    Degraded,
+    Unhealthy,
+    /// Not measured, or not used yet
+    #[default]
+    Unknown,
+}
+
+impl NodeHealth {
+    fn from_error_rate(requests: u64, errors: u64) -> Self {
+        if requests == 0 {
+            return Self::Unknown;
+        }
+        let rate = errors as f64 / requests as f64;
+        if rate >= UNHEALTHY_ERROR_RATE {
+            Self::Unhealthy
+        } else if rate >= DEGRADED_ERROR_RATE {
+            Self::Degraded
+        } else {
+            Self::Healthy
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlowNode {
+    pub id: String,
+    pub kind: NodeKind,
+    pub label: String,
+    /// Mode, language, table or URL, as fits the kind
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub health: NodeHealth,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlowEdge {
+    pub from: String,
+    pub to: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// What the server measured, to color the graph with
+#[derive(Debug, Clone, Default)]
+pub struct LiveHealth {
+    pub metrics: Vec<MetricsSnapshot>,
+    pub plugins: HashMap<String, PluginHealth>,
+    pub plugin_metrics: HashMap<String, PluginMetrics>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ArchitectureGraph {
+    pub nodes: Vec<FlowNode>,
+    pub edges: Vec<FlowEdge>,
+}
+
+impl ArchitectureGraph {
+    /// The graph of `config`, every node's health unknown
+    pub fn from_config(config: &BackworksConfig) -> Self {
+        let mut nodes = BTreeMap::new();
+        let mut edges = Vec::new();
+        let mut node = |id: String, kind: NodeKind, label: &str, detail: Option<String>| {
+            nodes.entry(id.clone()).or_insert_with(|| FlowNode { id: id.clone(), kind, label: label.to_string(), detail, health: NodeHealth::Unknown });
+            id
+        };
+        let mut edge = |from: &str, to: &str, label: Option<String>| {
+            edges.push(FlowEdge { from: from.to_string(), to: to.to_string(), label });
+        };
+
+        let server = node("server".to_string(), NodeKind::Server, &config.name, config.version.clone());
+        let mut hooks: BTreeSet<&String> = config.plugins.iter().filter(|(_, plugin)| plugin.enabled).map(|(name, _)| name).collect();
+
+        let names: BTreeSet<&String> = config.endpoints.keys().collect();
+        for name in names {
+            let endpoint = &config.endpoints[name];
+            let modes = endpoint.mode_chain(&config.mode);
+            let detail = modes.iter().map(|mode| mode_name(mode)).collect::<Vec<_>>().join(" → ");
+            let id = node(format!("endpoint:{}", name), NodeKind::Endpoint, name, Some(detail));
+            edge(&server, &id, Some(format!("{} {}", endpoint.methods.join(","), endpoint.path)));
+
+            for mode in modes {
+                match mode {
+                    ExecutionMode::Runtime => if let Some(ref runtime) = endpoint.runtime {
+                        let handler = node(format!("handler:{}", runtime.handler), NodeKind::Handler, &runtime.handler, Some(runtime.language.clone()));
+                        edge(&id, &handler, None);
+                    },
+                    ExecutionMode::Plugin => if let Some(ref plugin) = endpoint.plugin {
+                        hooks.remove(plugin);
+                        let plugin = node(format!("plugin:{}", plugin), NodeKind::Plugin, plugin, None);
+                        edge(&id, &plugin, Some("handles".to_string()));
+                    },
+                    ExecutionMode::Proxy => if let Some(ref proxy) = endpoint.proxy {
+                        let targets = proxy.upstream.iter().map(String::as_str).chain(proxy.targets.iter().map(|target| target.url()));
+                        for target in targets {
+                            let url = normalize_target(target);
+                            let upstream = node(format!("upstream:{}", url), NodeKind::Upstream, &url, None);
+                            edge(&id, &upstream, Some("proxies".to_string()));
+                        }
+                    },
+                    ExecutionMode::Database => {
+                        let database = config.database.as_ref().map(|database| database.db_type.clone());
+                        let database = node("database".to_string(), NodeKind::Database, "database", database);
+                        let table = endpoint.database.as_ref().and_then(|database| database.table.clone());
+                        edge(&id, &database, table);
+                    }
+                    ExecutionMode::Mock | ExecutionMode::Static => {}
+                }
+            }
+            for api in endpoint.apis.iter().flatten() {
+                let base_url = config.apis.as_ref().and_then(|apis| apis.get(api)).map(|api| api.base_url.clone());
+                let api = node(format!("api:{}", api), NodeKind::ExternalApi, api, base_url);
+                edge(&id, &api, Some("calls".to_string()));
+            }
+        }
+
+        // Plugins that run hooks around every request rather than handle one
+        for plugin in hooks {
+            let plugin = node(format!("plugin:{}", plugin), NodeKind::Plugin, plugin, None);
+            edge(&server, &plugin, Some("hooks".to_string()));
+        }
+        if let Some(ref database) = config.database {
+            node("database".to_string(), NodeKind::Database, "database", Some(database.db_type.clone()));
+        }
+        for (name, api) in config.apis.iter().flatten() {
+            node(format!("api:{}", name), NodeKind::ExternalApi, name, Some(api.base_url.clone()));
+        }
+
+        Self { nodes: nodes.into_values().collect(), edges }
+    }
+
+    /// Color nodes with what the server measured
+    pub fn with_health(mut self, live: &LiveHealth) -> Self {
+        let mut endpoints: HashMap<&str, (u64, u64)> = HashMap::new();
+        let mut upstreams: HashMap<&str, (u64, u64)> = HashMap::new();
+        for snapshot in &live.metrics {
+            let counts = match snapshot.source {
+                // The dashboard records endpoint requests as `/<name>`
+                MetricSource::Endpoint { ref path, .. } => endpoints.entry(path.trim_start_matches('/')).or_default(),
+                MetricSource::Upstream { ref target } => upstreams.entry(target.as_str()).or_default(),
+                MetricSource::Plugin { .. } => continue,
+            };
+            counts.0 += snapshot.requests;
+            counts.1 += snapshot.errors;
+        }
+
+        for node in &mut self.nodes {
+            let name = node.id.split_once(':').map_or("", |(_, name)| name);
+            let measured = |counts: Option<&(u64, u64)>| counts.map_or(NodeHealth::Unknown, |&(requests, errors)| NodeHealth::from_error_rate(requests, errors));
+            node.health = match node.kind {
+                NodeKind::Endpoint => measured(endpoints.get(name)),
+                NodeKind::Upstream => measured(upstreams.get(name)),
+                NodeKind::Plugin => plugin_health(live, name),
+                _ => NodeHealth::Unknown,
+            };
+        }
+        self
+    }
+}
+
+fn plugin_health(live: &LiveHealth, name: &str) -> NodeHealth {
+    let open = live.plugin_metrics.get(name).is_some_and(|metrics| metrics.circuit_breaker_state == "Open");
+    match live.plugins.get(name).map(|health| &health.status) {
+        _ if open => NodeHealth::Unhealthy,
+        Some(HealthStatus::Healthy) => NodeHealth::Healthy,
+        Some(HealthStatus::Degraded) => NodeHealth::Degraded,
+        Some(HealthStatus::Unhealthy) => NodeHealth::Unhealthy,
+        None => NodeHealth::Unknown,
+    }
+}
+
+fn mode_name(mode: &ExecutionMode) -> &'static str {
+    match mode {
+        ExecutionMode::Runtime => "runtime",
+        ExecutionMode::Database => "database",
+        ExecutionMode::Plugin => "plugin",
+        ExecutionMode::Static => "static",
+        ExecutionMode::Mock => "mock",
+        ExecutionMode::Proxy => "proxy",
+    }
+}
+
+/// A target URL as the proxy reports it
+fn normalize_target(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(url) => url.as_str().trim_end_matches('/').to_string(),
+        Err(_) => url.trim_end_matches('/').to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_graph_follows_the_blueprint_and_live_health() {
+        let config: BackworksConfig = serde_yaml::from_str(r#"
+name: shop
+plugins:
+  geoip: { enabled: true }
+  pricing: { enabled: true }
+apis:
+  payments: { base_url: "https://pay.example.com" }
+endpoints:
+  users: { path: /users, methods: [GET], mode: mock, mock: { schema: { id: $uuid } } }
+  quotes: { path: /quotes, methods: [POST], mode: plugin, plugin: pricing, apis: [payments] }
+  stock:
+    path: /stock
+    methods: [GET]
+    modes: [proxy, mock]
+    proxy: { upstream: "http://10.0.0.1:8080/", targets: ["http://10.0.0.2:8080"] }
+"#).unwrap();
+        let graph = ArchitectureGraph::from_config(&config);
+        let ids: Vec<&str> = graph.nodes.iter().map(|node| node.id.as_str()).collect();
+        assert_eq!(ids, vec![
+            "api:payments", "endpoint:quotes", "endpoint:stock", "endpoint:users", "plugin:geoip", "plugin:pricing",
+            "server", "upstream:http://10.0.0.1:8080", "upstream:http://10.0.0.2:8080",
+        ]);
+        let edge = |from: &str, to: &str| graph.edges.iter().find(|edge| edge.from == from && edge.to == to).and_then(|edge| edge.label.clone());
+        assert_eq!(edge("server", "endpoint:stock").as_deref(), Some("GET /stock"));
+        assert_eq!(edge("endpoint:quotes", "plugin:pricing").as_deref(), Some("handles"));
+        assert_eq!(edge("endpoint:quotes", "api:payments").as_deref(), Some("calls"));
+        assert_eq!(edge("server", "plugin:geoip").as_deref(), Some("hooks"));
+        assert!(edge("server", "plugin:pricing").is_none());
+        assert_eq!(graph.nodes[2].detail.as_deref(), Some("proxy → mock"));
+
+        let recorder = crate::metrics_recorder::MetricsRecorder::default();
+        for status in [200, 200, 503] {
+            recorder.record_request("GET", "/stock", status, Duration::from_millis(5));
+        }
+        recorder.record_request("GET", "/users", 200, Duration::from_millis(1));
+        recorder.record(MetricSource::upstream("http://10.0.0.1:8080"), Duration::from_millis(5), true);
+        let live = LiveHealth {
+            metrics: recorder.snapshots(),
+            plugins: HashMap::from([("geoip".to_string(), PluginHealth { status: HealthStatus::Degraded, message: String::new(), details: HashMap::new() })]),
+            plugin_metrics: HashMap::new(),
+        };
+        let graph = graph.with_health(&live);
+        let health = |id: &str| graph.nodes.iter().find(|node| node.id == id).unwrap().health;
+        assert_eq!(health("endpoint:stock"), NodeHealth::Degraded);
+        assert_eq!(health("endpoint:users"), NodeHealth::Healthy);
+        assert_eq!(health("endpoint:quotes"), NodeHealth::Unknown);
+        assert_eq!(health("upstream:http://10.0.0.1:8080"), NodeHealth::Unhealthy);
+        assert_eq!(health("plugin:geoip"), NodeHealth::Degraded);
+        assert_eq!(health("api:payments"), NodeHealth::Unknown);
+    }
+}
@@ -0,0 +1,234 @@
+//! Request authentication context
+//!
+//! `AuthContext` is the identity attached to a request once it has been
+//! authenticated, whatever the mechanism (JWT, API key, Basic credentials
+//! checked by an auth plugin, or identity headers injected by a trusted
+//! upstream gateway). It travels as a request extension and is exposed to
+//! handlers as the `auth` field of the request data.
+
+use crate::config::TrustedHeaderAuthConfig;
+use crate::error::{BackworksError, BackworksResult};
+use axum::http::HeaderMap;
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    Jwt,
+    ApiKey,
+    /// HTTP Basic credentials verified against a directory
+    Basic,
+    TrustedHeader,
+}
+
+/// Authenticated identity of the caller
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthContext {
+    pub subject: String,
+    pub method: AuthMethod,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub claims: HashMap<String, serde_json::Value>,
+}
+
+/// Common name of a client certificate the TLS listener verified against
+/// `server.tls.client_ca`, inserted into the extensions of the connection's
+/// requests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerCertificate {
+    pub subject: String,
+}
+
+/// Authenticates requests from identity headers set by an upstream gateway
+#[derive(Debug, Clone)]
+pub struct TrustedHeaderAuth {
+    config: TrustedHeaderAuthConfig,
+    proxies: Vec<IpNet>,
+}
+
+impl TrustedHeaderAuth {
+    pub fn new(config: TrustedHeaderAuthConfig) -> BackworksResult<Self> {
+        let proxies = config.trusted_proxies.iter()
+            .map(|entry| parse_network(entry))
+            .collect::<BackworksResult<Vec<_>>>()?;
+
+        if proxies.is_empty() && config.trusted_client_certs.is_empty() {
+            return Err(BackworksError::config(
+                "Trusted header authentication requires trusted_proxies or trusted_client_certs",
+            ));
+        }
+
+        Ok(Self { config, proxies })
+    }
+
+    fn is_trusted_injector(&self, peer: Option<IpAddr>, certificate: Option<&PeerCertificate>) -> bool {
+        // A dual-stack listener reports IPv4 peers as IPv4-mapped IPv6 addresses
+        let by_address = peer.is_some_and(|ip| self.proxies.iter().any(|net| net.contains(&ip.to_canonical())));
+        let by_certificate = certificate
+            .is_some_and(|cert| self.config.trusted_client_certs.iter().any(|subject| subject == &cert.subject));
+        by_address || by_certificate
+    }
+
+    fn identity_headers(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.config.user_header.as_str())
+            .chain(self.config.email_header.as_deref())
+            .chain(self.config.roles_header.as_deref())
+    }
+
+    /// Resolve the caller's identity. Identity headers from an unverified
+    /// injector are removed so nothing downstream can mistake them for a
+    /// verified identity.
+    pub fn authenticate(
+        &self,
+        headers: &mut HeaderMap,
+        peer: Option<IpAddr>,
+        certificate: Option<&PeerCertificate>,
+    ) -> BackworksResult<Option<AuthContext>> {
+        if !self.is_trusted_injector(peer, certificate) {
+            let names: Vec<String> = self.identity_headers().map(String::from).collect();
+            let spoofed = names.iter().any(|name| headers.contains_key(name.as_str()));
+            for name in &names {
+                headers.remove(name.as_str());
+            }
+            if spoofed {
+                warn!("Dropping identity headers from untrusted source {:?}", peer);
+            }
+            return self.anonymous();
+        }
+
+        let header = |name: &str| {
+            headers.get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(String::from)
+        };
+
+        let Some(subject) = header(&self.config.user_header) else {
+            return self.anonymous();
+        };
+
+        let roles = self.config.roles_header.as_deref()
+            .and_then(header)
+            .map(|value| {
+                value.split(self.config.roles_separator.as_str())
+                    .map(str::trim)
+                    .filter(|role| !role.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(Some(AuthContext {
+            subject,
+            method: AuthMethod::TrustedHeader,
+            email: self.config.email_header.as_deref().and_then(header),
+            roles,
+            claims: HashMap::new(),
+        }))
+    }
+
+    fn anonymous(&self) -> BackworksResult<Option<AuthContext>> {
+        if self.config.required {
+            Err(BackworksError::unauthorized("Missing trusted identity"))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+fn parse_network(entry: &str) -> BackworksResult<IpNet> {
+    entry.parse::<IpNet>()
+        .or_else(|_| entry.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| BackworksError::config(format!("Invalid trusted proxy address '{}'", entry)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth(required: bool) -> TrustedHeaderAuth {
+        TrustedHeaderAuth::new(TrustedHeaderAuthConfig {
+            user_header: "X-Authenticated-User".to_string(),
+            email_header: Some("X-Authenticated-Email".to_string()),
+            roles_header: Some("X-Authenticated-Groups".to_string()),
+            roles_separator: ",".to_string(),
+            trusted_proxies: vec!["10.0.0.0/8".to_string(), "127.0.0.1".to_string(), "fd00::/8".to_string(), "[::1]".to_string()],
+            trusted_client_certs: vec!["sso-proxy.internal".to_string()],
+            required,
+        }).unwrap()
+    }
+
+    fn identity_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Authenticated-User", "ada".parse().unwrap());
+        headers.insert("X-Authenticated-Email", "ada@example.com".parse().unwrap());
+        headers.insert("X-Authenticated-Groups", "admin, ops".parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_trusted_proxy_identity_is_mapped() {
+        let mut headers = identity_headers();
+        let context = auth(false)
+            .authenticate(&mut headers, Some("10.1.2.3".parse().unwrap()), None)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(context.subject, "ada");
+        assert_eq!(context.method, AuthMethod::TrustedHeader);
+        assert_eq!(context.email.as_deref(), Some("ada@example.com"));
+        assert_eq!(context.roles, vec!["admin", "ops"]);
+
+        // IPv6 proxies, and IPv4 proxies seen through a dual-stack listener
+        for peer in ["fd00::5", "::1", "::ffff:10.1.2.3"] {
+            let context = auth(false).authenticate(&mut identity_headers(), Some(peer.parse().unwrap()), None).unwrap();
+            assert!(context.is_some(), "{}", peer);
+        }
+    }
+
+    #[test]
+    fn test_client_certificate_verifies_injector() {
+        let mut headers = identity_headers();
+        let cert = PeerCertificate { subject: "sso-proxy.internal".to_string() };
+        let context = auth(false)
+            .authenticate(&mut headers, Some("192.168.1.1".parse().unwrap()), Some(&cert))
+            .unwrap();
+        assert!(context.is_some());
+    }
+
+    #[test]
+    fn test_trusted_client_certs_need_a_client_ca() {
+        let blueprint = |tls: &str| crate::config::parse_blueprint(serde_yaml::from_str(&format!(
+            "name: api\nserver: {{ port: 8443{} }}\nsecurity:\n  authentication:\n    type: trusted_header\n    trusted_headers: {{ trusted_client_certs: [sso-proxy.internal] }}\nendpoints:\n  hello: {{ path: /hello }}\n",
+            tls,
+        )).unwrap()).unwrap();
+        let err = crate::config::validate_config(&blueprint("")).unwrap_err().to_string();
+        assert!(err.contains("needs server.tls.client_ca"), "{}", err);
+        // With one, the CA file itself is what gets checked
+        let err = crate::config::validate_config(&blueprint(", tls: { cert: api.crt, key: api.key, client_ca: missing-ca.pem }")).unwrap_err().to_string();
+        assert!(err.contains("Failed to read client CA missing-ca.pem"), "{}", err);
+    }
+
+    #[test]
+    fn test_untrusted_source_headers_are_stripped() {
+        let mut headers = identity_headers();
+        let context = auth(false)
+            .authenticate(&mut headers, Some("203.0.113.7".parse().unwrap()), None)
+            .unwrap();
+
+        assert!(context.is_none());
+        assert!(!headers.contains_key("X-Authenticated-User"));
+        assert!(!headers.contains_key("X-Authenticated-Groups"));
+
+        let mut headers = identity_headers();
+        assert!(auth(true).authenticate(&mut headers, None, None).is_err());
+    }
+}
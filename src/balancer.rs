@@ -0,0 +1,143 @@
+//! Load-balancing strategies
+//!
+//! A proxy endpoint with several `targets` asks its strategy, named by
+//! `load_balancing.algorithm`, which target each request goes to. The
+//! strategy sees the targets currently taking requests with their live
+//! metrics (requests in flight, totals, failures, a moving average of
+//! latency) and labels, plus the request itself, so it can route on load,
+//! latency or the caller's zone.
+//!
+//! Strategies are looked up in a [`LoadBalancerRegistry`]. It starts with the
+//! built-ins below; plugins add their own in
+//! [`BackworksPlugin::register_load_balancers`](crate::plugin::BackworksPlugin::register_load_balancers).
+//!
+//! - `round_robin`: each target in turn (default)
+//! - `least_connections`: the target with the fewest requests in flight
+
+use crate::config::LoadBalancingConfig;
+use crate::error::{BackworksError, BackworksResult};
+use crate::server::RequestData;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A target taking requests, as strategies see it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TargetMetrics {
+    pub url: String,
+    pub labels: HashMap<String, String>,
+    pub in_flight: usize,
+    pub requests: u64,
+    /// Requests that failed or were answered with a 5xx status
+    pub failures: u64,
+    /// Exponentially weighted moving average of response time, once the
+    /// target has answered
+    pub latency_ms: Option<f64>,
+}
+
+/// Picks the target of each request
+pub trait LoadBalancer: Send + Sync {
+    /// Index into `targets` of the target for `request`, or `None` to refuse
+    /// it; `targets` is never empty
+    fn select(&self, targets: &[TargetMetrics], request: &RequestData) -> Option<usize>;
+}
+
+/// Builds a strategy for one endpoint from its `load_balancing.config`
+pub type LoadBalancerFactory = Arc<dyn Fn(&Value) -> BackworksResult<Arc<dyn LoadBalancer>> + Send + Sync>;
+
+/// Strategies available to proxy endpoints, by name
+#[derive(Clone)]
+pub struct LoadBalancerRegistry {
+    factories: HashMap<String, LoadBalancerFactory>,
+}
+
+impl Default for LoadBalancerRegistry {
+    /// The built-in strategies
+    fn default() -> Self {
+        let mut registry = Self { factories: HashMap::new() };
+        registry.register("round_robin", |_| Ok(Arc::new(RoundRobin::default())));
+        registry.register("least_connections", |_| Ok(Arc::new(LeastConnections)));
+        registry
+    }
+}
+
+impl LoadBalancerRegistry {
+    /// Make `name` available to proxy endpoints, replacing any strategy of
+    /// that name
+    pub fn register<F>(&mut self, name: impl Into<String>, factory: F)
+    where
+        F: Fn(&Value) -> BackworksResult<Arc<dyn LoadBalancer>> + Send + Sync + 'static,
+    {
+        let name = name.into();
+        if self.factories.insert(name.clone(), Arc::new(factory)).is_some() {
+            tracing::warn!("Load balancer '{}' registered twice; the last registration wins", name);
+        }
+    }
+
+    /// Registered names, sorted
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.factories.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    /// The strategy `config` names for `endpoint`
+    pub fn build(&self, endpoint: &str, config: &LoadBalancingConfig) -> BackworksResult<Arc<dyn LoadBalancer>> {
+        let factory = self.factories.get(&config.algorithm).ok_or_else(|| BackworksError::config(format!(
+            "Endpoint '{}' uses unknown load balancing algorithm '{}' (available: {})",
+            endpoint, config.algorithm, self.names().join(", ")
+        )))?;
+        factory(&config.config).map_err(|e| BackworksError::config(format!(
+            "Endpoint '{}' load balancing algorithm '{}': {}", endpoint, config.algorithm, e
+        )))
+    }
+}
+
+/// Each target in turn
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    next: AtomicUsize,
+}
+
+impl LoadBalancer for RoundRobin {
+    fn select(&self, targets: &[TargetMetrics], _request: &RequestData) -> Option<usize> {
+        Some(self.next.fetch_add(1, Ordering::Relaxed) % targets.len())
+    }
+}
+
+/// The target with the fewest requests in flight, the first listed on ties
+#[derive(Debug, Default)]
+pub struct LeastConnections;
+
+impl LoadBalancer for LeastConnections {
+    fn select(&self, targets: &[TargetMetrics], _request: &RequestData) -> Option<usize> {
+        (0..targets.len()).min_by_key(|&index| targets[index].in_flight)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(url: &str, in_flight: usize) -> TargetMetrics {
+        TargetMetrics { url: url.to_string(), labels: HashMap::new(), in_flight, requests: 0, failures: 0, latency_ms: None }
+    }
+
+    #[test]
+    fn test_builtin_strategies_and_unknown_names() {
+        let registry = LoadBalancerRegistry::default();
+        let build = |algorithm: &str| registry.build("orders", &LoadBalancingConfig { algorithm: algorithm.to_string(), config: Value::Null });
+        let targets = [target("http://a", 3), target("http://b", 1), target("http://c", 1)];
+        let request = RequestData::default();
+
+        let round_robin = build("round_robin").unwrap();
+        let picks: Vec<_> = (0..4).filter_map(|_| round_robin.select(&targets, &request)).collect();
+        assert_eq!(picks, vec![0, 1, 2, 0]);
+        assert_eq!(build("least_connections").unwrap().select(&targets, &request), Some(1));
+
+        let error = build("ewma").err().unwrap().to_string();
+        assert_eq!(error, "Configuration error: Endpoint 'orders' uses unknown load balancing algorithm 'ewma' (available: least_connections, round_robin)");
+    }
+}
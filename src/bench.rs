@@ -0,0 +1,359 @@
+//! Load testing
+//!
+//! `backworks bench <endpoint>` sends requests to one endpoint of a running
+//! server, or any URL, from a number of concurrent workers until the run's
+//! duration is over or its request budget is spent. Responses are recorded
+//! in a [`MetricsRecorder`], so the run reports the same counters and
+//! p50/p95/p99 latencies as the dashboard, and a snapshot is handed out
+//! every second while the run goes on. A server under test records the
+//! requests in its own metrics too, which its dashboard charts live.
+//!
+//! Statuses of 400 and above are errors, as for the server's metrics;
+//! requests that get no response count as errors with the time they took
+//! and are reported by cause.
+
+use crate::config::BackworksConfig;
+use crate::error::{BackworksError, Result};
+use crate::metrics_recorder::{MetricSource, MetricsRecorder, MetricsSnapshot};
+use crate::routes::RoutePattern;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often progress is reported
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What a run requests
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchTarget {
+    pub method: String,
+    pub url: String,
+    /// Endpoint of the blueprint the URL was made for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+}
+
+impl BenchTarget {
+    /// The target named by `endpoint`: a URL, a path on the server at
+    /// `base`, or an endpoint of `config` whose path parameters take their
+    /// value from `params` or else a sample of their type. `method`
+    /// defaults to the endpoint's first method, or `GET`.
+    pub fn resolve(
+        endpoint: &str,
+        config: Option<&BackworksConfig>,
+        base: &str,
+        method: Option<&str>,
+        params: &HashMap<String, String>,
+    ) -> Result<Self> {
+        let method = method.map(str::to_ascii_uppercase);
+        if endpoint.starts_with("http://") || endpoint.starts_with("https://") {
+            return Ok(Self { method: method.unwrap_or_else(|| "GET".to_string()), url: endpoint.to_string(), endpoint: None });
+        }
+        let base = base.trim_end_matches('/');
+        if endpoint.starts_with('/') {
+            return Ok(Self { method: method.unwrap_or_else(|| "GET".to_string()), url: format!("{}{}", base, endpoint), endpoint: None });
+        }
+
+        let declared = config.and_then(|config| config.endpoints.get(endpoint)).ok_or_else(|| {
+            BackworksError::config(format!("Endpoint '{}' is not in the blueprint; give a path or URL to benchmark instead", endpoint))
+        })?;
+        let method = match method {
+            Some(method) if !declared.methods.iter().any(|declared| declared.eq_ignore_ascii_case(&method)) => {
+                return Err(BackworksError::config(format!(
+                    "Endpoint '{}' does not answer {} (it answers {})", endpoint, method, declared.methods.join(", ")
+                )));
+            }
+            Some(method) => method,
+            None => declared.methods.first().map(|method| method.to_ascii_uppercase()).unwrap_or_else(|| "GET".to_string()),
+        };
+        let path = RoutePattern::parse(&declared.path).sample_path(params);
+        Ok(Self { method, url: format!("{}{}", base, path), endpoint: Some(endpoint.to_string()) })
+    }
+}
+
+/// A load test of one target
+#[derive(Debug, Clone)]
+pub struct Bench {
+    target: BenchTarget,
+    headers: Vec<(String, String)>,
+    body: Option<String>,
+    concurrency: usize,
+    duration: Duration,
+    requests: Option<u64>,
+    timeout: Duration,
+}
+
+/// How a run went
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchReport {
+    #[serde(flatten)]
+    pub target: BenchTarget,
+    pub concurrency: usize,
+    /// How long the run took
+    pub elapsed_ms: f64,
+    pub requests_per_second: f64,
+    /// Requests, errors and latencies; missing when no request was made
+    pub metrics: Option<MetricsSnapshot>,
+    /// Responses by status
+    pub statuses: BTreeMap<u16, u64>,
+    /// Requests that got no response, by cause
+    pub failures: BTreeMap<String, u64>,
+}
+
+impl Bench {
+    pub fn new(target: BenchTarget) -> Self {
+        Self {
+            target,
+            headers: Vec::new(),
+            body: None,
+            concurrency: 10,
+            duration: Duration::from_secs(10),
+            requests: None,
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    pub fn with_headers(mut self, headers: Vec<(String, String)>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Body of every request, sent as JSON unless a `content-type` header
+    /// says otherwise
+    pub fn with_body(mut self, body: Option<String>) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Number of workers, each with one request in flight
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Stop after this many requests, even before the duration is over
+    pub fn with_requests(mut self, requests: Option<u64>) -> Self {
+        self.requests = requests;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Run the load test, handing `progress` the metrics so far and the
+    /// time elapsed every second
+    pub async fn run(&self, progress: impl Fn(&MetricsSnapshot, Duration)) -> Result<BenchReport> {
+        let method = reqwest::Method::from_bytes(self.target.method.as_bytes())
+            .map_err(|_| BackworksError::config(format!("Invalid method '{}'", self.target.method)))?;
+        let url = reqwest::Url::parse(&self.target.url)
+            .map_err(|e| BackworksError::config(format!("Invalid URL '{}': {}", self.target.url, e)))?;
+        let client = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .pool_max_idle_per_host(self.concurrency)
+            .build()
+            .map_err(|e| BackworksError::config(format!("Failed to create HTTP client: {}", e)))?;
+        let json = !self.headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("content-type"));
+        let mut request = client.request(method, url);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        if let Some(ref body) = self.body {
+            if json {
+                request = request.header("content-type", "application/json");
+            }
+            request = request.body(body.clone());
+        }
+        let request = request.build().map_err(|e| BackworksError::config(format!("Invalid request: {}", e)))?;
+
+        let source = MetricSource::endpoint(&self.target.method, request.url().path());
+        let recorder = MetricsRecorder::default();
+        let statuses = Arc::new(Mutex::new(BTreeMap::new()));
+        let failures = Arc::new(Mutex::new(BTreeMap::new()));
+        let sent = Arc::new(AtomicU64::new(0));
+        let started = Instant::now();
+        let deadline = started + self.duration;
+
+        let mut workers = tokio::task::JoinSet::new();
+        for _ in 0..self.concurrency {
+            let (client, recorder, statuses, failures, sent) = (client.clone(), recorder.clone(), statuses.clone(), failures.clone(), sent.clone());
+            let (request, source, budget) = (request.try_clone(), source.clone(), self.requests);
+            workers.spawn(async move {
+                let Some(request) = request else { return };
+                while Instant::now() < deadline && budget.is_none_or(|budget| sent.fetch_add(1, Ordering::Relaxed) < budget) {
+                    let Some(attempt) = request.try_clone() else { return };
+                    let start = Instant::now();
+                    // Responses only count once their body has arrived
+                    let outcome = match client.execute(attempt).await {
+                        Ok(response) => {
+                            let status = response.status().as_u16();
+                            response.bytes().await.map(|_| status)
+                        }
+                        Err(e) => Err(e),
+                    };
+                    match outcome {
+                        Ok(status) => {
+                            recorder.record(source.clone(), start.elapsed(), status >= 400);
+                            *statuses.lock().unwrap_or_else(|e| e.into_inner()).entry(status).or_insert(0) += 1;
+                        }
+                        Err(e) => {
+                            recorder.record(source.clone(), start.elapsed(), true);
+                            *failures.lock().unwrap_or_else(|e| e.into_inner()).entry(failure_cause(&e)).or_insert(0) += 1;
+                        }
+                    }
+                }
+            });
+        }
+
+        let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + PROGRESS_INTERVAL, PROGRESS_INTERVAL);
+        loop {
+            tokio::select! {
+                done = workers.join_next() => match done {
+                    Some(result) => result.map_err(|e| BackworksError::config(format!("Load test worker failed: {}", e)))?,
+                    None => break,
+                },
+                _ = ticks.tick() => {
+                    if let Some(snapshot) = recorder.snapshot(&source) {
+                        progress(&snapshot, started.elapsed());
+                    }
+                }
+            }
+        }
+
+        let elapsed = started.elapsed();
+        let metrics = recorder.snapshot(&source);
+        let requests = metrics.as_ref().map_or(0, |metrics| metrics.requests);
+        let statuses = std::mem::take(&mut *statuses.lock().unwrap_or_else(|e| e.into_inner()));
+        let failures = std::mem::take(&mut *failures.lock().unwrap_or_else(|e| e.into_inner()));
+        Ok(BenchReport {
+            target: self.target.clone(),
+            concurrency: self.concurrency,
+            elapsed_ms: elapsed.as_secs_f64() * 1000.0,
+            requests_per_second: requests as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            metrics,
+            statuses,
+            failures,
+        })
+    }
+}
+
+fn failure_cause(error: &reqwest::Error) -> String {
+    if error.is_timeout() {
+        "timeout".to_string()
+    } else if error.is_connect() {
+        "connection failed".to_string()
+    } else if error.is_body() || error.is_decode() {
+        "response body failed".to_string()
+    } else {
+        "request failed".to_string()
+    }
+}
+
+/// One line of progress, as printed while a run goes on
+pub fn progress_line(snapshot: &MetricsSnapshot, elapsed: Duration) -> String {
+    format!(
+        "⏱️  {:>4.0}s  {} requests  {:.1}/s  p50 {:.1}ms  p95 {:.1}ms  p99 {:.1}ms  {} errors",
+        elapsed.as_secs_f64(), snapshot.requests, snapshot.requests as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        snapshot.p50_latency_ms, snapshot.p95_latency_ms, snapshot.p99_latency_ms, snapshot.errors,
+    )
+}
+
+impl BenchReport {
+    pub fn render_text(&self) -> String {
+        let mut out = String::new();
+        self.write_text(&mut out).expect("writing to a String cannot fail");
+        out
+    }
+
+    fn write_text(&self, out: &mut String) -> fmt::Result {
+        write!(out, "Load test of {} {}", self.target.method, self.target.url)?;
+        if let Some(ref endpoint) = self.target.endpoint {
+            write!(out, " ({})", endpoint)?;
+        }
+        writeln!(out, "\n  {} workers for {:.1}s", self.concurrency, self.elapsed_ms / 1000.0)?;
+        let Some(ref metrics) = self.metrics else {
+            return writeln!(out, "\nNo requests were made");
+        };
+        writeln!(out, "\n  Requests   {} ({:.1}/s)", metrics.requests, self.requests_per_second)?;
+        writeln!(out, "  Errors     {} ({:.1}%)", metrics.errors, metrics.error_rate * 100.0)?;
+        writeln!(out, "  Latency    avg {:.2}ms  p50 {:.2}ms  p95 {:.2}ms  p99 {:.2}ms",
+            metrics.avg_latency_ms, metrics.p50_latency_ms, metrics.p95_latency_ms, metrics.p99_latency_ms)?;
+        if !self.statuses.is_empty() {
+            let statuses: Vec<String> = self.statuses.iter().map(|(status, count)| format!("{} × {}", status, count)).collect();
+            writeln!(out, "  Statuses   {}", statuses.join(", "))?;
+        }
+        for (cause, count) in &self.failures {
+            writeln!(out, "  ⚠️  {} × {}", cause, count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use axum::Router;
+
+    #[test]
+    fn test_targets_resolve_endpoints_paths_and_urls() {
+        let config: BackworksConfig = serde_yaml::from_str(r#"
+name: shop
+endpoints:
+  order: { path: "/orders/{id:uuid}/items/{item}", methods: [PUT, DELETE] }
+"#).unwrap();
+        let params = HashMap::from([("item".to_string(), "42".to_string())]);
+        let target = BenchTarget::resolve("order", Some(&config), "http://localhost:3000/", None, &params).unwrap();
+        assert_eq!((target.method.as_str(), target.url.as_str()), ("PUT", "http://localhost:3000/orders/00000000-0000-0000-0000-000000000001/items/42"));
+        assert_eq!(BenchTarget::resolve("order", Some(&config), "http://localhost:3000", Some("delete"), &params).unwrap().method, "DELETE");
+
+        let err = BenchTarget::resolve("order", Some(&config), "http://localhost:3000", Some("GET"), &params).unwrap_err();
+        assert!(err.to_string().contains("does not answer GET"), "{}", err);
+        assert!(BenchTarget::resolve("users", Some(&config), "http://localhost:3000", None, &params).is_err());
+
+        let path = BenchTarget::resolve("/health", None, "http://localhost:3000", None, &params).unwrap();
+        assert_eq!(path.url, "http://localhost:3000/health");
+        let url = BenchTarget::resolve("https://api.example.com/users", None, "http://localhost:3000", Some("post"), &params).unwrap();
+        assert_eq!((url.method.as_str(), url.endpoint), ("POST", None));
+    }
+
+    #[tokio::test]
+    async fn test_runs_stop_at_their_request_budget_and_count_statuses() {
+        let hits = Arc::new(AtomicU64::new(0));
+        let app = Router::new().route("/users", get({
+            let hits = hits.clone();
+            move || async move {
+                match hits.fetch_add(1, Ordering::Relaxed) % 4 {
+                    0 => axum::http::StatusCode::SERVICE_UNAVAILABLE,
+                    _ => axum::http::StatusCode::OK,
+                }
+            }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/users", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let target = BenchTarget { method: "GET".to_string(), url, endpoint: None };
+        let report = Bench::new(target).with_concurrency(4).with_requests(Some(40)).run(|_, _| {}).await.unwrap();
+        let metrics = report.metrics.clone().unwrap();
+        assert_eq!((metrics.requests, metrics.errors), (40, 10));
+        assert_eq!(report.statuses, BTreeMap::from([(200, 30), (503, 10)]));
+        assert!(report.failures.is_empty());
+        assert!(metrics.p99_latency_ms >= metrics.p50_latency_ms);
+        let text = report.render_text();
+        assert!(text.contains("Errors     10 (25.0%)") && text.contains("Statuses   200 × 30, 503 × 10"), "{}", text);
+
+        let unreachable = BenchTarget { method: "GET".to_string(), url: "http://127.0.0.1:1/".to_string(), endpoint: None };
+        let report = Bench::new(unreachable).with_concurrency(2).with_requests(Some(3)).run(|_, _| {}).await.unwrap();
+        assert_eq!(report.failures, BTreeMap::from([("connection failed".to_string(), 3)]));
+    }
+}
@@ -0,0 +1,683 @@
+//! Multi-file blueprints
+//!
+//! A blueprint may list other files, directories or glob patterns under
+//! `includes:`. Included files are resolved recursively relative to the file
+//! that includes them and deep-merged into it before the configuration is
+//! parsed. The including file wins over anything it includes, endpoint
+//! collections are combined, and two endpoints serving the same method and
+//! path are reported as a conflict.
+//!
+//! String values may reference environment variables as `${VAR}` or
+//! `${VAR:-default}`; `$${...}` escapes a literal placeholder.
+//!
+//! Per-environment overrides live under `environments:`. The profile named by
+//! `--env` or `BACKWORKS_ENV` is deep-merged over the blueprint: mappings
+//! merge key by key, list endpoints are matched by path, other lists and
+//! scalars are replaced.
+
+use crate::error::{BackworksError, Result};
+use crate::routes::RoutePattern;
+use serde_yaml::{Mapping, Value};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+const INCLUDES_KEY: &str = "includes";
+const ENDPOINTS_KEY: &str = "endpoints";
+const ENVIRONMENTS_KEY: &str = "environments";
+
+/// Environment variable selecting the active environment profile
+pub const ENVIRONMENT_VAR: &str = "BACKWORKS_ENV";
+
+/// Where a merged endpoint was declared
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EndpointOrigin {
+    /// Endpoint name (map format only)
+    pub name: Option<String>,
+    pub path: String,
+    pub methods: Vec<String>,
+    pub source: PathBuf,
+}
+
+/// Blueprint after all includes have been merged
+#[derive(Debug, Clone)]
+pub struct ResolvedBlueprint {
+    pub value: Value,
+    /// Every file that contributed, root first
+    pub sources: Vec<PathBuf>,
+    pub endpoints: Vec<EndpointOrigin>,
+    /// Referenced environment variables that are unset and have no default
+    pub missing_env: Vec<String>,
+    /// Environment profile applied, if any
+    pub environment: Option<String>,
+    /// Profiles declared under `environments:`
+    pub environments: Vec<String>,
+}
+
+/// Load `path`, merge all of its includes and apply the environment profile
+/// selected by `BACKWORKS_ENV`
+pub fn resolve(path: &Path) -> Result<ResolvedBlueprint> {
+    let environment = std::env::var(ENVIRONMENT_VAR).ok().filter(|env| !env.is_empty());
+    resolve_for_environment(path, environment.as_deref())
+}
+
+pub fn resolve_for_environment(path: &Path, environment: Option<&str>) -> Result<ResolvedBlueprint> {
+    resolve_with(Resolver::default(), path, environment)
+}
+
+/// Like [`resolve`], but with `${VAR}` placeholders left as written, for
+/// builds that run where the variables are set
+pub fn resolve_unexpanded(path: &Path) -> Result<ResolvedBlueprint> {
+    let environment = std::env::var(ENVIRONMENT_VAR).ok().filter(|env| !env.is_empty());
+    resolve_with(Resolver { keep_placeholders: true, ..Default::default() }, path, environment.as_deref())
+}
+
+fn resolve_with(mut resolver: Resolver, path: &Path, environment: Option<&str>) -> Result<ResolvedBlueprint> {
+    let mut value = resolver.load(path)?;
+    detect_conflicts(&resolver.endpoints)?;
+    let (environment, environments) = apply_environment(&mut value, environment)?;
+
+    let mut missing_env = resolver.missing_env;
+    missing_env.sort();
+    missing_env.dedup();
+
+    Ok(ResolvedBlueprint {
+        value,
+        sources: resolver.sources,
+        endpoints: resolver.endpoints,
+        missing_env,
+        environment,
+        environments,
+    })
+}
+
+/// Strip `environments:` from the blueprint and merge the selected profile
+/// over it. Returns the applied profile and every declared profile name.
+fn apply_environment(value: &mut Value, environment: Option<&str>) -> Result<(Option<String>, Vec<String>)> {
+    let Value::Mapping(mapping) = value else {
+        return Ok((None, Vec::new()));
+    };
+
+    let profiles = match mapping.remove(ENVIRONMENTS_KEY) {
+        None | Some(Value::Null) => Mapping::new(),
+        Some(Value::Mapping(profiles)) => profiles,
+        Some(_) => return Err(BackworksError::config("'environments' must map profile names to overrides")),
+    };
+    let names: Vec<String> = profiles.keys().filter_map(|k| k.as_str().map(String::from)).collect();
+
+    let Some(environment) = environment else {
+        return Ok((None, names));
+    };
+    if profiles.is_empty() {
+        tracing::warn!("Environment '{}' selected but the blueprint declares no environments", environment);
+        return Ok((None, names));
+    }
+
+    let profile = match profiles.get(environment) {
+        Some(Value::Mapping(profile)) => profile.clone(),
+        Some(Value::Null) => Mapping::new(),
+        Some(_) => return Err(BackworksError::config(format!("Environment '{}' must be a mapping of overrides", environment))),
+        None => return Err(BackworksError::config(format!(
+            "Unknown environment '{}'; the blueprint defines: {}",
+            environment,
+            names.join(", ")
+        ))),
+    };
+    if profile.contains_key(INCLUDES_KEY) || profile.contains_key(ENVIRONMENTS_KEY) {
+        return Err(BackworksError::config(format!(
+            "Environment '{}' cannot declare includes or nested environments",
+            environment
+        )));
+    }
+
+    override_with(value, Value::Mapping(profile));
+    Ok((Some(environment.to_string()), names))
+}
+
+/// Deep-merge an environment profile, with the profile winning
+fn override_with(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) if key.as_str() == Some(ENDPOINTS_KEY) && existing.is_sequence() => {
+                        override_endpoint_list(existing, value)
+                    }
+                    Some(existing) if existing.is_mapping() && value.is_mapping() => override_with(existing, value),
+                    _ => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// List-format endpoints have no names, so profile entries are matched to
+/// base endpoints by path; unmatched entries are added
+fn override_endpoint_list(base: &mut Value, overlay: Value) {
+    let (Value::Sequence(endpoints), Value::Sequence(overrides)) = (&mut *base, overlay.clone()) else {
+        *base = overlay;
+        return;
+    };
+
+    for endpoint in overrides {
+        let path = endpoint.get("path").cloned();
+        match endpoints.iter_mut().find(|existing| path.is_some() && existing.get("path") == path.as_ref()) {
+            Some(existing) => override_with(existing, endpoint),
+            None => endpoints.push(endpoint),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Resolver {
+    /// Files currently being resolved, to detect include cycles
+    stack: Vec<PathBuf>,
+    seen: HashSet<PathBuf>,
+    sources: Vec<PathBuf>,
+    endpoints: Vec<EndpointOrigin>,
+    missing_env: Vec<String>,
+    keep_placeholders: bool,
+}
+
+impl Resolver {
+    fn load(&mut self, path: &Path) -> Result<Value> {
+        let canonical = path.canonicalize()
+            .map_err(|e| BackworksError::config(format!("Cannot read blueprint {}: {}", path.display(), e)))?;
+
+        if self.stack.contains(&canonical) {
+            let chain: Vec<String> = self.stack.iter().chain([&canonical]).map(|p| p.display().to_string()).collect();
+            return Err(BackworksError::config(format!("Blueprint include cycle: {}", chain.join(" -> "))));
+        }
+        // A file reached twice through different includes contributes once
+        if !self.seen.insert(canonical.clone()) {
+            return Ok(Value::Mapping(Mapping::new()));
+        }
+
+        let content = std::fs::read_to_string(&canonical)?;
+        let mut value: Value = serde_yaml::from_str(&content)
+            .map_err(|e| BackworksError::config(format!("Failed to parse {}: {}", path.display(), e)))?;
+        if value.is_null() {
+            value = Value::Mapping(Mapping::new());
+        }
+        if !self.keep_placeholders {
+            interpolate_env(&mut value, &|name| std::env::var(name).ok(), &mut self.missing_env);
+        }
+        let Value::Mapping(ref mut mapping) = value else {
+            return Err(BackworksError::config(format!("Blueprint {} must be a YAML mapping", path.display())));
+        };
+
+        self.sources.push(canonical.clone());
+        self.stack.push(canonical.clone());
+        let includes = mapping.remove(INCLUDES_KEY);
+        self.record_endpoints(mapping, &canonical);
+
+        let base_dir = canonical.parent().unwrap_or(Path::new(".")).to_path_buf();
+        let mut merged = Value::Mapping(Mapping::new());
+        for include in include_patterns(includes, &canonical)? {
+            for file in expand_include(&base_dir, &include)? {
+                let included = self.load(&file)?;
+                merge(&mut merged, included, &file)?;
+            }
+        }
+        self.stack.pop();
+
+        merge(&mut merged, value, &canonical)?;
+        Ok(merged)
+    }
+
+    fn record_endpoints(&mut self, mapping: &Mapping, source: &Path) {
+        let entries: Vec<(Option<String>, &Value)> = match mapping.get(ENDPOINTS_KEY) {
+            Some(Value::Mapping(endpoints)) => endpoints.iter()
+                .map(|(name, endpoint)| (name.as_str().map(String::from), endpoint))
+                .collect(),
+            Some(Value::Sequence(endpoints)) => endpoints.iter().map(|endpoint| (None, endpoint)).collect(),
+            _ => Vec::new(),
+        };
+
+        for (name, endpoint) in entries {
+            let Some(path) = endpoint.get("path").and_then(Value::as_str) else {
+                continue;
+            };
+            let methods = match endpoint.get("methods").or_else(|| endpoint.get("method")) {
+                Some(Value::String(method)) => vec![method.to_uppercase()],
+                Some(Value::Sequence(methods)) => methods.iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_uppercase)
+                    .collect(),
+                _ => vec!["GET".to_string()],
+            };
+
+            self.endpoints.push(EndpointOrigin {
+                name,
+                path: path.to_string(),
+                methods,
+                source: source.to_path_buf(),
+            });
+        }
+    }
+}
+
+fn include_patterns(includes: Option<Value>, source: &Path) -> Result<Vec<String>> {
+    match includes {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::String(pattern)) => Ok(vec![pattern]),
+        Some(Value::Sequence(patterns)) => patterns.into_iter()
+            .map(|p| match p {
+                Value::String(pattern) => Ok(pattern),
+                _ => Err(BackworksError::config(format!("Includes in {} must be strings", source.display()))),
+            })
+            .collect(),
+        Some(_) => Err(BackworksError::config(format!("Includes in {} must be a list of paths", source.display()))),
+    }
+}
+
+/// Expand an include entry into blueprint files: a file, a directory
+/// (every YAML file beneath it) or a glob pattern
+fn expand_include(base_dir: &Path, include: &str) -> Result<Vec<PathBuf>> {
+    let target = base_dir.join(include);
+
+    let mut files = if target.is_dir() {
+        let pattern = target.join("**").join("*");
+        glob_files(&pattern.to_string_lossy())?
+            .into_iter()
+            .filter(|f| is_yaml(f))
+            .collect()
+    } else if target.is_file() {
+        vec![target]
+    } else {
+        let files = glob_files(&target.to_string_lossy())?;
+        if files.is_empty() {
+            return Err(BackworksError::config(format!("Include '{}' matched no files", include)));
+        }
+        files
+    };
+
+    files.sort();
+    Ok(files)
+}
+
+fn glob_files(pattern: &str) -> Result<Vec<PathBuf>> {
+    let paths = glob::glob(pattern)
+        .map_err(|e| BackworksError::config(format!("Invalid include pattern '{}': {}", pattern, e)))?;
+    Ok(paths.filter_map(|p| p.ok()).filter(|p| p.is_file()).collect())
+}
+
+fn is_yaml(path: &Path) -> bool {
+    matches!(path.extension().and_then(|e| e.to_str()), Some("yaml" | "yml"))
+}
+
+/// Deep-merge `overlay` into `base`. Mappings merge key by key with the
+/// overlay winning; endpoint collections are combined and duplicate endpoint
+/// names are rejected.
+fn merge(base: &mut Value, overlay: Value, source: &Path) -> Result<()> {
+    let (Value::Mapping(base), Value::Mapping(overlay)) = (&mut *base, overlay) else {
+        return Ok(());
+    };
+
+    for (key, value) in overlay {
+        if key.as_str() == Some(ENDPOINTS_KEY) {
+            merge_endpoints(base, value, source)?;
+            continue;
+        }
+
+        match base.get_mut(&key) {
+            Some(existing @ Value::Mapping(_)) if value.is_mapping() => merge(existing, value, source)?,
+            _ => {
+                base.insert(key, value);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn merge_endpoints(base: &mut Mapping, endpoints: Value, source: &Path) -> Result<()> {
+    let key = Value::String(ENDPOINTS_KEY.to_string());
+    let Some(existing) = base.get_mut(&key) else {
+        base.insert(key, endpoints);
+        return Ok(());
+    };
+
+    match (existing, endpoints) {
+        (Value::Mapping(existing), Value::Mapping(incoming)) => {
+            for (name, endpoint) in incoming {
+                if existing.contains_key(&name) {
+                    return Err(BackworksError::config(format!(
+                        "Endpoint '{}' in {} is already defined by another blueprint file",
+                        name.as_str().unwrap_or_default(),
+                        source.display()
+                    )));
+                }
+                existing.insert(name, endpoint);
+            }
+            Ok(())
+        }
+        (Value::Sequence(existing), Value::Sequence(incoming)) => {
+            existing.extend(incoming);
+            Ok(())
+        }
+        (_, Value::Null) => Ok(()),
+        _ => Err(BackworksError::config(format!(
+            "Endpoints in {} use a different format (list vs map) than the blueprint including it",
+            source.display()
+        ))),
+    }
+}
+
+/// Replace `${VAR}` / `${VAR:-default}` placeholders in every string value.
+/// Values stay strings, so a token of digits is not turned into a number; a
+/// value tagged `!int`, `!float` or `!bool` is converted to that type once
+/// interpolated.
+pub fn interpolate_env(
+    value: &mut Value,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    missing: &mut Vec<String>,
+) {
+    match value {
+        Value::String(text) if text.contains('$') => {
+            *value = Value::String(interpolate_str(text, lookup, missing));
+        }
+        Value::Tagged(tagged) if SCALAR_TAGS.iter().any(|tag| tagged.tag == *tag) => {
+            interpolate_env(&mut tagged.value, lookup, missing);
+            let Value::String(ref text) = tagged.value else {
+                return;
+            };
+            let text = text.trim();
+            let typed = if tagged.tag == "int" {
+                text.parse::<i64>().ok().map(Value::from)
+            } else if tagged.tag == "float" {
+                text.parse::<f64>().ok().map(Value::from)
+            } else {
+                text.parse::<bool>().ok().map(Value::from)
+            };
+            // Left as text, deserializing reports the field's expected type
+            *value = typed.unwrap_or_else(|| tagged.value.clone());
+        }
+        Value::Sequence(items) => {
+            for item in items {
+                interpolate_env(item, lookup, missing);
+            }
+        }
+        Value::Mapping(mapping) => {
+            // Inline handler code keeps its own `${...}` template literals
+            for (_, item) in mapping.iter_mut().filter(|(key, _)| key.as_str() != Some("handler")) {
+                interpolate_env(item, lookup, missing);
+            }
+        }
+        Value::Tagged(tagged) => interpolate_env(&mut tagged.value, lookup, missing),
+        _ => {}
+    }
+}
+
+/// Tags converting an interpolated value to a number or boolean
+const SCALAR_TAGS: [&str; 3] = ["int", "float", "bool"];
+
+fn interpolate_str(
+    text: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    missing: &mut Vec<String>,
+) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        if let Some(escaped) = after.strip_prefix("${") {
+            output.push_str("${");
+            rest = escaped;
+            continue;
+        }
+        let Some(body) = after.strip_prefix('{') else {
+            output.push('$');
+            rest = after;
+            continue;
+        };
+        let Some(end) = body.find('}') else {
+            output.push_str("${");
+            rest = body;
+            continue;
+        };
+        let expression = &body[..end];
+
+        let (name, default) = match expression.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (expression, None),
+        };
+        // Only `${UPPER_CASE}` names are variables; anything else stays literal
+        if !is_env_name(name) {
+            output.push_str("${");
+            rest = body;
+            continue;
+        }
+
+        match lookup(name).filter(|v| !v.is_empty() || default.is_none()) {
+            Some(resolved) => output.push_str(&resolved),
+            None => match default {
+                Some(default) => output.push_str(default),
+                None => missing.push(name.to_string()),
+            },
+        }
+
+        rest = &body[end + 1..];
+    }
+    output.push_str(rest);
+    output
+}
+
+fn is_env_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase() || c == '_')
+        && name.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn detect_conflicts(endpoints: &[EndpointOrigin]) -> Result<()> {
+    for (i, first) in endpoints.iter().enumerate() {
+        for second in &endpoints[i + 1..] {
+            if RoutePattern::parse(&first.path).shape() != RoutePattern::parse(&second.path).shape() {
+                continue;
+            }
+            if let Some(method) = first.methods.iter().find(|m| second.methods.contains(m)) {
+                return Err(BackworksError::config(format!(
+                    "Duplicate route {} {}: declared in {} and {}",
+                    method,
+                    first.path,
+                    first.source.display(),
+                    second.source.display()
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("backworks_includes_{}_{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_directory_and_glob_includes_merge() {
+        let dir = temp_dir("merge");
+        let root = write(&dir, "main.yaml", r#"
+name: "shop"
+includes: ["./endpoints/", "./extra/*.yml"]
+server: { port: 4000 }
+endpoints:
+  root:
+    path: "/"
+    methods: ["GET"]
+"#);
+        write(&dir, "endpoints/users.yaml", r#"
+server: { host: "127.0.0.1", port: 1 }
+endpoints:
+  users:
+    path: "/users/{id}"
+    methods: ["GET"]
+"#);
+        write(&dir, "endpoints/nested/orders.yaml", "endpoints:\n  orders:\n    path: /orders\n    methods: [POST]\n");
+        write(&dir, "extra/health.yml", "endpoints:\n  status:\n    path: /status\n    methods: [GET]\n");
+
+        let resolved = resolve(&root).unwrap();
+        let endpoints = resolved.value.get("endpoints").unwrap().as_mapping().unwrap();
+        assert_eq!(endpoints.len(), 4);
+        assert_eq!(resolved.sources.len(), 4);
+        // The including file wins, included values fill the gaps
+        assert_eq!(resolved.value["server"]["port"].as_u64(), Some(4000));
+        assert_eq!(resolved.value["server"]["host"].as_str(), Some("127.0.0.1"));
+        assert!(resolved.value.get("includes").is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_env_interpolation() {
+        let lookup = |name: &str| match name {
+            "PORT" => Some("9090".to_string()),
+            "HOST" => Some("api.internal".to_string()),
+            "EMPTY" => Some(String::new()),
+            "TOKEN" => Some("0123".to_string()),
+            _ => None,
+        };
+        let mut value: Value = serde_yaml::from_str(r#"
+server: { port: "${PORT}", host: "${HOST}" }
+token: "${TOKEN}"
+workers: !int "${PORT}"
+debug: !bool "${DEBUG:-false}"
+url: "https://${HOST}:${PORT:-80}/v1"
+fallback: "${EMPTY:-dflt}"
+literal: "$${HOST} costs $5"
+missing: "${NOT_SET}"
+template: "Hello ${user.name}"
+runtime: { handler: "function handler(req) { return `${HOST}`; }" }
+"#).unwrap();
+        let mut missing = Vec::new();
+        interpolate_env(&mut value, &lookup, &mut missing);
+
+        // Values stay text unless tagged with a type
+        assert_eq!(value["server"]["port"].as_str(), Some("9090"));
+        let server: crate::config::ServerConfig = serde_yaml::from_value(value["server"].clone()).unwrap();
+        assert_eq!(server.port, 9090);
+        assert_eq!(value["token"].as_str(), Some("0123"));
+        assert_eq!(value["workers"].as_u64(), Some(9090));
+        assert_eq!(value["debug"].as_bool(), Some(false));
+        assert_eq!(value["url"].as_str(), Some("https://api.internal:9090/v1"));
+        assert_eq!(value["fallback"].as_str(), Some("dflt"));
+        assert_eq!(value["literal"].as_str(), Some("${HOST} costs $5"));
+        assert_eq!(value["template"].as_str(), Some("Hello ${user.name}"));
+        assert!(value["runtime"]["handler"].as_str().unwrap().contains("`${HOST}`"));
+        assert_eq!(missing, vec!["NOT_SET"]);
+    }
+
+    #[test]
+    fn test_duplicate_routes_and_cycles_are_rejected() {
+        let dir = temp_dir("conflict");
+        let root = write(&dir, "main.yaml", "name: api\nincludes: [a.yaml]\nendpoints:\n  one:\n    path: /users/{id}\n    methods: [GET]\n");
+        write(&dir, "a.yaml", "endpoints:\n  two:\n    path: /users/{user_id}\n    methods: [GET, PUT]\n");
+        let error = resolve(&root).unwrap_err().to_string();
+        assert!(error.contains("Duplicate route GET"), "{}", error);
+
+        let cyclic = write(&dir, "b.yaml", "includes: [c.yaml]\n");
+        write(&dir, "c.yaml", "includes: [b.yaml]\n");
+        assert!(resolve(&cyclic).unwrap_err().to_string().contains("cycle"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shadowed_routes_across_files() {
+        let dir = temp_dir("shadowing");
+        let root = write(&dir, "main.yaml", r#"
+name: api
+includes: [admin.yaml]
+endpoints:
+  user:
+    path: /users/{id}
+    methods: [GET]
+    runtime: { language: javascript, handler: "function handler() {}" }
+"#);
+        write(&dir, "admin.yaml", r#"
+endpoints:
+  me:
+    path: /users/me
+    methods: [GET]
+    runtime: { language: javascript, handler: "function handler() {}" }
+"#);
+        let config = crate::config::load_yaml_config(&root).await.unwrap();
+        assert_eq!(crate::config::route_conflicts(&config)[0].subject(), "user");
+
+        let strict = write(&dir, "strict.yaml", "name: api\nroute_conflicts: error\nincludes: [main.yaml]\nendpoints: {}\n");
+        let error = crate::config::load_yaml_config(&strict).await.unwrap_err().to_string();
+        assert!(error.contains("'user' (/users/{id}) is shadowed by 'me' (/users/me)"), "{}", error);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_environment_profile_overrides_blueprint() {
+        let dir = temp_dir("environments");
+        let root = write(&dir, "main.yaml", r#"
+name: api
+server:
+  port: 3000
+logging:
+  level: debug
+plugins:
+  proxy:
+    enabled: true
+    config:
+      targets: [http://localhost:9000, http://localhost:9001]
+endpoints:
+  - path: /orders
+    method: GET
+    description: Local orders
+environments:
+  staging:
+    server:
+      port: 8080
+    logging:
+      level: info
+    plugins:
+      proxy:
+        config:
+          targets: [http://orders.staging]
+    endpoints:
+      - path: /orders
+        description: Staging orders
+"#);
+
+        let resolved = resolve_for_environment(&root, Some("staging")).unwrap();
+        let value = &resolved.value;
+        assert_eq!(resolved.environment.as_deref(), Some("staging"));
+        assert_eq!(value["server"]["port"].as_u64(), Some(8080));
+        assert_eq!(value["logging"]["level"].as_str(), Some("info"));
+        assert_eq!(value["endpoints"][0]["method"].as_str(), Some("GET"));
+        assert_eq!(value["endpoints"][0]["description"].as_str(), Some("Staging orders"));
+        assert_eq!(value["endpoints"].as_sequence().unwrap().len(), 1);
+        assert_eq!(value["plugins"]["proxy"]["config"]["targets"], serde_yaml::from_str::<Value>("[http://orders.staging]").unwrap());
+        assert_eq!(value["plugins"]["proxy"]["enabled"].as_bool(), Some(true));
+        assert!(value.get("environments").is_none());
+
+        let resolved = resolve_for_environment(&root, None).unwrap();
+        assert_eq!(resolved.value["server"]["port"].as_u64(), Some(3000));
+        assert_eq!(resolved.environments, vec!["staging"]);
+
+        let error = resolve_for_environment(&root, Some("prod")).unwrap_err().to_string();
+        assert!(error.contains("Unknown environment 'prod'"), "{}", error);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
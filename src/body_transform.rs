@@ -0,0 +1,359 @@
+//! Declarative body transforms
+//!
+//! An endpoint's `transform` block reshapes JSON bodies without a script:
+//! `request` rewrites the payload before the handler runs, `response`
+//! rewrites successful response bodies, then `response_filter` keeps its
+//! `include_fields` and drops its `exclude_fields`. Fields are addressed with
+//! [JSONPath](crate::jsonpath).
+//!
+//! A [`BodyTransform`] removes `json_field_removal`, adds
+//! `json_field_addition`, moves values along `json_path_mapping` and finally
+//! renames the keys in `json_field_renaming`. A mapping whose source and
+//! target share a wildcard prefix, like `$.items[*].sku` to
+//! `$.items[*].product.id`, moves the value within each element.
+//!
+//! A request transform's `input_format` decodes the payload into JSON before
+//! anything else, and a response transform's `output_format` encodes the
+//! final body; see [`content_format`](crate::content_format).
+//!
+//! `status_code_mapping` sends a handler status as another status, with the
+//! handler's body or a replacement; templates render in
+//! [`templates`](crate::templates).
+
+use crate::config::{BodyTransform, ContentFormat, EndpointConfig, ResponseFilter, StatusCodeMapping};
+use crate::content_format::{self, FormatOptions};
+use crate::error::{BackworksError, Result};
+use crate::jsonpath::{JsonPath, Segment};
+use crate::response_filter;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// A `json_path_mapping` entry split at the wildcards both paths share
+#[derive(Debug, Clone)]
+struct PathMapping {
+    /// Selects the values the move applies within
+    scope: JsonPath,
+    from: JsonPath,
+    to: JsonPath,
+}
+
+impl PathMapping {
+    fn new(from: &str, to: &str) -> Result<Self> {
+        let (from, to) = (JsonPath::parse(from)?, JsonPath::parse(to)?);
+        let shared = from.segments().iter().zip(to.segments())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let scope_len = from.segments()[..shared].iter()
+            .rposition(|segment| *segment == Segment::Wildcard)
+            .map_or(0, |index| index + 1);
+
+        let mapping = Self { scope: from.prefix(scope_len), from: from.suffix(scope_len), to: to.suffix(scope_len) };
+        if !mapping.from.is_definite() || !mapping.to.is_definite() {
+            return Err(BackworksError::config(format!(
+                "Mapping '{}' -> '{}' needs its wildcards at the same place in both paths", from, to
+            )));
+        }
+        if mapping.from.segments().is_empty() {
+            return Err(BackworksError::config(format!("Mapping '{}' -> '{}' must move a field", from, to)));
+        }
+        Ok(mapping)
+    }
+
+    fn apply(&self, body: &mut Value) {
+        self.scope.for_each_mut(body, &mut |item| {
+            if let Some(value) = self.from.take(item) {
+                self.to.set(item, value);
+            }
+        });
+    }
+}
+
+/// A [`BodyTransform`] with its paths parsed
+#[derive(Debug, Clone, Default)]
+pub struct CompiledTransform {
+    removals: Vec<JsonPath>,
+    additions: Vec<(JsonPath, Value)>,
+    mappings: Vec<PathMapping>,
+    renames: Vec<(JsonPath, String)>,
+}
+
+impl CompiledTransform {
+    pub fn new(transform: &BodyTransform) -> Result<Self> {
+        let unsupported = [
+            ("string_replace", transform.string_replace.is_some()),
+            ("string_template", transform.string_template.is_some()),
+            ("transform_script", transform.transform_script.is_some()),
+        ];
+        if let Some((field, _)) = unsupported.iter().find(|(_, set)| *set) {
+            return Err(BackworksError::config(format!(
+                "`{}` is not supported in body transforms; use a template or runtime handler", field
+            )));
+        }
+
+        // Sorted so overlapping entries apply in a stable order
+        let sorted = |map: &Option<HashMap<String, String>>| {
+            let mut entries: Vec<(String, String)> = map.clone().unwrap_or_default().into_iter().collect();
+            entries.sort();
+            entries
+        };
+
+        let removals = transform.json_field_removal.iter().flatten()
+            .map(|path| JsonPath::parse(path))
+            .collect::<Result<Vec<_>>>()?;
+        let mut additions = transform.json_field_addition.iter().flatten()
+            .map(|(path, value)| Ok((JsonPath::parse(path)?, value.clone())))
+            .collect::<Result<Vec<_>>>()?;
+        additions.sort_by_key(|(path, _)| path.to_string());
+        let mappings = sorted(&transform.json_path_mapping).iter()
+            .map(|(from, to)| PathMapping::new(from, to))
+            .collect::<Result<Vec<_>>>()?;
+        let renames = sorted(&transform.json_field_renaming).into_iter()
+            .map(|(path, name)| {
+                let path = JsonPath::parse(&path)?;
+                match path.segments().last() {
+                    Some(Segment::Key(_)) => Ok((path, name)),
+                    _ => Err(BackworksError::config(format!("Renamed field '{}' must end in a field name", path))),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { removals, additions, mappings, renames })
+    }
+
+    pub fn apply(&self, mut body: Value) -> Value {
+        for path in &self.removals {
+            path.remove(&mut body);
+        }
+        for (path, value) in &self.additions {
+            path.set(&mut body, value.clone());
+        }
+        for mapping in &self.mappings {
+            mapping.apply(&mut body);
+        }
+        for (path, name) in &self.renames {
+            let Some(Segment::Key(key)) = path.segments().last() else {
+                continue;
+            };
+            path.prefix(path.segments().len() - 1).for_each_mut(&mut body, &mut |parent| {
+                if let Some(object) = parent.as_object_mut() {
+                    if let Some(value) = object.remove(key) {
+                        object.insert(name.clone(), value);
+                    }
+                }
+            });
+        }
+        body
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct EndpointTransforms {
+    request: HashMap<String, CompiledTransform>,
+    response: HashMap<String, CompiledTransform>,
+    filters: HashMap<String, ResponseFilter>,
+    input_formats: HashMap<String, (ContentFormat, FormatOptions)>,
+    output_formats: HashMap<String, (ContentFormat, FormatOptions)>,
+    statuses: HashMap<String, HashMap<u16, StatusCodeMapping>>,
+}
+
+impl EndpointTransforms {
+    /// Parse the transforms of every endpoint up front
+    pub fn new(endpoints: &HashMap<String, EndpointConfig>) -> Result<Self> {
+        let mut transforms = Self::default();
+        for (name, endpoint) in endpoints {
+            let Some(ref transform) = endpoint.transform else {
+                continue;
+            };
+            let context = |kind: &'static str| move |e: BackworksError| {
+                let message = match e {
+                    BackworksError::Config(message) => message,
+                    other => other.to_string(),
+                };
+                BackworksError::config(format!("Endpoint '{}' {} transform: {}", name, kind, message))
+            };
+            if let Some(ref request) = transform.request {
+                if request.output_format.is_some() {
+                    return Err(context("request")(BackworksError::config("`output_format` only applies to response transforms")));
+                }
+                if let Some(ref format) = request.input_format {
+                    let options = FormatOptions::new(request.format_options.as_ref()).map_err(context("request"))?;
+                    transforms.input_formats.insert(name.clone(), (format.clone(), options));
+                }
+                transforms.request.insert(name.clone(), CompiledTransform::new(request).map_err(context("request"))?);
+            }
+            if let Some(ref response) = transform.response {
+                if response.input_format.is_some() {
+                    return Err(context("response")(BackworksError::config("`input_format` only applies to request transforms")));
+                }
+                if let Some(ref format) = response.output_format {
+                    let options = FormatOptions::new(response.format_options.as_ref()).map_err(context("response"))?;
+                    transforms.output_formats.insert(name.clone(), (format.clone(), options));
+                }
+                transforms.response.insert(name.clone(), CompiledTransform::new(response).map_err(context("response"))?);
+            }
+            if let Some(ref filter) = transform.response_filter {
+                response_filter::validate(filter).map_err(context("response_filter"))?;
+                transforms.filters.insert(name.clone(), filter.clone());
+            }
+            if let Some(ref statuses) = transform.status_code_mapping {
+                validate_status_mapping(statuses).map_err(context("status_code_mapping"))?;
+                transforms.statuses.insert(name.clone(), statuses.clone());
+            }
+        }
+        Ok(transforms)
+    }
+
+    /// How the endpoint sends a handler `status`, if it maps it
+    pub fn map_status(&self, endpoint: &str, status: u16) -> Option<&StatusCodeMapping> {
+        self.statuses.get(endpoint)?.get(&status)
+    }
+
+    /// Decode a request body in the endpoint's `input_format`. Without one,
+    /// only bodies sent as JSON are read.
+    pub fn decode_request(&self, endpoint: &str, content_type: Option<&str>, body: &[u8]) -> std::result::Result<Option<Value>, String> {
+        if body.iter().all(u8::is_ascii_whitespace) {
+            return Ok(None);
+        }
+        match self.input_formats.get(endpoint) {
+            Some((format, options)) => content_format::decode(format, body, options).map(Some),
+            None => {
+                let is_json = content_type
+                    .and_then(|content_type| content_type.split(';').next())
+                    .map(|mime| mime.trim().to_ascii_lowercase())
+                    .is_some_and(|mime| mime == "application/json" || mime.ends_with("+json"));
+                Ok(is_json.then(|| serde_json::from_slice(body).ok()).flatten())
+            }
+        }
+    }
+
+    /// Whether the endpoint reads its requests in a format other than JSON
+    pub fn converts_request(&self, endpoint: &str) -> bool {
+        self.input_formats.contains_key(endpoint)
+    }
+
+    /// Encode a response body in the endpoint's `output_format`, as its
+    /// content type and bytes
+    pub fn encode_response(&self, endpoint: &str, body: &Value) -> Option<std::result::Result<(&'static str, Vec<u8>), String>> {
+        let (format, options) = self.output_formats.get(endpoint)?;
+        Some(content_format::encode(format, body, options).map(|bytes| (format.content_type(), bytes)))
+    }
+
+    pub fn transform_request(&self, endpoint: &str, body: Value) -> Value {
+        match self.request.get(endpoint) {
+            Some(transform) => transform.apply(body),
+            None => body,
+        }
+    }
+
+    /// A successful response body after the endpoint's transform and filter
+    pub fn transform_response(&self, endpoint: &str, body: Value) -> Value {
+        let body = match self.response.get(endpoint) {
+            Some(transform) => transform.apply(body),
+            None => body,
+        };
+        match self.filters.get(endpoint) {
+            Some(filter) => response_filter::apply(filter, body),
+            None => body,
+        }
+    }
+}
+
+fn validate_status_mapping(statuses: &HashMap<u16, StatusCodeMapping>) -> Result<()> {
+    for (from, to) in statuses {
+        for status in [*from, to.status()] {
+            if !(100..=599).contains(&status) {
+                return Err(BackworksError::config(format!("{} is not an HTTP status code", status)));
+            }
+        }
+        if let StatusCodeMapping::Response { body: Some(_), body_template: Some(_), .. } = to {
+            return Err(BackworksError::config(format!("Status {} sets both `body` and `body_template`", from)));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn transforms(yaml: &str) -> Result<EndpointTransforms> {
+        let endpoint: EndpointConfig = serde_yaml::from_str(yaml).unwrap();
+        EndpointTransforms::new(&HashMap::from([("orders".to_string(), endpoint)]))
+    }
+
+    #[test]
+    fn test_request_and_response_reshaping() {
+        let transforms = transforms(r#"
+path: /orders
+transform:
+  request:
+    json_path_mapping:
+      "$.customer.name": "$.customerName"
+      "$.items[*].sku": "$.items[*].product.id"
+    json_field_addition:
+      "$.source": "api"
+    json_field_removal: ["$.customer.internal_id"]
+  response:
+    json_field_renaming:
+      "$.data[*].created": "createdAt"
+  response_filter:
+    exclude_fields: ["$.data[*].cost"]
+"#).unwrap();
+
+        let request = json!({ "customer": { "name": "Ada", "internal_id": 7 }, "items": [{ "sku": "A1", "qty": 2 }] });
+        assert_eq!(transforms.transform_request("orders", request), json!({
+            "customerName": "Ada",
+            "customer": {},
+            "items": [{ "product": { "id": "A1" }, "qty": 2 }],
+            "source": "api",
+        }));
+
+        let response = json!({ "data": [{ "id": 1, "created": "2026-01-01", "cost": 3 }] });
+        assert_eq!(transforms.transform_response("orders", response), json!({ "data": [{ "id": 1, "createdAt": "2026-01-01" }] }));
+        assert_eq!(transforms.transform_response("other", json!({ "cost": 3 })), json!({ "cost": 3 }));
+    }
+
+    #[test]
+    fn test_invalid_transforms_are_rejected() {
+        let err = transforms("path: /x\ntransform:\n  request:\n    json_path_mapping: { \"$.items[*].a\": \"$.b\" }\n").unwrap_err();
+        assert!(err.to_string().contains("Endpoint 'orders' request transform: "), "{}", err);
+        assert!(err.to_string().contains("wildcards at the same place"));
+        let err = transforms("path: /x\ntransform:\n  response:\n    transform_script: x\n").unwrap_err();
+        assert!(err.to_string().contains("`transform_script` is not supported"));
+        let err = transforms("path: /x\ntransform:\n  response_filter:\n    include_fields: [\"$..id\"]\n").unwrap_err();
+        assert!(err.to_string().contains("recursive descent"));
+        let err = transforms("path: /x\ntransform:\n  status_code_mapping:\n    404: 1000\n").unwrap_err();
+        assert!(err.to_string().contains("Endpoint 'orders' status_code_mapping transform: 1000 is not an HTTP status code"), "{}", err);
+        let err = transforms("path: /x\ntransform:\n  status_code_mapping:\n    404: { status: 200, body: [], body_template: '[]' }\n").unwrap_err();
+        assert!(err.to_string().contains("both `body` and `body_template`"), "{}", err);
+        let err = transforms("path: /x\ntransform:\n  request:\n    output_format: xml\n").unwrap_err();
+        assert!(err.to_string().contains("`output_format` only applies to response transforms"), "{}", err);
+        let err = transforms("path: /x\ntransform:\n  response:\n    output_format: csv\n    format_options: { delimiter: '||' }\n").unwrap_err();
+        assert!(err.to_string().contains("single character"), "{}", err);
+    }
+
+    #[test]
+    fn test_format_conversion() {
+        let transforms = transforms(r#"
+path: /orders
+transform:
+  request:
+    input_format: xml
+    json_field_renaming:
+      "$.qty": "quantity"
+  response:
+    output_format: csv
+"#).unwrap();
+
+        let body = transforms.decode_request("orders", Some("application/xml"), b"<order><sku>A1</sku><qty>2</qty></order>").unwrap().unwrap();
+        assert_eq!(transforms.transform_request("orders", body), json!({ "sku": "A1", "quantity": "2" }));
+        assert!(transforms.decode_request("orders", None, b"<order>").is_err());
+        assert_eq!(transforms.decode_request("other", Some("application/json; charset=utf-8"), b"{\"a\":1}").unwrap(), Some(json!({ "a": 1 })));
+        assert_eq!(transforms.decode_request("other", Some("text/plain"), b"{\"a\":1}").unwrap(), None);
+
+        let (content_type, bytes) = transforms.encode_response("orders", &json!([{ "sku": "A1" }])).unwrap().unwrap();
+        assert_eq!((content_type, bytes.as_slice()), ("text/csv; charset=utf-8", b"sku\nA1\n".as_slice()));
+        assert!(transforms.encode_response("other", &json!({})).is_none());
+    }
+}
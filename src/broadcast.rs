@@ -0,0 +1,288 @@
+//! Event broadcast hub
+//!
+//! Fans events out to dashboard WebSocket clients, server-sent event streams
+//! and live capture viewers. Every subscriber has its own bounded queue, so
+//! publishing never waits on a reader: when a subscriber falls behind, the
+//! [`SlowConsumerPolicy`] decides whether its oldest event, the new event or
+//! the subscriber itself is dropped.
+
+use crate::config::{RealTimeConfig, SlowConsumerPolicy};
+use crate::error::{BackworksError, BackworksResult};
+use futures::Stream;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+const DEFAULT_QUEUE_SIZE: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HubEvent {
+    pub topic: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub data: Value,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct HubStats {
+    pub subscribers: usize,
+    pub published: u64,
+    /// Events discarded because a subscriber's queue was full
+    pub dropped: u64,
+    /// Subscribers ended by the `disconnect` policy
+    pub disconnected: u64,
+}
+
+#[derive(Debug)]
+struct Subscriber {
+    /// `None` receives every topic
+    topics: Option<HashSet<String>>,
+    queue: Mutex<VecDeque<Arc<HubEvent>>>,
+    notify: Notify,
+    closed: AtomicBool,
+    dropped: AtomicU64,
+}
+
+impl Subscriber {
+    fn wants(&self, topic: &str) -> bool {
+        self.topics.as_ref().is_none_or(|topics| topics.contains(topic))
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.notify.notify_one();
+    }
+}
+
+#[derive(Debug)]
+struct HubInner {
+    queue_size: usize,
+    policy: SlowConsumerPolicy,
+    max_subscribers: Option<usize>,
+    subscribers: Mutex<HashMap<u64, Arc<Subscriber>>>,
+    next_id: AtomicU64,
+    published: AtomicU64,
+    dropped: AtomicU64,
+    disconnected: AtomicU64,
+}
+
+/// Shared handle to the hub; clones publish to the same subscribers
+#[derive(Debug, Clone)]
+pub struct BroadcastHub {
+    inner: Arc<HubInner>,
+}
+
+impl Default for BroadcastHub {
+    fn default() -> Self {
+        Self::new(DEFAULT_QUEUE_SIZE, SlowConsumerPolicy::default(), None)
+    }
+}
+
+impl BroadcastHub {
+    pub fn new(queue_size: usize, policy: SlowConsumerPolicy, max_subscribers: Option<usize>) -> Self {
+        Self {
+            inner: Arc::new(HubInner {
+                queue_size: queue_size.max(1),
+                policy,
+                max_subscribers,
+                subscribers: Mutex::new(HashMap::new()),
+                next_id: AtomicU64::new(0),
+                published: AtomicU64::new(0),
+                dropped: AtomicU64::new(0),
+                disconnected: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    pub fn from_config(config: Option<&RealTimeConfig>) -> Self {
+        match config {
+            Some(config) => Self::new(
+                config.queue_size.unwrap_or(DEFAULT_QUEUE_SIZE),
+                config.slow_consumer.unwrap_or_default(),
+                config.max_subscribers,
+            ),
+            None => Self::default(),
+        }
+    }
+
+    /// Receive events of `topics`, or of every topic when `None`
+    pub fn subscribe(&self, topics: Option<HashSet<String>>) -> BackworksResult<Subscription> {
+        let mut subscribers = self.inner.subscribers.lock().unwrap();
+        if let Some(max) = self.inner.max_subscribers {
+            if subscribers.len() >= max {
+                return Err(BackworksError::server(format!("Event hub is full ({} subscribers)", max)));
+            }
+        }
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let subscriber = Arc::new(Subscriber {
+            topics,
+            queue: Mutex::new(VecDeque::new()),
+            notify: Notify::new(),
+            closed: AtomicBool::new(false),
+            dropped: AtomicU64::new(0),
+        });
+        subscribers.insert(id, subscriber.clone());
+        Ok(Subscription { id, hub: self.inner.clone(), subscriber })
+    }
+
+    /// Queue an event for every subscriber of `topic` without waiting on any
+    /// of them; returns how many subscribers received it
+    pub fn publish(&self, topic: &str, data: Value) -> usize {
+        let event = Arc::new(HubEvent { topic: topic.to_string(), timestamp: chrono::Utc::now(), data });
+        self.inner.published.fetch_add(1, Ordering::Relaxed);
+
+        let mut subscribers = self.inner.subscribers.lock().unwrap();
+        let mut delivered = 0;
+        let mut disconnected = Vec::new();
+        for (id, subscriber) in subscribers.iter() {
+            if !subscriber.wants(topic) {
+                continue;
+            }
+            let mut queue = subscriber.queue.lock().unwrap();
+            if queue.len() >= self.inner.queue_size {
+                subscriber.dropped.fetch_add(1, Ordering::Relaxed);
+                self.inner.dropped.fetch_add(1, Ordering::Relaxed);
+                match self.inner.policy {
+                    SlowConsumerPolicy::DropOldest => {
+                        queue.pop_front();
+                    }
+                    SlowConsumerPolicy::DropNewest => continue,
+                    SlowConsumerPolicy::Disconnect => {
+                        queue.clear();
+                        disconnected.push(*id);
+                        continue;
+                    }
+                }
+            }
+            queue.push_back(event.clone());
+            drop(queue);
+            subscriber.notify.notify_one();
+            delivered += 1;
+        }
+
+        for id in disconnected {
+            if let Some(subscriber) = subscribers.remove(&id) {
+                subscriber.close();
+                self.inner.disconnected.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!("Disconnected slow event subscriber {}", id);
+            }
+        }
+        delivered
+    }
+
+    pub fn stats(&self) -> HubStats {
+        HubStats {
+            subscribers: self.inner.subscribers.lock().unwrap().len(),
+            published: self.inner.published.load(Ordering::Relaxed),
+            dropped: self.inner.dropped.load(Ordering::Relaxed),
+            disconnected: self.inner.disconnected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// One subscriber's end of the hub; unsubscribes when dropped
+#[derive(Debug)]
+pub struct Subscription {
+    id: u64,
+    hub: Arc<HubInner>,
+    subscriber: Arc<Subscriber>,
+}
+
+impl Subscription {
+    /// The next event, or `None` once the hub disconnected this subscriber
+    pub async fn recv(&mut self) -> Option<Arc<HubEvent>> {
+        loop {
+            if let Some(event) = self.subscriber.queue.lock().unwrap().pop_front() {
+                return Some(event);
+            }
+            if self.subscriber.closed.load(Ordering::Acquire) {
+                return None;
+            }
+            // A notification sent before we wait is kept as a permit
+            self.subscriber.notify.notified().await;
+        }
+    }
+
+    /// Events this subscriber lost to a full queue
+    pub fn dropped(&self) -> u64 {
+        self.subscriber.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn into_stream(self) -> impl Stream<Item = Arc<HubEvent>> + Send {
+        futures::stream::unfold(self, |mut subscription| async move {
+            let event = subscription.recv().await?;
+            Some((event, subscription))
+        })
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.hub.subscribers.lock().unwrap().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn topics(names: &[&str]) -> Option<HashSet<String>> {
+        Some(names.iter().map(|name| name.to_string()).collect())
+    }
+
+    async fn drain(subscription: &mut Subscription) -> Vec<Value> {
+        let mut events = Vec::new();
+        while let Ok(Some(event)) = tokio::time::timeout(std::time::Duration::from_millis(20), subscription.recv()).await {
+            events.push(event.data.clone());
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn test_slow_consumer_policies() {
+        for (policy, expected) in [
+            (SlowConsumerPolicy::DropOldest, vec![json!(2), json!(3)]),
+            (SlowConsumerPolicy::DropNewest, vec![json!(0), json!(1)]),
+        ] {
+            let hub = BroadcastHub::new(2, policy, None);
+            let mut slow = hub.subscribe(None).unwrap();
+            for n in 0..4 {
+                hub.publish("requests", json!(n));
+            }
+            assert_eq!(drain(&mut slow).await, expected, "{:?}", policy);
+            assert_eq!(slow.dropped(), 2);
+            assert_eq!(hub.stats().dropped, 2);
+        }
+
+        let hub = BroadcastHub::new(2, SlowConsumerPolicy::Disconnect, None);
+        let mut slow = hub.subscribe(None).unwrap();
+        let mut fast = hub.subscribe(None).unwrap();
+        hub.publish("requests", json!(0));
+        hub.publish("requests", json!(1));
+        assert_eq!(fast.recv().await.unwrap().data, json!(0));
+        assert_eq!(hub.publish("requests", json!(2)), 1);
+        assert!(slow.recv().await.is_none());
+        assert_eq!(drain(&mut fast).await, vec![json!(1), json!(2)]);
+        assert_eq!(hub.stats(), HubStats { subscribers: 1, published: 3, dropped: 1, disconnected: 1 });
+    }
+
+    #[tokio::test]
+    async fn test_topics_limits_and_unsubscribe() {
+        let hub = BroadcastHub::new(8, SlowConsumerPolicy::DropOldest, Some(2));
+        let mut capture = hub.subscribe(topics(&["capture"])).unwrap();
+        let everything = hub.subscribe(None).unwrap();
+        assert!(hub.subscribe(None).is_err());
+
+        let waiter = tokio::spawn(async move { capture.recv().await.map(|event| event.topic.clone()) });
+        assert_eq!(hub.publish("requests", json!({})), 1);
+        assert_eq!(hub.publish("capture", json!({})), 2);
+        assert_eq!(waiter.await.unwrap().as_deref(), Some("capture"));
+
+        drop(everything);
+        assert_eq!(hub.stats().subscribers, 0);
+        assert!(hub.subscribe(None).is_ok());
+    }
+}
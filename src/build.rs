@@ -0,0 +1,341 @@
+//! Deployable builds
+//!
+//! `backworks build` turns a project into a directory that runs on its own:
+//!
+//! - the blueprint is validated, its includes are merged and the selected
+//!   environment profile applied; `${VAR}` placeholders and encrypted
+//!   values are kept as written, to be filled in where the build runs
+//! - settings meant for development are stripped, unless the build's
+//!   security profile has `enable_debug`: scenarios, fault injection, live
+//!   capture and the dashboard's blueprint editor; the profile is recorded
+//!   as `security.profile`, so the server enforces it
+//! - the merged blueprint replaces the files it was made of, and the
+//!   project's other files (handlers, seed data, static files, plugin
+//!   libraries) are copied, leaving out what a [package](crate::package)
+//!   leaves out; handler files the blueprint names must be among them
+//! - plugins are resolved: built-in ones to this Backworks version,
+//!   registry-installed ones to the version `package.json` records, and
+//!   external libraries to their checksum
+//! - `build.json` lists the build's settings, plugins and the SHA-256
+//!   checksum of every file
+
+use crate::config::BackworksConfig;
+use crate::error::{BackworksError, Result};
+use crate::package::{checksum, package_path, project_files, PackagedFile};
+use crate::plugin::registry::{library_file, Project, PLUGIN_DIR};
+use crate::plugin::PluginType;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+/// Manifest file at the root of a build
+pub const MANIFEST: &str = "build.json";
+/// Manifest layout this version writes
+const FORMAT: u32 = 1;
+
+/// Top-level settings only used while developing
+const DEV_SETTINGS: &[&str] = &["scenarios", "capture"];
+/// Endpoint settings only used while developing
+const DEV_ENDPOINT_SETTINGS: &[&str] = &["scenarios", "chaos"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildManifest {
+    pub format: u32,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Backworks version that made the build
+    pub backworks_version: String,
+    pub created_at: DateTime<Utc>,
+    pub target: String,
+    /// Security profile the build was stripped for and runs with
+    pub profile: String,
+    /// Environment profile applied
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    /// Blueprint path inside the build
+    pub blueprint: String,
+    /// Settings removed from the blueprint, as dotted paths
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stripped: Vec<String>,
+    pub plugins: Vec<ResolvedPlugin>,
+    pub files: Vec<PackagedFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedPlugin {
+    pub name: String,
+    pub plugin_type: PluginType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Library of an external plugin, or command of a process plugin
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Checksum of an external plugin's library
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// Build the project in `root`, whose validated blueprint `blueprint` loads
+/// as `config`, for `target` into the directory `output`. `profile` names
+/// the security profile, by default the blueprint's or, failing that,
+/// `development` for the `development` target and `production` otherwise.
+pub fn build(
+    root: &Path,
+    blueprint: &Path,
+    config: &BackworksConfig,
+    target: &str,
+    profile: Option<&str>,
+    output: &Path,
+) -> Result<BuildManifest> {
+    let entry = blueprint.strip_prefix(root).ok()
+        .and_then(package_path)
+        .ok_or_else(|| BackworksError::config(format!("Blueprint {} is outside the project directory {}", blueprint.display(), root.display())))?;
+    let profile = profile.map(String::from)
+        .or_else(|| config.security.as_ref()?.profile.clone())
+        .unwrap_or_else(|| if target == "development" { "development" } else { "production" }.to_string());
+    let settings = crate::security::profile_named(
+        &config.security.as_ref().map(|security| security.profiles.clone()).unwrap_or_default(),
+        &profile,
+    )?;
+
+    let resolved = crate::blueprint::resolve_unexpanded(blueprint)?;
+    let mut document = resolved.value;
+    let stripped = if settings.enable_debug { Vec::new() } else { strip_dev_settings(&mut document) };
+    set_profile(&mut document, &profile);
+
+    let plugins = resolve_plugins(root, config)?;
+    let sources: HashSet<_> = resolved.sources.iter().collect();
+    let mut contents: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+    for (name, path) in project_files(root, output)? {
+        let skip = name == MANIFEST || path.canonicalize().is_ok_and(|path| sources.contains(&path));
+        if !skip {
+            contents.insert(name, std::fs::read(&path)?);
+        }
+    }
+    contents.insert(entry.clone(), serde_yaml::to_string(&document)?.into_bytes());
+    check_handlers(config, &contents)?;
+
+    prepare_output(output)?;
+    let mut files = Vec::new();
+    for (name, data) in &contents {
+        let path = output.join(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, data)?;
+        files.push(PackagedFile { path: name.clone(), size: data.len() as u64, sha256: checksum(data) });
+    }
+
+    let manifest = BuildManifest {
+        format: FORMAT,
+        name: config.name.clone(),
+        version: config.version.clone(),
+        backworks_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: Utc::now(),
+        target: target.to_string(),
+        profile,
+        environment: resolved.environment,
+        blueprint: entry,
+        stripped,
+        plugins,
+        files,
+    };
+    std::fs::write(output.join(MANIFEST), serde_json::to_vec_pretty(&manifest)?)?;
+    Ok(manifest)
+}
+
+/// Remove development settings from a blueprint document, returning their
+/// dotted paths
+fn strip_dev_settings(document: &mut Value) -> Vec<String> {
+    let mut stripped = Vec::new();
+    let Value::Mapping(mapping) = document else {
+        return stripped;
+    };
+    for key in DEV_SETTINGS {
+        if mapping.remove(*key).is_some() {
+            stripped.push(key.to_string());
+        }
+    }
+    if let Some(Value::Mapping(dashboard)) = mapping.get_mut("dashboard") {
+        if dashboard.get("editor") != Some(&Value::Bool(false)) {
+            dashboard.insert(Value::from("editor"), Value::Bool(false));
+            stripped.push("dashboard.editor".to_string());
+        }
+    }
+
+    let endpoints: Vec<(String, &mut Mapping)> = match mapping.get_mut("endpoints") {
+        Some(Value::Mapping(endpoints)) => endpoints.iter_mut()
+            .filter_map(|(name, endpoint)| Some((name.as_str()?.to_string(), endpoint.as_mapping_mut()?)))
+            .collect(),
+        Some(Value::Sequence(endpoints)) => endpoints.iter_mut()
+            .filter_map(Value::as_mapping_mut)
+            .map(|endpoint| (endpoint.get("path").and_then(Value::as_str).unwrap_or_default().to_string(), endpoint))
+            .collect(),
+        _ => Vec::new(),
+    };
+    for (name, endpoint) in endpoints {
+        for key in DEV_ENDPOINT_SETTINGS {
+            if endpoint.remove(*key).is_some() {
+                stripped.push(format!("endpoints.{}.{}", name, key));
+            }
+        }
+    }
+    stripped.sort();
+    stripped
+}
+
+fn set_profile(document: &mut Value, profile: &str) {
+    let Value::Mapping(mapping) = document else {
+        return;
+    };
+    let security = mapping.entry(Value::from("security")).or_insert_with(|| Value::Mapping(Mapping::new()));
+    if !security.is_mapping() {
+        *security = Value::Mapping(Mapping::new());
+    }
+    if let Value::Mapping(security) = security {
+        security.insert(Value::from("profile"), Value::from(profile));
+    }
+}
+
+/// Versions of the plugins the blueprint enables
+fn resolve_plugins(root: &Path, config: &BackworksConfig) -> Result<Vec<ResolvedPlugin>> {
+    let installed = Project::open(root).map(|project| project.installed()).unwrap_or_default();
+    let mut plugins = Vec::new();
+    for (name, plugin) in config.plugins.iter().filter(|(_, plugin)| plugin.enabled) {
+        let mut resolved = ResolvedPlugin {
+            name: name.clone(),
+            plugin_type: plugin.plugin_type.clone(),
+            version: None,
+            path: plugin.path.clone(),
+            sha256: None,
+        };
+        match plugin.plugin_type {
+            PluginType::Builtin => resolved.version = Some(env!("CARGO_PKG_VERSION").to_string()),
+            PluginType::Process => {}
+            PluginType::External => {
+                let library = plugin.path.clone().unwrap_or_else(|| format!("{}/{}", PLUGIN_DIR, library_file(name)));
+                let data = std::fs::read(root.join(&library)).map_err(|e| BackworksError::config(format!(
+                    "Plugin {} needs its library {}: {}", name, library, e
+                )))?;
+                resolved.version = installed.get(name).cloned();
+                resolved.path = Some(library);
+                resolved.sha256 = Some(checksum(&data));
+            }
+        }
+        plugins.push(resolved);
+    }
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(plugins)
+}
+
+/// Fail when a runtime handler file is not part of the build
+fn check_handlers(config: &BackworksConfig, contents: &BTreeMap<String, Vec<u8>>) -> Result<()> {
+    let mut names: Vec<_> = config.endpoints.keys().collect();
+    names.sort();
+    for name in names {
+        let Some(ref runtime) = config.endpoints[name].runtime else {
+            continue;
+        };
+        let handler = runtime.handler.trim();
+        if !crate::analyzer::is_handler_file(handler) {
+            continue;
+        }
+        let bundled = package_path(Path::new(handler.trim_start_matches("./")))
+            .is_some_and(|path| contents.contains_key(&path));
+        if !bundled {
+            return Err(BackworksError::config(format!(
+                "Handler {} of endpoint '{}' is not a file inside the project", handler, name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Empty `output`, which must be missing, empty or an earlier build
+fn prepare_output(output: &Path) -> Result<()> {
+    if output.exists() {
+        let earlier_build = output.join(MANIFEST).is_file();
+        if !earlier_build && std::fs::read_dir(output)?.next().is_some() {
+            return Err(BackworksError::config(format!(
+                "Output directory {} is not empty and holds no earlier build", output.display()
+            )));
+        }
+        std::fs::remove_dir_all(output)?;
+    }
+    std::fs::create_dir_all(output)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builds_bundle_the_merged_blueprint_for_their_profile() {
+        let root = std::env::temp_dir().join(format!("backworks_build_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("handlers")).unwrap();
+        std::fs::create_dir_all(root.join("endpoints")).unwrap();
+        std::fs::create_dir_all(root.join(PLUGIN_DIR)).unwrap();
+        let blueprint = r#"
+name: Shop API
+version: "2.0"
+includes: ["endpoints/*.yaml"]
+server: { port: "${SHOP_PORT:-3000}" }
+capture: { enabled: true }
+dashboard: { enabled: true, port: 3001 }
+plugins:
+  geo: { enabled: true, plugin_type: external }
+  audit: { enabled: true }
+"#;
+        std::fs::write(root.join("backworks.yaml"), blueprint).unwrap();
+        std::fs::write(root.join("endpoints/users.yaml"), r#"
+endpoints:
+  users:
+    path: /users
+    runtime: { language: javascript, handler: ./handlers/users.js }
+    chaos: { error_rate: 0.5 }
+"#).unwrap();
+        std::fs::write(root.join("handlers/users.js"), "function handler() {}").unwrap();
+        std::fs::write(root.join(PLUGIN_DIR).join(library_file("geo")), "library").unwrap();
+        std::fs::write(root.join("package.json"), r#"{ "plugins": { "geo": "1.4.0" } }"#).unwrap();
+        let config = crate::config::parse_blueprint(
+            crate::blueprint::resolve_for_environment(&root.join("backworks.yaml"), None).unwrap().value
+        ).unwrap();
+        let output = root.join("target/production");
+
+        let manifest = build(&root, &root.join("backworks.yaml"), &config, "production", None, &output).unwrap();
+        assert_eq!(manifest.profile, "production");
+        assert_eq!(manifest.stripped, vec!["capture", "dashboard.editor", "endpoints.users.chaos"]);
+        let library = format!("{}/{}", PLUGIN_DIR, library_file("geo"));
+        assert_eq!(manifest.files.iter().map(|file| file.path.as_str()).collect::<Vec<_>>(),
+            vec!["backworks.yaml", "handlers/users.js", "package.json", library.as_str()]);
+        let geo = &manifest.plugins[1];
+        assert_eq!((geo.name.as_str(), geo.version.as_deref(), geo.sha256.as_deref()), ("geo", Some("1.4.0"), Some(checksum(b"library").as_str())));
+        assert_eq!(manifest.plugins[0].version.as_deref(), Some(env!("CARGO_PKG_VERSION")));
+
+        // One blueprint, placeholders kept, development settings gone
+        let built: Value = serde_yaml::from_str(&std::fs::read_to_string(output.join("backworks.yaml")).unwrap()).unwrap();
+        assert_eq!(built["server"]["port"], Value::from("${SHOP_PORT:-3000}"));
+        assert_eq!(built["security"]["profile"], Value::from("production"));
+        assert_eq!(built["dashboard"]["editor"], Value::Bool(false));
+        assert!(built.get("capture").is_none() && built.get("includes").is_none());
+        assert!(built["endpoints"]["users"].get("chaos").is_none());
+        assert!(!output.join("endpoints").exists());
+        let written: BuildManifest = serde_json::from_slice(&std::fs::read(output.join(MANIFEST)).unwrap()).unwrap();
+        assert_eq!(written.files, manifest.files);
+
+        // Development builds keep everything; stray output directories are left alone
+        let manifest = build(&root, &root.join("backworks.yaml"), &config, "development", None, &output).unwrap();
+        assert!(manifest.stripped.is_empty());
+        let err = build(&root, &root.join("backworks.yaml"), &config, "production", None, &root.join("endpoints")).unwrap_err();
+        assert!(err.to_string().contains("is not empty"), "{}", err);
+
+        std::fs::remove_file(root.join(PLUGIN_DIR).join(library_file("geo"))).unwrap();
+        let err = build(&root, &root.join("backworks.yaml"), &config, "production", None, &output).unwrap_err();
+        assert!(err.to_string().contains("Plugin geo needs its library"), "{}", err);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
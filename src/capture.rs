@@ -1,7 +1,15 @@
+use crate::broadcast::BroadcastHub;
 use crate::config::CaptureConfig;
 use crate::error::{BackworksError, BackworksResult};
+use crate::journal::REDACTED_HEADERS;
+use crate::security::Scrubber;
+use axum::body::{Body, HttpBody};
+use axum::extract::{Request, State};
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::Response;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use uuid::Uuid;
@@ -47,6 +55,21 @@ pub enum CaptureStatus {
     Stopped,
 }
 
+/// Whether requests are being recorded, and into which session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingStatus {
+    pub recording: bool,
+    pub session: Option<CaptureSession>,
+}
+
+/// Start, resume or stop recording
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecordingSwitch {
+    pub recording: bool,
+    /// Name of the session started when none is current
+    pub name: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CaptureFilter {
     pub methods: Option<Vec<String>>,
@@ -62,6 +85,8 @@ pub struct CaptureHandler {
     sessions: Arc<RwLock<HashMap<Uuid, CaptureSession>>>,
     captured_requests: Arc<RwLock<HashMap<Uuid, Vec<CapturedRequest>>>>,
     active_session: Arc<RwLock<Option<Uuid>>>,
+    events: Option<BroadcastHub>,
+    scrubber: Option<Arc<Scrubber>>,
 }
 
 impl Clone for CaptureHandler {
@@ -71,6 +96,8 @@ impl Clone for CaptureHandler {
             sessions: Arc::clone(&self.sessions),
             captured_requests: Arc::clone(&self.captured_requests),
             active_session: Arc::clone(&self.active_session),
+            events: self.events.clone(),
+            scrubber: self.scrubber.clone(),
         }
     }
 }
@@ -82,6 +109,42 @@ impl CaptureHandler {
             sessions: Arc::new(RwLock::new(HashMap::new())),
             captured_requests: Arc::new(RwLock::new(HashMap::new())),
             active_session: Arc::new(RwLock::new(None)),
+            events: None,
+            scrubber: None,
+        }
+    }
+
+    /// Mask secrets in what is recorded, as the `strip_secrets` security
+    /// profile asks
+    pub fn with_scrubber(mut self, scrubber: Option<Arc<Scrubber>>) -> Self {
+        self.scrubber = scrubber;
+        self
+    }
+
+    // Headers, query parameters and body with their secrets masked
+    fn scrub(&self, pairs: &mut [&mut HashMap<String, String>], body: &mut Option<serde_json::Value>) {
+        let Some(ref scrubber) = self.scrubber else {
+            return;
+        };
+        for (key, value) in pairs.iter_mut().flat_map(|pairs| pairs.iter_mut()) {
+            *value = scrubber.scrub_field(key, value);
+        }
+        if let Some(body) = body {
+            scrubber.scrub_value(body);
+        }
+    }
+
+    /// Stream captured requests and responses live as `capture` events
+    pub fn with_events(mut self, events: BroadcastHub) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    fn publish(&self, request: &CapturedRequest) {
+        if let Some(ref events) = self.events {
+            if let Ok(data) = serde_json::to_value(request) {
+                events.publish("capture", data);
+            }
         }
     }
 
@@ -187,10 +250,13 @@ impl CaptureHandler {
             return Ok(Uuid::nil());
         }
         
+        let (mut headers, mut query_params, mut body) = (headers, query_params, body);
+        self.scrub(&mut [&mut headers, &mut query_params], &mut body);
+        let path = self.scrubber.as_ref().map_or(path.clone(), |scrubber| scrubber.scrub(&path));
         let request_id = Uuid::new_v4();
         let captured_request = CapturedRequest {
             id: request_id,
-            session_id: None,
+            session_id: Some(session_id.to_string()),
             timestamp: chrono::Utc::now(),
             method,
             path,
@@ -206,6 +272,7 @@ impl CaptureHandler {
         
         let mut captured_requests = self.captured_requests.write().await;
         if let Some(requests) = captured_requests.get_mut(&session_id) {
+            self.publish(&captured_request);
             requests.push(captured_request);
             
             // Update session request count
@@ -227,6 +294,8 @@ impl CaptureHandler {
         body: Option<serde_json::Value>,
         duration: std::time::Duration,
     ) -> BackworksResult<()> {
+        let (mut headers, mut body) = (headers, body);
+        self.scrub(&mut [&mut headers], &mut body);
         let captured_response = CapturedResponse {
             status_code,
             headers,
@@ -238,6 +307,7 @@ impl CaptureHandler {
             if let Some(request) = requests.iter_mut().find(|r| r.id == request_id) {
                 request.response = Some(captured_response);
                 request.duration = Some(duration);
+                self.publish(request);
                 tracing::debug!("Captured response for request: {}", request_id);
                 break;
             }
@@ -246,6 +316,62 @@ impl CaptureHandler {
         Ok(())
     }
 
+    /// The session new requests are recorded in, if any
+    pub async fn current_session(&self) -> Option<CaptureSession> {
+        let active_session = *self.active_session.read().await;
+        self.sessions.read().await.get(&active_session?).cloned()
+    }
+
+    /// Whether requests arriving now are recorded
+    pub async fn is_recording(&self) -> bool {
+        self.current_session().await.is_some_and(|session| matches!(session.status, CaptureStatus::Active))
+    }
+
+    pub async fn recording_status(&self) -> RecordingStatus {
+        RecordingStatus { recording: self.is_recording().await, session: self.current_session().await }
+    }
+
+    /// Resume the current session or start one named `switch.name`, else
+    /// `default_name`; or stop the current session
+    pub async fn switch_recording(&self, switch: RecordingSwitch, default_name: &str) -> BackworksResult<RecordingStatus> {
+        match (switch.recording, self.current_session().await) {
+            (true, Some(session)) => self.resume_session(session.id).await?,
+            (true, None) => {
+                self.start_session(switch.name.unwrap_or_else(|| default_name.to_string())).await?;
+            }
+            (false, Some(session)) => self.stop_session(session.id).await?,
+            (false, None) => {}
+        }
+        Ok(self.recording_status().await)
+    }
+
+    /// Remove the requests `matches` picks that were captured at or before
+    /// `cutoff`, and all but the newest `max_items` of them in each session.
+    /// Returns how many were removed.
+    pub async fn purge(&self, matches: impl Fn(&CapturedRequest) -> bool, cutoff: Option<chrono::DateTime<chrono::Utc>>, max_items: Option<usize>) -> usize {
+        let mut captured_requests = self.captured_requests.write().await;
+        let mut sessions = self.sessions.write().await;
+        let mut removed = 0;
+        for (session_id, requests) in captured_requests.iter_mut() {
+            let mut newer = requests.iter().filter(|request| matches(request)).count();
+            let before = requests.len();
+            requests.retain(|request| {
+                if !matches(request) {
+                    return true;
+                }
+                let expired = cutoff.is_some_and(|cutoff| request.timestamp <= cutoff)
+                    || max_items.is_some_and(|max_items| newer > max_items);
+                newer -= 1;
+                !expired
+            });
+            removed += before - requests.len();
+            if let Some(session) = sessions.get_mut(session_id) {
+                session.request_count = session.request_count.saturating_sub((before - requests.len()) as u64);
+            }
+        }
+        removed
+    }
+
     pub async fn get_sessions(&self) -> Vec<CaptureSession> {
         self.sessions.read().await.values().cloned().collect()
     }
@@ -317,10 +443,10 @@ impl CaptureHandler {
                     }
                     
                     // For other wildcards, use glob matching
-                    return match glob::Pattern::new(pattern) {
+                    match glob::Pattern::new(pattern) {
                         Ok(p) => p.matches(path),
                         Err(_) => false,
-                    };
+                    }
                 } else {
                     // Exact match for patterns without wildcards
                     path == *pattern
@@ -346,10 +472,10 @@ impl CaptureHandler {
                     }
                     
                     // For other wildcards, use glob matching
-                    return match glob::Pattern::new(pattern) {
+                    match glob::Pattern::new(pattern) {
                         Ok(p) => p.matches(path),
                         Err(_) => false,
-                    };
+                    }
                 } else {
                     // Exact match for patterns without wildcards
                     path == *pattern
@@ -396,10 +522,10 @@ impl CaptureHandler {
                             }
                             
                             // For other wildcards, use glob matching
-                            return match glob::Pattern::new(pattern) {
+                            match glob::Pattern::new(pattern) {
                                 Ok(p) => p.matches(&request.path),
                                 Err(_) => false,
-                            };
+                            }
                         } else {
                             // Exact match for patterns without wildcards
                             request.path == *pattern
@@ -454,7 +580,7 @@ impl CaptureHandler {
         for request in &requests {
             let path_pattern = self.extract_path_pattern(&request.path);
             let key = (request.method.clone(), path_pattern);
-            endpoint_groups.entry(key).or_insert_with(Vec::new).push(request);
+            endpoint_groups.entry(key).or_default().push(request);
         }
         
         for ((method, path), group_requests) in endpoint_groups {
@@ -487,7 +613,7 @@ impl CaptureHandler {
                 }
             }
             
-            yaml.push_str("\n");
+            yaml.push('\n');
         }
         
         Ok(yaml)
@@ -571,7 +697,7 @@ impl CaptureHandler {
                 session_id: Some(session_id.to_string()),
                 timestamp: chrono::Utc::now(),
                 method: request_data.method.clone(),
-                path: request_data.path_params.get("path").unwrap_or(&"".to_string()).clone(),
+                path: request_data.path_params.get("path").and_then(|path| path.as_str()).unwrap_or_default().to_string(),
                 headers: request_data.headers.iter()
                     .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
                     .collect(),
@@ -609,6 +735,240 @@ impl CaptureHandler {
     }
 }
 
+/// Blueprint named `name` with a mock endpoint for each method and path
+/// pattern in `requests`, answering with the first successful response
+/// recorded for it (or the first response when none succeeded)
+/// Largest request or response body [`capture_exchanges`] records; larger
+/// and streamed bodies pass through unrecorded
+const MAX_CAPTURED_BODY: u64 = 256 * 1024;
+
+/// Middleware recording requests to the API server and their responses in
+/// the active capture session, which streams them as `capture` events.
+/// Admin routes under `/_backworks` and credential headers are left out.
+pub async fn capture_exchanges(
+    State(capture): State<CaptureHandler>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.uri().path().starts_with("/_backworks") || !capture.is_recording().await {
+        return next.run(request).await;
+    }
+    let started = std::time::Instant::now();
+    let (parts, body) = request.into_parts();
+    let (body, request_body) = buffer(body).await;
+    let query_params = parts.uri.query()
+        .and_then(|query| serde_urlencoded::from_str(query).ok())
+        .unwrap_or_default();
+    let request_id = capture.capture_request(
+        parts.method.to_string(),
+        parts.uri.path().to_string(),
+        recorded_headers(&parts.headers),
+        query_params,
+        request_body,
+    ).await.unwrap_or_else(|_| Uuid::nil());
+
+    let response = next.run(Request::from_parts(parts, body)).await;
+    if request_id.is_nil() {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let (body, response_body) = buffer(body).await;
+    let _ = capture.capture_response(request_id, parts.status.as_u16(), recorded_headers(&parts.headers), response_body, started.elapsed()).await;
+    Response::from_parts(parts, body)
+}
+
+/// Read a body of known, small size, handing back an equivalent body and its
+/// content: JSON as JSON, other text as a string
+async fn buffer(body: Body) -> (Body, Option<serde_json::Value>) {
+    if body.size_hint().upper().is_none_or(|size| size > MAX_CAPTURED_BODY) {
+        return (body, None);
+    }
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(_) => return (Body::empty(), None),
+    };
+    let content = match std::str::from_utf8(&bytes) {
+        _ if bytes.is_empty() => None,
+        Ok(text) => Some(serde_json::from_str(text).unwrap_or_else(|_| serde_json::Value::String(text.to_string()))),
+        Err(_) => None,
+    };
+    (Body::from(bytes), content)
+}
+
+fn recorded_headers(headers: &HeaderMap) -> HashMap<String, String> {
+    headers.iter()
+        .filter(|(name, _)| !REDACTED_HEADERS.contains(&name.as_str()))
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+/// Which captured exchanges a live stream shows: any of `methods`, paths
+/// matching the `path` glob, and statuses in any of the ranges
+#[derive(Debug, Clone, Default)]
+pub struct CaptureStreamFilter {
+    methods: Option<HashSet<String>>,
+    path: Option<glob::Pattern>,
+    statuses: Vec<(u16, u16)>,
+}
+
+impl CaptureStreamFilter {
+    /// The filter of comma-separated `methods` (`GET,POST`), a `path` glob
+    /// (`/api/users/*`) and comma-separated `statuses`, each a code (`404`),
+    /// a class (`5xx`) or a range (`200-299`)
+    pub fn parse(methods: Option<&str>, path: Option<&str>, statuses: Option<&str>) -> BackworksResult<Self> {
+        let items = |list: Option<&str>| -> Vec<String> {
+            list.unwrap_or_default().split(',').map(str::trim).filter(|item| !item.is_empty()).map(str::to_string).collect()
+        };
+        let methods: HashSet<String> = items(methods).into_iter().map(|method| method.to_uppercase()).collect();
+        let path = match path.map(str::trim).filter(|path| !path.is_empty()) {
+            Some(path) => Some(glob::Pattern::new(path)
+                .map_err(|e| BackworksError::config(format!("Invalid path filter '{}': {}", path, e)))?),
+            None => None,
+        };
+        let statuses = items(statuses).iter().map(|status| status_range(status)
+            .ok_or_else(|| BackworksError::config(format!("Invalid status filter '{}' (expected e.g. 404, 4xx or 500-599)", status))))
+            .collect::<BackworksResult<_>>()?;
+        Ok(Self { methods: (!methods.is_empty()).then_some(methods), path, statuses })
+    }
+
+    /// Whether `request` passes the filter; requests still waiting for their
+    /// response never do
+    pub fn matches(&self, request: &CapturedRequest) -> bool {
+        let Some(ref response) = request.response else {
+            return false;
+        };
+        self.accepts(&request.method, &request.path, response.status_code)
+    }
+
+    /// Whether an exchange of `method` on `path` answered with `status`
+    /// passes the filter
+    pub fn accepts(&self, method: &str, path: &str, status: u16) -> bool {
+        self.methods.as_ref().is_none_or(|methods| methods.contains(method))
+            && self.path.as_ref().is_none_or(|pattern| pattern.matches(path))
+            && (self.statuses.is_empty() || self.statuses.iter().any(|&(low, high)| (low..=high).contains(&status)))
+    }
+}
+
+fn status_range(status: &str) -> Option<(u16, u16)> {
+    if let Some((low, high)) = status.split_once('-') {
+        return Some((low.trim().parse().ok()?, high.trim().parse().ok()?));
+    }
+    match status.as_bytes() {
+        [class @ b'1'..=b'5', rest @ ..] if rest.eq_ignore_ascii_case(b"xx") => {
+            let low = u16::from(class - b'0') * 100;
+            Some((low, low + 99))
+        }
+        _ => status.parse().ok().map(|code| (code, code)),
+    }
+}
+
+pub fn blueprint_from_requests(name: &str, requests: &[CapturedRequest]) -> BackworksResult<String> {
+    let mut groups: BTreeMap<(String, String), Vec<&CapturedRequest>> = BTreeMap::new();
+    for request in requests {
+        groups.entry((path_pattern(&request.path), request.method.to_uppercase())).or_default().push(request);
+    }
+
+    let mut endpoints = serde_yaml::Mapping::new();
+    for ((path, method), group) in groups {
+        let recorded = group.iter().find(|request| recorded_status(request).is_some_and(|status| status < 400))
+            .or_else(|| group.iter().find(|request| recorded_status(request).is_some()))
+            .unwrap_or(&group[0]);
+        let mut mock = serde_yaml::Mapping::new();
+        mock.insert("status".into(), recorded_status(recorded).unwrap_or(200).into());
+        mock.insert("schema".into(), serde_yaml::to_value(literal(recorded_body(recorded)))
+            .map_err(|e| BackworksError::config(format!("Failed to serialize blueprint: {}", e)))?);
+        let mut endpoint = serde_yaml::Mapping::new();
+        endpoint.insert("path".into(), path.clone().into());
+        endpoint.insert("methods".into(), vec![method.clone()].into());
+        endpoint.insert("description".into(), format!("Recorded from {} request(s)", group.len()).into());
+        endpoint.insert("mode".into(), "mock".into());
+        endpoint.insert("mock".into(), mock.into());
+
+        let base = endpoint_name(&method, &path);
+        let mut name = base.clone();
+        for n in 2.. {
+            if !endpoints.contains_key(name.as_str()) {
+                break;
+            }
+            name = format!("{}_{}", base, n);
+        }
+        endpoints.insert(name.into(), endpoint.into());
+    }
+
+    let mut blueprint = serde_yaml::Mapping::new();
+    blueprint.insert("name".into(), name.into());
+    blueprint.insert("version".into(), "1.0.0".into());
+    blueprint.insert("endpoints".into(), endpoints.into());
+    let yaml = serde_yaml::to_string(&blueprint)
+        .map_err(|e| BackworksError::config(format!("Failed to serialize blueprint: {}", e)))?;
+    Ok(format!("# Generated API configuration from captured requests\n{}", yaml))
+}
+
+/// `get_api_users_id` for `GET /api/users/{id}`
+fn endpoint_name(method: &str, path: &str) -> String {
+    let words: Vec<String> = std::iter::once(method.to_lowercase())
+        .chain(path.split('/').map(|segment| {
+            segment.chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+                .collect::<String>()
+                .trim_matches('_')
+                .to_string()
+        }))
+        .filter(|word| !word.is_empty())
+        .collect();
+    words.join("_")
+}
+
+fn recorded_status(request: &CapturedRequest) -> Option<u16> {
+    request.response.as_ref().map(|response| response.status_code).or(request.response_status)
+}
+
+/// Response body recorded for `request`, `{}` when there was none
+fn recorded_body(request: &CapturedRequest) -> serde_json::Value {
+    let body = match request.response {
+        Some(ref response) => response.body.clone(),
+        None => request.response_body.as_deref().map(|body| serde_json::from_str(body).unwrap_or_else(|_| body.into())),
+    };
+    body.filter(|body| !body.is_null()).unwrap_or_else(|| serde_json::json!({}))
+}
+
+/// `value` as a mock schema serving it unchanged, with `$` escaped
+fn literal(value: serde_json::Value) -> serde_json::Value {
+    use serde_json::Value;
+    match value {
+        Value::String(text) if text.starts_with('$') => Value::String(format!("${}", text)),
+        Value::Array(items) => Value::Array(items.into_iter().map(literal).collect()),
+        Value::Object(fields) => Value::Object(fields.into_iter().map(|(key, value)| (key, literal(value))).collect()),
+        other => other,
+    }
+}
+
+/// Path with numeric, UUID and token-like segments replaced by parameters,
+/// numbered when one kind appears more than once
+pub fn path_pattern(path: &str) -> String {
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    path.split('/')
+        .map(|segment| {
+            let parameter = if segment.parse::<i64>().is_ok() {
+                "id"
+            } else if segment.parse::<uuid::Uuid>().is_ok() {
+                "uuid"
+            } else if segment.len() > 10 && segment.chars().all(|c| c.is_alphanumeric()) {
+                "token" // Likely a token or hash
+            } else {
+                return segment.to_string();
+            };
+            let count = seen.entry(parameter).or_default();
+            *count += 1;
+            match *count {
+                1 => format!("{{{}}}", parameter),
+                n => format!("{{{}{}}}", parameter, n),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
 #[derive(Debug, Clone)]
 pub struct Capturer {
     port: u16,
@@ -626,7 +986,7 @@ impl Capturer {
         let content = format!("# Simulated capture data\nCaptured on port: {}\n", self.port);
         tokio::fs::write(&self.output, content)
             .await
-            .map_err(|e| BackworksError::Io(e))?;
+            .map_err(BackworksError::Io)?;
         Ok(())
     }
 
@@ -636,7 +996,7 @@ impl Capturer {
         tokio::time::sleep(duration).await;
         tokio::fs::write(&self.output, format!("# Simulated capture data\nCaptured for {} seconds on port: {}\n", duration.as_secs(), self.port))
             .await
-            .map_err(|e| BackworksError::Io(e))?;
+            .map_err(BackworksError::Io)?;
         tracing::info!("Capture completed");
         Ok(())
     }
@@ -648,7 +1008,7 @@ impl Capturer {
             let content = format!("# Simulated capture data\nCaptured indefinitely on port: {}\n", self.port);
             tokio::fs::write(&self.output, content)
                 .await
-                .map_err(|e| BackworksError::Io(e))?;
+                .map_err(BackworksError::Io)?;
             tokio::time::sleep(std::time::Duration::from_secs(60)).await;
         }
     }
@@ -659,7 +1019,7 @@ impl Capturer {
         let config_content = format!("# Generated Backworks config\nname: generated-api\nendpoints: {{}}\nSource: {:?}", input);
         tokio::fs::write(output, config_content)
             .await
-            .map_err(|e| BackworksError::Io(e))?;
+            .map_err(BackworksError::Io)?;
         Ok(())
     }
 
@@ -772,10 +1132,45 @@ mod tests {
         assert_eq!(active_session, None);
     }
 
+    #[test]
+    fn test_stream_filter_matches_method_path_and_status() {
+        let exchange = |method: &str, path: &str, status: Option<u16>| CapturedRequest {
+            id: Uuid::new_v4(),
+            session_id: None,
+            timestamp: chrono::Utc::now(),
+            method: method.to_string(),
+            path: path.to_string(),
+            headers: HashMap::new(),
+            query_params: HashMap::new(),
+            body: None,
+            response: status.map(|status_code| CapturedResponse { status_code, headers: HashMap::new(), body: None }),
+            response_status: None,
+            response_headers: None,
+            response_body: None,
+            duration: None,
+        };
+        let filter = CaptureStreamFilter::parse(Some("get, post"), Some("/api/users/*"), Some("404,5xx,200-201")).unwrap();
+        assert!(filter.matches(&exchange("GET", "/api/users/7", Some(404))));
+        assert!(filter.matches(&exchange("POST", "/api/users/7/roles", Some(503))));
+        assert!(filter.matches(&exchange("POST", "/api/users/new", Some(201))));
+        assert!(!filter.matches(&exchange("DELETE", "/api/users/7", Some(404))));
+        assert!(!filter.matches(&exchange("GET", "/api/orders/7", Some(404))));
+        assert!(!filter.matches(&exchange("GET", "/api/users/7", Some(204))));
+        assert!(!filter.matches(&exchange("GET", "/api/users/7", None)));
+
+        let everything = CaptureStreamFilter::parse(None, Some(""), None).unwrap();
+        assert!(everything.matches(&exchange("PATCH", "/", Some(302))));
+        for status in ["4x", "6xx", "abc", "200-"] {
+            assert!(CaptureStreamFilter::parse(None, None, Some(status)).is_err(), "{}", status);
+        }
+    }
+
     #[tokio::test]
     async fn test_request_capture() {
         let config = create_test_capture_config();
-        let handler = CaptureHandler::new(config);
+        let events = BroadcastHub::default();
+        let mut live = events.subscribe(None).unwrap();
+        let handler = CaptureHandler::new(config).with_events(events);
         
         let session_id = handler.start_session("test_session".to_string()).await.unwrap();
         
@@ -805,6 +1200,11 @@ mod tests {
         assert_eq!(requests[0].response.as_ref().unwrap().status_code, 200);
         assert_eq!(requests[0].duration, Some(std::time::Duration::from_millis(100)));
         
+        // Streamed live: the request, then again with its response
+        let captured = live.recv().await.unwrap();
+        assert_eq!((captured.topic.as_str(), &captured.data["path"]), ("capture", &serde_json::json!("/users/123")));
+        assert_eq!(live.recv().await.unwrap().data["response"]["status_code"], 200);
+        
         // Check session request count
         let session = handler.get_session(session_id).await.unwrap();
         assert_eq!(session.request_count, 1);
@@ -1005,6 +1405,12 @@ mod tests {
         assert_eq!(handler.extract_path_pattern("/api/v2/users/profile"), "/api/v2/users/profile");
     }
 
+    #[test]
+    fn test_numbered_path_patterns() {
+        assert_eq!(path_pattern("/users/123"), "/users/{id}");
+        assert_eq!(path_pattern("/users/7/orders/12"), "/users/{id}/orders/{id2}");
+    }
+
     #[tokio::test]
     async fn test_yaml_config_generation() {
         let config = create_test_capture_config();
@@ -0,0 +1,287 @@
+//! API changelogs
+//!
+//! Turns the [drift](crate::drift) between two versions of a blueprint into
+//! a changelog for API consumers: endpoints added, changed (parameters added,
+//! removed or retyped) and removed, with breaking changes listed first.
+//! Versions are two blueprint files, or the blueprint at two git revisions,
+//! read with `git archive` so included files come from the same revision.
+
+use crate::drift::{self, ApiSnapshot, DriftReport, EndpointDrift};
+use crate::error::{BackworksError, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Changelog {
+    pub from: String,
+    pub to: String,
+    pub breaking: bool,
+    pub entries: Vec<ChangelogEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangelogEntry {
+    pub kind: ChangeKind,
+    pub method: String,
+    pub path: String,
+    pub breaking: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// What changed, one sentence each
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Changed,
+    Removed,
+}
+
+/// A git range, `<from>..<to>`; without `to` the working tree is compared
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitRange {
+    pub from: String,
+    pub to: Option<String>,
+}
+
+impl std::str::FromStr for GitRange {
+    type Err = BackworksError;
+
+    fn from_str(range: &str) -> Result<Self> {
+        let (from, to) = range.split_once("..").unwrap_or((range, ""));
+        if from.is_empty() || to.starts_with('.') {
+            return Err(BackworksError::config(format!("Invalid git range '{}' (expected <from>..<to> or <from>)", range)));
+        }
+        Ok(Self { from: from.to_string(), to: (!to.is_empty()).then(|| to.to_string()) })
+    }
+}
+
+impl Changelog {
+    /// The changelog of the drift between two blueprint versions, labelled
+    /// `from` and `to`
+    pub fn new(from: &str, to: &str, report: &DriftReport) -> Self {
+        let mut entries = Vec::new();
+        let endpoint = |kind, key: &drift::EndpointKey, breaking| ChangelogEntry {
+            kind,
+            method: key.method.clone(),
+            path: key.path.clone(),
+            breaking,
+            description: None,
+            changes: Vec::new(),
+        };
+        for key in &report.removed {
+            entries.push(endpoint(ChangeKind::Removed, key, true));
+        }
+        for drift in &report.changed {
+            entries.push(ChangelogEntry {
+                kind: ChangeKind::Changed,
+                method: drift.method.clone(),
+                path: drift.path.clone(),
+                breaking: drift.is_breaking(),
+                description: None,
+                changes: describe(drift),
+            });
+        }
+        for key in &report.added {
+            entries.push(endpoint(ChangeKind::Added, key, false));
+        }
+        Self { from: from.to_string(), to: to.to_string(), breaking: report.has_breaking_changes(), entries }
+    }
+
+    /// The changelog between two blueprint snapshots, with endpoint
+    /// descriptions taken from the blueprints
+    pub fn between(from: &str, baseline: &ApiSnapshot, to: &str, current: &ApiSnapshot) -> Self {
+        let mut changelog = Self::new(from, to, &drift::compare(baseline, current));
+        for entry in &mut changelog.entries {
+            let snapshot = if entry.kind == ChangeKind::Removed { baseline } else { current };
+            let key = drift::EndpointKey { method: entry.method.clone(), path: entry.path.clone() };
+            entry.description = snapshot.endpoints.get(&key).and_then(|shape| shape.description.clone());
+        }
+        changelog
+    }
+
+    pub fn render_markdown(&self) -> String {
+        let mut out = String::new();
+        self.write_markdown(&mut out).expect("writing to a String cannot fail");
+        out
+    }
+
+    fn write_markdown(&self, out: &mut String) -> std::fmt::Result {
+        writeln!(out, "# API changes from {} to {}", self.from, self.to)?;
+        if self.entries.is_empty() {
+            return writeln!(out, "\nNo changes to the API.");
+        }
+        for title in SECTIONS {
+            let entries: Vec<&ChangelogEntry> = self.entries.iter().filter(|entry| entry.section() == title).collect();
+            if entries.is_empty() {
+                continue;
+            }
+            writeln!(out, "\n## {}\n", title)?;
+            for entry in entries {
+                let verb = match entry.kind {
+                    ChangeKind::Added => "",
+                    ChangeKind::Changed if entry.breaking => "Changed ",
+                    ChangeKind::Changed => "",
+                    ChangeKind::Removed => "Removed ",
+                };
+                write!(out, "- {}`{} {}`", verb, entry.method, entry.path)?;
+                match entry.description {
+                    Some(ref description) => writeln!(out, ": {}", description)?,
+                    None => writeln!(out)?,
+                }
+                for change in &entry.changes {
+                    writeln!(out, "  - {}", change)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Markdown sections, in order
+const SECTIONS: [&str; 3] = ["⚠️ Breaking changes", "Added", "Changed"];
+
+impl ChangelogEntry {
+    /// Title of the Markdown section listing the entry
+    fn section(&self) -> &'static str {
+        match self.kind {
+            _ if self.breaking => SECTIONS[0],
+            ChangeKind::Added => SECTIONS[1],
+            ChangeKind::Changed | ChangeKind::Removed => SECTIONS[2],
+        }
+    }
+}
+
+/// Sentences describing an endpoint's changes
+fn describe(drift: &EndpointDrift) -> Vec<String> {
+    let mut changes = Vec::new();
+    for parameter in &drift.parameters_added {
+        let required = if parameter.shape.required { "required" } else { "optional" };
+        changes.push(format!("New {} {} parameter `{}` ({})", required, parameter.shape.location, parameter.name, parameter.shape.param_type));
+    }
+    for parameter in &drift.parameters_removed {
+        changes.push(format!("Parameter `{}` was removed", parameter.name));
+    }
+    for change in &drift.parameter_changes {
+        if change.before.param_type != change.after.param_type {
+            changes.push(format!("Parameter `{}` is now {} (was {})", change.name, change.after.param_type, change.before.param_type));
+        }
+        if change.before.required != change.after.required {
+            changes.push(format!("Parameter `{}` is now {}", change.name, if change.after.required { "required" } else { "optional" }));
+        }
+        if change.before.location != change.after.location {
+            changes.push(format!("Parameter `{}` moved from the {} to the {}", change.name, change.before.location, change.after.location));
+        }
+    }
+    for status in &drift.statuses_added {
+        changes.push(format!("May answer with status {}", status));
+    }
+    for status in &drift.statuses_removed {
+        changes.push(format!("No longer answers with status {}", status));
+    }
+    for field in &drift.fields_added {
+        changes.push(format!("New response field `{}`", field));
+    }
+    for field in &drift.fields_removed {
+        changes.push(format!("Response field `{}` was removed", field));
+    }
+    for change in &drift.type_changes {
+        changes.push(format!("Response field `{}` is now {} (was {})", change.field, change.after.join(" or "), change.before.join(" or ")));
+    }
+    changes
+}
+
+/// The endpoints of the blueprint at `path` as of git revision `revision`
+pub async fn blueprint_at(path: &Path, revision: &str) -> Result<ApiSnapshot> {
+    let path = std::path::absolute(path)?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let root = PathBuf::from(git(dir, &["rev-parse", "--show-toplevel"])?.trim_end());
+    let relative = path.strip_prefix(std::fs::canonicalize(&root)?)
+        .or_else(|_| path.strip_prefix(&root))
+        .map_err(|_| BackworksError::config(format!("{} is not in the git repository at {}", path.display(), root.display())))?
+        .to_path_buf();
+
+    let archive = Command::new("git").current_dir(&root).args(["archive", "--format=tar", revision]).output()?;
+    if !archive.status.success() {
+        return Err(BackworksError::config(format!("git archive {} failed: {}", revision, String::from_utf8_lossy(&archive.stderr).trim())));
+    }
+    let checkout = std::env::temp_dir().join(format!("backworks-changelog-{}", uuid::Uuid::new_v4()));
+    let snapshot = async {
+        tar::Archive::new(archive.stdout.as_slice()).unpack(&checkout)?;
+        let blueprint = checkout.join(&relative);
+        if !blueprint.exists() {
+            return Err(BackworksError::config(format!("{} does not exist at {}", relative.display(), revision)));
+        }
+        let config = crate::config::load_yaml_config(&blueprint).await?;
+        Ok(ApiSnapshot::from_blueprint(revision, &config))
+    }.await;
+    let _ = std::fs::remove_dir_all(&checkout);
+    snapshot
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git").current_dir(dir).args(args).output()?;
+    if !output.status.success() {
+        return Err(BackworksError::config(format!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BackworksConfig;
+
+    fn snapshot(source: &str, yaml: &str) -> ApiSnapshot {
+        let config: BackworksConfig = serde_yaml::from_str(yaml).unwrap();
+        ApiSnapshot::from_blueprint(source, &config)
+    }
+
+    #[test]
+    fn test_changelog_between_blueprints() {
+        let before = snapshot("v1", r#"
+name: shop
+endpoints:
+  orders: { path: /orders, methods: [GET], parameters: [{ name: page, type: integer }] }
+  order: { path: "/orders/{id}", methods: [GET, DELETE] }
+  legacy: { path: /legacy, methods: [GET] }
+"#);
+        let after = snapshot("v2", r#"
+name: shop
+endpoints:
+  orders:
+    path: /orders
+    methods: [GET]
+    parameters: [{ name: page, type: integer, required: true }, { name: status, type: string }]
+  order: { path: "/orders/{id:int}", methods: [GET, DELETE] }
+  refunds: { path: /refunds, methods: [POST], description: Refund an order }
+"#);
+
+        let changelog = Changelog::between("v1", &before, "v2", &after);
+        assert!(changelog.breaking);
+        let summary: Vec<_> = changelog.entries.iter().map(|entry| (entry.kind, entry.method.as_str(), entry.path.as_str(), entry.breaking)).collect();
+        assert_eq!(summary, vec![
+            (ChangeKind::Removed, "GET", "/legacy", true),
+            (ChangeKind::Changed, "DELETE", "/orders/{id}", true),
+            (ChangeKind::Changed, "GET", "/orders", true),
+            (ChangeKind::Changed, "GET", "/orders/{id}", true),
+            (ChangeKind::Added, "POST", "/refunds", false),
+        ]);
+
+        let markdown = changelog.render_markdown();
+        assert!(markdown.starts_with("# API changes from v1 to v2\n\n## ⚠️ Breaking changes\n\n- Removed `GET /legacy`\n"), "{}", markdown);
+        assert!(markdown.contains("- Changed `GET /orders`\n  - New optional request parameter `status` (string)\n  - Parameter `page` is now required\n"));
+        assert!(markdown.contains("  - Parameter `id` is now int (was string)\n"));
+        assert!(markdown.ends_with("## Added\n\n- `POST /refunds`: Refund an order\n"));
+        assert_eq!(Changelog::between("v2", &after, "v2", &after).render_markdown(), "# API changes from v2 to v2\n\nNo changes to the API.\n");
+
+        assert_eq!("v1.0..HEAD".parse::<GitRange>().unwrap(), GitRange { from: "v1.0".to_string(), to: Some("HEAD".to_string()) });
+        assert_eq!("v1.0".parse::<GitRange>().unwrap(), GitRange { from: "v1.0".to_string(), to: None });
+        assert!("..HEAD".parse::<GitRange>().is_err());
+    }
+}
@@ -0,0 +1,271 @@
+//! Fault injection
+//!
+//! Endpoints with a `chaos` block delay their responses and fail some of
+//! them on purpose, so clients can be tested against a slow or unreliable
+//! backend. Faults are injected after authentication and the endpoint's
+//! middleware, just before the handler:
+//!
+//! - `latency`: a delay drawn from a fixed, uniform, normal or exponential
+//!   distribution
+//! - `error_rate`: an error status instead of the handler's response
+//! - `drop_rate`: the connection is closed before the body is sent
+//! - `truncate_rate`: the handler's body is cut off halfway, under its full
+//!   `Content-Length`
+//!
+//! Settings can be changed and faults switched off at runtime through
+//! `/_backworks/chaos` or the dashboard.
+
+use crate::config::{BackworksConfig, ChaosConfig, LatencyDistribution};
+use crate::error::{BackworksError, ErrorSource, RequestError, Result};
+use axum::body::{Body, Bytes};
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+const FAULT_HEADER: &str = "x-backworks-fault";
+
+/// Fault settings of every endpoint and the global switch
+#[derive(Debug, Clone, Default)]
+pub struct Chaos {
+    enabled: Arc<AtomicBool>,
+    endpoints: Arc<RwLock<BTreeMap<String, ChaosConfig>>>,
+}
+
+/// Whether faults are injected, and each endpoint's settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChaosStatus {
+    pub enabled: bool,
+    pub endpoints: BTreeMap<String, ChaosConfig>,
+}
+
+/// Body of a request switching fault injection on or off everywhere
+#[derive(Debug, Deserialize)]
+pub struct ChaosSwitch {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Fault {
+    Error(u16),
+    Drop,
+    Truncate,
+}
+
+impl Chaos {
+    /// Collect the `chaos` settings of every endpoint
+    pub fn new(config: &BackworksConfig) -> Result<Self> {
+        let mut endpoints = BTreeMap::new();
+        for (name, endpoint) in &config.endpoints {
+            if let Some(ref chaos) = endpoint.chaos {
+                validate(chaos).map_err(|message| BackworksError::config(format!("Endpoint '{}' chaos: {}", name, message)))?;
+                endpoints.insert(name.clone(), chaos.clone());
+            }
+        }
+        Ok(Self {
+            enabled: Arc::new(AtomicBool::new(true)),
+            endpoints: Arc::new(RwLock::new(endpoints)),
+        })
+    }
+
+    pub async fn status(&self) -> ChaosStatus {
+        ChaosStatus {
+            enabled: self.enabled.load(Ordering::Relaxed),
+            endpoints: self.endpoints.read().await.clone(),
+        }
+    }
+
+    pub async fn set_enabled(&self, enabled: bool) -> ChaosStatus {
+        self.enabled.store(enabled, Ordering::Relaxed);
+        self.status().await
+    }
+
+    /// Replace the settings of an endpoint that has a `chaos` block
+    pub async fn configure(&self, endpoint: &str, config: ChaosConfig) -> std::result::Result<ChaosStatus, (StatusCode, String)> {
+        validate(&config).map_err(|message| (StatusCode::BAD_REQUEST, message))?;
+        match self.endpoints.write().await.get_mut(endpoint) {
+            Some(settings) => *settings = config,
+            None => return Err((StatusCode::NOT_FOUND, format!("Endpoint '{}' has no chaos settings", endpoint))),
+        }
+        Ok(self.status().await)
+    }
+
+    /// Settings to apply to a request to `endpoint`, if faults are on
+    async fn active(&self, endpoint: &str) -> Option<ChaosConfig> {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.endpoints.read().await.get(endpoint).filter(|config| config.enabled).cloned()
+    }
+}
+
+fn validate(config: &ChaosConfig) -> std::result::Result<(), String> {
+    for (name, rate) in [("error_rate", config.error_rate), ("drop_rate", config.drop_rate), ("truncate_rate", config.truncate_rate)] {
+        if !(0.0..=1.0).contains(&rate) {
+            return Err(format!("{} must be between 0 and 1, got {}", name, rate));
+        }
+    }
+    if config.error_rate + config.drop_rate + config.truncate_rate > 1.0 {
+        return Err("error_rate, drop_rate and truncate_rate add up to more than 1".to_string());
+    }
+    if let Some(status) = config.error_statuses.iter().find(|status| !(400..=599).contains(*status)) {
+        return Err(format!("{} is not an error status", status));
+    }
+    if let Some(LatencyDistribution::Uniform { min_ms, max_ms }) = config.latency {
+        if min_ms > max_ms {
+            return Err(format!("latency min_ms {} is above max_ms {}", min_ms, max_ms));
+        }
+    }
+    Ok(())
+}
+
+fn delay(distribution: &LatencyDistribution, rng: &mut impl Rng) -> Duration {
+    let ms = match *distribution {
+        LatencyDistribution::Fixed { ms } => ms as f64,
+        LatencyDistribution::Uniform { min_ms, max_ms } => rng.gen_range(min_ms..=max_ms) as f64,
+        LatencyDistribution::Normal { mean_ms, stddev_ms } => {
+            // Box-Muller transform
+            let (u1, u2): (f64, f64) = (1.0 - rng.gen::<f64>(), rng.gen());
+            mean_ms as f64 + stddev_ms as f64 * (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+        }
+        LatencyDistribution::Exponential { mean_ms } => -(mean_ms as f64) * (1.0 - rng.gen::<f64>()).ln(),
+    };
+    Duration::from_millis(ms.max(0.0).round() as u64)
+}
+
+fn pick_fault(config: &ChaosConfig, rng: &mut impl Rng) -> Option<Fault> {
+    let roll: f64 = rng.gen();
+    if roll < config.drop_rate {
+        Some(Fault::Drop)
+    } else if roll < config.drop_rate + config.error_rate {
+        let status = match config.error_statuses.len() {
+            0 => 500,
+            count => config.error_statuses[rng.gen_range(0..count)],
+        };
+        Some(Fault::Error(status))
+    } else if roll < config.drop_rate + config.error_rate + config.truncate_rate {
+        Some(Fault::Truncate)
+    } else {
+        None
+    }
+}
+
+/// Fault injection in front of one endpoint's handler
+#[derive(Debug, Clone)]
+pub struct ChaosGate {
+    pub chaos: Chaos,
+    pub endpoint: String,
+}
+
+/// Route layer injecting the endpoint's faults
+pub async fn inject_faults(State(gate): State<Arc<ChaosGate>>, request: Request, next: axum::middleware::Next) -> Response {
+    let Some(config) = gate.chaos.active(&gate.endpoint).await else {
+        return next.run(request).await;
+    };
+    let (latency, fault) = {
+        let mut rng = rand::thread_rng();
+        (config.latency.as_ref().map(|distribution| delay(distribution, &mut rng)), pick_fault(&config, &mut rng))
+    };
+    if let Some(latency) = latency {
+        tokio::time::sleep(latency).await;
+    }
+
+    match fault {
+        None => next.run(request).await,
+        Some(Fault::Error(status)) => {
+            let status = StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            let message = "Injected fault";
+            let mut response = (status, axum::Json(serde_json::json!({"error": message, "status": status.as_u16()}))).into_response();
+            response.headers_mut().insert(FAULT_HEADER, HeaderValue::from_static("error"));
+            response.extensions_mut().insert(RequestError::new(status, message, ErrorSource::Framework));
+            response
+        }
+        Some(Fault::Drop) => {
+            let mut response = Response::new(broken_body(Bytes::new()));
+            response.headers_mut().insert(FAULT_HEADER, HeaderValue::from_static("drop"));
+            response
+        }
+        Some(Fault::Truncate) => {
+            let (mut parts, body) = next.run(request).await.into_parts();
+            let body = match axum::body::to_bytes(body, usize::MAX).await {
+                Ok(body) => body,
+                Err(e) => return BackworksError::server(format!("cannot buffer response to truncate: {}", e)).into_response(),
+            };
+            parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+            parts.headers.insert(FAULT_HEADER, HeaderValue::from_static("truncate"));
+            Response::from_parts(parts, broken_body(body.slice(..body.len() / 2)))
+        }
+    }
+}
+
+/// A body that sends `sent` and then fails, which aborts the connection
+fn broken_body(sent: Bytes) -> Body {
+    let chunks: Vec<std::io::Result<Bytes>> = vec![
+        Ok(sent),
+        Err(std::io::Error::new(std::io::ErrorKind::ConnectionAborted, "connection dropped by fault injection")),
+    ];
+    Body::from_stream(futures::stream::iter(chunks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn config(yaml: &str) -> ChaosConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_faults_follow_their_rates() {
+        let chaos = config("{ error_rate: 0.2, error_statuses: [502, 503], drop_rate: 0.1, truncate_rate: 0.3 }");
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut counts = BTreeMap::new();
+        for _ in 0..10_000 {
+            let key = match pick_fault(&chaos, &mut rng) {
+                Some(Fault::Error(status)) => status.to_string(),
+                Some(fault) => format!("{:?}", fault),
+                None => "None".to_string(),
+            };
+            *counts.entry(key).or_insert(0) += 1;
+        }
+        let share = |key: &str| counts[key] as f64 / 10_000.0;
+        assert!((share("Drop") - 0.1).abs() < 0.02);
+        assert!((share("502") + share("503") - 0.2).abs() < 0.02);
+        assert!((share("Truncate") - 0.3).abs() < 0.02);
+        assert!((share("None") - 0.4).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_latency_distributions() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut sample = |yaml: &str| -> Vec<u128> {
+            let distribution: LatencyDistribution = serde_yaml::from_str(yaml).unwrap();
+            (0..2_000).map(|_| delay(&distribution, &mut rng).as_millis()).collect()
+        };
+        let mean = |samples: &[u128]| samples.iter().sum::<u128>() as f64 / samples.len() as f64;
+
+        assert!(sample("{ distribution: fixed, ms: 40 }").iter().all(|ms| *ms == 40));
+        assert!(sample("{ distribution: uniform, min_ms: 10, max_ms: 20 }").iter().all(|ms| (10..=20).contains(ms)));
+        let normal = sample("{ distribution: normal, mean_ms: 100, stddev_ms: 10 }");
+        assert!((mean(&normal) - 100.0).abs() < 2.0);
+        let exponential = sample("{ distribution: exponential, mean_ms: 50 }");
+        assert!((mean(&exponential) - 50.0).abs() < 5.0);
+    }
+
+    #[test]
+    fn test_invalid_settings_are_rejected() {
+        assert_eq!(validate(&config("{ error_rate: 1.5 }")).unwrap_err(), "error_rate must be between 0 and 1, got 1.5");
+        assert!(validate(&config("{ error_rate: 0.6, drop_rate: 0.6 }")).unwrap_err().contains("add up to more than 1"));
+        assert_eq!(validate(&config("{ error_rate: 0.1, error_statuses: [200] }")).unwrap_err(), "200 is not an error status");
+        assert!(validate(&config("{ latency: { distribution: uniform, min_ms: 9, max_ms: 1 } }")).is_err());
+        assert!(serde_yaml::from_str::<ChaosConfig>("{ latency: { distribution: fixed } }").is_err());
+    }
+}
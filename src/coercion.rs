@@ -0,0 +1,206 @@
+//! Request body coercion
+//!
+//! Form posts and query-like payloads carry every value as a string. An
+//! endpoint with `coerce` enabled converts the body fields named by its
+//! `parameters` to their declared `type` before templates, transforms and
+//! the handler see the body: `"42"` becomes `42` for an `integer`, `"on"`
+//! becomes `true` for a `boolean`, and a lone value becomes a one-element
+//! list for an `array`. Parameter names are [JSONPath](crate::jsonpath), so
+//! `items[*].qty` reaches into lists.
+//!
+//! Values that do not convert are left alone for the handler to reject.
+//! Empty strings become `null` for non-string types.
+
+use crate::config::{EndpointConfig, ParameterConfig};
+use crate::error::{BackworksError, Result};
+use crate::jsonpath::JsonPath;
+use serde_json::{Number, Value};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DeclaredType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    Array,
+    Object,
+}
+
+impl DeclaredType {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "string" | "str" => Some(Self::String),
+            "integer" | "int" => Some(Self::Integer),
+            "number" | "float" | "double" => Some(Self::Number),
+            "boolean" | "bool" => Some(Self::Boolean),
+            "array" | "list" => Some(Self::Array),
+            "object" | "map" => Some(Self::Object),
+            _ => None,
+        }
+    }
+
+    fn coerce(self, value: &mut Value, trim: bool) {
+        if let (true, Value::String(text)) = (trim, &mut *value) {
+            let trimmed = text.trim();
+            if trimmed.len() != text.len() {
+                *text = trimmed.to_string();
+            }
+        }
+        if let (false, Value::String(text)) = (self == Self::String, &*value) {
+            if text.is_empty() {
+                *value = Value::Null;
+                return;
+            }
+        }
+
+        let coerced = match (self, &*value) {
+            (Self::String, Value::Number(number)) => Some(Value::String(number.to_string())),
+            (Self::String, Value::Bool(flag)) => Some(Value::String(flag.to_string())),
+            (Self::Integer, Value::String(text)) => text.trim().parse::<i64>().ok().map(Value::from),
+            (Self::Integer, Value::Number(number)) => number.as_f64()
+                .filter(|float| float.fract() == 0.0 && !number.is_i64() && !number.is_u64())
+                .map(|float| Value::from(float as i64)),
+            (Self::Number, Value::String(text)) => text.trim().parse::<f64>().ok().and_then(Number::from_f64).map(Value::Number),
+            (Self::Boolean, Value::String(text)) => match text.trim().to_ascii_lowercase().as_str() {
+                "true" | "1" | "yes" | "on" => Some(Value::Bool(true)),
+                "false" | "0" | "no" | "off" => Some(Value::Bool(false)),
+                _ => None,
+            },
+            (Self::Boolean, Value::Number(number)) => match number.as_i64() {
+                Some(1) => Some(Value::Bool(true)),
+                Some(0) => Some(Value::Bool(false)),
+                _ => None,
+            },
+            (Self::Array, Value::String(text)) if text.trim_start().starts_with('[') => serde_json::from_str(text).ok(),
+            (Self::Object, Value::String(text)) => serde_json::from_str::<Value>(text).ok().filter(Value::is_object),
+            (Self::Array, Value::Array(_) | Value::Null) => None,
+            (Self::Array, other) => Some(Value::Array(vec![other.clone()])),
+            _ => None,
+        };
+        if let Some(coerced) = coerced {
+            *value = coerced;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Coercion {
+    trim: bool,
+    fields: Vec<(JsonPath, DeclaredType)>,
+}
+
+#[derive(Debug, Default)]
+pub struct EndpointCoercions {
+    endpoints: HashMap<String, Coercion>,
+}
+
+impl EndpointCoercions {
+    /// Resolve the declared parameter types of every endpoint with `coerce`
+    pub fn new(endpoints: &HashMap<String, EndpointConfig>) -> Result<Self> {
+        let mut coercions = Self::default();
+        for (name, endpoint) in endpoints {
+            let Some(ref config) = endpoint.coerce else {
+                continue;
+            };
+            if !config.enabled {
+                continue;
+            }
+            let fields = endpoint.parameters.iter().flatten()
+                .map(|parameter| field(name, parameter))
+                .collect::<Result<Vec<_>>>()?;
+            coercions.endpoints.insert(name.clone(), Coercion { trim: config.trim, fields });
+        }
+        Ok(coercions)
+    }
+
+    /// The request body with its declared fields converted
+    pub fn coerce(&self, endpoint: &str, mut body: Value) -> Value {
+        let Some(coercion) = self.endpoints.get(endpoint) else {
+            return body;
+        };
+        for (path, declared) in &coercion.fields {
+            path.for_each_mut(&mut body, &mut |value| declared.coerce(value, coercion.trim));
+        }
+        body
+    }
+}
+
+fn field(endpoint: &str, parameter: &ParameterConfig) -> Result<(JsonPath, DeclaredType)> {
+    let declared = DeclaredType::parse(&parameter.param_type).ok_or_else(|| BackworksError::config(format!(
+        "Endpoint '{}' parameter '{}' has unknown type '{}'; expected string, integer, number, boolean, array or object",
+        endpoint, parameter.name, parameter.param_type
+    )))?;
+    let path = JsonPath::parse(&parameter.name).map_err(|e| match e {
+        BackworksError::Config(message) => BackworksError::config(format!("Endpoint '{}' parameter: {}", endpoint, message)),
+        other => other,
+    })?;
+    Ok((path, declared))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn coercions(yaml: &str) -> Result<EndpointCoercions> {
+        let endpoint: EndpointConfig = serde_yaml::from_str(yaml).unwrap();
+        EndpointCoercions::new(&HashMap::from([("signup".to_string(), endpoint)]))
+    }
+
+    #[test]
+    fn test_form_fields_become_declared_types() {
+        let coercions = coercions(r#"
+path: /signup
+coerce: {}
+parameters:
+  - { name: age, type: integer }
+  - { name: score, type: number }
+  - { name: newsletter, type: boolean }
+  - { name: nickname, type: string }
+  - { name: zip, type: string }
+  - { name: tags, type: array }
+  - { name: prefs, type: object }
+  - { name: referrer, type: integer }
+  - { name: "items[*].qty", type: int }
+"#).unwrap();
+
+        let body = json!({
+            "age": " 42 ",
+            "score": "9.5",
+            "newsletter": "on",
+            "nickname": "  ada  ",
+            "zip": 1234,
+            "tags": "rust",
+            "prefs": "{\"theme\": \"dark\"}",
+            "referrer": "",
+            "items": [{ "qty": "2" }, { "qty": "many" }],
+            "other": " untouched ",
+        });
+        assert_eq!(coercions.coerce("signup", body.clone()), json!({
+            "age": 42,
+            "score": 9.5,
+            "newsletter": true,
+            "nickname": "ada",
+            "zip": "1234",
+            "tags": ["rust"],
+            "prefs": { "theme": "dark" },
+            "referrer": null,
+            "items": [{ "qty": 2 }, { "qty": "many" }],
+            "other": " untouched ",
+        }));
+        assert_eq!(coercions.coerce("other", body.clone()), body);
+    }
+
+    #[test]
+    fn test_disabled_trim_and_unknown_types() {
+        let coercions = coercions("path: /x\ncoerce: { trim: false }\nparameters:\n  - { name: nickname, type: string }\n  - { name: age, type: integer }\n").unwrap();
+        assert_eq!(coercions.coerce("signup", json!({ "nickname": " ada ", "age": " 7 " })), json!({ "nickname": " ada ", "age": 7 }));
+
+        let disabled = self::coercions("path: /x\ncoerce: { enabled: false }\nparameters:\n  - { name: age, type: integer }\n").unwrap();
+        assert_eq!(disabled.coerce("signup", json!({ "age": "7" })), json!({ "age": "7" }));
+
+        let err = self::coercions("path: /x\ncoerce: {}\nparameters:\n  - { name: age, type: uuid }\n").unwrap_err();
+        assert!(err.to_string().contains("parameter 'age' has unknown type 'uuid'"), "{}", err);
+    }
+}
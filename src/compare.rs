@@ -0,0 +1,400 @@
+//! Response comparison between execution modes
+//!
+//! Executes an endpoint's live handler alongside a baseline (a recorded
+//! response or a second execution mode) and reports where the two diverge.
+//! Used to verify that recorded/mock responses stay truthful to the real
+//! implementation.
+
+use crate::config::EndpointCompareConfig;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Maximum number of comparison reports kept in memory
+const MAX_REPORTS: usize = 200;
+
+/// A normalized response used as input to the comparator
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResponseSnapshot {
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+    pub body: Value,
+}
+
+impl ResponseSnapshot {
+    /// Build a snapshot from raw handler output.
+    ///
+    /// Structured output (`{"status": .., "headers": .., "body": ..}`) is
+    /// unpacked; anything else is treated as a plain 200 JSON body, mirroring
+    /// how the server turns handler output into a response.
+    pub fn from_handler_output(output: &str) -> Self {
+        let value: Value = serde_json::from_str(output)
+            .unwrap_or_else(|_| serde_json::json!({"response": output}));
+
+        if let (Some(status), Some(body)) = (
+            value.get("status").and_then(|s| s.as_u64()),
+            value.get("body"),
+        ) {
+            let headers = value.get("headers")
+                .and_then(|h| h.as_object())
+                .map(|h| {
+                    h.iter()
+                        .map(|(k, v)| (k.to_lowercase(), v.as_str().map(str::to_string).unwrap_or_else(|| v.to_string())))
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            return Self {
+                status: status as u16,
+                headers,
+                body: body.clone(),
+            };
+        }
+
+        Self {
+            status: 200,
+            headers: HashMap::new(),
+            body: value,
+        }
+    }
+
+    /// Snapshot of a failed execution, matching the server's error response
+    pub fn from_error(error: &str) -> Self {
+        Self {
+            status: 500,
+            headers: HashMap::new(),
+            body: serde_json::json!({"error": error}),
+        }
+    }
+}
+
+/// Which part of the response diverged
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DivergenceKind {
+    Status,
+    Header,
+    Body,
+}
+
+/// A single difference between baseline and live responses
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Divergence {
+    pub kind: DivergenceKind,
+    /// Header name or JSON pointer into the body (empty for status)
+    pub path: String,
+    pub baseline: Option<Value>,
+    pub live: Option<Value>,
+}
+
+/// Result of comparing one request's baseline and live responses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComparisonReport {
+    pub endpoint: String,
+    pub method: String,
+    pub path: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub baseline_source: String,
+    pub divergences: Vec<Divergence>,
+}
+
+impl ComparisonReport {
+    pub fn is_match(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+/// Ignore rules applied while diffing
+#[derive(Debug, Clone, Default)]
+pub struct CompareRules {
+    ignore_status: bool,
+    ignore_headers: Vec<String>,
+    ignore_fields: Vec<String>,
+}
+
+impl CompareRules {
+    pub fn from_config(config: &EndpointCompareConfig) -> Self {
+        Self {
+            ignore_status: config.ignore_status,
+            ignore_headers: config.ignore_headers.iter().map(|h| h.to_lowercase()).collect(),
+            ignore_fields: config.ignore_fields.iter()
+                .map(|f| normalize_pointer(f))
+                .filter(|f| !f.is_empty())
+                .collect(),
+        }
+    }
+
+    fn header_ignored(&self, name: &str) -> bool {
+        self.ignore_headers.iter().any(|h| h == "*" || h == name)
+    }
+
+    fn field_ignored(&self, pointer: &str) -> bool {
+        self.ignore_fields.iter().any(|rule| pointer_matches(rule, pointer))
+    }
+}
+
+/// Diff two snapshots under the given ignore rules
+pub fn diff_responses(baseline: &ResponseSnapshot, live: &ResponseSnapshot, rules: &CompareRules) -> Vec<Divergence> {
+    let mut divergences = Vec::new();
+
+    if !rules.ignore_status && baseline.status != live.status {
+        divergences.push(Divergence {
+            kind: DivergenceKind::Status,
+            path: String::new(),
+            baseline: Some(Value::from(baseline.status)),
+            live: Some(Value::from(live.status)),
+        });
+    }
+
+    // Only headers the baseline declares are compared; live responses commonly
+    // carry extra transport headers that a recording never would.
+    let live_headers: HashMap<String, &String> = live.headers.iter()
+        .map(|(k, v)| (k.to_lowercase(), v))
+        .collect();
+    let mut header_names: Vec<_> = baseline.headers.keys().collect();
+    header_names.sort();
+    for name in header_names {
+        let lower = name.to_lowercase();
+        if rules.header_ignored(&lower) {
+            continue;
+        }
+        let expected = &baseline.headers[name];
+        match live_headers.get(&lower) {
+            Some(actual) if *actual == expected => {}
+            actual => divergences.push(Divergence {
+                kind: DivergenceKind::Header,
+                path: lower,
+                baseline: Some(Value::String(expected.clone())),
+                live: actual.map(|v| Value::String((*v).clone())),
+            }),
+        }
+    }
+
+    diff_values("", Some(&baseline.body), Some(&live.body), rules, &mut divergences);
+    divergences
+}
+
+fn diff_values(pointer: &str, baseline: Option<&Value>, live: Option<&Value>, rules: &CompareRules, out: &mut Vec<Divergence>) {
+    if rules.field_ignored(pointer) {
+        return;
+    }
+
+    match (baseline, live) {
+        (Some(Value::Object(a)), Some(Value::Object(b))) => {
+            let mut keys: Vec<&String> = a.keys().chain(b.keys().filter(|k| !a.contains_key(*k))).collect();
+            keys.sort();
+            for key in keys {
+                let child = format!("{}/{}", pointer, escape_pointer_token(key));
+                diff_values(&child, a.get(key), b.get(key), rules, out);
+            }
+        }
+        (Some(Value::Array(a)), Some(Value::Array(b))) => {
+            for index in 0..a.len().max(b.len()) {
+                let child = format!("{}/{}", pointer, index);
+                diff_values(&child, a.get(index), b.get(index), rules, out);
+            }
+        }
+        (a, b) if a == b => {}
+        (a, b) => out.push(Divergence {
+            kind: DivergenceKind::Body,
+            path: if pointer.is_empty() { "/".to_string() } else { pointer.to_string() },
+            baseline: a.cloned(),
+            live: b.cloned(),
+        }),
+    }
+}
+
+/// Accept both JSON pointers (`/user/id`) and dotted paths (`user.id`)
+pub(crate) fn normalize_pointer(field: &str) -> String {
+    let field = field.trim();
+    if field.starts_with('/') {
+        field.trim_end_matches('/').to_string()
+    } else {
+        let trimmed = field.trim_start_matches("$.").trim_start_matches('$');
+        trimmed.split('.')
+            .filter(|s| !s.is_empty())
+            .map(|s| format!("/{}", escape_pointer_token(s)))
+            .collect()
+    }
+}
+
+fn escape_pointer_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+/// A rule matches the pointer itself and everything beneath it; `*` matches
+/// any single segment.
+fn pointer_matches(rule: &str, pointer: &str) -> bool {
+    let rule_segments: Vec<&str> = rule.split('/').skip(1).collect();
+    let pointer_segments: Vec<&str> = pointer.split('/').skip(1).collect();
+
+    if pointer_segments.len() < rule_segments.len() {
+        return false;
+    }
+
+    rule_segments.iter()
+        .zip(pointer_segments.iter())
+        .all(|(r, p)| *r == "*" || r == p)
+}
+
+/// In-memory store of recent comparison reports
+#[derive(Debug, Clone, Default)]
+pub struct ComparisonRecorder {
+    reports: Arc<RwLock<VecDeque<ComparisonReport>>>,
+}
+
+impl ComparisonRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn record(&self, report: ComparisonReport) {
+        if report.is_match() {
+            tracing::debug!("✅ {} {} matches baseline ({})", report.method, report.path, report.baseline_source);
+        } else {
+            tracing::warn!(
+                "⚠️ {} {} diverges from baseline ({}): {} difference(s)",
+                report.method, report.path, report.baseline_source, report.divergences.len()
+            );
+        }
+
+        let mut reports = self.reports.write().await;
+        if reports.len() >= MAX_REPORTS {
+            reports.pop_front();
+        }
+        reports.push_back(report);
+    }
+
+    /// Recent reports, newest first, optionally limited to one endpoint
+    pub async fn reports(&self, endpoint: Option<&str>) -> Vec<ComparisonReport> {
+        self.reports.read().await.iter()
+            .rev()
+            .filter(|r| endpoint.is_none_or(|e| r.endpoint == e))
+            .cloned()
+            .collect()
+    }
+
+    /// Per-endpoint totals of compared and diverging requests
+    pub async fn summary(&self) -> HashMap<String, ComparisonSummary> {
+        let mut summary: HashMap<String, ComparisonSummary> = HashMap::new();
+        for report in self.reports.read().await.iter() {
+            let entry = summary.entry(report.endpoint.clone()).or_default();
+            entry.compared += 1;
+            if !report.is_match() {
+                entry.diverged += 1;
+            }
+        }
+        summary
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ComparisonSummary {
+    pub compared: u64,
+    pub diverged: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn snapshot(status: u16, headers: &[(&str, &str)], body: Value) -> ResponseSnapshot {
+        ResponseSnapshot {
+            status,
+            headers: headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            body,
+        }
+    }
+
+    fn rules(ignore_headers: &[&str], ignore_fields: &[&str]) -> CompareRules {
+        CompareRules::from_config(&EndpointCompareConfig {
+            enabled: true,
+            baseline: Default::default(),
+            serve: Default::default(),
+            ignore_status: false,
+            ignore_headers: ignore_headers.iter().map(|s| s.to_string()).collect(),
+            ignore_fields: ignore_fields.iter().map(|s| s.to_string()).collect(),
+        })
+    }
+
+    #[test]
+    fn test_identical_responses_match() {
+        let a = snapshot(200, &[("content-type", "application/json")], json!({"id": 1}));
+        assert!(diff_responses(&a, &a.clone(), &CompareRules::default()).is_empty());
+    }
+
+    #[test]
+    fn test_status_and_body_divergence() {
+        let baseline = snapshot(200, &[], json!({"user": {"id": 1, "name": "a"}, "tags": ["x"]}));
+        let live = snapshot(201, &[], json!({"user": {"id": 2, "name": "a"}, "tags": ["x", "y"]}));
+
+        let diffs = diff_responses(&baseline, &live, &CompareRules::default());
+        let paths: Vec<_> = diffs.iter().map(|d| (d.kind.clone(), d.path.as_str())).collect();
+        assert_eq!(paths, vec![
+            (DivergenceKind::Status, ""),
+            (DivergenceKind::Body, "/tags/1"),
+            (DivergenceKind::Body, "/user/id"),
+        ]);
+    }
+
+    #[test]
+    fn test_header_comparison_is_case_insensitive_and_baseline_scoped() {
+        let baseline = snapshot(200, &[("Content-Type", "application/json"), ("X-Version", "1")], json!(null));
+        let live = snapshot(200, &[("content-type", "application/json"), ("date", "now")], json!(null));
+
+        let diffs = diff_responses(&baseline, &live, &CompareRules::default());
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].path, "x-version");
+        assert_eq!(diffs[0].live, None);
+
+        assert!(diff_responses(&baseline, &live, &rules(&["X-Version"], &[])).is_empty());
+    }
+
+    #[test]
+    fn test_ignore_fields_support_pointers_dotted_paths_and_wildcards() {
+        let baseline = snapshot(200, &[], json!({"meta": {"ts": 1}, "items": [{"id": 1, "at": "a"}, {"id": 2, "at": "b"}]}));
+        let live = snapshot(200, &[], json!({"meta": {"ts": 2}, "items": [{"id": 1, "at": "c"}, {"id": 2, "at": "d"}]}));
+
+        assert_eq!(diff_responses(&baseline, &live, &CompareRules::default()).len(), 3);
+        assert!(diff_responses(&baseline, &live, &rules(&[], &["/meta", "items.*.at"])).is_empty());
+        assert!(diff_responses(&baseline, &live, &rules(&[], &["$.meta.ts", "/items/*/at"])).is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_from_structured_handler_output() {
+        let output = r#"{"status": 404, "headers": {"X-Trace": "abc"}, "body": {"error": "missing"}}"#;
+        let snap = ResponseSnapshot::from_handler_output(output);
+        assert_eq!(snap.status, 404);
+        assert_eq!(snap.headers.get("x-trace").map(String::as_str), Some("abc"));
+        assert_eq!(snap.body, json!({"error": "missing"}));
+
+        let plain = ResponseSnapshot::from_handler_output("not json");
+        assert_eq!(plain.status, 200);
+        assert_eq!(plain.body, json!({"response": "not json"}));
+    }
+
+    #[tokio::test]
+    async fn test_recorder_keeps_recent_reports_and_summary() {
+        let recorder = ComparisonRecorder::new();
+        for i in 0..(MAX_REPORTS + 5) {
+            recorder.record(ComparisonReport {
+                endpoint: if i % 2 == 0 { "a".into() } else { "b".into() },
+                method: "GET".into(),
+                path: "/x".into(),
+                timestamp: chrono::Utc::now(),
+                baseline_source: "recorded".into(),
+                divergences: if i % 2 == 0 { vec![] } else {
+                    vec![Divergence { kind: DivergenceKind::Status, path: String::new(), baseline: None, live: None }]
+                },
+            }).await;
+        }
+
+        assert_eq!(recorder.reports(None).await.len(), MAX_REPORTS);
+        let summary = recorder.summary().await;
+        assert_eq!(summary["a"].diverged, 0);
+        assert_eq!(summary["b"].diverged, summary["b"].compared);
+    }
+}
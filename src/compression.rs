@@ -0,0 +1,136 @@
+//! Response compression
+//!
+//! `server.compression` compresses responses with brotli or gzip, whichever
+//! the client's `Accept-Encoding` prefers, once they reach `min_size`. An
+//! endpoint's own `compression` replaces the server's for its responses,
+//! including `enabled: false` to serve them as they are. Responses that
+//! already carry a `Content-Encoding`, images, server-sent events and gRPC
+//! are never compressed. Proxied upstreams may compress too: the proxy
+//! decodes their answers, so clients get them compressed the way the
+//! endpoint says.
+
+use crate::config::{parse_size, BackworksConfig, CompressionConfig};
+use crate::error::{BackworksError, Result};
+use crate::server::MatchedEndpoint;
+use axum::body::HttpBody as Body;
+use axum::http::{header, Response};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tower_http::compression::predicate::{DefaultPredicate, Predicate};
+use tower_http::compression::CompressionLayer;
+
+const DEFAULT_MIN_SIZE: u64 = 1024;
+
+/// Which responses are compressed: `None` where compression is off
+#[derive(Debug, Clone, Default)]
+pub struct CompressionPolicy {
+    /// Smallest response compressed, for responses of no endpoint or one
+    /// without its own settings
+    default: Option<u64>,
+    endpoints: Arc<HashMap<String, Option<u64>>>,
+}
+
+impl CompressionPolicy {
+    pub fn new(config: &BackworksConfig) -> Result<Self> {
+        let min_size = |context: &str, compression: &CompressionConfig, fallback: u64| -> Result<Option<u64>> {
+            if !compression.enabled {
+                return Ok(None);
+            }
+            match compression.min_size.as_deref() {
+                Some(size) => parse_size(size)
+                    .map(|size| Some(size as u64))
+                    .map_err(|e| BackworksError::config(format!("{}.min_size: {}", context, e))),
+                None => Ok(Some(fallback)),
+            }
+        };
+        let server = config.server.compression.as_ref();
+        let default = server.map(|c| min_size("server.compression", c, DEFAULT_MIN_SIZE)).transpose()?.flatten();
+        // Endpoints without a `min_size` take the server's, even where the
+        // server does not compress
+        let fallback = server.and_then(|c| c.min_size.as_deref()).and_then(|size| parse_size(size).ok()).map_or(DEFAULT_MIN_SIZE, |size| size as u64);
+        let mut endpoints = HashMap::new();
+        for (name, endpoint) in &config.endpoints {
+            if let Some(ref compression) = endpoint.compression {
+                endpoints.insert(name.clone(), min_size(&format!("Endpoint '{}' compression", name), compression, fallback)?);
+            }
+        }
+        Ok(Self { default, endpoints: Arc::new(endpoints) })
+    }
+
+    /// Whether any response may be compressed
+    pub fn is_enabled(&self) -> bool {
+        self.default.is_some() || self.endpoints.values().any(Option::is_some)
+    }
+
+    /// The layer compressing responses by this policy
+    pub fn layer(self) -> CompressionLayer<Self> {
+        CompressionLayer::new().compress_when(self)
+    }
+}
+
+impl Predicate for CompressionPolicy {
+    fn should_compress<B: Body>(&self, response: &Response<B>) -> bool {
+        let min_size = match response.extensions().get::<MatchedEndpoint>().and_then(|MatchedEndpoint(name)| self.endpoints.get(name)) {
+            Some(min_size) => *min_size,
+            None => self.default,
+        };
+        let Some(min_size) = min_size else {
+            return false;
+        };
+        let size = response.body().size_hint().exact().or_else(|| {
+            response.headers().get(header::CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+        });
+        // Streamed bodies of unknown length are compressed
+        size.is_none_or(|size| size >= min_size) && DefaultPredicate::new().should_compress(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(endpoint: Option<&str>, size: usize, content_type: &str) -> Response<axum::body::Body> {
+        let mut response = Response::builder()
+            .header(header::CONTENT_TYPE, content_type)
+            .body(axum::body::Body::from(vec![b'a'; size]))
+            .unwrap();
+        if let Some(endpoint) = endpoint {
+            response.extensions_mut().insert(MatchedEndpoint(endpoint.to_string()));
+        }
+        response
+    }
+
+    #[test]
+    fn test_endpoints_override_the_server_policy() {
+        let config = crate::config::parse_blueprint(serde_yaml::from_str(r#"
+name: api
+server:
+  compression: { min_size: 2KB }
+endpoints:
+  report:
+    path: /report
+    compression: { min_size: "100" }
+  download:
+    path: /download
+    compression: { enabled: false }
+  status:
+    path: /status
+"#).unwrap()).unwrap();
+        let policy = CompressionPolicy::new(&config).unwrap();
+        assert!(policy.is_enabled());
+
+        assert!(policy.should_compress(&response(Some("report"), 200, "application/json")));
+        assert!(!policy.should_compress(&response(Some("status"), 200, "application/json")));
+        assert!(policy.should_compress(&response(Some("status"), 4096, "application/json")));
+        assert!(policy.should_compress(&response(None, 4096, "text/html")));
+        assert!(!policy.should_compress(&response(Some("download"), 4096, "application/json")));
+        assert!(!policy.should_compress(&response(Some("report"), 4096, "image/png")));
+
+        let mut config = config;
+        config.server.compression = None;
+        let policy = CompressionPolicy::new(&config).unwrap();
+        assert!(!policy.should_compress(&response(Some("status"), 4096, "application/json")));
+        assert!(!policy.should_compress(&response(Some("report"), 50, "application/json")));
+        assert!(policy.should_compress(&response(Some("report"), 1024, "application/json")));
+    }
+}
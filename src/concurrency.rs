@@ -0,0 +1,234 @@
+//! Endpoint concurrency limits
+//!
+//! An endpoint with `concurrency` handles at most `max_concurrent` requests
+//! at once, so a slow handler ties up only its own share of the runtime.
+//! Up to `max_queue` more wait for a slot, first come first served, for at
+//! most `queue_timeout` or what is left of the request's time budget.
+//! A request finding the queue full is answered with `429`, one that waits
+//! too long with `503`, both with `Retry-After`. Requests running and
+//! waiting are reported in the request metrics.
+
+use crate::config::{parse_duration, ConcurrencyConfig, EndpointConfig};
+use crate::deadline::Deadline;
+use crate::error::{BackworksError, ErrorSource, RequestError, Result};
+use crate::request_metrics::RequestMetrics;
+use axum::http::{HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+const DEFAULT_QUEUE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Why a request was turned away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// Every slot and queue place was taken
+    QueueFull,
+    /// No slot freed up in time
+    QueueTimeout,
+}
+
+impl Rejection {
+    /// Label of the rejection in the request metrics
+    pub fn reason(self) -> &'static str {
+        match self {
+            Rejection::QueueFull => "queue_full",
+            Rejection::QueueTimeout => "queue_timeout",
+        }
+    }
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            Rejection::QueueFull => (StatusCode::TOO_MANY_REQUESTS, "Too many concurrent requests"),
+            Rejection::QueueTimeout => (StatusCode::SERVICE_UNAVAILABLE, "Timed out waiting for a free slot"),
+        };
+        let mut response = (status, Json(serde_json::json!({"error": message, "status": status.as_u16()}))).into_response();
+        response.headers_mut().insert("retry-after", HeaderValue::from_static("1"));
+        response.extensions_mut().insert(RequestError::new(status, message, ErrorSource::Framework));
+        response
+    }
+}
+
+struct Limiter {
+    settings: ConcurrencyConfig,
+    queue_timeout: Duration,
+    slots: Arc<Semaphore>,
+    in_flight: AtomicUsize,
+    queued: AtomicUsize,
+}
+
+impl Limiter {
+    fn report(&self, endpoint: &str, metrics: &RequestMetrics) {
+        metrics.record_concurrency(
+            endpoint,
+            self.in_flight.load(Ordering::Relaxed),
+            self.queued.load(Ordering::Relaxed),
+            self.settings.max_concurrent,
+        );
+    }
+}
+
+/// A place in the queue, given up also when the client goes away
+struct QueuePlace<'a>(&'a AtomicUsize);
+
+impl Drop for QueuePlace<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A slot held while a request runs; dropping it lets the next one in
+pub struct Slot {
+    endpoint: String,
+    limiter: Arc<Limiter>,
+    metrics: RequestMetrics,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.limiter.in_flight.fetch_sub(1, Ordering::Relaxed);
+        self.limiter.report(&self.endpoint, &self.metrics);
+    }
+}
+
+/// Concurrency limits of every endpoint that sets one
+#[derive(Clone, Default)]
+pub struct EndpointLimits {
+    limiters: Arc<HashMap<String, Arc<Limiter>>>,
+}
+
+impl EndpointLimits {
+    pub fn new(endpoints: &HashMap<String, EndpointConfig>) -> Result<Self> {
+        let mut limiters = HashMap::new();
+        for (name, endpoint) in endpoints {
+            let Some(settings) = endpoint.concurrency.clone() else {
+                continue;
+            };
+            if settings.max_concurrent == 0 {
+                return Err(BackworksError::config(format!("Endpoint '{}' concurrency.max_concurrent must be at least 1", name)));
+            }
+            let queue_timeout = match settings.queue_timeout.as_deref() {
+                Some(text) => parse_duration(text)
+                    .map_err(|e| BackworksError::config(format!("Endpoint '{}' concurrency.queue_timeout: {}", name, e)))?,
+                None => DEFAULT_QUEUE_TIMEOUT,
+            };
+            limiters.insert(name.clone(), Arc::new(Limiter {
+                slots: Arc::new(Semaphore::new(settings.max_concurrent)),
+                settings,
+                queue_timeout,
+                in_flight: AtomicUsize::new(0),
+                queued: AtomicUsize::new(0),
+            }));
+        }
+        Ok(Self { limiters: Arc::new(limiters) })
+    }
+
+    /// Keep the limits of `previous` for endpoints whose settings did not
+    /// change, so requests still running under them keep counting
+    pub fn carry_over(&self, previous: &EndpointLimits) -> Self {
+        let limiters = self.limiters.iter()
+            .map(|(name, limiter)| {
+                let limiter = match previous.limiters.get(name) {
+                    Some(old) if old.settings == limiter.settings => old.clone(),
+                    _ => limiter.clone(),
+                };
+                (name.clone(), limiter)
+            })
+            .collect();
+        Self { limiters: Arc::new(limiters) }
+    }
+
+    /// Wait for a slot to run a request to `endpoint` in; `None` when the
+    /// endpoint is not limited
+    pub async fn acquire(&self, endpoint: &str, deadline: Option<Deadline>, metrics: &RequestMetrics) -> std::result::Result<Option<Slot>, Rejection> {
+        let Some(limiter) = self.limiters.get(endpoint) else {
+            return Ok(None);
+        };
+        let permit = match limiter.slots.clone().try_acquire_owned() {
+            Ok(permit) => Ok(permit),
+            Err(_) if limiter.queued.fetch_add(1, Ordering::Relaxed) >= limiter.settings.max_queue => {
+                limiter.queued.fetch_sub(1, Ordering::Relaxed);
+                Err(Rejection::QueueFull)
+            }
+            Err(_) => {
+                let place = QueuePlace(&limiter.queued);
+                limiter.report(endpoint, metrics);
+                let wait = deadline.map_or(limiter.queue_timeout, |deadline| deadline.limit(limiter.queue_timeout));
+                let permit = tokio::time::timeout(wait, limiter.slots.clone().acquire_owned()).await;
+                drop(place);
+                match permit {
+                    Ok(permit) => Ok(permit.expect("endpoint slots are never closed")),
+                    Err(_) => Err(Rejection::QueueTimeout),
+                }
+            }
+        };
+        let permit = match permit {
+            Ok(permit) => permit,
+            Err(rejection) => {
+                metrics.record_rejection(endpoint, rejection.reason());
+                limiter.report(endpoint, metrics);
+                return Err(rejection);
+            }
+        };
+        limiter.in_flight.fetch_add(1, Ordering::Relaxed);
+        limiter.report(endpoint, metrics);
+        Ok(Some(Slot {
+            endpoint: endpoint.to_string(),
+            limiter: limiter.clone(),
+            metrics: metrics.clone(),
+            _permit: permit,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(yaml: &str) -> (EndpointLimits, RequestMetrics) {
+        let config = crate::config::parse_blueprint(serde_yaml::from_str(yaml).unwrap()).unwrap();
+        (EndpointLimits::new(&config.endpoints).unwrap(), RequestMetrics::new(&config))
+    }
+
+    #[tokio::test]
+    async fn test_requests_beyond_the_limit_queue_then_are_turned_away() {
+        let (limits, metrics) = limits(r#"
+name: api
+endpoints:
+  report:
+    path: /report
+    concurrency: { max_concurrent: 1, max_queue: 1, queue_timeout: 50ms }
+  status:
+    path: /status
+"#);
+        assert!(limits.acquire("status", None, &metrics).await.unwrap().is_none());
+
+        let running = limits.acquire("report", None, &metrics).await.unwrap().unwrap();
+        let queued = limits.acquire("report", None, &metrics);
+        let overflow = async {
+            tokio::task::yield_now().await;
+            limits.acquire("report", None, &metrics).await
+        };
+        let (queued, overflow) = tokio::join!(queued, overflow);
+        assert_eq!(queued.err(), Some(Rejection::QueueTimeout));
+        assert_eq!(overflow.err(), Some(Rejection::QueueFull));
+
+        let rendered = metrics.render();
+        assert!(rendered.contains(r#"backworks_endpoint_saturation{endpoint="report"} 1"#), "{}", rendered);
+        assert!(rendered.contains(r#"backworks_endpoint_rejections_total{endpoint="report",reason="queue_full"} 1"#));
+        assert!(rendered.contains(r#"backworks_endpoint_rejections_total{endpoint="report",reason="queue_timeout"} 1"#));
+
+        // A freed slot goes to the request waiting for it
+        let waiting = limits.acquire("report", None, &metrics);
+        let (waiting, _) = tokio::join!(waiting, async { drop(running) });
+        assert!(waiting.unwrap().is_some());
+        assert_eq!(Rejection::QueueFull.into_response().status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
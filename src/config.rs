@@ -3,11 +3,11 @@
 //! This module contains all configuration structures used throughout Backworks,
 //! including deprecated structs kept for backward compatibility.
 
-#![allow(deprecated)] // Allow deprecated MockConfig and MockResponse for backward compatibility
+#![allow(deprecated)] // Allow deprecated MockResponse for backward compatibility
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use crate::error::{BackworksError, Result};
 use crate::plugin::PluginConfig;
 
@@ -40,7 +40,7 @@ impl Default for PluginDiscoveryConfig {
     }
 }
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ExecutionMode {
     #[serde(rename = "runtime")]
     Runtime,
@@ -49,6 +49,12 @@ pub enum ExecutionMode {
     #[default]
     #[serde(rename = "plugin")]
     Plugin,
+    #[serde(rename = "static")]
+    Static,
+    #[serde(rename = "mock")]
+    Mock,
+    #[serde(rename = "proxy")]
+    Proxy,
 }
 
 
@@ -62,16 +68,17 @@ pub struct BuildTarget {
     pub output: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SecurityProfiles {
-    pub profiles: HashMap<String, SecurityProfile>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Security settings selected together with `security.profile`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SecurityProfile {
+    /// Mask credentials in logs, captures and the request log
+    #[serde(default)]
     pub strip_secrets: bool,
+    #[serde(default)]
     pub enable_debug: bool,
+    #[serde(default)]
     pub verbose_logging: bool,
+    /// Answer server errors with a generic body
     pub obfuscate_internals: Option<bool>,
     pub enable_rate_limiting: Option<bool>,
     pub require_https: Option<bool>,
@@ -111,6 +118,34 @@ pub struct BackworksConfig {
     pub cache: Option<CacheConfig>,
     pub security: Option<SecurityConfig>,
     pub monitoring: Option<MonitoringConfig>,
+    pub state: Option<StateStoreConfig>,
+    /// Error catalog: code -> status, message template, docs URL
+    pub errors: Option<HashMap<String, ErrorCatalogEntry>>,
+    /// Locale negotiation and time zone of requests
+    pub localization: Option<LocalizationConfig>,
+    /// Active scenario and how requests pick their own
+    pub scenarios: Option<ScenariosConfig>,
+    /// Append-only log of mutating requests for crash recovery
+    pub journal: Option<JournalConfig>,
+    /// Recording of request/response pairs, streamed live to the dashboard
+    pub capture: Option<CaptureConfig>,
+    /// Access to the runtime admin API under `/_backworks`
+    pub admin: Option<AdminConfig>,
+    /// Jobs run on cron expressions while the server runs
+    #[serde(default)]
+    pub schedules: HashMap<String, ScheduleConfig>,
+    /// Cookie sessions, exposed to runtime handlers as `req.session`
+    pub sessions: Option<SessionConfig>,
+    /// API reference served at `/docs`
+    pub docs: Option<DocsConfig>,
+    
+    /// Fail loading when a referenced `${VAR}` is unset and has no default
+    #[serde(default)]
+    pub strict_env: bool,
+    
+    /// Whether endpoints shadowing each other fail validation
+    #[serde(default)]
+    pub route_conflicts: RouteConflictMode,
     
     #[serde(default)]
     pub global_headers: HashMap<String, String>,
@@ -121,12 +156,47 @@ pub struct BackworksConfig {
 
 // ExecutionMode enum is defined above
 
+/// How validation treats endpoints whose paths overlap, such as
+/// `/users/{id}` and `/users/me`. Routes the server cannot register together
+/// fail validation in either mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteConflictMode {
+    /// Log shadowed endpoints
+    #[default]
+    Warn,
+    /// Fail validation on shadowed endpoints
+    Error,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
-    #[serde(default = "default_port")]
+    #[serde(default = "default_port", deserialize_with = "port_number")]
     pub port: u16,
+    /// Address or hostname to bind; `::` (or `[::]`) binds every IPv6 and,
+    /// unless `ipv6_only` is set, every IPv4 interface
     #[serde(default = "default_host")]
     pub host: String,
+    /// Refuse IPv4 clients on an IPv6 host instead of serving both stacks
+    #[serde(default)]
+    pub ipv6_only: bool,
+    /// Honour `?fields=` on JSON responses
+    #[serde(default = "default_partial_responses")]
+    pub partial_responses: bool,
+    /// Total time budget of a request, such as `10s`; clients can shorten
+    /// it with `X-Request-Timeout`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_timeout: Option<String>,
+    /// Compression of responses for clients that accept it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<CompressionConfig>,
+    /// Serve HTTPS instead of plain HTTP
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    /// Directory handlers may return files from (default: the working
+    /// directory)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub downloads: Option<PathBuf>,
 }
 
 impl Default for ServerConfig {
@@ -134,11 +204,78 @@ impl Default for ServerConfig {
         Self {
             port: default_port(),
             host: default_host(),
+            ipv6_only: false,
+            partial_responses: default_partial_responses(),
+            request_timeout: None,
+            compression: None,
+            tls: None,
+            downloads: None,
         }
     }
 }
 
+/// Certificate of an HTTPS server: PEM files, or one obtained from an ACME
+/// authority such as Let's Encrypt
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// Certificate chain, PEM encoded; reloaded when the file changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cert: Option<PathBuf>,
+    /// Private key of the certificate, PEM encoded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acme: Option<AcmeConfig>,
+    /// Port of a plain HTTP listener next to the HTTPS one, answering ACME
+    /// challenges and serving the API, or redirecting to HTTPS under
+    /// `security.require_https` (default 80 with `acme` or
+    /// `require_https`, none otherwise)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_port: Option<u16>,
+    /// CA certificates, PEM encoded, that client certificates are verified
+    /// against; clients without a certificate may still connect
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ca: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AcmeConfig {
+    /// Names the certificate is for; each must reach `http_port`
+    pub domains: Vec<String>,
+    /// Contact for expiry notices from the authority
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// Directory URL of the authority (default Let's Encrypt)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directory: Option<String>,
+    /// Where the account key and certificate are kept between runs
+    /// (default `.backworks/acme`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<PathBuf>,
+    /// How long before expiry the certificate is renewed (default `30d`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renew_before: Option<String>,
+}
+
+fn default_partial_responses() -> bool { true }
+
 fn default_port() -> u16 { 8080 }
+
+/// A port given as a number or, as `${PORT}` placeholders leave it, as text
+fn port_number<'de, D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<u16, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Port {
+        Number(u16),
+        Text(String),
+    }
+    match Port::deserialize(deserializer)? {
+        Port::Number(port) => Ok(port),
+        Port::Text(text) => text.trim().parse().map_err(|_| {
+            serde::de::Error::invalid_type(serde::de::Unexpected::Str(&text), &"a port number")
+        }),
+    }
+}
 fn default_host() -> String { "0.0.0.0".to_string() }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -153,8 +290,17 @@ pub struct EndpointConfig {
     #[serde(default)]
     pub mode: Option<ExecutionMode>,
     
-    // Mock configuration (removed)
-    // Removed mock and mock_responses fields
+    // Modes tried in order until one succeeds, instead of `mode`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modes: Vec<ExecutionMode>,
+    
+    // Generated data served in mock mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mock: Option<MockConfig>,
+    
+    // Upstream forwarded to in proxy mode, with optional record and replay
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<ProxyConfig>,
     
     // Runtime configuration  
     pub runtime: Option<RuntimeConfig>,
@@ -179,24 +325,321 @@ pub struct EndpointConfig {
     pub parameters: Option<Vec<ParameterConfig>>,
     pub validation: Option<ValidationConfig>,
     
+    // Conversion of request body fields to their declared parameter types
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coerce: Option<CoercionConfig>,
+    
     // Monitoring
     pub monitoring: Option<EndpointMonitoringConfig>,
+    
+    // Response comparison against a recorded response or another mode
+    #[serde(default)]
+    pub compare: Option<EndpointCompareConfig>,
+    
+    // Activation window and gradual traffic rollout
+    #[serde(default)]
+    pub rollout: Option<RolloutConfig>,
+    
+    // Handlebars templates rewriting the request and response bodies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<TemplateTransform>,
+    
+    // JSONPath-based reshaping of request and response bodies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<EndpointTransformConfig>,
+    
+    // Deprecation notice sent with every response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<DeprecationConfig>,
+    
+    // Directory served in static mode
+    #[serde(default, rename = "static", skip_serializing_if = "Option::is_none")]
+    pub static_files: Option<StaticFilesConfig>,
+    
+    // Middleware run in order between the global pipeline and the handler
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub middleware: Vec<MiddlewareSpec>,
+    
+    // Responses served instead of the handler while a named scenario is active
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub scenarios: HashMap<String, ScenarioConfig>,
+    
+    // Latency and faults injected for resilience testing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chaos: Option<ChaosConfig>,
+    
+    // How long data stored for the endpoint is kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention: Option<RetentionConfig>,
+    
+    // Total time budget of a request, replacing `server.request_timeout`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+    
+    // Response compression, replacing `server.compression`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<CompressionConfig>,
+    
+    // Requests handled at once, and how many more may wait
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<ConcurrencyConfig>,
+    
+    // Signature check of inbound webhooks, before the handler runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<WebhookConfig>,
+    
+    // `false` exempts the endpoint from `sessions.csrf` checks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub csrf: Option<bool>,
+}
+
+impl EndpointConfig {
+    /// Execution modes in the order they are tried: `modes`, else `mode`,
+    /// else the blueprint's default
+    pub fn mode_chain<'a>(&'a self, default: &'a ExecutionMode) -> Vec<&'a ExecutionMode> {
+        if self.modes.is_empty() {
+            vec![self.primary_mode(default)]
+        } else {
+            self.modes.iter().collect()
+        }
+    }
+
+    /// The mode tried first
+    pub fn primary_mode<'a>(&'a self, default: &'a ExecutionMode) -> &'a ExecutionMode {
+        self.modes.first().or(self.mode.as_ref()).unwrap_or(default)
+    }
+}
+
+/// Entry of an endpoint's `middleware` list: a registered middleware name,
+/// or a single-key map of the name to its settings
+///
+/// ```yaml
+/// middleware:
+///   - auth
+///   - rate_limit: { requests: 100, window: 60 }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct MiddlewareSpec {
+    pub name: String,
+    pub config: serde_json::Value,
+}
+
+impl MiddlewareSpec {
+    pub fn named(name: impl Into<String>) -> Self {
+        Self { name: name.into(), config: serde_json::Value::Null }
+    }
+}
+
+impl<'de> Deserialize<'de> for MiddlewareSpec {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Spec {
+            Name(String),
+            Configured(HashMap<String, serde_json::Value>),
+        }
+
+        match Spec::deserialize(deserializer)? {
+            Spec::Name(name) => Ok(Self::named(name)),
+            Spec::Configured(map) if map.len() == 1 => {
+                let (name, config) = map.into_iter().next().unwrap_or_default();
+                Ok(Self { name, config })
+            }
+            Spec::Configured(_) => Err(serde::de::Error::custom(
+                "middleware entry must be a name or a single `name: settings` map",
+            )),
+        }
+    }
+}
+
+impl Serialize for MiddlewareSpec {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        if self.config.is_null() {
+            serializer.serialize_str(&self.name)
+        } else {
+            HashMap::from([(&self.name, &self.config)]).serialize(serializer)
+        }
+    }
+}
+
+/// Directory served by a `mode: static` endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StaticFilesConfig {
+    /// Directory to serve, relative to the working directory
+    pub dir: PathBuf,
+    /// Files tried, in order, when a directory is requested
+    #[serde(default = "default_index_files")]
+    pub index: Vec<String>,
+    /// Serve the root index for unknown extensionless paths, for client-side routing
+    #[serde(default)]
+    pub spa_fallback: bool,
+    /// `Cache-Control` header sent with every file
+    pub cache_control: Option<String>,
+}
+
+fn default_index_files() -> Vec<String> {
+    vec!["index.html".to_string()]
 }
 
 fn default_methods() -> Vec<String> {
     vec!["GET".to_string()]
 }
 
-#[deprecated(since = "0.2.0", note = "Mock mode is deprecated, use runtime or plugin mode instead")]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Generated responses of a mock mode endpoint
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MockConfig {
-    pub data: Option<serde_json::Value>,
-    pub ai_generated: Option<bool>,
-    pub based_on_patterns: Option<bool>,
+    /// Shape of a generated item; string leaves starting with `$` are generators
+    pub schema: Option<serde_json::Value>,
+    /// Items in the endpoint's collection; responses are the whole list
     pub count: Option<usize>,
-    pub patterns: Option<Vec<String>>,
+    /// Seed of the generated data (default: derived from the endpoint name)
+    pub seed: Option<u64>,
+    /// Serve the item of this mock endpoint's collection that the path names
+    pub item_of: Option<String>,
+    /// Field matched against the path parameter of the same name (default `id`)
+    pub key: Option<String>,
+    /// Status of generated responses (default 200)
+    pub status: Option<u16>,
+    /// Keep the collection in the state store so requests can change it
+    #[serde(default)]
+    pub stateful: bool,
+}
+
+/// Upstream of a proxy mode endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    /// Base URL; the request path and query string are appended
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream: Option<String>,
+    /// Base URLs to balance requests over instead of one upstream
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<ProxyTargetConfig>,
+    /// How a target is picked for each request (default round robin)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_balancing: Option<LoadBalancingConfig>,
+    /// Upstream request timeout in milliseconds (default 30000)
+    pub timeout_ms: Option<u64>,
+    /// Serve recorded responses instead of calling the upstream
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replay: Option<ReplayConfig>,
+    /// TLS settings of calls to HTTPS upstreams; a target's own replace them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<UpstreamTlsConfig>,
+}
+
+/// How calls to an HTTPS upstream verify it and identify themselves
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UpstreamTlsConfig {
+    /// PEM bundle of certificate authorities trusted besides the system's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_bundle: Option<PathBuf>,
+    /// PEM certificate chain presented to upstreams requiring client
+    /// certificates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<PathBuf>,
+    /// PKCS #8 PEM private key of `client_cert`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<PathBuf>,
+    /// Accept any certificate the upstream presents; for development only
+    #[serde(default)]
+    pub danger_accept_invalid_certs: bool,
+}
+
+/// A proxy target: its base URL, optionally with labels such as `zone` that
+/// load-balancing strategies can route on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ProxyTargetConfig {
+    Url(String),
+    Labeled {
+        url: String,
+        #[serde(default)]
+        labels: HashMap<String, String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tls: Option<UpstreamTlsConfig>,
+    },
+}
+
+impl ProxyTargetConfig {
+    pub fn url(&self) -> &str {
+        match self {
+            ProxyTargetConfig::Url(url) | ProxyTargetConfig::Labeled { url, .. } => url,
+        }
+    }
+
+    pub fn labels(&self) -> HashMap<String, String> {
+        match self {
+            ProxyTargetConfig::Url(_) => HashMap::new(),
+            ProxyTargetConfig::Labeled { labels, .. } => labels.clone(),
+        }
+    }
+
+    pub fn tls(&self) -> Option<&UpstreamTlsConfig> {
+        match self {
+            ProxyTargetConfig::Url(_) => None,
+            ProxyTargetConfig::Labeled { tls, .. } => tls.as_ref(),
+        }
+    }
 }
 
+/// Target selection strategy of a proxy endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadBalancingConfig {
+    /// `round_robin` (default), `least_connections`, or a strategy a plugin
+    /// registers
+    #[serde(default = "default_load_balancing_algorithm")]
+    pub algorithm: String,
+    /// Settings passed to the strategy
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub config: serde_json::Value,
+}
+
+fn default_load_balancing_algorithm() -> String { "round_robin".to_string() }
+
+/// Recorded upstream responses, VCR style: a matching recording is served,
+/// anything else is proxied and recorded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayConfig {
+    /// Serve recordings (default true); proxy every request when false
+    #[serde(default = "default_replay_enabled")]
+    pub enabled: bool,
+    /// JSON file holding the recordings, created on the first recording
+    pub cassette: PathBuf,
+    /// Record requests without a recording (default true); without it they fail
+    #[serde(default = "default_replay_record")]
+    pub record: bool,
+    /// Which parts of a request must equal a recording's
+    #[serde(default, rename = "match")]
+    pub matching: ReplayMatchConfig,
+}
+
+fn default_replay_enabled() -> bool { true }
+
+fn default_replay_record() -> bool { true }
+
+/// Request parts compared with recordings; method and path always are
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayMatchConfig {
+    /// Compare query parameters (default true)
+    #[serde(default = "default_match_query")]
+    pub query: bool,
+    /// Headers whose values must be equal
+    #[serde(default)]
+    pub headers: Vec<String>,
+    /// JSON body fields that must be equal, as JSON pointers (`/customer/id`)
+    /// or dotted paths (`customer.id`); the whole body when empty
+    #[serde(default)]
+    pub body_fields: Vec<String>,
+}
+
+impl Default for ReplayMatchConfig {
+    fn default() -> Self {
+        Self { query: true, headers: Vec::new(), body_fields: Vec::new() }
+    }
+}
+
+fn default_match_query() -> bool { true }
+
 #[deprecated(since = "0.2.0", note = "Mock mode is deprecated, use runtime or plugin mode instead")]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MockResponse {
@@ -208,6 +651,34 @@ pub struct MockResponse {
 
 fn default_status() -> u16 { 200 }
 
+/// What an endpoint serves while a scenario is active: one response, or a
+/// `sequence` served in turn
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScenarioConfig {
+    #[serde(flatten)]
+    pub response: ScenarioResponse,
+    /// Responses served one per request, instead of a single response
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sequence: Vec<ScenarioResponse>,
+    /// Start the sequence over after its last response, instead of repeating
+    /// the last one
+    #[serde(default)]
+    pub cycle: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScenarioResponse {
+    /// Response status (default 200)
+    pub status: Option<u16>,
+    /// Response body (default `null`)
+    pub body: Option<serde_json::Value>,
+    /// Wait this long before responding
+    pub delay_ms: Option<u64>,
+    /// Run the endpoint's handler as usual for this turn
+    #[serde(default)]
+    pub passthrough: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RuntimeConfig {
     pub language: String,
@@ -272,6 +743,42 @@ pub struct TransformConfig {
     pub script: Option<ScriptTransform>,
 }
 
+/// Declarative reshaping of an endpoint's JSON bodies
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EndpointTransformConfig {
+    /// Applied to the request body before the handler runs
+    pub request: Option<BodyTransform>,
+    /// Applied to successful response bodies
+    pub response: Option<BodyTransform>,
+    /// Fields kept in or removed from successful response bodies, after `response`
+    pub response_filter: Option<ResponseFilter>,
+    /// Handler statuses sent as other statuses, optionally with another body
+    pub status_code_mapping: Option<HashMap<u16, StatusCodeMapping>>,
+}
+
+/// Target of a `status_code_mapping` entry: a status, or a status with the
+/// body to send instead of the handler's
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StatusCodeMapping {
+    Status(u16),
+    Response {
+        status: u16,
+        /// Replacement body, sent as is
+        body: Option<serde_json::Value>,
+        /// Handlebars template rendering the replacement body
+        body_template: Option<String>,
+    },
+}
+
+impl StatusCodeMapping {
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::Status(status) | Self::Response { status, .. } => *status,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BodyTransform {
     // JSON transformations
@@ -287,6 +794,7 @@ pub struct BodyTransform {
     // Format conversions
     pub input_format: Option<ContentFormat>,
     pub output_format: Option<ContentFormat>,
+    pub format_options: Option<HashMap<String, String>>, // "root", "item", "delimiter"
     
     // Custom transformation script
     pub transform_script: Option<String>,
@@ -300,14 +808,23 @@ pub struct StringReplace {
     pub case_sensitive: Option<bool>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ContentFormat {
+    #[serde(alias = "json")]
     Json,
+    #[serde(alias = "xml")]
     Xml,
+    #[serde(alias = "yaml")]
     Yaml,
+    #[serde(alias = "csv")]
     Csv,
+    #[serde(alias = "text", alias = "plain_text")]
     PlainText,
+    #[serde(alias = "html")]
+    Html,
+    #[serde(alias = "form", alias = "form_data")]
     FormData,
+    #[serde(alias = "base64")]
     Base64,
 }
 
@@ -370,16 +887,24 @@ pub struct PaginationTransform {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TemplateTransform {
+    #[serde(default)]
     pub engine: TemplateEngine,
+    /// Rewrites the request body before the handler runs
     pub request_template: Option<String>,
+    /// Renders the response body from the handler's response
     pub response_template: Option<String>,
+    /// Constants available to both templates as `vars`
     pub variables: Option<HashMap<String, String>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TemplateEngine {
+    #[default]
+    #[serde(alias = "handlebars")]
     Handlebars,
+    #[serde(alias = "mustache")]
     Mustache,
+    #[serde(alias = "jinja2")]
     Jinja2,
     Custom(String),
 }
@@ -401,7 +926,7 @@ pub enum ScriptLanguage {
     Wasm,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CaptureConfig {
     pub analyze: Option<bool>,
     pub learn_schema: Option<bool>,
@@ -444,6 +969,19 @@ pub struct ParameterConfig {
     pub format: Option<String>,
 }
 
+/// Converting request body fields to the types declared in `parameters`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoercionConfig {
+    #[serde(default = "default_coerce_enabled")]
+    pub enabled: bool,
+    /// Trim surrounding whitespace from string values
+    #[serde(default = "default_coerce_trim")]
+    pub trim: bool,
+}
+
+fn default_coerce_enabled() -> bool { true }
+fn default_coerce_trim() -> bool { true }
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValidationConfig {
     pub create: Option<HashMap<String, serde_json::Value>>,
@@ -458,11 +996,259 @@ pub struct EndpointMonitoringConfig {
     pub category: Option<String>,
     pub critical: Option<bool>,
     pub expected_duration_ms: Option<u64>,
+    
+    // Dimensions attached to the endpoint's request metrics and access logs
+    pub team: Option<String>,
+    pub domain: Option<String>,
+    pub criticality: Option<String>,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}
+
+/// Labels every request metric carries, which endpoints cannot override
+pub const RESERVED_METRIC_LABELS: &[&str] = &["endpoint", "method", "status", "le", "quantile"];
+
+impl EndpointMonitoringConfig {
+    /// Metric and log dimensions as sorted `(label, value)` pairs
+    pub fn dimensions(&self) -> Vec<(String, String)> {
+        let mut dimensions: Vec<(String, String)> = [("team", &self.team), ("domain", &self.domain), ("criticality", &self.criticality)]
+            .into_iter()
+            .filter_map(|(label, value)| Some((label.to_string(), value.clone()?)))
+            .chain(self.labels.iter().map(|(label, value)| (label.clone(), value.clone())))
+            .collect();
+        dimensions.sort();
+        dimensions
+    }
+}
+
+/// Serve an endpoint only within a time window and/or to a share of traffic
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RolloutConfig {
+    pub start: Option<chrono::DateTime<chrono::Utc>>,
+    pub end: Option<chrono::DateTime<chrono::Utc>>,
+    /// Share of traffic served until the first step is reached (default 100)
+    pub percentage: Option<f64>,
+    /// Percentage changes over time, e.g. 10% -> 50% -> 100%
+    #[serde(default)]
+    pub steps: Vec<RolloutStep>,
+    /// Header used to keep callers in the same bucket (default: the
+    /// authenticated subject, then the client IP)
+    pub sticky_header: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RolloutStep {
+    pub at: chrono::DateTime<chrono::Utc>,
+    pub percentage: f64,
+}
+
+/// Deprecation notice of an endpoint
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeprecationConfig {
+    /// Date the endpoint was deprecated, sent as the `Deprecation` header
+    pub since: Option<chrono::NaiveDate>,
+    /// Date the endpoint will be removed, sent as the `Sunset` header
+    pub sunset: Option<chrono::NaiveDate>,
+    /// Migration guide, sent as a `Link` with `rel="deprecation"`
+    pub link: Option<String>,
+    pub message: Option<String>,
+}
+
+/// Compare an endpoint's live response against a baseline on every request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EndpointCompareConfig {
+    #[serde(default = "default_compare_enabled")]
+    pub enabled: bool,
+    
+    /// What the live response is compared against
+    #[serde(default)]
+    pub baseline: CompareBaseline,
+    
+    /// Which of the two responses is returned to the client
+    #[serde(default)]
+    pub serve: CompareServe,
+    
+    #[serde(default)]
+    pub ignore_status: bool,
+    
+    /// Header names excluded from comparison (`*` ignores all headers)
+    #[serde(default)]
+    pub ignore_headers: Vec<String>,
+    
+    /// Body fields excluded from comparison, as JSON pointers (`/meta/ts`)
+    /// or dotted paths (`items.*.created_at`)
+    #[serde(default)]
+    pub ignore_fields: Vec<String>,
+}
+
+fn default_compare_enabled() -> bool { true }
+
+/// Faults injected into an endpoint's responses; every rate is the fraction
+/// of requests affected
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChaosConfig {
+    /// Inject faults (default true); switchable at runtime
+    #[serde(default = "default_chaos_enabled")]
+    pub enabled: bool,
+    /// Delay before the handler runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<LatencyDistribution>,
+    /// Requests answered with an error instead of running the handler
+    #[serde(default)]
+    pub error_rate: f64,
+    /// Statuses of injected errors, picked at random (default 500)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub error_statuses: Vec<u16>,
+    /// Requests whose connection is closed before the body is sent
+    #[serde(default)]
+    pub drop_rate: f64,
+    /// Responses cut off halfway through the body
+    #[serde(default)]
+    pub truncate_rate: f64,
+}
+
+fn default_chaos_enabled() -> bool { true }
+
+/// Bounds on the data stateful features keep for an endpoint: its stateful
+/// mock collection, captured requests and journal entries
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetentionConfig {
+    /// Remove data older than this, such as `7d` or `12h`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<String>,
+    /// Keep at most this many of the newest captured requests, journal
+    /// entries and items of each mock session's collection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_items: Option<usize>,
+}
+
+/// gzip or brotli compression of responses
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompressionConfig {
+    #[serde(default = "default_compression_enabled")]
+    pub enabled: bool,
+    /// Smallest response compressed, such as `1KB`; an endpoint without
+    /// its own takes the server's (default `1KB`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<String>,
+}
+
+fn default_compression_enabled() -> bool { true }
+
+/// Limit on the requests an endpoint handles at once
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConcurrencyConfig {
+    pub max_concurrent: usize,
+    /// Requests waiting for a slot; more are answered with `429`
+    #[serde(default)]
+    pub max_queue: usize,
+    /// How long a request waits for a slot before it is answered with
+    /// `503`, such as `500ms` (default `1s`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_timeout: Option<String>,
+}
+
+/// Signature check of an inbound webhook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub provider: WebhookProvider,
+    /// The signing secret, usually `${STRIPE_WEBHOOK_SECRET}` or alike
+    pub secret: String,
+    /// How far a signed timestamp may be from now, such as `5m` (default);
+    /// Stripe and Slack sign one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tolerance: Option<String>,
+    /// Header carrying the signature, for the `hmac` provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+    /// Text before the signature in the header, such as `sha256=`, for the
+    /// `hmac` provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub algorithm: WebhookAlgorithm,
+    #[serde(default)]
+    pub encoding: SignatureEncoding,
+}
+
+/// Whose signature scheme a webhook follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookProvider {
+    /// `Stripe-Signature: t=...,v1=...` over `{t}.{body}`
+    Stripe,
+    /// `X-Hub-Signature-256: sha256=...` over the body
+    Github,
+    /// `X-Slack-Signature: v0=...` over `v0:{timestamp}:{body}`
+    Slack,
+    /// An HMAC of the body in `header`
+    Hmac,
+}
+
+/// HMAC digest of the `hmac` provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookAlgorithm {
+    Sha1,
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+/// How the `hmac` provider's signature is written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureEncoding {
+    #[default]
+    Hex,
+    Base64,
+}
+
+/// How injected delays are spread
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "distribution", rename_all = "snake_case", deny_unknown_fields)]
+pub enum LatencyDistribution {
+    Fixed { ms: u64 },
+    Uniform { min_ms: u64, max_ms: u64 },
+    /// Clamped at zero
+    Normal { mean_ms: u64, stddev_ms: u64 },
+    /// Mostly short delays with a long tail
+    Exponential { mean_ms: u64 },
+}
+
+/// Baseline for comparison: a recorded response, or a second execution mode
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompareBaseline {
+    pub response: Option<RecordedResponse>,
+    pub mode: Option<ExecutionMode>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    #[serde(default = "default_status")]
+    pub status: u16,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub body: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompareServe {
+    #[default]
+    Live,
+    Baseline,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DashboardConfig {
-    #[serde(default = "default_dashboard_port")]
+    #[serde(default = "default_dashboard_port", deserialize_with = "port_number")]
     pub port: u16,
     
     #[serde(default)]
@@ -472,6 +1258,17 @@ pub struct DashboardConfig {
     pub real_time: Option<RealTimeConfig>,
     pub visualization: Option<VisualizationConfig>,
     pub access: Option<AccessConfig>,
+    /// Let the Studio edit and save the blueprint file (default false);
+    /// needs `admin.token`
+    pub editor: Option<bool>,
+    /// Serve the Studio from this directory instead of the one built into
+    /// the binary
+    pub assets_dir: Option<PathBuf>,
+    /// Recent requests the request log keeps (default 1000)
+    pub request_log_size: Option<usize>,
+    /// Serve the dashboard over HTTPS with the certificate of `server.tls`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub https: Option<bool>,
 }
 
 fn default_dashboard_port() -> u16 { 3000 }
@@ -481,6 +1278,23 @@ pub struct RealTimeConfig {
     pub enabled: Option<bool>,
     pub update_frequency: Option<u64>,
     pub max_history: Option<usize>,
+    /// Events buffered per subscriber before `slow_consumer` applies
+    pub queue_size: Option<usize>,
+    pub slow_consumer: Option<SlowConsumerPolicy>,
+    pub max_subscribers: Option<usize>,
+}
+
+/// What happens to a subscriber whose event queue is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlowConsumerPolicy {
+    /// Discard the oldest queued event to make room
+    #[default]
+    DropOldest,
+    /// Discard the event being published
+    DropNewest,
+    /// End the subscription
+    Disconnect,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -505,14 +1319,30 @@ pub struct DatabaseConfig {
     pub connection_string: Option<String>,
     pub connection_string_env: Option<String>,
     pub pool: Option<PoolConfig>,
+    /// Settings of the database plugin of that name, in place of these
     pub databases: Option<HashMap<String, DatabaseConfig>>,
 }
 
+impl DatabaseConfig {
+    /// `connection_string`, or the value of `connection_string_env`
+    pub fn resolved_connection_string(&self) -> crate::error::Result<Option<String>> {
+        match (&self.connection_string, &self.connection_string_env) {
+            (Some(connection), _) => Ok(Some(connection.clone())),
+            (None, Some(var)) => std::env::var(var).map(Some)
+                .map_err(|_| crate::error::BackworksError::config(format!("Database environment variable '{}' is not set", var))),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PoolConfig {
     pub min_connections: Option<u32>,
     pub max_connections: Option<u32>,
+    /// Seconds to wait for a connection
     pub connection_timeout: Option<u64>,
+    /// Seconds between pings of the pool
+    pub health_check_interval: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -561,6 +1391,34 @@ pub struct SecurityConfig {
     pub authentication: Option<SecurityAuthConfig>,
     pub validation: Option<SecurityValidationConfig>,
     pub headers: Option<HashMap<String, String>>,
+    /// Redirect plain HTTP requests to HTTPS, as a build profile's
+    /// `require_https` does; needs `server.tls`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub require_https: Option<bool>,
+    /// Active security profile: one of `profiles`, or the built-in
+    /// `production` and `development`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub profiles: HashMap<String, SecurityProfile>,
+    /// `Strict-Transport-Security`, sent by default with `server.tls`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hsts: Option<HstsConfig>,
+    /// `Content-Security-Policy` of API responses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_security_policy: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HstsConfig {
+    /// Default: whether `server.tls` is set
+    pub enabled: Option<bool>,
+    /// How long browsers remember to use HTTPS, 365 days by default
+    pub max_age: Option<String>,
+    #[serde(default)]
+    pub include_subdomains: bool,
+    #[serde(default)]
+    pub preload: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -570,6 +1428,26 @@ pub struct CorsConfig {
     pub methods: Option<Vec<String>>,
     pub headers: Option<Vec<String>>,
     pub credentials: Option<bool>,
+    /// Seconds browsers may cache a preflight answer (`Access-Control-Max-Age`)
+    pub max_age: Option<u64>,
+    /// Origin groups with their own settings, checked in order before the
+    /// settings above
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub policies: Vec<CorsPolicyConfig>,
+}
+
+/// CORS settings for a group of origins; unset fields fall back to the
+/// top-level `cors` settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorsPolicyConfig {
+    /// Label for the policy in metrics, `policy_<index>` by default
+    pub name: Option<String>,
+    /// Exact origins, `*`, or subdomain patterns such as `https://*.example.com`
+    pub origins: Vec<String>,
+    pub methods: Option<Vec<String>>,
+    pub headers: Option<Vec<String>>,
+    pub credentials: Option<bool>,
+    pub max_age: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -588,6 +1466,38 @@ pub struct SecurityAuthConfig {
     pub algorithm: Option<String>,
     pub expiration: Option<u64>,
     pub validation: Option<AuthValidationConfig>,
+    pub trusted_headers: Option<TrustedHeaderAuthConfig>,
+}
+
+/// Identity headers injected by an upstream gateway (e.g. an SSO proxy).
+/// Headers are only trusted when the injector is verified by source address
+/// or client certificate; otherwise they are stripped from the request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrustedHeaderAuthConfig {
+    #[serde(default = "default_trusted_user_header")]
+    pub user_header: String,
+    pub email_header: Option<String>,
+    pub roles_header: Option<String>,
+    #[serde(default = "default_roles_separator")]
+    pub roles_separator: String,
+    /// Source IPs or CIDR ranges allowed to inject identity headers
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+    /// Common names of client certificates allowed to inject identity
+    /// headers; needs `server.tls.client_ca`
+    #[serde(default)]
+    pub trusted_client_certs: Vec<String>,
+    /// Reject requests without a trusted identity instead of passing them through
+    #[serde(default)]
+    pub required: bool,
+}
+
+fn default_trusted_user_header() -> String {
+    "X-Authenticated-User".to_string()
+}
+
+fn default_roles_separator() -> String {
+    ",".to_string()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -597,7 +1507,12 @@ pub struct AuthValidationConfig {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecurityValidationConfig {
+    /// Largest request body accepted, such as `10MB` (default `2MB`)
     pub max_body_size: Option<String>,
+    /// Largest a compressed request body may grow to when decompressed
+    /// (default `max_body_size`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_decompressed_size: Option<String>,
     pub require_content_type: Option<bool>,
     pub validate_json: Option<bool>,
 }
@@ -645,34 +1560,66 @@ pub struct FileLoggingConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HealthConfig {
     pub enabled: Option<bool>,
+    /// Liveness endpoint (default "/health")
     pub endpoint: Option<String>,
+    /// Readiness endpoint (default "/ready")
+    pub ready_endpoint: Option<String>,
     pub checks: Option<Vec<HealthCheckConfig>>,
+    pub saturation: Option<SaturationConfig>,
+}
+
+/// Load limits past which readiness reports the replica saturated
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SaturationConfig {
+    /// Requests being served at once
+    pub max_in_flight: Option<usize>,
+    /// Work queued inside plugins, summed over their `queue_depth` health detail
+    pub max_queue_depth: Option<u64>,
+    /// Plugin circuit breakers allowed to be open at once
+    pub max_open_circuits: Option<usize>,
+    /// Share of any plugin connection pool in use (0.0-1.0), from the
+    /// `pool_in_use` and `pool_size` health details
+    pub max_pool_utilization: Option<f64>,
+    /// Fail readiness with 503 when saturated instead of reporting degraded
+    #[serde(default)]
+    pub fail: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HealthCheckConfig {
     pub name: String,
+    /// "url" or "database"
     #[serde(rename = "type")]
     pub check_type: String,
+    /// Timeout in seconds (default 5)
     pub timeout: Option<u64>,
     pub url: Option<String>,
+    /// A failing critical check marks the service not ready (default true)
+    pub critical: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AlertsConfig {
     pub enabled: Option<bool>,
+    /// How often rules are evaluated, e.g. "30s" (default 30s)
+    pub interval: Option<String>,
+    /// Lookback window for request metrics, e.g. "5m" (default 5m)
+    pub window: Option<String>,
     pub channels: Option<HashMap<String, AlertChannelConfig>>,
     pub rules: Option<Vec<AlertRuleConfig>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AlertChannelConfig {
+    pub webhook_url: Option<String>,
     pub webhook_url_env: Option<String>,
     pub channel: Option<String>,
     pub smtp_host: Option<String>,
     pub smtp_port: Option<u16>,
     pub username_env: Option<String>,
     pub password_env: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -683,6 +1630,231 @@ pub struct AlertRuleConfig {
     pub channels: Vec<String>,
 }
 
+/// Error catalog entry referenced by handlers via `ctx.error(code, params)`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorCatalogEntry {
+    pub status: u16,
+    /// Handlebars template rendered with the error params
+    pub message: String,
+    pub docs_url: Option<String>,
+    /// Per-locale message templates, selected via Accept-Language
+    pub messages: Option<HashMap<String, String>>,
+}
+
+/// Shared key-value state store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateStoreConfig {
+    /// Expose the `/_backworks/state` export/import endpoints (default false)
+    pub admin_api: Option<bool>,
+    /// JSON snapshot imported into the store at startup
+    pub seed: Option<PathBuf>,
+    /// File the store is loaded from at startup and rewritten after every change
+    pub persist: Option<PathBuf>,
+    /// Header isolating the data of stateful mocks per session (default `x-mock-session`)
+    pub mock_session_header: Option<String>,
+}
+
+/// Scenario selection for endpoints with `scenarios`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScenariosConfig {
+    /// Scenario active at startup; endpoints behave normally when unset
+    pub active: Option<String>,
+    /// Header naming the scenario of a single request (default `x-backworks-scenario`)
+    pub header: Option<String>,
+    /// Expose the `/_backworks/scenarios` endpoints (default true)
+    pub admin_api: Option<bool>,
+}
+
+/// Which requests are journaled and where
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalConfig {
+    /// JSON Lines file entries are appended to
+    pub path: PathBuf,
+    /// Methods journaled (default POST, PUT, PATCH and DELETE)
+    #[serde(default)]
+    pub methods: Vec<String>,
+    /// Only journal these endpoints (default: all)
+    #[serde(default)]
+    pub endpoints: Vec<String>,
+    /// Sync each entry to disk before its handler runs (default true)
+    pub fsync: Option<bool>,
+    /// Replay the journal into the handlers at startup
+    #[serde(default)]
+    pub replay_on_start: bool,
+    /// Expose `/_backworks/journal/replay` (default false)
+    pub admin_api: Option<bool>,
+}
+
+/// Who may use the admin API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdminConfig {
+    /// Bearer token every `/_backworks` request must carry, usually
+    /// `${BACKWORKS_ADMIN_TOKEN}`
+    pub token: String,
+}
+
+/// API reference the running server serves, see [`crate::docs`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DocsConfig {
+    /// Path of the HTML page; the OpenAPI document is `openapi.json` below it
+    #[serde(default = "default_docs_path")]
+    pub path: String,
+    /// Capture exports or proxy cassettes the examples are taken from
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub captures: Vec<PathBuf>,
+}
+
+fn default_docs_path() -> String { "/docs".to_string() }
+
+impl DocsConfig {
+    /// Path of the OpenAPI document
+    pub fn openapi_path(&self) -> String {
+        format!("{}/openapi.json", self.path.trim_end_matches('/'))
+    }
+}
+
+/// A job the server runs on a cron expression: a runtime handler, or an
+/// action of a plugin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleConfig {
+    /// Five fields (minute, hour, day of month, month, day of week) or a
+    /// macro such as `@hourly`
+    pub cron: String,
+    /// IANA time zone the expression is read in (default `UTC`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Handler the job runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<RuntimeConfig>,
+    /// Plugin whose `action` the job runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugin: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    /// Handed to the handler or plugin action on every run
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub params: serde_json::Value,
+    /// Longest a run may take, such as `5m`; runs are not bounded when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+    /// Whether a run starts while the previous one is still going
+    #[serde(default)]
+    pub overlap: ScheduleOverlap,
+    /// Runs kept for the admin API (default 20)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<usize>,
+    /// Disabled schedules only run when triggered through the admin API
+    #[serde(default = "default_schedule_enabled")]
+    pub enabled: bool,
+}
+
+fn default_schedule_enabled() -> bool { true }
+
+/// What happens when a schedule is due while its previous run is going
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleOverlap {
+    /// Record the run as skipped
+    #[default]
+    Skip,
+    /// Start the run anyway
+    Allow,
+}
+
+/// Sessions kept in a cookie, or on the server behind one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SessionConfig {
+    /// Key signing and encrypting session cookies, at least 32 characters,
+    /// usually `${SESSION_SECRET}`
+    pub secret: String,
+    /// Name of the session cookie (default `backworks_session`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cookie: Option<String>,
+    /// How long a session lasts after it last changed, such as `24h`
+    /// (default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<String>,
+    #[serde(default)]
+    pub same_site: SameSite,
+    /// Only send the cookie over HTTPS; defaults to whether `server.tls` is
+    /// set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secure: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    /// Path the cookie is sent for (default `/`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub store: SessionStore,
+    /// Whether cookie-stored sessions are encrypted (default) or only signed,
+    /// leaving their data readable by the client
+    #[serde(default = "default_session_encrypted")]
+    pub encrypted: bool,
+    /// Token checks on state-changing requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub csrf: Option<CsrfConfig>,
+}
+
+/// Where state-changing requests carry their session's CSRF token
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CsrfConfig {
+    /// Header carrying the token (default `X-CSRF-Token`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+    /// Form field carrying the token in form posts (default `_csrf`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    /// Cookie scripts can read the token from, such as `XSRF-TOKEN`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cookie: Option<String>,
+}
+
+fn default_session_encrypted() -> bool { true }
+
+/// `SameSite` attribute of the session cookie
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SameSite {
+    Strict,
+    #[default]
+    Lax,
+    /// Sent with cross-site requests too; needs `secure`
+    None,
+}
+
+/// Where session data is kept
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionStore {
+    /// In the cookie itself
+    #[default]
+    Cookie,
+    /// In the state store, the cookie carrying only a signed session id
+    Server,
+}
+
+/// How requests get their locale and time zone
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LocalizationConfig {
+    /// Locale of requests that accept none of the supported ones (default `en-US`)
+    pub default_locale: Option<String>,
+    /// Locales the blueprint serves; any requested locale when empty
+    #[serde(default)]
+    pub supported: Vec<String>,
+    /// IANA time zone of requests without a time zone header (default `UTC`)
+    pub timezone: Option<String>,
+    /// Header carrying the client's IANA time zone (default `x-timezone`)
+    pub timezone_header: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct LoggingConfig {
     #[serde(default = "default_log_level")]
@@ -691,31 +1863,92 @@ pub struct LoggingConfig {
     pub include_body: bool,
     #[serde(default)]
     pub include_headers: bool,
+    /// Capture a backtrace in panic reports
+    #[serde(default)]
+    pub backtraces: bool,
 }
 
 fn default_log_level() -> String {
     "info".to_string()
 }
 
-pub async fn load_config(path: &PathBuf) -> Result<BackworksConfig> {
+pub async fn load_config(path: &Path) -> Result<BackworksConfig> {
     load_yaml_config(path).await
 }
 
 /// Load YAML configuration with support for both old and new formats
-pub async fn load_yaml_config(path: &PathBuf) -> Result<BackworksConfig> {
-    let content = tokio::fs::read_to_string(path).await?;
+pub async fn load_yaml_config(path: &Path) -> Result<BackworksConfig> {
+    // Merge `includes:` before parsing so multi-file blueprints load as one
+    let mut resolved = crate::blueprint::resolve(path)?;
+    let decrypted = crate::secrets::decrypt_blueprint(&mut resolved.value)?;
+    if decrypted > 0 {
+        tracing::debug!("Decrypted {} blueprint value(s)", decrypted);
+    }
+    let parsed = crate::diagnostics::parse_resolved(&resolved)?;
+    for warning in &parsed.warnings {
+        tracing::warn!("{}", warning);
+    }
+    check_missing_env(&parsed.config, &resolved.missing_env)?;
+    validate_config(&parsed.config)?;
+    Ok(parsed.config)
+}
+
+/// Fail on unset environment variables in strict mode (`strict_env: true`
+/// or `BACKWORKS_STRICT_ENV=true`); otherwise warn and leave them empty
+fn check_missing_env(config: &BackworksConfig, missing: &[String]) -> Result<()> {
+    if missing.is_empty() {
+        return Ok(());
+    }
+    
+    let strict = config.strict_env
+        || std::env::var("BACKWORKS_STRICT_ENV").is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+    if strict {
+        return Err(BackworksError::config(format!(
+            "Missing environment variables referenced by the blueprint: {}",
+            missing.join(", ")
+        )));
+    }
     
-    // Try new array-based format first
-    if let Ok(new_config) = serde_yaml::from_str::<NewBlueprintConfig>(&content) {
-        let config = new_config.to_backworks_config();
-        validate_config(&config)?;
-        Ok(config)
-    } else {
-        // Fallback to legacy HashMap format
-        let config: BackworksConfig = serde_yaml::from_str(&content)?;
-        validate_config(&config)?;
-        Ok(config)
+    tracing::warn!("Environment variables not set, substituted with empty values: {}", missing.join(", "));
+    Ok(())
+}
+
+/// Parse a merged blueprint document in either the list or legacy format,
+/// chosen by the shape of `endpoints`
+pub fn parse_blueprint(value: serde_yaml::Value) -> Result<BackworksConfig> {
+    Ok(crate::diagnostics::parse(value, &[])?.config)
+}
+
+fn validate_static(name: &str, endpoint: &EndpointConfig) -> Result<()> {
+    let Some(ref files) = endpoint.static_files else {
+        return Err(BackworksError::config(format!("Endpoint '{}' runs in static mode but has no `static.dir`", name)));
+    };
+    if files.dir.as_os_str().is_empty() {
+        return Err(BackworksError::config(format!("Endpoint '{}' static dir cannot be empty", name)));
+    }
+    if let Some(method) = endpoint.methods.iter().find(|m| !matches!(m.to_uppercase().as_str(), "GET" | "HEAD")) {
+        return Err(BackworksError::config(format!("Endpoint '{}' is static and cannot serve {}", name, method)));
     }
+    if endpoint.path.contains(":*}") {
+        return Err(BackworksError::config(format!("Endpoint '{}' is static; its path already matches everything below it", name)));
+    }
+    if files.spa_fallback && files.index.is_empty() {
+        return Err(BackworksError::config(format!("Endpoint '{}' spa_fallback needs an index file", name)));
+    }
+    Ok(())
+}
+
+fn validate_mode_chain(name: &str, endpoint: &EndpointConfig) -> Result<()> {
+    if endpoint.mode.is_some() {
+        return Err(BackworksError::config(format!("Endpoint '{}' sets both `mode` and `modes`; keep one", name)));
+    }
+    if endpoint.modes.len() > 1 && endpoint.modes.contains(&ExecutionMode::Static) {
+        return Err(BackworksError::config(format!("Endpoint '{}' is static and cannot fall back to other modes", name)));
+    }
+    if let Some((index, mode)) = endpoint.modes.iter().enumerate().find(|(index, mode)| endpoint.modes[..*index].contains(mode)) {
+        return Err(BackworksError::config(format!("Endpoint '{}' lists {:?} mode twice in `modes` (position {})", name, mode, index + 1)));
+    }
+    Ok(())
 }
 
 pub fn validate_config(config: &BackworksConfig) -> Result<()> {
@@ -728,16 +1961,62 @@ pub fn validate_config(config: &BackworksConfig) -> Result<()> {
         return Err(BackworksError::config("At least one endpoint must be defined"));
     }
     
+    if config.server.ipv6_only && config.server.host.parse::<std::net::Ipv4Addr>().is_ok() {
+        return Err(BackworksError::config(format!("server.ipv6_only needs an IPv6 host, not '{}'", config.server.host)));
+    }
+    
+    if let Some(ref timeout) = config.server.request_timeout {
+        parse_duration(timeout).map_err(|e| BackworksError::config(format!("server.request_timeout: {}", e)))?;
+    }
+    
+    let trusts_client_certs = config.security.as_ref()
+        .and_then(|security| security.authentication.as_ref())
+        .and_then(|authentication| authentication.trusted_headers.as_ref())
+        .is_some_and(|trusted| !trusted.trusted_client_certs.is_empty());
+    if trusts_client_certs && config.server.tls.as_ref().is_none_or(|tls| tls.client_ca.is_none()) {
+        return Err(BackworksError::config("trusted_client_certs needs server.tls.client_ca to verify client certificates against"));
+    }
+    
     // Validate endpoints
     for (name, endpoint) in &config.endpoints {
         if endpoint.path.is_empty() {
             return Err(BackworksError::config(format!("Endpoint '{}' path cannot be empty", name)));
         }
-        
+
+        if let Err(e) = crate::routes::RoutePattern::try_parse(&endpoint.path) {
+            return Err(BackworksError::config(format!("Endpoint '{}' path '{}': {}", name, endpoint.path, e)));
+        }
+
         if endpoint.methods.is_empty() {
             return Err(BackworksError::config(format!("Endpoint '{}' must have at least one HTTP method", name)));
         }
         
+        if let Some(ref timeout) = endpoint.timeout {
+            parse_duration(timeout).map_err(|e| BackworksError::config(format!("Endpoint '{}' timeout: {}", name, e)))?;
+        }
+        
+        if let Some(ref rollout) = endpoint.rollout {
+            crate::rollout::validate_rollout(name, rollout)?;
+        }
+        
+        if !endpoint.modes.is_empty() {
+            validate_mode_chain(name, endpoint)?;
+        }
+        
+        if matches!(endpoint.primary_mode(&config.mode), ExecutionMode::Static) {
+            validate_static(name, endpoint)?;
+        }
+        
+        if let Some(ref monitoring) = endpoint.monitoring {
+            validate_dimensions(name, monitoring)?;
+        }
+        
+        if let Some(ref compare) = endpoint.compare {
+            if compare.baseline.response.is_none() && compare.baseline.mode.is_none() {
+                return Err(BackworksError::config(format!("Endpoint '{}' compare requires a baseline response or mode", name)));
+            }
+        }
+        
         // Validate HTTP methods
         for method in &endpoint.methods {
             match method.as_str() {
@@ -747,91 +2026,188 @@ pub fn validate_config(config: &BackworksConfig) -> Result<()> {
         }
     }
     
-    // Validate plugin configurations
+    // Compile endpoint templates so syntax errors surface before startup
+    crate::templates::EndpointTemplates::new(&config.endpoints)?;
+    crate::body_transform::EndpointTransforms::new(&config.endpoints)?;
+    crate::coercion::EndpointCoercions::new(&config.endpoints)?;
+    crate::queries::EndpointQueries::new(&config.endpoints)?;
+    crate::mock::MockEngine::new(&config.endpoints)?;
+    crate::locale::Localization::new(config.localization.as_ref())?;
+    crate::scenario::Scenarios::new(config)?;
+    crate::chaos::Chaos::new(config)?;
+    crate::retention::RetentionPolicy::from_config(config)?;
+    crate::schedule::Scheduler::new(config)?;
+    if let Some(ref sessions) = config.sessions {
+        crate::session::Sessions::new(sessions, config, crate::state::StateStore::new())?;
+    }
+    crate::concurrency::EndpointLimits::new(&config.endpoints)?;
+    crate::security::Security::new(config)?;
+    for (name, endpoint) in &config.endpoints {
+        if let Some(ref webhook) = endpoint.webhook {
+            crate::webhook::WebhookVerifier::new(name, webhook)?;
+        }
+    }
+    crate::request_body::BodyLimits::from_config(config)?;
+    crate::compression::CompressionPolicy::new(config)?;
+    crate::crud::validate(config)?;
+    crate::pool::validate(config)?;
+    let tls = crate::tls::Tls::from_config(config)?;
+    if tls.is_none() && config.dashboard.as_ref().is_some_and(|d| d.enabled && d.https.unwrap_or(false)) {
+        return Err(BackworksError::config("dashboard.https needs server.tls"));
+    }
+    
+    if config.admin.as_ref().is_some_and(|admin| admin.token.trim().is_empty()) {
+        return Err(BackworksError::config("admin.token cannot be empty"));
+    }
+    if config.admin.is_none() && config.dashboard.as_ref().is_some_and(|d| d.enabled && d.editor.unwrap_or(false)) {
+        return Err(BackworksError::config("dashboard.editor needs admin.token, which saving the blueprint requires"));
+    }
+    
+    if let Some(ref journal) = config.journal {
+        if let Some(endpoint) = journal.endpoints.iter().find(|name| !config.endpoints.contains_key(*name)) {
+            return Err(BackworksError::config(format!("Journal lists unknown endpoint '{}'", endpoint)));
+        }
+    }
+    
+    if let Some(cors) = config.security.as_ref().and_then(|s| s.cors.as_ref()).filter(|c| c.enabled.unwrap_or(false)) {
+        crate::cors::CorsPolicies::new(cors)?;
+    }
+    
+    // Check plugin config against the schemas of plugins loaded so far
     for (plugin_name, plugin_config) in &config.plugins {
         if plugin_config.enabled {
-            // Basic validation - each plugin can have its own validation logic
-            match plugin_name.as_str() {
-                "ai" => {
-                    // AI plugin specific validation could go here
-                    // For now, just ensure the config is valid JSON
-                },
-                _ => {
-                    // Unknown plugins are allowed for extensibility
-                }
+            crate::plugin::schema::validate_plugin_config(plugin_name, &plugin_config.config)?;
+        }
+    }
+    
+    for check in config.monitoring.as_ref().and_then(|m| m.health.as_ref()).and_then(|h| h.checks.as_ref()).into_iter().flatten() {
+        match check.check_type.as_str() {
+            "url" if check.url.is_none() => {
+                return Err(BackworksError::config(format!("Health check '{}' requires a url", check.name)));
             }
+            "url" | "database" => {},
+            other => return Err(BackworksError::config(format!("Unknown health check type '{}' in check '{}'", other, check.name))),
         }
     }
     
+    if let Some(ref errors) = config.errors {
+        crate::error_catalog::ErrorCatalog::new(errors)?;
+    }
+    
+    if let Some(alerts) = config.monitoring.as_ref().and_then(|m| m.alerts.as_ref()) {
+        crate::alerting::validate_alerts(alerts)?;
+    }
+    
+    let mut conflicts = Vec::new();
+    for conflict in route_conflicts(config) {
+        if conflict.is_fatal() || config.route_conflicts == RouteConflictMode::Error {
+            conflicts.push(conflict.to_string());
+        } else {
+            tracing::warn!("{}", conflict);
+        }
+    }
+    if !conflicts.is_empty() {
+        return Err(BackworksError::config(format!("Conflicting routes:\n  {}", conflicts.join("\n  "))));
+    }
+    
+    Ok(())
+}
+
+/// Custom labels must be valid Prometheus label names and must not clash
+/// with the built-in ones
+fn validate_dimensions(endpoint: &str, monitoring: &EndpointMonitoringConfig) -> Result<()> {
+    for label in monitoring.labels.keys() {
+        let valid = label.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !label.starts_with("__");
+        if !valid {
+            return Err(BackworksError::config(format!("Endpoint '{}' has invalid metric label '{}'", endpoint, label)));
+        }
+        if RESERVED_METRIC_LABELS.contains(&label.as_str()) || ["team", "domain", "criticality"].contains(&label.as_str()) {
+            return Err(BackworksError::config(format!("Endpoint '{}' metric label '{}' is reserved", endpoint, label)));
+        }
+    }
     Ok(())
 }
 
+/// Endpoint pairs whose routes collide or shadow each other, ordered by
+/// endpoint name
+pub fn route_conflicts(config: &BackworksConfig) -> Vec<crate::routes::RouteConflict> {
+    let mut routes: Vec<(&str, &str, &[String])> = config.endpoints.iter()
+        .map(|(name, endpoint)| (name.as_str(), endpoint.path.as_str(), endpoint.methods.as_slice()))
+        .collect();
+    routes.sort_by_key(|(name, _, _)| *name);
+    crate::routes::find_conflicts(&routes)
+}
+
+/// Parse a human-friendly duration such as "500ms", "30s", "5m" or "1h".
+/// A bare number is interpreted as seconds.
+pub fn parse_duration(value: &str) -> Result<std::time::Duration> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse()
+        .map_err(|_| BackworksError::config(format!("Invalid duration '{}'", value)))?;
+    
+    let seconds = match unit.trim() {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        "d" => number * 86400.0,
+        other => return Err(BackworksError::config(format!("Invalid duration unit '{}' in '{}'", other, value))),
+    };
+    
+    Ok(std::time::Duration::from_secs_f64(seconds))
+}
+
+
+/// Parse a size such as "512KB", "10MB" or "1GB", in multiples of 1024.
+/// A bare number is interpreted as bytes.
+pub fn parse_size(value: &str) -> Result<usize> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse()
+        .map_err(|_| BackworksError::config(format!("Invalid size '{}'", value)))?;
+    
+    let multiplier = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1.0,
+        "KB" | "K" => 1024.0,
+        "MB" | "M" => 1024.0 * 1024.0,
+        "GB" | "G" => 1024.0 * 1024.0 * 1024.0,
+        other => return Err(BackworksError::config(format!("Invalid size unit '{}' in '{}'", other, value))),
+    };
+    
+    Ok((number * multiplier) as usize)
+}
 
+/// Locate the project's blueprint in the current directory, in the same
+/// order `load_project_config` searches
+pub fn find_project_config() -> Option<PathBuf> {
+    let current_dir = std::env::current_dir().ok()?;
+    ["backworks.yaml", "main.yaml", "blueprints/main.yaml", "blueprint.yaml"]
+        .iter()
+        .map(|name| current_dir.join(name))
+        .find(|path| path.exists())
+}
 
 /// Detect project structure and load appropriate configuration - YAML-only approach
 pub fn load_project_config(path: Option<PathBuf>) -> Result<BackworksConfig> {
-    let current_dir = std::env::current_dir()
-        .map_err(|e| BackworksError::config(format!("Cannot get current directory: {}", e)))?;
-    
-    if let Some(config_path) = path {
-        // Explicit config file provided - try to load as YAML
-        let config = tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                load_yaml_config(&config_path).await
-            })
-        })?;
-        Ok(config)
-    } else {
-        // Auto-detect project structure - look for YAML files only
-        
-        // Try backworks.yaml first (new preferred format)
-        let backworks_file = current_dir.join("backworks.yaml");
-        if backworks_file.exists() {
-            let config = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    load_yaml_config(&backworks_file).await
-                })
-            })?;
-            Ok(config)
-        } else {
-            // Try main.yaml in current directory
-            let main_file = current_dir.join("main.yaml");
-            if main_file.exists() {
-                let config = tokio::task::block_in_place(|| {
-                    tokio::runtime::Handle::current().block_on(async {
-                        load_yaml_config(&main_file).await
-                    })
-                })?;
-                Ok(config)
-            } else {
-                // Try blueprints/main.yaml (project structure)
-                let blueprint_file = current_dir.join("blueprints/main.yaml");
-                if blueprint_file.exists() {
-                    let config = tokio::task::block_in_place(|| {
-                        tokio::runtime::Handle::current().block_on(async {
-                            load_yaml_config(&blueprint_file).await
-                        })
-                    })?;
-                    Ok(config)
-                } else {
-                    // Legacy blueprint.yaml
-                    let legacy_file = current_dir.join("blueprint.yaml");
-                    if legacy_file.exists() {
-                        let config = tokio::task::block_in_place(|| {
-                            tokio::runtime::Handle::current().block_on(async {
-                                load_yaml_config(&legacy_file).await
-                            })
-                        })?;
-                        Ok(config)
-                    } else {
-                        return Err(BackworksError::config(
-                            "No configuration found. Expected 'backworks.yaml', 'main.yaml', 'blueprints/main.yaml' or 'blueprint.yaml'".to_string()
-                        ));
-                    }
-                }
-            }
-        }
-    }
+    let config_path = project_config_path(path)?;
+    tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(async {
+            load_yaml_config(&config_path).await
+        })
+    })
+}
+
+/// The explicit blueprint path, or the project's blueprint in the current
+/// directory
+pub fn project_config_path(path: Option<PathBuf>) -> Result<PathBuf> {
+    path.or_else(find_project_config).ok_or_else(|| BackworksError::config(
+        "No configuration found. Expected 'backworks.yaml', 'main.yaml', 'blueprints/main.yaml' or 'blueprint.yaml'".to_string()
+    ))
 }
 
 /// New blueprint format with array-based endpoints
@@ -856,6 +2232,31 @@ pub struct NewBlueprintConfig {
     
     #[serde(default)]
     pub logging: LoggingConfig,
+    
+    #[serde(default)]
+    pub monitoring: Option<MonitoringConfig>,
+    
+    #[serde(default)]
+    pub state: Option<StateStoreConfig>,
+    
+    #[serde(default)]
+    pub errors: Option<HashMap<String, ErrorCatalogEntry>>,
+    pub localization: Option<LocalizationConfig>,
+    pub scenarios: Option<ScenariosConfig>,
+    pub journal: Option<JournalConfig>,
+    pub capture: Option<CaptureConfig>,
+    pub admin: Option<AdminConfig>,
+    
+    #[serde(default)]
+    pub schedules: HashMap<String, ScheduleConfig>,
+    pub sessions: Option<SessionConfig>,
+    pub docs: Option<DocsConfig>,
+    
+    #[serde(default)]
+    pub strict_env: bool,
+    
+    #[serde(default)]
+    pub route_conflicts: RouteConflictMode,
 }
 
 /// New endpoint configuration for array-based format
@@ -877,7 +2278,7 @@ pub struct NewEndpointConfig {
     
     // Middleware
     #[serde(default)]
-    pub middleware: Vec<String>,
+    pub middleware: Vec<MiddlewareSpec>,
 }
 
 /// Method specification - supports both single method and array
@@ -904,11 +2305,11 @@ impl NewBlueprintConfig {
         
         // Convert array-based endpoints to map-based endpoints
         for (index, endpoint) in self.endpoints.into_iter().enumerate() {
-            let endpoint_name = if let Some(last_segment) = endpoint.path.split('/').last() {
+            let endpoint_name = if let Some(last_segment) = endpoint.path.split('/').next_back() {
                 if last_segment.is_empty() || last_segment.starts_with('{') {
                     format!("endpoint_{}", index)
                 } else {
-                    last_segment.replace('{', "").replace('}', "")
+                    last_segment.replace(['{', '}'], "")
                 }
             } else {
                 format!("endpoint_{}", index)
@@ -934,6 +2335,9 @@ impl NewBlueprintConfig {
                 methods: endpoint.method.to_vec(),
                 description: endpoint.description,
                 mode: Some(ExecutionMode::Runtime),
+                modes: Vec::new(),
+                mock: None,
+                proxy: None,
                 runtime,
                 database: None,
                 capture: None,
@@ -944,6 +2348,22 @@ impl NewBlueprintConfig {
                 parameters: None,
                 validation: None,
                 monitoring: None,
+                compare: None,
+                rollout: None,
+                template: None,
+                transform: None,
+                deprecated: None,
+                coerce: None,
+                static_files: None,
+                middleware: endpoint.middleware,
+                scenarios: HashMap::new(),
+                chaos: None,
+                retention: None,
+                timeout: None,
+                compression: None,
+                concurrency: None,
+                webhook: None,
+                csrf: None,
             };
             
             endpoints.insert(endpoint_name, legacy_endpoint);
@@ -973,7 +2393,19 @@ impl NewBlueprintConfig {
             apis: None,
             cache: None,
             security: None,
-            monitoring: None,
+            monitoring: self.monitoring,
+            state: self.state,
+            errors: self.errors,
+            localization: self.localization,
+            scenarios: self.scenarios,
+            journal: self.journal,
+            capture: self.capture,
+            admin: self.admin,
+            schedules: self.schedules,
+            sessions: self.sessions,
+            docs: self.docs,
+            strict_env: self.strict_env,
+            route_conflicts: self.route_conflicts,
             global_headers: HashMap::new(),
             logging: self.logging,
         }
@@ -981,20 +2413,6 @@ impl NewBlueprintConfig {
 }
 
 /// Load configuration supporting both new and legacy blueprint formats
-pub async fn load_blueprint_config(path: &PathBuf) -> Result<BackworksConfig> {
-    let content = tokio::fs::read_to_string(path).await
-        .map_err(|e| BackworksError::config(format!("Failed to read blueprint file: {}", e)))?;
-    
-    // Try new array-based format first
-    if let Ok(new_config) = serde_yaml::from_str::<NewBlueprintConfig>(&content) {
-        let config = new_config.to_backworks_config();
-        validate_config(&config)?;
-        Ok(config)
-    } else {
-        // Fallback to legacy format
-        let config: BackworksConfig = serde_yaml::from_str(&content)
-            .map_err(|e| BackworksError::config(format!("Failed to parse blueprint: {}", e)))?;
-        validate_config(&config)?;
-        Ok(config)
-    }
+pub async fn load_blueprint_config(path: &Path) -> Result<BackworksConfig> {
+    load_yaml_config(path).await
 }
@@ -0,0 +1,513 @@
+//! Interactive console for a running server
+//!
+//! `backworks console` connects to a server's [admin API](crate::admin) and
+//! reads commands from a prompt: list endpoints and switch them off and on,
+//! call them with crafted requests, page through and follow the request log,
+//! check and reload plugins, and switch scenarios and fault injection.
+//! `help` lists the commands.
+//!
+//! Calls go to the server's own port, so they pass through the same
+//! middleware, scenarios and faults clients meet. The request log is the
+//! dashboard's, so `logs` needs the dashboard enabled.
+
+use crate::admin::{EndpointStatus, ReloadSummary};
+use crate::chaos::ChaosStatus;
+use crate::error::{BackworksError, Result};
+use crate::plugin::{HealthStatus, PluginHealth};
+use crate::request_log::{RequestLogEntry, RequestLogPage};
+use crate::scenario::ScenarioStatus;
+use serde::de::DeserializeOwned;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write};
+use std::time::Duration;
+
+/// How often `logs -f` asks for new requests
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+/// Requests `logs` shows when no `limit` is given
+const DEFAULT_LOG_LIMIT: usize = 20;
+/// Filters the request log accepts
+const LOG_FILTERS: &[&str] = &["method", "path", "status", "endpoint", "limit"];
+
+pub const HELP: &str = "\
+Commands:
+  endpoints                        list endpoints, their mode and whether they are enabled
+  enable <endpoint>                serve an endpoint again
+  disable <endpoint>               answer an endpoint with 503
+  call [METHOD] <path> [-H name:value]... [body | @file]
+                                   send a request, GET by default
+  logs [-f] [key=value]...         show recent requests, or follow them with -f;
+                                   filters: method, path, status, endpoint, limit
+  plugins                          check every plugin's health
+  reload [plugin]                  load the blueprint, or a plugin's library, again
+  scenarios                        list scenarios and the active one
+  scenario <name> | scenario off   switch the active scenario
+  chaos [on|off]                   show or switch fault injection
+  flush [endpoint]                 drop cached responses
+  help                             show this list
+  exit                             leave the console";
+
+/// A console command
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Help,
+    Exit,
+    Endpoints,
+    Switch { endpoint: String, enabled: bool },
+    Call { method: String, path: String, headers: Vec<(String, String)>, body: Option<String> },
+    Logs { filters: Vec<(String, String)>, follow: bool },
+    Plugins,
+    Reload { plugin: Option<String> },
+    Scenarios,
+    Scenario { name: Option<String> },
+    Chaos { enabled: Option<bool> },
+    Flush { endpoint: Option<String> },
+}
+
+impl Command {
+    /// Parse a prompt line; blank lines and `#` comments are no command
+    pub fn parse(line: &str) -> Result<Option<Self>> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+        let (name, rest) = next_word(line);
+        let words: Vec<&str> = rest.split_whitespace().collect();
+        let command = match (name.to_ascii_lowercase().as_str(), words.as_slice()) {
+            ("help" | "?", _) => Self::Help,
+            ("exit" | "quit", []) => Self::Exit,
+            ("endpoints" | "routes", []) => Self::Endpoints,
+            ("enable", [endpoint]) => Self::Switch { endpoint: endpoint.to_string(), enabled: true },
+            ("disable", [endpoint]) => Self::Switch { endpoint: endpoint.to_string(), enabled: false },
+            ("call", [_, ..]) => parse_call(rest)?,
+            ("logs" | "tail", _) => {
+                let mut filters = Vec::new();
+                let mut follow = name.eq_ignore_ascii_case("tail");
+                for word in words {
+                    if word == "-f" || word == "--follow" {
+                        follow = true;
+                        continue;
+                    }
+                    match word.split_once('=') {
+                        Some((key, value)) if LOG_FILTERS.contains(&key) => filters.push((key.to_string(), value.to_string())),
+                        _ => return Err(BackworksError::config(format!(
+                            "Invalid log filter '{}' (expected key=value with key one of {})", word, LOG_FILTERS.join(", ")
+                        ))),
+                    }
+                }
+                Self::Logs { filters, follow }
+            }
+            ("plugins", []) => Self::Plugins,
+            ("reload", []) => Self::Reload { plugin: None },
+            ("reload", [plugin]) => Self::Reload { plugin: Some(plugin.to_string()) },
+            ("scenarios", []) => Self::Scenarios,
+            ("scenario", ["off" | "none"]) => Self::Scenario { name: None },
+            ("scenario", [name]) => Self::Scenario { name: Some(name.to_string()) },
+            ("chaos", []) => Self::Chaos { enabled: None },
+            ("chaos", ["on"]) => Self::Chaos { enabled: Some(true) },
+            ("chaos", ["off"]) => Self::Chaos { enabled: Some(false) },
+            ("flush", []) => Self::Flush { endpoint: None },
+            ("flush", [endpoint]) => Self::Flush { endpoint: Some(endpoint.to_string()) },
+            ("exit" | "quit" | "endpoints" | "routes" | "enable" | "disable" | "call" | "plugins" | "reload" | "scenarios"
+                | "scenario" | "chaos" | "flush", _) => {
+                return Err(BackworksError::config(format!("Invalid arguments to '{}', see help", name)));
+            }
+            _ => return Err(BackworksError::config(format!("Unknown command '{}', see help", name))),
+        };
+        Ok(Some(command))
+    }
+}
+
+/// `[METHOD] <path> [-H name:value]... [body]`, the body being the rest of
+/// the line
+fn parse_call(rest: &str) -> Result<Command> {
+    let (word, mut rest) = next_word(rest);
+    let (method, path) = if word.starts_with('/') {
+        ("GET".to_string(), word.to_string())
+    } else {
+        let (path, after) = next_word(rest);
+        rest = after;
+        (word.to_ascii_uppercase(), path.to_string())
+    };
+    if !path.starts_with('/') {
+        return Err(BackworksError::config(format!("Invalid path '{}' (expected it to start with /)", path)));
+    }
+    reqwest::Method::from_bytes(method.as_bytes())
+        .map_err(|_| BackworksError::config(format!("Invalid method '{}'", method)))?;
+
+    let mut headers = Vec::new();
+    while let Some(after) = rest.strip_prefix("-H").filter(|after| after.starts_with(char::is_whitespace)) {
+        let (header, after) = next_word(after);
+        let Some((name, value)) = header.split_once(':').filter(|(name, _)| !name.is_empty()) else {
+            return Err(BackworksError::config(format!("Invalid header '{}' (expected name:value)", header)));
+        };
+        headers.push((name.to_string(), value.to_string()));
+        rest = after;
+    }
+    let body = Some(rest.to_string()).filter(|body| !body.is_empty());
+    Ok(Command::Call { method, path, headers, body })
+}
+
+/// The first word of `line` and the trimmed rest
+fn next_word(line: &str) -> (&str, &str) {
+    let line = line.trim_start();
+    match line.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (line, ""),
+    }
+}
+
+/// A connection to a running server
+pub struct Console {
+    client: reqwest::Client,
+    base: String,
+}
+
+impl Console {
+    /// Talk to the server at `base` through `client`, which carries the
+    /// admin token when one is needed
+    pub fn new(client: reqwest::Client, base: &str) -> Self {
+        Self { client, base: base.trim_end_matches('/').to_string() }
+    }
+
+    /// Run `command` and return what to print. `logs -f` shows recent
+    /// requests once; [`follow`](Self::follow) keeps following them.
+    pub async fn execute(&self, command: &Command) -> Result<String> {
+        let out = match command {
+            Command::Help => HELP.to_string(),
+            Command::Exit => String::new(),
+            Command::Endpoints => {
+                let endpoints: Vec<EndpointStatus> = self.admin(reqwest::Method::GET, "endpoints", None).await?;
+                render(|out| write_endpoints(out, &endpoints))
+            }
+            Command::Switch { endpoint, enabled } => {
+                let body = serde_json::json!({"enabled": enabled});
+                let status: EndpointStatus = self.admin(reqwest::Method::PUT, &format!("endpoints/{}", endpoint), Some(body)).await?;
+                format!("{} {} {}", if status.enabled { "✅" } else { "⛔" }, status.name, if status.enabled { "enabled" } else { "disabled" })
+            }
+            Command::Call { method, path, headers, body } => self.call(method, path, headers, body.as_deref()).await?,
+            Command::Logs { filters, .. } => {
+                let entries = self.requests(filters, None).await?;
+                match entries.is_empty() {
+                    true => "No requests logged".to_string(),
+                    false => entries.iter().map(log_line).collect::<Vec<_>>().join("\n"),
+                }
+            }
+            Command::Plugins => {
+                let plugins: HashMap<String, PluginHealth> = self.admin(reqwest::Method::GET, "plugins", None).await?;
+                match plugins.is_empty() {
+                    true => "No plugins registered".to_string(),
+                    false => render(|out| write_plugins(out, &plugins.into_iter().collect())),
+                }
+            }
+            Command::Reload { plugin: Some(plugin) } => {
+                let _: serde_json::Value = self.admin(reqwest::Method::POST, &format!("plugins/{}/reload", plugin), None).await?;
+                format!("🔄 Reloaded plugin {}", plugin)
+            }
+            Command::Reload { plugin: None } => {
+                let summary: ReloadSummary = self.admin(reqwest::Method::POST, "reload", None).await?;
+                format!("🔄 Reloaded {} endpoint(s): {} added, {} removed", summary.endpoints, summary.added.len(), summary.removed.len())
+            }
+            Command::Scenarios => {
+                let status: ScenarioStatus = self.admin(reqwest::Method::GET, "scenarios", None).await?;
+                render(|out| write_scenarios(out, &status))
+            }
+            Command::Scenario { name } => {
+                let status: ScenarioStatus = match name {
+                    Some(name) => {
+                        let body = serde_json::json!({"scenario": name});
+                        self.admin(reqwest::Method::PUT, "scenarios/active", Some(body)).await?
+                    }
+                    None => self.admin(reqwest::Method::DELETE, "scenarios/active", None).await?,
+                };
+                format!("🎬 Active scenario: {}", status.active.as_deref().unwrap_or("none"))
+            }
+            Command::Chaos { enabled } => {
+                let status: ChaosStatus = match enabled {
+                    Some(enabled) => self.admin(reqwest::Method::PUT, "chaos", Some(serde_json::json!({"enabled": enabled}))).await?,
+                    None => self.admin(reqwest::Method::GET, "chaos", None).await?,
+                };
+                render(|out| write_chaos(out, &status))
+            }
+            Command::Flush { endpoint } => {
+                let request = self.client.post(format!("{}/_backworks/caches/flush", self.base))
+                    .query(&[("endpoint", endpoint)]);
+                let flushed: serde_json::Value = answer(request.send().await?, "caches/flush").await?;
+                format!("🧹 Flushed {} cached entries", flushed["flushed"])
+            }
+        };
+        Ok(out.trim_end().to_string())
+    }
+
+    /// Print requests passing `filters` with `emit` as they are answered,
+    /// starting with the most recent ones; only returns on errors
+    pub async fn follow(&self, filters: &[(String, String)], mut emit: impl FnMut(String)) -> Result<()> {
+        let mut after = None;
+        loop {
+            let entries = self.requests(filters, after).await?;
+            if let Some(last) = entries.last() {
+                after = Some(last.id);
+            }
+            for entry in &entries {
+                emit(log_line(entry));
+            }
+            tokio::time::sleep(FOLLOW_INTERVAL).await;
+        }
+    }
+
+    /// Requests passing `filters`, oldest first; the latest ones, or every
+    /// one newer than `after`
+    async fn requests(&self, filters: &[(String, String)], after: Option<u64>) -> Result<Vec<RequestLogEntry>> {
+        let mut query: Vec<(String, String)> = filters.iter().filter(|(key, _)| key != "limit").cloned().collect();
+        match after {
+            Some(after) => query.push(("after".to_string(), after.to_string())),
+            None => {
+                let limit = filters.iter().find(|(key, _)| key == "limit").map(|(_, limit)| limit.clone());
+                query.push(("limit".to_string(), limit.unwrap_or_else(|| DEFAULT_LOG_LIMIT.to_string())));
+            }
+        }
+        let request = self.client.get(format!("{}/_backworks/requests", self.base)).query(&query);
+        let response = request.send().await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(BackworksError::config("The request log is kept by the dashboard, which this server runs without"));
+        }
+        let mut page: RequestLogPage = answer(response, "requests").await?;
+        page.entries.reverse();
+        Ok(page.entries)
+    }
+
+    async fn admin<T: DeserializeOwned>(&self, method: reqwest::Method, path: &str, body: Option<serde_json::Value>) -> Result<T> {
+        let mut request = self.client.request(method, format!("{}/_backworks/{}", self.base, path));
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        answer(request.send().await?, path).await
+    }
+
+    async fn call(&self, method: &str, path: &str, headers: &[(String, String)], body: Option<&str>) -> Result<String> {
+        let method = reqwest::Method::from_bytes(method.as_bytes())
+            .map_err(|_| BackworksError::config(format!("Invalid method '{}'", method)))?;
+        let mut request = self.client.request(method, format!("{}{}", self.base, path));
+        let mut typed = false;
+        for (name, value) in headers {
+            typed |= name.eq_ignore_ascii_case("content-type");
+            request = request.header(name.as_str(), value.as_str());
+        }
+        if let Some(body) = body {
+            let body = match body.strip_prefix('@') {
+                Some(file) => std::fs::read_to_string(file)
+                    .map_err(|e| BackworksError::config(format!("Failed to read body file {}: {}", file, e)))?,
+                None => body.to_string(),
+            };
+            if !typed && serde_json::from_str::<serde_json::Value>(&body).is_ok() {
+                request = request.header(reqwest::header::CONTENT_TYPE, "application/json");
+            }
+            request = request.body(body);
+        }
+
+        let started = std::time::Instant::now();
+        let response = request.send().await?;
+        let elapsed = started.elapsed();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let text = response.text().await?;
+        let body = match serde_json::from_str::<serde_json::Value>(&text) {
+            Ok(json) => serde_json::to_string_pretty(&json)?,
+            Err(_) => text,
+        };
+        Ok(render(|out| {
+            writeln!(out, "{} {} in {:.1} ms", status.as_u16(), status.canonical_reason().unwrap_or(""), elapsed.as_secs_f64() * 1000.0)?;
+            for (name, value) in &headers {
+                writeln!(out, "{}: {}", name, value.to_str().unwrap_or("<binary>"))?;
+            }
+            if !body.is_empty() {
+                write!(out, "\n{}", body)?;
+            }
+            Ok(())
+        }))
+    }
+}
+
+/// Decode a successful admin answer, or fail with the error it reports
+async fn answer<T: DeserializeOwned>(response: reqwest::Response, path: &str) -> Result<T> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response.json().await?);
+    }
+    let body: serde_json::Value = response.json().await.unwrap_or_default();
+    // Routes for features the blueprint leaves out are not registered, so
+    // the server answers them like any unknown path
+    let route = format!("/_backworks/{}", path);
+    let message = match body["error"].as_str() {
+        Some(error) if status != reqwest::StatusCode::NOT_FOUND || !error.contains(&route) => error.to_string(),
+        _ if status == reqwest::StatusCode::NOT_FOUND => format!("{} is not available on this server", route),
+        _ => "admin request failed".to_string(),
+    };
+    Err(BackworksError::config(format!("{} ({})", message, status)))
+}
+
+fn render(write: impl FnOnce(&mut String) -> fmt::Result) -> String {
+    let mut out = String::new();
+    write(&mut out).expect("writing to a String cannot fail");
+    out
+}
+
+fn write_endpoints(out: &mut String, endpoints: &[EndpointStatus]) -> fmt::Result {
+    let name_width = endpoints.iter().map(|endpoint| endpoint.name.len()).max().unwrap_or(0);
+    let methods: Vec<String> = endpoints.iter().map(|endpoint| match endpoint.methods.is_empty() {
+        true => "ANY".to_string(),
+        false => endpoint.methods.join(","),
+    }).collect();
+    let methods_width = methods.iter().map(String::len).max().unwrap_or(0);
+    for (endpoint, methods) in endpoints.iter().zip(&methods) {
+        let mode = serde_json::to_value(&endpoint.mode).ok().and_then(|mode| mode.as_str().map(str::to_string)).unwrap_or_default();
+        writeln!(
+            out, "{} {:name_width$}  {:methods_width$}  {}  ({})",
+            if endpoint.enabled { "✅" } else { "⛔" }, endpoint.name, methods, endpoint.path, mode,
+        )?;
+    }
+    Ok(())
+}
+
+fn write_plugins(out: &mut String, plugins: &BTreeMap<String, PluginHealth>) -> fmt::Result {
+    for (name, health) in plugins {
+        let (icon, status) = match health.status {
+            HealthStatus::Healthy => ("✅", "healthy"),
+            HealthStatus::Degraded => ("⚠️ ", "degraded"),
+            HealthStatus::Unhealthy => ("❌", "unhealthy"),
+        };
+        writeln!(out, "{} {} {}: {}", icon, name, status, health.message)?;
+    }
+    Ok(())
+}
+
+fn write_scenarios(out: &mut String, status: &ScenarioStatus) -> fmt::Result {
+    let width = status.scenarios.keys().map(String::len).max().unwrap_or(0);
+    for (name, endpoints) in &status.scenarios {
+        let marker = if status.active.as_ref() == Some(name) { "▶" } else { " " };
+        writeln!(out, "{} {:width$}  {}", marker, name, endpoints.join(", "))?;
+    }
+    write!(out, "Active: {}", status.active.as_deref().unwrap_or("none"))
+}
+
+fn write_chaos(out: &mut String, status: &ChaosStatus) -> fmt::Result {
+    write!(out, "💥 Fault injection {}", if status.enabled { "on" } else { "off" })?;
+    for (endpoint, config) in &status.endpoints {
+        write!(out, "\n   {}: {}", endpoint, serde_json::to_string(config).map_err(|_| fmt::Error)?)?;
+    }
+    Ok(())
+}
+
+/// One line of the request log
+pub fn log_line(entry: &RequestLogEntry) -> String {
+    let mut line = format!(
+        "{}  {:7} {}  {:>8.1} ms  {}",
+        entry.timestamp.format("%H:%M:%S%.3f"), entry.method, entry.status, entry.duration_ms, entry.path
+    );
+    if let Some(ref endpoint) = entry.endpoint {
+        line.push_str(&format!("  ({})", endpoint));
+    }
+    if let Some(ref upstream) = entry.upstream {
+        line.push_str(&format!("  → {}", upstream));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dashboard::Dashboard;
+    use crate::plugin::PluginManager;
+    use crate::server::BackworksServer;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_lines_parse_into_commands() {
+        assert_eq!(Command::parse("  ").unwrap(), None);
+        assert_eq!(Command::parse("disable users").unwrap(), Some(Command::Switch { endpoint: "users".to_string(), enabled: false }));
+        assert_eq!(Command::parse("call /users").unwrap(), Some(Command::Call {
+            method: "GET".to_string(), path: "/users".to_string(), headers: vec![], body: None,
+        }));
+        assert_eq!(Command::parse(r#"call post /users -H x-tenant:acme -H accept:text/plain {"name": "Ada Lovelace"}"#).unwrap(), Some(Command::Call {
+            method: "POST".to_string(),
+            path: "/users".to_string(),
+            headers: vec![("x-tenant".to_string(), "acme".to_string()), ("accept".to_string(), "text/plain".to_string())],
+            body: Some(r#"{"name": "Ada Lovelace"}"#.to_string()),
+        }));
+        assert_eq!(Command::parse("tail status=5xx").unwrap(), Some(Command::Logs {
+            filters: vec![("status".to_string(), "5xx".to_string())], follow: true,
+        }));
+        assert_eq!(Command::parse("scenario off").unwrap(), Some(Command::Scenario { name: None }));
+        assert_eq!(Command::parse("chaos on").unwrap(), Some(Command::Chaos { enabled: Some(true) }));
+
+        for (line, error) in [
+            ("launch", "Unknown command"),
+            ("enable", "Invalid arguments"),
+            ("call GET users", "Invalid path"),
+            ("call /users -H tenant", "Invalid header"),
+            ("logs color=red", "Invalid log filter"),
+        ] {
+            let err = Command::parse(line).unwrap_err().to_string();
+            assert!(err.contains(error), "{}: {}", line, err);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_commands_drive_a_running_server() {
+        let config: crate::config::BackworksConfig = serde_yaml::from_str(r#"
+name: Console
+admin: { token: secret }
+endpoints:
+  users:
+    path: /users
+    methods: [GET, POST]
+    mode: mock
+    mock: { schema: { id: 1 } }
+    scenarios:
+      outage: { status: 503, body: { error: down } }
+"#).unwrap();
+        let dashboard = Arc::new(Dashboard::new(serde_yaml::from_str("enabled: true").unwrap()));
+        let app = BackworksServer::new(Arc::new(config), PluginManager::new(), Some(dashboard)).unwrap().create_app().unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(reqwest::header::AUTHORIZATION, "Bearer secret".parse().unwrap());
+        let client = reqwest::Client::builder().default_headers(headers).build().unwrap();
+        let console = Console::new(client, &format!("http://{}/", listener.local_addr().unwrap()));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let run = |line: &str| {
+            let command = Command::parse(line).unwrap().unwrap();
+            let console = &console;
+            async move { console.execute(&command).await }
+        };
+
+        let endpoints = run("endpoints").await.unwrap();
+        assert!(endpoints.starts_with("✅ users  GET,POST  /users  (mock)"), "{}", endpoints);
+        let call = run("call /users").await.unwrap();
+        assert!(call.starts_with("200 OK in") && call.contains("\"id\": 1"), "{}", call);
+
+        assert_eq!(run("scenario outage").await.unwrap(), "🎬 Active scenario: outage");
+        assert!(run("call /users").await.unwrap().starts_with("503"));
+        assert!(run("scenarios").await.unwrap().contains("▶ outage  users"));
+        run("scenario off").await.unwrap();
+        assert_eq!(run("disable users").await.unwrap(), "⛔ users disabled");
+        assert!(run("call /users").await.unwrap().starts_with("503"));
+        run("enable users").await.unwrap();
+
+        let logs = run("logs status=503").await.unwrap();
+        assert_eq!(logs.lines().count(), 2, "{}", logs);
+        assert!(logs.lines().all(|line| line.contains("GET     503") && line.ends_with("/users  (users)")), "{}", logs);
+        let users = [("endpoint".to_string(), "users".to_string())];
+        let newest = console.requests(&users, None).await.unwrap();
+        assert_eq!(newest.iter().map(|entry| entry.status).collect::<Vec<_>>(), vec![200, 503, 503]);
+        assert!(console.requests(&[], Some(newest[2].id)).await.unwrap().iter().all(|entry| entry.path.starts_with("/_backworks/")));
+        // Reading the log leaves it alone
+        assert!(console.requests(&[], None).await.unwrap().iter().all(|entry| entry.path != "/_backworks/requests"));
+
+        assert_eq!(run("flush").await.unwrap(), "🧹 Flushed 0 cached entries");
+        assert_eq!(run("flush users").await.unwrap(), "🧹 Flushed 0 cached entries");
+
+        let err = run("chaos").await.unwrap_err().to_string();
+        assert!(err.contains("/_backworks/chaos is not available on this server (404 Not Found)"), "{}", err);
+        let err = run("enable orders").await.unwrap_err().to_string();
+        assert!(err.contains("Unknown endpoint 'orders'"), "{}", err);
+    }
+}
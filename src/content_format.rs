@@ -0,0 +1,394 @@
+//! Content format conversion
+//!
+//! Converts request and response payloads between JSON and the other
+//! [`ContentFormat`]s. Handlers always work with JSON: a body in another
+//! format is decoded on the way in and encoded on the way out.
+//!
+//! - XML: elements become objects, repeated elements arrays, attributes
+//!   `@name` keys and text next to child elements `#text`. Values stay
+//!   strings. The root element is dropped on decode; a root holding only
+//!   `item` elements decodes to an array.
+//! - CSV: one object per row, keyed by the header row. Encoding takes a
+//!   list of objects (or one object); nested values are written as JSON.
+//! - Form data: `application/x-www-form-urlencoded`; repeated keys become
+//!   arrays.
+//! - YAML, plain text, HTML (handled as text) and base64 (of the JSON text,
+//!   or of a string as is).
+
+use crate::config::ContentFormat;
+use crate::error::{BackworksError, Result};
+use base64::Engine;
+use quick_xml::events::{BytesStart, Event};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+const OPTIONS: [&str; 3] = ["root", "item", "delimiter"];
+
+/// Settings of the XML and CSV encodings
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatOptions {
+    /// Root element name of XML documents (default `root`)
+    pub root: String,
+    /// Element name of list items in XML documents (default `item`)
+    pub item: String,
+    /// CSV field delimiter (default `,`)
+    pub delimiter: u8,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self { root: "root".to_string(), item: "item".to_string(), delimiter: b',' }
+    }
+}
+
+impl FormatOptions {
+    pub fn new(options: Option<&HashMap<String, String>>) -> Result<Self> {
+        let mut parsed = Self::default();
+        for (key, value) in options.into_iter().flatten() {
+            match key.as_str() {
+                "root" => parsed.root = value.clone(),
+                "item" => parsed.item = value.clone(),
+                "delimiter" => match value.as_bytes() {
+                    [delimiter] => parsed.delimiter = *delimiter,
+                    _ => return Err(BackworksError::config(format!("CSV delimiter must be a single character, got '{}'", value))),
+                },
+                _ => return Err(BackworksError::config(format!(
+                    "Unknown format option '{}'; expected one of {}", key, OPTIONS.join(", ")
+                ))),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+impl ContentFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Xml => "application/xml",
+            Self::Yaml => "application/yaml",
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::FormData => "application/x-www-form-urlencoded",
+            Self::PlainText | Self::Base64 => "text/plain; charset=utf-8",
+            Self::Html => "text/html; charset=utf-8",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Json => "JSON",
+            Self::Xml => "XML",
+            Self::Yaml => "YAML",
+            Self::Csv => "CSV",
+            Self::PlainText => "text",
+            Self::Html => "HTML",
+            Self::FormData => "form data",
+            Self::Base64 => "base64",
+        }
+    }
+}
+
+/// Decode a `format` payload into JSON
+pub fn decode(format: &ContentFormat, body: &[u8], options: &FormatOptions) -> std::result::Result<Value, String> {
+    let invalid = |e: &dyn std::fmt::Display| format!("Body is not valid {}: {}", format.name(), e);
+    let text = || std::str::from_utf8(body).map_err(|e| invalid(&e));
+    match format {
+        ContentFormat::Json => serde_json::from_slice(body).map_err(|e| invalid(&e)),
+        ContentFormat::Yaml => serde_yaml::from_slice(body).map_err(|e| invalid(&e)),
+        ContentFormat::PlainText | ContentFormat::Html => Ok(Value::String(text()?.to_string())),
+        ContentFormat::Base64 => {
+            let decoded = base64::engine::general_purpose::STANDARD.decode(text()?.trim()).map_err(|e| invalid(&e))?;
+            let decoded = String::from_utf8(decoded).map_err(|e| invalid(&e))?;
+            Ok(serde_json::from_str(&decoded).unwrap_or(Value::String(decoded)))
+        }
+        ContentFormat::FormData => {
+            let pairs: Vec<(String, String)> = serde_urlencoded::from_bytes(body).map_err(|e| invalid(&e))?;
+            let mut object = Map::new();
+            for (key, value) in pairs {
+                add_child(&mut object, key, Value::String(value));
+            }
+            Ok(Value::Object(object))
+        }
+        ContentFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new().delimiter(options.delimiter).from_reader(body);
+            let headers = reader.headers().map_err(|e| invalid(&e))?.clone();
+            let rows = reader.records()
+                .map(|record| {
+                    let record = record.map_err(|e| invalid(&e))?;
+                    Ok(Value::Object(headers.iter().zip(record.iter())
+                        .map(|(header, field)| (header.to_string(), Value::String(field.to_string())))
+                        .collect()))
+                })
+                .collect::<std::result::Result<Vec<_>, String>>()?;
+            Ok(Value::Array(rows))
+        }
+        ContentFormat::Xml => decode_xml(body, options).map_err(|e| invalid(&e)),
+    }
+}
+
+/// Encode a JSON value as a `format` payload
+pub fn encode(format: &ContentFormat, value: &Value, options: &FormatOptions) -> std::result::Result<Vec<u8>, String> {
+    let as_text = |value: &Value| match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    match format {
+        ContentFormat::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+        ContentFormat::Yaml => serde_yaml::to_string(value).map(String::into_bytes).map_err(|e| e.to_string()),
+        ContentFormat::PlainText | ContentFormat::Html => Ok(as_text(value).into_bytes()),
+        ContentFormat::Base64 => Ok(base64::engine::general_purpose::STANDARD.encode(as_text(value)).into_bytes()),
+        ContentFormat::FormData => {
+            let Value::Object(object) = value else {
+                return Err("Form data can only encode an object".to_string());
+            };
+            let mut pairs = Vec::new();
+            for (key, value) in object {
+                match value {
+                    Value::Array(items) => pairs.extend(items.iter().map(|item| (key.clone(), cell(item)))),
+                    other => pairs.push((key.clone(), cell(other))),
+                }
+            }
+            serde_urlencoded::to_string(pairs)
+                .map(String::into_bytes)
+                .map_err(|e| format!("Cannot encode form data: {}", e))
+        }
+        ContentFormat::Csv => encode_csv(value, options),
+        ContentFormat::Xml => Ok(encode_xml(value, options).into_bytes()),
+    }
+}
+
+/// A scalar as a CSV cell or form value; nested values as JSON
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Add `value` under `key`, collecting repeated keys into an array
+fn add_child(object: &mut Map<String, Value>, key: String, value: Value) {
+    match object.get_mut(&key) {
+        Some(Value::Array(items)) => items.push(value),
+        Some(existing) => *existing = Value::Array(vec![existing.take(), value]),
+        None => {
+            object.insert(key, value);
+        }
+    }
+}
+
+fn encode_csv(value: &Value, options: &FormatOptions) -> std::result::Result<Vec<u8>, String> {
+    let rows = match value {
+        Value::Array(rows) => rows.iter().collect::<Vec<_>>(),
+        object @ Value::Object(_) => vec![object],
+        _ => return Err("CSV can only encode a list of objects".to_string()),
+    };
+    let mut columns: Vec<&String> = Vec::new();
+    for row in &rows {
+        let Value::Object(row) = row else {
+            return Err("CSV can only encode a list of objects".to_string());
+        };
+        for key in row.keys() {
+            if !columns.contains(&key) {
+                columns.push(key);
+            }
+        }
+    }
+
+    let failed = |e: csv::Error| format!("Cannot encode CSV: {}", e);
+    let mut writer = csv::WriterBuilder::new().delimiter(options.delimiter).from_writer(Vec::new());
+    writer.write_record(&columns).map_err(failed)?;
+    for row in rows {
+        writer.write_record(columns.iter().map(|column| row.get(column.as_str()).map(cell).unwrap_or_default()))
+            .map_err(failed)?;
+    }
+    writer.into_inner().map_err(|e| format!("Cannot encode CSV: {}", e))
+}
+
+/// An element being read: its name, attributes and children so far, and text
+struct OpenElement {
+    name: String,
+    children: Map<String, Value>,
+    text: String,
+}
+
+impl OpenElement {
+    fn new(start: &BytesStart) -> std::result::Result<Self, String> {
+        let mut children = Map::new();
+        for attribute in start.attributes() {
+            let attribute = attribute.map_err(|e| e.to_string())?;
+            let value = attribute.unescape_value().map_err(|e| e.to_string())?;
+            children.insert(format!("@{}", String::from_utf8_lossy(attribute.key.as_ref())), Value::String(value.into_owned()));
+        }
+        Ok(Self { name: String::from_utf8_lossy(start.name().as_ref()).into_owned(), children, text: String::new() })
+    }
+
+    fn finish(mut self) -> (String, Value) {
+        let value = match (self.children.is_empty(), self.text.is_empty()) {
+            (true, true) => Value::Null,
+            (true, false) => Value::String(self.text),
+            (false, text_empty) => {
+                if !text_empty {
+                    self.children.insert("#text".to_string(), Value::String(self.text));
+                }
+                Value::Object(self.children)
+            }
+        };
+        (self.name, value)
+    }
+}
+
+fn decode_xml(body: &[u8], options: &FormatOptions) -> std::result::Result<Value, String> {
+    let mut reader = quick_xml::Reader::from_reader(body);
+    reader.config_mut().trim_text(true);
+    let mut open: Vec<OpenElement> = Vec::new();
+    let mut root = None;
+    let mut buf = Vec::new();
+
+    loop {
+        let event = reader.read_event_into(&mut buf).map_err(|e| e.to_string())?;
+        let closed = match event {
+            Event::Start(ref start) => {
+                open.push(OpenElement::new(start)?);
+                None
+            }
+            Event::Empty(ref start) => Some(OpenElement::new(start)?.finish()),
+            Event::End(_) => open.pop().map(OpenElement::finish),
+            Event::Text(ref text) => {
+                let text = text.unescape().map_err(|e| e.to_string())?;
+                if let Some(element) = open.last_mut() {
+                    element.text.push_str(&text);
+                }
+                None
+            }
+            Event::CData(ref data) => {
+                if let Some(element) = open.last_mut() {
+                    element.text.push_str(&String::from_utf8_lossy(data));
+                }
+                None
+            }
+            Event::Eof => break,
+            _ => None,
+        };
+        if let Some((name, value)) = closed {
+            match open.last_mut() {
+                Some(parent) => add_child(&mut parent.children, name, value),
+                None if root.is_none() => root = Some(value),
+                None => return Err("more than one root element".to_string()),
+            }
+        }
+        buf.clear();
+    }
+
+    if !open.is_empty() {
+        return Err(format!("element <{}> is not closed", open[open.len() - 1].name));
+    }
+    let root = root.ok_or("no root element")?;
+    // A root holding only list items is the list
+    Ok(match root {
+        Value::Object(mut object) if object.len() == 1 && object.contains_key(&options.item) => {
+            match object.remove(&options.item) {
+                Some(Value::Array(items)) => Value::Array(items),
+                Some(item) => Value::Array(vec![item]),
+                None => Value::Null,
+            }
+        }
+        other => other,
+    })
+}
+
+fn encode_xml(value: &Value, options: &FormatOptions) -> String {
+    let mut out = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    match value {
+        Value::Array(items) => {
+            out.push_str(&format!("<{}>", options.root));
+            for item in items {
+                write_element(&mut out, &options.item, item);
+            }
+            out.push_str(&format!("</{}>", options.root));
+        }
+        other => write_element(&mut out, &options.root, other),
+    }
+    out
+}
+
+fn write_element(out: &mut String, name: &str, value: &Value) {
+    match value {
+        Value::Array(items) => items.iter().for_each(|item| write_element(out, name, item)),
+        Value::Null => out.push_str(&format!("<{}/>", name)),
+        Value::Object(object) => {
+            out.push('<');
+            out.push_str(name);
+            for (key, value) in object {
+                if let Some(attribute) = key.strip_prefix('@') {
+                    out.push_str(&format!(" {}=\"{}\"", attribute, quick_xml::escape::escape(cell(value).as_str())));
+                }
+            }
+            let mut content = String::new();
+            for (key, value) in object {
+                if key == "#text" {
+                    content.push_str(&quick_xml::escape::escape(cell(value).as_str()));
+                } else if !key.starts_with('@') {
+                    write_element(&mut content, key, value);
+                }
+            }
+            if content.is_empty() {
+                out.push_str("/>");
+            } else {
+                out.push_str(&format!(">{}</{}>", content, name));
+            }
+        }
+        scalar => out.push_str(&format!("<{0}>{1}</{0}>", name, quick_xml::escape::escape(cell(scalar).as_str()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn round_trip(format: ContentFormat, value: Value) -> Value {
+        let options = FormatOptions::default();
+        decode(&format, &encode(&format, &value, &options).unwrap(), &options).unwrap()
+    }
+
+    #[test]
+    fn test_xml_conversion() {
+        let options = FormatOptions::default();
+        let xml = br#"<?xml version="1.0"?>
+<order id="7"><customer>Ada &amp; Co</customer><line sku="A1">2</line><line sku="B2">1</line><note/></order>"#;
+        let value = decode(&ContentFormat::Xml, xml, &options).unwrap();
+        assert_eq!(value, json!({
+            "@id": "7",
+            "customer": "Ada & Co",
+            "line": [{ "@sku": "A1", "#text": "2" }, { "@sku": "B2", "#text": "1" }],
+            "note": null,
+        }));
+        assert_eq!(round_trip(ContentFormat::Xml, value.clone()), value);
+
+        let list = json!([{ "id": "1" }, { "id": "2" }]);
+        let encoded = String::from_utf8(encode(&ContentFormat::Xml, &list, &options).unwrap()).unwrap();
+        assert!(encoded.ends_with("<root><item><id>1</id></item><item><id>2</id></item></root>"));
+        assert_eq!(round_trip(ContentFormat::Xml, list.clone()), list);
+        assert!(decode(&ContentFormat::Xml, b"<a><b></a>", &options).is_err());
+    }
+
+    #[test]
+    fn test_csv_form_and_text_conversion() {
+        let options = FormatOptions::new(Some(&HashMap::from([("delimiter".to_string(), ";".to_string())]))).unwrap();
+        let rows = json!([{ "id": 1, "name": "Ada", "tags": ["x"] }, { "id": 2, "name": "Alan; Turing" }]);
+        let csv = String::from_utf8(encode(&ContentFormat::Csv, &rows, &options).unwrap()).unwrap();
+        assert_eq!(csv, "id;name;tags\n1;Ada;\"[\"\"x\"\"]\"\n2;\"Alan; Turing\";\n");
+        assert_eq!(decode(&ContentFormat::Csv, csv.as_bytes(), &options).unwrap()[1], json!({ "id": "2", "name": "Alan; Turing", "tags": "" }));
+        assert!(encode(&ContentFormat::Csv, &json!([1, 2]), &options).is_err());
+
+        assert_eq!(decode(&ContentFormat::FormData, b"name=Ada+L&tag=a&tag=b", &options).unwrap(), json!({ "name": "Ada L", "tag": ["a", "b"] }));
+        assert_eq!(round_trip(ContentFormat::FormData, json!({ "tag": ["a", "b"], "n": "1" })), json!({ "tag": ["a", "b"], "n": "1" }));
+        assert_eq!(round_trip(ContentFormat::Base64, json!({ "ok": true })), json!({ "ok": true }));
+        assert_eq!(round_trip(ContentFormat::Yaml, json!({ "ok": [1, 2] })), json!({ "ok": [1, 2] }));
+        assert_eq!(decode(&ContentFormat::PlainText, b"hi", &options).unwrap(), json!("hi"));
+
+        assert!(FormatOptions::new(Some(&HashMap::from([("delimiter".to_string(), "||".to_string())]))).is_err());
+        assert!(FormatOptions::new(Some(&HashMap::from([("indent".to_string(), "2".to_string())]))).is_err());
+    }
+}
@@ -0,0 +1,501 @@
+//! Contract verification
+//!
+//! `backworks verify --against https://api.example.com` checks that the
+//! blueprint still answers like the service it stands in for. Each endpoint
+//! of the blueprint is requested from the blueprint, served in process, and
+//! from the real service; the requests of capture exports and proxy
+//! cassettes are sent to the real service and its answers compared with the
+//! recorded responses. Path parameters get a sample value of their type
+//! unless one is given.
+//!
+//! Statuses must be equal and so must the compared headers the expected
+//! response has: the media type of `content-type` and any others asked
+//! for. Bodies are compared by [shape](crate::drift): fields the real
+//! service leaves out or adds, and types the expected response never has,
+//! are mismatches. Fields inside
+//! arrays are only compared when both sides have items. Only `GET`, `HEAD`
+//! and `OPTIONS` requests are sent unless writes are allowed, since the real
+//! service keeps what the others change.
+
+use crate::compare::{Divergence, DivergenceKind, ResponseSnapshot};
+use crate::config::BackworksConfig;
+use crate::drift::{collect_shape, BodyShape};
+use crate::engine::BackworksEngine;
+use crate::error::{BackworksError, Result};
+use crate::routes::RoutePattern;
+use axum::Router;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+use tower::ServiceExt;
+
+/// Methods sent without `--writes`
+const SAFE_METHODS: &[&str] = &["GET", "HEAD", "OPTIONS"];
+/// Headers of captured requests that are replayed
+const REPLAYED_HEADERS: &[&str] = &["accept", "content-type"];
+
+/// A request sent to both sides
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractRequest {
+    /// Blueprint endpoint or capture file the request comes from
+    pub source: String,
+    pub method: String,
+    /// Path and query string
+    pub path: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+}
+
+/// A captured request and the response recorded for it
+#[derive(Debug, Clone)]
+pub struct RecordedExchange {
+    pub request: ContractRequest,
+    pub response: ResponseSnapshot,
+}
+
+/// How one request was answered by the real service
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractResult {
+    #[serde(flatten)]
+    pub request: ContractRequest,
+    pub expected_status: u16,
+    /// Missing when the real service could not be reached
+    pub actual_status: Option<u16>,
+    /// Body differences have the types seen at the field on each side
+    pub divergences: Vec<Divergence>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ContractResult {
+    pub fn passed(&self) -> bool {
+        self.error.is_none() && self.divergences.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerifyReport {
+    pub against: String,
+    pub results: Vec<ContractResult>,
+    /// Requests not sent because they could change the real service
+    pub skipped_writes: usize,
+}
+
+/// Sends requests to the real service and compares its answers
+#[derive(Debug, Clone)]
+pub struct Verifier {
+    against: String,
+    client: reqwest::Client,
+    headers: Vec<(String, String)>,
+    compared_headers: Vec<String>,
+    params: HashMap<String, String>,
+    endpoint: Option<String>,
+    writes: bool,
+}
+
+impl Verifier {
+    /// Verify against the service at the base URL `against`
+    pub fn new(against: &str, timeout: Duration) -> Result<Self> {
+        let url = reqwest::Url::parse(against)
+            .map_err(|e| BackworksError::config(format!("Invalid URL to verify against '{}': {}", against, e)))?;
+        if !["http", "https"].contains(&url.scheme()) {
+            return Err(BackworksError::config(format!("URL to verify against '{}' must be http or https", against)));
+        }
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .map_err(|e| BackworksError::config(format!("Failed to create HTTP client: {}", e)))?;
+        Ok(Self {
+            against: against.trim_end_matches('/').to_string(),
+            client,
+            headers: Vec::new(),
+            compared_headers: vec!["content-type".to_string()],
+            params: HashMap::new(),
+            endpoint: None,
+            writes: false,
+        })
+    }
+
+    /// Headers sent to the real service only, such as its credentials
+    pub fn with_headers(mut self, headers: Vec<(String, String)>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Response headers compared besides `content-type`
+    pub fn with_compared_headers(mut self, headers: Vec<String>) -> Self {
+        for name in headers {
+            let name = name.to_lowercase();
+            if !self.compared_headers.contains(&name) {
+                self.compared_headers.push(name);
+            }
+        }
+        self
+    }
+
+    /// Path parameter values used instead of samples, by name
+    pub fn with_params(mut self, params: HashMap<String, String>) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Only request this endpoint of the blueprint
+    pub fn with_endpoint(mut self, endpoint: Option<String>) -> Self {
+        self.endpoint = endpoint;
+        self
+    }
+
+    /// Also send requests that could change the real service
+    pub fn with_writes(mut self, writes: bool) -> Self {
+        self.writes = writes;
+        self
+    }
+
+    /// The requests made for the endpoints of `config`, one per method
+    pub fn blueprint_requests(&self, config: &BackworksConfig) -> Result<Vec<ContractRequest>> {
+        if let Some(ref endpoint) = self.endpoint {
+            if !config.endpoints.contains_key(endpoint) {
+                return Err(BackworksError::config(format!("Endpoint '{}' is not in the blueprint", endpoint)));
+            }
+        }
+        let mut names: Vec<&String> = config.endpoints.keys()
+            .filter(|name| self.endpoint.as_ref().is_none_or(|endpoint| endpoint == *name))
+            .collect();
+        names.sort();
+
+        let mut requests = Vec::new();
+        for name in names {
+            let endpoint = &config.endpoints[name];
+            let path = RoutePattern::parse(&endpoint.path).sample_path(&self.params);
+            for method in &endpoint.methods {
+                requests.push(ContractRequest {
+                    source: name.clone(),
+                    method: method.to_ascii_uppercase(),
+                    path: path.clone(),
+                    headers: BTreeMap::new(),
+                    body: None,
+                });
+            }
+        }
+        Ok(requests)
+    }
+
+    /// Compare the real service with the blueprint `config`, served in
+    /// process, and with the `recorded` exchanges
+    pub async fn run(&self, config: Option<&BackworksConfig>, recorded: Vec<RecordedExchange>) -> Result<VerifyReport> {
+        let mut report = VerifyReport { against: self.against.clone(), results: Vec::new(), skipped_writes: 0 };
+        let mut exchanges = Vec::new();
+        if let Some(config) = config {
+            let requests = self.blueprint_requests(config)?;
+            let engine = BackworksEngine::new(config.clone()).await?;
+            let app = engine.app()?;
+            for request in requests {
+                if self.sends(&request) {
+                    let response = serve(&app, &request).await?;
+                    exchanges.push(RecordedExchange { request, response });
+                } else {
+                    report.skipped_writes += 1;
+                }
+            }
+        }
+        for exchange in recorded {
+            if self.sends(&exchange.request) {
+                exchanges.push(exchange);
+            } else {
+                report.skipped_writes += 1;
+            }
+        }
+
+        for exchange in exchanges {
+            report.results.push(self.check(exchange).await);
+        }
+        Ok(report)
+    }
+
+    fn sends(&self, request: &ContractRequest) -> bool {
+        self.writes || SAFE_METHODS.contains(&request.method.as_str())
+    }
+
+    async fn check(&self, exchange: RecordedExchange) -> ContractResult {
+        let RecordedExchange { request, response: expected } = exchange;
+        let mut result = ContractResult {
+            expected_status: expected.status,
+            actual_status: None,
+            divergences: Vec::new(),
+            error: None,
+            request,
+        };
+        match send(&self.client, &self.against, &result.request, &self.headers).await {
+            Ok(actual) => {
+                result.actual_status = Some(actual.status);
+                result.divergences = diff(&expected, &actual, &self.compared_headers);
+            }
+            Err(e) => result.error = Some(e),
+        }
+        result
+    }
+}
+
+/// Send `request` to the service at `base` with `headers` besides its own
+pub(crate) async fn send(
+    client: &reqwest::Client,
+    base: &str,
+    request: &ContractRequest,
+    headers: &[(String, String)],
+) -> std::result::Result<ResponseSnapshot, String> {
+    let method = reqwest::Method::from_bytes(request.method.as_bytes()).map_err(|e| e.to_string())?;
+    let mut builder = client.request(method, format!("{}{}", base, request.path));
+    for (name, value) in request.headers.iter().chain(headers.iter().map(|(name, value)| (name, value))) {
+        builder = builder.header(name, value);
+    }
+    // Captured bodies that are not JSON were kept as text
+    let json = request.headers.get("content-type").is_none_or(|content_type| content_type.contains("json"));
+    builder = match request.body {
+        Some(Value::String(ref text)) if !json => builder.body(text.clone()),
+        Some(ref body) => builder.json(body),
+        None => builder,
+    };
+    let response = builder.send().await.map_err(|e| e.to_string())?;
+    let status = response.status().as_u16();
+    let headers = response.headers().iter()
+        .map(|(name, value)| (name.as_str().to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+        .collect();
+    let body = response.bytes().await.map_err(|e| e.to_string())?;
+    Ok(ResponseSnapshot { status, headers, body: body_value(&body) })
+}
+
+/// How `actual` differs from `expected` in status, the `compared_headers`
+/// `expected` has and body shape
+pub(crate) fn diff(expected: &ResponseSnapshot, actual: &ResponseSnapshot, compared_headers: &[String]) -> Vec<Divergence> {
+    let mut divergences = Vec::new();
+    if expected.status != actual.status {
+        divergences.push(Divergence {
+            kind: DivergenceKind::Status,
+            path: String::new(),
+            baseline: Some(Value::from(expected.status)),
+            live: Some(Value::from(actual.status)),
+        });
+    }
+    for name in compared_headers {
+        let value = |snapshot: &ResponseSnapshot| snapshot.headers.iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| match name.as_str() {
+                "content-type" => value.split(';').next().unwrap_or_default().trim().to_ascii_lowercase(),
+                _ => value.clone(),
+            });
+        // Recordings without the header say nothing about it
+        let Some(before) = value(expected) else { continue };
+        let after = value(actual);
+        if Some(&before) != after.as_ref() {
+            divergences.push(Divergence {
+                kind: DivergenceKind::Header,
+                path: name.clone(),
+                baseline: Some(Value::String(before)),
+                live: after.map(Value::String),
+            });
+        }
+    }
+
+    let (mut before, mut after) = (BodyShape::new(), BodyShape::new());
+    collect_shape("", &expected.body, &mut before);
+    collect_shape("", &actual.body, &mut after);
+    let types = |shape: &BodyShape, field: &str| shape.get(field).map(|types| Value::from(types.iter().cloned().collect::<Vec<_>>()));
+    let body = |field: &String| Divergence {
+        kind: DivergenceKind::Body,
+        path: if field.is_empty() { "/".to_string() } else { field.clone() },
+        baseline: types(&before, field),
+        live: types(&after, field),
+    };
+    for (field, expected_types) in &before {
+        match after.get(field) {
+            None if compared(field, &after) => divergences.push(body(field)),
+            Some(actual_types) if !actual_types.is_subset(expected_types) => divergences.push(body(field)),
+            _ => {}
+        }
+    }
+    for field in after.keys() {
+        if !before.contains_key(field) && compared(field, &before) {
+            divergences.push(body(field));
+        }
+    }
+    divergences
+}
+
+/// Whether `shape` could have `field`: its parent is there, so a missing
+/// field is not just a missing object or an empty array
+fn compared(field: &str, shape: &BodyShape) -> bool {
+    match field.rsplit_once('/') {
+        Some((_, "*")) => false,
+        Some((parent, _)) => shape.contains_key(parent),
+        None => true,
+    }
+}
+
+/// JSON bodies as they are, others as a string, empty ones as null
+pub fn body_value(body: &[u8]) -> Value {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Value::Null;
+    }
+    serde_json::from_slice(body).unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).into_owned()))
+}
+
+/// Answer `request` from the blueprint served by `app`
+async fn serve(app: &Router, request: &ContractRequest) -> Result<ResponseSnapshot> {
+    let mut builder = axum::http::Request::builder().method(request.method.as_str()).uri(&request.path);
+    for (name, value) in &request.headers {
+        builder = builder.header(name, value);
+    }
+    let body = match request.body {
+        Some(ref body) => {
+            if !request.headers.contains_key("content-type") {
+                builder = builder.header("content-type", "application/json");
+            }
+            axum::body::Body::from(body.to_string())
+        }
+        None => axum::body::Body::empty(),
+    };
+    let http_request = builder.body(body)
+        .map_err(|e| BackworksError::config(format!("Invalid request {} {}: {}", request.method, request.path, e)))?;
+    let response = app.clone().oneshot(http_request).await.unwrap_or_else(|never| match never {});
+    let status = response.status().as_u16();
+    let headers = response.headers().iter()
+        .map(|(name, value)| (name.as_str().to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+        .collect();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await
+        .map_err(|e| BackworksError::config(format!("Failed to read the blueprint's response to {} {}: {}", request.method, request.path, e)))?;
+    Ok(ResponseSnapshot { status, headers, body: body_value(&body) })
+}
+
+/// The exchanges of a capture export or proxy cassette that have a
+/// response, with only the request headers describing the content
+pub async fn load_capture(path: &Path) -> Result<Vec<RecordedExchange>> {
+    Ok(crate::replay::load_session(path).await?.into_iter()
+        .filter_map(|exchange| {
+            let mut request = exchange.request;
+            request.headers.retain(|name, _| REPLAYED_HEADERS.contains(&name.as_str()));
+            Some(RecordedExchange { request, response: exchange.response? })
+        })
+        .collect())
+}
+
+impl VerifyReport {
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(ContractResult::passed)
+    }
+
+    pub fn render_text(&self) -> String {
+        let mut out = String::new();
+        self.write_text(&mut out).expect("writing to a String cannot fail");
+        out
+    }
+
+    fn write_text(&self, out: &mut String) -> fmt::Result {
+        writeln!(out, "Contract verification against {}", self.against)?;
+        for result in &self.results {
+            let request = &result.request;
+            let mark = match (&result.error, result.divergences.is_empty()) {
+                (Some(_), _) => "⚠️ ",
+                (None, true) => "✅",
+                (None, false) => "❌",
+            };
+            writeln!(out, "  {} {} {} ({})", mark, request.method, request.path, request.source)?;
+            if let Some(ref error) = result.error {
+                writeln!(out, "       {}", error)?;
+            }
+            write_divergences(out, &result.divergences)?;
+        }
+        let mismatched = self.results.iter().filter(|result| result.error.is_none() && !result.passed()).count();
+        let failed = self.results.iter().filter(|result| result.error.is_some()).count();
+        write!(out, "\n{} verified, {} mismatched, {} failed", self.results.len() - mismatched - failed, mismatched, failed)?;
+        if self.skipped_writes > 0 {
+            write!(out, ", {} write request(s) skipped", self.skipped_writes)?;
+        }
+        writeln!(out)
+    }
+}
+
+/// One line per divergence, under the line of its request
+pub(crate) fn write_divergences(out: &mut String, divergences: &[Divergence]) -> fmt::Result {
+    let types = |value: &Option<Value>| match value {
+        Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect::<Vec<_>>().join("|"),
+        Some(value) => value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string()),
+        None => "(none)".to_string(),
+    };
+    for divergence in divergences {
+        match (&divergence.kind, &divergence.baseline, &divergence.live) {
+            (DivergenceKind::Status, _, _) => writeln!(out, "       status {} -> {}", types(&divergence.baseline), types(&divergence.live))?,
+            (DivergenceKind::Header, _, _) => writeln!(out, "       header {}: {} -> {}", divergence.path, types(&divergence.baseline), types(&divergence.live))?,
+            (DivergenceKind::Body, Some(_), None) => writeln!(out, "       - {} ({})", divergence.path, types(&divergence.baseline))?,
+            (DivergenceKind::Body, None, _) => writeln!(out, "       + {} ({})", divergence.path, types(&divergence.live))?,
+            (DivergenceKind::Body, _, _) => writeln!(out, "       ~ {}: {} -> {}", divergence.path, types(&divergence.baseline), types(&divergence.live))?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_mismatches_with_the_real_service_are_reported() {
+        let real = Router::new()
+            .route("/users/:id", get(|| async { axum::Json(json!({ "id": 7, "name": 5, "nickname": "ada" })) }))
+            .route("/orders", get(|| async { axum::Json(json!([{ "id": 1 }, { "id": 2 }])) }))
+            .route("/health", get(|| async { (axum::http::StatusCode::SERVICE_UNAVAILABLE, "down") }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let against = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, real).await.unwrap() });
+
+        let config: BackworksConfig = serde_yaml::from_str(r#"
+name: shop
+endpoints:
+  user: { path: "/users/{id:int}", mode: mock, mock: { schema: { id: "$param(id)", name: "$name", email: "$email" } } }
+  orders: { path: /orders, methods: [GET, POST], mode: mock, mock: { schema: { id: "$seq" }, count: 2 } }
+"#).unwrap();
+        let recorded = vec![RecordedExchange {
+            request: ContractRequest { source: "traffic.json".to_string(), method: "GET".to_string(), path: "/health".to_string(), headers: BTreeMap::new(), body: None },
+            response: ResponseSnapshot { status: 200, headers: HashMap::new(), body: json!("ok") },
+        }];
+
+        let verifier = Verifier::new(&against, Duration::from_secs(5)).unwrap()
+            .with_params(HashMap::from([("id".to_string(), "7".to_string())]));
+        let requests = verifier.blueprint_requests(&config).unwrap();
+        assert_eq!(requests.iter().map(|request| format!("{} {}", request.method, request.path)).collect::<Vec<_>>(),
+            vec!["GET /orders", "POST /orders", "GET /users/7"]);
+
+        let report = verifier.run(Some(&config), recorded).await.unwrap();
+        assert!(!report.passed());
+        assert_eq!(report.skipped_writes, 1);
+        let changes = |index: usize| report.results[index].divergences.iter()
+            .map(|divergence| format!("{:?} {}", divergence.kind, divergence.path).trim_end().to_string())
+            .collect::<Vec<_>>();
+        assert!(report.results[0].passed(), "{:?}", report.results[0]);
+        assert_eq!(changes(1), vec!["Body /email", "Body /name", "Body /nickname"]);
+        assert_eq!(changes(2), vec!["Status"]);
+
+        let text = report.render_text();
+        assert!(text.contains("❌ GET /users/7 (user)\n       - /email (string)\n       ~ /name: string -> number\n       + /nickname (string)"), "{}", text);
+        assert!(text.contains("status 200 -> 503"), "{}", text);
+        assert!(text.ends_with("1 verified, 2 mismatched, 0 failed, 1 write request(s) skipped\n"), "{}", text);
+    }
+
+    #[test]
+    fn test_fields_of_missing_parents_and_empty_arrays_are_not_compared() {
+        let mut shape = BodyShape::new();
+        collect_shape("", &json!({ "items": [], "user": { "id": 1 } }), &mut shape);
+        assert!(compared("/user/name", &shape));
+        assert!(compared("/items", &shape));
+        assert!(!compared("/items/*", &shape));
+        assert!(!compared("/items/*/id", &shape));
+        assert!(!compared("/team/id", &shape));
+    }
+}
@@ -0,0 +1,287 @@
+//! CORS with per-origin policies
+//!
+//! The top-level `security.cors` settings form the default policy. Entries
+//! under `policies` give groups of origins their own methods, headers,
+//! credentials and preflight cache lifetime; the first policy listing the
+//! request's origin applies, otherwise the default one does.
+//!
+//! Preflight requests (`OPTIONS` with `Access-Control-Request-Method`) are
+//! answered here and never reach plugins or handlers. Each one is counted in
+//! `backworks_cors_preflights_total` by policy and outcome.
+
+use crate::config::{CorsConfig, CorsPolicyConfig};
+use crate::error::{BackworksError, Result};
+use crate::request_metrics::RequestMetrics;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::sync::Arc;
+
+pub const DEFAULT_POLICY: &str = "default";
+
+#[derive(Debug, Clone, PartialEq)]
+enum OriginPattern {
+    Any,
+    Exact(String),
+    /// `https://*.example.com`: scheme and the suffix after the wildcard
+    Subdomain { scheme: String, suffix: String },
+}
+
+impl OriginPattern {
+    fn parse(origin: &str) -> Self {
+        if origin == "*" {
+            return Self::Any;
+        }
+        match origin.split_once("://*.") {
+            Some((scheme, domain)) => Self::Subdomain {
+                scheme: format!("{}://", scheme.to_ascii_lowercase()),
+                suffix: format!(".{}", domain.to_ascii_lowercase()),
+            },
+            None => Self::Exact(origin.trim_end_matches('/').to_ascii_lowercase()),
+        }
+    }
+
+    fn matches(&self, origin: &str) -> bool {
+        let origin = origin.to_ascii_lowercase();
+        match self {
+            Self::Any => true,
+            Self::Exact(exact) => *exact == origin,
+            Self::Subdomain { scheme, suffix } => origin.strip_prefix(scheme.as_str())
+                .is_some_and(|host| host.ends_with(suffix.as_str()) && host.len() > suffix.len()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CorsPolicy {
+    pub name: String,
+    origins: Vec<OriginPattern>,
+    methods: Vec<Method>,
+    headers: Vec<HeaderName>,
+    credentials: bool,
+    max_age: Option<u64>,
+}
+
+impl CorsPolicy {
+    fn allows_origin(&self, origin: &str) -> bool {
+        self.origins.iter().any(|pattern| pattern.matches(origin))
+    }
+
+    fn allows_any_origin(&self) -> bool {
+        self.origins.contains(&OriginPattern::Any)
+    }
+
+    fn allows_method(&self, method: &str) -> bool {
+        // An empty list leaves the simple methods, which need no preflight
+        let simple = ["GET", "HEAD", "POST"];
+        if self.methods.is_empty() {
+            return simple.iter().any(|m| m.eq_ignore_ascii_case(method));
+        }
+        self.methods.iter().any(|m| m.as_str().eq_ignore_ascii_case(method))
+    }
+
+    fn allows_headers(&self, requested: &str) -> bool {
+        requested.split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .all(|name| self.headers.iter().any(|allowed| allowed.as_str().eq_ignore_ascii_case(name)))
+    }
+
+    /// Browsers refuse credentials with the wildcard origin, and answering any
+    /// origin with its own name would let every site send credentialed
+    /// requests, so the two cannot be combined
+    fn check_credentials(self) -> Result<Self> {
+        if self.credentials && self.allows_any_origin() {
+            return Err(BackworksError::config(format!(
+                "CORS policy '{}' allows credentials from any origin; list the origins it trusts", self.name
+            )));
+        }
+        Ok(self)
+    }
+
+    /// Headers every CORS response from this policy carries
+    fn apply(&self, origin: &HeaderValue, headers: &mut HeaderMap) {
+        if self.allows_any_origin() {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+        } else {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+            headers.append(header::VARY, HeaderValue::from_static("origin"));
+        }
+        if self.credentials {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+        }
+    }
+
+    fn apply_preflight(&self, headers: &mut HeaderMap) {
+        let join = |values: Vec<&str>| HeaderValue::from_str(&values.join(", ")).ok();
+        if let Some(methods) = join(self.methods.iter().map(Method::as_str).collect()).filter(|v| !v.is_empty()) {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, methods);
+        }
+        if let Some(allowed) = join(self.headers.iter().map(HeaderName::as_str).collect()).filter(|v| !v.is_empty()) {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, allowed);
+        }
+        if let Some(max_age) = self.max_age {
+            headers.insert(header::ACCESS_CONTROL_MAX_AGE, HeaderValue::from(max_age));
+        }
+    }
+}
+
+/// The default policy and the origin groups, ready to match requests
+#[derive(Debug, Clone)]
+pub struct CorsPolicies {
+    policies: Vec<CorsPolicy>,
+    default: CorsPolicy,
+}
+
+impl CorsPolicies {
+    pub fn new(config: &CorsConfig) -> Result<Self> {
+        let default = CorsPolicy {
+            name: DEFAULT_POLICY.to_string(),
+            origins: config.origins.as_ref()
+                .map(|origins| origins.iter().map(|o| OriginPattern::parse(o)).collect())
+                .unwrap_or_else(|| vec![OriginPattern::Any]),
+            methods: parse_methods(DEFAULT_POLICY, config.methods.as_deref())?,
+            headers: parse_headers(DEFAULT_POLICY, config.headers.as_deref())?,
+            credentials: config.credentials.unwrap_or(false),
+            max_age: config.max_age,
+        }.check_credentials()?;
+
+        let policies = config.policies.iter().enumerate()
+            .map(|(index, policy)| Self::policy(index, policy, &default, config))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { policies, default })
+    }
+
+    fn policy(index: usize, policy: &CorsPolicyConfig, default: &CorsPolicy, config: &CorsConfig) -> Result<CorsPolicy> {
+        let name = policy.name.clone().unwrap_or_else(|| format!("policy_{}", index));
+        if policy.origins.is_empty() {
+            return Err(BackworksError::config(format!("CORS policy '{}' must list at least one origin", name)));
+        }
+        CorsPolicy {
+            origins: policy.origins.iter().map(|o| OriginPattern::parse(o)).collect(),
+            methods: match policy.methods {
+                Some(ref methods) => parse_methods(&name, Some(methods))?,
+                None => default.methods.clone(),
+            },
+            headers: match policy.headers {
+                Some(ref headers) => parse_headers(&name, Some(headers))?,
+                None => default.headers.clone(),
+            },
+            credentials: policy.credentials.or(config.credentials).unwrap_or(false),
+            max_age: policy.max_age.or(config.max_age),
+            name,
+        }.check_credentials()
+    }
+
+    /// The policy for `origin`, or `None` when no policy allows it
+    pub fn policy_for(&self, origin: &str) -> Option<&CorsPolicy> {
+        self.policies.iter()
+            .find(|policy| policy.allows_origin(origin))
+            .or_else(|| Some(&self.default).filter(|policy| policy.allows_origin(origin)))
+    }
+}
+
+fn parse_methods(policy: &str, methods: Option<&[String]>) -> Result<Vec<Method>> {
+    methods.unwrap_or_default().iter()
+        .map(|method| Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+            .map_err(|_| BackworksError::config(format!("CORS policy '{}' has invalid method '{}'", policy, method))))
+        .collect()
+}
+
+fn parse_headers(policy: &str, headers: Option<&[String]>) -> Result<Vec<HeaderName>> {
+    headers.unwrap_or_default().iter()
+        .map(|name| HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| BackworksError::config(format!("CORS policy '{}' has invalid header '{}'", policy, name))))
+        .collect()
+}
+
+/// State of the CORS middleware
+#[derive(Clone)]
+pub struct Cors {
+    pub policies: Arc<CorsPolicies>,
+    pub metrics: RequestMetrics,
+}
+
+pub async fn cors_middleware(State(cors): State<Cors>, request: Request, next: Next) -> Response {
+    let Some(origin) = request.headers().get(header::ORIGIN).cloned() else {
+        return next.run(request).await;
+    };
+    let policy = origin.to_str().ok().and_then(|origin| cors.policies.policy_for(origin));
+
+    let requested_method = request.headers().get(header::ACCESS_CONTROL_REQUEST_METHOD).cloned();
+    if let (&Method::OPTIONS, Some(requested_method)) = (request.method(), requested_method) {
+        let requested_headers = request.headers().get(header::ACCESS_CONTROL_REQUEST_HEADERS)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+        let allowed = policy.filter(|policy| {
+            requested_method.to_str().is_ok_and(|method| policy.allows_method(method))
+                && policy.allows_headers(requested_headers)
+        });
+        cors.metrics.record_preflight(policy.map_or("none", |p| p.name.as_str()), allowed.is_some());
+
+        // Refused preflights get no CORS headers, so the browser blocks the request
+        let Some(policy) = allowed else {
+            return StatusCode::FORBIDDEN.into_response();
+        };
+        let mut response = StatusCode::NO_CONTENT.into_response();
+        policy.apply(&origin, response.headers_mut());
+        policy.apply_preflight(response.headers_mut());
+        return response;
+    }
+
+    let mut response = next.run(request).await;
+    if let Some(policy) = policy {
+        policy.apply(&origin, response.headers_mut());
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policies() -> CorsPolicies {
+        let config: CorsConfig = serde_yaml::from_str(r#"
+enabled: true
+origins: ["https://app.example.com"]
+methods: [GET, POST]
+headers: [content-type]
+max_age: 600
+policies:
+  - name: admin
+    origins: ["https://*.admin.example.com"]
+    methods: [GET, POST, DELETE]
+    headers: [content-type, authorization]
+    credentials: true
+    max_age: 60
+"#).unwrap();
+        CorsPolicies::new(&config).unwrap()
+    }
+
+    #[test]
+    fn test_origins_select_their_policy() {
+        let policies = policies();
+        assert_eq!(policies.policy_for("https://app.example.com").unwrap().name, "default");
+        let admin = policies.policy_for("https://ops.admin.example.com").unwrap();
+        assert_eq!(admin.name, "admin");
+        assert!(admin.allows_method("delete") && admin.allows_headers("Authorization, Content-Type"));
+        assert!(!policies.policy_for("https://app.example.com").unwrap().allows_method("DELETE"));
+        assert!(policies.policy_for("https://admin.example.com").is_none());
+        assert!(policies.policy_for("http://ops.admin.example.com").is_none());
+        assert!(policies.policy_for("https://evil.com").is_none());
+    }
+
+    #[test]
+    fn test_invalid_policies_are_rejected() {
+        let config: CorsConfig = serde_yaml::from_str("policies:\n  - origins: []\n").unwrap();
+        assert!(CorsPolicies::new(&config).unwrap_err().to_string().contains("'policy_0' must list at least one origin"));
+        let config: CorsConfig = serde_yaml::from_str("headers: [\"bad header\"]\n").unwrap();
+        assert!(CorsPolicies::new(&config).is_err());
+        let config: CorsConfig = serde_yaml::from_str("credentials: true\n").unwrap();
+        assert!(CorsPolicies::new(&config).unwrap_err().to_string().contains("'default' allows credentials from any origin"));
+        let config: CorsConfig = serde_yaml::from_str("origins: [\"https://app.example.com\"]\npolicies:\n  - name: open\n    origins: [\"*\"]\n    credentials: true\n").unwrap();
+        assert!(CorsPolicies::new(&config).unwrap_err().to_string().contains("'open' allows credentials"));
+    }
+}
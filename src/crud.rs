@@ -0,0 +1,551 @@
+//! CRUD routes of endpoints with `database.auto_crud`
+//!
+//! The table behind each such endpoint is introspected through its database
+//! plugin when the blueprint is loaded. The endpoint's path then lists and
+//! creates rows, and `{path}/{id}` reads, replaces, updates and deletes the
+//! row with that primary key, for the methods the endpoint lists. Request
+//! bodies, filters and keys are checked against the column types before the
+//! plugin sees them.
+
+use crate::config::{BackworksConfig, EndpointConfig, ExecutionMode};
+use crate::error::{BackworksError, ErrorSource, RequestError, Result};
+use crate::plugin::{Column, ListQuery, PluginManager, Row, SortKey, TableSchema};
+use crate::routes::RoutePattern;
+use crate::server::AppState;
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, patch, post, put, MethodRouter};
+use axum::Json;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Rows a list returns when the request does not ask for a number
+const DEFAULT_LIMIT: usize = 50;
+/// Most rows a list returns
+const MAX_LIMIT: usize = 1000;
+/// Query parameters that are not column filters
+const RESERVED_PARAMS: [&str; 4] = ["limit", "offset", "page", "sort"];
+
+/// Whether `endpoint` is served by the CRUD routes of its table
+pub fn is_auto_crud(endpoint: &EndpointConfig) -> bool {
+    endpoint.database.as_ref().and_then(|database| database.auto_crud).unwrap_or(false)
+}
+
+/// The route of a single row below an endpoint's path
+pub fn item_path(path: &str) -> String {
+    format!("{}/{{id}}", path.trim_end_matches('/'))
+}
+
+/// Routes of an auto-CRUD endpoint with the methods each serves
+pub fn routes(endpoint: &EndpointConfig) -> Vec<(String, Vec<String>)> {
+    let serves = |method: &str| endpoint.methods.iter().any(|m| m.eq_ignore_ascii_case(method));
+    let collection: Vec<String> = ["GET", "POST"].into_iter().filter(|m| serves(m)).map(String::from).collect();
+    let item: Vec<String> = ["GET", "PUT", "PATCH", "DELETE"].into_iter().filter(|m| serves(m)).map(String::from).collect();
+    [(endpoint.path.clone(), collection), (item_path(&endpoint.path), item)]
+        .into_iter()
+        .filter(|(_, methods)| !methods.is_empty())
+        .collect()
+}
+
+/// Check the settings of auto-CRUD endpoints
+pub fn validate(config: &BackworksConfig) -> Result<()> {
+    for (name, endpoint) in config.endpoints.iter().filter(|(_, endpoint)| is_auto_crud(endpoint)) {
+        if !matches!(endpoint.primary_mode(&config.mode), ExecutionMode::Database) {
+            return Err(BackworksError::config(format!(
+                "Endpoint '{}' sets database.auto_crud but does not run in database mode", name
+            )));
+        }
+        if endpoint.database.as_ref().is_some_and(|database| database.queries.is_some()) {
+            return Err(BackworksError::config(format!(
+                "Endpoint '{}' sets database.auto_crud, which serves its table without `queries`", name
+            )));
+        }
+        if RoutePattern::parse(&endpoint.path).params().next().is_some() {
+            return Err(BackworksError::config(format!(
+                "Endpoint '{}' sets database.auto_crud, so its path cannot take parameters", name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The table behind an auto-CRUD endpoint
+#[derive(Debug, Clone)]
+pub struct CrudTable {
+    path: String,
+    /// Database plugin the endpoint names, if any
+    plugin: Option<String>,
+    schema: TableSchema,
+    /// Keys wrapping list and single-row responses
+    list_key: String,
+    single_key: Option<String>,
+}
+
+/// Tables of the blueprint's auto-CRUD endpoints, by endpoint name
+#[derive(Debug, Clone, Default)]
+pub struct CrudTables {
+    tables: HashMap<String, Arc<CrudTable>>,
+}
+
+impl CrudTables {
+    /// Introspect the table of every auto-CRUD endpoint in `config`
+    pub async fn load(config: &BackworksConfig, plugins: &PluginManager) -> Result<Self> {
+        let mut tables = HashMap::new();
+        for (name, endpoint) in config.endpoints.iter().filter(|(_, endpoint)| is_auto_crud(endpoint)) {
+            let settings = endpoint.database.as_ref().expect("auto_crud is set under database");
+            let table = settings.table.clone().unwrap_or_else(|| name.clone());
+            let database = plugins.database(endpoint.plugin.as_deref()).await
+                .map_err(|e| BackworksError::config(format!("Endpoint '{}' sets database.auto_crud: {}", name, e)))?;
+            let schema = database.table_schema(&table).await
+                .map_err(|e| BackworksError::config(format!("Endpoint '{}' cannot read table '{}': {}", name, table, e)))?;
+            if schema.primary_key().is_none() {
+                return Err(BackworksError::config(format!(
+                    "Endpoint '{}' sets database.auto_crud, but table '{}' has no single primary key column", name, table
+                )));
+            }
+            let transform = settings.transform.as_ref();
+            tables.insert(name.clone(), Arc::new(CrudTable {
+                path: endpoint.path.clone(),
+                plugin: endpoint.plugin.clone(),
+                schema,
+                list_key: transform.and_then(|t| t.list.clone()).unwrap_or_else(|| "data".to_string()),
+                single_key: transform.and_then(|t| t.single.clone()),
+            }));
+        }
+        Ok(Self { tables })
+    }
+
+    pub fn get(&self, endpoint: &str) -> Option<Arc<CrudTable>> {
+        self.tables.get(endpoint).cloned()
+    }
+
+    /// Router of each route of `endpoint`, the table of endpoint `name`
+    pub fn method_routers(&self, name: &str, endpoint: &EndpointConfig) -> Result<Vec<(String, MethodRouter<AppState>)>> {
+        let table = self.get(name).ok_or_else(|| BackworksError::config(format!(
+            "Endpoint '{}' sets database.auto_crud, but its table was not loaded", name
+        )))?;
+        Ok(routes(endpoint).into_iter().map(|(path, methods)| {
+            let mut router = MethodRouter::new();
+            let item = path != endpoint.path;
+            for method in methods {
+                let table = table.clone();
+                router = match (method.as_str(), item) {
+                    ("GET", false) => router.merge(get(move |state, query| list_rows(table, state, query))),
+                    ("POST", _) => router.merge(post(move |state, body| create_row(table, state, body))),
+                    ("GET", true) => router.merge(get(move |state, id| read_row(table, state, id))),
+                    ("PUT", _) => router.merge(put(move |state, id, body| update_row(table, state, id, body, Write::Replace))),
+                    ("PATCH", _) => router.merge(patch(move |state, id, body| update_row(table, state, id, body, Write::Update))),
+                    _ => router.merge(delete(move |state, id| delete_row(table, state, id))),
+                };
+            }
+            (RoutePattern::parse(&path).router_path(), router)
+        }).collect())
+    }
+}
+
+async fn list_rows(table: Arc<CrudTable>, State(state): State<AppState>, Query(params): Query<HashMap<String, String>>) -> Response {
+    let query = match table.list_query(&params) {
+        Ok(query) => query,
+        Err(message) => return rejection(StatusCode::BAD_REQUEST, message),
+    };
+    let page = match table.database(&state).await {
+        Ok(database) => database.list(&table.schema.table, &query).await,
+        Err(e) => Err(e),
+    };
+    match page {
+        Ok(page) => {
+            let mut body = serde_json::Map::new();
+            body.insert(table.list_key.clone(), Value::Array(page.rows.into_iter().map(Value::Object).collect()));
+            body.insert("total".to_string(), json!(page.total));
+            body.insert("limit".to_string(), json!(query.limit));
+            body.insert("offset".to_string(), json!(query.offset));
+            Json(Value::Object(body)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn read_row(table: Arc<CrudTable>, State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    let key = match table.key(&id) {
+        Ok(key) => key,
+        Err(response) => return *response,
+    };
+    match table.database(&state).await {
+        Ok(database) => match database.get(&table.schema.table, &key).await {
+            Ok(Some(row)) => Json(table.single(row)).into_response(),
+            Ok(None) => table.missing(&id),
+            Err(e) => e.into_response(),
+        },
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn create_row(table: Arc<CrudTable>, State(state): State<AppState>, body: Bytes) -> Response {
+    let row = match table.row(&body, Write::Create) {
+        Ok(row) => row,
+        Err(message) => return rejection(StatusCode::BAD_REQUEST, message),
+    };
+    let created = match table.database(&state).await {
+        Ok(database) => database.insert(&table.schema.table, row).await,
+        Err(e) => Err(e),
+    };
+    match created {
+        Ok(row) => {
+            let location = table.schema.primary_key()
+                .and_then(|key| row.get(&key.name))
+                .map(|key| match key {
+                    Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                })
+                .and_then(|key| HeaderValue::from_str(&format!("{}/{}", table.path.trim_end_matches('/'), key)).ok());
+            let mut response = (StatusCode::CREATED, Json(table.single(row))).into_response();
+            if let Some(location) = location {
+                response.headers_mut().insert(header::LOCATION, location);
+            }
+            response
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn update_row(table: Arc<CrudTable>, State(state): State<AppState>, Path(id): Path<String>, body: Bytes, write: Write) -> Response {
+    let key = match table.key(&id) {
+        Ok(key) => key,
+        Err(response) => return *response,
+    };
+    let mut changes = match table.row(&body, write) {
+        Ok(changes) => changes,
+        Err(message) => return rejection(StatusCode::BAD_REQUEST, message),
+    };
+    // The key may be repeated in the body, but not changed
+    let key_column = table.key_column();
+    match changes.remove(&key_column.name) {
+        Some(ref value) if *value != key => {
+            return rejection(StatusCode::BAD_REQUEST, format!("Column '{}' cannot be changed", key_column.name));
+        }
+        _ => {}
+    }
+    match table.database(&state).await {
+        Ok(database) => match database.update(&table.schema.table, &key, changes).await {
+            Ok(Some(row)) => Json(table.single(row)).into_response(),
+            Ok(None) => table.missing(&id),
+            Err(e) => e.into_response(),
+        },
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn delete_row(table: Arc<CrudTable>, State(state): State<AppState>, Path(id): Path<String>) -> Response {
+    let key = match table.key(&id) {
+        Ok(key) => key,
+        Err(response) => return *response,
+    };
+    match table.database(&state).await {
+        Ok(database) => match database.delete(&table.schema.table, &key).await {
+            Ok(true) => StatusCode::NO_CONTENT.into_response(),
+            Ok(false) => table.missing(&id),
+            Err(e) => e.into_response(),
+        },
+        Err(e) => e.into_response(),
+    }
+}
+
+impl CrudTable {
+    async fn database(&self, state: &AppState) -> Result<crate::plugin::DatabaseHandle> {
+        state.plugin_manager.database(self.plugin.as_deref()).await
+    }
+
+    fn key_column(&self) -> &Column {
+        self.schema.primary_key().expect("checked when the table was loaded")
+    }
+
+    /// The primary key a path names
+    fn key(&self, id: &str) -> std::result::Result<Value, Box<Response>> {
+        let column = self.key_column();
+        column.column_type.parse(id).ok_or_else(|| Box::new(rejection(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid {} '{}': expected {}", column.name, id, type_name(column)),
+        )))
+    }
+
+    fn missing(&self, id: &str) -> Response {
+        rejection(StatusCode::NOT_FOUND, format!("No row of {} with {} '{}'", self.schema.table, self.key_column().name, id))
+    }
+
+    /// A row as a single-row response
+    fn single(&self, row: Row) -> Value {
+        match self.single_key {
+            Some(ref key) => json!({ key.as_str(): row }),
+            None => Value::Object(row),
+        }
+    }
+
+    /// Filters, order and page a list request asks for
+    fn list_query(&self, params: &HashMap<String, String>) -> std::result::Result<ListQuery, String> {
+        let number = |name: &str| params.get(name)
+            .map(|value| value.parse::<usize>().map_err(|_| format!("Query parameter '{}' must be a non-negative integer", name)))
+            .transpose();
+        let limit = number("limit")?.unwrap_or(DEFAULT_LIMIT);
+        if limit == 0 || limit > MAX_LIMIT {
+            return Err(format!("Query parameter 'limit' must be between 1 and {}", MAX_LIMIT));
+        }
+        let offset = match (number("offset")?, number("page")?) {
+            (Some(_), Some(_)) => return Err("Use either 'offset' or 'page', not both".to_string()),
+            (Some(offset), None) => offset,
+            (None, Some(0)) => return Err("Query parameter 'page' starts at 1".to_string()),
+            (None, Some(page)) => (page - 1).saturating_mul(limit),
+            (None, None) => 0,
+        };
+
+        let mut sort = Vec::new();
+        for key in params.get("sort").into_iter().flat_map(|sort| sort.split(',')).map(str::trim).filter(|key| !key.is_empty()) {
+            let (column, descending) = match key.strip_prefix('-') {
+                Some(column) => (column, true),
+                None => (key.strip_prefix('+').unwrap_or(key), false),
+            };
+            if self.schema.column(column).is_none() {
+                return Err(format!("Cannot sort by unknown column '{}'", column));
+            }
+            sort.push(SortKey { column: column.to_string(), descending });
+        }
+
+        let mut filters = Vec::new();
+        for (name, text) in params.iter().filter(|(name, _)| !RESERVED_PARAMS.contains(&name.as_str())) {
+            let column = self.schema.column(name).ok_or_else(|| format!("Cannot filter by unknown column '{}'", name))?;
+            let value = match column.column_type.parse(text) {
+                Some(value) => value,
+                None if column.nullable && text == "null" => Value::Null,
+                None => return Err(format!("Filter '{}' must be {}", name, type_name(column))),
+            };
+            filters.push((name.clone(), value));
+        }
+        // Query parameters come unordered; plugins see filters by column
+        filters.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(ListQuery { filters, sort, limit, offset })
+    }
+
+    /// A request body checked against the columns
+    fn row(&self, body: &[u8], write: Write) -> std::result::Result<Row, String> {
+        let value: Value = serde_json::from_slice(body).map_err(|e| format!("Invalid JSON body: {}", e))?;
+        let Value::Object(row) = value else {
+            return Err("The body must be a JSON object".to_string());
+        };
+        check_row(&self.schema, &row, write)?;
+        Ok(row)
+    }
+}
+
+/// Check the columns and values of `row` against `schema`
+pub(crate) fn check_row(schema: &TableSchema, row: &Row, write: Write) -> std::result::Result<(), String> {
+    for (name, value) in row {
+        let column = schema.column(name).ok_or_else(|| format!("Unknown column '{}'", name))?;
+        if value.is_null() {
+            if !column.nullable {
+                return Err(format!("Column '{}' cannot be null", name));
+            }
+        } else if !column.column_type.accepts(value) {
+            return Err(format!("Column '{}' must be {}", name, type_name(column)));
+        }
+    }
+    if write != Write::Update {
+        // A replaced row keeps its key from the path
+        if let Some(missing) = schema.columns.iter()
+            .filter(|column| write == Write::Create || !column.primary_key)
+            .find(|column| !column.nullable && !column.has_default && !row.contains_key(&column.name))
+        {
+            return Err(format!("Missing column '{}'", missing.name));
+        }
+    }
+    Ok(())
+}
+
+/// What a request body is written as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Write {
+    /// A new row, with every required column
+    Create,
+    /// A full row in place of an existing one
+    Replace,
+    /// Some columns of an existing row
+    Update,
+}
+
+pub(crate) fn type_name(column: &Column) -> &'static str {
+    match column.column_type {
+        crate::plugin::ColumnType::Integer => "an integer",
+        crate::plugin::ColumnType::Real => "a number",
+        crate::plugin::ColumnType::Text => "a string",
+        crate::plugin::ColumnType::Boolean => "a boolean",
+        crate::plugin::ColumnType::Timestamp => "an RFC 3339 timestamp",
+        crate::plugin::ColumnType::Json => "a JSON value",
+    }
+}
+
+fn rejection(status: StatusCode, message: String) -> Response {
+    let mut response = (status, Json(json!({"error": message, "status": status.as_u16()}))).into_response();
+    response.extensions_mut().insert(RequestError::new(status, message, ErrorSource::Handler));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::{BackworksPlugin, ColumnType, DatabasePlugin, RowPage};
+    use crate::server::BackworksServer;
+    use async_trait::async_trait;
+    use axum::body::Body;
+    use axum::Router;
+    use std::sync::Mutex;
+    use tower::ServiceExt;
+
+    #[derive(Default)]
+    struct People {
+        rows: Mutex<Vec<Row>>,
+    }
+
+    #[async_trait]
+    impl BackworksPlugin for People {
+        fn name(&self) -> &str { "people" }
+        fn version(&self) -> &str { "0.0.0" }
+        fn description(&self) -> &str { "keeps people in memory" }
+        async fn initialize(&self, _config: &Value) -> Result<()> { Ok(()) }
+        async fn shutdown(&self) -> Result<()> { Ok(()) }
+        fn database(&self) -> Option<&dyn DatabasePlugin> { Some(self) }
+    }
+
+    #[async_trait]
+    impl DatabasePlugin for People {
+        async fn table_schema(&self, table: &str) -> Result<TableSchema> {
+            Ok(TableSchema::new(table, vec![
+                Column::new("id", ColumnType::Integer).primary_key(),
+                Column::new("name", ColumnType::Text),
+                Column::new("age", ColumnType::Integer).nullable(),
+                Column::new("active", ColumnType::Boolean).with_default(),
+            ]))
+        }
+
+        async fn list(&self, _table: &str, query: &ListQuery) -> Result<RowPage> {
+            let mut rows: Vec<Row> = self.rows.lock().unwrap().iter()
+                .filter(|row| query.filters.iter().all(|(column, value)| row.get(column) == Some(value)))
+                .cloned()
+                .collect();
+            if let Some(key) = query.sort.first() {
+                rows.sort_by_key(|row| row[&key.column].to_string());
+                if key.descending {
+                    rows.reverse();
+                }
+            }
+            let total = rows.len() as u64;
+            Ok(RowPage { rows: rows.into_iter().skip(query.offset).take(query.limit).collect(), total })
+        }
+
+        async fn get(&self, _table: &str, key: &Value) -> Result<Option<Row>> {
+            Ok(self.rows.lock().unwrap().iter().find(|row| row["id"] == *key).cloned())
+        }
+
+        async fn insert(&self, _table: &str, mut row: Row) -> Result<Row> {
+            let mut rows = self.rows.lock().unwrap();
+            row.insert("id".to_string(), json!(rows.len() + 1));
+            row.entry("active").or_insert(json!(true));
+            rows.push(row.clone());
+            Ok(row)
+        }
+
+        async fn update(&self, _table: &str, key: &Value, changes: Row) -> Result<Option<Row>> {
+            let mut rows = self.rows.lock().unwrap();
+            Ok(rows.iter_mut().find(|row| row["id"] == *key).map(|row| {
+                row.extend(changes);
+                row.clone()
+            }))
+        }
+
+        async fn delete(&self, _table: &str, key: &Value) -> Result<bool> {
+            let mut rows = self.rows.lock().unwrap();
+            let before = rows.len();
+            rows.retain(|row| row["id"] != *key);
+            Ok(rows.len() < before)
+        }
+    }
+
+    async fn call(app: &Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Option<String>, Value) {
+        let request = axum::http::Request::builder().method(method).uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.map(|body| Body::from(body.to_string())).unwrap_or_default())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let location = response.headers().get(header::LOCATION).map(|value| value.to_str().unwrap().to_string());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, location, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_auto_crud_endpoints_serve_their_table() {
+        let config: BackworksConfig = serde_yaml::from_str(r#"
+name: crud
+mode: database
+endpoints:
+  people:
+    path: /people
+    methods: [GET, POST, PUT, PATCH, DELETE]
+    database:
+      auto_crud: true
+"#).unwrap();
+        crate::config::validate_config(&config).unwrap();
+        let manager = PluginManager::new();
+        manager.register_plugin(Arc::new(People::default()), None, None).await.unwrap();
+        let crud = CrudTables::load(&config, &manager).await.unwrap();
+        let app = BackworksServer::new(Arc::new(config), manager, None).unwrap()
+            .with_crud_tables(crud)
+            .create_app().unwrap();
+
+        let (status, location, ada) = call(&app, "POST", "/people", Some(json!({"name": "Ada", "age": 36}))).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(location.as_deref(), Some("/people/1"));
+        assert_eq!(ada, json!({"id": 1, "name": "Ada", "age": 36, "active": true}));
+        call(&app, "POST", "/people", Some(json!({"name": "Grace", "age": null, "active": false}))).await;
+        call(&app, "POST", "/people", Some(json!({"name": "Linus", "age": 21}))).await;
+
+        // Bodies are checked against the column types
+        let (status, _, body) = call(&app, "POST", "/people", Some(json!({"name": "Bob", "age": "old"}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "Column 'age' must be an integer");
+        let (status, _, body) = call(&app, "POST", "/people", Some(json!({"age": 3}))).await;
+        assert_eq!((status, body["error"].as_str()), (StatusCode::BAD_REQUEST, Some("Missing column 'name'")));
+        let (status, _, _) = call(&app, "POST", "/people", Some(json!({"name": "Eve", "email": "eve@example.com"}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Lists filter, sort and page
+        let (status, _, page) = call(&app, "GET", "/people?active=true&sort=-name&limit=1", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page, json!({"data": [{"id": 3, "name": "Linus", "age": 21, "active": true}], "total": 2, "limit": 1, "offset": 0}));
+        let (_, _, page) = call(&app, "GET", "/people?sort=name&limit=2&page=2", None).await;
+        assert_eq!((page["data"][0]["name"].as_str(), page["offset"].as_u64()), (Some("Linus"), Some(2)));
+        let (status, _, _) = call(&app, "GET", "/people?age=young", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _, _) = call(&app, "GET", "/people?sort=email", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Rows are read, replaced, updated and deleted by key
+        let (status, _, grace) = call(&app, "GET", "/people/2", None).await;
+        assert_eq!((status, grace["name"].as_str()), (StatusCode::OK, Some("Grace")));
+        let (status, _, _) = call(&app, "PUT", "/people/2", Some(json!({"age": 85}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _, grace) = call(&app, "PATCH", "/people/2", Some(json!({"age": 85}))).await;
+        assert_eq!((status, grace["age"].as_i64()), (StatusCode::OK, Some(85)));
+        let (status, _, _) = call(&app, "PATCH", "/people/2", Some(json!({"id": 7}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _, _) = call(&app, "GET", "/people/two", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _, _) = call(&app, "DELETE", "/people/2", None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _, _) = call(&app, "GET", "/people/2", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _, _) = call(&app, "DELETE", "/people/2", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,134 @@
+//! CSRF protection
+//!
+//! With `sessions.csrf`, every session holds a CSRF secret, and
+//! state-changing requests (anything but `GET`, `HEAD`, `OPTIONS` and
+//! `TRACE`) to endpoints must send the token derived from it: in the
+//! `X-CSRF-Token` header or, for form posts, the `_csrf` field. Requests
+//! without a valid token are answered with `403` before the handler runs.
+//! Endpoints opt out with `csrf: false`; webhook endpoints, whose senders
+//! sign their deliveries instead, are exempt.
+//!
+//! Handlers see the token as `req.csrf_token` and templates as
+//! `csrf_token`. HTML responses of endpoints get the token added as a
+//! hidden field to each form that posts, and with `cookie` set, scripts can
+//! read it from that cookie.
+
+use crate::config::{CsrfConfig, EndpointConfig};
+use crate::error::{BackworksError, ErrorSource, RequestError, Result};
+use crate::session::{Session, Sessions};
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderName, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use regex::Regex;
+use std::sync::{Arc, LazyLock};
+
+const DEFAULT_HEADER: &str = "x-csrf-token";
+const DEFAULT_FIELD: &str = "_csrf";
+
+static FORM_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<form\b[^>]*>").unwrap());
+static FORM_METHOD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(?i)\smethod\s*=\s*["']?([a-z]+)"#).unwrap());
+
+/// Where requests carry their CSRF token
+#[derive(Debug, Clone)]
+pub struct Csrf {
+    pub header: HeaderName,
+    pub field: String,
+    /// Cookie the token is readable from
+    pub cookie: Option<String>,
+}
+
+impl Csrf {
+    pub fn new(config: &CsrfConfig) -> Result<Self> {
+        let header = HeaderName::try_from(config.header.as_deref().unwrap_or(DEFAULT_HEADER))
+            .map_err(|e| BackworksError::config(format!("sessions.csrf.header: {}", e)))?;
+        Ok(Self {
+            header,
+            field: config.field.clone().unwrap_or_else(|| DEFAULT_FIELD.to_string()),
+            cookie: config.cookie.clone(),
+        })
+    }
+}
+
+/// Whether requests to `endpoint` need a token
+pub fn protects(endpoint: &EndpointConfig) -> bool {
+    endpoint.csrf != Some(false) && endpoint.webhook.is_none()
+}
+
+/// Route layer refusing state-changing requests without their session's
+/// token
+pub async fn verify_csrf(State(sessions): State<Arc<Sessions>>, request: Request, next: Next) -> Response {
+    let (Some(csrf), Some(session)) = (sessions.csrf(), request.extensions().get::<Session>().cloned()) else {
+        return next.run(request).await;
+    };
+    if matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE) {
+        return next.run(request).await;
+    }
+
+    let mut request = request;
+    let mut token = request.headers().get(&csrf.header).and_then(|value| value.to_str().ok()).map(String::from);
+    let form = request.headers().get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/x-www-form-urlencoded"));
+    if token.is_none() && form {
+        let (parts, body) = request.into_parts();
+        let body = match axum::body::to_bytes(body, usize::MAX).await {
+            Ok(body) => body,
+            Err(e) => return reject(StatusCode::BAD_REQUEST, format!("Request body could not be read: {}", e)),
+        };
+        token = serde_urlencoded::from_bytes::<Vec<(String, String)>>(&body).unwrap_or_default()
+            .into_iter()
+            .find(|(name, _)| *name == csrf.field)
+            .map(|(_, value)| value);
+        request = Request::from_parts(parts, Body::from(body));
+    }
+
+    match token {
+        None => reject(StatusCode::FORBIDDEN, "Missing CSRF token".to_string()),
+        Some(token) if !sessions.verify_csrf_token(&session, &token) => reject(StatusCode::FORBIDDEN, "Invalid CSRF token".to_string()),
+        Some(_) => next.run(request).await,
+    }
+}
+
+/// `html` with a hidden `field` holding `token` in each form that posts
+pub fn inject(html: &str, field: &str, token: &str) -> String {
+    FORM_TAG.replace_all(html, |captures: &regex::Captures| {
+        let tag = &captures[0];
+        let posts = FORM_METHOD.captures(tag).is_some_and(|method| !method[1].eq_ignore_ascii_case("get"));
+        if posts {
+            format!(r#"{}<input type="hidden" name="{}" value="{}">"#, tag, escape(field), token)
+        } else {
+            tag.to_string()
+        }
+    }).into_owned()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn reject(status: StatusCode, message: String) -> Response {
+    let mut response = (status, Json(serde_json::json!({"error": message, "status": status.as_u16()}))).into_response();
+    response.extensions_mut().insert(RequestError::new(status, message, ErrorSource::Framework));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_are_injected_into_forms_that_post() {
+        let html = r#"<form action="/search"><input name="q"></form>
+<FORM method="POST" action="/orders"></FORM>
+<form class="x" method='delete'></form>
+<form method=get></form>"#;
+        assert_eq!(inject(html, "_csrf", "abc"), r#"<form action="/search"><input name="q"></form>
+<FORM method="POST" action="/orders"><input type="hidden" name="_csrf" value="abc"></FORM>
+<form class="x" method='delete'><input type="hidden" name="_csrf" value="abc"></form>
+<form method=get></form>"#);
+        assert_eq!(inject("<p>formal</p>", "_csrf", "abc"), "<p>formal</p>");
+    }
+}
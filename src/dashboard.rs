@@ -1,26 +1,36 @@
-use crate::config::DashboardConfig;
+use crate::config::{BackworksConfig, ChaosConfig, DashboardConfig};
+use crate::architecture::{ArchitectureGraph, LiveHealth};
 use crate::error::{BackworksResult, BackworksError};
+use crate::auth::AuthContext;
+use crate::broadcast::{BroadcastHub, HubEvent, HubStats, Subscription};
+use crate::deprecation::{DeprecationReport, DeprecationUsage};
+use crate::rollout::{RolloutSchedule, RolloutStatus};
+use crate::scenario::{ScenarioStatus, ScenarioSwitch, Scenarios};
+use crate::chaos::{Chaos, ChaosStatus, ChaosSwitch};
+use crate::capture::{CaptureHandler, CaptureStreamFilter, CapturedRequest, RecordingSwitch};
+use crate::editor::{BlueprintDraft, BlueprintEditor, SaveError};
+use crate::admin::token_matches;
+use crate::studio::StudioAssets;
+use crate::plugin::routes::check_plugin_routes;
+use crate::plugin::{PluginManager, PluginRoutes};
+use crate::resilience::PluginMetrics;
+use crate::metrics_history::MetricsHistory;
+use crate::metrics_recorder::{MetricSource, MetricsRecorder, MetricsSnapshot};
+use crate::request_log::{LoggedRequest, RequestLog, RequestLogQuery, DEFAULT_REQUEST_LOG_SIZE, REQUEST_LOG_TOPIC};
 use axum::{
-    response::{Response, IntoResponse},
-    routing::{get, Router},
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    extract::Query,
+    response::{Response, IntoResponse, sse::{Event, KeepAlive, Sse}},
+    routing::{get, post, put, Router},
     http::{StatusCode, header},
     Json,
 };
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::convert::Infallible;
 use std::sync::Arc;
-use std::path::{Path, PathBuf};
-use tokio::sync::{broadcast, RwLock};
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct EndpointMetrics {
-    pub path: String,
-    pub method: String,
-    pub request_count: u64,
-    pub avg_response_time: f64,
-    pub error_rate: f64,
-    pub last_request: chrono::DateTime<chrono::Utc>,
-}
+use tokio::sync::RwLock;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemMetrics {
@@ -32,29 +42,105 @@ pub struct SystemMetrics {
     pub error_count: u64,
 }
 
+/// Hub topic plugin metrics are published on
+pub const PLUGINS_TOPIC: &str = "plugins";
+
+/// Event topics only streamed to callers presenting the admin token, since
+/// they carry request contents
+const ADMIN_TOPICS: &[&str] = &["capture", REQUEST_LOG_TOPIC];
+
+/// Milliseconds between periodic events when `real_time.update_frequency`
+/// is not set
+const DEFAULT_UPDATE_FREQUENCY_MS: u64 = 5000;
+
 #[derive(Debug, Clone)]
 pub struct DashboardState {
-    pub metrics: Arc<RwLock<HashMap<String, EndpointMetrics>>>,
+    pub metrics: MetricsRecorder,
     pub system_metrics: Arc<RwLock<SystemMetrics>>,
-    pub event_sender: broadcast::Sender<String>,
+    pub events: BroadcastHub,
+    pub rollouts: Arc<Vec<RolloutSchedule>>,
+    pub deprecations: DeprecationUsage,
+    pub scenarios: Scenarios,
+    pub chaos: Chaos,
+    pub capture: Option<CaptureHandler>,
+    pub editor: Option<BlueprintEditor>,
+    pub studio: Arc<StudioAssets>,
+    pub requests: RequestLog,
+    pub history: MetricsHistory,
+    pub plugins: Option<PluginManager>,
+    pub blueprint: Arc<std::sync::RwLock<Option<Arc<BackworksConfig>>>>,
+}
+
+impl DashboardState {
+    /// Whether a request presents the admin token of the blueprint being
+    /// served: as a bearer token, or as a `token` query parameter when
+    /// opening a WebSocket or event stream, which browsers cannot add
+    /// headers to
+    fn is_admin(&self, headers: &axum::http::HeaderMap, uri: &axum::http::Uri) -> bool {
+        let blueprint = self.blueprint.read().unwrap_or_else(|e| e.into_inner()).clone();
+        let Some(token) = blueprint.as_ref().and_then(|config| config.admin.as_ref()).map(|admin| admin.token.clone()) else {
+            return false;
+        };
+        let bearer = headers.get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::to_string);
+        let stream = headers.get(header::UPGRADE).and_then(|value| value.to_str().ok()).is_some_and(|value| value.eq_ignore_ascii_case("websocket"))
+            || headers.get(header::ACCEPT).and_then(|value| value.to_str().ok()).is_some_and(|value| value.contains("text/event-stream"));
+        let query = uri.query().filter(|_| stream).and_then(|query| {
+            url::form_urlencoded::parse(query.as_bytes()).find(|(name, _)| name == "token").map(|(_, value)| value.into_owned())
+        });
+        bearer.or(query).is_some_and(|presented| token_matches(&presented, &token))
+    }
+}
+
+/// Refuses requests without the admin token; routes that change what the API
+/// server does or show request contents sit behind it
+async fn require_admin(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Response {
+    if !state.is_admin(request.headers(), request.uri()) {
+        return BackworksError::unauthorized("This dashboard route requires the admin token").into_response();
+    }
+    next.run(request).await
 }
 
 pub struct Dashboard {
     config: DashboardConfig,
-    metrics: Arc<RwLock<HashMap<String, EndpointMetrics>>>,
+    metrics: MetricsRecorder,
     system_metrics: Arc<RwLock<SystemMetrics>>,
-    event_sender: broadcast::Sender<String>,
+    events: BroadcastHub,
+    rollouts: Arc<Vec<RolloutSchedule>>,
+    deprecations: DeprecationUsage,
+    scenarios: Scenarios,
+    chaos: Chaos,
+    capture: Option<CaptureHandler>,
+    editor: Option<BlueprintEditor>,
+    studio: Arc<StudioAssets>,
+    requests: RequestLog,
+    history: MetricsHistory,
+    plugins: Option<PluginManager>,
+    /// Routes plugins add, by plugin
+    plugin_pages: Vec<(String, PluginRoutes)>,
+    /// Blueprint the API server serves
+    blueprint: Arc<std::sync::RwLock<Option<Arc<BackworksConfig>>>>,
+    /// Certificate the dashboard is served over HTTPS with
+    tls: Option<Arc<crate::tls::Tls>>,
     #[allow(dead_code)] // TODO: Will be used for displaying uptime in dashboard
     start_time: chrono::DateTime<chrono::Utc>,
 }
 
 impl Dashboard {
     pub fn new(config: DashboardConfig) -> Self {
-        let (event_sender, _) = broadcast::channel(1000);
+        let events = BroadcastHub::from_config(config.real_time.as_ref());
+        let studio = Arc::new(StudioAssets::from_config(&config));
+        let requests = RequestLog::new(config.request_log_size.unwrap_or(DEFAULT_REQUEST_LOG_SIZE)).with_events(events.clone());
         
         Self {
             config,
-            metrics: Arc::new(RwLock::new(HashMap::new())),
+            metrics: MetricsRecorder::default(),
             system_metrics: Arc::new(RwLock::new(SystemMetrics {
                 uptime: 0,
                 memory_usage: 0,
@@ -63,24 +149,154 @@ impl Dashboard {
                 total_requests: 0,
                 error_count: 0,
             })),
-            event_sender,
+            events,
+            rollouts: Arc::new(Vec::new()),
+            deprecations: DeprecationUsage::default(),
+            scenarios: Scenarios::default(),
+            chaos: Chaos::default(),
+            capture: None,
+            editor: None,
+            studio,
+            requests,
+            history: MetricsHistory::default(),
+            plugins: None,
+            plugin_pages: Vec::new(),
+            blueprint: Arc::default(),
+            tls: None,
             start_time: chrono::Utc::now(),
         }
     }
 
+    /// Serve over HTTPS with the API server's certificate
+    pub fn with_tls(mut self, tls: Arc<crate::tls::Tls>) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Show the rollout status of these endpoints
+    pub fn with_rollouts(mut self, rollouts: Vec<RolloutSchedule>) -> Self {
+        self.rollouts = Arc::new(rollouts);
+        self
+    }
+
+    /// Track the remaining callers of deprecated endpoints
+    pub fn with_deprecations(mut self, deprecations: DeprecationUsage) -> Self {
+        self.deprecations = deprecations;
+        self
+    }
+
+    /// List scenarios and switch the active one
+    pub fn with_scenarios(mut self, scenarios: Scenarios) -> Self {
+        self.scenarios = scenarios;
+        self
+    }
+
+    /// Show and change the fault injection settings
+    pub fn with_chaos(mut self, chaos: Chaos) -> Self {
+        self.chaos = chaos;
+        self
+    }
+
+    /// Start and stop capture sessions and tail what they record
+    pub fn with_capture(mut self, capture: CaptureHandler) -> Self {
+        self.capture = Some(capture);
+        self
+    }
+
+    /// Read, review and save the blueprint the API server runs
+    pub fn with_editor(mut self, editor: BlueprintEditor) -> Self {
+        self.editor = Some(editor);
+        self
+    }
+
+    /// Report the metrics and health of these plugins
+    pub fn with_plugins(mut self, plugins: PluginManager) -> Self {
+        self.plugins = Some(plugins);
+        self
+    }
+
+    /// Serve the pages and APIs plugins add, each below `/plugins/{name}`
+    pub fn with_plugin_pages(mut self, pages: Vec<(String, PluginRoutes)>) -> BackworksResult<Self> {
+        check_plugin_routes(&pages, &[], &[])?;
+        self.plugin_pages = pages;
+        Ok(self)
+    }
+
+    /// Show the architecture of `config`, which the API server now serves
+    pub fn show_blueprint(&self, config: Arc<BackworksConfig>) {
+        *self.blueprint.write().unwrap_or_else(|e| e.into_inner()) = Some(config);
+    }
+
+    /// Metrics of endpoints, upstreams and plugins the dashboard reports
+    pub fn metrics(&self) -> MetricsRecorder {
+        self.metrics.clone()
+    }
+
+    /// Recent requests the request log shows
+    pub fn requests(&self) -> RequestLog {
+        self.requests.clone()
+    }
+
+    /// The hub the dashboard streams events from; capture sessions and other
+    /// producers publish to it too
+    pub fn events(&self) -> BroadcastHub {
+        self.events.clone()
+    }
+
     pub fn router(&self) -> Router {
         let dashboard_state = DashboardState {
             metrics: self.metrics.clone(),
             system_metrics: self.system_metrics.clone(),
-            event_sender: self.event_sender.clone(),
+            events: self.events.clone(),
+            rollouts: self.rollouts.clone(),
+            deprecations: self.deprecations.clone(),
+            scenarios: self.scenarios.clone(),
+            chaos: self.chaos.clone(),
+            capture: self.capture.clone(),
+            editor: self.editor.clone(),
+            studio: self.studio.clone(),
+            requests: self.requests.clone(),
+            history: self.history.clone(),
+            plugins: self.plugins.clone(),
+            blueprint: self.blueprint.clone(),
         };
 
-        Router::new()
+        let admin = Router::new()
+            .route("/api/scenarios", put(switch_scenario))
+            .route("/api/chaos", put(switch_chaos))
+            .route("/api/chaos/:endpoint", put(configure_chaos))
+            .route("/api/capture", put(switch_capture))
+            .route("/api/capture/stream", get(stream_capture))
+            .route("/api/requests", get(get_requests))
+            .route("/api/requests/stream", get(stream_requests))
+            .route_layer(axum::middleware::from_fn_with_state(dashboard_state.clone(), require_admin));
+        let mut router = Router::new()
             .route("/", get(serve_qwik_dashboard))
             .route("/api/system", get(get_system_info))
             .route("/api/metrics", get(get_api_metrics))
+            .route("/api/metrics/history", get(get_metrics_history))
+            .route("/api/metrics/sources", get(get_all_metrics))
+            .route("/api/plugins", get(get_plugins))
+            .route("/api/plugins/:name/health", get(get_plugin_health))
+            .route("/api/architecture", get(get_architecture))
+            .route("/api/rollouts", get(get_rollouts))
+            .route("/api/deprecations", get(get_deprecations))
+            .route("/api/scenarios", get(get_scenarios))
+            .route("/api/chaos", get(get_chaos))
+            .route("/capture", get(serve_capture_page))
+            .route("/api/capture", get(get_capture))
+            .route("/api/blueprint", get(get_blueprint).put(save_blueprint))
+            .route("/api/blueprint/review", post(review_blueprint))
+            .route("/api/events", get(stream_events))
+            .route("/api/events/ws", get(websocket_events))
+            .route("/api/events/stats", get(get_event_stats))
             .route("/build/*file", get(serve_static_files))
             .route("/assets/*file", get(serve_static_files))
+            .merge(admin);
+        for (plugin, pages) in &self.plugin_pages {
+            router = pages.mount(router, &format!("/plugins/{}", plugin));
+        }
+        router
             .fallback(serve_static_files)
             .with_state(dashboard_state)
     }
@@ -88,51 +304,47 @@ impl Dashboard {
     pub async fn start(&self) -> BackworksResult<()> {
         tracing::info!("Starting dashboard on port {}", self.config.port);
         
+        let publisher = self.plugins.clone().map(|plugins| tokio::spawn(publish_plugin_metrics(plugins, self.events.clone(), self.update_interval())));
+        
         let app = self.router();
         let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", self.config.port))
             .await
             .map_err(|e| BackworksError::Config(format!("Failed to bind dashboard to port {}: {}", self.config.port, e)))?;
             
-        tracing::info!("Dashboard server listening on http://0.0.0.0:{}", self.config.port);
-        
-        axum::serve(listener, app)
-            .await
-            .map_err(|e| BackworksError::Config(format!("Dashboard server error: {}", e)))?;
-        
-        Ok(())
+        let served = match self.tls {
+            Some(ref tls) => {
+                tracing::info!("Dashboard server listening on https://0.0.0.0:{}", self.config.port);
+                crate::tls::serve(listener, tls.acceptor(), app).await
+            }
+            None => {
+                tracing::info!("Dashboard server listening on http://0.0.0.0:{}", self.config.port);
+                axum::serve(listener, app)
+                    .await
+                    .map_err(|e| BackworksError::Config(format!("Dashboard server error: {}", e)))
+            }
+        };
+        if let Some(publisher) = publisher {
+            publisher.abort();
+        }
+        served
+    }
+
+    /// How often periodic events, such as plugin metrics, are published
+    fn update_interval(&self) -> std::time::Duration {
+        let millis = self.config.real_time.as_ref().and_then(|real_time| real_time.update_frequency).unwrap_or(DEFAULT_UPDATE_FREQUENCY_MS);
+        std::time::Duration::from_millis(millis.max(100))
     }
 
     pub async fn record_request(
         &self,
         method: &str,
         path: &str,
-        response_time: f64,
+        response_time: std::time::Duration,
         status_code: u16,
     ) -> BackworksResult<()> {
-        let key = format!("{} {}", method, path);
-        let mut metrics = self.metrics.write().await;
-        
-        if let Some(endpoint_metrics) = metrics.get_mut(&key) {
-            endpoint_metrics.request_count += 1;
-            endpoint_metrics.avg_response_time = 
-                (endpoint_metrics.avg_response_time + response_time) / 2.0;
-            
-            if status_code >= 400 {
-                endpoint_metrics.error_rate = 
-                    (endpoint_metrics.error_rate + 1.0) / endpoint_metrics.request_count as f64;
-            }
-            
-            endpoint_metrics.last_request = chrono::Utc::now();
-        } else {
-            metrics.insert(key.clone(), EndpointMetrics {
-                path: path.to_string(),
-                method: method.to_string(),
-                request_count: 1,
-                avg_response_time: response_time,
-                error_rate: if status_code >= 400 { 1.0 } else { 0.0 },
-                last_request: chrono::Utc::now(),
-            });
-        }
+        self.metrics.record_request(method, path, status_code, response_time);
+        let response_time = response_time.as_secs_f64() * 1000.0;
+        self.history.record(&format!("{} {}", method, path), status_code, response_time);
 
         // Update system metrics
         let mut system_metrics = self.system_metrics.write().await;
@@ -140,79 +352,36 @@ impl Dashboard {
         if status_code >= 400 {
             system_metrics.error_count += 1;
         }
+        drop(system_metrics);
+        
+        self.events.publish("requests", serde_json::json!({
+            "method": method,
+            "path": path,
+            "status": status_code,
+            "response_time": response_time,
+        }));
         
         Ok(())
     }
-}
 
-/// Find the studio directory by looking for it relative to the current working directory
-/// or relative to the executable location
-fn find_studio_path() -> BackworksResult<PathBuf> {
-    // Try current directory + studio first
-    let current_studio = Path::new("studio");
-    if current_studio.exists() && current_studio.join("dist").exists() {
-        return Ok(current_studio.to_path_buf());
-    }
-    
-    // Try parent directory + studio (for when running from examples/)
-    let parent_studio = Path::new("../studio");
-    if parent_studio.exists() && parent_studio.join("dist").exists() {
-        return Ok(parent_studio.to_path_buf());
-    }
-    
-    // Try two levels up + studio (for deeper nesting)
-    let grandparent_studio = Path::new("../../studio");
-    if grandparent_studio.exists() && grandparent_studio.join("dist").exists() {
-        return Ok(grandparent_studio.to_path_buf());
-    }
-    
-    // Try to find it relative to the executable
-    if let Ok(exe_path) = std::env::current_exe() {
-        if let Some(exe_dir) = exe_path.parent() {
-            // For cargo target/debug/backworks
-            let exe_studio = exe_dir.parent().unwrap_or(exe_dir).parent().unwrap_or(exe_dir).join("studio");
-            if exe_studio.exists() && exe_studio.join("dist").exists() {
-                return Ok(exe_studio);
-            }
-        }
+    /// Add an answered request to the request log
+    pub fn log_request(&self, request: LoggedRequest) {
+        self.requests.record(request);
     }
-    
-    Err(BackworksError::Server("Studio directory not found. Please ensure the studio is built with 'cd studio && npm run build'".to_string()))
-}
-
-// Route handlers for the Qwik dashboard
-async fn serve_qwik_dashboard() -> Response {
-    let studio_path = match find_studio_path() {
-        Ok(path) => path,
-        Err(e) => {
-            tracing::error!("Failed to find studio directory: {}", e);
-            return axum::response::Response::builder()
-                .status(503)
-                .header("content-type", "text/html")
-                .body("<h1>Studio Unavailable</h1><p>Please build the studio first: <code>cd studio && npm run build</code></p>".into())
-                .unwrap();
-        }
-    };
-    
-    let index_path = studio_path.join("dist/index.html");
-    match std::fs::read_to_string(&index_path) {
-        Ok(content) => {
-            axum::response::Response::builder()
-                .header("content-type", "text/html")
-                .body(content.into())
-                .unwrap()
-        },
-        Err(e) => {
-            tracing::error!("Failed to load studio dashboard from {:?}: {}. Please run 'npm run build' in the studio directory.", index_path, e);
-            axum::response::Response::builder()
-                .status(503)
-                .header("content-type", "text/html")
-                .body("<h1>Studio Unavailable</h1><p>Please build the studio first: <code>cd studio && npm run build</code></p>".into())
-                .unwrap()
-        }
+
+    /// Count a call to a deprecated endpoint against its caller
+    pub async fn record_deprecated_call(&self, endpoint: &str, auth: Option<&AuthContext>) {
+        self.deprecations.record(endpoint, auth).await;
     }
 }
 
+// The Studio's entry page
+async fn serve_qwik_dashboard(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+) -> Response {
+    state.studio.index()
+}
+
 async fn get_system_info(
     axum::extract::State(state): axum::extract::State<DashboardState>,
 ) -> Json<serde_json::Value> {
@@ -235,29 +404,429 @@ async fn get_system_info(
 async fn get_api_metrics(
     axum::extract::State(state): axum::extract::State<DashboardState>,
 ) -> Json<Vec<serde_json::Value>> {
-    let metrics = state.metrics.read().await;
-    let endpoint_metrics = metrics.values().map(|m| {
-        let last_accessed = match chrono::Utc::now().signed_duration_since(m.last_request).num_minutes() {
+    let endpoint_metrics = state.metrics.snapshots().into_iter().filter_map(|m| {
+        let MetricSource::Endpoint { ref method, ref path } = m.source else {
+            return None;
+        };
+        let last_accessed = match chrono::Utc::now().signed_duration_since(m.last_recorded).num_minutes() {
             0 => "Just now".to_string(),
             n if n < 60 => format!("{} minutes ago", n),
             n => format!("{} hours ago", n / 60),
         };
         
-        serde_json::json!({
-            "method": m.method,
-            "path": m.path,
-            "request_count": m.request_count,
-            "avg_response_time": m.avg_response_time,
+        Some(serde_json::json!({
+            "method": method,
+            "path": path,
+            "request_count": m.requests,
+            "error_count": m.errors,
+            "error_rate": m.error_rate,
+            "avg_response_time": m.avg_latency_ms,
+            "ema_response_time": m.ema_latency_ms,
+            "p50_response_time": m.p50_latency_ms,
+            "p95_response_time": m.p95_latency_ms,
+            "p99_response_time": m.p99_latency_ms,
             "last_accessed": last_accessed
-        })
+        }))
     }).collect();
     
     Json(endpoint_metrics)
 }
 
+// Metrics of every endpoint, upstream target and plugin measurement
+async fn get_all_metrics(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+) -> Json<Vec<MetricsSnapshot>> {
+    Json(state.metrics.snapshots())
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    /// How far back, such as `30m`, `6h` or `7d` (default `1h`)
+    range: Option<String>,
+    /// Endpoint key, such as `GET /users`; every endpoint when absent
+    endpoint: Option<String>,
+}
+
+// Request counts, error rates and latency percentiles over time
+async fn get_metrics_history(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+    Query(query): Query<HistoryQuery>,
+) -> Response {
+    let range = match crate::config::parse_duration(query.range.as_deref().unwrap_or("1h")) {
+        Ok(range) => range,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e.to_string() }))).into_response(),
+    };
+    match state.history.report(range, query.endpoint.as_deref()) {
+        Some(report) => Json(report).into_response(),
+        None => (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": "Metrics history goes back 7 days" }))).into_response(),
+    }
+}
+
+// Invocations, failures, latency and circuit state of every plugin
+async fn get_plugins(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+) -> Json<Vec<PluginMetrics>> {
+    let Some(ref plugins) = state.plugins else {
+        return Json(Vec::new());
+    };
+    Json(sorted_plugin_metrics(plugins).await)
+}
+
+// Run a plugin's health check
+async fn get_plugin_health(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+) -> Response {
+    let health = match state.plugins {
+        Some(ref plugins) => plugins.get_plugin_health(&name).await.map(|health| (health, plugins)),
+        None => None,
+    };
+    match health {
+        Some((health, plugins)) => Json(serde_json::json!({
+            "plugin": name,
+            "health": health,
+            "metrics": plugins.get_plugin_metrics(&name).await,
+        })).into_response(),
+        None => (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": format!("Unknown plugin '{}'", name) }))).into_response(),
+    }
+}
+
+async fn sorted_plugin_metrics(plugins: &PluginManager) -> Vec<PluginMetrics> {
+    let mut metrics: Vec<PluginMetrics> = plugins.get_all_plugin_metrics().await.into_values().collect();
+    metrics.sort_by(|a, b| a.plugin_name.cmp(&b.plugin_name));
+    metrics
+}
+
+// Publish plugin metrics on the `plugins` topic whenever they changed
+async fn publish_plugin_metrics(plugins: PluginManager, events: BroadcastHub, interval: std::time::Duration) {
+    let mut ticks = tokio::time::interval(interval);
+    let mut published = None;
+    loop {
+        ticks.tick().await;
+        let metrics = serde_json::to_value(sorted_plugin_metrics(&plugins).await).unwrap_or_default();
+        if published.as_ref() != Some(&metrics) {
+            events.publish(PLUGINS_TOPIC, metrics.clone());
+            published = Some(metrics);
+        }
+    }
+}
+
+// Graph of the served blueprint, colored by live health
+async fn get_architecture(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+) -> Response {
+    let blueprint = state.blueprint.read().unwrap_or_else(|e| e.into_inner()).clone();
+    let Some(config) = blueprint else {
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({ "error": "The API server is not running" }))).into_response();
+    };
+    let mut live = LiveHealth { metrics: state.metrics.snapshots(), ..Default::default() };
+    if let Some(ref plugins) = state.plugins {
+        live.plugins = plugins.get_all_plugin_health().await;
+        live.plugin_metrics = plugins.get_all_plugin_metrics().await;
+    }
+    Json(ArchitectureGraph::from_config(&config).with_health(&live)).into_response()
+}
+
+async fn get_rollouts(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+) -> Json<Vec<RolloutStatus>> {
+    let now = chrono::Utc::now();
+    Json(state.rollouts.iter().map(|schedule| schedule.status(now)).collect())
+}
+
+async fn get_deprecations(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+) -> Json<Vec<DeprecationReport>> {
+    Json(state.deprecations.report(chrono::Utc::now().date_naive()).await)
+}
+
+async fn get_scenarios(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+) -> Json<ScenarioStatus> {
+    Json(state.scenarios.status().await)
+}
+
+async fn switch_scenario(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+    Json(switch): Json<ScenarioSwitch>,
+) -> Response {
+    match state.scenarios.switch(switch).await {
+        Ok(status) => Json(status).into_response(),
+        Err(message) => (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": message }))).into_response(),
+    }
+}
+
+async fn get_chaos(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+) -> Json<ChaosStatus> {
+    Json(state.chaos.status().await)
+}
+
+async fn switch_chaos(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+    Json(switch): Json<ChaosSwitch>,
+) -> Json<ChaosStatus> {
+    Json(state.chaos.set_enabled(switch.enabled).await)
+}
+
+async fn configure_chaos(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+    axum::extract::Path(endpoint): axum::extract::Path<String>,
+    Json(config): Json<ChaosConfig>,
+) -> Response {
+    match state.chaos.configure(&endpoint, config).await {
+        Ok(status) => Json(status).into_response(),
+        Err((status, message)) => (status, Json(serde_json::json!({ "error": message }))).into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    /// Comma-separated topics; all topics when absent
+    topics: Option<String>,
+}
+
+impl EventsQuery {
+    /// Subscribe to the topics asked for; without the admin token, asking for
+    /// an admin topic is refused and they are left out of all topics
+    fn subscribe(&self, events: &BroadcastHub, admin: bool) -> Result<Subscription, Box<Response>> {
+        let topics = self.topics.as_ref()
+            .map(|topics| topics.split(',').map(|topic| topic.trim().to_string()).filter(|topic| !topic.is_empty()).collect::<HashSet<_>>());
+        if !admin && topics.as_ref().is_some_and(|topics| topics.iter().any(|topic| ADMIN_TOPICS.contains(&topic.as_str()))) {
+            return Err(Box::new(BackworksError::unauthorized(format!("The {} topics require the admin token", ADMIN_TOPICS.join(", "))).into_response()));
+        }
+        events.subscribe(topics).map_err(|e| Box::new((StatusCode::SERVICE_UNAVAILABLE, e.to_string()).into_response()))
+    }
+}
+
+/// Whether an event may go to a subscriber, by whether it presented the admin token
+fn visible(event: &HubEvent, admin: bool) -> bool {
+    admin || !ADMIN_TOPICS.contains(&event.topic.as_str())
+}
+
+async fn stream_events(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+    Query(query): Query<EventsQuery>,
+    headers: axum::http::HeaderMap,
+    uri: axum::http::Uri,
+) -> Response {
+    let admin = state.is_admin(&headers, &uri);
+    let subscription = match query.subscribe(&state.events, admin) {
+        Ok(subscription) => subscription,
+        Err(response) => return *response,
+    };
+    let stream = subscription.into_stream().filter(move |event| std::future::ready(visible(event, admin))).map(|event| {
+        Ok::<_, Infallible>(Event::default().event(event.topic.as_str()).data(serde_json::to_string(&*event).unwrap_or_default()))
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default()).into_response()
+}
+
+async fn websocket_events(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+    Query(query): Query<EventsQuery>,
+    headers: axum::http::HeaderMap,
+    uri: axum::http::Uri,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    let admin = state.is_admin(&headers, &uri);
+    match query.subscribe(&state.events, admin) {
+        Ok(subscription) => upgrade.on_upgrade(move |socket| forward_events(socket, subscription, move |event| {
+            visible(event, admin).then(|| serde_json::to_string(event).ok()).flatten()
+        })),
+        Err(response) => *response,
+    }
+}
+
+fn capture_disabled() -> Response {
+    (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": "Capture is disabled" }))).into_response()
+}
+
+async fn get_capture(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+) -> Response {
+    match state.capture {
+        Some(ref capture) => Json(capture.recording_status().await).into_response(),
+        None => capture_disabled(),
+    }
+}
+
+async fn switch_capture(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+    Json(switch): Json<RecordingSwitch>,
+) -> Response {
+    let Some(ref capture) = state.capture else {
+        return capture_disabled();
+    };
+    match capture.switch_recording(switch, "dashboard").await {
+        Ok(status) => Json(status).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": e.to_string() }))).into_response(),
+    }
+}
+
+fn editor_disabled() -> Response {
+    (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": "Blueprint editing is disabled" }))).into_response()
+}
+
+fn editor_failed(e: BackworksError) -> Response {
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": e.to_string() }))).into_response()
+}
+
+async fn get_blueprint(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+) -> Response {
+    let Some(ref editor) = state.editor else {
+        return editor_disabled();
+    };
+    match editor.read().await {
+        Ok(document) => Json(document).into_response(),
+        Err(e) => editor_failed(e),
+    }
+}
+
+// Validation, diff and changelog of a draft, without saving it
+async fn review_blueprint(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+    Json(draft): Json<BlueprintDraft>,
+) -> Response {
+    let Some(ref editor) = state.editor else {
+        return editor_disabled();
+    };
+    match editor.review(&draft).await {
+        Ok(review) => Json(review).into_response(),
+        Err(e) => editor_failed(e),
+    }
+}
+
+// Save a valid draft and reload the API server; saved blueprints can run
+// inline handlers, so this always needs the admin token
+async fn save_blueprint(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+    headers: axum::http::HeaderMap,
+    Json(draft): Json<BlueprintDraft>,
+) -> Response {
+    let Some(ref editor) = state.editor else {
+        return editor_disabled();
+    };
+    let presented = headers.get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !editor.admin_token().zip(presented).is_some_and(|(token, presented)| token_matches(presented, &token)) {
+        return BackworksError::unauthorized("Saving the blueprint requires the admin token").into_response();
+    }
+    match editor.save(&draft).await {
+        Ok(saved) => Json(saved).into_response(),
+        Err(SaveError::Conflict(current)) => (StatusCode::CONFLICT, Json(serde_json::json!({
+            "error": "The blueprint changed since the draft was loaded",
+            "current": current,
+        }))).into_response(),
+        Err(SaveError::Invalid(review)) => (StatusCode::UNPROCESSABLE_ENTITY, Json(review)).into_response(),
+        Err(SaveError::Failed(e)) => editor_failed(e),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CaptureStreamQuery {
+    /// Comma-separated methods
+    method: Option<String>,
+    /// Glob the path matches
+    path: Option<String>,
+    /// Comma-separated codes (`404`), classes (`5xx`) or ranges (`200-299`)
+    status: Option<String>,
+}
+
+async fn stream_capture(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+    Query(query): Query<CaptureStreamQuery>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    if state.capture.is_none() {
+        return capture_disabled();
+    }
+    let filter = match CaptureStreamFilter::parse(query.method.as_deref(), query.path.as_deref(), query.status.as_deref()) {
+        Ok(filter) => filter,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e.to_string() }))).into_response(),
+    };
+    let subscription = match state.events.subscribe(Some(HashSet::from(["capture".to_string()]))) {
+        Ok(subscription) => subscription,
+        Err(e) => return (StatusCode::SERVICE_UNAVAILABLE, e.to_string()).into_response(),
+    };
+    // Requests are published once on arrival and again with their response;
+    // the stream sends each completed exchange once
+    upgrade.on_upgrade(move |socket| forward_events(socket, subscription, move |event| {
+        let request: CapturedRequest = serde_json::from_value(event.data.clone()).ok()?;
+        filter.matches(&request).then(|| serde_json::to_string(&request).ok()).flatten()
+    }))
+}
+
+// Recent requests, newest first, filtered and a page at a time
+async fn get_requests(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+    Query(query): Query<RequestLogQuery>,
+) -> Response {
+    match query.filter() {
+        Ok(filter) => Json(state.requests.page(&query, &filter)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e.to_string() }))).into_response(),
+    }
+}
+
+// Requests passing the filters, as they are answered
+async fn stream_requests(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+    Query(query): Query<RequestLogQuery>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    let filter = match query.filter() {
+        Ok(filter) => filter,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e.to_string() }))).into_response(),
+    };
+    let subscription = match state.events.subscribe(Some(HashSet::from([REQUEST_LOG_TOPIC.to_string()]))) {
+        Ok(subscription) => subscription,
+        Err(e) => return (StatusCode::SERVICE_UNAVAILABLE, e.to_string()).into_response(),
+    };
+    upgrade.on_upgrade(move |socket| forward_events(socket, subscription, move |event| {
+        let entry = serde_json::from_value(event.data.clone()).ok()?;
+        query.matches(&filter, &entry).then(|| serde_json::to_string(&entry).ok()).flatten()
+    }))
+}
+
+async fn serve_capture_page() -> axum::response::Html<&'static str> {
+    axum::response::Html(CAPTURE_PAGE)
+}
+
+// A socket that stops reading only backs up its own queue in the hub
+async fn forward_events(mut socket: WebSocket, mut subscription: Subscription, render: impl Fn(&HubEvent) -> Option<String>) {
+    loop {
+        tokio::select! {
+            event = subscription.recv() => {
+                let Some(event) = event else {
+                    let _ = socket.send(Message::Close(None)).await;
+                    break;
+                };
+                let Some(text) = render(&event) else {
+                    continue;
+                };
+                if socket.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+async fn get_event_stats(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
+) -> Json<HubStats> {
+    Json(state.events.stats())
+}
+
 async fn serve_static_files(
+    axum::extract::State(state): axum::extract::State<DashboardState>,
     uri: axum::http::Uri,
-) -> impl IntoResponse {
+) -> Response {
     let path = uri.path();
     
     // Check if this is a static file by extension or path
@@ -271,42 +840,206 @@ async fn serve_static_files(
         return (StatusCode::NOT_FOUND, "File not found").into_response();
     }
     
-    // Find studio path and map to dist directory
-    let studio_path = match find_studio_path() {
-        Ok(path) => path,
-        Err(_) => {
-            return (StatusCode::SERVICE_UNAVAILABLE, "Studio not available").into_response();
-        }
-    };
-    
-    let file_path = studio_path.join("dist").join(path.strip_prefix("/").unwrap_or(path));
-    
-    // Determine content type
-    let content_type = match std::path::Path::new(&file_path)
-        .extension()
-        .and_then(|ext| ext.to_str())
-    {
-        Some("js") => "application/javascript",
-        Some("css") => "text/css",
-        Some("html") => "text/html",
-        Some("png") => "image/png",
-        Some("jpg") | Some("jpeg") => "image/jpeg",
-        Some("svg") => "image/svg+xml",
-        Some("ico") => "image/x-icon",
-        _ => "application/octet-stream",
-    };
-    
-    // Serve the file
-    match std::fs::read(&file_path) {
-        Ok(content) => {
-            (
-                StatusCode::OK,
-                [(header::CONTENT_TYPE, content_type)],
-                content,
-            ).into_response()
-        }
-        Err(_) => {
-            (StatusCode::NOT_FOUND, "File not found").into_response()
+    state.studio.serve(path)
+}
+
+/// Live tail of the capture stream, served at `/capture`
+const CAPTURE_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Backworks · Live capture</title>
+<style>
+  body { font: 14px system-ui, sans-serif; margin: 0; }
+  header { display: flex; gap: 8px; align-items: center; padding: 12px; background: #f4f4f5; border-bottom: 1px solid #ddd; }
+  header input { padding: 4px 6px; }
+  table { width: 100%; border-collapse: collapse; }
+  td, th { padding: 4px 12px; text-align: left; border-bottom: 1px solid #eee; white-space: nowrap; }
+  tbody tr { cursor: pointer; }
+  tbody tr:hover { background: #fafafa; }
+  .s2 { color: #15803d; } .s3 { color: #1d4ed8; } .s4 { color: #b45309; } .s5 { color: #b91c1c; }
+  pre { margin: 0; padding: 8px 12px; background: #fafafa; white-space: pre-wrap; }
+  #state { margin-left: auto; color: #666; }
+</style>
+</head>
+<body>
+<header>
+  <input id="token" type="password" placeholder="Admin token">
+  <button id="record"></button>
+  <input id="method" placeholder="Methods: GET,POST">
+  <input id="path" placeholder="Path: /api/*">
+  <input id="status" placeholder="Status: 4xx,500">
+  <button id="apply">Filter</button>
+  <button id="clear">Clear</button>
+  <span id="state"></span>
+</header>
+<table>
+  <thead><tr><th>Time</th><th>Method</th><th>Path</th><th>Status</th><th>Duration</th></tr></thead>
+  <tbody id="rows"></tbody>
+</table>
+<script>
+const $ = (id) => document.getElementById(id);
+let socket, recording = false;
+$("token").value = sessionStorage.getItem("backworks-admin-token") || "";
+
+async function capture(body) {
+  const headers = { "content-type": "application/json", "authorization": "Bearer " + $("token").value };
+  const response = await fetch("api/capture", body ? { method: "PUT", headers, body: JSON.stringify(body) } : {});
+  const status = await response.json();
+  if (!response.ok) { $("state").textContent = status.error; return; }
+  recording = status.recording;
+  $("record").textContent = recording ? "Stop recording" : "Start recording";
+}
+
+function connect() {
+  if (socket) { socket.onclose = null; socket.close(); }
+  const query = new URLSearchParams({ token: $("token").value });
+  for (const filter of ["method", "path", "status"]) {
+    if ($(filter).value.trim()) query.set(filter, $(filter).value.trim());
+  }
+  const url = new URL("api/capture/stream?" + query, location.href);
+  url.protocol = url.protocol.replace("http", "ws");
+  socket = new WebSocket(url);
+  socket.onopen = () => $("state").textContent = "Streaming";
+  socket.onclose = () => { $("state").textContent = "Disconnected, retrying..."; setTimeout(connect, 2000); };
+  socket.onmessage = (message) => show(JSON.parse(message.data));
+}
+
+function show(exchange) {
+  const status = exchange.response.status_code;
+  const duration = exchange.duration ? (exchange.duration.secs * 1000 + exchange.duration.nanos / 1e6).toFixed(1) + " ms" : "";
+  const row = $("rows").insertRow(0);
+  for (const text of [new Date(exchange.timestamp).toLocaleTimeString(), exchange.method, exchange.path, status, duration]) {
+    row.insertCell().textContent = text;
+  }
+  row.cells[3].className = "s" + String(status)[0];
+  row.onclick = () => {
+    if (row.nextSibling && row.nextSibling.detail) { row.nextSibling.remove(); return; }
+    const detail = $("rows").insertRow(row.rowIndex);
+    detail.detail = true;
+    const cell = detail.insertCell();
+    cell.colSpan = 5;
+    cell.innerHTML = "<pre></pre>";
+    cell.firstChild.textContent = JSON.stringify({ request: { headers: exchange.headers, query: exchange.query_params, body: exchange.body }, response: exchange.response }, null, 2);
+  };
+}
+
+$("record").onclick = () => capture({ recording: !recording });
+$("token").onchange = () => { sessionStorage.setItem("backworks-admin-token", $("token").value); connect(); };
+$("apply").onclick = connect;
+$("clear").onclick = () => $("rows").replaceChildren();
+capture();
+connect();
+</script>
+</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::{BackworksPlugin, HealthStatus};
+    use tower::ServiceExt;
+
+    struct FailingPlugin;
+
+    #[async_trait::async_trait]
+    impl BackworksPlugin for FailingPlugin {
+        fn name(&self) -> &str { "failing" }
+        fn version(&self) -> &str { "0.0.0" }
+        fn description(&self) -> &str { "fails every request" }
+        async fn initialize(&self, _config: &serde_json::Value) -> BackworksResult<()> { Ok(()) }
+        async fn shutdown(&self) -> BackworksResult<()> { Ok(()) }
+        async fn before_request(&self, _request: &mut axum::extract::Request) -> BackworksResult<()> {
+            Err(BackworksError::server("lookup failed"))
         }
     }
+
+    async fn get_json(router: Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let response = router.oneshot(axum::http::Request::get(uri).body(axum::body::Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        (status, serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_plugin_metrics_and_health_are_reported_and_published() {
+        let plugins = PluginManager::new();
+        plugins.register_plugin(Arc::new(FailingPlugin), None, None).await.unwrap();
+        let mut request = axum::extract::Request::new(axum::body::Body::empty());
+        assert!(plugins.before_request(&mut request).await.is_ok());
+        let dashboard = Dashboard::new(serde_yaml::from_str("enabled: true").unwrap()).with_plugins(plugins.clone());
+
+        let (_, metrics) = get_json(dashboard.router(), "/api/plugins").await;
+        assert_eq!(metrics[0]["plugin_name"], "failing");
+        assert_eq!((metrics[0]["total_invocations"].as_u64(), metrics[0]["failed_invocations"].as_u64()), (Some(1), Some(1)));
+        assert!(metrics[0]["last_execution_time_ms"].as_f64().is_some());
+
+        let (status, health) = get_json(dashboard.router(), "/api/plugins/failing/health").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(serde_json::from_value::<HealthStatus>(health["health"]["status"].clone()).unwrap(), HealthStatus::Healthy);
+        assert_eq!(health["metrics"]["total_invocations"], 2);
+        assert_eq!(get_json(dashboard.router(), "/api/plugins/missing/health").await.0, StatusCode::NOT_FOUND);
+
+        // Metrics go out on the hub when they change
+        let mut events = dashboard.events().subscribe(Some(HashSet::from([PLUGINS_TOPIC.to_string()]))).unwrap();
+        let publisher = tokio::spawn(publish_plugin_metrics(plugins, dashboard.events(), std::time::Duration::from_millis(10)));
+        assert_eq!(events.recv().await.unwrap().data[0]["plugin_name"], "failing");
+        publisher.abort();
+    }
+
+    async fn send(router: Router, request: axum::http::request::Builder, body: &str) -> StatusCode {
+        router.oneshot(request.header(header::CONTENT_TYPE, "application/json").body(axum::body::Body::from(body.to_string())).unwrap()).await.unwrap().status()
+    }
+
+    fn served_with_token(dashboard: &Dashboard) {
+        dashboard.show_blueprint(Arc::new(serde_yaml::from_str(
+            "name: shop\nadmin: { token: secret }\nendpoints:\n  users: { path: /users, mode: mock, mock: { schema: { name: $name } } }\n",
+        ).unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_switching_scenarios_needs_the_admin_token() {
+        let dashboard = Dashboard::new(serde_yaml::from_str("enabled: true").unwrap());
+        served_with_token(&dashboard);
+        let switch = r#"{"scenario": null}"#;
+        assert_eq!(send(dashboard.router(), axum::http::Request::put("/api/scenarios"), switch).await, StatusCode::UNAUTHORIZED);
+        let admin = axum::http::Request::put("/api/scenarios").header(header::AUTHORIZATION, "Bearer secret");
+        assert_eq!(send(dashboard.router(), admin, switch).await, StatusCode::OK);
+        assert_eq!(get_json(dashboard.router(), "/api/scenarios").await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_changing_faults_needs_the_admin_token() {
+        let dashboard = Dashboard::new(serde_yaml::from_str("enabled: true").unwrap());
+        served_with_token(&dashboard);
+        let switch = r#"{"enabled": false}"#;
+        assert_eq!(send(dashboard.router(), axum::http::Request::put("/api/chaos"), switch).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(dashboard.router(), axum::http::Request::put("/api/chaos/users"), r#"{"error_rate": 1.0}"#).await, StatusCode::UNAUTHORIZED);
+        let admin = axum::http::Request::put("/api/chaos").header(header::AUTHORIZATION, "Bearer secret");
+        assert_eq!(send(dashboard.router(), admin, switch).await, StatusCode::OK);
+        assert_eq!(get_json(dashboard.router(), "/api/chaos").await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_capture_control_needs_the_admin_token() {
+        let dashboard = Dashboard::new(serde_yaml::from_str("enabled: true").unwrap())
+            .with_capture(CaptureHandler::new(serde_yaml::from_str("enabled: true").unwrap()));
+        let switch = r#"{"recording": true}"#;
+        // Without a token in the blueprint nobody may switch recording
+        assert_eq!(send(dashboard.router(), axum::http::Request::put("/api/capture"), switch).await, StatusCode::UNAUTHORIZED);
+
+        served_with_token(&dashboard);
+        assert_eq!(send(dashboard.router(), axum::http::Request::put("/api/capture"), switch).await, StatusCode::UNAUTHORIZED);
+        let wrong = axum::http::Request::put("/api/capture").header(header::AUTHORIZATION, "Bearer wrong");
+        assert_eq!(send(dashboard.router(), wrong, switch).await, StatusCode::UNAUTHORIZED);
+        let admin = axum::http::Request::put("/api/capture").header(header::AUTHORIZATION, "Bearer secret");
+        assert_eq!(send(dashboard.router(), admin, switch).await, StatusCode::OK);
+        assert_eq!(get_json(dashboard.router(), "/api/capture").await.1["recording"], true);
+
+        // Nor can the stream or the capture event topic be read without it
+        assert_eq!(send(dashboard.router(), axum::http::Request::get("/api/capture/stream"), "").await, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(dashboard.router(), axum::http::Request::get("/api/events?topics=capture"), "").await, StatusCode::UNAUTHORIZED);
+        let events = axum::http::Request::get("/api/events?topics=capture&token=secret").header(header::ACCEPT, "text/event-stream");
+        assert_eq!(send(dashboard.router(), events, "").await, StatusCode::OK);
+    }
 }
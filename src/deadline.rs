@@ -0,0 +1,137 @@
+//! Request time budgets
+//!
+//! `server.request_timeout` gives every request a total time budget, an
+//! endpoint's `timeout` replaces it for that endpoint, and a client can set
+//! or shorten its own with the `X-Request-Timeout` header. The handler is
+//! dropped when the budget runs out, cancelling whatever it waits on, and
+//! downstream work gets whatever is left of it: proxied upstream calls are
+//! cut off when the budget runs out and pass the remainder on in their own
+//! `X-Request-Timeout`, and database plugins are cancelled when it runs out
+//! and see the remainder as `timeout_ms` in their request data. A request
+//! whose budget runs out while it waits is answered with `504`.
+
+use crate::config::parse_duration;
+use crate::error::{BackworksError, Result};
+use axum::http::HeaderMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+pub const REQUEST_TIMEOUT_HEADER: &str = "x-request-timeout";
+
+/// The instant a request's budget runs out
+///
+/// Serializes as the milliseconds left, so handlers see the remaining budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+    pub fn after(budget: Duration) -> Self {
+        Self(Instant::now() + budget)
+    }
+
+    /// The budget of a request arriving now: the configured one, shortened
+    /// by the client's `X-Request-Timeout`
+    pub fn for_request(configured: Option<Duration>, headers: &HeaderMap) -> Option<Self> {
+        let requested = headers.get(REQUEST_TIMEOUT_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_header);
+        let budget = match (configured, requested) {
+            (Some(configured), Some(requested)) => configured.min(requested),
+            (configured, requested) => configured.or(requested)?,
+        };
+        Some(Self::after(budget))
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_duration_since(Instant::now())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// `timeout`, shortened to the remaining budget
+    pub fn limit(&self, timeout: Duration) -> Duration {
+        timeout.min(self.remaining())
+    }
+
+    /// Run `work` until it finishes or the budget runs out, dropping it then
+    pub async fn run<T>(deadline: Option<Self>, what: &str, work: impl Future<Output = Result<T>>) -> Result<T> {
+        let Some(deadline) = deadline else {
+            return work.await;
+        };
+        match tokio::time::timeout(deadline.remaining(), work).await {
+            Ok(result) => result,
+            Err(_) => Err(deadline.exceeded(what)),
+        }
+    }
+
+    /// The error of work cancelled by this deadline
+    pub fn exceeded(&self, what: &str) -> BackworksError {
+        BackworksError::timeout(format!("request time budget ran out waiting for {}", what))
+    }
+}
+
+/// Milliseconds when bare (`2500`), else a duration such as `2.5s`
+fn parse_header(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    match value.parse::<u64>() {
+        Ok(ms) => Some(Duration::from_millis(ms)),
+        Err(_) => parse_duration(value).ok(),
+    }
+}
+
+impl Serialize for Deadline {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.remaining().as_millis() as u64)
+    }
+}
+
+impl<'de> Deserialize<'de> for Deadline {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        Ok(Self::after(Duration::from_millis(u64::deserialize(deserializer)?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(configured: Option<u64>, header: Option<&str>) -> Option<u128> {
+        let mut headers = HeaderMap::new();
+        if let Some(value) = header {
+            headers.insert(REQUEST_TIMEOUT_HEADER, value.parse().unwrap());
+        }
+        // Round up the time spent since the deadline was set
+        Deadline::for_request(configured.map(Duration::from_millis), &headers)
+            .map(|deadline| deadline.remaining().as_millis().div_ceil(100) * 100)
+    }
+
+    #[test]
+    fn test_client_can_only_shorten_the_configured_budget() {
+        assert_eq!(budget(None, None), None);
+        assert_eq!(budget(Some(5_000), None), Some(5_000));
+        assert_eq!(budget(None, Some("1500")), Some(1_500));
+        assert_eq!(budget(None, Some("2s")), Some(2_000));
+        assert_eq!(budget(Some(5_000), Some("200ms")), Some(200));
+        assert_eq!(budget(Some(5_000), Some("1m")), Some(5_000));
+        assert_eq!(budget(Some(5_000), Some("soon")), Some(5_000));
+    }
+
+    #[tokio::test]
+    async fn test_work_is_cancelled_when_the_budget_runs_out() {
+        let deadline = Deadline::after(Duration::from_millis(20));
+        assert!(deadline.limit(Duration::from_secs(30)) <= Duration::from_millis(20));
+        assert_eq!(deadline.limit(Duration::from_millis(1)), Duration::from_millis(1));
+        let slow = async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        };
+        let error = Deadline::run(Some(deadline), "the database", slow).await.unwrap_err();
+        assert!(matches!(error, BackworksError::Timeout(_)));
+        assert!(deadline.is_expired());
+        assert_eq!(serde_json::to_value(deadline).unwrap(), 0);
+        assert_eq!(Deadline::run(None, "the database", async { Ok(1) }).await.unwrap(), 1);
+    }
+}
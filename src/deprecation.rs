@@ -0,0 +1,202 @@
+//! Endpoint deprecation
+//!
+//! An endpoint marked `deprecated` keeps working, but every response tells
+//! clients so: `Deprecation` carries the date it was deprecated (RFC 9745),
+//! `Sunset` the date it goes away (RFC 8594) and `Link` the migration guide.
+//! Calls are counted per caller, the authenticated subject such as an API
+//! key's name, so the dashboard shows who still has to migrate.
+
+use crate::auth::{AuthContext, AuthMethod};
+use crate::config::{BackworksConfig, DeprecationConfig};
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Caller recorded for requests without an authenticated identity
+pub const ANONYMOUS_CALLER: &str = "anonymous";
+
+/// An endpoint together with its deprecation notice
+#[derive(Debug, Clone)]
+pub struct DeprecatedEndpoint {
+    pub endpoint: String,
+    pub path: String,
+    pub config: DeprecationConfig,
+}
+
+impl DeprecatedEndpoint {
+    /// Collect every endpoint marked deprecated
+    pub fn from_config(config: &BackworksConfig) -> Vec<Self> {
+        let mut deprecated: Vec<Self> = config.endpoints.iter()
+            .filter_map(|(name, endpoint)| {
+                endpoint.deprecated.as_ref().map(|deprecation| Self {
+                    endpoint: name.clone(),
+                    path: endpoint.path.clone(),
+                    config: deprecation.clone(),
+                })
+            })
+            .collect();
+        deprecated.sort_by(|a, b| a.endpoint.cmp(&b.endpoint));
+        deprecated
+    }
+}
+
+/// Add the `Deprecation`, `Sunset` and `Link` headers for `config`
+pub fn apply_headers(config: &DeprecationConfig, headers: &mut HeaderMap) {
+    let deprecation = match config.since {
+        Some(since) => HeaderValue::from_str(&format!("@{}", start_of_day(since).timestamp())).ok(),
+        None => Some(HeaderValue::from_static("true")),
+    };
+    if let Some(deprecation) = deprecation {
+        headers.insert(HeaderName::from_static("deprecation"), deprecation);
+    }
+    if let Some(sunset) = config.sunset {
+        let date = httpdate::fmt_http_date(start_of_day(sunset).into());
+        if let Ok(sunset) = HeaderValue::from_str(&date) {
+            headers.insert(HeaderName::from_static("sunset"), sunset);
+        }
+    }
+    if let Some(link) = config.link.as_deref().and_then(|link| HeaderValue::from_str(&format!("<{}>; rel=\"deprecation\"", link)).ok()) {
+        headers.append(axum::http::header::LINK, link);
+    }
+}
+
+fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()
+}
+
+/// Whether the sunset date of `config` is on or before `today`
+pub fn sunset_passed(config: &DeprecationConfig, today: NaiveDate) -> bool {
+    config.sunset.is_some_and(|sunset| sunset <= today)
+}
+
+/// Calls one caller made to a deprecated endpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CallerUsage {
+    pub caller: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_method: Option<AuthMethod>,
+    pub requests: u64,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Remaining callers of one deprecated endpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeprecationReport {
+    pub endpoint: String,
+    pub path: String,
+    pub since: Option<NaiveDate>,
+    pub sunset: Option<NaiveDate>,
+    pub link: Option<String>,
+    pub message: Option<String>,
+    /// Days left until the sunset date; negative once it has passed
+    pub days_until_sunset: Option<i64>,
+    /// Callers by most recent call first
+    pub callers: Vec<CallerUsage>,
+}
+
+/// Calls to deprecated endpoints by caller
+#[derive(Debug, Clone, Default)]
+pub struct DeprecationUsage {
+    endpoints: Arc<Vec<DeprecatedEndpoint>>,
+    callers: Arc<RwLock<HashMap<String, HashMap<String, CallerUsage>>>>,
+}
+
+impl DeprecationUsage {
+    pub fn new(endpoints: Vec<DeprecatedEndpoint>) -> Self {
+        Self { endpoints: Arc::new(endpoints), callers: Default::default() }
+    }
+
+    pub async fn record(&self, endpoint: &str, auth: Option<&AuthContext>) {
+        let now = Utc::now();
+        let caller = auth.map_or(ANONYMOUS_CALLER, |auth| auth.subject.as_str());
+        let mut callers = self.callers.write().await;
+        let usage = callers.entry(endpoint.to_string()).or_default()
+            .entry(caller.to_string())
+            .or_insert_with(|| CallerUsage {
+                caller: caller.to_string(),
+                auth_method: auth.map(|auth| auth.method),
+                requests: 0,
+                first_seen: now,
+                last_seen: now,
+            });
+        usage.requests += 1;
+        usage.last_seen = now;
+    }
+
+    pub async fn report(&self, today: NaiveDate) -> Vec<DeprecationReport> {
+        let callers = self.callers.read().await;
+        self.endpoints.iter().map(|deprecated| {
+            let mut usage: Vec<CallerUsage> = callers.get(&deprecated.endpoint)
+                .map(|callers| callers.values().cloned().collect())
+                .unwrap_or_default();
+            usage.sort_by(|a, b| b.last_seen.cmp(&a.last_seen).then_with(|| a.caller.cmp(&b.caller)));
+            let config = &deprecated.config;
+            DeprecationReport {
+                endpoint: deprecated.endpoint.clone(),
+                path: deprecated.path.clone(),
+                since: config.since,
+                sunset: config.sunset,
+                link: config.link.clone(),
+                message: config.message.clone(),
+                days_until_sunset: config.sunset.map(|sunset| (sunset - today).num_days()),
+                callers: usage,
+            }
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> DeprecationConfig {
+        serde_yaml::from_str("since: 2025-06-01\nsunset: 2025-12-31\nlink: https://docs.example.com/v2\n").unwrap()
+    }
+
+    #[test]
+    fn test_headers_announce_deprecation_and_sunset() {
+        let mut headers = HeaderMap::new();
+        apply_headers(&config(), &mut headers);
+        assert_eq!(headers["deprecation"], "@1748736000");
+        assert_eq!(headers["sunset"], "Wed, 31 Dec 2025 00:00:00 GMT");
+        assert_eq!(headers["link"], "<https://docs.example.com/v2>; rel=\"deprecation\"");
+
+        let mut headers = HeaderMap::new();
+        apply_headers(&DeprecationConfig::default(), &mut headers);
+        assert_eq!(headers["deprecation"], "true");
+        assert!(headers.get("sunset").is_none());
+
+        assert!(!sunset_passed(&config(), NaiveDate::from_ymd_opt(2025, 12, 30).unwrap()));
+        assert!(sunset_passed(&config(), NaiveDate::from_ymd_opt(2025, 12, 31).unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_usage_reports_callers_per_endpoint() {
+        let usage = DeprecationUsage::new(vec![DeprecatedEndpoint {
+            endpoint: "v1_users".to_string(),
+            path: "/v1/users".to_string(),
+            config: config(),
+        }]);
+        let partner = AuthContext {
+            subject: "partner-key".to_string(),
+            method: AuthMethod::ApiKey,
+            email: None,
+            roles: Vec::new(),
+            claims: HashMap::new(),
+        };
+        usage.record("v1_users", Some(&partner)).await;
+        usage.record("v1_users", Some(&partner)).await;
+        usage.record("v1_users", None).await;
+
+        let report = usage.report(NaiveDate::from_ymd_opt(2025, 12, 1).unwrap()).await;
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].days_until_sunset, Some(30));
+        let callers: Vec<_> = report[0].callers.iter().map(|c| (c.caller.as_str(), c.requests)).collect();
+        assert!(callers.contains(&("partner-key", 2)) && callers.contains(&(ANONYMOUS_CALLER, 1)));
+        assert_eq!(report[0].callers.iter().find(|c| c.requests == 2).unwrap().auth_method, Some(AuthMethod::ApiKey));
+    }
+}
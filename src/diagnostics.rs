@@ -0,0 +1,477 @@
+//! Blueprint parsing diagnostics
+//!
+//! Blueprints are deserialized through a tracking layer that records keys the
+//! target structs do not declare, so typos surface as warnings with a
+//! suggestion instead of being silently dropped. Errors and warnings carry the
+//! YAML path of the offending value and, when it can be found, the file, line
+//! and column it was written at.
+
+use crate::blueprint::ResolvedBlueprint;
+use crate::config::{BackworksConfig, NewBlueprintConfig};
+use crate::error::{BackworksError, Result};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::forward_to_deserialize_any;
+use serde_yaml::Value;
+use std::cell::RefCell;
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Where a diagnostic points in the blueprint sources (1-based)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub path: Vec<Segment>,
+    pub message: String,
+    pub suggestion: Option<String>,
+    pub location: Option<Location>,
+}
+
+impl Diagnostic {
+    /// Dotted path such as `endpoints[2].method`
+    pub fn path_string(&self) -> String {
+        format_path(&self.path)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{}:{}:{}: ", location.file.display(), location.line, location.column)?;
+        }
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path_string())?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (did you mean `{}`?)", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// Blueprint layout, decided by the shape of `endpoints`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlueprintFormat {
+    /// `endpoints:` is a list
+    List,
+    /// `endpoints:` is a map of named endpoints
+    Legacy,
+}
+
+pub fn detect_format(value: &Value) -> Result<BlueprintFormat> {
+    match value.get("endpoints") {
+        Some(Value::Sequence(_)) => Ok(BlueprintFormat::List),
+        Some(Value::Mapping(_)) | None => Ok(BlueprintFormat::Legacy),
+        Some(_) => Err(BackworksError::config(
+            "endpoints: expected a list of endpoints or a map of named endpoints",
+        )),
+    }
+}
+
+/// Parsed configuration plus non-fatal findings
+#[derive(Debug)]
+pub struct ParsedBlueprint {
+    pub config: BackworksConfig,
+    pub format: BlueprintFormat,
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Parse a merged blueprint, locating diagnostics in its source files
+pub fn parse_resolved(resolved: &ResolvedBlueprint) -> Result<ParsedBlueprint> {
+    parse(resolved.value.clone(), &resolved.sources)
+}
+
+pub fn parse(value: Value, sources: &[PathBuf]) -> Result<ParsedBlueprint> {
+    let format = detect_format(&value)?;
+    let (config, mut unknown) = match format {
+        BlueprintFormat::List => {
+            let (config, unknown) = deserialize_tracked::<NewBlueprintConfig>(value);
+            (config.map(NewBlueprintConfig::to_backworks_config), unknown)
+        }
+        BlueprintFormat::Legacy => deserialize_tracked::<BackworksConfig>(value),
+    };
+
+    let locator = Locator::new(sources);
+    for diagnostic in &mut unknown {
+        diagnostic.location = locator.locate(&diagnostic.path);
+    }
+
+    match config {
+        Ok(config) => Ok(ParsedBlueprint { config, format, warnings: unknown }),
+        Err(mut error) => {
+            // A required field reported missing next to a misspelled key is
+            // almost always the same field
+            if let Some(missing) = missing_field(&error.message) {
+                if let Some(typo) = unknown.iter().find(|d| d.suggestion.as_deref() == Some(missing)) {
+                    error.message = format!("{} (found `{}` instead)", error.message, typo.path.last().map(segment_name).unwrap_or_default());
+                    error.location = typo.location.clone();
+                }
+            }
+            if error.location.is_none() {
+                error.location = locator.locate(&error.path);
+            }
+            Err(BackworksError::config(error))
+        }
+    }
+}
+
+fn missing_field(message: &str) -> Option<&str> {
+    message.strip_prefix("missing field `")?.split('`').next()
+}
+
+fn segment_name(segment: &Segment) -> String {
+    match segment {
+        Segment::Key(key) => key.clone(),
+        Segment::Index(index) => index.to_string(),
+    }
+}
+
+fn format_path(path: &[Segment]) -> String {
+    let mut out = String::new();
+    for segment in path {
+        match segment {
+            Segment::Key(key) if out.is_empty() => out.push_str(key),
+            Segment::Key(key) => {
+                out.push('.');
+                out.push_str(key);
+            }
+            Segment::Index(index) => out.push_str(&format!("[{}]", index)),
+        }
+    }
+    out
+}
+
+/// Deserialize `value`, returning the outcome and any unknown fields seen
+fn deserialize_tracked<T: DeserializeOwned>(value: Value) -> (std::result::Result<T, Diagnostic>, Vec<Diagnostic>) {
+    let unknown = RefCell::new(Vec::new());
+    let deserializer = TrackingDeserializer { value, path: Vec::new(), unknown: &unknown };
+    let result = serde_path_to_error::deserialize(deserializer).map_err(|error| {
+        let path = error.path().iter()
+            .filter_map(|segment| match segment {
+                serde_path_to_error::Segment::Seq { index } => Some(Segment::Index(*index)),
+                serde_path_to_error::Segment::Map { key } => Some(Segment::Key(key.clone())),
+                serde_path_to_error::Segment::Enum { variant } => Some(Segment::Key(variant.clone())),
+                serde_path_to_error::Segment::Unknown => None,
+            })
+            .collect();
+        Diagnostic {
+            severity: Severity::Error,
+            path,
+            message: error.into_inner().to_string(),
+            suggestion: None,
+            location: None,
+        }
+    });
+    (result, unknown.into_inner())
+}
+
+/// Closest known field name, if one is plausibly what was meant
+fn suggest(key: &str, fields: &[&str]) -> Option<String> {
+    fields.iter()
+        .map(|field| (strsim::levenshtein(&key.to_lowercase(), field), *field))
+        .filter(|(distance, field)| *distance <= 2 && *distance < field.len())
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, field)| field.to_string())
+}
+
+/// Deserializer over a YAML value that records mapping keys a struct does
+/// not declare
+struct TrackingDeserializer<'a> {
+    value: Value,
+    path: Vec<Segment>,
+    unknown: &'a RefCell<Vec<Diagnostic>>,
+}
+
+fn child<'a>(path: &[Segment], segment: Segment, value: Value, unknown: &'a RefCell<Vec<Diagnostic>>) -> TrackingDeserializer<'a> {
+    let mut path = path.to_vec();
+    path.push(segment);
+    TrackingDeserializer { value, path, unknown }
+}
+
+impl<'de, 'a> de::Deserializer<'de> for TrackingDeserializer<'a> {
+    type Error = serde_yaml::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        let Self { value, path, unknown } = self;
+        match value {
+            Value::Mapping(mapping) => visitor.visit_map(TrackingMap { path, unknown, entries: mapping.into_iter(), value: None }),
+            Value::Sequence(items) => visitor.visit_seq(TrackingSeq { path, unknown, items: items.into_iter(), index: 0 }),
+            Value::Tagged(tagged) => tagged.value.deserialize_any(visitor),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        if let Value::Mapping(mapping) = &self.value {
+            for key in mapping.keys().filter_map(Value::as_str) {
+                if !fields.contains(&key) {
+                    let mut path = self.path.clone();
+                    path.push(Segment::Key(key.to_string()));
+                    self.unknown.borrow_mut().push(Diagnostic {
+                        severity: Severity::Warning,
+                        path,
+                        message: "unknown field is ignored".to_string(),
+                        suggestion: suggest(key, fields),
+                        location: None,
+                    });
+                }
+            }
+        }
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error> {
+        self.value.deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> {
+        self.value.deserialize_ignored_any(visitor)
+    }
+
+    // Scalars are handed to serde_yaml so its coercions still apply
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> { self.value.deserialize_bool(visitor) }
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> { self.value.deserialize_i8(visitor) }
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> { self.value.deserialize_i16(visitor) }
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> { self.value.deserialize_i32(visitor) }
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> { self.value.deserialize_i64(visitor) }
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> { self.value.deserialize_u8(visitor) }
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> { self.value.deserialize_u16(visitor) }
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> { self.value.deserialize_u32(visitor) }
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> { self.value.deserialize_u64(visitor) }
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> { self.value.deserialize_f32(visitor) }
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> { self.value.deserialize_f64(visitor) }
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> { self.value.deserialize_char(visitor) }
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> { self.value.deserialize_str(visitor) }
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> { self.value.deserialize_string(visitor) }
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Self::Error> { self.value.deserialize_identifier(visitor) }
+
+    forward_to_deserialize_any! {
+        i128 u128 bytes byte_buf unit unit_struct seq tuple tuple_struct map
+    }
+}
+
+struct TrackingMap<'a> {
+    path: Vec<Segment>,
+    unknown: &'a RefCell<Vec<Diagnostic>>,
+    entries: serde_yaml::mapping::IntoIter,
+    value: Option<(Value, Segment)>,
+}
+
+impl<'de, 'a> MapAccess<'de> for TrackingMap<'a> {
+    type Error = serde_yaml::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> std::result::Result<Option<K::Value>, Self::Error> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        let segment = match &key {
+            Value::String(key) => Segment::Key(key.clone()),
+            other => Segment::Key(serde_yaml::to_string(other).unwrap_or_default().trim().to_string()),
+        };
+        self.value = Some((value, segment));
+        seed.deserialize(key).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> std::result::Result<V::Value, Self::Error> {
+        let (value, segment) = self.value.take()
+            .ok_or_else(|| <serde_yaml::Error as de::Error>::custom("value requested before key"))?;
+        seed.deserialize(child(&self.path, segment, value, self.unknown))
+    }
+}
+
+struct TrackingSeq<'a> {
+    path: Vec<Segment>,
+    unknown: &'a RefCell<Vec<Diagnostic>>,
+    items: std::vec::IntoIter<Value>,
+    index: usize,
+}
+
+impl<'de, 'a> SeqAccess<'de> for TrackingSeq<'a> {
+    type Error = serde_yaml::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> std::result::Result<Option<T::Value>, Self::Error> {
+        let Some(item) = self.items.next() else {
+            return Ok(None);
+        };
+        let index = self.index;
+        self.index += 1;
+        seed.deserialize(child(&self.path, Segment::Index(index), item, self.unknown)).map(Some)
+    }
+}
+
+/// Best-effort mapping from a YAML path to a position in block-style source
+/// files. Flow-style collections resolve to their parent key.
+pub(crate) struct Locator {
+    files: Vec<(PathBuf, Vec<String>)>,
+}
+
+impl Locator {
+    pub(crate) fn new(sources: &[PathBuf]) -> Self {
+        let files = sources.iter()
+            .filter_map(|path| {
+                let text = std::fs::read_to_string(path).ok()?;
+                Some((path.clone(), text.lines().map(String::from).collect()))
+            })
+            .collect();
+        Self { files }
+    }
+
+    pub(crate) fn locate(&self, path: &[Segment]) -> Option<Location> {
+        // Prefer the file that resolves the most of the path
+        self.files.iter()
+            .filter_map(|(file, lines)| {
+                let (depth, line, column) = locate_in(lines.clone(), path)?;
+                Some((depth, Location { file: file.clone(), line: line + 1, column: column + 1 }))
+            })
+            .max_by_key(|(depth, _)| *depth)
+            .map(|(_, location)| location)
+    }
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+fn is_content(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    !trimmed.is_empty() && !trimmed.starts_with('#') && trimmed != "---"
+}
+
+/// Returns how many segments were resolved and the 0-based position of the
+/// deepest one
+fn locate_in(mut lines: Vec<String>, path: &[Segment]) -> Option<(usize, usize, usize)> {
+    let (mut start, mut end) = (0, lines.len());
+    let mut found = None;
+
+    for (depth, segment) in path.iter().enumerate() {
+        let child_indent = lines[start..end].iter().find(|l| is_content(l)).map(|l| indent_of(l))?;
+        let mut candidates = (start..end).filter(|&i| is_content(&lines[i]) && indent_of(&lines[i]) == child_indent);
+
+        let line = match segment {
+            Segment::Key(key) => candidates.find(|&i| {
+                let content = lines[i].trim_start();
+                [format!("{}:", key), format!("\"{}\":", key), format!("'{}':", key)]
+                    .iter()
+                    .any(|prefix| content.starts_with(prefix.as_str()))
+            }),
+            Segment::Index(index) => {
+                let line = candidates.filter(|&i| lines[i].trim_start().starts_with('-')).nth(*index);
+                // Blank the dash so the item's first key lines up with the rest
+                if let Some(i) = line {
+                    let column = indent_of(&lines[i]);
+                    lines[i].replace_range(column..column + 1, " ");
+                }
+                line
+            }
+        };
+        let Some(line) = line else { break };
+        found = Some((depth + 1, line, child_indent));
+
+        // A list item's first key sits on the (blanked) dash line itself
+        start = if matches!(segment, Segment::Index(_)) { line } else { line + 1 };
+        end = (line + 1..end)
+            .find(|&i| is_content(&lines[i]) && indent_of(&lines[i]) <= child_indent)
+            .unwrap_or(end);
+        if start >= end {
+            break;
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn write(name: &str, content: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("backworks_diagnostics_{}_{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("main.yaml");
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn parse_file(path: &Path) -> Result<ParsedBlueprint> {
+        let value: Value = serde_yaml::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        parse(value, &[path.to_path_buf()])
+    }
+
+    #[test]
+    fn test_unknown_fields_are_reported_with_suggestions() {
+        let path = write("unknown", "name: api\nserver:\n  prot: 8080\nendpoints:\n  - path: /users\n    method: GET\n    descripton: Users\n");
+        let parsed = parse_file(&path).unwrap();
+        assert_eq!(parsed.format, BlueprintFormat::List);
+
+        let messages: Vec<String> = parsed.warnings.iter().map(|d| d.to_string()).collect();
+        assert_eq!(parsed.warnings.len(), 2, "{:?}", messages);
+        assert_eq!(parsed.warnings[0].path_string(), "server.prot");
+        assert_eq!(parsed.warnings[0].suggestion.as_deref(), Some("port"));
+        assert_eq!(parsed.warnings[0].location.as_ref().map(|l| (l.line, l.column)), Some((3, 3)));
+        assert_eq!(parsed.warnings[1].path_string(), "endpoints[0].descripton");
+        assert_eq!(parsed.warnings[1].location.as_ref().map(|l| (l.line, l.column)), Some((7, 5)));
+    }
+
+    #[test]
+    fn test_errors_name_path_location_and_typo() {
+        let path = write("errors", "name: api\nendpoints:\n  - path: /users\n    metod: GET\n");
+        let error = parse_file(&path).unwrap_err().to_string();
+        assert!(error.contains("main.yaml:4:5"), "{}", error);
+        assert!(error.contains("missing field `method` (found `metod` instead)"), "{}", error);
+
+        let path = write("types", "name: api\nserver:\n  port: eighty\nendpoints:\n  users:\n    path: /users\n");
+        let error = parse_file(&path).unwrap_err().to_string();
+        assert!(error.contains("main.yaml:3:3: server.port: invalid type"), "{}", error);
+    }
+
+    #[test]
+    fn test_aliases_and_legacy_format_are_accepted() {
+        let path = write("legacy", "name: api\nendpoints:\n  users:\n    path: /users\n    methods: [GET]\n");
+        let parsed = parse_file(&path).unwrap();
+        assert_eq!(parsed.format, BlueprintFormat::Legacy);
+        assert!(parsed.warnings.is_empty(), "{:?}", parsed.warnings);
+
+        let path = write("alias", "name: api\nendpoints:\n  - path: /users\n    methods: [GET, POST]\n");
+        assert!(parse_file(&path).unwrap().warnings.is_empty());
+    }
+}
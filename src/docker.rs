@@ -0,0 +1,195 @@
+//! Container images
+//!
+//! `backworks build --target docker` writes a Docker build context: a
+//! [build](crate::build) of the project under `app/`, the Backworks binary
+//! and a `Dockerfile` that runs the blueprint as an unprivileged user. The
+//! image starts from `node` when runtime handlers are JavaScript and from
+//! Debian otherwise, with Python added for Python handlers. It exposes the
+//! server and dashboard ports the blueprint sets and checks the liveness
+//! endpoint. The Studio is served from the binary, or from
+//! `dashboard.assets_dir`, which must be inside the project.
+
+use crate::build::BuildManifest;
+use crate::config::BackworksConfig;
+use crate::error::{BackworksError, Result};
+use std::path::Path;
+
+pub const DOCKERFILE: &str = "Dockerfile";
+/// Directory of the build inside the context, and in the image
+const APP_DIR: &str = "app";
+const BINARY: &str = "backworks";
+/// First line of generated Dockerfiles, marking contexts that may be replaced
+const GENERATED: &str = "# Generated by `backworks build --target docker`";
+
+const NODE_IMAGE: &str = "node:20-bookworm-slim";
+const DEBIAN_IMAGE: &str = "debian:bookworm-slim";
+
+/// A written build context
+#[derive(Debug)]
+pub struct DockerContext {
+    pub dockerfile: String,
+    pub manifest: BuildManifest,
+    /// Settings that will not work as expected in a container
+    pub warnings: Vec<String>,
+}
+
+/// Write the build context of the project in `root`, whose validated
+/// blueprint `blueprint` loads as `config`, to `output`, with `binary` as
+/// the image's Backworks
+pub fn build(
+    root: &Path,
+    blueprint: &Path,
+    config: &BackworksConfig,
+    profile: Option<&str>,
+    binary: &Path,
+    output: &Path,
+) -> Result<DockerContext> {
+    if let Some(assets) = config.dashboard.as_ref().and_then(|dashboard| dashboard.assets_dir.as_ref()) {
+        if !std::path::absolute(assets)?.starts_with(root) {
+            return Err(BackworksError::config(format!(
+                "dashboard.assets_dir {} must be inside the project to be part of the image", assets.display()
+            )));
+        }
+    }
+    if !binary.is_file() {
+        return Err(BackworksError::config(format!("Backworks binary {} does not exist", binary.display())));
+    }
+
+    prepare_output(output)?;
+    let manifest = crate::build::build(root, blueprint, config, "docker", profile, &output.join(APP_DIR))?;
+    std::fs::copy(binary, output.join(BINARY))?;
+    let dockerfile = dockerfile(config, &manifest);
+    std::fs::write(output.join(DOCKERFILE), &dockerfile)?;
+
+    let mut warnings = Vec::new();
+    if ["127.0.0.1", "localhost", "::1", "[::1]"].contains(&config.server.host.as_str()) {
+        warnings.push(format!("server.host is {}, so the server cannot be reached from outside the container", config.server.host));
+    }
+    if config.plugins.values().any(|plugin| plugin.enabled && plugin.path.as_deref().is_some_and(|path| Path::new(path).is_absolute())) {
+        warnings.push("plugins with absolute paths must be present in the image at the same path".to_string());
+    }
+    Ok(DockerContext { dockerfile, manifest, warnings })
+}
+
+/// The Dockerfile running `manifest`'s blueprint
+pub fn dockerfile(config: &BackworksConfig, manifest: &BuildManifest) -> String {
+    let languages: Vec<&str> = config.endpoints.values().filter_map(|endpoint| endpoint.runtime.as_ref())
+        .chain(config.schedules.values().filter_map(|schedule| schedule.runtime.as_ref()))
+        .map(|runtime| runtime.language.as_str())
+        .collect();
+    let node = languages.iter().any(|language| matches!(*language, "javascript" | "js" | "node"));
+    let python = languages.iter().any(|language| matches!(*language, "python" | "python3" | "py"));
+
+    let mut packages = vec!["ca-certificates", "curl"];
+    if python {
+        packages.push("python3");
+    }
+    let mut ports = vec![config.server.port];
+    if let Some(dashboard) = config.dashboard.as_ref().filter(|dashboard| dashboard.enabled) {
+        ports.push(dashboard.port);
+    }
+    if let Some(http_port) = config.server.tls.as_ref().and_then(|tls| tls.http_port) {
+        ports.push(http_port);
+    }
+    let health = config.monitoring.as_ref()
+        .and_then(|monitoring| monitoring.health.as_ref())
+        .and_then(|health| health.endpoint.as_deref())
+        .unwrap_or("/health");
+    let (scheme, insecure) = if config.server.tls.is_some() { ("https", " -k") } else { ("http", "") };
+
+    let ports = ports.iter().map(ToString::to_string).collect::<Vec<_>>().join(" ");
+    format!(r#"{generated}
+# {name}: target {target}, security profile {profile}
+FROM {image}
+
+RUN apt-get update \
+    && apt-get install -y --no-install-recommends {packages} \
+    && rm -rf /var/lib/apt/lists/* \
+    && useradd --system --create-home --uid 10001 backworks
+
+COPY {binary} /usr/local/bin/backworks
+COPY --chown=backworks:backworks {app}/ /app/
+
+WORKDIR /app
+USER backworks
+EXPOSE {ports}
+HEALTHCHECK --interval=30s --timeout=5s --start-period=10s --retries=3 \
+    CMD curl -fsS{insecure} {scheme}://127.0.0.1:{port}{health} || exit 1
+
+CMD ["backworks", "start", "--config", "{blueprint}"]
+"#,
+        generated = GENERATED,
+        name = manifest.name,
+        target = manifest.target,
+        profile = manifest.profile,
+        image = if node { NODE_IMAGE } else { DEBIAN_IMAGE },
+        packages = packages.join(" "),
+        binary = BINARY,
+        app = APP_DIR,
+        port = config.server.port,
+        blueprint = manifest.blueprint,
+    )
+}
+
+/// Empty `output`, which must be missing, empty or an earlier context
+fn prepare_output(output: &Path) -> Result<()> {
+    if output.exists() {
+        let earlier = std::fs::read_to_string(output.join(DOCKERFILE)).is_ok_and(|text| text.starts_with(GENERATED));
+        if !earlier && std::fs::read_dir(output)?.next().is_some() {
+            return Err(BackworksError::config(format!(
+                "Output directory {} is not empty and holds no earlier Docker build", output.display()
+            )));
+        }
+        std::fs::remove_dir_all(output)?;
+    }
+    std::fs::create_dir_all(output)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contexts_hold_the_build_binary_and_dockerfile() {
+        let root = std::env::temp_dir().join(format!("backworks_docker_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("handlers")).unwrap();
+        let blueprint = r#"
+name: Shop API
+server: { port: 8080, host: 127.0.0.1 }
+dashboard: { enabled: true, port: 8081 }
+monitoring: { health: { endpoint: /livez } }
+endpoints:
+  users: { path: /users, runtime: { language: javascript, handler: ./handlers/users.js } }
+  report: { path: /report, runtime: { language: python, handler: "print('{}')" } }
+"#;
+        std::fs::write(root.join("backworks.yaml"), blueprint).unwrap();
+        std::fs::write(root.join("handlers/users.js"), "function handler() {}").unwrap();
+        std::fs::write(root.join("backworks-bin"), "binary").unwrap();
+        let config: BackworksConfig = serde_yaml::from_str(blueprint).unwrap();
+        let output = root.join("target/docker");
+
+        let context = build(&root, &root.join("backworks.yaml"), &config, None, &root.join("backworks-bin"), &output).unwrap();
+        assert_eq!(context.manifest.profile, "production");
+        assert!(context.warnings[0].contains("server.host is 127.0.0.1"), "{:?}", context.warnings);
+        assert_eq!(std::fs::read_to_string(output.join(BINARY)).unwrap(), "binary");
+        assert!(output.join("app/build.json").is_file() && output.join("app/handlers/users.js").is_file());
+        let dockerfile = std::fs::read_to_string(output.join(DOCKERFILE)).unwrap();
+        assert_eq!(dockerfile, context.dockerfile);
+        for line in [
+            "FROM node:20-bookworm-slim",
+            "apt-get install -y --no-install-recommends ca-certificates curl python3",
+            "EXPOSE 8080 8081",
+            "CMD curl -fsS http://127.0.0.1:8080/livez || exit 1",
+            r#"CMD ["backworks", "start", "--config", "backworks.yaml"]"#,
+        ] {
+            assert!(dockerfile.contains(line), "{} missing from\n{}", line, dockerfile);
+        }
+
+        // Earlier contexts are replaced, other directories are not
+        build(&root, &root.join("backworks.yaml"), &config, None, &root.join("backworks-bin"), &output).unwrap();
+        let err = build(&root, &root.join("backworks.yaml"), &config, None, &root.join("backworks-bin"), &root.join("handlers")).unwrap_err();
+        assert!(err.to_string().contains("not empty"), "{}", err);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
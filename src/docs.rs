@@ -0,0 +1,533 @@
+//! API reference documentation
+//!
+//! `backworks docs` renders the blueprint's endpoints as a static site: an
+//! `index.html` that needs nothing from the network, listing each endpoint
+//! with its methods, description, parameters and example exchanges, and the
+//! same API as `openapi.json` for Swagger UI, Redoc or client generators.
+//! With `docs` in the blueprint the running server serves both itself:
+//!
+//! ```yaml
+//! docs:
+//!   path: "/docs"                          # default; JSON at /docs/openapi.json
+//!   captures: ["./captures/checkout.json"] # examples from real traffic
+//! ```
+//!
+//! Examples come from capture exports and proxy cassettes: requests are
+//! matched to the endpoint whose path fits best, and each endpoint keeps
+//! one exchange per method and status. Mock endpoints without a captured
+//! example get a `GET` answered from their mock data. Parameters are the
+//! typed segments of the path and the body fields the endpoint's
+//! `parameters` declare.
+
+use crate::config::{BackworksConfig, EndpointConfig, ParameterConfig};
+use crate::contract::RecordedExchange;
+use crate::error::{BackworksError, Result};
+use crate::mock::MockEngine;
+use crate::routes::{PathSegment, RoutePattern};
+use crate::server::RequestData;
+use crate::state::StateStore;
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Examples kept per endpoint
+const MAX_EXAMPLES: usize = 4;
+
+/// The API a blueprint declares, ready to render
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiDocs {
+    pub name: String,
+    pub description: Option<String>,
+    pub version: Option<String>,
+    /// In path order
+    pub endpoints: Vec<EndpointDocs>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EndpointDocs {
+    pub name: String,
+    /// In OpenAPI syntax, e.g. `/users/{id}`
+    pub path: String,
+    pub methods: Vec<String>,
+    pub description: Option<String>,
+    /// Execution mode the endpoint tries first
+    pub mode: String,
+    /// Deprecation notice, when the endpoint is deprecated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<String>,
+    pub parameters: Vec<ParameterDocs>,
+    pub examples: Vec<Example>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParameterDocs {
+    pub name: String,
+    /// `path` or `body`
+    #[serde(rename = "in")]
+    pub location: String,
+    #[serde(rename = "type")]
+    pub param_type: String,
+    pub required: bool,
+    /// Bounds and format the value is validated against
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub constraints: Vec<String>,
+}
+
+/// A request and the response it got
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Example {
+    /// Capture file, or `mock data`
+    pub source: String,
+    pub method: String,
+    /// Path and query string
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_body: Option<Value>,
+    pub status: u16,
+    pub response_body: Value,
+}
+
+impl ApiDocs {
+    /// The endpoints of `config`, without examples
+    pub fn from_blueprint(config: &BackworksConfig) -> Self {
+        let mut endpoints: Vec<EndpointDocs> = config.endpoints.iter()
+            .map(|(name, endpoint)| EndpointDocs::new(name, endpoint, config))
+            .collect();
+        endpoints.sort_by(|a, b| (&a.path, &a.name).cmp(&(&b.path, &b.name)));
+        Self {
+            name: config.name.clone(),
+            description: config.description.clone(),
+            version: config.version.clone(),
+            endpoints,
+        }
+    }
+
+    /// The docs of `config` with examples from the captures its `docs`
+    /// section names, then those of `captures`, and from its mock data
+    pub async fn load(config: &BackworksConfig, captures: &[PathBuf], mocks: &MockEngine, store: &StateStore) -> Result<Self> {
+        let mut docs = Self::from_blueprint(config);
+        for capture in config.docs.iter().flat_map(|docs| &docs.captures).chain(captures) {
+            docs.add_exchanges(config, &capture.display().to_string(), &crate::contract::load_capture(capture).await?);
+        }
+        docs.add_mock_examples(config, mocks, store).await;
+        Ok(docs)
+    }
+
+    /// Attach captured exchanges to the endpoints they were sent to
+    pub fn add_exchanges(&mut self, config: &BackworksConfig, source: &str, exchanges: &[RecordedExchange]) {
+        let patterns: Vec<RoutePattern> = self.endpoints.iter().map(|endpoint| RoutePattern::parse(&config.endpoints[&endpoint.name].path)).collect();
+        for exchange in exchanges {
+            let method = exchange.request.method.to_ascii_uppercase();
+            let path = exchange.request.path.split('?').next().unwrap_or_default();
+            // Among the endpoints matching, the one with the most literal segments
+            let best = self.endpoints.iter().zip(&patterns).enumerate()
+                .filter(|(_, (endpoint, pattern))| endpoint.methods.contains(&method) && pattern.matches(path))
+                .max_by_key(|(_, (_, pattern))| pattern.segments().iter().filter(|segment| matches!(segment, PathSegment::Static(_))).count())
+                .map(|(index, _)| index);
+            if let Some(index) = best {
+                self.endpoints[index].add_example(Example {
+                    source: source.to_string(),
+                    method,
+                    path: exchange.request.path.clone(),
+                    request_body: exchange.request.body.clone(),
+                    status: exchange.response.status,
+                    response_body: exchange.response.body.clone(),
+                });
+            }
+        }
+    }
+
+    /// Answer a `GET` of each mock endpoint still without examples from its
+    /// mock data; parameters of the path get a sample of their type
+    pub async fn add_mock_examples(&mut self, config: &BackworksConfig, mocks: &MockEngine, store: &StateStore) {
+        for endpoint in &mut self.endpoints {
+            let declared = &config.endpoints[&endpoint.name];
+            if declared.mock.is_none() || !endpoint.examples.is_empty() || !endpoint.methods.iter().any(|method| method == "GET") {
+                continue;
+            }
+            let pattern = RoutePattern::parse(&declared.path);
+            let samples: HashMap<String, String> = pattern.segments().iter()
+                .filter_map(|segment| match segment {
+                    PathSegment::Param(name, kind) => Some((name.clone(), kind.sample().to_string())),
+                    PathSegment::CatchAll(name) => Some((name.clone(), "1".to_string())),
+                    PathSegment::Static(_) => None,
+                })
+                .collect();
+            let path = pattern.sample_path(&samples);
+            let request = RequestData {
+                method: "GET".to_string(),
+                path: path.clone(),
+                path_params: pattern.extract(samples).unwrap_or_default(),
+                query_params: HashMap::new(),
+                headers: HeaderMap::new(),
+                body: None,
+                auth: None,
+                origin: None,
+                locale: None,
+                deadline: None,
+                session: None,
+                csrf_token: None,
+            };
+            let output = match mocks.respond(&endpoint.name, &request, store).await {
+                Ok(output) => output,
+                Err(e) => {
+                    tracing::warn!("No mock example for endpoint '{}': {}", endpoint.name, e);
+                    continue;
+                }
+            };
+            let response = crate::compare::ResponseSnapshot::from_handler_output(&output);
+            endpoint.add_example(Example {
+                source: "mock data".to_string(),
+                method: "GET".to_string(),
+                path,
+                request_body: None,
+                status: response.status,
+                response_body: response.body,
+            });
+        }
+    }
+
+    /// The API as an OpenAPI 3 document
+    pub fn openapi(&self) -> Value {
+        let mut paths = Map::new();
+        for endpoint in &self.endpoints {
+            let Value::Object(operations) = paths.entry(endpoint.path.clone()).or_insert_with(|| json!({})) else {
+                continue;
+            };
+            for method in &endpoint.methods {
+                let operation_id = if endpoint.methods.len() == 1 { endpoint.name.clone() } else { format!("{}_{}", endpoint.name, method.to_ascii_lowercase()) };
+                operations.insert(method.to_ascii_lowercase(), endpoint.operation(method, operation_id));
+            }
+        }
+        let mut info = json!({ "title": self.name, "version": self.version.as_deref().unwrap_or("0.0.0") });
+        if let Some(ref description) = self.description {
+            info["description"] = json!(description);
+        }
+        json!({ "openapi": "3.0.3", "info": info, "paths": paths })
+    }
+
+    /// The API as a single HTML page, linking to the OpenAPI document at
+    /// `openapi_url`
+    pub fn render_html(&self, openapi_url: &str) -> String {
+        let mut html = String::new();
+        self.write_html(&mut html, openapi_url).expect("writing to a String cannot fail");
+        html
+    }
+
+    fn write_html(&self, out: &mut String, openapi_url: &str) -> fmt::Result {
+        writeln!(out, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">")?;
+        writeln!(out, "<title>{} API reference</title>\n<style>{}</style>\n</head>\n<body>", escape(&self.name), STYLE)?;
+        writeln!(out, "<nav>\n<h2>{}</h2>\n<ul>", escape(&self.name))?;
+        for endpoint in &self.endpoints {
+            write!(out, "<li><a href=\"#{}\">", escape(&endpoint.name))?;
+            for method in &endpoint.methods {
+                write!(out, "<span class=\"method {}\">{}</span>", method.to_ascii_lowercase(), escape(method))?;
+            }
+            writeln!(out, " <code>{}</code></a></li>", escape(&endpoint.path))?;
+        }
+        writeln!(out, "</ul>\n</nav>\n<main>\n<header>\n<h1>{}</h1>", escape(&self.name))?;
+        if let Some(ref version) = self.version {
+            writeln!(out, "<p class=\"version\">Version {}</p>", escape(version))?;
+        }
+        if let Some(ref description) = self.description {
+            writeln!(out, "<p>{}</p>", escape(description))?;
+        }
+        writeln!(out, "<p class=\"muted\">{} endpoint(s). The OpenAPI document is <a href=\"{}\">openapi.json</a>.</p>\n</header>", self.endpoints.len(), escape(openapi_url))?;
+        for endpoint in &self.endpoints {
+            endpoint.write_html(out)?;
+        }
+        writeln!(out, "</main>\n</body>\n</html>")
+    }
+
+    /// Write `index.html` and `openapi.json` into `dir`
+    pub fn write_site(&self, dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| BackworksError::config(format!("Failed to create {}: {}", dir.display(), e)))?;
+        std::fs::write(dir.join("index.html"), self.render_html("openapi.json"))?;
+        std::fs::write(dir.join("openapi.json"), serde_json::to_string_pretty(&self.openapi())?)?;
+        Ok(())
+    }
+}
+
+impl EndpointDocs {
+    fn new(name: &str, endpoint: &EndpointConfig, config: &BackworksConfig) -> Self {
+        let pattern = RoutePattern::parse(&endpoint.path);
+        let mut parameters: Vec<ParameterDocs> = pattern.segments().iter()
+            .filter_map(|segment| match segment {
+                PathSegment::Param(name, kind) => Some((name, kind.name())),
+                PathSegment::CatchAll(name) => Some((name, "path")),
+                PathSegment::Static(_) => None,
+            })
+            .map(|(name, param_type)| ParameterDocs {
+                name: name.clone(),
+                location: "path".to_string(),
+                param_type: param_type.to_string(),
+                required: true,
+                constraints: Vec::new(),
+            })
+            .collect();
+        parameters.extend(endpoint.parameters.iter().flatten().map(ParameterDocs::body));
+        let deprecated = endpoint.deprecated.as_ref().map(|deprecation| {
+            let mut notice = deprecation.message.clone().unwrap_or_else(|| "Deprecated".to_string());
+            if let Some(sunset) = deprecation.sunset {
+                notice.push_str(&format!(" (removed on {})", sunset));
+            }
+            notice
+        });
+        Self {
+            name: name.to_string(),
+            path: pattern.openapi_path(),
+            methods: endpoint.methods.iter().map(|method| method.to_ascii_uppercase()).collect(),
+            description: endpoint.description.clone(),
+            mode: serde_json::to_value(endpoint.primary_mode(&config.mode)).ok()
+                .and_then(|mode| mode.as_str().map(str::to_string))
+                .unwrap_or_default(),
+            deprecated,
+            parameters,
+            examples: Vec::new(),
+        }
+    }
+
+    /// Keep the first example of each method and status
+    fn add_example(&mut self, example: Example) {
+        let seen = self.examples.iter().any(|kept| kept.method == example.method && kept.status == example.status);
+        if !seen && self.examples.len() < MAX_EXAMPLES {
+            self.examples.push(example);
+        }
+    }
+
+    fn operation(&self, method: &str, operation_id: String) -> Value {
+        let mut operation = json!({ "operationId": operation_id });
+        if let Some(ref description) = self.description {
+            operation["summary"] = json!(description);
+        }
+        if self.deprecated.is_some() {
+            operation["deprecated"] = json!(true);
+        }
+        let path_parameters: Vec<Value> = self.parameters.iter()
+            .filter(|parameter| parameter.location == "path")
+            .map(|parameter| json!({ "name": parameter.name, "in": "path", "required": true, "schema": parameter.schema() }))
+            .collect();
+        if !path_parameters.is_empty() {
+            operation["parameters"] = json!(path_parameters);
+        }
+
+        let fields: Vec<&ParameterDocs> = self.parameters.iter().filter(|parameter| parameter.location == "body").collect();
+        let request_example = self.examples.iter().find(|example| example.method == method).and_then(|example| example.request_body.clone());
+        if !fields.is_empty() || request_example.is_some() {
+            let mut schema = json!({ "type": "object" });
+            if !fields.is_empty() {
+                schema["properties"] = fields.iter().map(|field| (field.name.clone(), field.schema())).collect::<Map<String, Value>>().into();
+                let required: Vec<&str> = fields.iter().filter(|field| field.required).map(|field| field.name.as_str()).collect();
+                if !required.is_empty() {
+                    schema["required"] = json!(required);
+                }
+            }
+            let mut content = json!({ "schema": schema });
+            if let Some(example) = request_example {
+                content["example"] = example;
+            }
+            operation["requestBody"] = json!({ "content": { "application/json": content } });
+        }
+
+        let mut responses = Map::new();
+        for example in self.examples.iter().filter(|example| example.method == method) {
+            responses.insert(example.status.to_string(), json!({
+                "description": format!("Example from {}", example.source),
+                "content": { "application/json": { "example": example.response_body } }
+            }));
+        }
+        if responses.is_empty() {
+            responses.insert("default".to_string(), json!({ "description": "Response of the endpoint" }));
+        }
+        operation["responses"] = Value::Object(responses);
+        operation
+    }
+
+    fn write_html(&self, out: &mut String) -> fmt::Result {
+        writeln!(out, "<section id=\"{}\">", escape(&self.name))?;
+        write!(out, "<h2>")?;
+        for method in &self.methods {
+            write!(out, "<span class=\"method {}\">{}</span>", method.to_ascii_lowercase(), escape(method))?;
+        }
+        writeln!(out, " <code>{}</code></h2>", escape(&self.path))?;
+        writeln!(out, "<p class=\"muted\">{} &middot; {} mode</p>", escape(&self.name), escape(&self.mode))?;
+        if let Some(ref notice) = self.deprecated {
+            writeln!(out, "<p class=\"deprecated\">{}</p>", escape(notice))?;
+        }
+        if let Some(ref description) = self.description {
+            writeln!(out, "<p>{}</p>", escape(description))?;
+        }
+        if !self.parameters.is_empty() {
+            writeln!(out, "<h3>Parameters</h3>\n<table>\n<tr><th>Name</th><th>In</th><th>Type</th><th>Required</th><th>Constraints</th></tr>")?;
+            for parameter in &self.parameters {
+                writeln!(out, "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    escape(&parameter.name), parameter.location, escape(&parameter.param_type),
+                    if parameter.required { "yes" } else { "no" }, escape(&parameter.constraints.join(", ")))?;
+            }
+            writeln!(out, "</table>")?;
+        }
+        if !self.examples.is_empty() {
+            writeln!(out, "<h3>Examples</h3>")?;
+        }
+        for example in &self.examples {
+            writeln!(out, "<div class=\"example\">\n<p><span class=\"method {}\">{}</span> <code>{}</code> <span class=\"status s{}\">{}</span> <span class=\"muted\">from {}</span></p>",
+                example.method.to_ascii_lowercase(), escape(&example.method), escape(&example.path),
+                example.status / 100, example.status, escape(&example.source))?;
+            if let Some(ref body) = example.request_body {
+                writeln!(out, "<p class=\"label\">Request</p>\n<pre>{}</pre>", escape(&pretty(body)))?;
+            }
+            writeln!(out, "<p class=\"label\">Response</p>\n<pre>{}</pre>\n</div>", escape(&pretty(&example.response_body)))?;
+        }
+        writeln!(out, "</section>")
+    }
+}
+
+impl ParameterDocs {
+    fn body(parameter: &ParameterConfig) -> Self {
+        let mut constraints = Vec::new();
+        if let Some(minimum) = parameter.minimum {
+            constraints.push(format!("minimum {}", minimum));
+        }
+        if let Some(maximum) = parameter.maximum {
+            constraints.push(format!("maximum {}", maximum));
+        }
+        if let Some(max_length) = parameter.max_length {
+            constraints.push(format!("at most {} characters", max_length));
+        }
+        if let Some(ref format) = parameter.format {
+            constraints.push(format!("format {}", format));
+        }
+        Self {
+            name: parameter.name.clone(),
+            location: "body".to_string(),
+            param_type: parameter.param_type.clone(),
+            required: parameter.required.unwrap_or(false),
+            constraints,
+        }
+    }
+
+    /// JSON schema of the parameter's type
+    fn schema(&self) -> Value {
+        match self.param_type.as_str() {
+            "int" | "integer" => json!({ "type": "integer" }),
+            "float" | "number" => json!({ "type": "number" }),
+            "bool" | "boolean" => json!({ "type": "boolean" }),
+            "array" => json!({ "type": "array", "items": {} }),
+            "object" => json!({ "type": "object" }),
+            "uuid" => json!({ "type": "string", "format": "uuid" }),
+            _ => json!({ "type": "string" }),
+        }
+    }
+}
+
+fn pretty(value: &Value) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+const STYLE: &str = "
+body { margin: 0; display: flex; font: 15px/1.5 system-ui, sans-serif; color: #1f2328; }
+nav { position: sticky; top: 0; height: 100vh; overflow-y: auto; width: 300px; flex-shrink: 0; padding: 16px; box-sizing: border-box; background: #f6f8fa; border-right: 1px solid #d0d7de; }
+nav h2 { font-size: 16px; margin: 0 0 12px; }
+nav ul { list-style: none; margin: 0; padding: 0; }
+nav li { margin: 4px 0; }
+nav a { color: inherit; text-decoration: none; font-size: 13px; }
+main { flex: 1; min-width: 0; max-width: 960px; padding: 24px 40px; }
+section { border-top: 1px solid #d0d7de; padding: 16px 0; }
+h2 code { font-size: 18px; }
+table { border-collapse: collapse; margin: 8px 0; }
+th, td { border: 1px solid #d0d7de; padding: 4px 10px; text-align: left; font-size: 14px; }
+pre { background: #f6f8fa; padding: 12px; overflow-x: auto; border-radius: 6px; font-size: 13px; margin: 4px 0 12px; }
+.example { margin-bottom: 12px; }
+.method { display: inline-block; min-width: 52px; margin-right: 4px; padding: 1px 6px; border-radius: 4px; color: #fff; font: bold 11px monospace; text-align: center; background: #6e7781; }
+.get { background: #0969da; } .post { background: #1a7f37; } .put { background: #9a6700; } .patch { background: #8250df; } .delete { background: #cf222e; }
+.status { font-family: monospace; font-weight: bold; }
+.s2 { color: #1a7f37; } .s3 { color: #0969da; } .s4 { color: #9a6700; } .s5 { color: #cf222e; }
+.muted, .version { color: #656d76; font-size: 13px; }
+.label { margin: 0; font-size: 12px; font-weight: bold; color: #656d76; text-transform: uppercase; }
+.deprecated { padding: 6px 10px; border-left: 4px solid #cf222e; background: #ffebe9; }
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compare::ResponseSnapshot;
+    use crate::contract::ContractRequest;
+
+    fn blueprint() -> BackworksConfig {
+        serde_yaml::from_str(r#"
+name: shop
+version: "2.1.0"
+endpoints:
+  users:
+    path: /users
+    methods: [GET, POST]
+    description: "Users <all of them>"
+    mode: mock
+    mock: { schema: { id: $seq, name: $name }, count: 2 }
+    parameters:
+      - { name: name, type: string, required: true, max_length: 40 }
+  user:
+    path: "/users/{id:int}"
+    methods: [GET]
+    mode: mock
+    mock: { item_of: users }
+  me:
+    path: /users/me
+    methods: [GET]
+    deprecated: { message: "Use /session" }
+"#).unwrap()
+    }
+
+    fn exchange(method: &str, path: &str, status: u16, body: Value) -> RecordedExchange {
+        RecordedExchange {
+            request: ContractRequest { source: "capture.json".to_string(), method: method.to_string(), path: path.to_string(), headers: Default::default(), body: None },
+            response: ResponseSnapshot { status, headers: HashMap::new(), body },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_docs_take_examples_from_captures_then_mock_data() {
+        let config = blueprint();
+        let mut docs = ApiDocs::from_blueprint(&config);
+        assert_eq!(docs.endpoints.iter().map(|endpoint| endpoint.path.as_str()).collect::<Vec<_>>(), ["/users", "/users/me", "/users/{id}"]);
+        let user = &docs.endpoints[2];
+        assert_eq!((user.parameters[0].name.as_str(), user.parameters[0].param_type.as_str(), user.mode.as_str()), ("id", "int", "mock"));
+        assert_eq!(docs.endpoints[0].parameters[0].constraints, ["at most 40 characters"]);
+
+        docs.add_exchanges(&config, "capture.json", &[
+            exchange("GET", "/users/me?verbose=1", 200, json!({ "id": 1 })),
+            exchange("GET", "/users/7", 404, json!({ "error": "missing" })),
+            exchange("GET", "/users/8", 404, json!({ "error": "missing" })),
+            exchange("DELETE", "/users/7", 204, Value::Null),
+        ]);
+        assert_eq!(docs.endpoints[1].examples[0].path, "/users/me?verbose=1");
+        assert_eq!(docs.endpoints[2].examples.len(), 1);
+
+        let mocks = MockEngine::new(&config.endpoints).unwrap();
+        docs.add_mock_examples(&config, &mocks, &StateStore::new()).await;
+        let listed = &docs.endpoints[0].examples[0];
+        assert_eq!((listed.source.as_str(), listed.status), ("mock data", 200));
+        assert_eq!(listed.response_body.as_array().unwrap().len(), 2);
+        assert_eq!(docs.endpoints[2].examples[0].status, 404);
+
+        let openapi = docs.openapi();
+        assert_eq!(openapi["info"]["version"], "2.1.0");
+        assert_eq!(openapi["paths"]["/users"]["post"]["operationId"], "users_post");
+        assert_eq!(openapi["paths"]["/users"]["post"]["requestBody"]["content"]["application/json"]["schema"]["required"], json!(["name"]));
+        assert_eq!(openapi["paths"]["/users/{id}"]["get"]["parameters"][0]["schema"]["type"], "integer");
+        assert_eq!(openapi["paths"]["/users/{id}"]["get"]["responses"]["404"]["content"]["application/json"]["example"]["error"], "missing");
+        assert_eq!(openapi["paths"]["/users/me"]["get"]["deprecated"], true);
+
+        let html = docs.render_html("/docs/openapi.json");
+        assert!(html.contains("Users &lt;all of them&gt;") && html.contains("href=\"/docs/openapi.json\""));
+        assert!(html.contains("<section id=\"me\">") && html.contains("Use /session"));
+    }
+}
@@ -0,0 +1,117 @@
+//! File responses from handlers
+//!
+//! A handler answers with a file instead of JSON by returning
+//! `{ "file": "./exports/report.csv", "download_name": "report.csv" }`. The
+//! file is streamed like a static file, with its content type, conditional
+//! requests and byte ranges. Output is only taken for a file when every key
+//! is one of the fields below, so JSON data that happens to have a `file`
+//! field is still sent as JSON.
+//!
+//! Only runtime handlers return files: proxied and mocked bodies are always
+//! data. Files must be inside the download root, `server.downloads`, which
+//! defaults to the working directory.
+
+use crate::static_files::file_response;
+use axum::http::{header, HeaderMap};
+use axum::response::Response;
+use serde::Deserialize;
+use serde_json::Value;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileDownload {
+    /// Path of the file; relative paths resolve against the working directory
+    pub file: PathBuf,
+    /// Offer the file as a download under this name
+    pub download_name: Option<String>,
+    /// Content type (default: guessed from the download name or the path)
+    pub content_type: Option<String>,
+    /// Let the browser display the file instead of saving it
+    #[serde(default)]
+    pub inline: bool,
+    /// Delete the file once it has been sent
+    #[serde(default)]
+    pub temporary: bool,
+}
+
+impl FileDownload {
+    /// Extract a file response from handler output, if it is one
+    pub fn from_output(value: &Value) -> Option<Self> {
+        value.get("file")?.as_str()?;
+        serde_json::from_value(value.clone()).ok()
+    }
+
+    /// Stream the file, which must resolve to a path inside `root`
+    pub async fn send(self, root: &Path, headers: &HeaderMap, head: bool) -> std::io::Result<Response> {
+        let root = tokio::fs::canonicalize(root).await?;
+        let file = tokio::fs::canonicalize(&self.file).await?;
+        if !file.starts_with(&root) {
+            return Err(Error::new(ErrorKind::PermissionDenied, format!("outside the download root {}", root.display())));
+        }
+
+        let content_type = match self.content_type {
+            Some(ref content_type) => content_type.clone(),
+            None => self.download_name.as_deref()
+                .and_then(|name| mime_guess::from_path(name).first())
+                .unwrap_or_else(|| mime_guess::from_path(&self.file).first_or_octet_stream())
+                .to_string(),
+        };
+
+        let mut builder = Response::builder();
+        let kind = if self.inline { "inline" } else { "attachment" };
+        match self.download_name {
+            Some(ref name) => builder = builder.header(header::CONTENT_DISPOSITION, content_disposition(kind, name)),
+            None if self.inline => builder = builder.header(header::CONTENT_DISPOSITION, kind),
+            None => {}
+        }
+
+        // Created before opening so a failed send still cleans up
+        let guard = self.temporary.then(|| TemporaryFile(file.clone()));
+        file_response(&file, &content_type, headers, head, builder, guard).await
+    }
+}
+
+/// Deletes the file when dropped
+#[derive(Debug)]
+struct TemporaryFile(PathBuf);
+
+impl Drop for TemporaryFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.0) {
+            tracing::warn!("Failed to delete temporary file {}: {}", self.0.display(), e);
+        }
+    }
+}
+
+/// `Content-Disposition` with a plain `filename` for old clients and the
+/// exact name as RFC 5987 `filename*`
+fn content_disposition(kind: &str, name: &str) -> String {
+    let fallback: String = name.chars()
+        .map(|c| if (c.is_ascii_graphic() || c == ' ') && c != '"' && c != '\\' { c } else { '_' })
+        .collect();
+    let encoded: String = name.bytes()
+        .map(|b| if b.is_ascii_alphanumeric() || b"-._~".contains(&b) { (b as char).to_string() } else { format!("%{:02X}", b) })
+        .collect();
+    format!("{}; filename=\"{}\"; filename*=UTF-8''{}", kind, fallback, encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_file_output_detection_and_disposition() {
+        let download = FileDownload::from_output(&json!({ "file": "./exports/report.csv", "download_name": "report.csv" })).unwrap();
+        assert_eq!(download.file, PathBuf::from("./exports/report.csv"));
+        assert!(!download.temporary);
+        assert!(FileDownload::from_output(&json!({ "file": "a.txt", "size": 10 })).is_none());
+        assert!(FileDownload::from_output(&json!({ "file": 1 })).is_none());
+        assert!(FileDownload::from_output(&json!({ "status": 200, "body": {} })).is_none());
+
+        assert_eq!(content_disposition("attachment", "report.csv"), "attachment; filename=\"report.csv\"; filename*=UTF-8''report.csv");
+        assert_eq!(content_disposition("inline", "Q1 \"résumé\".pdf"), "inline; filename=\"Q1 _r_sum__.pdf\"; filename*=UTF-8''Q1%20%22r%C3%A9sum%C3%A9%22.pdf");
+    }
+}
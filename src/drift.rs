@@ -0,0 +1,480 @@
+//! API drift detection
+//!
+//! Compares the API seen in one capture with another capture, or with what
+//! a blueprint declares, so CI can catch unintended changes. A snapshot
+//! groups exchanges by method and path; ID-like path segments are grouped
+//! as `{id}`, or as the declared path when a blueprint is involved. Each
+//! endpoint keeps the statuses it answered with and the shape of its JSON
+//! bodies, as the types seen at each field.
+//!
+//! Captures are JSON exports of a capture session (`session` and
+//! `requests`) or proxy cassettes. Blueprints declare endpoints and their
+//! parameters but no responses: against a capture only added and removed
+//! endpoints are reported, and between two blueprints parameter changes too.
+
+use crate::capture::CapturedRequest;
+use crate::config::BackworksConfig;
+use crate::error::{BackworksError, Result};
+use crate::proxy::Recording;
+use crate::routes::{PathSegment, RoutePattern};
+use crate::usage::candidate_path;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write};
+use std::path::Path;
+
+/// Field types seen in response bodies, by JSON pointer; array items are `*`
+pub type BodyShape = BTreeMap<String, BTreeSet<String>>;
+
+/// The API as one capture or blueprint shows it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ApiSnapshot {
+    pub source: String,
+    /// Whether responses were observed, so statuses and bodies are comparable
+    pub observed: bool,
+    pub endpoints: BTreeMap<EndpointKey, EndpointShape>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct EndpointKey {
+    pub method: String,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EndpointShape {
+    pub requests: usize,
+    pub statuses: BTreeSet<u16>,
+    pub body: BodyShape,
+    /// Parameters a blueprint declares, by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub parameters: BTreeMap<String, ParameterShape>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParameterShape {
+    /// `path` for path parameters, `request` for declared `parameters`
+    #[serde(rename = "in")]
+    pub location: String,
+    #[serde(rename = "type")]
+    pub param_type: String,
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Parameter {
+    pub name: String,
+    #[serde(flatten)]
+    pub shape: ParameterShape,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParameterChange {
+    pub name: String,
+    pub before: ParameterShape,
+    pub after: ParameterShape,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DriftReport {
+    pub baseline: String,
+    pub current: String,
+    /// Endpoints only in the current snapshot
+    pub added: Vec<EndpointKey>,
+    /// Endpoints only in the baseline
+    pub removed: Vec<EndpointKey>,
+    pub changed: Vec<EndpointDrift>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EndpointDrift {
+    pub method: String,
+    pub path: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub statuses_added: Vec<u16>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub statuses_removed: Vec<u16>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields_added: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields_removed: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub type_changes: Vec<TypeChange>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameters_added: Vec<Parameter>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameters_removed: Vec<Parameter>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameter_changes: Vec<ParameterChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypeChange {
+    pub field: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+impl fmt::Display for EndpointKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.path)
+    }
+}
+
+impl ApiSnapshot {
+    /// Group captured exchanges by endpoint, naming paths that match one of
+    /// `declared` after it
+    pub fn from_exchanges<'a>(source: &str, exchanges: impl IntoIterator<Item = Exchange<'a>>, declared: &[String]) -> Self {
+        let patterns: Vec<(&String, RoutePattern)> = declared.iter().map(|path| (path, RoutePattern::parse(path))).collect();
+        let mut endpoints: BTreeMap<EndpointKey, EndpointShape> = BTreeMap::new();
+        for exchange in exchanges {
+            let path = exchange.path.split('?').next().unwrap_or_default();
+            let path = match patterns.iter().find(|(_, pattern)| pattern.matches(path)) {
+                Some((declared, _)) => declared.to_string(),
+                None => candidate_path(path),
+            };
+            let endpoint = endpoints.entry(EndpointKey { method: exchange.method.to_ascii_uppercase(), path }).or_default();
+            endpoint.requests += 1;
+            if let Some(status) = exchange.status {
+                endpoint.statuses.insert(status);
+            }
+            if let Some(body) = exchange.body {
+                collect_shape("", body, &mut endpoint.body);
+            }
+        }
+        Self { source: source.to_string(), observed: true, endpoints }
+    }
+
+    /// The endpoints a blueprint declares, with parameter types taken out
+    /// of their paths (`/users/{id:int}` is `/users/{id}`)
+    pub fn from_blueprint(source: &str, config: &BackworksConfig) -> Self {
+        let mut endpoints = BTreeMap::new();
+        for endpoint in config.endpoints.values() {
+            let pattern = RoutePattern::parse(&endpoint.path);
+            let mut parameters = BTreeMap::new();
+            for segment in pattern.segments() {
+                let (name, param_type) = match segment {
+                    PathSegment::Param(name, kind) => (name, kind.name()),
+                    PathSegment::CatchAll(name) => (name, "path"),
+                    PathSegment::Static(_) => continue,
+                };
+                parameters.insert(name.clone(), ParameterShape { location: "path".to_string(), param_type: param_type.to_string(), required: true });
+            }
+            for parameter in endpoint.parameters.iter().flatten() {
+                parameters.entry(parameter.name.clone()).or_insert_with(|| ParameterShape {
+                    location: "request".to_string(),
+                    param_type: parameter.param_type.clone(),
+                    required: parameter.required.unwrap_or(false),
+                });
+            }
+            for method in &endpoint.methods {
+                let key = EndpointKey { method: method.to_ascii_uppercase(), path: pattern.openapi_path() };
+                endpoints.insert(key, EndpointShape { parameters: parameters.clone(), description: endpoint.description.clone(), ..Default::default() });
+            }
+        }
+        Self { source: source.to_string(), observed: false, endpoints }
+    }
+
+    fn declared_paths(&self) -> Vec<String> {
+        let paths: BTreeSet<&String> = self.endpoints.keys().map(|key| &key.path).collect();
+        paths.into_iter().cloned().collect()
+    }
+}
+
+/// One captured request and its response
+#[derive(Debug, Clone)]
+pub struct Exchange<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    pub status: Option<u16>,
+    pub body: Option<&'a Value>,
+}
+
+pub(crate) fn collect_shape(pointer: &str, value: &Value, shape: &mut BodyShape) {
+    let kind = match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(items) => {
+            for item in items {
+                collect_shape(&format!("{}/*", pointer), item, shape);
+            }
+            "array"
+        }
+        Value::Object(fields) => {
+            for (name, field) in fields {
+                let token = name.replace('~', "~0").replace('/', "~1");
+                collect_shape(&format!("{}/{}", pointer, token), field, shape);
+            }
+            "object"
+        }
+    };
+    shape.entry(pointer.to_string()).or_default().insert(kind.to_string());
+}
+
+/// Compare two files, each a capture export, proxy cassette (`.json`) or
+/// blueprint. Captured paths are named after the endpoints of a blueprint
+/// on the other side.
+pub async fn compare_files(baseline: &Path, current: &Path) -> Result<DriftReport> {
+    let mut blueprints = Vec::new();
+    for path in [baseline, current] {
+        blueprints.push(match is_capture(path) {
+            true => None,
+            false => Some(ApiSnapshot::from_blueprint(&path.display().to_string(), &crate::config::load_yaml_config(path).await?)),
+        });
+    }
+    let declared: Vec<String> = blueprints.iter().flatten().flat_map(ApiSnapshot::declared_paths).collect();
+    let mut snapshots = Vec::new();
+    for (path, blueprint) in [baseline, current].into_iter().zip(blueprints) {
+        snapshots.push(match blueprint {
+            Some(blueprint) => blueprint,
+            None => load_capture(path, &declared).await?,
+        });
+    }
+    Ok(compare(&snapshots[0], &snapshots[1]))
+}
+
+fn is_capture(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+}
+
+/// Load a capture export or proxy cassette, naming paths that match one of
+/// `declared` after it
+pub async fn load_capture(path: &Path, declared: &[String]) -> Result<ApiSnapshot> {
+    let source = path.display().to_string();
+    let content = tokio::fs::read_to_string(path).await
+        .map_err(|e| BackworksError::config(format!("Failed to read capture {}: {}", source, e)))?;
+    let invalid = |e: serde_json::Error| BackworksError::config(format!("{} is not a capture export or proxy cassette: {}", source, e));
+    let value: Value = serde_json::from_str(&content).map_err(invalid)?;
+
+    if value.is_array() {
+        let recordings: Vec<Recording> = serde_json::from_value(value).map_err(invalid)?;
+        let exchanges = recordings.iter().map(|recording| Exchange {
+            method: &recording.request.method,
+            path: &recording.request.path,
+            status: Some(recording.response.status),
+            body: Some(&recording.response.body),
+        });
+        return Ok(ApiSnapshot::from_exchanges(&source, exchanges, declared));
+    }
+
+    let requests: Vec<CapturedRequest> = serde_json::from_value(value.get("requests").cloned().unwrap_or(Value::Null)).map_err(invalid)?;
+    let bodies: Vec<Option<Value>> = requests.iter()
+        .map(|request| match request.response {
+            Some(ref response) => response.body.clone(),
+            None => request.response_body.as_deref().and_then(|body| serde_json::from_str(body).ok()),
+        })
+        .collect();
+    let exchanges = requests.iter().zip(&bodies).map(|(request, body)| Exchange {
+        method: &request.method,
+        path: &request.path,
+        status: request.response.as_ref().map(|response| response.status_code).or(request.response_status),
+        body: body.as_ref(),
+    });
+    Ok(ApiSnapshot::from_exchanges(&source, exchanges, declared))
+}
+
+/// What changed from `baseline` to `current`
+pub fn compare(baseline: &ApiSnapshot, current: &ApiSnapshot) -> DriftReport {
+    let added = current.endpoints.keys().filter(|key| !baseline.endpoints.contains_key(key)).cloned().collect();
+    let removed = baseline.endpoints.keys().filter(|key| !current.endpoints.contains_key(key)).cloned().collect();
+
+    let mut changed = Vec::new();
+    for (key, before) in &baseline.endpoints {
+        let Some(after) = current.endpoints.get(key) else { continue };
+        let mut drift = EndpointDrift { method: key.method.clone(), path: key.path.clone(), ..Default::default() };
+        if baseline.observed && current.observed {
+            drift.statuses_added = after.statuses.difference(&before.statuses).copied().collect();
+            drift.statuses_removed = before.statuses.difference(&after.statuses).copied().collect();
+            drift.fields_added = after.body.keys().filter(|field| !before.body.contains_key(*field)).cloned().collect();
+            drift.fields_removed = before.body.keys().filter(|field| !after.body.contains_key(*field)).cloned().collect();
+            drift.type_changes = before.body.iter()
+                .filter_map(|(field, types)| {
+                    let now = after.body.get(field).filter(|now| *now != types)?;
+                    Some(TypeChange { field: field.clone(), before: types.iter().cloned().collect(), after: now.iter().cloned().collect() })
+                })
+                .collect();
+        }
+        if !baseline.observed && !current.observed {
+            let only = |from: &BTreeMap<String, ParameterShape>, other: &BTreeMap<String, ParameterShape>| from.iter()
+                .filter(|(name, _)| !other.contains_key(*name))
+                .map(|(name, shape)| Parameter { name: name.clone(), shape: shape.clone() })
+                .collect();
+            drift.parameters_added = only(&after.parameters, &before.parameters);
+            drift.parameters_removed = only(&before.parameters, &after.parameters);
+            drift.parameter_changes = before.parameters.iter()
+                .filter_map(|(name, shape)| {
+                    let now = after.parameters.get(name).filter(|now| *now != shape)?;
+                    Some(ParameterChange { name: name.clone(), before: shape.clone(), after: now.clone() })
+                })
+                .collect();
+        }
+        if !drift.is_empty() {
+            changed.push(drift);
+        }
+    }
+
+    DriftReport { baseline: baseline.source.clone(), current: current.source.clone(), added, removed, changed }
+}
+
+impl EndpointDrift {
+    fn is_empty(&self) -> bool {
+        self.statuses_added.is_empty() && self.statuses_removed.is_empty() && self.fields_added.is_empty()
+            && self.fields_removed.is_empty() && self.type_changes.is_empty() && self.parameters_added.is_empty()
+            && self.parameters_removed.is_empty() && self.parameter_changes.is_empty()
+    }
+
+    /// Whether clients relying on the baseline could break
+    pub fn is_breaking(&self) -> bool {
+        !self.fields_removed.is_empty() || !self.type_changes.is_empty() || !self.statuses_added.is_empty()
+            || self.parameters_added.iter().any(|parameter| parameter.shape.required)
+            || self.parameter_changes.iter().any(ParameterChange::is_breaking)
+    }
+}
+
+impl ParameterChange {
+    /// A new type, or a parameter that became required
+    pub fn is_breaking(&self) -> bool {
+        self.before.param_type != self.after.param_type || (self.after.required && !self.before.required)
+    }
+}
+
+impl fmt::Display for ParameterShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}{}", self.location, self.param_type, if self.required { ", required" } else { "" })
+    }
+}
+
+impl DriftReport {
+    pub fn has_drift(&self) -> bool {
+        !self.added.is_empty() || !self.removed.is_empty() || !self.changed.is_empty()
+    }
+
+    /// Removed endpoints, removed fields, changed field types and new statuses
+    pub fn has_breaking_changes(&self) -> bool {
+        !self.removed.is_empty() || self.changed.iter().any(EndpointDrift::is_breaking)
+    }
+
+    pub fn render_text(&self) -> String {
+        let mut out = String::new();
+        self.write_text(&mut out).expect("writing to a String cannot fail");
+        out
+    }
+
+    fn write_text(&self, out: &mut String) -> fmt::Result {
+        writeln!(out, "API drift from {} to {}", self.baseline, self.current)?;
+        if !self.has_drift() {
+            return writeln!(out, "\n✅ No drift");
+        }
+        for key in &self.removed {
+            writeln!(out, "  ❌ removed  {}", key)?;
+        }
+        for key in &self.added {
+            writeln!(out, "  ➕ added    {}", key)?;
+        }
+        for drift in &self.changed {
+            writeln!(out, "  {} changed  {} {}", if drift.is_breaking() { "⚠️ " } else { "✏️ " }, drift.method, drift.path)?;
+            let statuses = |statuses: &[u16]| statuses.iter().map(u16::to_string).collect::<Vec<_>>().join(", ");
+            if !drift.statuses_added.is_empty() {
+                writeln!(out, "       new status {}", statuses(&drift.statuses_added))?;
+            }
+            if !drift.statuses_removed.is_empty() {
+                writeln!(out, "       no longer {}", statuses(&drift.statuses_removed))?;
+            }
+            for field in &drift.fields_removed {
+                writeln!(out, "       - {}", field)?;
+            }
+            for field in &drift.fields_added {
+                writeln!(out, "       + {}", field)?;
+            }
+            for change in &drift.type_changes {
+                writeln!(out, "       ~ {}: {} -> {}", change.field, change.before.join("|"), change.after.join("|"))?;
+            }
+            for parameter in &drift.parameters_removed {
+                writeln!(out, "       - parameter {} ({})", parameter.name, parameter.shape)?;
+            }
+            for parameter in &drift.parameters_added {
+                writeln!(out, "       + parameter {} ({})", parameter.name, parameter.shape)?;
+            }
+            for change in &drift.parameter_changes {
+                writeln!(out, "       ~ parameter {}: {} -> {}", change.name, change.before, change.after)?;
+            }
+        }
+        writeln!(out, "\n{} added, {} removed, {} changed{}", self.added.len(), self.removed.len(), self.changed.len(),
+            if self.has_breaking_changes() { " (breaking)" } else { "" })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn snapshot(source: &str, exchanges: &[(&str, &str, u16, Value)], declared: &[String]) -> ApiSnapshot {
+        ApiSnapshot::from_exchanges(source, exchanges.iter().map(|(method, path, status, body)| Exchange {
+            method,
+            path,
+            status: Some(*status),
+            body: Some(body),
+        }), declared)
+    }
+
+    #[test]
+    fn test_drift_between_captures() {
+        let before = snapshot("before.json", &[
+            ("GET", "/users/1", 200, json!({ "id": 1, "name": "Ada", "tags": ["a"] })),
+            ("GET", "/users/2", 404, json!({ "error": "not found" })),
+            ("GET", "/health", 200, json!("ok")),
+        ], &[]);
+        let after = snapshot("after.json", &[
+            ("get", "/users/3f2504e0-4f89-11d3-9a0c-0305e82c3301", 200, json!({ "id": "u1", "tags": [], "email": "a@b.c" })),
+            ("POST", "/users", 201, json!({ "id": 2 })),
+            ("GET", "/health", 200, json!("ok")),
+        ], &[]);
+
+        let report = compare(&before, &after);
+        assert_eq!(report.added, vec![EndpointKey { method: "POST".to_string(), path: "/users".to_string() }]);
+        assert!(report.removed.is_empty());
+        assert_eq!(report.changed, vec![EndpointDrift {
+            method: "GET".to_string(),
+            path: "/users/{id}".to_string(),
+            statuses_added: vec![],
+            statuses_removed: vec![404],
+            fields_added: vec!["/email".to_string()],
+            fields_removed: vec!["/error".to_string(), "/name".to_string(), "/tags/*".to_string()],
+            type_changes: vec![TypeChange { field: "/id".to_string(), before: vec!["number".to_string()], after: vec!["string".to_string()] }],
+            ..Default::default()
+        }]);
+        assert!(report.has_breaking_changes());
+        let text = report.render_text();
+        assert!(text.contains("➕ added    POST /users"));
+        assert!(text.contains("~ /id: number -> string"));
+        assert!(text.ends_with("1 added, 0 removed, 1 changed (breaking)\n"));
+
+        assert!(!compare(&before, &before).has_drift());
+    }
+
+    #[test]
+    fn test_drift_against_a_blueprint() {
+        let config: BackworksConfig = serde_yaml::from_str(r#"
+name: shop
+endpoints:
+  user: { path: "/users/{user_id}", methods: [GET, DELETE] }
+"#).unwrap();
+        let blueprint = ApiSnapshot::from_blueprint("backworks.yaml", &config);
+        let capture = snapshot("traffic.json", &[
+            ("GET", "/users/7", 200, json!({ "id": 7 })),
+            ("GET", "/orders/9", 200, json!({ "id": 9 })),
+        ], &blueprint.declared_paths());
+
+        let report = compare(&blueprint, &capture);
+        let keys = |keys: &[EndpointKey]| keys.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(keys(&report.added), vec!["GET /orders/{id}"]);
+        assert_eq!(keys(&report.removed), vec!["DELETE /users/{user_id}"]);
+        assert!(report.changed.is_empty());
+    }
+}
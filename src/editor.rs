@@ -0,0 +1,228 @@
+//! Blueprint editing from the dashboard
+//!
+//! The Studio reads the blueprint file the API server was started from,
+//! reviews drafts of it (validation, a line diff and the API changelog
+//! against the file) and writes them back. A write reloads the API server,
+//! so saved changes are served right away. Files the blueprint `includes`
+//! are not edited here.
+
+use crate::admin::{ReloadSummary, Reloader};
+use crate::changelog::Changelog;
+use crate::config::{load_yaml_config, BackworksConfig};
+use crate::drift::ApiSnapshot;
+use crate::error::{BackworksError, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The blueprint file as the editor shows it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlueprintDocument {
+    pub path: String,
+    pub content: String,
+    /// Hash of the content; writes based on another version are refused
+    pub version: String,
+}
+
+/// An edited blueprint
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlueprintDraft {
+    pub content: String,
+    /// Version the draft was edited from
+    pub base: Option<String>,
+}
+
+/// What saving a draft would change
+#[derive(Debug, Clone, Serialize)]
+pub struct DraftReview {
+    pub valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Unified diff from the file to the draft
+    pub diff: String,
+    /// Endpoint changes, when both versions load
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changelog: Option<Changelog>,
+}
+
+/// Result of saving a draft
+#[derive(Debug, Clone, Serialize)]
+pub struct SavedBlueprint {
+    pub version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reload: Option<ReloadSummary>,
+    /// Why the server still serves the previous blueprint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reload_error: Option<String>,
+}
+
+/// Why a draft was not saved
+#[derive(Debug)]
+pub enum SaveError {
+    /// The file changed since the draft's base version
+    Conflict(BlueprintDocument),
+    Invalid(DraftReview),
+    Failed(BackworksError),
+}
+
+impl From<BackworksError> for SaveError {
+    fn from(e: BackworksError) -> Self {
+        Self::Failed(e)
+    }
+}
+
+/// Reads, reviews and writes the blueprint of a reloadable server
+#[derive(Clone)]
+pub struct BlueprintEditor {
+    reloader: Arc<Reloader>,
+}
+
+impl std::fmt::Debug for BlueprintEditor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlueprintEditor").field("path", &self.path()).finish()
+    }
+}
+
+impl BlueprintEditor {
+    pub fn new(reloader: Arc<Reloader>) -> Self {
+        Self { reloader }
+    }
+
+    /// Token writes need, that of the blueprint being served
+    pub fn admin_token(&self) -> Option<String> {
+        self.reloader.config()?.admin.as_ref().map(|admin| admin.token.clone())
+    }
+
+    pub async fn read(&self) -> Result<BlueprintDocument> {
+        let content = tokio::fs::read_to_string(self.path()).await?;
+        Ok(BlueprintDocument { path: self.path().display().to_string(), version: version(&content), content })
+    }
+
+    /// Validate `draft` and compare it with the file
+    pub async fn review(&self, draft: &BlueprintDraft) -> Result<DraftReview> {
+        let current = self.read().await?;
+        let diff = similar::TextDiff::from_lines(&current.content, &draft.content)
+            .unified_diff()
+            .header(&current.path, &current.path)
+            .to_string();
+        let (valid, error, draft_config) = match self.load_draft(&draft.content).await {
+            Ok(config) => (true, None, Some(config)),
+            Err(e) => (false, Some(e.to_string()), None),
+        };
+        let changelog = match (load_yaml_config(self.path()).await, draft_config) {
+            (Ok(before), Some(after)) => Some(Changelog::between(
+                "current",
+                &ApiSnapshot::from_blueprint(&current.path, &before),
+                "draft",
+                &ApiSnapshot::from_blueprint(&current.path, &after),
+            )),
+            _ => None,
+        };
+        Ok(DraftReview { valid, error, diff, changelog })
+    }
+
+    /// Write a valid `draft` over the file and reload the server
+    pub async fn save(&self, draft: &BlueprintDraft) -> std::result::Result<SavedBlueprint, SaveError> {
+        let current = self.read().await?;
+        if draft.base.as_ref().is_some_and(|base| *base != current.version) {
+            return Err(SaveError::Conflict(current));
+        }
+        let review = self.review(draft).await?;
+        if !review.valid {
+            return Err(SaveError::Invalid(review));
+        }
+
+        self.write(&draft.content).await?;
+        tracing::info!("📝 Blueprint {} saved from the dashboard", current.path);
+
+        let (reload, reload_error) = match self.reloader.reload().await {
+            Ok(summary) => (Some(summary), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        Ok(SavedBlueprint { version: version(&draft.content), reload, reload_error })
+    }
+
+    /// Replace the file in one step, so a reload never reads half of it
+    async fn write(&self, content: &str) -> Result<()> {
+        let partial = sibling(self.path(), "partial");
+        tokio::fs::write(&partial, content).await?;
+        tokio::fs::rename(&partial, self.path()).await?;
+        Ok(())
+    }
+
+    fn path(&self) -> &Path {
+        self.reloader.source()
+    }
+
+    /// Load `content` as the blueprint would load, so includes resolve from
+    /// the blueprint's directory
+    async fn load_draft(&self, content: &str) -> Result<BackworksConfig> {
+        let draft = sibling(self.path(), &format!("draft-{}.yaml", uuid::Uuid::new_v4()));
+        tokio::fs::write(&draft, content).await?;
+        let loaded = load_yaml_config(&draft).await;
+        let _ = tokio::fs::remove_file(&draft).await;
+        loaded
+    }
+}
+
+/// A hidden file next to `path`
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(format!(".{}.{}", name, suffix))
+}
+
+fn version(content: &str) -> String {
+    Sha256::digest(content.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::balancer::LoadBalancerRegistry;
+    use crate::pipeline::MiddlewareRegistry;
+
+    #[tokio::test]
+    async fn test_drafts_are_reviewed_before_they_are_saved() {
+        let dir = std::env::temp_dir().join(format!("backworks_editor_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("backworks.yaml");
+        let original = "name: shop\nendpoints:\n  users: { path: /users, methods: [GET], mode: mock, mock: { schema: { name: $name } } }\n";
+        std::fs::write(&path, original).unwrap();
+        let editor = BlueprintEditor::new(Arc::new(Reloader::new(path.clone(), MiddlewareRegistry::default(), LoadBalancerRegistry::default())));
+
+        let document = editor.read().await.unwrap();
+        assert_eq!(document.content, original);
+        let draft = |content: &str, base: Option<&str>| BlueprintDraft { content: content.to_string(), base: base.map(str::to_string) };
+
+        let edited = format!("{}  orders: {{ path: /orders, methods: [GET], mode: mock, mock: {{ schema: {{ id: $uuid }} }} }}\n", original);
+        let review = editor.review(&draft(&edited, None)).await.unwrap();
+        assert!(review.valid);
+        assert!(review.diff.contains("+  orders: { path: /orders"));
+        let changelog = review.changelog.unwrap();
+        assert_eq!(changelog.entries.len(), 1);
+        assert_eq!(changelog.entries[0].path, "/orders");
+
+        let review = editor.review(&draft("name: shop\nendpoints: {}\n", None)).await.unwrap();
+        assert!(!review.valid && review.error.unwrap().contains("At least one endpoint"));
+        assert!(matches!(editor.save(&draft("name: shop\nendpoints: {}\n", None)).await, Err(SaveError::Invalid(_))));
+        assert!(matches!(editor.save(&draft(&edited, Some("stale"))).await, Err(SaveError::Conflict(_))));
+
+        // Without a running server the file is written but nothing reloads
+        let saved = editor.save(&draft(&edited, Some(&document.version))).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), edited);
+        assert!(saved.reload.is_none() && saved.reload_error.is_some());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_editor_needs_an_admin_token() {
+        let blueprint = |extra: &str| serde_yaml::from_str::<BackworksConfig>(&format!(
+            "name: shop\ndashboard: {{ enabled: true, editor: true }}\n{}endpoints:\n  users: {{ path: /users, mode: mock, mock: {{ schema: {{ name: $name }} }} }}\n", extra,
+        )).unwrap();
+        let err = crate::config::validate_config(&blueprint("")).unwrap_err().to_string();
+        assert!(err.contains("dashboard.editor needs admin.token"), "{}", err);
+        crate::config::validate_config(&blueprint("admin: { token: secret }\n")).unwrap();
+    }
+}
@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::signal;
 use tracing::{info, error};
@@ -5,8 +6,19 @@ use tracing::{info, error};
 use crate::config::BackworksConfig;
 use crate::server::BackworksServer;
 use crate::dashboard::Dashboard;
+use crate::capture::CaptureHandler;
+use crate::admin::Reloader;
+use crate::editor::BlueprintEditor;
 use crate::runtime::RuntimeManager;
 use crate::plugin::PluginManager;
+use crate::alerting::AlertEngine;
+use crate::rollout::RolloutSchedule;
+use crate::deprecation::{DeprecatedEndpoint, DeprecationUsage};
+use crate::scenario::Scenarios;
+use crate::chaos::Chaos;
+use crate::tls::Tls;
+use crate::crud::CrudTables;
+use crate::security::Security;
 use crate::error::Result;
 
 pub struct BackworksEngine {
@@ -20,6 +32,16 @@ pub struct BackworksEngine {
 
 impl BackworksEngine {
     pub async fn new(config: BackworksConfig) -> Result<Self> {
+        Self::build(config, None).await
+    }
+    
+    /// An engine whose blueprint, loaded from `source`, can be reloaded
+    /// through the admin API and edited on the dashboard
+    pub async fn from_blueprint(config: BackworksConfig, source: PathBuf) -> Result<Self> {
+        Self::build(config, Some(source)).await
+    }
+    
+    async fn build(config: BackworksConfig, source: Option<PathBuf>) -> Result<Self> {
         let config = Arc::new(config);
         
         info!("🎯 Initializing Backworks Engine");
@@ -27,26 +49,7 @@ impl BackworksEngine {
         info!("   Mode: {:?}", config.mode);
         info!("   Endpoints: {}", config.endpoints.len());
         
-        // Initialize plugin manager
-        let plugin_manager = PluginManager::new();
-        
-        // Initialize plugins from configuration
-        info!("🔌 Initializing plugins from configuration...");
-        
-        // Load external plugins from discovery configuration
-        if let Err(e) = plugin_manager.initialize_from_discovery(&config.plugin_discovery).await {
-            error!("Failed to initialize plugins from discovery: {}", e);
-        }
-        
-        // Load plugins specified in configuration
-        for (plugin_name, plugin_config) in &config.plugins {
-            if plugin_config.enabled {
-                info!("🔌 Loading plugin: {}", plugin_name);
-                if let Err(e) = plugin_manager.register_plugin_from_config(plugin_name, plugin_config, None).await {
-                    error!("Failed to load plugin {}: {}", plugin_name, e);
-                }
-            }
-        }
+        let plugin_manager = load_plugins(&config).await?;
         
         info!("🔌 Plugin initialization completed");
         
@@ -55,11 +58,55 @@ impl BackworksEngine {
         let runtime_config = crate::runtime::RuntimeManagerConfig::default(); // Create empty config for now
         let runtime_manager = RuntimeManager::new(runtime_config);
         
+        // Scenarios and fault settings changed on the dashboard apply to the API server
+        let scenarios = Scenarios::new(&config)?;
+        let chaos = Chaos::new(&config)?;
+        
+        // Requests are served through the reloader so reloads can swap routes
+        let middleware = plugin_manager.middleware_registry().await;
+        // `cache.type` other than `memory` names the plugin keeping cached responses
+        let middleware = match config.cache.as_ref().filter(|cache| cache.cache_type != "memory") {
+            Some(cache) => middleware.with_cache_store(plugin_manager.cache(&cache.cache_type).await?),
+            None => middleware,
+        };
+        let load_balancers = plugin_manager.load_balancer_registry().await;
+        let plugin_routes = plugin_manager.routes().await;
+        let reloader = source.map(|source| Arc::new(
+            Reloader::new(source, middleware.clone(), load_balancers.clone()).with_plugin_routes(plugin_routes.clone())
+        ));
+        
+        // Sessions are started and stopped through the admin API or the dashboard
+        let capture_config = config.capture.clone().unwrap_or_default();
+        let scrubber = Security::new(&config)?.scrubber().cloned();
+        let mut capture = capture_config.enabled.unwrap_or(true).then(|| CaptureHandler::new(capture_config).with_scrubber(scrubber));
+        
+        // The API server and dashboard present the same certificate
+        let tls = Tls::from_config(&config)?;
+        
         // Initialize dashboard if enabled
         let dashboard = if let Some(ref dashboard_config) = &config.dashboard {
             if dashboard_config.enabled {
                 info!("🎨 Initializing dashboard on port {}...", dashboard_config.port);
-                Some(Arc::new(Dashboard::new(dashboard_config.clone())))
+                let mut dashboard = Dashboard::new(dashboard_config.clone())
+                    .with_rollouts(RolloutSchedule::from_config(&config))
+                    .with_deprecations(DeprecationUsage::new(DeprecatedEndpoint::from_config(&config)))
+                    .with_scenarios(scenarios.clone())
+                    .with_chaos(chaos.clone())
+                    .with_plugins(plugin_manager.clone())
+                    .with_plugin_pages(plugin_manager.dashboard_routes().await)?;
+                
+                // Captured requests stream to the dashboard, which starts and stops sessions too
+                capture = capture.map(|handler| handler.with_events(dashboard.events()));
+                if let Some(ref handler) = capture {
+                    dashboard = dashboard.with_capture(handler.clone());
+                }
+                if let Some(reloader) = reloader.as_ref().filter(|_| dashboard_config.editor.unwrap_or(false)) {
+                    dashboard = dashboard.with_editor(BlueprintEditor::new(Arc::clone(reloader)));
+                }
+                if let Some(tls) = tls.as_ref().filter(|_| dashboard_config.https.unwrap_or(false)) {
+                    dashboard = dashboard.with_tls(tls.clone());
+                }
+                Some(Arc::new(dashboard))
             } else {
                 None
             }
@@ -67,13 +114,37 @@ impl BackworksEngine {
             None
         };
         
+        // Tables of auto-CRUD endpoints are read once the plugins are up
+        let crud = CrudTables::load(&config, &plugin_manager).await?;
+        
         // Initialize main server
         info!("🚀 Initializing API server on {}:{}...", config.server.host, config.server.port);
         let server = BackworksServer::new(
             config.clone(),
             plugin_manager.clone(),
             dashboard.clone(),
-        )?;
+        )?
+        .with_crud_tables(crud)
+        .with_middleware(middleware)
+        .with_load_balancers(load_balancers)
+        .with_plugin_routes(plugin_routes)
+        .with_scenarios(scenarios)
+        .with_chaos(chaos);
+        let server = match capture {
+            Some(capture) => {
+                capture.start().await?;
+                server.with_capture(capture)
+            }
+            None => server,
+        };
+        let server = match reloader {
+            Some(reloader) => server.with_reloader(reloader),
+            None => server,
+        };
+        let server = match tls {
+            Some(tls) => server.with_tls(tls),
+            None => server,
+        };
         
         Ok(Self {
             config,
@@ -84,6 +155,11 @@ impl BackworksEngine {
         })
     }
     
+    /// The API server's routes, to answer requests in process
+    pub(crate) fn app(&self) -> Result<axum::Router> {
+        self.server.create_app()
+    }
+
     pub async fn start(self) -> Result<()> {
         info!("🚀 Starting Backworks Engine...");
         
@@ -91,14 +167,24 @@ impl BackworksEngine {
         self.print_startup_info();
         
         // Start dashboard if enabled
-        let dashboard_handle = if let Some(dashboard) = self.dashboard.clone() {
-            Some(tokio::spawn(async move {
+        let dashboard_handle = self.dashboard.clone().map(|dashboard| tokio::spawn(async move {
                 if let Err(e) = dashboard.start().await {
                     error!("Dashboard error: {}", e);
                 }
-            }))
-        } else {
-            None
+            }));
+        
+        // Start alerting engine if configured
+        let alert_handle = match self.config.monitoring.as_ref().and_then(|m| m.alerts.as_ref()) {
+            Some(alerts) if alerts.enabled.unwrap_or(false) => {
+                match AlertEngine::new(alerts, self.server.request_stats(), self.plugin_manager.clone()) {
+                    Ok(engine) => Some(engine.spawn()),
+                    Err(e) => {
+                        error!("Failed to start alerting engine: {}", e);
+                        None
+                    }
+                }
+            }
+            _ => None,
         };
         
         // Start main server
@@ -133,6 +219,10 @@ impl BackworksEngine {
             handle.abort();
         }
         
+        if let Some(handle) = alert_handle {
+            handle.abort();
+        }
+        
         info!("✅ Backworks shutdown complete");
         Ok(())
     }
@@ -141,11 +231,13 @@ impl BackworksEngine {
         println!("\n🎉 Backworks is running!");
         println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
         println!("📋 API: {}", self.config.name);
-        println!("🌐 Server: http://{}:{}", self.config.server.host, self.config.server.port);
+        let scheme = if self.config.server.tls.is_some() { "https" } else { "http" };
+        println!("🌐 Server: {}://{}:{}", scheme, self.config.server.host, self.config.server.port);
         
         if let Some(ref dashboard) = &self.config.dashboard {
             if dashboard.enabled {
-                println!("🎨 Dashboard: http://localhost:{}", dashboard.port);
+                let scheme = if self.config.server.tls.is_some() && dashboard.https.unwrap_or(false) { "https" } else { "http" };
+                println!("🎨 Dashboard: {}://localhost:{}", scheme, dashboard.port);
             }
         }
         
@@ -181,6 +273,42 @@ impl BackworksEngine {
     }
 }
 
+/// Register the blueprint's plugins and the ones plugin discovery finds,
+/// under the blueprint's resilience policies
+pub async fn load_plugins(config: &BackworksConfig) -> Result<PluginManager> {
+    let plugin_manager = PluginManager::new();
+    
+    // Initialize plugins from configuration
+    info!("🔌 Initializing plugins from configuration...");
+    
+    // Load external plugins from discovery configuration
+    if let Err(e) = plugin_manager.initialize_from_discovery(&config.plugin_discovery).await {
+        error!("Failed to initialize plugins from discovery: {}", e);
+    }
+    
+    // Load plugins specified in configuration
+    for (plugin_name, plugin_config) in &config.plugins {
+        if plugin_config.enabled {
+            info!("🔌 Loading plugin: {}", plugin_name);
+            if let Err(e) = plugin_manager.register_plugin_from_config(plugin_name, plugin_config, None).await {
+                error!("Failed to load plugin {}: {}", plugin_name, e);
+            }
+        }
+    }
+    
+    // Resilience policies in the blueprint cover discovered plugins too
+    let policies = config.plugins.iter()
+        .filter(|(_, plugin)| plugin.enabled)
+        .map(|(name, plugin)| (name.clone(), plugin.resilience.clone()))
+        .collect();
+    plugin_manager.apply_resilience_policies(policies).await;
+    
+    // A plugin must not run without the plugins it requires
+    plugin_manager.check_requirements().await?;
+    
+    Ok(plugin_manager)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -194,6 +322,9 @@ mod tests {
             methods: vec!["GET".to_string()],
             description: None,
             mode: None,
+            modes: Vec::new(),
+            mock: None,
+            proxy: None,
             // mock and mock_responses fields removed (deprecated)
             runtime: None,
             database: None,
@@ -205,6 +336,22 @@ mod tests {
             validation: None,
             monitoring: None,
             plugin: None,
+            compare: None,
+            rollout: None,
+            template: None,
+            transform: None,
+            deprecated: None,
+            coerce: None,
+            static_files: None,
+            middleware: Vec::new(),
+            scenarios: HashMap::new(),
+            chaos: None,
+            retention: None,
+            timeout: None,
+            compression: None,
+            concurrency: None,
+            webhook: None,
+            csrf: None,
         });
         
         BackworksConfig {
@@ -222,6 +369,18 @@ mod tests {
             cache: None,
             security: None,
             monitoring: None,
+            state: None,
+            errors: None,
+            localization: None,
+            scenarios: None,
+            journal: None,
+            capture: None,
+            admin: None,
+            schedules: HashMap::new(),
+            sessions: None,
+            docs: None,
+            strict_env: false,
+            route_conflicts: Default::default(),
             global_headers: HashMap::new(),
             logging: Default::default(),
         }
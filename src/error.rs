@@ -1,4 +1,5 @@
 use thiserror::Error;
+use serde::{Deserialize, Serialize};
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
@@ -43,11 +44,12 @@ pub enum BackworksError {
     #[error("Request error: {0}")]
     Request(#[from] reqwest::Error),
     
+    // Boxed: the handlebars errors would otherwise make every Result large
     #[error("Template error: {0}")]
-    Template(#[from] handlebars::TemplateError),
+    Template(Box<handlebars::TemplateError>),
     
     #[error("Render error: {0}")]
-    Render(#[from] handlebars::RenderError),
+    Render(Box<handlebars::RenderError>),
     
     // Plugin system errors
     #[error("Plugin initialization failed: {0}")]
@@ -64,6 +66,18 @@ pub enum BackworksError {
     
     #[error("Plugin not found: {0}")]
     PluginNotFound(String),
+    
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    
+    #[error("Timeout: {0}")]
+    Timeout(String),
+    
+    #[error("Unavailable: {0}")]
+    Unavailable(String),
 }
 
 impl BackworksError {
@@ -98,30 +112,112 @@ impl BackworksError {
     pub fn plugin<T: ToString>(msg: T) -> Self {
         Self::Plugin(msg.to_string())
     }
+    
+    pub fn unauthorized<T: ToString>(msg: T) -> Self {
+        Self::Unauthorized(msg.to_string())
+    }
+    
+    pub fn forbidden<T: ToString>(msg: T) -> Self {
+        Self::Forbidden(msg.to_string())
+    }
+    
+    pub fn timeout<T: ToString>(msg: T) -> Self {
+        Self::Timeout(msg.to_string())
+    }
+    
+    pub fn unavailable<T: ToString>(msg: T) -> Self {
+        Self::Unavailable(msg.to_string())
+    }
+    
+    /// Whether the request was deliberately refused (bad credentials,
+    /// missing permission) rather than failing
+    pub fn is_rejection(&self) -> bool {
+        matches!(self, BackworksError::Unauthorized(_) | BackworksError::Forbidden(_))
+    }
+    
+    /// HTTP status used when this error is turned into a response
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            BackworksError::Config(_) => StatusCode::BAD_REQUEST,
+            BackworksError::Runtime(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            BackworksError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            BackworksError::AI(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            BackworksError::Capture(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            BackworksError::Http(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            BackworksError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            BackworksError::Serialization(_) => StatusCode::BAD_REQUEST,
+            BackworksError::Json(_) => StatusCode::BAD_REQUEST,
+            BackworksError::Plugin(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            BackworksError::Server(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            BackworksError::Request(_) => StatusCode::BAD_REQUEST,
+            BackworksError::Template(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            BackworksError::Render(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            BackworksError::PluginInitializationFailed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            BackworksError::PluginTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            BackworksError::CriticalPluginFailure(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            BackworksError::PluginConfigInvalid(_) => StatusCode::BAD_REQUEST,
+            BackworksError::PluginNotFound(_) => StatusCode::NOT_FOUND,
+            BackworksError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            BackworksError::Forbidden(_) => StatusCode::FORBIDDEN,
+            BackworksError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            BackworksError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+impl From<handlebars::TemplateError> for BackworksError {
+    fn from(error: handlebars::TemplateError) -> Self {
+        Self::Template(Box::new(error))
+    }
+}
+
+impl From<handlebars::RenderError> for BackworksError {
+    fn from(error: handlebars::RenderError) -> Self {
+        Self::Render(Box::new(error))
+    }
+}
+
+/// Where in the request pipeline an error originated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorSource {
+    /// Endpoint handler (runtime, database or plugin execution) failed
+    Handler,
+    /// A critical plugin rejected the request in `before_request`
+    Plugin,
+    /// The framework produced the error (unknown route, bad body, ...)
+    Framework,
+}
+
+/// Final error attached to an error response's extensions.
+///
+/// Trailing middleware, metrics and plugin `on_error`/`after_response` hooks
+/// read this to see why a request failed, regardless of where it failed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestError {
+    pub status: u16,
+    pub message: String,
+    pub source: ErrorSource,
+}
+
+impl RequestError {
+    pub fn new<T: ToString>(status: StatusCode, message: T, source: ErrorSource) -> Self {
+        Self {
+            status: status.as_u16(),
+            message: message.to_string(),
+            source,
+        }
+    }
 }
 
 impl IntoResponse for BackworksError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match &self {
-            BackworksError::Config(_) => (StatusCode::BAD_REQUEST, self.to_string()),
-            BackworksError::Runtime(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
-            BackworksError::Database(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
-            BackworksError::AI(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
-            BackworksError::Capture(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
-            BackworksError::Http(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
-            BackworksError::Io(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
-            BackworksError::Serialization(_) => (StatusCode::BAD_REQUEST, self.to_string()),
-            BackworksError::Json(_) => (StatusCode::BAD_REQUEST, self.to_string()),
-            BackworksError::Plugin(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
-            BackworksError::Server(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
-            BackworksError::Request(_) => (StatusCode::BAD_REQUEST, self.to_string()),
-            BackworksError::Template(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
-            BackworksError::Render(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
-            BackworksError::PluginInitializationFailed(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
-            BackworksError::PluginTimeout(_) => (StatusCode::GATEWAY_TIMEOUT, self.to_string()),
-            BackworksError::CriticalPluginFailure(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
-            BackworksError::PluginConfigInvalid(_) => (StatusCode::BAD_REQUEST, self.to_string()),
-            BackworksError::PluginNotFound(_) => (StatusCode::NOT_FOUND, self.to_string()),
+        let status = self.status_code();
+        let error_message = self.to_string();
+        let source = match self {
+            BackworksError::CriticalPluginFailure(_) | BackworksError::PluginTimeout(_) => ErrorSource::Plugin,
+            BackworksError::Unauthorized(_) | BackworksError::Forbidden(_) => ErrorSource::Framework,
+            _ => ErrorSource::Handler,
         };
 
         let body = Json(serde_json::json!({
@@ -129,7 +225,9 @@ impl IntoResponse for BackworksError {
             "status": status.as_u16()
         }));
 
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        response.extensions_mut().insert(RequestError::new(status, error_message, source));
+        response
     }
 }
 
@@ -0,0 +1,187 @@
+//! Blueprint error catalogs
+//!
+//! A catalog maps stable error codes to a status, a message template (with
+//! optional per-locale variants) and a documentation URL, so every handler,
+//! plugin and transform produces the same error body for the same failure.
+//!
+//! Handlers raise catalog errors by returning (or throwing) `ctx.error(code,
+//! params)`, which serializes to `{"$error": {"code": ..., "params": {...}}}`.
+
+use crate::config::ErrorCatalogEntry;
+use crate::error::{BackworksError, BackworksResult};
+use axum::http::{HeaderMap, StatusCode};
+use handlebars::Handlebars;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Marker key handlers use to reference a catalog error
+pub const ERROR_MARKER: &str = "$error";
+
+/// Reference to a catalog entry produced by a handler
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorReference {
+    pub code: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+impl ErrorReference {
+    /// Extract an error reference from handler output, if it is one
+    pub fn from_output(value: &Value) -> Option<Self> {
+        serde_json::from_value(value.get(ERROR_MARKER)?.clone()).ok()
+    }
+}
+
+/// Rendered catalog error, serialized as the `error` object of the response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CatalogError {
+    pub code: String,
+    pub status: u16,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub docs_url: Option<String>,
+}
+
+impl CatalogError {
+    pub fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    pub fn body(&self) -> Value {
+        serde_json::json!({ "error": self })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ErrorCatalog {
+    entries: HashMap<String, ErrorCatalogEntry>,
+    templates: Handlebars<'static>,
+}
+
+impl ErrorCatalog {
+    /// Build a catalog, compiling every message template up front
+    pub fn new(entries: &HashMap<String, ErrorCatalogEntry>) -> BackworksResult<Self> {
+        let mut templates = Handlebars::new();
+        templates.register_escape_fn(handlebars::no_escape);
+
+        for (code, entry) in entries {
+            StatusCode::from_u16(entry.status)
+                .map_err(|_| BackworksError::config(format!("Error '{}' has invalid status {}", code, entry.status)))?;
+
+            templates.register_template_string(&template_name(code, None), &entry.message)?;
+            for (locale, message) in entry.messages.iter().flatten() {
+                templates.register_template_string(&template_name(code, Some(&locale.to_lowercase())), message)?;
+            }
+        }
+
+        Ok(Self { entries: entries.clone(), templates })
+    }
+
+    pub fn contains(&self, code: &str) -> bool {
+        self.entries.contains_key(code)
+    }
+
+    /// Render `code` with `params`, preferring the first locale in
+    /// `locales` that has a translation
+    pub fn render(&self, code: &str, params: &Value, locales: &[String]) -> BackworksResult<CatalogError> {
+        let entry = self.entries.get(code)
+            .ok_or_else(|| BackworksError::runtime(format!("Unknown error code '{}'", code)))?;
+
+        let localized = locales.iter()
+            .flat_map(|locale| {
+                let primary = locale.split('-').next().unwrap_or(locale).to_string();
+                [locale.clone(), primary]
+            })
+            .map(|locale| template_name(code, Some(&locale)))
+            .find(|name| self.templates.has_template(name))
+            .unwrap_or_else(|| template_name(code, None));
+
+        Ok(CatalogError {
+            code: code.to_string(),
+            status: entry.status,
+            message: self.templates.render(&localized, params)?,
+            docs_url: entry.docs_url.clone(),
+        })
+    }
+}
+
+fn template_name(code: &str, locale: Option<&str>) -> String {
+    match locale {
+        Some(locale) => format!("{}@{}", code, locale),
+        None => code.to_string(),
+    }
+}
+
+/// Preferred locales from an `Accept-Language` header, highest quality first
+pub fn accepted_locales(headers: &HeaderMap) -> Vec<String> {
+    let Some(header) = headers.get(axum::http::header::ACCEPT_LANGUAGE).and_then(|v| v.to_str().ok()) else {
+        return Vec::new();
+    };
+
+    let mut locales: Vec<(String, f32)> = header.split(',')
+        .filter_map(|part| {
+            let mut pieces = part.trim().split(';');
+            let tag = pieces.next()?.trim().to_lowercase();
+            if tag.is_empty() || tag == "*" {
+                return None;
+            }
+            let quality = pieces
+                .find_map(|p| p.trim().strip_prefix("q=").and_then(|q| q.parse().ok()))
+                .unwrap_or(1.0);
+            Some((tag, quality))
+        })
+        .collect();
+
+    locales.sort_by(|a, b| b.1.total_cmp(&a.1));
+    locales.into_iter().map(|(tag, _)| tag).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn catalog() -> ErrorCatalog {
+        let mut entries = HashMap::new();
+        entries.insert("ORDER_NOT_FOUND".to_string(), ErrorCatalogEntry {
+            status: 404,
+            message: "Order {{order_id}} was not found".to_string(),
+            docs_url: Some("https://docs.example.com/errors#order-not-found".to_string()),
+            messages: Some(HashMap::from([
+                ("de".to_string(), "Bestellung {{order_id}} wurde nicht gefunden".to_string()),
+            ])),
+        });
+        ErrorCatalog::new(&entries).unwrap()
+    }
+
+    #[test]
+    fn test_render_default_and_localized_messages() {
+        let catalog = catalog();
+        let params = json!({"order_id": 42});
+
+        let error = catalog.render("ORDER_NOT_FOUND", &params, &[]).unwrap();
+        assert_eq!(error.status, 404);
+        assert_eq!(error.message, "Order 42 was not found");
+
+        let error = catalog.render("ORDER_NOT_FOUND", &params, &["de-at".to_string()]).unwrap();
+        assert_eq!(error.message, "Bestellung 42 wurde nicht gefunden");
+
+        assert!(catalog.render("MISSING", &params, &[]).is_err());
+    }
+
+    #[test]
+    fn test_accepted_locales_order_by_quality() {
+        let mut headers = HeaderMap::new();
+        headers.insert("accept-language", "fr;q=0.5, de-DE, en;q=0.8".parse().unwrap());
+        assert_eq!(accepted_locales(&headers), vec!["de-de", "en", "fr"]);
+    }
+
+    #[test]
+    fn test_error_reference_from_output() {
+        let output = json!({"$error": {"code": "ORDER_NOT_FOUND", "params": {"order_id": 1}}});
+        let reference = ErrorReference::from_output(&output).unwrap();
+        assert_eq!(reference.code, "ORDER_NOT_FOUND");
+        assert!(ErrorReference::from_output(&json!({"id": 1})).is_none());
+    }
+}
@@ -0,0 +1,282 @@
+//! HAR import
+//!
+//! Browsers export their network log as a HAR (HTTP Archive) file. Importing
+//! one turns the API calls it recorded into a capture session, the same
+//! requests a capture session on a running server would have seen, from
+//! which `backworks generate` writes a blueprint.
+//!
+//! Entries count as API calls when the browser marks them as `fetch` or
+//! `xhr`, or, for HAR files without resource types, when they exchanged
+//! JSON. Pages, scripts, styles, images and failed requests are skipped.
+
+use crate::capture::{CaptureSession, CaptureStatus, CapturedRequest, CapturedResponse};
+use crate::error::{BackworksError, BackworksResult};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+struct Har {
+    log: HarLog,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarLog {
+    #[serde(default)]
+    entries: Vec<HarEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarEntry {
+    started_date_time: Option<String>,
+    #[serde(default)]
+    time: f64,
+    request: HarRequest,
+    response: HarResponse,
+    /// Chrome's request type: document, script, fetch, xhr, ...
+    #[serde(rename = "_resourceType")]
+    resource_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarRequest {
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: Vec<NameValue>,
+    #[serde(default)]
+    query_string: Vec<NameValue>,
+    post_data: Option<HarPostData>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarPostData {
+    mime_type: Option<String>,
+    text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarResponse {
+    status: u16,
+    #[serde(default)]
+    headers: Vec<NameValue>,
+    content: Option<HarContent>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarContent {
+    mime_type: Option<String>,
+    text: Option<String>,
+    encoding: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NameValue {
+    name: String,
+    value: String,
+}
+
+/// API calls read from a HAR file
+#[derive(Debug, Clone)]
+pub struct HarImport {
+    pub session: CaptureSession,
+    pub requests: Vec<CapturedRequest>,
+    /// Entries that were not API calls
+    pub skipped: usize,
+}
+
+/// Read the API calls of the HAR document `content` into a capture session
+/// named `name`
+pub fn import(content: &str, name: &str) -> BackworksResult<HarImport> {
+    let har: Har = serde_json::from_str(content)
+        .map_err(|e| BackworksError::config(format!("Not a HAR file: {}", e)))?;
+    let session_id = Uuid::new_v4();
+    let total = har.log.entries.len();
+    let requests: Vec<CapturedRequest> = har.log.entries.into_iter()
+        .filter(is_api_call)
+        .filter_map(|entry| captured(entry, session_id))
+        .collect();
+
+    let started_at = requests.iter().map(|request| request.timestamp).min().unwrap_or_else(Utc::now);
+    let ended_at = requests.iter().map(|request| request.timestamp + request.duration.unwrap_or_default()).max();
+    let session = CaptureSession {
+        id: session_id,
+        name: name.to_string(),
+        started_at,
+        ended_at: ended_at.or(Some(started_at)),
+        request_count: requests.len() as u64,
+        status: CaptureStatus::Stopped,
+    };
+    Ok(HarImport { session, skipped: total - requests.len(), requests })
+}
+
+fn is_json(mime_type: Option<&str>) -> bool {
+    mime_type.is_some_and(|mime_type| mime_type.split(';').next().unwrap_or_default().trim().ends_with("json"))
+}
+
+fn is_api_call(entry: &HarEntry) -> bool {
+    if entry.response.status == 0 {
+        return false;
+    }
+    match entry.resource_type.as_deref() {
+        Some(kind) => kind.eq_ignore_ascii_case("fetch") || kind.eq_ignore_ascii_case("xhr"),
+        None => is_json(entry.response.content.as_ref().and_then(|content| content.mime_type.as_deref()))
+            || is_json(entry.request.post_data.as_ref().and_then(|data| data.mime_type.as_deref())),
+    }
+}
+
+fn captured(entry: HarEntry, session_id: Uuid) -> Option<CapturedRequest> {
+    let url = url::Url::parse(&entry.request.url).ok()?;
+    let mut query_params: HashMap<String, String> = entry.request.query_string.into_iter()
+        .map(|pair| (pair.name, pair.value))
+        .collect();
+    if query_params.is_empty() {
+        query_params = url.query_pairs().into_owned().collect();
+    }
+    let body = entry.request.post_data.and_then(|data| data.text).filter(|text| !text.is_empty()).map(parse_body);
+    let response_body = entry.response.content.and_then(|content| {
+        let text = content.text.filter(|text| !text.is_empty())?;
+        match content.encoding.as_deref() {
+            Some("base64") => base64::engine::general_purpose::STANDARD.decode(&text).ok()
+                .and_then(|bytes| String::from_utf8(bytes).ok())
+                .map(parse_body),
+            _ => Some(parse_body(text)),
+        }
+    });
+    let response_headers = headers(entry.response.headers);
+    let duration = Duration::from_secs_f64(entry.time.max(0.0) / 1000.0);
+
+    Some(CapturedRequest {
+        id: Uuid::new_v4(),
+        session_id: Some(session_id.to_string()),
+        timestamp: entry.started_date_time.as_deref()
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.with_timezone(&Utc))
+            .unwrap_or_else(Utc::now),
+        method: entry.request.method.to_uppercase(),
+        path: url.path().to_string(),
+        headers: headers(entry.request.headers),
+        query_params,
+        body,
+        response: Some(CapturedResponse {
+            status_code: entry.response.status,
+            headers: response_headers.clone(),
+            body: response_body.clone(),
+        }),
+        response_status: Some(entry.response.status),
+        response_headers: Some(response_headers),
+        response_body: response_body.map(|body| match body {
+            Value::String(text) => text,
+            other => other.to_string(),
+        }),
+        duration: Some(duration),
+    })
+}
+
+/// Header names lowercased, without HTTP/2 pseudo-headers
+fn headers(pairs: Vec<NameValue>) -> HashMap<String, String> {
+    pairs.into_iter()
+        .filter(|pair| !pair.name.starts_with(':'))
+        .map(|pair| (pair.name.to_lowercase(), pair.value))
+        .collect()
+}
+
+/// JSON bodies as JSON, anything else as text
+fn parse_body(text: String) -> Value {
+    serde_json::from_str(&text).unwrap_or(Value::String(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HAR: &str = r#"{
+      "log": {
+        "version": "1.2",
+        "creator": { "name": "WebInspector", "version": "537.36" },
+        "entries": [
+          {
+            "startedDateTime": "2026-03-02T10:00:00.000+01:00",
+            "time": 42.5,
+            "_resourceType": "document",
+            "request": { "method": "GET", "url": "https://shop.example/", "headers": [] },
+            "response": { "status": 200, "headers": [], "content": { "mimeType": "text/html", "text": "<html></html>" } }
+          },
+          {
+            "startedDateTime": "2026-03-02T10:00:01.000+01:00",
+            "time": 12,
+            "_resourceType": "fetch",
+            "request": {
+              "method": "GET",
+              "url": "https://shop.example/api/products/17?expand=reviews",
+              "headers": [{ "name": ":authority", "value": "shop.example" }, { "name": "Accept", "value": "application/json" }],
+              "queryString": [{ "name": "expand", "value": "reviews" }]
+            },
+            "response": {
+              "status": 200,
+              "headers": [{ "name": "Content-Type", "value": "application/json" }, { "name": "Date", "value": "Mon, 02 Mar 2026 09:00:01 GMT" }],
+              "content": { "mimeType": "application/json", "text": "eyJpZCI6MTcsIm5hbWUiOiJMYW1wIn0=", "encoding": "base64" }
+            }
+          },
+          {
+            "startedDateTime": "2026-03-02T10:00:02.000+01:00",
+            "time": 30,
+            "request": {
+              "method": "post",
+              "url": "https://shop.example/api/cart",
+              "headers": [],
+              "postData": { "mimeType": "application/json", "text": "{\"product\":17}" }
+            },
+            "response": { "status": 201, "headers": [], "content": { "mimeType": "application/json", "text": "{\"items\":1,\"total\":\"$12.50\"}" } }
+          },
+          {
+            "startedDateTime": "2026-03-02T10:00:03.000+01:00",
+            "time": 0,
+            "_resourceType": "xhr",
+            "request": { "method": "GET", "url": "https://shop.example/api/blocked", "headers": [] },
+            "response": { "status": 0, "headers": [], "content": {} }
+          }
+        ]
+      }
+    }"#;
+
+    #[test]
+    fn test_har_api_calls_become_a_capture_and_blueprint() {
+        let import = import(HAR, "shop").unwrap();
+        assert_eq!((import.requests.len(), import.skipped), (2, 2));
+        assert_eq!(import.session.request_count, 2);
+        assert_eq!(import.session.started_at.to_rfc3339(), "2026-03-02T09:00:01+00:00");
+
+        let product = &import.requests[0];
+        assert_eq!((product.method.as_str(), product.path.as_str()), ("GET", "/api/products/17"));
+        assert_eq!(product.query_params["expand"], "reviews");
+        assert_eq!(product.headers.keys().collect::<Vec<_>>(), vec!["accept"]);
+        assert_eq!(product.response.as_ref().unwrap().body, Some(serde_json::json!({ "id": 17, "name": "Lamp" })));
+        let cart = &import.requests[1];
+        assert_eq!((cart.method.as_str(), cart.body.clone()), ("POST", Some(serde_json::json!({ "product": 17 }))));
+
+        let blueprint = crate::capture::blueprint_from_requests("shop", &import.requests).unwrap();
+        let config = crate::config::parse_blueprint(serde_yaml::from_str(&blueprint).unwrap()).unwrap();
+        let mut routes: Vec<_> = config.endpoints.iter().map(|(name, endpoint)| (name.as_str(), endpoint.path.as_str())).collect();
+        routes.sort();
+        assert_eq!(routes, vec![("get_api_products_id", "/api/products/{id}"), ("post_api_cart", "/api/cart")]);
+        let mock = |name: &str| config.endpoints[name].mock.clone().unwrap();
+        let cart = mock("post_api_cart");
+        assert_eq!((cart.status, cart.schema), (Some(201), Some(serde_json::json!({ "items": 1, "total": "$$12.50" }))));
+        assert_eq!(mock("get_api_products_id").schema, Some(serde_json::json!({ "id": 17, "name": "Lamp" })));
+
+        assert!(import_error("{\"entries\": []}").starts_with("Configuration error: Not a HAR file"));
+    }
+
+    fn import_error(content: &str) -> String {
+        import(content, "broken").unwrap_err().to_string()
+    }
+}
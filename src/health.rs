@@ -0,0 +1,388 @@
+//! Liveness and readiness checks
+//!
+//! Liveness only reports that the process is serving requests. Readiness
+//! aggregates plugin `health_check()` results with the checks declared under
+//! `monitoring.health.checks` (database pings and upstream URL probes) and,
+//! when `monitoring.health.saturation` sets limits, with saturation signals:
+//! requests in flight, open plugin circuit breakers, and the queue depth and
+//! connection pool usage plugins report in their health details.
+
+use crate::config::{BackworksConfig, DatabaseConfig, HealthCheckConfig, SaturationConfig};
+use crate::error::{BackworksError, BackworksResult};
+use crate::plugin::{HealthStatus, PluginHealth, PluginManager};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const DEFAULT_CHECK_TIMEOUT_SECS: u64 = 5;
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckResult {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub check_type: String,
+    pub status: HealthStatus,
+    pub message: String,
+    pub critical: bool,
+    pub duration_ms: u64,
+}
+
+/// Aggregated health response body
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub timestamp: DateTime<Utc>,
+    pub version: String,
+    pub checks: Vec<CheckResult>,
+}
+
+impl HealthReport {
+    fn from_checks(checks: Vec<CheckResult>) -> Self {
+        let status = if checks.iter().any(|c| c.critical && c.status == HealthStatus::Unhealthy) {
+            HealthStatus::Unhealthy
+        } else if checks.iter().any(|c| c.status != HealthStatus::Healthy) {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Healthy
+        };
+
+        Self {
+            status,
+            timestamp: Utc::now(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            checks,
+        }
+    }
+
+    /// Ready to receive traffic unless a critical check failed
+    pub fn is_ready(&self) -> bool {
+        self.status != HealthStatus::Unhealthy
+    }
+}
+
+/// Counts a request as in flight until dropped
+pub struct InFlight(Arc<AtomicUsize>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Clone)]
+pub struct HealthChecker {
+    checks: Vec<HealthCheckConfig>,
+    saturation: Option<SaturationConfig>,
+    database: Option<DatabaseConfig>,
+    plugin_manager: PluginManager,
+    client: reqwest::Client,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl HealthChecker {
+    pub fn new(config: &BackworksConfig, plugin_manager: PluginManager) -> Self {
+        let health = config.monitoring.as_ref().and_then(|m| m.health.as_ref());
+        let checks = health.and_then(|h| h.checks.clone()).unwrap_or_default();
+
+        Self {
+            checks,
+            saturation: health.and_then(|h| h.saturation.clone()),
+            database: config.database.clone(),
+            plugin_manager,
+            client: reqwest::Client::new(),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Count a request as in flight for as long as the guard lives
+    pub fn track_request(&self) -> InFlight {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight(self.in_flight.clone())
+    }
+
+    /// Requests currently being served, readiness probes included
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// The process is alive and serving requests
+    pub fn liveness(&self) -> HealthReport {
+        HealthReport::from_checks(Vec::new())
+    }
+
+    /// Run every plugin health check and configured check concurrently
+    pub async fn readiness(&self) -> HealthReport {
+        let configured_checks = futures::future::join_all(self.checks.iter().map(|check| self.run_check(check)));
+        let (plugin_health, configured) = tokio::join!(self.plugin_manager.get_all_plugin_health(), configured_checks);
+
+        let saturation = match self.saturation {
+            Some(ref limits) => self.saturation_checks(limits, &plugin_health).await,
+            None => Vec::new(),
+        };
+        let mut checks: Vec<CheckResult> = plugin_health.into_iter()
+            .map(|(name, health)| CheckResult {
+                name,
+                check_type: "plugin".to_string(),
+                status: health.status,
+                message: health.message,
+                critical: true,
+                duration_ms: 0,
+            })
+            .collect();
+        checks.sort_by(|a, b| a.name.cmp(&b.name));
+        checks.extend(configured);
+        checks.extend(saturation);
+
+        HealthReport::from_checks(checks)
+    }
+
+    /// One check per configured limit; a saturated signal fails the check,
+    /// which only fails readiness when `fail` is set
+    async fn saturation_checks(&self, limits: &SaturationConfig, plugins: &HashMap<String, PluginHealth>) -> Vec<CheckResult> {
+        let mut signals: Vec<(&str, bool, String)> = Vec::new();
+
+        if let Some(max) = limits.max_in_flight {
+            let in_flight = self.in_flight();
+            signals.push(("in_flight", in_flight > max, format!("{} request(s) in flight (max {})", in_flight, max)));
+        }
+
+        if let Some(max) = limits.max_queue_depth {
+            let depth: u64 = plugins.values()
+                .filter_map(|health| health.details.get("queue_depth")?.as_u64())
+                .sum();
+            signals.push(("queue_depth", depth > max, format!("{} queued item(s) (max {})", depth, max)));
+        }
+
+        if let Some(max) = limits.max_open_circuits {
+            let open = self.plugin_manager.open_circuits().await;
+            let message = match open.len() {
+                0 => format!("no circuit breaker open (max {})", max),
+                n => format!("{} circuit breaker(s) open: {} (max {})", n, open.join(", "), max),
+            };
+            signals.push(("open_circuits", open.len() > max, message));
+        }
+
+        if let Some(max) = limits.max_pool_utilization {
+            let busiest = plugins.iter()
+                .filter_map(|(name, health)| {
+                    let in_use = health.details.get("pool_in_use")?.as_f64()?;
+                    let size = health.details.get("pool_size")?.as_f64()?;
+                    (size > 0.0).then(|| (name, in_use / size))
+                })
+                .max_by(|a, b| a.1.total_cmp(&b.1).then_with(|| b.0.cmp(a.0)));
+            let (saturated, message) = match busiest {
+                Some((name, utilization)) => (
+                    utilization > max,
+                    format!("pool '{}' {:.0}% in use (max {:.0}%)", name, utilization * 100.0, max * 100.0),
+                ),
+                None => (false, "no plugin reports a connection pool".to_string()),
+            };
+            signals.push(("pool_utilization", saturated, message));
+        }
+
+        signals.into_iter()
+            .map(|(name, saturated, message)| CheckResult {
+                name: name.to_string(),
+                check_type: "saturation".to_string(),
+                status: if saturated { HealthStatus::Unhealthy } else { HealthStatus::Healthy },
+                message,
+                critical: limits.fail,
+                duration_ms: 0,
+            })
+            .collect()
+    }
+
+    async fn run_check(&self, check: &HealthCheckConfig) -> CheckResult {
+        let started = Instant::now();
+        let timeout = Duration::from_secs(check.timeout.unwrap_or(DEFAULT_CHECK_TIMEOUT_SECS));
+
+        let outcome = match tokio::time::timeout(timeout, self.probe(check)).await {
+            Ok(result) => result,
+            Err(_) => Err(BackworksError::http(format!("timed out after {}s", timeout.as_secs()))),
+        };
+
+        let (status, message) = match outcome {
+            Ok(message) => (HealthStatus::Healthy, message),
+            Err(e) => (HealthStatus::Unhealthy, e.to_string()),
+        };
+
+        CheckResult {
+            name: check.name.clone(),
+            check_type: check.check_type.clone(),
+            status,
+            message,
+            critical: check.critical.unwrap_or(true),
+            duration_ms: started.elapsed().as_millis() as u64,
+        }
+    }
+
+    async fn probe(&self, check: &HealthCheckConfig) -> BackworksResult<String> {
+        match check.check_type.as_str() {
+            "url" => {
+                let url = check.url.as_deref()
+                    .ok_or_else(|| BackworksError::config(format!("Health check '{}' requires a url", check.name)))?;
+                self.probe_url(url).await
+            }
+            "database" => {
+                let target = match check.url.clone() {
+                    Some(url) => url,
+                    None => self.database_connection_string()?,
+                };
+                probe_database(&target).await
+            }
+            other => Err(BackworksError::config(format!("Unknown health check type '{}'", other))),
+        }
+    }
+
+    async fn probe_url(&self, url: &str) -> BackworksResult<String> {
+        let response = self.client.get(url).send().await?;
+        let status = response.status();
+        if status.is_success() || status.is_redirection() {
+            Ok(format!("{} responded {}", url, status.as_u16()))
+        } else {
+            Err(BackworksError::http(format!("{} responded {}", url, status.as_u16())))
+        }
+    }
+
+    fn database_connection_string(&self) -> BackworksResult<String> {
+        let database = self.database.as_ref()
+            .ok_or_else(|| BackworksError::config("Database health check requires a url or database configuration"))?;
+
+        database.resolved_connection_string()?
+            .ok_or_else(|| BackworksError::config("Database configuration has no connection string"))
+    }
+}
+
+/// Ping a database without a driver: SQLite files must exist, network
+/// databases must accept a TCP connection.
+async fn probe_database(connection: &str) -> BackworksResult<String> {
+    if let Some(path) = connection.strip_prefix("sqlite://").or_else(|| connection.strip_prefix("sqlite:")) {
+        if path == ":memory:" || tokio::fs::metadata(path).await.is_ok() {
+            return Ok(format!("sqlite database {} available", path));
+        }
+        return Err(BackworksError::database(format!("sqlite database {} not found", path)));
+    }
+
+    let url = url::Url::parse(connection)
+        .map_err(|e| BackworksError::config(format!("Invalid database connection string: {}", e)))?;
+    let host = url.host_str()
+        .ok_or_else(|| BackworksError::config("Database connection string has no host"))?;
+    let port = url.port().or_else(|| default_database_port(url.scheme()))
+        .ok_or_else(|| BackworksError::config(format!("No port known for database scheme '{}'", url.scheme())))?;
+
+    tokio::net::TcpStream::connect((host, port)).await
+        .map_err(|e| BackworksError::database(format!("{}:{} unreachable: {}", host, port, e)))?;
+    Ok(format!("{}:{} reachable", host, port))
+}
+
+fn default_database_port(scheme: &str) -> Option<u16> {
+    match scheme {
+        "postgres" | "postgresql" => Some(5432),
+        "mysql" | "mariadb" => Some(3306),
+        "redis" | "rediss" => Some(6379),
+        "mongodb" => Some(27017),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(status: HealthStatus, critical: bool) -> CheckResult {
+        CheckResult {
+            name: "check".to_string(),
+            check_type: "url".to_string(),
+            status,
+            message: String::new(),
+            critical,
+            duration_ms: 0,
+        }
+    }
+
+    #[test]
+    fn test_report_status_aggregation() {
+        assert_eq!(HealthReport::from_checks(vec![]).status, HealthStatus::Healthy);
+
+        let report = HealthReport::from_checks(vec![result(HealthStatus::Healthy, true), result(HealthStatus::Unhealthy, false)]);
+        assert_eq!(report.status, HealthStatus::Degraded);
+        assert!(report.is_ready());
+
+        let report = HealthReport::from_checks(vec![result(HealthStatus::Unhealthy, true)]);
+        assert_eq!(report.status, HealthStatus::Unhealthy);
+        assert!(!report.is_ready());
+    }
+
+    struct PoolPlugin;
+
+    #[async_trait::async_trait]
+    impl crate::plugin::BackworksPlugin for PoolPlugin {
+        fn name(&self) -> &str { "pool" }
+        fn version(&self) -> &str { "0.0.0" }
+        fn description(&self) -> &str { "reports pool usage" }
+        async fn initialize(&self, _config: &serde_json::Value) -> BackworksResult<()> { Ok(()) }
+        async fn shutdown(&self) -> BackworksResult<()> { Ok(()) }
+
+        async fn health_check(&self) -> BackworksResult<PluginHealth> {
+            Ok(PluginHealth {
+                status: HealthStatus::Healthy,
+                message: "ok".to_string(),
+                details: HashMap::from([
+                    ("pool_in_use".to_string(), serde_json::json!(19)),
+                    ("pool_size".to_string(), serde_json::json!(20)),
+                    ("queue_depth".to_string(), serde_json::json!(3)),
+                ]),
+            })
+        }
+    }
+
+    async fn checker(saturation: SaturationConfig) -> HealthChecker {
+        let manager = PluginManager::new();
+        manager.register_plugin(Arc::new(PoolPlugin), None, None).await.unwrap();
+        let mut checker = HealthChecker::new(&crate::config::parse_blueprint(serde_yaml::from_str(
+            "name: api\nendpoints:\n  ping:\n    path: /ping\n"
+        ).unwrap()).unwrap(), manager);
+        checker.saturation = Some(saturation);
+        checker
+    }
+
+    #[tokio::test]
+    async fn test_saturation_degrades_or_fails_readiness() {
+        let limits = SaturationConfig {
+            max_in_flight: Some(1),
+            max_queue_depth: Some(10),
+            max_open_circuits: Some(0),
+            max_pool_utilization: Some(0.9),
+            fail: false,
+        };
+        let health = checker(limits.clone()).await;
+        let _requests = (health.track_request(), health.track_request());
+
+        let report = health.readiness().await;
+        let status = |name: &str| report.checks.iter().find(|c| c.name == name).unwrap().status.clone();
+        assert_eq!(status("in_flight"), HealthStatus::Unhealthy);
+        assert_eq!(status("queue_depth"), HealthStatus::Healthy);
+        assert_eq!(status("open_circuits"), HealthStatus::Healthy);
+        assert_eq!(status("pool_utilization"), HealthStatus::Unhealthy);
+        assert_eq!(report.checks.iter().find(|c| c.name == "pool_utilization").unwrap().message, "pool 'pool' 95% in use (max 90%)");
+        assert_eq!(report.status, HealthStatus::Degraded);
+        assert!(report.is_ready());
+
+        let failing = checker(SaturationConfig { fail: true, ..limits }).await;
+        assert!(!failing.readiness().await.is_ready());
+        assert_eq!(failing.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_database_probe() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(probe_database(&format!("postgres://user@127.0.0.1:{}/db", port)).await.is_ok());
+        assert!(probe_database("sqlite:///definitely/missing.db").await.is_err());
+        assert!(probe_database("sqlite::memory:").await.is_ok());
+    }
+}
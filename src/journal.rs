@@ -0,0 +1,351 @@
+//! Request journal
+//!
+//! With a `journal` block, mutating requests are appended to a JSON Lines
+//! file after authentication and before their handler runs, and the entry
+//! reaches the disk before the handler changes anything. Replaying the
+//! journal into a fresh server rebuilds the state of stateful mocks and
+//! simple data APIs after a crash: at startup with `replay_on_start`, or
+//! with `backworks journal replay`.
+//!
+//! Requests retried with the same `Idempotency-Key` header are journaled
+//! each time but replayed once. Credentials are not written to the journal;
+//! entries carry the authenticated identity instead, and replaying the
+//! server's own journal at startup skips the middleware that would check
+//! them. Entries posted to `/_backworks/journal/replay` come from elsewhere,
+//! so they go through the server's routes like any request: their recorded
+//! identity is ignored and endpoints requiring authentication reject them.
+
+use crate::auth::AuthContext;
+use crate::config::JournalConfig;
+use crate::error::{BackworksError, Result};
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::fs::OpenOptions;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+const DEFAULT_METHODS: &[&str] = &["POST", "PUT", "PATCH", "DELETE"];
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// Headers holding credentials, which never reach the journal
+pub(crate) const REDACTED_HEADERS: &[&str] = &["authorization", "proxy-authorization", "cookie", "x-api-key"];
+
+/// One journaled request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: uuid::Uuid,
+    pub timestamp: DateTime<Utc>,
+    pub endpoint: String,
+    pub method: String,
+    /// Path and query string as requested
+    pub uri: String,
+    #[serde(default)]
+    pub path_params: HashMap<String, Value>,
+    #[serde(default)]
+    pub query_params: HashMap<String, String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth: Option<AuthContext>,
+    /// UTF-8 request body
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// Request body that is not UTF-8, base64 encoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_base64: Option<String>,
+}
+
+impl JournalEntry {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        endpoint: &str,
+        method: &str,
+        uri: &str,
+        path_params: &HashMap<String, Value>,
+        query_params: &HashMap<String, String>,
+        headers: &HeaderMap,
+        auth: Option<&AuthContext>,
+        body: &[u8],
+    ) -> Self {
+        let headers = headers.iter()
+            .filter(|(name, _)| !REDACTED_HEADERS.contains(&name.as_str()))
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let (body, body_base64) = match std::str::from_utf8(body) {
+            _ if body.is_empty() => (None, None),
+            Ok(text) => (Some(text.to_string()), None),
+            Err(_) => (None, Some(base64::engine::general_purpose::STANDARD.encode(body))),
+        };
+        Self {
+            id: uuid::Uuid::new_v4(),
+            timestamp: Utc::now(),
+            endpoint: endpoint.to_string(),
+            method: method.to_string(),
+            uri: uri.to_string(),
+            path_params: path_params.clone(),
+            query_params: query_params.clone(),
+            headers,
+            auth: auth.cloned(),
+            body,
+            body_base64,
+        }
+    }
+
+    pub fn header_map(&self) -> HeaderMap {
+        self.headers.iter()
+            .filter_map(|(name, value)| Some((HeaderName::from_bytes(name.as_bytes()).ok()?, HeaderValue::from_str(value).ok()?)))
+            .collect()
+    }
+
+    pub fn body_bytes(&self) -> Result<Vec<u8>> {
+        match (&self.body, &self.body_base64) {
+            (Some(body), _) => Ok(body.clone().into_bytes()),
+            (None, Some(encoded)) => base64::engine::general_purpose::STANDARD.decode(encoded)
+                .map_err(|e| BackworksError::config(format!("Journal entry {} has an invalid body: {}", self.id, e))),
+            (None, None) => Ok(Vec::new()),
+        }
+    }
+
+    /// The request as its client sent it, minus the credentials; the body's
+    /// length is taken from the body
+    pub fn to_request(&self) -> Result<axum::http::Request<axum::body::Body>> {
+        let invalid = |e: axum::http::Error| BackworksError::config(format!("Journal entry {} is not a valid request: {}", self.id, e));
+        let mut request = axum::http::Request::builder()
+            .method(self.method.as_str())
+            .uri(self.uri.as_str())
+            .body(axum::body::Body::from(self.body_bytes()?))
+            .map_err(invalid)?;
+        *request.headers_mut() = self.header_map();
+        request.headers_mut().remove(axum::http::header::CONTENT_LENGTH);
+        request.headers_mut().remove(axum::http::header::TRANSFER_ENCODING);
+        Ok(request)
+    }
+
+    fn idempotency_key(&self) -> Option<&str> {
+        self.headers.get(IDEMPOTENCY_KEY_HEADER).map(String::as_str)
+    }
+}
+
+/// Counts reported after a replay
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplaySummary {
+    /// Entries whose handler succeeded again
+    pub replayed: usize,
+    /// Entries answered with an error status
+    pub failed: usize,
+    /// Retries of an already replayed idempotency key
+    pub duplicates: usize,
+}
+
+impl ReplaySummary {
+    /// Count an entry its handler answered with `status`
+    pub(crate) fn count(&mut self, entry: &JournalEntry, status: axum::http::StatusCode) {
+        if status.is_client_error() || status.is_server_error() {
+            tracing::warn!("Replayed request {} to '{}' answered {}", entry.id, entry.endpoint, status);
+            self.failed += 1;
+        } else {
+            self.replayed += 1;
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    methods: HashSet<String>,
+    endpoints: HashSet<String>,
+    fsync: bool,
+    file: Mutex<File>,
+}
+
+impl Journal {
+    /// Open the journal for appending, creating it and its directory
+    pub fn open(config: &JournalConfig) -> Result<Self> {
+        let path = &config.path;
+        let cannot_open = |e: std::io::Error| BackworksError::config(format!("Cannot open journal {}: {}", path.display(), e));
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(cannot_open)?;
+        }
+        repair(path).map_err(cannot_open)?;
+        let file = OpenOptions::new().create(true).append(true).open(path).map_err(cannot_open)?;
+        let methods = if config.methods.is_empty() {
+            DEFAULT_METHODS.iter().map(|method| method.to_string()).collect()
+        } else {
+            config.methods.iter().map(|method| method.to_ascii_uppercase()).collect()
+        };
+        Ok(Self {
+            path: path.clone(),
+            methods,
+            endpoints: config.endpoints.iter().cloned().collect(),
+            fsync: config.fsync.unwrap_or(true),
+            file: Mutex::new(File::from_std(file)),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether requests to this endpoint with this method are journaled
+    pub fn records(&self, endpoint: &str, method: &str) -> bool {
+        self.methods.contains(method) && (self.endpoints.is_empty() || self.endpoints.contains(endpoint))
+    }
+
+    /// Append an entry; it is on disk when this returns
+    pub async fn append(&self, entry: &JournalEntry) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        let mut file = self.file.lock().await;
+        file.write_all(&line).await?;
+        file.flush().await?;
+        if self.fsync {
+            file.sync_data().await?;
+        }
+        Ok(())
+    }
+
+    /// Remove the endpoint's entries written at or before `cutoff`, and all
+    /// but its newest `max_items`, by rewriting the file. Returns how many
+    /// were removed.
+    pub async fn purge(&self, endpoint: &str, cutoff: Option<DateTime<Utc>>, max_items: Option<usize>) -> Result<usize> {
+        let mut file = self.file.lock().await;
+        let entries = load(&self.path).await?;
+        let mut newer = entries.iter().filter(|entry| entry.endpoint == endpoint).count();
+        let total = entries.len();
+        let kept: Vec<JournalEntry> = entries.into_iter()
+            .filter(|entry| {
+                if entry.endpoint != endpoint {
+                    return true;
+                }
+                let expired = cutoff.is_some_and(|cutoff| entry.timestamp <= cutoff)
+                    || max_items.is_some_and(|max_items| newer > max_items);
+                newer -= 1;
+                !expired
+            })
+            .collect();
+        if kept.len() == total {
+            return Ok(0);
+        }
+
+        // Write beside the journal and rename, so a crash leaves one or the other
+        let mut content = Vec::new();
+        for entry in &kept {
+            content.extend(serde_json::to_vec(entry)?);
+            content.push(b'\n');
+        }
+        let partial = self.path.with_extension("partial");
+        tokio::fs::write(&partial, content).await?;
+        tokio::fs::rename(&partial, &self.path).await?;
+        *file = File::from_std(OpenOptions::new().append(true).open(&self.path)?);
+        Ok(total - kept.len())
+    }
+}
+
+/// Cut off an entry torn by a crash, so appends start on a fresh line
+fn repair(path: &Path) -> std::io::Result<()> {
+    let content = match std::fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if content.is_empty() || content.ends_with(b"\n") {
+        return Ok(());
+    }
+    let intact = content.iter().rposition(|byte| *byte == b'\n').map_or(0, |newline| newline + 1);
+    tracing::warn!("Removing incomplete last entry of journal {}", path.display());
+    OpenOptions::new().write(true).open(path)?.set_len(intact as u64)
+}
+
+/// Read a journal file. A torn last line, left by a crash mid-append, is
+/// dropped; damage anywhere else is an error.
+pub async fn load(path: &Path) -> Result<Vec<JournalEntry>> {
+    let content = tokio::fs::read_to_string(path).await
+        .map_err(|e| BackworksError::config(format!("Failed to read journal {}: {}", path.display(), e)))?;
+    parse(&content).map_err(|message| BackworksError::config(format!("Journal {} {}", path.display(), message)))
+}
+
+fn parse(content: &str) -> std::result::Result<Vec<JournalEntry>, String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut entries = Vec::with_capacity(lines.len());
+    for (index, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(_) if index + 1 == lines.len() && !content.ends_with('\n') => {
+                tracing::warn!("Ignoring incomplete last journal entry");
+            }
+            Err(e) => return Err(format!("line {}: {}", index + 1, e)),
+        }
+    }
+    Ok(entries)
+}
+
+/// Entries in replay order, without retries of an idempotency key already
+/// seen for the same endpoint, and how many retries were dropped
+pub fn deduplicate(entries: Vec<JournalEntry>) -> (Vec<JournalEntry>, usize) {
+    let mut seen = HashSet::new();
+    let total = entries.len();
+    let unique: Vec<JournalEntry> = entries.into_iter()
+        .filter(|entry| match entry.idempotency_key() {
+            Some(key) => seen.insert((entry.endpoint.clone(), key.to_string())),
+            None => true,
+        })
+        .collect();
+    let duplicates = total - unique.len();
+    (unique, duplicates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(endpoint: &str, idempotency_key: Option<&str>, body: &[u8]) -> JournalEntry {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "application/json".parse().unwrap());
+        headers.insert("authorization", "Bearer secret".parse().unwrap());
+        if let Some(key) = idempotency_key {
+            headers.insert(IDEMPOTENCY_KEY_HEADER, key.parse().unwrap());
+        }
+        JournalEntry::new(endpoint, "POST", "/todos", &HashMap::new(), &HashMap::new(), &headers, None, body)
+    }
+
+    #[test]
+    fn test_entries_round_trip_without_credentials() {
+        let text = entry("todos", None, br#"{"title":"a"}"#);
+        assert_eq!(text.headers.keys().collect::<Vec<_>>(), vec!["content-type"]);
+        assert_eq!(text.body_bytes().unwrap(), br#"{"title":"a"}"#);
+        let binary = entry("upload", None, &[0xff, 0x00]);
+        assert_eq!(binary.body, None);
+        assert_eq!(binary.body_bytes().unwrap(), vec![0xff, 0x00]);
+
+        let lines = format!("{}\n{}\n", serde_json::to_string(&text).unwrap(), serde_json::to_string(&binary).unwrap());
+        assert_eq!(parse(&lines).unwrap(), vec![text.clone(), binary]);
+
+        // A crash can tear the last line only
+        let torn = format!("{}\n{{\"id\":", serde_json::to_string(&text).unwrap());
+        assert_eq!(parse(&torn).unwrap(), vec![text.clone()]);
+        let damaged = format!("{{\"id\":\n{}\n", serde_json::to_string(&text).unwrap());
+        assert!(parse(&damaged).unwrap_err().starts_with("line 1:"));
+    }
+
+    #[test]
+    fn test_retries_are_replayed_once() {
+        let entries = vec![
+            entry("orders", Some("k1"), b"{}"),
+            entry("orders", Some("k1"), b"{}"),
+            entry("refunds", Some("k1"), b"{}"),
+            entry("orders", None, b"{}"),
+            entry("orders", None, b"{}"),
+        ];
+        let (unique, duplicates) = deduplicate(entries.clone());
+        assert_eq!(duplicates, 1);
+        assert_eq!(unique.iter().map(|entry| entry.id).collect::<Vec<_>>(), vec![entries[0].id, entries[2].id, entries[3].id, entries[4].id]);
+    }
+}
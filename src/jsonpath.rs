@@ -0,0 +1,296 @@
+//! JSONPath subset addressing fields of JSON bodies
+//!
+//! Supported: the root `$`, child names (`.name`, `['name']`), array indexes
+//! (`[0]`, `[-1]` counting from the end) and wildcards (`.*`, `[*]`). A path
+//! without the leading `$` reads as a dotted path, so `user.name` is
+//! `$.user.name`. Recursive descent (`..`) and filter expressions are
+//! rejected.
+
+use crate::error::{BackworksError, Result};
+use serde_json::{Map, Value};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Key(String),
+    Index(i64),
+    Wildcard,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl JsonPath {
+    pub fn parse(path: &str) -> Result<Self> {
+        let invalid = |reason: &str| BackworksError::config(format!("Invalid JSONPath '{}': {}", path, reason));
+        let trimmed = path.trim();
+        let rest = match trimmed.strip_prefix('$') {
+            Some(rest) => rest.to_string(),
+            None => format!(".{}", trimmed),
+        };
+
+        let mut segments = Vec::new();
+        let mut chars = rest.chars().peekable();
+        while let Some(c) = chars.next() {
+            let segment = match c {
+                '.' => {
+                    if chars.peek() == Some(&'.') {
+                        return Err(invalid("recursive descent is not supported"));
+                    }
+                    let mut name = String::new();
+                    while let Some(&c) = chars.peek() {
+                        if c == '.' || c == '[' {
+                            break;
+                        }
+                        name.push(c);
+                        chars.next();
+                    }
+                    match name.as_str() {
+                        "" => return Err(invalid("empty field name")),
+                        "*" => Segment::Wildcard,
+                        _ => Segment::Key(name),
+                    }
+                }
+                '[' => match chars.peek().copied().filter(|c| *c == '\'' || *c == '"') {
+                    Some(quote) => {
+                        chars.next();
+                        let mut name = String::new();
+                        loop {
+                            match chars.next() {
+                                Some(c) if c == quote => break,
+                                Some(c) => name.push(c),
+                                None => return Err(invalid("unterminated quoted name")),
+                            }
+                        }
+                        if chars.next() != Some(']') {
+                            return Err(invalid("expected ']' after quoted name"));
+                        }
+                        Segment::Key(name)
+                    }
+                    None => {
+                        let mut inner = String::new();
+                        let mut closed = false;
+                        for c in chars.by_ref() {
+                            if c == ']' {
+                                closed = true;
+                                break;
+                            }
+                            inner.push(c);
+                        }
+                        let inner = inner.trim();
+                        if !closed {
+                            return Err(invalid("missing ']'"));
+                        } else if inner == "*" {
+                            Segment::Wildcard
+                        } else if inner.starts_with('?') || inner.starts_with('(') {
+                            return Err(invalid("filter expressions are not supported"));
+                        } else {
+                            Segment::Index(inner.parse().map_err(|_| invalid(&format!("'{}' is not an array index", inner)))?)
+                        }
+                    }
+                },
+                c => return Err(invalid(&format!("unexpected '{}'", c))),
+            };
+            segments.push(segment);
+        }
+
+        Ok(Self { source: trimmed.to_string(), segments })
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Whether the path selects at most one value
+    pub fn is_definite(&self) -> bool {
+        !self.segments.contains(&Segment::Wildcard)
+    }
+
+    /// Path of the first `len` segments
+    pub fn prefix(&self, len: usize) -> Self {
+        Self::from_segments(self.segments[..len].to_vec())
+    }
+
+    /// Path of the segments after the first `len`, relative to them
+    pub fn suffix(&self, len: usize) -> Self {
+        Self::from_segments(self.segments[len..].to_vec())
+    }
+
+    fn from_segments(segments: Vec<Segment>) -> Self {
+        let mut source = "$".to_string();
+        for segment in &segments {
+            match segment {
+                Segment::Key(key) if !key.is_empty() && !key.contains(['.', '[', ']', '\'']) => source.push_str(&format!(".{}", key)),
+                Segment::Key(key) => source.push_str(&format!("[\"{}\"]", key)),
+                Segment::Index(index) => source.push_str(&format!("[{}]", index)),
+                Segment::Wildcard => source.push_str("[*]"),
+            }
+        }
+        Self { source, segments }
+    }
+
+    /// Values the path selects
+    pub fn select<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        let mut current = vec![value];
+        for segment in &self.segments {
+            current = current.into_iter().flat_map(|value| children(value, segment)).collect();
+        }
+        current
+    }
+
+    /// Call `f` with every value the path selects
+    pub fn for_each_mut(&self, value: &mut Value, f: &mut dyn FnMut(&mut Value)) {
+        visit(&self.segments, value, false, f);
+    }
+
+    /// Store `new` at every place the path selects, creating missing objects
+    /// along the way
+    pub fn set(&self, value: &mut Value, new: Value) {
+        visit(&self.segments, value, true, &mut |target| *target = new.clone());
+    }
+
+    /// Remove the value a definite path selects and return it
+    pub fn take(&self, value: &mut Value) -> Option<Value> {
+        let (last, parents) = self.segments.split_last()?;
+        let mut parent = value;
+        for segment in parents {
+            parent = child_mut(parent, segment)?;
+        }
+        match (last, parent) {
+            (Segment::Key(key), Value::Object(map)) => map.remove(key),
+            (Segment::Index(index), Value::Array(items)) => {
+                let index = resolve(*index, items.len())?;
+                Some(items.remove(index))
+            }
+            _ => None,
+        }
+    }
+
+    /// Remove every value the path selects
+    pub fn remove(&self, value: &mut Value) {
+        let Some((last, parents)) = self.segments.split_last() else {
+            *value = Value::Null;
+            return;
+        };
+        visit(parents, value, false, &mut |parent| match (last, parent) {
+            (Segment::Key(key), Value::Object(map)) => {
+                map.remove(key);
+            }
+            (Segment::Index(index), Value::Array(items)) => {
+                if let Some(index) = resolve(*index, items.len()) {
+                    items.remove(index);
+                }
+            }
+            (Segment::Wildcard, Value::Object(map)) => map.clear(),
+            (Segment::Wildcard, Value::Array(items)) => items.clear(),
+            _ => {}
+        });
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn resolve(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 { len.checked_sub(index.unsigned_abs() as usize)? } else { index as usize };
+    (index < len).then_some(index)
+}
+
+fn children<'a>(value: &'a Value, segment: &Segment) -> Vec<&'a Value> {
+    match (segment, value) {
+        (Segment::Key(key), Value::Object(map)) => map.get(key).into_iter().collect(),
+        (Segment::Index(index), Value::Array(items)) => resolve(*index, items.len()).map(|i| &items[i]).into_iter().collect(),
+        (Segment::Wildcard, Value::Object(map)) => map.values().collect(),
+        (Segment::Wildcard, Value::Array(items)) => items.iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn child_mut<'a>(value: &'a mut Value, segment: &Segment) -> Option<&'a mut Value> {
+    match (segment, value) {
+        (Segment::Key(key), Value::Object(map)) => map.get_mut(key),
+        (Segment::Index(index), Value::Array(items)) => {
+            let index = resolve(*index, items.len())?;
+            items.get_mut(index)
+        }
+        _ => None,
+    }
+}
+
+fn visit(segments: &[Segment], value: &mut Value, create: bool, f: &mut dyn FnMut(&mut Value)) {
+    let Some((first, rest)) = segments.split_first() else {
+        f(value);
+        return;
+    };
+    match first {
+        Segment::Wildcard => match value {
+            Value::Object(map) => map.values_mut().for_each(|child| visit(rest, child, create, f)),
+            Value::Array(items) => items.iter_mut().for_each(|child| visit(rest, child, create, f)),
+            _ => {}
+        },
+        Segment::Key(key) if create => {
+            if value.is_null() {
+                *value = Value::Object(Map::new());
+            }
+            if let Value::Object(map) = value {
+                visit(rest, map.entry(key.clone()).or_insert(Value::Null), create, f);
+            }
+        }
+        _ => {
+            if let Some(child) = child_mut(value, first) {
+                visit(rest, child, create, f);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_paths() {
+        let path = JsonPath::parse("$.items[*]['first name'][-1].*").unwrap();
+        assert_eq!(path.segments(), &[
+            Segment::Key("items".to_string()),
+            Segment::Wildcard,
+            Segment::Key("first name".to_string()),
+            Segment::Index(-1),
+            Segment::Wildcard,
+        ]);
+        assert_eq!(JsonPath::parse("user.name").unwrap().segments(), JsonPath::parse("$.user.name").unwrap().segments());
+        assert!(JsonPath::parse("$").unwrap().segments().is_empty());
+        assert_eq!(JsonPath::parse("$.a[*].b").unwrap().suffix(2).to_string(), "$.b");
+
+        for (path, reason) in [("$..name", "recursive descent"), ("$.items[?(@.id)]", "filter expressions"), ("$.a.", "empty field name"), ("$.a[x]", "not an array index"), ("$a", "unexpected 'a'")] {
+            let err = JsonPath::parse(path).unwrap_err().to_string();
+            assert!(err.contains(reason), "{}: {}", path, err);
+        }
+    }
+
+    #[test]
+    fn test_select_take_set_and_remove() {
+        let mut value = json!({ "user": { "name": "Ada", "tags": ["a", "b", "c"] }, "items": [{ "id": 1, "secret": "x" }, { "id": 2 }] });
+        let path = |p: &str| JsonPath::parse(p).unwrap();
+
+        assert_eq!(path("$.items[*].id").select(&value), vec![&json!(1), &json!(2)]);
+        assert_eq!(path("$.user.tags[-1]").select(&value), vec![&json!("c")]);
+
+        assert_eq!(path("$.user.name").take(&mut value), Some(json!("Ada")));
+        path("$.profile.display.name").set(&mut value, json!("Ada"));
+        assert_eq!(value["profile"], json!({ "display": { "name": "Ada" } }));
+
+        path("$.items[*].secret").remove(&mut value);
+        path("$.user.tags[0]").remove(&mut value);
+        assert_eq!(value["items"], json!([{ "id": 1 }, { "id": 2 }]));
+        assert_eq!(value["user"], json!({ "tags": ["b", "c"] }));
+        assert_eq!(path("$.missing.name").take(&mut value), None);
+    }
+}
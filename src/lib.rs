@@ -14,19 +14,90 @@
 
 // Re-export main modules for library usage
 pub mod config;
+pub mod blueprint;
+pub mod package;
+pub mod build;
+pub mod docker;
+pub mod scaffold;
+pub mod secrets;
+pub mod upgrade;
+pub mod diagnostics;
+pub mod routes;
+pub mod rollout;
+pub mod deprecation;
 pub mod engine;
 pub mod server;
+pub mod pipeline;
+pub mod static_files;
+pub mod download;
+pub mod templates;
 pub mod error;
 pub mod plugin;
 pub mod resilience;
 pub mod dashboard;
+pub mod broadcast;
 pub mod runtime;
 pub mod capture;
+pub mod admin;
+pub mod console;
+pub mod editor;
+pub mod studio;
+pub mod request_log;
+pub mod metrics_history;
+pub mod metrics_recorder;
+pub mod architecture;
+pub mod har;
 pub mod analyzer;
+pub mod compare;
+pub mod drift;
+pub mod contract;
+pub mod docs;
+pub mod bench;
+pub mod replay;
+pub mod changelog;
+pub mod stats;
+pub mod request_metrics;
+pub mod response_filter;
+pub mod jsonpath;
+pub mod body_transform;
+pub mod content_format;
+pub mod coercion;
+pub mod mock;
+pub mod proxy;
+pub mod balancer;
+pub mod targets;
+pub mod deadline;
+pub mod concurrency;
+pub mod request_body;
+pub mod webhook;
+pub mod compression;
+pub mod crud;
+pub mod queries;
+pub mod seed;
+pub mod pool;
+pub mod tls;
+pub mod panic;
+pub mod alerting;
+pub mod auth;
+pub mod cors;
+pub mod origin;
+pub mod health;
+pub mod state;
+pub mod error_catalog;
+pub mod locale;
+pub mod scenario;
+pub mod journal;
+pub mod retention;
+pub mod schedule;
+pub mod session;
+pub mod csrf;
+pub mod security;
+pub mod chaos;
+pub mod usage;
 
 // Re-export commonly used types
 pub use config::BackworksConfig;
 pub use engine::BackworksEngine;
 pub use error::{BackworksError, Result};
-pub use plugin::{BackworksPlugin, PluginManager, PluginHealth, HealthStatus};
+pub use plugin::{BackworksPlugin, EndpointHandlerPlugin, EndpointResponse, PluginManager, PluginHealth, PluginRoutes, HealthStatus, ProcessPlugin};
 pub use resilience::{ResilientPluginConfig, PluginMetrics};
@@ -0,0 +1,459 @@
+//! Request localization
+//!
+//! Each request gets a [`LocaleContext`]: the locale negotiated from its
+//! `Accept-Language` header against `localization.supported`, and the time
+//! zone from its `x-timezone` header or `localization.timezone`. Handlers
+//! receive it as `locale` in their request data, templates as `locale`.
+//!
+//! The `format_date`, `format_number` and `format_currency` template helpers
+//! format for the request's locale and time zone unless given a `locale` or
+//! `tz`. Separators, month names and date layouts come from the glibc locale
+//! data.
+
+use crate::config::LocalizationConfig;
+use crate::error::{BackworksError, Result};
+use crate::error_catalog::accepted_locales;
+use axum::http::HeaderMap;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use handlebars::{Context, Handlebars, Helper, HelperResult, Output, RenderContext, RenderError};
+use pure_rust_locales::{locale_match, Locale};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::Write;
+
+const DEFAULT_LOCALE: &str = "en-US";
+const DEFAULT_TIMEZONE_HEADER: &str = "x-timezone";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocaleContext {
+    /// Negotiated locale, such as `de-AT`
+    pub tag: String,
+    pub language: String,
+    pub region: Option<String>,
+    /// Locales the client accepts, most preferred first
+    pub accepted: Vec<String>,
+    /// IANA time zone, such as `Europe/Vienna`
+    pub timezone: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Localization {
+    default_locale: String,
+    supported: Vec<String>,
+    timezone: Tz,
+    timezone_header: String,
+}
+
+impl Default for Localization {
+    fn default() -> Self {
+        Self {
+            default_locale: DEFAULT_LOCALE.to_string(),
+            supported: Vec::new(),
+            timezone: Tz::UTC,
+            timezone_header: DEFAULT_TIMEZONE_HEADER.to_string(),
+        }
+    }
+}
+
+impl Localization {
+    pub fn new(config: Option<&LocalizationConfig>) -> Result<Self> {
+        let Some(config) = config else {
+            return Ok(Self::default());
+        };
+        let tag = |value: &str| normalize(value)
+            .ok_or_else(|| BackworksError::config(format!("Localization: '{}' is not a locale tag", value)));
+        let timezone = match config.timezone {
+            Some(ref zone) => zone.parse()
+                .map_err(|_| BackworksError::config(format!("Localization: unknown time zone '{}'", zone)))?,
+            None => Tz::UTC,
+        };
+        Ok(Self {
+            default_locale: tag(config.default_locale.as_deref().unwrap_or(DEFAULT_LOCALE))?,
+            supported: config.supported.iter().map(|locale| tag(locale)).collect::<Result<_>>()?,
+            timezone,
+            timezone_header: config.timezone_header.clone().unwrap_or_else(|| DEFAULT_TIMEZONE_HEADER.to_string()),
+        })
+    }
+
+    /// Locale and time zone of a request
+    pub fn context(&self, headers: &HeaderMap) -> LocaleContext {
+        let accepted: Vec<String> = accepted_locales(headers).iter().filter_map(|tag| normalize(tag)).collect();
+        let tag = accepted.iter()
+            .find_map(|requested| self.negotiate(requested))
+            .unwrap_or_else(|| self.default_locale.clone());
+        // Unknown zones fall back to the default rather than failing the request
+        let timezone = headers.get(self.timezone_header.as_str())
+            .and_then(|value| value.to_str().ok())
+            .and_then(|zone| zone.trim().parse::<Tz>().ok())
+            .unwrap_or(self.timezone);
+        let (language, region) = split(&tag);
+        LocaleContext { tag, language, region, accepted, timezone: timezone.name().to_string() }
+    }
+
+    /// The supported locale serving a requested one: the same tag, or else
+    /// the first of the same language
+    fn negotiate(&self, requested: &str) -> Option<String> {
+        if self.supported.is_empty() {
+            return Some(requested.to_string());
+        }
+        let language = |tag: &str| split(tag).0;
+        self.supported.iter()
+            .find(|supported| supported.eq_ignore_ascii_case(requested))
+            .or_else(|| self.supported.iter().find(|supported| language(supported) == language(requested)))
+            .cloned()
+    }
+}
+
+/// `de_at` → `de-AT`, `zh-hant-tw` → `zh-Hant-TW`; `None` if not a locale tag
+fn normalize(tag: &str) -> Option<String> {
+    let mut parts = tag.trim().split(['-', '_']);
+    let language = parts.next()?;
+    if !(2..=8).contains(&language.len()) || !language.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let mut normalized = language.to_ascii_lowercase();
+    for part in parts {
+        if part.is_empty() || !part.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        normalized.push('-');
+        match part.len() {
+            2 if part.chars().all(|c| c.is_ascii_alphabetic()) => normalized.push_str(&part.to_ascii_uppercase()),
+            4 if part.chars().all(|c| c.is_ascii_alphabetic()) => {
+                normalized.push_str(&part[..1].to_ascii_uppercase());
+                normalized.push_str(&part[1..].to_ascii_lowercase());
+            }
+            _ => normalized.push_str(&part.to_ascii_lowercase()),
+        }
+    }
+    Some(normalized)
+}
+
+/// Language and region subtags of a normalized tag
+fn split(tag: &str) -> (String, Option<String>) {
+    let mut parts = tag.split('-');
+    let language = parts.next().unwrap_or(tag).to_string();
+    let region = parts.find(|part| {
+        (part.len() == 2 && part.chars().all(|c| c.is_ascii_uppercase())) || (part.len() == 3 && part.chars().all(|c| c.is_ascii_digit()))
+    });
+    (language, region.map(str::to_string))
+}
+
+/// glibc locale of a tag: the exact one, or the language's main region
+fn system_locale(tag: &str) -> Locale {
+    let (language, region) = split(tag);
+    let main_region = match language.as_str() {
+        "en" => "US",
+        "zh" => "CN",
+        "ja" => "JP",
+        "ko" => "KR",
+        "sv" => "SE",
+        "da" => "DK",
+        "cs" => "CZ",
+        "uk" => "UA",
+        "el" => "GR",
+        "he" => "IL",
+        "hi" => "IN",
+        "nb" | "nn" => "NO",
+        "ca" => "ES",
+        "vi" => "VN",
+        "sl" => "SI",
+        "et" => "EE",
+        "sr" => "RS",
+        _ => "",
+    };
+    region.into_iter().chain([main_region.to_string(), language.to_ascii_uppercase()])
+        .find_map(|region| Locale::try_from(format!("{}_{}", language, region).as_str()).ok())
+        .unwrap_or(Locale::en_US)
+}
+
+/// `1234567.891` → `1,234,567.891` in `en-US`, `1.234.567,891` in `de-DE`
+pub fn format_number(value: f64, decimals: Option<usize>, tag: &str) -> String {
+    let locale = system_locale(tag);
+    let separators = (
+        locale_match!(locale => LC_NUMERIC::DECIMAL_POINT),
+        locale_match!(locale => LC_NUMERIC::THOUSANDS_SEP),
+        locale_match!(locale => LC_NUMERIC::GROUPING),
+    );
+    let formatted = group_digits(value.abs(), decimals, separators);
+    signed(value, formatted)
+}
+
+/// `1234.5` in `EUR` → `€1,234.50` in `en-US`, `1.234,50 €` in `de-DE`
+pub fn format_currency(value: f64, currency: &str, tag: &str) -> String {
+    let locale = system_locale(tag);
+    let code = currency.trim().to_ascii_uppercase();
+    let digits = match code.as_str() {
+        "JPY" | "KRW" | "VND" | "CLP" | "ISK" | "HUF" => 0,
+        "BHD" | "KWD" | "OMR" | "JOD" | "TND" => 3,
+        _ => 2,
+    };
+    let symbol = match code.as_str() {
+        "EUR" => "€",
+        "USD" => "$",
+        "GBP" => "£",
+        "JPY" | "CNY" => "¥",
+        "INR" => "₹",
+        "KRW" => "₩",
+        "RUB" => "₽",
+        "BRL" => "R$",
+        "PLN" => "zł",
+        "TRY" => "₺",
+        "UAH" => "₴",
+        "ILS" => "₪",
+        other => other,
+    };
+
+    let amount = group_digits(value.abs(), Some(digits), (
+        locale_match!(locale => LC_MONETARY::MON_DECIMAL_POINT),
+        locale_match!(locale => LC_MONETARY::MON_THOUSANDS_SEP),
+        locale_match!(locale => LC_MONETARY::MON_GROUPING),
+    ));
+    // Letter codes such as CHF always stand apart from the amount
+    let spaced = locale_match!(locale => LC_MONETARY::P_SEP_BY_SPACE) == 1 || symbol.chars().all(|c| c.is_ascii_alphabetic());
+    let space = if spaced { "\u{a0}" } else { "" };
+    let formatted = if locale_match!(locale => LC_MONETARY::P_CS_PRECEDES) == 1 {
+        format!("{}{}{}", symbol, space, amount)
+    } else {
+        format!("{}{}{}", amount, space, symbol)
+    };
+    signed(value, formatted)
+}
+
+fn signed(value: f64, formatted: String) -> String {
+    let zero = !formatted.chars().any(|c| c.is_ascii_digit() && c != '0');
+    if value < 0.0 && !zero {
+        format!("-{}", formatted)
+    } else {
+        formatted
+    }
+}
+
+/// A non-negative number with `decimals` fraction digits (up to three,
+/// without trailing zeros, when `None`) and its integer digits grouped
+fn group_digits(value: f64, decimals: Option<usize>, (decimal, separator, grouping): (&str, &str, &[i64])) -> String {
+    let fixed = match decimals {
+        Some(decimals) => format!("{:.*}", decimals, value),
+        None => {
+            let fixed = format!("{:.3}", value);
+            fixed.trim_end_matches('0').trim_end_matches('.').to_string()
+        }
+    };
+    let (integer, fraction) = fixed.split_once('.').unwrap_or((&fixed, ""));
+
+    // Group sizes from the right; the last size repeats, and a size below 1 stops grouping
+    let digits: Vec<char> = integer.chars().collect();
+    let mut groups = Vec::new();
+    let mut end = digits.len();
+    let mut sizes = grouping.iter().copied();
+    let mut size = sizes.next().unwrap_or(0);
+    while end > 0 {
+        if size < 1 || separator.is_empty() {
+            groups.push(digits[..end].iter().collect::<String>());
+            break;
+        }
+        let start = end.saturating_sub(size as usize);
+        groups.push(digits[start..end].iter().collect::<String>());
+        end = start;
+        size = sizes.next().unwrap_or(size);
+    }
+    groups.reverse();
+
+    let mut formatted = groups.join(separator);
+    if !fraction.is_empty() {
+        formatted.push_str(decimal);
+        formatted.push_str(fraction);
+    }
+    formatted
+}
+
+/// Format a date in a `style` (`short`, `medium`, `long`, `time` or
+/// `datetime`) or a strftime pattern. Dates are RFC 3339 strings,
+/// `YYYY-MM-DD` or Unix timestamps in seconds.
+pub fn format_date(value: &Value, style: &str, tag: &str, zone: Tz) -> std::result::Result<String, String> {
+    let locale = system_locale(tag);
+    let datetime = parse_datetime(value, zone).ok_or_else(|| format!("{} is not a date", value))?;
+    let language = split(tag).0;
+    let pattern = match style {
+        "short" => "%x".to_string(),
+        "medium" => date_pattern(&language, true),
+        "long" => date_pattern(&language, false),
+        "time" => "%X".to_string(),
+        "datetime" => format!("{} %X", date_pattern(&language, true)),
+        custom if custom.contains('%') => custom.to_string(),
+        other => return Err(format!("unknown date style '{}'; use short, medium, long, time, datetime or a strftime pattern", other)),
+    };
+    let mut formatted = String::new();
+    write!(formatted, "{}", datetime.format_localized(&pattern, locale))
+        .map_err(|_| format!("invalid date pattern '{}'", pattern))?;
+    Ok(formatted)
+}
+
+fn parse_datetime(value: &Value, zone: Tz) -> Option<DateTime<Tz>> {
+    match value {
+        Value::String(text) => {
+            if let Ok(datetime) = DateTime::parse_from_rfc3339(text.trim()) {
+                return Some(datetime.with_timezone(&zone));
+            }
+            let date = NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d").ok()?;
+            zone.from_local_datetime(&date.and_hms_opt(0, 0, 0)?).earliest()
+        }
+        Value::Number(number) => {
+            let seconds = number.as_i64().or_else(|| number.as_f64().map(|seconds| seconds as i64))?;
+            Some(Utc.timestamp_opt(seconds, 0).single()?.with_timezone(&zone))
+        }
+        _ => None,
+    }
+}
+
+/// Day, month name and year in the language's usual order
+fn date_pattern(language: &str, abbreviated: bool) -> String {
+    let month = if abbreviated { "%b" } else { "%B" };
+    match language {
+        "en" => format!("{} %-d, %Y", month),
+        "de" | "da" | "nb" | "nn" | "no" | "fi" | "cs" | "sk" => format!("%-d. {} %Y", month),
+        "es" | "pt" => format!("%-d de {} de %Y", month),
+        "hu" => format!("%Y. {} %-d.", month),
+        "ja" | "zh" => "%Y年%-m月%-d日".to_string(),
+        "ko" => "%Y년 %-m월 %-d일".to_string(),
+        _ => format!("%-d {} %Y", month),
+    }
+}
+
+/// Register `format_date`, `format_number` and `format_currency`
+pub fn register_helpers(templates: &mut Handlebars) {
+    templates.register_helper("format_date", Box::new(format_date_helper));
+    templates.register_helper("format_number", Box::new(format_number_helper));
+    templates.register_helper("format_currency", Box::new(format_currency_helper));
+}
+
+/// The helper's `locale`, or the request's
+fn helper_locale(helper: &Helper, context: &Context) -> String {
+    helper.hash_get("locale").and_then(|locale| locale.value().as_str()).and_then(normalize)
+        .or_else(|| context.data().pointer("/locale/tag").and_then(Value::as_str).map(str::to_string))
+        .unwrap_or_else(|| DEFAULT_LOCALE.to_string())
+}
+
+fn helper_number(helper: &Helper, name: &str) -> std::result::Result<Option<f64>, RenderError> {
+    match helper.param(0).map(|param| param.value()) {
+        None => Err(RenderError::new(format!("{} needs a number", name))),
+        Some(Value::Null) => Ok(None),
+        Some(Value::Number(number)) => Ok(number.as_f64()),
+        Some(Value::String(text)) => text.trim().parse().map(Some)
+            .map_err(|_| RenderError::new(format!("{}: '{}' is not a number", name, text))),
+        Some(other) => Err(RenderError::new(format!("{}: {} is not a number", name, other))),
+    }
+}
+
+fn format_date_helper<'reg, 'rc>(
+    helper: &Helper<'reg, 'rc>,
+    _: &'reg Handlebars<'reg>,
+    context: &'rc Context,
+    _: &mut RenderContext<'reg, 'rc>,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let value = helper.param(0).ok_or_else(|| RenderError::new("format_date needs a date"))?.value();
+    if value.is_null() {
+        return Ok(());
+    }
+    let style = helper.param(1).and_then(|style| style.value().as_str()).unwrap_or("medium");
+    let zone = match helper.hash_get("tz").and_then(|zone| zone.value().as_str()) {
+        Some(zone) => zone.parse().map_err(|_| RenderError::new(format!("format_date: unknown time zone '{}'", zone)))?,
+        None => context.data().pointer("/locale/timezone").and_then(Value::as_str)
+            .and_then(|zone| zone.parse().ok())
+            .unwrap_or(Tz::UTC),
+    };
+    let formatted = format_date(value, style, &helper_locale(helper, context), zone)
+        .map_err(|e| RenderError::new(format!("format_date: {}", e)))?;
+    out.write(&formatted)?;
+    Ok(())
+}
+
+fn format_number_helper<'reg, 'rc>(
+    helper: &Helper<'reg, 'rc>,
+    _: &'reg Handlebars<'reg>,
+    context: &'rc Context,
+    _: &mut RenderContext<'reg, 'rc>,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let Some(value) = helper_number(helper, "format_number")? else {
+        return Ok(());
+    };
+    let decimals = helper.hash_get("decimals").and_then(|decimals| decimals.value().as_u64()).map(|decimals| decimals as usize);
+    out.write(&format_number(value, decimals, &helper_locale(helper, context)))?;
+    Ok(())
+}
+
+fn format_currency_helper<'reg, 'rc>(
+    helper: &Helper<'reg, 'rc>,
+    _: &'reg Handlebars<'reg>,
+    context: &'rc Context,
+    _: &mut RenderContext<'reg, 'rc>,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let Some(value) = helper_number(helper, "format_currency")? else {
+        return Ok(());
+    };
+    let currency = helper.param(1).and_then(|currency| currency.value().as_str())
+        .ok_or_else(|| RenderError::new("format_currency needs a currency code, such as \"EUR\""))?;
+    out.write(&format_currency(value, currency, &helper_locale(helper, context)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs.iter().map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap())).collect()
+    }
+
+    #[test]
+    fn test_locale_negotiation_and_time_zone() {
+        let localization = Localization::new(Some(&LocalizationConfig {
+            default_locale: Some("en_gb".to_string()),
+            supported: vec!["en-GB".to_string(), "de-DE".to_string(), "fr".to_string()],
+            timezone: Some("Europe/London".to_string()),
+            timezone_header: None,
+        })).unwrap();
+
+        let context = localization.context(&headers(&[("accept-language", "it;q=0.9, de-at, fr;q=0.5"), ("x-timezone", "Europe/Vienna")]));
+        assert_eq!(context, LocaleContext {
+            tag: "de-DE".to_string(),
+            language: "de".to_string(),
+            region: Some("DE".to_string()),
+            accepted: vec!["de-AT".to_string(), "it".to_string(), "fr".to_string()],
+            timezone: "Europe/Vienna".to_string(),
+        });
+
+        let context = localization.context(&headers(&[("accept-language", "ja"), ("x-timezone", "Mars/Olympus")]));
+        assert_eq!((context.tag.as_str(), context.timezone.as_str()), ("en-GB", "Europe/London"));
+        assert_eq!(Localization::default().context(&headers(&[("accept-language", "pt-br")])).tag, "pt-BR");
+
+        let invalid = LocalizationConfig { timezone: Some("Nowhere/Special".to_string()), ..Default::default() };
+        assert!(Localization::new(Some(&invalid)).unwrap_err().to_string().contains("unknown time zone 'Nowhere/Special'"));
+    }
+
+    #[test]
+    fn test_numbers_currencies_and_dates_follow_the_locale() {
+        assert_eq!(format_number(1234567.891, None, "en-US"), "1,234,567.891");
+        assert_eq!(format_number(1234567.891, Some(1), "de"), "1.234.567,9");
+        assert_eq!(format_number(-1234.5, Some(2), "fr-FR"), "-1\u{202f}234,50");
+        assert_eq!(format_number(-0.001, Some(2), "en"), "0.00");
+
+        assert_eq!(format_currency(1234.5, "EUR", "en-US"), "€1,234.50");
+        assert_eq!(format_currency(1234.5, "eur", "de-DE"), "1.234,50\u{a0}€");
+        assert_eq!(format_currency(-1500.0, "JPY", "ja"), "-¥1,500");
+
+        let zone: Tz = "America/New_York".parse().unwrap();
+        let date = Value::String("2024-03-05T02:30:00Z".to_string());
+        assert_eq!(format_date(&date, "short", "en-US", zone).unwrap(), "03/04/2024");
+        assert_eq!(format_date(&date, "long", "de-DE", Tz::UTC).unwrap(), "5. März 2024");
+        assert_eq!(format_date(&date, "medium", "en", Tz::UTC).unwrap(), "Mar 5, 2024");
+        assert_eq!(format_date(&Value::String("2024-12-24".to_string()), "long", "es", Tz::UTC).unwrap(), "24 de diciembre de 2024");
+        assert_eq!(format_date(&serde_json::json!(0), "%Y-%m-%d %H:%M", "en", Tz::UTC).unwrap(), "1970-01-01 00:00");
+        assert!(format_date(&date, "fancy", "en", Tz::UTC).is_err());
+        assert!(format_date(&Value::Bool(true), "short", "en", Tz::UTC).is_err());
+    }
+}
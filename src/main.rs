@@ -5,6 +5,7 @@ use backworks::{
     BackworksEngine, BackworksError, Result,
     config
 };
+use backworks::analyzer::{AnalysisStatus, BlueprintAnalyzer, IssueSeverity, ReportFormat};
 
 #[derive(Parser)]
 #[command(name = "backworks")]
@@ -22,9 +23,19 @@ enum Commands {
         /// Project name
         name: String,
         
-        /// Project template (hello-world, api, webapp)
+        /// Project template (hello-world, api, webapp, proxy-gateway, graphql,
+        /// database-crud, event-driven), or a repository as git:<url>[#<ref>]
         #[arg(short, long, default_value = "hello-world")]
         template: String,
+        
+        /// Value of a git template's variable, as name=value; repeatable
+        #[arg(long = "var", value_name = "NAME=VALUE")]
+        vars: Vec<String>,
+        
+        /// Add TypeScript definitions of the handler API in types/ and a
+        /// jsconfig.json, for editor completion in JavaScript handlers
+        #[arg(long)]
+        types: bool,
     },
     
     /// Start the Backworks API server
@@ -48,21 +59,56 @@ enum Commands {
         /// Enable hot reload
         #[arg(short, long)]
         watch: bool,
+        
+        /// Environment profile to apply (overrides BACKWORKS_ENV)
+        #[arg(short, long)]
+        env: Option<String>,
+    },
+    
+    /// Run a packaged blueprint (.bwpack)
+    Run {
+        /// Package file built with `backworks build --target package`
+        package: PathBuf,
+        
+        /// Override the server port
+        #[arg(short, long)]
+        port: Option<u16>,
+        
+        /// Override the dashboard port
+        #[arg(long)]
+        dashboard_port: Option<u16>,
+        
+        /// Environment profile to apply (overrides BACKWORKS_ENV)
+        #[arg(short, long)]
+        env: Option<String>,
     },
     
     /// Build the project for deployment
     Build {
-        /// Target profile (development, production)
+        /// Target profile (development, production, docker, package)
         #[arg(short, long, default_value = "development")]
         target: String,
         
-        /// Security profile
+        /// Security profile to strip the build for (default: the
+        /// blueprint's, or production unless the target is development)
         #[arg(short, long)]
         security: Option<String>,
         
-        /// Output directory
+        /// Output directory (default: target/<target>)
         #[arg(short, long)]
         output: Option<PathBuf>,
+        
+        /// Also write the build as a package, <output>.bwpack
+        #[arg(long)]
+        archive: bool,
+        
+        /// Backworks binary to put in the docker image (default: this one)
+        #[arg(long)]
+        binary: Option<PathBuf>,
+        
+        /// Environment profile to apply (overrides BACKWORKS_ENV)
+        #[arg(short, long)]
+        env: Option<String>,
     },
     
     /// Migrate from single file to project structure
@@ -81,6 +127,14 @@ enum Commands {
         /// Configuration file path (optional for project structure)
         #[arg(short, long)]
         config: Option<PathBuf>,
+        
+        /// Print the blueprint after all includes are merged
+        #[arg(long)]
+        merged: bool,
+        
+        /// Environment profile to apply (overrides BACKWORKS_ENV)
+        #[arg(short, long)]
+        env: Option<String>,
     },
     
     /// Analyze blueprint configuration with detailed feedback
@@ -96,6 +150,243 @@ enum Commands {
         /// Output file (optional, defaults to stdout)
         #[arg(short, long)]
         output: Option<PathBuf>,
+        
+        /// Also compare the blueprint with the traffic of the server at this URL
+        #[arg(long, value_name = "URL", num_args = 0..=1, default_missing_value = "http://localhost:8080")]
+        usage: Option<String>,
+        
+        /// Traffic window for --usage, such as 30m or 24h
+        #[arg(long, default_value = "1h", requires = "usage")]
+        window: String,
+    },
+    
+    /// Report API drift between two captures, or a blueprint and a capture
+    Drift {
+        /// Baseline: capture export or proxy cassette (.json), or blueprint
+        baseline: PathBuf,
+        
+        /// Capture export or proxy cassette (.json), or blueprint to compare
+        current: PathBuf,
+        
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+        
+        /// Output file (optional, defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// Fail on breaking changes, any drift, or never
+        #[arg(long, default_value = "breaking", value_parser = ["breaking", "any", "never"])]
+        fail_on: String,
+    },
+    
+    /// Check that the blueprint still answers like the real service
+    Verify {
+        /// Base URL of the real service
+        #[arg(long)]
+        against: String,
+        
+        /// Configuration file path (optional for project structure)
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+        
+        /// Capture export or proxy cassette (.json) whose requests are replayed
+        /// and compared with the recorded responses
+        #[arg(long)]
+        capture: Vec<PathBuf>,
+        
+        /// Only replay the captures, without requests for the blueprint's endpoints
+        #[arg(long, requires = "capture")]
+        captures_only: bool,
+        
+        /// Only request this endpoint of the blueprint
+        #[arg(long)]
+        endpoint: Option<String>,
+        
+        /// Header sent to the real service, as `Name: value`
+        #[arg(short = 'H', long = "header")]
+        headers: Vec<String>,
+        
+        /// Path parameter value, as `name=value` (default: a sample of its type)
+        #[arg(long = "param")]
+        params: Vec<String>,
+        
+        /// Response header compared besides content-type
+        #[arg(long = "compare-header")]
+        compare_headers: Vec<String>,
+        
+        /// Also send POST, PUT, PATCH and DELETE requests
+        #[arg(long)]
+        writes: bool,
+        
+        /// How long a request to the real service may take
+        #[arg(long, default_value = "30s")]
+        timeout: String,
+        
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+        
+        /// Output file (optional, defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// Environment profile to apply (overrides BACKWORKS_ENV)
+        #[arg(long)]
+        env: Option<String>,
+    },
+    
+    /// Send captured traffic to a target again and compare the responses
+    Replay {
+        /// Capture session export or proxy cassette (.json)
+        #[arg(long)]
+        session: PathBuf,
+        
+        /// Base URL requests are sent to
+        #[arg(long)]
+        target: String,
+        
+        /// Requests started per second or minute, such as 10/s (default:
+        /// one after another)
+        #[arg(long, conflicts_with = "speed")]
+        rate: Option<String>,
+        
+        /// Keep the recorded gaps between requests, sped up by this factor
+        #[arg(long)]
+        speed: Option<f64>,
+        
+        /// Header replacing the captured one, or added, as `Name: value`
+        #[arg(short = 'H', long = "header")]
+        headers: Vec<String>,
+        
+        /// Captured header not to send again; `*` drops them all
+        #[arg(long = "drop-header")]
+        drop_headers: Vec<String>,
+        
+        /// How long one request may take
+        #[arg(long, default_value = "30s")]
+        timeout: String,
+        
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+        
+        /// Output file (optional, defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// Fail on changed statuses and requests without response, any difference, or never
+        #[arg(long, default_value = "status", value_parser = ["status", "any", "never"])]
+        fail_on: String,
+    },
+    
+    /// Load test an endpoint of the running server, or a URL
+    Bench {
+        /// Endpoint of the blueprint, path on the server or URL
+        endpoint: String,
+        
+        /// Base URL of the running server (default: localhost on the blueprint's port)
+        #[arg(long)]
+        url: Option<String>,
+        
+        /// Configuration file path (optional for project structure)
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+        
+        /// Concurrent workers, each with one request in flight
+        #[arg(short = 'C', long, default_value_t = 10)]
+        concurrency: usize,
+        
+        /// How long the run lasts
+        #[arg(short, long, default_value = "10s")]
+        duration: String,
+        
+        /// Stop after this many requests
+        #[arg(short = 'n', long)]
+        requests: Option<u64>,
+        
+        /// Request method (default: the endpoint's first)
+        #[arg(short = 'X', long)]
+        method: Option<String>,
+        
+        /// Request header, as `Name: value`
+        #[arg(short = 'H', long = "header")]
+        headers: Vec<String>,
+        
+        /// Request body, or @file to read it from
+        #[arg(long)]
+        body: Option<String>,
+        
+        /// Path parameter value, as `name=value` (default: a sample of its type)
+        #[arg(long = "param")]
+        params: Vec<String>,
+        
+        /// How long one request may take
+        #[arg(long, default_value = "30s")]
+        timeout: String,
+        
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+    
+    /// Write an API changelog between two blueprint versions
+    Changelog {
+        /// Baseline and current blueprint files
+        #[arg(num_args = 2, value_names = ["BASELINE", "CURRENT"], required_unless_present = "git")]
+        blueprints: Vec<PathBuf>,
+        
+        /// Git range instead of files, such as v1.2.0..HEAD; without `..<to>` the working tree is current
+        #[arg(long, conflicts_with = "blueprints")]
+        git: Option<String>,
+        
+        /// Blueprint compared across the git range (optional for project structure)
+        #[arg(short, long, requires = "git")]
+        config: Option<PathBuf>,
+        
+        /// Output format (markdown, json)
+        #[arg(short, long, default_value = "markdown")]
+        format: String,
+        
+        /// Output file (optional, defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    
+    /// Write an HTML API reference and OpenAPI document for the blueprint
+    Docs {
+        /// Configuration file path (optional for project structure)
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+        
+        /// Capture export or proxy cassette (.json) to take examples from,
+        /// besides those of the blueprint's `docs.captures`
+        #[arg(long)]
+        capture: Vec<PathBuf>,
+        
+        /// Output directory
+        #[arg(short, long, default_value = "target/docs")]
+        output: PathBuf,
+        
+        /// Environment profile to apply (overrides BACKWORKS_ENV)
+        #[arg(short, long)]
+        env: Option<String>,
+    },
+    
+    /// Rewrite deprecated blueprint constructs and report manual changes
+    Upgrade {
+        /// Configuration file path (optional for project structure)
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+        
+        /// Backworks version the blueprints were written for; older migrations are skipped
+        #[arg(long)]
+        from: Option<String>,
+        
+        /// Apply the rewrites, keeping each original as <file>.bak
+        #[arg(long)]
+        write: bool,
     },
     
     /// Capture mode - listen and analyze existing APIs
@@ -115,13 +406,313 @@ enum Commands {
     
     /// Generate configuration from captured data
     Generate {
-        /// Input captured data file
+        /// Capture session export (.json) or browser HAR file (.har)
+        #[arg(short, long)]
+        input: PathBuf,
+        
+        /// Output configuration file
+        #[arg(short, long, default_value = "generated.yaml")]
+        output: PathBuf,
+        
+        /// Also save the requests read from a HAR file as a capture session export
+        #[arg(long)]
+        capture: Option<PathBuf>,
+    },
+    
+    /// Export or import the state store of a running server
+    State {
+        #[command(subcommand)]
+        action: StateAction,
+    },
+    
+    /// Load fixture data into the database tables of the blueprint's plugins
+    Db {
+        #[command(subcommand)]
+        action: DbAction,
+    },
+    
+    /// Inspect or replay a request journal
+    Journal {
+        #[command(subcommand)]
+        action: JournalAction,
+    },
+    
+    /// Remove data a running server stores for its endpoints
+    Purge {
+        /// Endpoint name or path
+        #[arg(short, long, required_unless_present = "all")]
+        endpoint: Option<String>,
+        
+        /// Purge every endpoint's data
+        #[arg(long, conflicts_with = "endpoint")]
+        all: bool,
+        
+        /// Only remove data older than this, such as 7d or 12h
+        #[arg(long)]
+        older_than: Option<String>,
+        
+        /// Base URL of the running server
+        #[arg(long, default_value = "http://localhost:8080")]
+        url: String,
+    },
+    
+    /// Drain, quarantine or re-enable the proxy targets of a running server
+    Targets {
+        #[command(subcommand)]
+        action: TargetsAction,
+    },
+    
+    /// Control a running server from an interactive prompt
+    Console {
+        /// Base URL of the running server
+        #[arg(long, default_value = "http://localhost:8080")]
+        url: String,
+        
+        /// Run a console command and exit instead of prompting; repeatable
+        #[arg(short = 'e', long = "execute", value_name = "COMMAND")]
+        execute: Vec<String>,
+    },
+    
+    /// Encrypt and decrypt blueprint values with the project key
+    Secrets {
+        #[command(subcommand)]
+        action: SecretsAction,
+    },
+    
+    /// Find, install, update and remove plugins from the plugin registry
+    Plugin {
+        #[command(subcommand)]
+        action: PluginAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum PluginAction {
+    /// List registry plugins whose name or description matches
+    Search {
+        /// Text to look for (lists every plugin when omitted)
+        query: Option<String>,
+        
+        /// Registry index (defaults to BACKWORKS_PLUGIN_REGISTRY or pluginRegistry.index in package.json)
+        #[arg(long)]
+        registry: Option<String>,
+    },
+    
+    /// Install a plugin into ./plugins and record it in package.json
+    Install {
+        /// Plugin name, optionally with a version (geoip@1.2.0)
+        plugin: String,
+        
+        /// Registry index (defaults to BACKWORKS_PLUGIN_REGISTRY or pluginRegistry.index in package.json)
+        #[arg(long)]
+        registry: Option<String>,
+    },
+    
+    /// Update installed plugins to their newest version
+    Update {
+        /// Only update this plugin
+        plugin: Option<String>,
+        
+        /// Registry index (defaults to BACKWORKS_PLUGIN_REGISTRY or pluginRegistry.index in package.json)
+        #[arg(long)]
+        registry: Option<String>,
+    },
+    
+    /// Delete an installed plugin and its package.json record
+    Remove {
+        plugin: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SecretsAction {
+    /// Generate a project key in .backworks/secret.key or the OS keyring
+    Init {
+        /// Replace an existing key (values encrypted with it become unreadable)
+        #[arg(long)]
+        force: bool,
+        
+        /// Store the key in the OS keyring under this name instead of a file
+        #[arg(long)]
+        keyring: Option<String>,
+    },
+    
+    /// Encrypt a value into an enc:// string for the blueprint
+    Encrypt {
+        /// Value to encrypt (read from stdin when omitted)
+        value: Option<String>,
+        
+        /// Key file (defaults to BACKWORKS_SECRET_KEY, BACKWORKS_SECRET_KEY_FILE, BACKWORKS_SECRET_KEYRING or .backworks/secret.key)
+        #[arg(long)]
+        key_file: Option<PathBuf>,
+    },
+    
+    /// Decrypt an enc:// string
+    Decrypt {
+        /// Encrypted value (read from stdin when omitted)
+        value: Option<String>,
+        
+        /// Key file (defaults to BACKWORKS_SECRET_KEY, BACKWORKS_SECRET_KEY_FILE, BACKWORKS_SECRET_KEYRING or .backworks/secret.key)
+        #[arg(long)]
+        key_file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum JournalAction {
+    /// List the journaled requests
+    Show {
+        /// Journal file
+        file: PathBuf,
+        
+        /// Only show requests to this endpoint
+        #[arg(short, long)]
+        endpoint: Option<String>,
+    },
+    
+    /// Send the journaled requests to a running server's handlers
+    Replay {
+        /// Journal file
+        file: PathBuf,
+        
+        /// Base URL of the running server
+        #[arg(long, default_value = "http://localhost:8080")]
+        url: String,
+        
+        /// Only replay requests to this endpoint
+        #[arg(short, long)]
+        endpoint: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TargetsAction {
+    /// List proxy targets and recent operator actions
+    List {
+        /// Base URL of the running server
+        #[arg(long, default_value = "http://localhost:8080")]
+        url: String,
+    },
+    
+    /// Stop sending requests to a target and wait for those in flight
+    Drain {
+        /// Proxy endpoint name
+        endpoint: String,
+        
+        /// Target URL, as in the blueprint
+        target: String,
+        
+        /// How long to wait for requests in flight
+        #[arg(long, default_value = "30s")]
+        timeout: String,
+        
+        /// Reason recorded in the audit trail
+        #[arg(long)]
+        reason: Option<String>,
+        
+        /// Base URL of the running server
+        #[arg(long, default_value = "http://localhost:8080")]
+        url: String,
+    },
+    
+    /// Keep a target out of rotation for a while, such as 10m
+    Quarantine {
+        /// Proxy endpoint name
+        endpoint: String,
+        
+        /// Target URL, as in the blueprint
+        target: String,
+        
+        /// How long to keep the target out of rotation
+        #[arg(long = "for", value_name = "DURATION")]
+        period: String,
+        
+        /// Reason recorded in the audit trail
+        #[arg(long)]
+        reason: Option<String>,
+        
+        /// Base URL of the running server
+        #[arg(long, default_value = "http://localhost:8080")]
+        url: String,
+    },
+    
+    /// Put a drained or quarantined target back into rotation
+    Enable {
+        /// Proxy endpoint name
+        endpoint: String,
+        
+        /// Target URL, as in the blueprint
+        target: String,
+        
+        /// Reason recorded in the audit trail
+        #[arg(long)]
+        reason: Option<String>,
+        
+        /// Base URL of the running server
+        #[arg(long, default_value = "http://localhost:8080")]
+        url: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbAction {
+    /// Insert fixture files (YAML, JSON or CSV, one table per file) into their tables
+    Seed {
+        /// Fixture files or directories of them
+        #[arg(default_value = "seeds")]
+        fixtures: Vec<PathBuf>,
+        
+        /// Configuration file path (optional for project structure)
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+        
+        /// Database plugin holding the tables (defaults to the only one)
+        #[arg(long)]
+        plugin: Option<String>,
+        
+        /// Keep rows already in their table instead of inserting them again
+        #[arg(long)]
+        idempotent: bool,
+        
+        /// Environment profile to apply (overrides BACKWORKS_ENV)
+        #[arg(short, long)]
+        env: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum StateAction {
+    /// Export state as JSON
+    Export {
+        /// Base URL of the running server
+        #[arg(long, default_value = "http://localhost:8080")]
+        url: String,
+        
+        /// Only export this namespace
+        #[arg(short, long)]
+        namespace: Option<String>,
+        
+        /// Output file (optional, defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    
+    /// Import state from a JSON snapshot
+    Import {
+        /// Snapshot file produced by `state export`
+        file: PathBuf,
+        
+        /// Base URL of the running server
+        #[arg(long, default_value = "http://localhost:8080")]
+        url: String,
+        
+        /// Only import this namespace from the snapshot
         #[arg(short, long)]
-        input: PathBuf,
+        namespace: Option<String>,
         
-        /// Output configuration file
-        #[arg(short, long, default_value = "generated.yaml")]
-        output: PathBuf,
+        /// Import mode (merge, replace)
+        #[arg(short, long, default_value = "merge")]
+        mode: String,
     },
 }
 
@@ -134,30 +725,101 @@ async fn main() -> Result<()> {
     init_logging(verbose);
     
     match cli.command {
-        Commands::Init { name, template } => {
-            init_project(name, template).await
+        Commands::Init { name, template, vars, types } => {
+            init_project(name, template, vars, types).await
         }
-        Commands::Start { config, port, dashboard_port, verbose: _, watch } => {
+        Commands::Start { config, port, dashboard_port, verbose: _, watch, env } => {
+            select_environment(env);
             start_server(config, port, dashboard_port, watch).await
         }
-        Commands::Build { target, security, output } => {
-            build_project(target, security, output).await
+        Commands::Run { package, port, dashboard_port, env } => {
+            select_environment(env);
+            run_package(package, port, dashboard_port).await
+        }
+        Commands::Build { target, security, output, archive, binary, env } => {
+            select_environment(env);
+            build_project(target, security, output, archive, binary).await
         }
         Commands::Migrate { from, to } => {
             migrate_project(from, to).await
         }
-        Commands::Validate { config } => {
-            validate_config(config).await
+        Commands::Validate { config, merged, env } => {
+            select_environment(env);
+            validate_config(config, merged).await
+        }
+        Commands::Analyze { config, format, output, usage, window } => {
+            let usage = usage.map(|url| (url, window));
+            analyze_blueprint(config, Some(format), output, usage).await
         }
-        Commands::Analyze { config, format, output } => {
-            analyze_blueprint(config, Some(format), output).await
+        Commands::Verify { against, config, capture, captures_only, endpoint, headers, params, compare_headers, writes, timeout, format, output, env } => {
+            select_environment(env);
+            let blueprint = (!captures_only).then_some(config);
+            let verify = VerifyArgs { headers, params, compare_headers, writes, timeout };
+            verify_contract(against, blueprint, capture, endpoint, verify, format, output).await
+        }
+        Commands::Replay { session, target, rate, speed, headers, drop_headers, timeout, format, output, fail_on } => {
+            let pacing = match (rate, speed) {
+                (Some(rate), _) => backworks::replay::Pacing::rate(&rate)?,
+                (None, Some(speed)) => backworks::replay::Pacing::speed(speed)?,
+                (None, None) => backworks::replay::Pacing::Sequential,
+            };
+            let replay = ReplayArgs { pacing, headers, drop_headers, timeout };
+            replay_session(session, target, replay, format, output, fail_on).await
+        }
+        Commands::Bench { endpoint, url, config, concurrency, duration, requests, method, headers, body, params, timeout, format } => {
+            let load = BenchArgs { concurrency, duration, requests, method, headers, body, params, timeout };
+            run_bench(endpoint, url, config, load, format).await
+        }
+        Commands::Drift { baseline, current, format, output, fail_on } => {
+            detect_drift(baseline, current, format, output, fail_on).await
+        }
+        Commands::Changelog { blueprints, git, config, format, output } => {
+            write_changelog(blueprints, git, config, format, output).await
+        }
+        Commands::Docs { config, capture, output, env } => {
+            select_environment(env);
+            write_docs(config, capture, output).await
+        }
+        Commands::Upgrade { config, from, write } => {
+            upgrade_blueprints(config, from, write)
         }
         Commands::Capture { port, output, duration } => {
             start_capture_mode(port, output, duration).await
         }
-        Commands::Generate { input, output } => {
-            generate_config(input, output).await
+        Commands::Generate { input, output, capture } => {
+            generate_config(input, output, capture).await
+        }
+        Commands::State { action } => {
+            manage_state(action).await
+        }
+        Commands::Db { action } => {
+            manage_database(action).await
         }
+        Commands::Journal { action } => {
+            manage_journal(action).await
+        }
+        Commands::Purge { endpoint, all: _, older_than, url } => {
+            purge_data(endpoint, older_than, url).await
+        }
+        Commands::Targets { action } => {
+            manage_targets(action).await
+        }
+        Commands::Console { url, execute } => {
+            run_console(url, execute).await
+        }
+        Commands::Secrets { action } => {
+            manage_secrets(action)
+        }
+        Commands::Plugin { action } => {
+            manage_plugins(action).await
+        }
+    }
+}
+
+/// Make `--env` visible to blueprint loading, which reads BACKWORKS_ENV
+fn select_environment(env: Option<String>) {
+    if let Some(env) = env {
+        std::env::set_var(backworks::blueprint::ENVIRONMENT_VAR, env);
     }
 }
 
@@ -165,9 +827,15 @@ async fn start_server(config_path: Option<PathBuf>, port: Option<u16>, dashboard
     println!("🚀 Starting Backworks...");
     
     // Load YAML configuration
-    let mut config = config::load_project_config(config_path)?;
+    let source = config::project_config_path(config_path)?;
+    let mut config = config::load_project_config(Some(source.clone()))?;
     
     println!("✅ Configuration loaded: {}", config.name);
+    if let Ok(env) = std::env::var(backworks::blueprint::ENVIRONMENT_VAR) {
+        if !env.is_empty() {
+            println!("🌍 Environment: {}", env);
+        }
+    }
     
     // Override ports if specified
     if let Some(p) = port {
@@ -180,7 +848,7 @@ async fn start_server(config_path: Option<PathBuf>, port: Option<u16>, dashboard
     }
     
     // Initialize the engine
-    let engine = BackworksEngine::new(config).await?;
+    let engine = BackworksEngine::from_blueprint(config, source).await?;
     println!("✅ Backworks engine initialized");
     
     if watch {
@@ -194,7 +862,23 @@ async fn start_server(config_path: Option<PathBuf>, port: Option<u16>, dashboard
     Ok(())
 }
 
-async fn init_project(name: String, template: String) -> Result<()> {
+/// Templates `backworks init` ships
+const TEMPLATES: &[&str] = &["hello-world", "api", "webapp", "proxy-gateway", "graphql", "database-crud", "event-driven"];
+
+async fn init_project(name: String, template: String, vars: Vec<String>, types: bool) -> Result<()> {
+    use backworks::scaffold::{ProjectTemplate, TemplateSource};
+    
+    let source = TemplateSource::parse(&template).transpose()?;
+    if source.is_none() {
+        if !TEMPLATES.contains(&template.as_str()) {
+            return Err(BackworksError::config(format!(
+                "Unknown template '{}' (expected one of {}, or git:<url>)", template, TEMPLATES.join(", ")
+            )));
+        }
+        if !vars.is_empty() {
+            return Err(BackworksError::config("--var only applies to git templates"));
+        }
+    }
     println!("🚀 Initializing new Backworks project: {}", name);
     
     // Create project directory
@@ -203,21 +887,43 @@ async fn init_project(name: String, template: String) -> Result<()> {
         return Err(BackworksError::config(format!("Directory '{}' already exists", name)));
     }
     
-    std::fs::create_dir_all(&project_dir)
-        .map_err(|e| BackworksError::config(format!("Failed to create project directory: {}", e)))?;
+    match source {
+        Some(source) => {
+            println!("📥 Fetching template {}", source.repository);
+            let template = ProjectTemplate::fetch(&source)?;
+            if let Some(ref description) = template.manifest.description {
+                println!("   {}", description);
+            }
+            let variables = template.variables(&name, &parse_pairs(&vars, '=', "Variable", "name=value")?)?;
+            std::fs::create_dir_all(&project_dir)
+                .map_err(|e| BackworksError::config(format!("Failed to create project directory: {}", e)))?;
+            if let Err(e) = template.render(&project_dir, &variables) {
+                let _ = std::fs::remove_dir_all(&project_dir);
+                return Err(e);
+            }
+        }
+        None => {
+            std::fs::create_dir_all(&project_dir)
+                .map_err(|e| BackworksError::config(format!("Failed to create project directory: {}", e)))?;
+            
+            // Create project structure
+            create_project_structure(&project_dir, &name, &template, types)?;
+        }
+    }
     
-    // Create project structure
-    create_project_structure(&project_dir, &name, &template)?;
+    if types {
+        for (path, content) in create_handler_types() {
+            std::fs::create_dir_all(project_dir.join(path).parent().unwrap_or(&project_dir))
+                .map_err(|e| BackworksError::config(format!("Failed to create types directory: {}", e)))?;
+            std::fs::write(project_dir.join(path), content)
+                .map_err(|e| BackworksError::config(format!("Failed to write {}: {}", path, e)))?;
+        }
+    }
     
     println!("✅ Project '{}' created successfully!", name);
     println!("📁 Project structure:");
     println!("   {}/", name);
-    println!("   ├── package.json");
-    println!("   ├── blueprints/");
-    println!("   │   └── main.yaml");
-    println!("   ├── handlers/");
-    println!("   │   └── echo.js");
-    println!("   └── README.md");
+    print_project_tree(&project_dir, "   ")?;
     println!();
     println!("🚀 Get started:");
     println!("   cd {}", name);
@@ -226,7 +932,26 @@ async fn init_project(name: String, template: String) -> Result<()> {
     Ok(())
 }
 
-fn create_project_structure(project_dir: &PathBuf, name: &str, template: &str) -> Result<()> {
+/// Print the files under `dir` as a tree, directories first
+fn print_project_tree(dir: &std::path::Path, indent: &str) -> Result<()> {
+    let mut entries: Vec<(bool, String, PathBuf)> = std::fs::read_dir(dir)?
+        .map(|entry| {
+            let entry = entry?;
+            Ok((!entry.file_type()?.is_dir(), entry.file_name().to_string_lossy().into_owned(), entry.path()))
+        })
+        .collect::<std::io::Result<_>>()?;
+    entries.sort();
+    for (index, (file, name, path)) in entries.iter().enumerate() {
+        let last = index + 1 == entries.len();
+        println!("{}{} {}{}", indent, if last { "└──" } else { "├──" }, name, if *file { "" } else { "/" });
+        if !file {
+            print_project_tree(path, &format!("{}{}", indent, if last { "    " } else { "│   " }))?;
+        }
+    }
+    Ok(())
+}
+
+fn create_project_structure(project_dir: &std::path::Path, name: &str, template: &str, types: bool) -> Result<()> {
     // Create main project configuration (package.json)
     let config_content = create_project_config(name, template);
     let config_path = project_dir.join("package.json");
@@ -244,22 +969,52 @@ fn create_project_structure(project_dir: &PathBuf, name: &str, template: &str) -
     std::fs::write(&main_path, main_blueprint)
         .map_err(|e| BackworksError::config(format!("Failed to write main.yaml: {}", e)))?;
     
+    // Create the blueprint fragments the webapp template includes
+    for (path, content) in create_included_blueprints(template) {
+        let fragment_path = blueprints_dir.join(path);
+        if let Some(parent) = fragment_path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| BackworksError::config(format!("Failed to create {}: {}", parent.display(), e)))?;
+        }
+        std::fs::write(&fragment_path, content)
+            .map_err(|e| BackworksError::config(format!("Failed to write {}: {}", path, e)))?;
+    }
+    
+    // Create the sample data the api template seeds its tables with
+    for (file, content) in create_seed_fixtures(template) {
+        let seeds_dir = project_dir.join("seeds");
+        std::fs::create_dir_all(&seeds_dir)
+            .map_err(|e| BackworksError::config(format!("Failed to create seeds directory: {}", e)))?;
+        std::fs::write(seeds_dir.join(file), content)
+            .map_err(|e| BackworksError::config(format!("Failed to write {}: {}", file, e)))?;
+    }
+    
     // Create README.md
     let readme = create_readme(name, template);
     let readme_path = project_dir.join("README.md");
     std::fs::write(&readme_path, readme)
         .map_err(|e| BackworksError::config(format!("Failed to write README.md: {}", e)))?;
     
-    // Create handlers directory and external handler
-    let handlers_dir = project_dir.join("handlers");
-    std::fs::create_dir_all(&handlers_dir)
-        .map_err(|e| BackworksError::config(format!("Failed to create handlers directory: {}", e)))?;
+    // Create the handlers and other files the template's blueprint names
+    for (path, content) in create_project_files(name, template) {
+        let content = if types { annotate_handler(path, content) } else { content };
+        let file_path = project_dir.join(path);
+        if let Some(parent) = file_path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| BackworksError::config(format!("Failed to create {}: {}", parent.display(), e)))?;
+        }
+        std::fs::write(&file_path, content)
+            .map_err(|e| BackworksError::config(format!("Failed to write {}: {}", path, e)))?;
+    }
     
-    // Create external echo handler
-    let echo_handler = create_echo_handler(name);
-    let echo_path = handlers_dir.join("echo.js");
-    std::fs::write(&echo_path, echo_handler)
-        .map_err(|e| BackworksError::config(format!("Failed to write echo.js: {}", e)))?;
+    // Create the frontend directory the webapp template serves
+    if template == "webapp" {
+        let public_dir = project_dir.join("public");
+        std::fs::create_dir_all(&public_dir)
+            .map_err(|e| BackworksError::config(format!("Failed to create public directory: {}", e)))?;
+        std::fs::write(public_dir.join("index.html"), create_frontend_index(name))
+            .map_err(|e| BackworksError::config(format!("Failed to write index.html: {}", e)))?;
+    }
     
     Ok(())
 }
@@ -274,6 +1029,7 @@ fn create_project_config(name: &str, template: &str) -> String {
   "scripts": {{
     "dev": "backworks start --watch",
     "build": "backworks build --target production",
+    "seed": "backworks db seed --idempotent",
     "test": "backworks test"
   }},
   "dependencies": {{
@@ -295,6 +1051,64 @@ fn create_project_config(name: &str, template: &str) -> String {
   }},
   "keywords": ["webapp", "web", "backworks", "application"],
   "license": "MIT"
+}}"#, name),
+        "proxy-gateway" => format!(r#"{{
+  "name": "{}",
+  "version": "1.0.0",
+  "description": "An API gateway built with Backworks",
+  "main": "blueprints/main.yaml",
+  "scripts": {{
+    "dev": "backworks start --watch",
+    "build": "backworks build --target production",
+    "targets": "backworks targets list"
+  }},
+  "keywords": ["gateway", "proxy", "backworks"],
+  "license": "MIT"
+}}"#, name),
+        "graphql" => format!(r#"{{
+  "name": "{}",
+  "version": "1.0.0",
+  "description": "A GraphQL API built with Backworks",
+  "main": "blueprints/main.yaml",
+  "scripts": {{
+    "dev": "backworks start --watch",
+    "build": "backworks build --target production",
+    "test": "backworks test"
+  }},
+  "keywords": ["graphql", "api", "backworks"],
+  "license": "MIT"
+}}"#, name),
+        "database-crud" => format!(r#"{{
+  "name": "{}",
+  "version": "1.0.0",
+  "description": "A CRUD API over a database built with Backworks",
+  "main": "blueprints/main.yaml",
+  "scripts": {{
+    "dev": "backworks start --watch",
+    "build": "backworks build --target production",
+    "seed": "backworks db seed --idempotent"
+  }},
+  "dependencies": {{
+    "backworks-sqlite": "^1.0.0"
+  }},
+  "keywords": ["crud", "database", "sqlite", "backworks"],
+  "license": "MIT"
+}}"#, name),
+        "event-driven" => format!(r#"{{
+  "name": "{}",
+  "version": "1.0.0",
+  "description": "An event-driven API built with Backworks",
+  "main": "blueprints/main.yaml",
+  "scripts": {{
+    "dev": "backworks start --watch",
+    "build": "backworks build --target production"
+  }},
+  "dependencies": {{
+    "backworks-messaging": "^1.0.0",
+    "backworks-jobs": "^1.0.0"
+  }},
+  "keywords": ["events", "webhooks", "jobs", "backworks"],
+  "license": "MIT"
 }}"#, name),
         _ => format!(r#"{{
   "name": "{}",
@@ -353,6 +1167,7 @@ endpoints:
 "#, name, name),
         "webapp" => format!(r#"name: "{}"
 description: "A web application with API and UI"
+mode: runtime
 
 includes:
   - "./endpoints/"
@@ -361,7 +1176,166 @@ includes:
 globals:
   app_name: "{}"
   api_version: "v1"
+
+# Signed-in users are kept in an encrypted cookie; set SESSION_SECRET in production
+sessions:
+  secret: "${{SESSION_SECRET:-development-secret-change-me-0123456789}}"
+  ttl: "12h"
+  same_site: lax
 "#, name, name),
+        "proxy-gateway" => format!(r#"name: "{}"
+description: "An API gateway in front of backend services"
+
+# Set the service URLs per environment; the defaults point at local services
+security:
+  cors:
+    enabled: true
+
+monitoring:
+  health:
+    checks:
+      - name: "catalog_service"
+        type: "url"
+        url: "${{CATALOG_SERVICE_URL:-http://localhost:9002}}/health"
+        critical: false
+
+endpoints:
+  users:
+    path: "/api/users/{{*rest}}"
+    methods: ["GET", "POST", "PUT", "PATCH", "DELETE"]
+    description: "Users service, spread over its instances"
+    mode: proxy
+    proxy:
+      targets:
+        - "${{USERS_SERVICE_URL:-http://localhost:9001}}"
+        - "${{USERS_SERVICE_URL_2:-http://localhost:9011}}"
+      load_balancing:
+        algorithm: least_connections
+      timeout_ms: 5000
+    middleware:
+      - rate_limit: {{ requests: 100, window: 60 }}
+
+  catalog:
+    path: "/api/catalog/{{*rest}}"
+    methods: ["GET"]
+    description: "Catalog service, cached, with generated data while it is down"
+    modes: [proxy, mock]
+    proxy:
+      upstream: "${{CATALOG_SERVICE_URL:-http://localhost:9002}}"
+      timeout_ms: 2000
+    mock:
+      count: 5
+      schema: {{ id: $seq, name: $name }}
+    middleware:
+      - cache: {{ ttl: 30 }}
+"#, name),
+        "graphql" => format!(r#"name: "{}"
+description: "A GraphQL API; the schema is in schema.graphql"
+mode: runtime
+
+endpoints:
+  graphql:
+    path: "/graphql"
+    methods: ["POST"]
+    description: "GraphQL queries and mutations, resolved in handlers/graphql.js"
+    runtime:
+      language: "javascript"
+      handler: "./handlers/graphql.js"
+"#, name),
+        "database-crud" => format!(r#"name: "{}"
+description: "A CRUD API over database tables"
+mode: database
+
+# Create the tables with schema.sql, then load seeds/ with `backworks db seed`
+database:
+  type: "sqlite"
+  connection_string: "${{DATABASE_URL:-sqlite://data/app.db}}"
+  pool:
+    max_connections: 5
+
+plugins:
+  sqlite:
+    enabled: true
+
+endpoints:
+  users:
+    path: "/users"
+    methods: ["GET", "POST", "PUT", "PATCH", "DELETE"]
+    description: "Users: list, create, read, replace, update and delete"
+    database:
+      table: "users"
+      auto_crud: true
+
+  posts:
+    path: "/posts"
+    methods: ["GET", "POST", "PUT", "PATCH", "DELETE"]
+    description: "Posts; filter by author with ?author_id=1"
+    database:
+      table: "posts"
+      auto_crud: true
+
+  feed:
+    path: "/feed"
+    methods: ["GET"]
+    description: "The ten newest posts, through a named query"
+    database:
+      queries:
+        GET: "SELECT * FROM posts ORDER BY id DESC LIMIT 10"
+      transform:
+        list: "posts"
+"#, name),
+        "event-driven" => format!(r#"name: "{}"
+description: "An event-driven API: webhooks in, events and background jobs out"
+mode: runtime
+
+plugins:
+  messaging:
+    enabled: true
+    config:
+      broker: {{ type: nats, url: "${{NATS_URL:-nats://localhost:4222}}" }}
+      bridges:
+        - {{ path: "/events/orders", topic: "orders.>" }}
+  jobs:
+    enabled: true
+    config:
+      store: {{ type: sqlite, path: "./data/jobs.db" }}
+      workers: 2
+      jobs:
+        fulfil_order:
+          runtime: {{ language: javascript, handler: "./jobs/fulfil_order.js" }}
+          timeout: 30s
+
+endpoints:
+  create_order:
+    path: "/orders"
+    methods: ["POST"]
+    description: "Take an order and publish orders.created"
+    runtime:
+      language: "javascript"
+      handler: "./handlers/create_order.js"
+
+  payment_webhook:
+    path: "/webhooks/payments"
+    methods: ["POST"]
+    description: "Signed payment notifications, published as orders.paid"
+    webhook:
+      provider: hmac
+      secret: "${{PAYMENTS_WEBHOOK_SECRET:-development-secret}}"
+      header: "X-Signature"
+      prefix: "sha256="
+    runtime:
+      language: "javascript"
+      handler: "./handlers/payment_webhook.js"
+
+schedules:
+  reconcile:
+    cron: "*/15 * * * *"
+    description: "Look for orders still waiting for payment"
+    runtime:
+      language: "javascript"
+      handler: "./jobs/reconcile.js"
+    timeout: 1m
+"#, name),
         _ => format!(r#"name: "{}"
 description: "A simple API demonstrating both inline and external handlers"
 
@@ -394,6 +1368,749 @@ endpoints:
     }
 }
 
+fn create_included_blueprints(template: &str) -> Vec<(&'static str, String)> {
+    if template != "webapp" {
+        return Vec::new();
+    }
+    
+    vec![
+        ("endpoints/api.yaml", r#"endpoints:
+  status:
+    path: "/api/status"
+    methods: ["GET"]
+    description: "API status"
+    runtime:
+      language: "javascript"
+      handler: |
+        function handler(req, res) {
+          return { status: 200, body: { status: "ok" } };
+        }
+"#.to_string()),
+        ("endpoints/auth.yaml", r#"endpoints:
+  login:
+    path: "/api/login"
+    methods: ["POST"]
+    description: "Sign in; replace the password check with your user store"
+    runtime:
+      language: "javascript"
+      handler: |
+        function handler(req, res) {
+          const { username, password } = req.body || {};
+          if (!username || password !== "demo") {
+            return { status: 401, body: { error: "Invalid username or password" } };
+          }
+          req.session.user = { name: username, signed_in_at: new Date().toISOString() };
+          return { status: 200, body: { user: req.session.user } };
+        }
+
+  logout:
+    path: "/api/logout"
+    methods: ["POST"]
+    description: "Sign out, ending the session"
+    runtime:
+      language: "javascript"
+      handler: |
+        function handler(req, res) {
+          req.session = null;
+          return { status: 204, body: null };
+        }
+
+  me:
+    path: "/api/me"
+    methods: ["GET"]
+    description: "The signed-in user"
+    runtime:
+      language: "javascript"
+      handler: |
+        function handler(req, res) {
+          if (!req.session.user) {
+            return { status: 401, body: { error: "Not signed in" } };
+          }
+          return { status: 200, body: { user: req.session.user } };
+        }
+"#.to_string()),
+        ("ui/pages.yaml", r#"endpoints:
+  frontend:
+    path: "/"
+    mode: static
+    description: "Built frontend, served beside the API"
+    static:
+      dir: "./public"
+      spa_fallback: true
+"#.to_string()),
+    ]
+}
+
+fn create_seed_fixtures(template: &str) -> Vec<(&'static str, String)> {
+    if template != "api" && template != "database-crud" {
+        return Vec::new();
+    }
+    
+    vec![
+        ("users.yaml", r#"# Loaded with `backworks db seed`; labels let other fixtures refer to rows
+ada:
+  name: "Ada Lovelace"
+  email: "ada@example.com"
+  role: "admin"
+
+grace:
+  name: "Grace Hopper"
+  email: "grace@example.com"
+  role: "member"
+"#.to_string()),
+        ("posts.csv", "title,body,author_id
+Welcome,First post of the sample data,$ref:users.ada
+Debugging,Notes on finding the moth,$ref:users.grace
+".to_string()),
+    ]
+}
+
+fn create_project_files(name: &str, template: &str) -> Vec<(&'static str, String)> {
+    match template {
+        "proxy-gateway" => Vec::new(),
+        "graphql" => vec![
+            ("schema.graphql", r#"# Types the resolvers in handlers/graphql.js serve
+type Query {
+  users: [User!]!
+  user(id: ID!): User
+  posts(authorId: ID): [Post!]!
+}
+
+type Mutation {
+  createPost(title: String!, body: String, authorId: ID!): Post!
+}
+
+type User {
+  id: ID!
+  name: String!
+  email: String!
+  posts: [Post!]!
+}
+
+type Post {
+  id: ID!
+  title: String!
+  body: String!
+  author: User!
+}
+"#.to_string()),
+            ("handlers/graphql.js", r#"/** GraphQL endpoint
+ *
+ * Resolves the queries and mutations of schema.graphql with the resolvers
+ * below. The executor covers what clients usually send: fields, aliases,
+ * arguments, variables with defaults, nested selections, named operations
+ * and __typename; fragments and directives are not supported.
+ *
+ * The data lives in this file and every request starts from it again;
+ * point the resolvers at a database or another service to keep changes.
+ */
+
+const users = [
+  { __typename: "User", id: "1", name: "Ada Lovelace", email: "ada@example.com" },
+  { __typename: "User", id: "2", name: "Grace Hopper", email: "grace@example.com" }
+];
+const posts = [
+  { __typename: "Post", id: "1", title: "Welcome", body: "First post", authorId: "1" },
+  { __typename: "Post", id: "2", title: "Debugging", body: "Notes on finding the moth", authorId: "2" }
+];
+
+// Field resolvers by type; fields without one are read from the object
+const resolvers = {
+  Query: {
+    users: () => users,
+    user: (_, { id }) => users.find((user) => user.id === String(id)) || null,
+    posts: (_, { authorId }) => authorId == null ? posts : posts.filter((post) => post.authorId === String(authorId))
+  },
+  Mutation: {
+    createPost: (_, { title, body, authorId }) => {
+      if (!users.some((user) => user.id === String(authorId))) {
+        throw new Error(`No user with id ${authorId}`);
+      }
+      const post = { __typename: "Post", id: String(posts.length + 1), title, body: body || "", authorId: String(authorId) };
+      posts.push(post);
+      return post;
+    }
+  },
+  User: {
+    posts: (user) => posts.filter((post) => post.authorId === user.id)
+  },
+  Post: {
+    author: (post) => users.find((user) => user.id === post.authorId)
+  }
+};
+
+function handler(req) {
+  const { query, variables, operationName } = req.body || {};
+  if (typeof query !== "string") {
+    return { status: 400, body: { errors: [{ message: "Send a JSON body with a query" }] } };
+  }
+  let operation;
+  try {
+    operation = parse(query, operationName);
+  } catch (error) {
+    return { status: 400, body: { errors: [{ message: error.message }] } };
+  }
+  try {
+    const values = Object.assign({}, operation.defaults, variables || {});
+    const root = { __typename: operation.type === "mutation" ? "Mutation" : "Query" };
+    return { status: 200, body: { data: select(root, operation.selections, values) } };
+  } catch (error) {
+    return { status: 200, body: { data: null, errors: [{ message: error.message }] } };
+  }
+}
+
+// Execution
+
+function select(parent, selections, variables) {
+  const result = {};
+  for (const field of selections) {
+    const key = field.alias || field.name;
+    if (field.name === "__typename") {
+      result[key] = parent.__typename;
+      continue;
+    }
+    const resolver = (resolvers[parent.__typename] || {})[field.name];
+    if (!resolver && !(field.name in parent)) {
+      throw new Error(`Cannot query field "${field.name}" on type "${parent.__typename}"`);
+    }
+    const value = resolver ? resolver(parent, evaluate(field.args, variables)) : parent[field.name];
+    result[key] = complete(value, field, variables);
+  }
+  return result;
+}
+
+function complete(value, field, variables) {
+  if (value === null || value === undefined) {
+    return null;
+  }
+  if (Array.isArray(value)) {
+    return value.map((item) => complete(item, field, variables));
+  }
+  if (typeof value === "object") {
+    if (!field.selections) {
+      throw new Error(`Field "${field.name}" needs a selection of subfields`);
+    }
+    return select(value, field.selections, variables);
+  }
+  if (field.selections) {
+    throw new Error(`Field "${field.name}" has no subfields`);
+  }
+  return value;
+}
+
+function evaluate(value, variables) {
+  if (value && value.variable !== undefined) {
+    return variables[value.variable];
+  }
+  if (Array.isArray(value)) {
+    return value.map((item) => evaluate(item, variables));
+  }
+  if (value && typeof value === "object") {
+    const result = {};
+    for (const [name, item] of Object.entries(value)) {
+      result[name] = evaluate(item, variables);
+    }
+    return result;
+  }
+  return value;
+}
+
+// Parsing
+
+function tokenize(source) {
+  const pattern = /[\s,]+|#[^\n\r]*|(\.\.\.|[{}()[\]:=!$@|])|("(?:[^"\\\n]|\\.)*")|(-?\d+(?:\.\d+)?(?:[eE][+-]?\d+)?)|([_A-Za-z][_0-9A-Za-z]*)/y;
+  const tokens = [];
+  while (pattern.lastIndex < source.length) {
+    const start = pattern.lastIndex;
+    const match = pattern.exec(source);
+    if (!match) {
+      throw new Error(`Syntax error: unexpected character "${source[start]}"`);
+    }
+    if (match[1]) tokens.push({ kind: "punctuator", value: match[1] });
+    else if (match[2]) tokens.push({ kind: "string", value: match[2] });
+    else if (match[3]) tokens.push({ kind: "number", value: match[3] });
+    else if (match[4]) tokens.push({ kind: "name", value: match[4] });
+  }
+  return tokens;
+}
+
+function parse(source, operationName) {
+  const tokens = tokenize(source);
+  let position = 0;
+  const peek = (value) => position < tokens.length && (value === undefined || tokens[position].value === value);
+  const next = () => {
+    if (position >= tokens.length) {
+      throw new Error("Syntax error: unexpected end of query");
+    }
+    return tokens[position++];
+  };
+  const expect = (value) => {
+    const token = next();
+    if (token.value !== value) {
+      throw new Error(`Syntax error: expected "${value}", found "${token.value}"`);
+    }
+  };
+  const name = () => {
+    const token = next();
+    if (token.kind !== "name") {
+      throw new Error(`Syntax error: expected a name, found "${token.value}"`);
+    }
+    return token.value;
+  };
+
+  const value = () => {
+    const token = next();
+    if (token.value === "$") return { variable: name() };
+    if (token.kind === "string") return JSON.parse(token.value);
+    if (token.kind === "number") return Number(token.value);
+    if (token.value === "[") {
+      const list = [];
+      while (!peek("]")) list.push(value());
+      expect("]");
+      return list;
+    }
+    if (token.value === "{") {
+      const object = {};
+      while (!peek("}")) {
+        const field = name();
+        expect(":");
+        object[field] = value();
+      }
+      expect("}");
+      return object;
+    }
+    if (token.kind === "name") {
+      const literals = { true: true, false: false, null: null };
+      return token.value in literals ? literals[token.value] : token.value;
+    }
+    throw new Error(`Syntax error: unexpected "${token.value}"`);
+  };
+
+  const type = () => {
+    if (peek("[")) {
+      next();
+      type();
+      expect("]");
+    } else {
+      name();
+    }
+    if (peek("!")) next();
+  };
+
+  const selectionSet = () => {
+    expect("{");
+    const selections = [];
+    while (!peek("}")) {
+      if (peek("...")) throw new Error("Fragments are not supported");
+      let field = { name: name(), args: {} };
+      if (peek(":")) {
+        next();
+        field = { alias: field.name, name: name(), args: {} };
+      }
+      if (peek("(")) {
+        next();
+        while (!peek(")")) {
+          const argument = name();
+          expect(":");
+          field.args[argument] = value();
+        }
+        expect(")");
+      }
+      if (peek("@")) throw new Error("Directives are not supported");
+      if (peek("{")) field.selections = selectionSet();
+      selections.push(field);
+    }
+    expect("}");
+    return selections;
+  };
+
+  const operations = [];
+  while (position < tokens.length) {
+    const operation = { type: "query", name: null, defaults: {} };
+    if (!peek("{")) {
+      const keyword = name();
+      if (keyword === "fragment") throw new Error("Fragments are not supported");
+      if (keyword !== "query" && keyword !== "mutation") {
+        throw new Error(`${keyword === "subscription" ? "Subscriptions are" : `"${keyword}" is`} not supported`);
+      }
+      operation.type = keyword;
+      if (position < tokens.length && tokens[position].kind === "name") operation.name = name();
+      if (peek("(")) {
+        next();
+        while (!peek(")")) {
+          expect("$");
+          const variable = name();
+          expect(":");
+          type();
+          if (peek("=")) {
+            next();
+            operation.defaults[variable] = value();
+          }
+        }
+        expect(")");
+      }
+    }
+    operation.selections = selectionSet();
+    operations.push(operation);
+  }
+
+  if (operations.length === 0) {
+    throw new Error("The query holds no operation");
+  }
+  if (operationName) {
+    const operation = operations.find((candidate) => candidate.name === operationName);
+    if (!operation) throw new Error(`No operation named "${operationName}"`);
+    return operation;
+  }
+  if (operations.length > 1) {
+    throw new Error("The query holds several operations; name one with operationName");
+  }
+  return operations[0];
+}
+
+module.exports = handler;
+"#.to_string()),
+        ],
+        "database-crud" => vec![
+            ("schema.sql", r#"-- Tables the blueprint serves; create them with `sqlite3 data/app.db < schema.sql`
+CREATE TABLE IF NOT EXISTS users (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  name TEXT NOT NULL,
+  email TEXT NOT NULL UNIQUE,
+  role TEXT NOT NULL DEFAULT 'member',
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS posts (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  title TEXT NOT NULL,
+  body TEXT,
+  author_id INTEGER NOT NULL REFERENCES users(id),
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+"#.to_string()),
+            ("data/.gitignore", "*.db\n".to_string()),
+        ],
+        "event-driven" => vec![
+            ("handlers/create_order.js", r#"/** Take an order
+ *
+ * The order is published as `orders.created` once the response is sent.
+ * Keep orders in a database or another service; this handler only
+ * checks them.
+ */
+
+function handler(req, ctx) {
+  const { customer, items } = req.body || {};
+  if (!Array.isArray(items) || items.length === 0) {
+    return { status: 400, body: { error: "An order needs items" } };
+  }
+  const order = {
+    id: `ord_${Date.now().toString(36)}`,
+    customer: customer || null,
+    items,
+    status: "awaiting_payment",
+    created_at: new Date().toISOString()
+  };
+  return {
+    status: 201,
+    body: order,
+    events: [{ topic: "orders.created", key: order.id, data: order }]
+  };
+}
+
+module.exports = handler;
+"#.to_string()),
+            ("handlers/payment_webhook.js", r#"/** Payment notifications
+ *
+ * Deliveries are signed with PAYMENTS_WEBHOOK_SECRET; unsigned ones never
+ * reach this handler. A paid order is published as `orders.paid` and its
+ * fulfilment queued for the jobs plugin.
+ */
+
+function handler(req, ctx) {
+  const { order_id, amount, currency } = req.body || {};
+  if (!order_id) {
+    return { status: 422, body: { error: "order_id is missing" } };
+  }
+  ctx.jobs.enqueue("fulfil_order", { order_id }, { max_attempts: 10 });
+  return {
+    status: 202,
+    body: { received: true },
+    events: [{
+      topic: "orders.paid",
+      key: order_id,
+      data: { order_id, amount, currency, paid_at: new Date().toISOString() }
+    }]
+  };
+}
+
+module.exports = handler;
+"#.to_string()),
+            ("jobs/fulfil_order.js", r#"/** Fulfil a paid order
+ *
+ * Runs on the jobs plugin's workers after the payment webhook answers.
+ * Throwing fails the attempt, and the job is retried after the backoff.
+ */
+
+function handler(req) {
+  const { job, payload } = req;
+  // Reserve stock, book the shipment, ...
+  return { order_id: payload.order_id, fulfilled_at: new Date().toISOString(), attempt: job.attempt };
+}
+
+module.exports = handler;
+"#.to_string()),
+            ("jobs/reconcile.js", r#"/** Chase orders waiting for payment
+ *
+ * Runs every 15 minutes; what it returns is kept as the run's output at
+ * GET /_backworks/schedules/reconcile/runs.
+ */
+
+function handler(run) {
+  // Look up orders still awaiting payment and remind or cancel them
+  return { checked_at: run.scheduled_at, stale_orders: 0 };
+}
+
+module.exports = handler;
+"#.to_string()),
+            ("data/.gitignore", "*.db\n".to_string()),
+        ],
+        _ => vec![("handlers/echo.js", create_echo_handler(name))],
+    }
+}
+
+/// The handler API as TypeScript definitions, with the jsconfig.json that
+/// gives JavaScript handlers completion against them
+fn create_handler_types() -> Vec<(&'static str, String)> {
+    vec![
+        ("types/backworks.d.ts", r#"// The Backworks JavaScript handler API, for editor completion
+//
+// Annotate a handler with JSDoc to get completion for what it receives and
+// returns:
+//
+//   /**
+//    * @param {Backworks.Request} req
+//    * @param {Backworks.Context} ctx
+//    * @returns {Backworks.Result}
+//    */
+//   function handler(req, ctx) { ... }
+//
+// Job handlers take a Backworks.JobRequest and schedule handlers a
+// Backworks.ScheduleRun instead. Written by `backworks init --types`.
+
+declare namespace Backworks {
+  type JsonValue = string | number | boolean | null | JsonValue[] | { [key: string]: JsonValue };
+  type JsonObject = { [key: string]: JsonValue };
+
+  /** The request an endpoint handler is called with */
+  interface Request {
+    method: string;
+    /** Path the client requested, such as `/users/42` */
+    path: string;
+    /** Values of `{name}` path segments; numbers or booleans when the endpoint coerces them */
+    path_params: Record<string, JsonValue>;
+    query_params: Record<string, string>;
+    /** Decoded request body, or null without one */
+    body: JsonValue;
+    /** Identity of the caller, once an auth middleware or plugin verified it */
+    auth?: Auth;
+    origin?: Origin;
+    locale?: Locale;
+    /** Milliseconds left of the endpoint's timeout */
+    timeout_ms?: number;
+    /** With `sessions`: changes are saved with the response, null ends the session */
+    session?: JsonObject | null;
+    /** With `sessions.csrf`: the token state-changing requests must send */
+    csrf_token?: string;
+  }
+
+  interface Auth {
+    subject: string;
+    method: "jwt" | "api_key" | "basic" | "trusted_header";
+    email?: string;
+    roles: string[];
+    claims: JsonObject;
+  }
+
+  interface Origin {
+    /** Client address, taken from X-Forwarded-For behind trusted proxies */
+    ip: string | null;
+    /** ISO 3166-1 alpha-2 country code, with an enrichment plugin */
+    country?: string;
+    country_name?: string;
+    /** Two-letter continent code */
+    continent?: string;
+    /** Autonomous system number of the client's network */
+    asn?: number;
+    as_org?: string;
+  }
+
+  interface Locale {
+    /** Negotiated locale, such as `de-AT` */
+    tag: string;
+    language: string;
+    region: string | null;
+    /** Locales the client accepts, most preferred first */
+    accepted: string[];
+    /** IANA time zone, such as `Europe/Vienna` */
+    timezone: string;
+  }
+
+  /** The second argument of every handler */
+  interface Context {
+    /** An error from the blueprint's error catalog; return or throw it */
+    error(code: string, params?: JsonObject): ErrorReference;
+    /** Emails for the email plugin, sent once the response is ready */
+    email: { send(message: Email): void };
+    /** Jobs for the jobs plugin, run after the response */
+    jobs: { enqueue(name: string, payload?: JsonValue, options?: JobOptions): void };
+  }
+
+  interface Email {
+    /** One address or a list */
+    to: string | string[];
+    cc?: string | string[];
+    bcc?: string | string[];
+    from?: string;
+    reply_to?: string;
+    /** Replaces the template's subject; required without a template */
+    subject?: string;
+    /** Name of a template in the plugin's config */
+    template?: string;
+    /** Values the subject and bodies are rendered with */
+    data?: JsonValue;
+    /** Plain text body template, without `template` */
+    text?: string;
+    /** HTML body template, without `template` */
+    html?: string;
+  }
+
+  interface JobOptions {
+    /** Run the job after this long, such as `30s`, instead of at once */
+    delay?: string;
+    /** Replaces the job's own `max_attempts` */
+    max_attempts?: number;
+  }
+
+  /** What ctx.error() returns */
+  interface ErrorReference {
+    $error: { code: string; params: JsonObject };
+  }
+
+  /** A response with its status; any other value is sent as a 200 body */
+  interface Response {
+    status: number;
+    body: JsonValue | FileDownload;
+    headers?: Record<string, string>;
+    /** Published by a messaging plugin once the response is sent */
+    events?: Event[];
+    /** Sent by the email plugin, as with ctx.email.send() */
+    emails?: Email[];
+    /** Queued with the jobs plugin, as with ctx.jobs.enqueue() */
+    jobs?: Array<JobOptions & { name: string; payload?: JsonValue }>;
+    /** Replaces the session; null ends it */
+    session?: JsonObject | null;
+  }
+
+  interface Event {
+    topic: string;
+    /** Partitioning or deduplication key */
+    key?: string;
+    data?: JsonValue;
+  }
+
+  /** A file sent as the response, on its own or as the body of a 2xx */
+  interface FileDownload {
+    /** Relative paths resolve against the working directory */
+    file: string;
+    /** Offer the file as a download under this name */
+    download_name?: string;
+    /** Guessed from the download name or the path when missing */
+    content_type?: string;
+    /** Let the browser display the file instead of saving it */
+    inline?: boolean;
+    /** Delete the file once it has been sent */
+    temporary?: boolean;
+  }
+
+  type Result = Response | ErrorReference | FileDownload | JsonValue;
+
+  /** What a job handler of the jobs plugin is called with */
+  interface JobRequest {
+    job: {
+      id: string;
+      name: string;
+      /** 1 on the first run */
+      attempt: number;
+    };
+    payload: JsonValue;
+  }
+
+  /** What a schedule's handler is called with; its return value is kept as the run's output */
+  interface ScheduleRun {
+    schedule: string;
+    trigger: "cron" | "manual";
+    /** RFC 3339 time the run started */
+    scheduled_at: string;
+    params: JsonValue;
+  }
+}
+"#.to_string()),
+        ("jsconfig.json", r#"{
+  "compilerOptions": {
+    "target": "ES2022",
+    "module": "commonjs",
+    "checkJs": false
+  },
+  "include": ["**/*.js", "types/**/*.d.ts"],
+  "exclude": ["node_modules", "public"]
+}
+"#.to_string()),
+    ]
+}
+
+/// Annotate the handler a template ships with the types of
+/// `create_handler_types`; files under `jobs/` run as jobs, or as schedules
+/// when their argument is a `run`
+fn annotate_handler(path: &str, content: String) -> String {
+    let Some(start) = content.find("\nfunction handler(").map(|index| index + 1) else {
+        return content;
+    };
+    let arguments = content[start + "function handler(".len()..].split(')').next().unwrap_or_default();
+    let mut tags = Vec::new();
+    for (index, argument) in arguments.split(',').map(str::trim).filter(|argument| !argument.is_empty()).enumerate() {
+        let kind = match index {
+            0 if argument == "run" => "ScheduleRun",
+            0 if path.starts_with("jobs/") => "JobRequest",
+            0 => "Request",
+            _ => "Context",
+        };
+        tags.push(format!(" * @param {{Backworks.{}}} {}", kind, argument));
+    }
+    if path.starts_with("handlers/") {
+        tags.push(" * @returns {Backworks.Result}".to_string());
+    }
+    if tags.is_empty() {
+        return content;
+    }
+    format!("{}/**\n{}\n */\n{}", &content[..start], tags.join("\n"), &content[start..])
+}
+
+fn create_frontend_index(name: &str) -> String {
+    format!(r#"<!DOCTYPE html>
+<html>
+  <head>
+    <meta charset="utf-8">
+    <title>{}</title>
+  </head>
+  <body>
+    <h1>Welcome to {}!</h1>
+    <p>Build your frontend into <code>public/</code>; the API is under <a href="/api/status">/api</a>.</p>
+  </body>
+</html>
+"#, name, name)
+}
+
 fn create_readme(name: &str, template: &str) -> String {
     format!(r#"# {}
 
@@ -402,14 +2119,14 @@ A {} built with Backworks.
 ## Quick Start
 
 ```bash
-# Start the development server
+{}# Start the development server
 backworks start
 
 # Or with hot reload
 backworks start --watch
 
 # Test the API
-curl http://localhost:3000/hello
+{}
 ```
 
 ## Development
@@ -417,7 +2134,7 @@ curl http://localhost:3000/hello
 ```bash
 # Validate configuration
 backworks validate
-
+{}
 # Build for production
 backworks build --target production
 
@@ -448,47 +2165,135 @@ backworks build --target production
     match template {
         "api" => "REST API",
         "webapp" => "web application", 
+        "proxy-gateway" => "API gateway",
+        "graphql" => "GraphQL API",
+        "database-crud" => "CRUD API over a database",
+        "event-driven" => "event-driven API",
         _ => "API application"
+    },
+    match template {
+        "database-crud" => "# Create the tables and load the sample data in seeds/\nsqlite3 data/app.db < schema.sql\nbackworks db seed --idempotent\n\n",
+        "event-driven" => "# Events go to NATS at NATS_URL (nats://localhost:4222 by default)\nnats-server &\n\n",
+        _ => "",
+    },
+    match template {
+        "api" => "curl http://localhost:8080/health",
+        "webapp" => "curl http://localhost:8080/api/status",
+        "proxy-gateway" => "curl http://localhost:8080/api/catalog/products",
+        "graphql" => r#"curl http://localhost:8080/graphql -H 'content-type: application/json' -d '{"query": "{ users { name posts { title } } }"}'"#,
+        "database-crud" => "curl 'http://localhost:8080/users?sort=name'",
+        "event-driven" => r#"curl http://localhost:8080/orders -H 'content-type: application/json' -d '{"items": [{"sku": "tea", "quantity": 2}]}'"#,
+        _ => "curl http://localhost:8080/hello",
+    },
+    if template == "api" {
+        "\n# Load the sample data in seeds/ (safe to run again)\nbackworks db seed --idempotent\n"
+    } else {
+        ""
     }
     )
 }
 
-async fn build_project(target: String, security: Option<String>, output: Option<PathBuf>) -> Result<()> {
-    println!("🔨 Building project for target: {}", target);
+async fn run_package(path: PathBuf, port: Option<u16>, dashboard_port: Option<u16>) -> Result<()> {
+    let package = backworks::package::Package::open(&path)?;
+    let manifest = &package.manifest;
+    let version = manifest.version.as_deref().map(|version| format!(" {}", version)).unwrap_or_default();
+    println!("📦 Package {}{} (built with Backworks {})", manifest.name, version, manifest.backworks_version);
     
-    // Load project configuration
-    let config = config::load_project_config(None)?;
+    let dir = package.run_dir();
+    let blueprint = package.unpack(&dir)?;
+    let missing = package.missing_plugins(&dir);
+    if !missing.is_empty() {
+        let names: Vec<String> = missing.iter()
+            .map(|plugin| format!("{} ({})", plugin.name, plugin.path.as_deref().unwrap_or("no path")))
+            .collect();
+        return Err(BackworksError::config(format!("Package needs plugins that are not available: {}", names.join(", "))));
+    }
+    println!("📁 Unpacked to {}", dir.display());
     
-    println!("✅ Configuration loaded successfully");
+    // Handlers, seed data and includes are relative to the package root
+    std::env::set_current_dir(&dir)?;
+    start_server(Some(blueprint), port, dashboard_port, false).await
+}
+
+async fn build_project(target: String, security: Option<String>, output: Option<PathBuf>, archive: bool, binary: Option<PathBuf>) -> Result<()> {
+    println!("🔨 Building project for target: {}", target);
     
-    // Apply security profile if specified
-    if let Some(security_profile) = security {
-        println!("🔒 Applying security profile: {}", security_profile);
-        // TODO: Implement security transformations
+    if target == "package" {
+        return build_package(output);
+    }
+    if target == "docker" && archive {
+        return Err(BackworksError::config("--archive does not apply to the docker target"));
     }
     
-    // Determine output directory
-    let output_dir = output.unwrap_or_else(|| {
-        PathBuf::from("target").join(&target)
-    });
+    let blueprint = std::path::absolute(config::project_config_path(None)?)?;
+    let config = config::load_project_config(Some(blueprint.clone()))?;
+    println!("✅ Configuration is valid");
+    
+    let root = std::env::current_dir()?;
+    let output = std::path::absolute(output.unwrap_or_else(|| PathBuf::from("target").join(&target)))?;
+    if target == "docker" {
+        let binary = match binary {
+            Some(binary) => std::path::absolute(binary)?,
+            None => std::env::current_exe()?,
+        };
+        let context = backworks::docker::build(&root, &blueprint, &config, security.as_deref(), &binary, &output)?;
+        print_build(&context.manifest);
+        for warning in &context.warnings {
+            println!("⚠️  {}", warning);
+        }
+        println!("🐳 Docker build context written to {}", output.display());
+        println!("▶️  Build the image with: docker build -t {} {}", context.manifest.name.to_lowercase().replace(' ', "-"), output.display());
+        return Ok(());
+    }
     
-    println!("📁 Output directory: {}", output_dir.display());
+    let manifest = backworks::build::build(&root, &blueprint, &config, &target, security.as_deref(), &output)?;
+    print_build(&manifest);
+    println!("📦 Build written to {}", output.display());
     
-    // Create output directory
-    std::fs::create_dir_all(&output_dir)
-        .map_err(|e| BackworksError::config(format!("Failed to create output directory: {}", e)))?;
+    if archive {
+        let mut file_name = output.file_name().unwrap_or_default().to_os_string();
+        file_name.push(format!(".{}", backworks::package::EXTENSION));
+        let package = output.with_file_name(file_name);
+        backworks::package::build(&output, &output.join(&manifest.blueprint), &config, &package)?;
+        println!("📦 Package written to {}", package.display());
+        println!("▶️  Run it with: backworks run {}", package.display());
+    }
     
-    // TODO: Implement actual build process
-    // For now, just copy the configuration
-    let config_output = output_dir.join("config.yaml");
-    let config_yaml = serde_yaml::to_string(&config)
-        .map_err(|e| BackworksError::config(format!("Failed to serialize config: {}", e)))?;
-    std::fs::write(&config_output, config_yaml)
-        .map_err(|e| BackworksError::config(format!("Failed to write config: {}", e)))?;
+    Ok(())
+}
+
+fn print_build(manifest: &backworks::build::BuildManifest) {
+    println!("🔒 Security profile: {}", manifest.profile);
+    if let Some(ref environment) = manifest.environment {
+        println!("🌍 Environment: {}", environment);
+    }
+    for setting in &manifest.stripped {
+        println!("  ✂️  stripped {}", setting);
+    }
+    for plugin in &manifest.plugins {
+        println!("  🔌 {} {}", plugin.name, plugin.version.as_deref().unwrap_or("(unversioned)"));
+    }
+    let size: u64 = manifest.files.iter().map(|file| file.size).sum();
+    println!("✅ Built {} file(s), {} bytes", manifest.files.len(), size);
+}
+
+fn build_package(output: Option<PathBuf>) -> Result<()> {
+    let blueprint = std::path::absolute(config::project_config_path(None)?)?;
+    let config = config::load_project_config(Some(blueprint.clone()))?;
+    println!("✅ Configuration loaded successfully");
     
-    println!("✅ Build completed successfully!");
-    println!("📦 Built files available in: {}", output_dir.display());
+    let root = std::env::current_dir()?;
+    let file_name = format!("{}.{}", config.name.to_lowercase().replace(' ', "-"), backworks::package::EXTENSION);
+    let output = std::path::absolute(output.unwrap_or_else(|| PathBuf::from("target").join("package")).join(file_name))?;
+    let manifest = backworks::package::build(&root, &blueprint, &config, &output)?;
     
+    let size: u64 = manifest.files.iter().map(|file| file.size).sum();
+    println!("✅ Packaged {} file(s), {} bytes", manifest.files.len(), size);
+    for plugin in &manifest.plugins {
+        println!("  🔌 requires plugin {}", plugin.name);
+    }
+    println!("📦 Package written to {}", output.display());
+    println!("▶️  Run it with: backworks run {}", output.display());
     Ok(())
 }
 
@@ -555,14 +2360,83 @@ backworks build --target production
     Ok(())
 }
 
-async fn validate_config(config_path: Option<PathBuf>) -> Result<()> {
+async fn validate_config(config_path: Option<PathBuf>, show_merged: bool) -> Result<()> {
     println!("🔍 Validating configuration...");
     
+    // Report how multi-file blueprints were merged
+    if let Some(path) = config_path.clone().or_else(config::find_project_config) {
+        let resolved = backworks::blueprint::resolve(&path)?;
+        if resolved.sources.len() > 1 {
+            println!("📚 Merged {} blueprint files:", resolved.sources.len());
+            for source in &resolved.sources {
+                println!("   - {}", source.display());
+            }
+            println!("📑 Endpoints:");
+            for endpoint in &resolved.endpoints {
+                println!("   {} {} ({})", endpoint.methods.join("|"), endpoint.path, endpoint.source.display());
+            }
+        }
+        match &resolved.environment {
+            Some(env) => println!("🌍 Applied environment '{}'", env),
+            None if !resolved.environments.is_empty() => {
+                println!("🌍 Environments available: {}", resolved.environments.join(", "));
+            }
+            None => {}
+        }
+        if show_merged {
+            println!("{}", serde_yaml::to_string(&resolved.value)?);
+        }
+        
+        let parsed = backworks::diagnostics::parse_resolved(&resolved)?;
+        for warning in &parsed.warnings {
+            println!("⚠️  {}", warning);
+        }
+
+        // Name the files involved when routes from different files collide
+        for conflict in config::route_conflicts(&parsed.config) {
+            let fails = conflict.is_fatal() || parsed.config.route_conflicts == config::RouteConflictMode::Error;
+            let other = if conflict.subject() == conflict.first { &conflict.second } else { &conflict.first };
+            let mut files: Vec<String> = [conflict.subject(), other.as_str()].into_iter()
+                .filter_map(|name| {
+                    let endpoint = parsed.config.endpoints.get(name)?;
+                    resolved.endpoints.iter()
+                        .find(|origin| match origin.name {
+                            Some(ref origin_name) => origin_name == name,
+                            None => origin.path == endpoint.path && origin.methods == endpoint.methods,
+                        })
+                        .map(|origin| origin.source.display().to_string())
+                })
+                .collect();
+            files.dedup();
+            let icon = if fails { "❌" } else { "⚠️ " };
+            match files.as_slice() {
+                [first, second] => println!("{} {} ({} / {})", icon, conflict, first, second),
+                _ => println!("{} {}", icon, conflict),
+            }
+        }
+    }
+    
     // Load configuration
     let config = config::load_project_config(config_path)?;
     
     println!("✅ Configuration loaded successfully");
     
+    // Plugin libraries export the schemas their config is checked against
+    let loader = backworks::plugin::DynamicPluginLoader::new();
+    for (name, plugin) in &config.plugins {
+        let Some(path) = plugin.path.as_ref().filter(|_| plugin.enabled && matches!(plugin.plugin_type, backworks::plugin::PluginType::External)) else {
+            continue;
+        };
+        match loader.load_plugin(path).await {
+            Ok(library) => {
+                if let Some(schema) = backworks::BackworksPlugin::config_schema(&library) {
+                    backworks::plugin::schema::register_config_schema(name, schema)?;
+                }
+            }
+            Err(e) => println!("⚠️  Config of plugin {} not checked: {}", name, e),
+        }
+    }
+    
     // Validate blueprint configuration
     config::validate_config(&config)?;
     println!("✅ Configuration is valid!");
@@ -578,35 +2452,335 @@ fn init_logging(verbose: bool) {
     }
 }
 
-async fn analyze_blueprint(config: Option<PathBuf>, _format: Option<String>, output: Option<PathBuf>) -> Result<()> {
-    println!("🔍 Analyzing blueprint configuration...");
+async fn analyze_blueprint(config: Option<PathBuf>, format: Option<String>, output: Option<PathBuf>, usage: Option<(String, String)>) -> Result<()> {
+    let format: ReportFormat = format.as_deref().unwrap_or("text").parse()?;
+    let config_path = config::project_config_path(config)?;
     
-    // Load configuration
-    let config = config::load_project_config(config)?;
+    let analyzer = BlueprintAnalyzer::new();
+    let mut report = analyzer.analyze_file(&config_path.to_string_lossy()).await?;
+    
+    if let Some((url, window)) = usage {
+        let usage: backworks::usage::UsageReport = admin_client()?
+            .get(format!("{}/_backworks/usage-report", url.trim_end_matches('/')))
+            .query(&[("window", &window)])
+            .send().await?
+            .error_for_status()?
+            .json().await?;
+        analyzer.add_usage(&mut report, &usage);
+    }
     
-    println!("📊 Analysis Results:");
-    println!("   Name: {}", config.name);
-    println!("   Mode: {:?}", config.mode);
-    println!("   Endpoints: {}", config.endpoints.len());
+    match output {
+        Some(output_path) => {
+            std::fs::write(&output_path, analyzer.render(&report, format)?)?;
+            println!("📝 Analysis written to {}", output_path.display());
+        }
+        None if format == ReportFormat::Text => analyzer.print_report(&report),
+        None => println!("{}", analyzer.render(&report, format)?),
+    }
     
-    for (name, endpoint) in &config.endpoints {
-        println!("     - {} ({})", name, endpoint.path);
+    if matches!(report.status, AnalysisStatus::Error) {
+        let errors = report.issues.iter().filter(|issue| issue.severity == IssueSeverity::Error).count();
+        return Err(BackworksError::config(format!("Blueprint analysis found {} error(s)", errors)));
     }
+    Ok(())
+}
+
+async fn detect_drift(baseline: PathBuf, current: PathBuf, format: String, output: Option<PathBuf>, fail_on: String) -> Result<()> {
+    let report = backworks::drift::compare_files(&baseline, &current).await?;
     
-    if !config.plugins.is_empty() {
-        println!("   Plugins: {}", config.plugins.len());
-        for (name, plugin_config) in &config.plugins {
-            if plugin_config.enabled {
-                println!("     - {} (enabled)", name);
-            }
+    let rendered = match format.as_str() {
+        "text" => report.render_text(),
+        "json" => serde_json::to_string_pretty(&report)?,
+        other => return Err(BackworksError::config(format!("Unsupported drift report format '{}' (expected text or json)", other))),
+    };
+    match output {
+        Some(path) => {
+            std::fs::write(&path, rendered)?;
+            println!("📝 Drift report written to {}", path.display());
+        }
+        None => print!("{}", rendered),
+    }
+    
+    let failed = match fail_on.as_str() {
+        "any" => report.has_drift(),
+        "breaking" => report.has_breaking_changes(),
+        _ => false,
+    };
+    if failed {
+        return Err(BackworksError::config(format!("API drift from {} to {}", report.baseline, report.current)));
+    }
+    Ok(())
+}
+
+struct ReplayArgs {
+    pacing: backworks::replay::Pacing,
+    headers: Vec<String>,
+    drop_headers: Vec<String>,
+    timeout: String,
+}
+
+async fn replay_session(session: PathBuf, target: String, args: ReplayArgs, format: String, output: Option<PathBuf>, fail_on: String) -> Result<()> {
+    use backworks::replay::{load_session, Replayer};
+    
+    if !["text", "json"].contains(&format.as_str()) {
+        return Err(BackworksError::config(format!("Unsupported replay report format '{}' (expected text or json)", format)));
+    }
+    let exchanges = load_session(&session).await?;
+    let replayer = Replayer::new(&target, config::parse_duration(&args.timeout)?)?
+        .with_pacing(args.pacing)
+        .with_headers(parse_pairs(&args.headers, ':', "Header", "Name: value")?)
+        .with_dropped_headers(args.drop_headers);
+    if format == "text" {
+        println!("🔁 Replaying {} request(s) from {} against {}", exchanges.len(), session.display(), target);
+    }
+    let report = replayer.run(exchanges).await?;
+    
+    let rendered = match format.as_str() {
+        "json" => serde_json::to_string_pretty(&report)?,
+        _ => report.render_text(),
+    };
+    match output {
+        Some(path) => {
+            std::fs::write(&path, rendered)?;
+            println!("📝 Replay report written to {}", path.display());
+        }
+        None => print!("{}", rendered),
+    }
+    
+    let failed = match fail_on.as_str() {
+        "any" => report.differences(),
+        "status" => report.regressions(),
+        _ => 0,
+    };
+    if failed > 0 {
+        return Err(BackworksError::config(format!("{} replayed request(s) were answered differently by {}", failed, report.target)));
+    }
+    Ok(())
+}
+
+struct BenchArgs {
+    concurrency: usize,
+    duration: String,
+    requests: Option<u64>,
+    method: Option<String>,
+    headers: Vec<String>,
+    body: Option<String>,
+    params: Vec<String>,
+    timeout: String,
+}
+
+async fn run_bench(endpoint: String, url: Option<String>, config_path: Option<PathBuf>, args: BenchArgs, format: String) -> Result<()> {
+    use backworks::bench::{progress_line, Bench, BenchTarget};
+    
+    if !["text", "json"].contains(&format.as_str()) {
+        return Err(BackworksError::config(format!("Unsupported load test report format '{}' (expected text or json)", format)));
+    }
+    // The blueprint names endpoints and the server's address
+    let config = match config_path.or_else(config::find_project_config) {
+        Some(path) => Some(config::load_project_config(Some(path))?),
+        None => None,
+    };
+    let base = url.unwrap_or_else(|| match config {
+        Some(ref config) => {
+            let scheme = if config.server.tls.is_some() { "https" } else { "http" };
+            format!("{}://localhost:{}", scheme, config.server.port)
+        }
+        None => "http://localhost:8080".to_string(),
+    });
+    let headers = parse_pairs(&args.headers, ':', "Header", "Name: value")?;
+    let params = parse_pairs(&args.params, '=', "Parameter", "name=value")?.into_iter().collect();
+    let body = match args.body {
+        Some(body) => match body.strip_prefix('@') {
+            Some(file) => Some(std::fs::read_to_string(file)?),
+            None => Some(body),
+        },
+        None => None,
+    };
+    
+    let target = BenchTarget::resolve(&endpoint, config.as_ref(), &base, args.method.as_deref(), &params)?;
+    let bench = Bench::new(target.clone())
+        .with_headers(headers)
+        .with_body(body)
+        .with_concurrency(args.concurrency)
+        .with_duration(config::parse_duration(&args.duration)?)
+        .with_requests(args.requests)
+        .with_timeout(config::parse_duration(&args.timeout)?);
+    
+    let text = format == "text";
+    if text {
+        println!("🔥 Load testing {} {} with {} workers for {}", target.method, target.url, args.concurrency.max(1), args.duration);
+    }
+    let report = bench.run(|snapshot, elapsed| if text { println!("{}", progress_line(snapshot, elapsed)) }).await?;
+    match format.as_str() {
+        "json" => println!("{}", serde_json::to_string_pretty(&report)?),
+        _ => print!("\n{}", report.render_text()),
+    }
+    Ok(())
+}
+
+/// Split each of `values` at the first `separator`, trimming both halves
+fn parse_pairs(values: &[String], separator: char, what: &str, form: &str) -> Result<Vec<(String, String)>> {
+    values.iter()
+        .map(|value| value.split_once(separator)
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .ok_or_else(|| BackworksError::config(format!("{} '{}' must be given as `{}`", what, value, form))))
+        .collect()
+}
+
+struct VerifyArgs {
+    headers: Vec<String>,
+    params: Vec<String>,
+    compare_headers: Vec<String>,
+    writes: bool,
+    timeout: String,
+}
+
+async fn verify_contract(against: String, blueprint: Option<Option<PathBuf>>, captures: Vec<PathBuf>, endpoint: Option<String>, args: VerifyArgs, format: String, output: Option<PathBuf>) -> Result<()> {
+    use backworks::contract::{load_capture, Verifier};
+    
+    let headers = parse_pairs(&args.headers, ':', "Header", "Name: value")?;
+    let params = parse_pairs(&args.params, '=', "Parameter", "name=value")?.into_iter().collect();
+    let verifier = Verifier::new(&against, config::parse_duration(&args.timeout)?)?
+        .with_headers(headers)
+        .with_compared_headers(args.compare_headers)
+        .with_params(params)
+        .with_endpoint(endpoint)
+        .with_writes(args.writes);
+    
+    let config = match blueprint {
+        Some(path) => Some(config::load_project_config(Some(config::project_config_path(path)?))?),
+        None => None,
+    };
+    let mut recorded = Vec::new();
+    for capture in &captures {
+        recorded.extend(load_capture(capture).await?);
+    }
+    let report = verifier.run(config.as_ref(), recorded).await?;
+    
+    let rendered = match format.as_str() {
+        "text" => report.render_text(),
+        "json" => serde_json::to_string_pretty(&report)?,
+        other => return Err(BackworksError::config(format!("Unsupported verification report format '{}' (expected text or json)", other))),
+    };
+    match output {
+        Some(path) => {
+            std::fs::write(&path, rendered)?;
+            println!("📝 Verification report written to {}", path.display());
+        }
+        None => print!("{}", rendered),
+    }
+    
+    if !report.passed() {
+        return Err(BackworksError::config(format!("Responses of {} do not match", report.against)));
+    }
+    Ok(())
+}
+
+async fn write_docs(config_path: Option<PathBuf>, captures: Vec<PathBuf>, output: PathBuf) -> Result<()> {
+    use backworks::docs::ApiDocs;
+    
+    let config = config::load_project_config(Some(config::project_config_path(config_path)?))?;
+    let mocks = backworks::mock::MockEngine::new(&config.endpoints)?;
+    let docs = ApiDocs::load(&config, &captures, &mocks, &backworks::state::StateStore::new()).await?;
+    docs.write_site(&output)?;
+    
+    let examples: usize = docs.endpoints.iter().map(|endpoint| endpoint.examples.len()).sum();
+    println!("📚 API reference for {} endpoint(s), with {} example(s), written to {}", docs.endpoints.len(), examples, output.display());
+    println!("▶️  Open {}", output.join("index.html").display());
+    Ok(())
+}
+
+async fn write_changelog(blueprints: Vec<PathBuf>, git: Option<String>, config_path: Option<PathBuf>, format: String, output: Option<PathBuf>) -> Result<()> {
+    use backworks::changelog::{blueprint_at, Changelog, GitRange};
+    use backworks::drift::ApiSnapshot;
+    
+    let blueprint = |path: PathBuf, label: String| async move {
+        let config = config::load_yaml_config(&path).await?;
+        Ok::<_, BackworksError>((label, ApiSnapshot::from_blueprint(&path.display().to_string(), &config)))
+    };
+    let ((from, baseline), (to, current)) = match git {
+        Some(range) => {
+            let range: GitRange = range.parse()?;
+            let path = config::project_config_path(config_path)?;
+            let baseline = (range.from.clone(), blueprint_at(&path, &range.from).await?);
+            let current = match range.to {
+                Some(to) => (to.clone(), blueprint_at(&path, &to).await?),
+                None => blueprint(path, "working tree".to_string()).await?,
+            };
+            (baseline, current)
+        }
+        None => {
+            let mut files = blueprints.into_iter();
+            let (baseline, current) = (files.next().unwrap_or_default(), files.next().unwrap_or_default());
+            (blueprint(baseline.clone(), baseline.display().to_string()).await?, blueprint(current.clone(), current.display().to_string()).await?)
+        }
+    };
+    
+    let changelog = Changelog::between(&from, &baseline, &to, &current);
+    let rendered = match format.as_str() {
+        "markdown" | "md" => changelog.render_markdown(),
+        "json" => serde_json::to_string_pretty(&changelog)?,
+        other => return Err(BackworksError::config(format!("Unsupported changelog format '{}' (expected markdown or json)", other))),
+    };
+    match output {
+        Some(path) => {
+            std::fs::write(&path, rendered)?;
+            println!("📝 Changelog written to {}", path.display());
         }
+        None => print!("{}", rendered),
     }
+    Ok(())
+}
+
+fn upgrade_blueprints(config: Option<PathBuf>, from: Option<String>, write: bool) -> Result<()> {
+    use backworks::upgrade::{upgrade_project, ChangeKind};
+    
+    let config_path = config::project_config_path(config)?;
+    println!("🔧 Checking blueprints for deprecated constructs...");
     
-    if let Some(output_path) = output {
-        println!("📝 Writing analysis to {}", output_path.display());
-        // TODO: Implement analysis output
+    let upgrades = upgrade_project(&config_path, from.as_deref())?;
+    let mut rewritten = 0;
+    let mut manual = 0;
+    for upgrade in &upgrades {
+        if upgrade.migrations.is_empty() {
+            continue;
+        }
+        println!("\n📄 {}", upgrade.path.display());
+        for migration in &upgrade.migrations {
+            println!("   [{}] {}", migration.since, migration.title);
+            for change in &migration.changes {
+                let marker = match change.kind {
+                    ChangeKind::Rewritten => "✅",
+                    ChangeKind::Manual => "✋",
+                };
+                println!("      {} {}: {}", marker, change.path, change.message);
+            }
+        }
+        rewritten += upgrade.count(ChangeKind::Rewritten);
+        manual += upgrade.count(ChangeKind::Manual);
+        
+        if let (true, Some(upgraded)) = (write, &upgrade.upgraded) {
+            let mut backup = upgrade.path.clone().into_os_string();
+            backup.push(".bak");
+            std::fs::copy(&upgrade.path, &backup)?;
+            std::fs::write(&upgrade.path, upgraded)?;
+            println!("   💾 Rewrote the file; the original is at {}", PathBuf::from(backup).display());
+        }
     }
     
+    if rewritten == 0 && manual == 0 {
+        println!("✅ Blueprints are up to date");
+        return Ok(());
+    }
+    println!();
+    match (rewritten, write) {
+        (0, _) => {}
+        (count, true) => println!("✅ Applied {} rewrite(s)", count),
+        (count, false) => println!("📝 {} rewrite(s) available; rerun with --write to apply them", count),
+    }
+    if manual > 0 {
+        println!("✋ {} change(s) need to be made by hand", manual);
+    }
     Ok(())
 }
 
@@ -626,14 +2800,426 @@ async fn start_capture_mode(port: u16, output: PathBuf, duration: Option<u64>) -
     Ok(())
 }
 
-async fn generate_config(input: PathBuf, output: PathBuf) -> Result<()> {
+async fn generate_config(input: PathBuf, output: PathBuf, capture: Option<PathBuf>) -> Result<()> {
+    use backworks::capture::CapturedRequest;
+    
     println!("🔧 Generating configuration from captured data...");
     println!("📥 Input: {}", input.display());
     println!("📤 Output: {}", output.display());
     
-    // TODO: Implement config generation from captured data
-    println!("⚠️  Config generation not yet implemented");
+    let content = std::fs::read_to_string(&input)
+        .map_err(|e| BackworksError::config(format!("Failed to read {}: {}", input.display(), e)))?;
+    let name = input.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_else(|| "captured_api".to_string());
+    let is_har = input.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("har"));
+    
+    let requests = if is_har {
+        let import = backworks::har::import(&content, &name)?;
+        println!("✅ Imported {} API call(s) from the HAR file ({} other entries skipped)", import.requests.len(), import.skipped);
+        if let Some(path) = capture {
+            let export = serde_json::json!({ "session": import.session, "requests": import.requests });
+            std::fs::write(&path, serde_json::to_string_pretty(&export)?)
+                .map_err(|e| BackworksError::config(format!("Failed to write {}: {}", path.display(), e)))?;
+            println!("💾 Capture session saved to {}", path.display());
+        }
+        import.requests
+    } else {
+        let export: serde_json::Value = serde_json::from_str(&content)
+            .map_err(|e| BackworksError::config(format!("{} is not a capture export: {}", input.display(), e)))?;
+        serde_json::from_value::<Vec<CapturedRequest>>(export.get("requests").cloned().unwrap_or_default())
+            .map_err(|e| BackworksError::config(format!("{} is not a capture export: {}", input.display(), e)))?
+    };
+    if requests.is_empty() {
+        return Err(BackworksError::config(format!("No API requests found in {}", input.display())));
+    }
+    
+    let blueprint = backworks::capture::blueprint_from_requests(&name, &requests)?;
+    std::fs::write(&output, blueprint)
+        .map_err(|e| BackworksError::config(format!("Failed to write {}: {}", output.display(), e)))?;
+    println!("✅ Blueprint generated from {} request(s)", requests.len());
+    println!("▶️  Start it with: backworks start --config {}", output.display());
+    
+    Ok(())
+}
+
+fn manage_secrets(action: SecretsAction) -> Result<()> {
+    use backworks::secrets::{SecretKey, DEFAULT_KEY_FILE, KEYRING_VAR};
+    
+    let key = |key_file: Option<PathBuf>| -> Result<SecretKey> {
+        match key_file {
+            Some(path) => SecretKey::load_file(&path),
+            None => SecretKey::from_environment()?.ok_or_else(|| BackworksError::config(
+                "No secret key found; run `backworks secrets init`, set BACKWORKS_SECRET_KEY or name a keyring entry in BACKWORKS_SECRET_KEYRING"
+            )),
+        }
+    };
+    let input = |value: Option<String>| -> Result<String> {
+        match value {
+            Some(value) => Ok(value),
+            None => {
+                let mut value = String::new();
+                std::io::Read::read_to_string(&mut std::io::stdin(), &mut value)?;
+                Ok(value.trim_end_matches(['\r', '\n']).to_string())
+            }
+        }
+    };
+    
+    match action {
+        SecretsAction::Init { force, keyring: Some(entry) } => {
+            if !force && SecretKey::load_keyring(&entry).is_ok() {
+                return Err(BackworksError::config(format!(
+                    "The OS keyring already has a key named '{}'; pass --force to replace it", entry
+                )));
+            }
+            SecretKey::generate().store_keyring(&entry)?;
+            println!("🔑 Stored project key in the OS keyring as '{}'", entry);
+            println!("   Set {}={} wherever the blueprint is loaded", KEYRING_VAR, entry);
+        }
+        SecretsAction::Init { force, keyring: None } => {
+            let path = PathBuf::from(DEFAULT_KEY_FILE);
+            if path.exists() && !force {
+                return Err(BackworksError::config(format!(
+                    "{} already exists; pass --force to replace it", path.display()
+                )));
+            }
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(&path, SecretKey::generate().to_base64())?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+            }
+            println!("🔑 Wrote project key to {}", path.display());
+            println!("   Keep it out of version control; add `.backworks/secret.key` to .gitignore");
+        }
+        SecretsAction::Encrypt { value, key_file } => {
+            let key = key(key_file)?;
+            println!("{}", key.encrypt(&input(value)?)?);
+        }
+        SecretsAction::Decrypt { value, key_file } => {
+            let key = key(key_file)?;
+            println!("{}", key.decrypt(input(value)?.trim())?);
+        }
+    }
+    
+    Ok(())
+}
+
+async fn manage_plugins(action: PluginAction) -> Result<()> {
+    use backworks::plugin::registry::{Installed, Project, Registry};
+    
+    let mut project = Project::open(&std::env::current_dir()?)?;
+    let registry = |index: Option<String>| {
+        let config = project.registry_config(index);
+        let dir = project.dir().to_path_buf();
+        async move { Registry::open(&config?, &dir).await }
+    };
+    let report = |installed: &Installed| {
+        let verified = if installed.signed { "signature and checksum verified" } else { "checksum verified, unsigned" };
+        println!("📦 Installed {} {} to {} ({})", installed.name, installed.version, installed.path.display(), verified);
+    };
+    
+    match action {
+        PluginAction::Search { query, registry: index } => {
+            let registry = registry(index).await?;
+            let found = registry.search(query.as_deref().unwrap_or(""));
+            for (name, plugin) in &found {
+                let latest = registry.latest(name).map(|version| version.version.as_str()).unwrap_or("no build for this platform");
+                println!("{} ({})  {}", name, latest, plugin.description);
+            }
+            println!("🔎 {} plugin(s)", found.len());
+        }
+        PluginAction::Install { plugin, registry: index } => {
+            let registry = registry(index).await?;
+            let (name, version) = match plugin.split_once('@') {
+                Some((name, version)) => (name, Some(version)),
+                None => (plugin.as_str(), None),
+            };
+            let installed = project.install(&registry, name, version).await?;
+            report(&installed);
+        }
+        PluginAction::Update { plugin, registry: index } => {
+            let registry = registry(index).await?;
+            let mut installed = project.installed();
+            if let Some(plugin) = plugin {
+                let version = installed.remove(&plugin)
+                    .ok_or_else(|| BackworksError::config(format!("Plugin {} is not installed", plugin)))?;
+                installed = [(plugin, version)].into();
+            }
+            let mut updated = 0;
+            for (name, current) in installed {
+                let latest = registry.latest(&name)?.version.clone();
+                if latest.trim_start_matches('v') == current.trim_start_matches('v') {
+                    println!("✅ {} {} is up to date", name, current);
+                    continue;
+                }
+                report(&project.install(&registry, &name, Some(&latest)).await?);
+                updated += 1;
+            }
+            println!("🔄 {} plugin(s) updated", updated);
+        }
+        PluginAction::Remove { plugin } => {
+            let path = project.remove(&plugin)?;
+            println!("🗑️ Removed {} ({})", plugin, path.display());
+        }
+    }
+    
+    Ok(())
+}
+
+async fn manage_journal(action: JournalAction) -> Result<()> {
+    let (JournalAction::Show { ref file, ref endpoint } | JournalAction::Replay { ref file, ref endpoint, .. }) = action;
+    let mut entries = backworks::journal::load(file).await?;
+    if let Some(endpoint) = endpoint {
+        entries.retain(|entry| &entry.endpoint == endpoint);
+    }
+    
+    match action {
+        JournalAction::Show { .. } => {
+            for entry in &entries {
+                let who = entry.auth.as_ref().map(|auth| format!(" ({})", auth.subject)).unwrap_or_default();
+                println!("{}  {} {} -> {}{}", entry.timestamp.to_rfc3339(), entry.method, entry.uri, entry.endpoint, who);
+            }
+            println!("📒 {} request(s)", entries.len());
+        }
+        JournalAction::Replay { url, .. } => {
+            let response = admin_client()?
+                .post(format!("{}/_backworks/journal/replay", url.trim_end_matches('/')))
+                .json(&entries)
+                .send().await?
+                .error_for_status()?;
+            let summary: backworks::journal::ReplaySummary = response.json().await?;
+            println!("✅ Replayed {} request(s) ({} failed, {} duplicate)", summary.replayed, summary.failed, summary.duplicates);
+        }
+    }
+    
+    Ok(())
+}
+
+/// Client for the admin API, sending the token in `BACKWORKS_ADMIN_TOKEN`
+/// when it is set
+fn admin_client() -> Result<reqwest::Client> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Some(token) = std::env::var(backworks::admin::ADMIN_TOKEN_VAR).ok().filter(|token| !token.is_empty()) {
+        let mut value = reqwest::header::HeaderValue::from_str(&format!("Bearer {}", token))
+            .map_err(|_| BackworksError::config(format!("{} is not a valid header value", backworks::admin::ADMIN_TOKEN_VAR)))?;
+        value.set_sensitive(true);
+        headers.insert(reqwest::header::AUTHORIZATION, value);
+    }
+    Ok(reqwest::Client::builder().default_headers(headers).build()?)
+}
+
+async fn manage_database(action: DbAction) -> Result<()> {
+    match action {
+        DbAction::Seed { fixtures, config, plugin, idempotent, env } => {
+            select_environment(env);
+            let config = config::load_project_config(config)?;
+            let fixtures = backworks::seed::Fixture::load_all(&fixtures)?;
+            if fixtures.is_empty() {
+                println!("ℹ️  No fixture files to seed");
+                return Ok(());
+            }
+            
+            let plugins = backworks::engine::load_plugins(&config).await?;
+            let database = plugins.database(plugin.as_deref()).await?;
+            if let Some(settings) = backworks::pool::settings(&config, database.plugin())? {
+                database.connect(&settings).await?;
+            }
+            println!("🌱 Seeding {} table(s) through plugin {}...", fixtures.len(), database.plugin());
+            let seeded = backworks::seed::seed(&database, &fixtures, idempotent).await;
+            plugins.shutdown_all().await?;
+            
+            for (table, counts) in &seeded?.tables {
+                if idempotent {
+                    println!("   {}: {} inserted, {} already present", table, counts.inserted, counts.existing);
+                } else {
+                    println!("   {}: {} inserted", table, counts.inserted);
+                }
+            }
+            println!("✅ Seed data loaded");
+        }
+    }
+    Ok(())
+}
+
+async fn manage_state(action: StateAction) -> Result<()> {
+    let client = admin_client()?;
+    
+    match action {
+        StateAction::Export { url, namespace, output } => {
+            let mut request = client.get(format!("{}/_backworks/state/export", url.trim_end_matches('/')));
+            if let Some(ref namespace) = namespace {
+                request = request.query(&[("namespace", namespace)]);
+            }
+            let response = request.send().await?.error_for_status()?;
+            let snapshot: backworks::state::StateSnapshot = response.json().await?;
+            let json = serde_json::to_string_pretty(&snapshot)?;
+            
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, json)?;
+                    println!("✅ Exported {} key(s) to {}", snapshot.key_count(), path.display());
+                }
+                None => println!("{}", json),
+            }
+        }
+        StateAction::Import { file, url, namespace, mode } => {
+            if mode != "merge" && mode != "replace" {
+                return Err(BackworksError::config(format!("Invalid import mode '{}' (expected merge or replace)", mode)));
+            }
+            let snapshot = backworks::state::StateSnapshot::load(&file).await?;
+            
+            let mut query = vec![("mode", mode)];
+            if let Some(namespace) = namespace {
+                query.push(("namespace", namespace));
+            }
+            let response = client.post(format!("{}/_backworks/state/import", url.trim_end_matches('/')))
+                .query(&query)
+                .json(&snapshot)
+                .send().await?
+                .error_for_status()?;
+            let summary: backworks::state::ImportSummary = response.json().await?;
+            println!("✅ Imported {} key(s) into {} namespace(s)", summary.keys, summary.namespaces);
+        }
+    }
+    
+    Ok(())
+}
+
+async fn purge_data(endpoint: Option<String>, older_than: Option<String>, url: String) -> Result<()> {
+    use backworks::retention::{PurgeRequest, PurgeSummary};
+    
+    let request = PurgeRequest { endpoint, older_than };
+    let response = admin_client()?
+        .post(format!("{}/_backworks/purge", url.trim_end_matches('/')))
+        .json(&request)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        return Err(BackworksError::config(format!("{} ({})", body["error"].as_str().unwrap_or("purge failed"), status)));
+    }
+    let summary: PurgeSummary = response.json().await?;
+    let scope = match request.older_than {
+        Some(ref older_than) => format!(" older than {}", older_than),
+        None => String::new(),
+    };
+    println!("🧹 Purged data{} of {} endpoint(s):", scope, summary.endpoints.len());
+    println!("   {} mock session collection(s), {} mock item(s)", summary.mock_collections, summary.mock_items);
+    println!("   {} captured request(s)", summary.captured_requests);
+    println!("   {} journal entries", summary.journal_entries);
+    Ok(())
+}
+
+async fn run_console(url: String, execute: Vec<String>) -> Result<()> {
+    use backworks::console::{Command, Console};
+    use tokio::io::AsyncBufReadExt;
+    
+    let console = Console::new(admin_client()?, &url);
+    if !execute.is_empty() {
+        for line in &execute {
+            if let Some(command) = Command::parse(line)? {
+                run_console_command(&console, command).await?;
+            }
+        }
+        return Ok(());
+    }
+    
+    println!("🔌 Connected to {}; type help for commands, exit or Ctrl-D to leave", url.trim_end_matches('/'));
+    let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        print!("backworks> ");
+        std::io::Write::flush(&mut std::io::stdout())?;
+        let Some(line) = lines.next_line().await? else {
+            println!();
+            break;
+        };
+        let command = match Command::parse(&line) {
+            Ok(Some(Command::Exit)) => break,
+            Ok(Some(command)) => command,
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = run_console_command(&console, command).await {
+            eprintln!("❌ {}", e);
+        }
+    }
+    Ok(())
+}
+
+async fn run_console_command(console: &backworks::console::Console, command: backworks::console::Command) -> Result<()> {
+    if let backworks::console::Command::Logs { ref filters, follow: true } = command {
+        println!("📜 Following requests, Ctrl-C to stop");
+        tokio::select! {
+            result = console.follow(filters, |line| println!("{}", line)) => result?,
+            _ = tokio::signal::ctrl_c() => {}
+        }
+        return Ok(());
+    }
+    let output = console.execute(&command).await?;
+    if !output.is_empty() {
+        println!("{}", output);
+    }
+    Ok(())
+}
+
+async fn manage_targets(action: TargetsAction) -> Result<()> {
+    use backworks::targets::{TargetCommand, TargetOperation, TargetState, TargetsReport};
+    
+    let (url, request) = match action {
+        TargetsAction::List { url } => (url, None),
+        TargetsAction::Drain { endpoint, target, timeout, reason, url } => {
+            (url, Some((endpoint, TargetOperation::Drain, TargetCommand { target, duration: Some(timeout), reason })))
+        }
+        TargetsAction::Quarantine { endpoint, target, period, reason, url } => {
+            (url, Some((endpoint, TargetOperation::Quarantine, TargetCommand { target, duration: Some(period), reason })))
+        }
+        TargetsAction::Enable { endpoint, target, reason, url } => {
+            (url, Some((endpoint, TargetOperation::Enable, TargetCommand { target, duration: None, reason })))
+        }
+    };
+    let base = format!("{}/_backworks/proxy/targets", url.trim_end_matches('/'));
+    let client = admin_client()?;
+    let response = match request {
+        Some((ref endpoint, operation, ref command)) => {
+            client.post(format!("{}/{}/{}", base, endpoint, operation)).json(command).send().await?
+        }
+        None => client.get(&base).send().await?,
+    };
+    if !response.status().is_success() {
+        let status = response.status();
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        return Err(BackworksError::config(format!("{} ({})", body["error"].as_str().unwrap_or("target operation failed"), status)));
+    }
+    let report: TargetsReport = response.json().await?;
     
+    if let Some((endpoint, _, command)) = request {
+        let target = command.target.trim_end_matches('/');
+        if let Some(status) = report.targets.iter().find(|t| t.endpoint == endpoint && t.target == target) {
+            match status.state {
+                TargetState::Draining => println!("⏳ {} is still draining: {} request(s) in flight", target, status.in_flight),
+                state => println!("✅ {} of '{}' is {}", target, endpoint, state),
+            }
+        }
+        return Ok(());
+    }
+    for status in &report.targets {
+        let until = status.quarantined_until.map(|until| format!(" until {}", until.to_rfc3339())).unwrap_or_default();
+        println!("{:<20} {:<40} {:<12} {:>4} in flight {:>8} requests{}",
+            status.endpoint, status.target, status.state.to_string(), status.in_flight, status.requests, until);
+    }
+    if !report.audit.is_empty() {
+        println!("\nRecent actions:");
+        for action in &report.audit {
+            let reason = action.reason.as_deref().map(|reason| format!(" ({})", reason)).unwrap_or_default();
+            println!("  {} {} {} of '{}'{}", action.at.to_rfc3339(), action.operation, action.target, action.endpoint, reason);
+        }
+    }
     Ok(())
 }
 
@@ -672,3 +3258,56 @@ function handler(req, res) {{
 module.exports = handler;
 "#, name)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handler_types_declare_the_handler_api() {
+        let files = create_handler_types();
+        let paths: Vec<&str> = files.iter().map(|(path, _)| *path).collect();
+        assert_eq!(paths, ["types/backworks.d.ts", "jsconfig.json"]);
+
+        let declarations = &files[0].1;
+        assert!(declarations.contains("declare namespace Backworks {"));
+        for name in ["Request", "Context", "Response", "JobRequest", "ScheduleRun", "FileDownload"] {
+            assert!(declarations.contains(&format!("  interface {} {{", name)), "{}", name);
+        }
+        assert!(declarations.contains("  type Result = Response | ErrorReference | FileDownload | JsonValue;"));
+
+        let jsconfig: serde_json::Value = serde_json::from_str(&files[1].1).unwrap();
+        assert_eq!(jsconfig["include"], serde_json::json!(["**/*.js", "types/**/*.d.ts"]));
+    }
+
+    #[test]
+    fn test_template_handlers_are_annotated() {
+        let handler = "// Echo\nfunction handler(req, ctx) {\n  return req.body;\n}\n";
+        assert_eq!(
+            annotate_handler("handlers/echo.js", handler.to_string()),
+            "// Echo\n/**\n * @param {Backworks.Request} req\n * @param {Backworks.Context} ctx\n * @returns {Backworks.Result}\n */\nfunction handler(req, ctx) {\n  return req.body;\n}\n"
+        );
+
+        let job = "\nfunction handler(job) {}\n";
+        assert_eq!(annotate_handler("jobs/cleanup.js", job.to_string()), "\n/**\n * @param {Backworks.JobRequest} job\n */\nfunction handler(job) {}\n");
+        let schedule = "\nfunction handler(run) {}\n";
+        assert_eq!(annotate_handler("jobs/stale.js", schedule.to_string()), "\n/**\n * @param {Backworks.ScheduleRun} run\n */\nfunction handler(run) {}\n");
+
+        // Files without a handler, or a handler without arguments, are kept
+        assert_eq!(annotate_handler("handlers/util.js", "module.exports = {};\n".to_string()), "module.exports = {};\n");
+        assert_eq!(annotate_handler("jobs/noop.js", "\nfunction handler() {}\n".to_string()), "\nfunction handler() {}\n");
+
+        // Every template's handlers only name declared types
+        let declarations = &create_handler_types()[0].1;
+        for template in ["basic", "graphql", "database-crud", "event-driven", "proxy-gateway"] {
+            for (path, content) in create_project_files("shop", template) {
+                let annotated = annotate_handler(path, content);
+                for kind in annotated.split("{Backworks.").skip(1).map(|rest| rest.split('}').next().unwrap()) {
+                    let declared = declarations.contains(&format!("interface {} {{", kind))
+                        || declarations.contains(&format!("type {} =", kind));
+                    assert!(declared, "{} in {}", kind, path);
+                }
+            }
+        }
+    }
+}
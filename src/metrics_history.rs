@@ -0,0 +1,278 @@
+//! Request metrics over time, for the dashboard's charts
+//!
+//! Requests are counted into time buckets at three resolutions: a minute for
+//! the last two hours, five minutes for the last day and an hour for the
+//! last week. Each bucket keeps request and error counts and a latency
+//! histogram, so any range reports p50, p95 and p99 latencies within 2%.
+//! Older buckets are dropped, which bounds the memory the history needs by
+//! the number of endpoints.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Relative width of a latency histogram bucket
+const LATENCY_GROWTH: f64 = 1.02;
+
+/// A bucket width and how many buckets of it are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    #[serde(rename = "1m")]
+    Minute,
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "1h")]
+    Hour,
+}
+
+impl Resolution {
+    const ALL: [Resolution; 3] = [Resolution::Minute, Resolution::FiveMinutes, Resolution::Hour];
+
+    pub fn width(self) -> Duration {
+        match self {
+            Self::Minute => Duration::from_secs(60),
+            Self::FiveMinutes => Duration::from_secs(5 * 60),
+            Self::Hour => Duration::from_secs(60 * 60),
+        }
+    }
+
+    /// How far back buckets of this width go
+    pub fn retention(self) -> Duration {
+        match self {
+            Self::Minute => Duration::from_secs(2 * 60 * 60),
+            Self::FiveMinutes => Duration::from_secs(24 * 60 * 60),
+            Self::Hour => Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+
+    /// The finest resolution that covers `range`
+    pub fn for_range(range: Duration) -> Option<Self> {
+        Self::ALL.into_iter().find(|resolution| resolution.retention() >= range)
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Self::Minute => 0,
+            Self::FiveMinutes => 1,
+            Self::Hour => 2,
+        }
+    }
+}
+
+/// Latencies counted into buckets 2% apart
+#[derive(Debug, Clone, Default)]
+struct LatencyHistogram {
+    counts: BTreeMap<i32, u64>,
+    total: u64,
+}
+
+impl LatencyHistogram {
+    fn record(&mut self, latency_ms: f64) {
+        // Bucket by microsecond, so sub-millisecond latencies keep apart
+        let micros = (latency_ms * 1000.0).max(1.0);
+        let bucket = micros.log(LATENCY_GROWTH).ceil() as i32;
+        *self.counts.entry(bucket).or_default() += 1;
+        self.total += 1;
+    }
+
+    fn merge(&mut self, other: &LatencyHistogram) {
+        for (bucket, count) in &other.counts {
+            *self.counts.entry(*bucket).or_default() += count;
+        }
+        self.total += other.total;
+    }
+
+    /// Nearest-rank percentile, as the upper bound of its bucket
+    fn percentile(&self, quantile: f64) -> f64 {
+        let rank = ((quantile * self.total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in &self.counts {
+            seen += count;
+            if seen >= rank {
+                return LATENCY_GROWTH.powi(*bucket) / 1000.0;
+            }
+        }
+        0.0
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Counts {
+    requests: u64,
+    errors: u64,
+    latency_sum_ms: f64,
+    latencies: LatencyHistogram,
+}
+
+impl Counts {
+    fn record(&mut self, status: u16, latency_ms: f64) {
+        self.requests += 1;
+        if status >= 400 {
+            self.errors += 1;
+        }
+        self.latency_sum_ms += latency_ms;
+        self.latencies.record(latency_ms);
+    }
+
+    fn merge(&mut self, other: &Counts) {
+        self.requests += other.requests;
+        self.errors += other.errors;
+        self.latency_sum_ms += other.latency_sum_ms;
+        self.latencies.merge(&other.latencies);
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    start: i64,
+    /// By endpoint key (`GET /users`)
+    endpoints: HashMap<String, Counts>,
+}
+
+/// One bucket of a history, all endpoints or one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricsPoint {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub requests: u64,
+    pub errors: u64,
+    /// Fraction of requests answered with a 4xx or 5xx status (0.0 - 1.0)
+    pub error_rate: f64,
+    pub avg_latency_ms: f64,
+    pub p50_latency_ms: f64,
+    pub p95_latency_ms: f64,
+    pub p99_latency_ms: f64,
+}
+
+impl MetricsPoint {
+    fn new(start: i64, counts: &Counts) -> Self {
+        let rate = |count: u64| if counts.requests == 0 { 0.0 } else { count as f64 / counts.requests as f64 };
+        Self {
+            timestamp: chrono::DateTime::from_timestamp(start, 0).unwrap_or_default(),
+            requests: counts.requests,
+            errors: counts.errors,
+            error_rate: rate(counts.errors),
+            avg_latency_ms: if counts.requests == 0 { 0.0 } else { counts.latency_sum_ms / counts.requests as f64 },
+            p50_latency_ms: counts.latencies.percentile(0.50),
+            p95_latency_ms: counts.latencies.percentile(0.95),
+            p99_latency_ms: counts.latencies.percentile(0.99),
+        }
+    }
+}
+
+/// Points covering a range, oldest first; buckets without requests are
+/// included so charts have no gaps
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsHistoryReport {
+    pub resolution: Resolution,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    pub points: Vec<MetricsPoint>,
+}
+
+/// Bucketed request metrics, shared between clones
+#[derive(Debug, Clone, Default)]
+pub struct MetricsHistory {
+    series: Arc<Mutex<[VecDeque<Bucket>; 3]>>,
+}
+
+impl MetricsHistory {
+    /// Count a request to `endpoint` (`GET /users`)
+    pub fn record(&self, endpoint: &str, status: u16, latency_ms: f64) {
+        self.record_at(chrono::Utc::now(), endpoint, status, latency_ms);
+    }
+
+    fn record_at(&self, at: chrono::DateTime<chrono::Utc>, endpoint: &str, status: u16, latency_ms: f64) {
+        let mut series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        for resolution in Resolution::ALL {
+            let buckets = &mut series[resolution.index()];
+            let width = resolution.width().as_secs() as i64;
+            let start = at.timestamp().div_euclid(width) * width;
+            // Buckets stay in order; a request finishing late may belong
+            // to an earlier one
+            let index = match buckets.iter().rposition(|bucket| bucket.start <= start) {
+                Some(index) if buckets[index].start == start => index,
+                before => {
+                    let index = before.map_or(0, |index| index + 1);
+                    buckets.insert(index, Bucket { start, endpoints: HashMap::new() });
+                    index
+                }
+            };
+            buckets[index].endpoints.entry(endpoint.to_string()).or_default().record(status, latency_ms);
+            let newest = buckets.back().map_or(start, |bucket| bucket.start);
+            let oldest = newest - resolution.retention().as_secs() as i64;
+            while buckets.front().is_some_and(|bucket| bucket.start <= oldest) {
+                buckets.pop_front();
+            }
+        }
+    }
+
+    /// Metrics of the last `range`, of one endpoint or all of them
+    pub fn report(&self, range: Duration, endpoint: Option<&str>) -> Option<MetricsHistoryReport> {
+        self.report_at(chrono::Utc::now(), range, endpoint)
+    }
+
+    fn report_at(&self, now: chrono::DateTime<chrono::Utc>, range: Duration, endpoint: Option<&str>) -> Option<MetricsHistoryReport> {
+        let resolution = Resolution::for_range(range)?;
+        let width = resolution.width().as_secs() as i64;
+        let last = now.timestamp().div_euclid(width) * width;
+        let first = last - (range.as_secs() as i64 / width - 1).max(0) * width;
+
+        let series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        let buckets = &series[resolution.index()];
+        let points = (first..=last).step_by(width as usize).map(|start| {
+            let mut counts = Counts::default();
+            if let Some(bucket) = buckets.iter().find(|bucket| bucket.start == start) {
+                for (name, endpoint_counts) in &bucket.endpoints {
+                    if endpoint.is_none_or(|endpoint| endpoint == name) {
+                        counts.merge(endpoint_counts);
+                    }
+                }
+            }
+            MetricsPoint::new(start, &counts)
+        }).collect();
+        Some(MetricsHistoryReport { resolution, endpoint: endpoint.map(str::to_string), points })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_buckets_requests_with_percentiles() {
+        let history = MetricsHistory::default();
+        let start = chrono::DateTime::from_timestamp(1_700_000_040, 0).unwrap();
+        let at = |seconds: i64| start + chrono::Duration::seconds(seconds);
+        for latency in 1..=100 {
+            history.record_at(at(0), "GET /users", if latency > 90 { 500 } else { 200 }, latency as f64);
+        }
+        history.record_at(at(60), "POST /orders", 201, 0.25);
+        history.record_at(at(180), "POST /orders", 404, 3.0);
+
+        let report = history.report_at(at(180), Duration::from_secs(5 * 60), None).unwrap();
+        assert_eq!(report.resolution, Resolution::Minute);
+        assert_eq!(report.points.len(), 5);
+        assert_eq!(report.points.iter().map(|point| point.requests).collect::<Vec<_>>(), vec![0, 100, 1, 0, 1]);
+        let users = &report.points[1];
+        assert_eq!((users.errors, users.error_rate), (10, 0.1));
+        assert_eq!(users.avg_latency_ms, 50.5);
+        for (percentile, expected) in [(users.p50_latency_ms, 50.0), (users.p95_latency_ms, 95.0), (users.p99_latency_ms, 99.0)] {
+            assert!((percentile - expected).abs() / expected <= 0.02, "{} vs {}", percentile, expected);
+        }
+        assert!((report.points[2].p99_latency_ms - 0.25).abs() <= 0.01);
+
+        let orders = history.report_at(at(180), Duration::from_secs(5 * 60), Some("POST /orders")).unwrap();
+        assert_eq!(orders.points.iter().map(|point| point.errors).sum::<u64>(), 1);
+        let day = history.report_at(at(180), Duration::from_secs(6 * 60 * 60), None).unwrap();
+        assert_eq!(day.resolution, Resolution::FiveMinutes);
+        assert_eq!(day.points.iter().map(|point| point.requests).sum::<u64>(), 102);
+        assert_eq!(day.points.len(), 72);
+        assert!(history.report(Duration::from_secs(30 * 24 * 60 * 60), None).is_none());
+
+        // Buckets past their retention are dropped
+        history.record_at(at(3 * 60 * 60), "GET /users", 200, 1.0);
+        assert_eq!(history.series.lock().unwrap()[Resolution::Minute.index()].len(), 1);
+    }
+}
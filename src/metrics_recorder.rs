@@ -0,0 +1,193 @@
+//! Latency and error metrics of endpoints, upstreams and plugins
+//!
+//! A [`MetricsRecorder`] keeps, per [`MetricSource`], exact request and
+//! error counters, the mean latency, an exponential moving average that
+//! follows recent latency, and an HDR histogram for p50, p95 and p99. The
+//! server records every endpoint request and the proxy every upstream call.
+//! Plugins find the recorder in the extensions of the requests they see, and
+//! record their own work as [`MetricSource::Plugin`].
+
+use hdrhistogram::Histogram;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Weight of the newest latency in the moving average
+pub const EMA_WEIGHT: f64 = 0.2;
+
+/// Longest latency the histograms resolve, in microseconds; longer ones
+/// count as this
+const MAX_LATENCY_MICROS: u64 = 60 * 60 * 1_000_000;
+
+/// What a measurement is about
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MetricSource {
+    /// Requests to an endpoint
+    Endpoint { method: String, path: String },
+    /// Calls the proxy made to an upstream target
+    Upstream { target: String },
+    /// Work a plugin measures itself
+    Plugin { plugin: String, metric: String },
+}
+
+impl MetricSource {
+    pub fn endpoint(method: &str, path: &str) -> Self {
+        Self::Endpoint { method: method.to_string(), path: path.to_string() }
+    }
+
+    pub fn upstream(target: &str) -> Self {
+        Self::Upstream { target: target.to_string() }
+    }
+
+    pub fn plugin(plugin: &str, metric: &str) -> Self {
+        Self::Plugin { plugin: plugin.to_string(), metric: metric.to_string() }
+    }
+}
+
+impl std::fmt::Display for MetricSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Endpoint { method, path } => write!(f, "{} {}", method, path),
+            Self::Upstream { target } => write!(f, "upstream {}", target),
+            Self::Plugin { plugin, metric } => write!(f, "plugin {}/{}", plugin, metric),
+        }
+    }
+}
+
+/// Metrics of one source
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    #[serde(flatten)]
+    pub source: MetricSource,
+    pub requests: u64,
+    pub errors: u64,
+    /// Fraction of measurements that failed (0.0 - 1.0)
+    pub error_rate: f64,
+    pub avg_latency_ms: f64,
+    /// Moving average weighted towards recent latencies
+    pub ema_latency_ms: f64,
+    pub p50_latency_ms: f64,
+    pub p95_latency_ms: f64,
+    pub p99_latency_ms: f64,
+    pub last_recorded: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug)]
+struct Series {
+    requests: u64,
+    errors: u64,
+    latency_sum_ms: f64,
+    ema_latency_ms: f64,
+    /// Latencies in microseconds
+    latencies: Histogram<u64>,
+    last_recorded: chrono::DateTime<chrono::Utc>,
+}
+
+impl Series {
+    fn new() -> Self {
+        Self {
+            requests: 0,
+            errors: 0,
+            latency_sum_ms: 0.0,
+            ema_latency_ms: 0.0,
+            latencies: Histogram::new_with_bounds(1, MAX_LATENCY_MICROS, 2).expect("valid histogram bounds"),
+            last_recorded: chrono::Utc::now(),
+        }
+    }
+
+    fn record(&mut self, latency: Duration, failed: bool) {
+        let latency_ms = latency.as_secs_f64() * 1000.0;
+        self.requests += 1;
+        if failed {
+            self.errors += 1;
+        }
+        self.latency_sum_ms += latency_ms;
+        self.ema_latency_ms = if self.requests == 1 {
+            latency_ms
+        } else {
+            EMA_WEIGHT * latency_ms + (1.0 - EMA_WEIGHT) * self.ema_latency_ms
+        };
+        self.latencies.saturating_record((latency.as_micros() as u64).clamp(1, MAX_LATENCY_MICROS));
+        self.last_recorded = chrono::Utc::now();
+    }
+
+    fn snapshot(&self, source: &MetricSource) -> MetricsSnapshot {
+        let percentile = |quantile: f64| self.latencies.value_at_quantile(quantile) as f64 / 1000.0;
+        MetricsSnapshot {
+            source: source.clone(),
+            requests: self.requests,
+            errors: self.errors,
+            error_rate: self.errors as f64 / self.requests as f64,
+            avg_latency_ms: self.latency_sum_ms / self.requests as f64,
+            ema_latency_ms: self.ema_latency_ms,
+            p50_latency_ms: percentile(0.50),
+            p95_latency_ms: percentile(0.95),
+            p99_latency_ms: percentile(0.99),
+            last_recorded: self.last_recorded,
+        }
+    }
+}
+
+/// Records measurements and reports them per source; clones share them
+#[derive(Debug, Clone, Default)]
+pub struct MetricsRecorder {
+    series: Arc<Mutex<HashMap<MetricSource, Series>>>,
+}
+
+impl MetricsRecorder {
+    /// Count one measurement of `source` that took `latency`
+    pub fn record(&self, source: MetricSource, latency: Duration, failed: bool) {
+        let mut series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        series.entry(source).or_insert_with(Series::new).record(latency, failed);
+    }
+
+    /// Count a request an endpoint answered with `status`; 4xx and 5xx
+    /// statuses are errors
+    pub fn record_request(&self, method: &str, path: &str, status: u16, latency: Duration) {
+        self.record(MetricSource::endpoint(method, path), latency, status >= 400);
+    }
+
+    pub fn snapshot(&self, source: &MetricSource) -> Option<MetricsSnapshot> {
+        let series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        series.get(source).map(|series| series.snapshot(source))
+    }
+
+    /// Every source measured so far, in order
+    pub fn snapshots(&self) -> Vec<MetricsSnapshot> {
+        let series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        let ordered: BTreeMap<&MetricSource, &Series> = series.iter().collect();
+        ordered.into_iter().map(|(source, series)| series.snapshot(source)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recorder_counts_averages_and_percentiles() {
+        let recorder = MetricsRecorder::default();
+        for latency in 1..=100 {
+            recorder.record_request("GET", "/users", if latency % 4 == 0 { 503 } else { 200 }, Duration::from_millis(latency));
+        }
+        recorder.record(MetricSource::upstream("http://10.0.0.1"), Duration::from_micros(250), false);
+
+        let users = recorder.snapshot(&MetricSource::endpoint("GET", "/users")).unwrap();
+        assert_eq!((users.requests, users.errors, users.error_rate), (100, 25, 0.25));
+        assert_eq!(users.avg_latency_ms, 50.5);
+        // Weighted towards the latest latencies, which were the longest
+        assert!(users.ema_latency_ms > 90.0 && users.ema_latency_ms < 100.0);
+        for (percentile, expected) in [(users.p50_latency_ms, 50.0), (users.p95_latency_ms, 95.0), (users.p99_latency_ms, 99.0)] {
+            assert!((percentile - expected).abs() / expected <= 0.01, "{} vs {}", percentile, expected);
+        }
+
+        let snapshots = recorder.snapshots();
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[1].source.to_string(), "upstream http://10.0.0.1");
+        assert!((snapshots[1].p99_latency_ms - 0.25).abs() < 0.01);
+        let json = serde_json::to_value(&snapshots[0]).unwrap();
+        assert_eq!((json["kind"].as_str(), json["path"].as_str()), (Some("endpoint"), Some("/users")));
+    }
+}
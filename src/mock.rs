@@ -0,0 +1,790 @@
+//! Mock mode
+//!
+//! Endpoints in `mock` mode answer with data generated from their `mock`
+//! block. The `schema` is the shape of one item: string leaves starting with
+//! `$` are generators, everything else is copied as is (`$$` writes a
+//! literal `$`).
+//!
+//! - `$uuid`, `$name`, `$first_name`, `$last_name`, `$username`, `$email`,
+//!   `$phone`, `$company`, `$street`, `$city`, `$country`, `$word`,
+//!   `$sentence`, `$date`, `$datetime`, `$bool`
+//! - `$int(min, max)`, `$float(min, max)`, `$pick(a, b, c)`
+//! - `$seq`: the item's position in its collection, from 1
+//! - `$ref(endpoint.field)`: the field of a random item of another mock
+//!   endpoint's collection (the whole item without `.field`)
+//! - `$param(name)`: a path parameter of the request
+//!
+//! With `count`, the collection is generated once at startup and every
+//! request gets the same list; `item_of` serves the item of such a
+//! collection that the path names. Without `count`, each request path gets
+//! its own, stable item. All data derives from the endpoint's `seed`, so
+//! restarts serve the same responses.
+//!
+//! A `stateful` collection lives in the [state store](crate::state): `POST`
+//! adds items and its `item_of` endpoints update and remove them. Each
+//! session, named by a request header, changes its own copy, until
+//! [retention](crate::retention) purges it and the session sees the
+//! generated collection again.
+
+use crate::config::{EndpointConfig, MockConfig};
+use crate::error::{BackworksError, Result};
+use crate::response_filter::FIELDS_PARAM;
+use crate::rollout::fnv1a;
+use crate::server::RequestData;
+use crate::state::StateStore;
+use chrono::{DateTime, Duration, NaiveDate, SecondsFormat, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+const DEFAULT_SESSION_HEADER: &str = "x-mock-session";
+
+const FIRST_NAMES: &[&str] = &[
+    "Ada", "Alan", "Grace", "Linus", "Margaret", "Dennis", "Barbara", "Ken", "Radia", "Edsger",
+    "Frances", "Donald", "Hedy", "John", "Katherine", "Tim", "Sophie", "Guido", "Annie", "Bjarne",
+];
+const LAST_NAMES: &[&str] = &[
+    "Lovelace", "Turing", "Hopper", "Torvalds", "Hamilton", "Ritchie", "Liskov", "Thompson", "Perlman", "Dijkstra",
+    "Allen", "Knuth", "Lamarr", "McCarthy", "Johnson", "Berners-Lee", "Wilson", "van Rossum", "Easley", "Stroustrup",
+];
+const COMPANIES: &[&str] = &[
+    "Acme Corp", "Globex", "Initech", "Umbrella Labs", "Hooli", "Stark Industries", "Wayne Enterprises", "Soylent", "Cyberdyne", "Vandelay Industries",
+];
+const STREETS: &[&str] = &["Main St", "Oak Ave", "Maple Rd", "Cedar Ln", "Elm St", "Park Blvd", "Lake Dr", "Hill Rd"];
+const CITIES: &[&str] = &["Berlin", "Lisbon", "Toronto", "Osaka", "Nairobi", "Austin", "Melbourne", "Oslo", "Bogotá", "Seoul"];
+const COUNTRIES: &[&str] = &["Germany", "Portugal", "Canada", "Japan", "Kenya", "United States", "Australia", "Norway", "Colombia", "South Korea"];
+const WORDS: &[&str] = &[
+    "alpha", "beacon", "cobalt", "delta", "ember", "falcon", "granite", "harbor", "iris", "juniper",
+    "kernel", "lumen", "meadow", "nimbus", "orbit", "prism", "quartz", "ripple", "summit", "tundra",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Faker {
+    Uuid,
+    Name,
+    FirstName,
+    LastName,
+    Username,
+    Email,
+    Phone,
+    Company,
+    Street,
+    City,
+    Country,
+    Word,
+    Sentence,
+    Date,
+    DateTime,
+    Bool,
+    Int(i64, i64),
+    Float(f64, f64),
+    Pick(Vec<Value>),
+    Seq,
+    Ref { endpoint: String, field: Option<String> },
+    Param(String),
+}
+
+impl Faker {
+    fn parse(spec: &str) -> std::result::Result<Self, String> {
+        let (name, args) = match spec.split_once('(') {
+            Some((name, rest)) => (name, Some(rest.strip_suffix(')').ok_or_else(|| format!("`${}` is missing ')'", spec))?)),
+            None => (spec, None),
+        };
+        let args: Vec<&str> = args.map(|args| args.split(',').map(str::trim).filter(|arg| !arg.is_empty()).collect()).unwrap_or_default();
+        let plain = |faker: Faker| if args.is_empty() { Ok(faker) } else { Err(format!("`${}` takes no arguments", name)) };
+        let one = |what: &str| match args.as_slice() {
+            [arg] => Ok(arg.to_string()),
+            _ => Err(format!("`${}` takes one argument, the {}", name, what)),
+        };
+
+        match name {
+            "uuid" => plain(Faker::Uuid),
+            "name" => plain(Faker::Name),
+            "first_name" => plain(Faker::FirstName),
+            "last_name" => plain(Faker::LastName),
+            "username" => plain(Faker::Username),
+            "email" => plain(Faker::Email),
+            "phone" => plain(Faker::Phone),
+            "company" => plain(Faker::Company),
+            "street" => plain(Faker::Street),
+            "city" => plain(Faker::City),
+            "country" => plain(Faker::Country),
+            "word" => plain(Faker::Word),
+            "sentence" => plain(Faker::Sentence),
+            "date" => plain(Faker::Date),
+            "datetime" => plain(Faker::DateTime),
+            "bool" => plain(Faker::Bool),
+            "seq" => plain(Faker::Seq),
+            "int" => {
+                let (min, max) = range(name, &args, (1.0, 1000.0))?;
+                Ok(Faker::Int(min as i64, max as i64))
+            }
+            "float" => {
+                let (min, max) = range(name, &args, (0.0, 1.0))?;
+                Ok(Faker::Float(min, max))
+            }
+            "pick" if args.is_empty() => Err("`$pick` needs at least one choice".to_string()),
+            "pick" => Ok(Faker::Pick(args.iter().map(|arg| serde_json::from_str(arg).unwrap_or_else(|_| Value::String(arg.to_string()))).collect())),
+            "ref" => {
+                let target = one("endpoint and field")?;
+                Ok(match target.split_once('.') {
+                    Some((endpoint, field)) => Faker::Ref { endpoint: endpoint.to_string(), field: Some(field.to_string()) },
+                    None => Faker::Ref { endpoint: target, field: None },
+                })
+            }
+            "param" => Ok(Faker::Param(one("path parameter name")?)),
+            _ => Err(format!("unknown generator `${}`", name)),
+        }
+    }
+
+    fn generate(&self, context: &mut Context) -> Value {
+        let rng = &mut *context.rng;
+        let mut choose = |values: &[&str]| values[rng.gen_range(0..values.len())].to_string();
+        match self {
+            Faker::Uuid => Value::String(uuid::Builder::from_random_bytes(context.rng.gen()).into_uuid().to_string()),
+            Faker::Name => Value::String(format!("{} {}", choose(FIRST_NAMES), choose(LAST_NAMES))),
+            Faker::FirstName => Value::String(choose(FIRST_NAMES)),
+            Faker::LastName => Value::String(choose(LAST_NAMES)),
+            Faker::Username => {
+                let first = choose(FIRST_NAMES).to_lowercase();
+                Value::String(format!("{}{}", first, context.rng.gen_range(1..1000)))
+            }
+            Faker::Email => {
+                let (first, last) = (choose(FIRST_NAMES), choose(LAST_NAMES));
+                let local = format!("{}.{}", first, last).to_lowercase().replace([' ', '-'], "");
+                Value::String(format!("{}{}@example.com", local, context.rng.gen_range(1..100)))
+            }
+            Faker::Phone => Value::String(format!("+1-555-{:03}-{:04}", context.rng.gen_range(100..1000), context.rng.gen_range(0..10000))),
+            Faker::Company => Value::String(choose(COMPANIES)),
+            Faker::Street => {
+                let street = choose(STREETS);
+                Value::String(format!("{} {}", context.rng.gen_range(1..2000), street))
+            }
+            Faker::City => Value::String(choose(CITIES)),
+            Faker::Country => Value::String(choose(COUNTRIES)),
+            Faker::Word => Value::String(choose(WORDS)),
+            Faker::Sentence => {
+                let length = context.rng.gen_range(4..10);
+                let mut sentence = (0..length).map(|_| WORDS[context.rng.gen_range(0..WORDS.len())]).collect::<Vec<_>>().join(" ");
+                sentence[..1].make_ascii_uppercase();
+                Value::String(format!("{}.", sentence))
+            }
+            Faker::Date => Value::String(random_date(context.rng).format("%Y-%m-%d").to_string()),
+            Faker::DateTime => {
+                let seconds = context.rng.gen_range(0..86_400);
+                let datetime = random_date(context.rng).and_hms_opt(0, 0, 0).unwrap_or_default().and_utc() + Duration::seconds(seconds);
+                Value::String(datetime.to_rfc3339_opts(SecondsFormat::Secs, true))
+            }
+            Faker::Bool => Value::Bool(context.rng.gen()),
+            Faker::Int(min, max) => json!(context.rng.gen_range(*min..=*max)),
+            Faker::Float(min, max) => json!((context.rng.gen_range(*min..=*max) * 100.0).round() / 100.0),
+            Faker::Pick(choices) => choices[context.rng.gen_range(0..choices.len())].clone(),
+            Faker::Seq => json!(context.seq),
+            Faker::Ref { endpoint, field } => {
+                let items = context.datasets.get(endpoint).map(Vec::as_slice).unwrap_or_default();
+                if items.is_empty() {
+                    return Value::Null;
+                }
+                let item = &items[context.rng.gen_range(0..items.len())];
+                match field {
+                    Some(field) => item.get(field).cloned().unwrap_or(Value::Null),
+                    None => item.clone(),
+                }
+            }
+            Faker::Param(name) => context.params.and_then(|params| params.get(name)).cloned().unwrap_or(Value::Null),
+        }
+    }
+}
+
+fn range(name: &str, args: &[&str], default: (f64, f64)) -> std::result::Result<(f64, f64), String> {
+    let (min, max) = match args {
+        [] => default,
+        [min, max] => match (min.parse(), max.parse()) {
+            (Ok(min), Ok(max)) => (min, max),
+            _ => return Err(format!("`${}` bounds must be numbers", name)),
+        },
+        _ => return Err(format!("`${}` takes a minimum and a maximum", name)),
+    };
+    if min > max {
+        return Err(format!("`${}` minimum {} is above its maximum {}", name, min, max));
+    }
+    Ok((min, max))
+}
+
+fn random_date(rng: &mut StdRng) -> NaiveDate {
+    let start = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap_or_default();
+    start + Duration::days(rng.gen_range(0..6 * 365))
+}
+
+struct Context<'a> {
+    rng: &'a mut StdRng,
+    seq: usize,
+    datasets: &'a HashMap<String, Vec<Value>>,
+    params: Option<&'a HashMap<String, Value>>,
+}
+
+/// A compiled `schema`
+#[derive(Debug, Clone)]
+enum Node {
+    Literal(Value),
+    Faker(Faker),
+    Object(Vec<(String, Node)>),
+    Array(Vec<Node>),
+}
+
+impl Node {
+    fn compile(schema: &Value) -> std::result::Result<Self, String> {
+        Ok(match schema {
+            Value::String(text) if text.starts_with("$$") => Node::Literal(Value::String(text[1..].to_string())),
+            Value::String(text) if text.starts_with('$') => Node::Faker(Faker::parse(&text[1..])?),
+            Value::Object(object) => Node::Object(object.iter()
+                .map(|(key, value)| Ok((key.clone(), Node::compile(value)?)))
+                .collect::<std::result::Result<_, String>>()?),
+            Value::Array(items) => Node::Array(items.iter().map(Node::compile).collect::<std::result::Result<_, String>>()?),
+            literal => Node::Literal(literal.clone()),
+        })
+    }
+
+    fn fakers<'a>(&'a self, found: &mut Vec<&'a Faker>) {
+        match self {
+            Node::Faker(faker) => found.push(faker),
+            Node::Object(fields) => fields.iter().for_each(|(_, node)| node.fakers(found)),
+            Node::Array(items) => items.iter().for_each(|node| node.fakers(found)),
+            Node::Literal(_) => {}
+        }
+    }
+
+    fn generate(&self, context: &mut Context) -> Value {
+        match self {
+            Node::Literal(value) => value.clone(),
+            Node::Faker(faker) => faker.generate(context),
+            Node::Object(fields) => Value::Object(fields.iter().map(|(key, node)| (key.clone(), node.generate(context))).collect::<Map<_, _>>()),
+            Node::Array(items) => Value::Array(items.iter().map(|node| node.generate(context)).collect()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MockEndpoint {
+    schema: Node,
+    count: Option<usize>,
+    seed: u64,
+    item_of: Option<String>,
+    key: String,
+    status: u16,
+    stateful: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct MockEngine {
+    endpoints: HashMap<String, MockEndpoint>,
+    /// Generated collections of the endpoints with a `count`
+    datasets: HashMap<String, Vec<Value>>,
+    session_header: Option<String>,
+    /// Serializes the read-modify-write cycles of stateful collections
+    writes: Mutex<()>,
+}
+
+impl MockEngine {
+    /// Compile every `mock` block and generate the collections
+    pub fn new(endpoints: &HashMap<String, EndpointConfig>) -> Result<Self> {
+        let mut engine = Self::default();
+        for (name, endpoint) in endpoints {
+            if let Some(ref mock) = endpoint.mock {
+                engine.endpoints.insert(name.clone(), compile(name, mock)?);
+            }
+        }
+
+        let mut names: Vec<&String> = engine.endpoints.keys().collect();
+        names.sort();
+        for name in &names {
+            let endpoint = &engine.endpoints[*name];
+            let mut fakers = Vec::new();
+            endpoint.schema.fakers(&mut fakers);
+            for faker in fakers {
+                match faker {
+                    Faker::Ref { endpoint: target, .. } => engine.collection(name, target)?,
+                    Faker::Param(_) if endpoint.count.is_some() => {
+                        return Err(BackworksError::config(format!("Endpoint '{}' mock collection cannot use `$param`", name)));
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(ref target) = endpoint.item_of {
+                engine.collection(name, target)?;
+            }
+        }
+
+        let mut datasets = HashMap::new();
+        for name in names {
+            build(&engine.endpoints, &mut datasets, name, &mut Vec::new())?;
+        }
+        engine.datasets = datasets;
+        Ok(engine)
+    }
+
+    /// Check that `target` is a mock endpoint with a collection
+    fn collection(&self, name: &str, target: &str) -> Result<()> {
+        match self.endpoints.get(target) {
+            Some(endpoint) if endpoint.count.is_some() => Ok(()),
+            Some(_) => Err(BackworksError::config(format!("Endpoint '{}' mock refers to '{}', which has no `count`", name, target))),
+            None => Err(BackworksError::config(format!("Endpoint '{}' mock refers to '{}', which is not a mock endpoint", name, target))),
+        }
+    }
+
+    /// Header naming the session of stateful mock requests, instead of
+    /// `x-mock-session`
+    pub fn with_session_header(mut self, header: Option<String>) -> Self {
+        self.session_header = header;
+        self
+    }
+
+    /// The stateful collection the endpoint lists or changes items of
+    fn stateful_collection(&self, endpoint: &str) -> Option<&str> {
+        let mock = self.endpoints.get(endpoint)?;
+        let (collection, target) = self.endpoints.get_key_value(mock.item_of.as_deref().unwrap_or(endpoint))?;
+        target.stateful.then_some(collection.as_str())
+    }
+
+    /// Drop the sessions' copies of the endpoint's stateful collection last
+    /// changed at or before `cutoff`, and trim the others to their newest
+    /// `max_items` items. Returns the copies dropped and items removed.
+    pub async fn purge(&self, endpoint: &str, store: &StateStore, cutoff: Option<DateTime<Utc>>, max_items: Option<usize>) -> (usize, usize) {
+        let Some(collection) = self.stateful_collection(endpoint) else {
+            return (0, 0);
+        };
+        let _write = self.writes.lock().await;
+        let (mut copies, mut items) = (0, 0);
+        for namespace in store.namespaces().await {
+            if namespace != "mock" && !namespace.starts_with("mock:") {
+                continue;
+            }
+            let Some(written) = store.written_at(&namespace, collection).await else {
+                continue;
+            };
+            if cutoff.is_some_and(|cutoff| written <= cutoff) {
+                store.delete(&namespace, collection).await;
+                copies += 1;
+                continue;
+            }
+            if let (Some(max_items), Some(Value::Array(mut kept))) = (max_items, store.get(&namespace, collection).await) {
+                if kept.len() > max_items {
+                    items += kept.len() - max_items;
+                    kept.drain(..kept.len() - max_items);
+                    store.set(&namespace, collection, Value::Array(kept)).await;
+                }
+            }
+        }
+        (copies, items)
+    }
+
+    /// The endpoint's generated response as handler output
+    pub async fn respond(&self, endpoint: &str, request: &RequestData, store: &StateStore) -> Result<String> {
+        let mock = self.endpoints.get(endpoint)
+            .ok_or_else(|| BackworksError::config(format!("Endpoint '{}' runs in mock mode but has no `mock` block", endpoint)))?;
+
+        let (status, body) = if let Some(ref target) = mock.item_of {
+            let wanted = request.path_params.get(&mock.key).ok_or_else(|| BackworksError::config(format!(
+                "Endpoint '{}' mock looks items up by '{}', which is not a path parameter", endpoint, mock.key
+            )))?;
+            if self.endpoints[target].stateful {
+                self.change_item(mock, target, wanted, request, store).await
+            } else {
+                let found = self.datasets.get(target).into_iter().flatten()
+                    .find(|item| item.get(&mock.key).is_some_and(|value| as_text(value) == as_text(wanted)));
+                match found {
+                    Some(item) => (mock.status, item.clone()),
+                    None => (404, not_found(target, &mock.key, wanted)),
+                }
+            }
+        } else if mock.stateful {
+            self.change_collection(endpoint, mock, request, store).await
+        } else if mock.count.is_some() {
+            (mock.status, Value::Array(self.datasets.get(endpoint).cloned().unwrap_or_default()))
+        } else {
+            let mut rng = StdRng::seed_from_u64(mock.seed ^ fnv1a(request.path.bytes()));
+            let mut context = Context { rng: &mut rng, seq: 1, datasets: &self.datasets, params: Some(&request.path_params) };
+            (mock.status, mock.schema.generate(&mut context))
+        };
+        Ok(json!({ "status": status, "body": body }).to_string())
+    }
+
+    /// State store namespace of the request's session
+    fn namespace(&self, request: &RequestData) -> String {
+        let header = self.session_header.as_deref().unwrap_or(DEFAULT_SESSION_HEADER);
+        match request.headers.get(header).and_then(|value| value.to_str().ok()).filter(|session| !session.is_empty()) {
+            Some(session) => format!("mock:{}", session),
+            None => "mock".to_string(),
+        }
+    }
+
+    /// A session's copy of a stateful collection, the generated one until
+    /// the session changes it
+    async fn items(&self, store: &StateStore, namespace: &str, collection: &str) -> Vec<Value> {
+        match store.get(namespace, collection).await {
+            Some(Value::Array(items)) => items,
+            _ => self.datasets.get(collection).cloned().unwrap_or_default(),
+        }
+    }
+
+    /// List with `GET`, create with `POST`
+    async fn change_collection(&self, name: &str, mock: &MockEndpoint, request: &RequestData, store: &StateStore) -> (u16, Value) {
+        let namespace = self.namespace(request);
+        match request.method.as_str() {
+            "GET" | "HEAD" => (mock.status, Value::Array(filter(self.items(store, &namespace, name).await, &request.query_params))),
+            "POST" => {
+                let Some(Value::Object(mut item)) = request.body.clone() else {
+                    return (400, json!({ "error": format!("Expected a JSON object to add to {}", name) }));
+                };
+                let _write = self.writes.lock().await;
+                let mut items = self.items(store, &namespace, name).await;
+                match item.get(&mock.key) {
+                    Some(key) if items.iter().any(|existing| existing.get(&mock.key).is_some_and(|value| as_text(value) == as_text(key))) => {
+                        return (409, json!({ "error": format!("A {} with {} {} already exists", name, mock.key, as_text(key)) }));
+                    }
+                    Some(_) => {}
+                    None => {
+                        item.insert(mock.key.clone(), next_key(&items, &mock.key));
+                    }
+                }
+                let item = Value::Object(item);
+                items.push(item.clone());
+                store.set(&namespace, name, Value::Array(items)).await;
+                (201, item)
+            }
+            method => method_not_allowed(method, name),
+        }
+    }
+
+    /// Read with `GET`, replace with `PUT`, merge with `PATCH`, remove with `DELETE`
+    async fn change_item(&self, mock: &MockEndpoint, collection: &str, wanted: &Value, request: &RequestData, store: &StateStore) -> (u16, Value) {
+        let namespace = self.namespace(request);
+        let reading = matches!(request.method.as_str(), "GET" | "HEAD");
+        let _write = if reading { None } else { Some(self.writes.lock().await) };
+        let mut items = self.items(store, &namespace, collection).await;
+        let Some(index) = items.iter().position(|item| item.get(&mock.key).is_some_and(|value| as_text(value) == as_text(wanted))) else {
+            return (404, not_found(collection, &mock.key, wanted));
+        };
+
+        match request.method.as_str() {
+            _ if reading => (mock.status, items.swap_remove(index)),
+            method @ ("PUT" | "PATCH") => {
+                let Some(body @ Value::Object(_)) = request.body.clone() else {
+                    return (400, json!({ "error": format!("Expected a JSON object to update the {} item", collection) }));
+                };
+                let key = items[index].get(&mock.key).cloned().unwrap_or(Value::Null);
+                let mut updated = if method == "PUT" {
+                    body
+                } else {
+                    let mut merged = items[index].clone();
+                    merge_patch(&mut merged, body);
+                    merged
+                };
+                // Items keep the key they are found by
+                updated[mock.key.as_str()] = key;
+                items[index] = updated.clone();
+                store.set(&namespace, collection, Value::Array(items)).await;
+                (mock.status, updated)
+            }
+            "DELETE" => {
+                items.remove(index);
+                store.set(&namespace, collection, Value::Array(items)).await;
+                (204, Value::Null)
+            }
+            method => method_not_allowed(method, collection),
+        }
+    }
+}
+
+/// Items whose fields equal the query parameters, then `_offset` and `_limit`
+fn filter(items: Vec<Value>, query: &HashMap<String, String>) -> Vec<Value> {
+    let offset = query.get("_offset").and_then(|offset| offset.parse().ok()).unwrap_or(0);
+    let limit = query.get("_limit").and_then(|limit| limit.parse().ok()).unwrap_or(usize::MAX);
+    let conditions: Vec<(&String, &String)> = query.iter()
+        .filter(|(name, _)| !name.starts_with('_') && name.as_str() != FIELDS_PARAM)
+        .collect();
+    items.into_iter()
+        .filter(|item| conditions.iter().all(|(name, value)| item.get(name.as_str()).is_some_and(|field| as_text(field) == **value)))
+        .skip(offset)
+        .take(limit)
+        .collect()
+}
+
+/// The key of a new item: one past the largest when keys are integers,
+/// a UUID otherwise
+fn next_key(items: &[Value], key: &str) -> Value {
+    let keys: Option<Vec<i64>> = items.iter().map(|item| item.get(key).and_then(Value::as_i64)).collect();
+    match keys {
+        Some(keys) => json!(keys.into_iter().max().unwrap_or(0) + 1),
+        None => Value::String(uuid::Uuid::new_v4().to_string()),
+    }
+}
+
+/// RFC 7396 JSON merge patch: `null` removes a field
+fn merge_patch(target: &mut Value, patch: Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(fields) = target {
+        for (name, value) in patch {
+            if value.is_null() {
+                fields.remove(&name);
+            } else {
+                merge_patch(fields.entry(name).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+fn not_found(collection: &str, key: &str, wanted: &Value) -> Value {
+    json!({ "error": format!("No {} with {} {}", collection, key, as_text(wanted)) })
+}
+
+fn method_not_allowed(method: &str, collection: &str) -> (u16, Value) {
+    (405, json!({ "error": format!("{} is not supported on {}", method, collection) }))
+}
+
+fn compile(name: &str, mock: &MockConfig) -> Result<MockEndpoint> {
+    let invalid = |message: String| BackworksError::config(format!("Endpoint '{}' mock: {}", name, message));
+    let schema = match (&mock.schema, &mock.item_of) {
+        (Some(_), Some(_)) => return Err(invalid("`item_of` serves another endpoint's items; remove the `schema`".to_string())),
+        (None, None) if !mock.stateful => return Err(invalid("needs a `schema` or `item_of`".to_string())),
+        (Some(schema), None) => Node::compile(schema).map_err(invalid)?,
+        (None, _) => Node::Literal(Value::Null),
+    };
+    if mock.item_of.is_some() && mock.count.is_some() {
+        return Err(invalid("`item_of` serves a single item and cannot have a `count`".to_string()));
+    }
+    if mock.item_of.is_some() && mock.stateful {
+        return Err(invalid("set `stateful` on the collection that `item_of` names".to_string()));
+    }
+    let status = mock.status.unwrap_or(200);
+    if !(100..=599).contains(&status) {
+        return Err(invalid(format!("{} is not an HTTP status code", status)));
+    }
+    Ok(MockEndpoint {
+        schema,
+        // Stateful collections without a count start empty
+        count: mock.count.or(mock.stateful.then_some(0)),
+        seed: mock.seed.unwrap_or_else(|| fnv1a(name.bytes())),
+        item_of: mock.item_of.clone(),
+        key: mock.key.clone().unwrap_or_else(|| "id".to_string()),
+        status,
+        stateful: mock.stateful,
+    })
+}
+
+/// Generate the collection of `name` after the collections it refers to
+fn build(endpoints: &HashMap<String, MockEndpoint>, datasets: &mut HashMap<String, Vec<Value>>, name: &str, visiting: &mut Vec<String>) -> Result<()> {
+    let endpoint = &endpoints[name];
+    let Some(count) = endpoint.count else {
+        return Ok(());
+    };
+    if datasets.contains_key(name) {
+        return Ok(());
+    }
+    if visiting.iter().any(|visited| visited == name) {
+        visiting.push(name.to_string());
+        return Err(BackworksError::config(format!("Mock collections refer to each other: {}", visiting.join(" -> "))));
+    }
+
+    visiting.push(name.to_string());
+    let mut fakers = Vec::new();
+    endpoint.schema.fakers(&mut fakers);
+    for faker in fakers {
+        if let Faker::Ref { endpoint: target, .. } = faker {
+            build(endpoints, datasets, target, visiting)?;
+        }
+    }
+    visiting.pop();
+
+    let mut rng = StdRng::seed_from_u64(endpoint.seed);
+    let items = (1..=count)
+        .map(|seq| endpoint.schema.generate(&mut Context { rng: &mut rng, seq, datasets, params: None }))
+        .collect();
+    datasets.insert(name.to_string(), items);
+    Ok(())
+}
+
+fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine(yaml: &str) -> Result<MockEngine> {
+        let endpoints: HashMap<String, EndpointConfig> = serde_yaml::from_str(yaml).unwrap();
+        MockEngine::new(&endpoints)
+    }
+
+    fn request(path: &str, params: &[(&str, Value)]) -> RequestData {
+        RequestData {
+            method: "GET".to_string(),
+            path: path.to_string(),
+            path_params: params.iter().map(|(name, value)| (name.to_string(), value.clone())).collect(),
+            query_params: HashMap::new(),
+            headers: Default::default(),
+            body: None,
+            auth: None,
+            origin: None,
+            locale: None,
+            deadline: None,
+            session: None,
+            csrf_token: None,
+        }
+    }
+
+    fn change(method: &str, path: &str, params: &[(&str, Value)], session: Option<&str>, body: Option<Value>) -> RequestData {
+        let mut request = request(path, params);
+        request.method = method.to_string();
+        request.body = body;
+        if let Some(session) = session {
+            request.headers.insert(DEFAULT_SESSION_HEADER, session.parse().unwrap());
+        }
+        request
+    }
+
+    fn body(output: String) -> (u64, Value) {
+        let output: Value = serde_json::from_str(&output).unwrap();
+        (output["status"].as_u64().unwrap(), output["body"].clone())
+    }
+
+    const SHOP: &str = r#"
+users:
+  path: /users
+  mode: mock
+  mock:
+    count: 5
+    seed: 42
+    schema:
+      id: $seq
+      uuid: $uuid
+      name: $name
+      email: $email
+      joined: $date
+      plan: $pick(free, pro)
+      score: $int(1, 10)
+      note: "$$5 off"
+user:
+  path: /users/{id:int}
+  mode: mock
+  mock: { item_of: users }
+orders:
+  path: /orders
+  mode: mock
+  mock:
+    count: 3
+    schema: { id: $seq, user_id: $ref(users.id), placed: $datetime }
+order:
+  path: /orders/{id}
+  mode: mock
+  mock:
+    schema:
+      id: $param(id)
+      status: $pick(open, shipped)
+      buyer: $ref(users)
+"#;
+
+    #[tokio::test]
+    async fn test_generated_collections_are_deterministic_and_related() {
+        let engine = engine(SHOP).unwrap();
+        let store = StateStore::new();
+        let (status, users) = body(engine.respond("users", &request("/users", &[]), &store).await.unwrap());
+        assert_eq!(status, 200);
+        let users = users.as_array().unwrap();
+        assert_eq!(users.len(), 5);
+        assert_eq!(users[2]["id"], 3);
+        assert_eq!(users[0]["note"], "$5 off");
+        assert!(users[0]["email"].as_str().unwrap().ends_with("@example.com"));
+        assert_eq!(users[0]["uuid"].as_str().unwrap().len(), 36);
+        assert!(["free", "pro"].contains(&users[0]["plan"].as_str().unwrap()));
+        assert!((1..=10).contains(&users[0]["score"].as_i64().unwrap()));
+        assert_eq!(self::engine(SHOP).unwrap().datasets["users"], engine.datasets["users"]);
+
+        let (_, orders) = body(engine.respond("orders", &request("/orders", &[]), &store).await.unwrap());
+        for order in orders.as_array().unwrap() {
+            assert!(users.iter().any(|user| user["id"] == order["user_id"]));
+            assert!(order["placed"].as_str().unwrap().ends_with('Z'));
+        }
+
+        assert_eq!(body(engine.respond("user", &request("/users/4", &[("id", json!(4))]), &store).await.unwrap()), (200, users[3].clone()));
+        let (status, missing) = body(engine.respond("user", &request("/users/9", &[("id", json!(9))]), &store).await.unwrap());
+        assert_eq!((status, missing["error"].as_str().unwrap()), (404, "No users with id 9"));
+
+        let first = engine.respond("order", &request("/orders/a1", &[("id", json!("a1"))]), &store).await.unwrap();
+        assert_eq!(first, engine.respond("order", &request("/orders/a1", &[("id", json!("a1"))]), &store).await.unwrap());
+        let (_, order) = body(first);
+        assert_eq!(order["id"], "a1");
+        assert!(users.contains(&order["buyer"]));
+    }
+
+    #[tokio::test]
+    async fn test_stateful_collections_support_crud_per_session() {
+        let engine = engine(r#"
+todos:
+  path: /todos
+  mock:
+    stateful: true
+    count: 2
+    schema: { id: $seq, title: $sentence, done: false }
+todo:
+  path: /todos/{id:int}
+  mock: { item_of: todos }
+"#).unwrap();
+        let store = StateStore::new();
+        let id = |id: i64| [("id", json!(id))];
+        let send = |request: RequestData| {
+            let (engine, store) = (&engine, &store);
+            async move { body(engine.respond(if request.path_params.is_empty() { "todos" } else { "todo" }, &request, store).await.unwrap()) }
+        };
+
+        let (status, created) = send(change("POST", "/todos", &[], None, Some(json!({ "title": "Ship it", "done": false })))).await;
+        assert_eq!((status, &created["id"]), (201, &json!(3)));
+        let (status, _) = send(change("POST", "/todos", &[], None, Some(json!({ "id": 3 })))).await;
+        assert_eq!(status, 409);
+
+        let (status, patched) = send(change("PATCH", "/todos/3", &id(3), None, Some(json!({ "done": true, "title": null })))).await;
+        assert_eq!((status, patched), (200, json!({ "id": 3, "done": true })));
+        let (status, replaced) = send(change("PUT", "/todos/1", &id(1), None, Some(json!({ "id": 9, "title": "Renamed" })))).await;
+        assert_eq!((status, replaced), (200, json!({ "id": 1, "title": "Renamed" })));
+        assert_eq!(send(change("DELETE", "/todos/2", &id(2), None, None)).await.0, 204);
+        assert_eq!(send(change("GET", "/todos/2", &id(2), None, None)).await.0, 404);
+
+        let mut listed = change("GET", "/todos", &[], None, None);
+        let (_, todos) = send(listed.clone()).await;
+        assert_eq!(todos.as_array().unwrap().iter().map(|todo| todo["id"].clone()).collect::<Vec<_>>(), vec![json!(1), json!(3)]);
+        listed.query_params.insert("done".to_string(), "true".to_string());
+        assert_eq!(send(listed).await.1, json!([{ "id": 3, "done": true }]));
+
+        // Other sessions start from the generated collection
+        let (_, fresh) = send(change("GET", "/todos", &[], Some("alice"), None)).await;
+        assert_eq!(fresh, Value::Array(engine.datasets["todos"].clone()));
+        assert_eq!(send(change("DELETE", "/todos", &[], Some("alice"), None)).await.0, 405);
+    }
+
+    #[test]
+    fn test_invalid_mocks_are_rejected() {
+        for (yaml, expected) in [
+            ("a: { path: /a, mock: { schema: { x: $nope } } }", "unknown generator `$nope`"),
+            ("a: { path: /a, mock: { schema: { x: '$int(9, 1)' } } }", "minimum 9 is above its maximum 1"),
+            ("a: { path: /a, mock: {} }", "needs a `schema` or `item_of`"),
+            ("a: { path: /a, mock: { item_of: b } }", "'b', which is not a mock endpoint"),
+            ("a: { path: /a, mock: { count: 2, schema: { x: $param(id) } } }", "cannot use `$param`"),
+            ("a: { path: /a, mock: { count: 1, schema: { b: $ref(b.id) } } }\nb: { path: /b, mock: { count: 1, schema: { a: $ref(a.id) } } }", "refer to each other: a -> b -> a"),
+        ] {
+            let err = engine(yaml).unwrap_err().to_string();
+            assert!(err.contains(expected), "{}: {}", yaml, err);
+        }
+    }
+}
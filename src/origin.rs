@@ -0,0 +1,78 @@
+//! Request origin context
+//!
+//! `RequestOrigin` describes where a request came from: the client address
+//! and, when an enrichment plugin such as GeoIP resolved it, the country and
+//! network it belongs to. Like `AuthContext` it travels as a request
+//! extension, is exposed to handlers as the `origin` field of the request
+//! data and is written to the access log.
+
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RequestOrigin {
+    pub ip: Option<IpAddr>,
+    /// ISO 3166-1 alpha-2 country code
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country_name: Option<String>,
+    /// Two-letter continent code
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub continent: Option<String>,
+    /// Autonomous system number of the client's network
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asn: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub as_org: Option<String>,
+}
+
+impl RequestOrigin {
+    /// Value of an origin attribute as a string, for use as a routing
+    /// condition or rate limiting key (`ip`, `country`, `continent`, `asn`
+    /// or `as_org`)
+    pub fn key(&self, field: &str) -> Option<String> {
+        match field {
+            "ip" => self.ip.map(|ip| ip.to_string()),
+            "country" => self.country.clone(),
+            "country_name" => self.country_name.clone(),
+            "continent" => self.continent.clone(),
+            "asn" => self.asn.map(|asn| asn.to_string()),
+            "as_org" => self.as_org.clone(),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a client address as written in forwarding headers: `192.0.2.1`,
+/// `192.0.2.1:4711`, `2001:db8::1`, `[2001:db8::1]:4711`, or quoted as in
+/// `Forwarded`. IPv4-mapped IPv6 addresses come back as IPv4.
+pub fn parse_client_ip(value: &str) -> Option<IpAddr> {
+    let value = value.trim().trim_matches('"');
+    let ip = match value.strip_prefix('[') {
+        Some(rest) => IpAddr::V6(rest.split_once(']')?.0.parse::<Ipv6Addr>().ok()?),
+        None => value.parse::<IpAddr>()
+            .or_else(|_| value.parse::<SocketAddr>().map(|addr| addr.ip()))
+            .ok()?,
+    };
+    Some(ip.to_canonical())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_client_ip_forms() {
+        let v4: IpAddr = "192.0.2.1".parse().unwrap();
+        let v6: IpAddr = "2001:db8::1".parse().unwrap();
+        for value in ["192.0.2.1", " 192.0.2.1:4711", "::ffff:192.0.2.1", "[::ffff:192.0.2.1]:80"] {
+            assert_eq!(parse_client_ip(value), Some(v4), "{}", value);
+        }
+        for value in ["2001:db8::1", "[2001:db8::1]", "\"[2001:db8::1]:4711\""] {
+            assert_eq!(parse_client_ip(value), Some(v6), "{}", value);
+        }
+        assert_eq!(parse_client_ip("unknown"), None);
+        assert_eq!(parse_client_ip("[2001:db8::1"), None);
+    }
+}
@@ -0,0 +1,299 @@
+//! Blueprint packages
+//!
+//! A `.bwpack` file is a tar archive holding a runnable project: the
+//! blueprint with everything it references (handlers, seed data, static
+//! files, cassettes, included blueprint files) and a `bwpack.json` manifest.
+//! The manifest names the blueprint, lists the plugins the blueprint enables
+//! and the SHA-256 checksum of every file, so a package is verified before
+//! it is unpacked and run.
+//!
+//! A package holds the project directory's files except hidden ones (such as
+//! `.git` and the `.backworks` secret key), `target`, `node_modules` and
+//! other packages.
+
+use crate::config::BackworksConfig;
+use crate::error::{BackworksError, Result};
+use crate::plugin::PluginType;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+pub const EXTENSION: &str = "bwpack";
+/// Manifest entry, first in the archive
+pub const MANIFEST: &str = "bwpack.json";
+/// Manifest layout this version writes and reads
+const FORMAT: u32 = 1;
+/// Directories never packaged, besides hidden ones
+const EXCLUDED_DIRS: &[&str] = &["target", "node_modules"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageManifest {
+    pub format: u32,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Backworks version that built the package
+    pub backworks_version: String,
+    pub created_at: DateTime<Utc>,
+    /// Blueprint path inside the package
+    pub blueprint: String,
+    /// Plugins the blueprint enables
+    #[serde(default)]
+    pub plugins: Vec<PluginRequirement>,
+    pub files: Vec<PackagedFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginRequirement {
+    pub name: String,
+    pub plugin_type: PluginType,
+    /// Library of an external plugin, inside the package when relative
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackagedFile {
+    /// Path inside the package, with `/` separators
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// A package read and verified against its manifest
+#[derive(Debug)]
+pub struct Package {
+    pub manifest: PackageManifest,
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+/// Package the project in `root`, whose blueprint is `blueprint`, into
+/// `output`
+pub fn build(root: &Path, blueprint: &Path, config: &BackworksConfig, output: &Path) -> Result<PackageManifest> {
+    let entry = blueprint.strip_prefix(root).ok()
+        .and_then(package_path)
+        .ok_or_else(|| BackworksError::config(format!("Blueprint {} is outside the project directory {}", blueprint.display(), root.display())))?;
+
+    let paths = project_files(root, output)?;
+    let mut files = Vec::new();
+    let mut contents = Vec::new();
+    for (name, path) in paths {
+        let data = std::fs::read(&path)?;
+        files.push(PackagedFile { path: name.clone(), size: data.len() as u64, sha256: checksum(&data) });
+        contents.push((name, data));
+    }
+    if !files.iter().any(|file| file.path == entry) {
+        return Err(BackworksError::config(format!("Blueprint {} is excluded from the package", entry)));
+    }
+
+    let mut plugins: Vec<PluginRequirement> = config.plugins.iter()
+        .filter(|(_, plugin)| plugin.enabled)
+        .map(|(name, plugin)| PluginRequirement { name: name.clone(), plugin_type: plugin.plugin_type.clone(), path: plugin.path.clone() })
+        .collect();
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+    let manifest = PackageManifest {
+        format: FORMAT,
+        name: config.name.clone(),
+        version: config.version.clone(),
+        backworks_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: Utc::now(),
+        blueprint: entry,
+        plugins,
+        files,
+    };
+
+    if let Some(dir) = output.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut archive = tar::Builder::new(std::fs::File::create(output)?);
+    append(&mut archive, MANIFEST, &serde_json::to_vec_pretty(&manifest)?)?;
+    for (name, data) in &contents {
+        append(&mut archive, name, data)?;
+    }
+    archive.into_inner()?;
+    Ok(manifest)
+}
+
+/// Files of the project in `root` a package holds, by their path inside
+/// it, leaving out `output`
+pub(crate) fn project_files(root: &Path, output: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut paths = Vec::new();
+    collect(root, root, output, &mut paths)?;
+    paths.sort();
+    Ok(paths)
+}
+
+fn collect(root: &Path, dir: &Path, output: &Path, paths: &mut Vec<(String, PathBuf)>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let file_type = entry.file_type()?;
+        if name.starts_with('.') || path == output {
+            continue;
+        }
+        if file_type.is_dir() {
+            if !EXCLUDED_DIRS.contains(&name.as_str()) {
+                collect(root, &path, output, paths)?;
+            }
+        } else if file_type.is_file() && path.extension().is_none_or(|extension| extension != EXTENSION) {
+            if let Some(name) = path.strip_prefix(root).ok().and_then(package_path) {
+                paths.push((name, path));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn append(archive: &mut tar::Builder<std::fs::File>, name: &str, data: &[u8]) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    archive.append_data(&mut header, name, data)?;
+    Ok(())
+}
+
+/// `/`-separated relative path, when `path` stays inside its root
+pub(crate) fn package_path(path: &Path) -> Option<String> {
+    let parts: Vec<&str> = path.components()
+        .map(|component| match component {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+pub(crate) fn checksum(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+impl Package {
+    /// Read the package at `path` and verify every file against the manifest
+    pub fn open(path: &Path) -> Result<Self> {
+        let invalid = |message: String| BackworksError::config(format!("Invalid package {}: {}", path.display(), message));
+        let mut archive = tar::Archive::new(std::fs::File::open(path)?);
+        let mut manifest = None;
+        let mut files = BTreeMap::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.to_str().map(String::from)
+                .ok_or_else(|| invalid("non UTF-8 file name".to_string()))?;
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            if name == MANIFEST {
+                manifest = Some(serde_json::from_slice::<PackageManifest>(&data).map_err(|e| invalid(format!("bad manifest: {}", e)))?);
+            } else if package_path(Path::new(&name)).as_deref() == Some(name.as_str()) {
+                files.insert(name, data);
+            } else {
+                return Err(invalid(format!("file '{}' escapes the package", name)));
+            }
+        }
+
+        let manifest = manifest.ok_or_else(|| invalid(format!("no {}", MANIFEST)))?;
+        if manifest.format > FORMAT {
+            return Err(invalid(format!("format {} needs a newer Backworks (this one reads up to {})", manifest.format, FORMAT)));
+        }
+        for file in &manifest.files {
+            let data = files.get(&file.path).ok_or_else(|| invalid(format!("'{}' is missing", file.path)))?;
+            if checksum(data) != file.sha256 {
+                return Err(invalid(format!("checksum mismatch for '{}'", file.path)));
+            }
+        }
+        if let Some(extra) = files.keys().find(|name| !manifest.files.iter().any(|file| &file.path == *name)) {
+            return Err(invalid(format!("'{}' is not in the manifest", extra)));
+        }
+        if !files.contains_key(&manifest.blueprint) {
+            return Err(invalid(format!("blueprint '{}' is missing", manifest.blueprint)));
+        }
+        Ok(Self { manifest, files })
+    }
+
+    /// Write the package's files under `dir`, returning the blueprint path
+    pub fn unpack(&self, dir: &Path) -> Result<PathBuf> {
+        for (name, data) in &self.files {
+            let path = dir.join(name);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, data)?;
+        }
+        Ok(dir.join(&self.manifest.blueprint))
+    }
+
+    /// External plugins whose library is neither in the package nor on this
+    /// machine, for a package unpacked in `dir`
+    pub fn missing_plugins(&self, dir: &Path) -> Vec<&PluginRequirement> {
+        self.manifest.plugins.iter()
+            .filter(|plugin| matches!(plugin.plugin_type, PluginType::External))
+            .filter(|plugin| plugin.path.as_ref().is_none_or(|path| !dir.join(path).exists()))
+            .collect()
+    }
+
+    /// Directory a package is unpacked to for running, unique to its contents
+    pub fn run_dir(&self) -> PathBuf {
+        let digest = checksum(&serde_json::to_vec(&self.manifest.files).unwrap_or_default());
+        let name: String = self.manifest.name.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+            .collect();
+        std::env::temp_dir().join(format!("backworks-{}-{}", name, &digest[..12]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_round_trip_and_verification() {
+        let root = std::env::temp_dir().join(format!("backworks_package_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("handlers")).unwrap();
+        std::fs::create_dir_all(root.join(".backworks")).unwrap();
+        std::fs::create_dir_all(root.join("target/debug")).unwrap();
+        let blueprint = r#"
+name: Shop API
+version: "1.2"
+plugins:
+  geo: { enabled: true, plugin_type: external, path: plugins/libgeo.so }
+  audit: { enabled: false }
+endpoints:
+  users: { path: /users, runtime: { language: javascript, handler: ./handlers/users.js } }
+"#;
+        std::fs::write(root.join("backworks.yaml"), blueprint).unwrap();
+        std::fs::write(root.join("handlers/users.js"), "function handler() {}").unwrap();
+        std::fs::write(root.join(".backworks/secret.key"), "secret").unwrap();
+        std::fs::write(root.join("target/debug/app"), "binary").unwrap();
+        let config: BackworksConfig = serde_yaml::from_str(blueprint).unwrap();
+        let output = root.join("dist/shop.bwpack");
+
+        let manifest = build(&root, &root.join("backworks.yaml"), &config, &output).unwrap();
+        assert_eq!(manifest.files.iter().map(|f| f.path.as_str()).collect::<Vec<_>>(), vec!["backworks.yaml", "handlers/users.js"]);
+        assert_eq!((manifest.blueprint.as_str(), manifest.version.as_deref()), ("backworks.yaml", Some("1.2")));
+        assert_eq!(manifest.plugins.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), vec!["geo"]);
+
+        let package = Package::open(&output).unwrap();
+        let dir = root.join("unpacked");
+        assert_eq!(package.unpack(&dir).unwrap(), dir.join("backworks.yaml"));
+        assert_eq!(std::fs::read_to_string(dir.join("handlers/users.js")).unwrap(), "function handler() {}");
+        assert_eq!(package.missing_plugins(&dir).len(), 1);
+        assert!(package.run_dir().to_string_lossy().contains("backworks-shop-api-"));
+
+        // A file changed after packaging fails verification
+        let mut tampered = package.manifest.clone();
+        tampered.files[1].sha256 = checksum(b"something else");
+        let mut archive = tar::Builder::new(std::fs::File::create(&output).unwrap());
+        append(&mut archive, MANIFEST, &serde_json::to_vec(&tampered).unwrap()).unwrap();
+        for (name, data) in &package.files {
+            append(&mut archive, name, data).unwrap();
+        }
+        archive.into_inner().unwrap();
+        let error = Package::open(&output).unwrap_err().to_string();
+        assert!(error.ends_with("checksum mismatch for 'handlers/users.js'"), "{}", error);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,178 @@
+//! Panic reports and recovery
+//!
+//! Panics are reported as one JSON object per line on stderr, in place of
+//! the default hook's free text, with a backtrace when `logging.backtraces`
+//! is on. A panic while serving a request, in a handler, plugin or plugin
+//! hook, is caught by [`recover_panics`]: the report names the request, the
+//! client gets a `500` carrying the request ID, `backworks_panics_total` is
+//! incremented and the server carries on.
+
+use crate::server::{AppState, MatchedEndpoint};
+use axum::extract::{Request, State};
+use axum::http::{HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::panic::{AssertUnwindSafe, PanicHookInfo};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+use std::task::Poll;
+
+/// Header carrying the ID a panic report is filed under; taken from the
+/// request when the client sends one
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+static INSTALL_HOOK: Once = Once::new();
+static BACKTRACES: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// Whether the panic being raised will be caught by [`catch`]
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+    /// Report of the panic [`catch`] is about to catch
+    static CAUGHT: RefCell<Option<PanicReport>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PanicReport {
+    pub timestamp: DateTime<Utc>,
+    pub level: &'static str,
+    pub event: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backtrace: Option<String>,
+}
+
+impl PanicReport {
+    fn new(message: String, location: Option<String>) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            level: "error",
+            event: "panic",
+            message,
+            location,
+            thread: std::thread::current().name().map(String::from),
+            request_id: None,
+            method: None,
+            path: None,
+            endpoint: None,
+            backtrace: BACKTRACES.load(Ordering::Relaxed).then(|| Backtrace::force_capture().to_string()),
+        }
+    }
+
+    /// Write the report as one line of JSON on stderr
+    pub fn write(&self) {
+        if let Ok(line) = serde_json::to_string(self) {
+            eprintln!("{}", line);
+        }
+    }
+}
+
+/// Replace the panic hook with one writing JSON reports; `backtraces` can be
+/// changed by later calls
+pub fn install(backtraces: bool) {
+    BACKTRACES.store(backtraces, Ordering::Relaxed);
+    INSTALL_HOOK.call_once(|| std::panic::set_hook(Box::new(hook)));
+}
+
+fn hook(info: &PanicHookInfo<'_>) {
+    let report = PanicReport::new(message(info.payload()), info.location().map(ToString::to_string));
+    if CATCHING.get() {
+        CAUGHT.set(Some(report));
+    } else {
+        report.write();
+    }
+}
+
+fn message(payload: &(dyn Any + Send)) -> String {
+    match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        _ => "Box<dyn Any>".to_string(),
+    }
+}
+
+/// Run `future`, turning a panic in any of its polls into the panic's
+/// report, which is left for the caller to write
+pub async fn catch<F: Future>(future: F) -> Result<F::Output, PanicReport> {
+    let mut future = std::pin::pin!(future);
+    std::future::poll_fn(move |cx| {
+        let outer = CATCHING.replace(true);
+        let polled = std::panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx)));
+        CATCHING.set(outer);
+        match polled {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(CAUGHT.take().unwrap_or_else(|| PanicReport::new(message(&*payload), None)))),
+        }
+    }).await
+}
+
+// Answer requests whose handling panicked with a 500 and report the panic
+pub async fn recover_panics(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let request_id = request.headers().get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+        .map(String::from)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let endpoint = crate::server::matched_endpoint(&state, &request).map(|MatchedEndpoint(name)| name);
+
+    let mut report = match catch(next.run(request)).await {
+        Ok(response) => return response,
+        Err(report) => report,
+    };
+    state.metrics.record_panic(endpoint.as_deref());
+    report.message = state.security.scrub(report.message);
+    report.request_id = Some(request_id.clone());
+    report.method = Some(method);
+    report.path = Some(state.security.scrub(path));
+    report.endpoint = endpoint;
+    report.write();
+
+    let body = serde_json::json!({ "error": "Internal server error", "request_id": request_id });
+    let mut response = (StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response();
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_panics_become_reports() {
+        install(false);
+        let report = catch(async {
+            tokio::task::yield_now().await;
+            panic!("handler exploded: {}", 42);
+        }).await.unwrap_err();
+        assert_eq!(report.message, "handler exploded: 42");
+        assert!(report.location.unwrap().starts_with("src/panic.rs:"));
+        assert!(report.backtrace.is_none());
+
+        let nested = catch(async { catch(async { panic!("inner") }).await.unwrap_err().message }).await;
+        assert_eq!(nested.unwrap(), "inner");
+        assert_eq!(catch(async { 7 }).await.unwrap(), 7);
+        assert!(!CATCHING.get());
+    }
+}
@@ -0,0 +1,491 @@
+//! Per-endpoint middleware pipelines
+//!
+//! Endpoints list middleware by name under `middleware`; they run in that
+//! order after the global request pipeline (authentication, plugin
+//! `before_request` hooks) and before the handler, each able to answer the
+//! request itself or pass it on with [`Next::run`].
+//!
+//! Middleware is looked up in a [`MiddlewareRegistry`]. It starts with the
+//! built-ins below; plugins add their own in
+//! [`BackworksPlugin::register_middleware`](crate::plugin::BackworksPlugin::register_middleware).
+//!
+//! - `auth`: requires an authenticated identity; `roles` restricts it further
+//! - `rate_limit`: at most `requests` per `window` seconds per caller
+//! - `cache`: serves successful `GET` responses for `ttl` seconds, from memory
+//!   or from the store of the plugin the blueprint's `cache.type` names
+
+use crate::auth::AuthContext;
+use crate::config::MiddlewareSpec;
+use crate::error::{BackworksError, BackworksResult, ErrorSource, RequestError};
+use crate::origin::RequestOrigin;
+use crate::plugin::CacheHandle;
+use base64::Engine;
+use crate::server::MatchedEndpoint;
+use async_trait::async_trait;
+use axum::body::{Body, Bytes};
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A step of an endpoint's middleware pipeline
+#[async_trait]
+pub trait EndpointMiddleware: Send + Sync {
+    /// Handle `request`, usually by passing it on with `next.run(request)`
+    async fn handle(&self, request: Request, next: Next<'_>) -> Response;
+
+    /// Drop whatever the middleware of `endpoint` keeps between requests,
+    /// such as cached responses; returns how many entries were dropped
+    async fn flush(&self, endpoint: &str) -> usize {
+        let _ = endpoint;
+        0
+    }
+}
+
+/// The rest of the pipeline, ending in the endpoint handler
+pub struct Next<'a> {
+    rest: &'a [Arc<dyn EndpointMiddleware>],
+    handler: axum::middleware::Next,
+}
+
+impl Next<'_> {
+    pub async fn run(self, request: Request) -> Response {
+        match self.rest.split_first() {
+            Some((middleware, rest)) => middleware.handle(request, Next { rest, handler: self.handler }).await,
+            None => self.handler.run(request).await,
+        }
+    }
+}
+
+/// Builds a middleware instance for one endpoint from its settings (`null`
+/// when the endpoint names it without settings)
+pub type MiddlewareFactory = Arc<dyn Fn(&Value) -> BackworksResult<Arc<dyn EndpointMiddleware>> + Send + Sync>;
+
+/// Middleware available to endpoints, by name
+#[derive(Clone)]
+pub struct MiddlewareRegistry {
+    factories: HashMap<String, MiddlewareFactory>,
+}
+
+impl Default for MiddlewareRegistry {
+    /// The built-in middleware
+    fn default() -> Self {
+        let mut registry = Self { factories: HashMap::new() };
+        registry.register("auth", |config| Ok(Arc::new(RequireAuth { config: settings("auth", config)? })));
+        registry.register("rate_limit", |config| Ok(Arc::new(RateLimit::new(settings("rate_limit", config)?))));
+        registry.register("cache", |config| Ok(Arc::new(ResponseCache::new(settings("cache", config)?, None))));
+        registry
+    }
+}
+
+impl MiddlewareRegistry {
+    /// Make `name` available to endpoints, replacing any middleware of that
+    /// name
+    pub fn register<F>(&mut self, name: impl Into<String>, factory: F)
+    where
+        F: Fn(&Value) -> BackworksResult<Arc<dyn EndpointMiddleware>> + Send + Sync + 'static,
+    {
+        let name = name.into();
+        if self.factories.insert(name.clone(), Arc::new(factory)).is_some() {
+            tracing::warn!("Middleware '{}' registered twice; the last registration wins", name);
+        }
+    }
+
+    /// Keep responses of the `cache` middleware in a plugin's store rather
+    /// than in memory
+    pub fn with_cache_store(mut self, store: CacheHandle) -> Self {
+        self.factories.insert("cache".to_string(), Arc::new(move |config: &Value| -> BackworksResult<Arc<dyn EndpointMiddleware>> {
+            Ok(Arc::new(ResponseCache::new(settings("cache", config)?, Some(store.clone()))))
+        }));
+        self
+    }
+
+    /// Registered names, sorted
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.factories.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    /// The pipeline of `endpoint`, or `None` when it declares no middleware
+    pub fn build(&self, endpoint: &str, specs: &[MiddlewareSpec]) -> BackworksResult<Option<Arc<Pipeline>>> {
+        if specs.is_empty() {
+            return Ok(None);
+        }
+        let steps = specs.iter()
+            .map(|spec| {
+                let factory = self.factories.get(&spec.name).ok_or_else(|| BackworksError::config(format!(
+                    "Endpoint '{}' uses unknown middleware '{}' (available: {})",
+                    endpoint, spec.name, self.names().join(", ")
+                )))?;
+                factory(&spec.config).map_err(|e| BackworksError::config(format!(
+                    "Endpoint '{}' middleware '{}': {}", endpoint, spec.name, e
+                )))
+            })
+            .collect::<BackworksResult<Vec<_>>>()?;
+
+        Ok(Some(Arc::new(Pipeline { endpoint: endpoint.to_string(), steps })))
+    }
+}
+
+/// The middleware of one endpoint, in declared order
+pub struct Pipeline {
+    endpoint: String,
+    steps: Vec<Arc<dyn EndpointMiddleware>>,
+}
+
+impl Pipeline {
+    pub async fn run(&self, mut request: Request, handler: axum::middleware::Next) -> Response {
+        request.extensions_mut().insert(MatchedEndpoint(self.endpoint.clone()));
+        let mut response = Next { rest: &self.steps, handler }.run(request).await;
+        // Responses produced by a middleware still belong to the endpoint
+        if response.extensions().get::<MatchedEndpoint>().is_none() {
+            response.extensions_mut().insert(MatchedEndpoint(self.endpoint.clone()));
+        }
+        response
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Flush every step; returns how many entries were dropped
+    pub async fn flush(&self) -> usize {
+        let mut flushed = 0;
+        for step in &self.steps {
+            flushed += step.flush(&self.endpoint).await;
+        }
+        flushed
+    }
+}
+
+/// The pipelines routes were built with, by endpoint, so the admin API can
+/// flush them
+#[derive(Clone, Default)]
+pub struct Pipelines(Arc<Mutex<HashMap<String, Arc<Pipeline>>>>);
+
+impl Pipelines {
+    pub fn insert(&self, pipeline: Arc<Pipeline>) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).insert(pipeline.endpoint.clone(), pipeline);
+    }
+
+    /// Flush the pipeline of `endpoint`, or every pipeline; returns how many
+    /// entries were dropped
+    pub async fn flush(&self, endpoint: Option<&str>) -> usize {
+        let pipelines: Vec<Arc<Pipeline>> = self.0.lock().unwrap_or_else(|e| e.into_inner()).values()
+            .filter(|pipeline| endpoint.is_none_or(|endpoint| pipeline.endpoint == endpoint))
+            .cloned()
+            .collect();
+        let mut flushed = 0;
+        for pipeline in pipelines {
+            flushed += pipeline.flush().await;
+        }
+        flushed
+    }
+}
+
+/// Route layer running an endpoint's pipeline
+pub async fn run_pipeline(
+    State(pipeline): State<Arc<Pipeline>>,
+    request: Request,
+    next: axum::middleware::Next,
+) -> Response {
+    pipeline.run(request, next).await
+}
+
+/// Settings of a built-in middleware; a bare name uses the defaults
+fn settings<T: DeserializeOwned + Default>(name: &str, config: &Value) -> BackworksResult<T> {
+    if config.is_null() {
+        return Ok(T::default());
+    }
+    serde_json::from_value(config.clone())
+        .map_err(|e| BackworksError::config(format!("invalid {} settings: {}", name, e)))
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AuthSettings {
+    /// Any one of these roles is required
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+struct RequireAuth {
+    config: AuthSettings,
+}
+
+#[async_trait]
+impl EndpointMiddleware for RequireAuth {
+    async fn handle(&self, request: Request, next: Next<'_>) -> Response {
+        let Some(auth) = request.extensions().get::<AuthContext>() else {
+            return BackworksError::unauthorized("Authentication required").into_response();
+        };
+        if !self.config.roles.is_empty() && !auth.roles.iter().any(|role| self.config.roles.contains(role)) {
+            return BackworksError::forbidden(format!("Requires one of the roles: {}", self.config.roles.join(", "))).into_response();
+        }
+        next.run(request).await
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RateLimitSettings {
+    requests: u32,
+    /// Seconds
+    window: u64,
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        Self { requests: 60, window: 60 }
+    }
+}
+
+/// Fixed-window limit per caller: the authenticated subject, else the
+/// client IP
+struct RateLimit {
+    requests: u32,
+    window: Duration,
+    windows: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl RateLimit {
+    fn new(settings: RateLimitSettings) -> Self {
+        Self {
+            requests: settings.requests,
+            window: Duration::from_secs(settings.window.max(1)),
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count a request from `caller`; `Err` holds the time until its window
+    /// resets
+    fn acquire(&self, caller: String, now: Instant) -> Result<(), Duration> {
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        if windows.len() > 10_000 {
+            windows.retain(|_, (started, _)| now.duration_since(*started) < self.window);
+        }
+        let (started, count) = windows.entry(caller).or_insert((now, 0));
+        if now.duration_since(*started) >= self.window {
+            *started = now;
+            *count = 0;
+        }
+        if *count >= self.requests {
+            return Err(self.window - now.duration_since(*started));
+        }
+        *count += 1;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EndpointMiddleware for RateLimit {
+    async fn handle(&self, request: Request, next: Next<'_>) -> Response {
+        let caller = request.extensions().get::<AuthContext>()
+            .map(|auth| format!("subject:{}", auth.subject))
+            .or_else(|| request.extensions().get::<RequestOrigin>().and_then(|origin| origin.key("ip")))
+            .unwrap_or_default();
+
+        match self.acquire(caller, Instant::now()) {
+            Ok(()) => next.run(request).await,
+            Err(retry_after) => {
+                let message = "Rate limit exceeded";
+                let mut response = (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    axum::Json(serde_json::json!({"error": message, "status": 429})),
+                ).into_response();
+                response.headers_mut().insert("retry-after", HeaderValue::from(retry_after.as_secs().max(1)));
+                response.extensions_mut().insert(RequestError::new(StatusCode::TOO_MANY_REQUESTS, message, ErrorSource::Framework));
+                response
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CacheSettings {
+    /// Seconds
+    ttl: u64,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self { ttl: 60 }
+    }
+}
+
+struct CachedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    expires: Instant,
+}
+
+/// A cached response as kept in a plugin's store
+#[derive(Serialize, Deserialize)]
+struct StoredResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    /// Base64
+    body: String,
+}
+
+/// Successful `GET` responses, keyed by path and query
+struct ResponseCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, CachedResponse>>,
+    /// Keeps the responses instead of `entries`, keyed by endpoint too
+    store: Option<CacheHandle>,
+}
+
+impl ResponseCache {
+    fn new(settings: CacheSettings, store: Option<CacheHandle>) -> Self {
+        Self { ttl: Duration::from_secs(settings.ttl), entries: Mutex::new(HashMap::new()), store }
+    }
+
+    fn lookup(&self, key: &str, now: Instant) -> Option<Response> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, entry| entry.expires > now);
+        let entry = entries.get(key)?;
+        Some(cached_response(entry.status, entry.headers.clone(), entry.body.clone()))
+    }
+
+    async fn load(&self, store: &CacheHandle, key: &str) -> Option<Response> {
+        let stored = match store.get(key).await {
+            Ok(stored) => stored?,
+            Err(e) => {
+                tracing::warn!("Cache store {} failed to read {}: {}", store.plugin(), key, e);
+                return None;
+            }
+        };
+        let stored: StoredResponse = serde_json::from_slice(&stored).ok()?;
+        let body = base64::engine::general_purpose::STANDARD.decode(stored.body).ok()?;
+        let mut headers = HeaderMap::new();
+        for (name, value) in stored.headers {
+            headers.append(HeaderName::try_from(name).ok()?, HeaderValue::try_from(value).ok()?);
+        }
+        Some(cached_response(StatusCode::from_u16(stored.status).ok()?, headers, Bytes::from(body)))
+    }
+
+    async fn save(&self, key: String, status: StatusCode, headers: &HeaderMap, body: &Bytes) {
+        let Some(ref store) = self.store else {
+            self.entries.lock().unwrap_or_else(|e| e.into_inner()).insert(key, CachedResponse {
+                status,
+                headers: headers.clone(),
+                body: body.clone(),
+                expires: Instant::now() + self.ttl,
+            });
+            return;
+        };
+        let stored = StoredResponse {
+            status: status.as_u16(),
+            headers: headers.iter()
+                .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+                .collect(),
+            body: base64::engine::general_purpose::STANDARD.encode(body),
+        };
+        let stored = serde_json::to_vec(&stored).expect("responses serialize");
+        if let Err(e) = store.set(&key, stored, self.ttl).await {
+            tracing::warn!("Cache store {} failed to write {}: {}", store.plugin(), key, e);
+        }
+    }
+}
+
+fn cached_response(status: StatusCode, headers: HeaderMap, body: Bytes) -> Response {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    *response.headers_mut() = headers;
+    response.headers_mut().insert("x-cache", HeaderValue::from_static("hit"));
+    response
+}
+
+/// Prefix of the keys an endpoint's responses are stored under
+fn store_prefix(endpoint: &str) -> String {
+    format!("response:{}:", endpoint)
+}
+
+#[async_trait]
+impl EndpointMiddleware for ResponseCache {
+    async fn handle(&self, request: Request, next: Next<'_>) -> Response {
+        if request.method() != Method::GET {
+            return next.run(request).await;
+        }
+        let path = request.uri().path_and_query().map(|pq| pq.to_string()).unwrap_or_default();
+        let key = match self.store {
+            Some(ref store) => {
+                let endpoint = request.extensions().get::<MatchedEndpoint>().map(|MatchedEndpoint(name)| name.as_str()).unwrap_or_default();
+                let key = format!("{}{}", store_prefix(endpoint), path);
+                if let Some(response) = self.load(store, &key).await {
+                    return response;
+                }
+                key
+            }
+            None => {
+                if let Some(response) = self.lookup(&path, Instant::now()) {
+                    return response;
+                }
+                path
+            }
+        };
+
+        let response = next.run(request).await;
+        if !response.status().is_success() {
+            return response;
+        }
+        let (mut parts, body) = response.into_parts();
+        let body = match axum::body::to_bytes(body, usize::MAX).await {
+            Ok(body) => body,
+            Err(e) => return BackworksError::server(format!("cannot buffer response for caching: {}", e)).into_response(),
+        };
+        self.save(key, parts.status, &parts.headers, &body).await;
+        parts.headers.insert("x-cache", HeaderValue::from_static("miss"));
+        Response::from_parts(parts, Body::from(body))
+    }
+
+    async fn flush(&self, endpoint: &str) -> usize {
+        let Some(ref store) = self.store else {
+            return self.entries.lock().unwrap_or_else(|e| e.into_inner()).drain().count();
+        };
+        store.clear(&store_prefix(endpoint)).await.unwrap_or_else(|e| {
+            tracing::warn!("Cache store {} failed to flush {}: {}", store.plugin(), endpoint, e);
+            0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_middleware_and_bad_settings_are_rejected() {
+        let registry = MiddlewareRegistry::default();
+        assert!(registry.build("users", &[]).unwrap().is_none());
+
+        let error = registry.build("users", &[MiddlewareSpec::named("audit")]).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Configuration error: Endpoint 'users' uses unknown middleware 'audit' (available: auth, cache, rate_limit)"
+        );
+
+        let spec = MiddlewareSpec { name: "rate_limit".to_string(), config: serde_json::json!({ "requests": "many" }) };
+        assert!(registry.build("users", &[spec]).err().unwrap().to_string().contains("invalid rate_limit settings"));
+    }
+
+    #[test]
+    fn test_rate_limit_windows() {
+        let limit = RateLimit::new(RateLimitSettings { requests: 2, window: 10 });
+        let start = Instant::now();
+        assert!(limit.acquire("a".to_string(), start).is_ok());
+        assert!(limit.acquire("a".to_string(), start).is_ok());
+        assert_eq!(limit.acquire("a".to_string(), start + Duration::from_secs(4)), Err(Duration::from_secs(6)));
+        assert!(limit.acquire("b".to_string(), start).is_ok());
+        assert!(limit.acquire("a".to_string(), start + Duration::from_secs(10)).is_ok());
+    }
+}
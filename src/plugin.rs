@@ -1,19 +1,36 @@
 use async_trait::async_trait;
-use crate::error::BackworksResult;
-use crate::resilience::{ResilientPluginExecutor, ResilientPluginConfig, PluginMetrics};
-use axum::{http::Request, response::Response};
+use crate::error::{BackworksResult, RequestError};
+use crate::balancer::LoadBalancerRegistry;
+use crate::pipeline::MiddlewareRegistry;
+use crate::resilience::{
+    CircuitBreakerError, PluginMetrics, PluginResourceLimits, ResilientExecutionError,
+    ResilientPluginConfig, ResilientPluginExecutor,
+};
+use axum::{http::{HeaderMap, HeaderName, HeaderValue, Request, StatusCode}, response::Response};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tokio::sync::RwLock;
 use crate::config::PluginDiscoveryConfig;
 
+pub mod cache;
+pub mod database;
 pub mod dynamic;
 pub mod discovery;
-pub use dynamic::{DynamicPluginLoader, PluginMetadata};
+mod ordering;
+pub mod process;
+pub mod registry;
+pub mod schema;
+pub mod routes;
+pub use cache::{CacheBackend, CacheHandle};
+pub use database::{Column, ColumnType, DatabaseHandle, DatabasePlugin, DatabaseSettings, ListQuery, PoolSettings, PoolStats, PreparedQuery, QueryResult, Row, RowPage, SortKey, TableSchema};
+pub use dynamic::{DynamicPluginLoader, PluginMetadata, PLUGIN_ABI_VERSION};
 pub use discovery::{PluginDiscovery, PluginRegistry};
+use ordering::PluginTable;
+pub use process::{ProcessPlugin, ProcessPluginBuilder};
+pub use routes::PluginRoutes;
 
 /// Configuration for a plugin
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -27,8 +44,23 @@ pub struct PluginConfig {
     #[serde(default)]
     pub config: Value,
     
-    // For external plugins
+    // For external plugins; the command for process plugins
     pub path: Option<String>,
+    
+    // For process plugins
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_restarts: Option<u32>,
+    
+    /// How long a call to a process plugin may take, e.g. `"500ms"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+    
+    /// Circuit breaker, timeout and bulkhead policy for calls into the plugin
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resilience: Option<ResilientPluginConfig>,
 }
 
 impl Default for PluginConfig {
@@ -38,6 +70,10 @@ impl Default for PluginConfig {
             plugin_type: PluginType::Builtin,
             config: Value::Null,
             path: None,
+            args: Vec::new(),
+            max_restarts: None,
+            timeout: None,
+            resilience: None,
         }
     }
 }
@@ -45,16 +81,15 @@ impl Default for PluginConfig {
 /// Type of plugin
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "lowercase")]
+#[derive(Default)]
 pub enum PluginType {
+    #[default]
     Builtin,
     External,
+    /// A separate process speaking JSON-RPC over stdio
+    Process,
 }
 
-impl Default for PluginType {
-    fn default() -> Self {
-        PluginType::Builtin
-    }
-}
 
 /// Plugin trait that all Backworks plugins must implement
 #[async_trait]
@@ -123,6 +158,18 @@ pub trait BackworksPlugin: Send + Sync {
         false // Most plugins are non-critical by default
     }
     
+    /// Plugins whose hooks must run after this plugin's `before_request`
+    /// hook (and before its `after_response` hook)
+    fn runs_before(&self) -> Vec<String> {
+        Vec::new()
+    }
+    
+    /// Plugins this plugin needs; startup fails without them, and their
+    /// `before_request` hooks run first
+    fn requires(&self) -> Vec<String> {
+        Vec::new()
+    }
+    
     
     /// Hook called before processing each request
     async fn before_request(&self, request: &mut Request<axum::body::Body>) -> BackworksResult<()> {
@@ -136,17 +183,135 @@ pub trait BackworksPlugin: Send + Sync {
         Ok(())
     }
     
+    /// Hook called for every error response before `after_response`.
+    ///
+    /// Plugins may rewrite the response (e.g. to map errors to a house
+    /// format); `error` describes the final failure and its origin.
+    async fn on_error(&self, error: &RequestError, response: &mut Response<axum::body::Body>) -> BackworksResult<()> {
+        let _ = (error, response); // Default implementation does nothing
+        Ok(())
+    }
+    
     /// Hook called when configuration changes
     async fn on_config_reload(&self, config: &Value) -> BackworksResult<()> {
         let _ = config; // Default implementation does nothing
         Ok(())
     }
     
+    /// Add middleware endpoints can list by name under `middleware`
+    fn register_middleware(&self, registry: &mut MiddlewareRegistry) {
+        let _ = registry; // Default implementation adds none
+    }
+    
+    /// Add load-balancing strategies proxy endpoints can name under
+    /// `load_balancing.algorithm`
+    fn register_load_balancers(&self, registry: &mut LoadBalancerRegistry) {
+        let _ = registry; // Default implementation adds none
+    }
+    
     /// Hook called for custom endpoint processing
     async fn process_endpoint_data(&self, _endpoint: &str, _method: &str, _data: &str) -> BackworksResult<Option<String>> {
         Ok(None) // Default implementation doesn't handle endpoints
     }
     
+    /// Run the job `action` names, such as a schedule's, with the job's
+    /// `params`; the output lands in the schedule's run history
+    async fn run_action(&self, action: &str, params: &Value) -> BackworksResult<Value> {
+        let _ = params; // Default implementation runs none
+        Err(crate::error::BackworksError::plugin(format!("Plugin {} has no action '{}'", self.name(), action)))
+    }
+    
+    /// JSON Schema of the plugin's `config` block, checked before the
+    /// plugin is initialized and whenever the blueprint is validated
+    fn config_schema(&self) -> Option<Value> {
+        None // Default implementation accepts any config
+    }
+    
+    /// Routes the plugin adds to the API server, next to the endpoints
+    fn routes(&self) -> PluginRoutes {
+        PluginRoutes::new() // Default implementation adds none
+    }
+    
+    /// Pages and APIs the plugin adds to the dashboard, below
+    /// `/plugins/{name}`
+    fn dashboard_routes(&self) -> PluginRoutes {
+        PluginRoutes::new() // Default implementation adds none
+    }
+    
+    /// Serve endpoints that name this plugin under `plugin`
+    fn endpoint_handler(&self) -> Option<&dyn EndpointHandlerPlugin> {
+        None // Default implementation serves none
+    }
+    
+    /// Tables endpoints with `database.auto_crud` are served from
+    fn database(&self) -> Option<&dyn DatabasePlugin> {
+        None // Default implementation has none
+    }
+    
+    /// Store the `cache` middleware keeps responses in, when the blueprint's
+    /// `cache.type` names the plugin
+    fn cache(&self) -> Option<&dyn CacheBackend> {
+        None // Default implementation has none
+    }
+    
+}
+
+/// Capability of a plugin to answer `mode: plugin` endpoints itself
+///
+/// A plugin offers it through [`BackworksPlugin::endpoint_handler`]:
+/// returning `Some(self)` routes every endpoint the plugin claims to
+/// [`EndpointHandlerPlugin::handle_endpoint`].
+#[async_trait]
+pub trait EndpointHandlerPlugin: Send + Sync {
+    /// Whether the plugin serves `endpoint`; by default every endpoint
+    /// naming the plugin
+    fn claims(&self, endpoint: &str) -> bool {
+        let _ = endpoint;
+        true
+    }
+    
+    /// Answer a request to `endpoint`
+    async fn handle_endpoint(&self, endpoint: &str, request: &crate::server::RequestData) -> BackworksResult<EndpointResponse>;
+}
+
+/// A full response a plugin answers an endpoint with
+#[derive(Debug, Clone)]
+pub struct EndpointResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Value,
+}
+
+impl EndpointResponse {
+    pub fn new(status: StatusCode, body: Value) -> Self {
+        Self { status, headers: HeaderMap::new(), body }
+    }
+    
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+    
+    /// The response as structured handler output
+    pub fn to_output(&self) -> String {
+        let headers: serde_json::Map<String, Value> = self.headers.iter()
+            .filter_map(|(name, value)| Some((name.to_string(), Value::String(value.to_str().ok()?.to_string()))))
+            .collect();
+        serde_json::json!({"status": self.status.as_u16(), "headers": headers, "body": self.body}).to_string()
+    }
+}
+
+/// The outcome of a plugin call made through its circuit breaker, keeping
+/// the plugin's own error
+fn resilient_result<T>(plugin_name: &str, result: Result<T, ResilientExecutionError>) -> BackworksResult<T> {
+    match result {
+        Ok(value) => Ok(value),
+        Err(ResilientExecutionError::CircuitBreakerError(CircuitBreakerError::PluginError(err))) => Err(err),
+        Err(ResilientExecutionError::CircuitBreakerError(CircuitBreakerError::Open(_))) => Err(
+            crate::error::BackworksError::unavailable(format!("Plugin {} is failing and not called for now", plugin_name))
+        ),
+        Err(ResilientExecutionError::PluginNotRegistered(name)) => Err(crate::error::BackworksError::PluginNotFound(name)),
+    }
 }
 
 /// Plugin health status
@@ -158,6 +323,7 @@ pub struct PluginHealth {
 }
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum HealthStatus {
     Healthy,
     Degraded,
@@ -167,19 +333,51 @@ pub enum HealthStatus {
 /// Enhanced plugin manager with resilience features
 #[derive(Clone)]
 pub struct PluginManager {
-    plugins: Arc<RwLock<HashMap<String, Arc<dyn BackworksPlugin>>>>,
+    plugins: Arc<RwLock<PluginTable>>,
     configs: Arc<RwLock<HashMap<String, Value>>>,
     resilient_executor: Arc<ResilientPluginExecutor>,
     dynamic_loader: Arc<DynamicPluginLoader>,
+    /// Plugins loaded from a library, by plugin name
+    external: Arc<RwLock<HashMap<String, ExternalPlugin>>>,
+    /// Resilience policy each plugin was registered with, restored when the
+    /// blueprint stops setting one
+    base_policies: Arc<RwLock<HashMap<String, ResilientPluginConfig>>>,
+}
+
+/// Where an external plugin was loaded from
+#[derive(Debug, Clone)]
+struct ExternalPlugin {
+    path: PathBuf,
+    library: String,
+}
+
+/// The policy `plugin` runs under; without explicit limits the plugin's own
+/// execution budget applies
+fn resolve_policy(plugin: &dyn BackworksPlugin, policy: Option<ResilientPluginConfig>) -> ResilientPluginConfig {
+    let mut policy = policy.unwrap_or_default();
+    policy
+        .resource_limits
+        .get_or_insert_with(PluginResourceLimits::default)
+        .max_execution_time
+        .get_or_insert(plugin.max_execution_time());
+    policy
+}
+
+impl std::fmt::Debug for PluginManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PluginManager").finish_non_exhaustive()
+    }
 }
 
 impl PluginManager {
     pub fn new() -> Self {
         Self {
-            plugins: Arc::new(RwLock::new(HashMap::new())),
+            plugins: Arc::new(RwLock::new(PluginTable::default())),
             configs: Arc::new(RwLock::new(HashMap::new())),
             resilient_executor: Arc::new(ResilientPluginExecutor::new()),
             dynamic_loader: Arc::new(DynamicPluginLoader::new()),
+            external: Arc::new(RwLock::new(HashMap::new())),
+            base_policies: Arc::new(RwLock::new(HashMap::new())),
         }
     }
     
@@ -192,11 +390,22 @@ impl PluginManager {
     ) -> BackworksResult<()> {
         let name = plugin.name().to_string();
         
+        // Refuse ordering constraints that cannot be met before initializing
+        self.plugins.read().await.order_with(&name, &plugin)?;
+        
+        // Refuse config the plugin's schema does not allow
+        if let Some(schema) = plugin.config_schema() {
+            schema::register_config_schema(&name, schema)?;
+        }
+        if let Some(config) = config.as_ref() {
+            schema::validate_plugin_config(&name, config)?;
+        }
+        
         // Register with resilient executor
-        self.resilient_executor.register_plugin(
-            name.clone(),
-            resilience_config.unwrap_or_default(),
-        ).await;
+        let resilience_config = resolve_policy(plugin.as_ref(), resilience_config);
+        let critical = resilience_config.is_critical || plugin.is_critical();
+        self.base_policies.write().await.insert(name.clone(), resilience_config.clone());
+        self.resilient_executor.register_plugin(name.clone(), resilience_config).await;
         
         // Initialize the plugin if config is provided
         if let Some(config) = config.as_ref() {
@@ -207,7 +416,7 @@ impl PluginManager {
             
             if let Err(err) = result {
                 tracing::error!("🔴 Failed to initialize plugin {}: {:?}", name, err);
-                if plugin.is_critical() {
+                if critical {
                     return Err(crate::error::BackworksError::PluginInitializationFailed(name));
                 }
                 // Non-critical plugins can fail to initialize without affecting the system
@@ -216,7 +425,7 @@ impl PluginManager {
         }
         
         // Store plugin and config
-        self.plugins.write().await.insert(name.clone(), plugin);
+        self.plugins.write().await.insert(name.clone(), plugin)?;
         if let Some(config) = config {
             self.configs.write().await.insert(name.clone(), config);
         }
@@ -225,6 +434,29 @@ impl PluginManager {
         Ok(())
     }
     
+    /// Apply the resilience policies a blueprint sets, by plugin name, to
+    /// registered plugins without restarting them; `None` restores the
+    /// policy the plugin was registered with
+    pub async fn apply_resilience_policies(&self, policies: HashMap<String, Option<ResilientPluginConfig>>) {
+        let plugins = self.plugins.read().await;
+        let base_policies = self.base_policies.read().await;
+        for (name, policy) in policies {
+            let Some(plugin) = plugins.get(&name) else {
+                continue;
+            };
+            let policy = match policy {
+                Some(policy) => resolve_policy(plugin.as_ref(), Some(policy)),
+                None => match base_policies.get(&name) {
+                    Some(policy) => policy.clone(),
+                    None => resolve_policy(plugin.as_ref(), None),
+                },
+            };
+            if self.resilient_executor.reconfigure_plugin(&name, policy).await {
+                tracing::debug!("🔌 Applied resilience policy to plugin {}", name);
+            }
+        }
+    }
+    
     /// Load and register an external plugin from a file path
     pub async fn load_external_plugin<P: AsRef<Path>>(
         &self, 
@@ -232,9 +464,72 @@ impl PluginManager {
         config: Option<Value>,
         resilience_config: Option<ResilientPluginConfig>
     ) -> BackworksResult<()> {
+        let path = path.as_ref();
         let plugin = self.dynamic_loader.load_plugin(path).await?;
-        let plugin_arc = Arc::from(plugin);
-        self.register_plugin(plugin_arc, config, resilience_config).await
+        let name = plugin.name().to_string();
+        let external = ExternalPlugin { path: path.to_path_buf(), library: plugin.library_name().to_string() };
+        if let Err(err) = self.register_plugin(Arc::new(plugin), config, resilience_config).await {
+            self.dynamic_loader.unload_library(&external.library).await;
+            return Err(err);
+        }
+        self.external.write().await.insert(name, external);
+        Ok(())
+    }
+    
+    /// Load an external plugin's library again and swap it in for the
+    /// running one
+    ///
+    /// Taking the plugin table for writing waits for hook calls in flight
+    /// and holds new ones back until the new library is initialized with the
+    /// plugin's stored config. When the new library fails to load, the old
+    /// one keeps running.
+    pub async fn reload_plugin(&self, name: &str) -> BackworksResult<()> {
+        let external = self.external.read().await.get(name).cloned()
+            .ok_or_else(|| crate::error::BackworksError::config(format!("Plugin {} was not loaded from a library", name)))?;
+        
+        let mut plugins = self.plugins.write().await;
+        let plugin = self.dynamic_loader.reload_plugin(&external.path).await?;
+        if plugin.name() != name {
+            self.dynamic_loader.unload_library(plugin.library_name()).await;
+            return Err(crate::error::BackworksError::config(format!(
+                "{} now provides plugin {}, not {}", external.path.display(), plugin.name(), name
+            )));
+        }
+        let reloaded = ExternalPlugin { path: external.path, library: plugin.library_name().to_string() };
+        let version = plugin.version().to_string();
+        let plugin: Arc<dyn BackworksPlugin> = Arc::new(plugin);
+        if let Err(err) = plugins.order_with(name, &plugin) {
+            self.dynamic_loader.unload_library(&reloaded.library).await;
+            return Err(err);
+        }
+        
+        if let Some(old) = plugins.remove(name) {
+            if let Err(err) = self.resilient_executor.execute_with_resilience(name, old.shutdown()).await {
+                tracing::warn!("⚠️ Plugin {} shutdown failed: {:?}", name, err);
+            }
+        }
+        self.dynamic_loader.unload_library(&external.library).await;
+        
+        if let Some(config) = self.configs.read().await.get(name) {
+            let result = self.resilient_executor.execute_with_resilience(
+                name,
+                plugin.initialize(config),
+            ).await;
+            
+            if let Err(err) = result {
+                tracing::error!("🔴 Failed to initialize reloaded plugin {}: {:?}", name, err);
+                if plugin.is_critical() {
+                    self.dynamic_loader.unload_library(&reloaded.library).await;
+                    self.external.write().await.remove(name);
+                    return Err(crate::error::BackworksError::PluginInitializationFailed(name.to_string()));
+                }
+            }
+        }
+        
+        plugins.insert(name.to_string(), plugin)?;
+        self.external.write().await.insert(name.to_string(), reloaded);
+        tracing::info!("🔄 Reloaded plugin {} v{}", name, version);
+        Ok(())
     }
     
     /// Register a plugin from configuration
@@ -267,6 +562,21 @@ impl PluginManager {
                     resilience_config
                 ).await
             }
+            PluginType::Process => {
+                let command = plugin_config.path.as_ref()
+                    .ok_or_else(|| crate::error::BackworksError::Config(
+                        format!("Process plugin {} missing path configuration", name)
+                    ))?;
+                
+                let mut plugin = ProcessPlugin::builder(name, command, plugin_config.args.clone());
+                if let Some(max_restarts) = plugin_config.max_restarts {
+                    plugin = plugin.with_max_restarts(max_restarts);
+                }
+                if let Some(timeout) = &plugin_config.timeout {
+                    plugin = plugin.with_timeout(crate::config::parse_duration(timeout)?);
+                }
+                self.register_plugin(Arc::new(plugin.build()), Some(plugin_config.config.clone()), resilience_config).await
+            }
         }
     }
     
@@ -331,10 +641,26 @@ impl PluginManager {
         drop(plugins); // Release read lock
         self.plugins.write().await.remove(name);
         self.configs.write().await.remove(name);
+        self.base_policies.write().await.remove(name);
+        if let Some(external) = self.external.write().await.remove(name) {
+            self.dynamic_loader.unload_library(&external.library).await;
+        }
         Ok(())
     }
     
-    /// Get list of registered plugin names
+    /// Fail when a plugin requires one that is not registered
+    pub async fn check_requirements(&self) -> BackworksResult<()> {
+        let unmet = self.plugins.read().await.unmet_requirements();
+        if unmet.is_empty() {
+            return Ok(());
+        }
+        Err(crate::error::BackworksError::config(unmet.into_iter()
+            .map(|(plugin, required)| format!("plugin {} requires plugin {}, which is not registered", plugin, required))
+            .collect::<Vec<_>>()
+            .join("; ")))
+    }
+    
+    /// Get list of registered plugin names, in hook order
     pub async fn list_plugins(&self) -> Vec<String> {
         self.plugins.read().await.keys().cloned().collect()
     }
@@ -354,6 +680,14 @@ impl PluginManager {
                 Ok(_) => {
                     tracing::debug!("✅ Plugin {} before_request hook completed", name);
                 }
+                // A plugin refusing the request (e.g. failed authentication)
+                // ends it with that status, whether or not it is critical
+                Err(ResilientExecutionError::CircuitBreakerError(CircuitBreakerError::PluginError(err)))
+                    if err.is_rejection() =>
+                {
+                    tracing::debug!("Plugin {} rejected request: {}", name, err);
+                    return Err(err);
+                }
                 Err(err) => {
                     tracing::warn!("⚠️ Plugin {} before_request hook failed: {:?}", name, err);
                     
@@ -414,6 +748,27 @@ impl PluginManager {
     }
     
     
+    /// Call on_error on all plugins with resilience
+    pub async fn on_error(&self, error: &RequestError, response: &mut Response<axum::body::Body>) -> BackworksResult<()> {
+        let plugins = self.plugins.read().await;
+        
+        // Error hooks run in the same (reverse) order as after_response
+        let plugin_vec: Vec<_> = plugins.iter().collect();
+        for (name, plugin) in plugin_vec.iter().rev() {
+            let result = self.resilient_executor.execute_with_resilience(
+                name,
+                plugin.on_error(error, response),
+            ).await;
+            
+            if let Err(err) = result {
+                // Error hooks never fail the response; the original error stands
+                tracing::warn!("⚠️ Plugin {} on_error hook failed: {:?}", name, err);
+            }
+        }
+        
+        Ok(())
+    }
+    
     /// Reload configuration for all plugins with resilience
     pub async fn reload_configs(&self, new_configs: HashMap<String, Value>) -> BackworksResult<()> {
         let plugins = self.plugins.read().await;
@@ -507,6 +862,45 @@ impl PluginManager {
         self.resilient_executor.get_all_metrics().await
     }
     
+    /// The built-in middleware plus what every plugin registers
+    pub async fn middleware_registry(&self) -> MiddlewareRegistry {
+        let mut registry = MiddlewareRegistry::default();
+        for plugin in self.plugins.read().await.values() {
+            plugin.register_middleware(&mut registry);
+        }
+        registry
+    }
+    
+    /// The built-in load-balancing strategies plus what every plugin registers
+    pub async fn load_balancer_registry(&self) -> LoadBalancerRegistry {
+        let mut registry = LoadBalancerRegistry::default();
+        for plugin in self.plugins.read().await.values() {
+            plugin.register_load_balancers(&mut registry);
+        }
+        registry
+    }
+    
+    /// Routes each plugin adds to the API server, in hook order
+    pub async fn routes(&self) -> Vec<(String, PluginRoutes)> {
+        self.plugins.read().await.iter()
+            .map(|(name, plugin)| (name.clone(), plugin.routes()))
+            .filter(|(_, routes)| !routes.is_empty())
+            .collect()
+    }
+    
+    /// Routes each plugin adds to the dashboard, in hook order
+    pub async fn dashboard_routes(&self) -> Vec<(String, PluginRoutes)> {
+        self.plugins.read().await.iter()
+            .map(|(name, plugin)| (name.clone(), plugin.dashboard_routes()))
+            .filter(|(_, routes)| !routes.is_empty())
+            .collect()
+    }
+    
+    /// Plugins whose circuit breaker is open
+    pub async fn open_circuits(&self) -> Vec<String> {
+        self.resilient_executor.open_circuits().await
+    }
+    
     /// Try to process endpoint with plugins (first plugin to return Some wins)
     pub async fn process_endpoint_data(&self, endpoint: &str, method: &str, data: &str) -> BackworksResult<Option<String>> {
         let plugins = self.plugins.read().await;
@@ -533,6 +927,84 @@ impl PluginManager {
         Ok(None)
     }
     
+    /// Answer a request to `endpoint` with the plugin it names, when the
+    /// plugin serves endpoints itself
+    ///
+    /// `None` when the plugin has no [`EndpointHandlerPlugin`] capability.
+    pub async fn handle_endpoint(&self, plugin_name: &str, endpoint: &str, request: &crate::server::RequestData) -> BackworksResult<Option<EndpointResponse>> {
+        let plugins = self.plugins.read().await;
+        let plugin = plugins.get(plugin_name)
+            .ok_or_else(|| crate::error::BackworksError::PluginNotFound(plugin_name.to_string()))?;
+        let Some(handler) = plugin.endpoint_handler() else {
+            return Ok(None);
+        };
+        if !handler.claims(endpoint) {
+            return Err(crate::error::BackworksError::config(format!(
+                "Plugin {} does not serve endpoint '{}'", plugin_name, endpoint
+            )));
+        }
+        
+        let result = self.resilient_executor.execute_with_resilience(
+            plugin_name,
+            handler.handle_endpoint(endpoint, request),
+        ).await;
+        resilient_result(plugin_name, result).map(Some)
+    }
+    
+    /// Run `action` of the plugin named `plugin_name`, through its circuit
+    /// breaker
+    pub async fn run_action(&self, plugin_name: &str, action: &str, params: &Value) -> BackworksResult<Value> {
+        // Jobs may run for long; reloads do not wait for them
+        let plugin = self.plugins.read().await.get(plugin_name).cloned()
+            .ok_or_else(|| crate::error::BackworksError::PluginNotFound(plugin_name.to_string()))?;
+        let result = self.resilient_executor.execute_with_resilience(
+            plugin_name,
+            plugin.run_action(action, params),
+        ).await;
+        resilient_result(plugin_name, result)
+    }
+    
+    /// Database operations of the plugin named `plugin_name`, or of the only
+    /// plugin offering them when no name is given
+    pub async fn database(&self, plugin_name: Option<&str>) -> BackworksResult<DatabaseHandle> {
+        let plugins = self.plugins.read().await;
+        if let Some(name) = plugin_name {
+            let plugin = plugins.get(name)
+                .ok_or_else(|| crate::error::BackworksError::PluginNotFound(name.to_string()))?;
+            return DatabaseHandle::new(name, plugin.clone(), self.resilient_executor.clone())
+                .ok_or_else(|| crate::error::BackworksError::config(format!("Plugin {} has no database tables", name)));
+        }
+        
+        let mut databases = plugins.iter()
+            .filter_map(|(name, plugin)| DatabaseHandle::new(name, plugin.clone(), self.resilient_executor.clone()));
+        match (databases.next(), databases.next()) {
+            (Some(database), None) => Ok(database),
+            (None, _) => Err(crate::error::BackworksError::config("No plugin provides database tables")),
+            (Some(_), Some(_)) => Err(crate::error::BackworksError::config(
+                "Several plugins provide database tables; name one under `plugin`"
+            )),
+        }
+    }
+    
+    /// Cache store of the plugin named `plugin_name`
+    pub async fn cache(&self, plugin_name: &str) -> BackworksResult<CacheHandle> {
+        let plugins = self.plugins.read().await;
+        let plugin = plugins.get(plugin_name)
+            .ok_or_else(|| crate::error::BackworksError::PluginNotFound(plugin_name.to_string()))?;
+        CacheHandle::new(plugin_name, plugin.clone(), self.resilient_executor.clone())
+            .ok_or_else(|| crate::error::BackworksError::config(format!("Plugin {} provides no cache store", plugin_name)))
+    }
+    
+    /// Database operations of every plugin offering them
+    pub async fn databases(&self) -> Vec<DatabaseHandle> {
+        let plugins = self.plugins.read().await;
+        let mut databases: Vec<DatabaseHandle> = plugins.iter()
+            .filter_map(|(name, plugin)| DatabaseHandle::new(name, plugin.clone(), self.resilient_executor.clone()))
+            .collect();
+        databases.sort_by(|a, b| a.plugin().cmp(b.plugin()));
+        databases
+    }
+    
     /// Execute a specific plugin with JSON data
     pub async fn execute_plugin(&self, plugin_name: &str, request_data: &str) -> BackworksResult<String> {
         let plugins = self.plugins.read().await;
@@ -575,3 +1047,82 @@ impl Default for PluginManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Build a plugin library answering every endpoint with `greeting` once
+    // initialized, and move it over `path`
+    fn build_plugin(path: &Path, greeting: &str, abi_version: u32) {
+        let source = path.with_file_name(format!("greeter_{}.rs", uuid::Uuid::new_v4().simple()));
+        std::fs::write(&source, r#"
+            use std::os::raw::c_char;
+            use std::sync::atomic::{AtomicBool, Ordering};
+
+            static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+            #[repr(C)]
+            pub struct PluginInfo { name: *const c_char, version: *const c_char, description: *const c_char }
+
+            #[no_mangle]
+            pub extern "C" fn plugin_info() -> PluginInfo {
+                PluginInfo { name: c"greeter".as_ptr(), version: c"GREETING".as_ptr(), description: c"Greets".as_ptr() }
+            }
+
+            #[no_mangle]
+            pub extern "C" fn backworks_plugin_abi_version() -> u32 { ABI_VERSION }
+
+            #[no_mangle]
+            pub extern "C" fn plugin_initialize(_config: *const c_char) -> i32 {
+                INITIALIZED.store(true, Ordering::SeqCst);
+                0
+            }
+
+            #[no_mangle]
+            pub extern "C" fn plugin_process_endpoint(_: *const c_char, _: *const c_char, _: *const c_char) -> *const c_char {
+                if INITIALIZED.load(Ordering::SeqCst) { c"GREETING".as_ptr() } else { std::ptr::null() }
+            }
+        "#.replace("GREETING", greeting).replace("ABI_VERSION", &abi_version.to_string())).unwrap();
+        let built = source.with_extension(std::env::consts::DLL_EXTENSION);
+        let status = std::process::Command::new(std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string()))
+            .args(["--edition", "2021", "--crate-type", "cdylib", "-o"])
+            .arg(&built)
+            .arg(&source)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        // A new file rather than new contents, as the old one is mapped
+        std::fs::rename(&built, path).unwrap();
+        std::fs::remove_file(source).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_external_plugin_reloads_with_its_config() {
+        let dir = std::env::temp_dir().join(format!("backworks_plugin_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("greeter").with_extension(std::env::consts::DLL_EXTENSION);
+        build_plugin(&path, "v1", PLUGIN_ABI_VERSION);
+
+        let manager = PluginManager::new();
+        manager.load_external_plugin(&path, Some(serde_json::json!({"greeting": true})), None).await.unwrap();
+        let greet = || manager.process_endpoint_data("/hello", "GET", "");
+        assert_eq!(greet().await.unwrap().as_deref(), Some("v1"));
+
+        // A library built against another interface is refused and the
+        // running one stays
+        build_plugin(&path, "v2", PLUGIN_ABI_VERSION + 1);
+        let err = manager.reload_plugin("greeter").await.unwrap_err();
+        assert!(err.to_string().contains("plugin interface v2"), "{}", err);
+        assert_eq!(greet().await.unwrap().as_deref(), Some("v1"));
+
+        // The new library is initialized with the stored config
+        build_plugin(&path, "v2", PLUGIN_ABI_VERSION);
+        manager.reload_plugin("greeter").await.unwrap();
+        assert_eq!(greet().await.unwrap().as_deref(), Some("v2"));
+
+        manager.unregister_plugin("greeter").await.unwrap();
+        assert!(manager.reload_plugin("greeter").await.is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
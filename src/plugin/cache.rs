@@ -0,0 +1,69 @@
+//! Stores plugins offer the `cache` middleware
+//!
+//! Responses are cached in memory unless the blueprint's `cache.type` names a
+//! plugin with a [`CacheBackend`]; they are then kept in the plugin's store,
+//! where every server sharing the store finds them and they survive restarts.
+
+use super::BackworksPlugin;
+use crate::error::BackworksResult;
+use crate::resilience::ResilientPluginExecutor;
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Capability of a plugin to store cached entries
+///
+/// A plugin offers it through [`super::BackworksPlugin::cache`].
+#[async_trait]
+pub trait CacheBackend: Send + Sync {
+    /// The entry stored under `key`, unless it expired
+    async fn get(&self, key: &str) -> BackworksResult<Option<Vec<u8>>>;
+
+    /// Store `value` under `key` for `ttl`
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Duration) -> BackworksResult<()>;
+
+    /// Remove every entry whose key starts with `prefix`; how many there were
+    async fn clear(&self, prefix: &str) -> BackworksResult<usize>;
+}
+
+/// Cache operations of a plugin, called through its circuit breaker
+#[derive(Clone)]
+pub struct CacheHandle {
+    name: String,
+    plugin: Arc<dyn BackworksPlugin>,
+    executor: Arc<ResilientPluginExecutor>,
+}
+
+impl CacheHandle {
+    /// `None` when `plugin` has no [`CacheBackend`] capability
+    pub(super) fn new(name: &str, plugin: Arc<dyn BackworksPlugin>, executor: Arc<ResilientPluginExecutor>) -> Option<Self> {
+        plugin.cache()?;
+        Some(Self { name: name.to_string(), plugin, executor })
+    }
+
+    /// Name of the plugin
+    pub fn plugin(&self) -> &str {
+        &self.name
+    }
+
+    fn backend(&self) -> &dyn CacheBackend {
+        self.plugin.cache().expect("checked when the handle was created")
+    }
+
+    async fn run<T>(&self, operation: impl std::future::Future<Output = BackworksResult<T>> + Send) -> BackworksResult<T> {
+        let result = self.executor.execute_with_resilience(&self.name, operation).await;
+        super::resilient_result(&self.name, result)
+    }
+
+    pub async fn get(&self, key: &str) -> BackworksResult<Option<Vec<u8>>> {
+        self.run(self.backend().get(key)).await
+    }
+
+    pub async fn set(&self, key: &str, value: Vec<u8>, ttl: Duration) -> BackworksResult<()> {
+        self.run(self.backend().set(key, value, ttl)).await
+    }
+
+    pub async fn clear(&self, prefix: &str) -> BackworksResult<usize> {
+        self.run(self.backend().clear(prefix)).await
+    }
+}
@@ -0,0 +1,370 @@
+//! Tables plugins give endpoints direct access to
+//!
+//! A database plugin describes its tables and reads and writes their rows;
+//! endpoints with `database.auto_crud` are served from these operations
+//! (see [`crate::crud`]). Values are validated against the table schema
+//! before they reach the plugin, so a plugin receives rows whose columns
+//! exist and hold values of the column's type.
+//!
+//! Plugins that hold connections are handed the blueprint's connection and
+//! pool settings through [`DatabasePlugin::connect`], and are pinged and
+//! reconnected by [`crate::pool::DatabasePools`].
+
+use super::BackworksPlugin;
+use crate::error::BackworksResult;
+use crate::resilience::ResilientPluginExecutor;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A table row, by column name
+pub type Row = Map<String, Value>;
+
+/// Capability of a plugin to read and write database tables
+///
+/// A plugin offers it through [`super::BackworksPlugin::database`].
+#[async_trait]
+pub trait DatabasePlugin: Send + Sync {
+    /// Columns of `table`; called when the blueprint is loaded
+    async fn table_schema(&self, table: &str) -> BackworksResult<TableSchema>;
+
+    /// Rows of `table` matching `query`, with the number of matching rows
+    /// before `limit` and `offset` apply
+    async fn list(&self, table: &str, query: &ListQuery) -> BackworksResult<RowPage>;
+
+    /// The row whose primary key is `key`
+    async fn get(&self, table: &str, key: &Value) -> BackworksResult<Option<Row>>;
+
+    /// Insert `row` and return it as stored, with generated columns filled in
+    async fn insert(&self, table: &str, row: Row) -> BackworksResult<Row>;
+
+    /// Set the columns in `changes` on the row whose primary key is `key`
+    /// and return the updated row, `None` when there is no such row
+    async fn update(&self, table: &str, key: &Value, changes: Row) -> BackworksResult<Option<Row>>;
+
+    /// Delete the row whose primary key is `key`; whether there was one
+    async fn delete(&self, table: &str, key: &Value) -> BackworksResult<bool>;
+
+    /// Open connections with the blueprint's `database` settings; called
+    /// before the server starts and again to reconnect after a failed
+    /// health check
+    async fn connect(&self, _settings: &DatabaseSettings) -> BackworksResult<()> {
+        Ok(())
+    }
+
+    /// Check that the database answers, through a connection of the pool
+    async fn ping(&self) -> BackworksResult<()> {
+        Ok(())
+    }
+
+    /// Connections of the pool, when the plugin keeps one
+    fn pool_stats(&self) -> Option<PoolStats> {
+        None
+    }
+
+    /// Run a named query of an endpoint as a prepared statement, binding
+    /// `query.params` to its placeholders
+    async fn query(&self, query: &PreparedQuery) -> BackworksResult<QueryResult> {
+        Err(crate::error::BackworksError::database(format!("Cannot run query {}: the plugin has tables but runs no SQL", query.name)))
+    }
+}
+
+/// Connection settings of a database plugin
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatabaseSettings {
+    /// `database.type`, such as `postgresql` or `sqlite`
+    pub db_type: String,
+    /// With environment variables resolved
+    pub connection_string: Option<String>,
+    pub pool: PoolSettings,
+}
+
+/// Connection pool options; plugins must not open more than
+/// `max_connections` and should keep `min_connections` open
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolSettings {
+    pub min_connections: u32,
+    pub max_connections: u32,
+    /// How long to wait for a connection, to open or from the pool
+    pub connect_timeout: Duration,
+    /// How often the pool is pinged
+    pub health_check_interval: Duration,
+}
+
+impl Default for PoolSettings {
+    fn default() -> Self {
+        Self {
+            min_connections: 0,
+            max_connections: 10,
+            connect_timeout: Duration::from_secs(30),
+            health_check_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Connections of a pool
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    pub in_use: u32,
+    pub idle: u32,
+    /// `max_connections` the pool was opened with
+    pub max: u32,
+}
+
+/// Type of a column's values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Integer,
+    Real,
+    Text,
+    Boolean,
+    /// RFC 3339 text
+    Timestamp,
+    /// Any JSON value
+    Json,
+}
+
+impl ColumnType {
+    /// Whether `value` is a non-null value of this type
+    pub fn accepts(&self, value: &Value) -> bool {
+        match self {
+            ColumnType::Integer => value.is_i64() || value.is_u64(),
+            ColumnType::Real => value.is_number(),
+            ColumnType::Text => value.is_string(),
+            ColumnType::Boolean => value.is_boolean(),
+            ColumnType::Timestamp => value.as_str().is_some_and(|text| chrono::DateTime::parse_from_rfc3339(text).is_ok()),
+            ColumnType::Json => !value.is_null(),
+        }
+    }
+
+    /// A value of this type written as text, as in query strings and paths
+    pub fn parse(&self, text: &str) -> Option<Value> {
+        match self {
+            ColumnType::Integer => text.parse::<i64>().ok().map(Value::from),
+            ColumnType::Real => text.parse::<f64>().ok().filter(|n| n.is_finite()).map(Value::from),
+            ColumnType::Text => Some(Value::String(text.to_string())),
+            ColumnType::Boolean => match text {
+                "true" | "1" => Some(Value::Bool(true)),
+                "false" | "0" => Some(Value::Bool(false)),
+                _ => None,
+            },
+            ColumnType::Timestamp => chrono::DateTime::parse_from_rfc3339(text).is_ok().then(|| Value::String(text.to_string())),
+            ColumnType::Json => serde_json::from_str(text).ok(),
+        }
+    }
+}
+
+/// A column of a table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Column {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub column_type: ColumnType,
+    #[serde(default)]
+    pub nullable: bool,
+    /// Filled in by the database when a row leaves it out
+    #[serde(default)]
+    pub has_default: bool,
+    #[serde(default)]
+    pub primary_key: bool,
+}
+
+impl Column {
+    pub fn new(name: &str, column_type: ColumnType) -> Self {
+        Self {
+            name: name.to_string(),
+            column_type,
+            nullable: false,
+            has_default: false,
+            primary_key: false,
+        }
+    }
+
+    pub fn nullable(mut self) -> Self {
+        self.nullable = true;
+        self
+    }
+
+    pub fn with_default(mut self) -> Self {
+        self.has_default = true;
+        self
+    }
+
+    /// The table's primary key, generated by the database
+    pub fn primary_key(mut self) -> Self {
+        self.primary_key = true;
+        self.has_default = true;
+        self
+    }
+}
+
+/// Columns of a table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableSchema {
+    pub table: String,
+    pub columns: Vec<Column>,
+}
+
+impl TableSchema {
+    pub fn new(table: &str, columns: Vec<Column>) -> Self {
+        Self { table: table.to_string(), columns }
+    }
+
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    /// The table's primary key, when it has exactly one key column
+    pub fn primary_key(&self) -> Option<&Column> {
+        let mut keys = self.columns.iter().filter(|column| column.primary_key);
+        match (keys.next(), keys.next()) {
+            (Some(key), None) => Some(key),
+            _ => None,
+        }
+    }
+}
+
+/// Rows a list request asks for
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ListQuery {
+    /// Columns that must equal a value
+    pub filters: Vec<(String, Value)>,
+    /// Columns to order by, the first one first
+    pub sort: Vec<SortKey>,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+/// A column rows are ordered by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub column: String,
+    pub descending: bool,
+}
+
+/// A page of rows
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RowPage {
+    pub rows: Vec<Row>,
+    /// Rows matching the filters on all pages
+    pub total: u64,
+}
+
+/// A statement with its parameters bound separately from the SQL text
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreparedQuery {
+    /// Name of the query under `database.queries`
+    pub name: String,
+    /// The statement with a `?` in place of each parameter
+    pub sql: String,
+    /// Values of the placeholders, in order
+    pub params: Vec<Value>,
+    /// Offsets of the placeholders in `sql`
+    pub(crate) placeholders: Vec<usize>,
+}
+
+impl PreparedQuery {
+    /// The statement with numbered placeholders (`$1`, `$2`, ...), as
+    /// PostgreSQL takes them
+    pub fn numbered_sql(&self) -> String {
+        let mut sql = String::with_capacity(self.sql.len() + self.placeholders.len() * 2);
+        let mut copied = 0;
+        for (number, &offset) in self.placeholders.iter().enumerate() {
+            sql.push_str(&self.sql[copied..offset]);
+            sql.push_str(&format!("${}", number + 1));
+            copied = offset + 1;
+        }
+        sql.push_str(&self.sql[copied..]);
+        sql
+    }
+}
+
+/// What a query returned
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryResult {
+    pub rows: Vec<Row>,
+    /// Rows the statement inserted, changed or deleted
+    pub rows_affected: u64,
+}
+
+/// Database operations of a plugin, called through its circuit breaker
+#[derive(Clone)]
+pub struct DatabaseHandle {
+    name: String,
+    plugin: Arc<dyn BackworksPlugin>,
+    executor: Arc<ResilientPluginExecutor>,
+}
+
+impl DatabaseHandle {
+    /// `None` when `plugin` has no [`DatabasePlugin`] capability
+    pub(super) fn new(name: &str, plugin: Arc<dyn BackworksPlugin>, executor: Arc<ResilientPluginExecutor>) -> Option<Self> {
+        plugin.database()?;
+        Some(Self { name: name.to_string(), plugin, executor })
+    }
+
+    /// Name of the plugin
+    pub fn plugin(&self) -> &str {
+        &self.name
+    }
+
+    fn database(&self) -> &dyn DatabasePlugin {
+        self.plugin.database().expect("checked when the handle was created")
+    }
+
+    async fn run<T>(&self, operation: impl std::future::Future<Output = BackworksResult<T>> + Send) -> BackworksResult<T> {
+        let result = self.executor.execute_with_resilience(&self.name, operation).await;
+        super::resilient_result(&self.name, result)
+    }
+
+    pub async fn table_schema(&self, table: &str) -> BackworksResult<TableSchema> {
+        self.run(self.database().table_schema(table)).await
+    }
+
+    pub async fn list(&self, table: &str, query: &ListQuery) -> BackworksResult<RowPage> {
+        self.run(self.database().list(table, query)).await
+    }
+
+    pub async fn get(&self, table: &str, key: &Value) -> BackworksResult<Option<Row>> {
+        self.run(self.database().get(table, key)).await
+    }
+
+    pub async fn insert(&self, table: &str, row: Row) -> BackworksResult<Row> {
+        self.run(self.database().insert(table, row)).await
+    }
+
+    pub async fn update(&self, table: &str, key: &Value, changes: Row) -> BackworksResult<Option<Row>> {
+        self.run(self.database().update(table, key, changes)).await
+    }
+
+    pub async fn delete(&self, table: &str, key: &Value) -> BackworksResult<bool> {
+        self.run(self.database().delete(table, key)).await
+    }
+
+    pub async fn query(&self, query: &PreparedQuery) -> BackworksResult<QueryResult> {
+        self.run(self.database().query(query)).await
+    }
+
+    // Connecting and pinging bypass the circuit breaker, which would
+    // otherwise keep a recovered database closed off while it is open
+
+    pub async fn connect(&self, settings: &DatabaseSettings) -> BackworksResult<()> {
+        within(settings.pool.connect_timeout, "connecting", &self.name, self.database().connect(settings)).await
+    }
+
+    pub async fn ping(&self, timeout: Duration) -> BackworksResult<()> {
+        within(timeout, "pinging", &self.name, self.database().ping()).await
+    }
+
+    pub fn pool_stats(&self) -> Option<PoolStats> {
+        self.database().pool_stats()
+    }
+}
+
+async fn within(timeout: Duration, doing: &str, plugin: &str, operation: impl std::future::Future<Output = BackworksResult<()>>) -> BackworksResult<()> {
+    tokio::time::timeout(timeout, operation).await.unwrap_or_else(|_| Err(crate::error::BackworksError::database(
+        format!("Timed out {} the database of plugin {} after {:?}", doing, plugin, timeout)
+    )))
+}
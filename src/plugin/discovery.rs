@@ -1,23 +1,17 @@
 use std::path::Path;
 use crate::error::{BackworksError, Result as BackworksResult};
 use crate::config::PluginDiscoveryConfig;
-use crate::plugin::dynamic::{DynamicPluginLoader, PluginMetadata};
+use crate::plugin::dynamic::PluginMetadata;
 use tokio::fs;
 
 /// Plugin discovery service that can find external plugins in configured directories
 pub struct PluginDiscovery {
     config: PluginDiscoveryConfig,
-    loader: DynamicPluginLoader,
 }
 
 impl PluginDiscovery {
     pub fn new(config: PluginDiscoveryConfig) -> Self {
-        let loader = DynamicPluginLoader::new();
-        
-        // Note: Plugin directories are configured in the discovery config
-        // The loader will use default directories
-        
-        Self { config, loader }
+        Self { config }
     }
     
     /// Discover all available plugins in configured directories
@@ -49,10 +43,10 @@ impl PluginDiscovery {
             let mut plugins = Vec::new();
             
             let mut entries = fs::read_dir(directory).await
-                .map_err(|e| BackworksError::Io(e))?;
+                .map_err(BackworksError::Io)?;
             
             while let Some(entry) = entries.next_entry().await
-                .map_err(|e| BackworksError::Io(e))? {
+                .map_err(BackworksError::Io)? {
                 
                 let path = entry.path();
                 
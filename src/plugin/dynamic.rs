@@ -9,10 +9,26 @@ use serde_json::Value;
 use crate::error::{BackworksError, Result as BackworksResult};
 use crate::plugin::{BackworksPlugin, PluginHealth, HealthStatus};
 
+/// Version of the symbol interface external plugins are built against
+///
+/// A plugin library exports it as `backworks_plugin_abi_version`; libraries
+/// reporting another version, or none, are refused.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+const ABI_VERSION_SYMBOL: &[u8] = b"backworks_plugin_abi_version";
+
 /// Dynamic plugin loader that can load external compiled plugins
 pub struct DynamicPluginLoader {
     plugin_directories: Vec<PathBuf>,
     loaded_libraries: Arc<RwLock<HashMap<String, Library>>>,
+    /// Copies reloaded libraries were opened from, by library name
+    copies: Arc<RwLock<HashMap<String, PathBuf>>>,
+}
+
+impl Default for DynamicPluginLoader {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl DynamicPluginLoader {
@@ -24,6 +40,7 @@ impl DynamicPluginLoader {
                 PathBuf::from("/usr/local/lib/backworks/plugins"),
             ],
             loaded_libraries: Arc::new(RwLock::new(HashMap::new())),
+            copies: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
@@ -42,10 +59,10 @@ impl DynamicPluginLoader {
             }
             
             let mut entries = tokio::fs::read_dir(dir).await
-                .map_err(|e| BackworksError::Io(e))?;
+                .map_err(BackworksError::Io)?;
                 
             while let Some(entry) = entries.next_entry().await
-                .map_err(|e| BackworksError::Io(e))? {
+                .map_err(BackworksError::Io)? {
                 
                 let path = entry.path();
                 if self.is_plugin_file(&path) {
@@ -60,26 +77,59 @@ impl DynamicPluginLoader {
     }
 
     /// Load a plugin from a specific path
-    pub async fn load_plugin<P: AsRef<Path>>(&self, path: P) -> BackworksResult<Box<dyn BackworksPlugin>> {
+    pub async fn load_plugin<P: AsRef<Path>>(&self, path: P) -> BackworksResult<DynamicPlugin> {
         let path = path.as_ref();
         let plugin_name = path.file_stem()
             .and_then(|s| s.to_str())
             .ok_or_else(|| BackworksError::Config("Invalid plugin filename".to_string()))?;
+        self.open(path, path, plugin_name).await
+    }
 
-        // Load the dynamic library
-        let lib = unsafe { Library::new(path) }
-            .map_err(|e| BackworksError::Config(format!("Failed to load plugin library: {}", e)))?;
+    /// Load a plugin's library again, next to the one already loaded
+    ///
+    /// The library is opened from a fresh copy under a new name, so the
+    /// system loader cannot hand back the library that is still in use.
+    pub async fn reload_plugin<P: AsRef<Path>>(&self, path: P) -> BackworksResult<DynamicPlugin> {
+        let path = path.as_ref();
+        let stem = path.file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| BackworksError::Config("Invalid plugin filename".to_string()))?;
+        let library_name = format!("{}-{}", stem, uuid::Uuid::new_v4().simple());
+        let dir = std::env::temp_dir().join("backworks-plugins");
+        tokio::fs::create_dir_all(&dir).await.map_err(BackworksError::Io)?;
+        let mut copy = dir.join(&library_name);
+        if let Some(extension) = path.extension() {
+            copy.set_extension(extension);
+        }
+        tokio::fs::copy(path, &copy).await.map_err(BackworksError::Io)?;
 
-        // Store the library to keep it alive
-        self.loaded_libraries.write().await.insert(plugin_name.to_string(), lib);
+        let plugin = self.open(path, &copy, &library_name).await;
+        if plugin.is_ok() {
+            self.copies.write().await.insert(library_name, copy);
+        } else {
+            let _ = tokio::fs::remove_file(&copy).await;
+        }
+        plugin
+    }
 
-        // Get the library reference again (since we moved it above)
-        let libraries = self.loaded_libraries.read().await;
-        let lib = libraries.get(plugin_name).unwrap();
+    /// Unload the library a plugin was loaded from; the plugin must no
+    /// longer be called
+    pub async fn unload_library(&self, library_name: &str) {
+        self.loaded_libraries.write().await.remove(library_name);
+        if let Some(copy) = self.copies.write().await.remove(library_name) {
+            let _ = tokio::fs::remove_file(copy).await;
+        }
+    }
+
+    async fn open(&self, path: &Path, file: &Path, library_name: &str) -> BackworksResult<DynamicPlugin> {
+        let lib = unsafe { Library::new(file) }
+            .map_err(|e| BackworksError::Config(format!("Failed to load plugin library: {}", e)))?;
+        check_abi_version(&lib, path)?;
+        let dynamic_plugin = DynamicPlugin::new(&lib, library_name, self.loaded_libraries.clone())?;
 
-        // Create dynamic plugin wrapper with access to loaded libraries
-        let dynamic_plugin = DynamicPlugin::new(lib, plugin_name, self.loaded_libraries.clone())?;
-        Ok(Box::new(dynamic_plugin))
+        // Store the library to keep it alive
+        self.loaded_libraries.write().await.insert(library_name.to_string(), lib);
+        Ok(dynamic_plugin)
     }
 
     /// Get plugin metadata without fully loading the plugin
@@ -117,6 +167,25 @@ impl DynamicPluginLoader {
     }
 }
 
+/// Fail unless `lib` was built against [`PLUGIN_ABI_VERSION`]
+fn check_abi_version(lib: &Library, path: &Path) -> BackworksResult<()> {
+    let abi_version: Symbol<extern "C" fn() -> u32> = unsafe {
+        lib.get(ABI_VERSION_SYMBOL)
+            .map_err(|_| BackworksError::Config(format!(
+                "Plugin {} does not export backworks_plugin_abi_version; rebuild it against plugin interface v{}",
+                path.display(), PLUGIN_ABI_VERSION
+            )))?
+    };
+    let version = abi_version();
+    if version != PLUGIN_ABI_VERSION {
+        return Err(BackworksError::Config(format!(
+            "Plugin {} was built against plugin interface v{}, this Backworks supports v{}",
+            path.display(), version, PLUGIN_ABI_VERSION
+        )));
+    }
+    Ok(())
+}
+
 /// Metadata about a discovered plugin
 #[derive(Debug, Clone)]
 pub struct PluginMetadata {
@@ -139,6 +208,7 @@ pub struct DynamicPlugin {
     name: String,
     version: String,
     description: String,
+    config_schema: Option<Value>,
     library_name: String,
     libraries: Arc<RwLock<HashMap<String, Library>>>,
 }
@@ -156,25 +226,32 @@ impl DynamicPlugin {
         let name = unsafe { CStr::from_ptr(info.name).to_string_lossy().to_string() };
         let version = unsafe { CStr::from_ptr(info.version).to_string_lossy().to_string() };
         let description = unsafe { CStr::from_ptr(info.description).to_string_lossy().to_string() };
+        
+        // Optional: JSON Schema of the plugin's config
+        let get_schema: Option<Symbol<extern "C" fn() -> *const c_char>> = unsafe { lib.get(b"plugin_config_schema").ok() };
+        let config_schema = get_schema
+            .map(|get_schema| get_schema())
+            .filter(|schema| !schema.is_null())
+            .map(|schema| {
+                let schema = unsafe { CStr::from_ptr(schema).to_string_lossy().to_string() };
+                serde_json::from_str(&schema)
+                    .map_err(|e| BackworksError::Config(format!("Plugin {} exports an invalid config schema: {}", name, e)))
+            })
+            .transpose()?;
 
         Ok(Self {
             name,
             version,
             description,
+            config_schema,
             library_name: plugin_name.to_string(),
             libraries,
         })
     }
 
-    /// Call a plugin function safely
-    fn call_plugin_function<'a, T>(&self, libraries: &'a HashMap<String, Library>, func_name: &[u8]) -> BackworksResult<Symbol<'a, T>> {
-        let lib = libraries.get(&self.library_name)
-            .ok_or_else(|| BackworksError::Config(format!("Plugin library not found: {}", self.library_name)))?;
-
-        unsafe {
-            lib.get(func_name)
-                .map_err(|e| BackworksError::Config(format!("Plugin function not found: {}", e)))
-        }
+    /// Name the plugin's library is kept under by the loader
+    pub fn library_name(&self) -> &str {
+        &self.library_name
     }
 }
 
@@ -192,6 +269,10 @@ impl BackworksPlugin for DynamicPlugin {
         &self.description
     }
 
+    fn config_schema(&self) -> Option<Value> {
+        self.config_schema.clone()
+    }
+
     async fn initialize(&self, config: &Value) -> BackworksResult<()> {
         let libraries = self.libraries.read().await;
         let lib = libraries.get(&self.library_name)
@@ -0,0 +1,630 @@
+//! Plugins running in their own process
+//!
+//! A process plugin is any executable that speaks JSON-RPC 2.0 over its
+//! standard input and output, one message per line, so plugins can be
+//! written in any language and a crashing plugin cannot take the server
+//! down. Lines it writes to standard error are logged. The plugin manager
+//! spawns the process, initializes it with the plugin's config and restarts
+//! it with a growing delay when it exits; calls in flight when it exits fail,
+//! and calls while it is down fail as unavailable.
+
+use crate::error::{BackworksError, BackworksResult};
+use crate::plugin::{BackworksPlugin, EndpointHandlerPlugin, EndpointResponse, HealthStatus, PluginHealth};
+use crate::server::RequestData;
+use async_trait::async_trait;
+use axum::body::Body;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{oneshot, watch};
+
+/// Version of the protocol, sent with `initialize`
+pub const PROCESS_PROTOCOL_VERSION: u32 = 1;
+
+/// Restarts in a row after which a crashing plugin stays down
+pub const DEFAULT_MAX_RESTARTS: u32 = 5;
+
+/// How long a call may take by default
+pub const DEFAULT_CALL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A process that ran this long before exiting starts the restart count over
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+const FIRST_RESTART_DELAY: Duration = Duration::from_millis(100);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(10);
+
+/// How long a plugin has to exit after `shutdown` before it is killed
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+/// A plugin served by a child process
+pub struct ProcessPlugin {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    name: String,
+    command: String,
+    args: Vec<String>,
+    max_restarts: u32,
+    timeout: Duration,
+    /// Version and description reported by the first start
+    reported: OnceLock<(String, String)>,
+    state: Mutex<State>,
+    shutting_down: AtomicBool,
+}
+
+#[derive(Default)]
+struct State {
+    connection: Option<Arc<Connection>>,
+    config: Value,
+    info: ProcessInfo,
+    restarts: u32,
+    /// Why the process is down for good
+    gave_up: Option<String>,
+}
+
+/// What a process reports in answer to `initialize`
+#[derive(Debug, Clone, Default, Deserialize)]
+struct ProcessInfo {
+    #[serde(default)]
+    protocol_version: Option<u32>,
+    #[serde(default)]
+    version: String,
+    #[serde(default)]
+    description: String,
+    /// Hooks the process implements; others are not called
+    #[serde(default)]
+    hooks: Vec<String>,
+    /// Endpoints the process serves, `*` for all naming the plugin
+    #[serde(default)]
+    endpoints: Vec<String>,
+    /// JSON Schema of the plugin's config, checked on later validations
+    #[serde(default)]
+    config_schema: Option<Value>,
+}
+
+#[derive(Serialize)]
+struct Message<'a> {
+    jsonrpc: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<u64>,
+    method: &'a str,
+    params: &'a Value,
+}
+
+#[derive(Deserialize)]
+struct Reply {
+    id: Option<u64>,
+    #[serde(default)]
+    result: Value,
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+type PendingCalls = Mutex<HashMap<u64, oneshot::Sender<Result<Value, RpcError>>>>;
+
+/// A running process; dropping the last handle kills it
+struct Connection {
+    stdin: tokio::sync::Mutex<ChildStdin>,
+    pending: Arc<PendingCalls>,
+    next_id: AtomicU64,
+    pid: Option<u32>,
+    exited: watch::Receiver<bool>,
+    _kill: oneshot::Sender<()>,
+}
+
+/// Forgets a call that was answered, timed out or abandoned
+struct PendingCall<'a> {
+    pending: &'a PendingCalls,
+    id: u64,
+}
+
+impl Drop for PendingCall<'_> {
+    fn drop(&mut self) {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.id);
+    }
+}
+
+impl Connection {
+    async fn send(&self, message: &Message<'_>) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(message)?;
+        line.push(b'\n');
+        let mut stdin = self.stdin.lock().await;
+        stdin.write_all(&line).await?;
+        stdin.flush().await
+    }
+
+    async fn call(&self, plugin: &str, method: &str, params: Value, timeout: Duration) -> BackworksResult<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).insert(id, tx);
+        let _call = PendingCall { pending: &self.pending, id };
+
+        let message = Message { jsonrpc: "2.0", id: Some(id), method, params: &params };
+        self.send(&message).await.map_err(|e| {
+            BackworksError::unavailable(format!("Plugin process {} is not accepting calls: {}", plugin, e))
+        })?;
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(Ok(result))) => Ok(result),
+            Ok(Ok(Err(error))) => Err(BackworksError::plugin(format!(
+                "Plugin process {} failed {} ({}): {}", plugin, method, error.code, error.message
+            ))),
+            Ok(Err(_)) => Err(BackworksError::unavailable(format!("Plugin process {} exited during {}", plugin, method))),
+            Err(_) => Err(BackworksError::plugin(format!(
+                "Plugin process {} did not answer {} within {:?}", plugin, method, timeout
+            ))),
+        }
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> std::io::Result<()> {
+        self.send(&Message { jsonrpc: "2.0", id: None, method, params: &params }).await
+    }
+}
+
+/// Settings of a [`ProcessPlugin`], taken before the plugin exists so a
+/// running process never sees them change
+pub struct ProcessPluginBuilder {
+    name: String,
+    command: String,
+    args: Vec<String>,
+    max_restarts: u32,
+    timeout: Duration,
+}
+
+impl ProcessPluginBuilder {
+    /// Stop restarting after this many crashes in a row
+    pub fn with_max_restarts(mut self, max_restarts: u32) -> Self {
+        self.max_restarts = max_restarts;
+        self
+    }
+
+    /// Fail calls the process does not answer within `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn build(self) -> ProcessPlugin {
+        ProcessPlugin {
+            inner: Arc::new(Inner {
+                name: self.name,
+                command: self.command,
+                args: self.args,
+                max_restarts: self.max_restarts,
+                timeout: self.timeout,
+                reported: OnceLock::new(),
+                state: Mutex::new(State::default()),
+                shutting_down: AtomicBool::new(false),
+            }),
+        }
+    }
+}
+
+impl ProcessPlugin {
+    /// A plugin named `name` served by running `command` with `args`; the
+    /// process starts when the plugin is initialized
+    pub fn new(name: impl Into<String>, command: impl Into<String>, args: Vec<String>) -> Self {
+        Self::builder(name, command, args).build()
+    }
+
+    /// Like [`new`](Self::new), with settings other than the defaults
+    pub fn builder(name: impl Into<String>, command: impl Into<String>, args: Vec<String>) -> ProcessPluginBuilder {
+        ProcessPluginBuilder {
+            name: name.into(),
+            command: command.into(),
+            args,
+            max_restarts: DEFAULT_MAX_RESTARTS,
+            timeout: DEFAULT_CALL_TIMEOUT,
+        }
+    }
+
+    /// Process id of the running process
+    pub fn pid(&self) -> Option<u32> {
+        self.inner.state().connection.as_ref().and_then(|connection| connection.pid)
+    }
+
+    fn implements(&self, hook: &str) -> bool {
+        self.inner.state().info.hooks.iter().any(|implemented| implemented == hook)
+    }
+}
+
+impl Inner {
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    async fn call(&self, method: &str, params: Value) -> BackworksResult<Value> {
+        let connection = self.state().connection.clone()
+            .ok_or_else(|| BackworksError::unavailable(format!("Plugin process {} is not running", self.name)))?;
+        connection.call(&self.name, method, params, self.timeout).await
+    }
+
+    /// Spawn the process and initialize it with the stored config
+    async fn start(self: &Arc<Self>) -> BackworksResult<()> {
+        let mut child = Command::new(&self.command)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| BackworksError::plugin(format!("Failed to start plugin process {} ({}): {}", self.name, self.command, e)))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+
+        let name = self.name.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                tracing::info!("🔌 [{}] {}", name, line);
+            }
+        });
+
+        let (kill, killed) = oneshot::channel();
+        let (exited_tx, exited) = watch::channel(false);
+        let connection = Arc::new(Connection {
+            stdin: tokio::sync::Mutex::new(stdin),
+            pending: Arc::default(),
+            next_id: AtomicU64::new(1),
+            pid: child.id(),
+            exited,
+            _kill: kill,
+        });
+        tokio::spawn(supervise(Arc::downgrade(self), connection.pending.clone(), child, stdout, killed, exited_tx));
+
+        let config = self.state().config.clone();
+        let params = json!({"protocol_version": PROCESS_PROTOCOL_VERSION, "name": self.name, "config": config});
+        let result = connection.call(&self.name, "initialize", params, self.timeout).await?;
+        let info: ProcessInfo = if result.is_null() {
+            ProcessInfo::default()
+        } else {
+            serde_json::from_value(result).map_err(|e| BackworksError::plugin(format!(
+                "Plugin process {} answered initialize with an invalid result: {}", self.name, e
+            )))?
+        };
+        if let Some(version) = info.protocol_version.filter(|version| *version != PROCESS_PROTOCOL_VERSION) {
+            return Err(BackworksError::plugin(format!(
+                "Plugin process {} speaks protocol version {}, Backworks speaks {}", self.name, version, PROCESS_PROTOCOL_VERSION
+            )));
+        }
+
+        if let Some(schema) = info.config_schema.clone() {
+            crate::plugin::schema::register_config_schema(&self.name, schema)?;
+        }
+        let _ = self.reported.set((info.version.clone(), info.description.clone()));
+        let mut state = self.state();
+        state.info = info;
+        state.connection = Some(connection);
+        state.gave_up = None;
+        Ok(())
+    }
+
+    /// Start the process again after it exited, backing off between
+    /// attempts until one succeeds or the restarts run out
+    fn restart(self: Arc<Self>, ran_for: Duration) -> futures::future::BoxFuture<'static, ()> {
+        // Boxed, as restarting spawns the task that restarts
+        Box::pin(async move {
+            let mut attempt = {
+                let mut state = self.state();
+                state.connection = None;
+                if ran_for >= STABLE_AFTER {
+                    state.restarts = 0;
+                }
+                state.restarts
+            };
+            loop {
+                attempt += 1;
+                if attempt > self.max_restarts {
+                    let reason = format!("exited {} times in a row", attempt - 1);
+                    tracing::error!("🔴 Plugin process {} {}; not restarting it", self.name, reason);
+                    self.state().gave_up = Some(reason);
+                    return;
+                }
+                tokio::time::sleep(restart_delay(attempt)).await;
+                if self.shutting_down.load(Ordering::SeqCst) {
+                    return;
+                }
+                self.state().restarts = attempt;
+                match self.start().await {
+                    Ok(()) => {
+                        tracing::info!("🔄 Restarted plugin process {} (attempt {})", self.name, attempt);
+                        return;
+                    }
+                    Err(err) => tracing::warn!("⚠️ Restarting plugin process {} failed: {}", self.name, err),
+                }
+            }
+        })
+    }
+}
+
+fn restart_delay(attempt: u32) -> Duration {
+    FIRST_RESTART_DELAY.saturating_mul(1 << attempt.saturating_sub(1).min(16)).min(MAX_RESTART_DELAY)
+}
+
+/// Route replies to their calls until the process exits or is killed, then
+/// restart it if it was the plugin's running process
+async fn supervise(
+    plugin: Weak<Inner>,
+    pending: Arc<PendingCalls>,
+    mut child: Child,
+    stdout: ChildStdout,
+    mut killed: oneshot::Receiver<()>,
+    exited: watch::Sender<bool>,
+) {
+    let started = Instant::now();
+    let mut lines = BufReader::new(stdout).lines();
+    loop {
+        tokio::select! {
+            line = lines.next_line() => match line {
+                Ok(Some(line)) => dispatch(&pending, &line),
+                _ => break,
+            },
+            _ = &mut killed => {
+                let _ = child.kill().await;
+                break;
+            }
+        }
+    }
+    // Calls still waiting fail now
+    pending.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    let status = match tokio::time::timeout(SHUTDOWN_GRACE, child.wait()).await {
+        Ok(status) => status.ok(),
+        Err(_) => {
+            let _ = child.kill().await;
+            child.wait().await.ok()
+        }
+    };
+    let _ = exited.send(true);
+
+    let Some(plugin) = plugin.upgrade() else { return };
+    let current = plugin.state().connection.as_ref().is_some_and(|connection| Arc::ptr_eq(&connection.pending, &pending));
+    if !current || plugin.shutting_down.load(Ordering::SeqCst) {
+        return;
+    }
+    let status = status.map_or_else(|| "an unknown status".to_string(), |status| status.to_string());
+    tracing::warn!("⚠️ Plugin process {} exited with {}", plugin.name, status);
+    plugin.restart(started.elapsed()).await;
+}
+
+fn dispatch(pending: &PendingCalls, line: &str) {
+    match serde_json::from_str::<Reply>(line) {
+        Ok(Reply { id: Some(id), result, error }) => {
+            if let Some(call) = pending.lock().unwrap_or_else(|e| e.into_inner()).remove(&id) {
+                let _ = call.send(error.map_or(Ok(result), Err));
+            }
+        }
+        _ => tracing::debug!("Ignoring plugin process output: {}", line),
+    }
+}
+
+fn headers_to_json(headers: &HeaderMap) -> Value {
+    let mut map = serde_json::Map::new();
+    for (name, value) in headers {
+        if let Ok(value) = value.to_str() {
+            map.entry(name.as_str()).or_insert_with(|| Value::String(value.to_string()));
+        }
+    }
+    Value::Object(map)
+}
+
+/// Set the headers of a `{"headers": {name: value}}` result
+fn apply_headers(plugin: &str, result: &Value, headers: &mut HeaderMap) -> BackworksResult<()> {
+    let Some(set) = result.get("headers").and_then(Value::as_object) else {
+        return Ok(());
+    };
+    for (name, value) in set {
+        let parsed = HeaderName::from_bytes(name.as_bytes()).ok()
+            .zip(value.as_str().and_then(|value| HeaderValue::from_str(value).ok()));
+        let Some((name, value)) = parsed else {
+            return Err(BackworksError::plugin(format!("Plugin process {} returned an invalid header {}: {}", plugin, name, value)));
+        };
+        headers.insert(name, value);
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl BackworksPlugin for ProcessPlugin {
+    fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    fn version(&self) -> &str {
+        self.inner.reported.get().map_or("", |(version, _)| version.as_str())
+    }
+
+    fn description(&self) -> &str {
+        self.inner.reported.get().map_or("Plugin process", |(_, description)| description.as_str())
+    }
+
+    async fn initialize(&self, config: &Value) -> BackworksResult<()> {
+        self.inner.state().config = config.clone();
+        self.inner.shutting_down.store(false, Ordering::SeqCst);
+        self.inner.start().await
+    }
+
+    async fn shutdown(&self) -> BackworksResult<()> {
+        self.inner.shutting_down.store(true, Ordering::SeqCst);
+        let Some(connection) = self.inner.state().connection.take() else {
+            return Ok(());
+        };
+        let mut exited = connection.exited.clone();
+        if connection.notify("shutdown", Value::Null).await.is_ok() {
+            let _ = tokio::time::timeout(SHUTDOWN_GRACE, exited.wait_for(|exited| *exited)).await;
+        }
+        // Dropping the connection kills a process still running
+        Ok(())
+    }
+
+    async fn health_check(&self) -> BackworksResult<PluginHealth> {
+        let (running, restarts, gave_up) = {
+            let state = self.inner.state();
+            (state.connection.is_some(), state.restarts, state.gave_up.clone())
+        };
+        let mut details = HashMap::new();
+        details.insert("restarts".to_string(), json!(restarts));
+        if let Some(pid) = self.pid() {
+            details.insert("pid".to_string(), json!(pid));
+        }
+        if !running {
+            let message = gave_up.map_or_else(|| "Process is not running".to_string(), |reason| format!("Process {}", reason));
+            return Ok(PluginHealth { status: HealthStatus::Unhealthy, message, details });
+        }
+        match self.inner.call("health", Value::Null).await {
+            Ok(result) => Ok(PluginHealth {
+                status: result.get("status").cloned().and_then(|status| serde_json::from_value(status).ok()).unwrap_or(HealthStatus::Healthy),
+                message: result.get("message").and_then(Value::as_str).unwrap_or("Process is running").to_string(),
+                details,
+            }),
+            Err(err) => Ok(PluginHealth { status: HealthStatus::Unhealthy, message: err.to_string(), details }),
+        }
+    }
+
+    fn max_execution_time(&self) -> Duration {
+        self.inner.timeout
+    }
+
+    async fn before_request(&self, request: &mut Request<Body>) -> BackworksResult<()> {
+        if !self.implements("before_request") {
+            return Ok(());
+        }
+        let params = json!({
+            "method": request.method().as_str(),
+            "uri": request.uri().to_string(),
+            "headers": headers_to_json(request.headers()),
+        });
+        let result = self.inner.call("before_request", params).await?;
+        if let Some(reject) = result.get("reject") {
+            let message = reject.get("message").and_then(Value::as_str).unwrap_or("Rejected by plugin").to_string();
+            return Err(match reject.get("status").and_then(Value::as_u64) {
+                Some(403) => BackworksError::forbidden(message),
+                _ => BackworksError::unauthorized(message),
+            });
+        }
+        apply_headers(&self.inner.name, &result, request.headers_mut())
+    }
+
+    async fn after_response(&self, response: &mut Response<Body>) -> BackworksResult<()> {
+        if !self.implements("after_response") {
+            return Ok(());
+        }
+        let params = json!({"status": response.status().as_u16(), "headers": headers_to_json(response.headers())});
+        let result = self.inner.call("after_response", params).await?;
+        apply_headers(&self.inner.name, &result, response.headers_mut())
+    }
+
+    async fn on_config_reload(&self, config: &Value) -> BackworksResult<()> {
+        // A restarted process is initialized with the new config either way
+        self.inner.state().config = config.clone();
+        if !self.implements("on_config_reload") {
+            return Ok(());
+        }
+        self.inner.call("on_config_reload", json!({"config": config})).await.map(|_| ())
+    }
+
+    fn endpoint_handler(&self) -> Option<&dyn EndpointHandlerPlugin> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl EndpointHandlerPlugin for ProcessPlugin {
+    fn claims(&self, endpoint: &str) -> bool {
+        self.inner.state().info.endpoints.iter().any(|served| served == endpoint || served == "*")
+    }
+
+    async fn handle_endpoint(&self, endpoint: &str, request: &RequestData) -> BackworksResult<EndpointResponse> {
+        let mut data = serde_json::to_value(request)?;
+        data["headers"] = headers_to_json(&request.headers);
+        let result = self.inner.call("handle_endpoint", json!({"endpoint": endpoint, "request": data})).await?;
+
+        let status = match result.get("status").and_then(Value::as_u64) {
+            None => StatusCode::OK,
+            Some(status) => u16::try_from(status).ok().and_then(|status| StatusCode::from_u16(status).ok())
+                .ok_or_else(|| BackworksError::plugin(format!("Plugin process {} returned an invalid status {}", self.inner.name, status)))?,
+        };
+        let mut response = EndpointResponse::new(status, result.get("body").cloned().unwrap_or(Value::Null));
+        apply_headers(&self.inner.name, &result, &mut response.headers)?;
+        Ok(response)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// A plugin in shell: stamps requests, serves `hello` and exits on `crash`
+    const SCRIPT: &str = r#"
+while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/^{"jsonrpc":"2.0","id":\([0-9]*\).*/\1/p')
+  method=$(printf '%s' "$line" | sed -n 's/^{"jsonrpc":"2.0",\("id":[0-9]*,\)\{0,1\}"method":"\([a-z_]*\)".*/\2/p')
+  case "$method" in
+    initialize) result='{"version":"0.3.0","description":"Shell plugin","hooks":["before_request"],"endpoints":["hello","crash"]}' ;;
+    before_request) result='{"headers":{"x-plugin":"sh"}}' ;;
+    health) result='{"status":"healthy","message":"ready"}' ;;
+    handle_endpoint)
+      case "$line" in *'"endpoint":"crash"'*) echo "crashing" >&2; exit 3 ;; esac
+      result='{"status":201,"headers":{"x-served-by":"sh"},"body":{"hello":"world"}}' ;;
+    shutdown) exit 0 ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"error":{"code":-32601,"message":"unknown method"}}\n' "$id"; continue ;;
+  esac
+  printf '{"jsonrpc":"2.0","id":%s,"result":%s}\n' "$id" "$result"
+done
+"#;
+
+    #[tokio::test]
+    async fn test_process_plugin_is_called_and_restarted() {
+        let plugin = ProcessPlugin::builder("shell", "sh", vec!["-c".to_string(), SCRIPT.to_string()])
+            .with_timeout(Duration::from_secs(5))
+            .build();
+        plugin.initialize(&json!({"greeting": "hi"})).await.unwrap();
+        assert_eq!((plugin.version(), plugin.description()), ("0.3.0", "Shell plugin"));
+        assert!(plugin.claims("hello") && !plugin.claims("other"));
+
+        let mut request = Request::builder().uri("/users").body(Body::empty()).unwrap();
+        plugin.before_request(&mut request).await.unwrap();
+        assert_eq!(request.headers()["x-plugin"], "sh");
+        // Hooks the process does not implement are not called
+        let mut response = Response::new(Body::empty());
+        plugin.after_response(&mut response).await.unwrap();
+
+        let response = plugin.handle_endpoint("hello", &RequestData::default()).await.unwrap();
+        assert_eq!((response.status, &response.body), (StatusCode::CREATED, &json!({"hello": "world"})));
+        assert_eq!(response.headers["x-served-by"], "sh");
+        assert_eq!(plugin.health_check().await.unwrap().message, "ready");
+
+        // A crash fails the call in flight; the process is started again
+        let first = plugin.pid().unwrap();
+        let err = plugin.handle_endpoint("crash", &RequestData::default()).await.unwrap_err();
+        assert!(err.to_string().contains("exited during handle_endpoint"), "{}", err);
+        let restarted = async {
+            loop {
+                if plugin.pid().is_some_and(|pid| pid != first) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), restarted).await.unwrap();
+        assert!(plugin.handle_endpoint("hello", &RequestData::default()).await.is_ok());
+        assert_eq!(plugin.health_check().await.unwrap().details["restarts"], json!(1));
+
+        plugin.shutdown().await.unwrap();
+        assert!(plugin.pid().is_none());
+        let err = plugin.handle_endpoint("hello", &RequestData::default()).await.unwrap_err();
+        assert!(err.to_string().contains("is not running"), "{}", err);
+    }
+}
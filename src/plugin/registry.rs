@@ -0,0 +1,481 @@
+//! Plugins installed from a registry
+//!
+//! A registry is an `index.json` listing plugins, their versions and a
+//! library per platform. It is fetched over HTTP, cloned from a git
+//! repository or read from a directory. `backworks plugin install` picks the
+//! library for this platform, checks its SHA-256 and, when the project trusts
+//! signing keys, its Ed25519 signature, and places it in `./plugins`, where
+//! plugin discovery loads it on start. Installed versions are recorded under
+//! `plugins` in the project's package.json.
+
+use crate::error::{BackworksError, Result};
+use crate::upgrade::parse_version;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const INDEX_FILE: &str = "index.json";
+
+/// Registry index to use instead of the project's
+pub const REGISTRY_VAR: &str = "BACKWORKS_PLUGIN_REGISTRY";
+
+/// Directory installed plugin libraries are placed in
+pub const PLUGIN_DIR: &str = "plugins";
+
+/// Where git registries are cloned to
+const CACHE_DIR: &str = ".backworks/registry";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RegistryIndex {
+    #[serde(default)]
+    pub plugins: BTreeMap<String, RegistryPlugin>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryPlugin {
+    #[serde(default)]
+    pub description: String,
+    pub versions: Vec<RegistryVersion>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryVersion {
+    pub version: String,
+    /// Library per platform (`linux-x86_64`, `macos-aarch64`, ...)
+    pub artifacts: BTreeMap<String, Artifact>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Artifact {
+    /// Absolute, or relative to the index
+    pub url: String,
+    pub sha256: String,
+    /// Base64 Ed25519 signature of the library
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// The project's `pluginRegistry` settings
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistryConfig {
+    /// URL of the index, a git repository (`git+https://...` or `.git`) or a
+    /// directory
+    #[serde(default)]
+    pub index: String,
+    /// Base64 Ed25519 public keys; when set, libraries must be signed by one
+    #[serde(default)]
+    pub public_keys: Vec<String>,
+}
+
+/// This machine's platform, as named in registry artifacts
+pub fn platform() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// File name of an installed plugin's library
+pub fn library_file(name: &str) -> String {
+    format!("{}{}{}", std::env::consts::DLL_PREFIX, name, std::env::consts::DLL_SUFFIX)
+}
+
+enum Location {
+    Http(url::Url),
+    Dir(PathBuf),
+}
+
+/// A registry's index and where its artifacts are found
+pub struct Registry {
+    location: Location,
+    index: RegistryIndex,
+    public_keys: Vec<Vec<u8>>,
+}
+
+impl Registry {
+    /// Fetch the index `config` names; git registries are cloned, or
+    /// updated, below `project_dir`
+    pub async fn open(config: &RegistryConfig, project_dir: &Path) -> Result<Self> {
+        let source = config.index.trim();
+        if source.is_empty() {
+            return Err(BackworksError::config(format!(
+                "No plugin registry configured; set pluginRegistry.index in package.json, {} or --registry", REGISTRY_VAR
+            )));
+        }
+        let public_keys = config.public_keys.iter()
+            .map(|key| STANDARD.decode(key.trim()).map_err(|e| BackworksError::config(format!("Invalid registry public key '{}': {}", key, e))))
+            .collect::<Result<Vec<_>>>()?;
+
+        let (location, index) = if let Some(repository) = git_repository(source) {
+            let dir = project_dir.join(CACHE_DIR).join(&checksum(repository.as_bytes())[..16]);
+            sync_repository(repository, &dir).await?;
+            let index = read_index(&dir.join(INDEX_FILE))?;
+            (Location::Dir(dir), index)
+        } else if source.starts_with("http://") || source.starts_with("https://") {
+            let mut url = url::Url::parse(source)
+                .map_err(|e| BackworksError::config(format!("Invalid registry URL '{}': {}", source, e)))?;
+            if url.path().ends_with('/') {
+                url = url.join(INDEX_FILE).expect("index file name is a valid URL path");
+            }
+            let index = fetch(url.as_str()).await?;
+            let index = serde_json::from_slice(&index)
+                .map_err(|e| BackworksError::config(format!("Invalid registry index {}: {}", url, e)))?;
+            (Location::Http(url), index)
+        } else {
+            let path = project_dir.join(source.strip_prefix("file://").unwrap_or(source));
+            let (dir, file) = if path.is_dir() { (path.clone(), path.join(INDEX_FILE)) } else {
+                (path.parent().map(Path::to_path_buf).unwrap_or_default(), path)
+            };
+            (Location::Dir(dir), read_index(&file)?)
+        };
+        Ok(Self { location, index, public_keys })
+    }
+
+    pub fn index(&self) -> &RegistryIndex {
+        &self.index
+    }
+
+    /// Plugins whose name or description contains `query`, ignoring case
+    pub fn search(&self, query: &str) -> Vec<(&str, &RegistryPlugin)> {
+        let query = query.to_lowercase();
+        self.index.plugins.iter()
+            .filter(|(name, plugin)| name.to_lowercase().contains(&query) || plugin.description.to_lowercase().contains(&query))
+            .map(|(name, plugin)| (name.as_str(), plugin))
+            .collect()
+    }
+
+    /// The newest version of `name` built for this platform
+    pub fn latest(&self, name: &str) -> Result<&RegistryVersion> {
+        let platform = platform();
+        self.plugin(name)?.versions.iter()
+            .filter(|version| version.artifacts.contains_key(&platform))
+            .filter_map(|version| Some((parse_version(&version.version).ok()?, version)))
+            .max_by_key(|(parsed, _)| *parsed)
+            .map(|(_, version)| version)
+            .ok_or_else(|| BackworksError::config(format!("Plugin {} has no version built for {}", name, platform)))
+    }
+
+    /// `version` of `name`, or its newest, with the library for this platform
+    pub fn resolve(&self, name: &str, version: Option<&str>) -> Result<(&RegistryVersion, &Artifact)> {
+        let resolved = match version {
+            None => self.latest(name)?,
+            Some(wanted) => self.plugin(name)?.versions.iter()
+                .find(|candidate| candidate.version.trim_start_matches('v') == wanted.trim_start_matches('v'))
+                .ok_or_else(|| BackworksError::config(format!("Plugin {} has no version {}", name, wanted)))?,
+        };
+        let artifact = resolved.artifacts.get(&platform()).ok_or_else(|| BackworksError::config(format!(
+            "Plugin {} {} has no build for {}", name, resolved.version, platform()
+        )))?;
+        Ok((resolved, artifact))
+    }
+
+    /// Download `artifact` and verify it; `true` when its signature was
+    /// checked
+    pub async fn download(&self, artifact: &Artifact) -> Result<(Vec<u8>, bool)> {
+        let data = match &self.location {
+            _ if artifact.url.starts_with("http://") || artifact.url.starts_with("https://") => fetch(&artifact.url).await?,
+            Location::Http(index) => {
+                let url = index.join(&artifact.url)
+                    .map_err(|e| BackworksError::config(format!("Invalid artifact URL '{}': {}", artifact.url, e)))?;
+                fetch(url.as_str()).await?
+            }
+            Location::Dir(dir) => {
+                let path = dir.join(artifact.url.strip_prefix("file://").unwrap_or(&artifact.url));
+                std::fs::read(&path).map_err(|e| BackworksError::config(format!("Cannot read {}: {}", path.display(), e)))?
+            }
+        };
+        let signed = verify(artifact, &data, &self.public_keys)?;
+        Ok((data, signed))
+    }
+
+    fn plugin(&self, name: &str) -> Result<&RegistryPlugin> {
+        self.index.plugins.get(name)
+            .ok_or_else(|| BackworksError::config(format!("Plugin {} is not in the registry", name)))
+    }
+}
+
+/// Check `data` against the artifact's checksum and, when `public_keys` are
+/// trusted, its signature; `true` when the signature was checked
+pub fn verify(artifact: &Artifact, data: &[u8], public_keys: &[Vec<u8>]) -> Result<bool> {
+    let actual = checksum(data);
+    if !actual.eq_ignore_ascii_case(artifact.sha256.trim()) {
+        return Err(BackworksError::config(format!(
+            "Checksum mismatch for {}: expected {}, got {}", artifact.url, artifact.sha256, actual
+        )));
+    }
+    if public_keys.is_empty() {
+        return Ok(false);
+    }
+    let signature = artifact.signature.as_deref()
+        .ok_or_else(|| BackworksError::config(format!("{} is not signed", artifact.url)))
+        .and_then(|signature| STANDARD.decode(signature.trim())
+            .map_err(|e| BackworksError::config(format!("Invalid signature for {}: {}", artifact.url, e))))?;
+    let trusted = public_keys.iter().any(|key| {
+        ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, key).verify(data, &signature).is_ok()
+    });
+    if !trusted {
+        return Err(BackworksError::config(format!("{} is not signed by a trusted key", artifact.url)));
+    }
+    Ok(true)
+}
+
+fn checksum(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn git_repository(source: &str) -> Option<&str> {
+    source.strip_prefix("git+").or_else(|| source.ends_with(".git").then_some(source))
+}
+
+async fn sync_repository(repository: &str, dir: &Path) -> Result<()> {
+    let mut git = tokio::process::Command::new("git");
+    if dir.join(".git").exists() {
+        git.arg("-C").arg(dir).args(["pull", "--ff-only", "--quiet"]);
+    } else {
+        git.args(["clone", "--depth", "1", "--quiet", repository]).arg(dir);
+    }
+    let output = git.output().await
+        .map_err(|e| BackworksError::config(format!("Cannot run git for registry {}: {}", repository, e)))?;
+    if !output.status.success() {
+        return Err(BackworksError::config(format!(
+            "Fetching registry {} failed: {}", repository, String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+async fn fetch(url: &str) -> Result<Vec<u8>> {
+    let response = reqwest::get(url).await
+        .map_err(|e| BackworksError::config(format!("Cannot fetch {}: {}", url, e)))?;
+    if !response.status().is_success() {
+        return Err(BackworksError::config(format!("Cannot fetch {}: {}", url, response.status())));
+    }
+    let bytes = response.bytes().await
+        .map_err(|e| BackworksError::config(format!("Cannot fetch {}: {}", url, e)))?;
+    Ok(bytes.to_vec())
+}
+
+fn read_index(path: &Path) -> Result<RegistryIndex> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| BackworksError::config(format!("Cannot read registry index {}: {}", path.display(), e)))?;
+    serde_json::from_str(&content)
+        .map_err(|e| BackworksError::config(format!("Invalid registry index {}: {}", path.display(), e)))
+}
+
+/// A project's package.json and installed plugins
+pub struct Project {
+    dir: PathBuf,
+    text: String,
+    manifest: Value,
+}
+
+/// A plugin library placed in the project
+#[derive(Debug, Clone)]
+pub struct Installed {
+    pub name: String,
+    pub version: String,
+    pub path: PathBuf,
+    pub signed: bool,
+}
+
+impl Project {
+    pub fn open(dir: &Path) -> Result<Self> {
+        let path = dir.join("package.json");
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| BackworksError::config(format!("Cannot read {}: {}", path.display(), e)))?;
+        let manifest = serde_json::from_str(&text)
+            .map_err(|e| BackworksError::config(format!("Invalid {}: {}", path.display(), e)))?;
+        Ok(Self { dir: dir.to_path_buf(), text, manifest })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The project's registry, with its index replaced by `index` when given
+    pub fn registry_config(&self, index: Option<String>) -> Result<RegistryConfig> {
+        let mut config: RegistryConfig = match self.manifest.get("pluginRegistry") {
+            Some(value) => serde_json::from_value(value.clone())
+                .map_err(|e| BackworksError::config(format!("Invalid pluginRegistry in package.json: {}", e)))?,
+            None => RegistryConfig::default(),
+        };
+        if let Some(index) = index.or_else(|| std::env::var(REGISTRY_VAR).ok()) {
+            config.index = index;
+        }
+        Ok(config)
+    }
+
+    /// Installed plugins and their versions
+    pub fn installed(&self) -> BTreeMap<String, String> {
+        self.manifest.get("plugins").and_then(Value::as_object).into_iter().flatten()
+            .filter_map(|(name, version)| Some((name.clone(), version.as_str()?.to_string())))
+            .collect()
+    }
+
+    /// Download and verify `version` of `name`, or its newest, place it in
+    /// the plugin directory and record it
+    pub async fn install(&mut self, registry: &Registry, name: &str, version: Option<&str>) -> Result<Installed> {
+        let (resolved, artifact) = registry.resolve(name, version)?;
+        let (data, signed) = registry.download(artifact).await?;
+
+        let dir = self.dir.join(PLUGIN_DIR);
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(library_file(name));
+        // Renamed into place, so a running server keeps its mapped library
+        let partial = dir.join(format!(".{}.{}", library_file(name), uuid::Uuid::new_v4()));
+        std::fs::write(&partial, &data)?;
+        std::fs::rename(&partial, &path).inspect_err(|_| { let _ = std::fs::remove_file(&partial); })?;
+
+        self.record(name, Some(&resolved.version))?;
+        Ok(Installed { name: name.to_string(), version: resolved.version.clone(), path, signed })
+    }
+
+    /// Delete an installed plugin's library and its record
+    pub fn remove(&mut self, name: &str) -> Result<PathBuf> {
+        if !self.installed().contains_key(name) {
+            return Err(BackworksError::config(format!("Plugin {} is not installed", name)));
+        }
+        let path = self.dir.join(PLUGIN_DIR).join(library_file(name));
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        self.record(name, None)?;
+        Ok(path)
+    }
+
+    /// Set or drop the recorded version of `name` and write package.json,
+    /// leaving the rest of the file as it was
+    fn record(&mut self, name: &str, version: Option<&str>) -> Result<()> {
+        let mut plugins = self.manifest.get("plugins").and_then(Value::as_object).cloned().unwrap_or_default();
+        match version {
+            Some(version) => plugins.insert(name.to_string(), Value::String(version.to_string())),
+            None => plugins.remove(name),
+        };
+        let rendered = serde_json::to_string_pretty(&plugins)?.replace('\n', "\n  ");
+        self.text = match top_level_value(&self.text, "plugins") {
+            Some(span) => format!("{}{}{}", &self.text[..span.start], rendered, &self.text[span.end..]),
+            None => {
+                let close = self.text.rfind('}')
+                    .ok_or_else(|| BackworksError::config("package.json is not an object"))?;
+                let head = self.text[..close].trim_end();
+                let separator = if head.ends_with('{') { "" } else { "," };
+                format!("{}{}\n  \"plugins\": {}\n{}", head, separator, rendered, &self.text[close..])
+            }
+        };
+        self.manifest["plugins"] = Value::Object(plugins);
+        std::fs::write(self.dir.join("package.json"), &self.text)?;
+        Ok(())
+    }
+}
+
+/// Byte range of the value of `key` in the top-level object of `json`
+fn top_level_value(json: &str, key: &str) -> Option<std::ops::Range<usize>> {
+    let bytes = json.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+    let mut value_start = None;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                let start = i;
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                let rest = json[i + 1..].trim_start();
+                if depth == 1 && value_start.is_none() && json[start + 1..i] == *key && rest.starts_with(':') {
+                    let colon = json.len() - rest.len();
+                    value_start = Some(colon + 1 + (rest[1..].len() - rest[1..].trim_start().len()));
+                }
+            }
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => {
+                depth -= 1;
+                if depth <= 1 && value_start.is_some() {
+                    // The value's own closing bracket, or the object's
+                    let end = if depth == 1 { i + 1 } else { json[..i].trim_end().len() };
+                    return value_start.map(|start| start..end);
+                }
+            }
+            b',' if depth == 1 && value_start.is_some() => {
+                return value_start.map(|start| start..json[..i].trim_end().len());
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    #[tokio::test]
+    async fn test_plugins_install_update_and_remove_from_a_registry() {
+        let root = std::env::temp_dir().join(format!("backworks-registry-{}", uuid::Uuid::new_v4()));
+        let (registry_dir, project_dir) = (root.join("registry"), root.join("project"));
+        std::fs::create_dir_all(&registry_dir).unwrap();
+        std::fs::create_dir_all(&project_dir).unwrap();
+
+        let rng = ring::rand::SystemRandom::new();
+        let key = Ed25519KeyPair::from_pkcs8(Ed25519KeyPair::generate_pkcs8(&rng).unwrap().as_ref()).unwrap();
+        let mut versions = Vec::new();
+        for version in ["1.0.0", "1.2.0"] {
+            let library = format!("geoip {}", version).into_bytes();
+            let file = format!("geoip-{}.so", version);
+            std::fs::write(registry_dir.join(&file), &library).unwrap();
+            let artifact = Artifact { url: file, sha256: checksum(&library), signature: Some(STANDARD.encode(key.sign(&library))) };
+            versions.push(RegistryVersion { version: version.to_string(), artifacts: BTreeMap::from([(platform(), artifact)]) });
+        }
+        let index = RegistryIndex { plugins: BTreeMap::from([
+            ("geoip".to_string(), RegistryPlugin { description: "Country lookup by IP".to_string(), versions }),
+        ]) };
+        std::fs::write(registry_dir.join(INDEX_FILE), serde_json::to_string(&index).unwrap()).unwrap();
+
+        let package = format!(
+            "{{\n  \"name\": \"shop\",\n  \"version\": \"1.0.0\",\n  \"pluginRegistry\": {{\n    \"index\": \"../registry\",\n    \"publicKeys\": [\"{}\"]\n  }}\n}}\n",
+            STANDARD.encode(key.public_key().as_ref())
+        );
+        std::fs::write(project_dir.join("package.json"), &package).unwrap();
+
+        let mut project = Project::open(&project_dir).unwrap();
+        let registry = Registry::open(&project.registry_config(None).unwrap(), &project_dir).await.unwrap();
+        assert_eq!(registry.search("COUNTRY").len(), 1);
+        assert!(registry.search("auth").is_empty());
+
+        let installed = project.install(&registry, "geoip", Some("1.0.0")).await.unwrap();
+        assert!(installed.signed);
+        assert_eq!(std::fs::read(&installed.path).unwrap(), b"geoip 1.0.0");
+        let written = std::fs::read_to_string(project_dir.join("package.json")).unwrap();
+        assert!(written.starts_with("{\n  \"name\": \"shop\",\n  \"version\": \"1.0.0\",\n  \"pluginRegistry\""), "{}", written);
+        assert!(written.ends_with("  },\n  \"plugins\": {\n    \"geoip\": \"1.0.0\"\n  }\n}\n"), "{}", written);
+
+        // Updating replaces the recorded version in place
+        assert_eq!(registry.latest("geoip").unwrap().version, "1.2.0");
+        project.install(&registry, "geoip", None).await.unwrap();
+        let reopened = Project::open(&project_dir).unwrap();
+        assert_eq!(reopened.installed(), BTreeMap::from([("geoip".to_string(), "1.2.0".to_string())]));
+        assert_eq!(std::fs::read(&installed.path).unwrap(), b"geoip 1.2.0");
+
+        // Tampered and unsigned libraries are refused
+        let (_, artifact) = registry.resolve("geoip", Some("1.2.0")).unwrap();
+        let err = verify(artifact, b"geoip 6.6.6", &registry.public_keys).unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"), "{}", err);
+        let unsigned = Artifact { signature: None, ..artifact.clone() };
+        assert!(verify(&unsigned, b"geoip 1.2.0", &registry.public_keys).unwrap_err().to_string().contains("is not signed"));
+        let other = Ed25519KeyPair::from_pkcs8(Ed25519KeyPair::generate_pkcs8(&rng).unwrap().as_ref()).unwrap();
+        assert!(verify(artifact, b"geoip 1.2.0", &[other.public_key().as_ref().to_vec()]).is_err());
+        assert!(!verify(artifact, b"geoip 1.2.0", &[]).unwrap());
+
+        project.remove("geoip").unwrap();
+        assert!(!installed.path.exists());
+        assert!(Project::open(&project_dir).unwrap().installed().is_empty());
+        assert!(project.remove("geoip").is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
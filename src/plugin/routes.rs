@@ -0,0 +1,168 @@
+//! Routes plugins add to the API server and the dashboard
+//!
+//! A plugin declares each route with its method and path, so the server can
+//! check them against the blueprint's endpoints, its own routes and the
+//! routes of other plugins before serving any of them. Routes on the API
+//! server pass through the same global middleware as endpoints; dashboard
+//! pages are served below `/plugins/{plugin}`.
+
+use crate::error::{BackworksError, BackworksResult};
+use crate::routes::{find_conflicts, RouteConflict, RoutePattern};
+use axum::handler::Handler;
+use axum::http::Method;
+use axum::routing::{on, on_service, MethodFilter, MethodRouter};
+use axum::Router;
+use std::collections::BTreeMap;
+
+/// Routes a plugin serves, with the methods they were declared for
+#[derive(Clone, Default)]
+pub struct PluginRoutes {
+    /// By router path
+    routes: BTreeMap<String, PluginRoute>,
+    invalid: Vec<String>,
+}
+
+#[derive(Clone)]
+struct PluginRoute {
+    path: String,
+    methods: Vec<String>,
+    filter: MethodFilter,
+    router: MethodRouter,
+}
+
+impl PluginRoutes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve `method` requests to `path` with `handler`; paths take
+    /// parameters as endpoint paths do
+    pub fn route<H, T>(mut self, method: Method, path: &str, handler: H) -> Self
+    where
+        H: Handler<T, ()>,
+        T: 'static,
+    {
+        let Ok(filter) = MethodFilter::try_from(method.clone()) else {
+            self.invalid.push(format!("{} {}", method, path));
+            return self;
+        };
+        let pattern = RoutePattern::parse(path);
+        let route = self.routes.entry(pattern.router_path()).or_insert_with(|| PluginRoute {
+            path: pattern.to_string(),
+            methods: Vec::new(),
+            filter,
+            router: MethodRouter::new(),
+        });
+        if route.methods.contains(&method.to_string()) {
+            self.invalid.push(format!("{} {} (declared twice)", method, path));
+            return self;
+        }
+        route.methods.push(method.to_string());
+        route.filter = route.filter.or(filter);
+        route.router = std::mem::take(&mut route.router).merge(on(filter, handler));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// `(path, methods)` of every route, paths in endpoint syntax
+    pub fn declared(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.routes.values().map(|route| (route.path.as_str(), route.methods.as_slice()))
+    }
+
+    /// Add the routes to `router`, below `prefix`
+    pub(crate) fn mount<S: Clone + Send + Sync + 'static>(&self, mut router: Router<S>, prefix: &str) -> Router<S> {
+        for (path, route) in &self.routes {
+            let path = if path == "/" && !prefix.is_empty() { prefix.to_string() } else { format!("{}{}", prefix, path) };
+            router = router.route(&path, on_service(route.filter, route.router.clone()));
+        }
+        router
+    }
+}
+
+/// Fail on routes plugins declared that the router cannot serve, that
+/// collide with each other or with `reserved` routes, or that cannot be
+/// registered next to `endpoints`; routes shadowing one another are logged
+///
+/// `reserved` and `endpoints` are `(name, path, methods)`; plugins report as
+/// `plugin <name>`.
+pub fn check_plugin_routes(
+    plugins: &[(String, PluginRoutes)],
+    reserved: &[(String, String, Vec<String>)],
+    endpoints: &[(String, String, Vec<String>)],
+) -> BackworksResult<()> {
+    let mut problems = Vec::new();
+    let mut declared = Vec::new();
+    for (plugin, routes) in plugins {
+        let owner = format!("plugin {}", plugin);
+        problems.extend(routes.invalid.iter().map(|route| format!("{} declares an invalid route {}", owner, route)));
+        declared.extend(routes.declared().map(|(path, methods)| (owner.clone(), path.to_string(), methods.to_vec())));
+    }
+
+    let all: Vec<(&str, &str, &[String])> = reserved.iter().chain(endpoints).chain(&declared)
+        .map(|(name, path, methods)| (name.as_str(), path.as_str(), methods.as_slice()))
+        .collect();
+    let is_reserved = |name: &str| reserved.iter().any(|(reserved, _, _)| reserved == name);
+    let is_plugin = |name: &str| name.starts_with("plugin ");
+    for conflict in find_conflicts(&all) {
+        if !is_plugin(&conflict.first) && !is_plugin(&conflict.second) {
+            continue;
+        }
+        if conflict.is_fatal() || is_reserved(&conflict.first) || is_reserved(&conflict.second) {
+            problems.push(describe(&conflict));
+        } else {
+            tracing::warn!("⚠️ {}", conflict);
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(BackworksError::config(format!("Plugin routes conflict: {}", problems.join("; "))))
+    }
+}
+
+fn describe(conflict: &RouteConflict) -> String {
+    match conflict.overlap {
+        crate::routes::Overlap::Shadowed { .. } => format!(
+            "{} ({}) overlaps {} ({})", conflict.first, conflict.first_path, conflict.second, conflict.second_path
+        ),
+        _ => conflict.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn routes(of: &[(&str, &str, &[&str])]) -> Vec<(String, String, Vec<String>)> {
+        of.iter().map(|(name, path, methods)| (name.to_string(), path.to_string(), methods.iter().map(|m| m.to_string()).collect())).collect()
+    }
+
+    #[test]
+    fn test_plugin_routes_are_checked_against_each_other_and_the_server() {
+        let reserved = routes(&[("admin API", "/_backworks/{*rest}", &["GET", "POST", "PUT", "DELETE"]), ("liveness", "/health", &["GET"])]);
+        let endpoints = routes(&[("users", "/users/{id}", &["GET"]), ("login_form", "/login", &["GET"])]);
+        let auth = PluginRoutes::new()
+            .route(Method::POST, "/login", || async { "welcome" })
+            .route(Method::POST, "/logout", || async { "bye" });
+        let cache = PluginRoutes::new().route(Method::GET, "/cache/stats", || async { "{}" });
+        let plugins = vec![("auth".to_string(), auth), ("cache".to_string(), cache)];
+        assert_eq!(plugins[0].1.declared().collect::<Vec<_>>(), [("/login", &["POST".to_string()][..]), ("/logout", &["POST".to_string()][..])]);
+        check_plugin_routes(&plugins, &reserved, &endpoints).unwrap();
+
+        let clashing = vec![
+            ("stats".to_string(), PluginRoutes::new().route(Method::GET, "/cache/stats", || async { "{}" })),
+            ("users".to_string(), PluginRoutes::new().route(Method::DELETE, "/users/{user_id}", || async { "" })),
+            ("admin".to_string(), PluginRoutes::new().route(Method::GET, "/_backworks/cache", || async { "" })),
+            ("twice".to_string(), PluginRoutes::new().route(Method::GET, "/twice", || async { "" }).route(Method::GET, "/twice", || async { "" })),
+        ];
+        let err = check_plugin_routes(&[plugins, clashing].concat(), &reserved, &endpoints).unwrap_err().to_string();
+        assert!(err.contains("Duplicate route GET /cache/stats: declared by 'plugin cache' and 'plugin stats'"), "{}", err);
+        assert!(err.contains("Routes '/users/{id}' and '/users/{user_id}' differ only in parameter names"), "{}", err);
+        assert!(err.contains("admin API (/_backworks/{*rest}) overlaps plugin admin (/_backworks/cache)"), "{}", err);
+        assert!(err.contains("plugin twice declares an invalid route GET /twice (declared twice)"), "{}", err);
+    }
+}
@@ -0,0 +1,253 @@
+//! JSON Schemas of plugin configuration
+//!
+//! A plugin exports the schema of its `config` block through
+//! [`BackworksPlugin::config_schema`](crate::plugin::BackworksPlugin::config_schema).
+//! Registering the plugin records the schema for the process, so every check
+//! of a blueprint after that (startup, reloads, `backworks validate`) reports
+//! config that does not match it, by path:
+//! `plugins.proxy.config.timeout must be an integer`.
+//!
+//! The keywords checked are `type`, `enum`, `const`, `properties`,
+//! `required`, `additionalProperties`, `items`, `minimum`, `maximum`,
+//! `exclusiveMinimum`, `exclusiveMaximum`, `minLength`, `maxLength`,
+//! `pattern`, `minItems`, `maxItems`, `allOf`, `anyOf` and `oneOf`; others,
+//! such as `description` and `default`, are ignored.
+
+use crate::error::{BackworksError, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+fn schemas() -> &'static RwLock<HashMap<String, Value>> {
+    static SCHEMAS: OnceLock<RwLock<HashMap<String, Value>>> = OnceLock::new();
+    SCHEMAS.get_or_init(Default::default)
+}
+
+/// Check the config of plugin `name` against `schema` from now on
+pub fn register_config_schema(name: &str, schema: Value) -> Result<()> {
+    if !schema.is_object() && !schema.is_boolean() {
+        return Err(BackworksError::config(format!("Plugin {} exports a config schema that is not an object", name)));
+    }
+    schemas().write().unwrap_or_else(|e| e.into_inner()).insert(name.to_string(), schema);
+    Ok(())
+}
+
+/// The config schema plugin `name` exported, if any
+pub fn config_schema(name: &str) -> Option<Value> {
+    schemas().read().unwrap_or_else(|e| e.into_inner()).get(name).cloned()
+}
+
+/// Fail with every way `config` does not match the schema of plugin `name`;
+/// plugins without a schema accept any config
+pub fn validate_plugin_config(name: &str, config: &Value) -> Result<()> {
+    let Some(schema) = config_schema(name) else {
+        return Ok(());
+    };
+    // A plugin listed without a `config` block gets an empty one
+    let empty = Value::Object(Default::default());
+    let config = if config.is_null() { &empty } else { config };
+    let mut problems = Vec::new();
+    check(&schema, config, &format!("plugins.{}.config", name), &mut problems);
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(BackworksError::config(problems.join("; ")))
+    }
+}
+
+/// Add a problem for each way `value` at `path` does not match `schema`
+pub fn check(schema: &Value, value: &Value, path: &str, problems: &mut Vec<String>) {
+    let schema = match schema {
+        Value::Bool(true) => return,
+        Value::Bool(false) => return problems.push(format!("{} is not allowed", path)),
+        Value::Object(schema) => schema,
+        _ => return,
+    };
+
+    if let Some(types) = schema.get("type") {
+        let types: Vec<&str> = match types {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|name| has_type(value, name)) {
+            let expected: Vec<String> = types.iter().map(|name| with_article(name)).collect();
+            // Checking further keywords would only repeat the mismatch
+            return problems.push(format!("{} must be {}", path, expected.join(" or ")));
+        }
+    }
+    if let Some(Value::Array(allowed)) = schema.get("enum") {
+        if !allowed.contains(value) {
+            let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            problems.push(format!("{} must be one of {}", path, allowed.join(", ")));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            problems.push(format!("{} must be {}", path, expected));
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for required in schema.get("required").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str) {
+                if !object.contains_key(required) {
+                    problems.push(format!("{}.{} is required", path, required));
+                }
+            }
+            for (key, item) in object {
+                let item_path = format!("{}.{}", path, key);
+                match (properties.and_then(|properties| properties.get(key)), schema.get("additionalProperties")) {
+                    (Some(property), _) => check(property, item, &item_path, problems),
+                    (None, Some(additional)) => check(additional, item, &item_path, problems),
+                    (None, None) => {}
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    check(item_schema, item, &format!("{}[{}]", path, index), problems);
+                }
+            }
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64).filter(|min| (items.len() as u64) < *min) {
+                problems.push(format!("{} must have at least {} item{}", path, min, plural(min)));
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64).filter(|max| (items.len() as u64) > *max) {
+                problems.push(format!("{} must have at most {} item{}", path, max, plural(max)));
+            }
+        }
+        Value::String(text) => {
+            let length = text.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64).filter(|min| length < *min) {
+                problems.push(format!("{} must be at least {} character{} long", path, min, plural(min)));
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64).filter(|max| length > *max) {
+                problems.push(format!("{} must be at most {} character{} long", path, max, plural(max)));
+            }
+            if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+                match regex::Regex::new(pattern) {
+                    Ok(regex) if !regex.is_match(text) => problems.push(format!("{} must match {}", path, pattern)),
+                    Ok(_) => {}
+                    Err(e) => problems.push(format!("{}: the plugin's schema has an invalid pattern: {}", path, e)),
+                }
+            }
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            let bound = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+            if let Some(min) = bound("minimum").filter(|min| number < *min) {
+                problems.push(format!("{} must be at least {}", path, min));
+            }
+            if let Some(max) = bound("maximum").filter(|max| number > *max) {
+                problems.push(format!("{} must be at most {}", path, max));
+            }
+            if let Some(min) = bound("exclusiveMinimum").filter(|min| number <= *min) {
+                problems.push(format!("{} must be greater than {}", path, min));
+            }
+            if let Some(max) = bound("exclusiveMaximum").filter(|max| number >= *max) {
+                problems.push(format!("{} must be less than {}", path, max));
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(Value::Array(all)) = schema.get("allOf") {
+        for sub in all {
+            check(sub, value, path, problems);
+        }
+    }
+    let matching = |subs: &Vec<Value>| subs.iter().filter(|sub| {
+        let mut sub_problems = Vec::new();
+        check(sub, value, path, &mut sub_problems);
+        sub_problems.is_empty()
+    }).count();
+    if let Some(Value::Array(any)) = schema.get("anyOf") {
+        if matching(any) == 0 {
+            problems.push(format!("{} does not match any of the allowed forms", path));
+        }
+    }
+    if let Some(Value::Array(one)) = schema.get("oneOf") {
+        if matching(one) != 1 {
+            problems.push(format!("{} must match exactly one of the allowed forms", path));
+        }
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|number| number.fract() == 0.0),
+        _ => true,
+    }
+}
+
+fn with_article(name: &str) -> String {
+    match name {
+        "null" => "null".to_string(),
+        "object" | "array" | "integer" => format!("an {}", name),
+        _ => format!("a {}", name),
+    }
+}
+
+fn plural(count: u64) -> &'static str {
+    if count == 1 { "" } else { "s" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_plugin_config_is_checked_against_its_schema() {
+        register_config_schema("schema_test_proxy", json!({
+            "type": "object",
+            "required": ["targets"],
+            "additionalProperties": false,
+            "properties": {
+                "targets": { "type": "array", "minItems": 1, "items": { "type": "string", "pattern": "^https?://" } },
+                "timeout": { "type": "integer", "minimum": 1 },
+                "mode": { "enum": ["round_robin", "random"] },
+                "retries": { "type": ["integer", "null"], "maximum": 5 },
+                "tls": { "anyOf": [{ "type": "boolean" }, { "type": "object", "required": ["ca"] }] }
+            }
+        })).unwrap();
+
+        validate_plugin_config("schema_test_proxy", &json!({ "targets": ["http://a"], "timeout": 30, "retries": null, "tls": true })).unwrap();
+        validate_plugin_config("schema_test_unknown", &json!({ "anything": 1 })).unwrap();
+
+        let err = validate_plugin_config("schema_test_proxy", &json!({
+            "targets": ["http://a", "ftp://b"],
+            "timeout": "30s",
+            "mode": "fastest",
+            "retries": 9,
+            "tls": { "verify": false },
+            "extra": 1
+        })).unwrap_err().to_string();
+        for expected in [
+            "plugins.schema_test_proxy.config.targets[1] must match ^https?://",
+            "plugins.schema_test_proxy.config.timeout must be an integer",
+            "plugins.schema_test_proxy.config.mode must be one of \"round_robin\", \"random\"",
+            "plugins.schema_test_proxy.config.retries must be at most 5",
+            "plugins.schema_test_proxy.config.tls does not match any of the allowed forms",
+            "plugins.schema_test_proxy.config.extra is not allowed",
+        ] {
+            assert!(err.contains(expected), "{} in {}", expected, err);
+        }
+
+        let err = validate_plugin_config("schema_test_proxy", &json!({ "targets": [] })).unwrap_err().to_string();
+        assert!(err.contains("config.targets must have at least 1 item"), "{}", err);
+        let err = validate_plugin_config("schema_test_proxy", &json!(null)).unwrap_err().to_string();
+        assert_eq!(err, "Configuration error: plugins.schema_test_proxy.config.targets is required");
+        let err = validate_plugin_config("schema_test_proxy", &json!(["http://a"])).unwrap_err().to_string();
+        assert!(err.contains("plugins.schema_test_proxy.config must be an object"), "{}", err);
+        assert!(register_config_schema("schema_test_bad", json!("object")).is_err());
+    }
+}
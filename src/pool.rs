@@ -0,0 +1,305 @@
+//! Connection pools of database plugins
+//!
+//! The blueprint's `database` section, or its `databases.<plugin>` entry, is
+//! handed to each database plugin before the server starts: the connection
+//! string and the pool's `min_connections`, `max_connections` and
+//! `connection_timeout`. Each pool is then pinged every
+//! `health_check_interval`; a pool that fails its check, or could not
+//! connect at startup, is reconnected with exponential backoff until it
+//! answers again. Pool connections and health are reported on the metrics
+//! endpoint.
+
+use crate::config::{BackworksConfig, DatabaseConfig, PoolConfig};
+use crate::error::{BackworksError, Result};
+use crate::plugin::{DatabaseHandle, DatabaseSettings, PluginManager, PoolSettings};
+use crate::request_metrics::RequestMetrics;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Delays between reconnect attempts, doubling from `initial` up to `max`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Backoff {
+    initial: Duration,
+    max: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self { initial: Duration::from_secs(1), max: Duration::from_secs(60) }
+    }
+}
+
+impl Backoff {
+    fn next(&self, delay: Duration) -> Duration {
+        (delay * 2).min(self.max)
+    }
+}
+
+/// Database plugins with the settings the blueprint gives them
+#[derive(Default)]
+pub struct DatabasePools {
+    pools: Vec<Pool>,
+    backoff: Backoff,
+}
+
+struct Pool {
+    database: DatabaseHandle,
+    settings: DatabaseSettings,
+}
+
+impl DatabasePools {
+    /// Every database plugin, when the blueprint has a `database` section
+    pub async fn new(config: &BackworksConfig, plugins: &PluginManager) -> Result<Self> {
+        let mut pools = Vec::new();
+        for database in plugins.databases().await {
+            if let Some(settings) = settings(config, database.plugin())? {
+                pools.push(Pool { database, settings });
+            }
+        }
+        Ok(Self { pools, backoff: Backoff::default() })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pools.is_empty()
+    }
+
+    /// Connect every pool, then keep each healthy in the background
+    pub async fn start(self, metrics: RequestMetrics) -> Vec<JoinHandle<()>> {
+        let mut supervisors = Vec::with_capacity(self.pools.len());
+        for pool in self.pools {
+            let plugin = pool.database.plugin().to_string();
+            let pool_settings = pool.settings.pool;
+            let connected = match pool.open().await {
+                Ok(()) => {
+                    info!("🗄️  Database of plugin {} connected ({}-{} connections, checked every {:?})",
+                        plugin, pool_settings.min_connections, pool_settings.max_connections, pool_settings.health_check_interval);
+                    true
+                }
+                Err(e) => {
+                    warn!("⚠️ Database of plugin {} did not connect, retrying in the background: {}", plugin, e);
+                    false
+                }
+            };
+            supervisors.push(tokio::spawn(pool.supervise(connected, metrics.clone(), self.backoff)));
+        }
+        supervisors
+    }
+}
+
+impl Pool {
+    /// Connect and check that the database answers
+    async fn open(&self) -> Result<()> {
+        self.database.connect(&self.settings).await?;
+        self.database.ping(self.settings.pool.connect_timeout).await
+    }
+
+    async fn supervise(self, mut up: bool, metrics: RequestMetrics, backoff: Backoff) {
+        let plugin = self.database.plugin().to_string();
+        loop {
+            if !up {
+                metrics.record_database_up(&plugin, false);
+                self.reconnect(&metrics, backoff).await;
+            }
+            metrics.record_database_up(&plugin, true);
+            if let Some(stats) = self.database.pool_stats() {
+                metrics.record_pool(&plugin, &stats);
+            }
+            tokio::time::sleep(self.settings.pool.health_check_interval).await;
+            up = match self.database.ping(self.settings.pool.connect_timeout).await {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("⚠️ Database of plugin {} failed its health check: {}", plugin, e);
+                    false
+                }
+            };
+        }
+    }
+
+    /// Try to connect until the database answers
+    async fn reconnect(&self, metrics: &RequestMetrics, backoff: Backoff) {
+        let plugin = self.database.plugin();
+        let mut delay = backoff.initial;
+        loop {
+            tokio::time::sleep(delay).await;
+            metrics.record_reconnect(plugin);
+            match self.open().await {
+                Ok(()) => {
+                    info!("🗄️  Database of plugin {} reconnected", plugin);
+                    return;
+                }
+                Err(e) => {
+                    delay = backoff.next(delay);
+                    warn!("⚠️ Reconnecting the database of plugin {} failed, retrying in {:?}: {}", plugin, delay, e);
+                }
+            }
+        }
+    }
+}
+
+/// Settings the blueprint gives the database plugin named `plugin`;
+/// `None` without a `database` section
+pub fn settings(config: &BackworksConfig, plugin: &str) -> Result<Option<DatabaseSettings>> {
+    let Some(database) = config.database.as_ref() else {
+        return Ok(None);
+    };
+    let own = database.databases.as_ref().and_then(|databases| databases.get(plugin)).unwrap_or(database);
+    let pool = pool_settings(own.pool.as_ref().or(database.pool.as_ref()))
+        .map_err(|e| BackworksError::config(format!("Database pool of plugin {}: {}", plugin, e)))?;
+    Ok(Some(DatabaseSettings {
+        db_type: own.db_type.clone(),
+        connection_string: own.resolved_connection_string()?,
+        pool,
+    }))
+}
+
+/// Check the pool options of the `database` section and its `databases`
+pub fn validate(config: &BackworksConfig) -> Result<()> {
+    let Some(database) = config.database.as_ref() else {
+        return Ok(());
+    };
+    let mut sections: Vec<(String, &DatabaseConfig)> = vec![("database".to_string(), database)];
+    sections.extend(database.databases.iter().flatten().map(|(name, nested)| (format!("database.databases.{}", name), nested)));
+    for (section, database) in sections {
+        pool_settings(database.pool.as_ref())
+            .map_err(|e| BackworksError::config(format!("{}.pool: {}", section, e)))?;
+    }
+    Ok(())
+}
+
+fn pool_settings(pool: Option<&PoolConfig>) -> std::result::Result<PoolSettings, String> {
+    let defaults = PoolSettings::default();
+    let Some(pool) = pool else {
+        return Ok(defaults);
+    };
+    let seconds = |value: Option<u64>, name: &str, default: Duration| match value {
+        Some(0) => Err(format!("{} must be at least 1 second", name)),
+        Some(seconds) => Ok(Duration::from_secs(seconds)),
+        None => Ok(default),
+    };
+    let max_connections = pool.max_connections.unwrap_or(defaults.max_connections.max(pool.min_connections.unwrap_or(0)));
+    let min_connections = pool.min_connections.unwrap_or(0);
+    if max_connections == 0 {
+        return Err("max_connections must be at least 1".to_string());
+    }
+    if min_connections > max_connections {
+        return Err(format!("min_connections ({}) exceeds max_connections ({})", min_connections, max_connections));
+    }
+    Ok(PoolSettings {
+        min_connections,
+        max_connections,
+        connect_timeout: seconds(pool.connection_timeout, "connection_timeout", defaults.connect_timeout)?,
+        health_check_interval: seconds(pool.health_check_interval, "health_check_interval", defaults.health_check_interval)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::{BackworksPlugin, DatabasePlugin, ListQuery, PoolStats, Row, RowPage, TableSchema};
+    use async_trait::async_trait;
+    use serde_json::Value;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// A pool whose database can be taken down
+    #[derive(Default)]
+    struct Flaky {
+        down: AtomicBool,
+        connects: AtomicU32,
+        settings: Mutex<Option<DatabaseSettings>>,
+    }
+
+    #[async_trait]
+    impl BackworksPlugin for Flaky {
+        fn name(&self) -> &str { "flaky" }
+        fn version(&self) -> &str { "0.0.0" }
+        fn description(&self) -> &str { "goes down on request" }
+        async fn initialize(&self, _config: &Value) -> Result<()> { Ok(()) }
+        async fn shutdown(&self) -> Result<()> { Ok(()) }
+        fn database(&self) -> Option<&dyn DatabasePlugin> { Some(self) }
+    }
+
+    #[async_trait]
+    impl DatabasePlugin for Flaky {
+        async fn table_schema(&self, table: &str) -> Result<TableSchema> { Ok(TableSchema::new(table, Vec::new())) }
+        async fn list(&self, _table: &str, _query: &ListQuery) -> Result<RowPage> { Ok(RowPage::default()) }
+        async fn get(&self, _table: &str, _key: &Value) -> Result<Option<Row>> { Ok(None) }
+        async fn insert(&self, _table: &str, row: Row) -> Result<Row> { Ok(row) }
+        async fn update(&self, _table: &str, _key: &Value, _changes: Row) -> Result<Option<Row>> { Ok(None) }
+        async fn delete(&self, _table: &str, _key: &Value) -> Result<bool> { Ok(false) }
+
+        async fn connect(&self, settings: &DatabaseSettings) -> Result<()> {
+            self.connects.fetch_add(1, Ordering::SeqCst);
+            *self.settings.lock().unwrap() = Some(settings.clone());
+            self.ping().await
+        }
+
+        async fn ping(&self) -> Result<()> {
+            match self.down.load(Ordering::SeqCst) {
+                true => Err(BackworksError::database("connection refused")),
+                false => Ok(()),
+            }
+        }
+
+        fn pool_stats(&self) -> Option<PoolStats> {
+            Some(PoolStats { in_use: 2, idle: 3, max: 5 })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pools_get_their_settings_and_reconnect() {
+        std::env::set_var("BACKWORKS_POOL_TEST_URL", "postgres://db/flaky");
+        let config: BackworksConfig = serde_yaml::from_str(r#"
+name: api
+endpoints: {}
+database:
+  type: sqlite
+  connection_string: "sqlite::memory:"
+  pool: { max_connections: 20, connection_timeout: 5 }
+  databases:
+    flaky:
+      type: postgresql
+      connection_string_env: BACKWORKS_POOL_TEST_URL
+      pool: { min_connections: 2, max_connections: 5, health_check_interval: 1 }
+"#).unwrap();
+        validate(&config).unwrap();
+        assert_eq!(settings(&config, "other").unwrap().unwrap().pool, PoolSettings {
+            max_connections: 20,
+            connect_timeout: Duration::from_secs(5),
+            ..PoolSettings::default()
+        });
+
+        let manager = PluginManager::new();
+        let flaky = Arc::new(Flaky::default());
+        manager.register_plugin(flaky.clone(), None, None).await.unwrap();
+        let mut pools = DatabasePools::new(&config, &manager).await.unwrap();
+        let given = &pools.pools[0].settings;
+        assert_eq!((given.db_type.as_str(), given.connection_string.as_deref()), ("postgresql", Some("postgres://db/flaky")));
+        assert_eq!((given.pool.min_connections, given.pool.max_connections), (2, 5));
+
+        // Checked and reconnected quickly, and down at startup
+        pools.backoff = Backoff { initial: Duration::from_millis(5), max: Duration::from_millis(20) };
+        pools.pools[0].settings.pool.health_check_interval = Duration::from_millis(5);
+        let given = pools.pools[0].settings.clone();
+        flaky.down.store(true, Ordering::SeqCst);
+        let metrics = RequestMetrics::new(&config);
+        let supervisors = pools.start(metrics.clone()).await;
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(metrics.render().contains(r#"backworks_db_up{plugin="flaky"} 0"#));
+
+        flaky.down.store(false, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        let rendered = metrics.render();
+        assert!(rendered.contains(r#"backworks_db_up{plugin="flaky"} 1"#), "{}", rendered);
+        assert!(rendered.contains(r#"backworks_db_pool_connections{plugin="flaky",state="in_use"} 2"#));
+        assert!(rendered.contains(r#"backworks_db_pool_max_connections{plugin="flaky"} 5"#));
+        assert!(flaky.connects.load(Ordering::SeqCst) >= 3);
+        assert_eq!(flaky.settings.lock().unwrap().as_ref(), Some(&given));
+        supervisors.into_iter().for_each(|supervisor| supervisor.abort());
+
+        let mut broken = config.clone();
+        broken.database.as_mut().unwrap().databases.as_mut().unwrap().get_mut("flaky").unwrap().pool.as_mut().unwrap().min_connections = Some(9);
+        assert!(validate(&broken).unwrap_err().to_string().contains("database.databases.flaky.pool: min_connections (9) exceeds max_connections (5)"));
+    }
+}
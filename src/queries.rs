@@ -0,0 +1,272 @@
+//! Named SQL queries of database endpoints
+//!
+//! `database.queries` maps an HTTP method of the endpoint to a statement.
+//! `{{ path.id }}`, `{{ body.field }}` and `{{ query.page }}` mark request
+//! values: they are replaced by placeholders when the blueprint is loaded
+//! and bound as parameters of a prepared statement when a request runs the
+//! query, so request values never become part of the SQL text. Nested body
+//! fields are reached with more segments (`{{ body.address.city }}`);
+//! values the request does not have are bound as `NULL`.
+
+use crate::config::EndpointConfig;
+use crate::error::{BackworksError, Result};
+use crate::plugin::{PluginManager, PreparedQuery, QueryResult};
+use crate::routes::RoutePattern;
+use crate::server::RequestData;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Queries of every endpoint, compiled when the blueprint is loaded
+#[derive(Debug, Default)]
+pub struct EndpointQueries {
+    endpoints: HashMap<String, QueryEndpoint>,
+}
+
+#[derive(Debug)]
+struct QueryEndpoint {
+    /// Database plugin the endpoint names, if any
+    plugin: Option<String>,
+    /// Whether the path names a single row, by its parameters
+    single: bool,
+    list_key: Option<String>,
+    single_key: Option<String>,
+    /// By upper-case method
+    queries: HashMap<String, CompiledQuery>,
+}
+
+#[derive(Debug)]
+struct CompiledQuery {
+    name: String,
+    sql: String,
+    placeholders: Vec<usize>,
+    bindings: Vec<Binding>,
+}
+
+/// A request value a placeholder is bound to
+#[derive(Debug, Clone, PartialEq)]
+struct Binding {
+    source: Source,
+    path: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Source {
+    Path,
+    Body,
+    Query,
+}
+
+impl EndpointQueries {
+    pub fn new(endpoints: &HashMap<String, EndpointConfig>) -> Result<Self> {
+        let mut compiled = HashMap::new();
+        for (name, endpoint) in endpoints {
+            let Some(database) = endpoint.database.as_ref() else {
+                continue;
+            };
+            let Some(ref queries) = database.queries else {
+                continue;
+            };
+            let params: Vec<String> = RoutePattern::parse(&endpoint.path).params().map(String::from).collect();
+            let mut by_method = HashMap::new();
+            for (query_name, source) in queries {
+                let method = query_name.to_ascii_uppercase();
+                if !endpoint.methods.iter().any(|m| m.eq_ignore_ascii_case(&method)) {
+                    return Err(BackworksError::config(format!(
+                        "Endpoint '{}' query '{}' must be named after one of the endpoint's methods", name, query_name
+                    )));
+                }
+                let query = compile(query_name, source)
+                    .map_err(|e| BackworksError::config(format!("Endpoint '{}' query '{}': {}", name, query_name, e)))?;
+                if let Some(unknown) = query.bindings.iter()
+                    .find(|binding| binding.source == Source::Path && !params.contains(&binding.path[0]))
+                {
+                    return Err(BackworksError::config(format!(
+                        "Endpoint '{}' query '{}' uses path.{}, which is not a parameter of {}",
+                        name, query_name, unknown.path[0], endpoint.path
+                    )));
+                }
+                by_method.insert(method, query);
+            }
+            let transform = database.transform.as_ref();
+            compiled.insert(name.clone(), QueryEndpoint {
+                plugin: endpoint.plugin.clone(),
+                single: !params.is_empty(),
+                list_key: transform.and_then(|t| t.list.clone()),
+                single_key: transform.and_then(|t| t.single.clone()),
+                queries: by_method,
+            });
+        }
+        Ok(Self { endpoints: compiled })
+    }
+
+    /// The query `endpoint` runs for `method`, with the request's values
+    /// bound to it
+    pub fn bind(&self, endpoint: &str, method: &str, request: &RequestData) -> Option<PreparedQuery> {
+        let query = self.endpoints.get(endpoint)?.queries.get(&method.to_ascii_uppercase())?;
+        Some(PreparedQuery {
+            name: query.name.clone(),
+            sql: query.sql.clone(),
+            params: query.bindings.iter().map(|binding| binding.resolve(request)).collect(),
+            placeholders: query.placeholders.clone(),
+        })
+    }
+
+    /// Answer a request with the endpoint's query for its method; `None`
+    /// when the endpoint has no query for it
+    pub async fn respond(&self, endpoint: &str, method: &str, request: &RequestData, plugins: &PluginManager) -> Result<Option<String>> {
+        let Some(query) = self.bind(endpoint, method, request) else {
+            return Ok(None);
+        };
+        let settings = &self.endpoints[endpoint];
+        let database = plugins.database(settings.plugin.as_deref()).await?;
+        let result = database.query(&query).await?;
+        let success = if method.eq_ignore_ascii_case("POST") { 201 } else { 200 };
+        let (status, body) = settings.shape(result, success);
+        Ok(Some(json!({ "status": status, "body": body }).to_string()))
+    }
+}
+
+impl QueryEndpoint {
+    /// The response body of a query's result
+    fn shape(&self, result: QueryResult, success: u16) -> (u16, Value) {
+        let affected = json!({ "rows_affected": result.rows_affected });
+        if self.single {
+            return match result.rows.into_iter().next() {
+                Some(row) => (success, wrap(self.single_key.as_deref(), Value::Object(row))),
+                None if result.rows_affected > 0 => (success, affected),
+                None => (404, json!({ "error": "No matching row" })),
+            };
+        }
+        if result.rows.is_empty() && result.rows_affected > 0 {
+            return (success, affected);
+        }
+        (success, wrap(self.list_key.as_deref(), Value::Array(result.rows.into_iter().map(Value::Object).collect())))
+    }
+}
+
+fn wrap(key: Option<&str>, value: Value) -> Value {
+    match key {
+        Some(key) => json!({ key: value }),
+        None => value,
+    }
+}
+
+impl Binding {
+    fn resolve(&self, request: &RequestData) -> Value {
+        let (first, rest) = self.path.split_first().expect("bindings name a value");
+        let root = match self.source {
+            Source::Path => request.path_params.get(first).cloned(),
+            Source::Query => request.query_params.get(first).cloned().map(Value::String),
+            Source::Body => request.body.as_ref().and_then(|body| body.get(first)).cloned(),
+        };
+        rest.iter()
+            .try_fold(root.unwrap_or(Value::Null), |value, segment| match value {
+                Value::Object(mut fields) => fields.remove(segment),
+                Value::Array(mut items) => segment.parse::<usize>().ok()
+                    .filter(|&index| index < items.len())
+                    .map(|index| items.swap_remove(index)),
+                _ => None,
+            })
+            .unwrap_or(Value::Null)
+    }
+}
+
+/// Replace the `{{ source.name }}` markers of a statement by placeholders
+fn compile(name: &str, source: &str) -> std::result::Result<CompiledQuery, String> {
+    let mut sql = String::with_capacity(source.len());
+    let mut placeholders = Vec::new();
+    let mut bindings = Vec::new();
+    // The quote character of the literal or identifier being read
+    let mut quoted: Option<char> = None;
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        if quoted.is_none() && rest.starts_with("{{") {
+            let end = rest.find("}}").ok_or_else(|| "unclosed `{{`".to_string())?;
+            bindings.push(parse_binding(rest[2..end].trim())?);
+            placeholders.push(sql.len());
+            sql.push('?');
+            rest = &rest[end + 2..];
+            continue;
+        }
+        if rest.starts_with("{{") {
+            return Err("request values cannot be placed inside quotes; bind them without quotes".to_string());
+        }
+        match (quoted, c) {
+            (None, '\'' | '"') => quoted = Some(c),
+            (Some(open), c) if c == open => quoted = None,
+            (None, '?') => return Err("`?` is reserved for placeholders; mark request values with `{{ }}`".to_string()),
+            _ => {}
+        }
+        sql.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    if quoted.is_some() {
+        return Err("unclosed quote".to_string());
+    }
+    Ok(CompiledQuery { name: name.to_string(), sql, placeholders, bindings })
+}
+
+fn parse_binding(marker: &str) -> std::result::Result<Binding, String> {
+    let mut segments = marker.split('.').map(str::trim);
+    let source = match segments.next() {
+        Some("path") => Source::Path,
+        Some("body") => Source::Body,
+        Some("query") => Source::Query,
+        _ => return Err(format!("`{{{{ {} }}}}` must start with path., body. or query.", marker)),
+    };
+    let path: Vec<String> = segments.map(String::from).collect();
+    if path.is_empty() || path.iter().any(|segment| segment.is_empty()) {
+        return Err(format!("`{{{{ {} }}}}` does not name a value", marker));
+    }
+    Ok(Binding { source, path })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queries_bind_request_values_as_parameters() {
+        let endpoints: HashMap<String, EndpointConfig> = serde_yaml::from_str(r#"
+user:
+  path: /users/{id}
+  methods: [GET, PUT]
+  database:
+    queries:
+      GET: "SELECT * FROM users WHERE id = {{ path.id }} AND name <> 'who?'"
+      put: "UPDATE users SET name = {{body.name}}, city = {{ body.address.city }} WHERE id = {{ path.id }} LIMIT {{ query.limit }}"
+"#).unwrap();
+        let queries = EndpointQueries::new(&endpoints).unwrap();
+
+        let mut request: RequestData = serde_json::from_value(json!({
+            "method": "PUT",
+            "path": "/users/7",
+            "path_params": {"id": 7},
+            "query_params": {},
+            "body": {"name": "Robert'); DROP TABLE users;--", "address": {"zip": "1000"}},
+        })).unwrap();
+        let query = queries.bind("user", "PUT", &request).unwrap();
+        assert_eq!(query.sql, "UPDATE users SET name = ?, city = ? WHERE id = ? LIMIT ?");
+        assert_eq!(query.numbered_sql(), "UPDATE users SET name = $1, city = $2 WHERE id = $3 LIMIT $4");
+        assert_eq!(query.params, vec![json!("Robert'); DROP TABLE users;--"), Value::Null, json!(7), Value::Null]);
+
+        request.query_params.insert("limit".to_string(), "5".to_string());
+        assert_eq!(queries.bind("user", "put", &request).unwrap().params[3], json!("5"));
+        let get = queries.bind("user", "GET", &request).unwrap();
+        assert_eq!(get.numbered_sql(), "SELECT * FROM users WHERE id = $1 AND name <> 'who?'");
+        assert!(queries.bind("user", "DELETE", &request).is_none());
+
+        // Markers must be bindable, outside quotes, and name a path parameter
+        for (sql, error) in [
+            ("SELECT {{ path.user }}", "not a parameter"),
+            ("SELECT '{{ body.name }}'", "inside quotes"),
+            ("SELECT {{ headers.host }}", "must start with"),
+            ("SELECT * WHERE id = ?", "reserved"),
+        ] {
+            let mut broken = endpoints.clone();
+            broken.get_mut("user").unwrap().database.as_mut().unwrap().queries = Some(HashMap::from([("GET".to_string(), sql.to_string())]));
+            let message = EndpointQueries::new(&broken).unwrap_err().to_string();
+            assert!(message.contains(error), "{}: {}", sql, message);
+        }
+    }
+}
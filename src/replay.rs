@@ -0,0 +1,475 @@
+//! Traffic replay
+//!
+//! `backworks replay --session traffic.json --target http://localhost:3000`
+//! sends the requests of a capture session export or proxy cassette to a
+//! target again, in their recorded order, and compares each response with
+//! the recorded one the way [contract verification](crate::contract) does:
+//! status, media type and body shape. Latencies are recorded in a
+//! [`MetricsRecorder`], per endpoint with IDs in paths grouped as `{id}`.
+//!
+//! Without pacing each request is sent once the previous one is answered.
+//! A rate (`10/s`, `600/m`) starts requests at fixed intervals and a speed
+//! keeps the recorded gaps between them, divided by the factor; paced
+//! requests do not wait for each other.
+//!
+//! Captured request headers are sent again, except the ones that belonged
+//! to the original connection. Headers can be replaced or added, and
+//! dropped by name, `*` dropping all captured ones, so credentials scrubbed
+//! from the capture can be supplied again.
+
+use crate::capture::CapturedRequest;
+use crate::compare::{Divergence, DivergenceKind, ResponseSnapshot};
+use crate::contract::{body_value, diff, send, write_divergences, ContractRequest};
+use crate::error::{BackworksError, Result};
+use crate::metrics_recorder::{MetricSource, MetricsRecorder, MetricsSnapshot};
+use crate::proxy::Recording;
+use crate::usage::candidate_path;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Captured headers that described the original connection, never replayed
+const CONNECTION_HEADERS: &[&str] = &[
+    "host", "content-length", "connection", "keep-alive", "transfer-encoding", "te", "trailer", "upgrade",
+    "proxy-connection", "accept-encoding",
+];
+/// Response headers compared with the recording
+const COMPARED_HEADERS: &[&str] = &["content-type"];
+
+/// A captured request, and its response when one was recorded
+#[derive(Debug, Clone)]
+pub struct CapturedExchange {
+    pub at: Option<DateTime<Utc>>,
+    pub request: ContractRequest,
+    pub response: Option<ResponseSnapshot>,
+}
+
+/// The exchanges of a capture session export or proxy cassette, in file
+/// order, with header names in lower case
+pub async fn load_session(path: &Path) -> Result<Vec<CapturedExchange>> {
+    let source = path.display().to_string();
+    let content = tokio::fs::read_to_string(path).await
+        .map_err(|e| BackworksError::config(format!("Failed to read capture {}: {}", source, e)))?;
+    let invalid = |e: serde_json::Error| BackworksError::config(format!("{} is not a capture export or proxy cassette: {}", source, e));
+    let value: Value = serde_json::from_str(&content).map_err(invalid)?;
+    let lowercase = |headers: &mut dyn Iterator<Item = (String, String)>| headers
+        .map(|(name, value)| (name.to_ascii_lowercase(), value))
+        .collect();
+
+    if value.is_array() {
+        let recordings: Vec<Recording> = serde_json::from_value(value).map_err(invalid)?;
+        return Ok(recordings.into_iter().map(|recording| {
+            let query: Vec<String> = recording.request.query.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+            CapturedExchange {
+                at: Some(recording.recorded_at),
+                request: ContractRequest {
+                    source: source.clone(),
+                    method: recording.request.method.to_ascii_uppercase(),
+                    path: with_query(&recording.request.path, &query),
+                    headers: lowercase(&mut recording.request.headers.into_iter()),
+                    body: recording.request.body,
+                },
+                response: Some(ResponseSnapshot {
+                    status: recording.response.status,
+                    headers: recording.response.headers,
+                    body: recording.response.body,
+                }),
+            }
+        }).collect());
+    }
+
+    let requests: Vec<CapturedRequest> = serde_json::from_value(value.get("requests").cloned().unwrap_or(Value::Null)).map_err(invalid)?;
+    Ok(requests.into_iter().map(|request| {
+        let response = match request.response {
+            Some(response) => Some(ResponseSnapshot {
+                status: response.status_code,
+                headers: response.headers,
+                body: response.body.unwrap_or(Value::Null),
+            }),
+            None => request.response_status.map(|status| ResponseSnapshot {
+                status,
+                headers: request.response_headers.unwrap_or_default(),
+                body: body_value(request.response_body.unwrap_or_default().as_bytes()),
+            }),
+        };
+        let mut query: Vec<String> = request.query_params.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+        query.sort();
+        CapturedExchange {
+            at: Some(request.timestamp),
+            request: ContractRequest {
+                source: source.clone(),
+                method: request.method.to_ascii_uppercase(),
+                path: with_query(&request.path, &query),
+                headers: lowercase(&mut request.headers.into_iter()),
+                body: request.body,
+            },
+            response,
+        }
+    }).collect())
+}
+
+fn with_query(path: &str, query: &[String]) -> String {
+    match path.contains('?') || query.is_empty() {
+        true => path.to_string(),
+        false => format!("{}?{}", path, query.join("&")),
+    }
+}
+
+/// When replayed requests are sent
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Pacing {
+    /// Each once the previous one is answered
+    #[default]
+    Sequential,
+    /// Requests per second
+    Rate(f64),
+    /// Recorded gaps divided by this factor
+    Speed(f64),
+}
+
+impl Pacing {
+    /// Parse a rate such as `10/s`, `600/m` or `2.5` (per second)
+    pub fn rate(value: &str) -> Result<Self> {
+        let invalid = || BackworksError::config(format!("Invalid replay rate '{}' (expected e.g. 10/s or 600/m)", value));
+        let (count, per) = value.trim().split_once('/').unwrap_or((value.trim(), "s"));
+        let seconds = match per {
+            "s" | "sec" => 1.0,
+            "m" | "min" => 60.0,
+            "h" => 3600.0,
+            _ => return Err(invalid()),
+        };
+        match count.trim().parse::<f64>() {
+            Ok(count) if count > 0.0 && count.is_finite() => Ok(Pacing::Rate(count / seconds)),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Replay at `factor` times the recorded speed
+    pub fn speed(factor: f64) -> Result<Self> {
+        if factor > 0.0 && factor.is_finite() {
+            Ok(Pacing::Speed(factor))
+        } else {
+            Err(BackworksError::config(format!("Replay speed must be above zero, not {}", factor)))
+        }
+    }
+
+    /// When each of `exchanges` starts, after the first; `None` when
+    /// requests wait for each other
+    fn offsets(&self, exchanges: &[CapturedExchange]) -> Result<Option<Vec<Duration>>> {
+        match *self {
+            Pacing::Sequential => Ok(None),
+            Pacing::Rate(rate) => Ok(Some((0..exchanges.len()).map(|index| Duration::from_secs_f64(index as f64 / rate)).collect())),
+            Pacing::Speed(factor) => {
+                let times: Option<Vec<DateTime<Utc>>> = exchanges.iter().map(|exchange| exchange.at).collect();
+                let times = times.ok_or_else(|| BackworksError::config("The capture has requests without a time; replay it at a rate instead"))?;
+                let Some(&first) = times.first() else { return Ok(Some(Vec::new())) };
+                Ok(Some(times.iter()
+                    .map(|at| (*at - first).to_std().unwrap_or_default().div_f64(factor))
+                    .collect()))
+            }
+        }
+    }
+}
+
+/// Sends captured requests to a target again
+#[derive(Debug, Clone)]
+pub struct Replayer {
+    target: String,
+    client: reqwest::Client,
+    pacing: Pacing,
+    headers: Vec<(String, String)>,
+    dropped_headers: Vec<String>,
+    recorder: MetricsRecorder,
+}
+
+/// How one captured request was answered this time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayResult {
+    pub method: String,
+    pub path: String,
+    pub recorded_status: Option<u16>,
+    /// Missing when the target gave no response
+    pub status: Option<u16>,
+    pub latency_ms: f64,
+    /// Differences from the recorded response
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub divergences: Vec<Divergence>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ReplayResult {
+    pub fn status_changed(&self) -> bool {
+        self.divergences.iter().any(|divergence| divergence.kind == DivergenceKind::Status)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayReport {
+    pub target: String,
+    pub elapsed_ms: f64,
+    /// Every request, in capture order
+    pub results: Vec<ReplayResult>,
+    /// Latencies and errors of all requests
+    pub metrics: Option<MetricsSnapshot>,
+    /// Latencies and errors by method and path
+    pub endpoints: Vec<MetricsSnapshot>,
+}
+
+impl Replayer {
+    /// Replay to the service at the base URL `target`
+    pub fn new(target: &str, timeout: Duration) -> Result<Self> {
+        let url = reqwest::Url::parse(target)
+            .map_err(|e| BackworksError::config(format!("Invalid replay target '{}': {}", target, e)))?;
+        if !["http", "https"].contains(&url.scheme()) {
+            return Err(BackworksError::config(format!("Replay target '{}' must be http or https", target)));
+        }
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .map_err(|e| BackworksError::config(format!("Failed to create HTTP client: {}", e)))?;
+        Ok(Self {
+            target: target.trim_end_matches('/').to_string(),
+            client,
+            pacing: Pacing::default(),
+            headers: Vec::new(),
+            dropped_headers: Vec::new(),
+            recorder: MetricsRecorder::default(),
+        })
+    }
+
+    pub fn with_pacing(mut self, pacing: Pacing) -> Self {
+        self.pacing = pacing;
+        self
+    }
+
+    /// Headers replacing the captured ones of the same name, or added
+    pub fn with_headers(mut self, headers: Vec<(String, String)>) -> Self {
+        self.headers = headers.into_iter().map(|(name, value)| (name.to_ascii_lowercase(), value)).collect();
+        self
+    }
+
+    /// Captured headers not sent again; `*` drops them all
+    pub fn with_dropped_headers(mut self, headers: Vec<String>) -> Self {
+        self.dropped_headers = headers.iter().map(|name| name.to_ascii_lowercase()).collect();
+        self
+    }
+
+    /// Replay `exchanges`, paced as configured
+    pub async fn run(&self, exchanges: Vec<CapturedExchange>) -> Result<ReplayReport> {
+        let offsets = self.pacing.offsets(&exchanges)?;
+        let started = Instant::now();
+        let mut results: Vec<Option<ReplayResult>> = vec![None; exchanges.len()];
+        match offsets {
+            None => {
+                for (index, exchange) in exchanges.into_iter().enumerate() {
+                    results[index] = Some(self.replay(exchange).await);
+                }
+            }
+            Some(offsets) => {
+                let start = tokio::time::Instant::now();
+                let mut replays = tokio::task::JoinSet::new();
+                for ((index, exchange), offset) in exchanges.into_iter().enumerate().zip(offsets) {
+                    tokio::time::sleep_until(start + offset).await;
+                    let replayer = self.clone();
+                    replays.spawn(async move { (index, replayer.replay(exchange).await) });
+                }
+                while let Some(done) = replays.join_next().await {
+                    let (index, result) = done.map_err(|e| BackworksError::config(format!("Replay failed: {}", e)))?;
+                    results[index] = Some(result);
+                }
+            }
+        }
+
+        let target = MetricSource::upstream(&self.target);
+        Ok(ReplayReport {
+            target: self.target.clone(),
+            elapsed_ms: started.elapsed().as_secs_f64() * 1000.0,
+            results: results.into_iter().flatten().collect(),
+            metrics: self.recorder.snapshot(&target),
+            endpoints: self.recorder.snapshots().into_iter().filter(|snapshot| snapshot.source != target).collect(),
+        })
+    }
+
+    async fn replay(&self, exchange: CapturedExchange) -> ReplayResult {
+        let CapturedExchange { mut request, response: recorded, .. } = exchange;
+        request.headers = self.rewrite(std::mem::take(&mut request.headers));
+        let start = Instant::now();
+        let outcome = send(&self.client, &self.target, &request, &[]).await;
+        let latency = start.elapsed();
+
+        let path = request.path.split('?').next().unwrap_or_default();
+        let failed = outcome.as_ref().map_or(true, |response| response.status >= 400);
+        self.recorder.record(MetricSource::upstream(&self.target), latency, failed);
+        self.recorder.record(MetricSource::endpoint(&request.method, &candidate_path(path)), latency, failed);
+
+        let compared: Vec<String> = COMPARED_HEADERS.iter().map(|name| name.to_string()).collect();
+        let (status, divergences, error) = match outcome {
+            Ok(response) => {
+                let divergences = recorded.as_ref().map(|recorded| diff(recorded, &response, &compared)).unwrap_or_default();
+                (Some(response.status), divergences, None)
+            }
+            Err(e) => (None, Vec::new(), Some(e)),
+        };
+        ReplayResult {
+            method: request.method,
+            path: request.path,
+            recorded_status: recorded.map(|recorded| recorded.status),
+            status,
+            latency_ms: latency.as_secs_f64() * 1000.0,
+            divergences,
+            error,
+        }
+    }
+
+    fn rewrite(&self, mut headers: BTreeMap<String, String>) -> BTreeMap<String, String> {
+        let all = self.dropped_headers.iter().any(|name| name == "*");
+        headers.retain(|name, _| !all && !CONNECTION_HEADERS.contains(&name.as_str()) && !self.dropped_headers.contains(name));
+        headers.extend(self.headers.iter().cloned());
+        headers
+    }
+}
+
+impl ReplayReport {
+    /// Requests whose status changed, or that got no response
+    pub fn regressions(&self) -> usize {
+        self.results.iter().filter(|result| result.error.is_some() || result.status_changed()).count()
+    }
+
+    /// Requests answered differently from their recording in any way
+    pub fn differences(&self) -> usize {
+        self.results.iter().filter(|result| result.error.is_some() || !result.divergences.is_empty()).count()
+    }
+
+    pub fn render_text(&self) -> String {
+        let mut out = String::new();
+        self.write_text(&mut out).expect("writing to a String cannot fail");
+        out
+    }
+
+    fn write_text(&self, out: &mut String) -> fmt::Result {
+        writeln!(out, "Replay against {}", self.target)?;
+        for result in self.results.iter().filter(|result| result.error.is_some() || !result.divergences.is_empty()) {
+            let mark = if result.error.is_some() { "⚠️ " } else { "❌" };
+            writeln!(out, "  {} {} {} ({:.1}ms)", mark, result.method, result.path, result.latency_ms)?;
+            if let Some(ref error) = result.error {
+                writeln!(out, "       {}", error)?;
+            }
+            write_divergences(out, &result.divergences)?;
+        }
+
+        if !self.endpoints.is_empty() {
+            writeln!(out)?;
+        }
+        for endpoint in &self.endpoints {
+            writeln!(out, "  {:<32} {:>5} requests  avg {:.1}ms  p95 {:.1}ms  {} errors",
+                endpoint.source.to_string(), endpoint.requests, endpoint.avg_latency_ms, endpoint.p95_latency_ms, endpoint.errors)?;
+        }
+
+        let failed = self.results.iter().filter(|result| result.error.is_some()).count();
+        let changed = self.differences() - failed;
+        let seconds = self.elapsed_ms / 1000.0;
+        writeln!(out, "\n{} replayed in {:.1}s ({:.1}/s): {} matched, {} changed, {} failed",
+            self.results.len(), seconds, self.results.len() as f64 / seconds.max(f64::EPSILON), self.results.len() - changed - failed, changed, failed)?;
+        if let Some(ref metrics) = self.metrics {
+            writeln!(out, "  Latency    avg {:.2}ms  p50 {:.2}ms  p95 {:.2}ms  p99 {:.2}ms",
+                metrics.avg_latency_ms, metrics.p50_latency_ms, metrics.p95_latency_ms, metrics.p99_latency_ms)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::get;
+    use axum::Router;
+    use serde_json::json;
+
+    #[test]
+    fn test_rates_and_recorded_gaps_pace_requests() {
+        assert_eq!(Pacing::rate("10/s").unwrap(), Pacing::Rate(10.0));
+        assert_eq!(Pacing::rate("600/m").unwrap(), Pacing::Rate(10.0));
+        assert_eq!(Pacing::rate("4").unwrap(), Pacing::Rate(4.0));
+        assert!(Pacing::rate("0/s").is_err() && Pacing::rate("5/week").is_err());
+        assert!(Pacing::speed(0.0).is_err());
+
+        let at = |seconds: i64| CapturedExchange {
+            at: Some(DateTime::from_timestamp(1_700_000_000 + seconds, 0).unwrap()),
+            request: ContractRequest { source: String::new(), method: "GET".to_string(), path: "/".to_string(), headers: BTreeMap::new(), body: None },
+            response: None,
+        };
+        let exchanges = vec![at(0), at(2), at(3)];
+        let offsets = Pacing::Speed(2.0).offsets(&exchanges).unwrap().unwrap();
+        assert_eq!(offsets, vec![Duration::ZERO, Duration::from_secs(1), Duration::from_millis(1500)]);
+        let offsets = Pacing::Rate(4.0).offsets(&exchanges).unwrap().unwrap();
+        assert_eq!(offsets[2], Duration::from_millis(500));
+        assert_eq!(Pacing::Sequential.offsets(&exchanges).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_captured_requests_are_replayed_with_rewritten_headers() {
+        let app = Router::new()
+            .route("/users/:id", get(|headers: HeaderMap| async move {
+                match headers.get("authorization").and_then(|value| value.to_str().ok()) {
+                    Some("Bearer fresh") if !headers.contains_key("x-debug") => (StatusCode::OK, axum::Json(json!({ "id": 7, "name": "Ada" }))),
+                    _ => (StatusCode::UNAUTHORIZED, axum::Json(json!({ "error": "unauthorized" }))),
+                }
+            }))
+            .route("/orders", get(|| async { axum::Json(json!({ "id": "o1" })) }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let session = std::env::temp_dir().join(format!("backworks_replay_{}.json", uuid::Uuid::new_v4()));
+        let captured = |path: &str, status: u16, body: Value| json!({
+            "id": uuid::Uuid::new_v4(),
+            "session_id": null,
+            "timestamp": "2024-05-01T10:00:00Z",
+            "method": "GET",
+            "path": path,
+            "headers": { "Authorization": "[redacted]", "X-Debug": "1", "Host": "shop.internal" },
+            "query_params": {},
+            "body": null,
+            "response": { "status_code": status, "headers": { "content-type": "application/json" }, "body": body },
+            "response_status": null, "response_headers": null, "response_body": null, "duration": null,
+        });
+        std::fs::write(&session, json!({
+            "session": { "name": "shop" },
+            "requests": [
+                captured("/users/7", 200, json!({ "id": 7, "name": "Ada" })),
+                captured("/users/8", 200, json!({ "id": 8, "name": "Alan" })),
+                captured("/orders", 200, json!({ "id": 1 })),
+            ],
+        }).to_string()).unwrap();
+        let exchanges = load_session(&session).await.unwrap();
+        std::fs::remove_file(&session).unwrap();
+        assert_eq!(exchanges[0].request.headers.get("x-debug").map(String::as_str), Some("1"));
+
+        let replayer = Replayer::new(&target, Duration::from_secs(5)).unwrap()
+            .with_pacing(Pacing::Rate(50.0))
+            .with_headers(vec![("Authorization".to_string(), "Bearer fresh".to_string())])
+            .with_dropped_headers(vec!["X-Debug".to_string()]);
+        let report = replayer.run(exchanges.clone()).await.unwrap();
+        let statuses: Vec<Option<u16>> = report.results.iter().map(|result| result.status).collect();
+        assert_eq!(statuses, vec![Some(200), Some(200), Some(200)]);
+        assert_eq!((report.regressions(), report.differences()), (0, 1));
+        assert_eq!(report.results[2].divergences[0].path, "/id");
+        let endpoints: Vec<(String, u64)> = report.endpoints.iter().map(|endpoint| (endpoint.source.to_string(), endpoint.requests)).collect();
+        assert_eq!(endpoints, vec![("GET /orders".to_string(), 1), ("GET /users/{id}".to_string(), 2)]);
+        assert_eq!(report.metrics.as_ref().unwrap().requests, 3);
+
+        // Without the rewrites the scrubbed credentials are sent as captured
+        let report = Replayer::new(&target, Duration::from_secs(5)).unwrap().run(exchanges).await.unwrap();
+        assert_eq!(report.regressions(), 2);
+        let text = report.render_text();
+        assert!(text.contains("❌ GET /users/7") && text.contains("status 200 -> 401"), "{}", text);
+        assert!(text.contains("3 replayed in") && text.contains("0 matched, 3 changed, 0 failed"), "{}", text);
+    }
+}
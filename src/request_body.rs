@@ -0,0 +1,162 @@
+//! Request body limits
+//!
+//! `security.validation.max_body_size` caps what a request may send (default
+//! 2MB). A `Content-Length` above it is answered with `413` before anything
+//! is read, and a body that turns out longer fails with `413` as it streams
+//! in. Bodies sent with `Content-Encoding: gzip` or `deflate` are
+//! decompressed before handlers see them, up to `max_decompressed_size`
+//! (default `max_body_size`), so a small compressed body cannot expand
+//! without bound; other encodings are answered with `415`.
+
+use crate::config::{parse_size, BackworksConfig};
+use crate::error::{BackworksError, ErrorSource, RequestError, Result};
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use std::io::Read;
+use std::sync::Arc;
+
+const DEFAULT_MAX_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+/// The body sizes a server accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyLimits {
+    pub max_body_size: usize,
+    pub max_decompressed_size: usize,
+}
+
+impl BodyLimits {
+    pub fn from_config(config: &BackworksConfig) -> Result<Self> {
+        let validation = config.security.as_ref().and_then(|security| security.validation.as_ref());
+        let size = |name: &str, value: Option<&String>| {
+            value
+                .map(|value| parse_size(value).map_err(|e| BackworksError::config(format!("security.validation.{}: {}", name, e))))
+                .transpose()
+        };
+        let max_body_size = size("max_body_size", validation.and_then(|v| v.max_body_size.as_ref()))?.unwrap_or(DEFAULT_MAX_BODY_SIZE);
+        let max_decompressed_size = size("max_decompressed_size", validation.and_then(|v| v.max_decompressed_size.as_ref()))?.unwrap_or(max_body_size);
+        Ok(Self { max_body_size, max_decompressed_size })
+    }
+}
+
+/// Hold request bodies to `limits`, decompressing them on the way
+pub async fn limit_request_body(State(limits): State<Arc<BodyLimits>>, request: Request, next: Next) -> Response {
+    let declared = request.headers().get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    if declared.is_some_and(|length| length > limits.max_body_size) {
+        return too_large(limits.max_body_size);
+    }
+
+    let encoding = request.headers().get(header::CONTENT_ENCODING)
+        .map(|value| value.to_str().unwrap_or_default().trim().to_ascii_lowercase());
+    let (mut parts, body) = request.into_parts();
+    let body = match encoding.as_deref() {
+        None | Some("identity") => Body::new(http_body_util::Limited::new(body, limits.max_body_size)),
+        Some(encoding @ ("gzip" | "x-gzip" | "deflate")) => {
+            let compressed = match axum::body::to_bytes(body, limits.max_body_size).await {
+                Ok(bytes) => bytes,
+                Err(_) => return too_large(limits.max_body_size),
+            };
+            let decompressed = match decompress(encoding, &compressed, limits.max_decompressed_size) {
+                Ok(Some(bytes)) => bytes,
+                Ok(None) => return reject(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    format!("Decompressed request body exceeds {} bytes", limits.max_decompressed_size),
+                ),
+                Err(e) => return reject(StatusCode::BAD_REQUEST, format!("Request body is not valid {}: {}", encoding, e)),
+            };
+            parts.headers.remove(header::CONTENT_ENCODING);
+            parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(decompressed.len()));
+            Body::from(decompressed)
+        }
+        Some(other) => return reject(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            format!("Unsupported Content-Encoding '{}'", other),
+        ),
+    };
+    next.run(Request::from_parts(parts, body)).await
+}
+
+/// `compressed` decoded; `None` when it grows beyond `limit` bytes
+fn decompress(encoding: &str, compressed: &[u8], limit: usize) -> std::io::Result<Option<Vec<u8>>> {
+    let read = |mut decoder: Box<dyn Read + '_>| {
+        let mut decompressed = Vec::new();
+        decoder.as_mut().take(limit as u64 + 1).read_to_end(&mut decompressed)?;
+        Ok::<_, std::io::Error>(decompressed)
+    };
+    let decompressed = match encoding {
+        // `deflate` is zlib-wrapped, though some clients send it raw
+        "deflate" => read(Box::new(ZlibDecoder::new(compressed)))
+            .or_else(|_| read(Box::new(DeflateDecoder::new(compressed))))?,
+        _ => read(Box::new(GzDecoder::new(compressed)))?,
+    };
+    Ok((decompressed.len() <= limit).then_some(decompressed))
+}
+
+fn too_large(limit: usize) -> Response {
+    reject(StatusCode::PAYLOAD_TOO_LARGE, format!("Request body exceeds {} bytes", limit))
+}
+
+fn reject(status: StatusCode, message: String) -> Response {
+    let mut response = (status, Json(serde_json::json!({"error": message, "status": status.as_u16()}))).into_response();
+    response.extensions_mut().insert(RequestError::new(status, message, ErrorSource::Framework));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::post;
+    use axum::Router;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+    use tower::ServiceExt;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    async fn send(app: &Router, body: Vec<u8>, encoding: Option<&str>) -> (StatusCode, String) {
+        let mut request = axum::http::Request::post("/echo");
+        if let Some(encoding) = encoding {
+            request = request.header(header::CONTENT_ENCODING, encoding);
+        }
+        let response = app.clone().oneshot(request.body(Body::from(body)).unwrap()).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    #[tokio::test]
+    async fn test_bodies_are_limited_before_and_after_decompression() {
+        let limits = Arc::new(BodyLimits { max_body_size: 1024, max_decompressed_size: 4096 });
+        let app = Router::new()
+            .route("/echo", post(|body: String| async move { body }))
+            .layer(axum::middleware::from_fn_with_state(limits, limit_request_body))
+            .layer(axum::extract::DefaultBodyLimit::disable());
+
+        assert_eq!(send(&app, b"hello".to_vec(), None).await, (StatusCode::OK, "hello".to_string()));
+        assert_eq!(send(&app, vec![b'a'; 2048], None).await.0, StatusCode::PAYLOAD_TOO_LARGE);
+
+        // 3KB of text compresses well below the wire limit and fits once decompressed
+        let text = "x".repeat(3072);
+        assert_eq!(send(&app, gzip(text.as_bytes()), Some("gzip")).await, (StatusCode::OK, text));
+
+        // A bomb is cut off once it grows past the decompressed limit
+        let bomb = gzip(&vec![0; 512 * 1024]);
+        assert!(bomb.len() < 1024);
+        let (status, body) = send(&app, bomb, Some("gzip")).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(body.contains("Decompressed request body exceeds 4096 bytes"), "{}", body);
+
+        assert_eq!(send(&app, b"not gzip".to_vec(), Some("gzip")).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(send(&app, b"hello".to_vec(), Some("br")).await.0, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
@@ -0,0 +1,240 @@
+//! Recent requests, for the dashboard's request log
+//!
+//! The log keeps the last `dashboard.request_log_size` requests the API
+//! server answered, with their status, duration, response size, the endpoint
+//! that served them and, for proxied ones, the upstream target. The dashboard
+//! pages through it at `/api/requests` and tails it over a WebSocket at
+//! `/api/requests/stream`, both behind the admin token; the admin API serves
+//! the same pages at `/_backworks/requests`.
+
+use crate::broadcast::BroadcastHub;
+use crate::capture::CaptureStreamFilter;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+/// Hub topic new entries are published on
+pub const REQUEST_LOG_TOPIC: &str = "request_log";
+
+/// Requests kept when `dashboard.request_log_size` is not set
+pub const DEFAULT_REQUEST_LOG_SIZE: usize = 1000;
+
+/// Entries returned per page when the query sets no `limit`
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+tokio::task_local! {
+    static UPSTREAM: RefCell<Option<String>>;
+}
+
+/// Note the upstream target the current request was forwarded to
+pub fn note_upstream(target: &str) {
+    let _ = UPSTREAM.try_with(|upstream| *upstream.borrow_mut() = Some(target.to_string()));
+}
+
+/// Run `request` and return its output with the upstream target it was
+/// forwarded to, if any
+pub async fn tracking_upstream<F: Future>(request: F) -> (F::Output, Option<String>) {
+    UPSTREAM.scope(RefCell::new(None), async move {
+        let output = request.await;
+        (output, UPSTREAM.with(|upstream| upstream.borrow_mut().take()))
+    }).await
+}
+
+/// A request as the log shows it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RequestLogEntry {
+    /// Increasing with every request; pages are cut by it
+    pub id: u64,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    pub duration_ms: f64,
+    /// Response body size in bytes, when known up front
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream: Option<String>,
+}
+
+/// What the server records about an answered request
+#[derive(Debug, Clone, Default)]
+pub struct LoggedRequest {
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    pub duration: std::time::Duration,
+    pub size: Option<u64>,
+    pub endpoint: Option<String>,
+    pub upstream: Option<String>,
+}
+
+/// A page of the log, newest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestLogPage {
+    pub entries: Vec<RequestLogEntry>,
+    /// `before` cursor of the next, older page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<u64>,
+    /// Requests the log holds, before filtering
+    pub total: usize,
+}
+
+/// Filters and cursor of a log query
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RequestLogQuery {
+    /// Comma-separated methods
+    pub method: Option<String>,
+    /// Glob the path matches
+    pub path: Option<String>,
+    /// Comma-separated codes (`404`), classes (`5xx`) or ranges (`200-299`)
+    pub status: Option<String>,
+    /// Name of the endpoint that served the request
+    pub endpoint: Option<String>,
+    /// Only entries older than this id
+    pub before: Option<u64>,
+    /// Only entries newer than this id, for following the log
+    pub after: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+impl RequestLogQuery {
+    pub fn filter(&self) -> crate::error::BackworksResult<CaptureStreamFilter> {
+        CaptureStreamFilter::parse(self.method.as_deref(), self.path.as_deref(), self.status.as_deref())
+    }
+
+    /// Whether `entry` passes `filter` and the endpoint filter
+    pub fn matches(&self, filter: &CaptureStreamFilter, entry: &RequestLogEntry) -> bool {
+        filter.accepts(&entry.method, &entry.path, entry.status)
+            && self.endpoint.as_ref().is_none_or(|endpoint| entry.endpoint.as_ref() == Some(endpoint))
+    }
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    entries: VecDeque<RequestLogEntry>,
+    next_id: u64,
+}
+
+/// Bounded log of recent requests, shared between clones
+#[derive(Debug, Clone)]
+pub struct RequestLog {
+    entries: Arc<Mutex<Entries>>,
+    capacity: usize,
+    events: Option<BroadcastHub>,
+}
+
+impl Default for RequestLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_REQUEST_LOG_SIZE)
+    }
+}
+
+impl RequestLog {
+    pub fn new(capacity: usize) -> Self {
+        Self { entries: Arc::default(), capacity, events: None }
+    }
+
+    /// Publish every new entry to `events`
+    pub fn with_events(mut self, events: BroadcastHub) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Append `request`, dropping the oldest entry when the log is full
+    pub fn record(&self, request: LoggedRequest) -> RequestLogEntry {
+        let entry = {
+            let mut log = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            log.next_id += 1;
+            let entry = RequestLogEntry {
+                id: log.next_id,
+                timestamp: chrono::Utc::now(),
+                method: request.method,
+                path: request.path,
+                status: request.status,
+                duration_ms: request.duration.as_secs_f64() * 1000.0,
+                size: request.size,
+                endpoint: request.endpoint,
+                upstream: request.upstream,
+            };
+            if self.capacity > 0 {
+                if log.entries.len() == self.capacity {
+                    log.entries.pop_front();
+                }
+                log.entries.push_back(entry.clone());
+            }
+            entry
+        };
+        if let Some(ref events) = self.events {
+            events.publish(REQUEST_LOG_TOPIC, serde_json::to_value(&entry).unwrap_or_default());
+        }
+        entry
+    }
+
+    /// Entries passing `query`, newest first, a page at a time
+    pub fn page(&self, query: &RequestLogQuery, filter: &CaptureStreamFilter) -> RequestLogPage {
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).max(1);
+        let log = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let mut matching = log.entries.iter().rev()
+            .filter(|entry| query.before.is_none_or(|before| entry.id < before))
+            .filter(|entry| query.after.is_none_or(|after| entry.id > after))
+            .filter(|entry| query.matches(filter, entry));
+        let entries: Vec<RequestLogEntry> = matching.by_ref().take(limit).cloned().collect();
+        let next = match matching.next() {
+            Some(_) => entries.last().map(|entry| entry.id),
+            None => None,
+        };
+        RequestLogPage { entries, next, total: log.entries.len() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, path: &str, status: u16) -> LoggedRequest {
+        LoggedRequest { method: method.to_string(), path: path.to_string(), status, ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn test_log_keeps_recent_requests_in_pages() {
+        let events = BroadcastHub::default();
+        let mut tail = events.subscribe(None).unwrap();
+        let log = RequestLog::new(4).with_events(events);
+        for (method, path, status) in [("GET", "/users", 200), ("POST", "/users", 201), ("GET", "/users/1", 404), ("GET", "/orders", 500), ("GET", "/users/2", 200)] {
+            log.record(request(method, path, status));
+        }
+        assert_eq!(tail.recv().await.unwrap().data["path"], "/users");
+
+        let query = RequestLogQuery { limit: Some(2), ..Default::default() };
+        let page = log.page(&query, &query.filter().unwrap());
+        assert_eq!(page.total, 4);
+        assert_eq!(page.entries.iter().map(|entry| entry.id).collect::<Vec<_>>(), vec![5, 4]);
+        let query = RequestLogQuery { limit: Some(2), before: page.next, ..Default::default() };
+        let page = log.page(&query, &query.filter().unwrap());
+        assert_eq!(page.entries.iter().map(|entry| entry.id).collect::<Vec<_>>(), vec![3, 2]);
+        assert_eq!(page.next, None);
+        let query = RequestLogQuery { after: Some(3), ..Default::default() };
+        let page = log.page(&query, &query.filter().unwrap());
+        assert_eq!(page.entries.iter().map(|entry| entry.id).collect::<Vec<_>>(), vec![5, 4]);
+
+        let query = RequestLogQuery { method: Some("get".to_string()), path: Some("/users*".to_string()), status: Some("4xx".to_string()), ..Default::default() };
+        let page = log.page(&query, &query.filter().unwrap());
+        assert_eq!(page.entries.len(), 1);
+        assert_eq!(page.entries[0].path, "/users/1");
+    }
+
+    #[tokio::test]
+    async fn test_upstream_is_tracked_per_request() {
+        let (_, upstream) = tracking_upstream(async { note_upstream("http://10.0.0.1:8080") }).await;
+        assert_eq!(upstream.as_deref(), Some("http://10.0.0.1:8080"));
+        let (_, upstream) = tracking_upstream(async {}).await;
+        assert_eq!(upstream, None);
+        // Outside a tracked request nothing is noted
+        note_upstream("http://10.0.0.1:8080");
+    }
+}
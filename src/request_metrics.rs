@@ -0,0 +1,190 @@
+//! Prometheus request metrics
+//!
+//! Every request is counted in `backworks_requests_total` and timed in
+//! `backworks_request_duration_seconds`, labelled with its endpoint, method
+//! and status plus the dimensions the endpoint declares under `monitoring`
+//! (team, domain, criticality and custom labels). Requests that match no
+//! endpoint are labelled `endpoint="unmatched"`. CORS preflights, answered
+//! before routing, are counted separately in `backworks_cors_preflights_total`,
+//! and requests whose handling panicked in `backworks_panics_total`.
+//! Endpoints with a concurrency limit report how saturated they are in
+//! `backworks_endpoint_in_flight`, `backworks_endpoint_queued` and
+//! `backworks_endpoint_saturation` (requests in flight over the limit), and
+//! count requests turned away in `backworks_endpoint_rejections_total`.
+//! Database plugins report their connection pools in
+//! `backworks_db_pool_connections` (by `state`, `in_use` or `idle`) and
+//! `backworks_db_pool_max_connections`, whether the last health check passed
+//! in `backworks_db_up`, and reconnect attempts in
+//! `backworks_db_reconnects_total`, all labelled with the `plugin`.
+//!
+//! The recorder belongs to the server rather than being installed globally,
+//! so several servers in one process keep separate metrics.
+
+use crate::config::BackworksConfig;
+use crate::plugin::PoolStats;
+use metrics::{Key, Label, Recorder};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusRecorder};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const REQUESTS_TOTAL: &str = "backworks_requests_total";
+pub const REQUEST_DURATION: &str = "backworks_request_duration_seconds";
+pub const CORS_PREFLIGHTS_TOTAL: &str = "backworks_cors_preflights_total";
+pub const PANICS_TOTAL: &str = "backworks_panics_total";
+pub const ENDPOINT_IN_FLIGHT: &str = "backworks_endpoint_in_flight";
+pub const ENDPOINT_QUEUED: &str = "backworks_endpoint_queued";
+pub const ENDPOINT_SATURATION: &str = "backworks_endpoint_saturation";
+pub const ENDPOINT_REJECTIONS_TOTAL: &str = "backworks_endpoint_rejections_total";
+pub const DB_POOL_CONNECTIONS: &str = "backworks_db_pool_connections";
+pub const DB_POOL_MAX_CONNECTIONS: &str = "backworks_db_pool_max_connections";
+pub const DB_UP: &str = "backworks_db_up";
+pub const DB_RECONNECTS_TOTAL: &str = "backworks_db_reconnects_total";
+
+/// Endpoint label of requests no endpoint served
+pub const UNMATCHED: &str = "unmatched";
+
+const DURATION_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Shared, cheaply clonable request metrics recorder
+#[derive(Clone)]
+pub struct RequestMetrics {
+    recorder: Arc<PrometheusRecorder>,
+    dimensions: Arc<HashMap<String, Vec<(String, String)>>>,
+}
+
+impl RequestMetrics {
+    pub fn new(config: &BackworksConfig) -> Self {
+        let recorder = PrometheusBuilder::new()
+            .set_buckets_for_metric(Matcher::Full(REQUEST_DURATION.to_string()), DURATION_BUCKETS)
+            .expect("duration buckets are not empty")
+            .build_recorder();
+        let dimensions = config.endpoints.iter()
+            .map(|(name, endpoint)| {
+                let dimensions = endpoint.monitoring.as_ref().map(|m| m.dimensions()).unwrap_or_default();
+                (name.clone(), dimensions)
+            })
+            .collect();
+
+        Self {
+            recorder: Arc::new(recorder),
+            dimensions: Arc::new(dimensions),
+        }
+    }
+
+    /// Dimensions declared by `endpoint`, sorted by label
+    pub fn dimensions(&self, endpoint: &str) -> &[(String, String)] {
+        self.dimensions.get(endpoint).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn record(&self, endpoint: Option<&str>, method: &str, status: u16, duration: Duration) {
+        let mut labels = vec![
+            Label::new("endpoint", endpoint.unwrap_or(UNMATCHED).to_string()),
+            Label::new("method", method.to_string()),
+            Label::new("status", status.to_string()),
+        ];
+        if let Some(endpoint) = endpoint {
+            labels.extend(self.dimensions(endpoint).iter().map(|(label, value)| Label::new(label.clone(), value.clone())));
+        }
+
+        self.recorder.register_counter(&Key::from_parts(REQUESTS_TOTAL, labels.clone())).increment(1);
+        self.recorder.register_histogram(&Key::from_parts(REQUEST_DURATION, labels)).record(duration.as_secs_f64());
+    }
+
+    /// Count a CORS preflight answered by `policy`
+    pub fn record_preflight(&self, policy: &str, allowed: bool) {
+        let labels = vec![
+            Label::new("policy", policy.to_string()),
+            Label::new("outcome", if allowed { "allowed" } else { "rejected" }),
+        ];
+        self.recorder.register_counter(&Key::from_parts(CORS_PREFLIGHTS_TOTAL, labels)).increment(1);
+    }
+
+    /// Count a request whose handling panicked
+    pub fn record_panic(&self, endpoint: Option<&str>) {
+        let labels = vec![Label::new("endpoint", endpoint.unwrap_or(UNMATCHED).to_string())];
+        self.recorder.register_counter(&Key::from_parts(PANICS_TOTAL, labels)).increment(1);
+    }
+
+    /// Report the requests `endpoint` runs and queues against its `limit`
+    pub fn record_concurrency(&self, endpoint: &str, in_flight: usize, queued: usize, limit: usize) {
+        let labels = vec![Label::new("endpoint", endpoint.to_string())];
+        self.recorder.register_gauge(&Key::from_parts(ENDPOINT_IN_FLIGHT, labels.clone())).set(in_flight as f64);
+        self.recorder.register_gauge(&Key::from_parts(ENDPOINT_QUEUED, labels.clone())).set(queued as f64);
+        self.recorder.register_gauge(&Key::from_parts(ENDPOINT_SATURATION, labels)).set(in_flight as f64 / limit.max(1) as f64);
+    }
+
+    /// Count a request `endpoint` turned away for `reason`
+    pub fn record_rejection(&self, endpoint: &str, reason: &'static str) {
+        let labels = vec![
+            Label::new("endpoint", endpoint.to_string()),
+            Label::new("reason", reason),
+        ];
+        self.recorder.register_counter(&Key::from_parts(ENDPOINT_REJECTIONS_TOTAL, labels)).increment(1);
+    }
+
+    /// Report the connections of a database plugin's pool
+    pub fn record_pool(&self, plugin: &str, stats: &PoolStats) {
+        for (state, connections) in [("in_use", stats.in_use), ("idle", stats.idle)] {
+            let labels = vec![
+                Label::new("plugin", plugin.to_string()),
+                Label::new("state", state),
+            ];
+            self.recorder.register_gauge(&Key::from_parts(DB_POOL_CONNECTIONS, labels)).set(connections as f64);
+        }
+        let labels = vec![Label::new("plugin", plugin.to_string())];
+        self.recorder.register_gauge(&Key::from_parts(DB_POOL_MAX_CONNECTIONS, labels)).set(stats.max as f64);
+    }
+
+    /// Report whether a database plugin's last health check passed
+    pub fn record_database_up(&self, plugin: &str, up: bool) {
+        let labels = vec![Label::new("plugin", plugin.to_string())];
+        self.recorder.register_gauge(&Key::from_parts(DB_UP, labels)).set(if up { 1.0 } else { 0.0 });
+    }
+
+    /// Count an attempt to reconnect a database plugin
+    pub fn record_reconnect(&self, plugin: &str) {
+        let labels = vec![Label::new("plugin", plugin.to_string())];
+        self.recorder.register_counter(&Key::from_parts(DB_RECONNECTS_TOTAL, labels)).increment(1);
+    }
+
+    /// Metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        self.recorder.handle().render()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_carry_endpoint_dimensions() {
+        let mut config = crate::config::parse_blueprint(serde_yaml::from_str(r#"
+name: api
+endpoints:
+  payments:
+    path: /payments
+    monitoring:
+      team: billing
+      criticality: high
+      labels: { tier: gold }
+  status:
+    path: /status
+"#).unwrap()).unwrap();
+        config.mode = crate::config::ExecutionMode::Plugin;
+        let metrics = RequestMetrics::new(&config);
+
+        metrics.record(Some("payments"), "POST", 201, Duration::from_millis(30));
+        metrics.record(Some("status"), "GET", 200, Duration::from_millis(1));
+        metrics.record(None, "GET", 404, Duration::from_millis(1));
+
+        let rendered = metrics.render();
+        assert!(rendered.contains(
+            r#"backworks_requests_total{endpoint="payments",method="POST",status="201",criticality="high",team="billing",tier="gold"} 1"#
+        ), "{}", rendered);
+        assert!(rendered.contains(r#"backworks_requests_total{endpoint="status",method="GET",status="200"} 1"#));
+        assert!(rendered.contains(r#"backworks_requests_total{endpoint="unmatched",method="GET",status="404"} 1"#));
+        assert!(rendered.contains(r#"backworks_request_duration_seconds_bucket{endpoint="payments",method="POST",status="201",criticality="high",team="billing",tier="gold",le="0.05"} 1"#));
+    }
+}
@@ -4,14 +4,18 @@
 //! to ensure plugin failures don't affect the core system.
 
 use crate::error::BackworksResult;
+use hdrhistogram::Histogram;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, Semaphore};
 use tokio::time::timeout;
 
+/// Longest execution time the percentiles resolve, in microseconds
+const MAX_EXECUTION_MICROS: u64 = 60 * 60 * 1_000_000;
+
 /// Circuit breaker states
 #[derive(Debug, Clone, PartialEq)]
 pub enum CircuitBreakerState {
@@ -24,11 +28,13 @@ pub enum CircuitBreakerState {
 pub struct PluginCircuitBreaker {
     plugin_name: String,
     state: Arc<RwLock<CircuitBreakerState>>,
-    failure_threshold: usize,
-    recovery_timeout: Duration,
+    failure_threshold: AtomicUsize,
+    recovery_timeout_ms: AtomicU64,
     failure_count: AtomicUsize,
+    /// Milliseconds since `created`
     last_failure_time: AtomicU64,
     success_count_in_half_open: AtomicUsize,
+    created: Instant,
 }
 
 impl PluginCircuitBreaker {
@@ -36,14 +42,21 @@ impl PluginCircuitBreaker {
         Self {
             plugin_name,
             state: Arc::new(RwLock::new(CircuitBreakerState::Closed)),
-            failure_threshold: config.failure_threshold,
-            recovery_timeout: config.recovery_timeout,
+            failure_threshold: AtomicUsize::new(config.failure_threshold),
+            recovery_timeout_ms: AtomicU64::new(config.recovery_timeout.as_millis() as u64),
             failure_count: AtomicUsize::new(0),
             last_failure_time: AtomicU64::new(0),
             success_count_in_half_open: AtomicUsize::new(0),
+            created: Instant::now(),
         }
     }
 
+    /// Apply new thresholds; the state and failures counted so far stay
+    pub fn reconfigure(&self, config: &CircuitBreakerConfig) {
+        self.failure_threshold.store(config.failure_threshold, Ordering::Relaxed);
+        self.recovery_timeout_ms.store(config.recovery_timeout.as_millis() as u64, Ordering::Relaxed);
+    }
+
     pub async fn execute<F, T>(&self, operation: F) -> CircuitBreakerResult<T>
     where
         F: std::future::Future<Output = BackworksResult<T>> + Send,
@@ -73,6 +86,8 @@ impl PluginCircuitBreaker {
                 self.record_success().await;
                 Ok(result)
             }
+            // Refusing a request is the plugin working, not failing
+            Err(err) if err.is_rejection() => Err(CircuitBreakerError::PluginError(err)),
             Err(err) => {
                 self.record_failure().await;
                 Err(CircuitBreakerError::PluginError(err))
@@ -92,6 +107,7 @@ impl PluginCircuitBreaker {
                 }
                 Ok(result)
             }
+            Err(err) if err.is_rejection() => Err(CircuitBreakerError::PluginError(err)),
             Err(err) => {
                 self.transition_to_open().await;
                 Err(CircuitBreakerError::PluginError(err))
@@ -100,13 +116,13 @@ impl PluginCircuitBreaker {
     }
 
     async fn record_failure(&self) {
-        let failure_count = self.failure_count.fetch_add(1, Ordering::Relaxed);
+        let failure_count = self.failure_count.fetch_add(1, Ordering::Relaxed) + 1;
         self.last_failure_time.store(
-            Instant::now().elapsed().as_millis() as u64,
+            self.created.elapsed().as_millis() as u64,
             Ordering::Relaxed,
         );
 
-        if failure_count >= self.failure_threshold {
+        if failure_count >= self.failure_threshold.load(Ordering::Relaxed) {
             self.transition_to_open().await;
         }
     }
@@ -117,11 +133,12 @@ impl PluginCircuitBreaker {
 
     async fn should_attempt_reset(&self) -> bool {
         let last_failure = self.last_failure_time.load(Ordering::Relaxed);
-        let now = Instant::now().elapsed().as_millis() as u64;
-        (now - last_failure) > self.recovery_timeout.as_millis() as u64
+        let now = self.created.elapsed().as_millis() as u64;
+        now.saturating_sub(last_failure) >= self.recovery_timeout_ms.load(Ordering::Relaxed)
     }
 
     async fn transition_to_open(&self) {
+        self.last_failure_time.store(self.created.elapsed().as_millis() as u64, Ordering::Relaxed);
         *self.state.write().await = CircuitBreakerState::Open;
         tracing::warn!("🔴 Circuit breaker OPEN for plugin: {}", self.plugin_name);
     }
@@ -147,30 +164,18 @@ impl PluginCircuitBreaker {
     }
 }
 
-impl Clone for PluginCircuitBreaker {
-    fn clone(&self) -> Self {
-        Self {
-            plugin_name: self.plugin_name.clone(),
-            state: self.state.clone(),
-            failure_threshold: self.failure_threshold,
-            recovery_timeout: self.recovery_timeout,
-            failure_count: AtomicUsize::new(self.failure_count.load(std::sync::atomic::Ordering::Relaxed)),
-            last_failure_time: AtomicU64::new(self.last_failure_time.load(std::sync::atomic::Ordering::Relaxed)),
-            success_count_in_half_open: AtomicUsize::new(self.success_count_in_half_open.load(std::sync::atomic::Ordering::Relaxed)),
-        }
-    }
-}
-
 /// Configuration for circuit breaker
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CircuitBreakerConfig {
+    /// Failures in a row that open the circuit
     #[serde(default = "default_failure_threshold")]
     pub failure_threshold: usize,
     
-    #[serde(default = "default_recovery_timeout")]
+    /// How long an open circuit blocks calls before letting one through
+    #[serde(default = "default_recovery_timeout", with = "duration_text")]
     pub recovery_timeout: Duration,
     
-    #[serde(default = "default_timeout")]
+    #[serde(default = "default_timeout", with = "duration_text")]
     pub timeout: Duration,
 }
 
@@ -194,9 +199,13 @@ pub struct PluginResourceLimits {
     #[serde(default)]
     pub max_memory_mb: Option<usize>,
     
-    #[serde(default)]
+    /// Calls taking longer fail; the plugin manager falls back to the
+    /// plugin's own `max_execution_time`
+    #[serde(default, with = "duration_text::option")]
     pub max_execution_time: Option<Duration>,
     
+    /// Bulkhead: calls beyond this many wait for one to finish, within
+    /// their execution time (default: unlimited)
     #[serde(default)]
     pub max_concurrent_operations: Option<usize>,
 }
@@ -205,8 +214,50 @@ impl Default for PluginResourceLimits {
     fn default() -> Self {
         Self {
             max_memory_mb: Some(100), // 100MB default limit
-            max_execution_time: Some(Duration::from_millis(100)), // 100ms default
-            max_concurrent_operations: Some(10), // 10 concurrent ops
+            max_execution_time: None,
+            max_concurrent_operations: None,
+        }
+    }
+}
+
+/// Durations written as in the rest of the blueprint (`"250ms"`, `"30s"`);
+/// bare numbers are seconds
+mod duration_text {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Text {
+        Text(String),
+        Seconds(f64),
+    }
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{}ms", duration.as_millis()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        parse(Text::deserialize(deserializer)?)
+    }
+
+    fn parse<E: Error>(text: Text) -> Result<Duration, E> {
+        match text {
+            Text::Text(text) => crate::config::parse_duration(&text).map_err(E::custom),
+            Text::Seconds(seconds) => Duration::try_from_secs_f64(seconds).map_err(E::custom),
+        }
+    }
+
+    pub mod option {
+        use super::*;
+        use serde::Serialize;
+
+        pub fn serialize<S: Serializer>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+            duration.map(|duration| format!("{}ms", duration.as_millis())).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+            Option::<Text>::deserialize(deserializer)?.map(parse).transpose()
         }
     }
 }
@@ -220,6 +271,8 @@ pub struct PluginMetrics {
     pub failed_invocations: u64,
     pub average_execution_time_ms: f64,
     pub p95_execution_time_ms: f64,
+    /// Duration of the latest invocation
+    pub last_execution_time_ms: f64,
     pub current_memory_usage_mb: f64,
     pub circuit_breaker_state: String,
     pub last_updated: chrono::DateTime<chrono::Utc>,
@@ -227,9 +280,19 @@ pub struct PluginMetrics {
 
 /// Plugin executor with resilience features
 pub struct ResilientPluginExecutor {
-    circuit_breakers: Arc<RwLock<HashMap<String, PluginCircuitBreaker>>>,
+    circuit_breakers: Arc<RwLock<HashMap<String, Arc<PluginCircuitBreaker>>>>,
     resource_limits: Arc<RwLock<HashMap<String, PluginResourceLimits>>>,
+    /// Permits for plugins with `max_concurrent_operations`
+    bulkheads: Arc<RwLock<HashMap<String, Arc<Semaphore>>>>,
     metrics: Arc<RwLock<HashMap<String, PluginMetrics>>>,
+    /// Execution times in microseconds, for percentiles
+    execution_times: Arc<RwLock<HashMap<String, Histogram<u64>>>>,
+}
+
+impl Default for ResilientPluginExecutor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ResilientPluginExecutor {
@@ -237,7 +300,9 @@ impl ResilientPluginExecutor {
         Self {
             circuit_breakers: Arc::new(RwLock::new(HashMap::new())),
             resource_limits: Arc::new(RwLock::new(HashMap::new())),
+            bulkheads: Arc::new(RwLock::new(HashMap::new())),
             metrics: Arc::new(RwLock::new(HashMap::new())),
+            execution_times: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
@@ -245,15 +310,12 @@ impl ResilientPluginExecutor {
         // Register circuit breaker
         let circuit_breaker = PluginCircuitBreaker::new(
             plugin_name.clone(),
-            config.circuit_breaker.unwrap_or_default(),
+            config.circuit_breaker.clone().unwrap_or_default(),
         );
-        self.circuit_breakers.write().await.insert(plugin_name.clone(), circuit_breaker);
+        self.circuit_breakers.write().await.insert(plugin_name.clone(), Arc::new(circuit_breaker));
 
         // Register resource limits
-        self.resource_limits.write().await.insert(
-            plugin_name.clone(),
-            config.resource_limits.unwrap_or_default(),
-        );
+        self.set_limits(&plugin_name, config.resource_limits.unwrap_or_default()).await;
 
         // Initialize metrics
         let metrics = PluginMetrics {
@@ -263,11 +325,40 @@ impl ResilientPluginExecutor {
             failed_invocations: 0,
             average_execution_time_ms: 0.0,
             p95_execution_time_ms: 0.0,
+            last_execution_time_ms: 0.0,
             current_memory_usage_mb: 0.0,
             circuit_breaker_state: "Closed".to_string(),
             last_updated: chrono::Utc::now(),
         };
-        self.metrics.write().await.insert(plugin_name, metrics);
+        self.metrics.write().await.insert(plugin_name.clone(), metrics);
+        let execution_times = Histogram::new_with_bounds(1, MAX_EXECUTION_MICROS, 2).expect("valid histogram bounds");
+        self.execution_times.write().await.insert(plugin_name, execution_times);
+    }
+
+    /// Apply a changed policy to a registered plugin; calls in flight
+    /// finish under the old one, and the circuit breaker keeps its state.
+    /// `false` when the plugin is not registered.
+    pub async fn reconfigure_plugin(&self, plugin_name: &str, config: ResilientPluginConfig) -> bool {
+        let Some(breaker) = self.circuit_breakers.read().await.get(plugin_name).cloned() else {
+            return false;
+        };
+        breaker.reconfigure(&config.circuit_breaker.unwrap_or_default());
+        self.set_limits(plugin_name, config.resource_limits.unwrap_or_default()).await;
+        true
+    }
+
+    async fn set_limits(&self, plugin_name: &str, limits: PluginResourceLimits) {
+        let mut bulkheads = self.bulkheads.write().await;
+        match limits.max_concurrent_operations {
+            Some(permits) => bulkheads.insert(plugin_name.to_string(), Arc::new(Semaphore::new(permits.max(1)))),
+            None => bulkheads.remove(plugin_name),
+        };
+        self.resource_limits.write().await.insert(plugin_name.to_string(), limits);
+    }
+
+    /// The policy a plugin currently runs under
+    pub async fn plugin_limits(&self, plugin_name: &str) -> Option<PluginResourceLimits> {
+        self.resource_limits.read().await.get(plugin_name).cloned()
     }
 
     pub async fn execute_with_resilience<F, T>(
@@ -299,6 +390,16 @@ impl ResilientPluginExecutor {
             limits_map.get(plugin_name).cloned().unwrap_or_default()
         };
 
+        // Beyond the bulkhead's permits calls wait, within their timeout
+        let bulkhead = self.bulkheads.read().await.get(plugin_name).cloned();
+        let operation = async {
+            let _permit = match &bulkhead {
+                Some(bulkhead) => Some(bulkhead.acquire().await.expect("bulkheads are never closed")),
+                None => None,
+            };
+            operation.await
+        };
+
         // Apply timeout if specified
         let operation_with_timeout = async {
             if let Some(max_time) = limits.max_execution_time {
@@ -335,10 +436,15 @@ impl ResilientPluginExecutor {
             }
 
             // Update average execution time (simple moving average)
-            let new_time_ms = execution_time.as_millis() as f64;
+            let new_time_ms = execution_time.as_secs_f64() * 1000.0;
             metrics.average_execution_time_ms = 
                 (metrics.average_execution_time_ms * (metrics.total_invocations - 1) as f64 + new_time_ms) 
                 / metrics.total_invocations as f64;
+            metrics.last_execution_time_ms = new_time_ms;
+            if let Some(execution_times) = self.execution_times.write().await.get_mut(plugin_name) {
+                execution_times.saturating_record((execution_time.as_micros() as u64).clamp(1, MAX_EXECUTION_MICROS));
+                metrics.p95_execution_time_ms = execution_times.value_at_quantile(0.95) as f64 / 1000.0;
+            }
 
             metrics.last_updated = chrono::Utc::now();
 
@@ -360,6 +466,19 @@ impl ResilientPluginExecutor {
     pub async fn get_all_metrics(&self) -> HashMap<String, PluginMetrics> {
         self.metrics.read().await.clone()
     }
+
+    /// Plugins whose circuit breaker currently blocks calls, sorted by name
+    pub async fn open_circuits(&self) -> Vec<String> {
+        let breakers = self.circuit_breakers.read().await;
+        let mut open = Vec::new();
+        for (name, breaker) in breakers.iter() {
+            if breaker.get_state().await == CircuitBreakerState::Open {
+                open.push(name.clone());
+            }
+        }
+        open.sort();
+        open
+    }
 }
 
 /// Configuration for resilient plugin execution
@@ -416,3 +535,45 @@ impl From<ResilientExecutionError> for crate::error::BackworksError {
         crate::error::BackworksError::plugin(err.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::BackworksError;
+
+    #[tokio::test]
+    async fn test_policy_from_yaml_applies_and_can_be_changed() {
+        let policy: ResilientPluginConfig = serde_yaml::from_str(
+            "circuit_breaker:\n  failure_threshold: 2\n  recovery_timeout: 30s\nresource_limits:\n  max_execution_time: 250ms\n  max_concurrent_operations: 1\n",
+        ).unwrap();
+        let limits = policy.resource_limits.clone().unwrap();
+        assert_eq!(limits.max_execution_time, Some(Duration::from_millis(250)));
+
+        let executor = ResilientPluginExecutor::new();
+        executor.register_plugin("flaky".to_string(), policy.clone()).await;
+        for _ in 0..2 {
+            let result = executor.execute_with_resilience("flaky", async { Err::<(), _>(BackworksError::plugin("boom")) }).await;
+            assert!(result.is_err());
+        }
+        assert_eq!(executor.open_circuits().await, vec!["flaky".to_string()]);
+
+        // An instant call waits for the bulkhead's only permit past its budget
+        executor.register_plugin("busy".to_string(), policy.clone()).await;
+        let slow = executor.execute_with_resilience("busy", async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            Ok(())
+        });
+        let queued = executor.execute_with_resilience("busy", async { Ok(()) });
+        let (slow, queued) = tokio::join!(slow, queued);
+        for result in [slow, queued] {
+            assert!(matches!(result, Err(ResilientExecutionError::CircuitBreakerError(CircuitBreakerError::PluginError(BackworksError::PluginTimeout(_))))));
+        }
+
+        let mut relaxed = policy;
+        relaxed.circuit_breaker.as_mut().unwrap().failure_threshold = 10;
+        relaxed.resource_limits.as_mut().unwrap().max_concurrent_operations = None;
+        assert!(executor.reconfigure_plugin("busy", relaxed).await);
+        assert_eq!(executor.plugin_limits("busy").await.unwrap().max_concurrent_operations, None);
+        assert!(!executor.reconfigure_plugin("missing", ResilientPluginConfig::default()).await);
+    }
+}
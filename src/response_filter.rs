@@ -0,0 +1,207 @@
+//! Response field filtering
+//!
+//! Applies a [`ResponseFilter`]'s `include_fields` and `exclude_fields` to a
+//! JSON body. Fields are JSONPath expressions such as `$.address.city`, or
+//! the dotted `address.city`; wildcards (`$.users[*].email`, `$.meta.*`)
+//! match every element or key. Arrays are also filtered element by element
+//! without one, so `id,name` selects those fields from every item of a list
+//! response. Unknown fields are ignored.
+//!
+//! Clients ask for a partial response with `?fields=id,name,address.city`,
+//! which becomes a filter with those `include_fields`.
+
+use crate::config::ResponseFilter;
+use crate::error::{BackworksError, Result};
+use crate::jsonpath::{JsonPath, Segment};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Query parameter selecting the fields of a partial response
+pub const FIELDS_PARAM: &str = "fields";
+
+/// Selected paths as a tree; a leaf keeps the whole value below it
+#[derive(Debug, Default)]
+struct FieldTree {
+    fields: BTreeMap<String, FieldTree>,
+    /// Subtree applying to every key or element, from a wildcard
+    any: Option<Box<FieldTree>>,
+}
+
+impl FieldTree {
+    /// Paths that do not parse, or index into arrays, select nothing
+    fn new<'a>(paths: impl IntoIterator<Item = &'a String>) -> Self {
+        let mut tree = Self::default();
+        for path in paths.into_iter().filter_map(|path| JsonPath::parse(path).ok()) {
+            if path.segments().iter().any(|segment| matches!(segment, Segment::Index(_))) {
+                continue;
+            }
+            let mut node = &mut tree;
+            for segment in path.segments() {
+                node = match segment {
+                    Segment::Key(key) => node.fields.entry(key.clone()).or_default(),
+                    _ => node.any.get_or_insert_with(Default::default),
+                };
+            }
+        }
+        tree
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.fields.is_empty() && self.any.is_none()
+    }
+
+    /// Tree applying to the elements of an array
+    fn elements(&self) -> &Self {
+        match self.any {
+            Some(ref any) if self.fields.is_empty() => any,
+            _ => self,
+        }
+    }
+}
+
+/// Reject filters this module cannot apply
+pub fn validate(filter: &ResponseFilter) -> Result<()> {
+    if filter.field_filters.is_some() || filter.pagination.is_some() {
+        return Err(BackworksError::config("response_filter supports only include_fields and exclude_fields"));
+    }
+    for path in filter.include_fields.iter().chain(&filter.exclude_fields).flatten() {
+        let parsed = JsonPath::parse(path)?;
+        if parsed.segments().iter().any(|segment| matches!(segment, Segment::Index(_))) {
+            return Err(BackworksError::config(format!("Field '{}' selects an array index; use [*] to filter every element", path)));
+        }
+    }
+    Ok(())
+}
+
+/// A filter selecting the comma-separated `fields`, or `None` when it names none
+pub fn from_fields_param(fields: &str) -> Option<ResponseFilter> {
+    let include: Vec<String> = fields.split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .map(str::to_string)
+        .collect();
+    if include.is_empty() {
+        return None;
+    }
+    Some(ResponseFilter {
+        include_fields: Some(include),
+        exclude_fields: None,
+        field_filters: None,
+        pagination: None,
+    })
+}
+
+/// Apply the filter's field selection and removals to `body`
+pub fn apply(filter: &ResponseFilter, body: Value) -> Value {
+    let body = match filter.include_fields {
+        Some(ref include) if !include.is_empty() => select(&FieldTree::new(include), body),
+        _ => body,
+    };
+    match filter.exclude_fields {
+        Some(ref exclude) if !exclude.is_empty() => remove(&FieldTree::new(exclude), body),
+        _ => body,
+    }
+}
+
+fn select(tree: &FieldTree, value: Value) -> Value {
+    match value {
+        Value::Array(items) => {
+            let tree = tree.elements();
+            Value::Array(items.into_iter().map(|item| select(tree, item)).collect())
+        }
+        Value::Object(object) => {
+            let mut selected = Map::new();
+            for (key, value) in object {
+                let subtree = match (tree.fields.get(&key), &tree.any) {
+                    (Some(subtree), _) => subtree,
+                    (None, Some(any)) => any,
+                    (None, None) => continue,
+                };
+                let value = if subtree.is_leaf() { value } else { select(subtree, value) };
+                selected.insert(key, value);
+            }
+            Value::Object(selected)
+        }
+        scalar => scalar,
+    }
+}
+
+fn remove(tree: &FieldTree, value: Value) -> Value {
+    match value {
+        Value::Array(items) => {
+            let tree = tree.elements();
+            Value::Array(items.into_iter().map(|item| remove(tree, item)).collect())
+        }
+        Value::Object(object) => {
+            let mut kept = Map::new();
+            for (key, value) in object {
+                let subtree = tree.fields.get(&key).or(tree.any.as_deref());
+                match subtree {
+                    Some(subtree) if subtree.is_leaf() => {}
+                    Some(subtree) => {
+                        kept.insert(key, remove(subtree, value));
+                    }
+                    None => {
+                        kept.insert(key, value);
+                    }
+                }
+            }
+            Value::Object(kept)
+        }
+        scalar => scalar,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_fields_select_nested_paths_in_lists() {
+        let body = json!([
+            { "id": 1, "name": "Ada", "email": "ada@example.com", "address": { "city": "London", "zip": "N1" } },
+            { "id": 2, "name": "Alan", "address": null },
+        ]);
+        let filter = from_fields_param("id, name,address.city,missing").unwrap();
+        assert_eq!(apply(&filter, body), json!([
+            { "id": 1, "name": "Ada", "address": { "city": "London" } },
+            { "id": 2, "name": "Alan", "address": null },
+        ]));
+        assert!(from_fields_param(" , ").is_none());
+    }
+
+    #[test]
+    fn test_exclude_fields_after_include() {
+        let filter = ResponseFilter {
+            include_fields: Some(vec!["user".to_string()]),
+            exclude_fields: Some(vec!["user.password_hash".to_string()]),
+            field_filters: None,
+            pagination: None,
+        };
+        let body = json!({ "user": { "id": 1, "password_hash": "x" }, "debug": true });
+        assert_eq!(apply(&filter, body), json!({ "user": { "id": 1 } }));
+    }
+
+    #[test]
+    fn test_jsonpath_fields_and_wildcards() {
+        let filter = ResponseFilter {
+            include_fields: Some(vec!["$.users[*].id".to_string(), "$['meta'].*".to_string()]),
+            exclude_fields: Some(vec!["$.meta.*.internal".to_string()]),
+            field_filters: None,
+            pagination: None,
+        };
+        let body = json!({
+            "users": [{ "id": 1, "email": "a@example.com" }, { "id": 2 }],
+            "meta": { "page": { "n": 1, "internal": true }, "total": 2 },
+            "debug": true,
+        });
+        assert_eq!(apply(&filter, body), json!({
+            "users": [{ "id": 1 }, { "id": 2 }],
+            "meta": { "page": { "n": 1 }, "total": 2 },
+        }));
+        assert!(validate(&filter).is_ok());
+        assert!(validate(&from_fields_param("users[0].id").unwrap()).is_err());
+        assert!(validate(&from_fields_param("$..id").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,193 @@
+//! Data retention
+//!
+//! Stateful features keep data about an endpoint's requests: sessions'
+//! copies of its stateful mock collection in the [state store](crate::state),
+//! captured requests, and journal entries. An endpoint's `retention` block
+//! bounds them by age (`max_age`) and count (`max_items`), enforced every
+//! minute while the server runs. `POST /_backworks/purge` and
+//! `backworks purge` remove data on demand.
+
+use crate::config::{parse_duration, BackworksConfig, RetentionConfig};
+use crate::error::{BackworksError, Result};
+use crate::routes::RoutePattern;
+use crate::server::AppState;
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What to remove of one endpoint's data
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetentionPolicy {
+    pub endpoint: String,
+    /// Data older than this is removed
+    pub max_age: Option<Duration>,
+    /// Only the newest this many records are kept
+    pub max_items: Option<usize>,
+}
+
+/// Body of `POST /_backworks/purge`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PurgeRequest {
+    /// Endpoint name or path; every endpoint when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    /// Only remove data older than this, such as `7d`; everything when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub older_than: Option<String>,
+}
+
+/// Counts reported after a purge
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PurgeSummary {
+    pub endpoints: Vec<String>,
+    /// Sessions' copies of stateful mock collections dropped
+    pub mock_collections: usize,
+    /// Items trimmed from stateful mock collections
+    pub mock_items: usize,
+    pub captured_requests: usize,
+    pub journal_entries: usize,
+}
+
+impl PurgeSummary {
+    pub fn removed(&self) -> usize {
+        self.mock_collections + self.mock_items + self.captured_requests + self.journal_entries
+    }
+}
+
+impl RetentionPolicy {
+    /// The `retention` blocks of the blueprint's endpoints
+    pub fn from_config(config: &BackworksConfig) -> Result<Vec<Self>> {
+        let mut policies = Vec::new();
+        for (name, endpoint) in &config.endpoints {
+            if let Some(ref retention) = endpoint.retention {
+                policies.push(Self::new(name, retention)?);
+            }
+        }
+        policies.sort_by(|a, b| a.endpoint.cmp(&b.endpoint));
+        Ok(policies)
+    }
+
+    fn new(endpoint: &str, retention: &RetentionConfig) -> Result<Self> {
+        if retention.max_age.is_none() && retention.max_items.is_none() {
+            return Err(BackworksError::config(format!("Endpoint '{}' retention needs `max_age` or `max_items`", endpoint)));
+        }
+        let max_age = retention.max_age.as_deref()
+            .map(parse_duration)
+            .transpose()
+            .map_err(|e| BackworksError::config(format!("Endpoint '{}' retention max_age: {}", endpoint, e)))?;
+        Ok(Self { endpoint: endpoint.to_string(), max_age, max_items: retention.max_items })
+    }
+
+    /// Policies removing what `request` asks for
+    pub fn for_purge(config: &BackworksConfig, request: &PurgeRequest) -> std::result::Result<Vec<Self>, (StatusCode, String)> {
+        let max_age = match request.older_than {
+            Some(ref older_than) => parse_duration(older_than).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?,
+            None => Duration::ZERO,
+        };
+        let mut endpoints: Vec<&String> = match request.endpoint {
+            Some(ref wanted) => config.endpoints.iter()
+                .filter(|(name, endpoint)| *name == wanted || endpoint.path == *wanted)
+                .map(|(name, _)| name)
+                .collect(),
+            None => config.endpoints.keys().collect(),
+        };
+        if endpoints.is_empty() {
+            let wanted = request.endpoint.as_deref().unwrap_or_default();
+            return Err((StatusCode::NOT_FOUND, format!("No endpoint is named or has the path '{}'", wanted)));
+        }
+        endpoints.sort();
+        Ok(endpoints.into_iter()
+            .map(|endpoint| Self { endpoint: endpoint.clone(), max_age: Some(max_age), max_items: None })
+            .collect())
+    }
+}
+
+/// Remove the data the policies no longer keep
+pub async fn purge(state: &AppState, policies: &[RetentionPolicy]) -> PurgeSummary {
+    let mut summary = PurgeSummary::default();
+    for policy in policies {
+        let Some(endpoint) = state.config.endpoints.get(&policy.endpoint) else {
+            continue;
+        };
+        let cutoff = policy.max_age
+            .and_then(|max_age| chrono::Duration::from_std(max_age).ok())
+            .map(|max_age| chrono::Utc::now() - max_age);
+        summary.endpoints.push(policy.endpoint.clone());
+
+        let (collections, items) = state.mocks.purge(&policy.endpoint, &state.state_store, cutoff, policy.max_items).await;
+        summary.mock_collections += collections;
+        summary.mock_items += items;
+
+        if let Some(ref capture) = state.capture {
+            let pattern = RoutePattern::parse(&endpoint.path);
+            let requested = |request: &crate::capture::CapturedRequest| {
+                endpoint.methods.iter().any(|method| method.eq_ignore_ascii_case(&request.method)) && pattern.matches(&request.path)
+            };
+            summary.captured_requests += capture.purge(requested, cutoff, policy.max_items).await;
+        }
+
+        if let Some(ref journal) = state.journal {
+            match journal.purge(&policy.endpoint, cutoff, policy.max_items).await {
+                Ok(removed) => summary.journal_entries += removed,
+                Err(e) => warn!("Failed to purge journal entries of '{}': {}", policy.endpoint, e),
+            }
+        }
+    }
+    summary
+}
+
+/// Enforce the policies in the background
+pub fn spawn(state: AppState, policies: Vec<RetentionPolicy>) -> JoinHandle<()> {
+    info!("🧹 Enforcing data retention of {} endpoint(s) every {:?}", policies.len(), SWEEP_INTERVAL);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let summary = purge(&state, &policies).await;
+            if summary.removed() > 0 {
+                info!("🧹 Retention removed {} mock collection(s), {} mock item(s), {} captured request(s) and {} journal entries",
+                    summary.mock_collections, summary.mock_items, summary.captured_requests, summary.journal_entries);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policies_from_blueprint_and_purge_requests() {
+        let config: BackworksConfig = serde_yaml::from_str(r#"
+name: shop
+endpoints:
+  users: { path: /users, methods: [GET], retention: { max_age: 7d } }
+  user: { path: "/users/{id}", methods: [GET], retention: { max_items: 50 } }
+  health: { path: /health, methods: [GET] }
+"#).unwrap();
+        let policies = RetentionPolicy::from_config(&config).unwrap();
+        assert_eq!(policies, vec![
+            RetentionPolicy { endpoint: "user".to_string(), max_age: None, max_items: Some(50) },
+            RetentionPolicy { endpoint: "users".to_string(), max_age: Some(Duration::from_secs(7 * 86400)), max_items: None },
+        ]);
+
+        let request = |endpoint: Option<&str>, older_than: Option<&str>| PurgeRequest {
+            endpoint: endpoint.map(str::to_string),
+            older_than: older_than.map(str::to_string),
+        };
+        let purged = RetentionPolicy::for_purge(&config, &request(Some("/users"), Some("12h"))).unwrap();
+        assert_eq!(purged, vec![RetentionPolicy { endpoint: "users".to_string(), max_age: Some(Duration::from_secs(12 * 3600)), max_items: None }]);
+        let everything = RetentionPolicy::for_purge(&config, &request(None, None)).unwrap();
+        assert_eq!(everything.iter().map(|policy| policy.endpoint.as_str()).collect::<Vec<_>>(), vec!["health", "user", "users"]);
+        assert!(everything.iter().all(|policy| policy.max_age == Some(Duration::ZERO)));
+        assert_eq!(RetentionPolicy::for_purge(&config, &request(Some("orders"), None)).unwrap_err().0, StatusCode::NOT_FOUND);
+
+        let mut invalid = config.clone();
+        invalid.endpoints.get_mut("health").unwrap().retention = Some(RetentionConfig { max_age: None, max_items: None });
+        assert!(RetentionPolicy::from_config(&invalid).unwrap_err().to_string().contains("needs `max_age` or `max_items`"));
+    }
+}
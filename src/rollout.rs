@@ -0,0 +1,199 @@
+//! Endpoint rollout scheduling
+//!
+//! Endpoints can be limited to an activation window and/or a share of
+//! traffic that grows over time. Requests are bucketed deterministically by a
+//! sticky key so the same caller keeps seeing the same variant while the
+//! percentage is unchanged.
+
+use crate::config::{BackworksConfig, RolloutConfig};
+use crate::error::{BackworksError, BackworksResult};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RolloutPhase {
+    /// Activation window has not started
+    Scheduled,
+    /// Serving a share of traffic
+    Rolling,
+    /// Serving all traffic
+    Complete,
+    /// Activation window has ended
+    Ended,
+}
+
+/// Rollout state of one endpoint at a point in time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RolloutStatus {
+    pub endpoint: String,
+    pub path: String,
+    pub phase: RolloutPhase,
+    pub percentage: f64,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    /// Next scheduled percentage change, if any
+    pub next_step_at: Option<DateTime<Utc>>,
+}
+
+/// An endpoint together with its rollout rules
+#[derive(Debug, Clone)]
+pub struct RolloutSchedule {
+    pub endpoint: String,
+    pub path: String,
+    pub config: RolloutConfig,
+}
+
+impl RolloutSchedule {
+    /// Collect the schedules of every endpoint declaring a rollout
+    pub fn from_config(config: &BackworksConfig) -> Vec<Self> {
+        let mut schedules: Vec<Self> = config.endpoints.iter()
+            .filter_map(|(name, endpoint)| {
+                endpoint.rollout.as_ref().map(|rollout| Self {
+                    endpoint: name.clone(),
+                    path: endpoint.path.clone(),
+                    config: rollout.clone(),
+                })
+            })
+            .collect();
+        schedules.sort_by(|a, b| a.endpoint.cmp(&b.endpoint));
+        schedules
+    }
+
+    pub fn status(&self, now: DateTime<Utc>) -> RolloutStatus {
+        let config = &self.config;
+        let (phase, percentage) = if config.start.is_some_and(|start| now < start) {
+            (RolloutPhase::Scheduled, 0.0)
+        } else if config.end.is_some_and(|end| now >= end) {
+            (RolloutPhase::Ended, 0.0)
+        } else {
+            let percentage = config.steps.iter()
+                .filter(|step| step.at <= now)
+                .max_by_key(|step| step.at)
+                .map(|step| step.percentage)
+                .unwrap_or(config.percentage.unwrap_or(100.0));
+            let phase = if percentage >= 100.0 { RolloutPhase::Complete } else { RolloutPhase::Rolling };
+            (phase, percentage)
+        };
+
+        RolloutStatus {
+            endpoint: self.endpoint.clone(),
+            path: self.path.clone(),
+            phase,
+            percentage,
+            starts_at: config.start,
+            ends_at: config.end,
+            next_step_at: config.steps.iter().map(|step| step.at).filter(|at| *at > now).min(),
+        }
+    }
+
+    /// Whether a request with the given sticky key is routed to the endpoint
+    pub fn admits(&self, now: DateTime<Utc>, sticky_key: Option<&str>) -> bool {
+        let percentage = self.status(now).percentage;
+        if percentage >= 100.0 {
+            return true;
+        }
+        if percentage <= 0.0 {
+            return false;
+        }
+
+        let bucket = match sticky_key {
+            Some(key) => bucket(&self.endpoint, key),
+            None => rand::random::<f64>() * 100.0,
+        };
+        bucket < percentage
+    }
+}
+
+/// Stable bucket in [0, 100) for a caller, independent per endpoint
+fn bucket(endpoint: &str, key: &str) -> f64 {
+    let hash = fnv1a(endpoint.bytes().chain([0]).chain(key.bytes()));
+    (hash % 10_000) as f64 / 100.0
+}
+
+/// FNV-1a: stable across processes and releases, unlike std's hasher
+pub(crate) fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+pub fn validate_rollout(endpoint: &str, config: &RolloutConfig) -> BackworksResult<()> {
+    let percentages = config.percentage.into_iter().chain(config.steps.iter().map(|step| step.percentage));
+    for percentage in percentages {
+        if !(0.0..=100.0).contains(&percentage) {
+            return Err(BackworksError::config(format!(
+                "Endpoint '{}' rollout percentage {} must be between 0 and 100",
+                endpoint, percentage
+            )));
+        }
+    }
+
+    if let (Some(start), Some(end)) = (config.start, config.end) {
+        if start >= end {
+            return Err(BackworksError::config(format!("Endpoint '{}' rollout must start before it ends", endpoint)));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RolloutStep;
+
+    fn at(value: &str) -> DateTime<Utc> {
+        value.parse().unwrap()
+    }
+
+    fn schedule(config: RolloutConfig) -> RolloutSchedule {
+        RolloutSchedule { endpoint: "orders_v2".to_string(), path: "/v2/orders".to_string(), config }
+    }
+
+    fn rollout() -> RolloutConfig {
+        RolloutConfig {
+            start: Some(at("2026-01-01T00:00:00Z")),
+            end: Some(at("2026-06-01T00:00:00Z")),
+            percentage: Some(10.0),
+            steps: vec![RolloutStep { at: at("2026-02-01T00:00:00Z"), percentage: 50.0 }],
+            sticky_header: None,
+        }
+    }
+
+    #[test]
+    fn test_status_follows_window_and_steps() {
+        let schedule = schedule(rollout());
+
+        let status = schedule.status(at("2025-12-31T00:00:00Z"));
+        assert_eq!(status.phase, RolloutPhase::Scheduled);
+        assert_eq!(status.percentage, 0.0);
+
+        let status = schedule.status(at("2026-01-15T00:00:00Z"));
+        assert_eq!((status.phase, status.percentage), (RolloutPhase::Rolling, 10.0));
+        assert_eq!(status.next_step_at, Some(at("2026-02-01T00:00:00Z")));
+
+        assert_eq!(schedule.status(at("2026-03-01T00:00:00Z")).percentage, 50.0);
+        assert_eq!(schedule.status(at("2026-07-01T00:00:00Z")).phase, RolloutPhase::Ended);
+    }
+
+    #[test]
+    fn test_sticky_bucketing_matches_percentage() {
+        let schedule = schedule(rollout());
+        let now = at("2026-01-15T00:00:00Z");
+
+        let admitted = (0..10_000).filter(|i| schedule.admits(now, Some(&format!("user-{}", i)))).count();
+        assert!((800..1200).contains(&admitted), "admitted {}", admitted);
+        assert_eq!(schedule.admits(now, Some("user-7")), schedule.admits(now, Some("user-7")));
+        assert!(!schedule.admits(at("2025-01-01T00:00:00Z"), Some("user-7")));
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_percentages() {
+        let mut config = rollout();
+        config.percentage = Some(120.0);
+        assert!(validate_rollout("orders_v2", &config).is_err());
+    }
+}
@@ -0,0 +1,570 @@
+//! Endpoint path patterns
+//!
+//! Endpoint paths use `:name` or `{name}` for a parameter matching one
+//! segment and `*name`, `{*name}` or `{name:*}` for a catch-all matching the
+//! rest of the path. Braced parameters may declare a type, as in
+//! `{id:int}`; the server rejects requests whose value does not parse and
+//! hands the converted value to the handler.
+//!
+//! Overlapping patterns are legal: the router prefers static segments
+//! over parameters and parameters over catch-alls, so the less specific
+//! endpoint silently never sees the requests the other one matches.
+//! [`find_conflicts`] reports such pairs, and the pairs the router refuses
+//! outright.
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Type a path parameter's value must parse as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParamType {
+    #[default]
+    String,
+    Int,
+    Float,
+    Bool,
+    Uuid,
+}
+
+impl ParamType {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "str" | "string" => Some(ParamType::String),
+            "int" | "integer" => Some(ParamType::Int),
+            "float" | "number" => Some(ParamType::Float),
+            "bool" | "boolean" => Some(ParamType::Bool),
+            "uuid" => Some(ParamType::Uuid),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParamType::String => "string",
+            ParamType::Int => "int",
+            ParamType::Float => "float",
+            ParamType::Bool => "bool",
+            ParamType::Uuid => "uuid",
+        }
+    }
+
+    /// A value of this type, for requests made up from the pattern alone
+    pub fn sample(&self) -> &'static str {
+        match self {
+            ParamType::Float => "1.5",
+            ParamType::Bool => "true",
+            ParamType::Uuid => "00000000-0000-0000-0000-000000000001",
+            ParamType::String | ParamType::Int => "1",
+        }
+    }
+
+    /// The value handed to handlers, or `None` when `raw` is not of this type
+    pub fn convert(&self, raw: &str) -> Option<Value> {
+        match self {
+            ParamType::String => Some(Value::String(raw.to_string())),
+            ParamType::Int => raw.parse::<i64>().ok().map(Value::from),
+            ParamType::Float => raw.parse::<f64>().ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(Value::Number),
+            ParamType::Bool => raw.parse::<bool>().ok().map(Value::Bool),
+            ParamType::Uuid => uuid::Uuid::parse_str(raw).ok().map(|id| Value::String(id.to_string())),
+        }
+    }
+
+    /// OpenAPI schema of the parameter
+    pub fn schema(&self) -> Value {
+        match self {
+            ParamType::String => json!({ "type": "string" }),
+            ParamType::Int => json!({ "type": "integer", "format": "int64" }),
+            ParamType::Float => json!({ "type": "number", "format": "double" }),
+            ParamType::Bool => json!({ "type": "boolean" }),
+            ParamType::Uuid => json!({ "type": "string", "format": "uuid" }),
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            ParamType::String => "a string",
+            ParamType::Int => "an integer",
+            ParamType::Float => "a number",
+            ParamType::Bool => "true or false",
+            ParamType::Uuid => "a UUID",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Static(String),
+    Param(String, ParamType),
+    CatchAll(String),
+}
+
+impl PathSegment {
+    fn parse(segment: &str) -> Result<Self, String> {
+        let braced = segment.strip_prefix('{').and_then(|s| s.strip_suffix('}'));
+        Ok(match (braced, segment.strip_prefix(':'), segment.strip_prefix('*')) {
+            (Some(name), _, _) => match (name.strip_prefix('*'), name.split_once(':')) {
+                (Some(name), _) => PathSegment::CatchAll(name.to_string()),
+                (None, Some((name, "*"))) => PathSegment::CatchAll(name.to_string()),
+                (None, Some((name, kind))) => match ParamType::parse(kind) {
+                    Some(kind) => PathSegment::Param(name.to_string(), kind),
+                    None => return Err(format!(
+                        "unknown type '{}' for parameter '{}' (expected string, int, float, bool, uuid or *)",
+                        kind, name
+                    )),
+                },
+                (None, None) => PathSegment::Param(name.to_string(), ParamType::String),
+            },
+            (None, Some(name), _) => PathSegment::Param(name.to_string(), ParamType::String),
+            (None, None, Some(name)) => PathSegment::CatchAll(name.to_string()),
+            (None, None, None) => PathSegment::Static(segment.to_string()),
+        })
+    }
+
+    /// Lower is preferred by the router
+    fn rank(&self) -> u8 {
+        match self {
+            PathSegment::Static(_) => 0,
+            PathSegment::Param(..) => 1,
+            PathSegment::CatchAll(_) => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutePattern {
+    segments: Vec<PathSegment>,
+}
+
+/// Which of two patterns wins the requests both match
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    First,
+    Second,
+}
+
+/// How two patterns relate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Overlap {
+    /// No request matches both
+    Disjoint,
+    /// Same pattern, parameter names included
+    Identical,
+    /// Same pattern with differently named parameters, which the router
+    /// refuses to register
+    ParamNames,
+    /// Some requests, like `example`, match both; `winner` serves them
+    Shadowed { winner: Side, example: String },
+}
+
+impl RoutePattern {
+    /// Parse `path`, reading parameters of unknown type as strings; only
+    /// route shapes matter to callers of this
+    pub fn parse(path: &str) -> Self {
+        let segments = path.trim_matches('/')
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| PathSegment::parse(segment).unwrap_or_else(|_| {
+                let name = segment.trim_matches(['{', '}']).split(':').next().unwrap_or_default();
+                PathSegment::Param(name.to_string(), ParamType::String)
+            }))
+            .collect();
+        Self { segments }
+    }
+
+    /// Parse `path`, rejecting unknown parameter types and catch-alls that
+    /// are not the last segment
+    pub fn try_parse(path: &str) -> Result<Self, String> {
+        let segments = path.trim_matches('/')
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(PathSegment::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(PathSegment::CatchAll(name)) = segments.iter().rev().skip(1).find(|s| matches!(s, PathSegment::CatchAll(_))) {
+            return Err(format!("catch-all parameter '{}' must be the last segment", name));
+        }
+        Ok(Self { segments })
+    }
+
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.segments
+    }
+
+    /// Parameter names in order
+    pub fn params(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().filter_map(|segment| match segment {
+            PathSegment::Param(name, _) | PathSegment::CatchAll(name) => Some(name.as_str()),
+            PathSegment::Static(_) => None,
+        })
+    }
+
+    /// The pattern in the router's syntax, e.g. `/users/:id/files/*path`
+    pub fn router_path(&self) -> String {
+        let segments: Vec<String> = self.segments.iter()
+            .map(|segment| match segment {
+                PathSegment::Static(value) => value.clone(),
+                PathSegment::Param(name, _) => format!(":{}", name),
+                PathSegment::CatchAll(name) => format!("*{}", name),
+            })
+            .collect();
+        format!("/{}", segments.join("/"))
+    }
+
+    /// The pattern in OpenAPI syntax, e.g. `/users/{id}/files/{path}`
+    pub fn openapi_path(&self) -> String {
+        let segments: Vec<String> = self.segments.iter()
+            .map(|segment| match segment {
+                PathSegment::Static(value) => value.clone(),
+                PathSegment::Param(name, _) | PathSegment::CatchAll(name) => format!("{{{}}}", name),
+            })
+            .collect();
+        format!("/{}", segments.join("/"))
+    }
+
+    /// A path the pattern matches, with the parameters in `values` and a
+    /// [sample](ParamType::sample) of their type for the others
+    pub fn sample_path(&self, values: &HashMap<String, String>) -> String {
+        let segments: Vec<&str> = self.segments.iter()
+            .map(|segment| match segment {
+                PathSegment::Static(value) => value.as_str(),
+                PathSegment::Param(name, kind) => values.get(name).map_or(kind.sample(), String::as_str),
+                PathSegment::CatchAll(name) => values.get(name).map_or("1", String::as_str),
+            })
+            .collect();
+        format!("/{}", segments.join("/"))
+    }
+
+    /// OpenAPI parameter objects of the path parameters, in order
+    pub fn openapi_parameters(&self) -> Vec<Value> {
+        self.segments.iter()
+            .filter_map(|segment| match segment {
+                PathSegment::Param(name, kind) => Some((name, kind.schema())),
+                PathSegment::CatchAll(name) => Some((name, json!({ "type": "string" }))),
+                PathSegment::Static(_) => None,
+            })
+            .map(|(name, schema)| json!({ "name": name, "in": "path", "required": true, "schema": schema }))
+            .collect()
+    }
+
+    /// Convert the raw parameters the router captured to their declared
+    /// types; the error names the first parameter that does not parse
+    pub fn extract(&self, raw: HashMap<String, String>) -> Result<HashMap<String, Value>, String> {
+        let mut params = HashMap::with_capacity(raw.len());
+        for (name, value) in raw {
+            let kind = self.segments.iter()
+                .find_map(|segment| match segment {
+                    PathSegment::Param(param, kind) if *param == name => Some(*kind),
+                    _ => None,
+                })
+                .unwrap_or_default();
+            let converted = kind.convert(&value)
+                .ok_or_else(|| format!("Path parameter '{}' must be {}, got '{}'", name, kind.description(), value))?;
+            params.insert(name, converted);
+        }
+        Ok(params)
+    }
+
+    /// Whether a request path has the pattern's shape; parameter types are
+    /// not checked
+    pub fn matches(&self, path: &str) -> bool {
+        let mut parts = path.trim_matches('/').split('/').filter(|part| !part.is_empty());
+        for segment in &self.segments {
+            match (segment, parts.next()) {
+                (PathSegment::CatchAll(_), _) => return true,
+                (PathSegment::Static(value), Some(part)) if value == part => {}
+                (PathSegment::Param(..), Some(_)) => {}
+                _ => return false,
+            }
+        }
+        parts.next().is_none()
+    }
+
+    /// The pattern with parameter names erased, e.g. `/users/{}`
+    pub fn shape(&self) -> String {
+        let segments: Vec<&str> = self.segments.iter()
+            .map(|segment| match segment {
+                PathSegment::Static(value) => value.as_str(),
+                PathSegment::Param(..) => "{}",
+                PathSegment::CatchAll(_) => "{*}",
+            })
+            .collect();
+        format!("/{}", segments.join("/"))
+    }
+
+    pub fn overlap(&self, other: &RoutePattern) -> Overlap {
+        if self.shape() == other.shape() {
+            return if self.params().eq(other.params()) { Overlap::Identical } else { Overlap::ParamNames };
+        }
+
+        let mut example = Vec::new();
+        let mut winner = None;
+        for i in 0.. {
+            let (first, second) = match (self.segments.get(i), other.segments.get(i)) {
+                (None, None) => break,
+                (Some(first), Some(second)) => (first, second),
+                // A catch-all needs at least one segment to match
+                _ => return Overlap::Disjoint,
+            };
+            if winner.is_none() && first.rank() != second.rank() {
+                winner = Some(if first.rank() < second.rank() { Side::First } else { Side::Second });
+            }
+            match (first, second) {
+                (PathSegment::Static(a), PathSegment::Static(b)) if a != b => return Overlap::Disjoint,
+                // The catch-all swallows whatever the other pattern has left
+                (PathSegment::CatchAll(_), _) => {
+                    example.extend(other.segments[i..].iter().map(|rest| sample(rest, first)));
+                    break;
+                }
+                (_, PathSegment::CatchAll(_)) => {
+                    example.extend(self.segments[i..].iter().map(|rest| sample(rest, second)));
+                    break;
+                }
+                _ => example.push(sample(first, second)),
+            }
+        }
+
+        Overlap::Shadowed {
+            winner: winner.unwrap_or(Side::First),
+            example: format!("/{}", example.join("/")),
+        }
+    }
+}
+
+/// A concrete segment both patterns accept
+fn sample(first: &PathSegment, second: &PathSegment) -> String {
+    match (first, second) {
+        (PathSegment::Static(value), _) | (_, PathSegment::Static(value)) => value.clone(),
+        (PathSegment::Param(name, _), _) | (_, PathSegment::Param(name, _)) => format!("<{}>", name),
+        (PathSegment::CatchAll(name), _) => format!("<{}...>", name),
+    }
+}
+
+impl fmt::Display for RoutePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.segments.is_empty() {
+            return write!(f, "/");
+        }
+        for segment in &self.segments {
+            match segment {
+                PathSegment::Static(value) => write!(f, "/{}", value)?,
+                PathSegment::Param(name, ParamType::String) => write!(f, "/{{{}}}", name)?,
+                PathSegment::Param(name, kind) => write!(f, "/{{{}:{}}}", name, kind.name())?,
+                PathSegment::CatchAll(name) => write!(f, "/{{*{}}}", name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Replace the `{param}` placeholders of `template`, such as a topic or key
+/// name, by the path parameters in `params`
+pub fn fill(template: &str, params: &HashMap<String, Value>) -> Result<String, String> {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..].find('}').ok_or_else(|| format!("unclosed `{{` in '{}'", template))? + start;
+        let name = &rest[start + 1..end];
+        let value = params.get(name).ok_or_else(|| format!("'{}' uses {{{}}}, which is not a path parameter", template, name))?;
+        filled.push_str(&rest[..start]);
+        match value {
+            Value::String(text) => filled.push_str(text),
+            other => filled.push_str(&other.to_string()),
+        }
+        rest = &rest[end + 1..];
+    }
+    filled.push_str(rest);
+    Ok(filled)
+}
+
+/// Two endpoints whose routes interfere
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteConflict {
+    pub first: String,
+    pub first_path: String,
+    pub second: String,
+    pub second_path: String,
+    /// Methods both endpoints serve
+    pub methods: Vec<String>,
+    pub overlap: Overlap,
+}
+
+impl RouteConflict {
+    /// Whether the router refuses to register both routes
+    pub fn is_fatal(&self) -> bool {
+        matches!(self.overlap, Overlap::Identical | Overlap::ParamNames)
+    }
+
+    /// The endpoint the conflict is about: the one that loses requests, or
+    /// the later of two that cannot coexist
+    pub fn subject(&self) -> &str {
+        match self.overlap {
+            Overlap::Shadowed { winner: Side::Second, .. } => &self.first,
+            _ => &self.second,
+        }
+    }
+}
+
+impl fmt::Display for RouteConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let methods = self.methods.join(",");
+        match self.overlap {
+            Overlap::Disjoint => write!(f, "'{}' and '{}' do not overlap", self.first, self.second),
+            Overlap::Identical => write!(
+                f, "Duplicate route {} {}: declared by '{}' and '{}'",
+                methods, self.first_path, self.first, self.second
+            ),
+            Overlap::ParamNames => write!(
+                f, "Routes '{}' and '{}' differ only in parameter names",
+                self.first_path, self.second_path
+            ),
+            Overlap::Shadowed { winner, ref example } => {
+                let ((winner, winner_path), (loser, loser_path)) = match winner {
+                    Side::First => ((&self.first, &self.first_path), (&self.second, &self.second_path)),
+                    Side::Second => ((&self.second, &self.second_path), (&self.first, &self.first_path)),
+                };
+                write!(
+                    f, "'{}' ({}) is shadowed by '{}' ({}) for requests like {} {}",
+                    loser, loser_path, winner, winner_path, methods, example
+                )
+            }
+        }
+    }
+}
+
+/// Every pair of `(name, path, methods)` routes that collide or shadow each
+/// other, in input order. Pairs only conflict on methods both serve, except
+/// for patterns differing only in parameter names, which the router refuses
+/// whatever the methods.
+pub fn find_conflicts(routes: &[(&str, &str, &[String])]) -> Vec<RouteConflict> {
+    let patterns: Vec<RoutePattern> = routes.iter().map(|(_, path, _)| RoutePattern::parse(path)).collect();
+    let mut conflicts = Vec::new();
+
+    for (i, (first, first_path, first_methods)) in routes.iter().enumerate() {
+        for (j, (second, second_path, second_methods)) in routes.iter().enumerate().skip(i + 1) {
+            let methods: Vec<String> = first_methods.iter()
+                .filter(|m| second_methods.iter().any(|other| other.eq_ignore_ascii_case(m)))
+                .cloned()
+                .collect();
+            let overlap = patterns[i].overlap(&patterns[j]);
+            let conflicting = match overlap {
+                Overlap::Disjoint => false,
+                Overlap::ParamNames => true,
+                Overlap::Identical | Overlap::Shadowed { .. } => !methods.is_empty(),
+            };
+            if conflicting {
+                conflicts.push(RouteConflict {
+                    first: first.to_string(),
+                    first_path: first_path.to_string(),
+                    second: second.to_string(),
+                    second_path: second_path.to_string(),
+                    methods,
+                    overlap,
+                });
+            }
+        }
+    }
+
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overlap(first: &str, second: &str) -> Overlap {
+        RoutePattern::parse(first).overlap(&RoutePattern::parse(second))
+    }
+
+    #[test]
+    fn test_parameter_syntaxes_are_equivalent() {
+        let braces = RoutePattern::parse("/users/{id}/files/{*path}");
+        let colons = RoutePattern::parse("/users/:id/files/*path");
+        assert_eq!(braces, colons);
+        assert_eq!(braces.shape(), "/users/{}/files/{*}");
+        assert_eq!(braces.params().collect::<Vec<_>>(), vec!["id", "path"]);
+        assert_eq!(colons.to_string(), "/users/{id}/files/{*path}");
+        assert!(braces.matches("/users/7/files/a/b.txt"));
+        assert!(!braces.matches("/users/7"));
+        assert!(RoutePattern::parse("/users/{id}").matches("/users/7/"));
+        assert!(!RoutePattern::parse("/users/{id}").matches("/users/7/files"));
+    }
+
+    #[test]
+    fn test_templates_are_filled_with_path_parameters() {
+        let params = HashMap::from([("room".to_string(), json!("lobby")), ("page".to_string(), json!(2))]);
+        assert_eq!(fill("chat:{room}:{page}", &params).unwrap(), "chat:lobby:2");
+        assert_eq!(fill("chat", &HashMap::new()).unwrap(), "chat");
+        assert!(fill("chat:{room", &HashMap::new()).unwrap_err().contains("unclosed"));
+        assert!(fill("chat:{user}", &params).unwrap_err().contains("not a path parameter"));
+    }
+
+    #[test]
+    fn test_typed_parameters() {
+        let pattern = RoutePattern::try_parse("/users/{id:int}/files/{rest:*}").unwrap();
+        assert_eq!(pattern, RoutePattern::parse("/users/{id:int}/files/*rest"));
+        assert_eq!(pattern.router_path(), "/users/:id/files/*rest");
+        assert_eq!(pattern.openapi_path(), "/users/{id}/files/{rest}");
+        assert_eq!(pattern.to_string(), "/users/{id:int}/files/{*rest}");
+        assert_eq!(pattern.openapi_parameters()[0]["schema"], json!({ "type": "integer", "format": "int64" }));
+        // Types do not change what the router can tell apart
+        assert_eq!(pattern.overlap(&RoutePattern::parse("/users/{id}/files/{*rest}")), Overlap::Identical);
+
+        let raw = |id: &str| HashMap::from([("id".to_string(), id.to_string()), ("rest".to_string(), "a/b.txt".to_string())]);
+        let params = pattern.extract(raw("42")).unwrap();
+        assert_eq!(params["id"], json!(42));
+        assert_eq!(params["rest"], json!("a/b.txt"));
+        assert_eq!(pattern.extract(raw("4x")).unwrap_err(), "Path parameter 'id' must be an integer, got '4x'");
+
+        assert!(RoutePattern::try_parse("/users/{id:long}").unwrap_err().contains("unknown type 'long'"));
+        assert!(RoutePattern::try_parse("/files/{*rest}/meta").unwrap_err().contains("must be the last segment"));
+    }
+
+    #[test]
+    fn test_identical_and_renamed_patterns() {
+        assert_eq!(overlap("/users/{id}", "/users/:id/"), Overlap::Identical);
+        assert_eq!(overlap("/users/{id}", "/users/{user_id}"), Overlap::ParamNames);
+        assert_eq!(overlap("/users/{id}", "/orders/{id}"), Overlap::Disjoint);
+        assert_eq!(overlap("/users", "/users/{id}"), Overlap::Disjoint);
+    }
+
+    #[test]
+    fn test_static_segments_shadow_parameters() {
+        assert_eq!(overlap("/users/{id}", "/users/me"), Overlap::Shadowed {
+            winner: Side::Second,
+            example: "/users/me".to_string(),
+        });
+        assert_eq!(overlap("/a/{x}/c", "/a/b/{y}"), Overlap::Shadowed {
+            winner: Side::Second,
+            example: "/a/b/c".to_string(),
+        });
+        assert_eq!(overlap("/files/{*path}", "/files/{id}/meta"), Overlap::Shadowed {
+            winner: Side::Second,
+            example: "/files/<id>/meta".to_string(),
+        });
+        assert_eq!(overlap("/files/{*path}", "/files"), Overlap::Disjoint);
+    }
+
+    #[test]
+    fn test_find_conflicts_needs_shared_methods() {
+        let get = vec!["GET".to_string()];
+        let post = vec!["post".to_string()];
+        let routes: Vec<(&str, &str, &[String])> = vec![
+            ("user", "/users/{id}", &get),
+            ("me", "/users/me", &get),
+            ("create", "/users/me", &post),
+            ("renamed", "/users/:user_id", &post),
+        ];
+
+        let conflicts = find_conflicts(&routes);
+        // `me` and `create` share a path but no method
+        assert_eq!(conflicts.len(), 3);
+        assert_eq!(conflicts[0].subject(), "user");
+        assert!(!conflicts[0].is_fatal());
+        assert_eq!(conflicts[0].to_string(), "'user' (/users/{id}) is shadowed by 'me' (/users/me) for requests like GET /users/me");
+        assert_eq!(conflicts[1].subject(), "renamed");
+        assert!(conflicts[1].is_fatal());
+        assert_eq!((conflicts[2].first.as_str(), conflicts[2].subject()), ("create", "renamed"));
+    }
+}
@@ -10,17 +10,11 @@ use tokio::sync::RwLock;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Default)]
 pub struct RuntimeManagerConfig {
     pub handlers: HashMap<String, HandlerConfig>,
 }
 
-impl Default for RuntimeManagerConfig {
-    fn default() -> Self {
-        Self {
-            handlers: HashMap::new(),
-        }
-    }
-}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HandlerInstance {
@@ -225,11 +219,11 @@ impl RuntimeManager {
         // Determine if this is a file path or inline code
         let actual_handler_code = if handler_code.starts_with("./") || handler_code.starts_with("../") || handler_code.ends_with(".js") {
             // This is a file path, read the file content
-            let file_path = if handler_code.starts_with("./") {
+            let file_path = if let Some(relative) = handler_code.strip_prefix("./") {
                 // Convert relative path to absolute path from current working directory
                 std::env::current_dir()
                     .map_err(|e| BackworksError::runtime(format!("Failed to get current directory: {}", e)))?
-                    .join(&handler_code[2..]) // Remove "./" prefix
+                    .join(relative)
             } else {
                 std::path::PathBuf::from(handler_code)
             };
@@ -246,16 +240,52 @@ impl RuntimeManager {
 // Parse request data
 const request = JSON.parse(process.argv[2] || '{{}}');
 
+// Handler context: ctx.error(code, params) references the blueprint error catalog,
+// ctx.email.send(message) queues an email for the email plugin,
+// ctx.jobs.enqueue(name, payload, options) queues a job for the jobs plugin
+const emails = [];
+const jobs = [];
+const ctx = {{
+    error: (code, params) => ({{ "$error": {{ code, params: params || {{}} }} }}),
+    email: {{ send: (message) => {{ emails.push(message); }} }},
+    jobs: {{ enqueue: (name, payload, options) => {{ jobs.push(Object.assign({{}}, options || {{}}, {{ name, payload: payload === undefined ? null : payload }})); }} }}
+}};
+
+// With sessions on, changes to req.session are saved; setting it to null ends the session
+const sessionBefore = JSON.stringify(request.session);
+
+// Queued emails and jobs and session changes travel with the response, which
+// becomes structured if it is not
+function withQueued(result) {{
+    const queued = {{}};
+    if (emails.length > 0) {{ queued.emails = emails; }}
+    if (jobs.length > 0) {{ queued.jobs = jobs; }}
+    if (sessionBefore !== undefined && JSON.stringify(request.session) !== sessionBefore) {{
+        queued.session = request.session === undefined ? null : request.session;
+    }}
+    if (Object.keys(queued).length === 0 || (result && result["$error"])) {{
+        return result;
+    }}
+    if (result && typeof result === "object" && "status" in result && "body" in result) {{
+        return Object.assign({{}}, result, queued);
+    }}
+    return Object.assign({{ status: 200, body: result === undefined ? null : result }}, queued);
+}}
+
 // Handler code
 {}
 
 // Execute handler and output result
 try {{
-    const result = handler(request);
-    console.log(JSON.stringify(result));
+    const result = handler(request, ctx);
+    console.log(JSON.stringify(withQueued(result)));
 }} catch (error) {{
-    console.error('Handler error:', error.message);
-    process.exit(1);
+    if (error && error["$error"]) {{
+        console.log(JSON.stringify(error));
+    }} else {{
+        console.error('Handler error:', error.message);
+        process.exit(1);
+    }}
 }}
 "#, actual_handler_code);
 
@@ -270,6 +300,7 @@ try {{
             .arg(request_data)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
+            .kill_on_drop(true)
             .spawn()
             .map_err(|e| BackworksError::runtime(format!("Failed to spawn Node.js process: {}", e)))?
             .wait_with_output()
@@ -300,6 +331,7 @@ try {{
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
+            .kill_on_drop(true)
             .spawn()
             .map_err(|e| BackworksError::runtime(format!("Failed to spawn Python process: {}", e)))?;
         
@@ -330,7 +362,7 @@ try {{
     
     async fn validate_handler(&self, config: &HandlerConfig) -> BackworksResult<()> {
         // Check if script file exists
-        if !tokio::fs::metadata(&config.script).await.is_ok() {
+        if tokio::fs::metadata(&config.script).await.is_err() {
             return Err(BackworksError::Config(format!("Handler script not found: {}", config.script)));
         }
         
@@ -368,6 +400,7 @@ try {{
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
+            .kill_on_drop(true)
             .spawn()
             .map_err(|e| BackworksError::Runtime(format!("Failed to spawn Node.js process: {}", e)))?;
         
@@ -415,4 +448,90 @@ mod tests {
         
         assert!(runtime_manager.start().await.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_javascript_handlers_queue_emails_and_jobs_with_the_response() {
+        if Command::new("node").arg("--version").output().await.is_err() {
+            return;
+        }
+        let runtime_manager = RuntimeManager::new(RuntimeManagerConfig::default());
+        let run = |handler: &str| RuntimeConfig {
+            language: "javascript".to_string(),
+            handler: handler.to_string(),
+            timeout: None,
+            memory_limit: None,
+            environment: None,
+            requirements: None,
+            working_dir: None,
+        };
+        let output = |text: String| serde_json::from_str::<serde_json::Value>(&text).unwrap();
+
+        let welcome = run(r#"function handler(req, ctx) {
+            ctx.email.send({ to: req.body.email, template: "welcome" });
+            return { status: 201, body: { ok: true } };
+        }"#);
+        let result = runtime_manager.handle_request(&welcome, r#"{"body":{"email":"ada@example.com"}}"#).await.unwrap();
+        assert_eq!(output(result), serde_json::json!({
+            "status": 201, "body": { "ok": true },
+            "emails": [{ "to": "ada@example.com", "template": "welcome" }],
+        }));
+
+        // A plain result becomes the body of a structured one
+        let plain = run(r#"function handler(req, ctx) { ctx.email.send({ to: "ops@example.com" }); return [1]; }"#);
+        let result = runtime_manager.handle_request(&plain, "{}").await.unwrap();
+        assert_eq!(output(result), serde_json::json!({ "status": 200, "body": [1], "emails": [{ "to": "ops@example.com" }] }));
+
+        // Jobs are queued the same way, with their options
+        let signup = run(r#"function handler(req, ctx) {
+            ctx.jobs.enqueue("send_welcome", { id: 7 }, { delay: "1m" });
+            ctx.jobs.enqueue("refresh");
+            return { status: 201, body: null };
+        }"#);
+        let result = runtime_manager.handle_request(&signup, "{}").await.unwrap();
+        assert_eq!(output(result), serde_json::json!({
+            "status": 201, "body": null,
+            "jobs": [{ "name": "send_welcome", "payload": { "id": 7 }, "delay": "1m" }, { "name": "refresh", "payload": null }],
+        }));
+
+        // Nothing changes without emails, and errors send none
+        let quiet = run(r#"function handler(req, ctx) { return [1]; }"#);
+        assert_eq!(output(runtime_manager.handle_request(&quiet, "{}").await.unwrap()), serde_json::json!([1]));
+        let failing = run(r#"function handler(req, ctx) { ctx.email.send({ to: "ops@example.com" }); return ctx.error("GONE"); }"#);
+        let result = runtime_manager.handle_request(&failing, "{}").await.unwrap();
+        assert_eq!(output(result), serde_json::json!({ "$error": { "code": "GONE", "params": {} } }));
+    }
+
+    #[tokio::test]
+    async fn test_javascript_handlers_send_session_changes_with_the_response() {
+        if Command::new("node").arg("--version").output().await.is_err() {
+            return;
+        }
+        let runtime_manager = RuntimeManager::new(RuntimeManagerConfig::default());
+        let run = |handler: &str| RuntimeConfig {
+            language: "javascript".to_string(),
+            handler: handler.to_string(),
+            timeout: None,
+            memory_limit: None,
+            environment: None,
+            requirements: None,
+            working_dir: None,
+        };
+        let output = |text: String| serde_json::from_str::<serde_json::Value>(&text).unwrap();
+
+        let login = run(r#"function handler(req) {
+            req.session.user = req.body.user;
+            return { status: 204, body: null };
+        }"#);
+        let result = runtime_manager.handle_request(&login, r#"{"body":{"user":"ada"},"session":{"visits":1}}"#).await.unwrap();
+        assert_eq!(output(result), serde_json::json!({ "status": 204, "body": null, "session": { "visits": 1, "user": "ada" } }));
+
+        let logout = run(r#"function handler(req) { req.session = null; return { ok: true }; }"#);
+        let result = runtime_manager.handle_request(&logout, r#"{"session":{"user":"ada"}}"#).await.unwrap();
+        assert_eq!(output(result), serde_json::json!({ "status": 200, "body": { "ok": true }, "session": null }));
+
+        // Reading the session, or writing one without sessions on, changes nothing
+        let whoami = run(r#"function handler(req) { req.session = req.session || {}; return { user: req.session.user }; }"#);
+        assert_eq!(output(runtime_manager.handle_request(&whoami, r#"{"session":{"user":"ada"}}"#).await.unwrap()), serde_json::json!({ "user": "ada" }));
+        assert_eq!(output(runtime_manager.handle_request(&whoami, "{}").await.unwrap()), serde_json::json!({}));
+    }
 }
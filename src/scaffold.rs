@@ -0,0 +1,307 @@
+//! Project templates from git repositories
+//!
+//! `backworks init shop --template git:https://github.com/acme/backworks-starter`
+//! clones a repository, at its default branch or the branch or tag after
+//! `#`, and copies its files into the new project. Placeholders of the form
+//! `{{ variable }}` in file contents and paths are replaced; `project_name`
+//! is always set, and a `backworks-template.yaml` at the repository root
+//! declares the others:
+//!
+//! ```yaml
+//! description: Orders service with a Postgres database
+//! variables:
+//!   port: { description: Port the API listens on, default: "8080" }
+//!   team: { description: Owning team }       # required, no default
+//! exclude: ["docs/**", "*.png"]
+//! ```
+//!
+//! Values are given with `--var name=value`. Placeholders naming no
+//! variable are left alone, so Handlebars templates in blueprints survive;
+//! files that are not UTF-8 are copied unchanged. The manifest and `.git`
+//! are not copied.
+
+use crate::error::{BackworksError, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Prefix of `--template` values naming a repository
+pub const GIT_PREFIX: &str = "git:";
+/// Manifest at the root of a template repository
+pub const MANIFEST: &str = "backworks-template.yaml";
+/// Variable every template gets, set to the project name
+pub const PROJECT_NAME: &str = "project_name";
+
+/// A template repository, with the branch or tag to use
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateSource {
+    pub repository: String,
+    pub reference: Option<String>,
+}
+
+impl TemplateSource {
+    /// The repository `template` names as `git:<url>[#<ref>]`, or `None`
+    /// for built-in templates
+    pub fn parse(template: &str) -> Option<Result<Self>> {
+        let source = template.strip_prefix(GIT_PREFIX)?;
+        let (repository, reference) = match source.rsplit_once('#') {
+            Some((repository, reference)) => (repository, Some(reference.to_string())),
+            None => (source, None),
+        };
+        if repository.is_empty() || reference.as_deref() == Some("") {
+            return Some(Err(BackworksError::config(format!(
+                "Invalid template '{}' (expected git:<url> or git:<url>#<branch or tag>)", template
+            ))));
+        }
+        Some(Ok(Self { repository: repository.to_string(), reference }))
+    }
+}
+
+/// `backworks-template.yaml`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateManifest {
+    pub description: Option<String>,
+    #[serde(default)]
+    pub variables: BTreeMap<String, TemplateVariable>,
+    /// Globs of files, relative to the root, not to copy
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateVariable {
+    pub description: Option<String>,
+    /// Value when none is given; without one the variable is required
+    pub default: Option<String>,
+}
+
+/// A template checked out on disk
+#[derive(Debug)]
+pub struct ProjectTemplate {
+    root: PathBuf,
+    pub manifest: TemplateManifest,
+    /// Clone to remove once the template is dropped
+    clone: Option<PathBuf>,
+}
+
+impl ProjectTemplate {
+    /// Clone `source` into a temporary directory
+    pub fn fetch(source: &TemplateSource) -> Result<Self> {
+        let clone = std::env::temp_dir().join(format!("backworks_template_{}", uuid::Uuid::new_v4()));
+        let mut git = Command::new("git");
+        git.args(["clone", "--depth", "1", "--quiet"]);
+        if let Some(ref reference) = source.reference {
+            git.args(["--branch", reference]);
+        }
+        let output = git.arg(&source.repository).arg(&clone).output()
+            .map_err(|e| BackworksError::config(format!("Cannot run git to fetch template {}: {}", source.repository, e)))?;
+        if !output.status.success() {
+            let _ = std::fs::remove_dir_all(&clone);
+            return Err(BackworksError::config(format!(
+                "Fetching template {} failed: {}", source.repository, String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        let mut template = Self::open(&clone)?;
+        template.clone = Some(clone);
+        Ok(template)
+    }
+
+    /// The template in directory `root`
+    pub fn open(root: &Path) -> Result<Self> {
+        let manifest = match std::fs::read_to_string(root.join(MANIFEST)) {
+            Ok(text) => serde_yaml::from_str(&text)
+                .map_err(|e| BackworksError::config(format!("Invalid {}: {}", MANIFEST, e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => TemplateManifest::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { root: root.to_path_buf(), manifest, clone: None })
+    }
+
+    /// Every variable's value for project `name`, from `given` and the
+    /// defaults
+    pub fn variables(&self, name: &str, given: &[(String, String)]) -> Result<BTreeMap<String, String>> {
+        let mut values = BTreeMap::from([(PROJECT_NAME.to_string(), name.to_string())]);
+        for (variable, value) in given {
+            if !self.manifest.variables.contains_key(variable) {
+                let declared: Vec<&str> = self.manifest.variables.keys().map(String::as_str).collect();
+                return Err(BackworksError::config(format!(
+                    "The template declares no variable '{}' (declared: {})",
+                    variable, if declared.is_empty() { "none".to_string() } else { declared.join(", ") }
+                )));
+            }
+            values.insert(variable.clone(), value.clone());
+        }
+        let mut missing = Vec::new();
+        for (variable, declaration) in &self.manifest.variables {
+            if values.contains_key(variable) {
+                continue;
+            }
+            match declaration.default {
+                Some(ref default) => {
+                    values.insert(variable.clone(), default.clone());
+                }
+                None => missing.push(match declaration.description {
+                    Some(ref description) => format!("{} ({})", variable, description),
+                    None => variable.clone(),
+                }),
+            }
+        }
+        if !missing.is_empty() {
+            return Err(BackworksError::config(format!(
+                "The template needs values for {}; pass them with --var name=value", missing.join(", ")
+            )));
+        }
+        Ok(values)
+    }
+
+    /// Copy the template into `output` with `variables` filled in, and
+    /// return the paths written, relative to `output`
+    pub fn render(&self, output: &Path, variables: &BTreeMap<String, String>) -> Result<Vec<String>> {
+        let exclude = self.manifest.exclude.iter()
+            .map(|pattern| glob::Pattern::new(pattern)
+                .map_err(|e| BackworksError::config(format!("Invalid exclude pattern '{}' in {}: {}", pattern, MANIFEST, e))))
+            .collect::<Result<Vec<_>>>()?;
+        let mut files = Vec::new();
+        collect(&self.root, &self.root, &mut files)?;
+
+        let mut written = Vec::new();
+        for (relative, source) in files {
+            if relative == MANIFEST || exclude.iter().any(|pattern| pattern.matches(&relative)) {
+                continue;
+            }
+            let target = substitute(&relative, variables);
+            if crate::package::package_path(Path::new(&target)).as_deref() != Some(target.as_str()) {
+                return Err(BackworksError::config(format!("Template file {} would be written outside the project as {}", relative, target)));
+            }
+            let path = output.join(&target);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let data = std::fs::read(&source)?;
+            match String::from_utf8(data) {
+                Ok(text) => std::fs::write(&path, substitute(&text, variables))?,
+                Err(binary) => std::fs::write(&path, binary.into_bytes())?,
+            }
+            written.push(target);
+        }
+        written.sort();
+        Ok(written)
+    }
+}
+
+impl Drop for ProjectTemplate {
+    fn drop(&mut self) {
+        if let Some(ref clone) = self.clone {
+            let _ = std::fs::remove_dir_all(clone);
+        }
+    }
+}
+
+/// Files under `dir`, by their `/`-separated path relative to `root`
+fn collect(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if entry.file_name() != ".git" {
+                collect(root, &path, files)?;
+            }
+        } else if file_type.is_file() {
+            if let Some(relative) = path.strip_prefix(root).ok().and_then(crate::package::package_path) {
+                files.push((relative, path));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `text` with `{{ variable }}` placeholders of `variables` replaced
+fn substitute(text: &str, variables: &BTreeMap<String, String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(length) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let inner = &rest[start + 2..start + 2 + length];
+        out.push_str(&rest[..start]);
+        match variables.get(inner.trim()) {
+            Some(value) => out.push_str(value),
+            None => out.push_str(&rest[start..start + 4 + length]),
+        }
+        rest = &rest[start + 4 + length..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sources_are_git_urls_with_an_optional_reference() {
+        assert!(TemplateSource::parse("api").is_none());
+        let source = TemplateSource::parse("git:https://github.com/acme/starter.git#v2").unwrap().unwrap();
+        assert_eq!(source, TemplateSource { repository: "https://github.com/acme/starter.git".to_string(), reference: Some("v2".to_string()) });
+        assert_eq!(TemplateSource::parse("git:../starter").unwrap().unwrap().reference, None);
+        assert!(TemplateSource::parse("git:").unwrap().is_err());
+    }
+
+    #[test]
+    fn test_repositories_render_with_variables() {
+        let root = std::env::temp_dir().join(format!("backworks_scaffold_{}", uuid::Uuid::new_v4()));
+        let repository = root.join("starter");
+        std::fs::create_dir_all(repository.join("handlers")).unwrap();
+        std::fs::create_dir_all(repository.join("docs")).unwrap();
+        std::fs::write(repository.join(MANIFEST), r#"
+variables:
+  port: { default: "8080" }
+  team: { description: Owning team }
+exclude: ["docs/**"]
+"#).unwrap();
+        std::fs::write(repository.join("backworks.yaml"), "name: {{ project_name }}\nserver: { port: {{port}} }\nendpoints:\n  hi: { template: { response: \"{{request.path}}\" } }\n").unwrap();
+        std::fs::write(repository.join("handlers/{{team}}.js"), "// owned by {{ team }}").unwrap();
+        std::fs::write(repository.join("docs/template.md"), "How to use the template").unwrap();
+        std::fs::write(repository.join("logo.bin"), [0xff, 0xfe, b'{', b'{']).unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git").current_dir(&repository)
+                .args(["-c", "user.name=Backworks", "-c", "user.email=dev@example.com"]).args(args)
+                .output().unwrap().status;
+            assert!(status.success(), "git {:?}", args);
+        };
+        git(&["init", "--quiet"]);
+        git(&["add", "-A"]);
+        git(&["commit", "--quiet", "-m", "Starter"]);
+
+        let source = TemplateSource::parse(&format!("git:{}", repository.display())).unwrap().unwrap();
+        let template = ProjectTemplate::fetch(&source).unwrap();
+        let err = template.variables("shop", &[]).unwrap_err();
+        assert!(err.to_string().contains("needs values for team (Owning team)"), "{}", err);
+        let err = template.variables("shop", &[("colour".to_string(), "red".to_string())]).unwrap_err();
+        assert!(err.to_string().contains("declares no variable 'colour' (declared: port, team)"), "{}", err);
+
+        let variables = template.variables("shop", &[("team".to_string(), "payments".to_string())]).unwrap();
+        let output = root.join("shop");
+        let written = template.render(&output, &variables).unwrap();
+        assert_eq!(written, vec!["backworks.yaml", "handlers/payments.js", "logo.bin"]);
+        assert_eq!(
+            std::fs::read_to_string(output.join("backworks.yaml")).unwrap(),
+            "name: shop\nserver: { port: 8080 }\nendpoints:\n  hi: { template: { response: \"{{request.path}}\" } }\n"
+        );
+        assert_eq!(std::fs::read_to_string(output.join("handlers/payments.js")).unwrap(), "// owned by payments");
+        assert_eq!(std::fs::read(output.join("logo.bin")).unwrap(), [0xff, 0xfe, b'{', b'{']);
+
+        // The clone goes away with the template
+        let clone = template.clone.clone().unwrap();
+        drop(template);
+        assert!(!clone.exists());
+        let missing = TemplateSource { repository: root.join("missing").display().to_string(), reference: None };
+        assert!(ProjectTemplate::fetch(&missing).unwrap_err().to_string().contains("Fetching template"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
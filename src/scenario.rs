@@ -0,0 +1,255 @@
+//! Scenarios
+//!
+//! An endpoint's `scenarios` map names the responses it serves instead of
+//! its handler while that scenario is active, such as a declined payment or
+//! a gateway that fails twice before recovering. One scenario is active at a
+//! time, chosen by `scenarios.active` and switched at runtime through
+//! `/_backworks/scenarios` or the dashboard; a request can pick its own with
+//! the `x-backworks-scenario` header. Endpoints without responses for the
+//! scenario behave normally.
+//!
+//! A `sequence` serves its responses one per request, then repeats the last
+//! one or, with `cycle`, starts over. Activating a scenario restarts its
+//! sequences.
+
+use crate::config::{BackworksConfig, ScenarioConfig, ScenarioResponse};
+use crate::error::{BackworksError, Result};
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+
+const DEFAULT_SCENARIO_HEADER: &str = "x-backworks-scenario";
+
+#[derive(Debug)]
+struct Scenario {
+    responses: Vec<ScenarioResponse>,
+    cycle: bool,
+}
+
+/// Scenario responses of every endpoint and the active scenario
+#[derive(Debug, Clone, Default)]
+pub struct Scenarios {
+    /// Endpoint responses by scenario, then endpoint
+    scenarios: Arc<BTreeMap<String, BTreeMap<String, Scenario>>>,
+    header: Option<Arc<str>>,
+    active: Arc<RwLock<Option<String>>>,
+    /// Next response of each (scenario, endpoint) sequence
+    positions: Arc<Mutex<HashMap<(String, String), usize>>>,
+}
+
+/// The active scenario and the endpoints each scenario changes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScenarioStatus {
+    pub active: Option<String>,
+    pub scenarios: BTreeMap<String, Vec<String>>,
+}
+
+/// Body of a request switching the active scenario; `null` deactivates it
+#[derive(Debug, Deserialize)]
+pub struct ScenarioSwitch {
+    pub scenario: Option<String>,
+}
+
+impl Scenarios {
+    /// Collect the `scenarios` of every endpoint
+    pub fn new(config: &BackworksConfig) -> Result<Self> {
+        let mut scenarios: BTreeMap<String, BTreeMap<String, Scenario>> = BTreeMap::new();
+        for (endpoint, endpoint_config) in &config.endpoints {
+            for (name, scenario) in &endpoint_config.scenarios {
+                scenarios.entry(name.clone()).or_default()
+                    .insert(endpoint.clone(), compile(endpoint, name, scenario)?);
+            }
+        }
+
+        let settings = config.scenarios.clone().unwrap_or_default();
+        if let Some(ref active) = settings.active {
+            if !scenarios.contains_key(active) {
+                return Err(BackworksError::config(format!("Active scenario '{}' is not defined by any endpoint", active)));
+            }
+        }
+        Ok(Self {
+            scenarios: Arc::new(scenarios),
+            header: settings.header.map(Arc::from),
+            active: Arc::new(RwLock::new(settings.active)),
+            positions: Default::default(),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scenarios.is_empty()
+    }
+
+    /// The endpoint's response under the request's scenario as handler
+    /// output, or `None` when its handler should run
+    pub async fn respond(&self, endpoint: &str, headers: &HeaderMap) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+        let header = self.header.as_deref().unwrap_or(DEFAULT_SCENARIO_HEADER);
+        let name = match headers.get(header).and_then(|value| value.to_str().ok()).filter(|name| !name.is_empty()) {
+            Some(name) => name.to_string(),
+            None => self.active.read().await.clone()?,
+        };
+        let scenario = self.scenarios.get(&name)?.get(endpoint)?;
+
+        let response = if scenario.responses.len() == 1 {
+            &scenario.responses[0]
+        } else {
+            let mut positions = self.positions.lock().await;
+            let position = positions.entry((name, endpoint.to_string())).or_default();
+            let index = *position;
+            *position = if scenario.cycle { (index + 1) % scenario.responses.len() } else { (index + 1).min(scenario.responses.len() - 1) };
+            &scenario.responses[index]
+        };
+
+        if let Some(delay) = response.delay_ms {
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+        }
+        if response.passthrough {
+            return None;
+        }
+        Some(json!({
+            "status": response.status.unwrap_or(200),
+            "body": response.body.clone().unwrap_or(Value::Null),
+        }).to_string())
+    }
+
+    pub async fn status(&self) -> ScenarioStatus {
+        ScenarioStatus {
+            active: self.active.read().await.clone(),
+            scenarios: self.scenarios.iter()
+                .map(|(name, endpoints)| (name.clone(), endpoints.keys().cloned().collect()))
+                .collect(),
+        }
+    }
+
+    /// Make `name` the active scenario, restarting its sequences
+    pub async fn activate(&self, name: &str) -> std::result::Result<ScenarioStatus, String> {
+        if !self.scenarios.contains_key(name) {
+            return Err(format!("Unknown scenario '{}'", name));
+        }
+        self.positions.lock().await.retain(|(scenario, _), _| scenario != name);
+        *self.active.write().await = Some(name.to_string());
+        Ok(self.status().await)
+    }
+
+    /// Return every endpoint to its handler
+    pub async fn deactivate(&self) -> ScenarioStatus {
+        *self.active.write().await = None;
+        self.status().await
+    }
+
+    /// Apply a switch request
+    pub async fn switch(&self, switch: ScenarioSwitch) -> std::result::Result<ScenarioStatus, String> {
+        match switch.scenario {
+            Some(ref name) => self.activate(name).await,
+            None => Ok(self.deactivate().await),
+        }
+    }
+}
+
+fn compile(endpoint: &str, name: &str, scenario: &ScenarioConfig) -> Result<Scenario> {
+    let invalid = |message: String| BackworksError::config(format!("Endpoint '{}' scenario '{}': {}", endpoint, name, message));
+    let single = &scenario.response;
+    let responses = if scenario.sequence.is_empty() {
+        vec![single.clone()]
+    } else if single.status.is_some() || single.body.is_some() || single.delay_ms.is_some() || single.passthrough {
+        return Err(invalid("give either a response or a `sequence`, not both".to_string()));
+    } else {
+        scenario.sequence.clone()
+    };
+    for response in &responses {
+        if let Some(status) = response.status.filter(|status| !(100..=599).contains(status)) {
+            return Err(invalid(format!("{} is not an HTTP status code", status)));
+        }
+        if response.passthrough && (response.status.is_some() || response.body.is_some()) {
+            return Err(invalid("a `passthrough` response cannot have a status or body".to_string()));
+        }
+    }
+    Ok(Scenario { responses, cycle: scenario.cycle })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenarios(yaml: &str) -> Result<Scenarios> {
+        Scenarios::new(&serde_yaml::from_str(yaml).unwrap())
+    }
+
+    const BLUEPRINT: &str = r#"
+name: shop
+endpoints:
+  charge:
+    path: /charge
+    methods: [POST]
+    scenarios:
+      payment_declined:
+        status: 402
+        body: { error: card_declined }
+      flaky_gateway:
+        sequence:
+          - { status: 503, delay_ms: 1 }
+          - passthrough: true
+        cycle: true
+  refund:
+    path: /refund
+    methods: [POST]
+    scenarios:
+      payment_declined:
+        sequence:
+          - { status: 500 }
+          - { status: 200, body: { refunded: true } }
+scenarios:
+  active: payment_declined
+"#;
+
+    fn output(value: Option<String>) -> Option<Value> {
+        value.map(|output| serde_json::from_str(&output).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_active_scenario_switches_and_sequences_responses() {
+        let scenarios = scenarios(BLUEPRINT).unwrap();
+        let none = HeaderMap::new();
+        assert_eq!(output(scenarios.respond("charge", &none).await), Some(json!({ "status": 402, "body": { "error": "card_declined" } })));
+
+        // Sequences repeat their last response unless they cycle
+        let mut statuses = Vec::new();
+        for _ in 0..3 {
+            statuses.push(output(scenarios.respond("refund", &none).await).unwrap()["status"].clone());
+        }
+        assert_eq!(statuses, vec![json!(500), json!(200), json!(200)]);
+
+        let status = scenarios.activate("flaky_gateway").await.unwrap();
+        assert_eq!(status.active.as_deref(), Some("flaky_gateway"));
+        assert_eq!(status.scenarios["payment_declined"], vec!["charge", "refund"]);
+        assert_eq!(output(scenarios.respond("charge", &none).await), Some(json!({ "status": 503, "body": null })));
+        assert_eq!(scenarios.respond("charge", &none).await, None);
+        assert!(scenarios.respond("charge", &none).await.is_some());
+        assert_eq!(scenarios.respond("refund", &none).await, None);
+
+        // The request header overrides the active scenario
+        let mut headers = HeaderMap::new();
+        headers.insert(DEFAULT_SCENARIO_HEADER, "payment_declined".parse().unwrap());
+        assert_eq!(output(scenarios.respond("charge", &headers).await).unwrap()["status"], 402);
+
+        assert_eq!(scenarios.activate("missing").await.unwrap_err(), "Unknown scenario 'missing'");
+        scenarios.switch(ScenarioSwitch { scenario: None }).await.unwrap();
+        assert_eq!(scenarios.respond("charge", &none).await, None);
+    }
+
+    #[test]
+    fn test_invalid_scenarios_are_rejected() {
+        let error = scenarios(&BLUEPRINT.replace("active: payment_declined", "active: outage")).unwrap_err();
+        assert_eq!(error.to_string(), "Configuration error: Active scenario 'outage' is not defined by any endpoint");
+        let error = scenarios(&BLUEPRINT.replace("{ status: 500 }", "{ status: 700 }")).unwrap_err();
+        assert!(error.to_string().contains("Endpoint 'refund' scenario 'payment_declined': 700 is not an HTTP status code"));
+        let error = scenarios(&BLUEPRINT.replace("        cycle: true", "        cycle: true\n        status: 200")).unwrap_err();
+        assert!(error.to_string().contains("give either a response or a `sequence`"));
+    }
+}
@@ -0,0 +1,511 @@
+//! Scheduled jobs
+//!
+//! The blueprint's `schedules` run runtime handlers or plugin actions on cron
+//! expressions while the server runs: cleanup tasks, polling upstreams,
+//! warming caches. A schedule due while its previous run is still going
+//! records a skipped run unless it allows overlap. The latest runs of each
+//! schedule are kept for the admin API, which also starts runs on demand:
+//!
+//! - `GET /_backworks/schedules` lists schedules with their next and last run
+//! - `GET /_backworks/schedules/:name/runs` returns a schedule's runs, newest first
+//! - `POST /_backworks/schedules/:name/run` runs a schedule now and returns the run
+
+use crate::config::{parse_duration, BackworksConfig, ScheduleConfig, ScheduleOverlap};
+use crate::error::{BackworksError, Result};
+use crate::server::AppState;
+use chrono::{DateTime, Datelike, Duration as Days, NaiveDate, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, VecDeque};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+const DEFAULT_HISTORY: usize = 20;
+
+/// How far ahead the next run of an expression is looked for
+const LOOKAHEAD_DAYS: i64 = 5 * 366;
+
+/// One field of a cron expression, as the set of values it matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CronField {
+    values: u64,
+    /// Written as `*` or `*/n`, which matters for the day fields
+    any: bool,
+}
+
+impl CronField {
+    fn parse(text: &str, min: u32, max: u32, names: &[&str]) -> std::result::Result<Self, String> {
+        let value = |text: &str| -> std::result::Result<u32, String> {
+            let named = names.iter().position(|name| name.eq_ignore_ascii_case(text)).map(|n| n as u32 + min);
+            let value = named.or_else(|| text.parse().ok()).ok_or_else(|| format!("'{}' is not a value", text))?;
+            if value < min || value > max {
+                return Err(format!("{} is outside {}-{}", value, min, max));
+            }
+            Ok(value)
+        };
+        let mut values = 0u64;
+        for part in text.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => match step.parse::<u32>() {
+                    Ok(step) if step > 0 => (range, step),
+                    _ => return Err(format!("'{}' is not a step", step)),
+                },
+                None => (part, 1),
+            };
+            let (first, last) = match range {
+                "*" => (min, max),
+                _ => match range.split_once('-') {
+                    Some((first, last)) => (value(first)?, value(last)?),
+                    // `5/15` runs from 5 to the end of the range
+                    None if part.contains('/') => (value(range)?, max),
+                    None => (value(range)?, value(range)?),
+                },
+            };
+            if first > last {
+                return Err(format!("'{}' is an empty range", range));
+            }
+            values |= (first..=last).step_by(step as usize).fold(0, |values, value| values | 1 << value);
+        }
+        Ok(Self { values, any: text.starts_with('*') })
+    }
+
+    fn contains(&self, value: u32) -> bool {
+        self.values & (1 << value) != 0
+    }
+}
+
+/// A five-field cron expression: minute, hour, day of month, month and day
+/// of week, with lists, ranges, steps, month and day names, and macros such
+/// as `@daily`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpression {
+    minutes: CronField,
+    hours: CronField,
+    days: CronField,
+    months: CronField,
+    weekdays: CronField,
+}
+
+impl FromStr for CronExpression {
+    type Err = BackworksError;
+
+    fn from_str(expression: &str) -> Result<Self> {
+        let invalid = |reason: String| BackworksError::config(format!("Invalid cron expression '{}': {}", expression, reason));
+        let fields = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            fields if fields.starts_with('@') => return Err(invalid("unknown macro".to_string())),
+            fields => fields,
+        };
+        let fields: Vec<&str> = fields.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(invalid(format!("expected 5 fields, found {}", fields.len())));
+        };
+        const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+        const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+        let mut weekdays = CronField::parse(weekdays, 0, 7, &WEEKDAYS).map_err(invalid)?;
+        // Sunday is both 0 and 7
+        if weekdays.contains(7) {
+            weekdays.values |= 1;
+        }
+        Ok(Self {
+            minutes: CronField::parse(minutes, 0, 59, &[]).map_err(invalid)?,
+            hours: CronField::parse(hours, 0, 23, &[]).map_err(invalid)?,
+            days: CronField::parse(days, 1, 31, &[]).map_err(invalid)?,
+            months: CronField::parse(months, 1, 12, &MONTHS).map_err(invalid)?,
+            weekdays,
+        })
+    }
+}
+
+impl CronExpression {
+    /// When both day fields are restricted a day matching either qualifies,
+    /// as in cron
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day = self.days.contains(date.day());
+        let weekday = self.weekdays.contains(date.weekday().num_days_from_sunday());
+        match (self.days.any, self.weekdays.any) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    /// The first time after `after` the expression matches, read in the
+    /// time zone of `after`; local times a clock change skips never match
+    pub fn next_after<Z: TimeZone>(&self, after: &DateTime<Z>) -> Option<DateTime<Z>> {
+        let zone = after.timezone();
+        let local = after.naive_local();
+        let start = local.date().and_hms_opt(local.hour(), local.minute(), 0)? + Days::minutes(1);
+        let limit = start + Days::days(LOOKAHEAD_DAYS);
+        let mut time = start;
+        while time < limit {
+            let date = time.date();
+            if !self.months.contains(date.month()) {
+                let (year, month) = if date.month() == 12 { (date.year() + 1, 1) } else { (date.year(), date.month() + 1) };
+                time = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+            } else if !self.matches_day(date) {
+                time = date.succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if !self.hours.contains(time.hour()) {
+                time = date.and_hms_opt(time.hour(), 0, 0)? + Days::hours(1);
+            } else if !self.minutes.contains(time.minute()) {
+                time += Days::minutes(1);
+            } else {
+                match zone.from_local_datetime(&time).earliest() {
+                    Some(at) if at > *after => return Some(at),
+                    _ => time += Days::minutes(1),
+                }
+            }
+        }
+        None
+    }
+}
+
+/// What started a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunTrigger {
+    Cron,
+    Manual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Succeeded,
+    Failed,
+    TimedOut,
+    /// The previous run was still going
+    Skipped,
+}
+
+/// One run of a schedule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleRun {
+    pub id: String,
+    pub schedule: String,
+    pub trigger: RunTrigger,
+    pub status: RunStatus,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    /// What the handler or plugin action returned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A schedule as the admin API lists it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleStatus {
+    pub name: String,
+    pub cron: String,
+    pub timezone: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub enabled: bool,
+    pub running: bool,
+    pub next_run: Option<DateTime<Utc>>,
+    pub last_run: Option<ScheduleRun>,
+}
+
+struct Job {
+    name: String,
+    config: ScheduleConfig,
+    cron: CronExpression,
+    timezone: Tz,
+    timeout: Option<Duration>,
+    history_size: usize,
+    running: AtomicUsize,
+    /// Newest last
+    history: Mutex<VecDeque<ScheduleRun>>,
+    next_run: Mutex<Option<DateTime<Utc>>>,
+}
+
+/// Counts a run as going until it finishes or is dropped
+struct Running<'a>(&'a AtomicUsize);
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Job {
+    fn new(name: &str, config: &ScheduleConfig) -> Result<Self> {
+        let invalid = |reason: String| BackworksError::config(format!("Schedule '{}': {}", name, reason));
+        let cron: CronExpression = config.cron.parse().map_err(|e: BackworksError| invalid(e.to_string()))?;
+        let timezone = match config.timezone {
+            Some(ref zone) => zone.parse::<Tz>().map_err(|_| invalid(format!("unknown time zone '{}'", zone)))?,
+            None => Tz::UTC,
+        };
+        match (&config.runtime, &config.plugin, &config.action) {
+            (Some(_), None, None) | (None, Some(_), Some(_)) => {}
+            (None, Some(_), None) => return Err(invalid("`plugin` needs an `action`".to_string())),
+            (None, None, Some(_)) => return Err(invalid("`action` needs a `plugin`".to_string())),
+            (None, None, None) => return Err(invalid("needs a `runtime` handler or a `plugin` action".to_string())),
+            (Some(_), _, _) => return Err(invalid("runs a `runtime` handler or a `plugin` action, not both".to_string())),
+        }
+        let timeout = config.timeout.as_deref()
+            .map(parse_duration)
+            .transpose()
+            .map_err(|e| invalid(format!("timeout: {}", e)))?;
+        if config.history == Some(0) {
+            return Err(invalid("`history` must keep at least one run".to_string()));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            config: config.clone(),
+            cron,
+            timezone,
+            timeout,
+            history_size: config.history.unwrap_or(DEFAULT_HISTORY),
+            running: AtomicUsize::new(0),
+            history: Mutex::new(VecDeque::new()),
+            next_run: Mutex::new(None),
+        })
+    }
+
+    fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.cron.next_after(&after.with_timezone(&self.timezone)).map(|at| at.with_timezone(&Utc))
+    }
+
+    fn next_run(&self) -> Option<DateTime<Utc>> {
+        *self.next_run.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn record(&self, run: ScheduleRun) {
+        let mut history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        history.push_back(run);
+        while history.len() > self.history_size {
+            history.pop_front();
+        }
+    }
+
+    /// Run the handler or plugin action, returning its output
+    async fn execute(&self, state: &AppState, trigger: RunTrigger, started_at: DateTime<Utc>) -> Result<Value> {
+        if let (Some(ref plugin), Some(ref action)) = (&self.config.plugin, &self.config.action) {
+            return state.plugin_manager.run_action(plugin, action, &self.config.params).await;
+        }
+        let Some(ref runtime) = self.config.runtime else {
+            return Err(BackworksError::config(format!("Schedule '{}' runs nothing", self.name)));
+        };
+        let request = json!({
+            "schedule": self.name,
+            "trigger": trigger,
+            "scheduled_at": started_at,
+            "params": self.config.params,
+        });
+        let output = state.runtime_manager.handle_request(runtime, &request.to_string()).await?;
+        let output = serde_json::from_str(output.trim()).unwrap_or_else(|_| Value::String(output.trim().to_string()));
+        match output.get("$error") {
+            Some(error) => Err(BackworksError::runtime(format!("Handler returned error {}", error["code"].as_str().unwrap_or_default()))),
+            None => Ok(output),
+        }
+    }
+}
+
+/// The blueprint's schedules, their run history and which are running
+#[derive(Clone, Default)]
+pub struct Scheduler {
+    jobs: Arc<BTreeMap<String, Arc<Job>>>,
+}
+
+impl Scheduler {
+    pub fn new(config: &BackworksConfig) -> Result<Self> {
+        let jobs = config.schedules.iter()
+            .map(|(name, schedule)| Ok((name.clone(), Arc::new(Job::new(name, schedule)?))))
+            .collect::<Result<_>>()?;
+        Ok(Self { jobs: Arc::new(jobs) })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// Every schedule, sorted by name
+    pub fn statuses(&self) -> Vec<ScheduleStatus> {
+        self.jobs.values()
+            .map(|job| ScheduleStatus {
+                name: job.name.clone(),
+                cron: job.config.cron.clone(),
+                timezone: job.timezone.name().to_string(),
+                description: job.config.description.clone(),
+                enabled: job.config.enabled,
+                running: job.running.load(Ordering::SeqCst) > 0,
+                next_run: if job.config.enabled { job.next_run().or_else(|| job.next_after(Utc::now())) } else { None },
+                last_run: job.history.lock().unwrap_or_else(|e| e.into_inner()).back().cloned(),
+            })
+            .collect()
+    }
+
+    /// The kept runs of the schedule `name`, newest first
+    pub fn runs(&self, name: &str) -> Option<Vec<ScheduleRun>> {
+        let job = self.jobs.get(name)?;
+        let history = job.history.lock().unwrap_or_else(|e| e.into_inner());
+        Some(history.iter().rev().cloned().collect())
+    }
+
+    /// Run the schedule `name` now and record the run; `None` for unknown
+    /// schedules
+    pub async fn run(&self, state: &AppState, name: &str, trigger: RunTrigger) -> Option<ScheduleRun> {
+        let job = self.jobs.get(name)?.clone();
+        let started_at = Utc::now();
+        let started = Instant::now();
+        let finish = |status: RunStatus, output: Option<Value>, error: Option<String>| ScheduleRun {
+            id: uuid::Uuid::new_v4().to_string(),
+            schedule: job.name.clone(),
+            trigger,
+            status,
+            started_at,
+            finished_at: Utc::now(),
+            duration_ms: started.elapsed().as_millis() as u64,
+            output,
+            error,
+        };
+
+        let previous = job.running.fetch_add(1, Ordering::SeqCst);
+        let running = Running(&job.running);
+        let run = if previous > 0 && job.config.overlap == ScheduleOverlap::Skip {
+            drop(running);
+            info!("⏭️  Skipped schedule '{}': its previous run is still going", job.name);
+            finish(RunStatus::Skipped, None, Some("The previous run was still going".to_string()))
+        } else {
+            let execution = job.execute(state, trigger, started_at);
+            let outcome = match job.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, execution).await.ok(),
+                None => Some(execution.await),
+            };
+            drop(running);
+            match outcome {
+                Some(Ok(output)) => finish(RunStatus::Succeeded, Some(output), None),
+                Some(Err(e)) => {
+                    warn!("Schedule '{}' failed: {}", job.name, e);
+                    finish(RunStatus::Failed, None, Some(e.to_string()))
+                }
+                None => {
+                    warn!("Schedule '{}' timed out", job.name);
+                    finish(RunStatus::TimedOut, None, Some(format!("The run took longer than {:?}", job.timeout.unwrap_or_default())))
+                }
+            }
+        };
+        job.record(run.clone());
+        Some(run)
+    }
+}
+
+/// Run the enabled schedules of `state` when they are due
+pub fn spawn(state: AppState) -> JoinHandle<()> {
+    let jobs: Vec<Arc<Job>> = state.schedules.jobs.values().filter(|job| job.config.enabled).cloned().collect();
+    info!("⏰ Running {} schedule(s)", jobs.len());
+    tokio::spawn(async move {
+        loop {
+            let now = Utc::now();
+            for job in &jobs {
+                let mut next_run = job.next_run.lock().unwrap_or_else(|e| e.into_inner());
+                if next_run.is_none() {
+                    *next_run = job.next_after(now);
+                }
+            }
+            let Some(due) = jobs.iter().filter_map(|job| job.next_run()).min() else {
+                warn!("No schedule will run again");
+                return;
+            };
+            tokio::time::sleep((due - now).to_std().unwrap_or_default()).await;
+
+            let now = Utc::now();
+            for job in jobs.iter().filter(|job| job.next_run().is_some_and(|at| at <= now)) {
+                *job.next_run.lock().unwrap_or_else(|e| e.into_inner()) = job.next_after(now);
+                let (state, name) = (state.clone(), job.name.clone());
+                tokio::spawn(async move {
+                    state.schedules.run(&state, &name, RunTrigger::Cron).await;
+                });
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+
+    fn at(text: &str) -> DateTime<Utc> {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap().and_utc()
+    }
+
+    fn next(expression: &str, after: &str) -> String {
+        let cron: CronExpression = expression.parse().unwrap();
+        cron.next_after(&at(after)).unwrap().format("%Y-%m-%d %H:%M").to_string()
+    }
+
+    #[test]
+    fn test_cron_expressions_find_their_next_run() {
+        // 2026-10-18 is a Sunday
+        assert_eq!(next("*/15 * * * *", "2026-10-18 10:07"), "2026-10-18 10:15");
+        assert_eq!(next("*/15 * * * *", "2026-10-18 10:45"), "2026-10-18 11:00");
+        assert_eq!(next("0 3 * * *", "2026-10-18 03:00"), "2026-10-19 03:00");
+        assert_eq!(next("30 9 * * mon-fri", "2026-10-16 10:00"), "2026-10-19 09:30");
+        assert_eq!(next("0 0 1,15 * *", "2026-10-18 00:00"), "2026-11-01 00:00");
+        assert_eq!(next("0 12 * feb 7", "2026-10-18 00:00"), "2027-02-07 12:00");
+        assert_eq!(next("5/20 8-9 * * *", "2026-10-18 08:46"), "2026-10-18 09:05");
+        assert_eq!(next("@monthly", "2026-12-31 23:59"), "2027-01-01 00:00");
+        assert_eq!(next("@hourly", "2026-10-18 10:00"), "2026-10-18 11:00");
+        assert_eq!(next("0 0 29 2 *", "2026-10-18 00:00"), "2028-02-29 00:00");
+        // Both day fields restricted: the 13th or a Friday
+        assert_eq!(next("0 0 13 * 5", "2026-10-18 00:00"), "2026-10-23 00:00");
+        assert!("0 0 30 2 *".parse::<CronExpression>().unwrap().next_after(&at("2026-10-18 00:00")).is_none());
+
+        // In a time zone, across the switch to summer time
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+        let cron: CronExpression = "30 2 * * *".parse().unwrap();
+        let after = berlin.with_ymd_and_hms(2027, 3, 27, 12, 0, 0).unwrap();
+        let next = cron.next_after(&after).unwrap();
+        assert_eq!(next.with_timezone(&Utc), at("2027-03-29 00:30"));
+
+        for (expression, problem) in [
+            ("* * * *", "expected 5 fields"),
+            ("60 * * * *", "outside 0-59"),
+            ("* * * foo *", "'foo' is not a value"),
+            ("*/0 * * * *", "not a step"),
+            ("10-5 * * * *", "empty range"),
+            ("@often", "unknown macro"),
+        ] {
+            let error = expression.parse::<CronExpression>().unwrap_err();
+            assert!(error.to_string().contains(problem), "{}: {}", expression, error);
+        }
+    }
+
+    #[test]
+    fn test_schedules_are_validated() {
+        let config = |schedule: &str| -> BackworksConfig {
+            serde_yaml::from_str(&format!("name: jobs\nendpoints: {{}}\nschedules:\n  cleanup: {}\n", schedule)).unwrap()
+        };
+        let scheduler = Scheduler::new(&config(r#"{ cron: "0 3 * * *", timezone: Europe/Berlin, runtime: { language: javascript, handler: "function handler() {}" } }"#)).unwrap();
+        let statuses = scheduler.statuses();
+        assert_eq!((statuses[0].name.as_str(), statuses[0].timezone.as_str(), statuses[0].running), ("cleanup", "Europe/Berlin", false));
+        assert!(statuses[0].next_run.is_some());
+        assert_eq!(scheduler.runs("cleanup"), Some(Vec::new()));
+        assert_eq!(scheduler.runs("backup"), None);
+
+        for (schedule, problem) in [
+            (r#"{ cron: "0 3 * *", plugin: redis, action: warm }"#, "expected 5 fields"),
+            (r#"{ cron: "@daily", timezone: Mars/Olympus, plugin: redis, action: warm }"#, "unknown time zone"),
+            (r#"{ cron: "@daily", plugin: redis }"#, "needs an `action`"),
+            (r#"{ cron: "@daily" }"#, "needs a `runtime` handler"),
+            (r#"{ cron: "@daily", plugin: redis, action: warm, timeout: soon }"#, "timeout"),
+            (r#"{ cron: "@daily", plugin: redis, action: warm, history: 0 }"#, "at least one run"),
+        ] {
+            let error = Scheduler::new(&config(schedule)).err().unwrap();
+            assert!(error.to_string().contains("Schedule 'cleanup'") && error.to_string().contains(problem), "{}", error);
+        }
+    }
+}
@@ -0,0 +1,315 @@
+//! Encrypted blueprint values
+//!
+//! Sensitive values can be committed encrypted as `enc://v1/<data>`, produced
+//! by `backworks secrets encrypt`. `<data>` is the base64 of a random 96-bit
+//! nonce followed by the AES-256-GCM ciphertext of the value.
+//!
+//! The loader decrypts every such string before the blueprint is parsed. The
+//! project key is taken from, in order:
+//!
+//! - `BACKWORKS_SECRET_KEY`: the base64 key itself
+//! - `BACKWORKS_SECRET_KEY_FILE`: a file holding it
+//! - `BACKWORKS_SECRET_KEYRING`: the name of an entry in the OS keyring
+//!   (service `backworks`), stored by `backworks secrets init --keyring`
+//! - `.backworks/secret.key` in the current directory, written by
+//!   `backworks secrets init`
+//!
+//! The keyring is reached through the platform's own tool: `security` on
+//! macOS and `secret-tool` (libsecret) elsewhere on Unix. Other platforms
+//! report the keyring as unsupported.
+//!
+//! A blueprint with encrypted values and no available key fails to load.
+
+use crate::error::{BackworksError, Result};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_yaml::Value;
+use std::path::{Path, PathBuf};
+
+pub const PREFIX: &str = "enc://v1/";
+pub const KEY_VAR: &str = "BACKWORKS_SECRET_KEY";
+pub const KEY_FILE_VAR: &str = "BACKWORKS_SECRET_KEY_FILE";
+pub const KEYRING_VAR: &str = "BACKWORKS_SECRET_KEYRING";
+pub const DEFAULT_KEY_FILE: &str = ".backworks/secret.key";
+pub const KEYRING_SERVICE: &str = "backworks";
+
+const NONCE_LEN: usize = 12;
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX)
+}
+
+/// A project's 256-bit secret key
+#[derive(Clone)]
+pub struct SecretKey(Key<Aes256Gcm>);
+
+impl SecretKey {
+    pub fn generate() -> Self {
+        Self(Aes256Gcm::generate_key(OsRng))
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self> {
+        let bytes = STANDARD.decode(encoded.trim())
+            .map_err(|e| BackworksError::config(format!("Secret key is not valid base64: {}", e)))?;
+        if bytes.len() != 32 {
+            return Err(BackworksError::config(format!("Secret key must be 32 bytes, got {}", bytes.len())));
+        }
+        Ok(Self(*Key::<Aes256Gcm>::from_slice(&bytes)))
+    }
+
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.0)
+    }
+
+    pub fn load_file(path: &Path) -> Result<Self> {
+        let encoded = std::fs::read_to_string(path)
+            .map_err(|e| BackworksError::config(format!("Cannot read secret key {}: {}", path.display(), e)))?;
+        Self::from_base64(&encoded)
+    }
+
+    /// The key stored in the OS keyring under `entry`
+    pub fn load_keyring(entry: &str) -> Result<Self> {
+        Self::from_base64(&keyring::load(entry)?)
+    }
+
+    /// Store this key in the OS keyring under `entry`, replacing any there
+    pub fn store_keyring(&self, entry: &str) -> Result<()> {
+        keyring::store(entry, &self.to_base64())
+    }
+
+    /// The project key from the environment, the keyring or the default key
+    /// file, if any
+    pub fn from_environment() -> Result<Option<Self>> {
+        if let Ok(encoded) = std::env::var(KEY_VAR) {
+            return Self::from_base64(&encoded).map(Some);
+        }
+        if let Ok(path) = std::env::var(KEY_FILE_VAR) {
+            return Self::load_file(Path::new(&path)).map(Some);
+        }
+        if let Ok(entry) = std::env::var(KEYRING_VAR) {
+            return Self::load_keyring(&entry).map(Some);
+        }
+        let default = PathBuf::from(DEFAULT_KEY_FILE);
+        if default.exists() {
+            return Self::load_file(&default).map(Some);
+        }
+        Ok(None)
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Result<String> {
+        let nonce = Aes256Gcm::generate_nonce(OsRng);
+        let ciphertext = Aes256Gcm::new(&self.0).encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| BackworksError::config("Encryption failed"))?;
+        let mut data = nonce.to_vec();
+        data.extend(ciphertext);
+        Ok(format!("{}{}", PREFIX, STANDARD.encode(data)))
+    }
+
+    pub fn decrypt(&self, value: &str) -> Result<String> {
+        let encoded = value.strip_prefix(PREFIX)
+            .ok_or_else(|| BackworksError::config(format!("Encrypted values start with '{}'", PREFIX)))?;
+        let data = STANDARD.decode(encoded)
+            .map_err(|e| BackworksError::config(format!("Encrypted value is not valid base64: {}", e)))?;
+        if data.len() < NONCE_LEN {
+            return Err(BackworksError::config("Encrypted value is truncated"));
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let plaintext = Aes256Gcm::new(&self.0).decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| BackworksError::config("Cannot decrypt value: wrong key or corrupted data"))?;
+        String::from_utf8(plaintext).map_err(|_| BackworksError::config("Decrypted value is not UTF-8"))
+    }
+}
+
+/// Decrypt a loaded blueprint with the project key, loading the key only when
+/// the blueprint has encrypted values
+pub fn decrypt_blueprint(value: &mut Value) -> Result<usize> {
+    if !contains_encrypted(value) {
+        return Ok(0);
+    }
+    decrypt_values(value, SecretKey::from_environment()?.as_ref())
+}
+
+fn contains_encrypted(value: &Value) -> bool {
+    match value {
+        Value::String(text) => is_encrypted(text),
+        Value::Sequence(items) => items.iter().any(contains_encrypted),
+        Value::Mapping(mapping) => mapping.values().any(contains_encrypted),
+        Value::Tagged(tagged) => contains_encrypted(&tagged.value),
+        _ => false,
+    }
+}
+
+/// Decrypt every `enc://` string in a blueprint document in place, returning
+/// how many were decrypted. `key` is only needed when there are any.
+pub fn decrypt_values(value: &mut Value, key: Option<&SecretKey>) -> Result<usize> {
+    match value {
+        Value::String(text) if is_encrypted(text) => {
+            let key = key.ok_or_else(|| BackworksError::config(format!(
+                "The blueprint contains encrypted values but no secret key is available; set {}, {} or {}, or add {}",
+                KEY_VAR, KEY_FILE_VAR, KEYRING_VAR, DEFAULT_KEY_FILE
+            )))?;
+            *text = key.decrypt(text)?;
+            Ok(1)
+        }
+        Value::Sequence(items) => items.iter_mut().map(|item| decrypt_values(item, key)).sum(),
+        Value::Mapping(mapping) => mapping.iter_mut().map(|(_, item)| decrypt_values(item, key)).sum(),
+        Value::Tagged(tagged) => decrypt_values(&mut tagged.value, key),
+        _ => Ok(0),
+    }
+}
+
+/// OS keyring access through the platform's command-line tool, which keeps
+/// the key off the command line by passing it on stdin
+mod keyring {
+    use crate::error::{BackworksError, Result};
+    #[cfg(unix)]
+    use super::KEYRING_SERVICE;
+    #[cfg(unix)]
+    use std::io::Write;
+    #[cfg(unix)]
+    use std::process::{Command, Stdio};
+
+    #[cfg(target_os = "macos")]
+    const TOOL: &str = "security";
+    #[cfg(all(unix, not(target_os = "macos")))]
+    const TOOL: &str = "secret-tool";
+
+    #[cfg(target_os = "macos")]
+    fn lookup(entry: &str) -> Command {
+        let mut command = Command::new(TOOL);
+        command.args(["find-generic-password", "-s", KEYRING_SERVICE, "-a", entry, "-w"]);
+        command
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn lookup(entry: &str) -> Command {
+        let mut command = Command::new(TOOL);
+        command.args(["lookup", "service", KEYRING_SERVICE, "account", entry]);
+        command
+    }
+
+    /// The command storing a secret and what it reads from stdin
+    #[cfg(target_os = "macos")]
+    fn storing(entry: &str, secret: &str) -> (Command, String) {
+        let mut command = Command::new(TOOL);
+        command.arg("-i");
+        let script = format!(
+            "add-generic-password -U -s {} -a '{}' -w '{}'\n",
+            KEYRING_SERVICE, entry.replace('\'', ""), secret
+        );
+        (command, script)
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn storing(entry: &str, secret: &str) -> (Command, String) {
+        let mut command = Command::new(TOOL);
+        command.args(["store", "--label", &format!("Backworks secret key ({})", entry)])
+            .args(["service", KEYRING_SERVICE, "account", entry]);
+        (command, secret.to_string())
+    }
+
+    #[cfg(unix)]
+    fn run(mut command: Command, stdin: Option<&str>) -> Result<std::process::Output> {
+        command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        let mut child = command.spawn().map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => BackworksError::config(format!(
+                "The OS keyring needs `{}`, which is not installed; use {} or a key file instead",
+                TOOL, super::KEY_FILE_VAR
+            )),
+            _ => BackworksError::config(format!("Cannot run `{}`: {}", TOOL, e)),
+        })?;
+        if let Some(mut pipe) = child.stdin.take() {
+            pipe.write_all(stdin.unwrap_or_default().as_bytes())?;
+        }
+        Ok(child.wait_with_output()?)
+    }
+
+    #[cfg(unix)]
+    pub fn load(entry: &str) -> Result<String> {
+        let output = run(lookup(entry), None)?;
+        let secret = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if !output.status.success() || secret.is_empty() {
+            return Err(BackworksError::config(format!(
+                "No secret key named '{}' in the OS keyring (service '{}')", entry, KEYRING_SERVICE
+            )));
+        }
+        Ok(secret)
+    }
+
+    #[cfg(unix)]
+    pub fn store(entry: &str, secret: &str) -> Result<()> {
+        let (command, stdin) = storing(entry, secret);
+        let output = run(command, Some(&stdin))?;
+        if !output.status.success() {
+            return Err(BackworksError::config(format!(
+                "Cannot store the secret key in the OS keyring: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn load(_entry: &str) -> Result<String> {
+        Err(unsupported())
+    }
+
+    #[cfg(not(unix))]
+    pub fn store(_entry: &str, _secret: &str) -> Result<()> {
+        Err(unsupported())
+    }
+
+    #[cfg(not(unix))]
+    fn unsupported() -> BackworksError {
+        BackworksError::config(format!(
+            "The OS keyring is not supported on this platform; use {} or a key file instead",
+            super::KEY_FILE_VAR
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_wrong_key() {
+        let key = SecretKey::generate();
+        let encrypted = key.encrypt("s3cr3t").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert_ne!(encrypted, key.encrypt("s3cr3t").unwrap(), "nonces must differ");
+        assert_eq!(key.decrypt(&encrypted).unwrap(), "s3cr3t");
+
+        let reloaded = SecretKey::from_base64(&key.to_base64()).unwrap();
+        assert_eq!(reloaded.decrypt(&encrypted).unwrap(), "s3cr3t");
+        assert!(SecretKey::generate().decrypt(&encrypted).unwrap_err().to_string().contains("wrong key"));
+        assert!(SecretKey::from_base64("c2hvcnQ=").is_err());
+    }
+
+    #[test]
+    fn test_decrypt_blueprint_values() {
+        let key = SecretKey::generate();
+        let mut blueprint: Value = serde_yaml::from_str(&format!(
+            "database:\n  connection_string: \"{}\"\nplugins:\n  - \"{}\"\n  - plain\n",
+            key.encrypt("postgres://app:pw@db/app").unwrap(),
+            key.encrypt("token").unwrap(),
+        )).unwrap();
+
+        let mut without_key = blueprint.clone();
+        assert!(decrypt_values(&mut without_key, None).unwrap_err().to_string().contains(KEY_VAR));
+
+        assert_eq!(decrypt_values(&mut blueprint, Some(&key)).unwrap(), 2);
+        assert_eq!(blueprint["database"]["connection_string"], "postgres://app:pw@db/app");
+        assert_eq!(blueprint["plugins"][1], "plain");
+    }
+
+    #[test]
+    fn test_missing_keyring_entry_is_an_error() {
+        // Without the keyring tool, or without the entry, loading must fail
+        // rather than fall back to another key
+        let error = SecretKey::load_keyring("backworks-test-missing-entry").err().unwrap().to_string();
+        assert!(error.contains("keyring"), "{}", error);
+    }
+}
@@ -0,0 +1,298 @@
+//! Security headers and profiles
+//!
+//! Every API response gets `X-Content-Type-Options: nosniff`, the
+//! `Strict-Transport-Security` header when the server speaks HTTPS (or
+//! `security.hsts` asks for it), the `security.content_security_policy` and
+//! the extra `security.headers`. Headers a handler set itself are kept.
+//!
+//! `security.profile` selects one of `security.profiles`, or the built-in
+//! `production` and `development` profiles, and enforces it:
+//!
+//! - `obfuscate_internals`: server errors are answered with a generic body
+//!   carrying only the status and request ID
+//! - `strip_secrets`: the [`Scrubber`] masks credentials in the access log,
+//!   panic reports, the dashboard's request log and captured exchanges
+//! - `require_https`: plain HTTP requests are redirected, as with
+//!   `security.require_https`
+
+use crate::config::{parse_duration, BackworksConfig, SecurityConfig, SecurityProfile};
+use crate::error::{BackworksError, Result};
+use crate::panic::REQUEST_ID_HEADER;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+
+const DEFAULT_HSTS_MAX_AGE: u64 = 365 * 24 * 3600;
+
+/// Replaces every secret the [`Scrubber`] finds
+pub const REDACTED: &str = "[redacted]";
+
+/// Secrets from the blueprint shorter than this are too likely to appear in
+/// ordinary text to be masked
+const MIN_SECRET_LENGTH: usize = 8;
+
+/// Object keys whose values are masked in captured bodies
+const SENSITIVE_KEYS: &[&str] = &["password", "passwd", "secret", "token", "api_key", "apikey", "authorization", "credential", "private_key"];
+
+static URL_CREDENTIALS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)\b([a-z][a-z0-9+.-]*://[^:/@\s]+:)[^@/\s]+@").unwrap());
+static AUTHORIZATION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)\b(bearer|basic)\s+[a-z0-9._~+/=-]+").unwrap());
+static QUERY_SECRETS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)([?&](?:access_token|api_key|apikey|token|secret|client_secret|password|signature|sig|key)=)[^&#\s]+").unwrap()
+});
+
+/// Response headers and the active profile
+#[derive(Debug, Clone, Default)]
+pub struct Security {
+    headers: Vec<(HeaderName, HeaderValue)>,
+    profile: SecurityProfile,
+    scrubber: Option<Arc<Scrubber>>,
+}
+
+impl Security {
+    pub fn new(config: &BackworksConfig) -> Result<Self> {
+        let mut headers = vec![(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"))];
+        let Some(ref security) = config.security else {
+            return Ok(Self { headers, ..Default::default() });
+        };
+
+        let hsts = security.hsts.clone().unwrap_or_default();
+        if hsts.enabled.unwrap_or(config.server.tls.is_some()) {
+            let max_age = match hsts.max_age.as_deref() {
+                Some(text) => parse_duration(text).map_err(|e| BackworksError::config(format!("security.hsts.max_age: {}", e)))?.as_secs(),
+                None => DEFAULT_HSTS_MAX_AGE,
+            };
+            let mut value = format!("max-age={}", max_age);
+            if hsts.include_subdomains {
+                value.push_str("; includeSubDomains");
+            }
+            if hsts.preload {
+                value.push_str("; preload");
+            }
+            headers.push((header::STRICT_TRANSPORT_SECURITY, header_value("security.hsts", &value)?));
+        }
+        if let Some(ref policy) = security.content_security_policy {
+            headers.push((header::CONTENT_SECURITY_POLICY, header_value("security.content_security_policy", policy)?));
+        }
+        let mut extra: Vec<_> = security.headers.iter().flatten().collect();
+        extra.sort();
+        for (name, value) in extra {
+            let name = HeaderName::try_from(name.as_str())
+                .map_err(|e| BackworksError::config(format!("security.headers: '{}': {}", name, e)))?;
+            headers.push((name, header_value("security.headers", value)?));
+        }
+
+        let profile = active_profile(security)?;
+        let scrubber = profile.strip_secrets.then(|| Arc::new(Scrubber::new(config)));
+        Ok(Self { headers, profile, scrubber })
+    }
+
+    /// Whether server errors are answered with a generic body
+    pub fn obfuscates_internals(&self) -> bool {
+        self.profile.obfuscate_internals.unwrap_or(false)
+    }
+
+    /// Masks secrets, when the profile strips them
+    pub fn scrubber(&self) -> Option<&Arc<Scrubber>> {
+        self.scrubber.as_ref()
+    }
+
+    /// `text` with its secrets masked, when the profile strips them
+    pub fn scrub(&self, text: String) -> String {
+        match self.scrubber {
+            Some(ref scrubber) => scrubber.scrub(&text),
+            None => text,
+        }
+    }
+}
+
+/// The profile `security.profile` names; no settings without one
+pub fn active_profile(security: &SecurityConfig) -> Result<SecurityProfile> {
+    match security.profile {
+        Some(ref name) => profile_named(&security.profiles, name),
+        None => Ok(SecurityProfile::default()),
+    }
+}
+
+/// Profile `name` of `profiles`, or the built-in one of that name
+pub fn profile_named(profiles: &HashMap<String, SecurityProfile>, name: &str) -> Result<SecurityProfile> {
+    if let Some(profile) = profiles.get(name) {
+        return Ok(profile.clone());
+    }
+    match name {
+        "production" => Ok(SecurityProfile {
+            strip_secrets: true,
+            obfuscate_internals: Some(true),
+            ..Default::default()
+        }),
+        "development" => Ok(SecurityProfile { enable_debug: true, verbose_logging: true, ..Default::default() }),
+        _ => Err(BackworksError::config(format!(
+            "security.profile '{}' is not one of security.profiles, production or development", name
+        ))),
+    }
+}
+
+/// Whether plain HTTP requests are redirected to HTTPS, by
+/// `security.require_https` or the active profile
+pub fn requires_https(config: &BackworksConfig) -> Result<bool> {
+    let Some(ref security) = config.security else {
+        return Ok(false);
+    };
+    Ok(security.require_https.or(active_profile(security)?.require_https).unwrap_or(false))
+}
+
+fn header_value(setting: &str, value: &str) -> Result<HeaderValue> {
+    HeaderValue::from_str(value).map_err(|e| BackworksError::config(format!("{}: {}", setting, e)))
+}
+
+/// Masks credentials: passwords in URLs, `Bearer` and `Basic` credentials,
+/// secret query parameters and the secrets the blueprint configures
+#[derive(Debug, Clone, Default)]
+pub struct Scrubber {
+    secrets: Vec<String>,
+}
+
+impl Scrubber {
+    pub fn new(config: &BackworksConfig) -> Self {
+        let mut secrets: Vec<String> = config.sessions.iter().map(|sessions| sessions.secret.clone())
+            .chain(config.endpoints.values().filter_map(|endpoint| Some(endpoint.webhook.as_ref()?.secret.clone())))
+            .chain(config.admin.iter().map(|admin| admin.token.clone()))
+            .chain(config.security.iter()
+                .filter_map(|security| security.authentication.as_ref()?.secret_env.as_ref())
+                .filter_map(|var| std::env::var(var).ok()))
+            .filter(|secret| secret.len() >= MIN_SECRET_LENGTH)
+            .collect();
+        // Longer secrets first, so one containing another is masked whole
+        secrets.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        secrets.dedup();
+        Self { secrets }
+    }
+
+    pub fn scrub(&self, text: &str) -> String {
+        let mut text = URL_CREDENTIALS.replace_all(text, format!("${{1}}{}@", REDACTED)).into_owned();
+        text = AUTHORIZATION.replace_all(&text, format!("${{1}} {}", REDACTED)).into_owned();
+        text = QUERY_SECRETS.replace_all(&text, format!("${{1}}{}", REDACTED)).into_owned();
+        for secret in &self.secrets {
+            if text.contains(secret.as_str()) {
+                text = text.replace(secret.as_str(), REDACTED);
+            }
+        }
+        text
+    }
+
+    /// `value` of the field or header `key`, masked whole under a sensitive
+    /// key
+    pub fn scrub_field(&self, key: &str, value: &str) -> String {
+        if sensitive(key) {
+            REDACTED.to_string()
+        } else {
+            self.scrub(value)
+        }
+    }
+
+    /// Mask the strings of `value`, and whole values under sensitive keys
+    pub fn scrub_value(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => *text = self.scrub(text),
+            Value::Array(items) => items.iter_mut().for_each(|item| self.scrub_value(item)),
+            Value::Object(fields) => {
+                for (key, field) in fields.iter_mut() {
+                    if sensitive(key) && !field.is_null() {
+                        *field = Value::String(REDACTED.to_string());
+                    } else {
+                        self.scrub_value(field);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn sensitive(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SENSITIVE_KEYS.iter().any(|sensitive| key.contains(sensitive))
+}
+
+/// App layer adding the security headers and, with `obfuscate_internals`,
+/// replacing the bodies of server errors
+pub async fn secure_responses(State(security): State<Arc<Security>>, request: Request, next: Next) -> Response {
+    let request_id = request.headers().get(REQUEST_ID_HEADER).cloned();
+    let mut response = next.run(request).await;
+
+    if security.obfuscates_internals() && response.status().is_server_error() {
+        let status = response.status();
+        let mut body = serde_json::json!({
+            "error": status.canonical_reason().unwrap_or("Internal Server Error"),
+            "status": status.as_u16(),
+        });
+        let request_id = response.headers().get(REQUEST_ID_HEADER).or(request_id.as_ref());
+        if let Some(request_id) = request_id.and_then(|value| value.to_str().ok()) {
+            body["request_id"] = Value::String(request_id.to_string());
+        }
+        let (mut parts, _) = response.into_parts();
+        parts.headers.remove(header::CONTENT_LENGTH);
+        parts.headers.remove(header::CONTENT_ENCODING);
+        let generic = Json(body).into_response();
+        parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        response = Response::from_parts(parts, generic.into_body());
+    }
+
+    let headers = response.headers_mut();
+    for (name, value) in &security.headers {
+        if !headers.contains_key(name) {
+            headers.insert(name.clone(), value.clone());
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(security: &str) -> BackworksConfig {
+        let mut config: BackworksConfig = serde_yaml::from_str("name: test\nendpoints: {}").unwrap();
+        config.security = Some(serde_yaml::from_str(security).unwrap());
+        config
+    }
+
+    #[test]
+    fn test_profiles_resolve_to_their_settings() {
+        let production = Security::new(&config("profile: production")).unwrap();
+        assert!(production.obfuscates_internals() && production.scrubber().is_some());
+        let development = Security::new(&config("profile: development")).unwrap();
+        assert!(!development.obfuscates_internals() && development.scrubber().is_none());
+
+        let custom = config("profile: staging\nprofiles:\n  staging: { strip_secrets: true, require_https: true }");
+        assert!(Security::new(&custom).unwrap().scrubber().is_some());
+        assert!(requires_https(&custom).unwrap());
+        let err = Security::new(&config("profile: prod")).unwrap_err();
+        assert!(err.to_string().contains("security.profile 'prod'"), "{}", err);
+        assert!(Security::new(&config("headers: { \"bad header\": x }")).is_err());
+    }
+
+    #[test]
+    fn test_scrubber_masks_credentials() {
+        let mut config = config("profile: production");
+        config.admin = Some(serde_yaml::from_str("token: admin-token-0123").unwrap());
+        let scrubber = Scrubber::new(&config);
+
+        assert_eq!(
+            scrubber.scrub("connect postgres://app:hunter2@db:5432/app failed"),
+            "connect postgres://app:[redacted]@db:5432/app failed"
+        );
+        assert_eq!(scrubber.scrub("Authorization: Bearer eyJhbGciOi.x.y"), "Authorization: Bearer [redacted]");
+        assert_eq!(scrubber.scrub("/orders?page=2&api_key=abc123&sort=id"), "/orders?page=2&api_key=[redacted]&sort=id");
+        assert_eq!(scrubber.scrub("token admin-token-0123 rejected"), "token [redacted] rejected");
+        assert_eq!(scrubber.scrub("/orders/7"), "/orders/7");
+
+        let mut body = serde_json::json!({ "user": "ada", "password": "pw", "nested": [{ "apiKey": "k" }], "note": "Bearer abc" });
+        scrubber.scrub_value(&mut body);
+        assert_eq!(body, serde_json::json!({ "user": "ada", "password": REDACTED, "nested": [{ "apiKey": REDACTED }], "note": "Bearer [redacted]" }));
+    }
+}
@@ -0,0 +1,397 @@
+//! Fixture files loaded into database tables
+//!
+//! Each fixture file fills the table named after it: `users.yaml` fills
+//! `users`. YAML and JSON fixtures hold a list of rows, or a map of labels
+//! to rows; CSV fixtures hold a header row and one row per line, with an
+//! optional `_label` column. A value `$ref:users.ada` stands for the primary
+//! key of the row labelled `ada` in the `users` fixture (`$ref:users.ada.email`
+//! for another column), so rows are inserted once the rows they refer to
+//! are. Rows are checked against the table schema before they are inserted;
+//! CSV cells are converted to the column types, and empty cells are left out.
+//!
+//! In idempotent mode, rows already in their table are kept instead of being
+//! inserted again: a row is found by its primary key when it sets one, and
+//! by all of its values otherwise.
+
+use crate::crud::{check_row, type_name, Write};
+use crate::error::{BackworksError, Result};
+use crate::plugin::{DatabaseHandle, ListQuery, Row, TableSchema};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Prefix of values that refer to a row of another fixture
+const REFERENCE_PREFIX: &str = "$ref:";
+/// Column or key labelling a row in list and CSV fixtures
+const LABEL_KEY: &str = "_label";
+
+/// The rows of one fixture file
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    pub table: String,
+    pub source: PathBuf,
+    rows: Vec<FixtureRow>,
+    /// Whether values are text to convert to the column types, as in CSV
+    text: bool,
+}
+
+#[derive(Debug, Clone)]
+struct FixtureRow {
+    label: Option<String>,
+    values: Row,
+}
+
+impl Fixture {
+    /// Read a fixture file, choosing the format by its extension
+    pub fn load(path: &Path) -> Result<Self> {
+        let table = path.file_stem().and_then(|stem| stem.to_str())
+            .ok_or_else(|| BackworksError::config(format!("Cannot name a table after {}", path.display())))?
+            .to_string();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| BackworksError::config(format!("Cannot read fixture {}: {}", path.display(), e)))?;
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default().to_ascii_lowercase();
+        let (rows, text) = match extension.as_str() {
+            "csv" => (csv_rows(&content), true),
+            "yaml" | "yml" | "json" => {
+                let value: Value = serde_yaml::from_str(&content)
+                    .map_err(|e| BackworksError::config(format!("Invalid fixture {}: {}", path.display(), e)))?;
+                (document_rows(value), false)
+            }
+            _ => return Err(BackworksError::config(format!(
+                "Fixture {} is not YAML, JSON or CSV", path.display()
+            ))),
+        };
+        let rows = rows.map_err(|e| BackworksError::config(format!("Invalid fixture {}: {}", path.display(), e)))?;
+        Ok(Self { table, source: path.to_path_buf(), rows, text })
+    }
+
+    /// Read fixture files, and the fixture files in directories by name
+    pub fn load_all(paths: &[PathBuf]) -> Result<Vec<Self>> {
+        let mut fixtures = Vec::new();
+        for path in paths {
+            if !path.is_dir() {
+                fixtures.push(Self::load(path)?);
+                continue;
+            }
+            let mut files: Vec<PathBuf> = std::fs::read_dir(path)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|file| file.is_file() && matches!(
+                    file.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase).as_deref(),
+                    Some("yaml" | "yml" | "json" | "csv")
+                ))
+                .collect();
+            files.sort();
+            for file in files {
+                fixtures.push(Self::load(&file)?);
+            }
+        }
+        let mut tables = HashSet::new();
+        if let Some(twice) = fixtures.iter().find(|fixture| !tables.insert(fixture.table.clone())) {
+            return Err(BackworksError::config(format!("Table '{}' has more than one fixture", twice.table)));
+        }
+        Ok(fixtures)
+    }
+}
+
+fn document_rows(value: Value) -> std::result::Result<Vec<FixtureRow>, String> {
+    let labelled: Vec<(Option<String>, Value)> = match value {
+        Value::Array(rows) => rows.into_iter().map(|row| (None, row)).collect(),
+        Value::Object(rows) => rows.into_iter().map(|(label, row)| (Some(label), row)).collect(),
+        Value::Null => Vec::new(),
+        _ => return Err("expected a list of rows or a map of labelled rows".to_string()),
+    };
+    labelled.into_iter()
+        .map(|(label, row)| {
+            let Value::Object(mut values) = row else {
+                return Err(format!("row {} is not a map of columns", label.as_deref().unwrap_or("in the list")));
+            };
+            let label = match values.remove(LABEL_KEY) {
+                Some(Value::String(own)) => Some(own),
+                Some(_) => return Err(format!("{} must be a string", LABEL_KEY)),
+                None => label,
+            };
+            Ok(FixtureRow { label, values })
+        })
+        .collect()
+}
+
+fn csv_rows(content: &str) -> std::result::Result<Vec<FixtureRow>, String> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        let mut label = None;
+        let mut values = Row::new();
+        for (column, cell) in headers.iter().zip(record.iter()) {
+            if column == LABEL_KEY {
+                label = Some(cell.to_string()).filter(|label| !label.is_empty());
+            } else if !cell.is_empty() {
+                values.insert(column.to_string(), Value::String(cell.to_string()));
+            }
+        }
+        rows.push(FixtureRow { label, values });
+    }
+    Ok(rows)
+}
+
+/// Rows each table received
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SeedSummary {
+    pub tables: BTreeMap<String, TableSeed>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TableSeed {
+    pub inserted: usize,
+    /// Rows found in the table already, in idempotent mode
+    pub existing: usize,
+}
+
+/// Insert the rows of `fixtures` through `database`, each once the rows it
+/// refers to are stored
+pub async fn seed(database: &DatabaseHandle, fixtures: &[Fixture], idempotent: bool) -> Result<SeedSummary> {
+    let mut schemas = HashMap::new();
+    for fixture in fixtures {
+        let schema = database.table_schema(&fixture.table).await
+            .map_err(|e| BackworksError::config(format!("Cannot read table '{}': {}", fixture.table, e)))?;
+        schemas.insert(fixture.table.clone(), schema);
+    }
+    let labels: HashSet<(&str, &str)> = fixtures.iter()
+        .flat_map(|fixture| fixture.rows.iter().filter_map(|row| Some((fixture.table.as_str(), row.label.as_deref()?))))
+        .collect();
+
+    let mut summary = SeedSummary::default();
+    let mut stored: HashMap<(String, String), Row> = HashMap::new();
+    let mut pending: Vec<(usize, usize)> = fixtures.iter().enumerate()
+        .flat_map(|(index, fixture)| (0..fixture.rows.len()).map(move |row| (index, row)))
+        .collect();
+    while !pending.is_empty() {
+        let mut waiting = Vec::new();
+        for (index, row_index) in pending.iter().copied() {
+            let fixture = &fixtures[index];
+            let row = &fixture.rows[row_index];
+            let describe = || match row.label {
+                Some(ref label) => format!("{} row '{}'", fixture.source.display(), label),
+                None => format!("{} row {}", fixture.source.display(), row_index + 1),
+            };
+            let schema = &schemas[&fixture.table];
+            let Some(values) = resolve(row, fixture.text, schema, &schemas, &labels, &stored)
+                .map_err(|e| BackworksError::config(format!("{}: {}", describe(), e)))?
+            else {
+                waiting.push((index, row_index));
+                continue;
+            };
+            check_row(schema, &values, Write::Create)
+                .map_err(|e| BackworksError::config(format!("{}: {}", describe(), e)))?;
+
+            let counts = summary.tables.entry(fixture.table.clone()).or_default();
+            let existing = if idempotent { find(database, schema, &values).await? } else { None };
+            let row_stored = match existing {
+                Some(existing) => {
+                    counts.existing += 1;
+                    existing
+                }
+                None => {
+                    counts.inserted += 1;
+                    database.insert(&fixture.table, values).await
+                        .map_err(|e| BackworksError::config(format!("{}: {}", describe(), e)))?
+                }
+            };
+            if let Some(ref label) = row.label {
+                stored.insert((fixture.table.clone(), label.clone()), row_stored);
+            }
+        }
+        if waiting.len() == pending.len() {
+            let rows: Vec<String> = waiting.iter()
+                .map(|&(index, row)| format!("{}.{}", fixtures[index].table, fixtures[index].rows[row].label.as_deref().unwrap_or("?")))
+                .collect();
+            return Err(BackworksError::config(format!("Fixture rows refer to each other in a cycle: {}", rows.join(", "))));
+        }
+        pending = waiting;
+    }
+    for fixture in fixtures {
+        summary.tables.entry(fixture.table.clone()).or_default();
+    }
+    Ok(summary)
+}
+
+/// The values of `row` with its references resolved, converted to the
+/// column types for text fixtures; `None` while a referenced row is not
+/// stored yet
+fn resolve(
+    row: &FixtureRow,
+    text: bool,
+    schema: &TableSchema,
+    schemas: &HashMap<String, TableSchema>,
+    labels: &HashSet<(&str, &str)>,
+    stored: &HashMap<(String, String), Row>,
+) -> std::result::Result<Option<Row>, String> {
+    let mut values = Row::new();
+    for (column, value) in &row.values {
+        let reference = value.as_str().and_then(|value| value.strip_prefix(REFERENCE_PREFIX));
+        let resolved = match reference {
+            Some(reference) => {
+                let mut parts = reference.splitn(3, '.');
+                let (Some(table), Some(label)) = (parts.next(), parts.next()) else {
+                    return Err(format!("reference '{}' must name a table and a row label", reference));
+                };
+                if !labels.contains(&(table, label)) {
+                    return Err(format!("reference '{}' names no labelled fixture row", reference));
+                }
+                let Some(target) = stored.get(&(table.to_string(), label.to_string())) else {
+                    return Ok(None);
+                };
+                let field = match parts.next() {
+                    Some(field) => field.to_string(),
+                    None => schemas[table].primary_key()
+                        .ok_or_else(|| format!("table '{}' has no single primary key to refer to", table))?
+                        .name.clone(),
+                };
+                target.get(&field).cloned()
+                    .ok_or_else(|| format!("reference '{}': the row has no column '{}'", reference, field))?
+            }
+            None if text => {
+                let text = value.as_str().unwrap_or_default();
+                let column_schema = schema.column(column).ok_or_else(|| format!("Unknown column '{}'", column))?;
+                column_schema.column_type.parse(text)
+                    .ok_or_else(|| format!("Column '{}' must be {}, not '{}'", column, type_name(column_schema), text))?
+            }
+            None => value.clone(),
+        };
+        values.insert(column.clone(), resolved);
+    }
+    Ok(Some(values))
+}
+
+/// The row of the table `values` describes, if it is stored already
+async fn find(database: &DatabaseHandle, schema: &TableSchema, values: &Row) -> Result<Option<Row>> {
+    if let Some(key) = schema.primary_key().and_then(|key| values.get(&key.name)) {
+        return database.get(&schema.table, key).await;
+    }
+    let query = ListQuery {
+        filters: values.iter().map(|(column, value)| (column.clone(), value.clone())).collect(),
+        limit: 1,
+        ..ListQuery::default()
+    };
+    Ok(database.list(&schema.table, &query).await?.rows.into_iter().next())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::{BackworksPlugin, Column, ColumnType, DatabasePlugin, PluginManager, RowPage};
+    use async_trait::async_trait;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    /// Tables `users` and `posts` kept in memory
+    #[derive(Default)]
+    struct Blog {
+        tables: Mutex<HashMap<String, Vec<Row>>>,
+    }
+
+    #[async_trait]
+    impl BackworksPlugin for Blog {
+        fn name(&self) -> &str { "blog" }
+        fn version(&self) -> &str { "0.0.0" }
+        fn description(&self) -> &str { "keeps a blog in memory" }
+        async fn initialize(&self, _config: &Value) -> Result<()> { Ok(()) }
+        async fn shutdown(&self) -> Result<()> { Ok(()) }
+        fn database(&self) -> Option<&dyn DatabasePlugin> { Some(self) }
+    }
+
+    #[async_trait]
+    impl DatabasePlugin for Blog {
+        async fn table_schema(&self, table: &str) -> Result<TableSchema> {
+            let columns = match table {
+                "users" => vec![
+                    Column::new("id", ColumnType::Integer).primary_key(),
+                    Column::new("email", ColumnType::Text),
+                    Column::new("admin", ColumnType::Boolean).with_default(),
+                ],
+                "posts" => vec![
+                    Column::new("id", ColumnType::Integer).primary_key(),
+                    Column::new("author_id", ColumnType::Integer),
+                    Column::new("title", ColumnType::Text),
+                ],
+                _ => return Err(BackworksError::database(format!("no table {}", table))),
+            };
+            Ok(TableSchema::new(table, columns))
+        }
+
+        async fn list(&self, table: &str, query: &ListQuery) -> Result<RowPage> {
+            let tables = self.tables.lock().unwrap();
+            let rows: Vec<Row> = tables.get(table).into_iter().flatten()
+                .filter(|row| query.filters.iter().all(|(column, value)| row.get(column) == Some(value)))
+                .cloned()
+                .collect();
+            Ok(RowPage { total: rows.len() as u64, rows: rows.into_iter().take(query.limit).collect() })
+        }
+
+        async fn get(&self, table: &str, key: &Value) -> Result<Option<Row>> {
+            Ok(self.tables.lock().unwrap().get(table).into_iter().flatten().find(|row| row["id"] == *key).cloned())
+        }
+
+        async fn insert(&self, table: &str, mut row: Row) -> Result<Row> {
+            let mut tables = self.tables.lock().unwrap();
+            let rows = tables.entry(table.to_string()).or_default();
+            row.entry("id").or_insert(json!(rows.len() + 1));
+            rows.push(row.clone());
+            Ok(row)
+        }
+
+        async fn update(&self, _table: &str, _key: &Value, _changes: Row) -> Result<Option<Row>> {
+            Ok(None)
+        }
+
+        async fn delete(&self, _table: &str, _key: &Value) -> Result<bool> {
+            Ok(false)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fixtures_are_seeded_in_reference_order_and_idempotently() {
+        let dir = std::env::temp_dir().join(format!("backworks_seed_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Posts sort first but refer to users
+        std::fs::write(dir.join("posts.csv"), "title,author_id\nHello,$ref:users.ada\nNotes,$ref:users.grace\n").unwrap();
+        std::fs::write(dir.join("users.yaml"), "ada:\n  email: ada@example.com\n  admin: true\ngrace:\n  email: grace@example.com\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a fixture").unwrap();
+
+        let manager = PluginManager::new();
+        let blog = Arc::new(Blog::default());
+        manager.register_plugin(blog.clone(), None, None).await.unwrap();
+        let database = manager.database(None).await.unwrap();
+        let fixtures = Fixture::load_all(std::slice::from_ref(&dir)).unwrap();
+        assert_eq!(fixtures.iter().map(|f| f.table.as_str()).collect::<Vec<_>>(), ["posts", "users"]);
+
+        let summary = seed(&database, &fixtures, true).await.unwrap();
+        assert_eq!((summary.tables["users"].inserted, summary.tables["posts"].inserted), (2, 2));
+        {
+            let tables = blog.tables.lock().unwrap();
+            assert_eq!(tables["posts"][1], json!({"title": "Notes", "author_id": 2, "id": 2}).as_object().unwrap().clone());
+        }
+
+        // Seeding again finds every row
+        let summary = seed(&database, &fixtures, true).await.unwrap();
+        assert_eq!((summary.tables["users"].existing, summary.tables["posts"].existing), (2, 2));
+        assert_eq!(summary.tables["users"].inserted + summary.tables["posts"].inserted, 0);
+        assert_eq!(blog.tables.lock().unwrap()["users"].len(), 2);
+
+        // Without idempotency rows are inserted again; bad values are refused
+        seed(&database, &fixtures, false).await.unwrap();
+        assert_eq!(blog.tables.lock().unwrap()["users"].len(), 4);
+        std::fs::write(dir.join("posts.csv"), "title,author_id\nHello,first\n").unwrap();
+        let fixtures = Fixture::load_all(std::slice::from_ref(&dir)).unwrap();
+        let error = seed(&database, &fixtures, true).await.unwrap_err().to_string();
+        assert!(error.contains("Column 'author_id' must be an integer"), "{}", error);
+        std::fs::write(dir.join("posts.csv"), "title,author_id\nHello,$ref:users.linus\n").unwrap();
+        let fixtures = Fixture::load_all(std::slice::from_ref(&dir)).unwrap();
+        let error = seed(&database, &fixtures, true).await.unwrap_err().to_string();
+        assert!(error.contains("names no labelled fixture row"), "{}", error);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,27 +1,71 @@
 use std::sync::Arc;
 use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
 use axum::{
     Router,
-    routing::{get, post, put, delete, any},
-    response::Json,
-    extract::{Path, Query, State},
-    http::{StatusCode, HeaderMap, Method},
-    middleware,
-};
-use tower::ServiceBuilder;
-use tower_http::{
-    cors::{CorsLayer, Any},
-    trace::TraceLayer,
+    routing::{get, post, put, delete, any, MethodRouter},
+    response::{IntoResponse, Json},
+    extract::{MatchedPath, Path, Query, State},
+    http::{header, HeaderName, HeaderValue, StatusCode, HeaderMap, Method},
+    middleware, Extension,
 };
+use tower_http::trace::TraceLayer;
 use serde_json::Value;
 use serde::{Serialize, Deserialize};
-use tracing::{info, debug, error};
+use tracing::{info, debug, error, warn};
 
-use crate::config::{BackworksConfig, ExecutionMode};
+use crate::config::{BackworksConfig, ChaosConfig, CompareServe, ExecutionMode, ServerConfig, StatusCodeMapping};
+use crate::compare::{diff_responses, CompareRules, ComparisonRecorder, ComparisonReport, ResponseSnapshot};
 use crate::runtime::RuntimeManager;
 use crate::plugin::PluginManager;
+use crate::plugin::routes::{check_plugin_routes, PluginRoutes};
 use crate::dashboard::Dashboard;
-use crate::error::{BackworksError, Result};
+use crate::stats::RequestStats;
+use crate::usage::{UsageRecorder, UsageReport};
+use crate::request_metrics::RequestMetrics;
+use crate::pool::DatabasePools;
+use crate::response_filter;
+use crate::state::{ImportMode, ImportSummary, StateSnapshot, StateStore};
+use crate::error_catalog::{accepted_locales, ErrorCatalog, ErrorReference};
+use crate::rollout::RolloutSchedule;
+use crate::scenario::{ScenarioStatus, ScenarioSwitch, Scenarios};
+use crate::journal::{self, Journal, JournalEntry, ReplaySummary};
+use crate::chaos::{inject_faults, Chaos, ChaosGate, ChaosStatus, ChaosSwitch};
+use crate::pipeline::{run_pipeline, MiddlewareRegistry, Pipelines};
+use crate::balancer::LoadBalancerRegistry;
+use crate::routes::RoutePattern;
+use crate::docs::ApiDocs;
+use crate::static_files::StaticFiles;
+use crate::templates::EndpointTemplates;
+use crate::body_transform::EndpointTransforms;
+use crate::coercion::EndpointCoercions;
+use crate::queries::EndpointQueries;
+use crate::mock::MockEngine;
+use crate::panic::recover_panics;
+use crate::capture::{capture_exchanges, CaptureHandler};
+use crate::admin::{self, require_token, EndpointSwitches, LiveRouter, Reloader};
+use crate::retention::{PurgeRequest, PurgeSummary, RetentionPolicy};
+use crate::schedule::Scheduler;
+use crate::proxy::ProxyEngine;
+use crate::metrics_recorder::MetricsRecorder;
+use crate::targets::{TargetCommand, TargetOperation, TargetsReport};
+use crate::deadline::Deadline;
+use crate::crud::CrudTables;
+use crate::concurrency::EndpointLimits;
+use crate::request_body::{limit_request_body, BodyLimits};
+use crate::webhook::{verify_webhook, WebhookVerifier};
+use crate::session::{manage_sessions, Session, SessionChange, Sessions};
+use crate::csrf::verify_csrf;
+use crate::security::{secure_responses, Security};
+use crate::compression::CompressionPolicy;
+use crate::tls::Tls;
+use crate::download::FileDownload;
+use crate::locale::{LocaleContext, Localization};
+use crate::health::{HealthChecker, HealthReport};
+use crate::auth::{AuthContext, PeerCertificate, TrustedHeaderAuth};
+use crate::origin::RequestOrigin;
+use crate::cors::{cors_middleware, Cors, CorsPolicies};
+use crate::error::{BackworksError, ErrorSource, RequestError, Result};
 
 #[derive(Clone)]
 pub struct AppState {
@@ -29,10 +73,45 @@ pub struct AppState {
     pub plugin_manager: PluginManager,
     pub runtime_manager: RuntimeManager,
     pub dashboard: Option<Arc<Dashboard>>,
+    pub comparisons: ComparisonRecorder,
+    pub stats: RequestStats,
+    pub metrics: RequestMetrics,
+    /// Latency and error metrics, the dashboard's when it runs
+    pub recorder: MetricsRecorder,
+    pub trusted_headers: Option<Arc<TrustedHeaderAuth>>,
+    pub health: HealthChecker,
+    pub state_store: StateStore,
+    pub error_catalog: Arc<ErrorCatalog>,
+    pub templates: Arc<EndpointTemplates>,
+    pub transforms: Arc<EndpointTransforms>,
+    pub coercions: Arc<EndpointCoercions>,
+    pub queries: Arc<EndpointQueries>,
+    pub limits: EndpointLimits,
+    pub mocks: Arc<MockEngine>,
+    pub proxies: Arc<ProxyEngine>,
+    pub localization: Arc<Localization>,
+    pub scenarios: Scenarios,
+    pub journal: Option<Arc<Journal>>,
+    pub chaos: Chaos,
+    pub usage: UsageRecorder,
+    pub capture: Option<CaptureHandler>,
+    pub switches: EndpointSwitches,
+    pub pipelines: Pipelines,
+    pub crud: CrudTables,
+    pub schedules: Scheduler,
+    pub sessions: Option<Arc<Sessions>>,
+    pub security: Arc<Security>,
+    pub reloader: Option<Arc<Reloader>>,
+    /// Routes built from this state, for requests the server sends itself
+    pub routes: LiveRouter,
 }
 
 pub struct BackworksServer {
     state: AppState,
+    middleware: MiddlewareRegistry,
+    load_balancers: LoadBalancerRegistry,
+    plugin_routes: Vec<(String, PluginRoutes)>,
+    tls: Option<Arc<Tls>>,
 }
 
 impl BackworksServer {
@@ -45,46 +124,279 @@ impl BackworksServer {
         let runtime_config = crate::runtime::RuntimeManagerConfig::default();
         let runtime_manager = RuntimeManager::new(runtime_config);
         
+        let trusted_headers = config.security.as_ref()
+            .and_then(|s| s.authentication.as_ref())
+            .and_then(|a| a.trusted_headers.clone())
+            .map(TrustedHeaderAuth::new)
+            .transpose()?
+            .map(Arc::new);
+        
+        let health = HealthChecker::new(&config, plugin_manager.clone());
+        let error_catalog = Arc::new(match config.errors {
+            Some(ref errors) => ErrorCatalog::new(errors)?,
+            None => ErrorCatalog::default(),
+        });
+        
+        let templates = Arc::new(EndpointTemplates::new(&config.endpoints)?);
+        let transforms = Arc::new(EndpointTransforms::new(&config.endpoints)?);
+        let coercions = Arc::new(EndpointCoercions::new(&config.endpoints)?);
+        let queries = Arc::new(EndpointQueries::new(&config.endpoints)?);
+        let limits = EndpointLimits::new(&config.endpoints)?;
+        let mocks = Arc::new(MockEngine::new(&config.endpoints)?
+            .with_session_header(config.state.as_ref().and_then(|s| s.mock_session_header.clone())));
+        let recorder = dashboard.as_ref().map(|dashboard| dashboard.metrics()).unwrap_or_default();
+        let proxies = Arc::new(ProxyEngine::new(&config.endpoints)?.with_metrics(recorder.clone()));
+        let localization = Arc::new(Localization::new(config.localization.as_ref())?);
+        let scenarios = Scenarios::new(&config)?;
+        let journal = config.journal.as_ref().map(Journal::open).transpose()?.map(Arc::new);
+        let chaos = Chaos::new(&config)?;
+        let schedules = Scheduler::new(&config)?;
+        let state_store = match config.state.as_ref().and_then(|s| s.persist.clone()) {
+            Some(path) => StateStore::persisted(path),
+            None => StateStore::new(),
+        };
+        let sessions = config.sessions.as_ref()
+            .map(|sessions| Sessions::new(sessions, &config, state_store.clone()))
+            .transpose()?
+            .map(Arc::new);
+        let security = Arc::new(Security::new(&config)?);
+        let metrics = RequestMetrics::new(&config);
+        crate::panic::install(config.logging.backtraces);
         let state = AppState {
             config,
             plugin_manager,
             runtime_manager,
             dashboard,
+            comparisons: ComparisonRecorder::new(),
+            stats: RequestStats::default(),
+            metrics,
+            recorder,
+            trusted_headers,
+            health,
+            state_store,
+            error_catalog,
+            templates,
+            transforms,
+            coercions,
+            queries,
+            limits,
+            mocks,
+            proxies,
+            localization,
+            scenarios,
+            journal,
+            chaos,
+            usage: UsageRecorder::default(),
+            capture: None,
+            switches: EndpointSwitches::default(),
+            pipelines: Pipelines::default(),
+            crud: CrudTables::default(),
+            schedules,
+            sessions,
+            security,
+            reloader: None,
+            routes: LiveRouter::default(),
         };
         
-        Ok(Self { state })
+        Ok(Self {
+            state,
+            middleware: MiddlewareRegistry::default(),
+            load_balancers: LoadBalancerRegistry::default(),
+            plugin_routes: Vec::new(),
+            tls: None,
+        })
+    }
+    
+    /// A server for a reloaded blueprint that keeps the runtime state of
+    /// the server running `state`
+    pub(crate) fn succeeding(config: BackworksConfig, state: &AppState) -> Result<Self> {
+        let journal_path = config.journal.as_ref().map(|journal| journal.path.clone());
+        let mut server = Self::new(Arc::new(config), state.plugin_manager.clone(), state.dashboard.clone())?;
+        let next = &mut server.state;
+        if journal_path.is_some() && journal_path == state.config.journal.as_ref().map(|journal| journal.path.clone()) {
+            next.journal = state.journal.clone();
+        }
+        next.comparisons = state.comparisons.clone();
+        next.stats = state.stats.clone();
+        next.metrics = state.metrics.clone();
+        next.limits = next.limits.carry_over(&state.limits);
+        next.state_store = state.state_store.clone();
+        // Server-side sessions live on in the carried-over store
+        next.sessions = next.config.sessions.as_ref()
+            .map(|sessions| Sessions::new(sessions, &next.config, next.state_store.clone()))
+            .transpose()?
+            .map(Arc::new);
+        next.scenarios = state.scenarios.clone();
+        next.chaos = state.chaos.clone();
+        next.usage = state.usage.clone();
+        next.capture = state.capture.clone();
+        next.switches = state.switches.clone();
+        // Schedules run from the blueprint the server started with
+        next.schedules = state.schedules.clone();
+        next.reloader = state.reloader.clone();
+        Ok(server)
+    }
+    
+    /// Resolve endpoint `middleware` names in `registry` instead of the
+    /// built-ins alone
+    pub fn with_middleware(mut self, registry: MiddlewareRegistry) -> Self {
+        self.middleware = registry;
+        self
+    }
+    
+    /// Resolve proxy `load_balancing` algorithms in `registry` instead of the
+    /// built-ins alone
+    pub fn with_load_balancers(mut self, registry: LoadBalancerRegistry) -> Self {
+        self.load_balancers = registry;
+        self
+    }
+    
+    /// Serve the routes plugins add next to the endpoints
+    pub fn with_plugin_routes(mut self, routes: Vec<(String, PluginRoutes)>) -> Self {
+        self.plugin_routes = routes;
+        self
+    }
+    
+    /// Share the active scenario with `scenarios`, such as the dashboard's
+    pub fn with_scenarios(mut self, scenarios: Scenarios) -> Self {
+        self.state.scenarios = scenarios;
+        self
+    }
+    
+    /// Share fault injection settings with `chaos`, such as the dashboard's
+    pub fn with_chaos(mut self, chaos: Chaos) -> Self {
+        self.state.chaos = chaos;
+        self
+    }
+    
+    /// Serve auto-CRUD endpoints from the tables in `crud`
+    pub fn with_crud_tables(mut self, crud: CrudTables) -> Self {
+        self.state.crud = crud;
+        self
+    }
+    
+    /// Record requests and their responses while `capture` has an active session
+    pub fn with_capture(mut self, capture: CaptureHandler) -> Self {
+        self.state.capture = Some(capture);
+        self
+    }
+    
+    /// Serve requests through `reloader`, so `POST /_backworks/reload` can
+    /// load the blueprint again
+    pub fn with_reloader(mut self, reloader: Arc<Reloader>) -> Self {
+        self.state.reloader = Some(reloader);
+        self
+    }
+    
+    /// Serve HTTPS with the certificate `tls` keeps, shared with the
+    /// dashboard, instead of one of the server's own
+    pub fn with_tls(mut self, tls: Arc<Tls>) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+    
+    /// Run the requests in the journal file through their handlers again,
+    /// without journaling them a second time
+    pub async fn replay_journal(&self) -> Result<ReplaySummary> {
+        let Some(ref journal) = self.state.journal else {
+            return Ok(ReplaySummary::default());
+        };
+        let entries = journal::load(journal.path()).await?;
+        Ok(replay_entries(&self.state, entries).await)
+    }
+    
+    /// Rolling request statistics shared with the alerting engine
+    pub fn request_stats(&self) -> RequestStats {
+        self.state.stats.clone()
     }
     
     pub async fn start(self) -> Result<()> {
-        let app = self.create_app();
+        // Built first so replayed requests see the server's load balancers
+        let app = self.served_app()?;
+        
+        if let Some(seed) = self.state.config.state.as_ref().and_then(|s| s.seed.as_ref()) {
+            let snapshot = StateSnapshot::load(seed).await?;
+            let summary = self.state.state_store.import(snapshot, ImportMode::Merge, None).await;
+            info!("🗃️  Seeded state store with {} key(s) from {}", summary.keys, seed.display());
+        }
+        if let Some(persisted) = self.state.state_store.persist_path().filter(|path| path.exists()) {
+            let snapshot = StateSnapshot::load(persisted).await?;
+            let summary = self.state.state_store.import(snapshot, ImportMode::Merge, None).await;
+            info!("🗃️  Restored {} state key(s) from {}", summary.keys, persisted.display());
+        }
+        if self.state.config.journal.as_ref().is_some_and(|j| j.replay_on_start) {
+            let summary = self.replay_journal().await?;
+            info!("📒 Replayed {} journaled request(s) ({} failed, {} duplicate)", summary.replayed, summary.failed, summary.duplicates);
+        }
+        
+        let retention = RetentionPolicy::from_config(&self.state.config)?;
+        if !retention.is_empty() {
+            crate::retention::spawn(self.state.clone(), retention);
+        }
+        if !self.state.schedules.is_empty() {
+            crate::schedule::spawn(self.state.clone());
+        }
         
-        let listener = tokio::net::TcpListener::bind(
-            format!("{}:{}", self.state.config.server.host, self.state.config.server.port)
-        ).await?;
+        let pools = DatabasePools::new(&self.state.config, &self.state.plugin_manager).await?;
+        if !pools.is_empty() {
+            pools.start(self.state.metrics.clone()).await;
+        }
         
-        info!("🌐 API server listening on {}", listener.local_addr()?);
+        let server = &self.state.config.server;
+        let listener = bind_listener(server).await?;
+        let address = listener.local_addr()?;
+        let stacks = match address {
+            SocketAddr::V6(v6) if v6.ip().is_unspecified() && !server.ipv6_only => " (IPv4 and IPv6)",
+            _ => "",
+        };
         
-        axum::serve(listener, app).await?;
+        let tls = match self.tls {
+            Some(ref tls) => Some(tls.clone()),
+            None => Tls::from_config(&self.state.config)?,
+        };
+        match tls {
+            Some(tls) => {
+                tls.start(app.clone()).await?;
+                info!("🔒 API server listening on {} over HTTPS{}", address, stacks);
+                crate::tls::serve(listener, tls.acceptor(), app).await?;
+            }
+            None => {
+                info!("🌐 API server listening on {}{}", address, stacks);
+                axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
+            }
+        }
         
         Ok(())
     }
     
-    fn create_app(&self) -> Router {
+    /// The app the listener serves; with a reloader, requests pass through
+    /// a router reloads can swap
+    fn served_app(&self) -> Result<Router> {
+        let Some(ref reloader) = self.state.reloader else {
+            return self.create_app();
+        };
+        self.serve_through(reloader)?;
+        Ok(reloader.service())
+    }
+    
+    /// Route the reloader's new requests to this server
+    pub(crate) fn serve_through(&self, reloader: &Reloader) -> Result<()> {
+        reloader.serve(self.state.clone(), self.create_app()?);
+        Ok(())
+    }
+    
+    pub(crate) fn create_app(&self) -> Result<Router> {
         let mut app = Router::new();
+        self.state.proxies.use_load_balancers(&self.load_balancers)?;
         
-        // Add global middleware
-        app = app.layer(
-            ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(self.create_cors_layer())
-                .layer(middleware::from_fn_with_state(
-                    self.state.clone(),
-                    request_middleware,
-                ))
-        );
-        
-        // Add health check endpoint
-        app = app.route("/health", get(health_check));
+        // Add liveness and readiness endpoints
+        let health = self.state.config.monitoring.as_ref().and_then(|m| m.health.as_ref());
+        let liveness_path = health.and_then(|h| h.endpoint.as_deref()).unwrap_or("/health");
+        let readiness_path = health.and_then(|h| h.ready_endpoint.as_deref()).unwrap_or("/ready");
+        app = app.route(liveness_path, get(health_check));
+        if health.and_then(|h| h.enabled).unwrap_or(true) {
+            app = app.route(readiness_path, get(readiness_check));
+        }
         
         // Add metrics endpoint if monitoring is enabled
         if let Some(ref monitoring) = &self.state.config.monitoring {
@@ -96,87 +408,416 @@ impl BackworksServer {
             }
         }
         
+        match self.state.config.admin {
+            Some(ref config) => app = app.merge(self.admin_routes(config)),
+            None => info!("Admin API disabled: it needs an admin.token"),
+        }
+        let mut reserved = vec![
+            ("admin API".to_string(), "/_backworks/{*rest}".to_string(), ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"].map(String::from).to_vec()),
+            ("liveness check".to_string(), liveness_path.to_string(), vec!["GET".to_string()]),
+            ("readiness check".to_string(), readiness_path.to_string(), vec!["GET".to_string()]),
+        ];
+        if let Some(endpoint) = self.state.config.monitoring.as_ref()
+            .and_then(|monitoring| monitoring.metrics.as_ref())
+            .filter(|metrics| metrics.enabled.unwrap_or(false))
+            .map(|metrics| metrics.export_endpoint.as_deref().unwrap_or("/metrics"))
+        {
+            reserved.push(("metrics export".to_string(), endpoint.to_string(), vec!["GET".to_string()]));
+        }
+        
+        // Serve the API reference next to the API
+        if let Some(ref docs) = self.state.config.docs {
+            let routes = [docs.path.clone(), docs.openapi_path()];
+            if !docs.path.starts_with('/') || RoutePattern::parse(&docs.path).params().next().is_some() {
+                return Err(BackworksError::config(format!("Docs path '{}' must be a fixed path starting with /", docs.path)));
+            }
+            if let Some((name, _)) = self.state.config.endpoints.iter()
+                .find(|(_, endpoint)| routes.contains(&RoutePattern::parse(&endpoint.path).router_path()))
+            {
+                return Err(BackworksError::config(format!("Endpoint '{}' takes the path of the docs at {}", name, docs.path)));
+            }
+            app = app
+                .route(&routes[0], get(docs_handler))
+                .route(&routes[1], get(openapi_handler));
+            for route in routes {
+                reserved.push(("API docs".to_string(), route, vec!["GET".to_string()]));
+            }
+        }
+        
         // Add dynamic endpoints based on configuration
         for (name, endpoint_config) in &self.state.config.endpoints {
             let path = &endpoint_config.path;
+            let pattern = Arc::new(RoutePattern::parse(path));
+            let pipeline = self.middleware.build(name, &endpoint_config.middleware)?;
+            if let Some(ref pipeline) = pipeline {
+                self.state.pipelines.insert(pipeline.clone());
+            }
             debug!("Registering endpoint: {} -> {}", name, path);
             
+            // Gate scheduled and partially rolled out endpoints
+            let rollout = endpoint_config.rollout.clone().map(|config| Arc::new(RolloutSchedule {
+                endpoint: name.clone(),
+                path: path.clone(),
+                config,
+            }));
+            
+            // Inject faults just before the handler
+            let chaos = endpoint_config.chaos.is_some().then(|| Arc::new(ChaosGate {
+                chaos: self.state.chaos.clone(),
+                endpoint: name.clone(),
+            }));
+            
+            // Check webhook signatures before anything reads the body
+            let webhook = endpoint_config.webhook.as_ref()
+                .map(|config| WebhookVerifier::new(name, config).map(Arc::new))
+                .transpose()?;
+            
+            // State-changing requests need their session's CSRF token
+            let csrf = self.state.sessions.clone()
+                .filter(|sessions| sessions.csrf().is_some() && crate::csrf::protects(endpoint_config));
+            
+            let layered = |mut method_router: MethodRouter<AppState>| {
+                if let Some(ref chaos) = chaos {
+                    method_router = method_router.route_layer(middleware::from_fn_with_state(chaos.clone(), inject_faults));
+                }
+                if let Some(ref pipeline) = pipeline {
+                    method_router = method_router.route_layer(middleware::from_fn_with_state(pipeline.clone(), run_pipeline));
+                }
+                if let Some(ref webhook) = webhook {
+                    method_router = method_router.route_layer(middleware::from_fn_with_state(webhook.clone(), verify_webhook));
+                }
+                if let Some(ref sessions) = csrf {
+                    method_router = method_router.route_layer(middleware::from_fn_with_state(sessions.clone(), verify_csrf));
+                }
+                // Added last so requests outside the rollout never reach the pipeline
+                if let Some(ref rollout) = rollout {
+                    method_router = method_router.route_layer(middleware::from_fn_with_state(rollout.clone(), rollout_gate));
+                }
+                method_router
+            };
+            
+            // Auto-CRUD endpoints serve their table at the path and one row below it
+            if crate::crud::is_auto_crud(endpoint_config) {
+                for (route, method_router) in self.state.crud.method_routers(name, endpoint_config)? {
+                    app = app.route(&route, layered(method_router));
+                }
+                continue;
+            }
+            
+            // Static endpoints serve their directory at the path and below it
+            if matches!(endpoint_config.primary_mode(&self.state.config.mode), ExecutionMode::Static) {
+                let files = endpoint_config.static_files.as_ref().ok_or_else(|| {
+                    BackworksError::config(format!("Endpoint '{}' runs in static mode but has no `static.dir`", name))
+                })?;
+                let method_router = layered(get(create_static_handler(Arc::new(StaticFiles::new(files)))));
+                for route in static_routes(&pattern) {
+                    app = app.route(&route, method_router.clone());
+                }
+                continue;
+            }
+            
             // Create handler for each HTTP method
             for method in &endpoint_config.methods {
-                let handler = create_endpoint_handler(method.clone(), name.clone());
+                let handler = create_endpoint_handler(method.clone(), name.clone(), pattern.clone());
                 
-                app = match method.as_str() {
-                    "GET" => app.route(path, get(handler)),
-                    "POST" => app.route(path, post(handler)),
-                    "PUT" => app.route(path, put(handler)),
-                    "DELETE" => app.route(path, delete(handler)),
-                    "PATCH" => app.route(path, axum::routing::patch(handler)),
-                    _ => app.route(path, any(handler)),
+                let method_router = match method.as_str() {
+                    "GET" => get(handler),
+                    "POST" => post(handler),
+                    "PUT" => put(handler),
+                    "DELETE" => delete(handler),
+                    "PATCH" => axum::routing::patch(handler),
+                    _ => any(handler),
                 };
+                app = app.route(&pattern.router_path(), layered(method_router));
+            }
+        }
+        
+        // Plugin routes may not take the place of the server's own
+        if !self.plugin_routes.is_empty() {
+            let endpoints: Vec<(String, String, Vec<String>)> = self.state.config.endpoints.iter()
+                .flat_map(|(name, endpoint)| {
+                    if matches!(endpoint.primary_mode(&self.state.config.mode), ExecutionMode::Static) {
+                        let pattern = RoutePattern::parse(&endpoint.path);
+                        static_routes(&pattern).into_iter().map(|route| (name.clone(), route, vec!["GET".to_string()])).collect()
+                    } else if crate::crud::is_auto_crud(endpoint) {
+                        crate::crud::routes(endpoint).into_iter().map(|(route, methods)| (name.clone(), route, methods)).collect()
+                    } else {
+                        vec![(name.clone(), endpoint.path.clone(), endpoint.methods.clone())]
+                    }
+                })
+                .collect();
+            check_plugin_routes(&self.plugin_routes, &reserved, &endpoints)?;
+            for (plugin, routes) in &self.plugin_routes {
+                debug!("Registering routes of plugin {}", plugin);
+                app = routes.mount(app, "");
             }
         }
         
-        app.with_state(self.state.clone())
+        // Unmatched routes go through the same error pipeline as handler failures
+        app = app.fallback(not_found_handler);
+        
+        // Add global middleware last so it wraps every route and the fallback;
+        // each layer wraps the ones added before it
+        if let Some(ref sessions) = self.state.sessions {
+            app = app.layer(middleware::from_fn_with_state(sessions.clone(), manage_sessions));
+        }
+        let body_limits = Arc::new(BodyLimits::from_config(&self.state.config)?);
+        app = app
+            .layer(middleware::from_fn_with_state(body_limits, limit_request_body))
+            .layer(axum::extract::DefaultBodyLimit::disable())
+            .layer(middleware::from_fn_with_state(self.state.clone(), request_middleware))
+            .layer(middleware::from_fn_with_state(self.state.clone(), recover_panics))
+            .layer(middleware::from_fn_with_state(self.state.security.clone(), secure_responses));
+        if let Some(ref capture) = self.state.capture {
+            app = app.layer(middleware::from_fn_with_state(capture.clone(), capture_exchanges));
+        }
+        // Compressed last, so hooks and capture see the response as it was produced
+        let compression = CompressionPolicy::new(&self.state.config)?;
+        if compression.is_enabled() {
+            app = app.layer(compression.layer());
+        }
+        if let Some(cors) = self.create_cors()? {
+            app = app.layer(middleware::from_fn_with_state(cors, cors_middleware));
+        }
+        app = app.layer(TraceLayer::new_for_http());
+        
+        if let Some(ref dashboard) = self.state.dashboard {
+            dashboard.show_blueprint(self.state.config.clone());
+        }
+        let app = app.with_state(self.state.clone());
+        self.state.routes.replace(app.clone());
+        Ok(app)
     }
     
-    fn create_cors_layer(&self) -> CorsLayer {
-        let mut cors = CorsLayer::new();
+    /// Runtime control routes under `/_backworks`, behind the admin token;
+    /// without a token they are not served at all
+    fn admin_routes(&self, config: &crate::config::AdminConfig) -> Router<AppState> {
+        let mut admin = Router::new()
+            .route("/_backworks/endpoints", get(admin::endpoints_handler))
+            .route("/_backworks/endpoints/:name", put(admin::switch_endpoint_handler))
+            .route("/_backworks/caches/flush", post(admin::flush_caches_handler))
+            .route("/_backworks/plugins", get(admin::plugins_handler))
+            .route("/_backworks/plugins/:name/reload", post(admin::reload_plugin_handler));
         
-        if let Some(ref security) = &self.state.config.security {
-            if let Some(ref cors_config) = &security.cors {
-                if cors_config.enabled.unwrap_or(false) {
-                    if let Some(ref origins) = &cors_config.origins {
-                        for origin in origins {
-                            // Parse as HeaderValue and create AllowOrigin
-                            if let Ok(header_value) = origin.parse::<http::HeaderValue>() {
-                                let allow_origin = tower_http::cors::AllowOrigin::exact(header_value);
-                                cors = cors.allow_origin(allow_origin);
-                            }
-                        }
-                    } else {
-                        cors = cors.allow_origin(Any);
-                    }
-                    
-                    if let Some(ref methods) = &cors_config.methods {
-                        let parsed_methods: Vec<Method> = methods
-                            .iter()
-                            .filter_map(|m| m.parse().ok())
-                            .collect();
-                        cors = cors.allow_methods(parsed_methods);
-                    }
-                    
-                    if let Some(ref headers) = cors_config.headers {
-                        for header in headers {
-                            cors = cors.allow_headers([header.parse().unwrap()]);
-                        }
-                    }
-                    
-                    if cors_config.credentials.unwrap_or(false) {
-                        cors = cors.allow_credentials(true);
-                    }
-                }
+        // Page through the request log the dashboard keeps
+        if self.state.dashboard.is_some() {
+            admin = admin.route("/_backworks/requests", get(admin::requests_handler));
+        }
+        
+        // Reload the blueprint the server was started from
+        if self.state.reloader.is_some() {
+            admin = admin.route("/_backworks/reload", post(admin::reload_handler));
+        }
+        
+        // Start and stop capture sessions without the dashboard
+        if self.state.capture.is_some() {
+            admin = admin
+                .route("/_backworks/capture", get(admin::capture_handler).put(admin::switch_capture_handler))
+                .route("/_backworks/capture/sessions", get(admin::capture_sessions_handler));
+        }
+        
+        // Expose baseline comparison reports when any endpoint compares responses
+        if self.state.config.endpoints.values().any(|e| e.compare.as_ref().is_some_and(|c| c.enabled)) {
+            admin = admin.route("/_backworks/comparisons", get(comparisons_handler));
+        }
+        
+        // Expose state store export/import for fixture seeding and debugging
+        if let Some(ref state_config) = self.state.config.state {
+            if state_config.admin_api.unwrap_or(false) {
+                admin = admin
+                    .route("/_backworks/state/export", get(state_export_handler))
+                    .route("/_backworks/state/import", post(state_import_handler));
             }
         }
         
-        cors
+        // Replay journals from other servers, such as a crashed instance's
+        if self.state.config.journal.as_ref().is_some_and(|j| j.admin_api.unwrap_or(false)) {
+            admin = admin.route("/_backworks/journal/replay", post(journal_replay_handler));
+        }
+        
+        // Let QA switch scenarios without restarting
+        let scenarios_api = self.state.config.scenarios.as_ref().and_then(|s| s.admin_api).unwrap_or(true);
+        if !self.state.scenarios.is_empty() && scenarios_api {
+            admin = admin
+                .route("/_backworks/scenarios", get(scenarios_handler))
+                .route("/_backworks/scenarios/active", put(activate_scenario_handler).delete(deactivate_scenario_handler));
+        }
+        
+        // Let resilience tests tune or stop fault injection while running
+        if self.state.config.endpoints.values().any(|e| e.chaos.is_some()) {
+            admin = admin
+                .route("/_backworks/chaos", get(chaos_handler).put(switch_chaos_handler))
+                .route("/_backworks/chaos/:endpoint", put(configure_chaos_handler));
+        }
+        
+        // Let operators drain, quarantine and re-enable proxy targets
+        if !self.state.proxies.is_empty() {
+            admin = admin
+                .route("/_backworks/proxy/targets", get(proxy_targets_handler))
+                .route("/_backworks/proxy/targets/:endpoint/:operation", post(operate_proxy_target_handler));
+        }
+        
+        // Compare the blueprint with the traffic it actually receives
+        admin = admin.route("/_backworks/usage-report", get(usage_report_handler));
+        
+        // Remove stored data on demand, beyond what retention policies remove
+        admin = admin.route("/_backworks/purge", post(purge_handler));
+        
+        // Follow scheduled jobs and run them on demand
+        if !self.state.schedules.is_empty() {
+            admin = admin
+                .route("/_backworks/schedules", get(admin::schedules_handler))
+                .route("/_backworks/schedules/:name/runs", get(admin::schedule_runs_handler))
+                .route("/_backworks/schedules/:name/run", post(admin::run_schedule_handler));
+        }
+        
+        admin.route_layer(middleware::from_fn_with_state(Arc::<str>::from(config.token.as_str()), require_token))
+    }
+    
+    fn create_cors(&self) -> Result<Option<Cors>> {
+        let Some(cors_config) = self.state.config.security.as_ref().and_then(|s| s.cors.as_ref()) else {
+            return Ok(None);
+        };
+        if !cors_config.enabled.unwrap_or(false) {
+            return Ok(None);
+        }
+        Ok(Some(Cors {
+            policies: Arc::new(CorsPolicies::new(cors_config)?),
+            metrics: self.state.metrics.clone(),
+        }))
     }
 }
 
-// Middleware for request processing and plugin hooks
+/// Endpoint a request was routed to, attached to request extensions before
+/// plugin hooks run and to the extensions of the response it produced
+#[derive(Debug, Clone)]
+pub struct MatchedEndpoint(pub String);
+
+/// An event a handler returned under `events`, for plugins to publish
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HandlerEvent {
+    pub topic: String,
+    /// Partitioning or deduplication key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(default)]
+    pub data: Value,
+}
+
+/// Events a handler emitted, attached to the extensions of its response
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HandlerEvents(pub Vec<HandlerEvent>);
+
+/// Emails a handler returned under `emails` (what `ctx.email.send()`
+/// collects), as written, for an email plugin to render and send
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HandlerEmails(pub Vec<Value>);
+
+/// Jobs a handler returned under `jobs` (what `ctx.jobs.enqueue()`
+/// collects), as written, for a jobs plugin to queue
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HandlerJobs(pub Vec<Value>);
+
+// Middleware for request processing and plugin hooks.
+//
+// Every request, including ones rejected by a critical plugin, unmatched
+// routes and handler failures, leaves through the same trailing pipeline:
+// error normalization, plugin `on_error` hooks, plugin `after_response`
+// hooks and metrics recording, in that order.
 async fn request_middleware(
     State(state): State<AppState>,
     mut request: axum::extract::Request,
     next: axum::middleware::Next,
 ) -> axum::response::Response {
     let start_time = std::time::Instant::now();
+    let _in_flight = state.health.track_request();
+    let method = request.method().to_string();
+    let request_path = request.uri().path().to_string();
+    
+    // Resolve identity from trusted gateway headers, then call before_request
+    // hooks on all plugins; a failure in either short-circuits the handler but
+    // not the trailing pipeline
+    let endpoint = matched_endpoint(&state, &request);
+    if let Some(ref endpoint) = endpoint {
+        request.extensions_mut().insert(endpoint.clone());
+    }
+    // The endpoint's own timeout replaces the server's
+    let configured_timeout = endpoint.as_ref()
+        .and_then(|MatchedEndpoint(name)| state.config.endpoints.get(name)?.timeout.as_deref())
+        .or(state.config.server.request_timeout.as_deref())
+        .and_then(|timeout| crate::config::parse_duration(timeout).ok());
+    let deadline = Deadline::for_request(configured_timeout, request.headers());
+    if let Some(deadline) = deadline {
+        request.extensions_mut().insert(deadline);
+    }
+    request.extensions_mut().insert(state.recorder.clone());
+    let mut origin = None;
+    let mut caller = None;
+    let mut upstream = None;
+    let disabled = endpoint.as_ref().filter(|MatchedEndpoint(name)| !state.switches.is_enabled(name));
+    let mut response = if let Some(MatchedEndpoint(name)) = disabled {
+        // Switched off through the admin API
+        BackworksError::unavailable(format!("Endpoint '{}' is disabled", name)).into_response()
+    } else {
+        match authenticate_request(&state, &mut request) {
+            Err(e) => e.into_response(),
+            Ok(()) => match state.plugin_manager.before_request(&mut request).await {
+                Ok(()) => {
+                    origin = request_origin(&request);
+                    if let Some(ref origin) = origin {
+                        request.extensions_mut().insert(origin.clone());
+                    }
+                    caller = request.extensions().get::<AuthContext>().cloned();
+                    // A handler still running when the budget runs out is dropped
+                    let handled = Deadline::run(deadline, "the handler", async { Ok(next.run(request).await) });
+                    let (response, target) = crate::request_log::tracking_upstream(handled).await;
+                    upstream = target;
+                    response.unwrap_or_else(|e| {
+                        let mut response = e.into_response();
+                        if let Some(ref endpoint) = endpoint {
+                            response.extensions_mut().insert(endpoint.clone());
+                        }
+                        response
+                    })
+                }
+                Err(e) => {
+                    error!("Plugin before_request hook failed: {}", e);
+                    e.into_response()
+                }
+            },
+        }
+    };
+    
+    // Make sure every error response carries its final error
+    if (response.status().is_client_error() || response.status().is_server_error())
+        && response.extensions().get::<RequestError>().is_none()
+    {
+        let source = if response.extensions().get::<MatchedEndpoint>().is_some() {
+            ErrorSource::Handler
+        } else {
+            ErrorSource::Framework
+        };
+        let status = response.status();
+        let message = status.canonical_reason().unwrap_or("Request failed");
+        response.extensions_mut().insert(RequestError::new(status, message, source));
+    }
     
-    // Call before_request hooks on all plugins
-    if let Err(e) = state.plugin_manager.before_request(&mut request).await {
-        error!("Plugin before_request hook failed: {}", e);
+    // Call on_error hooks so plugins can map the error response
+    if let Some(request_error) = response.extensions().get::<RequestError>().cloned() {
+        if let Err(e) = state.plugin_manager.on_error(&request_error, &mut response).await {
+            error!("Plugin on_error hook failed: {}", e);
+        }
     }
     
-    // Process request through middleware chain
-    let mut response = next.run(request).await;
+    // Announce deprecated endpoints and note who still calls them
+    let deprecation = endpoint.as_ref()
+        .and_then(|MatchedEndpoint(name)| Some((name, state.config.endpoints.get(name)?.deprecated.as_ref()?)));
+    if let Some((name, deprecation)) = deprecation {
+        crate::deprecation::apply_headers(deprecation, response.headers_mut());
+        if let Some(ref dashboard) = state.dashboard {
+            dashboard.record_deprecated_call(name, caller.as_ref()).await;
+        }
+    }
     
     // Call after_response hooks on all plugins
     if let Err(e) = state.plugin_manager.after_response(&mut response).await {
@@ -184,110 +825,468 @@ async fn request_middleware(
     }
     
     let duration = start_time.elapsed();
-    debug!("Request processed in {:?}", duration);
+    let origin = origin.unwrap_or_default();
+    let endpoint = endpoint.map(|MatchedEndpoint(name)| name);
+    let labels = endpoint.as_deref()
+        .map(|name| state.metrics.dimensions(name).iter().map(|(label, value)| format!("{}={}", label, value)).collect::<Vec<_>>().join(","))
+        .unwrap_or_default();
+    // Logged paths and targets leave out secrets, with `strip_secrets`
+    let logged_path = state.security.scrub(request_path.clone());
+    info!(
+        target: "backworks::access",
+        method = %method,
+        path = %logged_path,
+        endpoint = endpoint.as_deref().unwrap_or("-"),
+        labels = %labels,
+        status = response.status().as_u16(),
+        duration_ms = duration.as_millis() as u64,
+        client = origin.key("ip").as_deref().unwrap_or("-"),
+        country = origin.country.as_deref().unwrap_or("-"),
+        asn = origin.asn.unwrap_or_default(),
+        "request"
+    );
+    
+    // Record the final status, after plugins had a chance to remap it
+    state.stats.record(response.status().as_u16(), duration).await;
+    state.metrics.record(endpoint.as_deref(), &method, response.status().as_u16(), duration);
+    state.usage.record(&method, &request_path, endpoint.as_deref(), response.status().as_u16()).await;
+    if let Some(ref dashboard) = state.dashboard {
+        // Unmatched paths share one key, so probes cannot grow the histograms
+        let path = match response.extensions().get::<MatchedEndpoint>() {
+            Some(MatchedEndpoint(name)) => format!("/{}", name),
+            None => format!("/({})", crate::request_metrics::UNMATCHED),
+        };
+        if let Err(e) = dashboard.record_request(&method, &path, duration, response.status().as_u16()).await {
+            error!("Failed to record request to dashboard: {}", e);
+        }
+        // Reading the request log, as `backworks console` does while
+        // following it, does not add to it
+        if request_path != "/_backworks/requests" {
+            dashboard.log_request(crate::request_log::LoggedRequest {
+                method: method.clone(),
+                path: logged_path,
+                status: response.status().as_u16(),
+                duration,
+                size: axum::body::HttpBody::size_hint(response.body()).exact(),
+                endpoint: endpoint.clone(),
+                upstream: upstream.map(|target| state.security.scrub(target)),
+            });
+        }
+    }
+    
+    response
+}
+
+/// Bind the API listener. An IPv6 host accepts IPv4 clients too, as
+/// IPv4-mapped addresses, unless `ipv6_only` is set
+async fn bind_listener(config: &ServerConfig) -> Result<tokio::net::TcpListener> {
+    let host = config.host.trim_start_matches('[').trim_end_matches(']');
+    let address = match host.parse::<IpAddr>() {
+        Ok(ip) => SocketAddr::new(ip, config.port),
+        Err(_) => tokio::net::lookup_host((host, config.port)).await?
+            .next()
+            .ok_or_else(|| BackworksError::config(format!("Host '{}' does not resolve to an address", config.host)))?,
+    };
+    
+    let socket = socket2::Socket::new(socket2::Domain::for_address(address), socket2::Type::STREAM, Some(socket2::Protocol::TCP))?;
+    if address.is_ipv6() {
+        socket.set_only_v6(config.ipv6_only)?;
+    }
+    // As tokio's own bind does, so restarts do not wait out TIME_WAIT
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&address.into())?;
+    socket.listen(1024)?;
+    Ok(tokio::net::TcpListener::from_std(socket.into())?)
+}
+
+/// Endpoint whose route the router matched, by route path and method
+pub(crate) fn matched_endpoint(state: &AppState, request: &axum::extract::Request) -> Option<MatchedEndpoint> {
+    let matched = request.extensions().get::<MatchedPath>()?;
+    let method = request.method().as_str();
+    state.config.endpoints.iter()
+        .find(|(_, endpoint)| {
+            let served = |m: &String| m.eq_ignore_ascii_case(method) || (method == "HEAD" && m.eq_ignore_ascii_case("GET"));
+            if crate::crud::is_auto_crud(endpoint) {
+                return crate::crud::routes(endpoint).iter()
+                    .any(|(route, methods)| RoutePattern::parse(route).router_path() == matched.as_str() && methods.iter().any(served));
+            }
+            let pattern = RoutePattern::parse(&endpoint.path);
+            let routed = if matches!(endpoint.primary_mode(&state.config.mode), ExecutionMode::Static) {
+                static_routes(&pattern).iter().any(|route| route == matched.as_str())
+            } else {
+                pattern.router_path() == matched.as_str()
+            };
+            // GET routes answer HEAD too
+            routed && endpoint.methods.iter().any(served)
+        })
+        .map(|(name, _)| MatchedEndpoint(name.clone()))
+}
+
+/// Catch-all parameter holding the file path below a static endpoint
+const STATIC_PATH_PARAM: &str = "static_path";
+
+/// Routes of a static endpoint: its path, with a trailing slash, and everything below it
+fn static_routes(pattern: &RoutePattern) -> Vec<String> {
+    let base = pattern.router_path();
+    let prefix = base.trim_end_matches('/');
+    let mut routes = vec![base.clone(), format!("{}/*{}", prefix, STATIC_PATH_PARAM)];
+    if base != "/" {
+        routes.push(format!("{}/", prefix));
+    }
+    routes
+}
+
+#[allow(clippy::type_complexity)]
+fn create_static_handler(
+    files: Arc<StaticFiles>,
+) -> impl Fn(Method, Option<Path<HashMap<String, String>>>, HeaderMap) -> std::pin::Pin<Box<dyn std::future::Future<Output = axum::response::Response> + Send>> + Clone + Send + Sync + 'static {
+    move |method, path, headers| {
+        let files = files.clone();
+        Box::pin(async move {
+            let relative = path
+                .and_then(|Path(mut params)| params.remove(STATIC_PATH_PARAM))
+                .unwrap_or_default();
+            files.serve(&relative, &headers, method == Method::HEAD).await
+        })
+    }
+}
+
+/// Origin attached by an enrichment plugin, or just the peer address
+fn request_origin(request: &axum::extract::Request) -> Option<RequestOrigin> {
+    request.extensions().get::<RequestOrigin>().cloned().or_else(|| {
+        request.extensions()
+            .get::<axum::extract::ConnectInfo<SocketAddr>>()
+            .map(|info| RequestOrigin { ip: Some(info.0.ip().to_canonical()), ..Default::default() })
+    })
+}
+
+fn authenticate_request(state: &AppState, request: &mut axum::extract::Request) -> Result<()> {
+    let Some(ref trusted_headers) = state.trusted_headers else {
+        return Ok(());
+    };
+    
+    let peer = request.extensions()
+        .get::<axum::extract::ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip().to_canonical());
+    let certificate = request.extensions().get::<PeerCertificate>().cloned();
+    
+    if let Some(context) = trusted_headers.authenticate(request.headers_mut(), peer, certificate.as_ref())? {
+        debug!("Authenticated {} via trusted headers", context.subject);
+        request.extensions_mut().insert(context);
+    }
+    Ok(())
+}
+
+// Route requests outside an endpoint's rollout as if the endpoint did not exist
+async fn rollout_gate(
+    State(rollout): State<Arc<RolloutSchedule>>,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let sticky_key = rollout.config.sticky_header.as_deref()
+        .and_then(|header| request.headers().get(header))
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+        .or_else(|| request.extensions().get::<AuthContext>().map(|auth| auth.subject.clone()))
+        .or_else(|| {
+            request.extensions()
+                .get::<axum::extract::ConnectInfo<SocketAddr>>()
+                .map(|info| info.0.ip().to_canonical().to_string())
+        });
     
+    if rollout.admits(chrono::Utc::now(), sticky_key.as_deref()) {
+        next.run(request).await
+    } else {
+        debug!("Request to '{}' outside its rollout", rollout.endpoint);
+        not_found_handler(request.uri().clone()).await
+    }
+}
+
+// Fallback for requests that match no route
+async fn not_found_handler(uri: axum::http::Uri) -> axum::response::Response {
+    let message = format!("No endpoint matches {}", uri.path());
+    let body = Json(serde_json::json!({"error": message}));
+    let mut response = (StatusCode::NOT_FOUND, body).into_response();
+    response.extensions_mut().insert(RequestError::new(StatusCode::NOT_FOUND, message, ErrorSource::Framework));
     response
 }
 
 // Create handler function for specific endpoint and method
+#[allow(clippy::type_complexity)]
 fn create_endpoint_handler(
     method: String,
     endpoint_name: String,
-) -> impl Fn(State<AppState>, axum::extract::OriginalUri, Path<HashMap<String, String>>, Query<HashMap<String, String>>, HeaderMap, Option<axum::extract::Json<Value>>) -> std::pin::Pin<Box<dyn std::future::Future<Output = axum::response::Result<(StatusCode, Json<Value>)>> + Send>> + Clone + Send + Sync + 'static {
-    move |state, original_uri, path, query, headers, body| {
+    pattern: Arc<RoutePattern>,
+) -> impl Fn(State<AppState>, axum::extract::OriginalUri, Path<HashMap<String, String>>, Query<HashMap<String, String>>, HeaderMap, Option<Extension<AuthContext>>, Option<Extension<RequestOrigin>>, Option<Extension<Deadline>>, Option<Extension<Session>>, axum::body::Bytes) -> std::pin::Pin<Box<dyn std::future::Future<Output = axum::response::Response> + Send>> + Clone + Send + Sync + 'static {
+    move |state, original_uri, path, query, headers, auth, origin, deadline, session, body| {
         let method = method.clone();
         let endpoint_name = endpoint_name.clone();
+        let pattern = pattern.clone();
         
         Box::pin(async move {
-            handle_endpoint_request(state, original_uri, method, endpoint_name, path, query, headers, body).await
+            handle_endpoint_request(state, original_uri, method, endpoint_name, pattern, path, query, headers, auth, origin, deadline, session, body).await
         })
     }
 }
 
 // Main endpoint request handler
+#[allow(clippy::too_many_arguments)]
 async fn handle_endpoint_request(
     State(state): State<AppState>,
     axum::extract::OriginalUri(original_uri): axum::extract::OriginalUri,
     method: String,
     endpoint_name: String,
+    pattern: Arc<RoutePattern>,
     Path(path_params): Path<HashMap<String, String>>,
     Query(query_params): Query<HashMap<String, String>>,
     headers: HeaderMap,
-    body: Option<axum::extract::Json<Value>>,
-) -> axum::response::Result<(StatusCode, Json<Value>)> {
+    auth: Option<Extension<AuthContext>>,
+    origin: Option<Extension<RequestOrigin>>,
+    deadline: Option<Extension<Deadline>>,
+    session: Option<Extension<Session>>,
+    body: axum::body::Bytes,
+) -> axum::response::Response {
+    let auth = auth.map(|Extension(context)| context);
+    let origin = origin.map(|Extension(origin)| origin);
+    let deadline = deadline.map(|Extension(deadline)| deadline);
+    let session = session.map(|Extension(session)| session);
+    
+    // Typed parameters are converted before the handler sees them
+    let path_params = match pattern.extract(path_params) {
+        Ok(params) => params,
+        Err(message) => {
+            let mut response = (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({"error": message, "status": 400}))
+            ).into_response();
+            response.extensions_mut().insert(RequestError::new(StatusCode::BAD_REQUEST, message, ErrorSource::Framework));
+            response.extensions_mut().insert(MatchedEndpoint(endpoint_name));
+            return response;
+        }
+    };
+    
+    // Beyond the endpoint's concurrency limit requests wait or are turned away
+    let _slot = match state.limits.acquire(&endpoint_name, deadline, &state.metrics).await {
+        Ok(slot) => slot,
+        Err(rejection) => {
+            let mut response = rejection.into_response();
+            response.extensions_mut().insert(MatchedEndpoint(endpoint_name));
+            return response;
+        }
+    };
+    
+    // Journaled requests are on disk before the handler changes anything
+    if let Some(journal) = state.journal.as_ref().filter(|journal| journal.records(&endpoint_name, &method)) {
+        let uri = original_uri.path_and_query().map_or(original_uri.path(), |uri| uri.as_str());
+        let entry = JournalEntry::new(&endpoint_name, &method, uri, &path_params, &query_params, &headers, auth.as_ref(), &body);
+        if let Err(e) = journal.append(&entry).await {
+            error!("Failed to journal request to endpoint '{}': {}", endpoint_name, e);
+            let message = "Request could not be journaled";
+            let mut response = (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(serde_json::json!({"error": message, "status": 503}))
+            ).into_response();
+            response.extensions_mut().insert(RequestError::new(StatusCode::SERVICE_UNAVAILABLE, message, ErrorSource::Framework));
+            response.extensions_mut().insert(MatchedEndpoint(endpoint_name));
+            return response;
+        }
+    }
+    
+    let mut response = execute_endpoint_request(&state, original_uri, &method, &endpoint_name, path_params, query_params, headers, auth, origin, deadline, session, body).await;
+    response.extensions_mut().insert(MatchedEndpoint(endpoint_name));
+    response
+}
+
+// Run the server's own journaled requests through their handlers in order,
+// as their recorded identity, skipping retries of an idempotency key
+async fn replay_entries(state: &AppState, entries: Vec<JournalEntry>) -> ReplaySummary {
+    let (entries, duplicates) = journal::deduplicate(entries);
+    let mut summary = ReplaySummary { duplicates, ..Default::default() };
+    for entry in entries {
+        let body = match entry.body_bytes() {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Skipping journal entry {}: {}", entry.id, e);
+                summary.failed += 1;
+                continue;
+            }
+        };
+        let uri = entry.uri.parse().unwrap_or_else(|_| axum::http::Uri::from_static("/"));
+        let headers = entry.header_map();
+        let response = execute_endpoint_request(
+            state, uri, &entry.method, &entry.endpoint, entry.path_params.clone(), entry.query_params.clone(), headers, entry.auth.clone(), None, None, None, body.into(),
+        ).await;
+        summary.count(&entry, response.status());
+    }
+    summary
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn execute_endpoint_request(
+    state: &AppState,
+    original_uri: axum::http::Uri,
+    method: &str,
+    endpoint_name: &str,
+    path_params: HashMap<String, Value>,
+    query_params: HashMap<String, String>,
+    headers: HeaderMap,
+    auth: Option<AuthContext>,
+    origin: Option<RequestOrigin>,
+    deadline: Option<Deadline>,
+    session: Option<Session>,
+    body: axum::body::Bytes,
+) -> axum::response::Response {
     debug!("Handling {} request to endpoint: {}", method, endpoint_name);
     
     // Extract the original path from the original URI
     let original_path = original_uri.path().to_string();
     debug!("Original request path: {}", original_path);
     
-    // Record request start time for dashboard metrics
-    let start_time = std::time::Instant::now();
-    
-    let endpoint_config = match state.config.endpoints.get(&endpoint_name) {
+    let endpoint_config = match state.config.endpoints.get(endpoint_name) {
         Some(config) => config,
         None => {
-            return Ok((
+            let mut response = (
                 StatusCode::NOT_FOUND,
                 Json(serde_json::json!({"error": "Endpoint not found"}))
-            ));
+            ).into_response();
+            response.extensions_mut().insert(RequestError::new(StatusCode::NOT_FOUND, "Endpoint not found", ErrorSource::Framework));
+            return response;
         }
     };
     
-    // Determine execution mode for this endpoint
-    let mode = endpoint_config.mode.as_ref().unwrap_or(&state.config.mode);
+    // Determine the execution modes to try for this endpoint
+    let modes = endpoint_config.mode_chain(&state.config.mode);
+    
+    // Bodies in the endpoint's input format reach the handler as JSON, with
+    // declared parameters converted to their types
+    let content_type = headers.get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok());
+    let body = match state.transforms.decode_request(endpoint_name, content_type, &body) {
+        Ok(body) => body.map(|body| state.coercions.coerce(endpoint_name, body)),
+        Err(message) => {
+            let mut response = (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({"error": message, "status": 400}))
+            ).into_response();
+            response.extensions_mut().insert(RequestError::new(StatusCode::BAD_REQUEST, message, ErrorSource::Framework));
+            return response;
+        }
+    };
+    let mut headers = headers;
+    if state.transforms.converts_request(endpoint_name) {
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    }
     
-    let request_data = crate::server::RequestData {
-        method: method.clone(),
+    let mut request_data = crate::server::RequestData {
+        method: method.to_string(),
         path: original_path.clone(),
         path_params,
         query_params,
         headers: headers.clone(),
-        body: body.map(|b| b.0),
+        body,
+        auth,
+        origin,
+        locale: Some(state.localization.context(&headers)),
+        deadline,
+        csrf_token: session.as_ref().zip(state.sessions.as_ref()).and_then(|(session, sessions)| sessions.csrf_token(session)),
+        session: session.map(|session| session.data),
     };
-
-    // Serialize request data for handlers that need string representation
-    let request_data_json = serde_json::to_string(&request_data)
-        .map_err(|e| BackworksError::Json(e))?;
     
-    let result = match mode {
-        ExecutionMode::Runtime => {
-            if let Some(ref runtime_config) = endpoint_config.runtime {
-                state.runtime_manager.handle_request(runtime_config, &request_data_json).await
-            } else {
-                Err(BackworksError::config("Runtime mode requires runtime configuration"))
+    // Only runtime handlers answer with files; proxied, mocked and
+    // recorded bodies are data
+    let mut from_runtime = false;
+    
+    // Rewrite the payload with the endpoint's request template, then its transform
+    let result = match state.templates.render_request(endpoint_name, &request_data) {
+        Ok(rewritten) => {
+            if let Some(body) = rewritten {
+                request_data.body = Some(body);
+            }
+            request_data.body = request_data.body.take().map(|body| state.transforms.transform_request(endpoint_name, body));
+            // An active scenario answers in place of the handler
+            match state.scenarios.respond(endpoint_name, &request_data.headers).await {
+                Some(output) => Ok(output),
+                None => execute_modes(state, &modes, endpoint_name, endpoint_config, method, &request_data).await
+                    .map(|(mode, output)| {
+                        from_runtime = *mode == ExecutionMode::Runtime;
+                        output
+                    }),
             }
         }
-        ExecutionMode::Database => {
-            // Database mode now requires plugins to handle the actual database operations
-            debug!("Database mode endpoint - delegating to plugins");
-            
-            // Let plugins handle database operations with simple data interface
-            let data_str = serde_json::to_string(&request_data)
-                .map_err(|e| BackworksError::plugin(format!("Failed to serialize request data: {}", e)))?;
-            
-            match state.plugin_manager.process_endpoint_data(&endpoint_name, &method, &data_str).await {
-                Ok(Some(response)) => Ok(response),
-                Ok(None) => Err(BackworksError::config("No plugin handled database endpoint")),
-                Err(e) => Err(e),
+        Err(e) => Err(e),
+    };
+    
+    // Compare against the configured baseline and optionally serve it instead
+    let result = match endpoint_config.compare {
+        Some(ref compare) if compare.enabled => {
+            if compare.serve == CompareServe::Baseline {
+                match (&compare.baseline.response, &compare.baseline.mode) {
+                    (Some(_), _) => from_runtime = false,
+                    (None, Some(mode)) => from_runtime = *mode == ExecutionMode::Runtime,
+                    (None, None) => {}
+                }
             }
+            compare_with_baseline(state, endpoint_name, endpoint_config, compare, &request_data, result).await
         }
-        ExecutionMode::Plugin => {
-            // Handle plugin-based execution
-            if let Some(plugin_name) = &endpoint_config.plugin {
-                let request_data_json = serde_json::to_string(&request_data)
-                    .map_err(|e| BackworksError::Json(e))?;
-                state.plugin_manager.execute_plugin(plugin_name, &request_data_json).await
-            } else {
-                Err(BackworksError::config("Plugin mode requires plugin name"))
+        _ => result,
+    };
+    
+    let result = result.and_then(|output| state.templates.render_response(endpoint_name, &request_data, output));
+    
+    // Successful JSON bodies go through the endpoint's transform, then
+    // partial responses select the requested fields
+    let fields = request_data.query_params.get(response_filter::FIELDS_PARAM)
+        .filter(|_| state.config.server.partial_responses)
+        .and_then(|fields| response_filter::from_fields_param(fields));
+    let project = |body: Value| {
+        let body = state.transforms.transform_response(endpoint_name, body);
+        match fields {
+            Some(ref filter) => response_filter::apply(filter, body),
+            None => body,
+        }
+    };
+    
+    // Mapped statuses go out as their target, with the mapping's body if it
+    // has one; only unreplaced successful bodies are projected
+    let respond = |status: StatusCode, body: Value, failure: Option<String>| {
+        let (mapped, body, replaced) = match map_status(state, endpoint_name, &request_data, status, body) {
+            Ok(mapped) => mapped,
+            Err(e) => {
+                error!("Failed to render status body of endpoint '{}': {}", endpoint_name, e);
+                return handler_failure(e.to_string());
             }
+        };
+        let body = if status.is_success() && !replaced { project(body) } else { body };
+        if mapped.is_success() {
+            return success_response(state, endpoint_name, mapped, body, request_data.csrf_token.as_deref());
+        }
+        let mut response = (mapped, Json(body)).into_response();
+        if let Some(message) = failure {
+            response.extensions_mut().insert(RequestError::new(mapped, message, ErrorSource::Handler));
         }
+        response
     };
     
     match result {
         Ok(response) => {
             // Try to parse as structured response first
             if let Ok(structured_response) = serde_json::from_str::<serde_json::Value>(&response) {
+                // Catalog error raised via ctx.error(code, params)
+                if let Some(reference) = ErrorReference::from_output(&structured_response) {
+                    return catalog_error_response(state, &reference, &request_data.headers);
+                }
+                
+                // File returned via { file, download_name }, on its own or as a successful body
+                let download = from_runtime.then(|| FileDownload::from_output(&structured_response).or_else(|| {
+                    let status = structured_response.get("status").and_then(|s| s.as_u64());
+                    status.filter(|status| (200..300).contains(status))?;
+                    FileDownload::from_output(structured_response.get("body")?)
+                })).flatten();
+                if let Some(download) = download {
+                    return send_file(state, endpoint_name, download, &request_data.headers, method == "HEAD").await;
+                }
+                
                 if let (Some(status), Some(body)) = (
                     structured_response.get("status").and_then(|s| s.as_u64()),
                     structured_response.get("body")
@@ -295,105 +1294,2456 @@ async fn handle_endpoint_request(
                     // Structured response with status, headers, body
                     let status_code = StatusCode::from_u16(status as u16)
                         .unwrap_or(StatusCode::OK);
-                    
-                    let response_time = start_time.elapsed().as_millis() as f64;
-                    if let Some(ref dashboard) = state.dashboard {
-                        let path = format!("/{}", endpoint_name);
-                        if let Err(e) = dashboard.record_request(&method, &path, response_time, status as u16).await {
-                            error!("Failed to record request to dashboard: {}", e);
+                    let mut response = respond(status_code, body.clone(), None);
+                    if let Some(headers) = structured_response.get("headers").and_then(|h| h.as_object()) {
+                        apply_handler_headers(response.headers_mut(), headers);
+                    }
+                    if let Some(events) = structured_response.get("events") {
+                        match serde_json::from_value::<Vec<HandlerEvent>>(events.clone()) {
+                            Ok(events) => {
+                                response.extensions_mut().insert(HandlerEvents(events));
+                            }
+                            Err(e) => warn!("Ignoring invalid events from handler of endpoint '{}': {}", endpoint_name, e),
                         }
                     }
-                    
-                    return Ok((status_code, Json(body.clone())));
-                }
-            }
+                    match structured_response.get("emails") {
+                        Some(Value::Array(emails)) => {
+                            response.extensions_mut().insert(HandlerEmails(emails.clone()));
+                        }
+                        Some(_) => warn!("Ignoring emails from handler of endpoint '{}': not a list", endpoint_name),
+                        None => {}
+                    }
+                    match structured_response.get("jobs") {
+                        Some(Value::Array(jobs)) => {
+                            response.extensions_mut().insert(HandlerJobs(jobs.clone()));
+                        }
+                        Some(_) => warn!("Ignoring jobs from handler of endpoint '{}': not a list", endpoint_name),
+                        None => {}
+                    }
+                    match structured_response.get("session") {
+                        Some(Value::Object(session)) => {
+                            response.extensions_mut().insert(SessionChange(Some(session.clone())));
+                        }
+                        Some(Value::Null) => {
+                            response.extensions_mut().insert(SessionChange(None));
+                        }
+                        Some(_) => warn!("Ignoring session from handler of endpoint '{}': not an object", endpoint_name),
+                        None => {}
+                    }
+                    return response;
+                }
+            }
             
             // Fallback: treat as simple JSON response
             let json_value: serde_json::Value = serde_json::from_str(&response)
                 .unwrap_or_else(|_| serde_json::json!({"response": response}));
             
-            // Record successful request to dashboard
-            let response_time = start_time.elapsed().as_millis() as f64;
-            if let Some(ref dashboard) = state.dashboard {
-                let path = format!("/{}", endpoint_name);
-                if let Err(e) = dashboard.record_request(&method, &path, response_time, 200).await {
-                    error!("Failed to record request to dashboard: {}", e);
-                }
-            }
-            
-            Ok((StatusCode::OK, Json(json_value)))
+            respond(StatusCode::OK, json_value, None)
         },
         Err(e) => {
             error!("Request handling error: {}", e);
             
-            // Record failed request to dashboard
-            let response_time = start_time.elapsed().as_millis() as f64;
-            if let Some(ref dashboard) = state.dashboard {
-                let path = format!("/{}", endpoint_name);
-                if let Err(dashboard_err) = dashboard.record_request(&method, &path, response_time, 500).await {
-                    error!("Failed to record failed request to dashboard: {}", dashboard_err);
-                }
+            let status = match e {
+                BackworksError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+                BackworksError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            let message = e.to_string();
+            respond(status, serde_json::json!({"error": message}), Some(message))
+        }
+    }
+}
+
+// A handler status and body after the endpoint's status code mapping, and
+// whether the mapping replaced the body
+fn map_status(state: &AppState, endpoint_name: &str, request_data: &RequestData, status: StatusCode, body: Value) -> Result<(StatusCode, Value, bool)> {
+    let Some(mapping) = state.transforms.map_status(endpoint_name, status.as_u16()) else {
+        return Ok((status, body, false));
+    };
+    let mapped = StatusCode::from_u16(mapping.status()).unwrap_or(status);
+    if let StatusCodeMapping::Response { body: Some(replacement), .. } = mapping {
+        return Ok((mapped, replacement.clone(), true));
+    }
+    match state.templates.render_status_body(endpoint_name, request_data, status.as_u16(), &body)? {
+        Some(rendered) => Ok((mapped, rendered, true)),
+        None => Ok((mapped, body, false)),
+    }
+}
+
+// Headers a handler set on its response; framing headers stay the server's
+fn apply_handler_headers(headers: &mut HeaderMap, set: &serde_json::Map<String, Value>) {
+    for (name, value) in set {
+        let (Ok(name), Some(Ok(value))) = (HeaderName::try_from(name.as_str()), value.as_str().map(HeaderValue::from_str)) else {
+            warn!("Ignoring invalid response header '{}' from handler", name);
+            continue;
+        };
+        if name != header::CONTENT_LENGTH && name != header::TRANSFER_ENCODING && name != header::CONNECTION {
+            headers.insert(name, value);
+        }
+    }
+}
+
+// Handler failures keep their historical 500 + {"error"} shape
+fn handler_failure(message: String) -> axum::response::Response {
+    let mut response = (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(serde_json::json!({"error": message}))
+    ).into_response();
+    response.extensions_mut().insert(RequestError::new(StatusCode::INTERNAL_SERVER_ERROR, message, ErrorSource::Handler));
+    response
+}
+
+// Stream a file a handler returned
+async fn send_file(state: &AppState, endpoint_name: &str, download: FileDownload, headers: &HeaderMap, head: bool) -> axum::response::Response {
+    let path = download.file.clone();
+    let root = state.config.server.downloads.as_deref().unwrap_or(std::path::Path::new("."));
+    match download.send(root, headers, head).await {
+        Ok(response) => response,
+        Err(e) => {
+            error!("Failed to send file {} of endpoint '{}': {}", path.display(), endpoint_name, e);
+            handler_failure(format!("Cannot send file {}: {}", path.display(), e))
+        }
+    }
+}
+
+// Send a successful body, encoded in the endpoint's output format if it has
+// one; HTML forms that post get the session's CSRF token
+fn success_response(state: &AppState, endpoint_name: &str, status: StatusCode, body: Value, csrf_token: Option<&str>) -> axum::response::Response {
+    match state.transforms.encode_response(endpoint_name, &body) {
+        None => (status, Json(body)).into_response(),
+        Some(Ok((content_type, bytes))) if content_type.starts_with("text/html") && csrf_token.is_some() => {
+            let field = state.sessions.as_ref().and_then(|sessions| sessions.csrf()).map_or("_csrf", |csrf| csrf.field.as_str());
+            let html = crate::csrf::inject(&String::from_utf8_lossy(&bytes), field, csrf_token.unwrap_or_default());
+            (status, [(header::CONTENT_TYPE, content_type)], html).into_response()
+        }
+        Some(Ok((content_type, bytes))) => (status, [(header::CONTENT_TYPE, content_type)], bytes).into_response(),
+        Some(Err(message)) => {
+            error!("Failed to encode response of endpoint '{}': {}", endpoint_name, message);
+            handler_failure(message)
+        }
+    }
+}
+
+// Render a catalog error referenced by a handler into the standard error body
+fn catalog_error_response(state: &AppState, reference: &ErrorReference, headers: &HeaderMap) -> axum::response::Response {
+    let locales = accepted_locales(headers);
+    match state.error_catalog.render(&reference.code, &reference.params, &locales) {
+        Ok(catalog_error) => {
+            let status = catalog_error.status_code();
+            let mut response = (status, Json(catalog_error.body())).into_response();
+            response.extensions_mut().insert(RequestError::new(status, format!("{}: {}", catalog_error.code, catalog_error.message), ErrorSource::Handler));
+            response
+        }
+        Err(e) => {
+            error!("Failed to render catalog error: {}", e);
+            let message = e.to_string();
+            let mut response = (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"error": message}))
+            ).into_response();
+            response.extensions_mut().insert(RequestError::new(StatusCode::INTERNAL_SERVER_ERROR, message, ErrorSource::Handler));
+            response
+        }
+    }
+}
+
+// Execute an endpoint with each mode in turn until one succeeds, returning
+// the mode that answered
+async fn execute_modes<'m>(
+    state: &AppState,
+    modes: &[&'m ExecutionMode],
+    endpoint_name: &str,
+    endpoint_config: &crate::config::EndpointConfig,
+    method: &str,
+    request_data: &RequestData,
+) -> Result<(&'m ExecutionMode, String)> {
+    let mut failure = None;
+    for mode in modes {
+        if let Some(ref e) = failure {
+            warn!("Endpoint '{}' falling back to {:?} mode: {}", endpoint_name, mode, e);
+        }
+        match execute_mode(state, mode, endpoint_name, endpoint_config, method, request_data).await {
+            Ok(output) => return Ok((*mode, output)),
+            // No budget is left for a fallback
+            Err(e @ BackworksError::Timeout(_)) => return Err(e),
+            Err(e) => failure = Some(e),
+        }
+    }
+    Err(failure.unwrap_or_else(|| BackworksError::config(format!("Endpoint '{}' has no execution mode", endpoint_name))))
+}
+
+// Execute an endpoint using the given execution mode
+async fn execute_mode(
+    state: &AppState,
+    mode: &ExecutionMode,
+    endpoint_name: &str,
+    endpoint_config: &crate::config::EndpointConfig,
+    method: &str,
+    request_data: &RequestData,
+) -> Result<String> {
+    // Serialize request data for handlers that need string representation
+    let request_data_json = serde_json::to_string(request_data)
+        .map_err(BackworksError::Json)?;
+    
+    match mode {
+        ExecutionMode::Runtime => {
+            if let Some(ref runtime_config) = endpoint_config.runtime {
+                state.runtime_manager.handle_request(runtime_config, &request_data_json).await
+            } else {
+                Err(BackworksError::config("Runtime mode requires runtime configuration"))
+            }
+        }
+        ExecutionMode::Database => {
+            // Named queries run as prepared statements of the database plugin
+            let queried = state.queries.respond(endpoint_name, method, request_data, &state.plugin_manager);
+            if let Some(output) = Deadline::run(request_data.deadline, "the database", queried).await? {
+                return Ok(output);
             }
             
-            Ok((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({"error": e.to_string()}))
-            ))
+            // Database mode now requires plugins to handle the actual database operations
+            debug!("Database mode endpoint - delegating to plugins");
+            
+            // Let plugins handle database operations with simple data interface
+            let processed = state.plugin_manager.process_endpoint_data(endpoint_name, method, &request_data_json);
+            match Deadline::run(request_data.deadline, "the database", processed).await {
+                Ok(Some(response)) => Ok(response),
+                Ok(None) => Err(BackworksError::config("No plugin handled database endpoint")),
+                Err(e) => Err(e),
+            }
+        }
+        ExecutionMode::Plugin => {
+            // Handle plugin-based execution
+            if let Some(plugin_name) = &endpoint_config.plugin {
+                // Plugins serving endpoints themselves answer with a full response
+                let handled = state.plugin_manager.handle_endpoint(plugin_name, endpoint_name, request_data);
+                match Deadline::run(request_data.deadline, "the plugin", handled).await? {
+                    Some(response) => Ok(response.to_output()),
+                    None => state.plugin_manager.execute_plugin(plugin_name, &request_data_json).await,
+                }
+            } else {
+                Err(BackworksError::config("Plugin mode requires plugin name"))
+            }
         }
+        ExecutionMode::Mock => state.mocks.respond(endpoint_name, request_data, &state.state_store).await,
+        ExecutionMode::Proxy => state.proxies.respond(endpoint_name, request_data).await,
+        ExecutionMode::Static => Err(BackworksError::config("Static endpoints are served from their directory, not executed")),
     }
 }
 
-// Health check endpoint
-async fn health_check(State(state): State<AppState>) -> Json<Value> {
-    let start_time = std::time::Instant::now();
+// Diff the live result against the endpoint's baseline and record the report
+async fn compare_with_baseline(
+    state: &AppState,
+    endpoint_name: &str,
+    endpoint_config: &crate::config::EndpointConfig,
+    compare: &crate::config::EndpointCompareConfig,
+    request_data: &RequestData,
+    live: Result<String>,
+) -> Result<String> {
+    let (baseline, baseline_source) = if let Some(ref recorded) = compare.baseline.response {
+        (Ok(serde_json::json!({
+            "status": recorded.status,
+            "headers": recorded.headers,
+            "body": recorded.body,
+        }).to_string()), "recorded".to_string())
+    } else if let Some(ref mode) = compare.baseline.mode {
+        let output = execute_mode(state, mode, endpoint_name, endpoint_config, &request_data.method, request_data).await;
+        (output, format!("{:?}", mode).to_lowercase())
+    } else {
+        return live;
+    };
     
-    let response = Json(serde_json::json!({
-        "status": "healthy",
-        "timestamp": chrono::Utc::now(),
-        "version": env!("CARGO_PKG_VERSION")
-    }));
+    let snapshot = |result: &Result<String>| match result {
+        Ok(output) => ResponseSnapshot::from_handler_output(output),
+        Err(e) => ResponseSnapshot::from_error(&e.to_string()),
+    };
     
-    // Record health check request to dashboard
-    let response_time = start_time.elapsed().as_millis() as f64;
-    if let Some(ref dashboard) = state.dashboard {
-        if let Err(e) = dashboard.record_request("GET", "/health", response_time, 200).await {
-            error!("Failed to record health check to dashboard: {}", e);
-        }
+    let divergences = diff_responses(&snapshot(&baseline), &snapshot(&live), &CompareRules::from_config(compare));
+    state.comparisons.record(ComparisonReport {
+        endpoint: endpoint_name.to_string(),
+        method: request_data.method.clone(),
+        path: request_data.path.clone(),
+        timestamp: chrono::Utc::now(),
+        baseline_source,
+        divergences,
+    }).await;
+    
+    match compare.serve {
+        CompareServe::Live => live,
+        CompareServe::Baseline => baseline,
     }
+}
+
+#[derive(Debug, Deserialize)]
+struct ComparisonQuery {
+    endpoint: Option<String>,
+}
+
+// Recent baseline comparison reports
+async fn comparisons_handler(
+    State(state): State<AppState>,
+    Query(query): Query<ComparisonQuery>,
+) -> Json<Value> {
+    let reports = state.comparisons.reports(query.endpoint.as_deref()).await;
+    let summary = state.comparisons.summary().await;
     
-    response
+    Json(serde_json::json!({
+        "summary": summary,
+        "reports": reports,
+    }))
+}
+
+// Health check endpoint
+async fn health_check(State(state): State<AppState>) -> Json<HealthReport> {
+    Json(state.health.liveness())
+}
+
+// Readiness endpoint: 503 while any critical check fails
+async fn readiness_check(State(state): State<AppState>) -> (StatusCode, Json<HealthReport>) {
+    let report = state.health.readiness().await;
+    let status = if report.is_ready() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
 }
 
 // Metrics endpoint
 async fn metrics_handler(State(state): State<AppState>) -> String {
-    let start_time = std::time::Instant::now();
-    
-    // Simulate metrics collection
-    let response = format!(
-        "# HELP backworks_requests_total Total number of requests\n\
-         # TYPE backworks_requests_total counter\n\
-         backworks_requests_total {}\n",
-        42 // Simulated request count
-    );
-    
-    // Record metrics request to dashboard
-    let response_time = start_time.elapsed().as_millis() as f64;
-    if let Some(ref dashboard) = state.dashboard {
-        if let Err(e) = dashboard.record_request("GET", "/metrics", response_time, 200).await {
-            error!("Failed to record metrics request to dashboard: {}", e);
+    state.metrics.render()
+}
+
+// API reference page, built from the blueprint the server runs
+async fn docs_handler(State(state): State<AppState>) -> std::result::Result<axum::response::Html<String>, (StatusCode, String)> {
+    let docs = ApiDocs::load(&state.config, &[], &state.mocks, &state.state_store).await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let openapi_url = state.config.docs.as_ref().map(|docs| docs.openapi_path()).unwrap_or_default();
+    Ok(axum::response::Html(docs.render_html(&openapi_url)))
+}
+
+// The API reference as an OpenAPI document
+async fn openapi_handler(State(state): State<AppState>) -> std::result::Result<Json<Value>, (StatusCode, String)> {
+    let docs = ApiDocs::load(&state.config, &[], &state.mocks, &state.state_store).await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(docs.openapi()))
+}
+
+#[derive(Debug, Deserialize)]
+struct StateQuery {
+    namespace: Option<String>,
+    #[serde(default)]
+    mode: ImportMode,
+}
+
+// Export the state store as JSON
+async fn state_export_handler(
+    State(state): State<AppState>,
+    Query(query): Query<StateQuery>,
+) -> Json<StateSnapshot> {
+    Json(state.state_store.export(query.namespace.as_deref()).await)
+}
+
+// Import a JSON snapshot into the state store
+async fn state_import_handler(
+    State(state): State<AppState>,
+    Query(query): Query<StateQuery>,
+    Json(snapshot): Json<StateSnapshot>,
+) -> Json<ImportSummary> {
+    let summary = state.state_store.import(snapshot, query.mode, query.namespace.as_deref()).await;
+    info!("Imported {} key(s) into {} namespace(s) ({:?})", summary.keys, summary.namespaces, query.mode);
+    Json(summary)
+}
+
+// Send journaled requests of another server through the routes, like their
+// clients did: they are authenticated, checked and journaled as any request,
+// whatever identity the entries record
+async fn journal_replay_handler(
+    State(state): State<AppState>,
+    Json(entries): Json<Vec<JournalEntry>>,
+) -> Json<ReplaySummary> {
+    let (entries, duplicates) = journal::deduplicate(entries);
+    let mut summary = ReplaySummary { duplicates, ..Default::default() };
+    let routes = state.routes.service();
+    for entry in entries {
+        let request = match entry.to_request() {
+            Ok(request) => request,
+            Err(e) => {
+                warn!("Skipping journal entry {}: {}", entry.id, e);
+                summary.failed += 1;
+                continue;
+            }
+        };
+        let response = tower::ServiceExt::oneshot(routes.clone(), request).await.unwrap_or_else(|never| match never {});
+        summary.count(&entry, response.status());
+    }
+    info!("Replayed {} journaled request(s) ({} failed, {} duplicate)", summary.replayed, summary.failed, summary.duplicates);
+    Json(summary)
+}
+
+// Fault injection settings of every endpoint
+async fn chaos_handler(State(state): State<AppState>) -> Json<ChaosStatus> {
+    Json(state.chaos.status().await)
+}
+
+#[derive(Debug, Deserialize)]
+struct UsageReportQuery {
+    window: Option<String>,
+}
+
+// Declared endpoints against the traffic of the last `window`, one hour by default
+async fn usage_report_handler(
+    State(state): State<AppState>,
+    Query(query): Query<UsageReportQuery>,
+) -> std::result::Result<Json<UsageReport>, (StatusCode, String)> {
+    let window = match query.window {
+        Some(ref window) => crate::config::parse_duration(window).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?,
+        None => std::time::Duration::from_secs(60 * 60),
+    };
+    Ok(Json(state.usage.report(&state.config, window).await))
+}
+
+// Remove an endpoint's stored data, or every endpoint's
+async fn purge_handler(
+    State(state): State<AppState>,
+    Json(request): Json<PurgeRequest>,
+) -> std::result::Result<Json<PurgeSummary>, (StatusCode, String)> {
+    let policies = RetentionPolicy::for_purge(&state.config, &request)?;
+    let summary = crate::retention::purge(&state, &policies).await;
+    info!("Purged {} record(s) of {} endpoint(s)", summary.removed(), summary.endpoints.len());
+    Ok(Json(summary))
+}
+
+// Proxy targets with their state and the operator actions on them
+async fn proxy_targets_handler(State(state): State<AppState>) -> Json<TargetsReport> {
+    Json(state.proxies.targets())
+}
+
+// Drain, quarantine or enable one proxy target
+async fn operate_proxy_target_handler(
+    State(state): State<AppState>,
+    Path((endpoint, operation)): Path<(String, TargetOperation)>,
+    Json(command): Json<TargetCommand>,
+) -> axum::response::Response {
+    match state.proxies.operate(&endpoint, operation, command).await {
+        Ok(report) => Json(report).into_response(),
+        Err((status, message)) => (status, Json(serde_json::json!({"error": message}))).into_response(),
+    }
+}
+
+// Switch fault injection on or off everywhere
+async fn switch_chaos_handler(
+    State(state): State<AppState>,
+    Json(switch): Json<ChaosSwitch>,
+) -> Json<ChaosStatus> {
+    info!("Fault injection {}", if switch.enabled { "enabled" } else { "disabled" });
+    Json(state.chaos.set_enabled(switch.enabled).await)
+}
+
+// Replace an endpoint's fault injection settings
+async fn configure_chaos_handler(
+    State(state): State<AppState>,
+    Path(endpoint): Path<String>,
+    Json(config): Json<ChaosConfig>,
+) -> axum::response::Response {
+    match state.chaos.configure(&endpoint, config).await {
+        Ok(status) => Json(status).into_response(),
+        Err((status, message)) => (status, Json(serde_json::json!({"error": message}))).into_response(),
+    }
+}
+
+// List scenarios and the active one
+async fn scenarios_handler(State(state): State<AppState>) -> Json<ScenarioStatus> {
+    Json(state.scenarios.status().await)
+}
+
+// Switch the active scenario
+async fn activate_scenario_handler(
+    State(state): State<AppState>,
+    Json(switch): Json<ScenarioSwitch>,
+) -> axum::response::Response {
+    match state.scenarios.switch(switch).await {
+        Ok(status) => {
+            info!("Active scenario: {}", status.active.as_deref().unwrap_or("none"));
+            Json(status).into_response()
         }
+        Err(message) => (StatusCode::NOT_FOUND, Json(serde_json::json!({"error": message}))).into_response(),
     }
-    
-    response
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Return every endpoint to its handler
+async fn deactivate_scenario_handler(State(state): State<AppState>) -> Json<ScenarioStatus> {
+    info!("Active scenario: none");
+    Json(state.scenarios.deactivate().await)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RequestData {
     pub method: String,
     pub path: String, // Add original path
-    pub path_params: HashMap<String, String>,
+    pub path_params: HashMap<String, Value>,
     pub query_params: HashMap<String, String>,
     #[serde(skip)] // HeaderMap doesn't implement Serialize
     pub headers: HeaderMap,
     pub body: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth: Option<AuthContext>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin: Option<RequestOrigin>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<LocaleContext>,
+    /// Remaining time budget, as `timeout_ms`
+    #[serde(default, rename = "timeout_ms", skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<Deadline>,
+    /// Session data, when `sessions` are on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<serde_json::Map<String, Value>>,
+    /// Token state-changing requests of the session send, with `sessions.csrf`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub csrf_token: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{EndpointConfig, ServerConfig};
+    use crate::plugin::BackworksPlugin;
+    use crate::schedule::{RunStatus, RunTrigger};
+    use std::sync::Mutex;
+    use tower::ServiceExt;
+
+    #[derive(Default)]
+    struct RecordingPlugin {
+        critical: bool,
+        forbid: bool,
+        panic: bool,
+        errors: Mutex<Vec<RequestError>>,
+        after_statuses: Mutex<Vec<u16>>,
+        endpoints: Mutex<Vec<Option<String>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl BackworksPlugin for RecordingPlugin {
+        fn name(&self) -> &str { "recording" }
+        fn version(&self) -> &str { "0.0.0" }
+        fn description(&self) -> &str { "records pipeline hooks" }
+        async fn initialize(&self, _config: &Value) -> Result<()> { Ok(()) }
+        async fn shutdown(&self) -> Result<()> { Ok(()) }
+        fn is_critical(&self) -> bool { self.critical }
+
+        async fn before_request(&self, request: &mut axum::extract::Request) -> Result<()> {
+            let endpoint = request.extensions().get::<MatchedEndpoint>().map(|e| e.0.clone());
+            self.endpoints.lock().unwrap().push(endpoint);
+            if self.panic {
+                panic!("plugin bug");
+            } else if self.forbid {
+                Err(BackworksError::forbidden("not in group"))
+            } else if self.critical {
+                Err(BackworksError::plugin("rejected"))
+            } else {
+                Ok(())
+            }
+        }
+
+        async fn on_error(&self, error: &RequestError, response: &mut axum::response::Response) -> Result<()> {
+            self.errors.lock().unwrap().push(error.clone());
+            response.headers_mut().insert("x-error-mapped", http::HeaderValue::from_static("1"));
+            Ok(())
+        }
+
+        async fn after_response(&self, response: &mut axum::response::Response) -> Result<()> {
+            self.after_statuses.lock().unwrap().push(response.status().as_u16());
+            Ok(())
+        }
+
+        fn register_middleware(&self, registry: &mut MiddlewareRegistry) {
+            registry.register("tag", |config| Ok(Arc::new(TagMiddleware(config["value"].as_str().unwrap_or("-").to_string()))));
+        }
+
+        fn register_load_balancers(&self, registry: &mut LoadBalancerRegistry) {
+            registry.register("same_zone", |config| Ok(Arc::new(SameZone(config["header"].as_str().unwrap_or("x-zone").to_string()))));
+        }
+    }
+
+    /// Prefers targets labelled with the caller's zone, then the least loaded
+    struct SameZone(String);
+
+    impl crate::balancer::LoadBalancer for SameZone {
+        fn select(&self, targets: &[crate::balancer::TargetMetrics], request: &RequestData) -> Option<usize> {
+            let zone = request.headers.get(&self.0).and_then(|zone| zone.to_str().ok());
+            (0..targets.len()).min_by_key(|&index| (targets[index].labels.get("zone").map(String::as_str) != zone, targets[index].requests))
+        }
+    }
+
+    struct TagMiddleware(String);
+
+    #[async_trait::async_trait]
+    impl crate::pipeline::EndpointMiddleware for TagMiddleware {
+        async fn handle(&self, request: axum::extract::Request, next: crate::pipeline::Next<'_>) -> axum::response::Response {
+            let mut response = next.run(request).await;
+            response.headers_mut().insert("x-tag", http::HeaderValue::from_str(&self.0).unwrap());
+            response
+        }
+    }
+
+    fn test_config() -> BackworksConfig {
+        let mut endpoints = HashMap::new();
+        endpoints.insert("missing_plugin".to_string(), EndpointConfig {
+            path: "/broken".to_string(),
+            methods: vec!["GET".to_string()],
+            description: None,
+            mode: Some(ExecutionMode::Plugin),
+            modes: Vec::new(),
+            mock: None,
+            proxy: None,
+            runtime: None,
+            database: None,
+            capture: None,
+            plugin: None,
+            ai_enhanced: None,
+            ai_suggestions: None,
+            apis: None,
+            parameters: None,
+            validation: None,
+            monitoring: None,
+            compare: None,
+            rollout: None,
+            template: None,
+            transform: None,
+            deprecated: None,
+            coerce: None,
+            static_files: None,
+            middleware: Vec::new(),
+            scenarios: HashMap::new(),
+            chaos: None,
+            retention: None,
+            timeout: None,
+            compression: None,
+            concurrency: None,
+            webhook: None,
+            csrf: None,
+        });
+        let mut typed = endpoints["missing_plugin"].clone();
+        typed.path = "/orders/{id:int}".to_string();
+        endpoints.insert("order".to_string(), typed);
+
+        BackworksConfig {
+            name: "pipeline".to_string(),
+            description: None,
+            version: None,
+            mode: ExecutionMode::Plugin,
+            endpoints,
+            server: ServerConfig::default(),
+            plugins: HashMap::new(),
+            plugin_discovery: crate::config::PluginDiscoveryConfig::default(),
+            dashboard: None,
+            database: None,
+            apis: None,
+            cache: None,
+            security: None,
+            monitoring: None,
+            state: None,
+            errors: None,
+            localization: None,
+            scenarios: None,
+            journal: None,
+            capture: None,
+            admin: Some(crate::config::AdminConfig { token: ADMIN_TOKEN.to_string() }),
+            schedules: HashMap::new(),
+            sessions: None,
+            docs: None,
+            strict_env: false,
+            route_conflicts: Default::default(),
+            global_headers: HashMap::new(),
+            logging: Default::default(),
+        }
+    }
+
+    async fn app_with(plugin: Arc<RecordingPlugin>) -> Router {
+        let manager = PluginManager::new();
+        manager.register_plugin(plugin, None, None).await.unwrap();
+        BackworksServer::new(Arc::new(test_config()), manager, None).unwrap().create_app().unwrap()
+    }
+
+    async fn send(app: Router, uri: &str) -> axum::response::Response {
+        app.oneshot(axum::http::Request::get(uri).body(axum::body::Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    /// Admin token of [`test_config`]
+    const ADMIN_TOKEN: &str = "test-admin-token";
+
+    fn as_admin(request: http::request::Builder) -> http::request::Builder {
+        request.header(http::header::AUTHORIZATION, format!("Bearer {}", ADMIN_TOKEN))
+    }
+
+    async fn send_admin(app: Router, uri: &str) -> axum::response::Response {
+        app.oneshot(as_admin(axum::http::Request::get(uri)).body(axum::body::Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_panics_are_answered_with_request_ids() {
+        let manager = PluginManager::new();
+        manager.register_plugin(Arc::new(RecordingPlugin { panic: true, ..Default::default() }), None, None).await.unwrap();
+        let server = BackworksServer::new(Arc::new(test_config()), manager, None).unwrap();
+        let app = server.create_app().unwrap();
+
+        let request = axum::http::Request::get("/orders/7").header("x-request-id", "req-42").body(axum::body::Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.headers()["x-request-id"], "req-42");
+        let body: Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body, serde_json::json!({ "error": "Internal server error", "request_id": "req-42" }));
+
+        let response = send(app, "/orders/8").await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(uuid::Uuid::parse_str(response.headers()["x-request-id"].to_str().unwrap()).is_ok());
+        assert!(server.state.metrics.render().contains(r#"backworks_panics_total{endpoint="order"} 2"#));
+    }
+
+    #[tokio::test]
+    async fn test_handler_error_runs_trailing_hooks() {
+        let plugin = Arc::new(RecordingPlugin::default());
+        let response = send(app_with(plugin.clone()).await, "/broken").await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(response.headers().contains_key("x-error-mapped"));
+        let errors = plugin.errors.lock().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].source, ErrorSource::Handler);
+        assert!(errors[0].message.contains("Plugin mode requires plugin name"));
+        assert_eq!(*plugin.after_statuses.lock().unwrap(), vec![500]);
+        assert_eq!(*plugin.endpoints.lock().unwrap(), vec![Some("missing_plugin".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_unmatched_route_runs_trailing_hooks() {
+        let plugin = Arc::new(RecordingPlugin::default());
+        let response = send(app_with(plugin.clone()).await, "/nowhere").await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(*plugin.endpoints.lock().unwrap(), vec![None]);
+        assert_eq!(plugin.errors.lock().unwrap()[0].source, ErrorSource::Framework);
+        assert_eq!(*plugin.after_statuses.lock().unwrap(), vec![404]);
+    }
+
+    #[tokio::test]
+    async fn test_typed_path_parameters_are_validated() {
+        let plugin = Arc::new(RecordingPlugin::default());
+        let app = app_with(plugin.clone()).await;
+
+        let response = send(app.clone(), "/orders/abc").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let error = response.extensions().get::<RequestError>().unwrap();
+        assert_eq!(error.message, "Path parameter 'id' must be an integer, got 'abc'");
+        assert_eq!(error.source, ErrorSource::Framework);
+
+        // A valid id reaches the (failing) handler
+        let response = send(app, "/orders/42").await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(*plugin.endpoints.lock().unwrap(), vec![Some("order".to_string()), Some("order".to_string())]);
+    }
+
+    /// Serves the `orders` endpoint with full responses
+    struct OrdersPlugin;
+
+    #[async_trait::async_trait]
+    impl BackworksPlugin for OrdersPlugin {
+        fn name(&self) -> &str { "orders" }
+        fn version(&self) -> &str { "0.0.0" }
+        fn description(&self) -> &str { "serves orders" }
+        async fn initialize(&self, _config: &Value) -> Result<()> { Ok(()) }
+        async fn shutdown(&self) -> Result<()> { Ok(()) }
+        fn endpoint_handler(&self) -> Option<&dyn crate::plugin::EndpointHandlerPlugin> { Some(self) }
+
+        fn routes(&self) -> PluginRoutes {
+            PluginRoutes::new().route(Method::GET, "/orders/stats", || async { Json(serde_json::json!({"open": 3})) })
+        }
+
+        fn dashboard_routes(&self) -> PluginRoutes {
+            PluginRoutes::new().route(Method::GET, "/", || async { "<h1>Orders</h1>" })
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl crate::plugin::EndpointHandlerPlugin for OrdersPlugin {
+        fn claims(&self, endpoint: &str) -> bool { endpoint == "order" }
+
+        async fn handle_endpoint(&self, endpoint: &str, request: &RequestData) -> Result<crate::plugin::EndpointResponse> {
+            let id = request.path_params["id"].as_str().unwrap_or_default();
+            Ok(crate::plugin::EndpointResponse::new(StatusCode::CREATED, serde_json::json!({"endpoint": endpoint, "id": id}))
+                .with_header(header::LOCATION, HeaderValue::from_str(&format!("/orders/{}", id)).unwrap()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plugin_endpoints_answer_with_full_responses() {
+        let mut config = test_config();
+        let mut order = config.endpoints["missing_plugin"].clone();
+        order.path = "/orders/{id}".to_string();
+        order.plugin = Some("orders".to_string());
+        config.endpoints.insert("order".to_string(), order.clone());
+        order.path = "/refunds".to_string();
+        config.endpoints.insert("refunds".to_string(), order);
+        let manager = PluginManager::new();
+        manager.register_plugin(Arc::new(OrdersPlugin), None, None).await.unwrap();
+        let app = BackworksServer::new(Arc::new(config), manager, None).unwrap().create_app().unwrap();
+
+        let response = send(app.clone(), "/orders/42").await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[header::LOCATION], "/orders/42");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), serde_json::json!({"endpoint": "order", "id": "42"}));
+
+        // An endpoint the plugin does not claim fails
+        let response = send(app, "/refunds").await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_plugins_add_routes_to_the_server_and_dashboard() {
+        let mut config = test_config();
+        let mut order = config.endpoints["missing_plugin"].clone();
+        order.path = "/orders/{id}".to_string();
+        order.plugin = Some("orders".to_string());
+        config.endpoints.insert("order".to_string(), order.clone());
+        let manager = PluginManager::new();
+        manager.register_plugin(Arc::new(OrdersPlugin), None, None).await.unwrap();
+        let server = |config: &BackworksConfig| BackworksServer::new(Arc::new(config.clone()), manager.clone(), None).unwrap();
+
+        // The plugin's static route wins over the endpoint's parameter
+        let app = server(&config).with_plugin_routes(manager.routes().await).create_app().unwrap();
+        let response = send(app.clone(), "/orders/stats").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), serde_json::json!({"open": 3}));
+        assert_eq!(send(app, "/orders/42").await.status(), StatusCode::CREATED);
+
+        let dashboard = Dashboard::new(serde_yaml::from_str("enabled: true").unwrap())
+            .with_plugin_pages(manager.dashboard_routes().await)
+            .unwrap();
+        assert_eq!(send(dashboard.router(), "/plugins/orders").await.status(), StatusCode::OK);
+
+        // An endpoint at the same route is refused
+        order.path = "/orders/stats".to_string();
+        config.endpoints.insert("stats".to_string(), order);
+        let err = server(&config).with_plugin_routes(manager.routes().await).create_app().unwrap_err();
+        assert!(err.to_string().contains("Duplicate route GET /orders/stats"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_fields_parameter_selects_response_fields() {
+        let mut config = test_config();
+        let mut echo = config.endpoints["missing_plugin"].clone();
+        echo.path = "/echo".to_string();
+        echo.plugin = Some("recording".to_string());
+        config.endpoints.insert("echo".to_string(), echo);
+        let manager = PluginManager::new();
+        manager.register_plugin(Arc::new(RecordingPlugin::default()), None, None).await.unwrap();
+        let app = BackworksServer::new(Arc::new(config.clone()), manager.clone(), None).unwrap().create_app().unwrap();
+
+        let response = send(app, "/echo?fields=plugin,data.method").await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), serde_json::json!({ "plugin": "recording", "data": { "method": "GET" } }));
+
+        config.server.partial_responses = false;
+        let app = BackworksServer::new(Arc::new(config), manager, None).unwrap().create_app().unwrap();
+        let body = axum::body::to_bytes(send(app, "/echo?fields=plugin").await.into_body(), usize::MAX).await.unwrap();
+        assert!(serde_json::from_slice::<Value>(&body).unwrap().get("processed").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_deprecated_endpoint_announces_sunset_and_tracks_callers() {
+        let mut config = test_config();
+        let mut echo = config.endpoints["missing_plugin"].clone();
+        echo.path = "/v1/echo".to_string();
+        echo.plugin = Some("recording".to_string());
+        echo.deprecated = Some(serde_yaml::from_str("sunset: 2030-06-30\nlink: https://docs.example.com/v2").unwrap());
+        config.endpoints.insert("echo".to_string(), echo);
+        let manager = PluginManager::new();
+        manager.register_plugin(Arc::new(RecordingPlugin::default()), None, None).await.unwrap();
+        let dashboard = Arc::new(
+            Dashboard::new(serde_yaml::from_str("enabled: true").unwrap())
+                .with_deprecations(crate::deprecation::DeprecationUsage::new(crate::deprecation::DeprecatedEndpoint::from_config(&config)))
+        );
+        let app = BackworksServer::new(Arc::new(config), manager, Some(dashboard.clone())).unwrap().create_app().unwrap();
+
+        let response = send(app.clone(), "/v1/echo").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["deprecation"], "true");
+        assert_eq!(response.headers()["sunset"], "Sun, 30 Jun 2030 00:00:00 GMT");
+        assert_eq!(response.headers()[http::header::LINK], "<https://docs.example.com/v2>; rel=\"deprecation\"");
+        assert!(!send(app, "/broken").await.headers().contains_key("deprecation"));
+
+        let response = dashboard.router().oneshot(axum::http::Request::get("/api/deprecations").body(axum::body::Body::empty()).unwrap()).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let report: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(report[0]["endpoint"], "echo");
+        assert_eq!(report[0]["callers"][0]["caller"], "anonymous");
+        assert_eq!(report[0]["callers"][0]["requests"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_converts_xml_bodies() {
+        let mut config = test_config();
+        let mut echo = config.endpoints["missing_plugin"].clone();
+        echo.path = "/orders".to_string();
+        echo.methods = vec!["POST".to_string()];
+        echo.plugin = Some("recording".to_string());
+        echo.transform = Some(serde_yaml::from_str(r#"
+request:
+  input_format: xml
+response:
+  output_format: xml
+  format_options: { root: echo }
+response_filter:
+  include_fields: ["$.data.body"]
+"#).unwrap());
+        config.endpoints.insert("echo".to_string(), echo);
+        let manager = PluginManager::new();
+        manager.register_plugin(Arc::new(RecordingPlugin::default()), None, None).await.unwrap();
+        let app = BackworksServer::new(Arc::new(config), manager, None).unwrap().create_app().unwrap();
+        let post = |body: &'static str| axum::http::Request::post("/orders")
+            .header(header::CONTENT_TYPE, "application/xml")
+            .body(axum::body::Body::from(body))
+            .unwrap();
+
+        let response = app.clone().oneshot(post(r#"<order id="7"><sku>A1</sku></order>"#)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/xml");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).ends_with(r#"<echo><data><body id="7"><sku>A1</sku></body></data></echo>"#), "{:?}", body);
+
+        let response = app.oneshot(post("<order>")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(response.extensions().get::<RequestError>().unwrap().message.contains("not valid XML"));
+    }
+
+    #[tokio::test]
+    async fn test_status_code_mapping_replaces_status_and_body() {
+        let mut config = test_config();
+        config.endpoints.get_mut("missing_plugin").unwrap().transform = Some(serde_yaml::from_str(r#"
+status_code_mapping:
+  500:
+    status: 502
+    body_template: '{ "error": "upstream failed", "path": "{{request.path}}", "cause": {{json response.body.error}} }'
+"#).unwrap());
+        let mut echo = config.endpoints["missing_plugin"].clone();
+        echo.path = "/echo".to_string();
+        echo.plugin = Some("recording".to_string());
+        echo.transform = Some(serde_yaml::from_str("status_code_mapping:\n  200: { status: 202, body: [] }\n").unwrap());
+        config.endpoints.insert("echo".to_string(), echo);
+        let plugin = Arc::new(RecordingPlugin::default());
+        let manager = PluginManager::new();
+        manager.register_plugin(plugin.clone(), None, None).await.unwrap();
+        let app = BackworksServer::new(Arc::new(config), manager, None).unwrap().create_app().unwrap();
+
+        let response = send(app.clone(), "/broken").await;
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "upstream failed");
+        assert_eq!(body["path"], "/broken");
+        assert!(body["cause"].as_str().unwrap().contains("Plugin mode requires plugin name"));
+        assert_eq!(plugin.errors.lock().unwrap().last().unwrap().status, 502);
+
+        let response = send(app, "/echo").await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_mode_chain_falls_back_until_a_mode_succeeds() {
+        let mut config = test_config();
+        let mut echo = config.endpoints["missing_plugin"].clone();
+        echo.path = "/echo".to_string();
+        echo.mode = None;
+        // No runtime handler and no database plugin: both fail before the plugin answers
+        echo.modes = vec![ExecutionMode::Runtime, ExecutionMode::Database, ExecutionMode::Plugin];
+        echo.plugin = Some("recording".to_string());
+        config.endpoints.insert("echo".to_string(), echo);
+        let broken = config.endpoints.get_mut("missing_plugin").unwrap();
+        broken.mode = None;
+        broken.modes = vec![ExecutionMode::Runtime, ExecutionMode::Plugin];
+        let manager = PluginManager::new();
+        manager.register_plugin(Arc::new(RecordingPlugin::default()), None, None).await.unwrap();
+        let app = BackworksServer::new(Arc::new(config), manager, None).unwrap().create_app().unwrap();
+
+        let response = send(app.clone(), "/echo").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap()["plugin"], "recording");
+
+        // The last mode's error is reported when every mode fails
+        let response = send(app, "/broken").await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(response.extensions().get::<RequestError>().unwrap().message.contains("Plugin mode requires plugin name"));
+    }
+
+    #[tokio::test]
+    async fn test_mock_mode_serves_generated_collections_and_items() {
+        let mut config = test_config();
+        config.endpoints.extend(serde_yaml::from_str::<HashMap<String, crate::config::EndpointConfig>>(r#"
+users:
+  path: /users
+  mode: mock
+  mock: { count: 3, schema: { id: $seq, name: $name, email: $email } }
+user:
+  path: /users/{id:int}
+  mode: mock
+  mock: { item_of: users }
+"#).unwrap());
+        let app = BackworksServer::new(Arc::new(config), PluginManager::new(), None).unwrap().create_app().unwrap();
+
+        let response = send(app.clone(), "/users").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let users: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(users.as_array().unwrap().len(), 3);
+
+        let response = send(app.clone(), "/users/2").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), users[1]);
+
+        assert_eq!(send(app, "/users/7").await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_deliveries_need_a_valid_signature() {
+        let mut config = test_config();
+        config.endpoints.extend(serde_yaml::from_str::<HashMap<String, crate::config::EndpointConfig>>(r#"
+github_events:
+  path: /webhooks/github
+  methods: [POST]
+  mode: mock
+  mock: { schema: { received: true } }
+  webhook: { provider: github, secret: hush }
+"#).unwrap());
+        let app = BackworksServer::new(Arc::new(config), PluginManager::new(), None).unwrap().create_app().unwrap();
+
+        let body = r#"{"action":"opened"}"#;
+        let tag = ring::hmac::sign(&ring::hmac::Key::new(ring::hmac::HMAC_SHA256, b"hush"), body.as_bytes());
+        let signature = format!("sha256={}", tag.as_ref().iter().map(|byte| format!("{:02x}", byte)).collect::<String>());
+        let deliver = |signature: Option<&str>| {
+            let mut request = axum::http::Request::post("/webhooks/github").header(header::CONTENT_TYPE, "application/json");
+            if let Some(signature) = signature {
+                request = request.header("x-hub-signature-256", signature);
+            }
+            app.clone().oneshot(request.body(axum::body::Body::from(body)).unwrap())
+        };
+
+        let response = deliver(Some(&signature)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), serde_json::json!({ "received": true }));
+
+        let response = deliver(Some("sha256=00")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.extensions().get::<RequestError>().unwrap().message, "Invalid webhook signature");
+        let response = deliver(None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.extensions().get::<RequestError>().unwrap().message, "Missing x-hub-signature-256 header");
+    }
+
+    #[tokio::test]
+    async fn test_scenarios_replace_responses_until_deactivated() {
+        let mut config = test_config();
+        config.endpoints.extend(serde_yaml::from_str::<HashMap<String, crate::config::EndpointConfig>>(r#"
+charge:
+  path: /charge
+  mode: mock
+  mock: { schema: { paid: true } }
+  scenarios:
+    payment_declined: { status: 402, body: { error: card_declined } }
+"#).unwrap());
+        let app = BackworksServer::new(Arc::new(config), PluginManager::new(), None).unwrap().create_app().unwrap();
+        assert_eq!(send(app.clone(), "/charge").await.status(), StatusCode::OK);
+
+        let switch = |method: &str, body: &str| as_admin(axum::http::Request::builder())
+            .method(method)
+            .uri("/_backworks/scenarios/active")
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(axum::body::Body::from(body.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(switch("PUT", r#"{"scenario": "payment_declined"}"#)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send(app.clone(), "/charge").await;
+        assert_eq!(response.status(), StatusCode::PAYMENT_REQUIRED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), serde_json::json!({"error": "card_declined"}));
+
+        let response = app.clone().oneshot(switch("PUT", r#"{"scenario": "outage"}"#)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        app.clone().oneshot(switch("DELETE", "")).await.unwrap();
+        assert_eq!(send(app.clone(), "/charge").await.status(), StatusCode::OK);
+
+        let response = send_admin(app.clone(), "/_backworks/scenarios").await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), serde_json::json!({"active": null, "scenarios": {"payment_declined": ["charge"]}}));
+    }
+
+    #[tokio::test]
+    async fn test_journaled_requests_rebuild_state_on_replay() {
+        let dir = std::env::temp_dir().join(format!("backworks_journal_{}", uuid::Uuid::new_v4()));
+        let mut config = test_config();
+        config.endpoints.extend(serde_yaml::from_str::<HashMap<String, crate::config::EndpointConfig>>(r#"
+todos:
+  path: /todos
+  methods: [GET, POST]
+  mode: mock
+  mock: { stateful: true, schema: { title: $word } }
+"#).unwrap());
+        config.journal = Some(serde_yaml::from_str(&format!("path: {}", dir.join("journal.jsonl").display())).unwrap());
+        let config = Arc::new(config);
+
+        let app = BackworksServer::new(config.clone(), PluginManager::new(), None).unwrap().create_app().unwrap();
+        for (title, key) in [("milk", Some("k1")), ("milk", Some("k1")), ("eggs", None)] {
+            let mut request = axum::http::Request::post("/todos").header(http::header::CONTENT_TYPE, "application/json");
+            if let Some(key) = key {
+                request = request.header("idempotency-key", key);
+            }
+            let body = axum::body::Body::from(serde_json::json!({ "title": title }).to_string());
+            assert_eq!(app.clone().oneshot(request.body(body).unwrap()).await.unwrap().status(), StatusCode::CREATED);
+        }
+        assert_eq!(std::fs::read_to_string(dir.join("journal.jsonl")).unwrap().lines().count(), 3);
+
+        // A fresh server starts empty until the journal is replayed
+        let server = BackworksServer::new(config, PluginManager::new(), None).unwrap();
+        let summary = server.replay_journal().await.unwrap();
+        assert_eq!(summary, ReplaySummary { replayed: 2, failed: 0, duplicates: 1 });
+        let response = send(server.create_app().unwrap(), "/todos").await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let titles: Vec<Value> = serde_json::from_slice::<Value>(&body).unwrap().as_array().unwrap().iter().map(|todo| todo["title"].clone()).collect();
+        assert_eq!(titles, vec![serde_json::json!("milk"), serde_json::json!("eggs")]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_posted_journal_entries_are_authenticated_like_requests() {
+        let dir = std::env::temp_dir().join(format!("backworks_journal_{}", uuid::Uuid::new_v4()));
+        let mut config = test_config();
+        config.endpoints.extend(serde_yaml::from_str::<HashMap<String, crate::config::EndpointConfig>>(r#"
+todos:
+  path: /todos
+  methods: [GET, POST]
+  mode: mock
+  mock: { stateful: true, schema: { title: $word } }
+  middleware: [auth]
+"#).unwrap());
+        config.journal = Some(serde_yaml::from_str(&format!("{{ path: {}, admin_api: true }}", dir.join("journal.jsonl").display())).unwrap());
+        let app = BackworksServer::new(Arc::new(config), PluginManager::new(), None).unwrap().create_app().unwrap();
+
+        // The identity an entry claims is not taken on trust
+        let mut headers = HeaderMap::new();
+        headers.insert(http::header::CONTENT_TYPE, "application/json".parse().unwrap());
+        let admin = AuthContext {
+            subject: "admin".to_string(),
+            method: crate::auth::AuthMethod::Jwt,
+            email: None,
+            roles: vec!["admin".to_string()],
+            claims: HashMap::new(),
+        };
+        let entry = JournalEntry::new("todos", "POST", "/todos", &HashMap::new(), &HashMap::new(), &headers, Some(&admin), br#"{"title":"milk"}"#);
+        let replay = |entries: Value| as_admin(axum::http::Request::post("/_backworks/journal/replay"))
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(axum::body::Body::from(entries.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(replay(serde_json::json!([entry]))).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<ReplaySummary>(&body).unwrap(), ReplaySummary { replayed: 0, failed: 1, duplicates: 0 });
+
+        // Nor are entries replayed without the admin token
+        let mut anonymous = replay(serde_json::json!([entry]));
+        anonymous.headers_mut().remove(http::header::AUTHORIZATION);
+        assert_eq!(app.oneshot(anonymous).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_capture_records_exchanges_while_a_session_is_active() {
+        let mut config = test_config();
+        config.endpoints.extend(serde_yaml::from_str::<HashMap<String, crate::config::EndpointConfig>>(r#"
+todos:
+  path: /todos
+  methods: [POST]
+  mode: mock
+  mock: { stateful: true, schema: { title: $word } }
+"#).unwrap());
+        let events = crate::broadcast::BroadcastHub::default();
+        let mut live = events.subscribe(None).unwrap();
+        let capture = CaptureHandler::new(Default::default()).with_events(events);
+        let app = BackworksServer::new(Arc::new(config), PluginManager::new(), None).unwrap()
+            .with_capture(capture.clone())
+            .create_app().unwrap();
+        let post = |title: &str| axum::http::Request::post("/todos?source=test")
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header(http::header::AUTHORIZATION, "Bearer secret")
+            .body(axum::body::Body::from(serde_json::json!({ "title": title }).to_string()))
+            .unwrap();
+
+        // Nothing is recorded until a session starts
+        assert_eq!(app.clone().oneshot(post("early")).await.unwrap().status(), StatusCode::CREATED);
+        let session = capture.start_session("tail".to_string()).await.unwrap();
+        let response = app.clone().oneshot(post("milk")).await.unwrap();
+        let body: Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["title"], "milk");
+        send_admin(app, "/_backworks/usage-report").await;
+
+        let requests = capture.get_captured_requests(session, None).await;
+        assert_eq!(requests.len(), 1);
+        let exchange = &requests[0];
+        assert_eq!((exchange.method.as_str(), exchange.path.as_str()), ("POST", "/todos"));
+        assert_eq!(exchange.query_params["source"], "test");
+        assert_eq!(exchange.body, Some(serde_json::json!({ "title": "milk" })));
+        assert!(!exchange.headers.contains_key("authorization"));
+        let response = exchange.response.as_ref().unwrap();
+        assert_eq!((response.status_code, response.body.clone()), (201, Some(body)));
+
+        // Published on arrival, then again with the response
+        assert!(live.recv().await.unwrap().data["response"].is_null());
+        assert_eq!(live.recv().await.unwrap().data["response"]["status_code"], 201);
+    }
+
+    #[tokio::test]
+    async fn test_retention_and_purge_remove_endpoint_data() {
+        let dir = std::env::temp_dir().join(format!("backworks_retention_{}", uuid::Uuid::new_v4()));
+        let mut config = test_config();
+        config.endpoints.extend(serde_yaml::from_str::<HashMap<String, crate::config::EndpointConfig>>(r#"
+todos:
+  path: /todos
+  methods: [GET, POST]
+  mode: mock
+  mock: { stateful: true, schema: { title: $word } }
+  retention: { max_age: 7d, max_items: 2 }
+"#).unwrap());
+        config.journal = Some(serde_yaml::from_str(&format!("path: {}", dir.join("journal.jsonl").display())).unwrap());
+        let capture = CaptureHandler::new(Default::default());
+        capture.start_session("retention".to_string()).await.unwrap();
+        let server = BackworksServer::new(Arc::new(config), PluginManager::new(), None).unwrap().with_capture(capture);
+        let app = server.create_app().unwrap();
+        let post = |uri: &str, body: Value| axum::http::Request::post(uri)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(axum::body::Body::from(body.to_string()))
+            .unwrap();
+        for title in ["milk", "eggs", "bread"] {
+            assert_eq!(app.clone().oneshot(post("/todos", serde_json::json!({ "title": title }))).await.unwrap().status(), StatusCode::CREATED);
+        }
+
+        // The sweep keeps the newest two of everything
+        let policies = RetentionPolicy::from_config(&server.state.config).unwrap();
+        let summary = crate::retention::purge(&server.state, &policies).await;
+        assert_eq!((summary.mock_items, summary.captured_requests, summary.journal_entries), (1, 1, 1));
+        let body = axum::body::to_bytes(send(app.clone(), "/todos").await.into_body(), usize::MAX).await.unwrap();
+        let titles: Vec<Value> = serde_json::from_slice::<Value>(&body).unwrap().as_array().unwrap().iter().map(|todo| todo["title"].clone()).collect();
+        assert_eq!(titles, vec![serde_json::json!("eggs"), serde_json::json!("bread")]);
+
+        let purge = |body: Value| async {
+            let mut request = post("/_backworks/purge", body);
+            request.headers_mut().insert(http::header::AUTHORIZATION, format!("Bearer {}", ADMIN_TOKEN).parse().unwrap());
+            let response = app.clone().oneshot(request).await.unwrap();
+            let status = response.status();
+            (status, serde_json::from_slice::<Value>(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap_or_default())
+        };
+        let (status, summary) = purge(serde_json::json!({ "endpoint": "/todos", "older_than": "1h" })).await;
+        assert_eq!((status, summary["endpoints"].clone()), (StatusCode::OK, serde_json::json!(["todos"])));
+        assert_eq!(summary["mock_collections"], 0);
+
+        let (_, summary) = purge(serde_json::json!({ "endpoint": "/todos" })).await;
+        assert_eq!(summary["mock_collections"], 1);
+        assert_eq!(summary["captured_requests"], 3);
+        assert_eq!(summary["journal_entries"], 2);
+        let body = axum::body::to_bytes(send(app.clone(), "/todos").await.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), serde_json::json!([]));
+        assert_eq!(journal::load(&dir.join("journal.jsonl")).await.unwrap().len(), 0);
+
+        assert_eq!(purge(serde_json::json!({ "endpoint": "/nowhere" })).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(purge(serde_json::json!({ "older_than": "soon" })).await.0, StatusCode::BAD_REQUEST);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_admin_api_controls_endpoints_behind_its_token() {
+        let dir = std::env::temp_dir().join(format!("backworks_admin_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let blueprint = dir.join("backworks.yaml");
+        let write_blueprint = |extra: &str| std::fs::write(&blueprint, format!(r#"
+name: shop
+admin: {{ token: secret }}
+endpoints:
+  users:
+    path: /users
+    methods: [GET]
+    mode: mock
+    mock: {{ schema: {{ name: $name }} }}
+    middleware: [cache]
+{}"#, extra)).unwrap();
+        write_blueprint("");
+        let config = crate::config::load_yaml_config(&blueprint).await.unwrap();
+        let reloader = Reloader::new(blueprint.clone(), MiddlewareRegistry::default(), LoadBalancerRegistry::default());
+        let server = BackworksServer::new(Arc::new(config), PluginManager::new(), None).unwrap()
+            .with_capture(CaptureHandler::new(Default::default()))
+            .with_reloader(Arc::new(reloader));
+        let app = server.served_app().unwrap();
+        let request = |method: Method, uri: &str, body: Option<Value>| {
+            let builder = axum::http::Request::builder().method(method).uri(uri).header(header::AUTHORIZATION, "Bearer secret");
+            match body {
+                Some(body) => builder.header(header::CONTENT_TYPE, "application/json").body(axum::body::Body::from(body.to_string())),
+                None => builder.body(axum::body::Body::empty()),
+            }.unwrap()
+        };
+        let json = |response: axum::response::Response| async {
+            serde_json::from_slice::<Value>(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap()
+        };
+
+        // Every admin route needs the token, and there is no admin API
+        // without one; endpoints do not
+        assert_eq!(send(app.clone(), "/_backworks/endpoints").await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(send(app.clone(), "/_backworks/usage-report").await.status(), StatusCode::UNAUTHORIZED);
+        let mut open = test_config();
+        open.admin = None;
+        let open = BackworksServer::new(Arc::new(open), PluginManager::new(), None).unwrap().create_app().unwrap();
+        assert_eq!(send(open, "/_backworks/endpoints").await.status(), StatusCode::NOT_FOUND);
+        // The state store's contents are only exposed when asked for
+        let mut stateful = test_config();
+        stateful.state = Some(serde_yaml::from_str("seed: null").unwrap());
+        let stateful = BackworksServer::new(Arc::new(stateful), PluginManager::new(), None).unwrap().create_app().unwrap();
+        assert_eq!(send_admin(stateful, "/_backworks/state/export").await.status(), StatusCode::NOT_FOUND);
+        let endpoints = json(app.clone().oneshot(request(Method::GET, "/_backworks/endpoints", None)).await.unwrap()).await;
+        assert_eq!(endpoints, serde_json::json!([{"name": "users", "path": "/users", "methods": ["GET"], "mode": "mock", "enabled": true}]));
+
+        let disable = request(Method::PUT, "/_backworks/endpoints/users", Some(serde_json::json!({"enabled": false})));
+        assert_eq!(app.clone().oneshot(disable).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(send(app.clone(), "/users").await.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let enable = request(Method::PUT, "/_backworks/endpoints/users", Some(serde_json::json!({"enabled": true})));
+        app.clone().oneshot(enable).await.unwrap();
+        assert_eq!(send(app.clone(), "/users").await.headers()["x-cache"], "miss");
+        assert_eq!(send(app.clone(), "/users").await.headers()["x-cache"], "hit");
+        let flushed = json(app.clone().oneshot(request(Method::POST, "/_backworks/caches/flush", None)).await.unwrap()).await;
+        assert_eq!(flushed["flushed"], 1);
+        assert_eq!(send(app.clone(), "/users").await.headers()["x-cache"], "miss");
+
+        let recording = request(Method::PUT, "/_backworks/capture", Some(serde_json::json!({"recording": true, "name": "smoke"})));
+        assert_eq!(json(app.clone().oneshot(recording).await.unwrap()).await["session"]["name"], "smoke");
+        let sessions = json(app.clone().oneshot(request(Method::GET, "/_backworks/capture/sessions", None)).await.unwrap()).await;
+        assert_eq!(sessions.as_array().unwrap().len(), 1);
+
+        // A reload serves the new blueprint and keeps runtime switches
+        let disable = request(Method::PUT, "/_backworks/endpoints/users", Some(serde_json::json!({"enabled": false})));
+        app.clone().oneshot(disable).await.unwrap();
+        write_blueprint("  orders: { path: /orders, methods: [GET], mode: mock, mock: { schema: { id: $uuid } } }");
+        let summary = json(app.clone().oneshot(request(Method::POST, "/_backworks/reload", None)).await.unwrap()).await;
+        assert_eq!(summary, serde_json::json!({"endpoints": 2, "added": ["orders"], "removed": []}));
+        assert_eq!(send(app.clone(), "/orders").await.status(), StatusCode::OK);
+        assert_eq!(send(app.clone(), "/users").await.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        std::fs::write(&blueprint, "name: shop\nendpoints: {}\n").unwrap();
+        let response = app.clone().oneshot(request(Method::POST, "/_backworks/reload", None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(send(app, "/orders").await.status(), StatusCode::OK);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_chaos_injects_faults_until_switched_off() {
+        let mut config = test_config();
+        config.endpoints.extend(serde_yaml::from_str::<HashMap<String, crate::config::EndpointConfig>>(r#"
+flaky:
+  path: /flaky
+  mode: mock
+  mock: { schema: { ok: true } }
+  chaos: { error_rate: 1.0, error_statuses: [503], latency: { distribution: fixed, ms: 1 } }
+"#).unwrap());
+        let app = BackworksServer::new(Arc::new(config), PluginManager::new(), None).unwrap().create_app().unwrap();
+        let response = send(app.clone(), "/flaky").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()["x-backworks-fault"], "error");
+
+        let admin = |uri: &str, body: Value| as_admin(axum::http::Request::put(uri))
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(axum::body::Body::from(body.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(admin("/_backworks/chaos", serde_json::json!({"enabled": false}))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(send(app.clone(), "/flaky").await.status(), StatusCode::OK);
+
+        // Truncated bodies announce their full length, then break off
+        app.clone().oneshot(admin("/_backworks/chaos", serde_json::json!({"enabled": true}))).await.unwrap();
+        let response = app.clone().oneshot(admin("/_backworks/chaos/flaky", serde_json::json!({"truncate_rate": 1.0}))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send(app.clone(), "/flaky").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[http::header::CONTENT_LENGTH], "11");
+        assert!(axum::body::to_bytes(response.into_body(), usize::MAX).await.is_err());
+
+        let response = app.clone().oneshot(admin("/_backworks/chaos/users", serde_json::json!({"error_rate": 0.5}))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = app.oneshot(admin("/_backworks/chaos/flaky", serde_json::json!({"error_rate": 2}))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_usage_report_compares_endpoints_with_traffic() {
+        let mut config = test_config();
+        config.endpoints.extend(serde_yaml::from_str::<HashMap<String, crate::config::EndpointConfig>>(r#"
+ping:
+  path: /ping
+  mode: mock
+  mock: { schema: { ok: true } }
+"#).unwrap());
+        let app = BackworksServer::new(Arc::new(config), PluginManager::new(), None).unwrap().create_app().unwrap();
+        assert_eq!(send(app.clone(), "/ping").await.status(), StatusCode::OK);
+        for uri in ["/invoices/1", "/invoices/2"] {
+            assert_eq!(send(app.clone(), uri).await.status(), StatusCode::NOT_FOUND);
+        }
+        let delete = axum::http::Request::delete("/ping").body(axum::body::Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(delete).await.unwrap().status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let response = send_admin(app.clone(), "/_backworks/usage-report?window=30m").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let report: UsageReport = serde_json::from_slice(&body).unwrap();
+        assert_eq!(report.window_seconds, 1800);
+        assert_eq!(report.unused, vec!["missing_plugin", "order"]);
+        assert_eq!(report.endpoints.iter().find(|e| e.endpoint == "ping").unwrap().requests, 1);
+        assert_eq!((report.undeclared[0].path.as_str(), report.undeclared[0].requests), ("/invoices/{id}", 2));
+        assert_eq!((report.method_mismatches[0].endpoint.as_str(), report.method_mismatches[0].method.as_str()), ("ping", "DELETE"));
+
+        assert_eq!(send_admin(app, "/_backworks/usage-report?window=soon").await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_proxy_records_then_replays_upstream_responses() {
+        let hits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = hits.clone();
+        let upstream = Router::new().route("/rates/:currency", get(move |Path(currency): Path<String>, headers: HeaderMap| async move {
+            let hit = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            let language = headers.get("accept-language").and_then(|v| v.to_str().ok()).unwrap_or("-").to_string();
+            Json(serde_json::json!({ "currency": currency, "language": language, "hit": hit }))
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move { axum::serve(listener, upstream).await });
+
+        let cassette = std::env::temp_dir().join(format!("backworks_cassette_{}.json", uuid::Uuid::new_v4()));
+        let blueprint = |upstream: &str| {
+            let mut config = test_config();
+            config.endpoints.extend(serde_yaml::from_str::<HashMap<String, crate::config::EndpointConfig>>(&format!(r#"
+rates:
+  path: "/rates/{{currency}}"
+  mode: proxy
+  proxy:
+    upstream: "{}"
+    timeout_ms: 2000
+    replay:
+      cassette: "{}"
+      match: {{ headers: [accept-language] }}
+"#, upstream, cassette.display())).unwrap());
+            BackworksServer::new(Arc::new(config), PluginManager::new(), None).unwrap().create_app().unwrap()
+        };
+        let get_rate = |app: Router, language: &'static str| async move {
+            let request = axum::http::Request::get("/rates/EUR?precision=2").header("accept-language", language).body(axum::body::Body::empty()).unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            serde_json::from_slice::<Value>(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap()
+        };
+
+        let app = blueprint(&format!("http://{}", address));
+        assert_eq!(get_rate(app.clone(), "de").await, serde_json::json!({ "currency": "EUR", "language": "de", "hit": 1 }));
+        assert_eq!(get_rate(app.clone(), "de").await["hit"], 1);
+        assert_eq!(get_rate(app.clone(), "fr").await["hit"], 2);
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        // A fresh server replays the cassette without the upstream
+        server.abort();
+        let app = blueprint("http://127.0.0.1:9");
+        assert_eq!(get_rate(app.clone(), "fr").await["language"], "fr");
+        let request = axum::http::Request::get("/rates/USD").body(axum::body::Body::empty()).unwrap();
+        assert_eq!(app.oneshot(request).await.unwrap().status(), StatusCode::INTERNAL_SERVER_ERROR);
+        std::fs::remove_file(&cassette).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_request_log_shows_endpoints_and_upstream_targets() {
+        let upstream = Router::new().route("/stock", get(|| async { Json(serde_json::json!({ "count": 3 })) }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, upstream).await });
+
+        let mut config = test_config();
+        config.endpoints.extend(serde_yaml::from_str::<HashMap<String, crate::config::EndpointConfig>>(&format!(
+            "stock: {{ path: /stock, mode: proxy, proxy: {{ upstream: \"http://{}\" }} }}", address
+        )).unwrap());
+        let dashboard = Arc::new(Dashboard::new(serde_yaml::from_str("enabled: true").unwrap()));
+        let app = BackworksServer::new(Arc::new(config), PluginManager::new(), Some(dashboard.clone())).unwrap().create_app().unwrap();
+        assert_eq!(send(app.clone(), "/stock").await.status(), StatusCode::OK);
+        assert_eq!(send(app.clone(), "/nowhere").await.status(), StatusCode::NOT_FOUND);
+
+        let page = |query: &str| {
+            let request = as_admin(axum::http::Request::get(format!("/api/requests{}", query))).body(axum::body::Body::empty()).unwrap();
+            let router = dashboard.router();
+            async move {
+                let response = router.oneshot(request).await.unwrap();
+                (response.status(), serde_json::from_slice::<Value>(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap())
+            }
+        };
+        let (status, log) = page("").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(log["total"], 2);
+        assert_eq!(log["entries"][0]["path"], "/nowhere");
+        assert!(log["entries"][0].get("endpoint").is_none());
+        let proxied = &log["entries"][1];
+        assert_eq!((proxied["method"].as_str(), proxied["status"].as_u64()), (Some("GET"), Some(200)));
+        assert_eq!(proxied["endpoint"], "stock");
+        assert_eq!(proxied["upstream"], format!("http://{}", address));
+
+        let (_, log) = page("?endpoint=stock&status=2xx").await;
+        assert_eq!(log["entries"].as_array().unwrap().len(), 1);
+        assert_eq!(page("?status=6xx").await.0, StatusCode::BAD_REQUEST);
+        let anonymous = dashboard.router().oneshot(axum::http::Request::get("/api/requests").body(axum::body::Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+
+        // The proxy measures its upstream calls in the dashboard's metrics
+        let upstream = dashboard.metrics().snapshot(&crate::metrics_recorder::MetricSource::upstream(&format!("http://{}", address))).unwrap();
+        assert_eq!((upstream.requests, upstream.errors), (1, 0));
+        assert_eq!(send(app, "/elsewhere").await.status(), StatusCode::NOT_FOUND);
+        let unmatched = dashboard.metrics().snapshot(&crate::metrics_recorder::MetricSource::endpoint("GET", "/(unmatched)")).unwrap();
+        assert_eq!((unmatched.requests, unmatched.errors), (2, 2));
+        assert!(dashboard.metrics().snapshot(&crate::metrics_recorder::MetricSource::endpoint("GET", "/nowhere")).is_none());
+
+        // And the architecture graph colors it with that
+        let response = dashboard.router().oneshot(axum::http::Request::get("/api/architecture").body(axum::body::Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let graph: Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        let node = |id: String| graph["nodes"].as_array().unwrap().iter().find(|node| node["id"] == id.as_str()).cloned().unwrap();
+        assert_eq!(node("endpoint:stock".to_string())["health"], "healthy");
+        assert_eq!(node(format!("upstream:http://{}", address))["health"], "healthy");
+    }
+
+    #[tokio::test]
+    async fn test_proxy_targets_can_be_drained_and_quarantined() {
+        let mut addresses = Vec::new();
+        for name in ["a", "b"] {
+            let upstream = Router::new().route("/stock", get(move || async move { Json(serde_json::json!({ "from": name })) }));
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            addresses.push(format!("http://{}", listener.local_addr().unwrap()));
+            tokio::spawn(async move { axum::serve(listener, upstream).await });
+        }
+        let mut config = test_config();
+        config.endpoints.extend(serde_yaml::from_str::<HashMap<String, crate::config::EndpointConfig>>(&format!(r#"
+stock:
+  path: /stock
+  mode: proxy
+  proxy: {{ targets: ["{}", "{}"] }}
+"#, addresses[0], addresses[1])).unwrap());
+        let app = BackworksServer::new(Arc::new(config), PluginManager::new(), None).unwrap().create_app().unwrap();
+        let served_by = |app: Router| async move {
+            let response = send(app, "/stock").await;
+            let status = response.status();
+            let body: Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+            (status, body["from"].as_str().unwrap_or_default().to_string())
+        };
+        let operate = |app: Router, operation: &str, command: Value| {
+            let request = as_admin(axum::http::Request::post(format!("/_backworks/proxy/targets/stock/{}", operation)))
+                .header("content-type", "application/json")
+                .body(axum::body::Body::from(command.to_string()))
+                .unwrap();
+            app.oneshot(request)
+        };
+
+        assert_eq!(served_by(app.clone()).await.1, "a");
+        assert_eq!(served_by(app.clone()).await.1, "b");
+
+        let response = operate(app.clone(), "drain", serde_json::json!({ "target": addresses[0], "reason": "deploy" })).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let report: TargetsReport = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(report.targets[0].state, crate::targets::TargetState::Drained);
+        assert_eq!(report.audit[0].reason.as_deref(), Some("deploy"));
+        for _ in 0..2 {
+            assert_eq!(served_by(app.clone()).await.1, "b");
+        }
+
+        let quarantine = serde_json::json!({ "target": addresses[1], "duration": "10m" });
+        assert_eq!(operate(app.clone(), "quarantine", quarantine).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(served_by(app.clone()).await.0, StatusCode::SERVICE_UNAVAILABLE);
+
+        assert_eq!(operate(app.clone(), "enable", serde_json::json!({ "target": addresses[0] })).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(served_by(app.clone()).await.1, "a");
+        let unknown = operate(app.clone(), "quarantine", serde_json::json!({ "target": "http://elsewhere" })).await.unwrap();
+        assert_eq!(unknown.status(), StatusCode::BAD_REQUEST);
+
+        let response = send_admin(app, "/_backworks/proxy/targets").await;
+        let report: TargetsReport = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(report.audit.len(), 3);
+        assert_eq!(report.targets[1].state, crate::targets::TargetState::Quarantined);
+    }
+
+    #[tokio::test]
+    async fn test_plugins_provide_load_balancing_strategies() {
+        let mut addresses = Vec::new();
+        for zone in ["eu", "us"] {
+            let upstream = Router::new().route("/stock", get(move || async move { Json(serde_json::json!({ "zone": zone })) }));
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            addresses.push(format!("http://{}", listener.local_addr().unwrap()));
+            tokio::spawn(async move { axum::serve(listener, upstream).await });
+        }
+        let mut config = test_config();
+        config.endpoints.extend(serde_yaml::from_str::<HashMap<String, crate::config::EndpointConfig>>(&format!(r#"
+stock:
+  path: /stock
+  mode: proxy
+  proxy:
+    targets:
+      - {{ url: "{}", labels: {{ zone: eu }} }}
+      - {{ url: "{}", labels: {{ zone: us }} }}
+    load_balancing: {{ algorithm: same_zone, config: {{ header: x-region }} }}
+"#, addresses[0], addresses[1])).unwrap());
+        let config = Arc::new(config);
+        assert!(BackworksServer::new(config.clone(), PluginManager::new(), None).unwrap().create_app().is_err());
+
+        let manager = PluginManager::new();
+        manager.register_plugin(Arc::new(RecordingPlugin::default()), None, None).await.unwrap();
+        let server = BackworksServer::new(config, manager.clone(), None).unwrap()
+            .with_load_balancers(manager.load_balancer_registry().await);
+        let app = server.create_app().unwrap();
+        let zone_of = |region: &'static str| {
+            let app = app.clone();
+            async move {
+                let request = axum::http::Request::get("/stock").header("x-region", region).body(axum::body::Body::empty()).unwrap();
+                let response = app.oneshot(request).await.unwrap();
+                let body: Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+                body["zone"].as_str().unwrap().to_string()
+            }
+        };
+
+        assert_eq!(zone_of("us").await, "us");
+        assert_eq!(zone_of("eu").await, "eu");
+        assert_eq!(zone_of("us").await, "us");
+        // Without a zone match the least used target wins
+        assert_eq!(zone_of("ap").await, "eu");
+
+        let targets = server.state.proxies.targets().targets;
+        assert_eq!((targets[0].requests, targets[1].requests), (2, 2));
+        assert!(targets.iter().all(|target| target.latency_ms.is_some() && target.failures == 0));
+    }
+
+    #[tokio::test]
+    async fn test_request_budget_is_propagated_to_upstreams() {
+        let upstream = Router::new().route("/report", get(|headers: HeaderMap| async move {
+            let budget: u64 = headers[crate::deadline::REQUEST_TIMEOUT_HEADER].to_str().unwrap().parse().unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(if budget > 1_000 { 0 } else { 2_000 })).await;
+            Json(serde_json::json!({ "budget_ms": budget }))
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, upstream).await });
+
+        let mut config = test_config();
+        config.server.request_timeout = Some("5s".to_string());
+        config.endpoints.extend(serde_yaml::from_str::<HashMap<String, crate::config::EndpointConfig>>(&format!(r#"
+report:
+  path: /report
+  mode: proxy
+  proxy: {{ upstream: "http://{}" }}
+"#, address)).unwrap());
+        let app = BackworksServer::new(Arc::new(config), PluginManager::new(), None).unwrap().create_app().unwrap();
+
+        let response = send(app.clone(), "/report").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        let budget = body["budget_ms"].as_u64().unwrap();
+        assert!((4_000..=5_000).contains(&budget), "upstream budget {}", budget);
+
+        // A client that gives up sooner cancels the upstream call
+        let started = std::time::Instant::now();
+        let request = axum::http::Request::get("/report").header("x-request-timeout", "300ms").body(axum::body::Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert!(started.elapsed() < std::time::Duration::from_millis(1_500));
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_timeout_cancels_a_hung_handler() {
+        struct Hang(Arc<std::sync::atomic::AtomicBool>);
+        struct Cancelled(Arc<std::sync::atomic::AtomicBool>);
+        impl Drop for Cancelled {
+            fn drop(&mut self) {
+                self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+            }
+        }
+        #[async_trait::async_trait]
+        impl crate::pipeline::EndpointMiddleware for Hang {
+            async fn handle(&self, _request: axum::extract::Request, _next: crate::pipeline::Next<'_>) -> axum::response::Response {
+                let _cancelled = Cancelled(self.0.clone());
+                std::future::pending().await
+            }
+        }
+
+        let cancelled = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let mut registry = MiddlewareRegistry::default();
+        let flag = cancelled.clone();
+        registry.register("hang", move |_| Ok(Arc::new(Hang(flag.clone())) as Arc<dyn crate::pipeline::EndpointMiddleware>));
+        let mut config = test_config();
+        config.server.request_timeout = Some("30s".to_string());
+        let endpoint = config.endpoints.get_mut("missing_plugin").unwrap();
+        endpoint.timeout = Some("100ms".to_string());
+        endpoint.middleware = vec![crate::config::MiddlewareSpec::named("hang")];
+        let app = BackworksServer::new(Arc::new(config), PluginManager::new(), None).unwrap()
+            .with_middleware(registry)
+            .create_app()
+            .unwrap();
+
+        let started = std::time::Instant::now();
+        let response = send(app, "/broken").await;
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert!(cancelled.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_responses_are_compressed_for_clients_that_accept_it() {
+        let mut config = test_config();
+        config.server.compression = Some(serde_yaml::from_str("min_size: \"1\"").unwrap());
+        let app = BackworksServer::new(Arc::new(config), PluginManager::new(), None).unwrap().create_app().unwrap();
+
+        let request = axum::http::Request::get("/health").header(http::header::ACCEPT_ENCODING, "gzip;q=0.5, br").body(axum::body::Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.headers()[http::header::CONTENT_ENCODING], "br");
+        assert_eq!(response.headers()[http::header::VARY], "accept-encoding");
+
+        let response = send(app, "/health").await;
+        assert!(!response.headers().contains_key(http::header::CONTENT_ENCODING));
+    }
+
+    #[tokio::test]
+    async fn test_handler_files_are_streamed_as_downloads() {
+        let dir = std::env::temp_dir().join(format!("backworks_download_{}", uuid::Uuid::new_v4()));
+        let root = dir.join("exports");
+        std::fs::create_dir_all(&root).unwrap();
+        let report = root.join("report.tmp");
+        std::fs::write(&report, "id,total\n1,9.5\n").unwrap();
+        let secret = dir.join("secret.txt");
+        std::fs::write(&secret, "hunter2").unwrap();
+
+        let mut config = test_config();
+        config.server.downloads = Some(root.clone());
+        let mut add = |name: &str, output: Value| {
+            let mut endpoint = config.endpoints["missing_plugin"].clone();
+            endpoint.path = format!("/{}", name);
+            endpoint.mode = Some(ExecutionMode::Runtime);
+            endpoint.runtime = Some(crate::config::RuntimeConfig {
+                language: "javascript".to_string(),
+                handler: format!("function handler(req) {{ return {}; }}", output),
+                timeout: None,
+                memory_limit: None,
+                environment: None,
+                requirements: None,
+                working_dir: None,
+            });
+            config.endpoints.insert(name.to_string(), endpoint);
+        };
+        add("export", serde_json::json!({ "file": report, "download_name": "report.csv", "temporary": true }));
+        add("escape", serde_json::json!({ "file": root.join("../secret.txt"), "temporary": true }));
+        add("garbled", serde_json::json!({ "file": secret, "content_type": "text/plain\nx" }));
+
+        // Other modes only ever return data
+        let mut proxied = config.endpoints["missing_plugin"].clone();
+        proxied.path = "/proxied".to_string();
+        proxied.plugin = Some("recording".to_string());
+        let output = serde_json::json!({ "file": secret, "temporary": true });
+        proxied.template = Some(serde_yaml::from_str(&format!("response_template: '{}'", output)).unwrap());
+        config.endpoints.insert("proxied".to_string(), proxied);
+
+        let manager = PluginManager::new();
+        manager.register_plugin(Arc::new(RecordingPlugin::default()), None, None).await.unwrap();
+        let app = BackworksServer::new(Arc::new(config), manager, None).unwrap().create_app().unwrap();
+
+        let ranged = axum::http::Request::get("/export")
+            .header(http::header::RANGE, "bytes=9-")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(ranged).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[http::header::CONTENT_TYPE], "text/csv");
+        assert_eq!(response.headers()[http::header::CONTENT_DISPOSITION], "attachment; filename=\"report.csv\"; filename*=UTF-8''report.csv");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"1,9.5\n");
+
+        // The temporary file is gone once sent
+        assert!(!report.exists());
+        let response = send(app.clone(), "/export").await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(response.extensions().get::<RequestError>().unwrap().message.contains("Cannot send file"));
+
+        // Files outside the download root are neither sent nor deleted
+        let response = send(app.clone(), "/escape").await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(response.extensions().get::<RequestError>().unwrap().message.contains("outside the download root"));
+        let response = send(app.clone(), "/proxied").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap()["file"], serde_json::json!(secret));
+        assert!(secret.exists());
+
+        let response = send(app, "/garbled").await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Answers database endpoints with the output of a handler emitting
+    /// events, emails and jobs
+    struct EventsPlugin;
+
+    #[async_trait::async_trait]
+    impl BackworksPlugin for EventsPlugin {
+        fn name(&self) -> &str { "events" }
+        fn version(&self) -> &str { "0.0.0" }
+        fn description(&self) -> &str { "emits events" }
+        async fn initialize(&self, _config: &Value) -> Result<()> { Ok(()) }
+        async fn shutdown(&self) -> Result<()> { Ok(()) }
+
+        async fn process_endpoint_data(&self, endpoint: &str, _method: &str, _data: &str) -> Result<Option<String>> {
+            let events = match endpoint {
+                "order" => serde_json::json!([
+                    { "topic": "orders.created", "key": "7", "data": { "id": 7 } },
+                    { "topic": "orders.audit" },
+                ]),
+                _ => serde_json::json!([{ "key": "7" }]),
+            };
+            let emails = serde_json::json!([{ "to": "ada@example.com", "template": "receipt" }]);
+            let jobs = serde_json::json!([{ "name": "ship", "payload": { "id": 7 } }]);
+            Ok(Some(serde_json::json!({ "status": 201, "body": { "id": 7 }, "events": events, "emails": emails, "jobs": jobs }).to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handler_events_emails_and_jobs_are_attached_to_the_response() {
+        let mut config = test_config();
+        let mut order = config.endpoints["missing_plugin"].clone();
+        order.path = "/orders".to_string();
+        order.mode = Some(ExecutionMode::Database);
+        config.endpoints.insert("order".to_string(), order.clone());
+        order.path = "/invalid".to_string();
+        config.endpoints.insert("invalid".to_string(), order);
+        let manager = PluginManager::new();
+        manager.register_plugin(Arc::new(EventsPlugin), None, None).await.unwrap();
+        let app = BackworksServer::new(Arc::new(config), manager, None).unwrap().create_app().unwrap();
+
+        let response = send(app.clone(), "/orders").await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.extensions().get::<HandlerEvents>().unwrap().0, vec![
+            HandlerEvent { topic: "orders.created".to_string(), key: Some("7".to_string()), data: serde_json::json!({"id": 7}) },
+            HandlerEvent { topic: "orders.audit".to_string(), key: None, data: Value::Null },
+        ]);
+        assert_eq!(response.extensions().get::<HandlerEmails>().unwrap().0, vec![
+            serde_json::json!({ "to": "ada@example.com", "template": "receipt" }),
+        ]);
+        assert_eq!(response.extensions().get::<HandlerJobs>().unwrap().0, vec![
+            serde_json::json!({ "name": "ship", "payload": { "id": 7 } }),
+        ]);
+        // Events, emails and jobs are not part of the body
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), serde_json::json!({"id": 7}));
+
+        // Events without a topic are dropped, the response is not
+        let response = send(app, "/invalid").await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(response.extensions().get::<HandlerEvents>().is_none());
+    }
+
+    /// Counts visits in the session, and ends it on `logout`
+    struct VisitsPlugin;
+
+    #[async_trait::async_trait]
+    impl BackworksPlugin for VisitsPlugin {
+        fn name(&self) -> &str { "visits" }
+        fn version(&self) -> &str { "0.0.0" }
+        fn description(&self) -> &str { "counts visits" }
+        async fn initialize(&self, _config: &Value) -> Result<()> { Ok(()) }
+        async fn shutdown(&self) -> Result<()> { Ok(()) }
+
+        async fn process_endpoint_data(&self, endpoint: &str, _method: &str, data: &str) -> Result<Option<String>> {
+            let request: Value = serde_json::from_str(data)?;
+            if endpoint == "page" {
+                return Ok(Some(serde_json::json!({ "status": 200, "body": "<form method=\"post\" action=\"/visit\"></form>" }).to_string()));
+            }
+            let visits = request["session"]["visits"].as_u64().unwrap_or(0) + 1;
+            let session = if endpoint == "logout" { Value::Null } else { serde_json::json!({ "visits": visits }) };
+            Ok(Some(serde_json::json!({ "status": 200, "body": { "visits": visits }, "session": session }).to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sessions_carry_handler_changes_between_requests() {
+        let mut config = test_config();
+        let mut visit = config.endpoints["missing_plugin"].clone();
+        visit.path = "/visit".to_string();
+        visit.mode = Some(ExecutionMode::Database);
+        config.endpoints.insert("visit".to_string(), visit.clone());
+        visit.path = "/logout".to_string();
+        config.endpoints.insert("logout".to_string(), visit);
+        config.sessions = Some(serde_yaml::from_str("{ secret: 0123456789abcdef0123456789abcdef, store: server }").unwrap());
+        let manager = PluginManager::new();
+        manager.register_plugin(Arc::new(VisitsPlugin), None, None).await.unwrap();
+        let server = BackworksServer::new(Arc::new(config), manager, None).unwrap();
+        let store = server.state.state_store.clone();
+        let app = server.create_app().unwrap();
+
+        let visit = |uri: &'static str, cookie: Option<String>| {
+            let mut request = axum::http::Request::get(uri);
+            if let Some(cookie) = cookie {
+                request = request.header(header::COOKIE, cookie);
+            }
+            app.clone().oneshot(request.body(axum::body::Body::empty()).unwrap())
+        };
+        let visits = |response: axum::response::Response| async move {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<Value>(&body).unwrap()["visits"].clone()
+        };
+
+        let first = visit("/visit", None).await.unwrap();
+        let cookie = first.headers()[header::SET_COOKIE].to_str().unwrap().split(';').next().unwrap().to_string();
+        assert!(cookie.starts_with("backworks_session="), "{}", cookie);
+        assert_eq!(visits(first).await, 1);
+        assert_eq!(visits(visit("/visit", Some(cookie.clone())).await.unwrap()).await, 2);
+        assert_eq!(store.keys(crate::session::NAMESPACE).await.len(), 1);
+
+        let ended = visit("/logout", Some(cookie.clone())).await.unwrap();
+        assert!(ended.headers()[header::SET_COOKIE].to_str().unwrap().contains("Max-Age=0"));
+        assert!(store.keys(crate::session::NAMESPACE).await.is_empty());
+        assert_eq!(visits(visit("/visit", Some(cookie)).await.unwrap()).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_csrf_tokens_guard_state_changing_session_requests() {
+        let mut config = test_config();
+        let mut visit = config.endpoints["missing_plugin"].clone();
+        visit.path = "/visit".to_string();
+        visit.methods = vec!["GET".to_string(), "POST".to_string()];
+        visit.mode = Some(ExecutionMode::Database);
+        config.endpoints.insert("visit".to_string(), visit.clone());
+        visit.path = "/open".to_string();
+        visit.csrf = Some(false);
+        config.endpoints.insert("open".to_string(), visit.clone());
+        visit.path = "/page".to_string();
+        visit.csrf = None;
+        visit.transform = Some(serde_yaml::from_str("response: { output_format: html }").unwrap());
+        config.endpoints.insert("page".to_string(), visit);
+        config.sessions = Some(serde_yaml::from_str("{ secret: 0123456789abcdef0123456789abcdef, csrf: { cookie: XSRF-TOKEN } }").unwrap());
+        let manager = PluginManager::new();
+        manager.register_plugin(Arc::new(VisitsPlugin), None, None).await.unwrap();
+        let app = BackworksServer::new(Arc::new(config), manager, None).unwrap().create_app().unwrap();
+
+        let request = |method: Method, uri: &'static str, cookie: &str, token: Option<&str>| {
+            let mut request = axum::http::Request::builder().method(method).uri(uri).header(header::COOKIE, cookie);
+            if let Some(token) = token {
+                request = request.header("x-csrf-token", token);
+            }
+            app.clone().oneshot(request.body(axum::body::Body::empty()).unwrap())
+        };
+
+        // The first response sets the session and a readable token cookie
+        let first = request(Method::GET, "/visit", "", None).await.unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        let cookies: Vec<String> = first.headers().get_all(header::SET_COOKIE).iter()
+            .map(|value| value.to_str().unwrap().to_string())
+            .collect();
+        let value = |name: &str| cookies.iter()
+            .find_map(|cookie| cookie.strip_prefix(&format!("{}=", name)))
+            .map(|cookie| cookie.split(';').next().unwrap().to_string())
+            .unwrap();
+        let session = format!("backworks_session={}", value("backworks_session"));
+        let token = value("XSRF-TOKEN");
+        assert!(cookies.iter().any(|cookie| cookie.starts_with("XSRF-TOKEN=") && !cookie.contains("HttpOnly")));
+
+        let missing = request(Method::POST, "/visit", &session, None).await.unwrap();
+        assert_eq!(missing.status(), StatusCode::FORBIDDEN);
+        assert_eq!(missing.extensions().get::<RequestError>().unwrap().message, "Missing CSRF token");
+        assert_eq!(request(Method::POST, "/visit", &session, Some("forged")).await.unwrap().status(), StatusCode::FORBIDDEN);
+        // Another session's token doesn't pass
+        assert_eq!(request(Method::POST, "/visit", "", Some(&token)).await.unwrap().status(), StatusCode::FORBIDDEN);
+        assert_eq!(request(Method::POST, "/visit", &session, Some(&token)).await.unwrap().status(), StatusCode::OK);
+
+        let form = axum::http::Request::post("/visit")
+            .header(header::COOKIE, &session)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(axum::body::Body::from(format!("name=x&_csrf={}", token)))
+            .unwrap();
+        assert_eq!(app.clone().oneshot(form).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(request(Method::POST, "/open", &session, None).await.unwrap().status(), StatusCode::OK);
+
+        let page = request(Method::GET, "/page", &session, None).await.unwrap();
+        assert_eq!(page.headers()[header::CONTENT_TYPE], "text/html; charset=utf-8");
+        let body = axum::body::to_bytes(page.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(String::from_utf8_lossy(&body), format!(r#"<form method="post" action="/visit"><input type="hidden" name="_csrf" value="{}"></form>"#, token));
+    }
+
+    #[tokio::test]
+    async fn test_production_profile_hides_server_error_details() {
+        let mut config = test_config();
+        config.security = Some(serde_yaml::from_str(r#"
+profile: production
+hsts: { enabled: true, max_age: 1d, include_subdomains: true }
+content_security_policy: "default-src 'none'"
+headers: { x-frame-options: DENY }
+"#).unwrap());
+        let manager = PluginManager::new();
+        let app = BackworksServer::new(Arc::new(config), manager, None).unwrap().create_app().unwrap();
+
+        let request = axum::http::Request::get("/broken").header("x-request-id", "req-7").body(axum::body::Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.headers()[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(response.headers()[header::STRICT_TRANSPORT_SECURITY], "max-age=86400; includeSubDomains");
+        assert_eq!(response.headers()[header::CONTENT_SECURITY_POLICY], "default-src 'none'");
+        assert_eq!(response.headers()["x-frame-options"], "DENY");
+        // The cause is still known to hooks and metrics, not to the client
+        assert!(response.extensions().get::<RequestError>().unwrap().message.contains("Plugin mode requires plugin name"));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), serde_json::json!({ "error": "Internal Server Error", "status": 500, "request_id": "req-7" }));
+    }
+
+    #[tokio::test]
+    async fn test_ipv6_listener_dual_stack_and_v6_only() {
+        let mut config = ServerConfig { host: "[::]".to_string(), port: 0, ..Default::default() };
+        let dual = bind_listener(&config).await.unwrap();
+        let port = dual.local_addr().unwrap().port();
+        assert!(dual.local_addr().unwrap().is_ipv6());
+        let (connected, accepted) = tokio::join!(tokio::net::TcpStream::connect(("127.0.0.1", port)), dual.accept());
+        assert!(connected.is_ok());
+        // IPv4 clients arrive as IPv4-mapped addresses
+        let peer = accepted.unwrap().1.ip();
+        assert!(peer.is_ipv6());
+        assert_eq!(peer.to_canonical(), "127.0.0.1".parse::<IpAddr>().unwrap());
+
+        config.ipv6_only = true;
+        let v6_only = bind_listener(&config).await.unwrap();
+        let port = v6_only.local_addr().unwrap().port();
+        assert!(tokio::net::TcpStream::connect(("127.0.0.1", port)).await.is_err());
+        assert!(tokio::net::TcpStream::connect(("::1", port)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_cors_preflights_follow_origin_policies() {
+        let mut config = test_config();
+        config.security = Some(serde_yaml::from_str(r#"
+cors:
+  enabled: true
+  origins: ["https://app.example.com"]
+  methods: [GET]
+  max_age: 600
+  policies:
+    - name: partners
+      origins: ["https://*.partner.io"]
+      methods: [GET, DELETE]
+      headers: [authorization]
+"#).unwrap());
+        let plugin = Arc::new(RecordingPlugin::default());
+        let manager = PluginManager::new();
+        manager.register_plugin(plugin.clone(), None, None).await.unwrap();
+        let server = BackworksServer::new(Arc::new(config), manager, None).unwrap();
+        let metrics = server.state.metrics.clone();
+        let app = server.create_app().unwrap();
+
+        let preflight = |origin: &str, method: &str| axum::http::Request::options("/broken")
+            .header(http::header::ORIGIN, origin)
+            .header(http::header::ACCESS_CONTROL_REQUEST_METHOD, method)
+            .header(http::header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization")
+            .body(axum::body::Body::empty())
+            .unwrap();
+
+        let response = app.clone().oneshot(preflight("https://acme.partner.io", "DELETE")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()[http::header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://acme.partner.io");
+        assert_eq!(response.headers()[http::header::ACCESS_CONTROL_ALLOW_METHODS], "GET, DELETE");
+        assert_eq!(response.headers()[http::header::ACCESS_CONTROL_MAX_AGE], "600");
+
+        let response = app.clone().oneshot(preflight("https://app.example.com", "DELETE")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(!response.headers().contains_key(http::header::ACCESS_CONTROL_ALLOW_ORIGIN));
+
+        // Preflights are answered before plugin hooks; simple requests get the origin header
+        assert!(plugin.endpoints.lock().unwrap().is_empty());
+        let request = axum::http::Request::get("/health")
+            .header(http::header::ORIGIN, "https://app.example.com")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.headers()[http::header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example.com");
+
+        let rendered = metrics.render();
+        assert!(rendered.contains(r#"backworks_cors_preflights_total{policy="partners",outcome="allowed"} 1"#));
+        assert!(rendered.contains(r#"backworks_cors_preflights_total{policy="default",outcome="rejected"} 1"#));
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_middleware_runs_in_declared_order() {
+        let mut config = test_config();
+        config.endpoints.get_mut("missing_plugin").unwrap().middleware = vec![
+            crate::config::MiddlewareSpec { name: "tag".to_string(), config: serde_json::json!({ "value": "outer" }) },
+            crate::config::MiddlewareSpec::named("auth"),
+        ];
+        let plugin = Arc::new(RecordingPlugin::default());
+        let manager = PluginManager::new();
+        manager.register_plugin(plugin.clone(), None, None).await.unwrap();
+        let registry = manager.middleware_registry().await;
+        let app = BackworksServer::new(Arc::new(config), manager, None).unwrap()
+            .with_middleware(registry)
+            .create_app()
+            .unwrap();
+
+        // `auth` answers before the handler runs; `tag` wraps its answer
+        let response = send(app, "/broken").await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()["x-tag"], "outer");
+        assert_eq!(plugin.errors.lock().unwrap()[0].message, "Unauthorized: Authentication required");
+
+        let mut config = test_config();
+        config.endpoints.get_mut("missing_plugin").unwrap().middleware = vec![crate::config::MiddlewareSpec::named("tag")];
+        assert!(BackworksServer::new(Arc::new(config), PluginManager::new(), None).unwrap().create_app().is_err());
+    }
+
+    /// Cache entries kept in memory, standing in for a shared store
+    #[derive(Default)]
+    struct SharedStore {
+        entries: Mutex<HashMap<String, Vec<u8>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl BackworksPlugin for SharedStore {
+        fn name(&self) -> &str { "store" }
+        fn version(&self) -> &str { "0.0.0" }
+        fn description(&self) -> &str { "keeps cache entries" }
+        async fn initialize(&self, _config: &Value) -> Result<()> { Ok(()) }
+        async fn shutdown(&self) -> Result<()> { Ok(()) }
+        fn cache(&self) -> Option<&dyn crate::plugin::CacheBackend> { Some(self) }
+    }
+
+    #[async_trait::async_trait]
+    impl crate::plugin::CacheBackend for SharedStore {
+        async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+            Ok(self.entries.lock().unwrap().get(key).cloned())
+        }
+
+        async fn set(&self, key: &str, value: Vec<u8>, _ttl: std::time::Duration) -> Result<()> {
+            self.entries.lock().unwrap().insert(key.to_string(), value);
+            Ok(())
+        }
+
+        async fn clear(&self, prefix: &str) -> Result<usize> {
+            let mut entries = self.entries.lock().unwrap();
+            let before = entries.len();
+            entries.retain(|key, _| !key.starts_with(prefix));
+            Ok(before - entries.len())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_middleware_shares_a_plugin_store() {
+        let config: BackworksConfig = serde_yaml::from_str(r#"
+name: shop
+endpoints:
+  users:
+    path: /users
+    methods: [GET]
+    mode: mock
+    mock: { schema: { name: $name } }
+    middleware: [cache]
+"#).unwrap();
+        let config = Arc::new(config);
+        let store = Arc::new(SharedStore::default());
+        let manager = PluginManager::new();
+        manager.register_plugin(store.clone(), None, None).await.unwrap();
+        let registry = manager.middleware_registry().await.with_cache_store(manager.cache("store").await.unwrap());
+        let servers: Vec<BackworksServer> = (0..2)
+            .map(|_| BackworksServer::new(config.clone(), manager.clone(), None).unwrap().with_middleware(registry.clone()))
+            .collect();
+        let apps: Vec<Router> = servers.iter().map(|server| server.create_app().unwrap()).collect();
+
+        // The second server answers from what the first one stored
+        let first = send(apps[0].clone(), "/users?page=2").await;
+        assert_eq!(first.headers()["x-cache"], "miss");
+        let first = axum::body::to_bytes(first.into_body(), usize::MAX).await.unwrap();
+        assert!(store.entries.lock().unwrap().contains_key("response:users:/users?page=2"));
+        let second = send(apps[1].clone(), "/users?page=2").await;
+        assert_eq!(second.headers()["x-cache"], "hit");
+        assert_eq!(axum::body::to_bytes(second.into_body(), usize::MAX).await.unwrap(), first);
+
+        assert_eq!(servers[1].state.pipelines.flush(Some("users")).await, 1);
+        assert_eq!(send(apps[1].clone(), "/users?page=2").await.headers()["x-cache"], "miss");
+        assert!(manager.cache("missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_static_endpoint_serves_directory_beside_api() {
+        let dir = std::env::temp_dir().join(format!("backworks_static_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("assets")).unwrap();
+        std::fs::write(dir.join("index.html"), "<html>app</html>").unwrap();
+        std::fs::write(dir.join("assets/app.js"), "console.log(1);").unwrap();
+
+        let mut config = test_config();
+        let mut site = config.endpoints["missing_plugin"].clone();
+        site.path = "/".to_string();
+        site.mode = Some(ExecutionMode::Static);
+        site.static_files = Some(crate::config::StaticFilesConfig {
+            dir: dir.clone(),
+            index: vec!["index.html".to_string()],
+            spa_fallback: true,
+            cache_control: None,
+        });
+        let mut docs = site.clone();
+        docs.path = "/docs".to_string();
+        config.endpoints.insert("site".to_string(), site);
+        config.endpoints.insert("docs".to_string(), docs);
+        let plugin = Arc::new(RecordingPlugin::default());
+        let manager = PluginManager::new();
+        manager.register_plugin(plugin.clone(), None, None).await.unwrap();
+        let app = BackworksServer::new(Arc::new(config), manager, None).unwrap().create_app().unwrap();
+
+        let response = send(app.clone(), "/assets/app.js").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[http::header::CONTENT_TYPE], "text/javascript");
+        let etag = response.headers()[http::header::ETAG].clone();
+
+        let conditional = axum::http::Request::get("/assets/app.js")
+            .header(http::header::IF_NONE_MATCH, etag)
+            .body(axum::body::Body::empty())
+            .unwrap();
+        assert_eq!(app.clone().oneshot(conditional).await.unwrap().status(), StatusCode::NOT_MODIFIED);
+
+        let ranged = axum::http::Request::get("/assets/app.js")
+            .header(http::header::RANGE, "bytes=0-6")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(ranged).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[http::header::CONTENT_RANGE], "bytes 0-6/15");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"console");
+
+        // Client-side routes get the index; missing assets and traversal do not
+        let response = send(app.clone(), "/orders-page/42").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"<html>app</html>");
+        assert_eq!(send(app.clone(), "/assets/missing.css").await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(send(app.clone(), "/assets/..%2F..%2Fsecret").await.status(), StatusCode::NOT_FOUND);
+
+        assert_eq!(send(app.clone(), "/docs/").await.status(), StatusCode::OK);
+        assert_eq!(send(app.clone(), "/docs/assets/app.js").await.status(), StatusCode::OK);
+
+        // API routes still win over the directory
+        assert_eq!(send(app, "/orders/abc").await.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(plugin.endpoints.lock().unwrap()[0], Some("site".to_string()));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_critical_plugin_rejection_short_circuits_handler() {
+        let plugin = Arc::new(RecordingPlugin { critical: true, ..Default::default() });
+        let response = send(app_with(plugin.clone()).await, "/health").await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(plugin.errors.lock().unwrap()[0].source, ErrorSource::Plugin);
+        assert_eq!(*plugin.after_statuses.lock().unwrap(), vec![500]);
+    }
+
+    #[tokio::test]
+    async fn test_plugin_rejection_keeps_status_and_closed_breaker() {
+        let plugin = Arc::new(RecordingPlugin { forbid: true, ..Default::default() });
+        let app = app_with(plugin.clone()).await;
+
+        // More rejections than the breaker's failure threshold
+        for _ in 0..8 {
+            let response = send(app.clone(), "/health").await;
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+        assert_eq!(plugin.errors.lock().unwrap()[7].source, ErrorSource::Framework);
+    }
+
+    #[tokio::test]
+    async fn test_required_trusted_identity_rejects_unverified_source() {
+        let mut config = test_config();
+        config.security = Some(crate::config::SecurityConfig {
+            cors: None,
+            rate_limiting: None,
+            authentication: Some(crate::config::SecurityAuthConfig {
+                auth_type: "trusted_header".to_string(),
+                secret_env: None,
+                algorithm: None,
+                expiration: None,
+                validation: None,
+                trusted_headers: Some(crate::config::TrustedHeaderAuthConfig {
+                    user_header: "X-Authenticated-User".to_string(),
+                    email_header: None,
+                    roles_header: None,
+                    roles_separator: ",".to_string(),
+                    trusted_proxies: vec!["10.0.0.0/8".to_string()],
+                    trusted_client_certs: vec![],
+                    required: true,
+                }),
+            }),
+            validation: None,
+            headers: None,
+            require_https: None,
+            profile: None,
+            profiles: HashMap::new(),
+            hsts: None,
+            content_security_policy: None,
+        });
+        let plugin = Arc::new(RecordingPlugin::default());
+        let manager = PluginManager::new();
+        manager.register_plugin(plugin.clone(), None, None).await.unwrap();
+        let app = BackworksServer::new(Arc::new(config), manager, None).unwrap().create_app().unwrap();
+
+        let request = axum::http::Request::get("/health")
+            .header("X-Authenticated-User", "mallory")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(*plugin.after_statuses.lock().unwrap(), vec![401]);
+    }
+
+    #[tokio::test]
+    async fn test_readiness_reports_failing_checks() {
+        let mut config = test_config();
+        config.monitoring = Some(crate::config::MonitoringConfig {
+            metrics: None,
+            logging: None,
+            health: Some(crate::config::HealthConfig {
+                enabled: Some(true),
+                endpoint: None,
+                ready_endpoint: None,
+                saturation: None,
+                checks: Some(vec![crate::config::HealthCheckConfig {
+                    name: "upstream".to_string(),
+                    check_type: "url".to_string(),
+                    timeout: Some(1),
+                    url: Some("http://127.0.0.1:1/".to_string()),
+                    critical: None,
+                }]),
+            }),
+            alerts: None,
+        });
+        let app = BackworksServer::new(Arc::new(config), PluginManager::new(), None).unwrap().create_app().unwrap();
+
+        let response = send(app.clone(), "/health").await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = send(app, "/ready").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let report: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(report["status"], "unhealthy");
+        assert_eq!(report["checks"][0]["name"], "upstream");
+    }
+
+    /// Runs scheduled actions
+    struct JobsPlugin;
+
+    #[async_trait::async_trait]
+    impl BackworksPlugin for JobsPlugin {
+        fn name(&self) -> &str { "jobs" }
+        fn version(&self) -> &str { "0.0.0" }
+        fn description(&self) -> &str { "runs jobs" }
+        async fn initialize(&self, _config: &Value) -> Result<()> { Ok(()) }
+        async fn shutdown(&self) -> Result<()> { Ok(()) }
+        fn max_execution_time(&self) -> std::time::Duration { std::time::Duration::from_secs(5) }
+
+        async fn run_action(&self, action: &str, params: &Value) -> Result<Value> {
+            match action {
+                "warm" => Ok(serde_json::json!({ "warmed": params["keys"] })),
+                "slow" => {
+                    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                    Ok(Value::Null)
+                }
+                _ => Err(BackworksError::plugin(format!("Plugin jobs has no action '{}'", action))),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_schedules_run_on_demand_and_keep_their_history() {
+        let mut config = test_config();
+        config.schedules = serde_yaml::from_str(r#"
+warm: { cron: "0 * * * *", plugin: jobs, action: warm, params: { keys: 3 }, history: 2 }
+slow: { cron: "@daily", plugin: jobs, action: slow, enabled: false }
+limited: { cron: "@daily", plugin: jobs, action: slow, timeout: 50ms }
+broken: { cron: "@daily", plugin: jobs, action: vanish }
+"#).unwrap();
+        let manager = PluginManager::new();
+        manager.register_plugin(Arc::new(JobsPlugin), None, None).await.unwrap();
+        let server = BackworksServer::new(Arc::new(config), manager, None).unwrap();
+        let app = server.create_app().unwrap();
+        let call = |request: axum::http::Request<axum::body::Body>| async {
+            let response = app.clone().oneshot(request).await.unwrap();
+            let status = response.status();
+            (status, serde_json::from_slice::<Value>(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap())
+        };
+        let run = |name: &str| as_admin(axum::http::Request::post(format!("/_backworks/schedules/{}/run", name))).body(axum::body::Body::empty()).unwrap();
+
+        let (status, run_now) = call(run("warm")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((run_now["status"].as_str(), run_now["trigger"].as_str()), (Some("succeeded"), Some("manual")));
+        assert_eq!(run_now["output"], serde_json::json!({ "warmed": 3 }));
+        let (_, failed) = call(run("broken")).await;
+        assert_eq!(failed["status"], "failed");
+        assert!(failed["error"].as_str().unwrap().contains("no action 'vanish'"));
+        let (_, timed_out) = call(run("limited")).await;
+        assert_eq!(timed_out["status"], "timed_out");
+
+        // A run due while the previous one is going is skipped
+        let schedules = &server.state.schedules;
+        let (first, second) = tokio::join!(
+            schedules.run(&server.state, "slow", RunTrigger::Cron),
+            async {
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                schedules.run(&server.state, "slow", RunTrigger::Cron).await
+            },
+        );
+        assert_eq!((first.unwrap().status, second.unwrap().status), (RunStatus::Succeeded, RunStatus::Skipped));
+
+        // Only the newest runs are kept
+        for _ in 0..2 {
+            call(run("warm")).await;
+        }
+        let (_, runs) = call(as_admin(axum::http::Request::get("/_backworks/schedules/warm/runs")).body(axum::body::Body::empty()).unwrap()).await;
+        assert_eq!(runs.as_array().unwrap().len(), 2);
+        assert_ne!(runs[0]["id"], run_now["id"]);
+
+        let (_, listed) = call(as_admin(axum::http::Request::get("/_backworks/schedules")).body(axum::body::Body::empty()).unwrap()).await;
+        let names: Vec<&str> = listed.as_array().unwrap().iter().map(|schedule| schedule["name"].as_str().unwrap()).collect();
+        assert_eq!(names, vec!["broken", "limited", "slow", "warm"]);
+        assert_eq!((listed[2]["enabled"].as_bool(), listed[2]["next_run"].is_null(), listed[2]["last_run"]["status"].as_str()), (Some(false), true, Some("succeeded")));
+        let next_run: chrono::DateTime<chrono::Utc> = serde_json::from_value(listed[3]["next_run"].clone()).unwrap();
+        assert_eq!(next_run.format("%M:%S").to_string(), "00:00");
+
+        let (status, _) = call(run("backup")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_docs_are_served_next_to_the_api() {
+        let mut config = test_config();
+        config.docs = Some(serde_yaml::from_str("path: /reference").unwrap());
+        let app = BackworksServer::new(Arc::new(config.clone()), PluginManager::new(), None).unwrap().create_app().unwrap();
+
+        let page = send(app.clone(), "/reference").await;
+        assert_eq!(page.status(), StatusCode::OK);
+        let html = axum::body::to_bytes(page.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&html).contains("href=\"/reference/openapi.json\""));
+        let openapi = send(app, "/reference/openapi.json").await;
+        let openapi: Value = serde_json::from_slice(&axum::body::to_bytes(openapi.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(openapi["paths"]["/broken"]["get"]["operationId"], "missing_plugin");
+
+        config.endpoints.get_mut("missing_plugin").unwrap().path = "/reference".to_string();
+        let taken = BackworksServer::new(Arc::new(config), PluginManager::new(), None).unwrap().create_app();
+        assert!(taken.unwrap_err().to_string().contains("Endpoint 'missing_plugin' takes the path of the docs"));
+    }
 }
@@ -0,0 +1,422 @@
+//! Sessions
+//!
+//! With `sessions`, requests to endpoints carry a session, `req.session` in
+//! runtime handlers, which starts out empty. A handler changing
+//! `req.session` has the change saved with its response; setting it to
+//! `null` ends the session. A session lasts `ttl` (default `24h`) after it
+//! last changed.
+//!
+//! With `store: cookie` (default) the session data travels in the cookie,
+//! encrypted with AES-256-GCM or, with `encrypted: false`, signed with
+//! HMAC-SHA256 only. With `store: server` it is kept in the
+//! [state store](crate::state) under the `sessions` namespace, and the
+//! cookie carries only its signed id. Session cookies are `HttpOnly`, with
+//! the configured `SameSite`, `Secure`, `Domain` and `Path` attributes.
+//!
+//! With `csrf`, sessions also hold the secret of their
+//! [CSRF token](crate::csrf); a session without one gets it on its first
+//! request, and is saved for it.
+
+use crate::config::{parse_duration, BackworksConfig, SameSite, SessionConfig, SessionStore};
+use crate::csrf::Csrf;
+use crate::error::{BackworksError, Result};
+use crate::state::StateStore;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{aead, hmac};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// State store namespace of server-side sessions
+pub const NAMESPACE: &str = "sessions";
+
+const DEFAULT_COOKIE: &str = "backworks_session";
+const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const MIN_SECRET_LENGTH: usize = 32;
+/// Browsers drop larger cookies
+const MAX_COOKIE_SIZE: usize = 4096;
+/// Seconds between sweeps of expired server-side sessions
+const SWEEP_INTERVAL: i64 = 60;
+
+/// The session a request arrived with
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Session {
+    /// Id of a server-side session
+    pub id: Option<String>,
+    pub data: Map<String, Value>,
+    /// Secret the session's CSRF token derives from
+    pub csrf: Option<String>,
+}
+
+/// A handler's change to the session: its new data, or `None` to end it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionChange(pub Option<Map<String, Value>>);
+
+/// Session data with its expiry, as kept in the cookie or the state store
+#[derive(Debug, Serialize, Deserialize)]
+struct Stored {
+    data: Map<String, Value>,
+    expires_at: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    csrf: Option<String>,
+}
+
+pub struct Sessions {
+    cookie: String,
+    ttl: Duration,
+    /// Cookie attributes after the value
+    attributes: String,
+    encrypted: bool,
+    csrf: Option<Csrf>,
+    /// The state store of server-side sessions
+    store: Option<StateStore>,
+    signing: hmac::Key,
+    sealing: aead::LessSafeKey,
+    random: SystemRandom,
+    last_sweep: AtomicI64,
+}
+
+impl Sessions {
+    /// Sessions as `config` describes them, kept in `state` when stored on
+    /// the server
+    pub fn new(config: &SessionConfig, blueprint: &BackworksConfig, state: StateStore) -> Result<Self> {
+        if config.secret.len() < MIN_SECRET_LENGTH {
+            return Err(BackworksError::config(format!("sessions.secret must be at least {} characters", MIN_SECRET_LENGTH)));
+        }
+        let ttl = match config.ttl.as_deref() {
+            Some(text) => parse_duration(text).map_err(|e| BackworksError::config(format!("sessions.ttl: {}", e)))?,
+            None => DEFAULT_TTL,
+        };
+        let cookie = config.cookie.clone().unwrap_or_else(|| DEFAULT_COOKIE.to_string());
+        if cookie.is_empty() || !cookie.bytes().all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)) {
+            return Err(BackworksError::config(format!("sessions.cookie '{}' is not a valid cookie name", cookie)));
+        }
+
+        let secure = config.secure.unwrap_or(blueprint.server.tls.is_some());
+        if config.same_site == SameSite::None && !secure {
+            return Err(BackworksError::config("sessions.same_site: none needs secure cookies"));
+        }
+        for (name, value) in [("path", config.path.as_deref()), ("domain", config.domain.as_deref())] {
+            if value.is_some_and(|value| value.contains(|c: char| c == ';' || c.is_control())) {
+                return Err(BackworksError::config(format!("sessions.{} cannot contain ';' or control characters", name)));
+            }
+        }
+        let mut attributes = format!("; Path={}; HttpOnly", config.path.as_deref().unwrap_or("/"));
+        attributes.push_str(match config.same_site {
+            SameSite::Strict => "; SameSite=Strict",
+            SameSite::Lax => "; SameSite=Lax",
+            SameSite::None => "; SameSite=None",
+        });
+        if secure {
+            attributes.push_str("; Secure");
+        }
+        if let Some(ref domain) = config.domain {
+            attributes.push_str(&format!("; Domain={}", domain));
+        }
+
+        // Separate keys for signing and encrypting, both derived from the secret
+        let derive = |purpose: &[u8]| hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, config.secret.as_bytes()), purpose);
+        let sealing = aead::UnboundKey::new(&aead::AES_256_GCM, derive(b"backworks session encryption").as_ref())
+            .map_err(|_| BackworksError::config("sessions.secret cannot be used as an encryption key"))?;
+        Ok(Self {
+            cookie,
+            ttl,
+            attributes,
+            encrypted: config.encrypted,
+            csrf: config.csrf.as_ref().map(Csrf::new).transpose()?,
+            store: (config.store == SessionStore::Server).then_some(state),
+            signing: hmac::Key::new(hmac::HMAC_SHA256, derive(b"backworks session signing").as_ref()),
+            sealing: aead::LessSafeKey::new(sealing),
+            random: SystemRandom::new(),
+            last_sweep: AtomicI64::new(0),
+        })
+    }
+
+    /// The session of a request with `headers` at `now` (Unix seconds); an
+    /// empty one when it has none, or an expired or tampered one
+    pub async fn load(&self, headers: &HeaderMap, now: i64) -> Session {
+        let Some(value) = self.cookie_value(headers) else {
+            return Session::default();
+        };
+        let Some(ref store) = self.store else {
+            let payload = if self.encrypted { self.open(&value) } else { self.verify(&value) };
+            let stored = payload.and_then(|payload| serde_json::from_slice::<Stored>(&payload).ok())
+                .filter(|stored| stored.expires_at > now);
+            return stored.map(|stored| Session { id: None, data: stored.data, csrf: stored.csrf }).unwrap_or_default();
+        };
+        let Some(id) = self.verify(&value).map(|id| String::from_utf8_lossy(&id).into_owned()) else {
+            return Session::default();
+        };
+        match store.get(NAMESPACE, &id).await.and_then(|stored| serde_json::from_value::<Stored>(stored).ok()) {
+            Some(stored) if stored.expires_at > now => Session { id: Some(id), data: stored.data, csrf: stored.csrf },
+            Some(_) => {
+                store.delete(NAMESPACE, &id).await;
+                Session::default()
+            }
+            None => Session::default(),
+        }
+    }
+
+    /// Apply `change` to `session` at `now`, returning the `Set-Cookie`
+    /// value that goes out with the response
+    pub async fn save(&self, session: &Session, change: SessionChange, now: i64) -> Result<HeaderValue> {
+        let Some(data) = change.0 else {
+            if let (Some(store), Some(id)) = (&self.store, &session.id) {
+                store.delete(NAMESPACE, id).await;
+            }
+            return self.set_cookie("", Duration::ZERO);
+        };
+
+        let stored = Stored { data, expires_at: now + self.ttl.as_secs() as i64, csrf: session.csrf.clone() };
+        let value = match self.store {
+            Some(ref store) => {
+                let id = session.id.clone().unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+                store.set(NAMESPACE, &id, serde_json::to_value(&stored)?).await;
+                if session.id.is_none() {
+                    self.sweep(store, now).await;
+                }
+                self.sign(id.as_bytes())
+            }
+            None => {
+                let payload = serde_json::to_vec(&stored)?;
+                if self.encrypted { self.seal(&payload)? } else { self.sign(&payload) }
+            }
+        };
+        if value.len() + self.cookie.len() + 1 > MAX_COOKIE_SIZE {
+            tracing::warn!("Session cookie of {} bytes exceeds the {} bytes browsers keep; consider `sessions.store: server`", value.len(), MAX_COOKIE_SIZE);
+        }
+        self.set_cookie(&value, self.ttl)
+    }
+
+    fn set_cookie(&self, value: &str, max_age: Duration) -> Result<HeaderValue> {
+        HeaderValue::from_str(&format!("{}={}; Max-Age={}{}", self.cookie, value, max_age.as_secs(), self.attributes))
+            .map_err(|e| BackworksError::server(format!("Invalid session cookie: {}", e)))
+    }
+
+    /// Where requests carry their CSRF token, when sessions have one
+    pub fn csrf(&self) -> Option<&Csrf> {
+        self.csrf.as_ref()
+    }
+
+    /// The CSRF token of `session`, derived from its secret
+    pub fn csrf_token(&self, session: &Session) -> Option<String> {
+        let secret = session.csrf.as_deref()?;
+        Some(URL_SAFE_NO_PAD.encode(hmac::sign(&self.signing, format!("csrf:{}", secret).as_bytes())))
+    }
+
+    /// Whether `token` is the CSRF token of `session`
+    pub fn verify_csrf_token(&self, session: &Session, token: &str) -> bool {
+        let (Some(secret), Ok(token)) = (session.csrf.as_deref(), URL_SAFE_NO_PAD.decode(token.trim())) else {
+            return false;
+        };
+        hmac::verify(&self.signing, format!("csrf:{}", secret).as_bytes(), &token).is_ok()
+    }
+
+    /// The cookie scripts read the CSRF token of `session` from, if there is
+    /// one; ending the session clears it
+    fn csrf_cookie(&self, session: Option<&Session>) -> Option<HeaderValue> {
+        let name = self.csrf.as_ref()?.cookie.as_deref()?;
+        let (token, max_age) = match session {
+            Some(session) => (self.csrf_token(session)?, self.ttl),
+            None => (String::new(), Duration::ZERO),
+        };
+        let attributes = self.attributes.replace("; HttpOnly", "");
+        HeaderValue::from_str(&format!("{}={}; Max-Age={}{}", name, token, max_age.as_secs(), attributes)).ok()
+    }
+
+    fn new_csrf_secret(&self) -> Result<String> {
+        let mut secret = [0u8; 32];
+        self.random.fill(&mut secret).map_err(|_| BackworksError::server("No randomness for a CSRF secret"))?;
+        Ok(URL_SAFE_NO_PAD.encode(secret))
+    }
+
+    fn cookie_value(&self, headers: &HeaderMap) -> Option<String> {
+        headers.get_all(header::COOKIE).iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(name, _)| *name == self.cookie)
+            .map(|(_, value)| value.trim_matches('"').to_string())
+    }
+
+    /// `payload.signature`, both base64url
+    fn sign(&self, payload: &[u8]) -> String {
+        let payload = URL_SAFE_NO_PAD.encode(payload);
+        let signature = hmac::sign(&self.signing, format!("{}={}", self.cookie, payload).as_bytes());
+        format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(signature.as_ref()))
+    }
+
+    fn verify(&self, value: &str) -> Option<Vec<u8>> {
+        let (payload, signature) = value.split_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        hmac::verify(&self.signing, format!("{}={}", self.cookie, payload).as_bytes(), &signature).ok()?;
+        URL_SAFE_NO_PAD.decode(payload).ok()
+    }
+
+    /// The nonce followed by the ciphertext, base64url
+    fn seal(&self, payload: &[u8]) -> Result<String> {
+        let mut nonce = [0u8; aead::NONCE_LEN];
+        self.random.fill(&mut nonce).map_err(|_| BackworksError::server("No randomness for a session nonce"))?;
+        let mut sealed = payload.to_vec();
+        self.sealing.seal_in_place_append_tag(aead::Nonce::assume_unique_for_key(nonce), aead::Aad::from(self.cookie.as_bytes()), &mut sealed)
+            .map_err(|_| BackworksError::server("Failed to encrypt the session"))?;
+        Ok(URL_SAFE_NO_PAD.encode([nonce.as_slice(), &sealed].concat()))
+    }
+
+    fn open(&self, value: &str) -> Option<Vec<u8>> {
+        let mut sealed = URL_SAFE_NO_PAD.decode(value).ok()?;
+        if sealed.len() < aead::NONCE_LEN {
+            return None;
+        }
+        let nonce = aead::Nonce::try_assume_unique_for_key(&sealed[..aead::NONCE_LEN]).ok()?;
+        let opened = self.sealing.open_in_place(nonce, aead::Aad::from(self.cookie.as_bytes()), &mut sealed[aead::NONCE_LEN..]).ok()?;
+        Some(opened.to_vec())
+    }
+
+    /// Drop expired server-side sessions, at most once a minute
+    async fn sweep(&self, store: &StateStore, now: i64) {
+        let last = self.last_sweep.load(Ordering::Relaxed);
+        if now - last < SWEEP_INTERVAL || self.last_sweep.compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed).is_err() {
+            return;
+        }
+        for id in store.keys(NAMESPACE).await {
+            let expired = store.get(NAMESPACE, &id).await
+                .and_then(|stored| serde_json::from_value::<Stored>(stored).ok())
+                .is_none_or(|stored| stored.expires_at <= now);
+            if expired {
+                store.delete(NAMESPACE, &id).await;
+            }
+        }
+    }
+}
+
+/// Load the session of each request and save what its handler changed
+pub async fn manage_sessions(State(sessions): State<Arc<Sessions>>, mut request: Request, next: Next) -> Response {
+    let now = chrono::Utc::now().timestamp();
+    let mut session = sessions.load(request.headers(), now).await;
+    // Sessions get their CSRF secret on their first request
+    let issued = sessions.csrf.is_some() && session.csrf.is_none();
+    if issued {
+        match sessions.new_csrf_secret() {
+            Ok(secret) => session.csrf = Some(secret),
+            Err(e) => tracing::error!("Failed to issue a CSRF token: {}", e),
+        }
+    }
+    request.extensions_mut().insert(session.clone());
+    let mut response = next.run(request).await;
+
+    let change = response.extensions_mut().remove::<SessionChange>()
+        .or_else(|| (issued && session.csrf.is_some()).then(|| SessionChange(Some(session.data.clone()))));
+    if let Some(change) = change {
+        let ended = change.0.is_none();
+        match sessions.save(&session, change, now).await {
+            Ok(cookie) => {
+                response.headers_mut().append(header::SET_COOKIE, cookie);
+                if let Some(cookie) = sessions.csrf_cookie((!ended).then_some(&session)) {
+                    response.headers_mut().append(header::SET_COOKIE, cookie);
+                }
+            }
+            Err(e) => tracing::error!("Failed to save session: {}", e),
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const NOW: i64 = 1_700_000_000;
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+    fn sessions(yaml: &str) -> Result<Sessions> {
+        let blueprint: BackworksConfig = serde_yaml::from_str("name: app\nendpoints: {}").unwrap();
+        Sessions::new(&serde_yaml::from_str(yaml).unwrap(), &blueprint, StateStore::new())
+    }
+
+    fn data(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    /// The request headers of a browser sending back `set_cookie`
+    fn cookie(set_cookie: &HeaderValue) -> HeaderMap {
+        let pair = set_cookie.to_str().unwrap().split(';').next().unwrap().to_string();
+        HeaderMap::from_iter([(header::COOKIE, HeaderValue::from_str(&format!("theme=dark; {}", pair)).unwrap())])
+    }
+
+    #[tokio::test]
+    async fn test_cookie_sessions_round_trip_and_refuse_tampering() {
+        for encrypted in [true, false] {
+            let sessions = sessions(&format!("{{ secret: {}, ttl: 1h, encrypted: {} }}", SECRET, encrypted)).unwrap();
+            assert_eq!(sessions.load(&HeaderMap::new(), NOW).await, Session::default());
+
+            let saved = sessions.save(&Session::default(), SessionChange(Some(data(json!({ "user": "ada" })))), NOW).await.unwrap();
+            let text = saved.to_str().unwrap();
+            assert!(text.starts_with("backworks_session="), "{}", text);
+            assert!(text.ends_with("; Max-Age=3600; Path=/; HttpOnly; SameSite=Lax"), "{}", text);
+            // Only signed data can be read by the client
+            assert_eq!(text.contains(&URL_SAFE_NO_PAD.encode(r#"{"data":{"user":"ada""#)), !encrypted);
+
+            let headers = cookie(&saved);
+            assert_eq!(sessions.load(&headers, NOW + 10).await.data, data(json!({ "user": "ada" })));
+            assert_eq!(sessions.load(&headers, NOW + 3600).await, Session::default());
+
+            let tampered = headers[header::COOKIE].to_str().unwrap().replace("session=", "session=A");
+            assert_eq!(sessions.load(&HeaderMap::from_iter([(header::COOKIE, tampered.parse().unwrap())]), NOW).await, Session::default());
+            let other = sessions_with_secret(&"z".repeat(32));
+            assert_eq!(other.load(&headers, NOW).await, Session::default());
+
+            let ended = sessions.save(&Session::default(), SessionChange(None), NOW).await.unwrap();
+            assert!(ended.to_str().unwrap().starts_with("backworks_session=; Max-Age=0;"));
+        }
+    }
+
+    fn sessions_with_secret(secret: &str) -> Sessions {
+        sessions(&format!("{{ secret: {} }}", secret)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_server_sessions_keep_data_in_the_state_store() {
+        let sessions = sessions(&format!("{{ secret: {}, store: server, cookie: sid, same_site: strict, domain: example.com }}", SECRET)).unwrap();
+        let store = sessions.store.clone().unwrap();
+
+        let saved = sessions.save(&Session::default(), SessionChange(Some(data(json!({ "cart": [1, 2] })))), NOW).await.unwrap();
+        assert!(saved.to_str().unwrap().ends_with("; Max-Age=86400; Path=/; HttpOnly; SameSite=Strict; Domain=example.com"));
+        let session = sessions.load(&cookie(&saved), NOW).await;
+        let id = session.id.clone().unwrap();
+        assert_eq!(session.data, data(json!({ "cart": [1, 2] })));
+        assert_eq!(store.get(NAMESPACE, &id).await.unwrap()["data"], json!({ "cart": [1, 2] }));
+
+        // Changes keep the id, an end removes the data
+        let again = sessions.save(&session, SessionChange(Some(data(json!({ "cart": [] })))), NOW).await.unwrap();
+        assert_eq!(again, saved);
+        sessions.save(&session, SessionChange(None), NOW).await.unwrap();
+        assert!(store.get(NAMESPACE, &id).await.is_none());
+        assert_eq!(sessions.load(&cookie(&saved), NOW).await, Session::default());
+
+        // Expired sessions are swept when new ones start
+        sessions.save(&Session::default(), SessionChange(Some(Map::new())), NOW).await.unwrap();
+        sessions.save(&Session::default(), SessionChange(Some(Map::new())), NOW + 2 * 86400).await.unwrap();
+        assert_eq!(store.keys(NAMESPACE).await.len(), 1);
+    }
+
+    #[test]
+    fn test_session_settings_are_validated() {
+        let error = |yaml: &str| sessions(yaml).err().map(|e| e.to_string()).unwrap_or_default();
+        assert!(error("{ secret: short }").contains("at least 32 characters"));
+        assert!(error(&format!("{{ secret: {}, ttl: forever }}", SECRET)).contains("sessions.ttl"));
+        assert!(error(&format!("{{ secret: {}, cookie: 'a b' }}", SECRET)).contains("not a valid cookie name"));
+        assert!(error(&format!("{{ secret: {}, same_site: none }}", SECRET)).contains("needs secure cookies"));
+        assert!(sessions(&format!("{{ secret: {}, same_site: none, secure: true }}", SECRET)).is_ok());
+        assert!(error(&format!("{{ secret: {}, path: '/; Secure' }}", SECRET)).contains("sessions.path cannot contain ';'"));
+    }
+}
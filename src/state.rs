@@ -0,0 +1,257 @@
+//! Shared key-value state store
+//!
+//! A namespaced in-memory store for stateful mocks and plugins. Each blueprint
+//! (or tenant) writes into its own namespace; whole namespaces can be exported
+//! to and imported from JSON for fixture seeding and debugging.
+//!
+//! The store remembers when each key was last written, for
+//! [retention](crate::retention); keys loaded from a snapshot count as
+//! written when they were loaded.
+
+use crate::error::{BackworksError, BackworksResult};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::RwLock;
+
+/// Portable JSON form of the store: `{ "namespaces": { ns: { key: value } } }`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    #[serde(default)]
+    pub namespaces: BTreeMap<String, BTreeMap<String, Value>>,
+}
+
+impl StateSnapshot {
+    pub async fn load(path: &Path) -> BackworksResult<Self> {
+        let content = tokio::fs::read_to_string(path).await
+            .map_err(|e| BackworksError::config(format!("Failed to read state file {}: {}", path.display(), e)))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn key_count(&self) -> usize {
+        self.namespaces.values().map(BTreeMap::len).sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Overwrite imported keys, keep everything else
+    #[default]
+    Merge,
+    /// Clear each imported namespace before loading it
+    Replace,
+}
+
+/// Counts reported after an import
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub namespaces: usize,
+    pub keys: usize,
+}
+
+type Namespaces = HashMap<String, HashMap<String, Value>>;
+
+/// Last write of each (namespace, key)
+type WriteTimes = HashMap<(String, String), DateTime<Utc>>;
+
+#[derive(Debug, Clone, Default)]
+pub struct StateStore {
+    namespaces: Arc<RwLock<Namespaces>>,
+    /// Updated with the namespaces' write lock held
+    written: Arc<Mutex<WriteTimes>>,
+    /// Snapshot file rewritten after every change
+    persist: Option<Arc<PathBuf>>,
+}
+
+impl StateStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A store that writes a snapshot to `path` after every change
+    pub fn persisted(path: PathBuf) -> Self {
+        Self { persist: Some(Arc::new(path)), ..Self::default() }
+    }
+
+    pub fn persist_path(&self) -> Option<&Path> {
+        self.persist.as_deref().map(PathBuf::as_path)
+    }
+
+    pub async fn get(&self, namespace: &str, key: &str) -> Option<Value> {
+        self.namespaces.read().await.get(namespace)?.get(key).cloned()
+    }
+
+    pub async fn set(&self, namespace: &str, key: &str, value: Value) -> Option<Value> {
+        let mut namespaces = self.namespaces.write().await;
+        let previous = namespaces
+            .entry(namespace.to_string())
+            .or_default()
+            .insert(key.to_string(), value);
+        self.write_times().insert((namespace.to_string(), key.to_string()), Utc::now());
+        self.save(&namespaces).await;
+        previous
+    }
+
+    pub async fn delete(&self, namespace: &str, key: &str) -> Option<Value> {
+        let mut namespaces = self.namespaces.write().await;
+        let entries = namespaces.get_mut(namespace)?;
+        let removed = entries.remove(key);
+        if entries.is_empty() {
+            namespaces.remove(namespace);
+        }
+        self.write_times().remove(&(namespace.to_string(), key.to_string()));
+        self.save(&namespaces).await;
+        removed
+    }
+
+    pub async fn keys(&self, namespace: &str) -> Vec<String> {
+        let namespaces = self.namespaces.read().await;
+        let mut keys: Vec<String> = namespaces.get(namespace)
+            .map(|entries| entries.keys().cloned().collect())
+            .unwrap_or_default();
+        keys.sort();
+        keys
+    }
+
+    /// Names of the namespaces holding keys, sorted
+    pub async fn namespaces(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.namespaces.read().await.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// When the key was last written
+    pub async fn written_at(&self, namespace: &str, key: &str) -> Option<DateTime<Utc>> {
+        let _namespaces = self.namespaces.read().await;
+        self.write_times().get(&(namespace.to_string(), key.to_string())).copied()
+    }
+
+    fn write_times(&self) -> std::sync::MutexGuard<'_, WriteTimes> {
+        self.written.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Export one namespace, or every namespace when `namespace` is `None`
+    pub async fn export(&self, namespace: Option<&str>) -> StateSnapshot {
+        snapshot(&*self.namespaces.read().await, namespace)
+    }
+
+    /// Load a snapshot. When `namespace` is given, only that namespace of the
+    /// snapshot is imported.
+    pub async fn import(&self, snapshot: StateSnapshot, mode: ImportMode, namespace: Option<&str>) -> ImportSummary {
+        let mut namespaces = self.namespaces.write().await;
+        let mut summary = ImportSummary::default();
+        let now = Utc::now();
+
+        for (name, entries) in snapshot.namespaces {
+            if namespace.is_some_and(|wanted| wanted != name) {
+                continue;
+            }
+
+            let target = namespaces.entry(name.clone()).or_default();
+            if mode == ImportMode::Replace {
+                target.clear();
+            }
+            summary.namespaces += 1;
+            summary.keys += entries.len();
+            let mut written = self.write_times();
+            written.extend(entries.keys().map(|key| ((name.clone(), key.clone()), now)));
+            target.extend(entries);
+        }
+
+        namespaces.retain(|_, entries| !entries.is_empty());
+        self.write_times().retain(|(name, key), _| namespaces.get(name).is_some_and(|entries| entries.contains_key(key)));
+        self.save(&namespaces).await;
+        summary
+    }
+
+    /// Write the persisted snapshot; called with the write lock held so
+    /// snapshots land in the order of the changes
+    async fn save(&self, namespaces: &Namespaces) {
+        let Some(ref path) = self.persist else {
+            return;
+        };
+        let written = match serde_json::to_vec_pretty(&snapshot(namespaces, None)) {
+            Ok(json) => {
+                // Write beside the file and rename so readers never see half a snapshot
+                let partial = path.with_extension("partial");
+                match tokio::fs::write(&partial, json).await {
+                    Ok(()) => tokio::fs::rename(&partial, path.as_ref()).await,
+                    Err(e) => Err(e),
+                }
+            }
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = written {
+            tracing::warn!("Failed to persist state store to {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn snapshot(namespaces: &Namespaces, namespace: Option<&str>) -> StateSnapshot {
+    let namespaces = namespaces.iter()
+        .filter(|(name, _)| namespace.is_none_or(|wanted| wanted == name.as_str()))
+        .map(|(name, entries)| {
+            let entries = entries.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+            (name.clone(), entries)
+        })
+        .collect();
+
+    StateSnapshot { namespaces }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_export_import_round_trip() {
+        let store = StateStore::new();
+        store.set("shop", "cart:1", json!({"items": 2})).await;
+        store.set("shop", "cart:2", json!({"items": 0})).await;
+        store.set("tenant-b", "flag", json!(true)).await;
+
+        let snapshot = store.export(Some("shop")).await;
+        assert_eq!(snapshot.namespaces.len(), 1);
+        assert_eq!(snapshot.key_count(), 2);
+
+        let restored = StateStore::new();
+        let summary = restored.import(store.export(None).await, ImportMode::Merge, None).await;
+        assert_eq!(summary, ImportSummary { namespaces: 2, keys: 3 });
+        assert_eq!(restored.get("shop", "cart:1").await, Some(json!({"items": 2})));
+    }
+
+    #[tokio::test]
+    async fn test_persisted_store_rewrites_its_snapshot() {
+        let path = std::env::temp_dir().join(format!("backworks_state_{}.json", uuid::Uuid::new_v4()));
+        let store = StateStore::persisted(path.clone());
+        store.set("shop", "cart:1", json!({"items": 2})).await;
+        store.set("shop", "cart:2", json!({"items": 0})).await;
+        store.delete("shop", "cart:2").await;
+
+        let restored = StateStore::new();
+        restored.import(StateSnapshot::load(&path).await.unwrap(), ImportMode::Merge, None).await;
+        assert_eq!(restored.keys("shop").await, vec!["cart:1"]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replace_clears_only_imported_namespaces() {
+        let store = StateStore::new();
+        store.set("shop", "stale", json!(1)).await;
+        store.set("other", "kept", json!(1)).await;
+
+        let mut snapshot = StateSnapshot::default();
+        snapshot.namespaces.entry("shop".to_string()).or_default().insert("fresh".to_string(), json!(2));
+        snapshot.namespaces.entry("ignored".to_string()).or_default().insert("x".to_string(), json!(3));
+
+        store.import(snapshot, ImportMode::Replace, Some("shop")).await;
+        assert_eq!(store.keys("shop").await, vec!["fresh"]);
+        assert_eq!(store.keys("other").await, vec!["kept"]);
+        assert!(store.keys("ignored").await.is_empty());
+    }
+}
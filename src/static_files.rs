@@ -0,0 +1,281 @@
+//! Static file serving for `mode: static` endpoints
+//!
+//! A static endpoint serves the files under its `dir` at its path and
+//! everything below it, so a built frontend can share the API's port.
+//! Responses carry an `ETag` and `Last-Modified` for conditional requests,
+//! honour a single byte `Range`, and with `spa_fallback` answer unknown
+//! extensionless paths with the root index so client-side routes resolve.
+
+use crate::config::StaticFilesConfig;
+use axum::body::{Body, Bytes};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::http::response;
+use axum::response::{IntoResponse, Response};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    root: PathBuf,
+    index: Vec<String>,
+    spa_fallback: bool,
+    cache_control: Option<HeaderValue>,
+}
+
+/// Byte range selected by a `Range` header
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ByteRange {
+    Full,
+    Partial { start: u64, end: u64 },
+    Unsatisfiable,
+}
+
+impl StaticFiles {
+    pub fn new(config: &StaticFilesConfig) -> Self {
+        Self {
+            root: config.dir.clone(),
+            index: config.index.clone(),
+            spa_fallback: config.spa_fallback,
+            cache_control: config.cache_control.as_deref().and_then(|value| HeaderValue::from_str(value).ok()),
+        }
+    }
+
+    /// Serve `relative`, the request path below the endpoint's own path
+    pub async fn serve(&self, relative: &str, headers: &HeaderMap, head: bool) -> Response {
+        let Some(path) = self.resolve(relative).await else {
+            return not_found(relative);
+        };
+        match self.respond(&path, headers, head).await {
+            Ok(response) => response,
+            Err(e) => {
+                tracing::warn!("Failed to read {}: {}", path.display(), e);
+                not_found(relative)
+            }
+        }
+    }
+
+    /// The file for a request path, after index and SPA fallback lookup
+    async fn resolve(&self, relative: &str) -> Option<PathBuf> {
+        let candidate = self.root.join(sanitize(relative)?);
+        match tokio::fs::metadata(&candidate).await {
+            Ok(metadata) if metadata.is_file() => return Some(candidate),
+            Ok(metadata) if metadata.is_dir() => {
+                if let Some(index) = self.find_index(&candidate).await {
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+
+        // Client-side routes look like `/orders/42`; missing assets keep their 404
+        let last = relative.rsplit('/').find(|segment| !segment.is_empty()).unwrap_or("");
+        if self.spa_fallback && !last.contains('.') {
+            return self.find_index(&self.root).await;
+        }
+        None
+    }
+
+    async fn find_index(&self, dir: &Path) -> Option<PathBuf> {
+        for name in &self.index {
+            let path = dir.join(name);
+            if tokio::fs::metadata(&path).await.is_ok_and(|m| m.is_file()) {
+                return Some(path);
+            }
+        }
+        None
+    }
+
+    async fn respond(&self, path: &Path, headers: &HeaderMap, head: bool) -> std::io::Result<Response> {
+        let mut builder = Response::builder();
+        if let Some(ref cache_control) = self.cache_control {
+            builder = builder.header(header::CACHE_CONTROL, cache_control);
+        }
+        let content_type = mime_guess::from_path(path).first_or_octet_stream();
+        file_response(path, content_type.as_ref(), headers, head, builder, ()).await
+    }
+}
+
+/// Answer with the contents of `path`, honouring conditional requests and a
+/// single byte range. The body is streamed from disk; `guard` is dropped once
+/// it has been sent or the client went away.
+pub(crate) async fn file_response<G: Send + 'static>(
+    path: &Path,
+    content_type: &str,
+    headers: &HeaderMap,
+    head: bool,
+    builder: response::Builder,
+    guard: G,
+) -> std::io::Result<Response> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let metadata = file.metadata().await?;
+    let len = metadata.len();
+    let modified = metadata.modified().ok();
+    let etag = entity_tag(len, modified);
+
+    let mut builder = builder
+        .header(header::ETAG, &etag)
+        .header(header::ACCEPT_RANGES, "bytes");
+    if let Some(modified) = modified {
+        builder = builder.header(header::LAST_MODIFIED, httpdate::fmt_http_date(modified));
+    }
+
+    if not_modified(headers, &etag, modified) {
+        return built(builder.status(StatusCode::NOT_MODIFIED).body(Body::empty()));
+    }
+
+    builder = builder.header(header::CONTENT_TYPE, content_type);
+
+    let (status, start, end) = match requested_range(headers, len) {
+        ByteRange::Full => (StatusCode::OK, 0, len),
+        ByteRange::Partial { start, end } => {
+            builder = builder.header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end - 1, len));
+            (StatusCode::PARTIAL_CONTENT, start, end)
+        }
+        ByteRange::Unsatisfiable => {
+            return built(builder
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+                .body(Body::empty()));
+        }
+    };
+
+    builder = builder.status(status).header(header::CONTENT_LENGTH, end - start);
+    if head {
+        return built(builder.body(Body::empty()));
+    }
+
+    file.seek(std::io::SeekFrom::Start(start)).await?;
+    let chunks = futures::stream::unfold((file.take(end - start), guard), |(mut reader, guard)| async move {
+        let mut chunk = vec![0; CHUNK_SIZE];
+        match reader.read(&mut chunk).await {
+            Ok(0) => None,
+            Ok(read) => {
+                chunk.truncate(read);
+                Some((Ok(Bytes::from(chunk)), (reader, guard)))
+            }
+            Err(e) => Some((Err(e), (reader, guard))),
+        }
+    });
+    built(builder.body(Body::from_stream(chunks)))
+}
+
+/// A response whose headers could not be built, such as an invalid content
+/// type, is an error rather than an empty response
+fn built(response: axum::http::Result<Response>) -> std::io::Result<Response> {
+    response.map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+}
+
+/// Relative file path for a request path, or `None` if it would escape the root
+fn sanitize(relative: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for segment in relative.split('/').filter(|segment| !segment.is_empty()) {
+        if segment.contains('\\') {
+            return None;
+        }
+        match Path::new(segment).components().next() {
+            Some(Component::Normal(part)) => path.push(part),
+            Some(Component::CurDir) => {}
+            _ => return None,
+        }
+    }
+    Some(path)
+}
+
+fn entity_tag(len: u64, modified: Option<SystemTime>) -> String {
+    let nanos = modified
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+    format!("\"{:x}-{:x}\"", len, nanos)
+}
+
+/// Whether `If-None-Match`, or failing that `If-Modified-Since`, allows a 304
+fn not_modified(headers: &HeaderMap, etag: &str, modified: Option<SystemTime>) -> bool {
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+        return if_none_match.split(',').map(str::trim).any(|tag| {
+            tag == "*" || tag.trim_start_matches("W/") == etag
+        });
+    }
+    let since = headers.get(header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| httpdate::parse_http_date(v).ok());
+    match (since, modified) {
+        // HTTP dates have second precision
+        (Some(since), Some(modified)) => httpdate::HttpDate::from(modified) <= httpdate::HttpDate::from(since),
+        _ => false,
+    }
+}
+
+/// The range to serve; multiple ranges are answered with the whole file
+fn requested_range(headers: &HeaderMap, len: u64) -> ByteRange {
+    let Some(spec) = headers.get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().strip_prefix("bytes="))
+    else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((first, last)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+
+    let (start, end) = match (first.parse::<u64>().ok(), last.parse::<u64>().ok()) {
+        (Some(start), Some(last)) if last >= start => (start, (last + 1).min(len)),
+        (Some(start), None) if last.is_empty() => (start, len),
+        (None, Some(suffix)) if first.is_empty() => (len.saturating_sub(suffix), len),
+        _ => return ByteRange::Full,
+    };
+    if start >= len || start >= end {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Partial { start, end }
+}
+
+fn not_found(relative: &str) -> Response {
+    let message = format!("No file matches /{}", relative.trim_start_matches('/'));
+    let mut response = (StatusCode::NOT_FOUND, axum::Json(serde_json::json!({"error": message}))).into_response();
+    response.extensions_mut().insert(crate::error::RequestError::new(
+        StatusCode::NOT_FOUND,
+        message,
+        crate::error::ErrorSource::Handler,
+    ));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(value: &str, len: u64) -> ByteRange {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_str(value).unwrap());
+        requested_range(&headers, len)
+    }
+
+    #[test]
+    fn test_sanitize_rejects_traversal() {
+        assert_eq!(sanitize("assets/app.js"), Some(PathBuf::from("assets/app.js")));
+        assert_eq!(sanitize("/./a//b/"), Some(PathBuf::from("a/b")));
+        assert_eq!(sanitize(""), Some(PathBuf::new()));
+        assert_eq!(sanitize("../secret"), None);
+        assert_eq!(sanitize("a/../../b"), None);
+        assert_eq!(sanitize("a\\..\\b"), None);
+    }
+
+    #[test]
+    fn test_requested_range() {
+        assert_eq!(range("bytes=0-3", 10), ByteRange::Partial { start: 0, end: 4 });
+        assert_eq!(range("bytes=6-", 10), ByteRange::Partial { start: 6, end: 10 });
+        assert_eq!(range("bytes=-3", 10), ByteRange::Partial { start: 7, end: 10 });
+        assert_eq!(range("bytes=4-100", 10), ByteRange::Partial { start: 4, end: 10 });
+        assert_eq!(range("bytes=10-", 10), ByteRange::Unsatisfiable);
+        assert_eq!(range("bytes=0-1,4-5", 10), ByteRange::Full);
+        assert_eq!(range("items=0-1", 10), ByteRange::Full);
+        assert_eq!(requested_range(&HeaderMap::new(), 10), ByteRange::Full);
+    }
+}
@@ -0,0 +1,114 @@
+//! Rolling request statistics
+//!
+//! Keeps a bounded window of recent request outcomes so that subsystems such
+//! as alerting can evaluate error rates and latency percentiles without an
+//! external metrics backend.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+/// Upper bound on retained samples, regardless of window length
+const MAX_SAMPLES: usize = 50_000;
+
+#[derive(Debug, Clone, Copy)]
+struct RequestSample {
+    at: Instant,
+    status: u16,
+    latency_ms: f64,
+}
+
+/// Aggregated view over the samples in a time window
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatsSnapshot {
+    pub requests: u64,
+    pub errors: u64,
+    /// Fraction of requests answered with a 5xx status (0.0 - 1.0)
+    pub error_rate: f64,
+    pub avg_latency_ms: f64,
+    pub p50_latency_ms: f64,
+    pub p95_latency_ms: f64,
+    pub p99_latency_ms: f64,
+}
+
+/// Shared, cheaply clonable request statistics recorder
+#[derive(Debug, Clone)]
+pub struct RequestStats {
+    samples: Arc<RwLock<VecDeque<RequestSample>>>,
+    retention: Duration,
+}
+
+impl Default for RequestStats {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(15 * 60))
+    }
+}
+
+impl RequestStats {
+    /// Create a recorder that retains samples for `retention`
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            samples: Arc::new(RwLock::new(VecDeque::new())),
+            retention,
+        }
+    }
+
+    pub async fn record(&self, status: u16, latency: Duration) {
+        self.record_at(Instant::now(), status, latency).await;
+    }
+
+    async fn record_at(&self, at: Instant, status: u16, latency: Duration) {
+        let mut samples = self.samples.write().await;
+        samples.push_back(RequestSample {
+            at,
+            status,
+            latency_ms: latency.as_secs_f64() * 1000.0,
+        });
+
+        while samples.len() > MAX_SAMPLES {
+            samples.pop_front();
+        }
+        while samples.front().is_some_and(|s| at.saturating_duration_since(s.at) > self.retention) {
+            samples.pop_front();
+        }
+    }
+
+    /// Aggregate the samples recorded within the last `window`
+    pub async fn snapshot(&self, window: Duration) -> StatsSnapshot {
+        let now = Instant::now();
+        let samples = self.samples.read().await;
+        let recent: Vec<&RequestSample> = samples.iter()
+            .filter(|s| now.saturating_duration_since(s.at) <= window)
+            .collect();
+
+        if recent.is_empty() {
+            return StatsSnapshot::default();
+        }
+
+        let requests = recent.len() as u64;
+        let errors = recent.iter().filter(|s| s.status >= 500).count() as u64;
+        let mut latencies: Vec<f64> = recent.iter().map(|s| s.latency_ms).collect();
+        latencies.sort_by(|a, b| a.total_cmp(b));
+
+        StatsSnapshot {
+            requests,
+            errors,
+            error_rate: errors as f64 / requests as f64,
+            avg_latency_ms: latencies.iter().sum::<f64>() / requests as f64,
+            p50_latency_ms: percentile(&latencies, 0.50),
+            p95_latency_ms: percentile(&latencies, 0.95),
+            p99_latency_ms: percentile(&latencies, 0.99),
+        }
+    }
+}
+
+/// Nearest-rank percentile over an ascending slice
+pub fn percentile(sorted: &[f64], quantile: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (quantile * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
@@ -0,0 +1,109 @@
+//! Studio assets served by the dashboard
+//!
+//! The Studio built into `studio/dist` is embedded into the binary at
+//! compile time (see `build.rs`), so the dashboard works from any directory
+//! and in containers. `dashboard.assets_dir`, or the `BACKWORKS_STUDIO_DIR`
+//! environment variable, serves a directory from disk instead, such as a
+//! Studio being worked on.
+
+use crate::config::DashboardConfig;
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+
+include!(concat!(env!("OUT_DIR"), "/studio_assets.rs"));
+
+/// Environment variable naming a Studio directory to serve from disk
+pub const STUDIO_DIR_VAR: &str = "BACKWORKS_STUDIO_DIR";
+
+const UNAVAILABLE: &str = "<h1>Studio Unavailable</h1><p>This binary was built without the Studio. Build it with <code>cd studio && npm run build</code> and rebuild Backworks, or point <code>dashboard.assets_dir</code> at a built Studio.</p>";
+
+/// Where the dashboard reads Studio files from
+#[derive(Debug, Clone)]
+pub enum StudioAssets {
+    Embedded(&'static [(&'static str, &'static [u8])]),
+    Directory(PathBuf),
+}
+
+impl StudioAssets {
+    /// The configured directory, else the assets built into the binary
+    pub fn from_config(config: &DashboardConfig) -> Self {
+        let dir = config.assets_dir.clone()
+            .or_else(|| std::env::var_os(STUDIO_DIR_VAR).filter(|dir| !dir.is_empty()).map(PathBuf::from));
+        match dir {
+            Some(dir) => {
+                tracing::info!("🎨 Serving the Studio from {}", dir.display());
+                Self::Directory(dir)
+            }
+            None => Self::Embedded(EMBEDDED),
+        }
+    }
+
+    /// Contents of `path`, relative to the Studio root
+    pub fn get(&self, path: &str) -> Option<Cow<'static, [u8]>> {
+        let path = path.trim_start_matches('/');
+        match self {
+            Self::Embedded(files) => files.iter()
+                .find(|(name, _)| *name == path)
+                .map(|(_, content)| Cow::Borrowed(*content)),
+            Self::Directory(dir) => {
+                // Only files below the directory
+                let relative = Path::new(path);
+                if relative.components().any(|component| !matches!(component, Component::Normal(_))) {
+                    return None;
+                }
+                std::fs::read(dir.join(relative)).ok().map(Cow::Owned)
+            }
+        }
+    }
+
+    /// `path` with its content type, or `404`
+    pub fn serve(&self, path: &str) -> Response {
+        match self.get(path) {
+            Some(content) => {
+                let mime = mime_guess::from_path(path).first_or_octet_stream();
+                let content_type = HeaderValue::from_str(mime.as_ref()).unwrap_or(HeaderValue::from_static("application/octet-stream"));
+                ([(header::CONTENT_TYPE, content_type)], content.into_owned()).into_response()
+            }
+            None => (StatusCode::NOT_FOUND, "File not found").into_response(),
+        }
+    }
+
+    /// The Studio's `index.html`, or a page explaining how to get one
+    pub fn index(&self) -> Response {
+        match self.get("index.html") {
+            Some(content) => ([(header::CONTENT_TYPE, "text/html; charset=utf-8")], content.into_owned()).into_response(),
+            None => {
+                tracing::warn!("No Studio to serve; see {}", match self {
+                    Self::Embedded(_) => "`dashboard.assets_dir`".to_string(),
+                    Self::Directory(dir) => dir.display().to_string(),
+                });
+                (StatusCode::SERVICE_UNAVAILABLE, [(header::CONTENT_TYPE, "text/html; charset=utf-8")], UNAVAILABLE).into_response()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assets_come_from_the_binary_or_a_directory() {
+        let embedded = StudioAssets::Embedded(&[("index.html", b"<h1>Studio</h1>"), ("build/app.js", b"run()")]);
+        assert_eq!(embedded.get("/build/app.js").as_deref(), Some(&b"run()"[..]));
+        assert!(embedded.get("build/missing.js").is_none());
+        assert_eq!(embedded.serve("/build/app.js").headers()[header::CONTENT_TYPE], "text/javascript");
+        assert_eq!(embedded.index().status(), StatusCode::OK);
+        assert_eq!(StudioAssets::Embedded(&[]).index().status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let dir = std::env::temp_dir().join(format!("backworks_studio_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("assets")).unwrap();
+        std::fs::write(dir.join("assets/app.css"), "body {}").unwrap();
+        let directory = StudioAssets::Directory(dir.join("assets"));
+        assert_eq!(directory.get("app.css").as_deref(), Some(&b"body {}"[..]));
+        assert!(directory.get("../assets/app.css").is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,418 @@
+//! Proxy target pools
+//!
+//! A proxy endpoint with several `targets` sends each request to the one its
+//! [load-balancing strategy](crate::balancer) picks among those available,
+//! and tracks each target's load, failures and latency for the strategy.
+//! Operators can take a target out of rotation
+//! without a restart: draining stops new requests and waits for the ones in
+//! flight, quarantine keeps the target out for a while, and enabling puts it
+//! back. Every action is logged under the `backworks::audit` target and kept
+//! in the pools' audit trail.
+
+use crate::balancer::{LoadBalancer, RoundRobin, TargetMetrics};
+use crate::server::RequestData;
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tracing::info;
+
+/// Audit entries kept, oldest dropped first
+const MAX_AUDIT_ENTRIES: usize = 200;
+/// Weight of the latest response time in a target's latency average
+const LATENCY_SMOOTHING: f64 = 0.3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetState {
+    Active,
+    /// No new requests; waiting for the ones in flight
+    Draining,
+    /// No new requests and none in flight
+    Drained,
+    /// No new requests until the quarantine ends
+    Quarantined,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TargetStatus {
+    pub endpoint: String,
+    pub target: String,
+    pub state: TargetState,
+    pub in_flight: usize,
+    pub requests: u64,
+    pub failures: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quarantined_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetOperation {
+    Drain,
+    Quarantine,
+    Enable,
+}
+
+/// One operator action on a target
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TargetAction {
+    pub at: DateTime<Utc>,
+    pub endpoint: String,
+    pub target: String,
+    pub operation: TargetOperation,
+    /// The target's state once the action completed
+    pub state: TargetState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Body of the drain, quarantine and enable admin operations
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TargetCommand {
+    /// Target URL, as in the blueprint
+    pub target: String,
+    /// Drain: how long to wait for requests in flight (default 30s).
+    /// Quarantine: how long to keep the target out of rotation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TargetsReport {
+    pub targets: Vec<TargetStatus>,
+    /// Operator actions, oldest first
+    pub audit: Vec<TargetAction>,
+}
+
+impl fmt::Display for TargetState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TargetState::Active => "active",
+            TargetState::Draining => "draining",
+            TargetState::Drained => "drained",
+            TargetState::Quarantined => "quarantined",
+        })
+    }
+}
+
+impl fmt::Display for TargetOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TargetOperation::Drain => "drain",
+            TargetOperation::Quarantine => "quarantine",
+            TargetOperation::Enable => "enable",
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Control {
+    state: TargetState,
+    quarantined_until: Option<DateTime<Utc>>,
+    failures: u64,
+    latency_ms: Option<f64>,
+}
+
+#[derive(Debug)]
+struct Target {
+    url: String,
+    labels: HashMap<String, String>,
+    control: Mutex<Control>,
+    in_flight: AtomicUsize,
+    requests: AtomicU64,
+    idle: Notify,
+}
+
+/// The targets of one proxy endpoint
+pub struct TargetPool {
+    endpoint: String,
+    targets: Vec<Target>,
+    balancer: OnceLock<Arc<dyn LoadBalancer>>,
+    /// Used until a strategy is set
+    round_robin: RoundRobin,
+}
+
+impl fmt::Debug for TargetPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TargetPool").field("endpoint", &self.endpoint).field("targets", &self.targets).finish_non_exhaustive()
+    }
+}
+
+/// A request in flight to a target; dropping it ends the request
+#[derive(Debug)]
+pub struct TargetLease<'a> {
+    target: &'a Target,
+    started: Instant,
+}
+
+impl TargetPool {
+    /// A pool of targets, given by URL and labels
+    pub fn new(endpoint: &str, targets: Vec<(String, HashMap<String, String>)>) -> Self {
+        let targets = targets.into_iter()
+            .map(|(url, labels)| Target {
+                url,
+                labels,
+                control: Mutex::new(Control { state: TargetState::Active, quarantined_until: None, failures: 0, latency_ms: None }),
+                in_flight: AtomicUsize::new(0),
+                requests: AtomicU64::new(0),
+                idle: Notify::new(),
+            })
+            .collect();
+        Self { endpoint: endpoint.to_string(), targets, balancer: OnceLock::new(), round_robin: RoundRobin::default() }
+    }
+
+    /// Pick targets with `balancer` from now on; only the first strategy set
+    /// is kept
+    pub fn use_balancer(&self, balancer: Arc<dyn LoadBalancer>) {
+        let _ = self.balancer.set(balancer);
+    }
+
+    /// The target the strategy picks for `request` among those taking
+    /// requests, if any
+    pub fn acquire(&self, request: &RequestData) -> Option<TargetLease<'_>> {
+        let balancer: &dyn LoadBalancer = match self.balancer.get() {
+            Some(balancer) => balancer.as_ref(),
+            None => &self.round_robin,
+        };
+        // A target picked may leave rotation before it is claimed; pick again
+        for _ in 0..self.targets.len() {
+            let (indices, available): (Vec<usize>, Vec<TargetMetrics>) = self.targets.iter().enumerate()
+                .filter_map(|(index, target)| Some((index, self.available(target)?)))
+                .unzip();
+            if available.is_empty() {
+                return None;
+            }
+            let index = *indices.get(balancer.select(&available, request)?)?;
+            if let Some(lease) = self.claim(&self.targets[index]) {
+                return Some(lease);
+            }
+        }
+        None
+    }
+
+    /// A target's metrics when it takes requests
+    fn available(&self, target: &Target) -> Option<TargetMetrics> {
+        let mut control = target.control.lock().expect("target lock poisoned");
+        self.refresh(target, &mut control);
+        (control.state == TargetState::Active).then(|| TargetMetrics {
+            url: target.url.clone(),
+            labels: target.labels.clone(),
+            in_flight: target.in_flight.load(Ordering::Acquire),
+            requests: target.requests.load(Ordering::Relaxed),
+            failures: control.failures,
+            latency_ms: control.latency_ms,
+        })
+    }
+
+    /// End a quarantine that ran out
+    fn refresh(&self, target: &Target, control: &mut Control) {
+        if control.state == TargetState::Quarantined && control.quarantined_until.is_some_and(|until| until <= Utc::now()) {
+            info!(target: "backworks::audit", "Proxy target {} of '{}' left quarantine", target.url, self.endpoint);
+            control.state = TargetState::Active;
+            control.quarantined_until = None;
+        }
+    }
+
+    /// Start a request to `target` unless it left rotation
+    fn claim<'a>(&self, target: &'a Target) -> Option<TargetLease<'a>> {
+        let mut control = target.control.lock().expect("target lock poisoned");
+        self.refresh(target, &mut control);
+        if control.state != TargetState::Active {
+            return None;
+        }
+        // Counted under the lock so a drain never misses this request
+        target.in_flight.fetch_add(1, Ordering::AcqRel);
+        target.requests.fetch_add(1, Ordering::Relaxed);
+        Some(TargetLease { target, started: Instant::now() })
+    }
+
+    pub fn status(&self) -> Vec<TargetStatus> {
+        let now = Utc::now();
+        self.targets.iter()
+            .map(|target| {
+                let control = target.control.lock().expect("target lock poisoned");
+                let quarantined_until = control.quarantined_until.filter(|until| *until > now);
+                let state = match control.state {
+                    TargetState::Quarantined if quarantined_until.is_none() => TargetState::Active,
+                    state => state,
+                };
+                TargetStatus {
+                    endpoint: self.endpoint.clone(),
+                    target: target.url.clone(),
+                    state,
+                    in_flight: target.in_flight.load(Ordering::Acquire),
+                    requests: target.requests.load(Ordering::Relaxed),
+                    failures: control.failures,
+                    latency_ms: control.latency_ms,
+                    quarantined_until,
+                }
+            })
+            .collect()
+    }
+
+    /// Stop sending requests to `url` and wait up to `timeout` for those in
+    /// flight; the target stays `draining` when they outlast it
+    pub async fn drain(&self, url: &str, timeout: Duration) -> Result<TargetState, (StatusCode, String)> {
+        let target = self.find(url)?;
+        target.set(TargetState::Draining, None);
+
+        let waited = tokio::time::timeout(timeout, async {
+            loop {
+                let idle = target.idle.notified();
+                if target.in_flight.load(Ordering::Acquire) == 0 {
+                    break;
+                }
+                idle.await;
+            }
+        }).await;
+
+        let mut control = target.control.lock().expect("target lock poisoned");
+        // Another operator may have enabled or quarantined it meanwhile
+        if waited.is_ok() && control.state == TargetState::Draining {
+            control.state = TargetState::Drained;
+        }
+        Ok(control.state)
+    }
+
+    /// Keep `url` out of rotation for `period`
+    pub fn quarantine(&self, url: &str, period: Duration) -> Result<TargetState, (StatusCode, String)> {
+        let until = chrono::Duration::from_std(period).ok().and_then(|period| Utc::now().checked_add_signed(period));
+        self.find(url)?.set(TargetState::Quarantined, Some(until.unwrap_or(DateTime::<Utc>::MAX_UTC)));
+        Ok(TargetState::Quarantined)
+    }
+
+    /// Put `url` back into rotation
+    pub fn enable(&self, url: &str) -> Result<TargetState, (StatusCode, String)> {
+        self.find(url)?.set(TargetState::Active, None);
+        Ok(TargetState::Active)
+    }
+
+    fn find(&self, url: &str) -> Result<&Target, (StatusCode, String)> {
+        let url = url.trim_end_matches('/');
+        self.targets.iter().find(|target| target.url == url).ok_or_else(|| {
+            let known: Vec<&str> = self.targets.iter().map(|target| target.url.as_str()).collect();
+            (StatusCode::NOT_FOUND, format!("Endpoint '{}' has no proxy target '{}' (targets: {})", self.endpoint, url, known.join(", ")))
+        })
+    }
+}
+
+impl Target {
+    fn set(&self, state: TargetState, quarantined_until: Option<DateTime<Utc>>) {
+        let mut control = self.control.lock().expect("target lock poisoned");
+        control.state = state;
+        control.quarantined_until = quarantined_until;
+    }
+}
+
+impl TargetLease<'_> {
+    pub fn url(&self) -> &str {
+        &self.target.url
+    }
+
+    /// End the request, noting its response time and whether it failed
+    pub fn finish(self, failed: bool) {
+        let elapsed = self.started.elapsed().as_secs_f64() * 1000.0;
+        let mut control = self.target.control.lock().expect("target lock poisoned");
+        control.failures += u64::from(failed);
+        control.latency_ms = Some(match control.latency_ms {
+            Some(average) => average + LATENCY_SMOOTHING * (elapsed - average),
+            None => elapsed,
+        });
+    }
+}
+
+impl Drop for TargetLease<'_> {
+    fn drop(&mut self) {
+        if self.target.in_flight.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.target.idle.notify_waiters();
+        }
+    }
+}
+
+/// Recent operator actions on proxy targets
+#[derive(Debug, Default)]
+pub struct TargetAudit {
+    actions: Mutex<VecDeque<TargetAction>>,
+}
+
+impl TargetAudit {
+    pub fn record(&self, action: TargetAction) {
+        info!(
+            target: "backworks::audit",
+            "Proxy target {} of '{}': {} -> {}{}",
+            action.target, action.endpoint, action.operation, action.state,
+            action.reason.as_deref().map(|reason| format!(" ({})", reason)).unwrap_or_default(),
+        );
+        let mut actions = self.actions.lock().expect("audit lock poisoned");
+        actions.push_back(action);
+        while actions.len() > MAX_AUDIT_ENTRIES {
+            actions.pop_front();
+        }
+    }
+
+    pub fn entries(&self) -> Vec<TargetAction> {
+        self.actions.lock().expect("audit lock poisoned").iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool() -> TargetPool {
+        TargetPool::new("orders", vec![("http://a".to_string(), HashMap::new()), ("http://b".to_string(), HashMap::new())])
+    }
+
+    fn picks(pool: &TargetPool, count: usize) -> Vec<String> {
+        (0..count).map(|_| pool.acquire(&RequestData::default()).map(|lease| lease.url().to_string()).unwrap_or_default()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_drain_waits_for_requests_in_flight() {
+        let pool = pool();
+        assert_eq!(picks(&pool, 3), vec!["http://a", "http://b", "http://a"]);
+
+        let lease = loop {
+            let lease = pool.acquire(&RequestData::default()).unwrap();
+            if lease.url() == "http://a" {
+                break lease;
+            }
+        };
+        assert_eq!(pool.drain("http://a/", Duration::from_millis(20)).await.unwrap(), TargetState::Draining);
+        assert_eq!(picks(&pool, 2), vec!["http://b", "http://b"]);
+        assert_eq!(pool.status()[0].in_flight, 1);
+
+        let (state, _) = tokio::join!(pool.drain("http://a", Duration::from_secs(5)), async { drop(lease) });
+        assert_eq!(state.unwrap(), TargetState::Drained);
+        assert_eq!(pool.status()[0].in_flight, 0);
+
+        pool.enable("http://a").unwrap();
+        assert!(picks(&pool, 2).contains(&"http://a".to_string()));
+        assert_eq!(pool.drain("http://c", Duration::ZERO).await.unwrap_err().0, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_quarantine_ends_on_its_own() {
+        let pool = pool();
+        pool.quarantine("http://b", Duration::from_secs(60)).unwrap();
+        assert_eq!(picks(&pool, 2), vec!["http://a", "http://a"]);
+        assert!(pool.status()[1].quarantined_until.is_some());
+
+        pool.quarantine("http://a", Duration::ZERO).unwrap();
+        assert_eq!(pool.status()[0].state, TargetState::Active);
+        assert_eq!(picks(&pool, 2), vec!["http://a", "http://a"]);
+
+        pool.quarantine("http://a", Duration::from_secs(60)).unwrap();
+        assert!(pool.acquire(&RequestData::default()).is_none());
+    }
+}
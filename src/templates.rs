@@ -0,0 +1,235 @@
+//! Endpoint request and response templates
+//!
+//! An endpoint's `template` block holds Handlebars templates that rewrite the
+//! request body before the handler runs and render the response body from
+//! what the handler returned. Templates see:
+//!
+//! - `request`: `method`, `path`, `params` (path captures), `query`,
+//!   `headers`, `body` and, with [sessions](crate::session), `session`
+//! - `csrf_token`: the session's [CSRF token](crate::csrf), for forms
+//! - `response`: the handler's `status` and `body` (response template only)
+//! - `locale`: the request's [locale and time zone](crate::locale), for the
+//!   `format_date`, `format_number` and `format_currency` helpers
+//! - `vars`: the block's `variables`
+//! - `env`: the process environment
+//!
+//! The `body_template`s of an endpoint's `transform.status_code_mapping`
+//! render here too, with the handler's `response`.
+//!
+//! Output that parses as JSON becomes a JSON body, anything else a string.
+//! Values are written unescaped; `{{json value}}` writes one as JSON, for
+//! building JSON documents.
+
+use crate::config::{EndpointConfig, StatusCodeMapping, TemplateEngine};
+use crate::error::{BackworksError, Result};
+use crate::error_catalog::ErrorReference;
+use crate::server::RequestData;
+use handlebars::{handlebars_helper, Handlebars};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+handlebars_helper!(json_helper: |value: Json| serde_json::to_string(value).unwrap_or_default());
+
+#[derive(Debug, Default)]
+pub struct EndpointTemplates {
+    templates: Handlebars<'static>,
+    variables: HashMap<String, HashMap<String, String>>,
+}
+
+impl EndpointTemplates {
+    /// Compile the templates of every endpoint up front
+    pub fn new(endpoints: &HashMap<String, EndpointConfig>) -> Result<Self> {
+        let mut templates = Handlebars::new();
+        templates.register_escape_fn(handlebars::no_escape);
+        templates.register_helper("json", Box::new(json_helper));
+        crate::locale::register_helpers(&mut templates);
+
+        let mut variables = HashMap::new();
+        for (name, endpoint) in endpoints {
+            let statuses = endpoint.transform.as_ref().and_then(|transform| transform.status_code_mapping.as_ref());
+            for (status, mapping) in statuses.into_iter().flatten() {
+                if let StatusCodeMapping::Response { body_template: Some(source), .. } = mapping {
+                    templates.register_template_string(&status_template_name(name, *status), source)
+                        .map_err(|e| BackworksError::config(format!("Endpoint '{}' status {} body template: {}", name, status, e)))?;
+                }
+            }
+            let Some(ref template) = endpoint.template else {
+                continue;
+            };
+            if template.engine != TemplateEngine::Handlebars {
+                return Err(BackworksError::config(format!(
+                    "Endpoint '{}' uses the {:?} template engine; only Handlebars is supported", name, template.engine
+                )));
+            }
+            for (kind, source) in [("request", &template.request_template), ("response", &template.response_template)] {
+                if let Some(source) = source {
+                    templates.register_template_string(&template_name(name, kind), source)
+                        .map_err(|e| BackworksError::config(format!("Endpoint '{}' {} template: {}", name, kind, e)))?;
+                }
+            }
+            variables.insert(name.clone(), template.variables.clone().unwrap_or_default());
+        }
+
+        Ok(Self { templates, variables })
+    }
+
+    /// The request body the endpoint's request template produces, if it has one
+    pub fn render_request(&self, endpoint: &str, request: &RequestData) -> Result<Option<Value>> {
+        let name = template_name(endpoint, "request");
+        if !self.templates.has_template(&name) {
+            return Ok(None);
+        }
+        let rendered = self.templates.render(&name, &self.context(endpoint, request, None))?;
+        Ok(Some(parse_output(rendered)))
+    }
+
+    /// Handler output with its body replaced by the endpoint's response
+    /// template; output without a template, and catalog errors, pass through
+    pub fn render_response(&self, endpoint: &str, request: &RequestData, output: String) -> Result<String> {
+        let name = template_name(endpoint, "response");
+        if !self.templates.has_template(&name) {
+            return Ok(output);
+        }
+
+        let parsed = serde_json::from_str::<Value>(&output).ok();
+        if parsed.as_ref().and_then(ErrorReference::from_output).is_some() {
+            return Ok(output);
+        }
+        let (status, body) = match parsed {
+            Some(Value::Object(ref structured)) if structured.get("status").is_some_and(Value::is_u64) && structured.contains_key("body") => {
+                (structured["status"].clone(), structured["body"].clone())
+            }
+            Some(value) => (json!(200), value),
+            None => (json!(200), json!({ "response": output })),
+        };
+
+        let response = json!({ "status": status, "body": body });
+        let rendered = self.templates.render(&name, &self.context(endpoint, request, Some(response)))?;
+        Ok(json!({ "status": status, "body": parse_output(rendered) }).to_string())
+    }
+
+    /// The replacement body the endpoint's `status_code_mapping` renders for
+    /// a handler `status` and `body`, if it has a template for it
+    pub fn render_status_body(&self, endpoint: &str, request: &RequestData, status: u16, body: &Value) -> Result<Option<Value>> {
+        let name = status_template_name(endpoint, status);
+        if !self.templates.has_template(&name) {
+            return Ok(None);
+        }
+        let response = json!({ "status": status, "body": body });
+        let rendered = self.templates.render(&name, &self.context(endpoint, request, Some(response)))?;
+        Ok(Some(parse_output(rendered)))
+    }
+
+    fn context(&self, endpoint: &str, request: &RequestData, response: Option<Value>) -> Value {
+        let headers: HashMap<&str, &str> = request.headers.iter()
+            .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?)))
+            .collect();
+        let mut context = json!({
+            "request": {
+                "method": request.method,
+                "path": request.path,
+                "params": request.path_params,
+                "query": request.query_params,
+                "headers": headers,
+                "body": request.body,
+                "session": request.session,
+            },
+            "csrf_token": request.csrf_token,
+            "locale": request.locale,
+            "vars": self.variables.get(endpoint),
+            "env": std::env::vars().collect::<HashMap<_, _>>(),
+        });
+        if let Some(response) = response {
+            context["response"] = response;
+        }
+        context
+    }
+}
+
+fn template_name(endpoint: &str, kind: &str) -> String {
+    format!("{}@{}", endpoint, kind)
+}
+
+fn status_template_name(endpoint: &str, status: u16) -> String {
+    format!("{}@status{}", endpoint, status)
+}
+
+fn parse_output(rendered: String) -> Value {
+    serde_json::from_str(&rendered).unwrap_or(Value::String(rendered))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TemplateTransform;
+
+    fn templates(request: Option<&str>, response: Option<&str>) -> EndpointTemplates {
+        let mut endpoint: EndpointConfig = serde_yaml::from_str("path: /orders/{id}").unwrap();
+        endpoint.template = Some(TemplateTransform {
+            engine: TemplateEngine::Handlebars,
+            request_template: request.map(str::to_string),
+            response_template: response.map(str::to_string),
+            variables: Some(HashMap::from([("region".to_string(), "eu".to_string())])),
+        });
+        EndpointTemplates::new(&HashMap::from([("order".to_string(), endpoint)])).unwrap()
+    }
+
+    fn request() -> RequestData {
+        RequestData {
+            method: "POST".to_string(),
+            path: "/orders/7".to_string(),
+            path_params: HashMap::from([("id".to_string(), json!(7))]),
+            query_params: HashMap::from([("currency".to_string(), "EUR".to_string())]),
+            headers: Default::default(),
+            body: Some(json!({ "items": ["a", "b"], "note": "say \"hi\"" })),
+            auth: None,
+            origin: None,
+            locale: Some(crate::locale::Localization::default().context(&Default::default())),
+            deadline: None,
+            session: None,
+            csrf_token: None,
+        }
+    }
+
+    #[test]
+    fn test_request_template_rewrites_body() {
+        let templates = templates(
+            Some(r#"{ "order": {{request.params.id}}, "currency": "{{request.query.currency}}", "region": "{{vars.region}}", "note": {{json request.body.note}}, "count": {{len request.body.items}}, "locale": "{{locale.tag}}", "total": "{{format_currency 1234.5 "EUR" locale="de"}}" }"#),
+            None,
+        );
+        let body = templates.render_request("order", &request()).unwrap().unwrap();
+        assert_eq!(body, json!({ "order": 7, "currency": "EUR", "region": "eu", "note": "say \"hi\"", "count": 2, "locale": "en-US", "total": "1.234,50\u{a0}€" }));
+        assert!(templates.render_request("other", &request()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_response_template_renders_handler_output() {
+        let templates = templates(None, Some(r#"{ "data": {{json response.body}}, "id": {{request.params.id}} }"#));
+        let output = templates.render_response("order", &request(), r#"{"status": 201, "body": {"ok": true}}"#.to_string()).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&output).unwrap(), json!({ "status": 201, "body": { "data": { "ok": true }, "id": 7 } }));
+
+        let text = self::templates(None, Some("Order {{request.params.id}}: {{response.body.state}}"));
+        let output = text.render_response("order", &request(), r#"{"state": "paid"}"#.to_string()).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&output).unwrap(), json!({ "status": 200, "body": "Order 7: paid" }));
+
+        // Catalog errors keep their shape
+        let error = r#"{"$error": {"code": "ORDER_NOT_FOUND", "params": {}}}"#.to_string();
+        assert_eq!(templates.render_response("order", &request(), error.clone()).unwrap(), error);
+    }
+
+    #[test]
+    fn test_unsupported_engine_and_invalid_template_fail() {
+        let mut endpoint: EndpointConfig = serde_yaml::from_str("path: /x\ntemplate:\n  engine: jinja2\n  response_template: x\n").unwrap();
+        let err = EndpointTemplates::new(&HashMap::from([("x".to_string(), endpoint.clone())])).unwrap_err();
+        assert!(err.to_string().contains("only Handlebars is supported"));
+
+        endpoint.template = Some(TemplateTransform {
+            engine: TemplateEngine::Handlebars,
+            request_template: Some("{{#if}}".to_string()),
+            response_template: None,
+            variables: None,
+        });
+        let err = EndpointTemplates::new(&HashMap::from([("x".to_string(), endpoint)])).unwrap_err();
+        assert!(err.to_string().contains("Endpoint 'x' request template"));
+    }
+}
@@ -0,0 +1,400 @@
+//! A minimal ACME client (RFC 8555)
+//!
+//! Enough of the protocol to get a certificate through `http-01`
+//! challenges: an ECDSA P-256 account, kept in the cache directory, orders
+//! a certificate for the configured domains, proves control of each by
+//! serving a key authorization under `/.well-known/acme-challenge/`, then
+//! sends a signing request for a fresh P-256 key.
+
+use super::Challenges;
+use crate::config::AcmeConfig;
+use crate::error::{BackworksError, Result};
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING, ECDSA_P256_SHA256_FIXED_SIGNING};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::PrivatePkcs8KeyDer;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::time::Duration;
+
+/// How often, and how many times, pending authorizations and orders are
+/// checked again
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const POLL_ATTEMPTS: usize = 60;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Directory {
+    new_nonce: String,
+    new_account: String,
+    new_order: String,
+}
+
+#[derive(Deserialize)]
+struct Order {
+    status: String,
+    #[serde(default)]
+    authorizations: Vec<String>,
+    finalize: String,
+    certificate: Option<String>,
+    error: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct Authorization {
+    status: String,
+    identifier: Identifier,
+    #[serde(default)]
+    challenges: Vec<Challenge>,
+}
+
+#[derive(Deserialize)]
+struct Identifier {
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct Challenge {
+    #[serde(rename = "type")]
+    kind: String,
+    url: String,
+    token: String,
+    error: Option<Value>,
+}
+
+struct Account {
+    http: reqwest::Client,
+    directory: Directory,
+    key: EcdsaKeyPair,
+    /// Account URL, identifying the key once registered
+    kid: Option<String>,
+    nonce: Option<String>,
+    rng: SystemRandom,
+}
+
+impl Account {
+    /// The account key as a JSON Web Key, members in the order its
+    /// thumbprint needs
+    fn jwk(&self) -> Value {
+        let point = self.key.public_key().as_ref();
+        json!({
+            "crv": "P-256",
+            "kty": "EC",
+            "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&point[33..65]),
+        })
+    }
+
+    /// What the authority expects under `/.well-known/acme-challenge/{token}`
+    fn key_authorization(&self, token: &str) -> String {
+        format!("{}.{}", token, thumbprint(&self.jwk()))
+    }
+
+    async fn nonce(&mut self) -> Result<String> {
+        if let Some(nonce) = self.nonce.take() {
+            return Ok(nonce);
+        }
+        let response = self.http.head(&self.directory.new_nonce).send().await?;
+        replay_nonce(&response).ok_or_else(|| BackworksError::config("The ACME authority sent no nonce"))
+    }
+
+    /// POST `payload` to `url`, signed with the account key; `None` posts
+    /// as GET
+    async fn post(&mut self, url: &str, payload: Option<&Value>) -> Result<reqwest::Response> {
+        let mut retried = false;
+        loop {
+            let mut protected = json!({ "alg": "ES256", "nonce": self.nonce().await?, "url": url });
+            match self.kid {
+                Some(ref kid) => protected["kid"] = json!(kid),
+                None => protected["jwk"] = self.jwk(),
+            }
+            let protected = URL_SAFE_NO_PAD.encode(protected.to_string());
+            let payload = payload.map(|payload| URL_SAFE_NO_PAD.encode(payload.to_string())).unwrap_or_default();
+            let signature = self.key.sign(&self.rng, format!("{}.{}", protected, payload).as_bytes())
+                .map_err(|_| BackworksError::config("Failed to sign an ACME request"))?;
+            let body = json!({ "protected": protected, "payload": payload, "signature": URL_SAFE_NO_PAD.encode(signature) });
+            let response = self.http.post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/jose+json")
+                .body(body.to_string())
+                .send()
+                .await?;
+            self.nonce = replay_nonce(&response);
+            if response.status().is_success() {
+                return Ok(response);
+            }
+            let problem: Value = response.json().await.unwrap_or_default();
+            // Nonces expire; the authority sends a fresh one to try again with
+            if !retried && problem["type"] == "urn:ietf:params:acme:error:badNonce" {
+                retried = true;
+                continue;
+            }
+            return Err(BackworksError::config(format!("ACME request to {} failed: {}", url, describe(&problem))));
+        }
+    }
+
+    async fn post_json<T: serde::de::DeserializeOwned>(&mut self, url: &str, payload: Option<&Value>) -> Result<T> {
+        Ok(self.post(url, payload).await?.json().await?)
+    }
+
+    /// Prove control of the domain of authorization `url`
+    async fn authorize(&mut self, url: &str, challenges: &Challenges) -> Result<()> {
+        let authorization: Authorization = self.post_json(url, None).await?;
+        if authorization.status == "valid" {
+            return Ok(());
+        }
+        let domain = authorization.identifier.value;
+        let challenge = authorization.challenges.into_iter()
+            .find(|challenge| challenge.kind == "http-01")
+            .ok_or_else(|| BackworksError::config(format!("The ACME authority offers no http-01 challenge for {}", domain)))?;
+        let key_authorization = self.key_authorization(&challenge.token);
+        challenges.write().unwrap_or_else(|e| e.into_inner()).insert(challenge.token.clone(), key_authorization);
+        let result = async {
+            self.post(&challenge.url, Some(&json!({}))).await?;
+            for _ in 0..POLL_ATTEMPTS {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                let authorization: Authorization = self.post_json(url, None).await?;
+                match authorization.status.as_str() {
+                    "valid" => return Ok(()),
+                    "pending" | "processing" => continue,
+                    status => {
+                        let error = authorization.challenges.iter()
+                            .find(|c| c.token == challenge.token)
+                            .and_then(|c| c.error.as_ref())
+                            .map_or_else(|| status.to_string(), describe);
+                        return Err(BackworksError::config(format!("Validating {} failed: {}", domain, error)));
+                    }
+                }
+            }
+            Err(BackworksError::config(format!("Validating {} did not finish in time", domain)))
+        }.await;
+        challenges.write().unwrap_or_else(|e| e.into_inner()).remove(&challenge.token);
+        result
+    }
+}
+
+/// Get a certificate for `settings.domains` from the authority at
+/// `directory`, answering its challenges through `challenges`; returns the
+/// certificate chain and private key, PEM encoded
+pub(super) async fn issue(directory: &str, settings: &AcmeConfig, cache_dir: &Path, challenges: &Challenges) -> Result<(String, String)> {
+    let http = reqwest::Client::builder().timeout(Duration::from_secs(30)).build()?;
+    let directory: Directory = http.get(directory).send().await?.error_for_status()?.json().await?;
+    let rng = SystemRandom::new();
+    let key = account_key(cache_dir, &rng).await?;
+    let mut account = Account { http, directory, key, kid: None, nonce: None, rng };
+
+    let mut registration = json!({ "termsOfServiceAgreed": true });
+    if let Some(ref email) = settings.email {
+        registration["contact"] = json!([format!("mailto:{}", email)]);
+    }
+    let new_account = account.directory.new_account.clone();
+    let response = account.post(&new_account, Some(&registration)).await?;
+    account.kid = Some(location(&response)?);
+
+    let identifiers: Vec<Value> = settings.domains.iter().map(|domain| json!({ "type": "dns", "value": domain })).collect();
+    let new_order = account.directory.new_order.clone();
+    let response = account.post(&new_order, Some(&json!({ "identifiers": identifiers }))).await?;
+    let order_url = location(&response)?;
+    let order: Order = response.json().await?;
+    for authorization in &order.authorizations {
+        account.authorize(authorization, challenges).await?;
+    }
+
+    let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &account.rng)
+        .map_err(|_| BackworksError::config("Failed to generate a certificate key"))?;
+    let csr = signing_request(&settings.domains, pkcs8.as_ref(), &account.rng)?;
+    let mut order: Order = account.post_json(&order.finalize, Some(&json!({ "csr": URL_SAFE_NO_PAD.encode(csr) }))).await?;
+    for _ in 0..POLL_ATTEMPTS {
+        match order.status.as_str() {
+            "valid" => break,
+            "invalid" => {
+                let error = order.error.as_ref().map_or_else(|| "order is invalid".to_string(), describe);
+                return Err(BackworksError::config(format!("The ACME authority refused the certificate: {}", error)));
+            }
+            _ => {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                order = account.post_json(&order_url, None).await?;
+            }
+        }
+    }
+    let certificate = order.certificate
+        .ok_or_else(|| BackworksError::config("The ACME authority did not issue the certificate in time"))?;
+    let chain = account.post(&certificate, None).await?.text().await?;
+    Ok((chain, pem("PRIVATE KEY", pkcs8.as_ref())))
+}
+
+/// The account key kept in `cache_dir`, created on first use
+async fn account_key(cache_dir: &Path, rng: &SystemRandom) -> Result<EcdsaKeyPair> {
+    let path = cache_dir.join("account.pem");
+    let pkcs8 = match tokio::fs::read(&path).await {
+        Ok(pem) => PrivatePkcs8KeyDer::from_pem_slice(&pem)
+            .map_err(|e| BackworksError::config(format!("Invalid ACME account key {}: {}", path.display(), e)))?
+            .secret_pkcs8_der()
+            .to_vec(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, rng)
+                .map_err(|_| BackworksError::config("Failed to generate an ACME account key"))?;
+            tokio::fs::create_dir_all(cache_dir).await?;
+            write_private(&path, pem("PRIVATE KEY", pkcs8.as_ref()).as_bytes()).await?;
+            pkcs8.as_ref().to_vec()
+        }
+        Err(e) => return Err(e.into()),
+    };
+    EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &pkcs8, rng)
+        .map_err(|e| BackworksError::config(format!("Unusable ACME account key {}: {}", path.display(), e)))
+}
+
+/// Write a file only its owner may read
+pub(super) async fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path).await?;
+    tokio::io::AsyncWriteExt::write_all(&mut file, contents).await?;
+    Ok(())
+}
+
+fn replay_nonce(response: &reqwest::Response) -> Option<String> {
+    response.headers().get("replay-nonce")?.to_str().ok().map(str::to_string)
+}
+
+fn location(response: &reqwest::Response) -> Result<String> {
+    response.headers().get(reqwest::header::LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .map(str::to_string)
+        .ok_or_else(|| BackworksError::config("The ACME authority sent no Location"))
+}
+
+/// An ACME problem document as one line
+fn describe(problem: &Value) -> String {
+    match (problem["type"].as_str(), problem["detail"].as_str()) {
+        (_, Some(detail)) => detail.to_string(),
+        (Some(kind), None) => kind.to_string(),
+        _ => problem.to_string(),
+    }
+}
+
+/// The RFC 7638 thumbprint of a JSON Web Key holding only its required members
+fn thumbprint(jwk: &Value) -> String {
+    // serde_json sorts object members, as RFC 7638 requires
+    let digest = ring::digest::digest(&ring::digest::SHA256, jwk.to_string().as_bytes());
+    URL_SAFE_NO_PAD.encode(digest)
+}
+
+fn pem(label: &str, der: &[u8]) -> String {
+    let encoded = STANDARD.encode(der);
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+/// A DER element of `tag` around `content`
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut element = vec![tag];
+    let length = content.len();
+    if length < 0x80 {
+        element.push(length as u8);
+    } else {
+        let bytes: Vec<u8> = length.to_be_bytes().into_iter().skip_while(|&byte| byte == 0).collect();
+        element.push(0x80 | bytes.len() as u8);
+        element.extend(bytes);
+    }
+    element.extend_from_slice(content);
+    element
+}
+
+fn sequence(items: &[Vec<u8>]) -> Vec<u8> {
+    der(0x30, &items.concat())
+}
+
+/// A bit string of whole bytes
+fn bit_string(bytes: &[u8]) -> Vec<u8> {
+    der(0x03, &[&[0][..], bytes].concat())
+}
+
+const OID_COMMON_NAME: &[u8] = &[0x06, 0x03, 0x55, 0x04, 0x03];
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_P256: &[u8] = &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_EXTENSION_REQUEST: &[u8] = &[0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x0e];
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x06, 0x03, 0x55, 0x1d, 0x11];
+const OID_ECDSA_SHA256: &[u8] = &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+
+/// A PKCS #10 signing request for `domains`, named after the first, by the
+/// P-256 key `pkcs8`
+fn signing_request(domains: &[String], pkcs8: &[u8], rng: &SystemRandom) -> Result<Vec<u8>> {
+    let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8, rng)
+        .map_err(|e| BackworksError::config(format!("Unusable certificate key: {}", e)))?;
+    let subject = sequence(&[der(0x31, &sequence(&[OID_COMMON_NAME.to_vec(), der(0x0c, domains[0].as_bytes())]))]);
+    let public_key = sequence(&[sequence(&[OID_EC_PUBLIC_KEY.to_vec(), OID_P256.to_vec()]), bit_string(key.public_key().as_ref())]);
+    let names: Vec<Vec<u8>> = domains.iter().map(|domain| der(0x82, domain.as_bytes())).collect();
+    let alt_names = sequence(&[OID_SUBJECT_ALT_NAME.to_vec(), der(0x04, &sequence(&names))]);
+    let attributes = der(0xa0, &sequence(&[OID_EXTENSION_REQUEST.to_vec(), der(0x31, &sequence(&[alt_names]))]));
+    let info = sequence(&[der(0x02, &[0]), subject, public_key, attributes]);
+    let signature = key.sign(rng, &info)
+        .map_err(|_| BackworksError::config("Failed to sign the certificate request"))?;
+    Ok(sequence(&[info, sequence(&[OID_ECDSA_SHA256.to_vec()]), bit_string(signature.as_ref())]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thumbprint_matches_rfc_7638() {
+        let jwk = json!({
+            "e": "AQAB",
+            "kty": "RSA",
+            "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+        });
+        assert_eq!(thumbprint(&jwk), "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs");
+    }
+
+    #[test]
+    fn test_der_lengths() {
+        assert_eq!(der(0x04, &[7; 3]), [&[0x04, 3][..], &[7; 3]].concat());
+        assert_eq!(der(0x04, &[7; 127])[..2], [0x04, 0x7f]);
+        assert_eq!(der(0x04, &[7; 128])[..3], [0x04, 0x81, 0x80]);
+        let long = der(0x30, &[7; 300]);
+        assert_eq!(long[..4], [0x30, 0x82, 0x01, 0x2c]);
+        assert_eq!(long.len(), 4 + 300);
+        assert_eq!(bit_string(&[1, 2]), [0x03, 3, 0, 1, 2]);
+    }
+
+    #[test]
+    fn test_signing_request_parses_back() {
+        use x509_parser::extensions::{GeneralName, ParsedExtension};
+        use x509_parser::prelude::FromDer;
+
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        let domains = vec!["api.example.com".to_string(), "www.example.com".to_string()];
+        let csr = signing_request(&domains, pkcs8.as_ref(), &rng).unwrap();
+
+        let (rest, request) = x509_parser::certification_request::X509CertificationRequest::from_der(&csr).unwrap();
+        assert!(rest.is_empty());
+        request.verify_signature().unwrap();
+        let info = &request.certification_request_info;
+        let common_name = info.subject.iter_common_name().next().unwrap().as_str().unwrap();
+        assert_eq!(common_name, "api.example.com");
+
+        let names: Vec<String> = request.requested_extensions().into_iter().flatten()
+            .filter_map(|extension| match extension {
+                ParsedExtension::SubjectAlternativeName(names) => Some(names),
+                _ => None,
+            })
+            .flat_map(|names| names.general_names.iter())
+            .filter_map(|name| match name {
+                GeneralName::DNSName(name) => Some(name.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, domains);
+    }
+}
@@ -0,0 +1,287 @@
+//! Blueprint upgrades across Backworks releases
+//!
+//! Each migration names the release that deprecated or removed a blueprint
+//! construct. `backworks upgrade` runs them over every file of a project:
+//! constructs with a mechanical replacement are rewritten, the rest are
+//! reported as manual changes with the release and the replacement to use.
+
+use crate::diagnostics::{detect_format, BlueprintFormat};
+use crate::error::{BackworksError, Result};
+use serde_yaml::{Mapping, Value};
+use std::path::{Path, PathBuf};
+
+/// A construct deprecated by a release, and how to move off it
+pub struct Migration {
+    pub id: &'static str,
+    /// Release that deprecated or removed the construct
+    pub since: &'static str,
+    pub title: &'static str,
+    apply: fn(&mut Value, BlueprintFormat, &mut Vec<Change>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// Rewritten automatically
+    Rewritten,
+    /// Needs a manual change
+    Manual,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    /// Dotted path of the construct, such as `endpoints.users.mock`
+    pub path: String,
+    pub message: String,
+}
+
+/// Changes one migration found in a file
+#[derive(Debug, Clone)]
+pub struct MigrationChanges {
+    pub id: &'static str,
+    pub since: &'static str,
+    pub title: &'static str,
+    pub changes: Vec<Change>,
+}
+
+/// Result of upgrading one blueprint file
+#[derive(Debug, Clone)]
+pub struct FileUpgrade {
+    pub path: PathBuf,
+    pub migrations: Vec<MigrationChanges>,
+    /// The rewritten document, when any rewrite applied
+    pub upgraded: Option<String>,
+}
+
+impl FileUpgrade {
+    pub fn count(&self, kind: ChangeKind) -> usize {
+        self.migrations.iter().flat_map(|m| &m.changes).filter(|c| c.kind == kind).count()
+    }
+}
+
+/// Every known migration, oldest release first
+pub fn migrations() -> &'static [Migration] {
+    &[
+        Migration {
+            id: "mock-mode",
+            since: "0.2.0",
+            title: "Mock blocks of fixed data were removed; endpoints serve it through runtime handlers",
+            apply: migrate_mock_mode,
+        },
+        Migration {
+            id: "proxy-mode",
+            since: "0.2.0",
+            title: "Proxying moved from the core to the proxy plugin",
+            apply: migrate_proxy_mode,
+        },
+    ]
+}
+
+/// Run the migrations newer than `from` (all of them without one) over a
+/// blueprint document in place
+pub fn upgrade_document(value: &mut Value, from: Option<&str>) -> Result<Vec<MigrationChanges>> {
+    let format = detect_format(value)?;
+    let from = from.map(parse_version).transpose()?;
+    let mut found = Vec::new();
+    for migration in migrations() {
+        if from.is_some_and(|from| parse_version(migration.since).is_ok_and(|since| since <= from)) {
+            continue;
+        }
+        let mut changes = Vec::new();
+        (migration.apply)(value, format, &mut changes);
+        if !changes.is_empty() {
+            found.push(MigrationChanges { id: migration.id, since: migration.since, title: migration.title, changes });
+        }
+    }
+    Ok(found)
+}
+
+/// Upgrade a blueprint file and its includes without writing anything
+pub fn upgrade_project(path: &Path, from: Option<&str>) -> Result<Vec<FileUpgrade>> {
+    let resolved = crate::blueprint::resolve_for_environment(path, None)?;
+    resolved.sources.iter().map(|source| upgrade_file(source, from)).collect()
+}
+
+pub fn upgrade_file(path: &Path, from: Option<&str>) -> Result<FileUpgrade> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| BackworksError::config(format!("Cannot read {}: {}", path.display(), e)))?;
+    let mut value: Value = serde_yaml::from_str(&content)?;
+    let migrations = upgrade_document(&mut value, from)?;
+    let rewritten = migrations.iter().flat_map(|m| &m.changes).any(|c| c.kind == ChangeKind::Rewritten);
+    let upgraded = if rewritten { Some(serde_yaml::to_string(&value)?) } else { None };
+    Ok(FileUpgrade { path: path.to_path_buf(), migrations, upgraded })
+}
+
+pub(crate) fn parse_version(version: &str) -> Result<(u64, u64, u64)> {
+    let invalid = || BackworksError::config(format!("Invalid version '{}', expected MAJOR.MINOR.PATCH", version));
+    let mut parts = version.trim_start_matches('v').split('.').map(|part| part.parse::<u64>().map_err(|_| invalid()));
+    let major = parts.next().ok_or_else(invalid)??;
+    let minor = parts.next().transpose()?.unwrap_or(0);
+    let patch = parts.next().transpose()?.unwrap_or(0);
+    Ok((major, minor, patch))
+}
+
+/// Endpoints of a document with their dotted paths
+fn endpoints_mut(value: &mut Value, format: BlueprintFormat) -> Vec<(String, &mut Mapping)> {
+    match (format, value.get_mut("endpoints")) {
+        (BlueprintFormat::List, Some(Value::Sequence(items))) => items.iter_mut().enumerate()
+            .filter_map(|(index, item)| item.as_mapping_mut().map(|endpoint| (format!("endpoints[{}]", index), endpoint)))
+            .collect(),
+        (BlueprintFormat::Legacy, Some(Value::Mapping(map))) => map.iter_mut()
+            .filter_map(|(name, item)| Some((format!("endpoints.{}", name.as_str()?), item.as_mapping_mut()?)))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn mode_is(mapping: &Mapping, mode: &str) -> bool {
+    mapping.get("mode").and_then(Value::as_str) == Some(mode)
+}
+
+/// Mock blocks of the current format generate their data from a `schema`
+fn is_generated_mock(endpoint: &Mapping) -> bool {
+    endpoint.get("mock").is_some_and(|mock| mock.get("schema").is_some() || mock.get("item_of").is_some())
+}
+
+fn migrate_mock_mode(value: &mut Value, format: BlueprintFormat, changes: &mut Vec<Change>) {
+    let generated = endpoints_mut(value, format).into_iter().any(|(_, endpoint)| is_generated_mock(endpoint));
+    if !generated && value.as_mapping().is_some_and(|root| mode_is(root, "mock")) {
+        value["mode"] = Value::from("runtime");
+        changes.push(Change {
+            kind: ChangeKind::Rewritten,
+            path: "mode".to_string(),
+            message: "set the blueprint mode to `runtime`".to_string(),
+        });
+    }
+
+    for (path, endpoint) in endpoints_mut(value, format) {
+        if is_generated_mock(endpoint) {
+            continue;
+        }
+        if let Some(mock) = endpoint.remove("mock") {
+            let (kind, message) = match mock.get("data") {
+                _ if endpoint.contains_key("runtime") => (ChangeKind::Rewritten, "removed the mock block; the endpoint already has a runtime handler"),
+                Some(data) => {
+                    endpoint.insert("runtime".into(), fixed_data_handler(data));
+                    (ChangeKind::Rewritten, "replaced mock data with a runtime handler returning it")
+                }
+                None => {
+                    endpoint.insert("mock".into(), mock);
+                    (ChangeKind::Manual, "describe the generated data with a mock `schema`, or write a runtime handler for the endpoint")
+                }
+            };
+            changes.push(Change { kind, path: format!("{}.mock", path), message: message.to_string() });
+        }
+
+        if mode_is(endpoint, "mock") {
+            if !endpoint.contains_key("runtime") {
+                changes.push(Change {
+                    kind: ChangeKind::Manual,
+                    path: format!("{}.mode", path),
+                    message: "set `mode: runtime` and give the endpoint a handler".to_string(),
+                });
+            } else if format == BlueprintFormat::List {
+                // List-format endpoints always run in runtime mode
+                endpoint.remove("mode");
+            } else {
+                endpoint.insert("mode".into(), "runtime".into());
+            }
+        }
+
+        if endpoint.contains_key("mock_responses") {
+            changes.push(Change {
+                kind: ChangeKind::Manual,
+                path: format!("{}.mock_responses", path),
+                message: "return the responses from a runtime handler, choosing by request".to_string(),
+            });
+        }
+    }
+}
+
+/// Runtime handler answering every request with `data`
+fn fixed_data_handler(data: &Value) -> Value {
+    let body = serde_json::to_value(data)
+        .and_then(|json| serde_json::to_string_pretty(&json))
+        .unwrap_or_else(|_| "null".to_string())
+        .replace('\n', "\n  ");
+    let mut runtime = Mapping::new();
+    runtime.insert("language".into(), "javascript".into());
+    runtime.insert("handler".into(), format!(
+        "function handler(req, res) {{\n  return {{ status: 200, body: {} }};\n}}\n", body
+    ).into());
+    Value::Mapping(runtime)
+}
+
+fn migrate_proxy_mode(value: &mut Value, format: BlueprintFormat, changes: &mut Vec<Change>) {
+    const HELP: &str = "serve it with `mode: plugin` and `plugin: proxy`, moving the targets under `plugins.proxy.config`";
+
+    if value.as_mapping().is_some_and(|root| mode_is(root, "proxy")) {
+        changes.push(Change {
+            kind: ChangeKind::Manual,
+            path: "mode".to_string(),
+            message: format!("the blueprint runs in proxy mode; {}", HELP),
+        });
+    }
+    for (path, endpoint) in endpoints_mut(value, format) {
+        if endpoint.contains_key("proxy") {
+            changes.push(Change { kind: ChangeKind::Manual, path: format!("{}.proxy", path), message: HELP.to_string() });
+        } else if mode_is(endpoint, "proxy") {
+            changes.push(Change { kind: ChangeKind::Manual, path: format!("{}.mode", path), message: HELP.to_string() });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_data_is_rewritten_and_proxy_reported() {
+        let mut value: Value = serde_yaml::from_str(r#"
+name: shop
+mode: mock
+endpoints:
+  users:
+    path: /users
+    mode: mock
+    mock:
+      data: [{ id: 1, name: Ada }]
+  search:
+    path: /search
+    mock_responses:
+      - body: []
+  orders:
+    path: /orders
+    mode: proxy
+    proxy:
+      targets: ["http://orders"]
+"#).unwrap();
+
+        let found = upgrade_document(&mut value, None).unwrap();
+        assert_eq!(found.iter().map(|m| m.id).collect::<Vec<_>>(), vec!["mock-mode", "proxy-mode"]);
+        let paths = |kind| found.iter().flat_map(|m| &m.changes).filter(|c| c.kind == kind).map(|c| c.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths(ChangeKind::Rewritten), vec!["mode", "endpoints.users.mock"]);
+        assert_eq!(paths(ChangeKind::Manual), vec!["endpoints.search.mock_responses", "endpoints.orders.proxy"]);
+
+        let users = &value["endpoints"]["users"];
+        assert_eq!(users["mode"], "runtime");
+        assert!(users.get("mock").is_none());
+        assert!(users["runtime"]["handler"].as_str().unwrap().contains("\"name\": \"Ada\""));
+
+        // The rewritten blueprint parses again
+        value["endpoints"].as_mapping_mut().unwrap().retain(|name, _| name == "users");
+        assert!(crate::config::parse_blueprint(value).is_ok());
+    }
+
+    #[test]
+    fn test_from_version_skips_older_migrations() {
+        let mut value: Value = serde_yaml::from_str("name: api\nmode: mock\nendpoints: []\n").unwrap();
+        assert!(upgrade_document(&mut value.clone(), Some("0.2.0")).unwrap().is_empty());
+        assert_eq!(upgrade_document(&mut value, Some("0.1")).unwrap().len(), 1);
+        assert!(upgrade_document(&mut Value::Null, Some("latest")).is_err());
+
+        // Generated mocks are current and left alone
+        let mut value: Value = serde_yaml::from_str("name: api\nmode: mock\nendpoints:\n  users: { path: /users, mock: { count: 3, schema: { id: $seq } } }\n").unwrap();
+        assert!(upgrade_document(&mut value, None).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,266 @@
+//! Declared versus observed traffic
+//!
+//! The server notes the method, path, matched endpoint and status of every
+//! request for a day. The usage report compares that traffic with the
+//! blueprint over a window: how often each endpoint is called and when,
+//! endpoints nobody calls, paths that answer 404 often enough to be
+//! candidate endpoints, and declared paths called with a method they do not
+//! declare.
+
+use crate::config::BackworksConfig;
+use crate::routes::RoutePattern;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+/// Upper bound on retained requests, regardless of their age
+const MAX_SAMPLES: usize = 100_000;
+/// Time slots of each endpoint's heatmap row
+const HEATMAP_SLOTS: usize = 24;
+/// Undeclared paths listed in a report
+const MAX_HOTSPOTS: usize = 20;
+
+#[derive(Debug, Clone)]
+struct UsageSample {
+    at: DateTime<Utc>,
+    method: String,
+    path: String,
+    endpoint: Option<String>,
+    status: u16,
+}
+
+/// Shared, cheaply clonable recorder of recent requests
+#[derive(Debug, Clone)]
+pub struct UsageRecorder {
+    samples: Arc<RwLock<VecDeque<UsageSample>>>,
+    retention: Duration,
+}
+
+impl Default for UsageRecorder {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(24 * 60 * 60))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub window_seconds: u64,
+    pub generated_at: DateTime<Utc>,
+    pub requests: u64,
+    /// Every declared endpoint, by name
+    pub endpoints: Vec<EndpointUsage>,
+    /// Declared endpoints without a single request in the window
+    pub unused: Vec<String>,
+    /// Paths answered with 404 that no endpoint declares, busiest first
+    pub undeclared: Vec<UndeclaredPath>,
+    /// Requests to a declared path with a method it does not declare
+    pub method_mismatches: Vec<MethodMismatch>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EndpointUsage {
+    pub endpoint: String,
+    pub path: String,
+    pub methods: Vec<String>,
+    pub requests: u64,
+    /// Requests answered with a 5xx status
+    pub errors: u64,
+    pub by_method: BTreeMap<String, u64>,
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Requests per equal slot of the window, oldest first
+    pub heatmap: Vec<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UndeclaredPath {
+    pub method: String,
+    /// The path with ID-like segments replaced by `{id}`
+    pub path: String,
+    pub requests: u64,
+    /// One of the requested paths
+    pub example: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MethodMismatch {
+    pub endpoint: String,
+    pub path: String,
+    pub method: String,
+    pub requests: u64,
+}
+
+impl UsageRecorder {
+    /// Create a recorder that retains requests for `retention`
+    pub fn new(retention: Duration) -> Self {
+        Self { samples: Arc::new(RwLock::new(VecDeque::new())), retention }
+    }
+
+    pub async fn record(&self, method: &str, path: &str, endpoint: Option<&str>, status: u16) {
+        self.record_at(Utc::now(), method, path, endpoint, status).await;
+    }
+
+    async fn record_at(&self, at: DateTime<Utc>, method: &str, path: &str, endpoint: Option<&str>, status: u16) {
+        let mut samples = self.samples.write().await;
+        samples.push_back(UsageSample {
+            at,
+            method: method.to_string(),
+            path: path.to_string(),
+            endpoint: endpoint.map(String::from),
+            status,
+        });
+
+        while samples.len() > MAX_SAMPLES {
+            samples.pop_front();
+        }
+        let retention = chrono::Duration::from_std(self.retention).unwrap_or(chrono::Duration::MAX);
+        while samples.front().is_some_and(|s| at - s.at > retention) {
+            samples.pop_front();
+        }
+    }
+
+    /// Compare the requests of the last `window` with the endpoints of `config`
+    pub async fn report(&self, config: &BackworksConfig, window: Duration) -> UsageReport {
+        let now = Utc::now();
+        let span = chrono::Duration::from_std(window).unwrap_or(chrono::Duration::MAX);
+        let start = now.checked_sub_signed(span).unwrap_or(DateTime::<Utc>::MIN_UTC);
+        let slot_ms = (window.as_millis() / HEATMAP_SLOTS as u128).max(1);
+
+        let mut names: Vec<&String> = config.endpoints.keys().collect();
+        names.sort();
+        let declared: Vec<(&String, RoutePattern)> = names.iter()
+            .map(|name| (*name, RoutePattern::parse(&config.endpoints[*name].path)))
+            .collect();
+        let mut usage: BTreeMap<&str, EndpointUsage> = names.iter()
+            .map(|name| {
+                let endpoint = &config.endpoints[*name];
+                (name.as_str(), EndpointUsage {
+                    endpoint: name.to_string(),
+                    path: endpoint.path.clone(),
+                    methods: endpoint.methods.clone(),
+                    requests: 0,
+                    errors: 0,
+                    by_method: BTreeMap::new(),
+                    last_seen: None,
+                    heatmap: vec![0; HEATMAP_SLOTS],
+                })
+            })
+            .collect();
+        let mut undeclared: HashMap<(String, String), UndeclaredPath> = HashMap::new();
+        let mut mismatches: BTreeMap<(String, String), u64> = BTreeMap::new();
+
+        let samples = self.samples.read().await;
+        let recent: Vec<&UsageSample> = samples.iter().filter(|s| s.at >= start).collect();
+        for sample in &recent {
+            match sample.endpoint.as_deref().and_then(|name| usage.get_mut(name)) {
+                Some(endpoint) => {
+                    endpoint.requests += 1;
+                    endpoint.errors += u64::from(sample.status >= 500);
+                    *endpoint.by_method.entry(sample.method.clone()).or_default() += 1;
+                    endpoint.last_seen = endpoint.last_seen.max(Some(sample.at));
+                    let elapsed = (sample.at - start).num_milliseconds().max(0) as u128;
+                    endpoint.heatmap[((elapsed / slot_ms) as usize).min(HEATMAP_SLOTS - 1)] += 1;
+                }
+                None if sample.status == 404 || sample.status == 405 => {
+                    match declared.iter().find(|(_, pattern)| pattern.matches(&sample.path)) {
+                        Some((name, _)) => *mismatches.entry(((*name).clone(), sample.method.clone())).or_default() += 1,
+                        None => {
+                            let path = candidate_path(&sample.path);
+                            undeclared.entry((sample.method.clone(), path.clone()))
+                                .or_insert_with(|| UndeclaredPath { method: sample.method.clone(), path, requests: 0, example: sample.path.clone() })
+                                .requests += 1;
+                        }
+                    }
+                }
+                None => {}
+            }
+        }
+
+        let mut undeclared: Vec<UndeclaredPath> = undeclared.into_values().collect();
+        undeclared.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.path.cmp(&b.path)).then_with(|| a.method.cmp(&b.method)));
+        undeclared.truncate(MAX_HOTSPOTS);
+        let endpoints: Vec<EndpointUsage> = usage.into_values().collect();
+
+        UsageReport {
+            window_seconds: window.as_secs(),
+            generated_at: now,
+            requests: recent.len() as u64,
+            unused: endpoints.iter().filter(|e| e.requests == 0).map(|e| e.endpoint.clone()).collect(),
+            method_mismatches: mismatches.into_iter()
+                .map(|((endpoint, method), requests)| MethodMismatch {
+                    path: config.endpoints[&endpoint].path.clone(),
+                    endpoint,
+                    method,
+                    requests,
+                })
+                .collect(),
+            endpoints,
+            undeclared,
+        }
+    }
+}
+
+/// Group requests to the same resource: numeric, UUID and long hex segments
+/// become `{id}`
+pub(crate) fn candidate_path(path: &str) -> String {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').filter(|s| !s.is_empty())
+        .map(|segment| {
+            let numeric = segment.chars().all(|c| c.is_ascii_digit());
+            let uuid = uuid::Uuid::parse_str(segment).is_ok();
+            let hex = segment.len() >= 16 && segment.chars().all(|c| c.is_ascii_hexdigit());
+            if numeric || uuid || hex { "{id}" } else { segment }
+        })
+        .collect();
+    format!("/{}", segments.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_report_compares_declared_and_observed_traffic() {
+        let config: BackworksConfig = serde_yaml::from_str(r#"
+name: shop
+endpoints:
+  users: { path: /users, methods: [GET] }
+  user: { path: "/users/{id}", methods: [GET] }
+  reports: { path: /reports, methods: [GET] }
+"#).unwrap();
+        let recorder = UsageRecorder::default();
+        let now = Utc::now();
+        let minutes_ago = |minutes: i64| now - chrono::Duration::minutes(minutes);
+        recorder.record_at(minutes_ago(30 * 60), "GET", "/reports", Some("reports"), 200).await;
+        recorder.record_at(minutes_ago(155), "GET", "/users", Some("users"), 200).await;
+        recorder.record_at(minutes_ago(60), "GET", "/users/7", Some("user"), 500).await;
+        recorder.record_at(now, "GET", "/users", Some("users"), 200).await;
+        recorder.record_at(now, "DELETE", "/users/7", None, 405).await;
+        recorder.record_at(now, "DELETE", "/users/8", None, 405).await;
+        for id in ["1", "2", "3f2504e0-4f89-11d3-9a0c-0305e82c3301"] {
+            recorder.record_at(now, "GET", &format!("/orders/{}/items", id), None, 404).await;
+        }
+        recorder.record_at(now, "POST", "/orders", None, 404).await;
+        recorder.record_at(now, "GET", "/_backworks/scenarios", None, 200).await;
+
+        let report = recorder.report(&config, Duration::from_secs(4 * 60 * 60)).await;
+        assert_eq!(report.requests, 10);
+        assert_eq!(report.unused, vec!["reports"]);
+        let users = &report.endpoints[2];
+        assert_eq!((users.endpoint.as_str(), users.requests, users.by_method["GET"]), ("users", 2, 2));
+        assert_eq!(users.heatmap.iter().sum::<u64>(), 2);
+        assert_eq!((users.heatmap[8], users.heatmap[23]), (1, 1));
+        assert_eq!(report.endpoints[1].errors, 1);
+
+        assert_eq!(report.method_mismatches, vec![MethodMismatch {
+            endpoint: "user".to_string(),
+            path: "/users/{id}".to_string(),
+            method: "DELETE".to_string(),
+            requests: 2,
+        }]);
+        assert_eq!(report.undeclared.len(), 2);
+        assert_eq!((report.undeclared[0].path.as_str(), report.undeclared[0].requests), ("/orders/{id}/items", 3));
+        assert_eq!((report.undeclared[1].method.as_str(), report.undeclared[1].path.as_str()), ("POST", "/orders"));
+    }
+}
@@ -0,0 +1,259 @@
+//! Inbound webhook signatures
+//!
+//! An endpoint with `webhook` checks the signature its provider sends with
+//! each delivery before the handler runs:
+//!
+//! - `stripe`: `Stripe-Signature: t=...,v1=...`, an HMAC-SHA256 of
+//!   `{t}.{body}`; any of several `v1` signatures may match
+//! - `github`: `X-Hub-Signature-256: sha256=...`, an HMAC-SHA256 of the body
+//! - `slack`: `X-Slack-Signature: v0=...`, an HMAC-SHA256 of
+//!   `v0:{X-Slack-Request-Timestamp}:{body}`
+//! - `hmac`: an HMAC of the body in `header`, hex or base64 after an
+//!   optional `prefix`
+//!
+//! Signed timestamps further than `tolerance` (default `5m`) from now are
+//! refused as well, so a captured delivery cannot be replayed later.
+//! Deliveries failing the check are answered with `401` and never reach
+//! the handler.
+
+use crate::config::{parse_duration, SignatureEncoding, WebhookAlgorithm, WebhookConfig, WebhookProvider};
+use crate::error::{BackworksError, ErrorSource, RequestError, Result};
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, HeaderName, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use base64::Engine;
+use ring::hmac;
+use std::sync::Arc;
+use std::time::Duration;
+
+const DEFAULT_TOLERANCE: Duration = Duration::from_secs(5 * 60);
+
+/// Checks the signatures of one endpoint's webhook deliveries
+pub struct WebhookVerifier {
+    provider: WebhookProvider,
+    key: hmac::Key,
+    tolerance: Duration,
+    header: HeaderName,
+    prefix: String,
+    encoding: SignatureEncoding,
+}
+
+impl WebhookVerifier {
+    pub fn new(endpoint: &str, config: &WebhookConfig) -> Result<Self> {
+        let invalid = |message: String| BackworksError::config(format!("Endpoint '{}' webhook{}", endpoint, message));
+        if config.secret.is_empty() {
+            return Err(invalid(".secret cannot be empty".to_string()));
+        }
+        let tolerance = match config.tolerance.as_deref() {
+            Some(text) => parse_duration(text).map_err(|e| invalid(format!(".tolerance: {}", e)))?,
+            None => DEFAULT_TOLERANCE,
+        };
+
+        let (header, prefix) = match config.provider {
+            WebhookProvider::Hmac => {
+                let header = config.header.as_deref().ok_or_else(|| invalid(": the hmac provider needs a `header`".to_string()))?;
+                (header, config.prefix.clone().unwrap_or_default())
+            }
+            provider => {
+                let own_settings = [
+                    ("header", config.header.is_some()),
+                    ("prefix", config.prefix.is_some()),
+                    ("algorithm", config.algorithm != WebhookAlgorithm::default()),
+                    ("encoding", config.encoding != SignatureEncoding::default()),
+                ];
+                if let Some((setting, _)) = own_settings.iter().find(|(_, set)| *set) {
+                    return Err(invalid(format!(".{} only applies to the hmac provider", setting)));
+                }
+                match provider {
+                    WebhookProvider::Stripe => ("stripe-signature", String::new()),
+                    WebhookProvider::Github => ("x-hub-signature-256", "sha256=".to_string()),
+                    _ => ("x-slack-signature", "v0=".to_string()),
+                }
+            }
+        };
+        let header = HeaderName::try_from(header).map_err(|e| invalid(format!(".header: {}", e)))?;
+
+        let algorithm = match (config.provider, config.algorithm) {
+            (WebhookProvider::Hmac, WebhookAlgorithm::Sha1) => hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
+            (WebhookProvider::Hmac, WebhookAlgorithm::Sha512) => hmac::HMAC_SHA512,
+            _ => hmac::HMAC_SHA256,
+        };
+        Ok(Self {
+            provider: config.provider,
+            key: hmac::Key::new(algorithm, config.secret.as_bytes()),
+            tolerance,
+            header,
+            prefix,
+            encoding: config.encoding,
+        })
+    }
+
+    /// Check a delivery received at `now` (Unix seconds); the error says
+    /// why it was refused
+    pub fn verify(&self, headers: &HeaderMap, body: &[u8], now: i64) -> std::result::Result<(), String> {
+        let header = headers.get(&self.header)
+            .ok_or_else(|| format!("Missing {} header", self.header))?
+            .to_str()
+            .map_err(|_| format!("Malformed {} header", self.header))?;
+
+        let (timestamp, signatures, message) = match self.provider {
+            WebhookProvider::Stripe => {
+                let mut timestamp = None;
+                let mut signatures = Vec::new();
+                for (key, value) in header.split(',').filter_map(|pair| pair.trim().split_once('=')) {
+                    match key {
+                        "t" => timestamp = Some(value),
+                        "v1" => signatures.push(value),
+                        _ => {}
+                    }
+                }
+                let timestamp = timestamp.filter(|_| !signatures.is_empty())
+                    .ok_or_else(|| format!("Malformed {} header", self.header))?;
+                (Some(timestamp), signatures, [timestamp.as_bytes(), b".", body].concat())
+            }
+            WebhookProvider::Slack => {
+                let timestamp = headers.get("x-slack-request-timestamp")
+                    .and_then(|value| value.to_str().ok())
+                    .ok_or_else(|| "Missing X-Slack-Request-Timestamp header".to_string())?;
+                (Some(timestamp), vec![header], [b"v0:", timestamp.as_bytes(), b":", body].concat())
+            }
+            WebhookProvider::Github | WebhookProvider::Hmac => (None, vec![header], body.to_vec()),
+        };
+
+        if let Some(timestamp) = timestamp {
+            let signed_at: i64 = timestamp.trim().parse().map_err(|_| format!("Malformed webhook timestamp '{}'", timestamp))?;
+            if signed_at.abs_diff(now) > self.tolerance.as_secs() {
+                return Err("Webhook timestamp is outside the tolerance".to_string());
+            }
+        }
+
+        let matches = signatures.iter()
+            .filter_map(|signature| self.decode(signature.trim()))
+            .any(|signature| hmac::verify(&self.key, &message, &signature).is_ok());
+        if matches {
+            Ok(())
+        } else {
+            Err("Invalid webhook signature".to_string())
+        }
+    }
+
+    fn decode(&self, signature: &str) -> Option<Vec<u8>> {
+        let signature = signature.strip_prefix(self.prefix.as_str())?;
+        match self.encoding {
+            SignatureEncoding::Hex => decode_hex(signature),
+            SignatureEncoding::Base64 => base64::engine::general_purpose::STANDARD.decode(signature).ok(),
+        }
+    }
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Route layer refusing deliveries whose signature does not check out
+pub async fn verify_webhook(State(verifier): State<Arc<WebhookVerifier>>, request: Request, next: Next) -> Response {
+    let (parts, body) = request.into_parts();
+    let body = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            let too_large = std::iter::successors(std::error::Error::source(&e), |source| source.source())
+                .any(|source| source.is::<http_body_util::LengthLimitError>());
+            return match too_large {
+                true => reject(StatusCode::PAYLOAD_TOO_LARGE, "Request body is too large".to_string()),
+                false => reject(StatusCode::BAD_REQUEST, format!("Request body could not be read: {}", e)),
+            };
+        }
+    };
+    if let Err(message) = verifier.verify(&parts.headers, &body, chrono::Utc::now().timestamp()) {
+        tracing::debug!("Refused webhook delivery to {}: {}", parts.uri.path(), message);
+        return reject(StatusCode::UNAUTHORIZED, message);
+    }
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
+fn reject(status: StatusCode, message: String) -> Response {
+    let mut response = (status, Json(serde_json::json!({"error": message, "status": status.as_u16()}))).into_response();
+    response.extensions_mut().insert(RequestError::new(status, message, ErrorSource::Framework));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn verifier(yaml: &str) -> Result<WebhookVerifier> {
+        WebhookVerifier::new("events", &serde_yaml::from_str(yaml).unwrap())
+    }
+
+    fn sign(secret: &str, message: &[u8]) -> String {
+        hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()), message)
+            .as_ref().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn headers(pairs: &[(&'static str, String)]) -> HeaderMap {
+        pairs.iter().map(|(name, value)| (HeaderName::from_static(name), value.parse().unwrap())).collect()
+    }
+
+    #[test]
+    fn test_provider_signatures_are_checked() {
+        let body = br#"{"type":"charge.succeeded"}"#;
+
+        let stripe = verifier("{ provider: stripe, secret: whsec_test }").unwrap();
+        let signature = sign("whsec_test", format!("{}.{}", NOW, std::str::from_utf8(body).unwrap()).as_bytes());
+        let signed = |header: String| stripe.verify(&headers(&[("stripe-signature", header)]), body, NOW);
+        assert_eq!(signed(format!("t={},v1={}", NOW, signature)), Ok(()));
+        // Several signatures are sent while a secret is rolled
+        assert_eq!(signed(format!("t={},v1={},v1={},v0=old", NOW, "ab".repeat(32), signature)), Ok(()));
+        assert_eq!(signed(format!("t={},v1={}", NOW + 1, signature)), Err("Invalid webhook signature".to_string()));
+        assert_eq!(signed(format!("v1={}", signature)), Err("Malformed stripe-signature header".to_string()));
+        assert_eq!(stripe.verify(&headers(&[("stripe-signature", format!("t={},v1={}", NOW, signature))]), b"{}", NOW), Err("Invalid webhook signature".to_string()));
+        assert_eq!(
+            stripe.verify(&headers(&[("stripe-signature", format!("t={},v1={}", NOW, signature))]), body, NOW + 301),
+            Err("Webhook timestamp is outside the tolerance".to_string())
+        );
+        assert_eq!(stripe.verify(&HeaderMap::new(), body, NOW), Err("Missing stripe-signature header".to_string()));
+
+        let github = verifier("{ provider: github, secret: hush }").unwrap();
+        let signature = format!("sha256={}", sign("hush", body));
+        assert_eq!(github.verify(&headers(&[("x-hub-signature-256", signature.clone())]), body, NOW), Ok(()));
+        assert!(github.verify(&headers(&[("x-hub-signature-256", signature.replace("sha256=", ""))]), body, NOW).is_err());
+        assert!(verifier("{ provider: github, secret: other }").unwrap().verify(&headers(&[("x-hub-signature-256", signature)]), body, NOW).is_err());
+
+        let slack = verifier("{ provider: slack, secret: hush, tolerance: 1m }").unwrap();
+        let signature = format!("v0={}", sign("hush", format!("v0:{}:{}", NOW, std::str::from_utf8(body).unwrap()).as_bytes()));
+        let delivery = headers(&[("x-slack-signature", signature.clone()), ("x-slack-request-timestamp", NOW.to_string())]);
+        assert_eq!(slack.verify(&delivery, body, NOW - 30), Ok(()));
+        assert!(slack.verify(&delivery, body, NOW + 61).is_err());
+        assert_eq!(
+            slack.verify(&headers(&[("x-slack-signature", signature)]), body, NOW),
+            Err("Missing X-Slack-Request-Timestamp header".to_string())
+        );
+
+        let custom = verifier("{ provider: hmac, secret: hush, header: X-Signature, algorithm: sha512, encoding: base64, prefix: 'sha512=' }").unwrap();
+        let tag = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA512, b"hush"), body);
+        let signature = format!("sha512={}", base64::engine::general_purpose::STANDARD.encode(tag.as_ref()));
+        assert_eq!(custom.verify(&headers(&[("x-signature", signature)]), body, NOW), Ok(()));
+    }
+
+    #[test]
+    fn test_webhook_settings_are_validated() {
+        let error = |yaml: &str| verifier(yaml).err().map(|e| e.to_string()).unwrap_or_default();
+        assert!(error("{ provider: stripe, secret: '' }").contains("Endpoint 'events' webhook.secret cannot be empty"));
+        assert!(error("{ provider: hmac, secret: hush }").contains("the hmac provider needs a `header`"));
+        assert!(error("{ provider: github, secret: hush, header: X-Signature }").contains("webhook.header only applies to the hmac provider"));
+        assert!(error("{ provider: slack, secret: hush, encoding: base64 }").contains("webhook.encoding only applies"));
+        assert!(error("{ provider: slack, secret: hush, tolerance: soon }").contains("webhook.tolerance"));
+        assert!(error("{ provider: hmac, secret: hush, header: 'not a header' }").contains("webhook.header"));
+    }
+}
@@ -329,7 +329,7 @@ async fn test_concurrent_capture_operations() {
         max_duration: None,
     };
     let slow_requests = handler.get_captured_requests(session_id, Some(slow_requests_filter)).await;
-    assert!(slow_requests.len() > 0);
+    assert!(!slow_requests.is_empty());
     
     println!("Concurrent test completed: {} total requests captured", captured_requests.len());
 }
@@ -413,7 +413,7 @@ async fn test_realistic_data_export() {
     assert!(json_data["requests"].is_array());
     
     let requests_array = json_data["requests"].as_array().unwrap();
-    assert!(requests_array.len() > 0);
+    assert!(!requests_array.is_empty());
 
     // Test YAML configuration generation
     let yaml_config = handler.generate_api_from_capture(session_id).await.unwrap();
@@ -435,7 +435,7 @@ async fn test_realistic_data_export() {
     assert!(har_data["log"]["entries"].is_array());
     
     let entries = har_data["log"]["entries"].as_array().unwrap();
-    assert!(entries.len() > 0);
+    assert!(!entries.is_empty());
     
     // Verify HAR entry structure
     let first_entry = &entries[0];
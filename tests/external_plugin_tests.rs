@@ -5,10 +5,12 @@ use std::path::PathBuf;
 #[tokio::test]
 async fn test_external_plugin_discovery() {
     // Test plugin discovery
-    let mut config = PluginDiscoveryConfig::default();
-    config.directories = vec![
-        PathBuf::from("./examples/external-plugins/weather-plugin/target/release"),
-    ];
+    let config = PluginDiscoveryConfig {
+        directories: vec![
+            PathBuf::from("./examples/external-plugins/weather-plugin/target/release"),
+        ],
+        ..Default::default()
+    };
     
     let discovery = PluginDiscovery::new(config);
     let plugins = discovery.discover_all_plugins().await.unwrap();
@@ -54,10 +56,12 @@ async fn test_external_plugin_loading() {
 async fn test_plugin_manager_discovery_integration() {
     let manager = PluginManager::new();
     
-    let mut config = PluginDiscoveryConfig::default();
-    config.directories = vec![
-        PathBuf::from("./examples/external-plugins/weather-plugin/target/release"),
-    ];
+    let config = PluginDiscoveryConfig {
+        directories: vec![
+            PathBuf::from("./examples/external-plugins/weather-plugin/target/release"),
+        ],
+        ..Default::default()
+    };
     
     // Test discovery integration
     let result = manager.initialize_from_discovery(&config).await;